@@ -14,6 +14,17 @@ ARM_HOST="${ARM_HOST:-}"
 ARM_USER="${ARM_USER:-}"
 SKIP_CLIPPY="${SKIP_CLIPPY:-false}"
 
+# Feature combinations that must each compile: the slimmest build, each
+# optional feature alone, and the default build
+FEATURE_SETS=(
+    "--no-default-features"
+    "--no-default-features --features api"
+    "--no-default-features --features cpu-miner"
+    "--no-default-features --features direct-attach"
+    "--no-default-features --features tui"
+    ""
+)
+
 # Function to run a command in the container
 run_in_container() {
     local cmd="$1"
@@ -76,6 +87,15 @@ deploy_to_arm() {
         --test-mode
 }
 
+# Check that every feature combination compiles
+check_features() {
+    local cmd="true"
+    for features in "${FEATURE_SETS[@]}"; do
+        cmd="$cmd && echo 'cargo check -p mujina-miner --all-targets $features' && cargo check -p mujina-miner --all-targets $features"
+    done
+    run_in_container "$cmd" "feature combination checks"
+}
+
 # Parse command line arguments
 case "${1:-all}" in
     "fmt")
@@ -91,6 +111,9 @@ case "${1:-all}" in
     "build")
         run_in_container "cargo build --verbose" "build"
         ;;
+    "features")
+        check_features
+        ;;
     "build-release")
         run_in_container "cargo build --release --verbose" "release build"
         ;;
@@ -108,6 +131,7 @@ case "${1:-all}" in
             run_in_container "cargo clippy --all-targets --all-features -- -D warnings" "clippy check"
         fi
         run_in_container "cargo build --verbose" "build"
+        check_features
         run_in_container "cargo test --verbose" "tests"
         ;;
     "hybrid")
@@ -135,12 +159,13 @@ case "${1:-all}" in
         deploy_to_arm "$binary_path"
         ;;
     *)
-        echo "Usage: $0 [fmt|clippy|build|build-release|test|test-release|all|hybrid]"
+        echo "Usage: $0 [fmt|clippy|build|features|build-release|test|test-release|all|hybrid]"
         echo ""
         echo "Commands:"
         echo "  fmt           Check formatting"
         echo "  clippy        Run clippy lints"
         echo "  build         Build debug version"
+        echo "  features      Check each feature combination compiles"
         echo "  build-release Build release version"
         echo "  test          Run tests"
         echo "  test-release  Run release tests"
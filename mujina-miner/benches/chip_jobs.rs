@@ -0,0 +1,73 @@
+//! BM13xx job dispatch: encoding job frames.
+//!
+//! A board's writer encodes each job into one buffer it drains as frames
+//! go out, so once the buffer has held the largest frame, dispatching a job
+//! should cost no allocation at all. This encodes full-header and midstate
+//! jobs that way at dispatch rates far above any chain's, and fails if
+//! either allocates.
+//!
+//! Run with `cargo bench --bench chip_jobs`.
+
+mod util;
+
+use std::hint::black_box;
+
+use bitcoin::block::Version;
+use bitcoin::hashes::Hash;
+use bitcoin::{BlockHash, CompactTarget, TxMerkleNode};
+use bytes::BytesMut;
+use mujina_miner::asic::bm13xx::protocol::{Command, FrameCodec, JobFullFormat, JobMidstateFormat};
+use tokio_util::codec::Encoder;
+
+const JOBS: u64 = 1_000_000;
+
+fn main() {
+    let full = JobFullFormat {
+        job_id: 3,
+        num_midstates: 1,
+        starting_nonce: 0,
+        nbits: CompactTarget::from_consensus(0x1702_8c61),
+        ntime: 0x679a_c169,
+        merkle_root: TxMerkleNode::from_byte_array([0x11; 32]),
+        prev_block_hash: BlockHash::from_byte_array([0x22; 32]),
+        version: Version::from_consensus(0x2000_0000),
+    };
+    let midstate = JobMidstateFormat {
+        job_id: 3,
+        num_midstates: 4,
+        starting_nonce: [0; 4],
+        nbits: [0x61, 0x8c, 0x02, 0x17],
+        ntime: [0x69, 0xc1, 0x9a, 0x67],
+        merkle4: [0xde, 0xad, 0xbe, 0xef],
+        midstate0: [0x33; 32],
+        midstate1: Some([0x44; 32]),
+        midstate2: Some([0x55; 32]),
+        midstate3: Some([0x66; 32]),
+    };
+
+    let mut codec = FrameCodec::default();
+    let mut buf = BytesMut::new();
+    let results = [
+        util::measure("encode full-header job", JOBS, || {
+            let job = Command::JobFull {
+                job_data: black_box(full.clone()),
+            };
+            codec.encode(job, &mut buf).unwrap();
+            black_box(&buf);
+            buf.clear();
+        }),
+        util::measure("encode four-midstate job", JOBS, || {
+            let job = Command::JobMidstate {
+                job_data: black_box(midstate.clone()),
+            };
+            codec.encode(job, &mut buf).unwrap();
+            black_box(&buf);
+            buf.clear();
+        }),
+    ];
+
+    util::report("chip_jobs", &results);
+    for result in &results {
+        result.assert_no_allocations();
+    }
+}
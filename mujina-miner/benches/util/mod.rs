@@ -0,0 +1,142 @@
+//! Timing and allocation counting shared by the benchmarks.
+//!
+//! The benchmarks are plain binaries (`harness = false`) so they build
+//! without extra dependencies. Each measurement runs a closure many times
+//! after a warm-up and reports the mean time and heap allocations per call;
+//! a benchmark fails, so `cargo bench` does, when a path that must not
+//! allocate starts to.
+//!
+//! [`report`] prints each benchmark's results beside those of its previous
+//! run, kept under the target directory, so a change's cost shows up in the
+//! bench output of the run after it. Timings vary by several percent between
+//! runs even on a quiet machine; only changes beyond [`NOISE`] are flagged.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+/// Relative change in time below which a result counts as unchanged.
+pub const NOISE: f64 = 0.10;
+
+/// The system allocator, counting allocations.
+pub struct CountingAlloc;
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+// SAFETY: defers to the system allocator, only counting calls
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+/// Mean cost of one call of a measured closure.
+#[derive(Debug, Clone)]
+pub struct Measurement {
+    pub name: &'static str,
+    pub nanos: f64,
+    pub allocations: f64,
+}
+
+impl Measurement {
+    /// Panic if the call allocated.
+    pub fn assert_no_allocations(&self) {
+        assert!(
+            self.allocations == 0.0,
+            "{} allocates {:.2} times per call",
+            self.name,
+            self.allocations
+        );
+    }
+}
+
+/// Time `f` over `iterations` calls, after a tenth as many to warm up.
+pub fn measure(name: &'static str, iterations: u64, mut f: impl FnMut()) -> Measurement {
+    for _ in 0..(iterations / 10).max(1) {
+        f();
+    }
+
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..iterations {
+        f();
+    }
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+
+    Measurement {
+        name,
+        nanos: elapsed.as_nanos() as f64 / iterations as f64,
+        allocations: allocations as f64 / iterations as f64,
+    }
+}
+
+/// Print `results` against those of `bench`'s previous run, then save them
+/// for the next.
+pub fn report(bench: &str, results: &[Measurement]) {
+    let path = baseline_path(bench);
+    let baseline = load_baseline(&path);
+
+    for result in results {
+        let change = match baseline.get(result.name) {
+            Some(&previous) if previous > 0.0 => {
+                let change = result.nanos / previous - 1.0;
+                let verdict = if change > NOISE {
+                    "slower"
+                } else if change < -NOISE {
+                    "faster"
+                } else {
+                    ""
+                };
+                format!("{:>+7.1}% {verdict}", change * 100.0)
+            }
+            _ => String::new(),
+        };
+        println!(
+            "{:<40} {:>10.1} ns {:>8.2} allocs  {change}",
+            result.name, result.nanos, result.allocations
+        );
+    }
+
+    let saved: String = results
+        .iter()
+        .map(|result| format!("{}\t{}\n", result.name, result.nanos))
+        .collect();
+    if let Err(e) = fs::write(&path, saved) {
+        eprintln!("could not save results to {}: {e}", path.display());
+    }
+}
+
+fn baseline_path(bench: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{bench}.baseline"))
+}
+
+/// Mean times by name from a saved run; empty if there's none.
+fn load_baseline(path: &Path) -> HashMap<String, f64> {
+    let Ok(saved) = fs::read_to_string(path) else {
+        return HashMap::new();
+    };
+    saved
+        .lines()
+        .filter_map(|line| {
+            let (name, nanos) = line.split_once('\t')?;
+            Some((name.to_string(), nanos.parse().ok()?))
+        })
+        .collect()
+}
@@ -0,0 +1,116 @@
+//! The work pipeline between a job template and a share.
+//!
+//! Every nonce a chip reports is decoded from its frame, placed in a header
+//! whose merkle root comes from the task's extranonce2, hashed, and compared
+//! with the share target; shares are then weighed by the work their target
+//! represents, which takes 256-bit division. A slowdown anywhere here is
+//! paid per nonce by every thread, and shows up first as nonces arriving
+//! faster than they're checked. This measures each step on its own, with
+//! block 881,423's template, so a regression points at its step.
+//!
+//! Encoding the jobs sent the other way is measured by `chip_jobs`.
+//!
+//! Run with `cargo bench --bench work_pipeline`.
+
+mod util;
+
+use std::hint::black_box;
+use std::sync::Arc;
+
+use bitcoin::consensus::Encodable;
+use bytes::BytesMut;
+use mujina_miner::asic::bm13xx::protocol::{self, FrameCodec};
+use mujina_miner::asic::hash_thread::HashTask;
+use mujina_miner::job_source::{dummy, test_blocks::block_881423, MerkleRootKind};
+use mujina_miner::types::{difficulty_from_hash, Difficulty, Target};
+use tokio::sync::mpsc;
+use tokio_util::codec::Decoder;
+
+const ITERATIONS: u64 = 1_000_000;
+
+/// Merkle roots take a dozen double SHA-256s; fewer runs suffice.
+const MERKLE_ITERATIONS: u64 = 100_000;
+
+/// A BM1370 nonce response, as captured from a chip.
+const NONCE_FRAME: [u8; 11] = [
+    0xaa, 0x55, 0x4c, 0x03, 0x52, 0x75, 0x0c, 0xd2, 0x05, 0xa2, 0x9c,
+];
+
+fn main() {
+    let template = Arc::new(dummy::job_template().unwrap());
+    let MerkleRootKind::Computed(merkle) = &template.merkle_root else {
+        unreachable!("the dummy template rolls extranonce2");
+    };
+    let task = HashTask {
+        en2_range: Some(merkle.extranonce2_range().clone()),
+        en2: Some(*block_881423::EXTRANONCE2),
+        share_target: Difficulty::from(1024_u64).to_target(),
+        ntime: block_881423::TIME,
+        template: template.clone(),
+        share_tx: mpsc::channel(1).0,
+    };
+    let header = *block_881423::HEADER;
+    let hash = *block_881423::BLOCK_HASH;
+    let share_target = task.share_target;
+
+    let mut codec = FrameCodec::default();
+    let mut frames = BytesMut::with_capacity(NONCE_FRAME.len());
+    let mut serialized = Vec::with_capacity(80);
+
+    let results = [
+        util::measure("decode nonce response", ITERATIONS, || {
+            frames.extend_from_slice(black_box(&NONCE_FRAME));
+            black_box(codec.decode(&mut frames).unwrap().unwrap());
+        }),
+        util::measure("recompute merkle root", MERKLE_ITERATIONS, || {
+            black_box(merkle.compute_merkle_root(black_box(&block_881423::EXTRANONCE2))).unwrap();
+        }),
+        util::measure("build header for nonce", MERKLE_ITERATIONS, || {
+            black_box(task.header(header.version, header.time, black_box(header.nonce))).unwrap();
+        }),
+        util::measure("serialize header", ITERATIONS, || {
+            black_box(&header)
+                .consensus_encode(&mut serialized)
+                .unwrap();
+            black_box(&serialized);
+            serialized.clear();
+        }),
+        util::measure("hash header", ITERATIONS, || {
+            black_box(black_box(&header).block_hash());
+        }),
+        util::measure("header midstate", ITERATIONS, || {
+            black_box(protocol::header_midstate(black_box(&header)));
+        }),
+        util::measure("compare hash with target", ITERATIONS, || {
+            black_box(black_box(share_target).is_met_by(black_box(hash)));
+        }),
+        util::measure("verify nonce", ITERATIONS, || {
+            black_box(task.verify_nonce(black_box(&header))).unwrap();
+        }),
+        util::measure("difficulty from u64", ITERATIONS, || {
+            black_box(Difficulty::from(black_box(1024_u64)));
+        }),
+        util::measure("difficulty from f64", ITERATIONS, || {
+            black_box(Difficulty::from_f64(black_box(1024.5)));
+        }),
+        util::measure("difficulty as f64", ITERATIONS, || {
+            black_box(Difficulty::from_target(black_box(share_target)).as_f64());
+        }),
+        util::measure("difficulty of hash", ITERATIONS, || {
+            black_box(difficulty_from_hash(black_box(&hash)));
+        }),
+        util::measure("work of target", ITERATIONS, || {
+            black_box(black_box(Target::MAX).to_work());
+        }),
+    ];
+
+    util::report("work_pipeline", &results);
+    for result in &results {
+        if !matches!(
+            result.name,
+            "recompute merkle root" | "build header for nonce"
+        ) {
+            result.assert_no_allocations();
+        }
+    }
+}
@@ -0,0 +1,25 @@
+//! Bitaxe-raw control responses from a noisy serial line.
+//!
+//! The first byte sets how the rest arrives in reads. Decoding must never
+//! fail, which would end the control channel for good.
+
+#![no_main]
+
+use bytes::BytesMut;
+use libfuzzer_sys::fuzz_target;
+use mujina_miner::mgmt_protocol::bitaxe_raw::ControlCodec;
+use tokio_util::codec::Decoder;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&control, line)) = data.split_first() else {
+        return;
+    };
+    let read_len = usize::from(control) + 1;
+
+    let mut codec = ControlCodec::default();
+    let mut buf = BytesMut::new();
+    for read in line.chunks(read_len) {
+        buf.extend_from_slice(read);
+        while codec.decode(&mut buf).unwrap().is_some() {}
+    }
+});
@@ -0,0 +1,32 @@
+//! BM13xx response decoding from a noisy serial line.
+//!
+//! The first byte picks the protocol variant and how the rest arrives in
+//! reads; the rest is the line. Decoding must never fail, which would end
+//! the board's stream, and must never sit on a frame's worth of bytes.
+
+#![no_main]
+
+use bytes::BytesMut;
+use libfuzzer_sys::fuzz_target;
+use mujina_miner::asic::bm13xx::protocol::{FrameCodec, ProtocolVariant};
+use tokio_util::codec::Decoder;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&control, line)) = data.split_first() else {
+        return;
+    };
+    let variant = if control & 0x80 != 0 {
+        ProtocolVariant::Midstate
+    } else {
+        ProtocolVariant::FullHeader
+    };
+    let read_len = usize::from(control & 0x3f) + 1;
+
+    let mut codec = FrameCodec::default().with_variant(variant);
+    let mut buf = BytesMut::new();
+    for read in line.chunks(read_len) {
+        buf.extend_from_slice(read);
+        while codec.decode(&mut buf).unwrap().is_some() {}
+        assert!(buf.len() < 2 + variant.response_len());
+    }
+});
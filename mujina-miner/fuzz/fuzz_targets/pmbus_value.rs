@@ -0,0 +1,41 @@
+//! PMBus register values as read from a voltage regulator.
+//!
+//! The first byte is the command, the second VOUT_MODE if nonzero, and the
+//! rest the data read. Parsing and display must accept anything. The first
+//! two data bytes are also taken as a LINEAR11 word, which every decoder
+//! must read alike, and the first four as a value to encode, which must
+//! come back to within the format's precision.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mujina_miner::peripheral::pmbus::{linear11, parse_pmbus_value, Linear11, PmbusCommand};
+
+fuzz_target!(|data: &[u8]| {
+    let [command, vout_mode, data @ ..] = data else {
+        return;
+    };
+    let vout_mode = (*vout_mode != 0).then_some(*vout_mode);
+
+    if let Ok(command) = PmbusCommand::try_from(*command) {
+        let value = parse_pmbus_value(command, data, vout_mode);
+        let _ = value.to_string();
+    }
+
+    if let [lo, hi, ..] = data {
+        let raw = u16::from_le_bytes([*lo, *hi]);
+        let value = linear11::to_float(raw);
+        assert_eq!(Linear11::new(raw).to_f32(), value);
+        assert_eq!(linear11::to_float(linear11::from_float(value)), value);
+    }
+
+    if let [a, b, c, d, ..] = data {
+        let value = f32::from_le_bytes([*a, *b, *c, *d]);
+        if value.is_finite() && value.abs() <= linear11::MAX {
+            // Half a step of the finest exponent that holds the value
+            let tolerance = value.abs() / 1024.0 + 2.0_f32.powi(-17);
+            let encoded = linear11::to_float(linear11::from_float(value));
+            assert!((encoded - value).abs() <= tolerance, "{value} -> {encoded}");
+        }
+    }
+});
@@ -0,0 +1,76 @@
+//! Stratum v1 lines from a pool or a downstream miner.
+//!
+//! Each line is parsed as the connection does, and the messages among them
+//! as the client and proxy read them. A subscribe result sets the session's
+//! extranonce size, as untrusted as the rest, and later jobs are checked by
+//! `validate_job` against it. Jobs and submissions that parse must survive
+//! a round trip through their wire form.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mujina_miner::stratum_v1::{validate_job, JobNotification, JsonRpcMessage, SubmitParams};
+use mujina_miner::types::Target;
+use serde_json::Value;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    let mut extranonce_len = 8;
+    for line in text.lines().map(str::trim) {
+        let messages = if line.starts_with('[') {
+            serde_json::from_str::<Vec<JsonRpcMessage>>(line).unwrap_or_default()
+        } else {
+            serde_json::from_str::<JsonRpcMessage>(line)
+                .into_iter()
+                .collect()
+        };
+        for message in messages {
+            check(message, &mut extranonce_len);
+        }
+    }
+});
+
+fn check(message: JsonRpcMessage, extranonce_len: &mut usize) {
+    let (method, params) = match message {
+        JsonRpcMessage::Request { method, params, .. } => (method, params),
+        JsonRpcMessage::Response {
+            result: Some(Value::Array(result)),
+            ..
+        } => {
+            // [[subscriptions...], extranonce1, extranonce2_size]
+            if let [_, Value::String(extranonce1), size, ..] = &result[..] {
+                if let Some(size) = size.as_u64() {
+                    *extranonce_len = (extranonce1.len() / 2).saturating_add(size as usize);
+                }
+            }
+            return;
+        }
+        JsonRpcMessage::Response { .. } => return,
+    };
+    let Value::Array(params) = params else {
+        return;
+    };
+
+    match method.as_str() {
+        "mining.notify" => {
+            let Ok(job) = JobNotification::from_stratum_params(&params) else {
+                return;
+            };
+            let _ = validate_job(&job, *extranonce_len, Target::MAX, job.ntime);
+
+            let wire = job.to_stratum_params();
+            let again = JobNotification::from_stratum_params(&wire).unwrap();
+            assert_eq!(again.to_stratum_params(), wire);
+        }
+        "mining.submit" => {
+            let Ok(submit) = SubmitParams::from_stratum_params(&params) else {
+                return;
+            };
+            let again = SubmitParams::from_stratum_params(&submit.to_stratum_json()).unwrap();
+            assert_eq!(again, submit);
+        }
+        _ => {}
+    }
+}
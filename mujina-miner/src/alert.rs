@@ -0,0 +1,797 @@
+//! Notifications when boards or pools need attention.
+//!
+//! A home miner often runs unattended for weeks, so a board that dropped
+//! off USB or a pool rejecting every share can go unnoticed until the
+//! payout doesn't arrive. The alert manager checks a few rules every
+//! [`ALERT_CHECK_INTERVAL`] against the boards' cached status and the
+//! pools' share counts:
+//!
+//! - a board not running for `board_offline_secs`, including one that's
+//!   disappeared from the backplane
+//! - a board's chips hotter than `max_temperature_c`
+//! - more than `max_reject_percent` of a pool's recent shares rejected
+//! - the pool being mined disconnected for `pool_down_secs`
+//!
+//! A block found is sent the moment it's announced on the event bus, not
+//! at the next check, and never resolves: there's nothing to put right,
+//! but whoever runs the miner will want to know at once.
+//!
+//! Each other alert is sent once when it starts firing and once more when it
+//! resolves, to every configured webhook (generic JSON, Discord or
+//! Telegram) and by email through the local `sendmail`. A temperature
+//! alert resolves only [`TEMPERATURE_HYSTERESIS`] below its limit, so a
+//! board hovering at the limit doesn't send one after another.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::process::Stdio;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    backplane::{BackplaneCommand, BoardStatus},
+    board::task::BoardHealth,
+    config::{AlertConfig, EmailConfig, WebhookConfig},
+    pools::{PoolCommand, PoolInfo},
+    runtime::RuntimeEvent,
+    secret,
+    tracing::prelude::*,
+};
+
+/// How often the rules are checked.
+pub const ALERT_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Degrees below its limit a board must cool to resolve its alert.
+pub const TEMPERATURE_HYSTERESIS: f32 = 5.0;
+
+/// Fewest shares a reject rate is judged on.
+const MIN_REJECT_SAMPLE: u64 = 20;
+
+/// How long a notification may take to send.
+const NOTIFY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long to wait for the backplane or pool manager to answer.
+const STATUS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What an alert is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rule {
+    BoardOffline,
+    Temperature,
+    RejectRate,
+    PoolDown,
+    BlockFound,
+}
+
+/// Whether an alert started or stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertState {
+    Firing,
+    Resolved,
+}
+
+/// How a webhook wants its notifications.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookFormat {
+    /// The [`Notification`] as JSON
+    #[default]
+    Json,
+    /// A Discord webhook message
+    Discord,
+    /// A Telegram Bot API `sendMessage` call
+    Telegram,
+}
+
+/// An alert starting or stopping, as sent to notifiers.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Notification {
+    pub state: AlertState,
+    pub rule: Rule,
+    /// Board ID or pool URL the alert is about
+    pub subject: String,
+    /// What's wrong, for people
+    pub message: String,
+    /// Unix seconds
+    pub at: u64,
+}
+
+/// Somewhere notifications are sent.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// Send one notification.
+    async fn notify(&self, notification: &Notification) -> anyhow::Result<()>;
+}
+
+/// Notifier that posts to a webhook URL.
+pub struct Webhook {
+    client: reqwest::Client,
+    url: String,
+    format: WebhookFormat,
+    chat_id: Option<String>,
+}
+
+/// Notifier that mails through the local `sendmail`.
+pub struct Email {
+    config: EmailConfig,
+}
+
+/// The rules and which alerts are firing.
+#[derive(Debug)]
+pub struct Alerts {
+    config: AlertConfig,
+    /// Message of each firing alert, by rule and subject
+    firing: HashMap<(Rule, String), String>,
+    /// Boards seen, so one that disappears is noticed
+    known_boards: HashSet<String>,
+    /// Since when each board has been down
+    board_down_since: HashMap<String, Instant>,
+    /// Each pool's share counts when its current reject sample began
+    reject_baselines: HashMap<String, (u64, u64)>,
+    /// Since when the pool being mined has been disconnected, by URL
+    pool_down_since: HashMap<String, Instant>,
+}
+
+/// Checks the rules and sends notifications.
+pub struct AlertManager {
+    alerts: Alerts,
+    notifiers: Vec<Box<dyn Notifier>>,
+    backplane_tx: mpsc::Sender<BackplaneCommand>,
+    pool_tx: mpsc::Sender<PoolCommand>,
+    /// Event bus announcing found blocks, if given
+    events: Option<broadcast::Receiver<RuntimeEvent>>,
+}
+
+impl fmt::Display for AlertState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Firing => write!(f, "FIRING"),
+            Self::Resolved => write!(f, "RESOLVED"),
+        }
+    }
+}
+
+impl Notification {
+    /// Notification of a block `hash` found mining at `source`.
+    pub fn block_found(hash: &str, source: &str) -> Self {
+        Self {
+            state: AlertState::Firing,
+            rule: Rule::BlockFound,
+            subject: source.to_string(),
+            message: format!("Block found mining at {}: {}", source, hash),
+            at: unix_now(),
+        }
+    }
+
+    /// One line for chat messages and email subjects.
+    pub fn summary(&self) -> String {
+        format!("[mujina {}] {}", self.state, self.message)
+    }
+}
+
+impl Webhook {
+    /// Webhook posting to `config.url`, with any secret reference in it
+    /// resolved.
+    pub fn new(config: &WebhookConfig) -> anyhow::Result<Self> {
+        let url = secret::resolve(&config.url).context("webhook URL")?;
+        Ok(Self {
+            client: reqwest::Client::new(),
+            url,
+            format: config.format,
+            chat_id: config.chat_id.clone(),
+        })
+    }
+
+    /// Body to post for `notification`.
+    fn body(&self, notification: &Notification) -> serde_json::Value {
+        match self.format {
+            WebhookFormat::Json => serde_json::json!(notification),
+            WebhookFormat::Discord => serde_json::json!({ "content": notification.summary() }),
+            WebhookFormat::Telegram => serde_json::json!({
+                "chat_id": self.chat_id,
+                "text": notification.summary(),
+            }),
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for Webhook {
+    async fn notify(&self, notification: &Notification) -> anyhow::Result<()> {
+        self.client
+            .post(&self.url)
+            .timeout(NOTIFY_TIMEOUT)
+            .json(&self.body(notification))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+impl Email {
+    pub fn new(config: EmailConfig) -> Self {
+        Self { config }
+    }
+
+    /// The message handed to `sendmail`.
+    fn message(&self, notification: &Notification) -> String {
+        format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\n\r\n{}\r\n\r\nRule: {:?}\r\nAbout: {}\r\n",
+            self.config.from,
+            self.config.to.join(", "),
+            notification.summary(),
+            notification.message,
+            notification.rule,
+            notification.subject,
+        )
+    }
+}
+
+#[async_trait]
+impl Notifier for Email {
+    async fn notify(&self, notification: &Notification) -> anyhow::Result<()> {
+        let mut child = tokio::process::Command::new(&self.config.sendmail)
+            .arg("-i")
+            .arg("--")
+            .args(&self.config.to)
+            .stdin(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("running {}", self.config.sendmail.display()))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(self.message(notification).as_bytes())
+                .await?;
+        }
+        let status = tokio::time::timeout(NOTIFY_TIMEOUT, child.wait()).await??;
+        if !status.success() {
+            bail!("{} exited with {}", self.config.sendmail.display(), status);
+        }
+        Ok(())
+    }
+}
+
+impl Alerts {
+    pub fn new(config: AlertConfig) -> Self {
+        Self {
+            config,
+            firing: HashMap::new(),
+            known_boards: HashSet::new(),
+            board_down_since: HashMap::new(),
+            reject_baselines: HashMap::new(),
+            pool_down_since: HashMap::new(),
+        }
+    }
+
+    /// Check the rules against the latest status, returning the alerts that
+    /// started or stopped firing since the last check.
+    pub fn check(
+        &mut self,
+        boards: &[BoardStatus],
+        pools: &[PoolInfo],
+        now: Instant,
+    ) -> Vec<Notification> {
+        let mut conditions = HashMap::new();
+        self.check_boards(boards, now, &mut conditions);
+        self.check_pools(pools, now, &mut conditions);
+
+        let at = unix_now();
+        let mut notifications = Vec::new();
+        self.firing.retain(|(rule, subject), message| {
+            if conditions.contains_key(&(*rule, subject.clone())) {
+                return true;
+            }
+            notifications.push(Notification {
+                state: AlertState::Resolved,
+                rule: *rule,
+                subject: subject.clone(),
+                message: format!("Resolved: {}", message),
+                at,
+            });
+            false
+        });
+        for ((rule, subject), message) in conditions {
+            if self.firing.contains_key(&(rule, subject.clone())) {
+                continue;
+            }
+            self.firing.insert((rule, subject.clone()), message.clone());
+            notifications.push(Notification {
+                state: AlertState::Firing,
+                rule,
+                subject,
+                message,
+                at,
+            });
+        }
+        notifications
+    }
+
+    fn check_boards(
+        &mut self,
+        boards: &[BoardStatus],
+        now: Instant,
+        conditions: &mut HashMap<(Rule, String), String>,
+    ) {
+        let listed: HashSet<&str> = boards.iter().map(|b| b.id.as_str()).collect();
+        for id in &self.known_boards {
+            if !listed.contains(id.as_str()) {
+                self.board_down_since.entry(id.clone()).or_insert(now);
+            }
+        }
+
+        for board in boards {
+            self.known_boards.insert(board.id.clone());
+            if board.health == BoardHealth::Running {
+                self.board_down_since.remove(&board.id);
+            } else {
+                self.board_down_since.entry(board.id.clone()).or_insert(now);
+            }
+
+            let (Some(limit), Some(temp)) = (
+                self.config.max_temperature_c,
+                board.telemetry.as_ref().and_then(|t| t.temperature_c),
+            ) else {
+                continue;
+            };
+            let firing = self
+                .firing
+                .contains_key(&(Rule::Temperature, board.id.clone()));
+            if temp > limit || (firing && temp > limit - TEMPERATURE_HYSTERESIS) {
+                conditions.insert(
+                    (Rule::Temperature, board.id.clone()),
+                    format!(
+                        "Board {} ({}) at {:.1} °C, over the {:.1} °C limit",
+                        board.id, board.model, temp, limit
+                    ),
+                );
+            }
+        }
+
+        let Some(limit) = self.config.board_offline_secs else {
+            return;
+        };
+        for (id, since) in &self.board_down_since {
+            let down = now.saturating_duration_since(*since);
+            if down.as_secs() < limit {
+                continue;
+            }
+            let state = match boards.iter().find(|b| &b.id == id) {
+                Some(board) => match &board.error {
+                    Some(error) => format!("{:?}: {}", board.health, error),
+                    None => format!("{:?}", board.health),
+                },
+                None => "gone from the backplane".to_string(),
+            };
+            conditions.insert(
+                (Rule::BoardOffline, id.clone()),
+                format!(
+                    "Board {} offline for {} min ({})",
+                    id,
+                    down.as_secs() / 60,
+                    state
+                ),
+            );
+        }
+    }
+
+    fn check_pools(
+        &mut self,
+        pools: &[PoolInfo],
+        now: Instant,
+        conditions: &mut HashMap<(Rule, String), String>,
+    ) {
+        if let Some(limit) = self.config.max_reject_percent {
+            for pool in pools {
+                let (accepted, rejected) = (pool.status.accepted, pool.status.rejected);
+                let baseline = self
+                    .reject_baselines
+                    .entry(pool.url.clone())
+                    .or_insert((accepted, rejected));
+                // Counts start again with each session
+                if accepted < baseline.0 || rejected < baseline.1 {
+                    *baseline = (accepted, rejected);
+                }
+                let (new_accepted, new_rejected) = (accepted - baseline.0, rejected - baseline.1);
+                let key = (Rule::RejectRate, pool.url.clone());
+                if new_accepted + new_rejected < MIN_REJECT_SAMPLE {
+                    // Too few shares to judge; as it was
+                    if let Some(message) = self.firing.get(&key) {
+                        conditions.insert(key, message.clone());
+                    }
+                    continue;
+                }
+                *baseline = (accepted, rejected);
+
+                let percent = 100.0 * new_rejected as f64 / (new_accepted + new_rejected) as f64;
+                if percent > limit {
+                    conditions.insert(
+                        key,
+                        format!(
+                            "Pool {} rejected {:.0}% of the last {} shares",
+                            pool.url,
+                            percent,
+                            new_accepted + new_rejected
+                        ),
+                    );
+                }
+            }
+        }
+
+        let Some(limit) = self.config.pool_down_secs else {
+            return;
+        };
+        let active: Vec<&PoolInfo> = pools.iter().filter(|p| p.active).collect();
+        self.pool_down_since
+            .retain(|url, _| active.iter().any(|p| &p.url == url && !p.status.connected));
+        for pool in active {
+            if pool.status.connected {
+                continue;
+            }
+            let since = *self.pool_down_since.entry(pool.url.clone()).or_insert(now);
+            let down = now.saturating_duration_since(since);
+            if down.as_secs() >= limit {
+                conditions.insert(
+                    (Rule::PoolDown, pool.url.clone()),
+                    format!(
+                        "Pool {} disconnected for {} min",
+                        pool.url,
+                        down.as_secs() / 60
+                    ),
+                );
+            }
+        }
+    }
+}
+
+impl AlertManager {
+    /// Create a manager that reads status from the backplane and the pool
+    /// manager and notifies as `config` says. Fails if a webhook URL
+    /// refers to a secret that can't be had.
+    pub fn new(
+        config: AlertConfig,
+        backplane_tx: mpsc::Sender<BackplaneCommand>,
+        pool_tx: mpsc::Sender<PoolCommand>,
+    ) -> anyhow::Result<Self> {
+        let mut notifiers: Vec<Box<dyn Notifier>> = Vec::new();
+        for webhook in &config.webhooks {
+            notifiers.push(Box::new(Webhook::new(webhook)?));
+        }
+        if let Some(email) = &config.email {
+            notifiers.push(Box::new(Email::new(email.clone())));
+        }
+        Ok(Self {
+            alerts: Alerts::new(config),
+            notifiers,
+            backplane_tx,
+            pool_tx,
+            events: None,
+        })
+    }
+
+    /// Notify of the blocks announced on `events`.
+    pub fn with_events(mut self, events: broadcast::Receiver<RuntimeEvent>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Check the rules every [`ALERT_CHECK_INTERVAL`] until shutdown.
+    pub async fn run(mut self, shutdown: CancellationToken) -> anyhow::Result<()> {
+        info!(notifiers = self.notifiers.len(), "Alerting enabled");
+        let mut ticker = tokio::time::interval(ALERT_CHECK_INTERVAL);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                Some(event) = next_event(&mut self.events) => {
+                    if let RuntimeEvent::BlockFound { hash, source } = event {
+                        self.send(&Notification::block_found(&hash, &source)).await;
+                    }
+                    continue;
+                }
+                _ = shutdown.cancelled() => return Ok(()),
+            }
+
+            let Some(boards) = self.list_boards().await else {
+                warn!("Backplane didn't list boards; alerts not checked");
+                continue;
+            };
+            let pools = self.list_pools().await.unwrap_or_default();
+            for notification in self.alerts.check(&boards, &pools, Instant::now()) {
+                self.send(&notification).await;
+            }
+        }
+    }
+
+    /// Send a notification to every notifier; failures are only logged.
+    async fn send(&self, notification: &Notification) {
+        info!(
+            rule = ?notification.rule,
+            subject = %notification.subject,
+            state = %notification.state,
+            "{}",
+            notification.message
+        );
+        for notifier in &self.notifiers {
+            if let Err(e) = notifier.notify(notification).await {
+                warn!(rule = ?notification.rule, error = %e, "Failed to send alert");
+            }
+        }
+    }
+
+    async fn list_boards(&self) -> Option<Vec<BoardStatus>> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.backplane_tx
+            .send(BackplaneCommand::ListBoards { reply_tx })
+            .await
+            .ok()?;
+        tokio::time::timeout(STATUS_TIMEOUT, reply_rx)
+            .await
+            .ok()?
+            .ok()
+    }
+
+    async fn list_pools(&self) -> Option<Vec<PoolInfo>> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.pool_tx
+            .send(PoolCommand::List { reply_tx })
+            .await
+            .ok()?;
+        tokio::time::timeout(STATUS_TIMEOUT, reply_rx)
+            .await
+            .ok()?
+            .ok()
+    }
+}
+
+/// The next event on `events`, or never if there's no bus to listen to.
+async fn next_event(
+    events: &mut Option<broadcast::Receiver<RuntimeEvent>>,
+) -> Option<RuntimeEvent> {
+    let Some(rx) = events else {
+        return std::future::pending().await;
+    };
+    loop {
+        match rx.recv().await {
+            Ok(event) => return Some(event),
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                warn!(missed, "Alert manager fell behind the event bus");
+            }
+            Err(broadcast::error::RecvError::Closed) => {
+                *events = None;
+                return None;
+            }
+        }
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::TelemetrySnapshot;
+    use crate::job_source::stratum_v1::PoolStatus;
+
+    fn config() -> AlertConfig {
+        AlertConfig {
+            board_offline_secs: Some(300),
+            max_temperature_c: Some(70.0),
+            max_reject_percent: Some(5.0),
+            pool_down_secs: Some(120),
+            webhooks: Vec::new(),
+            email: None,
+        }
+    }
+
+    fn board(id: &str, health: BoardHealth, temperature_c: Option<f32>) -> BoardStatus {
+        let mut telemetry = TelemetrySnapshot::new();
+        telemetry.temperature_c = temperature_c;
+        BoardStatus {
+            id: id.into(),
+            model: "Bitaxe Gamma".into(),
+            health,
+            generation: 1,
+            error: None,
+            power_limited: None,
+            telemetry: Some(telemetry),
+        }
+    }
+
+    fn pool(connected: bool, accepted: u64, rejected: u64) -> PoolInfo {
+        PoolInfo {
+            id: 0,
+            url: "stratum+tcp://pool.example.com:3333".into(),
+            worker: "rig1".into(),
+            priority: 0,
+            active: true,
+            forced: false,
+            status: PoolStatus {
+                connected,
+                accepted,
+                rejected,
+                ..Default::default()
+            },
+        }
+    }
+
+    fn fired(notifications: &[Notification]) -> Vec<(AlertState, Rule)> {
+        notifications.iter().map(|n| (n.state, n.rule)).collect()
+    }
+
+    #[test]
+    fn test_board_offline_fires_once_and_resolves() {
+        let mut alerts = Alerts::new(config());
+        let start = Instant::now();
+        let up = [board("BX0001", BoardHealth::Running, Some(50.0))];
+        let pools = [pool(true, 0, 0)];
+        assert!(alerts.check(&up, &pools, start).is_empty());
+
+        // Unplugged: alerted on once it's been gone long enough, and once only
+        assert!(alerts.check(&[], &pools, start).is_empty());
+        let later = start + Duration::from_secs(300);
+        let notifications = alerts.check(&[], &pools, later);
+        assert_eq!(
+            fired(&notifications),
+            [(AlertState::Firing, Rule::BoardOffline)]
+        );
+        assert_eq!(notifications[0].subject, "BX0001");
+        assert!(alerts
+            .check(&[], &pools, later + Duration::from_secs(30))
+            .is_empty());
+
+        let notifications = alerts.check(&up, &pools, later + Duration::from_secs(60));
+        assert_eq!(
+            fired(&notifications),
+            [(AlertState::Resolved, Rule::BoardOffline)]
+        );
+    }
+
+    #[test]
+    fn test_temperature_resolves_below_hysteresis() {
+        let mut alerts = Alerts::new(config());
+        let now = Instant::now();
+        let at = |temp| [board("BX0001", BoardHealth::Running, Some(temp))];
+
+        let notifications = alerts.check(&at(72.0), &[], now);
+        assert_eq!(
+            fired(&notifications),
+            [(AlertState::Firing, Rule::Temperature)]
+        );
+        assert!(alerts.check(&at(68.0), &[], now).is_empty());
+        assert_eq!(
+            fired(&alerts.check(&at(64.0), &[], now)),
+            [(AlertState::Resolved, Rule::Temperature)]
+        );
+    }
+
+    #[test]
+    fn test_reject_rate_judged_on_enough_shares() {
+        let mut alerts = Alerts::new(config());
+        let now = Instant::now();
+        assert!(alerts.check(&[], &[pool(true, 100, 0)], now).is_empty());
+
+        // 3 rejects of 5 shares is too few to go on
+        assert!(alerts.check(&[], &[pool(true, 102, 3)], now).is_empty());
+        let notifications = alerts.check(&[], &[pool(true, 118, 5)], now);
+        assert_eq!(
+            fired(&notifications),
+            [(AlertState::Firing, Rule::RejectRate)]
+        );
+
+        // Still firing until a whole sample comes back clean
+        assert!(alerts.check(&[], &[pool(true, 128, 5)], now).is_empty());
+        assert_eq!(
+            fired(&alerts.check(&[], &[pool(true, 140, 5)], now)),
+            [(AlertState::Resolved, Rule::RejectRate)]
+        );
+    }
+
+    #[test]
+    fn test_pool_down() {
+        let mut alerts = Alerts::new(config());
+        let start = Instant::now();
+        assert!(alerts.check(&[], &[pool(false, 0, 0)], start).is_empty());
+        let notifications =
+            alerts.check(&[], &[pool(false, 0, 0)], start + Duration::from_secs(120));
+        assert_eq!(
+            fired(&notifications),
+            [(AlertState::Firing, Rule::PoolDown)]
+        );
+        let notifications =
+            alerts.check(&[], &[pool(true, 0, 0)], start + Duration::from_secs(150));
+        assert_eq!(
+            fired(&notifications),
+            [(AlertState::Resolved, Rule::PoolDown)]
+        );
+    }
+
+    /// Notifier that keeps what it's sent.
+    #[derive(Clone, Default)]
+    struct Recorder(std::sync::Arc<std::sync::Mutex<Vec<Notification>>>);
+
+    #[async_trait]
+    impl Notifier for Recorder {
+        async fn notify(&self, notification: &Notification) -> anyhow::Result<()> {
+            self.0.lock().unwrap().push(notification.clone());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_block_found_sent_at_once() {
+        let recorder = Recorder::default();
+        let (events, events_rx) = broadcast::channel(4);
+        let manager = AlertManager {
+            alerts: Alerts::new(config()),
+            notifiers: vec![Box::new(recorder.clone())],
+            backplane_tx: mpsc::channel(1).0,
+            pool_tx: mpsc::channel(1).0,
+            events: None,
+        }
+        .with_events(events_rx);
+        let shutdown = CancellationToken::new();
+        let run = tokio::spawn(manager.run(shutdown.clone()));
+
+        events
+            .send(RuntimeEvent::BlockFound {
+                hash: "00000000000000000001".into(),
+                source: "solo".into(),
+            })
+            .unwrap();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while recorder.0.lock().unwrap().is_empty() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("block found never notified");
+        shutdown.cancel();
+        run.await.unwrap().unwrap();
+
+        let sent = recorder.0.lock().unwrap();
+        assert_eq!(fired(&sent), [(AlertState::Firing, Rule::BlockFound)]);
+        assert_eq!(sent[0].subject, "solo");
+    }
+
+    #[test]
+    fn test_webhook_bodies() {
+        let notification = Notification {
+            state: AlertState::Firing,
+            rule: Rule::PoolDown,
+            subject: "stratum+tcp://pool.example.com:3333".into(),
+            message: "Pool down".into(),
+            at: 1_760_486_400,
+        };
+        let webhook = |format, chat_id: Option<&str>| {
+            Webhook::new(&WebhookConfig {
+                url: "https://hooks.example.com/alert".into(),
+                format,
+                chat_id: chat_id.map(Into::into),
+            })
+            .unwrap()
+        };
+
+        let json = webhook(WebhookFormat::Json, None).body(&notification);
+        assert_eq!(json["state"], "firing");
+        assert_eq!(json["rule"], "pool_down");
+        let discord = webhook(WebhookFormat::Discord, None).body(&notification);
+        assert_eq!(discord["content"], "[mujina FIRING] Pool down");
+        let telegram = webhook(WebhookFormat::Telegram, Some("-100123")).body(&notification);
+        assert_eq!(telegram["chat_id"], "-100123");
+        assert_eq!(telegram["text"], "[mujina FIRING] Pool down");
+    }
+}
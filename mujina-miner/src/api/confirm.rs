@@ -0,0 +1,131 @@
+//! Confirmation tokens for destructive admin actions.
+//!
+//! Endpoints that restart the daemon or power down hardware require a
+//! two-step exchange: the client first requests a token for a specific action,
+//! then repeats the request with that token. This guards against a stray
+//! `curl` taking the miner offline. Tokens are single-use, bound to one
+//! action, and expire quickly.
+//!
+//! The tokens are not a security mechanism---the API is unauthenticated and
+//! anyone who can reach it can also request a token.
+
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+/// How long an issued token remains valid.
+pub const TOKEN_LIFETIME: Duration = Duration::from_secs(30);
+
+/// A token that was handed out but not yet used.
+#[derive(Debug)]
+struct PendingToken {
+    action: String,
+    expires_at: Instant,
+}
+
+/// Store of outstanding confirmation tokens.
+///
+/// Cheap to clone; clones share the same store.
+#[derive(Debug, Clone, Default)]
+pub struct ConfirmationTokens {
+    pending: Arc<Mutex<HashMap<String, PendingToken>>>,
+}
+
+impl ConfirmationTokens {
+    /// Create an empty token store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Issue a new token for `action`.
+    pub fn issue(&self, action: &str) -> String {
+        let token = generate_token();
+        let now = Instant::now();
+
+        let mut pending = self.pending.lock().unwrap();
+        pending.retain(|_, p| p.expires_at > now);
+        pending.insert(
+            token.clone(),
+            PendingToken {
+                action: action.to_string(),
+                expires_at: now + TOKEN_LIFETIME,
+            },
+        );
+
+        token
+    }
+
+    /// Consume `token` for `action`.
+    ///
+    /// Returns true if the token was issued for this action and has not
+    /// expired. A token is removed on any presentation, so a token used for
+    /// the wrong action cannot be retried.
+    pub fn consume(&self, action: &str, token: &str) -> bool {
+        let mut pending = self.pending.lock().unwrap();
+        match pending.remove(token) {
+            Some(p) => p.action == action && p.expires_at > Instant::now(),
+            None => false,
+        }
+    }
+}
+
+/// Generate a random 128-bit token as hex.
+///
+/// Uses the randomly seeded keys of `RandomState` rather than pulling in a
+/// dedicated RNG; unpredictability only needs to defeat accidents.
+fn generate_token() -> String {
+    let word = || RandomState::new().build_hasher().finish();
+    format!("{:016x}{:016x}", word(), word())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_is_single_use() {
+        let tokens = ConfirmationTokens::new();
+        let token = tokens.issue("restart");
+
+        assert!(tokens.consume("restart", &token));
+        assert!(!tokens.consume("restart", &token));
+    }
+
+    #[test]
+    fn test_token_bound_to_action() {
+        let tokens = ConfirmationTokens::new();
+        let token = tokens.issue("restart");
+
+        assert!(!tokens.consume("shutdown", &token));
+        // Wrong-action presentation burns the token
+        assert!(!tokens.consume("restart", &token));
+    }
+
+    #[test]
+    fn test_unknown_token_rejected() {
+        let tokens = ConfirmationTokens::new();
+        tokens.issue("restart");
+
+        assert!(!tokens.consume("restart", "not-a-token"));
+    }
+
+    #[test]
+    fn test_tokens_are_distinct() {
+        let tokens = ConfirmationTokens::new();
+        assert_ne!(tokens.issue("restart"), tokens.issue("restart"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_token_expires() {
+        let tokens = ConfirmationTokens::new();
+        let token = tokens.issue("shutdown");
+
+        tokio::time::advance(TOKEN_LIFETIME + Duration::from_secs(1)).await;
+
+        assert!(!tokens.consume("shutdown", &token));
+    }
+}
@@ -0,0 +1,357 @@
+//! API error type.
+//!
+//! Every failed request gets a JSON body of the same shape, so clients can
+//! branch on `code` rather than parsing messages:
+//!
+//! ```json
+//! {
+//!   "code": "board_not_found",
+//!   "message": "no board with ID 1a2b3c",
+//!   "details": { "id": "1a2b3c" }
+//! }
+//! ```
+//!
+//! `code` is stable; `message` is for people and may change. `details` is
+//! present only for errors that carry structured context.
+
+use std::time::Duration;
+
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use thiserror::Error;
+
+use crate::{
+    board::{preset::PresetError, BoardError},
+    config::InvalidConfig,
+    pools::{PoolError, PoolId},
+    settings::SettingsError,
+    tracing::LogLevelError,
+};
+
+/// An error an API handler can return.
+#[derive(Error, Debug)]
+pub enum ApiError {
+    /// No board has the requested ID.
+    #[error("no board with ID {0}")]
+    BoardNotFound(String),
+
+    /// No pool has the requested ID.
+    #[error("no pool with ID {0}")]
+    PoolNotFound(PoolId),
+
+    /// A pool to add has a bad URL or worker name.
+    #[error("{0}")]
+    InvalidPool(String),
+
+    /// A pool change couldn't be written to the config file, so it wasn't
+    /// made.
+    #[error("{0}")]
+    ConfigSave(String),
+
+    /// The daemon wasn't started from a config file.
+    #[error("no config file; start the daemon with --config to manage it here")]
+    NoConfigFile,
+
+    /// A configuration update failed validation.
+    #[error(transparent)]
+    InvalidConfig(#[from] InvalidConfig),
+
+    /// The daemon isn't mining (e.g., it's benchmarking), so there are no
+    /// pools to manage.
+    #[error("pool manager is not running")]
+    PoolsUnavailable,
+
+    /// The daemon isn't serving downstream miners (no `[proxy]` section, or
+    /// it's benchmarking).
+    #[error("proxy is not running")]
+    ProxyUnavailable,
+
+    /// The daemon isn't mining (e.g., it's benchmarking), so there's no
+    /// scheduler to pause.
+    #[error("scheduler is not running")]
+    MiningUnavailable,
+
+    /// The path names an admin action that doesn't exist.
+    #[error("unknown admin action: {0}")]
+    UnknownAction(String),
+
+    /// The confirmation token is missing, expired, or for another action.
+    #[error(
+        "invalid or expired confirmation token; request one from /api/v1/admin/{action}/token"
+    )]
+    InvalidConfirmation { action: String },
+
+    /// A board identity to store is incomplete or malformed.
+    #[error("{0}")]
+    InvalidIdentity(String),
+
+    /// Board settings to store are out of range or malformed.
+    #[error("{0}")]
+    InvalidSettings(String),
+
+    /// Board settings couldn't be written to the state directory, so they
+    /// weren't changed.
+    #[error("{0}")]
+    SettingsSave(String),
+
+    /// The requested core voltage is outside the board's range.
+    #[error("core voltage {volts} V is outside the board's range of {min}-{max} V")]
+    VoltageOutOfRange { volts: f32, min: f32, max: f32 },
+
+    /// The board was recreated while the request was on its way, so the
+    /// request was dropped; retrying reaches the new incarnation.
+    #[error("board restarted before the request reached it; retry")]
+    BoardRestarted,
+
+    /// Another operation is in progress on the board.
+    #[error("board {id} is busy with a {operation}; retry once it's done")]
+    BoardBusy { id: String, operation: &'static str },
+
+    /// The operation was started on the board too recently.
+    #[error("{operation} was started on this board too recently; retry in {}s", retry_after_secs(*.retry_after))]
+    RateLimited {
+        operation: &'static str,
+        retry_after: Duration,
+    },
+
+    /// The board refused or failed a control request.
+    #[error("{0}")]
+    BoardControl(String),
+
+    /// A log level or module path couldn't be applied.
+    #[error(transparent)]
+    LogLevel(#[from] LogLevelError),
+
+    /// The board hasn't had a firmware update.
+    #[error("no firmware update for board {0}")]
+    FirmwareUpdateNotFound(String),
+
+    /// The backplane has stopped, so boards can't be reached.
+    #[error("backplane is not running")]
+    BackplaneUnavailable,
+
+    /// The backplane didn't answer in time.
+    #[error("timed out waiting for the backplane to {operation}")]
+    BackplaneTimeout { operation: &'static str },
+
+    /// Something failed that the client can't do anything about.
+    #[error("{0}")]
+    Internal(String),
+}
+
+/// JSON body of an error response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorBody {
+    /// Stable, machine-readable error code.
+    pub code: String,
+    /// Human-readable description.
+    pub message: String,
+    /// Structured context, for errors that have any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+impl ApiError {
+    /// Machine-readable code for this error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::BoardNotFound(_) => "board_not_found",
+            Self::FirmwareUpdateNotFound(_) => "firmware_update_not_found",
+            Self::PoolNotFound(_) => "pool_not_found",
+            Self::InvalidPool(_) => "invalid_pool",
+            Self::ConfigSave(_) => "config_save_failed",
+            Self::NoConfigFile => "no_config_file",
+            Self::InvalidConfig(_) => "invalid_config",
+            Self::PoolsUnavailable => "pools_unavailable",
+            Self::ProxyUnavailable => "proxy_unavailable",
+            Self::MiningUnavailable => "mining_unavailable",
+            Self::UnknownAction(_) => "unknown_action",
+            Self::InvalidConfirmation { .. } => "invalid_confirmation",
+            Self::InvalidIdentity(_) => "invalid_identity",
+            Self::InvalidSettings(_) => "invalid_settings",
+            Self::SettingsSave(_) => "settings_save_failed",
+            Self::VoltageOutOfRange { .. } => "voltage_out_of_range",
+            Self::BoardRestarted => "board_restarted",
+            Self::BoardBusy { .. } => "board_busy",
+            Self::RateLimited { .. } => "rate_limited",
+            Self::BoardControl(_) => "board_control_failed",
+            Self::LogLevel(LogLevelError::InvalidLevel(_)) => "invalid_log_level",
+            Self::LogLevel(LogLevelError::InvalidModule(_)) => "invalid_module",
+            Self::LogLevel(LogLevelError::NotInitialized) => "logging_unavailable",
+            Self::LogLevel(LogLevelError::Reload(_)) => "log_filter_failed",
+            Self::BackplaneUnavailable => "backplane_unavailable",
+            Self::BackplaneTimeout { .. } => "backplane_timeout",
+            Self::Internal(_) => "internal",
+        }
+    }
+
+    /// HTTP status this error is reported with.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::BoardNotFound(_)
+            | Self::FirmwareUpdateNotFound(_)
+            | Self::PoolNotFound(_)
+            | Self::NoConfigFile
+            | Self::UnknownAction(_) => StatusCode::NOT_FOUND,
+            Self::InvalidConfirmation { .. } => StatusCode::FORBIDDEN,
+            Self::BoardControl(_) | Self::BoardRestarted | Self::BoardBusy { .. } => {
+                StatusCode::CONFLICT
+            }
+            Self::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            Self::VoltageOutOfRange { .. }
+            | Self::InvalidIdentity(_)
+            | Self::InvalidSettings(_)
+            | Self::InvalidPool(_)
+            | Self::InvalidConfig(_)
+            | Self::LogLevel(LogLevelError::InvalidLevel(_))
+            | Self::LogLevel(LogLevelError::InvalidModule(_)) => StatusCode::BAD_REQUEST,
+            Self::LogLevel(LogLevelError::NotInitialized)
+            | Self::BackplaneUnavailable
+            | Self::PoolsUnavailable
+            | Self::ProxyUnavailable
+            | Self::MiningUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+            Self::BackplaneTimeout { .. } => StatusCode::GATEWAY_TIMEOUT,
+            Self::LogLevel(LogLevelError::Reload(_))
+            | Self::ConfigSave(_)
+            | Self::SettingsSave(_)
+            | Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn details(&self) -> Option<serde_json::Value> {
+        match self {
+            Self::BoardNotFound(id) | Self::FirmwareUpdateNotFound(id) => Some(json!({ "id": id })),
+            Self::PoolNotFound(id) => Some(json!({ "id": id })),
+            Self::InvalidConfig(InvalidConfig(problems)) => Some(json!({ "problems": problems })),
+            Self::UnknownAction(action) | Self::InvalidConfirmation { action } => {
+                Some(json!({ "action": action }))
+            }
+            Self::VoltageOutOfRange { volts, min, max } => Some(json!({
+                "volts": volts,
+                "allowed": { "min": min, "max": max },
+            })),
+            Self::LogLevel(LogLevelError::InvalidLevel(level)) => Some(json!({ "level": level })),
+            Self::LogLevel(LogLevelError::InvalidModule(module)) => {
+                Some(json!({ "module": module }))
+            }
+            Self::BackplaneTimeout { operation } => Some(json!({ "operation": operation })),
+            Self::BoardBusy { id, operation } => Some(json!({ "id": id, "operation": operation })),
+            Self::RateLimited {
+                operation,
+                retry_after,
+            } => Some(json!({
+                "operation": operation,
+                "retry_after_secs": retry_after_secs(*retry_after),
+            })),
+            _ => None,
+        }
+    }
+
+    /// The response body for this error.
+    pub fn body(&self) -> ErrorBody {
+        ErrorBody {
+            code: self.code().to_string(),
+            message: self.to_string(),
+            details: self.details(),
+        }
+    }
+}
+
+impl From<BoardError> for ApiError {
+    fn from(err: BoardError) -> Self {
+        match err {
+            BoardError::VoltageOutOfRange { volts, min, max } => {
+                Self::VoltageOutOfRange { volts, min, max }
+            }
+            BoardError::Restarted => Self::BoardRestarted,
+            other => Self::BoardControl(other.to_string()),
+        }
+    }
+}
+
+impl From<SettingsError> for ApiError {
+    fn from(err: SettingsError) -> Self {
+        match err {
+            SettingsError::Apply(e) => e.into(),
+            other => Self::SettingsSave(other.to_string()),
+        }
+    }
+}
+
+impl From<PresetError> for ApiError {
+    fn from(err: PresetError) -> Self {
+        match err {
+            PresetError::NoBoard(id) => Self::BoardNotFound(id),
+            PresetError::Undefined { .. } => Self::InvalidSettings(err.to_string()),
+            PresetError::Settings(e) => e.into(),
+        }
+    }
+}
+
+impl From<PoolError> for ApiError {
+    fn from(err: PoolError) -> Self {
+        match err {
+            PoolError::NotFound(id) => Self::PoolNotFound(id),
+            PoolError::Invalid(_) => Self::InvalidPool(err.to_string()),
+            PoolError::Save(_) => Self::ConfigSave(err.to_string()),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let mut response = (self.status(), Json(self.body())).into_response();
+        if let Self::RateLimited { retry_after, .. } = self {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, retry_after_secs(retry_after).into());
+        }
+        response
+    }
+}
+
+/// Whole seconds to wait, rounded up so a retry isn't early.
+fn retry_after_secs(retry_after: Duration) -> u64 {
+    retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::to_bytes;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_error_response_shape() {
+        let response = ApiError::BoardNotFound("1a2b3c".into()).into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(
+            body,
+            json!({
+                "code": "board_not_found",
+                "message": "no board with ID 1a2b3c",
+                "details": { "id": "1a2b3c" },
+            })
+        );
+    }
+
+    #[test]
+    fn test_details_omitted_when_absent() {
+        let body = serde_json::to_value(ApiError::BackplaneUnavailable.body()).unwrap();
+        assert_eq!(
+            body,
+            json!({
+                "code": "backplane_unavailable",
+                "message": "backplane is not running",
+            })
+        );
+    }
+}
@@ -0,0 +1,145 @@
+//! Guards on API operations that act on a board's hardware.
+//!
+//! The backplane serves board requests one at a time, so overlapping
+//! requests for one board don't corrupt anything, but they do queue up: a
+//! client retrying a slow retune would find its retries applied one after
+//! another, long after it gave up. Instead, an operation on a board is
+//! refused while another is in progress on the same board, and each kind of
+//! operation may only be started so often per board. Operations on
+//! different boards don't affect each other.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+/// A kind of board operation and how often it may be started on one board.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoardOperation {
+    /// Name reported to clients, e.g. "retune"
+    pub name: &'static str,
+    /// Least time between starts on one board
+    pub min_interval: Duration,
+}
+
+/// Why an operation wasn't started.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GuardError {
+    /// Another operation, named, is in progress on the board
+    Busy(&'static str),
+    /// The operation was started on the board too recently
+    TooSoon { retry_after: Duration },
+}
+
+/// Operations in progress and recently started, by board.
+///
+/// Cheap to clone; clones share the same state.
+#[derive(Debug, Clone, Default)]
+pub struct BoardGuards {
+    state: Arc<Mutex<GuardState>>,
+}
+
+#[derive(Debug, Default)]
+struct GuardState {
+    /// Operation in progress, by board ID
+    busy: HashMap<String, &'static str>,
+    /// When each kind of operation was last started, by board ID
+    started: HashMap<(String, &'static str), Instant>,
+}
+
+/// An operation in progress; the board is free again when it's dropped.
+#[derive(Debug)]
+pub struct InProgress {
+    state: Arc<Mutex<GuardState>>,
+    id: String,
+}
+
+impl BoardOperation {
+    pub const fn new(name: &'static str, min_interval: Duration) -> Self {
+        Self { name, min_interval }
+    }
+}
+
+impl BoardGuards {
+    /// Create guards with nothing in progress.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start `operation` on board `id`, if the board is free and the
+    /// operation wasn't started on it too recently.
+    pub fn begin(&self, id: &str, operation: BoardOperation) -> Result<InProgress, GuardError> {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        if let Some(&other) = state.busy.get(id) {
+            return Err(GuardError::Busy(other));
+        }
+
+        let key = (id.to_string(), operation.name);
+        if let Some(&last) = state.started.get(&key) {
+            let next = last + operation.min_interval;
+            if next > now {
+                return Err(GuardError::TooSoon {
+                    retry_after: next - now,
+                });
+            }
+        }
+
+        state.started.insert(key, now);
+        state.busy.insert(id.to_string(), operation.name);
+        Ok(InProgress {
+            state: self.state.clone(),
+            id: id.to_string(),
+        })
+    }
+}
+
+impl Drop for InProgress {
+    fn drop(&mut self) {
+        self.state.lock().unwrap().busy.remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RETUNE: BoardOperation = BoardOperation::new("retune", Duration::from_secs(2));
+    const IDENTIFY: BoardOperation = BoardOperation::new("identify", Duration::ZERO);
+
+    #[tokio::test(start_paused = true)]
+    async fn test_board_busy_until_operation_ends() {
+        let guards = BoardGuards::new();
+        let retune = guards.begin("a1", RETUNE).unwrap();
+
+        assert_eq!(
+            guards.begin("a1", IDENTIFY).unwrap_err(),
+            GuardError::Busy("retune")
+        );
+        // Other boards are unaffected
+        let _other = guards.begin("b2", IDENTIFY).unwrap();
+
+        drop(retune);
+        guards.begin("a1", IDENTIFY).unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_operation_limited_per_board() {
+        let guards = BoardGuards::new();
+        drop(guards.begin("a1", RETUNE).unwrap());
+
+        tokio::time::advance(Duration::from_millis(500)).await;
+        assert_eq!(
+            guards.begin("a1", RETUNE).unwrap_err(),
+            GuardError::TooSoon {
+                retry_after: Duration::from_millis(1500)
+            }
+        );
+        // The limit is per kind of operation
+        drop(guards.begin("a1", IDENTIFY).unwrap());
+
+        tokio::time::advance(Duration::from_millis(1500)).await;
+        guards.begin("a1", RETUNE).unwrap();
+    }
+}
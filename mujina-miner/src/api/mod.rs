@@ -5,17 +5,49 @@
 //! configuration, and real-time updates.
 //!
 //! The API binds to localhost only by default and does not require
-//! authentication for local access.
+//! authentication for local access. Failed requests return an [`ErrorBody`]
+//! with a machine-readable code; see [`ApiError`].
+//!
+//! Mining can be paused and resumed, wholly or by board, under `/mining`
+//! and `/boards/:id/pause`; the scheduler stops handing out work while the
+//! boards stay up.
+//!
+//! Requests that act on a board's hardware are refused with `board_busy`
+//! while another is in progress on the same board, and with `rate_limited`
+//! if the same operation was started on it too recently.
 
+mod confirm;
+mod error;
+mod guard;
 mod v1;
 
+pub use error::{ApiError, ErrorBody};
+
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
 use anyhow::Result;
 use axum::Router;
-use tokio::net::TcpListener;
+use tokio::{
+    net::TcpListener,
+    sync::{mpsc, watch},
+};
 use tokio_util::sync::CancellationToken;
 use tower_http::trace::{DefaultMakeSpan, DefaultOnResponse, TraceLayer};
 use tracing::{info, warn, Level};
 
+use crate::{
+    backplane::BackplaneCommand, config::Config, pools::PoolCommand, proxy::ProxyStats,
+    scheduler::Paused,
+};
+use confirm::ConfirmationTokens;
+use guard::BoardGuards;
+
 /// API server configuration.
 #[derive(Debug, Clone)]
 pub struct ApiConfig {
@@ -32,15 +64,126 @@ impl Default for ApiConfig {
     }
 }
 
+/// Handles through which API handlers act on the rest of the daemon.
+///
+/// Cheap to clone; each request handler receives its own copy.
+#[derive(Clone)]
+pub struct ApiState {
+    /// Commands to the backplane (board power control)
+    backplane_tx: mpsc::Sender<BackplaneCommand>,
+    /// Requests to the pool manager, when mining
+    pools_tx: Option<mpsc::Sender<PoolCommand>>,
+    /// Statistics of downstream miners, when serving them
+    proxy: Option<ProxyStats>,
+    /// What the scheduler has paused, when mining
+    pause_tx: Option<watch::Sender<Paused>>,
+    /// Config file the daemon was started from, if any
+    config_file: Option<ConfigFile>,
+    /// Daemon-wide shutdown token
+    shutdown: CancellationToken,
+    /// Set before cancelling `shutdown` when the daemon should restart
+    restart_requested: Arc<AtomicBool>,
+    /// Outstanding confirmation tokens for admin actions
+    confirmations: ConfirmationTokens,
+    /// Board operations in progress and recently started
+    guards: BoardGuards,
+}
+
+/// A config file and where to announce changes saved to it.
+#[derive(Clone)]
+struct ConfigFile {
+    path: PathBuf,
+    saved_tx: Arc<watch::Sender<Config>>,
+}
+
+impl ApiState {
+    /// Create API state wired to the daemon.
+    ///
+    /// The daemon reads `restart_requested` after `shutdown` is cancelled to
+    /// decide whether to exit or start over.
+    pub fn new(
+        backplane_tx: mpsc::Sender<BackplaneCommand>,
+        shutdown: CancellationToken,
+        restart_requested: Arc<AtomicBool>,
+    ) -> Self {
+        Self {
+            backplane_tx,
+            pools_tx: None,
+            proxy: None,
+            pause_tx: None,
+            config_file: None,
+            shutdown,
+            restart_requested,
+            confirmations: ConfirmationTokens::new(),
+            guards: BoardGuards::new(),
+        }
+    }
+
+    /// Serve the pool endpoints through this pool manager.
+    pub fn with_pools(mut self, pools_tx: mpsc::Sender<PoolCommand>) -> Self {
+        self.pools_tx = Some(pools_tx);
+        self
+    }
+
+    /// Serve the proxy endpoints from these statistics.
+    pub fn with_proxy(mut self, stats: ProxyStats) -> Self {
+        self.proxy = Some(stats);
+        self
+    }
+
+    /// Pause and resume mining through `pause_tx`, the scheduler's.
+    pub fn with_pause(mut self, pause_tx: watch::Sender<Paused>) -> Self {
+        self.pause_tx = Some(pause_tx);
+        self
+    }
+
+    /// Serve the config endpoints for the file at `path`.
+    ///
+    /// Configurations saved through the API are sent on `saved_tx` for the
+    /// daemon to apply.
+    pub fn with_config_file(mut self, path: PathBuf, saved_tx: watch::Sender<Config>) -> Self {
+        self.config_file = Some(ConfigFile {
+            path,
+            saved_tx: Arc::new(saved_tx),
+        });
+        self
+    }
+
+    /// Stop the daemon, optionally asking it to start again.
+    fn request_exit(&self, restart: bool) {
+        if restart {
+            self.restart_requested.store(true, Ordering::SeqCst);
+        }
+        self.shutdown.cancel();
+    }
+}
+
 /// Start the API server.
 ///
 /// This function starts the HTTP API server and runs until the provided
 /// cancellation token is triggered. It binds to localhost only by default for
 /// security.
-pub async fn serve(config: ApiConfig, shutdown: CancellationToken) -> Result<()> {
-    let app = build_router();
-
+pub async fn serve(config: ApiConfig, state: ApiState, shutdown: CancellationToken) -> Result<()> {
     let listener = TcpListener::bind(&config.bind_addr).await?;
+    run(listener, state, shutdown).await
+}
+
+/// Start the API server on an already-bound listener.
+///
+/// Used with socket activation, where the service manager owns the socket
+/// and `ApiConfig::bind_addr` does not apply. The listener must be in
+/// non-blocking mode.
+pub async fn serve_listener(
+    listener: std::net::TcpListener,
+    state: ApiState,
+    shutdown: CancellationToken,
+) -> Result<()> {
+    let listener = TcpListener::from_std(listener)?;
+    run(listener, state, shutdown).await
+}
+
+async fn run(listener: TcpListener, state: ApiState, shutdown: CancellationToken) -> Result<()> {
+    let app = build_router(state);
     let actual_addr = listener.local_addr()?;
 
     info!(url = %format!("http://{}", actual_addr), "API server listening.");
@@ -65,8 +208,8 @@ pub async fn serve(config: ApiConfig, shutdown: CancellationToken) -> Result<()>
 }
 
 /// Build the application router with all API routes.
-fn build_router() -> Router {
-    Router::new().nest("/api/v1", v1::routes()).layer(
+fn build_router(state: ApiState) -> Router {
+    Router::new().nest("/api/v1", v1::routes(state)).layer(
         TraceLayer::new_for_http()
             .make_span_with(DefaultMakeSpan::new().level(Level::INFO))
             .on_response(DefaultOnResponse::new().level(Level::INFO)),
@@ -1,11 +1,122 @@
 //! API version 1 endpoints.
 
+use std::collections::BTreeMap;
+use std::time::Duration;
+
 use axum::{
-    extract::Json,
-    routing::{get, post},
+    body::Bytes,
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        DefaultBodyLimit, Json, Path, Query, State,
+    },
+    http::StatusCode,
+    response::Response,
+    routing::{delete, get, post, put},
     Router,
 };
 use serde::{Deserialize, Serialize};
+use tokio::sync::{oneshot, watch};
+
+use super::{
+    confirm::TOKEN_LIFETIME,
+    error::ApiError,
+    guard::{BoardOperation, GuardError, InProgress},
+    ApiState,
+};
+use crate::{
+    asic::nonce_map::NonceMap,
+    backplane::{BackplaneCommand, BoardStatus},
+    board::{
+        history::HistoryEntry, identity::BoardIdentity, preset::Preset, supply::PowerLimited,
+        task::BoardHealth, OperatingPoint, TelemetrySnapshot,
+    },
+    config::{Config, PoolConfig},
+    firmware::{FirmwareImage, FirmwareProgress},
+    peripheral::scan::ScannedDevice,
+    pools::{PoolCommand, PoolId, PoolInfo},
+    proxy::DownstreamStats,
+    scheduler::Paused,
+    settings::BoardSettings,
+    stratum_v1::ShareRejectionCounts,
+    tracing::{self as logging, prelude::*, LogLevels},
+};
+
+/// How long to wait for the backplane to power down boards.
+const POWER_DOWN_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// How long to wait for a board to be retuned.
+const OPERATING_POINT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long to wait for a board's chips to be reset. Covers the reset
+/// pulse, baud rate escalation, and reprogramming every register.
+const CHIP_RESET_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// How long to wait for the pool manager. Switching pools only starts the
+/// new connection, so this covers a config file write at most.
+const POOL_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait for a board's nonce counts. They're kept in memory, so
+/// this only covers a board busy with another request.
+const NONCE_MAP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait for a board's history. The backplane answers from
+/// memory, so only a wedged event loop takes this long.
+const HISTORY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long to wait for a board's I2C bus to be scanned: a transaction for
+/// each of a hundred-odd addresses, through the board's controller.
+const I2C_SCAN_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// How long to wait for the backplane to list boards. It answers from
+/// cached state, so only a wedged event loop takes this long.
+const BOARD_LIST_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long to wait for a board to start signalling for identification.
+const IDENTIFY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait for a regulator to store its settings. Covers the
+/// regulator programming its NVM.
+const REGULATOR_STORE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait for one board to shut down: every shutdown stage's
+/// timeout, with room to spare.
+const BOARD_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long to wait for a board's identity to be read or written. A write
+/// is a few dozen EEPROM pages, each a round trip to the board.
+const IDENTITY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long to wait for a board's settings to be stored. Covers applying
+/// them to a running board, which may retune it.
+const SETTINGS_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long to wait for boards to be switched to a preset: each is retuned
+/// in turn.
+const PRESET_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long to wait for the backplane to start a firmware update. Covers
+/// stopping the board, which powers it down.
+const FIRMWARE_UPDATE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Board operations guarded against overlapping (see [`super::guard`]),
+/// and how often each may be started on one board.
+const RETUNE: BoardOperation = BoardOperation::new("retune", Duration::from_secs(1));
+const CHIP_RESET: BoardOperation = BoardOperation::new("chip reset", Duration::from_secs(10));
+const IDENTIFY: BoardOperation = BoardOperation::new("identify", Duration::from_secs(5));
+const BOARD_SHUTDOWN: BoardOperation = BoardOperation::new("shutdown", Duration::ZERO);
+const I2C_SCAN: BoardOperation = BoardOperation::new("I2C scan", Duration::from_secs(5));
+// NVM wears with every store
+const REGULATOR_STORE: BoardOperation =
+    BoardOperation::new("regulator store", Duration::from_secs(60));
+const IDENTITY_WRITE: BoardOperation =
+    BoardOperation::new("identity write", Duration::from_secs(5));
+const SETTINGS_WRITE: BoardOperation =
+    BoardOperation::new("settings write", Duration::from_secs(1));
+const FIRMWARE_UPDATE: BoardOperation =
+    BoardOperation::new("firmware update", Duration::from_secs(30));
+
+/// Largest firmware image accepted: the ESP32-S3's 16 MB of flash.
+const FIRMWARE_MAX_SIZE: usize = 16 * 1024 * 1024;
 
 /// Echo request payload.
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -21,11 +132,470 @@ pub struct EchoResponse {
     pub message: String,
 }
 
+/// Confirmation token issued for an admin action.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ConfirmationTokenResponse {
+    /// The action this token confirms.
+    pub action: String,
+    /// Token to send back in [`AdminActionRequest::confirm`].
+    pub token: String,
+    /// Seconds until the token expires.
+    pub expires_in_secs: u64,
+}
+
+/// Body of a confirmed admin action.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AdminActionRequest {
+    /// Token previously obtained from `/admin/{action}/token`.
+    pub confirm: String,
+}
+
+/// Result of an accepted admin action.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AdminActionResponse {
+    /// The action that was performed.
+    pub action: String,
+    /// Number of boards powered down, for actions that power down hardware.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub boards_powered_down: Option<usize>,
+}
+
+/// Identity a board stores about itself.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct IdentityResponse {
+    /// The stored identity; null if none was stored.
+    pub identity: Option<BoardIdentity>,
+}
+
+/// Body of a request to store a board's identity.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct IdentityWriteRequest {
+    /// Token previously obtained from `/admin/identity/token`.
+    pub confirm: String,
+    /// Identity to store, replacing any stored already.
+    pub identity: BoardIdentity,
+}
+
+/// A board's lifecycle events.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HistoryResponse {
+    /// Events oldest first, each with its Unix time in `at` and its kind
+    /// in `event`: "attached" (with `model`), "initialized", "failed"
+    /// (with `reason`), "reinitialized" (with `generation`), "reconnected",
+    /// or "detached" (with `reason`).
+    pub events: Vec<HistoryEntry>,
+}
+
+/// What's paused. Paused threads get no work; their boards stay up.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PauseResponse {
+    /// Whether all mining is paused, for either reason below
+    pub paused: bool,
+    /// Paused by the time-of-day schedule
+    pub schedule: bool,
+    /// Paused through the API or the config file
+    pub manual: bool,
+    /// Boards paused on their own, by ID
+    pub boards: Vec<String>,
+}
+
+/// Settings stored for a board.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SettingsResponse {
+    /// The stored settings; fields left out aren't set.
+    pub settings: BoardSettings,
+}
+
+/// Preset to switch boards to.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PresetRequest {
+    /// "eco", "balanced" or "turbo"
+    pub preset: Preset,
+}
+
+/// Boards switched to a preset.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PresetResponse {
+    pub preset: Preset,
+    /// IDs of the boards switched
+    pub boards: Vec<String>,
+}
+
+/// Where to write an uploaded firmware image, and the confirmation for it.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FirmwareUpdateQuery {
+    /// Flash address to write at; 0, the default, for a merged image.
+    #[serde(default)]
+    pub offset: u32,
+    /// Token previously obtained from `/admin/firmware/token`.
+    pub confirm: String,
+}
+
+/// A firmware update that has started.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FirmwareUpdateResponse {
+    /// Board being updated.
+    pub board: String,
+    /// Image size in bytes.
+    pub bytes: usize,
+    /// WebSocket to follow the update's progress on.
+    pub progress_url: String,
+}
+
+/// Result of a chip reset.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ChipResetResponse {
+    /// Number of chips that answered after the reset.
+    pub chips: usize,
+}
+
+/// Devices found on a board's I2C bus.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct I2cScanResponse {
+    /// Devices that answered, by address, with the part each was
+    /// recognized as.
+    pub devices: Vec<ScannedDevice>,
+}
+
+/// Nonces found per core of a board's chips.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NonceMapResponse {
+    /// Nonces counted across all cores.
+    pub total: u64,
+    /// Cores finding far fewer nonces than the rest, once there are enough
+    /// to tell.
+    pub weak_cores: Vec<u8>,
+    /// Nonces found, by core and then by small core within it.
+    pub cores: BTreeMap<u8, Vec<u64>>,
+}
+
+impl From<NonceMap> for NonceMapResponse {
+    fn from(map: NonceMap) -> Self {
+        Self {
+            total: map.total(),
+            weak_cores: map.weak_cores(),
+            cores: map.cores,
+        }
+    }
+}
+
+/// A board's health and latest sensor readings.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BoardResponse {
+    /// Board ID (serial number or virtual device ID).
+    pub id: String,
+    /// Board model.
+    pub model: String,
+    /// One of "starting", "running", "restarting", "waiting" (for a serial
+    /// port another process holds), "failed", "stopped".
+    pub health: String,
+    /// Consecutive restarts, while restarting.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restarts: Option<u32>,
+    /// Incarnation of the board: 1 when first created, and one more each
+    /// time it's recreated. Requests that reach a board recreated since
+    /// they were made fail with `board_restarted`.
+    pub generation: u64,
+    /// Why the board last failed, until it next comes up.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Seconds since the telemetry was read.
+    pub telemetry_age_secs: Option<f64>,
+    /// Latest sensor readings; absent until the board is up and polled.
+    pub telemetry: Option<TelemetrySnapshot>,
+    /// Present if the board's input sagged under load: its supply can't
+    /// keep up, and its core voltage may have been cut to match. Kept
+    /// across board restarts until the daemon restarts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub power_limited: Option<PowerLimited>,
+}
+
+impl From<BoardStatus> for BoardResponse {
+    fn from(status: BoardStatus) -> Self {
+        let (health, restarts) = match status.health {
+            BoardHealth::Starting => ("starting", None),
+            BoardHealth::Running => ("running", None),
+            BoardHealth::Restarting { restarts } => ("restarting", Some(restarts)),
+            BoardHealth::Waiting => ("waiting", None),
+            BoardHealth::Failed => ("failed", None),
+            BoardHealth::Stopped => ("stopped", None),
+        };
+        let telemetry_age_secs = status
+            .telemetry
+            .as_ref()
+            .map(|t| t.taken_at.elapsed().unwrap_or(Duration::ZERO).as_secs_f64());
+
+        Self {
+            id: status.id,
+            model: status.model,
+            health: health.into(),
+            restarts,
+            generation: status.generation,
+            error: status.error,
+            telemetry_age_secs,
+            telemetry: status.telemetry,
+            power_limited: status.power_limited,
+        }
+    }
+}
+
+/// A configured pool and its connection status.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PoolResponse {
+    /// Pool ID, stable until the daemon restarts.
+    pub id: PoolId,
+    /// Pool URL.
+    pub url: String,
+    /// Worker name.
+    pub worker: String,
+    /// Priority; the lowest is mined unless another pool was activated.
+    pub priority: u32,
+    /// Whether this pool is being mined.
+    pub active: bool,
+    /// Whether this pool was activated through the API, overriding priority.
+    pub forced: bool,
+    /// Whether the pool connection is up.
+    pub connected: bool,
+    /// Share difficulty set by the pool.
+    pub difficulty: Option<f64>,
+    /// Shares the pool accepted.
+    pub accepted: u64,
+    /// Shares the pool rejected.
+    pub rejected: u64,
+    /// The rejected shares, by reason.
+    #[serde(default)]
+    pub rejections: RejectionsResponse,
+    /// Pool time minus local time, in seconds, estimated from its jobs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clock_offset_secs: Option<i64>,
+}
+
+/// Rejected shares by the reason the pool gave.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct RejectionsResponse {
+    /// Found on a job the pool had moved past.
+    pub stale: u64,
+    /// Already submitted.
+    pub duplicate: u64,
+    /// Short of the share difficulty.
+    pub low_difficulty: u64,
+    /// For a job the pool didn't know.
+    pub job_not_found: u64,
+    /// Any other reason.
+    pub other: u64,
+}
+
+impl From<PoolInfo> for PoolResponse {
+    fn from(pool: PoolInfo) -> Self {
+        Self {
+            id: pool.id,
+            url: pool.url,
+            worker: pool.worker,
+            priority: pool.priority,
+            active: pool.active,
+            forced: pool.forced,
+            connected: pool.status.connected,
+            difficulty: pool.status.difficulty,
+            accepted: pool.status.accepted,
+            rejected: pool.status.rejected,
+            rejections: pool.status.rejections.into(),
+            clock_offset_secs: pool.status.clock_offset,
+        }
+    }
+}
+
+impl From<ShareRejectionCounts> for RejectionsResponse {
+    fn from(counts: ShareRejectionCounts) -> Self {
+        Self {
+            stale: counts.stale,
+            duplicate: counts.duplicate,
+            low_difficulty: counts.low_difficulty,
+            job_not_found: counts.job_not_found,
+            other: counts.other,
+        }
+    }
+}
+
+/// A downstream miner connected to the proxy.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DownstreamResponse {
+    /// Connection ID, stable while the miner stays connected.
+    pub id: u64,
+    /// Address the miner connected from.
+    pub address: String,
+    /// Worker name it authorized as.
+    pub worker: Option<String>,
+    /// User agent it subscribed with.
+    pub user_agent: Option<String>,
+    /// When it connected, in seconds since the Unix epoch.
+    pub connected_at: u64,
+    /// Share difficulty last sent to it.
+    pub difficulty: Option<f64>,
+    /// Hashrate estimated from its shares, in GH/s.
+    pub hashrate_ghs: f64,
+    /// Shares accepted and passed on to the pool.
+    pub accepted: u64,
+    /// Shares refused.
+    pub rejected: u64,
+}
+
+impl From<DownstreamStats> for DownstreamResponse {
+    fn from(stats: DownstreamStats) -> Self {
+        Self {
+            id: stats.id,
+            address: stats.address.to_string(),
+            worker: stats.worker,
+            user_agent: stats.user_agent,
+            connected_at: stats
+                .connected_at
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs()),
+            difficulty: stats.difficulty,
+            hashrate_ghs: stats.hashrate.0 as f64 / 1e9,
+            accepted: stats.accepted,
+            rejected: stats.rejected,
+        }
+    }
+}
+
+impl From<Paused> for PauseResponse {
+    fn from(paused: Paused) -> Self {
+        Self {
+            paused: paused.all(),
+            schedule: paused.schedule,
+            manual: paused.manual,
+            boards: paused.boards.into_iter().collect(),
+        }
+    }
+}
+
+/// Request to add a pool.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AddPoolRequest {
+    /// Pool URL, e.g. stratum+tcp://pool.example.com:3333.
+    pub url: String,
+    /// Worker name.
+    pub worker: String,
+    /// Password, if the pool needs one.
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Priority; lower is preferred. Defaults to 0.
+    #[serde(default)]
+    pub priority: u32,
+    /// Share rate to steer the pool's difficulty toward, if any.
+    #[serde(default)]
+    pub shares_per_minute: Option<f64>,
+    /// Keep a transcript of the messages exchanged with the pool.
+    #[serde(default)]
+    pub trace: bool,
+}
+
+/// Request to change a pool's priority.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SetPoolPriorityRequest {
+    /// New priority; lower is preferred.
+    pub priority: u32,
+}
+
+/// Query parameters of a config update.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ConfigUpdateQuery {
+    /// Validate and report what would change, without saving.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Outcome of a config update.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ConfigUpdateResponse {
+    /// Whether the configuration was saved (false for a dry run).
+    pub applied: bool,
+    /// The configuration as saved, with secrets redacted.
+    pub config: Config,
+    /// Changed settings applied to the running daemon.
+    pub reloaded: Vec<String>,
+    /// Changed settings that take effect after a restart.
+    pub restart_required: Vec<String>,
+}
+
+/// Runtime log levels.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LogLevelsResponse {
+    /// Level for targets without a more specific setting.
+    pub default_level: String,
+    /// Levels set at runtime, keyed by full module path.
+    pub modules: BTreeMap<String, String>,
+    /// Complete filter directives in effect (`RUST_LOG` syntax).
+    pub directives: String,
+}
+
+impl From<LogLevels> for LogLevelsResponse {
+    fn from(levels: LogLevels) -> Self {
+        Self {
+            default_level: levels.default_level,
+            modules: levels.modules,
+            directives: levels.directives,
+        }
+    }
+}
+
+/// Request to change a log level.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SetLogLevelRequest {
+    /// One of "off", "error", "warn", "info", "debug", "trace".
+    pub level: String,
+}
+
+/// Admin actions that require confirmation.
+const ADMIN_ACTIONS: &[&str] = &["restart", "shutdown", "firmware", "identity"];
+
 /// Build the v1 API routes.
-pub fn routes() -> Router {
+pub fn routes(state: ApiState) -> Router {
     Router::new()
         .route("/echo", post(echo))
         .route("/health", get(health))
+        .route("/boards", get(list_boards))
+        .route("/boards/:id", get(get_board))
+        .route("/boards/:id/operating-point", put(set_operating_point))
+        .route("/boards/:id/reset-chips", post(reset_chips))
+        .route("/boards/:id/nonce-map", get(get_nonce_map))
+        .route("/boards/:id/history", get(get_history))
+        .route("/boards/:id/pause", post(pause_board))
+        .route("/boards/:id/resume", post(resume_board))
+        .route("/boards/:id/identify", post(identify_board))
+        .route("/boards/:id/shutdown", post(shutdown_board))
+        .route("/boards/:id/regulator/store", post(store_regulator_config))
+        .route("/boards/:id/i2c/scan", post(scan_i2c))
+        .route("/boards/:id/identity", get(get_identity).put(set_identity))
+        .route("/boards/:id/settings", get(get_settings).put(set_settings))
+        .route("/boards/:id/preset", put(set_board_preset))
+        .route(
+            "/boards/:id/firmware",
+            post(update_firmware).layer(DefaultBodyLimit::max(FIRMWARE_MAX_SIZE)),
+        )
+        .route("/boards/:id/firmware/progress", get(firmware_progress))
+        .route("/pools", get(list_pools).post(add_pool))
+        .route("/pools/:id", delete(remove_pool))
+        .route("/pools/:id/priority", put(set_pool_priority))
+        .route("/pools/:id/activate", post(activate_pool))
+        .route("/proxy/downstreams", get(list_downstreams))
+        .route("/preset", put(set_preset))
+        .route("/mining", get(get_pause))
+        .route("/mining/pause", post(pause_mining))
+        .route("/mining/resume", post(resume_mining))
+        .route("/config", get(get_config).put(update_config))
+        .route("/admin/:action/token", post(issue_confirmation_token))
+        .route("/admin/restart", post(restart))
+        .route("/admin/shutdown", post(shutdown))
+        .route(
+            "/admin/log-level",
+            get(get_log_levels).put(set_default_log_level),
+        )
+        .route(
+            "/admin/log-level/:module",
+            put(set_module_log_level).delete(clear_module_log_level),
+        )
+        .with_state(state)
 }
 
 /// Echo endpoint handler.
@@ -43,3 +613,1961 @@ async fn echo(Json(req): Json<EchoRequest>) -> Json<EchoResponse> {
 async fn health() -> &'static str {
     "OK"
 }
+
+/// List boards with their cached telemetry.
+///
+/// Readings come from each board's periodic poll, so this never waits on
+/// hardware; `telemetry_age_secs` says how fresh they are.
+async fn list_boards(State(state): State<ApiState>) -> Result<Json<Vec<BoardResponse>>, ApiError> {
+    let boards = fetch_boards(&state).await?;
+    Ok(Json(boards.into_iter().map(Into::into).collect()))
+}
+
+/// Get one board with its cached telemetry.
+async fn get_board(
+    State(state): State<ApiState>,
+    Path(id): Path<String>,
+) -> Result<Json<BoardResponse>, ApiError> {
+    fetch_boards(&state)
+        .await?
+        .into_iter()
+        .find(|board| board.id == id)
+        .map(|board| Json(board.into()))
+        .ok_or(ApiError::BoardNotFound(id))
+}
+
+/// Retune one board.
+///
+/// Fields left out keep their current value. The voltage is checked against
+/// the range the board's regulator is configured for; a request outside it
+/// fails with `voltage_out_of_range`, whose details give the allowed range.
+///
+/// The change lasts until the board restarts; to keep it, store it with
+/// `/boards/:id/settings`.
+async fn set_operating_point(
+    State(state): State<ApiState>,
+    Path(id): Path<String>,
+    Json(point): Json<OperatingPoint>,
+) -> Result<StatusCode, ApiError> {
+    let _operation = begin_operation(&state, &id, RETUNE)?;
+    let (reply_tx, reply_rx) = oneshot::channel();
+    state
+        .backplane_tx
+        .send(BackplaneCommand::SetBoardOperatingPoint {
+            id: id.clone(),
+            point,
+            reply_tx,
+        })
+        .await
+        .map_err(|_| ApiError::BackplaneUnavailable)?;
+
+    match tokio::time::timeout(OPERATING_POINT_TIMEOUT, reply_rx).await {
+        Ok(Ok(Some(result))) => {
+            result?;
+            info!(board = %id, %point, "Board retuned via API.");
+            Ok(StatusCode::NO_CONTENT)
+        }
+        Ok(Ok(None)) => Err(ApiError::BoardNotFound(id)),
+        Ok(Err(_)) => Err(ApiError::Internal(
+            "backplane dropped the retune request".into(),
+        )),
+        Err(_) => Err(ApiError::BackplaneTimeout {
+            operation: "retune board",
+        }),
+    }
+}
+
+/// Reset a board's chips without reinitializing the board.
+///
+/// For chips that have locked up; the board keeps its power and its hash
+/// threads, and resumes the job it was working on.
+async fn reset_chips(
+    State(state): State<ApiState>,
+    Path(id): Path<String>,
+) -> Result<Json<ChipResetResponse>, ApiError> {
+    let _operation = begin_operation(&state, &id, CHIP_RESET)?;
+    let (reply_tx, reply_rx) = oneshot::channel();
+    state
+        .backplane_tx
+        .send(BackplaneCommand::ResetBoardChips {
+            id: id.clone(),
+            reply_tx,
+        })
+        .await
+        .map_err(|_| ApiError::BackplaneUnavailable)?;
+
+    match tokio::time::timeout(CHIP_RESET_TIMEOUT, reply_rx).await {
+        Ok(Ok(Some(result))) => {
+            let chips = result?;
+            info!(board = %id, chips, "Board chips reset via API.");
+            Ok(Json(ChipResetResponse { chips }))
+        }
+        Ok(Ok(None)) => Err(ApiError::BoardNotFound(id)),
+        Ok(Err(_)) => Err(ApiError::Internal(
+            "backplane dropped the chip reset request".into(),
+        )),
+        Err(_) => Err(ApiError::BackplaneTimeout {
+            operation: "reset board chips",
+        }),
+    }
+}
+
+/// Make a board signal so it can be picked out among others; a Bitaxe
+/// pulses its fan for a few seconds.
+async fn identify_board(
+    State(state): State<ApiState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let _operation = begin_operation(&state, &id, IDENTIFY)?;
+    let (reply_tx, reply_rx) = oneshot::channel();
+    state
+        .backplane_tx
+        .send(BackplaneCommand::IdentifyBoard {
+            id: id.clone(),
+            reply_tx,
+        })
+        .await
+        .map_err(|_| ApiError::BackplaneUnavailable)?;
+
+    match tokio::time::timeout(IDENTIFY_TIMEOUT, reply_rx).await {
+        Ok(Ok(Some(result))) => {
+            result?;
+            Ok(StatusCode::NO_CONTENT)
+        }
+        Ok(Ok(None)) => Err(ApiError::BoardNotFound(id)),
+        Ok(Err(_)) => Err(ApiError::Internal(
+            "backplane dropped the identify request".into(),
+        )),
+        Err(_) => Err(ApiError::BackplaneTimeout {
+            operation: "identify board",
+        }),
+    }
+}
+
+/// Store a board's regulator settings, including a voltage set through
+/// `/boards/:id/operating-point`, in the regulator's NVM, so it powers up
+/// with them.
+///
+/// Stores wear the NVM, so each board takes at most one a minute.
+async fn store_regulator_config(
+    State(state): State<ApiState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let _operation = begin_operation(&state, &id, REGULATOR_STORE)?;
+    let (reply_tx, reply_rx) = oneshot::channel();
+    state
+        .backplane_tx
+        .send(BackplaneCommand::StoreBoardRegulatorConfig {
+            id: id.clone(),
+            reply_tx,
+        })
+        .await
+        .map_err(|_| ApiError::BackplaneUnavailable)?;
+
+    match tokio::time::timeout(REGULATOR_STORE_TIMEOUT, reply_rx).await {
+        Ok(Ok(Some(result))) => {
+            result?;
+            info!(board = %id, "Regulator settings stored via API.");
+            Ok(StatusCode::NO_CONTENT)
+        }
+        Ok(Ok(None)) => Err(ApiError::BoardNotFound(id)),
+        Ok(Err(_)) => Err(ApiError::Internal(
+            "backplane dropped the regulator store request".into(),
+        )),
+        Err(_) => Err(ApiError::BackplaneTimeout {
+            operation: "store regulator settings",
+        }),
+    }
+}
+
+/// Power down one board: chips parked, core voltage off, fans slowed.
+///
+/// The board is removed until it's next plugged in or the daemon restarts;
+/// the other boards keep mining.
+async fn shutdown_board(
+    State(state): State<ApiState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let _operation = begin_operation(&state, &id, BOARD_SHUTDOWN)?;
+    let (reply_tx, reply_rx) = oneshot::channel();
+    state
+        .backplane_tx
+        .send(BackplaneCommand::ShutdownBoard {
+            id: id.clone(),
+            reply_tx,
+        })
+        .await
+        .map_err(|_| ApiError::BackplaneUnavailable)?;
+
+    match tokio::time::timeout(BOARD_SHUTDOWN_TIMEOUT, reply_rx).await {
+        Ok(Ok(Some(result))) => {
+            result?;
+            info!(board = %id, "Board shut down via API.");
+            Ok(StatusCode::NO_CONTENT)
+        }
+        Ok(Ok(None)) => Err(ApiError::BoardNotFound(id)),
+        Ok(Err(_)) => Err(ApiError::Internal(
+            "backplane dropped the shutdown request".into(),
+        )),
+        Err(_) => Err(ApiError::BackplaneTimeout {
+            operation: "shut down board",
+        }),
+    }
+}
+
+/// Nonces a board's chips have found per core.
+///
+/// A heatmap of the chips: a core with none is dead, and one listed in
+/// `weak_cores` is marginal at the current operating point.
+async fn get_nonce_map(
+    State(state): State<ApiState>,
+    Path(id): Path<String>,
+) -> Result<Json<NonceMapResponse>, ApiError> {
+    let (reply_tx, reply_rx) = oneshot::channel();
+    state
+        .backplane_tx
+        .send(BackplaneCommand::ReadNonceMap {
+            id: id.clone(),
+            reply_tx,
+        })
+        .await
+        .map_err(|_| ApiError::BackplaneUnavailable)?;
+
+    match tokio::time::timeout(NONCE_MAP_TIMEOUT, reply_rx).await {
+        Ok(Ok(Some(result))) => Ok(Json(result?.into())),
+        Ok(Ok(None)) => Err(ApiError::BoardNotFound(id)),
+        Ok(Err(_)) => Err(ApiError::Internal(
+            "backplane dropped the nonce map request".into(),
+        )),
+        Err(_) => Err(ApiError::BackplaneTimeout {
+            operation: "read nonce map",
+        }),
+    }
+}
+
+/// A board's attach, detach, start and failure events, kept across
+/// restarts, for diagnosing intermittent USB or power problems.
+///
+/// Answers for a board that isn't attached if it ever was.
+async fn get_history(
+    State(state): State<ApiState>,
+    Path(id): Path<String>,
+) -> Result<Json<HistoryResponse>, ApiError> {
+    let (reply_tx, reply_rx) = oneshot::channel();
+    state
+        .backplane_tx
+        .send(BackplaneCommand::ReadBoardHistory {
+            id: id.clone(),
+            reply_tx,
+        })
+        .await
+        .map_err(|_| ApiError::BackplaneUnavailable)?;
+
+    match tokio::time::timeout(HISTORY_TIMEOUT, reply_rx).await {
+        Ok(Ok(Some(events))) => Ok(Json(HistoryResponse { events })),
+        Ok(Ok(None)) => Err(ApiError::BoardNotFound(id)),
+        Ok(Err(_)) => Err(ApiError::Internal(
+            "backplane dropped the history request".into(),
+        )),
+        Err(_) => Err(ApiError::BackplaneTimeout {
+            operation: "read board history",
+        }),
+    }
+}
+
+/// Scan a board's I2C bus and identify the parts on it, for bringing up
+/// board revisions whose parts aren't known.
+///
+/// Unknown devices are only read from; known parts are recognized by their
+/// ID registers, and EEPROMs by their address.
+async fn scan_i2c(
+    State(state): State<ApiState>,
+    Path(id): Path<String>,
+) -> Result<Json<I2cScanResponse>, ApiError> {
+    let _operation = begin_operation(&state, &id, I2C_SCAN)?;
+    let (reply_tx, reply_rx) = oneshot::channel();
+    state
+        .backplane_tx
+        .send(BackplaneCommand::ScanBoardI2c {
+            id: id.clone(),
+            reply_tx,
+        })
+        .await
+        .map_err(|_| ApiError::BackplaneUnavailable)?;
+
+    match tokio::time::timeout(I2C_SCAN_TIMEOUT, reply_rx).await {
+        Ok(Ok(Some(result))) => Ok(Json(I2cScanResponse { devices: result? })),
+        Ok(Ok(None)) => Err(ApiError::BoardNotFound(id)),
+        Ok(Err(_)) => Err(ApiError::Internal(
+            "backplane dropped the I2C scan request".into(),
+        )),
+        Err(_) => Err(ApiError::BackplaneTimeout {
+            operation: "scan I2C bus",
+        }),
+    }
+}
+
+/// Read the identity a board stores about itself: model, revision, serial
+/// number and calibration values.
+///
+/// Boards with nowhere to store one fail with `board_control_failed`.
+async fn get_identity(
+    State(state): State<ApiState>,
+    Path(id): Path<String>,
+) -> Result<Json<IdentityResponse>, ApiError> {
+    let (reply_tx, reply_rx) = oneshot::channel();
+    state
+        .backplane_tx
+        .send(BackplaneCommand::ReadBoardIdentity {
+            id: id.clone(),
+            reply_tx,
+        })
+        .await
+        .map_err(|_| ApiError::BackplaneUnavailable)?;
+
+    match tokio::time::timeout(IDENTITY_TIMEOUT, reply_rx).await {
+        Ok(Ok(Some(result))) => Ok(Json(IdentityResponse { identity: result? })),
+        Ok(Ok(None)) => Err(ApiError::BoardNotFound(id)),
+        Ok(Err(_)) => Err(ApiError::Internal(
+            "backplane dropped the identity request".into(),
+        )),
+        Err(_) => Err(ApiError::BackplaneTimeout {
+            operation: "read board identity",
+        }),
+    }
+}
+
+/// Store a board's identity, replacing any stored already.
+///
+/// Needs a confirmation token for the `identity` action, as the record
+/// decides which board type the board is created as from then on. The
+/// board keeps running as it is; the new identity is matched against board
+/// descriptors the next time it's created.
+async fn set_identity(
+    State(state): State<ApiState>,
+    Path(id): Path<String>,
+    Json(req): Json<IdentityWriteRequest>,
+) -> Result<StatusCode, ApiError> {
+    check_confirmation(&state, "identity", &req.confirm)?;
+    req.identity
+        .validate()
+        .map_err(|e| ApiError::InvalidIdentity(e.to_string()))?;
+    let _operation = begin_operation(&state, &id, IDENTITY_WRITE)?;
+
+    let model = req.identity.model.clone();
+    let (reply_tx, reply_rx) = oneshot::channel();
+    state
+        .backplane_tx
+        .send(BackplaneCommand::WriteBoardIdentity {
+            id: id.clone(),
+            identity: req.identity,
+            reply_tx,
+        })
+        .await
+        .map_err(|_| ApiError::BackplaneUnavailable)?;
+
+    match tokio::time::timeout(IDENTITY_TIMEOUT, reply_rx).await {
+        Ok(Ok(Some(result))) => {
+            result?;
+            info!(board = %id, %model, "Board identity stored via API.");
+            Ok(StatusCode::NO_CONTENT)
+        }
+        Ok(Ok(None)) => Err(ApiError::BoardNotFound(id)),
+        Ok(Err(_)) => Err(ApiError::Internal(
+            "backplane dropped the identity write".into(),
+        )),
+        Err(_) => Err(ApiError::BackplaneTimeout {
+            operation: "write board identity",
+        }),
+    }
+}
+
+/// Settings stored for a board, applied each time it starts.
+///
+/// Answers for a board that isn't attached if settings are stored for it.
+async fn get_settings(
+    State(state): State<ApiState>,
+    Path(id): Path<String>,
+) -> Result<Json<SettingsResponse>, ApiError> {
+    let (reply_tx, reply_rx) = oneshot::channel();
+    state
+        .backplane_tx
+        .send(BackplaneCommand::ReadBoardSettings {
+            id: id.clone(),
+            reply_tx,
+        })
+        .await
+        .map_err(|_| ApiError::BackplaneUnavailable)?;
+
+    match tokio::time::timeout(SETTINGS_TIMEOUT, reply_rx).await {
+        Ok(Ok(Some(settings))) => Ok(Json(SettingsResponse { settings })),
+        Ok(Ok(None)) => Err(ApiError::BoardNotFound(id)),
+        Ok(Err(_)) => Err(ApiError::Internal(
+            "backplane dropped the settings request".into(),
+        )),
+        Err(_) => Err(ApiError::BackplaneTimeout {
+            operation: "read board settings",
+        }),
+    }
+}
+
+/// Replace the settings stored for a board.
+///
+/// The body is the whole set of settings; fields left out are cleared. A
+/// running board is retuned to them at once, and settings it refuses (e.g.,
+/// a voltage outside its range) aren't stored. A cleared override stays in
+/// effect until the board next starts.
+async fn set_settings(
+    State(state): State<ApiState>,
+    Path(id): Path<String>,
+    Json(settings): Json<BoardSettings>,
+) -> Result<StatusCode, ApiError> {
+    settings.validate().map_err(ApiError::InvalidSettings)?;
+    let _operation = begin_operation(&state, &id, SETTINGS_WRITE)?;
+
+    let (reply_tx, reply_rx) = oneshot::channel();
+    state
+        .backplane_tx
+        .send(BackplaneCommand::WriteBoardSettings {
+            id: id.clone(),
+            settings,
+            reply_tx,
+        })
+        .await
+        .map_err(|_| ApiError::BackplaneUnavailable)?;
+
+    match tokio::time::timeout(SETTINGS_TIMEOUT, reply_rx).await {
+        Ok(Ok(Some(result))) => {
+            result?;
+            info!(board = %id, "Board settings stored via API.");
+            Ok(StatusCode::NO_CONTENT)
+        }
+        Ok(Ok(None)) => Err(ApiError::BoardNotFound(id)),
+        Ok(Err(_)) => Err(ApiError::Internal(
+            "backplane dropped the settings write".into(),
+        )),
+        Err(_) => Err(ApiError::BackplaneTimeout {
+            operation: "write board settings",
+        }),
+    }
+}
+
+/// Switch every board to a preset.
+///
+/// The preset's values are stored as each board's settings and applied at
+/// once. If it isn't defined for one of the boards' models, none are
+/// switched.
+async fn set_preset(
+    State(state): State<ApiState>,
+    Json(request): Json<PresetRequest>,
+) -> Result<Json<PresetResponse>, ApiError> {
+    apply_preset(&state, None, request.preset).await
+}
+
+/// Switch one board to a preset.
+async fn set_board_preset(
+    State(state): State<ApiState>,
+    Path(id): Path<String>,
+    Json(request): Json<PresetRequest>,
+) -> Result<Json<PresetResponse>, ApiError> {
+    let _operation = begin_operation(&state, &id, SETTINGS_WRITE)?;
+    apply_preset(&state, Some(id), request.preset).await
+}
+
+/// Ask the backplane to switch boards to `preset`.
+async fn apply_preset(
+    state: &ApiState,
+    id: Option<String>,
+    preset: Preset,
+) -> Result<Json<PresetResponse>, ApiError> {
+    let (reply_tx, reply_rx) = oneshot::channel();
+    state
+        .backplane_tx
+        .send(BackplaneCommand::ApplyPreset {
+            id,
+            preset,
+            reply_tx,
+        })
+        .await
+        .map_err(|_| ApiError::BackplaneUnavailable)?;
+
+    match tokio::time::timeout(PRESET_TIMEOUT, reply_rx).await {
+        Ok(Ok(result)) => {
+            let boards = result?;
+            info!(%preset, boards = boards.len(), "Preset applied via API.");
+            Ok(Json(PresetResponse { preset, boards }))
+        }
+        Ok(Err(_)) => Err(ApiError::Internal(
+            "backplane dropped the preset request".into(),
+        )),
+        Err(_) => Err(ApiError::BackplaneTimeout {
+            operation: "apply preset",
+        }),
+    }
+}
+
+/// Write new firmware to a board's management controller.
+///
+/// The body is the raw image, as esptool would write it, and the query gives
+/// its flash offset and a confirmation token for the `firmware` action. The
+/// board stops mining for the update and comes back on its own once the
+/// controller restarts; follow along on `progress_url`.
+async fn update_firmware(
+    State(state): State<ApiState>,
+    Path(id): Path<String>,
+    Query(query): Query<FirmwareUpdateQuery>,
+    data: Bytes,
+) -> Result<(StatusCode, Json<FirmwareUpdateResponse>), ApiError> {
+    check_confirmation(&state, "firmware", &query.confirm)?;
+    let _operation = begin_operation(&state, &id, FIRMWARE_UPDATE)?;
+
+    let bytes = data.len();
+    let image = FirmwareImage {
+        offset: query.offset,
+        data,
+    };
+    let (reply_tx, reply_rx) = oneshot::channel();
+    state
+        .backplane_tx
+        .send(BackplaneCommand::UpdateFirmware {
+            id: id.clone(),
+            image,
+            reply_tx,
+        })
+        .await
+        .map_err(|_| ApiError::BackplaneUnavailable)?;
+
+    match tokio::time::timeout(FIRMWARE_UPDATE_TIMEOUT, reply_rx).await {
+        Ok(Ok(Some(result))) => {
+            result?;
+            info!(board = %id, bytes, offset = query.offset, "Firmware update started via API.");
+            Ok((
+                StatusCode::ACCEPTED,
+                Json(FirmwareUpdateResponse {
+                    progress_url: format!("/api/v1/boards/{id}/firmware/progress"),
+                    board: id,
+                    bytes,
+                }),
+            ))
+        }
+        Ok(Ok(None)) => Err(ApiError::BoardNotFound(id)),
+        Ok(Err(_)) => Err(ApiError::Internal(
+            "backplane dropped the firmware update request".into(),
+        )),
+        Err(_) => Err(ApiError::BackplaneTimeout {
+            operation: "start firmware update",
+        }),
+    }
+}
+
+/// Follow a board's latest firmware update over a WebSocket.
+///
+/// Sends the update's [`FirmwareProgress`] as a JSON text message each time
+/// it changes, and closes once the update is done or has failed.
+async fn firmware_progress(
+    State(state): State<ApiState>,
+    Path(id): Path<String>,
+    ws: WebSocketUpgrade,
+) -> Result<Response, ApiError> {
+    let (reply_tx, reply_rx) = oneshot::channel();
+    state
+        .backplane_tx
+        .send(BackplaneCommand::FirmwareProgress {
+            id: id.clone(),
+            reply_tx,
+        })
+        .await
+        .map_err(|_| ApiError::BackplaneUnavailable)?;
+
+    let progress = match tokio::time::timeout(BOARD_LIST_TIMEOUT, reply_rx).await {
+        Ok(Ok(Some(progress))) => progress,
+        Ok(Ok(None)) => return Err(ApiError::FirmwareUpdateNotFound(id)),
+        Ok(Err(_)) => {
+            return Err(ApiError::Internal(
+                "backplane dropped the firmware progress request".into(),
+            ))
+        }
+        Err(_) => {
+            return Err(ApiError::BackplaneTimeout {
+                operation: "look up firmware update",
+            })
+        }
+    };
+    Ok(ws.on_upgrade(move |socket| stream_progress(socket, progress)))
+}
+
+/// Send each change of `progress` until the update finishes or the client
+/// goes away.
+async fn stream_progress(mut socket: WebSocket, mut progress: watch::Receiver<FirmwareProgress>) {
+    loop {
+        let current = progress.borrow_and_update().clone();
+        let json = match serde_json::to_string(&current) {
+            Ok(json) => json,
+            Err(e) => {
+                warn!(error = %e, "Failed to encode firmware progress");
+                break;
+            }
+        };
+        if socket.send(Message::Text(json)).await.is_err() || current.is_finished() {
+            break;
+        }
+        // A dropped sender means the update task is gone; its last state was
+        // sent above
+        if progress.changed().await.is_err() {
+            break;
+        }
+    }
+    let _ = socket.send(Message::Close(None)).await;
+}
+
+/// Ask the backplane for every board's status.
+async fn fetch_boards(state: &ApiState) -> Result<Vec<BoardStatus>, ApiError> {
+    let (reply_tx, reply_rx) = oneshot::channel();
+    state
+        .backplane_tx
+        .send(BackplaneCommand::ListBoards { reply_tx })
+        .await
+        .map_err(|_| ApiError::BackplaneUnavailable)?;
+
+    match tokio::time::timeout(BOARD_LIST_TIMEOUT, reply_rx).await {
+        Ok(Ok(boards)) => Ok(boards),
+        Ok(Err(_)) => Err(ApiError::Internal(
+            "backplane dropped the board list request".into(),
+        )),
+        Err(_) => Err(ApiError::BackplaneTimeout {
+            operation: "list boards",
+        }),
+    }
+}
+
+/// List pools in priority order with their connection status.
+async fn list_pools(State(state): State<ApiState>) -> Result<Json<Vec<PoolResponse>>, ApiError> {
+    let pools = pool_request(&state, |reply_tx| PoolCommand::List { reply_tx }).await?;
+    Ok(Json(pools.into_iter().map(Into::into).collect()))
+}
+
+/// Add a pool, saving it to the config file.
+///
+/// The new pool is mined right away if its priority beats the current one.
+async fn add_pool(
+    State(state): State<ApiState>,
+    Json(req): Json<AddPoolRequest>,
+) -> Result<(StatusCode, Json<PoolResponse>), ApiError> {
+    let pool = PoolConfig {
+        url: req.url,
+        worker: req.worker,
+        password: req.password,
+        priority: req.priority,
+        shares_per_minute: req.shares_per_minute,
+        trace: req.trace,
+    };
+    let pool = pool_request(&state, |reply_tx| PoolCommand::Add { pool, reply_tx }).await??;
+    Ok((StatusCode::CREATED, Json(pool.into())))
+}
+
+/// Remove a pool, saving the change to the config file.
+///
+/// Removing the pool being mined switches to the next by priority.
+async fn remove_pool(
+    State(state): State<ApiState>,
+    Path(id): Path<PoolId>,
+) -> Result<StatusCode, ApiError> {
+    pool_request(&state, |reply_tx| PoolCommand::Remove { id, reply_tx }).await??;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Change a pool's priority, saving it to the config file.
+///
+/// Hands the choice of pool back to priority order if a pool was activated.
+async fn set_pool_priority(
+    State(state): State<ApiState>,
+    Path(id): Path<PoolId>,
+    Json(req): Json<SetPoolPriorityRequest>,
+) -> Result<Json<PoolResponse>, ApiError> {
+    let priority = req.priority;
+    let pool = pool_request(&state, |reply_tx| PoolCommand::SetPriority {
+        id,
+        priority,
+        reply_tx,
+    })
+    .await??;
+    Ok(Json(pool.into()))
+}
+
+/// Switch to a pool regardless of priority.
+///
+/// The choice lasts until the pool is removed, a priority changes, or the
+/// daemon restarts; it isn't saved.
+async fn activate_pool(
+    State(state): State<ApiState>,
+    Path(id): Path<PoolId>,
+) -> Result<Json<PoolResponse>, ApiError> {
+    let pool = pool_request(&state, |reply_tx| PoolCommand::Activate { id, reply_tx }).await??;
+    Ok(Json(pool.into()))
+}
+
+/// List the downstream miners connected to the proxy.
+async fn list_downstreams(
+    State(state): State<ApiState>,
+) -> Result<Json<Vec<DownstreamResponse>>, ApiError> {
+    let stats = state.proxy.as_ref().ok_or(ApiError::ProxyUnavailable)?;
+    Ok(Json(stats.snapshot().into_iter().map(Into::into).collect()))
+}
+
+/// What's paused.
+async fn get_pause(State(state): State<ApiState>) -> Result<Json<PauseResponse>, ApiError> {
+    let pause_tx = state.pause_tx.as_ref().ok_or(ApiError::MiningUnavailable)?;
+    Ok(Json(pause_tx.borrow().clone().into()))
+}
+
+/// Stop handing out work, keeping the boards up and the pools connected.
+/// Lasts until resumed or the daemon restarts; `daemon.paused` in the
+/// config starts the daemon paused.
+async fn pause_mining(State(state): State<ApiState>) -> Result<Json<PauseResponse>, ApiError> {
+    set_paused(&state, |paused| paused.manual = true)
+}
+
+/// Undo [`pause_mining`]. Mining stays paused while the schedule says so.
+async fn resume_mining(State(state): State<ApiState>) -> Result<Json<PauseResponse>, ApiError> {
+    set_paused(&state, |paused| paused.manual = false)
+}
+
+/// Stop handing one board work, keeping it up with its fans running.
+async fn pause_board(
+    State(state): State<ApiState>,
+    Path(id): Path<String>,
+) -> Result<Json<PauseResponse>, ApiError> {
+    if !fetch_boards(&state)
+        .await?
+        .iter()
+        .any(|board| board.id == id)
+    {
+        return Err(ApiError::BoardNotFound(id));
+    }
+    set_paused(&state, |paused| {
+        paused.boards.insert(id);
+    })
+}
+
+/// Undo [`pause_board`]. Works on a board that's since gone, so it isn't
+/// paused when it comes back.
+async fn resume_board(
+    State(state): State<ApiState>,
+    Path(id): Path<String>,
+) -> Result<Json<PauseResponse>, ApiError> {
+    let listed = state
+        .pause_tx
+        .as_ref()
+        .is_some_and(|pause_tx| pause_tx.borrow().boards.contains(&id));
+    if !listed
+        && !fetch_boards(&state)
+            .await?
+            .iter()
+            .any(|board| board.id == id)
+    {
+        return Err(ApiError::BoardNotFound(id));
+    }
+    set_paused(&state, |paused| {
+        paused.boards.remove(&id);
+    })
+}
+
+/// Change what the scheduler has paused, answering with the result.
+fn set_paused(
+    state: &ApiState,
+    change: impl FnOnce(&mut Paused),
+) -> Result<Json<PauseResponse>, ApiError> {
+    let pause_tx = state.pause_tx.as_ref().ok_or(ApiError::MiningUnavailable)?;
+    pause_tx.send_modify(change);
+    Ok(Json(pause_tx.borrow().clone().into()))
+}
+
+/// Send a request to the pool manager and wait for its reply.
+async fn pool_request<T>(
+    state: &ApiState,
+    command: impl FnOnce(oneshot::Sender<T>) -> PoolCommand,
+) -> Result<T, ApiError> {
+    let pools_tx = state.pools_tx.as_ref().ok_or(ApiError::PoolsUnavailable)?;
+    let (reply_tx, reply_rx) = oneshot::channel();
+    pools_tx
+        .send(command(reply_tx))
+        .await
+        .map_err(|_| ApiError::PoolsUnavailable)?;
+
+    match tokio::time::timeout(POOL_REQUEST_TIMEOUT, reply_rx).await {
+        Ok(Ok(reply)) => Ok(reply),
+        Ok(Err(_)) => Err(ApiError::PoolsUnavailable),
+        Err(_) => Err(ApiError::Internal(
+            "timed out waiting for the pool manager".into(),
+        )),
+    }
+}
+
+/// Get the configuration from the daemon's config file, secrets redacted.
+async fn get_config(State(state): State<ApiState>) -> Result<Json<Config>, ApiError> {
+    let config_file = state.config_file.as_ref().ok_or(ApiError::NoConfigFile)?;
+    let config = load_config(&config_file.path)?;
+    Ok(Json(config.redacted()))
+}
+
+/// Replace the configuration.
+///
+/// The new configuration is validated as a whole; every problem is listed in
+/// the error details. Secrets left redacted keep their current values. With
+/// `?dry_run=true`, nothing is saved and the response says what would
+/// change. Otherwise the settings that changed are saved to the file, its
+/// comments kept, and the daemon applies what it can while running; the
+/// rest is listed as needing a restart.
+async fn update_config(
+    State(state): State<ApiState>,
+    Query(query): Query<ConfigUpdateQuery>,
+    Json(mut config): Json<Config>,
+) -> Result<Json<ConfigUpdateResponse>, ApiError> {
+    let config_file = state.config_file.as_ref().ok_or(ApiError::NoConfigFile)?;
+    let current = load_config(&config_file.path)?;
+    config.restore_secrets(&current);
+    config.validate()?;
+
+    let response = ConfigUpdateResponse {
+        applied: !query.dry_run,
+        config: config.redacted(),
+        reloaded: strings(current.reloadable_changes(&config)),
+        restart_required: strings(current.restart_required_changes(&config)),
+    };
+    if query.dry_run {
+        return Ok(Json(response));
+    }
+
+    config
+        .save_to(&config_file.path)
+        .map_err(|e| ApiError::ConfigSave(format!("{:#}", e)))?;
+    info!(
+        reloaded = ?response.reloaded,
+        restart_required = ?response.restart_required,
+        "Config updated via API."
+    );
+    config_file.saved_tx.send_replace(config);
+
+    Ok(Json(response))
+}
+
+fn load_config(path: &std::path::Path) -> Result<Config, ApiError> {
+    Config::load_from(path).map_err(|e| ApiError::Internal(format!("{:#}", e)))
+}
+
+fn strings(names: Vec<&'static str>) -> Vec<String> {
+    names.into_iter().map(String::from).collect()
+}
+
+/// Issue a confirmation token for an admin action.
+///
+/// The token must be presented to the action's endpoint within
+/// [`TOKEN_LIFETIME`].
+async fn issue_confirmation_token(
+    State(state): State<ApiState>,
+    Path(action): Path<String>,
+) -> Result<Json<ConfirmationTokenResponse>, ApiError> {
+    if !ADMIN_ACTIONS.contains(&action.as_str()) {
+        return Err(ApiError::UnknownAction(action));
+    }
+
+    let token = state.confirmations.issue(&action);
+    debug!(action = %action, "Issued admin confirmation token");
+
+    Ok(Json(ConfirmationTokenResponse {
+        action,
+        token,
+        expires_in_secs: TOKEN_LIFETIME.as_secs(),
+    }))
+}
+
+/// Start a guarded operation on board `id`; it ends when the result is
+/// dropped.
+fn begin_operation(
+    state: &ApiState,
+    id: &str,
+    operation: BoardOperation,
+) -> Result<InProgress, ApiError> {
+    state.guards.begin(id, operation).map_err(|e| {
+        debug!(board = %id, operation = operation.name, error = ?e, "Board operation refused");
+        match e {
+            GuardError::Busy(other) => ApiError::BoardBusy {
+                id: id.to_string(),
+                operation: other,
+            },
+            GuardError::TooSoon { retry_after } => ApiError::RateLimited {
+                operation: operation.name,
+                retry_after,
+            },
+        }
+    })
+}
+
+/// Check the confirmation token for `action`, consuming it.
+fn check_confirmation(state: &ApiState, action: &str, token: &str) -> Result<(), ApiError> {
+    if state.confirmations.consume(action, token) {
+        Ok(())
+    } else {
+        warn!(
+            action,
+            "Rejected admin action with invalid confirmation token"
+        );
+        Err(ApiError::InvalidConfirmation {
+            action: action.to_string(),
+        })
+    }
+}
+
+/// Restart the mining service.
+///
+/// Shuts the daemon down in the usual order (boards powered down, pool
+/// connections closed) and then starts it again in-process.
+async fn restart(
+    State(state): State<ApiState>,
+    Json(req): Json<AdminActionRequest>,
+) -> Result<(StatusCode, Json<AdminActionResponse>), ApiError> {
+    check_confirmation(&state, "restart", &req.confirm)?;
+
+    info!("Restart requested via API.");
+    state.request_exit(true);
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(AdminActionResponse {
+            action: "restart".into(),
+            boards_powered_down: None,
+        }),
+    ))
+}
+
+/// Power down all boards and stop the daemon.
+///
+/// Boards are powered down through the backplane (core voltage off, fans to
+/// a safe speed) before the response is sent, so a successful response means
+/// the hardware is safe.
+async fn shutdown(
+    State(state): State<ApiState>,
+    Json(req): Json<AdminActionRequest>,
+) -> Result<Json<AdminActionResponse>, ApiError> {
+    check_confirmation(&state, "shutdown", &req.confirm)?;
+
+    info!("Shutdown requested via API.");
+
+    let (reply_tx, reply_rx) = oneshot::channel();
+    let powered_down = match state
+        .backplane_tx
+        .send(BackplaneCommand::PowerDownAll { reply_tx })
+        .await
+    {
+        Ok(()) => tokio::time::timeout(POWER_DOWN_TIMEOUT, reply_rx).await,
+        Err(_) => {
+            // Backplane already gone; its boards were shut down on exit
+            Ok(Ok(0))
+        }
+    };
+
+    // Stop the daemon regardless; it shuts down any remaining boards on exit
+    state.request_exit(false);
+
+    match powered_down {
+        Ok(Ok(count)) => Ok(Json(AdminActionResponse {
+            action: "shutdown".into(),
+            boards_powered_down: Some(count),
+        })),
+        Ok(Err(_)) => Err(ApiError::Internal(
+            "backplane dropped the power-down request".into(),
+        )),
+        Err(_) => Err(ApiError::BackplaneTimeout {
+            operation: "power down boards",
+        }),
+    }
+}
+
+/// Get the current log levels.
+async fn get_log_levels() -> Result<Json<LogLevelsResponse>, ApiError> {
+    logging::log_levels()
+        .map(|levels| Json(levels.into()))
+        .map_err(ApiError::from)
+}
+
+/// Set the default log level.
+async fn set_default_log_level(
+    Json(req): Json<SetLogLevelRequest>,
+) -> Result<Json<LogLevelsResponse>, ApiError> {
+    let level = logging::parse_level(&req.level)?;
+    logging::set_default_level(level)
+        .map(|levels| Json(levels.into()))
+        .map_err(ApiError::from)
+}
+
+/// Set the log level for one module.
+///
+/// Module paths are relative to the crate, as shown in log output (e.g.,
+/// `stratum_v1` or `asic::bm13xx::thread`).
+async fn set_module_log_level(
+    Path(module): Path<String>,
+    Json(req): Json<SetLogLevelRequest>,
+) -> Result<Json<LogLevelsResponse>, ApiError> {
+    let level = logging::parse_level(&req.level)?;
+    logging::set_module_level(&module, level)
+        .map(|levels| Json(levels.into()))
+        .map_err(ApiError::from)
+}
+
+/// Remove a runtime log level for one module.
+async fn clear_module_log_level(
+    Path(module): Path<String>,
+) -> Result<Json<LogLevelsResponse>, ApiError> {
+    logging::clear_module_level(&module)
+        .map(|levels| Json(levels.into()))
+        .map_err(ApiError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{atomic::AtomicBool, atomic::Ordering, Arc};
+
+    use axum::{
+        body::{to_bytes, Body},
+        http::{header, Request},
+    };
+    use tokio::sync::{mpsc, watch};
+    use tokio_util::sync::CancellationToken;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{
+        api::ErrorBody,
+        board::{history::BoardEvent, preset::PresetError, FanMode, VoltageRange},
+        config::REDACTED,
+        proxy::ProxyStats,
+    };
+
+    struct Harness {
+        router: Router,
+        shutdown: CancellationToken,
+        restart_requested: Arc<AtomicBool>,
+        backplane_rx: mpsc::Receiver<BackplaneCommand>,
+    }
+
+    fn harness() -> Harness {
+        let (backplane_tx, backplane_rx) = mpsc::channel(1);
+        let shutdown = CancellationToken::new();
+        let restart_requested = Arc::new(AtomicBool::new(false));
+        let state = ApiState::new(backplane_tx, shutdown.clone(), restart_requested.clone());
+        Harness {
+            router: routes(state),
+            shutdown,
+            restart_requested,
+            backplane_rx,
+        }
+    }
+
+    async fn post_json(
+        router: &Router,
+        uri: &str,
+        body: serde_json::Value,
+    ) -> (StatusCode, Vec<u8>) {
+        let request = Request::post(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, bytes.to_vec())
+    }
+
+    async fn put_json(
+        router: &Router,
+        uri: &str,
+        body: serde_json::Value,
+    ) -> (StatusCode, Vec<u8>) {
+        let request = Request::put(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, bytes.to_vec())
+    }
+
+    async fn get_token(router: &Router, action: &str) -> String {
+        let (status, body) = post_json(
+            router,
+            &format!("/admin/{}/token", action),
+            serde_json::json!({}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let resp: ConfirmationTokenResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(resp.action, action);
+        resp.token
+    }
+
+    #[tokio::test]
+    async fn test_restart_requires_token() {
+        let h = harness();
+
+        let (status, body) = post_json(
+            &h.router,
+            "/admin/restart",
+            serde_json::json!({ "confirm": "bogus" }),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let error: ErrorBody = serde_json::from_slice(&body).unwrap();
+        assert_eq!(error.code, "invalid_confirmation");
+        assert!(!h.shutdown.is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn test_restart_with_token() {
+        let h = harness();
+        let token = get_token(&h.router, "restart").await;
+
+        let (status, _) = post_json(
+            &h.router,
+            "/admin/restart",
+            serde_json::json!({ "confirm": token }),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert!(h.shutdown.is_cancelled());
+        assert!(h.restart_requested.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_token_for_other_action_rejected() {
+        let h = harness();
+        let token = get_token(&h.router, "restart").await;
+
+        let (status, _) = post_json(
+            &h.router,
+            "/admin/shutdown",
+            serde_json::json!({ "confirm": token }),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(!h.shutdown.is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_action_has_no_token() {
+        let h = harness();
+
+        let (status, _) = post_json(&h.router, "/admin/explode/token", serde_json::json!({})).await;
+
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_log_level_rejected() {
+        let h = harness();
+        let request = Request::put("/admin/log-level/stratum_v1")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"level":"loud"}"#))
+            .unwrap();
+
+        let response = h.router.clone().oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_powers_down_through_backplane() {
+        let mut h = harness();
+        let token = get_token(&h.router, "shutdown").await;
+
+        // Stand in for the backplane
+        let backplane = tokio::spawn(async move {
+            match h.backplane_rx.recv().await {
+                Some(BackplaneCommand::PowerDownAll { reply_tx }) => reply_tx.send(2).unwrap(),
+                other => panic!("expected PowerDownAll, got {:?}", other),
+            }
+        });
+
+        let (status, body) = post_json(
+            &h.router,
+            "/admin/shutdown",
+            serde_json::json!({ "confirm": token }),
+        )
+        .await;
+        backplane.await.unwrap();
+
+        assert_eq!(status, StatusCode::OK);
+        let resp: AdminActionResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(resp.boards_powered_down, Some(2));
+        assert!(h.shutdown.is_cancelled());
+        assert!(!h.restart_requested.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_boards_listed_from_backplane() {
+        let mut h = harness();
+
+        let backplane = tokio::spawn(async move {
+            match h.backplane_rx.recv().await {
+                Some(BackplaneCommand::ListBoards { reply_tx }) => reply_tx
+                    .send(vec![
+                        BoardStatus {
+                            id: "a1".into(),
+                            model: "Bitaxe Gamma".into(),
+                            health: BoardHealth::Running,
+                            generation: 1,
+                            error: None,
+                            power_limited: None,
+                            telemetry: Some(TelemetrySnapshot {
+                                temperature_c: Some(52.5),
+                                ..TelemetrySnapshot::new()
+                            }),
+                        },
+                        BoardStatus {
+                            id: "b2".into(),
+                            model: "Bitaxe Gamma".into(),
+                            health: BoardHealth::Restarting { restarts: 2 },
+                            generation: 3,
+                            error: None,
+                            power_limited: None,
+                            telemetry: None,
+                        },
+                        BoardStatus {
+                            id: "c3".into(),
+                            model: "Bitaxe Gamma".into(),
+                            health: BoardHealth::Waiting,
+                            generation: 1,
+                            error: Some("/dev/ttyACM1 is in use by pid 812".into()),
+                            power_limited: None,
+                            telemetry: None,
+                        },
+                    ])
+                    .unwrap(),
+                other => panic!("expected ListBoards, got {:?}", other),
+            }
+        });
+
+        let request = Request::get("/boards").body(Body::empty()).unwrap();
+        let response = h.router.clone().oneshot(request).await.unwrap();
+        backplane.await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let boards: Vec<BoardResponse> = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(boards.len(), 3);
+        assert_eq!(boards[0].health, "running");
+        assert_eq!(
+            boards[0].telemetry.as_ref().unwrap().temperature_c,
+            Some(52.5)
+        );
+        assert!(boards[0].telemetry_age_secs.unwrap() < 60.0);
+        assert_eq!(boards[1].health, "restarting");
+        assert_eq!(boards[1].restarts, Some(2));
+        assert_eq!(boards[1].generation, 3);
+        assert!(boards[1].telemetry.is_none());
+        assert_eq!(boards[2].health, "waiting");
+        assert_eq!(
+            boards[2].error.as_deref(),
+            Some("/dev/ttyACM1 is in use by pid 812")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unknown_board_not_found() {
+        let mut h = harness();
+
+        let backplane = tokio::spawn(async move {
+            if let Some(BackplaneCommand::ListBoards { reply_tx }) = h.backplane_rx.recv().await {
+                reply_tx.send(Vec::new()).unwrap();
+            }
+        });
+
+        let request = Request::get("/boards/1a2b3c").body(Body::empty()).unwrap();
+        let response = h.router.clone().oneshot(request).await.unwrap();
+        backplane.await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let error: ErrorBody = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(error.code, "board_not_found");
+        assert_eq!(error.details, Some(serde_json::json!({ "id": "1a2b3c" })));
+    }
+
+    #[tokio::test]
+    async fn test_voltage_out_of_range_reports_allowed_range() {
+        let mut h = harness();
+
+        let backplane = tokio::spawn(async move {
+            if let Some(BackplaneCommand::SetBoardOperatingPoint {
+                id,
+                point,
+                reply_tx,
+                ..
+            }) = h.backplane_rx.recv().await
+            {
+                assert_eq!(id, "1a2b3c");
+                let range = VoltageRange { min: 1.0, max: 2.0 };
+                reply_tx
+                    .send(Some(point.check_voltage(Some(range))))
+                    .unwrap();
+            }
+        });
+
+        let request = Request::put("/boards/1a2b3c/operating-point")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"voltage":2.5}"#))
+            .unwrap();
+        let response = h.router.clone().oneshot(request).await.unwrap();
+        backplane.await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let error: ErrorBody = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(error.code, "voltage_out_of_range");
+        assert_eq!(
+            error.details,
+            Some(serde_json::json!({
+                "volts": 2.5,
+                "allowed": { "min": 1.0, "max": 2.0 },
+            }))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_identity_write_needs_token_and_model() {
+        let mut h = harness();
+        let identity = serde_json::json!({ "model": "Bitaxe Supra", "revision": "401" });
+
+        let (status, _) = put_json(
+            &h.router,
+            "/boards/1a2b3c/identity",
+            serde_json::json!({ "confirm": "bogus", "identity": identity }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let token = get_token(&h.router, "identity").await;
+        let (status, body) = put_json(
+            &h.router,
+            "/boards/1a2b3c/identity",
+            serde_json::json!({ "confirm": token, "identity": { "model": "" } }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let error: ErrorBody = serde_json::from_slice(&body).unwrap();
+        assert_eq!(error.code, "invalid_identity");
+        assert!(h.backplane_rx.try_recv().is_err(), "board left alone");
+
+        let backplane = tokio::spawn(async move {
+            if let Some(BackplaneCommand::WriteBoardIdentity {
+                id,
+                identity,
+                reply_tx,
+            }) = h.backplane_rx.recv().await
+            {
+                assert_eq!(id, "1a2b3c");
+                assert_eq!(identity.model, "Bitaxe Supra");
+                assert_eq!(identity.revision.as_deref(), Some("401"));
+                reply_tx.send(Some(Ok(()))).unwrap();
+            }
+        });
+        let token = get_token(&h.router, "identity").await;
+        let (status, _) = put_json(
+            &h.router,
+            "/boards/1a2b3c/identity",
+            serde_json::json!({ "confirm": token, "identity": identity }),
+        )
+        .await;
+        backplane.await.unwrap();
+        assert_eq!(status, StatusCode::NO_CONTENT);
+    }
+
+    #[tokio::test]
+    async fn test_firmware_update_requires_token() {
+        let mut h = harness();
+
+        let request = Request::post("/boards/1a2b3c/firmware?confirm=bogus")
+            .body(Body::from(vec![0xe9; 64]))
+            .unwrap();
+        let response = h.router.clone().oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert!(h.backplane_rx.try_recv().is_err(), "board left alone");
+    }
+
+    #[tokio::test]
+    async fn test_firmware_update_hands_image_to_backplane() {
+        let mut h = harness();
+        let token = get_token(&h.router, "firmware").await;
+
+        let backplane = tokio::spawn(async move {
+            if let Some(BackplaneCommand::UpdateFirmware {
+                id,
+                image,
+                reply_tx,
+            }) = h.backplane_rx.recv().await
+            {
+                assert_eq!(id, "1a2b3c");
+                assert_eq!(image.offset, 0x10000);
+                assert_eq!(image.data.len(), 64);
+                let (_progress_tx, progress_rx) = watch::channel(FirmwareProgress::Resetting);
+                reply_tx.send(Some(Ok(progress_rx))).unwrap();
+            }
+        });
+
+        let request = Request::post(format!(
+            "/boards/1a2b3c/firmware?offset=65536&confirm={token}"
+        ))
+        .body(Body::from(vec![0xe9; 64]))
+        .unwrap();
+        let response = h.router.clone().oneshot(request).await.unwrap();
+        backplane.await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let resp: FirmwareUpdateResponse = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(resp.bytes, 64);
+        assert_eq!(resp.progress_url, "/api/v1/boards/1a2b3c/firmware/progress");
+    }
+
+    #[tokio::test]
+    async fn test_reset_chips_reports_chip_count() {
+        let mut h = harness();
+
+        let backplane = tokio::spawn(async move {
+            if let Some(BackplaneCommand::ResetBoardChips { id, reply_tx }) =
+                h.backplane_rx.recv().await
+            {
+                assert_eq!(id, "1a2b3c");
+                reply_tx.send(Some(Ok(1))).unwrap();
+            }
+        });
+
+        let request = Request::post("/boards/1a2b3c/reset-chips")
+            .body(Body::empty())
+            .unwrap();
+        let response = h.router.clone().oneshot(request).await.unwrap();
+        backplane.await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let reset: ChipResetResponse = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(reset.chips, 1);
+    }
+
+    #[tokio::test]
+    async fn test_regulator_store_limited_to_one_a_minute() {
+        let mut h = harness();
+
+        let backplane = tokio::spawn(async move {
+            if let Some(BackplaneCommand::StoreBoardRegulatorConfig { id, reply_tx }) =
+                h.backplane_rx.recv().await
+            {
+                assert_eq!(id, "1a2b3c");
+                reply_tx.send(Some(Ok(()))).unwrap();
+            }
+        });
+
+        let store = || {
+            Request::post("/boards/1a2b3c/regulator/store")
+                .body(Body::empty())
+                .unwrap()
+        };
+        let response = h.router.clone().oneshot(store()).await.unwrap();
+        backplane.await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let response = h.router.clone().oneshot(store()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[tokio::test]
+    async fn test_i2c_scan_lists_devices() {
+        let mut h = harness();
+
+        let backplane = tokio::spawn(async move {
+            if let Some(BackplaneCommand::ScanBoardI2c { id, reply_tx }) =
+                h.backplane_rx.recv().await
+            {
+                assert_eq!(id, "1a2b3c");
+                let devices = vec![
+                    ScannedDevice {
+                        address: 0x24,
+                        part: Some("TPS546D24A".into()),
+                    },
+                    ScannedDevice {
+                        address: 0x68,
+                        part: None,
+                    },
+                ];
+                reply_tx.send(Some(Ok(devices))).unwrap();
+            }
+        });
+
+        let request = Request::post("/boards/1a2b3c/i2c/scan")
+            .body(Body::empty())
+            .unwrap();
+        let response = h.router.clone().oneshot(request).await.unwrap();
+        backplane.await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let scan: I2cScanResponse = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(scan.devices.len(), 2);
+        assert_eq!(scan.devices[0].part.as_deref(), Some("TPS546D24A"));
+    }
+
+    #[tokio::test]
+    async fn test_nonce_map_flags_weak_cores() {
+        let mut h = harness();
+
+        let backplane = tokio::spawn(async move {
+            if let Some(BackplaneCommand::ReadNonceMap { id, reply_tx }) =
+                h.backplane_rx.recv().await
+            {
+                assert_eq!(id, "1a2b3c");
+                let mut map = NonceMap::with_layout(3, 2);
+                for _ in 0..50 {
+                    map.record(0, 0);
+                    map.record(1, 1);
+                }
+                reply_tx.send(Some(Ok(map))).unwrap();
+            }
+        });
+
+        let request = Request::get("/boards/1a2b3c/nonce-map")
+            .body(Body::empty())
+            .unwrap();
+        let response = h.router.clone().oneshot(request).await.unwrap();
+        backplane.await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let map: NonceMapResponse = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(map.total, 100);
+        assert_eq!(map.weak_cores, [2]);
+        assert_eq!(map.cores[&1], [0, 50]);
+    }
+
+    #[tokio::test]
+    async fn test_pause_and_resume() {
+        let (backplane_tx, mut backplane_rx) = mpsc::channel(1);
+        let (pause_tx, pause_rx) = watch::channel(Paused::default());
+        let schedule_tx = pause_tx.clone();
+        let state = ApiState::new(
+            backplane_tx,
+            CancellationToken::new(),
+            Arc::new(AtomicBool::new(false)),
+        )
+        .with_pause(pause_tx);
+        let router = routes(state);
+
+        let backplane = tokio::spawn(async move {
+            while let Some(command) = backplane_rx.recv().await {
+                if let BackplaneCommand::ListBoards { reply_tx } = command {
+                    let board = BoardStatus {
+                        id: "1a2b3c".into(),
+                        model: "Bitaxe Gamma".into(),
+                        health: BoardHealth::Running,
+                        generation: 1,
+                        error: None,
+                        power_limited: None,
+                        telemetry: None,
+                    };
+                    reply_tx.send(vec![board]).unwrap();
+                }
+            }
+        });
+
+        let (status, body) =
+            post_json(&router, "/boards/1a2b3c/pause", serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::OK);
+        let response: PauseResponse = serde_json::from_slice(&body).unwrap();
+        assert!(!response.paused);
+        assert_eq!(response.boards, ["1a2b3c"]);
+        let (status, _) = post_json(&router, "/boards/unknown/pause", serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // The schedule's pause outlasts the operator's resume
+        let (status, _) = post_json(&router, "/mining/pause", serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(pause_rx.borrow().manual);
+        schedule_tx.send_modify(|paused| paused.schedule = true);
+        let (_, body) = post_json(&router, "/mining/resume", serde_json::json!({})).await;
+        let response: PauseResponse = serde_json::from_slice(&body).unwrap();
+        assert!(response.paused && response.schedule && !response.manual);
+
+        let (status, body) =
+            post_json(&router, "/boards/1a2b3c/resume", serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::OK);
+        let response: PauseResponse = serde_json::from_slice(&body).unwrap();
+        assert!(response.boards.is_empty());
+        backplane.abort();
+
+        // Unavailable without a scheduler
+        let h = harness();
+        let request = Request::get("/mining").body(Body::empty()).unwrap();
+        let response = h.router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn test_history_of_detached_board() {
+        let mut h = harness();
+
+        let backplane = tokio::spawn(async move {
+            while let Some(command) = h.backplane_rx.recv().await {
+                if let BackplaneCommand::ReadBoardHistory { id, reply_tx } = command {
+                    let events = (id == "1a2b3c").then(|| {
+                        vec![
+                            HistoryEntry {
+                                at: 1_760_486_400,
+                                event: BoardEvent::Attached {
+                                    model: "Bitaxe Gamma".into(),
+                                },
+                            },
+                            HistoryEntry {
+                                at: 1_760_486_460,
+                                event: BoardEvent::Detached {
+                                    reason: "unplugged".into(),
+                                },
+                            },
+                        ]
+                    });
+                    reply_tx.send(events).unwrap();
+                }
+            }
+        });
+
+        let request = Request::get("/boards/1a2b3c/history")
+            .body(Body::empty())
+            .unwrap();
+        let response = h.router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let history: HistoryResponse = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(history.events.len(), 2);
+        assert_eq!(
+            history.events[1].event,
+            BoardEvent::Detached {
+                reason: "unplugged".into()
+            }
+        );
+
+        let request = Request::get("/boards/unknown/history")
+            .body(Body::empty())
+            .unwrap();
+        let response = h.router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        backplane.abort();
+    }
+
+    #[tokio::test]
+    async fn test_pools_unavailable_without_manager() {
+        let h = harness();
+
+        let request = Request::get("/pools").body(Body::empty()).unwrap();
+        let response = h.router.clone().oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let error: ErrorBody = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(error.code, "pools_unavailable");
+    }
+
+    #[tokio::test]
+    async fn test_downstreams_need_proxy() {
+        let h = harness();
+        let request = Request::get("/proxy/downstreams")
+            .body(Body::empty())
+            .unwrap();
+        let response = h.router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let error: ErrorBody = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(error.code, "proxy_unavailable");
+
+        let (backplane_tx, _backplane_rx) = mpsc::channel(1);
+        let state = ApiState::new(
+            backplane_tx,
+            CancellationToken::new(),
+            Arc::new(AtomicBool::new(false)),
+        )
+        .with_proxy(ProxyStats::default());
+        let request = Request::get("/proxy/downstreams")
+            .body(Body::empty())
+            .unwrap();
+        let response = routes(state).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let downstreams: Vec<DownstreamResponse> = serde_json::from_slice(&bytes).unwrap();
+        assert!(downstreams.is_empty());
+    }
+
+    const CONFIG: &str = r#"
+        [daemon]
+        log_level = "info"
+
+        [[pools]]
+        url = "stratum+tcp://pool.example.com:3333"
+        worker = "rig1"
+        password = "hunter2"
+
+        [hardware]
+        temp_limit = 85.0
+        fan_min_rpm = 1000
+        fan_max_rpm = 6000
+
+        [api]
+        listen = "127.0.0.1:7785"
+    "#;
+
+    async fn put_config(router: &Router, uri: &str, config: &Config) -> (StatusCode, Vec<u8>) {
+        let request = Request::put(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(serde_json::to_vec(config).unwrap()))
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, bytes.to_vec())
+    }
+
+    #[tokio::test]
+    async fn test_config_update_staged_then_applied() {
+        let dir = std::env::temp_dir().join(format!("mujina-api-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("mujina.toml");
+        std::fs::write(&path, CONFIG).unwrap();
+        let original = Config::parse(CONFIG).unwrap();
+
+        let (backplane_tx, _backplane_rx) = mpsc::channel(1);
+        let (saved_tx, saved_rx) = watch::channel(original.clone());
+        let state = ApiState::new(
+            backplane_tx,
+            CancellationToken::new(),
+            Arc::new(AtomicBool::new(false)),
+        )
+        .with_config_file(path.clone(), saved_tx);
+        let router = routes(state);
+
+        // Secrets are never shown
+        let request = Request::get("/config").body(Body::empty()).unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let mut shown: Config = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(shown.pools[0].password.as_deref(), Some(REDACTED));
+
+        // Every problem is reported, and nothing is saved
+        shown.daemon.log_level = "loud".into();
+        shown.hardware.temp_limit = 0.0;
+        let (status, body) = put_config(&router, "/config", &shown).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let error: ErrorBody = serde_json::from_slice(&body).unwrap();
+        assert_eq!(error.code, "invalid_config");
+        assert_eq!(
+            error.details.unwrap()["problems"].as_array().unwrap().len(),
+            2
+        );
+
+        // A dry run reports what would change without saving
+        shown.daemon.log_level = "debug".into();
+        shown.hardware.temp_limit = 80.0;
+        let (status, body) = put_config(&router, "/config?dry_run=true", &shown).await;
+        assert_eq!(status, StatusCode::OK);
+        let outcome: ConfigUpdateResponse = serde_json::from_slice(&body).unwrap();
+        assert!(!outcome.applied);
+        assert_eq!(outcome.reloaded, vec!["daemon.log_level"]);
+        assert_eq!(outcome.restart_required, vec!["hardware"]);
+        assert_eq!(Config::load_from(&path).unwrap(), original);
+        assert!(!saved_rx.has_changed().unwrap());
+
+        // Applying saves with the redacted password restored
+        let (status, body) = put_config(&router, "/config", &shown).await;
+        assert_eq!(status, StatusCode::OK);
+        let outcome: ConfigUpdateResponse = serde_json::from_slice(&body).unwrap();
+        assert!(outcome.applied);
+        let saved = Config::load_from(&path).unwrap();
+        assert_eq!(saved.daemon.log_level, "debug");
+        assert_eq!(saved.pools[0].password.as_deref(), Some("hunter2"));
+        assert_eq!(*saved_rx.borrow(), saved);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_settings_validated_before_store() {
+        let mut h = harness();
+
+        let (status, body) = put_json(
+            &h.router,
+            "/boards/1a2b3c/settings",
+            serde_json::json!({ "fan": { "mode": "fixed", "percent": 140 } }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let error: ErrorBody = serde_json::from_slice(&body).unwrap();
+        assert_eq!(error.code, "invalid_settings");
+        assert!(h.backplane_rx.try_recv().is_err(), "nothing stored");
+
+        let backplane = tokio::spawn(async move {
+            if let Some(BackplaneCommand::WriteBoardSettings {
+                id,
+                settings,
+                reply_tx,
+            }) = h.backplane_rx.recv().await
+            {
+                assert_eq!(id, "1a2b3c");
+                assert_eq!(settings.frequency_mhz, Some(525.0));
+                assert_eq!(settings.fan, Some(FanMode::Auto));
+                reply_tx.send(Some(Ok(()))).unwrap();
+            }
+        });
+        let (status, _) = put_json(
+            &h.router,
+            "/boards/1a2b3c/settings",
+            serde_json::json!({ "frequency_mhz": 525.0, "fan": { "mode": "auto" } }),
+        )
+        .await;
+        backplane.await.unwrap();
+        assert_eq!(status, StatusCode::NO_CONTENT);
+    }
+
+    #[tokio::test]
+    async fn test_preset_goes_through_backplane() {
+        let mut h = harness();
+
+        let backplane = tokio::spawn(async move {
+            while let Some(command) = h.backplane_rx.recv().await {
+                if let BackplaneCommand::ApplyPreset {
+                    id,
+                    preset,
+                    reply_tx,
+                } = command
+                {
+                    let result = match id {
+                        None => Ok(vec!["1a2b3c".into(), "4d5e6f".into()]),
+                        Some(_) => Err(PresetError::Undefined {
+                            preset,
+                            model: "Bitaxe Gamma".into(),
+                        }),
+                    };
+                    reply_tx.send(result).unwrap();
+                }
+            }
+        });
+
+        let (status, body) =
+            put_json(&h.router, "/preset", serde_json::json!({ "preset": "eco" })).await;
+        assert_eq!(status, StatusCode::OK);
+        let switched: PresetResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(switched.preset, Preset::Eco);
+        assert_eq!(switched.boards, ["1a2b3c", "4d5e6f"]);
+
+        let (status, body) = put_json(
+            &h.router,
+            "/boards/1a2b3c/preset",
+            serde_json::json!({ "preset": "turbo" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let error: ErrorBody = serde_json::from_slice(&body).unwrap();
+        assert_eq!(error.code, "invalid_settings");
+
+        let (status, _) = put_json(
+            &h.router,
+            "/preset",
+            serde_json::json!({ "preset": "ludicrous" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        drop(h.router);
+        backplane.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_board_shutdown_goes_through_backplane() {
+        let mut h = harness();
+
+        let backplane = tokio::spawn(async move {
+            for expected in ["1a2b3c", "gone"] {
+                match h.backplane_rx.recv().await {
+                    Some(BackplaneCommand::ShutdownBoard { id, reply_tx }) => {
+                        assert_eq!(id, expected);
+                        let result = (id == "1a2b3c").then_some(Ok(()));
+                        reply_tx.send(result).unwrap();
+                    }
+                    other => panic!("expected ShutdownBoard, got {:?}", other),
+                }
+            }
+        });
+
+        let (status, _) =
+            post_json(&h.router, "/boards/1a2b3c/shutdown", serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, body) =
+            post_json(&h.router, "/boards/gone/shutdown", serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let error: ErrorBody = serde_json::from_slice(&body).unwrap();
+        assert_eq!(error.code, "board_not_found");
+        backplane.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_overlapping_board_operations_refused() {
+        let mut h = harness();
+
+        // Hold the first retune in the backplane until told to finish
+        let (held_tx, held_rx) = oneshot::channel();
+        tokio::spawn(async move {
+            if let Some(BackplaneCommand::SetBoardOperatingPoint { reply_tx, .. }) =
+                h.backplane_rx.recv().await
+            {
+                held_tx.send(reply_tx).unwrap();
+            }
+        });
+        let router = h.router.clone();
+        let first = tokio::spawn(async move {
+            put_json(
+                &router,
+                "/boards/1a2b3c/operating-point",
+                serde_json::json!({ "frequency_mhz": 500.0 }),
+            )
+            .await
+        });
+        let reply_tx = held_rx.await.unwrap();
+
+        let (status, body) = post_json(
+            &h.router,
+            "/boards/1a2b3c/reset-chips",
+            serde_json::json!({}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let error: ErrorBody = serde_json::from_slice(&body).unwrap();
+        assert_eq!(error.code, "board_busy");
+        assert_eq!(
+            error.details,
+            Some(serde_json::json!({ "id": "1a2b3c", "operation": "retune" }))
+        );
+
+        reply_tx.send(Some(Ok(()))).unwrap();
+        assert_eq!(first.await.unwrap().0, StatusCode::NO_CONTENT);
+
+        // Free again, but a retune was just started
+        let request = Request::put("/boards/1a2b3c/operating-point")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"frequency_mhz":525.0}"#))
+            .unwrap();
+        let response = h.router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let error: ErrorBody = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(error.code, "rate_limited");
+    }
+}
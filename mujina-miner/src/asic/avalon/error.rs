@@ -0,0 +1,15 @@
+//! Error types for Avalon protocol operations
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ProtocolError {
+    #[error("Invalid packet type: 0x{0:02x}")]
+    InvalidPacketType(u8),
+
+    #[error("Invalid packet format")]
+    InvalidFrame,
+
+    #[error("Packet CRC mismatch")]
+    CrcMismatch,
+}
@@ -0,0 +1,13 @@
+//! Avalon chip family support.
+//!
+//! This module provides the packet protocol and HashThread implementation
+//! for Canaan's Avalon chips (A3197, Kxx), which are driven through a
+//! controller rather than register by register like BM13xx chips.
+
+pub mod error;
+pub mod protocol;
+pub mod thread;
+
+// Re-export commonly used types
+pub use protocol::{AvalonCodec, Command, Response};
+pub use thread::AvalonThread;
@@ -0,0 +1,431 @@
+//! Avalon packet protocol.
+//!
+//! Canaan's Avalon chips (the A3197 and the Kxx series) sit behind a
+//! controller that speaks in fixed 40-byte packets:
+//!
+//! | Bytes | Field                                                  |
+//! |-------|--------------------------------------------------------|
+//! | 0-1   | Preamble, ASCII "CN"                                   |
+//! | 2     | Packet type                                            |
+//! | 3     | Option byte, per type; the job ID for work and nonces  |
+//! | 4     | Index of this packet within its message, from 1        |
+//! | 5     | Number of packets in the message                       |
+//! | 6-37  | Data                                                   |
+//! | 38-39 | CRC16 (XMODEM) of the data, big-endian                 |
+//!
+//! A job is the 80-byte block header, split over three header packets, and
+//! a target packet that completes it. The controller filters nonces against
+//! that target and rolls ntime on its own, reporting the offset it used with
+//! each nonce. There's no version rolling.
+
+use bytes::{Buf, BufMut, BytesMut};
+use crc_all::CrcAlgo;
+use strum::FromRepr;
+use tokio_util::codec::{Decoder, Encoder};
+
+use super::error::ProtocolError;
+use crate::tracing::prelude::*;
+
+/// Packet preamble, ASCII "CN".
+pub const PREAMBLE: [u8; 2] = *b"CN";
+
+/// Bytes of data each packet carries.
+pub const DATA_LEN: usize = 32;
+
+/// Total packet length on the wire.
+pub const PACKET_LEN: usize = PREAMBLE.len() + 4 + DATA_LEN + 2;
+
+/// Header packets a job is split over.
+const HEADER_PACKETS: u8 = 3;
+
+const CRC16: CrcAlgo<u16> = CrcAlgo::<u16>::new(
+    0x1021, // polynomial (CRC-16/XMODEM)
+    16,     // width
+    0,      // init
+    0,      // xorout
+    false,  // reflect
+);
+
+/// Packet types, host to controller (0x1x-0x3x) and back (0x4x).
+#[derive(FromRepr, Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PacketType {
+    Detect = 0x10,
+    Header = 0x15,
+    Target = 0x16,
+    SetFrequency = 0x25,
+    Poll = 0x30,
+    AckDetect = 0x40,
+    Status = 0x41,
+    Nonce = 0x42,
+}
+
+/// One 40-byte packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Packet {
+    pub kind: PacketType,
+    pub opt: u8,
+    pub idx: u8,
+    pub cnt: u8,
+    pub data: [u8; DATA_LEN],
+}
+
+impl Packet {
+    /// Single-packet message of `kind` carrying `data`.
+    fn single(kind: PacketType, opt: u8, data: [u8; DATA_LEN]) -> Self {
+        Self {
+            kind,
+            opt,
+            idx: 1,
+            cnt: 1,
+            data,
+        }
+    }
+
+    fn encode(&self, dst: &mut BytesMut) {
+        dst.put_slice(&PREAMBLE);
+        dst.put_u8(self.kind as u8);
+        dst.put_u8(self.opt);
+        dst.put_u8(self.idx);
+        dst.put_u8(self.cnt);
+        dst.put_slice(&self.data);
+        dst.put_u16(crc16(&self.data));
+    }
+
+    /// Parse a whole packet, preamble included.
+    fn decode(bytes: &[u8]) -> Result<Self, ProtocolError> {
+        if bytes.len() != PACKET_LEN || bytes[..2] != PREAMBLE {
+            return Err(ProtocolError::InvalidFrame);
+        }
+        let mut data = [0u8; DATA_LEN];
+        data.copy_from_slice(&bytes[6..6 + DATA_LEN]);
+
+        let crc = u16::from_be_bytes([bytes[PACKET_LEN - 2], bytes[PACKET_LEN - 1]]);
+        if crc != crc16(&data) {
+            return Err(ProtocolError::CrcMismatch);
+        }
+        let kind =
+            PacketType::from_repr(bytes[2]).ok_or(ProtocolError::InvalidPacketType(bytes[2]))?;
+
+        Ok(Self {
+            kind,
+            opt: bytes[3],
+            idx: bytes[4],
+            cnt: bytes[5],
+            data,
+        })
+    }
+}
+
+/// Work for the chips.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Job {
+    /// Echoed back with each nonce
+    pub job_id: u8,
+    /// Serialized block header; its nonce field is ignored
+    pub header: [u8; 80],
+    /// Nonces are reported if their hash meets this target, little-endian
+    pub target: [u8; 32],
+}
+
+/// Commands sent to an Avalon controller.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Ask the controller to identify itself
+    Detect,
+    /// Start hashing a job, abandoning the previous one
+    Job(Job),
+    /// Set the chips' clock
+    SetFrequency { mhz: u16 },
+    /// Ask for a status report
+    Poll,
+}
+
+impl Command {
+    /// Packets making up this command.
+    pub fn packets(&self) -> Vec<Packet> {
+        match self {
+            Command::Detect => vec![Packet::single(PacketType::Detect, 0, [0; DATA_LEN])],
+            Command::Job(job) => {
+                let mut packets: Vec<Packet> = job
+                    .header
+                    .chunks(DATA_LEN)
+                    .zip(1..=HEADER_PACKETS)
+                    .map(|(chunk, idx)| {
+                        let mut data = [0u8; DATA_LEN];
+                        data[..chunk.len()].copy_from_slice(chunk);
+                        Packet {
+                            kind: PacketType::Header,
+                            opt: job.job_id,
+                            idx,
+                            cnt: HEADER_PACKETS,
+                            data,
+                        }
+                    })
+                    .collect();
+                packets.push(Packet::single(PacketType::Target, job.job_id, job.target));
+                packets
+            }
+            Command::SetFrequency { mhz } => {
+                let mut data = [0u8; DATA_LEN];
+                data[..2].copy_from_slice(&mhz.to_be_bytes());
+                vec![Packet::single(PacketType::SetFrequency, 0, data)]
+            }
+            Command::Poll => vec![Packet::single(PacketType::Poll, 0, [0; DATA_LEN])],
+        }
+    }
+}
+
+/// Responses from an Avalon controller.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Response {
+    /// Answer to [`Command::Detect`]
+    AckDetect {
+        /// Unique chip identifier
+        dna: [u8; 8],
+        /// Controller firmware version
+        firmware: String,
+    },
+    /// Periodic or polled status
+    Status {
+        temperature_c: i16,
+        frequency_mhz: u16,
+    },
+    /// A nonce meeting the job's target
+    Nonce {
+        job_id: u8,
+        /// Chip on the chain that found it
+        chip: u8,
+        /// Seconds the controller had rolled ntime forward
+        ntime_offset: u8,
+        nonce: u32,
+    },
+}
+
+impl Response {
+    fn from_packet(packet: &Packet) -> Result<Self, ProtocolError> {
+        let data = &packet.data;
+        match packet.kind {
+            PacketType::AckDetect => {
+                let mut dna = [0u8; 8];
+                dna.copy_from_slice(&data[..8]);
+                let firmware = data[8..]
+                    .iter()
+                    .take_while(|&&b| b != 0)
+                    .map(|&b| b as char)
+                    .collect();
+                Ok(Response::AckDetect { dna, firmware })
+            }
+            PacketType::Status => Ok(Response::Status {
+                temperature_c: i16::from_be_bytes([data[0], data[1]]),
+                frequency_mhz: u16::from_be_bytes([data[2], data[3]]),
+            }),
+            PacketType::Nonce => Ok(Response::Nonce {
+                job_id: packet.opt,
+                chip: data[0],
+                ntime_offset: data[1],
+                nonce: u32::from_le_bytes([data[2], data[3], data[4], data[5]]),
+            }),
+            other => Err(ProtocolError::InvalidPacketType(other as u8)),
+        }
+    }
+
+    /// Packet carrying this response, for simulators and tests.
+    pub fn to_packet(&self) -> Packet {
+        let mut data = [0u8; DATA_LEN];
+        match self {
+            Response::AckDetect { dna, firmware } => {
+                data[..8].copy_from_slice(dna);
+                let firmware = firmware.as_bytes();
+                let len = firmware.len().min(DATA_LEN - 8);
+                data[8..8 + len].copy_from_slice(&firmware[..len]);
+                Packet::single(PacketType::AckDetect, 0, data)
+            }
+            Response::Status {
+                temperature_c,
+                frequency_mhz,
+            } => {
+                data[..2].copy_from_slice(&temperature_c.to_be_bytes());
+                data[2..4].copy_from_slice(&frequency_mhz.to_be_bytes());
+                Packet::single(PacketType::Status, 0, data)
+            }
+            Response::Nonce {
+                job_id,
+                chip,
+                ntime_offset,
+                nonce,
+            } => {
+                data[0] = *chip;
+                data[1] = *ntime_offset;
+                data[2..6].copy_from_slice(&nonce.to_le_bytes());
+                Packet::single(PacketType::Nonce, *job_id, data)
+            }
+        }
+    }
+}
+
+/// Encoder for Avalon commands and decoder for their responses.
+#[derive(Debug, Default, Clone)]
+pub struct AvalonCodec;
+
+impl Encoder<Command> for AvalonCodec {
+    type Error = std::io::Error;
+
+    fn encode(&mut self, command: Command, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        for packet in command.packets() {
+            packet.encode(dst);
+        }
+        trace!(cmd = ?command, bytes = dst.len(), "TX Avalon");
+        Ok(())
+    }
+}
+
+impl Decoder for AvalonCodec {
+    type Item = Response;
+    type Error = std::io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        // As with BM13xx, resynchronize fully within one call: skip to the
+        // next preamble, and past its first byte if what follows it isn't a
+        // valid packet. Errors would end the stream, so none are returned.
+        loop {
+            match src.windows(PREAMBLE.len()).position(|w| w == PREAMBLE) {
+                Some(0) => {}
+                Some(offset) => {
+                    trace!(skipped = offset, "Avalon RX resync");
+                    src.advance(offset);
+                }
+                None => {
+                    // Keep a trailing 'C', which may begin a preamble in flight
+                    let keep = usize::from(src.last() == Some(&PREAMBLE[0]));
+                    src.advance(src.len() - keep);
+                    return Ok(None);
+                }
+            }
+
+            if src.len() < PACKET_LEN {
+                return Ok(None);
+            }
+
+            match Packet::decode(&src[..PACKET_LEN]).and_then(|p| Response::from_packet(&p)) {
+                Ok(response) => {
+                    src.advance(PACKET_LEN);
+                    trace!(resp = ?response, "RX Avalon");
+                    return Ok(Some(response));
+                }
+                Err(e) => {
+                    trace!(error = %e, "Avalon RX bad packet, searching for next");
+                    src.advance(1);
+                }
+            }
+        }
+    }
+}
+
+/// CRC-16/XMODEM of `data`.
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0;
+    CRC16.update_crc(&mut crc, data);
+    CRC16.finish_crc(&crc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(command: Command) -> BytesMut {
+        let mut buf = BytesMut::new();
+        AvalonCodec.encode(command, &mut buf).unwrap();
+        buf
+    }
+
+    fn encode_response(response: &Response) -> BytesMut {
+        let mut buf = BytesMut::new();
+        response.to_packet().encode(&mut buf);
+        buf
+    }
+
+    #[test]
+    fn test_crc16_check_value() {
+        assert_eq!(crc16(b"123456789"), 0x31c3);
+    }
+
+    #[test]
+    fn test_job_split_over_packets() {
+        let mut header = [0u8; 80];
+        for (i, byte) in header.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        let buf = encode(Command::Job(Job {
+            job_id: 7,
+            header,
+            target: [0xff; 32],
+        }));
+        assert_eq!(buf.len(), 4 * PACKET_LEN);
+
+        let packets: Vec<Packet> = buf
+            .chunks(PACKET_LEN)
+            .map(|chunk| Packet::decode(chunk).unwrap())
+            .collect();
+        assert!(packets[..3]
+            .iter()
+            .all(|p| p.kind == PacketType::Header && p.opt == 7 && p.cnt == 3));
+        assert_eq!(packets[2].idx, 3);
+        assert_eq!(packets[2].data[..16], header[64..]);
+        assert_eq!(packets[2].data[16..], [0; 16]);
+        assert_eq!(packets[3].kind, PacketType::Target);
+        assert_eq!(packets[3].data, [0xff; 32]);
+    }
+
+    #[test]
+    fn test_decode_responses() {
+        let nonce = Response::Nonce {
+            job_id: 3,
+            chip: 1,
+            ntime_offset: 2,
+            nonce: 0xdeadbeef,
+        };
+        let detect = Response::AckDetect {
+            dna: [1, 2, 3, 4, 5, 6, 7, 8],
+            firmware: "4.11.1".into(),
+        };
+
+        let mut buf = encode_response(&nonce);
+        buf.extend_from_slice(&encode_response(&detect));
+        assert_eq!(AvalonCodec.decode(&mut buf).unwrap(), Some(nonce));
+        assert_eq!(AvalonCodec.decode(&mut buf).unwrap(), Some(detect));
+        assert_eq!(AvalonCodec.decode(&mut buf).unwrap(), None);
+    }
+
+    #[test]
+    fn test_decoder_resyncs_past_garbage_and_bad_crc() {
+        let status = Response::Status {
+            temperature_c: 65,
+            frequency_mhz: 500,
+        };
+        let mut corrupt = encode_response(&status);
+        corrupt[10] ^= 0x01;
+
+        let mut buf = BytesMut::from(&b"xxC"[..]);
+        buf.extend_from_slice(&corrupt);
+        buf.extend_from_slice(&encode_response(&status));
+        assert_eq!(AvalonCodec.decode(&mut buf).unwrap(), Some(status));
+        assert!(buf.is_empty());
+
+        // A partial packet waits for the rest
+        let mut buf = encode_response(&Response::Status {
+            temperature_c: -5,
+            frequency_mhz: 0,
+        });
+        let mut partial = buf.split_to(20);
+        assert_eq!(AvalonCodec.decode(&mut partial).unwrap(), None);
+        partial.extend_from_slice(&buf);
+        assert!(matches!(
+            AvalonCodec.decode(&mut partial).unwrap(),
+            Some(Response::Status {
+                temperature_c: -5,
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,590 @@
+//! Avalon HashThread implementation.
+//!
+//! An AvalonThread represents the chips behind one Avalon controller. Like
+//! the BM13xx thread it's an actor task: it detects the controller on the
+//! first assignment, turns tasks into header jobs, and checks each nonce
+//! the controller reports against the task's share target.
+//!
+//! The controller rolls ntime itself, so unlike BM13xx the thread doesn't
+//! resend jobs each second; nonces carry the ntime offset they were found
+//! at instead.
+
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bitcoin::block::Header as BlockHeader;
+use futures::{sink::Sink, stream::Stream, SinkExt};
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio_stream::StreamExt;
+use tracing::{info_span, Instrument};
+
+use super::protocol;
+use crate::{
+    asic::hash_thread::{
+        BoardPeripherals, HashTask, HashThread, HashThreadCapabilities, HashThreadError,
+        HashThreadEvent, HashThreadStatus, Share, ThreadRemovalSignal,
+    },
+    tracing::prelude::*,
+    types::{Difficulty, HashRate},
+};
+
+/// How long the controller gets to answer detection.
+const DETECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often the controller is polled for status while hashing.
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Job IDs in flight; nonces for older jobs are dropped.
+const JOB_SLOTS: usize = 16;
+
+/// Command messages sent from scheduler to thread
+#[derive(Debug)]
+enum ThreadCommand {
+    /// Update task (old shares still valid)
+    UpdateTask {
+        new_task: HashTask,
+        response_tx: oneshot::Sender<Result<Option<HashTask>, HashThreadError>>,
+    },
+
+    /// Replace task (old shares invalid)
+    ReplaceTask {
+        new_task: HashTask,
+        response_tx: oneshot::Sender<Result<Option<HashTask>, HashThreadError>>,
+    },
+
+    /// Go idle (stop hashing)
+    GoIdle {
+        response_tx: oneshot::Sender<Result<Option<HashTask>, HashThreadError>>,
+    },
+}
+
+/// Tasks sent to the controller, indexed by job ID.
+struct JobTracker {
+    tasks: [Option<HashTask>; JOB_SLOTS],
+    next_id: u8,
+}
+
+impl JobTracker {
+    fn new() -> Self {
+        Self {
+            tasks: Default::default(),
+            next_id: 0,
+        }
+    }
+
+    fn insert(&mut self, task: HashTask) -> u8 {
+        let job_id = self.next_id;
+        self.tasks[usize::from(job_id)] = Some(task);
+        self.next_id = (self.next_id + 1) % JOB_SLOTS as u8;
+        job_id
+    }
+
+    fn get(&self, job_id: u8) -> Option<&HashTask> {
+        self.tasks.get(usize::from(job_id)).and_then(|t| t.as_ref())
+    }
+
+    fn clear(&mut self) {
+        self.tasks = Default::default();
+    }
+}
+
+/// Avalon HashThread implementation.
+///
+/// Represents the chips behind one Avalon controller as a schedulable
+/// worker. The controller is detected lazily when work is first assigned.
+pub struct AvalonThread {
+    /// Human-readable name for logging
+    name: String,
+
+    /// Channel for sending commands to the actor
+    command_tx: mpsc::Sender<ThreadCommand>,
+
+    /// Event receiver (taken by scheduler)
+    event_rx: Option<mpsc::Receiver<HashThreadEvent>>,
+
+    /// Cached capabilities
+    capabilities: HashThreadCapabilities,
+
+    /// Shared status (updated by actor task)
+    status: Arc<RwLock<HashThreadStatus>>,
+}
+
+impl AvalonThread {
+    /// Create a new Avalon thread with Stream/Sink for controller
+    /// communication.
+    ///
+    /// # Arguments
+    /// * `name` - Human-readable name for logging
+    /// * `hashrate` - Nominal hashrate of the chips behind the controller
+    /// * `responses` - Stream of decoded responses from the controller
+    /// * `commands` - Sink for sending commands to the controller
+    /// * `peripherals` - Hardware interfaces from board
+    /// * `removal_rx` - Watch channel for board-triggered removal
+    pub fn new<R, W>(
+        name: String,
+        hashrate: HashRate,
+        responses: R,
+        commands: W,
+        peripherals: BoardPeripherals,
+        removal_rx: watch::Receiver<ThreadRemovalSignal>,
+    ) -> Self
+    where
+        R: Stream<Item = Result<protocol::Response, std::io::Error>> + Unpin + Send + 'static,
+        W: Sink<protocol::Command> + Unpin + Send + 'static,
+        W::Error: std::fmt::Debug,
+    {
+        let (cmd_tx, cmd_rx) = mpsc::channel(10);
+        let (evt_tx, evt_rx) = mpsc::channel(100);
+
+        let status = Arc::new(RwLock::new(HashThreadStatus::default()));
+        let actor = Actor {
+            status: Arc::clone(&status),
+            responses,
+            commands,
+            peripherals,
+            detected: false,
+            current_task: None,
+            jobs: JobTracker::new(),
+        };
+
+        tokio::spawn(
+            actor
+                .run(cmd_rx, evt_tx, removal_rx)
+                .instrument(info_span!("hash_thread", thread = %name)),
+        );
+
+        Self {
+            name,
+            command_tx: cmd_tx,
+            event_rx: Some(evt_rx),
+            capabilities: HashThreadCapabilities::new(hashrate).with_max_pending_jobs(JOB_SLOTS),
+            status,
+        }
+    }
+
+    async fn request(
+        &self,
+        command: ThreadCommand,
+        response_rx: oneshot::Receiver<Result<Option<HashTask>, HashThreadError>>,
+    ) -> Result<Option<HashTask>, HashThreadError> {
+        self.command_tx
+            .send(command)
+            .await
+            .map_err(|_| HashThreadError::ChannelClosed("command channel closed".into()))?;
+
+        response_rx
+            .await
+            .map_err(|_| HashThreadError::WorkAssignmentFailed("no response from thread".into()))?
+    }
+}
+
+#[async_trait]
+impl HashThread for AvalonThread {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn capabilities(&self) -> &HashThreadCapabilities {
+        &self.capabilities
+    }
+
+    async fn update_task(
+        &mut self,
+        new_task: HashTask,
+    ) -> Result<Option<HashTask>, HashThreadError> {
+        let (response_tx, response_rx) = oneshot::channel();
+        self.request(
+            ThreadCommand::UpdateTask {
+                new_task,
+                response_tx,
+            },
+            response_rx,
+        )
+        .await
+    }
+
+    async fn replace_task(
+        &mut self,
+        new_task: HashTask,
+    ) -> Result<Option<HashTask>, HashThreadError> {
+        let (response_tx, response_rx) = oneshot::channel();
+        self.request(
+            ThreadCommand::ReplaceTask {
+                new_task,
+                response_tx,
+            },
+            response_rx,
+        )
+        .await
+    }
+
+    async fn go_idle(&mut self) -> Result<Option<HashTask>, HashThreadError> {
+        let (response_tx, response_rx) = oneshot::channel();
+        self.request(ThreadCommand::GoIdle { response_tx }, response_rx)
+            .await
+    }
+
+    fn take_event_receiver(&mut self) -> Option<mpsc::Receiver<HashThreadEvent>> {
+        self.event_rx.take()
+    }
+
+    fn status(&self) -> HashThreadStatus {
+        self.status.read().unwrap().clone()
+    }
+}
+
+/// State owned by the actor task.
+struct Actor<R, W> {
+    status: Arc<RwLock<HashThreadStatus>>,
+    responses: R,
+    commands: W,
+    peripherals: BoardPeripherals,
+    detected: bool,
+    current_task: Option<HashTask>,
+    jobs: JobTracker,
+}
+
+impl<R, W> Actor<R, W>
+where
+    R: Stream<Item = Result<protocol::Response, std::io::Error>> + Unpin,
+    W: Sink<protocol::Command> + Unpin,
+    W::Error: std::fmt::Debug,
+{
+    async fn run(
+        mut self,
+        mut cmd_rx: mpsc::Receiver<ThreadCommand>,
+        _evt_tx: mpsc::Sender<HashThreadEvent>,
+        mut removal_rx: watch::Receiver<ThreadRemovalSignal>,
+    ) {
+        if let Some(ref mut enable) = self.peripherals.asic_enable {
+            if let Err(e) = enable.disable().await {
+                warn!(error = %e, "Failed to disable chips on startup");
+            }
+        }
+
+        let mut poll_ticker = tokio::time::interval(POLL_INTERVAL);
+        poll_ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        loop {
+            tokio::select! {
+                changed = removal_rx.changed() => {
+                    let signal = match changed {
+                        Ok(()) => removal_rx.borrow().clone(),
+                        Err(_) => ThreadRemovalSignal::BoardDisconnected,
+                    };
+                    if signal != ThreadRemovalSignal::Running {
+                        self.status.write().unwrap().is_active = false;
+                        break;
+                    }
+                }
+
+                Some(cmd) = cmd_rx.recv() => match cmd {
+                    ThreadCommand::UpdateTask { new_task, response_tx } => {
+                        response_tx.send(self.assign(new_task, false).await).ok();
+                    }
+                    ThreadCommand::ReplaceTask { new_task, response_tx } => {
+                        response_tx.send(self.assign(new_task, true).await).ok();
+                    }
+                    ThreadCommand::GoIdle { response_tx } => {
+                        debug!("Going idle");
+                        self.status.write().unwrap().is_active = false;
+                        response_tx.send(Ok(self.current_task.take())).ok();
+                    }
+                },
+
+                Some(result) = self.responses.next() => match result {
+                    Ok(response) => self.handle_response(response).await,
+                    Err(e) => error!(error = ?e, "Serial decode error"),
+                },
+
+                _ = poll_ticker.tick(), if self.current_task.is_some() => {
+                    if let Err(e) = self.commands.send(protocol::Command::Poll).await {
+                        warn!(error = ?e, "Failed to poll controller");
+                    }
+                }
+            }
+        }
+
+        debug!("Avalon thread actor exiting");
+    }
+
+    /// Start hashing `task`, detecting the controller first if need be.
+    async fn assign(
+        &mut self,
+        task: HashTask,
+        replace: bool,
+    ) -> Result<Option<HashTask>, HashThreadError> {
+        debug!(new_job = %task.template.id, replace, "Assigning work");
+
+        if !self.detected {
+            self.detect().await?;
+            self.detected = true;
+        }
+        if replace {
+            self.jobs.clear();
+        }
+
+        let job = task_to_job(&task, self.jobs.insert(task.clone()))?;
+        self.commands
+            .send(protocol::Command::Job(job))
+            .await
+            .map_err(|e| {
+                HashThreadError::WorkAssignmentFailed(format!("Failed to send job: {:?}", e))
+            })?;
+
+        self.status.write().unwrap().is_active = true;
+        Ok(self.current_task.replace(task))
+    }
+
+    /// Power up the chips and wait for the controller to identify itself.
+    async fn detect(&mut self) -> Result<(), HashThreadError> {
+        if let Some(ref mut enable) = self.peripherals.asic_enable {
+            enable.enable().await.map_err(|e| {
+                HashThreadError::InitializationFailed(format!("Failed to enable chips: {}", e))
+            })?;
+        }
+
+        self.commands
+            .send(protocol::Command::Detect)
+            .await
+            .map_err(|e| {
+                HashThreadError::InitializationFailed(format!("Failed to send detect: {:?}", e))
+            })?;
+
+        let answer = tokio::time::timeout(DETECT_TIMEOUT, async {
+            while let Some(result) = self.responses.next().await {
+                if let Ok(protocol::Response::AckDetect { dna, firmware }) = result {
+                    return Some((dna, firmware));
+                }
+            }
+            None
+        })
+        .await;
+
+        match answer {
+            Ok(Some((dna, firmware))) => {
+                info!(dna = %hex::encode(dna), %firmware, "Avalon controller detected");
+                Ok(())
+            }
+            _ => Err(HashThreadError::InitializationFailed(
+                "Avalon controller didn't answer detection".into(),
+            )),
+        }
+    }
+
+    async fn handle_response(&mut self, response: protocol::Response) {
+        match response {
+            protocol::Response::Nonce {
+                job_id,
+                chip,
+                ntime_offset,
+                nonce,
+            } => {
+                let Some(task) = self.jobs.get(job_id) else {
+                    trace!(job_id, "Nonce for unknown job_id (possibly stale)");
+                    return;
+                };
+                self.status.write().unwrap().chip_shares_found += 1;
+
+                let Some(share) = check_nonce(task, ntime_offset, nonce) else {
+                    trace!(
+                        job_id,
+                        chip,
+                        nonce = format!("{:#x}", nonce),
+                        "Nonce does not meet target (filtered)"
+                    );
+                    return;
+                };
+                debug!(
+                    job_id,
+                    chip,
+                    nonce = format!("{:#x}", nonce),
+                    hash_diff = %Difficulty::from_hash(&share.hash),
+                    "Share found and sent"
+                );
+                if task.share_tx.send(share).await.is_err() {
+                    debug!("Share channel closed (task replaced)");
+                }
+            }
+            protocol::Response::Status {
+                temperature_c,
+                frequency_mhz,
+            } => {
+                trace!(temperature_c, frequency_mhz, "Controller status");
+                self.status.write().unwrap().temperature_c = Some(f32::from(temperature_c));
+            }
+            protocol::Response::AckDetect { .. } => {
+                trace!("Unsolicited detection answer");
+            }
+        }
+    }
+}
+
+/// Convert a HashTask to an Avalon header job.
+fn task_to_job(task: &HashTask, job_id: u8) -> Result<protocol::Job, HashThreadError> {
+    let header = task_header(task, 0, 0)?;
+    let mut bytes = [0u8; 80];
+    bytes.copy_from_slice(&bitcoin::consensus::serialize(&header));
+
+    Ok(protocol::Job {
+        job_id,
+        header: bytes,
+        target: task.share_target.to_le_bytes(),
+    })
+}
+
+/// Block header for `task` at `ntime_offset` seconds past its ntime.
+fn task_header(
+    task: &HashTask,
+    ntime_offset: u8,
+    nonce: u32,
+) -> Result<BlockHeader, HashThreadError> {
+    let version = task.template.version.base();
+    task.header(version, task.ntime + u32::from(ntime_offset), nonce)
+        .ok_or_else(|| HashThreadError::WorkAssignmentFailed("no merkle root for task".into()))
+}
+
+/// The share a reported nonce makes, if it meets the task's target.
+fn check_nonce(task: &HashTask, ntime_offset: u8, nonce: u32) -> Option<Share> {
+    let header = task_header(task, ntime_offset, nonce).ok()?;
+    task.verify_nonce(&header).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job_source::{
+        Extranonce2, GeneralPurposeBits, JobTemplate, MerkleRootKind, VersionTemplate,
+    };
+    use bitcoin::hashes::Hash;
+    use bitcoin::pow::Target;
+
+    fn task(share_tx: mpsc::Sender<Share>) -> HashTask {
+        // A target every hash meets, so any nonce makes a share
+        let easiest = Target::from_le_bytes([0xff; 32]);
+        let template = Arc::new(JobTemplate {
+            id: "job".into(),
+            prev_blockhash: bitcoin::BlockHash::from_byte_array([0x11; 32]),
+            version: VersionTemplate::new(
+                bitcoin::block::Version::from_consensus(0x2000_0000),
+                GeneralPurposeBits::none(),
+            )
+            .unwrap(),
+            bits: bitcoin::CompactTarget::from_consensus(0x1d00_ffff),
+            share_target: easiest,
+            time: 0x6650_0000,
+            merkle_root: MerkleRootKind::Fixed(bitcoin::TxMerkleNode::from_byte_array([0x22; 32])),
+        });
+        HashTask {
+            template,
+            en2_range: None,
+            en2: Some(Extranonce2::new(0, 1).unwrap()),
+            share_target: easiest,
+            ntime: 0x6650_0000,
+            share_tx,
+        }
+    }
+
+    #[test]
+    fn test_task_to_job_serializes_header() {
+        let (share_tx, _share_rx) = mpsc::channel(1);
+        let task = task(share_tx);
+        let job = task_to_job(&task, 5).unwrap();
+        assert_eq!(job.job_id, 5);
+        assert_eq!(job.header[..4], 0x2000_0000u32.to_le_bytes());
+        assert_eq!(job.header[68..72], 0x6650_0000u32.to_le_bytes());
+        assert_eq!(job.target, [0xff; 32]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_detects_then_turns_nonces_into_shares() {
+        let (response_tx, responses) = futures::channel::mpsc::unbounded();
+        let (commands, mut sent) = futures::channel::mpsc::unbounded();
+        let (_removal_tx, removal_rx) = watch::channel(ThreadRemovalSignal::Running);
+        let mut thread = AvalonThread::new(
+            "avalon".into(),
+            HashRate::from_terahashes(1.0),
+            responses,
+            commands,
+            BoardPeripherals {
+                asic_enable: None,
+                voltage_regulator: None,
+                baud_rate: None,
+                board_fault: None,
+            },
+            removal_rx,
+        );
+
+        // Answer detection only once the thread has asked for it
+        let (share_tx, mut share_rx) = mpsc::channel(4);
+        let controller = async {
+            assert_eq!(sent.next().await, Some(protocol::Command::Detect));
+            response_tx
+                .unbounded_send(Ok(protocol::Response::AckDetect {
+                    dna: [0; 8],
+                    firmware: "test".into(),
+                }))
+                .unwrap();
+        };
+        let (old, ()) = tokio::join!(thread.replace_task(task(share_tx)), controller);
+        assert!(old.unwrap().is_none());
+
+        assert!(matches!(
+            sent.next().await,
+            Some(protocol::Command::Job(protocol::Job { job_id: 0, .. }))
+        ));
+
+        // Any hash meets the easiest target
+        response_tx
+            .unbounded_send(Ok(protocol::Response::Nonce {
+                job_id: 0,
+                chip: 0,
+                ntime_offset: 3,
+                nonce: 42,
+            }))
+            .unwrap();
+        let share = share_rx.recv().await.unwrap();
+        assert_eq!(share.nonce, 42);
+        assert_eq!(share.ntime, 0x6650_0003);
+        assert_eq!(thread.status().chip_shares_found, 1);
+
+        // Nonces for jobs never sent are dropped
+        response_tx
+            .unbounded_send(Ok(protocol::Response::Nonce {
+                job_id: 9,
+                chip: 0,
+                ntime_offset: 0,
+                nonce: 1,
+            }))
+            .unwrap();
+        tokio::task::yield_now().await;
+        assert!(share_rx.try_recv().is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_detection_times_out() {
+        let (_response_tx, responses) = futures::channel::mpsc::unbounded();
+        let (commands, _sent) = futures::channel::mpsc::unbounded();
+        let (_removal_tx, removal_rx) = watch::channel(ThreadRemovalSignal::Running);
+        let mut thread = AvalonThread::new(
+            "avalon".into(),
+            HashRate::from_terahashes(1.0),
+            responses,
+            commands,
+            BoardPeripherals {
+                asic_enable: None,
+                voltage_regulator: None,
+                baud_rate: None,
+                board_fault: None,
+            },
+            removal_rx,
+        );
+
+        let (share_tx, _share_rx) = mpsc::channel(1);
+        assert!(matches!(
+            thread.replace_task(task(share_tx)).await,
+            Err(HashThreadError::InitializationFailed(_))
+        ));
+    }
+}
@@ -3,6 +3,11 @@
 //! This module handles the encoding and decoding of commands and responses
 //! for BM13xx family chips (BM1366, BM1370, etc).
 //!
+//! The older BM1387 and BM1397 speak a variant of the same protocol (see
+//! [`ProtocolVariant`]): they take jobs as host-computed SHA-256 midstates
+//! rather than full headers, don't roll version bits themselves, and answer
+//! in shorter frames. The variant comes from the chip type the board declares.
+//!
 //! TODO: Remove redundancy in BM13xx protocol implementation
 //! - Consolidate CRC validation logic between decoder and dissector code paths
 //! - Extract common frame parsing utilities to reduce duplication
@@ -12,12 +17,13 @@
 use bitcoin::hashes::Hash;
 use bitvec::prelude::*;
 use bytes::{Buf, BufMut, BytesMut};
-use std::{fmt, io};
+use std::{fmt, io, sync::Arc};
 use strum::FromRepr;
 use tokio_util::codec::{Decoder, Encoder};
 
 use super::crc::{crc16, crc5, crc5_is_valid};
 use super::error::ProtocolError;
+use super::rx::RxStats;
 use crate::job_source::GeneralPurposeBits;
 use crate::tracing::prelude::*;
 
@@ -174,7 +180,9 @@ pub enum ChipType {
     /// BM1370 - Used in Bitaxe Gamma and Antminer S21 Pro
     /// 1,280 hash engines organized as 80 domains of 16 engines each
     BM1370,
-    /// BM1397 - Previous generation chip
+    /// BM1387 - Used in Antminer S9
+    BM1387,
+    /// BM1397 - Used in Antminer S17 and Bitaxe Max
     BM1397,
     /// Unknown chip type with raw ID bytes
     Unknown([u8; 2]),
@@ -187,11 +195,20 @@ impl ChipType {
             Self::BM1362 => [0x13, 0x62],
             Self::BM1366 => [0x13, 0x66],
             Self::BM1370 => [0x13, 0x70],
+            Self::BM1387 => [0x13, 0x87],
             Self::BM1397 => [0x13, 0x97],
             Self::Unknown(bytes) => *bytes,
         }
     }
 
+    /// Protocol variant the chip speaks
+    pub fn variant(&self) -> ProtocolVariant {
+        match self {
+            Self::BM1387 | Self::BM1397 => ProtocolVariant::Midstate,
+            _ => ProtocolVariant::FullHeader,
+        }
+    }
+
     /// Get expected hash engine count for this chip type, if known
     pub fn core_count(&self) -> Option<u32> {
         match self {
@@ -199,6 +216,15 @@ impl ChipType {
             _ => None,
         }
     }
+
+    /// Cores and small cores per core, for chips whose nonces say which
+    /// found them
+    pub fn core_layout(&self) -> Option<(u8, u8)> {
+        match self {
+            Self::BM1370 => Some((80, 16)),
+            _ => None,
+        }
+    }
 }
 
 impl From<[u8; 2]> for ChipType {
@@ -207,6 +233,7 @@ impl From<[u8; 2]> for ChipType {
             [0x13, 0x62] => Self::BM1362,
             [0x13, 0x66] => Self::BM1366,
             [0x13, 0x70] => Self::BM1370,
+            [0x13, 0x87] => Self::BM1387,
             [0x13, 0x97] => Self::BM1397,
             _ => Self::Unknown(bytes),
         }
@@ -219,6 +246,41 @@ impl From<ChipType> for [u8; 2] {
     }
 }
 
+/// How a generation of chips takes work and reports nonces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProtocolVariant {
+    /// BM1362, BM1366 and BM1370: full block headers, version bits rolled on
+    /// chip, and nonce responses carrying the rolled bits
+    #[default]
+    FullHeader,
+    /// BM1387 and BM1397: up to four host-computed midstates per job, one per
+    /// block version, and nonce responses naming the midstate instead
+    Midstate,
+}
+
+impl ProtocolVariant {
+    /// Response frame length after the preamble, CRC included
+    pub fn response_len(self) -> usize {
+        match self {
+            Self::FullHeader => 9,
+            Self::Midstate => 7,
+        }
+    }
+
+    /// Whether the chips roll version bits themselves
+    pub fn rolls_version(self) -> bool {
+        self == Self::FullHeader
+    }
+
+    /// Most midstates a job can carry
+    pub fn max_midstates(self) -> usize {
+        match self {
+            Self::FullHeader => 1,
+            Self::Midstate => 4,
+        }
+    }
+}
+
 /// Nonce range configuration for work distribution.
 ///
 /// NOTE: We store this as a byte array rather than interpreting it as a u32
@@ -345,7 +407,9 @@ impl ReportingRate {
 }
 
 /// Reporting interval: report 1 nonce per 2^exponent hashes
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///
+/// Intervals order by length, so a longer interval filters harder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct ReportingInterval {
     exponent: u8, // N in "report 1 nonce per 2^N hashes"
 }
@@ -374,9 +438,30 @@ impl ReportingInterval {
         Self::from_exponent(total_bits.clamp(32, 56))
     }
 
+    /// Longest interval that still reports every nonce meeting
+    /// `difficulty`
+    ///
+    /// The chips filter at powers of two, so the difficulty is rounded
+    /// down to one.
+    ///
+    /// # Example
+    /// ```
+    /// use mujina_miner::asic::bm13xx::protocol::ReportingInterval;
+    /// assert_eq!(ReportingInterval::for_difficulty(1000.0).difficulty(), 512);
+    /// ```
+    pub fn for_difficulty(difficulty: f64) -> Self {
+        let zero_bits = difficulty.max(1.0).log2().floor().min(24.0) as u8;
+        Self::from_exponent(32 + zero_bits)
+    }
+
     pub const fn exponent(&self) -> u8 {
         self.exponent
     }
+
+    /// Lowest difficulty of the nonces reported at this interval
+    pub const fn difficulty(&self) -> u64 {
+        1 << (self.exponent - 32)
+    }
 }
 
 impl std::fmt::Display for ReportingInterval {
@@ -474,6 +559,28 @@ pub enum BaudRate {
     Custom(u32),
 }
 
+impl BaudRate {
+    /// Rate with a known register value for the given bits per second.
+    pub fn from_bps(bps: u32) -> Option<Self> {
+        match bps {
+            115_200 => Some(BaudRate::Baud115200),
+            1_000_000 => Some(BaudRate::Baud1M),
+            3_000_000 => Some(BaudRate::Baud3M),
+            _ => None,
+        }
+    }
+
+    /// Bits per second, if known.
+    pub fn bps(&self) -> Option<u32> {
+        match self {
+            BaudRate::Baud115200 => Some(115_200),
+            BaudRate::Baud1M => Some(1_000_000),
+            BaudRate::Baud3M => Some(3_000_000),
+            BaudRate::Custom(_) => None,
+        }
+    }
+}
+
 impl From<BaudRate> for [u8; 4] {
     fn from(baud: BaudRate) -> Self {
         let value = match baud {
@@ -536,9 +643,13 @@ impl IoDriverStrength {
 }
 
 /// Version mask for version rolling
+///
+/// The mask selects which of the general purpose bits (BIP320, version bits
+/// 13-28) the chip may roll. It goes on the wire big-endian, in the same order
+/// the chip reports rolled bits in nonce responses.
 #[derive(Clone, Copy, PartialEq)]
 pub struct VersionMask {
-    /// Which bits can be rolled
+    /// Which bits can be rolled (bit 0 is version bit 13)
     mask: u16,
     /// Enable flag and other control bits
     control: u16,
@@ -557,6 +668,21 @@ impl VersionMask {
             control: Self::ENABLE_ROLLING,
         }
     }
+
+    /// Create version mask allowing only the given general purpose bits.
+    ///
+    /// An empty mask stops the chip from rolling the version at all.
+    pub fn from_gp_bits(gp_bits: GeneralPurposeBits) -> Self {
+        Self {
+            mask: u16::from_be_bytes(*gp_bits.as_bytes()),
+            control: Self::ENABLE_ROLLING,
+        }
+    }
+
+    /// General purpose bits the chip may roll.
+    pub fn gp_bits(&self) -> GeneralPurposeBits {
+        GeneralPurposeBits::new(self.mask.to_be_bytes())
+    }
 }
 
 impl fmt::Debug for VersionMask {
@@ -577,7 +703,7 @@ impl From<VersionMask> for [u8; 4] {
     fn from(mask: VersionMask) -> Self {
         let mut bytes = [0u8; 4];
         bytes[0..2].copy_from_slice(&mask.control.to_le_bytes());
-        bytes[2..4].copy_from_slice(&mask.mask.to_le_bytes());
+        bytes[2..4].copy_from_slice(&mask.mask.to_be_bytes());
         bytes
     }
 }
@@ -590,12 +716,15 @@ pub enum RegisterAddress {
     NonceRange = 0x10,
     TicketMask = 0x14,
     MiscControl = 0x18,
+    OrderedClockEnable = 0x20,
     UartBaud = 0x28,
     UartRelay = 0x2C,
     Core = 0x3C,
     AnalogMux = 0x54,
     IoDriverStrength = 0x58,
     Pll3Parameter = 0x68,
+    ClockOrderControl0 = 0x80,
+    ClockOrderControl1 = 0x84,
     VersionMask = 0xA4,
     InitControl = 0xA8,
     MiscSettings = 0xB9,
@@ -614,6 +743,10 @@ pub enum Register {
     MiscControl {
         raw_value: u32,
     },
+    /// BM1397 only: which clock domains run
+    OrderedClockEnable {
+        raw_value: u32,
+    },
     UartBaud(BaudRate),
     UartRelay {
         raw_value: u32, // Domain relay configuration (complex format)
@@ -628,6 +761,14 @@ pub enum Register {
     Pll3Parameter {
         raw_value: u32,
     },
+    /// BM1397 only: clock domain ordering, low half
+    ClockOrderControl0 {
+        raw_value: u32,
+    },
+    /// BM1397 only: clock domain ordering, high half
+    ClockOrderControl1 {
+        raw_value: u32,
+    },
     VersionMask(VersionMask),
     InitControl {
         raw_value: u32,
@@ -656,18 +797,22 @@ impl Register {
                 Register::TicketMask(TicketMask { zero_bits })
             }
             RegisterAddress::MiscControl => Register::MiscControl { raw_value },
+            RegisterAddress::OrderedClockEnable => Register::OrderedClockEnable { raw_value },
             RegisterAddress::UartBaud => {
                 // Decode known baud rates
                 let baud = match raw_value {
                     0x00000271 => BaudRate::Baud115200,
-                    0x00000130 => BaudRate::Baud1M,
+                    0x00023011 => BaudRate::Baud1M,
                     0x00003001 => BaudRate::Baud3M,
                     other => BaudRate::Custom(other),
                 };
                 Register::UartBaud(baud)
             }
             RegisterAddress::UartRelay => Register::UartRelay { raw_value },
-            RegisterAddress::Core => Register::Core { raw_value },
+            RegisterAddress::Core => Register::Core {
+                // Big-endian on the wire, unlike its neighbours
+                raw_value: u32::from_be_bytes(*bytes),
+            },
             RegisterAddress::AnalogMux => Register::AnalogMux { raw_value },
             RegisterAddress::IoDriverStrength => {
                 // Parse driver strength from raw value
@@ -678,8 +823,10 @@ impl Register {
                 Register::IoDriverStrength(IoDriverStrength { strengths })
             }
             RegisterAddress::Pll3Parameter => Register::Pll3Parameter { raw_value },
+            RegisterAddress::ClockOrderControl0 => Register::ClockOrderControl0 { raw_value },
+            RegisterAddress::ClockOrderControl1 => Register::ClockOrderControl1 { raw_value },
             RegisterAddress::VersionMask => {
-                let mask = (raw_value >> 16) as u16;
+                let mask = ((raw_value >> 16) as u16).swap_bytes();
                 let control = (raw_value & 0xffff) as u16;
                 Register::VersionMask(VersionMask { mask, control })
             }
@@ -696,12 +843,15 @@ impl Register {
             Register::NonceRange(_) => RegisterAddress::NonceRange,
             Register::TicketMask(_) => RegisterAddress::TicketMask,
             Register::MiscControl { .. } => RegisterAddress::MiscControl,
+            Register::OrderedClockEnable { .. } => RegisterAddress::OrderedClockEnable,
             Register::UartBaud(_) => RegisterAddress::UartBaud,
             Register::UartRelay { .. } => RegisterAddress::UartRelay,
             Register::Core { .. } => RegisterAddress::Core,
             Register::AnalogMux { .. } => RegisterAddress::AnalogMux,
             Register::IoDriverStrength(_) => RegisterAddress::IoDriverStrength,
             Register::Pll3Parameter { .. } => RegisterAddress::Pll3Parameter,
+            Register::ClockOrderControl0 { .. } => RegisterAddress::ClockOrderControl0,
+            Register::ClockOrderControl1 { .. } => RegisterAddress::ClockOrderControl1,
             Register::VersionMask(_) => RegisterAddress::VersionMask,
             Register::InitControl { .. } => RegisterAddress::InitControl,
             Register::MiscSettings { .. } => RegisterAddress::MiscSettings,
@@ -741,9 +891,12 @@ impl Register {
                 dst.put_u32(*raw_value);
             }
             Register::MiscControl { raw_value }
+            | Register::OrderedClockEnable { raw_value }
             | Register::UartRelay { raw_value }
             | Register::AnalogMux { raw_value }
             | Register::Pll3Parameter { raw_value }
+            | Register::ClockOrderControl0 { raw_value }
+            | Register::ClockOrderControl1 { raw_value }
             | Register::InitControl { raw_value }
             | Register::MiscSettings { raw_value } => {
                 dst.put_u32_le(*raw_value);
@@ -782,17 +935,23 @@ impl std::fmt::Debug for Register {
             }
             Register::VersionMask(mask) => f.debug_tuple("VersionMask").field(mask).finish(),
             Register::MiscControl { raw_value }
+            | Register::OrderedClockEnable { raw_value }
             | Register::UartRelay { raw_value }
             | Register::AnalogMux { raw_value }
             | Register::Pll3Parameter { raw_value }
+            | Register::ClockOrderControl0 { raw_value }
+            | Register::ClockOrderControl1 { raw_value }
             | Register::InitControl { raw_value }
             | Register::Core { raw_value }
             | Register::MiscSettings { raw_value } => {
                 let register_name = match self {
                     Register::MiscControl { .. } => "MiscControl",
+                    Register::OrderedClockEnable { .. } => "OrderedClockEnable",
                     Register::UartRelay { .. } => "UartRelay",
                     Register::AnalogMux { .. } => "AnalogMux",
                     Register::Pll3Parameter { .. } => "Pll3Parameter",
+                    Register::ClockOrderControl0 { .. } => "ClockOrderControl0",
+                    Register::ClockOrderControl1 { .. } => "ClockOrderControl1",
                     Register::InitControl { .. } => "InitControl",
                     Register::Core { .. } => "Core",
                     Register::MiscSettings { .. } => "MiscSettings",
@@ -806,12 +965,14 @@ impl std::fmt::Debug for Register {
     }
 }
 
+#[derive(FromRepr)]
 #[repr(u8)]
 enum CommandFlagsType {
     Job = 1,
     Command = 2,
 }
 
+#[derive(FromRepr)]
 #[repr(u8)]
 enum CommandFlagsCmd {
     SetChipAddress = 0,
@@ -841,6 +1002,25 @@ pub fn hash_to_wire_bytes(hash: &[u8; 32]) -> [u8; 32] {
     wire_bytes
 }
 
+/// SHA-256 midstate of a header's first 64 bytes, in BM13xx wire format.
+///
+/// The midstate is the hash state after the first compression round, eight
+/// 32-bit words. Midstate-format chips take the words in reverse order, each
+/// big-endian, which is [`hash_to_wire_bytes`] applied to the usual
+/// big-endian serialization.
+pub fn header_midstate(header: &bitcoin::block::Header) -> [u8; 32] {
+    use bitcoin::consensus::Encodable;
+    use bitcoin::hashes::{sha256, HashEngine};
+
+    let mut bytes = [0u8; 80];
+    header
+        .consensus_encode(&mut &mut bytes[..])
+        .expect("a header fits in 80 bytes");
+    let mut engine = sha256::Hash::engine();
+    engine.input(&bytes[..64]);
+    hash_to_wire_bytes(&engine.midstate().to_byte_array())
+}
+
 /// Convert BM13xx wire format to Bitcoin internal hash format.
 ///
 /// Inverse of `hash_to_wire_bytes`. Takes wire bytes and reverses the 4-byte
@@ -934,6 +1114,20 @@ impl Command {
         flags
     }
 
+    /// Bytes the command takes on the wire, preamble and CRC included.
+    fn frame_len(&self) -> usize {
+        const PREAMBLE_LEN: usize = 2;
+        PREAMBLE_LEN
+            + match self {
+                Command::SetChipAddress { .. }
+                | Command::ChainInactive
+                | Command::ReadRegister { .. } => 5,
+                Command::WriteRegister { .. } => 9,
+                Command::JobFull { .. } => 86,
+                Command::JobMidstate { job_data } => 22 + 32 * usize::from(job_data.num_midstates),
+            }
+    }
+
     fn encode(&self, dst: &mut BytesMut) {
         match self {
             Command::SetChipAddress { chip_address } => {
@@ -1103,6 +1297,143 @@ impl Command {
             }
         }
     }
+
+    /// Decode a complete command frame, preamble and CRC included.
+    ///
+    /// The inverse of encoding through [`FrameCodec`], for tools watching the
+    /// host-to-chip line. The frame must be exactly as long as its length
+    /// field says, and its CRC must match.
+    pub fn decode(frame: &[u8]) -> Result<Command, ProtocolError> {
+        const PREAMBLE: [u8; 2] = [0x55, 0xaa];
+        const HEADER_LEN: usize = PREAMBLE.len() + 2; // flags, length
+
+        if frame.len() < HEADER_LEN + 1 {
+            return Err(ProtocolError::BufferTooSmall {
+                need: HEADER_LEN + 1,
+                have: frame.len(),
+            });
+        }
+        if frame[..2] != PREAMBLE || frame.len() != PREAMBLE.len() + frame[3] as usize {
+            return Err(ProtocolError::InvalidFrame);
+        }
+
+        let flags = frame[2].view_bits::<Lsb0>();
+        let typ = CommandFlagsType::from_repr(flags[5..7].load::<u8>())
+            .ok_or(ProtocolError::InvalidFrame)?;
+        let broadcast = flags[4];
+        let cmd = CommandFlagsCmd::from_repr(flags[0..4].load::<u8>())
+            .ok_or(ProtocolError::InvalidFrame)?;
+
+        // Jobs carry CRC16 (big-endian on the wire), everything else CRC5
+        let body = match typ {
+            CommandFlagsType::Job => {
+                let (payload, crc) = frame.split_at(frame.len() - 2);
+                if payload.len() < HEADER_LEN
+                    || crc16(&payload[2..]).to_be_bytes() != [crc[0], crc[1]]
+                {
+                    return Err(ProtocolError::InvalidFrame);
+                }
+                &payload[HEADER_LEN..]
+            }
+            CommandFlagsType::Command => {
+                let (payload, crc) = frame.split_at(frame.len() - 1);
+                if crc5(&payload[2..]) != crc[0] {
+                    return Err(ProtocolError::InvalidFrame);
+                }
+                &payload[HEADER_LEN..]
+            }
+        };
+
+        let register_address = |repr: u8| {
+            RegisterAddress::from_repr(repr).ok_or(ProtocolError::InvalidRegisterAddress(repr))
+        };
+
+        match (typ, cmd, body) {
+            (CommandFlagsType::Job, CommandFlagsCmd::WriteRegisterOrJob, body) => {
+                Self::decode_job(body)
+            }
+            (CommandFlagsType::Command, CommandFlagsCmd::SetChipAddress, &[chip_address, _]) => {
+                Ok(Command::SetChipAddress { chip_address })
+            }
+            (CommandFlagsType::Command, CommandFlagsCmd::ChainInactive, &[_, _]) => {
+                Ok(Command::ChainInactive)
+            }
+            (CommandFlagsType::Command, CommandFlagsCmd::ReadRegister, &[chip_address, reg]) => {
+                Ok(Command::ReadRegister {
+                    broadcast,
+                    chip_address,
+                    register_address: register_address(reg)?,
+                })
+            }
+            (
+                CommandFlagsType::Command,
+                CommandFlagsCmd::WriteRegisterOrJob,
+                &[chip_address, reg, a, b, c, d],
+            ) => Ok(Command::WriteRegister {
+                broadcast,
+                chip_address,
+                register: Register::decode(register_address(reg)?, &[a, b, c, d]),
+            }),
+            _ => Err(ProtocolError::InvalidFrame),
+        }
+    }
+
+    /// Decode job data, telling full-header and midstate jobs apart by length.
+    ///
+    /// A midstate job carrying two midstates is the same length as a full
+    /// job and decodes as one; midstate chips are sent one or four.
+    fn decode_job(body: &[u8]) -> Result<Command, ProtocolError> {
+        const FULL_LEN: usize = 82;
+        const MIDSTATE_BASE_LEN: usize = 18;
+        const MIDSTATE_LEN: usize = 32;
+
+        let word = |at: usize| u32::from_le_bytes(body[at..at + 4].try_into().unwrap());
+        let array4 = |at: usize| -> [u8; 4] { body[at..at + 4].try_into().unwrap() };
+        let hash = |at: usize| -> [u8; 32] { body[at..at + 32].try_into().unwrap() };
+
+        if body.len() == FULL_LEN {
+            return Ok(Command::JobFull {
+                job_data: JobFullFormat {
+                    job_id: body[0] >> 3,
+                    num_midstates: body[1],
+                    starting_nonce: word(2),
+                    nbits: bitcoin::CompactTarget::from_consensus(word(6)),
+                    ntime: word(10),
+                    merkle_root: bitcoin::hash_types::TxMerkleNode::from_byte_array(
+                        hash_from_wire_bytes(&hash(14)),
+                    ),
+                    prev_block_hash: bitcoin::BlockHash::from_byte_array(hash_from_wire_bytes(
+                        &hash(46),
+                    )),
+                    version: bitcoin::block::Version::from_consensus(word(78) as i32),
+                },
+            });
+        }
+
+        let num_midstates = *body.get(1).ok_or(ProtocolError::InvalidFrame)?;
+        if !(1..=4).contains(&num_midstates)
+            || body.len() != MIDSTATE_BASE_LEN + num_midstates as usize * MIDSTATE_LEN
+        {
+            return Err(ProtocolError::InvalidFrame);
+        }
+        let midstate =
+            |i: u8| (i < num_midstates).then(|| hash(MIDSTATE_BASE_LEN + i as usize * 32));
+
+        Ok(Command::JobMidstate {
+            job_data: JobMidstateFormat {
+                job_id: body[0] >> 3,
+                num_midstates,
+                starting_nonce: array4(2),
+                nbits: array4(6),
+                ntime: array4(10),
+                merkle4: array4(14),
+                midstate0: hash(MIDSTATE_BASE_LEN),
+                midstate1: midstate(1),
+                midstate2: midstate(2),
+                midstate3: midstate(3),
+            },
+        })
+    }
 }
 
 #[derive(FromRepr)]
@@ -1129,20 +1460,21 @@ pub enum Response {
 }
 
 impl Response {
-    fn decode(bytes: &mut BytesMut) -> Result<Response, ProtocolError> {
+    /// Decode a frame's bytes after the preamble, in place.
+    fn decode(mut bytes: &[u8], variant: ProtocolVariant) -> Result<Response, ProtocolError> {
         let type_and_crc = bytes[bytes.len() - 1].view_bits::<Lsb0>();
         let type_repr = type_and_crc[5..].load::<u8>();
 
         match ResponseType::from_repr(type_repr) {
             Some(ResponseType::ReadRegister) => {
-                let value_bytes = bytes.split_to(4);
-                let value: [u8; 4] =
-                    value_bytes[..]
-                        .try_into()
-                        .map_err(|_| ProtocolError::BufferTooSmall {
-                            need: 4,
-                            have: value_bytes.len(),
-                        })?;
+                if bytes.remaining() < 6 {
+                    return Err(ProtocolError::BufferTooSmall {
+                        need: 6,
+                        have: bytes.remaining(),
+                    });
+                }
+                let mut value = [0; 4];
+                bytes.copy_to_slice(&mut value);
                 let chip_address = bytes.get_u8();
                 let register_address_repr = bytes.get_u8();
 
@@ -1156,6 +1488,25 @@ impl Response {
                     Err(ProtocolError::InvalidRegisterAddress(register_address_repr))
                 }
             }
+            Some(ResponseType::Nonce) if variant == ProtocolVariant::Midstate => {
+                // BM1397 nonce response format (9 bytes total, including preamble):
+                // nonce(4) + chip-local byte(1) + job byte(1) + crc(1)
+                //
+                // The job byte echoes the job header, job_id in bits 6-3,
+                // with the index of the midstate that found the nonce in
+                // bits 1-0. There's no version field: the midstate implies it.
+                let nonce = bytes.get_u32_le();
+                let _chip_local = bytes.get_u8();
+                let job_byte = bytes.get_u8();
+
+                Ok(Response::Nonce {
+                    nonce,
+                    job_id: (job_byte >> 3) & 0x0f,
+                    midstate_num: job_byte & 0x03,
+                    version: GeneralPurposeBits::none(),
+                    subcore_id: 0,
+                })
+            }
             Some(ResponseType::Nonce) => {
                 // BM1370 nonce response format (11 bytes total, including preamble):
                 // Already consumed: preamble (2 bytes)
@@ -1188,39 +1539,88 @@ impl Response {
     }
 }
 
-#[derive(Default)]
-pub struct FrameCodec;
+/// Encoder for BM13xx commands and decoder for their responses.
+#[derive(Debug, Default, Clone)]
+pub struct FrameCodec {
+    variant: ProtocolVariant,
+    stats: Option<Arc<RxStats>>,
+    /// Bytes skipped since the last good frame
+    garbage_run: usize,
+}
+
+impl FrameCodec {
+    /// Codec that records what its decoder sees in `stats`.
+    pub fn with_stats(stats: Arc<RxStats>) -> Self {
+        Self {
+            stats: Some(stats),
+            ..Self::default()
+        }
+    }
+
+    /// Decode responses in `variant`'s frame format.
+    pub fn with_variant(mut self, variant: ProtocolVariant) -> Self {
+        self.variant = variant;
+        self
+    }
+
+    fn skip(&mut self, src: &mut BytesMut, bytes: usize) {
+        // Past this much garbage the line has likely lost sync rather than
+        // glitched; say so once per run
+        const GARBAGE_WARN_BYTES: usize = 256;
+
+        src.advance(bytes);
+        if let Some(stats) = &self.stats {
+            stats.record_skipped(bytes);
+        }
+
+        let before = self.garbage_run;
+        self.garbage_run += bytes;
+        if before < GARBAGE_WARN_BYTES && self.garbage_run >= GARBAGE_WARN_BYTES {
+            warn!(
+                skipped = self.garbage_run,
+                "No valid BM13xx frame in a long run of received bytes"
+            );
+        }
+    }
+}
 
 impl Encoder<Command> for FrameCodec {
     type Error = io::Error;
 
     fn encode(&mut self, command: Command, dst: &mut BytesMut) -> Result<(), Self::Error> {
         const PREAMBLE: [u8; 2] = [0x55, 0xaa];
+
+        // The writer's buffer is reused from frame to frame; reserving the
+        // whole frame up front means it grows at most once, and not at all
+        // once it has held the largest frame
+        let frame_start = dst.len();
+        dst.reserve(command.frame_len());
         dst.put_slice(&PREAMBLE);
 
         let start_pos = dst.len();
         command.encode(dst);
 
-        // Jobs use CRC16, other commands use CRC5
+        // Jobs use CRC16, other commands use CRC5. Both cover everything
+        // after the preamble of this frame; the buffer may already hold
+        // frames not yet written out.
         match &command {
             Command::JobFull { .. } | Command::JobMidstate { .. } => {
-                // Calculate CRC16 over flags + length + data
                 let crc = crc16(&dst[start_pos..]);
                 // Wire format: CRC transmitted big-endian (high byte, low byte)
                 dst.put_slice(&crc.to_be_bytes());
             }
             _ => {
-                // Calculate CRC5 over everything after preamble
-                let crc = crc5(&dst[2..]);
+                let crc = crc5(&dst[start_pos..]);
                 dst.put_u8(crc);
             }
         }
+        debug_assert_eq!(dst.len() - frame_start, command.frame_len());
 
         // Log the encoded frame for debugging
         trace!(
             cmd = ?command,
-            bytes = dst.len(),
-            frame = %HexBytes(dst.as_ref()),
+            bytes = dst.len() - frame_start,
+            frame = %HexBytes(&dst[frame_start..]),
             "TX BM13xx"
         );
 
@@ -1233,75 +1633,78 @@ impl Decoder for FrameCodec {
     type Error = io::Error;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        // Return Ok(Item) with a valid frame, or Ok(None) if to be called again, potentially with
-        // more data. Returning an Error causes the stream to be terminated, so don't do that.
+        // Return Ok(Item) with a valid frame, or Ok(None) if more data is needed. Returning an
+        // Error causes the stream to be terminated, so don't do that.
         //
-        // There are three cases:
+        // FramedRead won't call again after Ok(None) until more bytes arrive, so resynchronize
+        // fully within this call. Otherwise a valid frame sitting behind line noise would go
+        // unread until the chips happened to send something else.
         //
-        // 1. More data needed
-        // 2. Invalid frame
-        // 3. Valid frame
+        // There are three cases at each candidate preamble:
         //
-        // In the case of an invalid frame, consume the first byte and request another call by
-        // returning Ok(None). In the case of a valid frame, consume that frame's worth of bytes.
+        // 1. More data needed
+        // 2. Invalid frame: skip past this preamble's first byte and look for the next
+        // 3. Valid frame: consume that frame's worth of bytes
 
         const PREAMBLE: [u8; 2] = [0xaa, 0x55];
-        // All BM13xx responses are 11 bytes (2 preamble + 9 data)
-        const FRAME_LEN: usize = PREAMBLE.len() + 9;
         const CALL_AGAIN: Result<Option<Response>, io::Error> = Ok(None);
 
-        if src.len() < FRAME_LEN {
-            return CALL_AGAIN;
-        }
-
-        // Check preamble without consuming the buffer
-        if src[0] != PREAMBLE[0] {
-            src.advance(1);
-            return CALL_AGAIN;
-        }
-
-        if src[1] != PREAMBLE[1] {
-            src.advance(1);
-            return CALL_AGAIN;
-        }
-
-        // Validate CRC5 over the entire frame (excluding preamble)
-        // CRC5 is computed over the 9 data bytes after the preamble
-        if !crc5_is_valid(&src[2..FRAME_LEN]) {
-            trace!(
-                "Frame sync lost: CRC5 failed for potential frame at position 0. Searching for next frame..."
-            );
-            src.advance(1);
-            return CALL_AGAIN;
-        }
-
-        // We have a valid frame with correct CRC
-        // Save the frame bytes before consuming
-        let frame_bytes = src[..FRAME_LEN].to_vec();
-
-        // Create a buffer for decoding
-        let mut decode_buf = BytesMut::from(&src[..FRAME_LEN]);
-        decode_buf.advance(2); // Skip preamble for Response::decode
+        loop {
+            // Drop everything before the next preamble
+            match src.windows(PREAMBLE.len()).position(|w| w == PREAMBLE) {
+                Some(0) => {}
+                Some(offset) => {
+                    trace!(skipped = offset, "BM13xx RX resync");
+                    self.skip(src, offset);
+                }
+                None => {
+                    // Keep a trailing 0xaa, which may begin a preamble still in flight
+                    let keep = usize::from(src.last() == Some(&PREAMBLE[0]));
+                    self.skip(src, src.len() - keep);
+                    return CALL_AGAIN;
+                }
+            }
 
-        match Response::decode(&mut decode_buf) {
-            Ok(response) => {
-                // Only advance if decode was successful
-                src.advance(FRAME_LEN);
+            // Responses are fixed length within a variant: 11 bytes for
+            // current chips, 9 for midstate-format ones
+            let frame_len = PREAMBLE.len() + self.variant.response_len();
+            if src.len() < frame_len {
+                return CALL_AGAIN;
+            }
 
-                // Log the received frame for debugging
+            // Validate CRC5 over the data bytes after the preamble
+            if !crc5_is_valid(&src[2..frame_len]) {
                 trace!(
-                    resp = ?response,
-                    bytes = FRAME_LEN,
-                    frame = %HexBytes(&frame_bytes),
-                    "RX BM13xx"
+                    frame = %HexBytes(&src[..frame_len]),
+                    "BM13xx RX CRC5 failed, searching for next frame"
                 );
-                Ok(Some(response))
+                if let Some(stats) = &self.stats {
+                    stats.record_crc_error();
+                }
+                self.skip(src, 1);
+                continue;
             }
-            Err(err) => {
-                warn!("Failed to decode response: {}", err);
-                // Advance by 1 to try to find next valid frame
-                src.advance(1);
-                CALL_AGAIN
+
+            match Response::decode(&src[PREAMBLE.len()..frame_len], self.variant) {
+                Ok(response) => {
+                    trace!(
+                        resp = ?response,
+                        bytes = frame_len,
+                        frame = %HexBytes(&src[..frame_len]),
+                        "RX BM13xx"
+                    );
+                    src.advance(frame_len);
+                    self.garbage_run = 0;
+                    if let Some(stats) = &self.stats {
+                        stats.record_frame();
+                    }
+                    return Ok(Some(response));
+                }
+                Err(err) => {
+                    // CRC5 passes one false preamble in 32, so step past just this byte
+                    warn!("Failed to decode response: {}", err);
+                    self.skip(src, 1);
+                }
             }
         }
     }
@@ -1311,6 +1714,43 @@ impl Decoder for FrameCodec {
 mod init_tests {
     use super::*;
 
+    #[test]
+    fn legacy_chip_init_sequence() {
+        let protocol = BM13xxProtocol::new();
+        let ticket_mask = TicketMask::new(ReportingInterval::from_rate(
+            Hashrate::gibihashes_per_sec(512.0),
+            ReportingRate::nonces_per_sec(1.0),
+        ));
+
+        let bm1397 = protocol.legacy_chip_init(ChipType::BM1397, ticket_mask);
+        assert!(matches!(bm1397[0], Command::ChainInactive));
+        assert!(bm1397.iter().any(|c| matches!(
+            c,
+            Command::WriteRegister {
+                register: Register::OrderedClockEnable { .. },
+                ..
+            }
+        )));
+        // No version rolling to configure on these chips
+        assert!(!bm1397.iter().any(|c| matches!(
+            c,
+            Command::WriteRegister {
+                register: Register::VersionMask(_),
+                ..
+            }
+        )));
+        assert!(matches!(
+            bm1397.last(),
+            Some(Command::WriteRegister {
+                register: Register::TicketMask(_),
+                ..
+            })
+        ));
+
+        let bm1387 = protocol.legacy_chip_init(ChipType::BM1387, ticket_mask);
+        assert_eq!(bm1387.len(), 3);
+    }
+
     #[test]
     fn multi_chip_init_sequence() {
         let protocol = BM13xxProtocol::new();
@@ -1538,6 +1978,31 @@ mod init_tests {
 mod command_tests {
     use super::*;
 
+    #[test]
+    fn header_midstate_in_wire_order() {
+        use sha2::digest::generic_array::GenericArray;
+
+        let header = bitcoin::block::Header {
+            version: bitcoin::block::Version::from_consensus(0x2000_0000),
+            prev_blockhash: bitcoin::BlockHash::from_byte_array([0x11; 32]),
+            merkle_root: bitcoin::TxMerkleNode::from_byte_array([0x22; 32]),
+            time: 0x6650_0000,
+            bits: bitcoin::CompactTarget::from_consensus(0x1703_4219),
+            nonce: 0,
+        };
+        let bytes = bitcoin::consensus::serialize(&header);
+
+        // One compression from the SHA-256 IV, words last to first
+        let mut state: [u32; 8] = [
+            0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+            0x5be0cd19,
+        ];
+        sha2::compress256(&mut state, &[*GenericArray::from_slice(&bytes[..64])]);
+        let expected: Vec<u8> = state.iter().rev().flat_map(|w| w.to_be_bytes()).collect();
+
+        assert_eq!(header_midstate(&header).to_vec(), expected);
+    }
+
     #[test]
     fn read_register() {
         assert_frame_eq(
@@ -1584,6 +2049,18 @@ mod command_tests {
         );
     }
 
+    #[test]
+    fn write_version_mask_from_pool_mask() {
+        // Stratum mask 0x1fffe000 without its top bit: GP bits 0x7fff
+        let mask = VersionMask::from_gp_bits(GeneralPurposeBits::new([0x7f, 0xff]));
+        assert_eq!(<[u8; 4]>::from(mask), [0x90, 0x00, 0x7f, 0xff]);
+        assert_eq!(mask.gp_bits(), GeneralPurposeBits::new([0x7f, 0xff]));
+
+        // No rolling authorized: empty mask
+        let mask = VersionMask::from_gp_bits(GeneralPurposeBits::none());
+        assert_eq!(<[u8; 4]>::from(mask), [0x90, 0x00, 0x00, 0x00]);
+    }
+
     #[test]
     fn write_init_control_from_capture() {
         // From Bitaxe capture: TX: 55 AA 51 09 00 A8 00 07 00 00 03
@@ -1655,6 +2132,20 @@ mod command_tests {
         );
     }
 
+    #[test]
+    fn write_uart_baud_1m() {
+        // From esp-miner BM1370_set_max_baud: TX: 55 AA 51 09 00 28 11 30 02 00 03
+        assert_frame_eq(
+            BM13xxProtocol::new().set_baudrate(BaudRate::Baud1M),
+            &[
+                0x55, 0xaa, 0x51, 0x09, 0x00, 0x28, 0x11, 0x30, 0x02, 0x00, 0x03,
+            ],
+        );
+        assert_eq!(BaudRate::from_bps(1_000_000), Some(BaudRate::Baud1M));
+        assert_eq!(BaudRate::Baud3M.bps(), Some(3_000_000));
+        assert_eq!(BaudRate::from_bps(2_000_000), None);
+    }
+
     #[test]
     fn write_ticket_mask_from_capture() {
         // From S21 Pro capture: TX: 55 AA 51 09 00 14 00 00 00 FF 08
@@ -1730,7 +2221,7 @@ mod command_tests {
             version: bitcoin::block::Version::from_consensus(0x20000000),
         };
 
-        let mut codec = FrameCodec;
+        let mut codec = FrameCodec::default();
         let mut frame = BytesMut::new();
         codec
             .encode(
@@ -1808,7 +2299,7 @@ mod command_tests {
             version: *esp_miner_job::wire_tx::VERSION,
         };
 
-        let mut codec = FrameCodec;
+        let mut codec = FrameCodec::default();
         let mut frame = BytesMut::new();
         codec
             .encode(
@@ -1828,7 +2319,8 @@ mod command_tests {
     }
 
     fn assert_frame_eq(cmd: Command, expect: &[u8]) {
-        let mut codec = FrameCodec;
+        let debug = format!("{:?}", cmd);
+        let mut codec = FrameCodec::default();
         let mut frame = BytesMut::new();
         codec
             .encode(cmd, &mut frame)
@@ -1841,6 +2333,10 @@ mod command_tests {
             as_hex(expect),
             as_hex(&frame[..])
         );
+
+        // Every encoding must decode back to the command it came from
+        let decoded = Command::decode(expect).expect("Failed to decode command frame");
+        assert_eq!(format!("{:?}", decoded), debug);
     }
 
     fn as_hex(bytes: &[u8]) -> String {
@@ -1871,7 +2367,7 @@ mod command_tests {
             version: *esp_miner_job::wire_tx::VERSION,
         };
 
-        let mut codec = FrameCodec;
+        let mut codec = FrameCodec::default();
         let mut frame = BytesMut::new();
         codec
             .encode(Command::JobFull { job_data: job }, &mut frame)
@@ -1884,12 +2380,122 @@ mod command_tests {
             "JobFull encoding doesn't match hardware capture"
         );
     }
+
+    #[test]
+    fn decode_job_full_from_capture() {
+        use crate::asic::bm13xx::test_data::esp_miner_job;
+
+        let Command::JobFull { job_data } =
+            Command::decode(&esp_miner_job::wire_tx::FRAME).expect("capture should decode")
+        else {
+            panic!("Expected JobFull");
+        };
+
+        assert_eq!(job_data.job_id, *esp_miner_job::wire_tx::JOB_ID);
+        assert_eq!(job_data.nbits, *esp_miner_job::wire_tx::NBITS);
+        assert_eq!(job_data.ntime, *esp_miner_job::wire_tx::NTIME);
+        assert_eq!(job_data.merkle_root, *esp_miner_job::wire_tx::MERKLE_ROOT);
+        assert_eq!(
+            job_data.prev_block_hash,
+            *esp_miner_job::wire_tx::PREV_BLOCKHASH
+        );
+        assert_eq!(job_data.version, *esp_miner_job::wire_tx::VERSION);
+    }
+
+    #[test]
+    fn job_midstate_round_trip() {
+        // Two midstates would be indistinguishable from a full job
+        for num_midstates in [1, 3, 4] {
+            let midstate = |i: u8| (i < num_midstates).then_some([i; 32]);
+            let cmd = Command::JobMidstate {
+                job_data: JobMidstateFormat {
+                    job_id: 5,
+                    num_midstates,
+                    starting_nonce: [0; 4],
+                    nbits: [0x17, 0x03, 0x4e, 0x2a],
+                    ntime: [0x65, 0x43, 0x21, 0x00],
+                    merkle4: [0xde, 0xad, 0xbe, 0xef],
+                    midstate0: [0xaa; 32],
+                    midstate1: midstate(1),
+                    midstate2: midstate(2),
+                    midstate3: midstate(3),
+                },
+            };
+            let debug = format!("{:?}", cmd);
+
+            let mut frame = BytesMut::new();
+            FrameCodec::default().encode(cmd, &mut frame).unwrap();
+            let decoded = Command::decode(&frame).expect("midstate job should decode");
+            assert_eq!(format!("{:?}", decoded), debug);
+        }
+    }
+
+    #[test]
+    fn frames_encode_back_to_back() {
+        use crate::asic::bm13xx::test_data::esp_miner_job;
+
+        // A writer can queue frames before flushing; each must carry its
+        // own CRC
+        let job = Command::decode(&esp_miner_job::wire_tx::FRAME).unwrap();
+        let read = Command::ReadRegister {
+            broadcast: true,
+            chip_address: 0,
+            register_address: RegisterAddress::ChipId,
+        };
+        let (job_len, read_len) = (job.frame_len(), read.frame_len());
+
+        let mut codec = FrameCodec::default();
+        let mut buf = BytesMut::new();
+        codec.encode(job, &mut buf).unwrap();
+        codec.encode(read, &mut buf).unwrap();
+        assert_eq!(buf.len(), job_len + read_len);
+        assert_eq!(&buf[..job_len], &esp_miner_job::wire_tx::FRAME[..]);
+        assert!(Command::decode(&buf[job_len..]).is_ok());
+    }
+
+    #[test]
+    fn decode_rejects_malformed_frames() {
+        use crate::asic::bm13xx::test_data::esp_miner_job;
+
+        let read_chip_id = [0x55, 0xaa, 0x52, 0x05, 0x00, 0x00, 0x0a];
+        assert!(Command::decode(&read_chip_id).is_ok());
+
+        // Bad CRC5
+        let mut frame = read_chip_id;
+        frame[6] ^= 0x01;
+        assert!(matches!(
+            Command::decode(&frame),
+            Err(ProtocolError::InvalidFrame)
+        ));
+
+        // Length field disagrees with the frame
+        assert!(Command::decode(&read_chip_id[..6]).is_err());
+
+        // Response preamble
+        let mut frame = read_chip_id;
+        frame[..2].copy_from_slice(&[0xaa, 0x55]);
+        assert!(Command::decode(&frame).is_err());
+
+        // Unknown register, with a CRC that matches
+        let mut frame = vec![0x55, 0xaa, 0x52, 0x05, 0x00, 0xfd];
+        frame.push(crc5(&frame[2..]));
+        assert!(matches!(
+            Command::decode(&frame),
+            Err(ProtocolError::InvalidRegisterAddress(0xfd))
+        ));
+
+        // Bad CRC16 on a job
+        let mut frame = esp_miner_job::wire_tx::FRAME.to_vec();
+        *frame.last_mut().unwrap() ^= 0x01;
+        assert!(Command::decode(&frame).is_err());
+    }
 }
 
 #[cfg(test)]
 mod response_tests {
     use super::*;
     use bytes::BufMut;
+    use proptest::prelude::*;
 
     #[test]
     fn verify_crc_calculation() {
@@ -1903,7 +2509,7 @@ mod response_tests {
 
     #[test]
     fn decoder_with_exact_frame_size() {
-        let mut codec = FrameCodec;
+        let mut codec = FrameCodec::default();
 
         // Exactly 11 bytes - a complete frame
         let mut buf = BytesMut::new();
@@ -1952,10 +2558,73 @@ mod response_tests {
 
     fn decode_frame(frame: &[u8]) -> Option<Response> {
         let mut buf = BytesMut::from(frame);
-        let mut codec = FrameCodec;
+        let mut codec = FrameCodec::default();
         codec.decode(&mut buf).expect("Failed to decode frame")
     }
 
+    /// Complete `data` into a response frame, with `type_bits` beside the CRC.
+    fn response_frame(data: &[u8], type_bits: u8) -> Vec<u8> {
+        let mut frame = vec![0xaa, 0x55];
+        frame.extend_from_slice(data);
+        let last = (0..0x20)
+            .map(|crc| type_bits | crc)
+            .find(|&last| {
+                let mut body = frame[2..].to_vec();
+                body.push(last);
+                crc5_is_valid(&body)
+            })
+            .expect("some CRC5 is valid");
+        frame.push(last);
+        frame
+    }
+
+    #[test]
+    fn decode_midstate_nonce_response() {
+        // Nonce 0x12345678, chip-local byte, job 5 from midstate 2
+        let frame = response_frame(&[0x78, 0x56, 0x34, 0x12, 0x01, (5 << 3) | 2], 0x80);
+        assert_eq!(frame.len(), 9);
+
+        let mut buf = BytesMut::from(&frame[..]);
+        let mut codec = FrameCodec::default().with_variant(ProtocolVariant::Midstate);
+        match codec.decode(&mut buf).unwrap() {
+            Some(Response::Nonce {
+                nonce,
+                job_id,
+                midstate_num,
+                version,
+                ..
+            }) => {
+                assert_eq!(nonce, 0x12345678);
+                assert_eq!(job_id, 5);
+                assert_eq!(midstate_num, 2);
+                assert_eq!(version, GeneralPurposeBits::none());
+            }
+            other => panic!("Expected nonce response, got {:?}", other),
+        }
+        assert!(buf.is_empty());
+
+        // The full-header decoder waits for two more bytes instead
+        let mut buf = BytesMut::from(&frame[..]);
+        assert!(FrameCodec::default().decode(&mut buf).unwrap().is_none());
+    }
+
+    #[test]
+    fn decode_midstate_register_response() {
+        let frame = response_frame(&[0x13, 0x97, 0x18, 0x00, 0x00, 0x00], 0x00);
+        let mut buf = BytesMut::from(&frame[..]);
+        let mut codec = FrameCodec::default().with_variant(ProtocolVariant::Midstate);
+        match codec.decode(&mut buf).unwrap() {
+            Some(Response::ReadRegister {
+                register: Register::ChipId { chip_type, .. },
+                ..
+            }) => {
+                assert_eq!(chip_type, ChipType::BM1397);
+                assert_eq!(chip_type.variant(), ProtocolVariant::Midstate);
+            }
+            other => panic!("Expected chip ID response, got {:?}", other),
+        }
+    }
+
     #[test]
     fn decode_nonce_response_from_capture() {
         // From Bitaxe capture: RX: AA 55 18 00 A6 40 02 99 22 F9 91
@@ -2048,7 +2717,7 @@ mod response_tests {
 
     #[test]
     fn decoder_handles_partial_frames() {
-        let mut codec = FrameCodec;
+        let mut codec = FrameCodec::default();
 
         // Test with incomplete frame (less than 11 bytes)
         let mut buf = BytesMut::new();
@@ -2068,7 +2737,7 @@ mod response_tests {
 
     #[test]
     fn decoder_handles_corrupted_crc() {
-        let mut codec = FrameCodec;
+        let mut codec = FrameCodec::default();
 
         // Valid frame with corrupted CRC (last byte)
         let mut buf = BytesMut::new();
@@ -2080,14 +2749,14 @@ mod response_tests {
         assert!(result.is_none(), "Should reject frame with bad CRC");
         assert_eq!(
             buf.len(),
-            10,
-            "Should consume 1 byte when searching for valid frame"
+            0,
+            "Should discard the bad frame while searching for a valid one"
         );
     }
 
     #[test]
     fn decoder_finds_frame_after_garbage() {
-        let mut codec = FrameCodec;
+        let mut codec = FrameCodec::default();
 
         // Garbage bytes followed by valid frame
         let mut buf = BytesMut::new();
@@ -2096,12 +2765,7 @@ mod response_tests {
             0xaa, 0x55, 0x13, 0x70, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10,
         ]); // Valid frame
 
-        // First calls should skip garbage
-        assert!(codec.decode(&mut buf).unwrap().is_none());
-        assert!(codec.decode(&mut buf).unwrap().is_none());
-        assert!(codec.decode(&mut buf).unwrap().is_none());
-
-        // Should find valid frame
+        // Garbage is skipped within a single call
         let result = codec.decode(&mut buf).unwrap();
         assert!(result.is_some(), "Should find valid frame after garbage");
         assert_eq!(buf.len(), 0, "All data should be consumed");
@@ -2109,7 +2773,7 @@ mod response_tests {
 
     #[test]
     fn decoder_handles_false_start() {
-        let mut codec = FrameCodec;
+        let mut codec = FrameCodec::default();
 
         // Frame that starts with 0xAA but not followed by 0x55
         let mut buf = BytesMut::new();
@@ -2121,22 +2785,11 @@ mod response_tests {
         // Total buffer: [AA, 00, AA, 55, 13, 70, 00, 00, 00, 00, 00, 00, 10] = 13 bytes
         assert_eq!(buf.len(), 13, "Initial buffer should have 13 bytes");
 
-        // First decode: sees AA at pos 0, but 00 at pos 1, so should skip 1 byte
-        let first = codec.decode(&mut buf).unwrap();
-        assert!(first.is_none(), "First decode should return None");
-        assert_eq!(buf.len(), 12, "Should have consumed 1 byte");
-
-        // Buffer now: [00, AA, 55, 13, 70, 00, 00, 00, 00, 00, 00, 10] = 12 bytes
-        // Second decode: sees 00 at pos 0, should skip 1 byte
-        let second = codec.decode(&mut buf).unwrap();
-        assert!(second.is_none(), "Second decode should return None");
-        assert_eq!(buf.len(), 11, "Should have consumed another byte");
-
-        // Buffer now: [AA, 55, 13, 70, 00, 00, 00, 00, 00, 00, 10] = 11 bytes = valid frame
-        // Third decode should succeed
+        // AA at pos 0 isn't followed by 55, so the decoder resyncs on the
+        // preamble at pos 2 and returns that frame from the same call
         let result = codec.decode(&mut buf);
         match result {
-            Ok(Some(Response::ReadRegister { .. })) => {} // Success
+            Ok(Some(Response::ReadRegister { .. })) => assert!(buf.is_empty()),
             Ok(Some(other)) => panic!("Expected ReadRegister, got {:?}", other),
             Ok(None) => panic!(
                 "Expected Some, got None. Buffer len: {}, contents: {:02x?}",
@@ -2149,7 +2802,7 @@ mod response_tests {
 
     #[test]
     fn decoder_handles_back_to_back_frames() {
-        let mut codec = FrameCodec;
+        let mut codec = FrameCodec::default();
 
         // Two valid frames back-to-back
         let mut buf = BytesMut::new();
@@ -2175,7 +2828,7 @@ mod response_tests {
 
     #[test]
     fn decoder_handles_real_s21_pro_frames() {
-        let mut codec = FrameCodec;
+        let mut codec = FrameCodec::default();
 
         // Real frames from S21 Pro capture
         let frames = vec![
@@ -2205,7 +2858,7 @@ mod response_tests {
 
     #[test]
     fn decoder_handles_stream_with_lost_bytes() {
-        let mut codec = FrameCodec;
+        let mut codec = FrameCodec::default();
 
         // Simulate a stream where some bytes in the middle are lost
         let mut buf = BytesMut::new();
@@ -2233,7 +2886,7 @@ mod response_tests {
 
     #[test]
     fn decoder_handles_mid_frame_start() {
-        let mut codec = FrameCodec;
+        let mut codec = FrameCodec::default();
 
         // Start reading in the middle of a frame
         let mut buf = BytesMut::new();
@@ -2245,19 +2898,7 @@ mod response_tests {
         ]);
 
         // Total: 5 + 11 = 16 bytes
-        // Should skip the partial frame bytes one by one until finding the valid frame
-        for i in 0..5 {
-            let result = codec.decode(&mut buf).unwrap();
-            assert!(result.is_none(), "Decode {} should return None", i + 1);
-            assert_eq!(
-                buf.len(),
-                16 - i - 1,
-                "Should have consumed {} bytes",
-                i + 1
-            );
-        }
-
-        // Now we should have the valid frame
+        // Should skip the partial frame and return the valid one in one call
         let result = codec.decode(&mut buf).unwrap();
         assert!(
             result.is_some(),
@@ -2272,7 +2913,7 @@ mod response_tests {
     #[test]
     fn decoder_validates_real_register_responses() {
         // Test all register read responses are handled correctly
-        let mut codec = FrameCodec;
+        let mut codec = FrameCodec::default();
 
         // Standard chip detection response
         let mut buf = BytesMut::new();
@@ -2354,7 +2995,7 @@ mod response_tests {
             version: *esp_miner_job::notify::VERSION,
         };
 
-        let mut codec = FrameCodec;
+        let mut codec = FrameCodec::default();
         let mut tx_frame = BytesMut::new();
         codec
             .encode(
@@ -2411,6 +3052,137 @@ mod response_tests {
             "Hash should meet pool difficulty"
         );
     }
+
+    #[test]
+    fn decoder_counts_what_it_sees() {
+        let stats = Arc::new(RxStats::default());
+        let mut codec = FrameCodec::with_stats(stats.clone());
+
+        let mut buf = BytesMut::new();
+        buf.put_slice(&[0x01, 0x02, 0x03]); // Garbage
+        buf.put_slice(&[
+            0xaa, 0x55, 0x13, 0x70, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff,
+        ]); // Bad CRC
+        buf.put_slice(&[
+            0xaa, 0x55, 0x13, 0x70, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10,
+        ]);
+        while codec.decode(&mut buf).unwrap().is_some() {}
+
+        let counts = stats.snapshot(None);
+        assert_eq!(counts.frames, 1);
+        assert_eq!(counts.crc_errors, 1);
+        // The garbage and the whole rejected frame
+        assert_eq!(counts.skipped_bytes, 3 + 11);
+    }
+
+    /// Frames the fuzz tests hide in noise: a chip ID read and nonces from
+    /// real captures.
+    const KNOWN_FRAMES: [[u8; 11]; 4] = [
+        [
+            0xaa, 0x55, 0x13, 0x70, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10,
+        ],
+        [
+            0xaa, 0x55, 0x18, 0x00, 0xa6, 0x40, 0x02, 0x99, 0x22, 0xf9, 0x91,
+        ],
+        [
+            0xaa, 0x55, 0x07, 0x35, 0xcd, 0xcf, 0x02, 0x5e, 0x00, 0x2e, 0x96,
+        ],
+        [
+            0xaa, 0x55, 0x32, 0x2a, 0x84, 0x5a, 0x02, 0x52, 0x01, 0xb2, 0x8c,
+        ],
+    ];
+
+    /// Feed `stream` to the decoder `chunk_len` bytes at a time, draining
+    /// frames after each read the way FramedRead does.
+    fn decode_stream(stream: &[u8], chunk_len: usize) -> Vec<String> {
+        let mut codec = FrameCodec::default();
+        let mut buf = BytesMut::new();
+        let mut responses = Vec::new();
+
+        for chunk in stream.chunks(chunk_len) {
+            buf.put_slice(chunk);
+            while let Some(response) = codec.decode(&mut buf).unwrap() {
+                responses.push(format!("{:?}", response));
+            }
+        }
+
+        responses
+    }
+
+    /// Noise that can't start a false preamble, so every frame is recoverable.
+    fn noise() -> impl Strategy<Value = Vec<u8>> {
+        proptest::collection::vec(
+            any::<u8>().prop_filter("preamble byte", |b| *b != 0xaa),
+            0..16,
+        )
+    }
+
+    proptest! {
+        #[test]
+        fn prop_decoder_survives_arbitrary_bytes(
+            stream in proptest::collection::vec(any::<u8>(), 0..512),
+            chunk_len in 1usize..64,
+        ) {
+            let mut codec = FrameCodec::default();
+            let mut buf = BytesMut::new();
+
+            for chunk in stream.chunks(chunk_len) {
+                buf.put_slice(chunk);
+                // Never an error, which would end the stream
+                while codec.decode(&mut buf).unwrap().is_some() {}
+                // Whatever is left could still become a frame
+                prop_assert!(buf.len() < 11, "{} bytes left undecoded", buf.len());
+            }
+        }
+
+        #[test]
+        fn prop_decoder_recovers_frames_from_noise(
+            frames in proptest::collection::vec(proptest::sample::select(KNOWN_FRAMES.to_vec()), 1..8),
+            noise in proptest::collection::vec(noise(), 8),
+            chunk_len in 1usize..32,
+        ) {
+            let mut stream = Vec::new();
+            for (frame, noise) in frames.iter().zip(&noise) {
+                stream.extend(noise);
+                stream.extend(frame);
+            }
+
+            let expected: Vec<String> = frames
+                .iter()
+                .map(|frame| format!("{:?}", decode_frame(frame).unwrap()))
+                .collect();
+            prop_assert_eq!(decode_stream(&stream, chunk_len), expected);
+        }
+
+        #[test]
+        fn prop_decoder_drops_corrupted_frames(
+            frames in proptest::collection::vec(proptest::sample::select(KNOWN_FRAMES.to_vec()), 2..8),
+            corrupt in any::<proptest::sample::Index>(),
+            byte in 2usize..11,
+            bit in 0u8..8,
+            chunk_len in 1usize..32,
+        ) {
+            let corrupt = corrupt.index(frames.len());
+            let mut stream = Vec::new();
+            for (i, frame) in frames.iter().enumerate() {
+                let mut frame = *frame;
+                if i == corrupt {
+                    frame[byte] ^= 1 << bit;
+                }
+                stream.extend(frame);
+            }
+
+            // CRC5 catches every single-bit error, and losing one frame
+            // doesn't cost the decoder its neighbours
+            let expected: Vec<String> = frames
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| *i != corrupt)
+                .map(|(_, frame)| format!("{:?}", decode_frame(frame).unwrap()))
+                .collect();
+            prop_assert_eq!(decode_stream(&stream, chunk_len), expected);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -2492,6 +3264,18 @@ mod ticket_mask_tests {
         assert_eq!(format!("{}", interval), "2^39");
     }
 
+    #[test]
+    fn test_reporting_interval_for_difficulty() {
+        assert_eq!(ReportingInterval::for_difficulty(0.5).exponent(), 32);
+        assert_eq!(ReportingInterval::for_difficulty(256.0).exponent(), 40);
+        assert_eq!(ReportingInterval::for_difficulty(511.9).difficulty(), 256);
+        assert_eq!(ReportingInterval::for_difficulty(1e12).exponent(), 56);
+
+        let fast = ReportingInterval::for_difficulty(4.0);
+        let slow = ReportingInterval::for_difficulty(1024.0);
+        assert!(fast < slow);
+    }
+
     #[test]
     fn test_ticket_mask_wire_encoding() {
         // Test case 1: 40 bits total (8 zero_bits)
@@ -2693,6 +3477,51 @@ impl BM13xxProtocol {
         commands
     }
 
+    /// Register setup for a single midstate-format chip, before its PLL is
+    /// programmed.
+    ///
+    /// The BM1397 sequence follows esp-miner's driver: clock domain ordering,
+    /// core setup, and the misc control value that keeps the UART at
+    /// 115200 baud. The BM1387's misc and PLL registers sit elsewhere in its
+    /// register map, so it gets addressing and the ticket mask only and runs
+    /// at its power-on clock.
+    pub fn legacy_chip_init(&self, chip_type: ChipType, ticket_mask: TicketMask) -> Vec<Command> {
+        const CLOCK_ORDER: u32 = 0x0000_0000;
+        const ORDERED_CLOCK_ENABLE: u32 = 0x0100_0000;
+        const CORE_REG_INIT: u32 = 0x8000_8074;
+        const MISC_CONTROL_115200: u32 = 0x317A_0000;
+        const PLL3_PARAMETER: u32 = 0x1101_70C0;
+
+        let mut commands = vec![
+            Command::ChainInactive,
+            Command::SetChipAddress { chip_address: 0x00 },
+        ];
+        if chip_type == ChipType::BM1397 {
+            commands.extend([
+                self.broadcast_write(Register::ClockOrderControl0 {
+                    raw_value: CLOCK_ORDER,
+                }),
+                self.broadcast_write(Register::ClockOrderControl1 {
+                    raw_value: CLOCK_ORDER,
+                }),
+                self.broadcast_write(Register::OrderedClockEnable {
+                    raw_value: ORDERED_CLOCK_ENABLE,
+                }),
+                self.broadcast_write(Register::Core {
+                    raw_value: CORE_REG_INIT,
+                }),
+                self.broadcast_write(Register::MiscControl {
+                    raw_value: MISC_CONTROL_115200,
+                }),
+                self.broadcast_write(Register::Pll3Parameter {
+                    raw_value: PLL3_PARAMETER,
+                }),
+            ]);
+        }
+        commands.push(self.broadcast_write(Register::TicketMask(ticket_mask)));
+        commands
+    }
+
     /// Configure domain boundaries for a multi-chip chain.
     ///
     /// Domains are groups of chips that share signal integrity settings.
@@ -2808,6 +3637,9 @@ impl BM13xxProtocol {
                 Register::TicketMask(TicketMask { zero_bits })
             }
             RegisterAddress::MiscControl => Register::MiscControl { raw_value: value },
+            RegisterAddress::OrderedClockEnable => {
+                Register::OrderedClockEnable { raw_value: value }
+            }
             RegisterAddress::UartBaud => Register::UartBaud(BaudRate::Custom(value)),
             RegisterAddress::UartRelay => Register::UartRelay { raw_value: value },
             RegisterAddress::Core => Register::Core { raw_value: value },
@@ -2820,8 +3652,14 @@ impl BM13xxProtocol {
                 Register::IoDriverStrength(IoDriverStrength { strengths })
             }
             RegisterAddress::Pll3Parameter => Register::Pll3Parameter { raw_value: value },
+            RegisterAddress::ClockOrderControl0 => {
+                Register::ClockOrderControl0 { raw_value: value }
+            }
+            RegisterAddress::ClockOrderControl1 => {
+                Register::ClockOrderControl1 { raw_value: value }
+            }
             RegisterAddress::VersionMask => {
-                let mask = (value >> 16) as u16;
+                let mask = ((value >> 16) as u16).swap_bytes();
                 let control = (value & 0xffff) as u16;
                 Register::VersionMask(VersionMask { mask, control })
             }
@@ -0,0 +1,287 @@
+//! Receive-path health for the ASIC data UART.
+//!
+//! The response decoder skips anything that isn't a valid frame, which keeps
+//! mining going through the odd glitch but would also hide a line that has
+//! gone bad entirely. [`RxStats`] counts what the decoder saw so the board
+//! can report it, and [`BaudMonitor`] turns those counts into a decision to
+//! retune the port when corruption persists; the usual cause is the chips
+//! and the host disagreeing about the baud rate, e.g. after a brownout
+//! reset the chips to their power-on rate.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::transport::LineErrors;
+
+/// Consecutive corrupt intervals before trying the next baud rate.
+const PERSISTENT_INTERVALS: u32 = 3;
+
+/// Ceiling on the intervals required between retunes, so a line that is
+/// simply noisy doesn't flap between rates forever.
+const MAX_PERSISTENT_INTERVALS: u32 = 48;
+
+/// Errors an interval needs before it counts as corrupt, so a single glitch
+/// on an otherwise idle line doesn't.
+const MIN_ERRORS: u64 = 8;
+
+/// Counters shared between a response decoder and whoever reports on it.
+#[derive(Debug, Default)]
+pub struct RxStats {
+    frames: AtomicU64,
+    crc_errors: AtomicU64,
+    skipped_bytes: AtomicU64,
+}
+
+/// Point-in-time receive counts for one data port.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RxCounts {
+    /// Frames decoded
+    pub frames: u64,
+    /// Candidate frames rejected by CRC
+    pub crc_errors: u64,
+    /// Bytes discarded while looking for a frame
+    pub skipped_bytes: u64,
+    /// Errors counted by the UART driver, where it keeps them
+    pub line: LineErrors,
+}
+
+/// Watches receive counts and picks a new baud rate when corruption
+/// persists.
+#[derive(Debug)]
+pub struct BaudMonitor {
+    candidates: Vec<u32>,
+    current: usize,
+    last: RxCounts,
+    corrupt_intervals: u32,
+    required: u32,
+}
+
+impl RxStats {
+    pub(crate) fn record_frame(&self) {
+        self.frames.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_crc_error(&self) {
+        self.crc_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_skipped(&self, bytes: usize) {
+        self.skipped_bytes
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Current counts, with the port's line errors if it reports them.
+    pub fn snapshot(&self, line: Option<LineErrors>) -> RxCounts {
+        RxCounts {
+            frames: self.frames.load(Ordering::Relaxed),
+            crc_errors: self.crc_errors.load(Ordering::Relaxed),
+            skipped_bytes: self.skipped_bytes.load(Ordering::Relaxed),
+            line: line.unwrap_or_default(),
+        }
+    }
+}
+
+impl RxCounts {
+    /// Errors of any kind: rejected frames, garbage in whole-frame units,
+    /// and characters the UART itself flagged.
+    fn errors(&self) -> u64 {
+        const FRAME_LEN: u64 = 11;
+        self.crc_errors
+            + self.skipped_bytes / FRAME_LEN
+            + self.line.framing
+            + self.line.overrun
+            + self.line.parity
+            + self.line.buffer_overrun
+    }
+
+    fn since(&self, earlier: &RxCounts) -> RxCounts {
+        RxCounts {
+            frames: self.frames.saturating_sub(earlier.frames),
+            crc_errors: self.crc_errors.saturating_sub(earlier.crc_errors),
+            skipped_bytes: self.skipped_bytes.saturating_sub(earlier.skipped_bytes),
+            line: LineErrors {
+                framing: self.line.framing.saturating_sub(earlier.line.framing),
+                overrun: self.line.overrun.saturating_sub(earlier.line.overrun),
+                parity: self.line.parity.saturating_sub(earlier.line.parity),
+                buffer_overrun: self
+                    .line
+                    .buffer_overrun
+                    .saturating_sub(earlier.line.buffer_overrun),
+            },
+        }
+    }
+}
+
+impl BaudMonitor {
+    /// Monitor a port running at `current`, able to retune to any of
+    /// `candidates`.
+    pub fn new(candidates: &[u32], current: u32) -> Self {
+        let mut candidates = candidates.to_vec();
+        if !candidates.contains(&current) {
+            candidates.insert(0, current);
+        }
+        let current = candidates.iter().position(|&b| b == current).unwrap_or(0);
+
+        Self {
+            candidates,
+            current,
+            last: RxCounts::default(),
+            corrupt_intervals: 0,
+            required: PERSISTENT_INTERVALS,
+        }
+    }
+
+    /// Baud rate the monitor believes the port is at.
+    pub fn baud_rate(&self) -> u32 {
+        self.candidates[self.current]
+    }
+
+    /// Feed the counts at the end of an interval, along with the rate the
+    /// port is at now. Returns the baud rate to retune to if corruption has
+    /// persisted.
+    ///
+    /// An interval is corrupt when its errors are both numerous and
+    /// outnumber good frames. Idle intervals don't count either way; a clean
+    /// one resets the count, as does the port changing rate under the
+    /// monitor, e.g. when the chips are moved to a faster rate after init.
+    pub fn check(&mut self, counts: RxCounts, baud: u32) -> Option<u32> {
+        let delta = counts.since(&self.last);
+        self.last = counts;
+
+        if baud != self.baud_rate() {
+            self.current = match self.candidates.iter().position(|&b| b == baud) {
+                Some(i) => i,
+                None => {
+                    self.candidates.insert(0, baud);
+                    0
+                }
+            };
+            self.corrupt_intervals = 0;
+            self.required = PERSISTENT_INTERVALS;
+            return None;
+        }
+
+        let errors = delta.errors();
+        if errors >= MIN_ERRORS && errors > delta.frames {
+            self.corrupt_intervals += 1;
+        } else if delta.frames > 0 {
+            self.corrupt_intervals = 0;
+            self.required = PERSISTENT_INTERVALS;
+        }
+
+        if self.corrupt_intervals < self.required || self.candidates.len() < 2 {
+            return None;
+        }
+
+        // If the new rate doesn't help either, wait longer before the next
+        // attempt
+        self.corrupt_intervals = 0;
+        self.required = (self.required * 2).min(MAX_PERSISTENT_INTERVALS);
+        self.current = (self.current + 1) % self.candidates.len();
+        Some(self.baud_rate())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counts(frames: u64, crc_errors: u64, framing: u64) -> RxCounts {
+        RxCounts {
+            frames,
+            crc_errors,
+            skipped_bytes: 0,
+            line: LineErrors {
+                framing,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_persistent_corruption_retunes() {
+        let mut monitor = BaudMonitor::new(&[115_200, 1_000_000], 1_000_000);
+
+        assert_eq!(monitor.check(counts(0, 10, 50), monitor.baud_rate()), None);
+        assert_eq!(monitor.check(counts(0, 20, 100), monitor.baud_rate()), None);
+        assert_eq!(
+            monitor.check(counts(0, 30, 150), monitor.baud_rate()),
+            Some(115_200)
+        );
+        assert_eq!(monitor.baud_rate(), 115_200);
+
+        // Still bad at the new rate: the next attempt waits twice as long
+        for i in 1..6 {
+            assert_eq!(
+                monitor.check(counts(0, 30 + i * 10, 150), monitor.baud_rate()),
+                None
+            );
+        }
+        assert_eq!(
+            monitor.check(counts(0, 90, 150), monitor.baud_rate()),
+            Some(1_000_000)
+        );
+    }
+
+    #[test]
+    fn test_clean_or_idle_intervals() {
+        let mut monitor = BaudMonitor::new(&[115_200, 1_000_000], 115_200);
+
+        // Two corrupt intervals, then a clean one starts the count over
+        monitor.check(counts(0, 10, 0), monitor.baud_rate());
+        monitor.check(counts(0, 20, 0), monitor.baud_rate());
+        assert_eq!(monitor.check(counts(100, 20, 0), monitor.baud_rate()), None);
+        monitor.check(counts(100, 30, 0), monitor.baud_rate());
+        monitor.check(counts(100, 40, 0), monitor.baud_rate());
+
+        // An idle interval neither resets nor advances the count
+        assert_eq!(monitor.check(counts(100, 40, 0), monitor.baud_rate()), None);
+        assert_eq!(
+            monitor.check(counts(100, 50, 0), monitor.baud_rate()),
+            Some(1_000_000)
+        );
+
+        // A few errors among plenty of frames is a working line
+        let mut monitor = BaudMonitor::new(&[115_200, 1_000_000], 115_200);
+        for i in 1..10 {
+            assert_eq!(
+                monitor.check(counts(i * 1000, i * 20, 0), monitor.baud_rate()),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn test_skipped_bytes_count_as_errors() {
+        let mut monitor = BaudMonitor::new(&[115_200], 1_000_000);
+        assert_eq!(monitor.candidates, vec![1_000_000, 115_200]);
+
+        let garbage = |bytes| RxCounts {
+            skipped_bytes: bytes,
+            ..Default::default()
+        };
+        assert_eq!(monitor.check(garbage(1000), monitor.baud_rate()), None);
+        assert_eq!(monitor.check(garbage(2000), monitor.baud_rate()), None);
+        assert_eq!(
+            monitor.check(garbage(3000), monitor.baud_rate()),
+            Some(115_200)
+        );
+    }
+
+    #[test]
+    fn test_follows_port_rate_changes() {
+        let mut monitor = BaudMonitor::new(&[115_200, 1_000_000], 115_200);
+
+        // Corruption at the old rate is forgotten once the port moves on
+        monitor.check(counts(0, 10, 0), 115_200);
+        monitor.check(counts(0, 20, 0), 115_200);
+        assert_eq!(monitor.check(counts(0, 30, 0), 1_000_000), None);
+        assert_eq!(monitor.baud_rate(), 1_000_000);
+        assert_eq!(monitor.check(counts(0, 40, 0), 1_000_000), None);
+        assert_eq!(monitor.check(counts(0, 50, 0), 1_000_000), None);
+        assert_eq!(monitor.check(counts(0, 60, 0), 1_000_000), Some(115_200));
+
+        // A rate it wasn't told about becomes a candidate
+        monitor.check(counts(0, 60, 0), 3_000_000);
+        assert_eq!(monitor.candidates, vec![3_000_000, 115_200, 1_000_000]);
+    }
+}
@@ -4,35 +4,99 @@
 //! chips (BM1362, BM1366, BM1370, etc.). A BM13xxThread represents a chain of
 //! BM13xx chips connected via a shared serial bus.
 //!
+//! The older BM1387 and BM1397 are driven through the same thread, told apart
+//! by the chip type the board passes in. They take midstate jobs: the thread
+//! rolls the version bits itself, computing one midstate per block version,
+//! and works out which version a nonce was found with from the midstate the
+//! chip names.
+//!
 //! The thread is implemented as an actor task that monitors the serial bus for
 //! chip responses, filters shares, and manages work assignment.
+//!
+//! The actor also watches for the chips going quiet (see
+//! [`crate::asic::stall`]). A stalled chain is first reset and reprogrammed in
+//! place; if nonces still don't come, the thread asks its board to be
+//! reinitialized. The same chip reset can be requested through a
+//! [`ChipResetHandle`], which the board keeps after the thread itself has
+//! gone to the scheduler.
 
 use std::sync::{Arc, RwLock};
 
 use async_trait::async_trait;
-use bitcoin::block::Header as BlockHeader;
+use bitcoin::block::Version;
+use bitcoin::hashes::Hash;
 use futures::{sink::Sink, stream::Stream, SinkExt};
 use tokio::sync::{mpsc, oneshot, watch};
+use tokio::time::Instant;
 use tokio_stream::StreamExt;
+use tracing::{info_span, Instrument};
 
 use super::protocol;
 use crate::{
     asic::hash_thread::{
-        BoardPeripherals, HashTask, HashThread, HashThreadCapabilities, HashThreadError,
-        HashThreadEvent, HashThreadStatus, Share, ThreadRemovalSignal,
+        BaudRateControl, BoardPeripherals, HashTask, HashThread, HashThreadCapabilities,
+        HashThreadError, HashThreadEvent, HashThreadStatus, ThreadRemovalSignal,
     },
+    asic::{
+        nonce_map::NonceMap,
+        pregen::Pregenerator,
+        stall::{StallAction, StallDetector},
+    },
+    job_source::{GeneralPurposeBits, HeaderTemplate, VersionTemplate},
     tracing::prelude::*,
     types::{Difficulty, HashRate},
-    u256::U256,
 };
 
+/// Rate chips use after reset, and the fallback when a faster one fails.
+const INITIAL_BAUD_RATE: u32 = 115_200;
+
+/// Pause around a baud rate change, covering the rate write on the wire.
+const BAUD_SWITCH_SETTLE: std::time::Duration = std::time::Duration::from_millis(10);
+
+/// How long chips get to answer at a new baud rate.
+const BAUD_CONFIRM_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Clock midstate-format BM1397s are ramped to, as in esp-miner.
+const BM1397_FREQUENCY_MHZ: f32 = 425.0;
+
+/// Clock version-rolling chips are ramped to unless given another.
+const DEFAULT_FREQUENCY_MHZ: f32 = 525.0;
+
+/// How far the PLL moves per step while the clock is ramped.
+const FREQUENCY_STEP_MHZ: f32 = 6.25;
+
+/// Chain hashrate the nonce reporting rate is tuned for, in GiH/s
+/// (1000 GiH/s = 1.074 TH/s).
+const CHAIN_HASHRATE_GIBIHASHES: f64 = 1000.0;
+
+/// Fewest nonces per second the ticket mask is set to produce at that
+/// hashrate, however hard the share target; they're the thread's health signal.
+const NONCES_PER_SEC: f64 = 1.0;
+
+/// Most nonces per second the ticket mask lets through at that hashrate,
+/// however easy the share target, to keep the serial link clear.
+const MAX_NONCES_PER_SEC: f64 = 64.0;
+
+/// How often the actor checks whether the chips have gone quiet.
+const STALL_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// How long chips are held in reset during a soft reset.
+const CHIP_RESET_HOLD: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// How long chips get to answer the chip ID read after a reset. Each chip
+/// answers in turn, so this is kept generous for long chains.
+const CHIP_ENUMERATE_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Job IDs the chips tell apart (4 bits).
+const CHIP_JOB_IDS: usize = 16;
+
 /// Tracks tasks sent to chip hardware, indexed by chip_job_id.
 ///
 /// BM13xx chips use 4-bit job IDs. This tracker maintains snapshots of
 /// HashTasks sent to the chip so we can match nonce responses back to the
 /// correct task context (EN2, ntime, etc.).
 struct ChipJobTracker {
-    tasks: [Option<HashTask>; 16],
+    tasks: [Option<HashTask>; CHIP_JOB_IDS],
     next_id: u8,
 }
 
@@ -82,6 +146,17 @@ enum ThreadCommand {
         response_tx: oneshot::Sender<std::result::Result<Option<HashTask>, HashThreadError>>,
     },
 
+    /// Reset and reprogram the chips, keeping the current task
+    ResetChips {
+        response_tx: oneshot::Sender<std::result::Result<usize, HashThreadError>>,
+    },
+
+    /// Ramp the chips' clock to a new frequency
+    SetFrequency {
+        mhz: f32,
+        response_tx: oneshot::Sender<std::result::Result<(), HashThreadError>>,
+    },
+
     /// Shutdown the thread
     #[expect(unused)]
     Shutdown,
@@ -107,6 +182,86 @@ pub struct BM13xxThread {
 
     /// Shared status (updated by actor task)
     status: Arc<RwLock<HashThreadStatus>>,
+
+    /// Nonces found per core (updated by actor task)
+    nonce_map: Arc<RwLock<NonceMap>>,
+}
+
+/// Reads a thread's status from outside the scheduler.
+///
+/// Taken from the thread before it's handed to the scheduler, so the board
+/// can include what the thread knows about its chips in its telemetry.
+#[derive(Debug, Clone)]
+pub struct ThreadStatusHandle {
+    status: Arc<RwLock<HashThreadStatus>>,
+    nonce_map: Arc<RwLock<NonceMap>>,
+}
+
+impl ThreadStatusHandle {
+    /// The thread's current status.
+    pub fn get(&self) -> HashThreadStatus {
+        self.status.read().unwrap().clone()
+    }
+
+    /// Nonces the chips have found per core since the thread started.
+    /// Empty for chips that don't say which core found a nonce.
+    pub fn nonce_map(&self) -> NonceMap {
+        self.nonce_map.read().unwrap().clone()
+    }
+}
+
+/// Resets a thread's chips on request.
+///
+/// Taken from the thread before it's handed to the scheduler, so the board
+/// can recover chips that locked up without a full reinitialization.
+#[derive(Debug, Clone)]
+pub struct ChipResetHandle {
+    command_tx: mpsc::Sender<ThreadCommand>,
+}
+
+impl ChipResetHandle {
+    /// Pulse the chips' reset line, re-enumerate them, and reprogram their
+    /// registers; a task being hashed is resent.
+    ///
+    /// Returns the number of chips that answered after the reset.
+    pub async fn reset(&self) -> std::result::Result<usize, HashThreadError> {
+        let (response_tx, response_rx) = oneshot::channel();
+
+        self.command_tx
+            .send(ThreadCommand::ResetChips { response_tx })
+            .await
+            .map_err(|_| HashThreadError::ChannelClosed("command channel closed".into()))?;
+
+        response_rx
+            .await
+            .map_err(|_| HashThreadError::ChannelClosed("no response from thread".into()))?
+    }
+}
+
+/// Retunes a thread's chips to a new clock frequency.
+///
+/// Taken from the thread before it's handed to the scheduler. The PLL is
+/// programmed over the chips' data channel, which only the thread may write.
+#[derive(Debug, Clone)]
+pub struct ChipClockHandle {
+    command_tx: mpsc::Sender<ThreadCommand>,
+}
+
+impl ChipClockHandle {
+    /// Ramp the chips' clock to `mhz` a PLL step at a time. Chips that
+    /// haven't been initialized yet are ramped there when they are.
+    pub async fn set_frequency(&self, mhz: f32) -> std::result::Result<(), HashThreadError> {
+        let (response_tx, response_rx) = oneshot::channel();
+
+        self.command_tx
+            .send(ThreadCommand::SetFrequency { mhz, response_tx })
+            .await
+            .map_err(|_| HashThreadError::ChannelClosed("command channel closed".into()))?;
+
+        response_rx
+            .await
+            .map_err(|_| HashThreadError::ChannelClosed("no response from thread".into()))?
+    }
 }
 
 impl BM13xxThread {
@@ -117,12 +272,14 @@ impl BM13xxThread {
     ///
     /// # Arguments
     /// * `name` - Human-readable name for logging (e.g., "Bitaxe Gamma (e2f56f9b)")
+    /// * `chip_type` - Chips on the chain, which sets the protocol variant
     /// * `chip_responses` - Stream of decoded responses from chips
     /// * `chip_commands` - Sink for sending encoded commands to chips
     /// * `peripherals` - Hardware interfaces from board (enable, regulator, etc.)
     /// * `removal_rx` - Watch channel for board-triggered removal
     pub fn new<R, W>(
         name: String,
+        chip_type: protocol::ChipType,
         chip_responses: R,
         chip_commands: W,
         peripherals: BoardPeripherals,
@@ -138,29 +295,63 @@ impl BM13xxThread {
 
         let status = Arc::new(RwLock::new(HashThreadStatus::default()));
         let status_clone = Arc::clone(&status);
-
-        // Spawn the actor task
-        tokio::spawn(async move {
-            bm13xx_thread_actor(
-                cmd_rx,
-                evt_tx,
-                removal_rx,
-                status_clone,
-                chip_responses,
-                chip_commands,
-                peripherals,
-            )
-            .await;
-        });
+        let nonce_map = Arc::new(RwLock::new(
+            chip_type
+                .core_layout()
+                .map(|(cores, small_cores)| NonceMap::with_layout(cores, small_cores))
+                .unwrap_or_default(),
+        ));
+        let nonce_map_clone = Arc::clone(&nonce_map);
+
+        // Spawn the actor task; the span tags all of its events with the
+        // thread name so they can be told apart per board
+        tokio::spawn(
+            async move {
+                bm13xx_thread_actor(
+                    cmd_rx,
+                    evt_tx,
+                    removal_rx,
+                    status_clone,
+                    nonce_map_clone,
+                    chip_type,
+                    chip_responses,
+                    chip_commands,
+                    peripherals,
+                )
+                .await;
+            }
+            .instrument(info_span!("hash_thread", thread = %name)),
+        );
 
         Self {
             name,
             command_tx: cmd_tx,
             event_rx: Some(evt_rx),
-            capabilities: HashThreadCapabilities {
-                hashrate_estimate: HashRate::from_terahashes(1.0), // Stub
-            },
+            capabilities: capabilities(chip_type.variant()),
             status,
+            nonce_map,
+        }
+    }
+
+    /// Handle for resetting this thread's chips.
+    pub fn chip_reset_handle(&self) -> ChipResetHandle {
+        ChipResetHandle {
+            command_tx: self.command_tx.clone(),
+        }
+    }
+
+    /// Handle for retuning this thread's chips.
+    pub fn chip_clock_handle(&self) -> ChipClockHandle {
+        ChipClockHandle {
+            command_tx: self.command_tx.clone(),
+        }
+    }
+
+    /// Handle for reading this thread's status.
+    pub fn status_handle(&self) -> ThreadStatusHandle {
+        ThreadStatusHandle {
+            status: Arc::clone(&self.status),
+            nonce_map: Arc::clone(&self.nonce_map),
         }
     }
 }
@@ -237,17 +428,35 @@ impl HashThread for BM13xxThread {
 
 /// Initialize BM13xx chip for mining.
 ///
-/// Enables chip, configures all registers, and ramps frequency to target.
-async fn initialize_chip<W>(
+/// Enables chip, configures all registers, and ramps frequency to
+/// `frequency_mhz`, or the chips' default if that's unset.
+async fn initialize_chip<R, W>(
+    chip_responses: &mut R,
     chip_commands: &mut W,
     peripherals: &mut BoardPeripherals,
+    chip_type: protocol::ChipType,
+    frequency_mhz: Option<f32>,
 ) -> Result<(), HashThreadError>
 where
+    R: Stream<Item = Result<protocol::Response, std::io::Error>> + Unpin,
     W: Sink<protocol::Command> + Unpin,
     W::Error: std::fmt::Debug,
 {
     use protocol::{Command, Register};
 
+    // Chips come out of reset at their power-on rate
+    if let Some(ref mut baud_rate) = peripherals.baud_rate {
+        baud_rate
+            .set_baud_rate(INITIAL_BAUD_RATE)
+            .await
+            .map_err(|e| {
+                HashThreadError::InitializationFailed(format!(
+                    "Failed to reset host baud rate: {}",
+                    e
+                ))
+            })?;
+    }
+
     // Enable the ASIC
     if let Some(ref mut asic_enable) = peripherals.asic_enable {
         debug!("Enabling ASIC");
@@ -258,6 +467,10 @@ where
 
     tokio::time::sleep(std::time::Duration::from_millis(200)).await;
 
+    if chip_type.variant() == protocol::ProtocolVariant::Midstate {
+        return initialize_midstate_chip(chip_commands, chip_type, frequency_mhz).await;
+    }
+
     // Send version mask configuration (3 times)
     debug!("Configuring version mask");
     for _ in 1..=3 {
@@ -348,13 +561,7 @@ where
         })?;
 
     // Ticket mask, IO strength
-    // Target: ~1 nonce per second at 1 TH/s (1000 GiH/s = 1.074 TH/s)
-    use protocol::{Hashrate, ReportingInterval, ReportingRate, TicketMask};
-    let reporting_interval = ReportingInterval::from_rate(
-        Hashrate::gibihashes_per_sec(1000.0),
-        ReportingRate::nonces_per_sec(1.0),
-    );
-    let ticket_mask = TicketMask::new(reporting_interval);
+    let ticket_mask = protocol::TicketMask::new(reporting_interval());
 
     chip_commands
         .send(Command::WriteRegister {
@@ -499,9 +706,10 @@ where
             HashThreadError::InitializationFailed(format!("Core final send failed: {:?}", e))
         })?;
 
-    // Frequency ramping (56.25 MHz -> 525 MHz)
-    debug!("Ramping frequency from 56.25 MHz to 525 MHz");
-    let frequency_steps = generate_frequency_ramp_steps(56.25, 525.0, 6.25);
+    // Frequency ramping (56.25 MHz -> target)
+    let target_mhz = frequency_mhz.unwrap_or(DEFAULT_FREQUENCY_MHZ);
+    debug!(target_mhz, "Ramping frequency from 56.25 MHz");
+    let frequency_steps = generate_frequency_ramp_steps(56.25, target_mhz, FREQUENCY_STEP_MHZ);
 
     for (i, pll_config) in frequency_steps.iter().enumerate() {
         chip_commands
@@ -518,7 +726,11 @@ where
         tokio::time::sleep(std::time::Duration::from_millis(100)).await;
 
         if i % 10 == 0 || i == frequency_steps.len() - 1 {
-            trace!("Frequency ramp step {}/{}", i + 1, frequency_steps.len());
+            trace!(
+                step = i + 1,
+                steps = frequency_steps.len(),
+                "Frequency ramp step"
+            );
         }
     }
 
@@ -550,9 +762,370 @@ where
 
     tokio::time::sleep(std::time::Duration::from_millis(150)).await;
 
+    if let Some(ref mut baud_rate) = peripherals.baud_rate {
+        escalate_baud_rate(chip_responses, chip_commands, baud_rate.as_mut()).await?;
+    }
+
     Ok(())
 }
 
+/// Register setup and frequency ramp for midstate-format chips.
+///
+/// These chips don't roll versions, so there's no version mask to program,
+/// and the link stays at the power-on baud rate: their UART divider lives in
+/// a different register than the BM1370's.
+async fn initialize_midstate_chip<W>(
+    chip_commands: &mut W,
+    chip_type: protocol::ChipType,
+    frequency_mhz: Option<f32>,
+) -> Result<(), HashThreadError>
+where
+    W: Sink<protocol::Command> + Unpin,
+    W::Error: std::fmt::Debug,
+{
+    debug!(?chip_type, "Sending midstate chip configuration");
+    let ticket_mask = protocol::TicketMask::new(reporting_interval());
+    for command in protocol::BM13xxProtocol::new().legacy_chip_init(chip_type, ticket_mask) {
+        chip_commands.send(command).await.map_err(|e| {
+            HashThreadError::InitializationFailed(format!("Chip setup send failed: {:?}", e))
+        })?;
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+    }
+
+    if chip_type != protocol::ChipType::BM1397 {
+        info!(?chip_type, "Leaving chips at their power-on clock");
+        return Ok(());
+    }
+
+    let target_mhz = frequency_mhz.unwrap_or(BM1397_FREQUENCY_MHZ);
+    debug!(target_mhz, "Ramping frequency from 56.25 MHz");
+    for pll_config in generate_frequency_ramp_steps(56.25, target_mhz, FREQUENCY_STEP_MHZ) {
+        chip_commands
+            .send(protocol::Command::WriteRegister {
+                broadcast: true,
+                chip_address: 0x00,
+                register: protocol::Register::PllDivider(pll_config),
+            })
+            .await
+            .map_err(|e| {
+                HashThreadError::InitializationFailed(format!("PLL ramp failed: {:?}", e))
+            })?;
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    Ok(())
+}
+
+/// Move the chip link from the power-on rate to the board's target rate.
+///
+/// Chips switch as soon as they receive the UART baud write, so the host
+/// follows once the write has gone out, then reads back chip IDs to confirm
+/// both ends agree. If nothing answers at the new rate, both ends go back to
+/// the power-on rate and mining carries on there, just with less headroom.
+async fn escalate_baud_rate<R, W>(
+    chip_responses: &mut R,
+    chip_commands: &mut W,
+    baud_rate: &mut dyn BaudRateControl,
+) -> Result<(), HashThreadError>
+where
+    R: Stream<Item = Result<protocol::Response, std::io::Error>> + Unpin,
+    W: Sink<protocol::Command> + Unpin,
+    W::Error: std::fmt::Debug,
+{
+    let target = baud_rate.target_baud_rate();
+    if target == INITIAL_BAUD_RATE {
+        return Ok(());
+    }
+    let Some(register) = protocol::BaudRate::from_bps(target) else {
+        warn!(baud = target, "No chip register value for target baud rate");
+        return Ok(());
+    };
+
+    debug!(baud = target, "Switching chip link baud rate");
+    switch_baud_rate(chip_commands, baud_rate, register, target).await?;
+
+    if confirm_chips_respond(chip_responses, chip_commands).await {
+        info!(baud = target, "Chip link running at target baud rate");
+        return Ok(());
+    }
+
+    warn!(
+        baud = target,
+        fallback = INITIAL_BAUD_RATE,
+        "No chip response at target baud rate, falling back"
+    );
+    switch_baud_rate(
+        chip_commands,
+        baud_rate,
+        protocol::BaudRate::Baud115200,
+        INITIAL_BAUD_RATE,
+    )
+    .await
+}
+
+/// Tell the chips to change rate, then follow on the host side.
+async fn switch_baud_rate<W>(
+    chip_commands: &mut W,
+    baud_rate: &mut dyn BaudRateControl,
+    register: protocol::BaudRate,
+    bps: u32,
+) -> Result<(), HashThreadError>
+where
+    W: Sink<protocol::Command> + Unpin,
+    W::Error: std::fmt::Debug,
+{
+    chip_commands
+        .send(protocol::BM13xxProtocol::new().set_baudrate(register))
+        .await
+        .map_err(|e| {
+            HashThreadError::InitializationFailed(format!("Failed to send baud rate: {:?}", e))
+        })?;
+
+    // Let the write leave the UART before changing its rate under it
+    tokio::time::sleep(BAUD_SWITCH_SETTLE).await;
+
+    baud_rate.set_baud_rate(bps).await.map_err(|e| {
+        HashThreadError::InitializationFailed(format!("Failed to set host baud rate: {}", e))
+    })?;
+
+    tokio::time::sleep(BAUD_SWITCH_SETTLE).await;
+    Ok(())
+}
+
+/// Read chip IDs and report whether any chip answered.
+async fn confirm_chips_respond<R, W>(chip_responses: &mut R, chip_commands: &mut W) -> bool
+where
+    R: Stream<Item = Result<protocol::Response, std::io::Error>> + Unpin,
+    W: Sink<protocol::Command> + Unpin,
+    W::Error: std::fmt::Debug,
+{
+    if let Err(e) = chip_commands
+        .send(protocol::BM13xxProtocol::discover_chips())
+        .await
+    {
+        warn!(error = ?e, "Failed to send chip ID read");
+        return false;
+    }
+
+    let deadline = tokio::time::Instant::now() + BAUD_CONFIRM_TIMEOUT;
+    loop {
+        match tokio::time::timeout_at(deadline, chip_responses.next()).await {
+            Ok(Some(Ok(protocol::Response::ReadRegister {
+                register: protocol::Register::ChipId { .. },
+                ..
+            }))) => return true,
+            Ok(Some(_)) => continue,
+            Ok(None) | Err(_) => return false,
+        }
+    }
+}
+
+/// Read chip IDs and count the chips that answer.
+async fn enumerate_chips<R, W>(
+    chip_responses: &mut R,
+    chip_commands: &mut W,
+) -> Result<usize, HashThreadError>
+where
+    R: Stream<Item = Result<protocol::Response, std::io::Error>> + Unpin,
+    W: Sink<protocol::Command> + Unpin,
+    W::Error: std::fmt::Debug,
+{
+    chip_commands
+        .send(protocol::BM13xxProtocol::discover_chips())
+        .await
+        .map_err(|e| {
+            HashThreadError::InitializationFailed(format!("Failed to send chip ID read: {:?}", e))
+        })?;
+
+    let mut chips = 0;
+    let deadline = tokio::time::Instant::now() + CHIP_ENUMERATE_TIMEOUT;
+    loop {
+        match tokio::time::timeout_at(deadline, chip_responses.next()).await {
+            Ok(Some(Ok(protocol::Response::ReadRegister {
+                register: protocol::Register::ChipId { .. },
+                ..
+            }))) => chips += 1,
+            Ok(Some(_)) => continue,
+            Ok(None) | Err(_) => return Ok(chips),
+        }
+    }
+}
+
+/// Program the version-rolling mask on all chips.
+///
+/// Chips are initialized with full rolling; each job narrows that to the bits
+/// its pool authorized. An empty mask (pool refused version rolling) stops
+/// the chips from rolling at all.
+async fn set_version_mask<W>(
+    chip_commands: &mut W,
+    gp_bits_mask: GeneralPurposeBits,
+) -> Result<(), HashThreadError>
+where
+    W: Sink<protocol::Command> + Unpin,
+    W::Error: std::fmt::Debug,
+{
+    debug!(mask = ?gp_bits_mask, "Setting version mask");
+    chip_commands
+        .send(protocol::Command::WriteRegister {
+            broadcast: true,
+            chip_address: 0x00,
+            register: protocol::Register::VersionMask(protocol::VersionMask::from_gp_bits(
+                gp_bits_mask,
+            )),
+        })
+        .await
+        .map_err(|e| {
+            HashThreadError::WorkAssignmentFailed(format!("Failed to send version mask: {:?}", e))
+        })
+}
+
+/// Program the version-rolling mask for `gp_bits_mask` unless the chips
+/// already have it, keeping `chip_version_mask` up to date.
+///
+/// If sending fails the chips' mask is unknown, so it's forgotten and sent
+/// again next time.
+async fn ensure_version_mask<W>(
+    chip_commands: &mut W,
+    chip_version_mask: &mut Option<GeneralPurposeBits>,
+    gp_bits_mask: GeneralPurposeBits,
+) -> Result<(), HashThreadError>
+where
+    W: Sink<protocol::Command> + Unpin,
+    W::Error: std::fmt::Debug,
+{
+    if *chip_version_mask == Some(gp_bits_mask) {
+        return Ok(());
+    }
+    *chip_version_mask = None;
+    set_version_mask(chip_commands, gp_bits_mask).await?;
+    *chip_version_mask = Some(gp_bits_mask);
+    Ok(())
+}
+
+/// Program the ticket mask on all chips, so they report one nonce per
+/// `interval` hashes.
+async fn set_ticket_mask<W>(
+    chip_commands: &mut W,
+    interval: protocol::ReportingInterval,
+) -> Result<(), HashThreadError>
+where
+    W: Sink<protocol::Command> + Unpin,
+    W::Error: std::fmt::Debug,
+{
+    debug!(%interval, chip_difficulty = interval.difficulty(), "Setting ticket mask");
+    chip_commands
+        .send(protocol::Command::WriteRegister {
+            broadcast: true,
+            chip_address: 0x00,
+            register: protocol::Register::TicketMask(protocol::TicketMask::new(interval)),
+        })
+        .await
+        .map_err(|e| {
+            HashThreadError::WorkAssignmentFailed(format!("Failed to send ticket mask: {:?}", e))
+        })
+}
+
+/// Hashes per reported nonce, as set by the ticket mask at initialization.
+fn reporting_interval() -> protocol::ReportingInterval {
+    protocol::ReportingInterval::from_rate(
+        protocol::Hashrate::gibihashes_per_sec(CHAIN_HASHRATE_GIBIHASHES),
+        protocol::ReportingRate::nonces_per_sec(NONCES_PER_SEC),
+    )
+}
+
+/// Hashes per reported nonce while working on a task with `share_target`.
+///
+/// The chips filter at the share target, so every share reaches the thread
+/// but little else does. The rate stays within [`NONCES_PER_SEC`] and
+/// [`MAX_NONCES_PER_SEC`]: a harder target is filtered by the thread, and an
+/// easier one loses the shares the serial link couldn't keep up with anyway.
+fn ticket_interval(share_target: bitcoin::pow::Target) -> protocol::ReportingInterval {
+    let fastest = protocol::ReportingInterval::from_rate(
+        protocol::Hashrate::gibihashes_per_sec(CHAIN_HASHRATE_GIBIHASHES),
+        protocol::ReportingRate::nonces_per_sec(MAX_NONCES_PER_SEC),
+    );
+    let difficulty = Difficulty::from_target(share_target).as_f64();
+    protocol::ReportingInterval::for_difficulty(difficulty).clamp(fastest, reporting_interval())
+}
+
+/// Raise the thread's best difficulty to that of `hash`, if higher.
+fn record_best(status: &RwLock<HashThreadStatus>, hash: &bitcoin::BlockHash) {
+    let difficulty = Difficulty::from_hash(hash);
+    let mut status = status.write().unwrap();
+    if status.best_difficulty.is_none_or(|best| difficulty > best) {
+        status.best_difficulty = Some(difficulty);
+    }
+}
+
+/// Stall detector for a chain reporting at `interval`.
+fn stall_detector(interval: protocol::ReportingInterval, now: Instant) -> StallDetector {
+    let hashrate = HashRate((CHAIN_HASHRATE_GIBIHASHES * 2f64.powi(30)) as u64);
+    let hashes_per_nonce = 2f64.powi(interval.exponent().into());
+    StallDetector::new(
+        StallDetector::expected_interval(hashrate, hashes_per_nonce),
+        now,
+    )
+}
+
+/// Reset the chips and bring them back to work on `task`, if any.
+///
+/// Pulses the reset line, reprograms every register as on first
+/// initialization, counts the chips that answer, and resends the task. Jobs
+/// sent before the reset are forgotten, as the chips have lost them.
+///
+/// Returns the number of chips found; finding none is an error.
+async fn reset_chips<R, W>(
+    chip_responses: &mut R,
+    chip_commands: &mut W,
+    peripherals: &mut BoardPeripherals,
+    chip_jobs: &mut ChipJobTracker,
+    chip_type: protocol::ChipType,
+    frequency_mhz: Option<f32>,
+    task: Option<&HashTask>,
+) -> Result<usize, HashThreadError>
+where
+    R: Stream<Item = Result<protocol::Response, std::io::Error>> + Unpin,
+    W: Sink<protocol::Command> + Unpin,
+    W::Error: std::fmt::Debug,
+{
+    if let Some(ref mut asic_enable) = peripherals.asic_enable {
+        asic_enable.disable().await.map_err(|e| {
+            HashThreadError::InitializationFailed(format!("Failed to hold ASIC in reset: {}", e))
+        })?;
+        tokio::time::sleep(CHIP_RESET_HOLD).await;
+    }
+
+    initialize_chip(
+        chip_responses,
+        chip_commands,
+        peripherals,
+        chip_type,
+        frequency_mhz,
+    )
+    .await?;
+
+    let chips = enumerate_chips(chip_responses, chip_commands).await?;
+    if chips == 0 {
+        return Err(HashThreadError::InitializationFailed(
+            "no chips answered after reset".into(),
+        ));
+    }
+    debug!(chips, "Chips enumerated after reset");
+
+    chip_jobs.clear();
+    if let Some(task) = task {
+        let variant = chip_type.variant();
+        if variant.rolls_version() {
+            set_version_mask(chip_commands, task.template.version.gp_bits_mask()).await?;
+        }
+        set_ticket_mask(chip_commands, ticket_interval(task.share_target)).await?;
+        let job = task_to_job(task, chip_jobs.insert(task.clone()), variant)?;
+        chip_commands.send(job).await.map_err(|e| {
+            HashThreadError::WorkAssignmentFailed(format!("Failed to send job to chip: {:?}", e))
+        })?;
+    }
+    Ok(chips)
+}
+
 /// Generate frequency ramp steps for smooth PLL transitions
 fn generate_frequency_ramp_steps(
     start_mhz: f32,
@@ -575,52 +1148,228 @@ fn generate_frequency_ramp_steps(
     configs
 }
 
-/// Convert HashTask to JobFullFormat for chip hardware.
+/// Clock `chip_type` chips are ramped to when initialized, or `None` for
+/// chips left at their power-on clock, whose PLL isn't programmed.
+fn default_frequency(chip_type: protocol::ChipType) -> Option<f32> {
+    match chip_type {
+        protocol::ChipType::BM1397 => Some(BM1397_FREQUENCY_MHZ),
+        _ if chip_type.variant() == protocol::ProtocolVariant::Midstate => None,
+        _ => Some(DEFAULT_FREQUENCY_MHZ),
+    }
+}
+
+/// Move the chips' clock from `from_mhz` to `to_mhz`, one PLL step at a
+/// time in either direction.
+async fn retune<W>(chip_commands: &mut W, from_mhz: f32, to_mhz: f32) -> Result<(), HashThreadError>
+where
+    W: Sink<protocol::Command> + Unpin,
+    W::Error: std::fmt::Debug,
+{
+    let step = if to_mhz > from_mhz {
+        FREQUENCY_STEP_MHZ
+    } else {
+        -FREQUENCY_STEP_MHZ
+    };
+    let count = ((to_mhz - from_mhz) / step).ceil() as u32;
+    for i in 1..=count {
+        let mhz = if i == count {
+            to_mhz
+        } else {
+            from_mhz + step * i as f32
+        };
+        let Some(pll_config) = calculate_pll_for_frequency(mhz) else {
+            continue;
+        };
+        chip_commands
+            .send(protocol::Command::WriteRegister {
+                broadcast: true,
+                chip_address: 0x00,
+                register: protocol::Register::PllDivider(pll_config),
+            })
+            .await
+            .map_err(|e| HashThreadError::RetuneFailed(format!("PLL write failed: {:?}", e)))?;
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    Ok(())
+}
+
+/// What a chain of `variant` chips offers the scheduler.
+fn capabilities(variant: protocol::ProtocolVariant) -> HashThreadCapabilities {
+    let capabilities = HashThreadCapabilities::new(HashRate::from_terahashes(1.0)) // Stub
+        .with_midstates(variant.max_midstates())
+        .with_max_pending_jobs(CHIP_JOB_IDS);
+    if variant.rolls_version() {
+        capabilities.with_version_rolling()
+    } else {
+        capabilities
+    }
+}
+
+/// Convert HashTask to the job command `variant` chips take.
 ///
-/// Extracts or computes the merkle root, then builds a JobFullFormat with all
-/// block header fields. For computed merkle roots, requires EN2. For fixed merkle
-/// roots (Stratum v2 header-only), uses the template's fixed value directly.
-fn task_to_job_full(
+/// Computes the task's merkle root; jobs sent while mining come ready-made
+/// from a [`Pregenerator`] instead.
+fn task_to_job(
     task: &HashTask,
     chip_job_id: u8,
-) -> Result<protocol::JobFullFormat, HashThreadError> {
-    use crate::job_source::MerkleRootKind;
-
-    let template = task.template.as_ref();
-
-    // Get merkle root (computed or fixed)
-    let merkle_root = match &template.merkle_root {
-        MerkleRootKind::Computed(_) => {
-            // Extract EN2 (required for computed merkle roots)
-            let en2 = task.en2.as_ref().ok_or_else(|| {
-                HashThreadError::WorkAssignmentFailed(
-                    "EN2 required for computed merkle root".into(),
-                )
-            })?;
+    variant: protocol::ProtocolVariant,
+) -> Result<protocol::Command, HashThreadError> {
+    let header = task
+        .header_template()
+        .ok_or_else(|| HashThreadError::WorkAssignmentFailed("no merkle root for task".into()))?;
+    Ok(header_to_job(&header, chip_job_id, variant))
+}
 
-            // Compute merkle root for this EN2
-            template.compute_merkle_root(en2).map_err(|e| {
-                HashThreadError::WorkAssignmentFailed(format!(
-                    "Merkle root computation failed: {}",
-                    e
-                ))
-            })?
-        }
-        MerkleRootKind::Fixed(merkle_root) => *merkle_root,
-    };
+/// Convert a header template to the job command `variant` chips take.
+fn header_to_job(
+    header: &HeaderTemplate,
+    chip_job_id: u8,
+    variant: protocol::ProtocolVariant,
+) -> protocol::Command {
+    match variant {
+        protocol::ProtocolVariant::FullHeader => protocol::Command::JobFull {
+            job_data: header_to_job_full(header, chip_job_id),
+        },
+        protocol::ProtocolVariant::Midstate => protocol::Command::JobMidstate {
+            job_data: header_to_job_midstate(header, chip_job_id, variant.max_midstates()),
+        },
+    }
+}
+
+/// Give a pregenerated job the chip job ID it's sent under.
+fn with_job_id(mut job: protocol::Command, chip_job_id: u8) -> protocol::Command {
+    match &mut job {
+        protocol::Command::JobFull { job_data } => job_data.job_id = chip_job_id,
+        protocol::Command::JobMidstate { job_data } => job_data.job_id = chip_job_id,
+        _ => {}
+    }
+    job
+}
 
-    Ok(protocol::JobFullFormat {
+/// Convert a header template to JobFullFormat for chip hardware.
+fn header_to_job_full(header: &HeaderTemplate, chip_job_id: u8) -> protocol::JobFullFormat {
+    protocol::JobFullFormat {
         job_id: chip_job_id,
         num_midstates: 1,
         starting_nonce: 0,
-        nbits: template.bits,
-        ntime: task.ntime,
-        merkle_root,
-        prev_block_hash: template.prev_blockhash,
-        version: template.version.base(),
+        nbits: header.bits,
+        ntime: header.ntime,
+        merkle_root: header.merkle_root,
+        prev_block_hash: header.prev_blockhash,
+        version: header.version.base(),
+    }
+}
+
+/// Convert a header template to JobMidstateFormat for chip hardware.
+///
+/// Computes a midstate for each block version from [`midstate_versions`].
+/// The chip hashes the rest of the header from the last four bytes of the
+/// merkle root, ntime and nbits.
+fn header_to_job_midstate(
+    header: &HeaderTemplate,
+    chip_job_id: u8,
+    max_midstates: usize,
+) -> protocol::JobMidstateFormat {
+    let mut midstates = [None; 4];
+    for (midstate, version) in midstates
+        .iter_mut()
+        .zip(midstate_versions(&header.version, max_midstates))
+    {
+        *midstate = Some(protocol::header_midstate(&header.header(version, 0)));
+    }
+
+    let mut merkle4 = [0u8; 4];
+    merkle4.copy_from_slice(&header.merkle_root.to_byte_array()[28..]);
+
+    protocol::JobMidstateFormat {
+        job_id: chip_job_id,
+        num_midstates: midstates.iter().flatten().count() as u8,
+        starting_nonce: [0; 4],
+        nbits: header.bits.to_consensus().to_le_bytes(),
+        ntime: header.ntime.to_le_bytes(),
+        merkle4,
+        midstate0: midstates[0].expect("at least one version"),
+        midstate1: midstates[1],
+        midstate2: midstates[2],
+        midstate3: midstates[3],
+    }
+}
+
+/// Send the chips `task`'s job for the next ntime from `jobs`, tracked under
+/// a fresh chip job ID.
+///
+/// Advances the task's ntime to the job's.
+async fn send_next_job<W>(
+    jobs: &mut Pregenerator<protocol::Command>,
+    chip_jobs: &mut ChipJobTracker,
+    task: &mut HashTask,
+    chip_commands: &mut W,
+) -> Result<(), HashThreadError>
+where
+    W: Sink<protocol::Command> + Unpin,
+    W::Error: std::fmt::Debug,
+{
+    let next = jobs.next().await.ok_or_else(|| {
+        HashThreadError::WorkAssignmentFailed("no job could be prepared for task".into())
+    })?;
+    task.ntime = next.header.ntime;
+    let chip_job_id = chip_jobs.insert(task.clone());
+    chip_commands
+        .send(with_job_id(next.job, chip_job_id))
+        .await
+        .map_err(|e| {
+            HashThreadError::WorkAssignmentFailed(format!("Failed to send job to chip: {:?}", e))
+        })
+}
+
+/// Block versions a midstate job covers, one per midstate.
+///
+/// The base version comes first, followed by successive values of the bits
+/// the pool lets us roll. Chips take one midstate or a full set; a mask too
+/// narrow for a full set gets the base version alone.
+fn midstate_versions(
+    template: &VersionTemplate,
+    max_midstates: usize,
+) -> impl Iterator<Item = Version> + '_ {
+    let mask = u16::from_be_bytes(*template.gp_bits_mask().as_bytes());
+    let count = if mask.count_ones() >= max_midstates.ilog2() {
+        max_midstates
+    } else {
+        1
+    };
+
+    let mut bits = 0u16;
+    (0..count).map(move |_| {
+        let version = GeneralPurposeBits::new(bits.to_be_bytes()).apply_to_version(template.base());
+        // Next value within the mask: carry through the bits outside it
+        bits = (bits | !mask).wrapping_add(1) & mask;
+        version
     })
 }
 
+/// Core that found `nonce`, which full-header chips give in its top bits.
+fn nonce_core(nonce: u32) -> u8 {
+    ((nonce >> 25) & 0x7f) as u8
+}
+
+/// Block version a nonce was found with.
+///
+/// Full-header chips report the rolled bits; midstate chips report which
+/// midstate, and so which of [`midstate_versions`], found it.
+fn nonce_version(
+    template: &VersionTemplate,
+    variant: protocol::ProtocolVariant,
+    rolled: GeneralPurposeBits,
+    midstate_num: u8,
+) -> Option<Version> {
+    match variant {
+        protocol::ProtocolVariant::FullHeader => Some(rolled.apply_to_version(template.base())),
+        protocol::ProtocolVariant::Midstate => {
+            midstate_versions(template, variant.max_midstates()).nth(usize::from(midstate_num))
+        }
+    }
+}
+
 /// Calculate PLL configuration for a specific frequency
 fn calculate_pll_for_frequency(target_freq: f32) -> Option<protocol::PllConfig> {
     const CRYSTAL_FREQ: f32 = 25.0;
@@ -690,11 +1439,17 @@ fn calculate_pll_for_frequency(target_freq: f32) -> Option<protocol::PllConfig>
 ///
 /// Chip is disabled on startup to establish known state. Chip is enabled and
 /// configured when scheduler assigns first work.
+#[expect(
+    clippy::too_many_arguments,
+    reason = "the actor owns everything the thread was built with"
+)]
 async fn bm13xx_thread_actor<R, W>(
     mut cmd_rx: mpsc::Receiver<ThreadCommand>,
-    _evt_tx: mpsc::Sender<HashThreadEvent>,
+    evt_tx: mpsc::Sender<HashThreadEvent>,
     mut removal_rx: watch::Receiver<ThreadRemovalSignal>,
     status: Arc<RwLock<HashThreadStatus>>,
+    nonce_map: Arc<RwLock<NonceMap>>,
+    chip_type: protocol::ChipType,
     mut chip_responses: R,
     mut chip_commands: W,
     mut peripherals: BoardPeripherals,
@@ -710,17 +1465,29 @@ async fn bm13xx_thread_actor<R, W>(
         }
     }
 
+    let variant = chip_type.variant();
     let mut chip_initialized = false;
+    let mut frequency_mhz: Option<f32> = None;
+    let mut chip_version_mask: Option<GeneralPurposeBits> = None;
     let mut current_task: Option<HashTask> = None;
+    let mut pregen: Option<Pregenerator<protocol::Command>> = None;
     let mut chip_jobs = ChipJobTracker::new();
     let mut ntime_ticker = tokio::time::interval(tokio::time::Duration::from_secs(1));
     ntime_ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    let mut chip_interval: Option<protocol::ReportingInterval> = None;
+    let mut stall = stall_detector(reporting_interval(), Instant::now());
+    let mut stall_ticker = tokio::time::interval(STALL_CHECK_INTERVAL);
+    stall_ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
 
     loop {
         tokio::select! {
             // Removal signal (highest priority)
-            _ = removal_rx.changed() => {
-                let signal = removal_rx.borrow().clone();  // Clone to avoid holding borrow across await
+            changed = removal_rx.changed() => {
+                // A dropped sender means the board is gone
+                let signal = match changed {
+                    Ok(()) => removal_rx.borrow().clone(),  // Clone to avoid holding borrow across await
+                    Err(_) => ThreadRemovalSignal::BoardDisconnected,
+                };
                 match signal {
                     ThreadRemovalSignal::Running => {
                         // False alarm - still running
@@ -745,45 +1512,66 @@ async fn bm13xx_thread_actor<R, W>(
                         if let Some(ref old) = current_task {
                             debug!(
                                 old_job = %old.template.id,
-                                new_job = %new_task.template.id,
+                                job_id = %new_task.template.id,
                                 "Updating work"
                             );
                         } else {
-                            debug!(new_job = %new_task.template.id, "Updating work from idle");
+                            debug!(job_id = %new_task.template.id, "Updating work from idle");
                         }
 
                         if !chip_initialized {
                             trace!("Initializing chip on first assignment.");
-                            if let Err(e) = initialize_chip(&mut chip_commands, &mut peripherals).await {
+                            if let Err(e) = initialize_chip(&mut chip_responses, &mut chip_commands, &mut peripherals, chip_type, frequency_mhz).await {
                                 error!(error = %e, "Chip initialization failed");
                                 response_tx.send(Err(e)).ok();
                                 continue;
                             }
                             chip_initialized = true;
+                            chip_version_mask = None;
+                            chip_interval = None;
                         }
 
-                        // Send initial job to chip
-                        let chip_job_id = chip_jobs.insert(new_task.clone());
-                        let old_task = current_task.replace(new_task.clone());
-                        match task_to_job_full(&new_task, chip_job_id) {
-                            Ok(job_data) => {
-                                if let Err(e) = chip_commands.send(protocol::Command::JobFull { job_data }).await {
-                                    error!(error = ?e, "Failed to send initial JobFull to chip");
-                                    response_tx.send(Err(HashThreadError::WorkAssignmentFailed(
-                                        format!("Failed to send job to chip: {:?}", e)
-                                    ))).ok();
-                                    continue;
-                                } else {
-                                    debug!("Sent initial job to chip");
-                                }
+                        // Roll only the version bits the pool authorized
+                        if variant.rolls_version() {
+                            let gp_bits_mask = new_task.template.version.gp_bits_mask();
+                            if let Err(e) = ensure_version_mask(&mut chip_commands, &mut chip_version_mask, gp_bits_mask).await {
+                                error!(error = %e, "Failed to set version mask");
+                                response_tx.send(Err(e)).ok();
+                                continue;
                             }
-                            Err(e) => {
-                                error!(error = %e, "Failed to convert task to JobFull");
+                        }
+
+                        // Filter on the chips at the task's share target
+                        let interval = ticket_interval(new_task.share_target);
+                        if chip_interval != Some(interval) {
+                            if let Err(e) = set_ticket_mask(&mut chip_commands, interval).await {
+                                error!(error = %e, "Failed to set ticket mask");
                                 response_tx.send(Err(e)).ok();
                                 continue;
                             }
+                            chip_interval = Some(interval);
+                            stall = stall_detector(interval, Instant::now());
+                            status.write().unwrap().chip_difficulty = Some(interval.difficulty());
+                        }
+
+                        // Prepare the task's jobs ahead of the ntime rolls,
+                        // and send the first
+                        let jobs = pregen.insert(Pregenerator::start(&new_task, move |header| {
+                            Ok(header_to_job(header, 0, variant))
+                        }));
+                        let old_task = current_task.replace(new_task);
+                        let task = current_task.as_mut().unwrap();
+                        if let Err(e) = send_next_job(jobs, &mut chip_jobs, task, &mut chip_commands).await {
+                            error!(job_id = %task.template.id, error = %e, "Failed to send initial job to chip");
+                            response_tx.send(Err(e)).ok();
+                            continue;
                         }
+                        debug!(job_id = %task.template.id, ntime = task.ntime, "Sent initial job to chip");
 
+                        if old_task.is_none() {
+                            // Silence while idle doesn't count
+                            stall.restart(Instant::now());
+                        }
                         {
                             let mut s = status.write().unwrap();
                             s.is_active = true;
@@ -796,48 +1584,69 @@ async fn bm13xx_thread_actor<R, W>(
                         if let Some(ref old) = current_task {
                             debug!(
                                 old_job = %old.template.id,
-                                new_job = %new_task.template.id,
+                                job_id = %new_task.template.id,
                                 "Replacing work"
                             );
                         } else {
-                            debug!(new_job = %new_task.template.id, "Replacing work from idle");
+                            debug!(job_id = %new_task.template.id, "Replacing work from idle");
                         }
 
                         if !chip_initialized {
                             trace!("Initializing chip on first assignment.");
-                            if let Err(e) = initialize_chip(&mut chip_commands, &mut peripherals).await {
+                            if let Err(e) = initialize_chip(&mut chip_responses, &mut chip_commands, &mut peripherals, chip_type, frequency_mhz).await {
                                 error!(error = %e, "Chip initialization failed");
                                 response_tx.send(Err(e)).ok();
                                 continue;
                             }
                             chip_initialized = true;
+                            chip_version_mask = None;
+                            chip_interval = None;
                         }
 
-                        // Clear old jobs (old shares invalid)
-                        chip_jobs.clear();
-
-                        // Send initial job to chip
-                        let chip_job_id = chip_jobs.insert(new_task.clone());
-                        let old_task = current_task.replace(new_task.clone());
-                        match task_to_job_full(&new_task, chip_job_id) {
-                            Ok(job_data) => {
-                                if let Err(e) = chip_commands.send(protocol::Command::JobFull { job_data }).await {
-                                    error!(error = ?e, "Failed to send initial JobFull to chip");
-                                    response_tx.send(Err(HashThreadError::WorkAssignmentFailed(
-                                        format!("Failed to send job to chip: {:?}", e)
-                                    ))).ok();
-                                    continue;
-                                } else {
-                                    debug!("Sent initial job to chip (old work invalidated)");
-                                }
+                        // Roll only the version bits the pool authorized
+                        if variant.rolls_version() {
+                            let gp_bits_mask = new_task.template.version.gp_bits_mask();
+                            if let Err(e) = ensure_version_mask(&mut chip_commands, &mut chip_version_mask, gp_bits_mask).await {
+                                error!(error = %e, "Failed to set version mask");
+                                response_tx.send(Err(e)).ok();
+                                continue;
                             }
-                            Err(e) => {
-                                error!(error = %e, "Failed to convert task to JobFull");
+                        }
+
+                        // Filter on the chips at the task's share target
+                        let interval = ticket_interval(new_task.share_target);
+                        if chip_interval != Some(interval) {
+                            if let Err(e) = set_ticket_mask(&mut chip_commands, interval).await {
+                                error!(error = %e, "Failed to set ticket mask");
                                 response_tx.send(Err(e)).ok();
                                 continue;
                             }
+                            chip_interval = Some(interval);
+                            stall = stall_detector(interval, Instant::now());
+                            status.write().unwrap().chip_difficulty = Some(interval.difficulty());
+                        }
+
+                        // Clear old jobs (old shares invalid)
+                        chip_jobs.clear();
+
+                        // Prepare the task's jobs ahead of the ntime rolls,
+                        // and send the first
+                        let jobs = pregen.insert(Pregenerator::start(&new_task, move |header| {
+                            Ok(header_to_job(header, 0, variant))
+                        }));
+                        let old_task = current_task.replace(new_task);
+                        let task = current_task.as_mut().unwrap();
+                        if let Err(e) = send_next_job(jobs, &mut chip_jobs, task, &mut chip_commands).await {
+                            error!(job_id = %task.template.id, error = %e, "Failed to send initial job to chip");
+                            response_tx.send(Err(e)).ok();
+                            continue;
                         }
+                        debug!(job_id = %task.template.id, ntime = task.ntime, "Sent initial job to chip (old work invalidated)");
 
+                        if old_task.is_none() {
+                            // Silence while idle doesn't count
+                            stall.restart(Instant::now());
+                        }
                         {
                             let mut s = status.write().unwrap();
                             s.is_active = true;
@@ -850,6 +1659,7 @@ async fn bm13xx_thread_actor<R, W>(
                         debug!("Going idle");
 
                         let old_task = current_task.take();
+                        pregen = None;
 
                         {
                             let mut s = status.write().unwrap();
@@ -859,6 +1669,55 @@ async fn bm13xx_thread_actor<R, W>(
                         response_tx.send(Ok(old_task)).ok();
                     }
 
+                    ThreadCommand::ResetChips { response_tx } => {
+                        info!("Resetting chips on request");
+                        let task = current_task.as_ref();
+                        let result = reset_chips(&mut chip_responses, &mut chip_commands, &mut peripherals, &mut chip_jobs, chip_type, frequency_mhz, task).await;
+                        match &result {
+                            Ok(chips) => {
+                                chip_initialized = true;
+                                chip_version_mask = task.map(|t| t.template.version.gp_bits_mask());
+                                chip_interval = Some(task.map_or_else(reporting_interval, |t| ticket_interval(t.share_target)));
+                                stall.restart(Instant::now());
+                                info!(chips, "Chips reset.");
+                            }
+                            Err(e) => {
+                                chip_initialized = false;
+                                chip_version_mask = None;
+                                chip_interval = None;
+                                error!(error = %e, "Chip reset failed");
+                            }
+                        }
+                        response_tx.send(result).ok();
+                    }
+
+                    ThreadCommand::SetFrequency { mhz, response_tx } => {
+                        let result = match default_frequency(chip_type) {
+                            None => Err(HashThreadError::RetuneFailed(format!("{:?} clock isn't programmable", chip_type))),
+                            Some(_) if calculate_pll_for_frequency(mhz).is_none() => {
+                                Err(HashThreadError::RetuneFailed(format!("no PLL setting for {} MHz", mhz)))
+                            }
+                            Some(default) if chip_initialized => {
+                                let from = frequency_mhz.unwrap_or(default);
+                                debug!(from_mhz = from, to_mhz = mhz, "Retuning chips");
+                                let result = retune(&mut chip_commands, from, mhz).await;
+                                // Chips left partway are ramped afresh on the next assignment
+                                chip_initialized = result.is_ok();
+                                result
+                            }
+                            // Taken up when the chips are initialized
+                            Some(_) => Ok(()),
+                        };
+                        match &result {
+                            Ok(()) => {
+                                frequency_mhz = Some(mhz);
+                                info!(mhz, "Chip frequency set.");
+                            }
+                            Err(e) => error!(error = %e, "Frequency change failed"),
+                        }
+                        response_tx.send(result).ok();
+                    }
+
                     ThreadCommand::Shutdown => {
                         info!("Shutdown command received");
                         // Exit actor loop (channel closure signals shutdown to scheduler)
@@ -873,47 +1732,38 @@ async fn bm13xx_thread_actor<R, W>(
                     Ok(response) => {
                         match response {
                             protocol::Response::Nonce { nonce, job_id, version, midstate_num, subcore_id } => {
+                                if stall.nonce(Instant::now()) {
+                                    info!("Chips are reporting nonces again.");
+                                    let _ = evt_tx.send(HashThreadEvent::Recovered).await;
+                                }
+                                if chip_type.core_layout().is_some() {
+                                    nonce_map.write().unwrap().record(nonce_core(nonce), subcore_id);
+                                }
+
                                 // Look up the task for this job_id
                                 if let Some(task) = chip_jobs.get(job_id) {
                                     let template = task.template.as_ref();
 
-                                    // Reconstruct full version from rolling field
-                                    let full_version = version.apply_to_version(template.version.base());
-
-                                    // Compute merkle root for this task's EN2
-                                    match task.en2.as_ref().and_then(|en2| template.compute_merkle_root(en2).ok()) {
-                                        Some(merkle_root) => {
-                                            // Build block header
-                                            let header = BlockHeader {
-                                                version: full_version,
-                                                prev_blockhash: template.prev_blockhash,
-                                                merkle_root,
-                                                time: task.ntime,
-                                                bits: template.bits,
-                                                nonce,
-                                            };
-
-                                            // Compute hash
-                                            let hash = header.block_hash();
-
-                                            // Validate against task share target
-                                            if task.share_target.is_met_by(hash) {
-                                                let expected_hashes = U256::from(task.share_target.to_work());
-                                                let share = Share {
-                                                    nonce,
-                                                    hash,
-                                                    version: full_version,
-                                                    ntime: task.ntime,
-                                                    extranonce2: task.en2,
-                                                    expected_hashes,
-                                                };
+                                    // Reconstruct full version from rolling field or midstate
+                                    let Some(full_version) = nonce_version(&template.version, variant, version, midstate_num) else {
+                                        debug!(job_id = %template.id, chip_job_id = job_id, midstate_num, "Nonce from a midstate the job didn't have");
+                                        continue;
+                                    };
+
+                                    // Merkle root is fixed, or computed for this task's EN2
+                                    match task.header(full_version, task.ntime, nonce) {
+                                        Some(header) => match task.verify_nonce(&header) {
+                                            Ok(share) => {
+                                                let hash = share.hash;
+                                                record_best(&status, &hash);
 
                                                 // Send via task's dedicated channel
                                                 if task.share_tx.send(share).await.is_err() {
                                                     // Channel closed = task replaced, share is stale
-                                                    debug!("Share channel closed (task replaced)");
+                                                    debug!(job_id = %template.id, "Share channel closed (task replaced)");
                                                 } else {
                                                     debug!(
+                                                        job_id = %template.id,
                                                         chip_job_id = job_id,
                                                         nonce = format!("{:#x}", nonce),
                                                         hash = %hash,
@@ -922,8 +1772,11 @@ async fn bm13xx_thread_actor<R, W>(
                                                         "Share found and sent"
                                                     );
                                                 }
-                                            } else {
+                                            }
+                                            Err(hash) => {
+                                                record_best(&status, &hash);
                                                 trace!(
+                                                    job_id = %template.id,
                                                     chip_job_id = job_id,
                                                     nonce = format!("{:#x}", nonce),
                                                     hash = %hash,
@@ -932,9 +1785,10 @@ async fn bm13xx_thread_actor<R, W>(
                                                     "Nonce does not meet target (filtered)"
                                                 );
                                             }
-                                        }
+                                        },
                                         None => {
                                             error!(
+                                                job_id = %template.id,
                                                 chip_job_id = job_id,
                                                 "Failed to compute merkle root for nonce"
                                             );
@@ -947,8 +1801,6 @@ async fn bm13xx_thread_actor<R, W>(
                                         "Nonce for unknown job_id (possibly stale)"
                                     );
                                 }
-
-                                let _ = (midstate_num, subcore_id); // Unused for now
                             }
 
                             protocol::Response::ReadRegister { chip_address, register } => {
@@ -964,27 +1816,60 @@ async fn bm13xx_thread_actor<R, W>(
                 }
             }
 
-            // ntime rolling timer (roll forward every second)
-            _ = ntime_ticker.tick(), if current_task.is_some() => {
-                let task = current_task.as_mut().unwrap();
-
-                // Increment ntime
-                task.ntime += 1;
-
-                // Convert to chip format and send
-                match task_to_job_full(task, chip_jobs.insert(task.clone())) {
-                    Ok(job_data) => {
-                        if let Err(e) = chip_commands.send(protocol::Command::JobFull { job_data }).await {
-                            error!(error = ?e, "Failed to send JobFull to chip");
-                        } else {
-                            trace!(ntime = task.ntime, "Sent ntime-rolled job to chip");
+            // Chips gone quiet while they have work
+            _ = stall_ticker.tick(), if current_task.is_some() => {
+                let now = Instant::now();
+                let Some(action) = stall.check(now) else {
+                    continue;
+                };
+                let silent_for = stall.silent_for(now);
+                warn!(silent_secs = silent_for.as_secs(), %action, "No nonces from chips; recovering");
+                let _ = evt_tx.send(HashThreadEvent::Stalled { silent_for, action }).await;
+
+                match action {
+                    StallAction::ResetChips => {
+                        let task = current_task.as_ref().unwrap();
+                        chip_version_mask = None;
+                        match reset_chips(&mut chip_responses, &mut chip_commands, &mut peripherals, &mut chip_jobs, chip_type, frequency_mhz, Some(task)).await {
+                            Ok(chips) => {
+                                chip_version_mask = Some(task.template.version.gp_bits_mask());
+                                chip_interval = Some(ticket_interval(task.share_target));
+                                info!(chips, "Chips reset.");
+                            }
+                            Err(e) => {
+                                // Initialize afresh on the next assignment
+                                chip_initialized = false;
+                                error!(error = %e, "Chip reset failed");
+                            }
                         }
                     }
-                    Err(e) => {
-                        error!(error = %e, "Failed to convert task to JobFull");
+                    StallAction::ReinitBoard => {
+                        let reason = format!(
+                            "no nonces for {}s, even after a chip reset",
+                            silent_for.as_secs()
+                        );
+                        match &peripherals.board_fault {
+                            Some(board_fault) => {
+                                let _ = board_fault.send(reason).await;
+                            }
+                            None => error!("Board can't be reinitialized from the hash thread"),
+                        }
                     }
                 }
             }
+
+            // ntime rolling timer (roll forward every second)
+            _ = ntime_ticker.tick(), if current_task.is_some() => {
+                let (Some(task), Some(jobs)) = (current_task.as_mut(), pregen.as_mut()) else {
+                    continue;
+                };
+
+                // The next ntime's job is already prepared
+                match send_next_job(jobs, &mut chip_jobs, task, &mut chip_commands).await {
+                    Ok(()) => trace!(job_id = %task.template.id, ntime = task.ntime, "Sent ntime-rolled job to chip"),
+                    Err(e) => error!(job_id = %task.template.id, error = %e, "Failed to send ntime-rolled job to chip"),
+                }
+            }
         }
     }
 
@@ -994,9 +1879,10 @@ async fn bm13xx_thread_actor<R, W>(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use bitcoin::block::Header as BlockHeader;
 
     #[test]
-    fn test_task_to_job_full_converts_high_level_types() {
+    fn test_header_to_job_full_converts_high_level_types() {
         use crate::asic::bm13xx::test_data::esp_miner_job;
         use crate::job_source::{
             Extranonce2, GeneralPurposeBits, JobTemplate, MerkleRootKind, VersionTemplate,
@@ -1034,7 +1920,8 @@ mod tests {
         };
 
         // Convert to JobFullFormat
-        let result = task_to_job_full(&task, *esp_miner_job::wire_tx::JOB_ID).unwrap();
+        let header = task.header_template().unwrap();
+        let result = header_to_job_full(&header, *esp_miner_job::wire_tx::JOB_ID);
 
         // Verify all fields match expected Bitcoin types
         assert_eq!(result.job_id, *esp_miner_job::wire_tx::JOB_ID);
@@ -1049,4 +1936,279 @@ mod tests {
         );
         assert_eq!(result.merkle_root, *esp_miner_job::wire_tx::MERKLE_ROOT);
     }
+
+    fn midstate_task(gp_bits_mask: GeneralPurposeBits) -> HashTask {
+        use crate::asic::bm13xx::test_data::esp_miner_job;
+        use crate::job_source::{Extranonce2, JobTemplate, MerkleRootKind};
+
+        let template = Arc::new(JobTemplate {
+            id: "test".into(),
+            prev_blockhash: *esp_miner_job::wire_tx::PREV_BLOCKHASH,
+            version: VersionTemplate::new(*esp_miner_job::wire_tx::VERSION, gp_bits_mask).unwrap(),
+            bits: *esp_miner_job::wire_tx::NBITS,
+            share_target: crate::types::Difficulty::from(100_u64).to_target(),
+            time: *esp_miner_job::wire_tx::NTIME,
+            merkle_root: MerkleRootKind::Fixed(*esp_miner_job::wire_tx::MERKLE_ROOT),
+        });
+        let (share_tx, _share_rx) = mpsc::channel(1);
+        HashTask {
+            template,
+            en2_range: None,
+            en2: Some(Extranonce2::new(0, 1).unwrap()),
+            share_target: crate::types::Difficulty::from(100_u64).to_target(),
+            ntime: *esp_miner_job::wire_tx::NTIME,
+            share_tx,
+        }
+    }
+
+    #[test]
+    fn test_midstate_versions_roll_within_mask() {
+        let base = Version::from_consensus(0x2000_0000);
+
+        let none = VersionTemplate::new(base, GeneralPurposeBits::none()).unwrap();
+        assert!(midstate_versions(&none, 4).eq([base]));
+
+        // Too narrow for four midstates
+        let one_bit = VersionTemplate::new(base, GeneralPurposeBits::new([0x00, 0x01])).unwrap();
+        assert!(midstate_versions(&one_bit, 4).eq([base]));
+
+        // Stratum's usual 0x1fffe000 mask rolls from bit 13 up
+        let full = VersionTemplate::new(base, GeneralPurposeBits::full()).unwrap();
+        let versions: Vec<i32> = midstate_versions(&full, 4)
+            .map(|v| v.to_consensus())
+            .collect();
+        assert_eq!(
+            versions,
+            [0x2000_0000, 0x2000_2000, 0x2000_4000, 0x2000_6000]
+        );
+
+        // Gaps in the mask are skipped
+        let gappy = VersionTemplate::new(base, GeneralPurposeBits::new([0x00, 0x05])).unwrap();
+        let versions: Vec<i32> = midstate_versions(&gappy, 4)
+            .map(|v| v.to_consensus())
+            .collect();
+        assert_eq!(
+            versions,
+            [0x2000_0000, 0x2000_2000, 0x2000_8000, 0x2000_a000]
+        );
+        assert_eq!(midstate_versions(&gappy, 1).count(), 1);
+    }
+
+    #[test]
+    fn test_ticket_interval_follows_share_target() {
+        let interval = |diff: u64| ticket_interval(Difficulty::from(diff).to_target());
+
+        // Within the reporting rate bounds the chips filter at the target
+        assert_eq!(interval(100).difficulty(), 64);
+
+        // A hard target is left to the thread, to keep the health signal
+        assert_eq!(interval(1_000_000), reporting_interval());
+
+        // An easy target doesn't flood the serial link
+        assert!(interval(1).difficulty() > 1);
+    }
+
+    #[test]
+    fn test_header_to_job_midstate() {
+        use crate::asic::bm13xx::test_data::esp_miner_job;
+
+        let task = midstate_task(GeneralPurposeBits::full());
+        let job = header_to_job_midstate(&task.header_template().unwrap(), 3, 4);
+        assert_eq!(job.job_id, 3);
+        assert_eq!(job.num_midstates, 4);
+        assert!(job.midstate3.is_some());
+        assert_eq!(job.ntime, esp_miner_job::wire_tx::NTIME.to_le_bytes());
+
+        // Each midstate is of the header with its version
+        let template = task.template.as_ref();
+        let versions: Vec<Version> = midstate_versions(&template.version, 4).collect();
+        let midstates = [
+            job.midstate0,
+            job.midstate1.unwrap(),
+            job.midstate2.unwrap(),
+            job.midstate3.unwrap(),
+        ];
+        for (version, midstate) in versions.iter().zip(midstates) {
+            let header = BlockHeader {
+                version: *version,
+                prev_blockhash: template.prev_blockhash,
+                merkle_root: *esp_miner_job::wire_tx::MERKLE_ROOT,
+                time: task.ntime,
+                bits: template.bits,
+                nonce: 0,
+            };
+            assert_eq!(midstate, protocol::header_midstate(&header));
+            let bytes = bitcoin::consensus::serialize(&header);
+            assert_eq!(job.merkle4, bytes[64..68]);
+            assert_eq!(job.nbits, bytes[72..76]);
+        }
+
+        // A nonce names its midstate, which gives back the version
+        assert_eq!(
+            nonce_version(
+                &template.version,
+                protocol::ProtocolVariant::Midstate,
+                GeneralPurposeBits::none(),
+                2
+            ),
+            Some(versions[2])
+        );
+        assert_eq!(
+            nonce_version(
+                &template.version,
+                protocol::ProtocolVariant::Midstate,
+                GeneralPurposeBits::none(),
+                3
+            ),
+            Some(versions[3])
+        );
+
+        // Without rolling the job carries a single midstate
+        let fixed = midstate_task(GeneralPurposeBits::none());
+        let job = header_to_job_midstate(&fixed.header_template().unwrap(), 0, 4);
+        assert_eq!(job.num_midstates, 1);
+        assert!(job.midstate1.is_none());
+        assert_eq!(
+            nonce_version(
+                &fixed.template.version,
+                protocol::ProtocolVariant::Midstate,
+                GeneralPurposeBits::none(),
+                1
+            ),
+            None
+        );
+    }
+
+    /// Records the host rates a thread asks for.
+    struct RecordingBaudRate {
+        rates: Arc<std::sync::Mutex<Vec<u32>>>,
+    }
+
+    #[async_trait]
+    impl BaudRateControl for RecordingBaudRate {
+        fn target_baud_rate(&self) -> u32 {
+            1_000_000
+        }
+
+        async fn set_baud_rate(&mut self, baud: u32) -> anyhow::Result<()> {
+            self.rates.lock().unwrap().push(baud);
+            Ok(())
+        }
+    }
+
+    async fn escalate(responses: Vec<protocol::Response>) -> (Vec<u32>, Vec<protocol::Command>) {
+        let rates = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut baud_rate = RecordingBaudRate {
+            rates: rates.clone(),
+        };
+        let mut chip_responses = futures::stream::iter(responses.into_iter().map(Ok));
+        let (mut chip_commands, sent) = futures::channel::mpsc::unbounded();
+
+        escalate_baud_rate(&mut chip_responses, &mut chip_commands, &mut baud_rate)
+            .await
+            .unwrap();
+
+        drop(chip_commands);
+        let sent = futures::StreamExt::collect::<Vec<_>>(sent).await;
+        let rates = rates.lock().unwrap().clone();
+        (rates, sent)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_baud_escalation_confirmed() {
+        let chip_id = protocol::Response::ReadRegister {
+            chip_address: 0,
+            register: protocol::Register::ChipId {
+                chip_type: protocol::ChipType::BM1370,
+                core_count: 0,
+                address: 0,
+            },
+        };
+
+        let (rates, sent) = escalate(vec![chip_id]).await;
+
+        assert_eq!(rates, vec![1_000_000]);
+        assert_eq!(sent.len(), 2);
+        assert!(matches!(
+            sent[0],
+            protocol::Command::WriteRegister {
+                register: protocol::Register::UartBaud(protocol::BaudRate::Baud1M),
+                ..
+            }
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_baud_escalation_falls_back_without_response() {
+        let (rates, sent) = escalate(Vec::new()).await;
+
+        // Both ends go back to the power-on rate
+        assert_eq!(rates, vec![1_000_000, 115_200]);
+        assert!(matches!(
+            sent.last(),
+            Some(protocol::Command::WriteRegister {
+                register: protocol::Register::UartBaud(protocol::BaudRate::Baud115200),
+                ..
+            })
+        ));
+    }
+    #[tokio::test(start_paused = true)]
+    async fn test_enumerate_counts_chip_ids() {
+        let chip_id = |address| protocol::Response::ReadRegister {
+            chip_address: address,
+            register: protocol::Register::ChipId {
+                chip_type: protocol::ChipType::BM1370,
+                core_count: 0,
+                address,
+            },
+        };
+        let stray = protocol::Response::Nonce {
+            nonce: 0,
+            job_id: 0,
+            midstate_num: 0,
+            version: GeneralPurposeBits::new([0, 0]),
+            subcore_id: 0,
+        };
+        let mut chip_responses = futures::stream::iter(
+            [chip_id(0), stray, chip_id(2), chip_id(4)]
+                .into_iter()
+                .map(Ok),
+        );
+        let (mut chip_commands, _sent) = futures::channel::mpsc::unbounded();
+
+        let chips = enumerate_chips(&mut chip_responses, &mut chip_commands)
+            .await
+            .unwrap();
+        assert_eq!(chips, 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retune_steps_both_ways() {
+        async fn plls(from_mhz: f32, to_mhz: f32) -> Vec<protocol::PllConfig> {
+            let (mut chip_commands, sent) = futures::channel::mpsc::unbounded();
+            retune(&mut chip_commands, from_mhz, to_mhz).await.unwrap();
+            drop(chip_commands);
+            futures::StreamExt::collect::<Vec<_>>(sent)
+                .await
+                .into_iter()
+                .map(|command| match command {
+                    protocol::Command::WriteRegister {
+                        register: protocol::Register::PllDivider(pll),
+                        ..
+                    } => pll,
+                    other => panic!("unexpected command {:?}", other),
+                })
+                .collect()
+        }
+
+        let pll = |mhz| calculate_pll_for_frequency(mhz).unwrap();
+
+        let up = plls(525.0, 540.0).await;
+        assert_eq!(up, vec![pll(531.25), pll(537.5), pll(540.0)]);
+
+        let down = plls(525.0, 512.5).await;
+        assert_eq!(down, vec![pll(518.75), pll(512.5)]);
+
+        assert!(plls(525.0, 525.0).await.is_empty());
+    }
 }
@@ -10,8 +10,9 @@
 //! Share filtering happens at three independent levels:
 //!
 //! 1. **Chip TicketMask (hardware pre-filter):**
-//!    Thread configures chip with low difficulty for frequent health signals.
-//!    Chip only reports nonces meeting this hardware threshold.
+//!    Thread configures chip at the task's share target, bounded so nonces
+//!    still arrive often enough to signal health and seldom enough for the
+//!    link. Chip only reports nonces meeting this hardware threshold.
 //!
 //! 2. **HashTask.share_target (thread-to-scheduler filter):**
 //!    Scheduler sets when assigning work. Thread computes hash for every chip
@@ -30,26 +31,83 @@ use std::fmt;
 use std::sync::Arc;
 
 use async_trait::async_trait;
-use bitcoin::block::Version;
+use bitcoin::block::{Header as BlockHeader, Version};
 use bitcoin::pow::Target;
 use bitcoin::BlockHash;
 use tokio::sync::mpsc;
 
-use crate::job_source::{Extranonce2, Extranonce2Range, JobTemplate};
-use crate::types::HashRate;
+use super::stall::StallAction;
+use crate::job_source::{
+    Extranonce2, Extranonce2Range, HeaderTemplate, JobTemplate, MerkleRootKind, VersionTemplate,
+};
+use crate::types::{Difficulty, HashRate};
 use crate::u256::U256;
 
 /// HashThread capabilities reported to scheduler for work assignment decisions.
+///
+/// Threads differ in how much of a task they search before moving to the
+/// next extranonce2: chips that roll version bits cover every version the
+/// pool allows, midstate chips cover a handful, and others only the base
+/// version. The scheduler sizes and reclaims extranonce2 slices from this
+/// rather than assuming every thread works the same way.
 #[derive(Debug, Clone)]
 pub struct HashThreadCapabilities {
     /// Estimated hashrate
     pub hashrate_estimate: HashRate,
-    // Future capabilities:
-    // pub can_roll_version: bool,
-    // pub version_roll_bits: u32,
-    // pub can_roll_ntime: bool,
-    // pub ntime_range: Option<std::ops::Range<u32>>,
-    // pub can_iterate_extranonce2: bool,
+
+    /// Whether the hardware rolls the version bits the template allows
+    pub rolls_version: bool,
+
+    /// Versions hashed side by side per job, for hardware that takes
+    /// host-computed midstates (1 otherwise)
+    pub midstates: usize,
+
+    /// Jobs the thread keeps track of at once; nonces for jobs older than
+    /// this are dropped
+    pub max_pending_jobs: usize,
+}
+
+impl HashThreadCapabilities {
+    /// A thread that hashes the base version of one job at a time.
+    pub fn new(hashrate_estimate: HashRate) -> Self {
+        Self {
+            hashrate_estimate,
+            rolls_version: false,
+            midstates: 1,
+            max_pending_jobs: 1,
+        }
+    }
+
+    /// The hardware rolls version bits itself.
+    pub fn with_version_rolling(mut self) -> Self {
+        self.rolls_version = true;
+        self
+    }
+
+    /// The hardware hashes `midstates` versions per job.
+    pub fn with_midstates(mut self, midstates: usize) -> Self {
+        self.midstates = midstates.max(1);
+        self
+    }
+
+    /// The thread tracks up to `jobs` jobs in flight.
+    pub fn with_max_pending_jobs(mut self, jobs: usize) -> Self {
+        self.max_pending_jobs = jobs.max(1);
+        self
+    }
+
+    /// Hashes the thread searches under one extranonce2 of a job with
+    /// `version` before it has to roll ntime or move on.
+    pub fn hashes_per_en2(&self, version: &VersionTemplate) -> f64 {
+        let mask = u16::from_be_bytes(*version.gp_bits_mask().as_bytes());
+        let versions = 2f64.powi(mask.count_ones() as i32);
+        let hashed = if self.rolls_version {
+            versions
+        } else {
+            versions.min(self.midstates as f64)
+        };
+        hashed * 2f64.powi(32)
+    }
 }
 
 /// Current runtime status of a HashThread.
@@ -67,9 +125,17 @@ pub struct HashThreadStatus {
     /// Number of hardware errors detected
     pub hardware_errors: u64,
 
+    /// Highest difficulty of any nonce the chips have found, whether or not
+    /// it made a share
+    pub best_difficulty: Option<Difficulty>,
+
     /// Current chip temperature if available
     pub temperature_c: Option<f32>,
 
+    /// Difficulty below which the chips drop nonces themselves, if they
+    /// filter in hardware
+    pub chip_difficulty: Option<u64>,
+
     /// Whether thread is actively working
     pub is_active: bool,
 }
@@ -99,6 +165,18 @@ pub enum HashThreadEvent {
 
     /// Periodic status update
     StatusUpdate(HashThreadStatus),
+
+    /// No nonces for far longer than the hashrate predicts (see
+    /// [`crate::asic::stall`]); the thread is trying `action` to recover
+    Stalled {
+        /// Time since the last nonce
+        silent_for: std::time::Duration,
+        /// Recovery being attempted
+        action: StallAction,
+    },
+
+    /// Nonces are arriving again after a stall
+    Recovered,
 }
 
 /// Error types for HashThread operations.
@@ -121,6 +199,9 @@ pub enum HashThreadError {
 
     #[error("Chip initialization failed: {0}")]
     InitializationFailed(String),
+
+    #[error("Frequency change failed: {0}")]
+    RetuneFailed(String),
 }
 
 // ---------------------------------------------------------------------------
@@ -150,6 +231,19 @@ pub trait VoltageRegulator: Send + Sync {
     async fn set_voltage(&mut self, volts: f32) -> anyhow::Result<()>;
 }
 
+/// Host side of the serial link to the chips.
+///
+/// Chips come out of reset talking at a slow rate. Hash threads that move
+/// them to a faster one use this to follow on the host side.
+#[async_trait]
+pub trait BaudRateControl: Send + Sync {
+    /// Fastest rate the board's link carries reliably.
+    fn target_baud_rate(&self) -> u32;
+
+    /// Switch the host end of the link to `baud`.
+    async fn set_baud_rate(&mut self, baud: u32) -> anyhow::Result<()>;
+}
+
 /// Hardware interfaces provided by the board to the hash thread.
 ///
 /// Bundles optional hardware capabilities. Not all boards provide all
@@ -160,6 +254,13 @@ pub struct BoardPeripherals {
 
     /// Voltage regulator control
     pub voltage_regulator: Option<Box<dyn VoltageRegulator>>,
+
+    /// Host UART rate control for the chip link
+    pub baud_rate: Option<Box<dyn BaudRateControl>>,
+
+    /// Asks the board's task to reinitialize the board, giving the reason.
+    /// For faults a thread can't clear by resetting its chips.
+    pub board_fault: Option<mpsc::Sender<String>>,
 }
 
 /// Signal from board to hash thread for shutdown coordination.
@@ -201,6 +302,12 @@ pub trait HashThread: Send {
     /// Human-readable name for logging (e.g., "Bitaxe Gamma (e2f56f9b)")
     fn name(&self) -> &str;
 
+    /// ID of the board the thread hashes on, if it's on one. The scheduler
+    /// pauses boards by it.
+    fn board_id(&self) -> Option<&str> {
+        None
+    }
+
     /// Get thread capabilities for scheduling decisions
     fn capabilities(&self) -> &HashThreadCapabilities;
 
@@ -298,6 +405,93 @@ pub struct HashTask {
     pub share_tx: mpsc::Sender<Share>,
 }
 
+impl HashTask {
+    /// Merkle root of the block header this task hashes.
+    ///
+    /// Returns None if the template's merkle root depends on extranonce2 and
+    /// the task doesn't carry one, or the root can't be computed.
+    pub fn merkle_root(&self) -> Option<bitcoin::TxMerkleNode> {
+        match &self.template.merkle_root {
+            MerkleRootKind::Fixed(root) => Some(*root),
+            MerkleRootKind::Computed(_) => {
+                let en2 = self.en2.as_ref()?;
+                self.template.compute_merkle_root(en2).ok()
+            }
+        }
+    }
+
+    /// The header template the task starts from: its extranonce2 at its
+    /// time.
+    ///
+    /// Returns None under the same conditions as [`HashTask::merkle_root`].
+    pub fn header_template(&self) -> Option<HeaderTemplate> {
+        HeaderTemplate::new(&self.template, self.en2, self.ntime)
+    }
+
+    /// Advance ntime by a second, unless that takes it past what the pool
+    /// accepts for the job. Returns whether it advanced.
+    pub fn roll_ntime(&mut self) -> bool {
+        if self.ntime >= self.template.max_ntime() {
+            return false;
+        }
+        self.ntime += 1;
+        true
+    }
+
+    /// Header a thread's hardware hashed to find `nonce`, given the version
+    /// and time it reports.
+    ///
+    /// Returns None if the task has no merkle root (see
+    /// [`HashTask::merkle_root`]).
+    pub fn header(&self, version: Version, ntime: u32, nonce: u32) -> Option<BlockHeader> {
+        Some(BlockHeader {
+            version,
+            prev_blockhash: self.template.prev_blockhash,
+            merkle_root: self.merkle_root()?,
+            time: ntime,
+            bits: self.template.bits,
+            nonce,
+        })
+    }
+
+    /// Hash `header` and check it against the task's share target.
+    ///
+    /// Every thread that checks nonces from hardware goes through here, so
+    /// they agree on how the hash is compared with the target. Returns the
+    /// share the nonce makes, or the hash that fell short.
+    pub fn verify_nonce(&self, header: &BlockHeader) -> Result<Share, BlockHash> {
+        let hash = header.block_hash();
+        if !self.share_target.is_met_by(hash) {
+            return Err(hash);
+        }
+        Ok(Share {
+            nonce: header.nonce,
+            hash,
+            version: header.version,
+            ntime: header.time,
+            extranonce2: self.en2,
+            expected_hashes: U256::from(self.share_target.to_work()),
+        })
+    }
+
+    /// Move to the next extranonce2 in the task's range.
+    ///
+    /// Returns false, leaving the task unchanged, when the range is used up
+    /// or the task has no extranonce2 to roll.
+    pub fn advance_en2(&mut self) -> bool {
+        let (Some(en2), Some(range)) = (&self.en2, &self.en2_range) else {
+            return false;
+        };
+        match en2.checked_increment().filter(|next| range.contains(next)) {
+            Some(next) => {
+                self.en2 = Some(next);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
 impl fmt::Debug for HashTask {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("HashTask")
@@ -354,3 +548,66 @@ impl From<(Share, String)> for crate::job_source::Share {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job_source::test_blocks::block_881423;
+    use crate::job_source::{dummy, GeneralPurposeBits};
+
+    #[test]
+    fn test_verify_nonce_finds_real_block() {
+        let (share_tx, _share_rx) = mpsc::channel(1);
+        let task = HashTask {
+            template: Arc::new(dummy::job_template().unwrap()),
+            en2_range: None,
+            en2: Some(*block_881423::EXTRANONCE2),
+            share_target: Target::from(*block_881423::BITS),
+            ntime: block_881423::TIME,
+            share_tx,
+        };
+
+        let header = task
+            .header(
+                *block_881423::VERSION,
+                block_881423::TIME,
+                block_881423::NONCE,
+            )
+            .unwrap();
+        assert_eq!(header, *block_881423::HEADER);
+        let share = task.verify_nonce(&header).unwrap();
+        assert_eq!(share.hash, *block_881423::BLOCK_HASH);
+        assert_eq!(share.extranonce2, Some(*block_881423::EXTRANONCE2));
+
+        // One nonce over misses the block's target
+        let header = task
+            .header(
+                *block_881423::VERSION,
+                block_881423::TIME,
+                block_881423::NONCE + 1,
+            )
+            .unwrap();
+        assert!(task.verify_nonce(&header).is_err());
+    }
+
+    #[test]
+    fn test_hashes_per_en2() {
+        let version = VersionTemplate::new(
+            Version::from_consensus(0x2000_0000),
+            GeneralPurposeBits::new([0x00, 0xff]),
+        )
+        .unwrap();
+        let nonces = 2f64.powi(32);
+        let base = HashThreadCapabilities::new(HashRate::from_terahashes(1.0));
+
+        assert_eq!(base.hashes_per_en2(&version), nonces);
+        assert_eq!(
+            base.clone().with_midstates(4).hashes_per_en2(&version),
+            4.0 * nonces
+        );
+        assert_eq!(
+            base.with_version_rolling().hashes_per_en2(&version),
+            256.0 * nonces
+        );
+    }
+}
@@ -1,5 +1,10 @@
+pub mod avalon;
 pub mod bm13xx;
 pub mod hash_thread;
+pub mod nonce_map;
+pub mod pregen;
+pub mod self_test;
+pub mod stall;
 
 use async_trait::async_trait;
 use std::error::Error;
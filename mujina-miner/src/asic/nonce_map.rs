@@ -0,0 +1,115 @@
+//! Nonce counts by core, for chip health diagnostics.
+//!
+//! Chips that report where each nonce came from let us count nonces per
+//! core. Every working core searches an equal slice of the nonce space, so
+//! over enough nonces they all find about the same number. A core with far
+//! fewer than the rest is marginal (its clock is too high for it, or its
+//! supply sags), and one with none at all is dead. The [`NonceMap`] keeps
+//! those counts, shaped as the chip's cores and the small cores within them.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Mean nonces per core below which no core is judged weak; before then
+/// the counts are mostly noise.
+pub const MIN_MEAN_NONCES: f64 = 20.0;
+
+/// Fraction of the mean per core below which a core counts as weak.
+pub const WEAK_CORE_FRACTION: f64 = 0.25;
+
+/// Nonces found by each core of a chain.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NonceMap {
+    /// Nonces found, by core and then by small core within it
+    pub cores: BTreeMap<u8, Vec<u64>>,
+}
+
+impl NonceMap {
+    /// Map for chips with `cores` cores of `small_cores` small cores each.
+    ///
+    /// Laying the cores out up front means ones that never report show up
+    /// as zeros rather than being missing.
+    pub fn with_layout(cores: u8, small_cores: u8) -> Self {
+        Self {
+            cores: (0..cores)
+                .map(|core| (core, vec![0; usize::from(small_cores)]))
+                .collect(),
+        }
+    }
+
+    /// Count a nonce from `small_core` of `core`.
+    pub fn record(&mut self, core: u8, small_core: u8) {
+        let counts = self.cores.entry(core).or_default();
+        let index = usize::from(small_core);
+        if counts.len() <= index {
+            counts.resize(index + 1, 0);
+        }
+        counts[index] += 1;
+    }
+
+    /// Nonces counted across all cores.
+    pub fn total(&self) -> u64 {
+        self.cores.values().flatten().sum()
+    }
+
+    /// Nonces counted for `core`.
+    pub fn core_total(&self, core: u8) -> u64 {
+        self.cores
+            .get(&core)
+            .map_or(0, |counts| counts.iter().sum())
+    }
+
+    /// Cores that found less than [`WEAK_CORE_FRACTION`] of the mean per
+    /// core, once there are enough nonces to tell.
+    pub fn weak_cores(&self) -> Vec<u8> {
+        if self.cores.is_empty() {
+            return Vec::new();
+        }
+        let mean = self.total() as f64 / self.cores.len() as f64;
+        if mean < MIN_MEAN_NONCES {
+            return Vec::new();
+        }
+        self.cores
+            .keys()
+            .copied()
+            .filter(|&core| (self.core_total(core) as f64) < mean * WEAK_CORE_FRACTION)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout_shows_silent_cores() {
+        let mut map = NonceMap::with_layout(4, 2);
+        map.record(1, 1);
+        map.record(1, 1);
+        assert_eq!(map.cores[&0], [0, 0]);
+        assert_eq!(map.cores[&1], [0, 2]);
+        assert_eq!(map.total(), 2);
+
+        // Cores outside the layout are still counted
+        map.record(7, 3);
+        assert_eq!(map.cores[&7], [0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn test_weak_cores() {
+        let mut map = NonceMap::with_layout(4, 1);
+        for _ in 0..40 {
+            map.record(0, 0);
+            map.record(1, 0);
+            map.record(2, 0);
+        }
+        map.record(3, 0);
+        assert_eq!(map.weak_cores(), [3]);
+
+        // Too few nonces to judge
+        let mut sparse = NonceMap::with_layout(4, 1);
+        sparse.record(0, 0);
+        assert!(sparse.weak_cores().is_empty());
+    }
+}
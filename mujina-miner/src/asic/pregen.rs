@@ -0,0 +1,165 @@
+//! Hardware jobs prepared ahead of dispatch.
+//!
+//! Sending a chain its next job should take no longer than the serial write.
+//! Computing the merkle root and midstates when the job is due instead adds
+//! to the time the chips spend on stale work after a new block, and jitters
+//! ntime rolling. A [`Pregenerator`] moves that work to a task of its own:
+//! it makes the hash task's header template once, prepares the hardware's
+//! jobs for successive ntimes from it, and keeps a few of them queued, so
+//! the thread takes each job ready-made.
+//!
+//! Rolling stops at the job's [`max_ntime`](crate::job_source::JobTemplate::max_ntime);
+//! past it, the job for the last ntime is prepared again.
+//!
+//! The queue is a bounded channel with the generator as its only producer
+//! and the hash thread as its only consumer. The generator waits while it's
+//! full, and stops when the pregenerator is dropped for the next task.
+
+use tokio::sync::mpsc;
+use tokio::task::AbortHandle;
+
+use super::hash_thread::{HashTask, HashThreadError};
+use crate::job_source::HeaderTemplate;
+use crate::tracing::prelude::*;
+
+/// Jobs kept ready ahead of the thread.
+///
+/// Threads take one per ntime roll, about one a second, so a few cover any
+/// delay in scheduling the generator.
+pub const PREGEN_DEPTH: usize = 4;
+
+/// A job prepared for the hardware, and the header it was made from.
+#[derive(Debug)]
+pub struct Pregenerated<T> {
+    pub header: HeaderTemplate,
+    pub job: T,
+}
+
+/// Prepares one hash task's jobs ahead of the thread's demand.
+pub struct Pregenerator<T> {
+    jobs: mpsc::Receiver<Pregenerated<T>>,
+    generator: AbortHandle,
+}
+
+impl<T: Send + 'static> Pregenerator<T> {
+    /// Start preparing jobs for `task`, one per ntime from the task's on,
+    /// turning each header into the hardware's job with `prepare`.
+    pub fn start<F>(task: &HashTask, prepare: F) -> Self
+    where
+        F: Fn(&HeaderTemplate) -> Result<T, HashThreadError> + Send + 'static,
+    {
+        let (job_tx, jobs) = mpsc::channel(PREGEN_DEPTH);
+        let template = task.template.clone();
+        let en2 = task.en2;
+        let mut ntime = task.ntime;
+
+        let generator = tokio::spawn(async move {
+            let Some(base) = HeaderTemplate::new(&template, en2, ntime) else {
+                warn!(job = %template.id, "No merkle root for task; no jobs prepared");
+                return;
+            };
+            loop {
+                let header = base.with_ntime(ntime);
+                let job = match prepare(&header) {
+                    Ok(job) => job,
+                    Err(e) => {
+                        warn!(job = %template.id, error = %e, "Failed to prepare job");
+                        return;
+                    }
+                };
+                if job_tx.send(Pregenerated { header, job }).await.is_err() {
+                    return;
+                }
+                if ntime < template.max_ntime() {
+                    ntime += 1;
+                }
+            }
+        })
+        .abort_handle();
+
+        Self { jobs, generator }
+    }
+
+    /// The job for the next ntime, waiting only if the generator is behind.
+    ///
+    /// Returns None if the task's jobs couldn't be prepared.
+    pub async fn next(&mut self) -> Option<Pregenerated<T>> {
+        self.jobs.recv().await
+    }
+}
+
+impl<T> Drop for Pregenerator<T> {
+    fn drop(&mut self) {
+        self.generator.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job_source::dummy;
+    use std::sync::Arc;
+
+    fn task() -> HashTask {
+        let template = Arc::new(dummy::job_template().unwrap());
+        let en2_range = match &template.merkle_root {
+            crate::job_source::MerkleRootKind::Computed(merkle) => {
+                merkle.extranonce2_range().clone()
+            }
+            _ => unreachable!(),
+        };
+        HashTask {
+            en2: en2_range.iter().next(),
+            en2_range: Some(en2_range),
+            share_target: template.share_target,
+            ntime: template.time,
+            template,
+            share_tx: mpsc::channel(1).0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_prepares_successive_ntimes() {
+        let task = task();
+        let expected = task.header_template().unwrap();
+        let mut jobs = Pregenerator::start(&task, |header| Ok(header.ntime));
+
+        for offset in 0..PREGEN_DEPTH as u32 * 2 {
+            let next = jobs.next().await.unwrap();
+            assert_eq!(next.job, task.ntime + offset);
+            assert_eq!(next.header.merkle_root, expected.merkle_root);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rolling_stops_at_max_ntime() {
+        let mut task = task();
+        let max = task.template.max_ntime();
+        task.ntime = max - 1;
+        let mut jobs = Pregenerator::start(&task, |header| Ok(header.ntime));
+
+        let ntimes = [
+            jobs.next().await.unwrap().job,
+            jobs.next().await.unwrap().job,
+            jobs.next().await.unwrap().job,
+        ];
+        assert_eq!(ntimes, [max - 1, max, max]);
+
+        assert!(task.roll_ntime());
+        assert!(!task.roll_ntime());
+        assert_eq!(task.ntime, max);
+    }
+
+    #[tokio::test]
+    async fn test_stops_when_jobs_cant_be_prepared() {
+        let mut jobs = Pregenerator::<()>::start(&task(), |_| {
+            Err(HashThreadError::WorkAssignmentFailed("test".into()))
+        });
+        assert!(jobs.next().await.is_none());
+
+        let mut task = task();
+        task.en2 = None;
+        let mut jobs = Pregenerator::start(&task, |_| Ok(()));
+        assert!(jobs.next().await.is_none());
+    }
+}
@@ -0,0 +1,294 @@
+//! Known-answer self-test for hash threads.
+//!
+//! A chain can come up, accept jobs and report nonces while still hashing
+//! wrongly: a miswired chip, a bad job encoding or a nonce decoded with the
+//! wrong byte order all produce nonces that never meet a pool's target. To
+//! catch this before a thread is put to work, its board hands it blocks
+//! whose winning nonce is known and checks the thread finds each one.
+//!
+//! The vectors are genesis blocks. Their version is 1, so a thread finds the
+//! nonce without rolling version bits, and their merkle root is fixed, so no
+//! extranonce is involved. Their share target is the block's own target,
+//! which in practice no nonce but the winning one meets.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use bitcoin::block::Header as BlockHeader;
+use bitcoin::pow::Target;
+use bitcoin::Network;
+use tokio::sync::mpsc;
+use tracing::{debug, info};
+
+use super::hash_thread::{HashTask, HashThread, HashThreadError, Share};
+use crate::job_source::{GeneralPurposeBits, JobTemplate, MerkleRootKind, VersionTemplate};
+
+/// How long a thread gets to find each vector's nonce.
+///
+/// A single chip sweeps the whole nonce range in milliseconds; this leaves
+/// room for job hand-off and the chips reporting at a low rate.
+pub const VECTOR_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Why a thread failed its self-test.
+#[derive(Debug, thiserror::Error)]
+pub enum SelfTestError {
+    #[error("{vector}: {source}")]
+    Thread {
+        vector: &'static str,
+        source: HashThreadError,
+    },
+
+    #[error("{vector}: nonce {nonce:#010x} not found within {timeout:?}")]
+    NonceNotFound {
+        vector: &'static str,
+        nonce: u32,
+        timeout: Duration,
+    },
+
+    #[error("{vector}: nonce {nonce:#010x} reported with hash {got}, expected {expected}")]
+    WrongHash {
+        vector: &'static str,
+        nonce: u32,
+        got: bitcoin::BlockHash,
+        expected: bitcoin::BlockHash,
+    },
+}
+
+/// A block whose winning nonce is known.
+#[derive(Debug, Clone)]
+pub struct Vector {
+    /// Name for logs and errors
+    pub name: &'static str,
+
+    /// Header, including the winning nonce
+    pub header: BlockHeader,
+}
+
+impl Vector {
+    /// Task that asks a thread to find this vector's nonce.
+    pub fn task(&self, share_tx: mpsc::Sender<Share>) -> HashTask {
+        let target = Target::from(self.header.bits);
+        let template = JobTemplate {
+            id: format!("self-test-{}", self.name),
+            prev_blockhash: self.header.prev_blockhash,
+            version: VersionTemplate::new(self.header.version, GeneralPurposeBits::none())
+                .expect("genesis versions have no general purpose bits"),
+            bits: self.header.bits,
+            share_target: target,
+            time: self.header.time,
+            merkle_root: MerkleRootKind::Fixed(self.header.merkle_root),
+        };
+        HashTask {
+            template: Arc::new(template),
+            en2_range: None,
+            en2: None,
+            share_target: target,
+            ntime: self.header.time,
+            share_tx,
+        }
+    }
+}
+
+/// The blocks every thread is tested against.
+pub fn vectors() -> Vec<Vector> {
+    [
+        ("mainnet genesis", Network::Bitcoin),
+        ("testnet genesis", Network::Testnet),
+        ("signet genesis", Network::Signet),
+    ]
+    .into_iter()
+    .map(|(name, network)| Vector {
+        name,
+        header: bitcoin::constants::genesis_block(network).header,
+    })
+    .collect()
+}
+
+/// Check that `thread` finds the nonce of every vector, leaving it idle.
+///
+/// Must run before the thread's event receiver goes to the scheduler, as
+/// the thread is given tasks the scheduler knows nothing about.
+pub async fn run(thread: &mut dyn HashThread) -> Result<(), SelfTestError> {
+    for vector in vectors() {
+        check(thread, &vector).await?;
+    }
+    thread
+        .go_idle()
+        .await
+        .map_err(|source| SelfTestError::Thread {
+            vector: "idle",
+            source,
+        })?;
+    info!(thread = %thread.name(), "Self-test passed.");
+    Ok(())
+}
+
+/// Give `thread` one vector and wait for its nonce.
+async fn check(thread: &mut dyn HashThread, vector: &Vector) -> Result<(), SelfTestError> {
+    let (share_tx, mut share_rx) = mpsc::channel(8);
+    thread
+        .replace_task(vector.task(share_tx))
+        .await
+        .map_err(|source| SelfTestError::Thread {
+            vector: vector.name,
+            source,
+        })?;
+
+    let expected = vector.header.block_hash();
+    let nonce = vector.header.nonce;
+    let deadline = tokio::time::Instant::now() + VECTOR_TIMEOUT;
+    loop {
+        let share = match tokio::time::timeout_at(deadline, share_rx.recv()).await {
+            Ok(Some(share)) => share,
+            Ok(None) | Err(_) => {
+                return Err(SelfTestError::NonceNotFound {
+                    vector: vector.name,
+                    nonce,
+                    timeout: VECTOR_TIMEOUT,
+                })
+            }
+        };
+        if share.nonce != nonce {
+            debug!(
+                vector = vector.name,
+                nonce = format!("{:#x}", share.nonce),
+                "Ignoring other nonce"
+            );
+            continue;
+        }
+        if share.hash != expected {
+            return Err(SelfTestError::WrongHash {
+                vector: vector.name,
+                nonce,
+                got: share.hash,
+                expected,
+            });
+        }
+        debug!(thread = %thread.name(), vector = vector.name, "Self-test vector found");
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::asic::hash_thread::{HashThreadCapabilities, HashThreadEvent, HashThreadStatus};
+    use crate::types::HashRate;
+    use crate::u256::U256;
+
+    /// Thread that hashes a window of nonces around each vector's winning
+    /// one, with its header's time off by `time_skew`.
+    struct WindowThread {
+        capabilities: HashThreadCapabilities,
+        time_skew: u32,
+    }
+
+    impl WindowThread {
+        fn new(time_skew: u32) -> Self {
+            Self {
+                capabilities: HashThreadCapabilities::new(HashRate::from_terahashes(1.0)),
+                time_skew,
+            }
+        }
+    }
+
+    #[async_trait]
+    impl HashThread for WindowThread {
+        fn name(&self) -> &str {
+            "window"
+        }
+
+        fn capabilities(&self) -> &HashThreadCapabilities {
+            &self.capabilities
+        }
+
+        async fn update_task(
+            &mut self,
+            task: HashTask,
+        ) -> Result<Option<HashTask>, HashThreadError> {
+            self.replace_task(task).await
+        }
+
+        async fn replace_task(
+            &mut self,
+            task: HashTask,
+        ) -> Result<Option<HashTask>, HashThreadError> {
+            let template = task.template.as_ref();
+            let winning = vectors()
+                .into_iter()
+                .find(|v| v.header.time == task.ntime)
+                .unwrap()
+                .header
+                .nonce;
+            for nonce in winning.saturating_sub(64)..=winning.saturating_add(64) {
+                let header = BlockHeader {
+                    version: template.version.base(),
+                    prev_blockhash: template.prev_blockhash,
+                    merkle_root: task.merkle_root().unwrap(),
+                    time: task.ntime + self.time_skew,
+                    bits: template.bits,
+                    nonce,
+                };
+                let hash = header.block_hash();
+                if task.share_target.is_met_by(hash) {
+                    let share = Share {
+                        nonce,
+                        hash,
+                        version: header.version,
+                        ntime: header.time,
+                        extranonce2: None,
+                        expected_hashes: U256::from(task.share_target.to_work()),
+                    };
+                    task.share_tx.send(share).await.unwrap();
+                }
+            }
+            Ok(None)
+        }
+
+        async fn go_idle(&mut self) -> Result<Option<HashTask>, HashThreadError> {
+            Ok(None)
+        }
+
+        fn take_event_receiver(&mut self) -> Option<mpsc::Receiver<HashThreadEvent>> {
+            None
+        }
+
+        fn status(&self) -> HashThreadStatus {
+            HashThreadStatus::default()
+        }
+    }
+
+    #[test]
+    fn test_vectors_meet_their_own_target() {
+        for vector in vectors() {
+            let target = Target::from(vector.header.bits);
+            assert!(
+                target.is_met_by(vector.header.block_hash()),
+                "{}",
+                vector.name
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_thread_that_hashes_correctly_passes() {
+        run(&mut WindowThread::new(0)).await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_thread_that_hashes_wrongly_fails() {
+        let err = run(&mut WindowThread::new(1)).await.unwrap_err();
+        assert!(
+            matches!(
+                err,
+                SelfTestError::NonceNotFound {
+                    vector: "mainnet genesis",
+                    ..
+                }
+            ),
+            "{err}"
+        );
+    }
+}
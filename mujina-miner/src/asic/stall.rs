@@ -0,0 +1,198 @@
+//! Detection of hash threads that have stopped producing nonces.
+//!
+//! A healthy chain reports nonces at a rate set by its hashrate and the
+//! difficulty it reports at (for BM13xx, the ticket mask). Silence for many
+//! times the expected interval means the chips have locked up, as they now
+//! and then do after a brown-out or a corrupted register write. The
+//! [`StallDetector`] measures that silence and says what to do about it:
+//! first reset the chips in place, and if nonces still don't come back,
+//! have the board reinitialized from scratch.
+
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::types::HashRate;
+
+/// Expected nonce intervals of silence before a thread is suspect.
+///
+/// Nonces arrive as a Poisson process, so a healthy thread goes this many
+/// intervals without one with probability e^-30, about 1 in 10^13.
+pub const STALL_INTERVALS: u32 = 30;
+
+/// Shortest silence treated as a stall, however fast nonces should come.
+/// Leaves room for a frequency ramp or a slow job hand-off.
+pub const MIN_STALL_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// What a stalled thread should do to recover.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StallAction {
+    /// Reset and reprogram the chips, keeping the board up
+    ResetChips,
+    /// The chip reset didn't help; have the board reinitialized
+    ReinitBoard,
+}
+
+impl std::fmt::Display for StallAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ResetChips => write!(f, "reset chips"),
+            Self::ReinitBoard => write!(f, "reinitialize board"),
+        }
+    }
+}
+
+/// How far recovery has got.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    Healthy,
+    ChipsReset,
+    BoardReinit,
+}
+
+/// Tracks the time since a thread's last nonce.
+///
+/// Call [`StallDetector::nonce`] for every nonce the chips report, and
+/// [`StallDetector::check`] periodically while the thread has work.
+#[derive(Debug)]
+pub struct StallDetector {
+    timeout: Duration,
+    last_nonce: Instant,
+    /// Start of the current silence window: the last nonce, restart, or
+    /// recovery action, whichever came last
+    window_start: Instant,
+    stage: Stage,
+}
+
+impl StallDetector {
+    /// Detector for a thread expected to report a nonce every
+    /// `expected_interval`.
+    pub fn new(expected_interval: Duration, now: Instant) -> Self {
+        Self {
+            timeout: (expected_interval * STALL_INTERVALS).max(MIN_STALL_TIMEOUT),
+            last_nonce: now,
+            window_start: now,
+            stage: Stage::Healthy,
+        }
+    }
+
+    /// Expected time between nonces at `hashrate`, when one in
+    /// `hashes_per_nonce` hashes is reported.
+    pub fn expected_interval(hashrate: HashRate, hashes_per_nonce: f64) -> Duration {
+        if hashrate.is_zero() {
+            return Duration::MAX;
+        }
+        Duration::try_from_secs_f64(hashes_per_nonce / hashrate.0 as f64).unwrap_or(Duration::MAX)
+    }
+
+    /// Silence after which the thread is taken to be stalled.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Time since the last nonce.
+    pub fn silent_for(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(self.last_nonce)
+    }
+
+    /// Start a fresh silence window, e.g., when work resumes after idling.
+    pub fn restart(&mut self, now: Instant) {
+        self.window_start = now;
+    }
+
+    /// Record a nonce. Returns whether the thread had been stalled.
+    pub fn nonce(&mut self, now: Instant) -> bool {
+        self.last_nonce = now;
+        self.window_start = now;
+        std::mem::replace(&mut self.stage, Stage::Healthy) != Stage::Healthy
+    }
+
+    /// The recovery action due now, if any.
+    ///
+    /// Each action is returned once, and the next is due only after another
+    /// full timeout of silence. Once the board has been asked to
+    /// reinitialize there's nothing more to try.
+    pub fn check(&mut self, now: Instant) -> Option<StallAction> {
+        if now.saturating_duration_since(self.window_start) < self.timeout {
+            return None;
+        }
+        let (stage, action) = match self.stage {
+            Stage::Healthy => (Stage::ChipsReset, StallAction::ResetChips),
+            Stage::ChipsReset => (Stage::BoardReinit, StallAction::ReinitBoard),
+            Stage::BoardReinit => return None,
+        };
+        self.stage = stage;
+        self.window_start = now;
+        Some(action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expected_interval() {
+        // One nonce per 2^32 hashes at 2^32 H/s
+        let interval = StallDetector::expected_interval(HashRate(1 << 32), 2f64.powi(32));
+        assert_eq!(interval, Duration::from_secs(1));
+        assert_eq!(
+            StallDetector::expected_interval(HashRate(0), 1.0),
+            Duration::MAX
+        );
+    }
+
+    #[test]
+    fn test_timeout_has_floor() {
+        let now = Instant::now();
+        let fast = StallDetector::new(Duration::from_millis(100), now);
+        assert_eq!(fast.timeout(), MIN_STALL_TIMEOUT);
+        let slow = StallDetector::new(Duration::from_secs(10), now);
+        assert_eq!(slow.timeout(), Duration::from_secs(300));
+    }
+
+    #[test]
+    fn test_escalates_then_recovers() {
+        let start = Instant::now();
+        let mut detector = StallDetector::new(Duration::from_secs(10), start);
+        let timeout = detector.timeout();
+
+        assert_eq!(detector.check(start + timeout / 2), None);
+        assert_eq!(
+            detector.check(start + timeout),
+            Some(StallAction::ResetChips)
+        );
+
+        // The reset gets a full window before escalating
+        assert_eq!(detector.check(start + timeout * 3 / 2), None);
+        assert_eq!(
+            detector.check(start + timeout * 2),
+            Some(StallAction::ReinitBoard)
+        );
+        assert_eq!(detector.check(start + timeout * 10), None);
+        assert_eq!(detector.silent_for(start + timeout * 10), timeout * 10);
+
+        // A nonce clears the stall
+        let later = start + timeout * 10;
+        assert!(detector.nonce(later));
+        assert!(!detector.nonce(later));
+        assert_eq!(
+            detector.check(later + timeout),
+            Some(StallAction::ResetChips)
+        );
+    }
+
+    #[test]
+    fn test_restart_extends_window() {
+        let start = Instant::now();
+        let mut detector = StallDetector::new(Duration::from_secs(10), start);
+        let timeout = detector.timeout();
+
+        detector.restart(start + timeout / 2);
+        assert_eq!(detector.check(start + timeout), None);
+        assert_eq!(
+            detector.check(start + timeout * 3 / 2),
+            Some(StallAction::ResetChips)
+        );
+    }
+}
@@ -4,35 +4,320 @@
 //! the scheduler. Like a hardware backplane, it provides connection points for
 //! boards to plug into, routes events between components, and manages board
 //! lifecycle (hotplug, emergency shutdown, etc.).
+//!
+//! Each board runs on its own supervised task (see [`crate::board::task`]),
+//! so the event loop only starts and stops boards and relays requests to
+//! them; a board initializing or crashing doesn't hold it up.
+//!
+//! Firmware updates (see [`crate::firmware`]) run on their own task too. The
+//! backplane stops the board first, since its controller drops off USB for
+//! the update, and hands the update the ROM loader's port when the loader
+//! turns up among hotplug events.
+//!
+//! Boards' stored settings (see [`crate::settings`]) are looked up by board
+//! ID, the serial number for USB boards, each time a board is started.
+//! Switching boards to a preset (see [`crate::board::preset`]) stores the
+//! preset's values as their settings.
+//!
+//! Boards being attached and detached, and their tasks' reports of coming
+//! up and failing, are recorded in each board's history (see
+//! [`crate::board::history`]).
+//!
+//! A USB board that drops off the bus isn't shut down at once. It's given
+//! [`RestartPolicy::reconnect_grace`] to come back with the same serial
+//! number, and if it does, it's moved over to the new device in place (see
+//! [`crate::board::Board::reconnect`]): its ports are reopened and its
+//! firmware and chips brought back in step, while the scheduler keeps its
+//! hash threads. Only a board that doesn't come back in time, or can't
+//! reconnect, is shut down or recreated.
+//!
+//! A [`BoardFilter`] limits which of the boards that turn up are started,
+//! for a runtime embedded alongside other software that owns some of them.
 
 use crate::{
-    asic::hash_thread::HashThread,
-    board::{Board, BoardDescriptor, VirtualBoardRegistry},
+    asic::{hash_thread::HashThread, nonce_map::NonceMap},
+    board::{
+        history::{BoardEvent, BoardHistory, BoardLifecycle, HistoryEntry},
+        identity::BoardIdentity,
+        panel::PanelLink,
+        preset::{self, Preset, PresetError, PresetSet},
+        supply::PowerLimited,
+        task::{BoardHandle, BoardHealth, MakeBoardFn, RestartPolicy},
+        warmup::Warmup,
+        BoardDescriptor, BoardError, OperatingPoint, TelemetrySnapshot, VirtualBoardRegistry,
+        VirtualDeviceInfo,
+    },
     error::Result,
+    firmware::{self, FirmwareImage, FirmwareProgress},
+    mgmt_protocol::esp_loader::ESPRESSIF_VID,
+    peripheral::scan::ScannedDevice,
+    runtime::RuntimeEvent,
+    settings::{BoardSettings, SettingsError, SettingsStore},
     tracing::prelude::*,
     transport::{
-        cpu::TransportEvent as CpuTransportEvent, usb::TransportEvent as UsbTransportEvent,
+        cpu::TransportEvent as CpuTransportEvent, direct::TransportEvent as DirectTransportEvent,
+        sim::TransportEvent as SimTransportEvent, usb::TransportEvent as UsbTransportEvent,
         TransportEvent, UsbDeviceInfo,
     },
 };
-use std::collections::HashMap;
-use tokio::sync::mpsc;
+use futures::future::join_all;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::sync::{broadcast, mpsc, oneshot, watch};
+use tokio::time::Instant;
+
+/// How long a board gets to answer a status request before it's left out.
+const BOARD_STATUS_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Commands other components (e.g., the API server) send to the backplane.
+#[derive(Debug)]
+pub enum BackplaneCommand {
+    /// Power down every board (core voltage off, fans to a safe speed) and
+    /// remove it from the backplane. Replies with the number of boards that
+    /// were shut down.
+    PowerDownAll { reply_tx: oneshot::Sender<usize> },
+
+    /// Reply immediately. Used to check that the event loop is responsive.
+    Ping { reply_tx: oneshot::Sender<()> },
+
+    /// Retune every board to an operating point. Replies with the number of
+    /// boards retuned, or the first failure.
+    SetOperatingPoint {
+        point: OperatingPoint,
+        reply_tx: oneshot::Sender<std::result::Result<usize, String>>,
+    },
+
+    /// Retune one board. Replies with None if there's no board with that
+    /// ID; a voltage outside the board's range is refused with
+    /// [`BoardError::VoltageOutOfRange`].
+    SetBoardOperatingPoint {
+        id: String,
+        point: OperatingPoint,
+        reply_tx: oneshot::Sender<Option<std::result::Result<(), BoardError>>>,
+    },
+
+    /// Reset one board's chips in place. Replies with None if there's no
+    /// board with that ID, otherwise with the number of chips that answered
+    /// after the reset.
+    ResetBoardChips {
+        id: String,
+        reply_tx: oneshot::Sender<Option<std::result::Result<usize, BoardError>>>,
+    },
+
+    /// Signal so one board can be picked out among others (e.g., by
+    /// pulsing its fan). Replies with None if there's no board with that ID.
+    IdentifyBoard {
+        id: String,
+        reply_tx: oneshot::Sender<Option<std::result::Result<(), BoardError>>>,
+    },
+
+    /// Store one board's regulator settings in the regulator's NVM.
+    /// Replies with None if there's no board with that ID.
+    StoreBoardRegulatorConfig {
+        id: String,
+        reply_tx: oneshot::Sender<Option<std::result::Result<(), BoardError>>>,
+    },
+
+    /// Power down one board and remove it from the backplane. It comes
+    /// back when it's next plugged in, or when the daemon restarts. Replies
+    /// with None if there's no board with that ID.
+    ShutdownBoard {
+        id: String,
+        reply_tx: oneshot::Sender<Option<std::result::Result<(), BoardError>>>,
+    },
+
+    /// Read the identity one board stores about itself. Replies with None
+    /// if there's no board with that ID.
+    ReadBoardIdentity {
+        id: String,
+        reply_tx: oneshot::Sender<Option<std::result::Result<Option<BoardIdentity>, BoardError>>>,
+    },
+
+    /// Store one board's identity. Replies with None if there's no board
+    /// with that ID.
+    WriteBoardIdentity {
+        id: String,
+        identity: BoardIdentity,
+        reply_tx: oneshot::Sender<Option<std::result::Result<(), BoardError>>>,
+    },
+
+    /// Read the settings stored for one board. Replies with None if
+    /// there's no board with that ID and nothing is stored for it.
+    ReadBoardSettings {
+        id: String,
+        reply_tx: oneshot::Sender<Option<BoardSettings>>,
+    },
+
+    /// Read one board's history, oldest event first. Replies with None if
+    /// nothing was ever recorded for a board with that ID.
+    ReadBoardHistory {
+        id: String,
+        reply_tx: oneshot::Sender<Option<Vec<HistoryEntry>>>,
+    },
+
+    /// Replace the settings stored for one board, applying them now if
+    /// it's running. Replies with None if there's no board with that ID.
+    WriteBoardSettings {
+        id: String,
+        settings: BoardSettings,
+        reply_tx: oneshot::Sender<Option<std::result::Result<(), SettingsError>>>,
+    },
+
+    /// Switch one board, or every board if `id` is None, to a preset.
+    /// Replies with the IDs of the boards switched.
+    ApplyPreset {
+        id: Option<String>,
+        preset: Preset,
+        reply_tx: oneshot::Sender<std::result::Result<Vec<String>, PresetError>>,
+    },
+
+    /// Read one board's nonce counts per core. Replies with None if
+    /// there's no board with that ID.
+    ReadNonceMap {
+        id: String,
+        reply_tx: oneshot::Sender<Option<std::result::Result<NonceMap, BoardError>>>,
+    },
+
+    /// Scan one board's I2C bus. Replies with None if there's no board
+    /// with that ID.
+    ScanBoardI2c {
+        id: String,
+        reply_tx: oneshot::Sender<Option<std::result::Result<Vec<ScannedDevice>, BoardError>>>,
+    },
+
+    /// Read the combined power draw of the boards that can measure it.
+    /// Replies with None if none can.
+    ReadPower {
+        reply_tx: oneshot::Sender<Option<f32>>,
+    },
+
+    /// List the boards with their health and latest cached telemetry.
+    /// Replies without reading any hardware.
+    ListBoards {
+        reply_tx: oneshot::Sender<Vec<BoardStatus>>,
+    },
+
+    /// Write new firmware to one board's management controller. The board
+    /// is stopped for the update and comes back through hotplug. Replies
+    /// with None if there's no board with that ID, otherwise with the
+    /// update's progress once it has started.
+    UpdateFirmware {
+        id: String,
+        image: FirmwareImage,
+        reply_tx: oneshot::Sender<
+            Option<std::result::Result<watch::Receiver<FirmwareProgress>, BoardError>>,
+        >,
+    },
+
+    /// Progress of the latest firmware update of one board. Replies with
+    /// None if the board hasn't had one.
+    FirmwareProgress {
+        id: String,
+        reply_tx: oneshot::Sender<Option<watch::Receiver<FirmwareProgress>>>,
+    },
+}
+
+/// A board as the backplane sees it.
+#[derive(Debug, Clone)]
+pub struct BoardStatus {
+    /// Board ID (serial number or virtual device ID)
+    pub id: String,
+    /// Board model
+    pub model: String,
+    /// Supervision state
+    pub health: BoardHealth,
+    /// Incarnation of the board, one more each time it's recreated
+    pub generation: u64,
+    /// Why the board last failed, until it next comes up
+    pub error: Option<String>,
+    /// Latest sensor readings, if any
+    pub telemetry: Option<TelemetrySnapshot>,
+    /// How the board was found short of power, if it was
+    pub power_limited: Option<PowerLimited>,
+}
+
+/// Which boards the backplane starts, decided by board type name and board
+/// ID.
+#[derive(Clone)]
+pub struct BoardFilter(Arc<AllowsFn>);
+
+/// Predicate of a [`BoardFilter`], given board type name and board ID.
+type AllowsFn = dyn Fn(&str, &str) -> bool + Send + Sync;
+
+impl BoardFilter {
+    /// Start only the boards for which `allows(name, id)` is true.
+    pub fn new(allows: impl Fn(&str, &str) -> bool + Send + Sync + 'static) -> Self {
+        Self(Arc::new(allows))
+    }
+
+    /// Whether to start the board `id` of type `name`.
+    pub fn allows(&self, name: &str, id: &str) -> bool {
+        (self.0)(name, id)
+    }
+}
+
+impl fmt::Debug for BoardFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("BoardFilter(..)")
+    }
+}
 
 /// Board registry that uses inventory to find registered boards.
 pub struct BoardRegistry;
 
 impl BoardRegistry {
-    /// Find the best matching board descriptor for this USB device.
+    /// Find the best matching board descriptor for this USB device, from
+    /// its USB descriptors alone.
     ///
     /// Uses pattern matching with specificity scoring to select the most
     /// appropriate board handler. When multiple patterns match, the one
-    /// with the highest specificity score wins.
+    /// with the highest specificity score wins. Patterns naming a stored
+    /// model are passed over unless nothing else matches; [`Self::select`]
+    /// makes the final choice once the board's identity is read.
     ///
     /// Returns None if no registered boards match the device.
     pub fn find_descriptor(&self, device: &UsbDeviceInfo) -> Option<&'static BoardDescriptor> {
-        inventory::iter::<BoardDescriptor>()
-            .filter(|desc| desc.pattern.matches(device))
-            .max_by_key(|desc| desc.pattern.specificity())
+        best_descriptor(Self::candidates(device), None)
+            .or_else(|| Self::candidates(device).max_by_key(|desc| desc.pattern.specificity()))
+    }
+
+    /// Choose the descriptor to create a USB board with, reading the
+    /// identity the board stores about itself if any matching pattern names
+    /// a model.
+    pub async fn select(&self, device: &UsbDeviceInfo) -> Result<&'static BoardDescriptor> {
+        let identify_fn = Self::candidates(device)
+            .filter(|desc| desc.pattern.needs_identity())
+            .find_map(|desc| desc.identify_fn);
+        let identity = match identify_fn {
+            Some(identify) => match identify(device.clone()).await {
+                Ok(identity) => identity,
+                Err(e) => {
+                    warn!(serial = ?device.serial_number, error = %e, "Failed to read board identity");
+                    None
+                }
+            },
+            None => None,
+        };
+
+        let descriptor = best_descriptor(Self::candidates(device), identity.as_ref());
+        if let Some(identity) = identity {
+            debug!(
+                model = %identity.model,
+                board = ?descriptor.map(|desc| desc.name),
+                "Board identified by stored identity"
+            );
+        }
+        descriptor.ok_or_else(|| {
+            crate::error::Error::Hardware("no board type matches the stored identity".into())
+        })
+    }
+
+    fn candidates(device: &UsbDeviceInfo) -> impl Iterator<Item = &'static BoardDescriptor> + '_ {
+        inventory::iter::<BoardDescriptor>().filter(|desc| desc.pattern.matches(device))
     }
 }
 
@@ -45,10 +330,40 @@ pub struct Backplane {
     registry: BoardRegistry,
     virtual_registry: VirtualBoardRegistry,
     /// Active boards managed by the backplane
-    boards: HashMap<String, Box<dyn Board + Send>>,
+    boards: HashMap<String, BoardHandle>,
+    /// Board IDs of USB boards, by device path
+    usb_boards: HashMap<String, String>,
+    /// Unplugged USB boards, by board ID, with when they're shut down if
+    /// they haven't come back
+    reconnecting: HashMap<String, Instant>,
+    /// When crashed boards are restarted
+    restart_policy: RestartPolicy,
     event_rx: mpsc::Receiver<TransportEvent>,
     /// Channel to send hash threads to the scheduler
     scheduler_tx: mpsc::Sender<Box<dyn HashThread>>,
+    /// Commands from other components
+    command_rx: mpsc::Receiver<BackplaneCommand>,
+    /// Progress of the latest firmware update, by board ID
+    firmware_updates: HashMap<String, watch::Receiver<FirmwareProgress>>,
+    /// Where to send the port of the next ROM loader to turn up
+    loader_tx: Option<oneshot::Sender<String>>,
+    /// Settings kept for each board, by board ID
+    settings: SettingsStore,
+    /// Each board's lifecycle events, by board ID
+    history: BoardHistory,
+    /// Configured presets, by board model
+    presets: BTreeMap<String, PresetSet>,
+    /// How boards are brought up to their stored operating point
+    warmup: Warmup,
+    /// Lifecycle events from board tasks, for the history
+    lifecycle_tx: mpsc::UnboundedSender<BoardLifecycle>,
+    lifecycle_rx: mpsc::UnboundedReceiver<BoardLifecycle>,
+    /// Boards to start, if not all of them
+    filter: Option<BoardFilter>,
+    /// Where boards coming and going are announced
+    events: Option<broadcast::Sender<RuntimeEvent>>,
+    /// Handed to each board for its front panel
+    panel: Option<PanelLink>,
 }
 
 impl Backplane {
@@ -56,25 +371,131 @@ impl Backplane {
     pub fn new(
         event_rx: mpsc::Receiver<TransportEvent>,
         scheduler_tx: mpsc::Sender<Box<dyn HashThread>>,
+        command_rx: mpsc::Receiver<BackplaneCommand>,
     ) -> Self {
+        let (lifecycle_tx, lifecycle_rx) = mpsc::unbounded_channel();
         Self {
             registry: BoardRegistry,
             virtual_registry: VirtualBoardRegistry,
             boards: HashMap::new(),
+            usb_boards: HashMap::new(),
+            reconnecting: HashMap::new(),
+            restart_policy: RestartPolicy::default(),
             event_rx,
             scheduler_tx,
+            command_rx,
+            firmware_updates: HashMap::new(),
+            loader_tx: None,
+            settings: SettingsStore::in_memory(),
+            history: BoardHistory::in_memory(),
+            presets: BTreeMap::new(),
+            warmup: Warmup::default(),
+            lifecycle_tx,
+            lifecycle_rx,
+            filter: None,
+            events: None,
+            panel: None,
         }
     }
 
+    /// Keep boards' settings in `settings` rather than in memory only.
+    pub fn with_settings(mut self, settings: SettingsStore) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Keep boards' history in `history` rather than in memory only.
+    pub fn with_history(mut self, history: BoardHistory) -> Self {
+        self.history = history;
+        self
+    }
+
+    /// Offer `presets`, by board model, for boards to be switched to.
+    pub fn with_presets(mut self, presets: BTreeMap<String, PresetSet>) -> Self {
+        self.presets = presets;
+        self
+    }
+
+    /// Warm boards up to their stored operating point as `warmup` says.
+    pub fn with_warmup(mut self, warmup: Warmup) -> Self {
+        self.warmup = warmup;
+        self
+    }
+
+    /// Restart crashed boards as `policy` says.
+    pub fn with_restart_policy(mut self, policy: RestartPolicy) -> Self {
+        self.restart_policy = policy;
+        self
+    }
+
+    /// Start only the boards `filter` allows.
+    pub fn with_board_filter(mut self, filter: BoardFilter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Announce boards being started and stopped on `events`.
+    pub fn with_events(mut self, events: broadcast::Sender<RuntimeEvent>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Give boards with a screen or buttons `panel` to show the miner's
+    /// state and take a restart through.
+    pub fn with_panel(mut self, panel: PanelLink) -> Self {
+        self.panel = Some(panel);
+        self
+    }
+
     /// Run the backplane event loop.
+    ///
+    /// Returns when the transport event channel closes. A closed command
+    /// channel only means nobody can send commands anymore, so the loop keeps
+    /// serving transport events.
     pub async fn run(&mut self) -> Result<()> {
-        while let Some(event) = self.event_rx.recv().await {
-            match event {
-                TransportEvent::Usb(usb_event) => {
-                    self.handle_usb_event(usb_event).await?;
+        let mut commands_open = true;
+
+        loop {
+            let reconnect_deadline = self.reconnecting.values().min().copied();
+            tokio::select! {
+                event = self.event_rx.recv() => {
+                    let Some(event) = event else {
+                        break;
+                    };
+                    match event {
+                        TransportEvent::Usb(usb_event) => {
+                            self.handle_usb_event(usb_event).await?;
+                        }
+                        TransportEvent::Cpu(cpu_event) => {
+                            self.handle_cpu_event(cpu_event).await?;
+                        }
+                        TransportEvent::Sim(sim_event) => {
+                            self.handle_sim_event(sim_event).await?;
+                        }
+                        TransportEvent::Direct(direct_event) => {
+                            self.handle_direct_event(direct_event).await;
+                        }
+                    }
                 }
-                TransportEvent::Cpu(cpu_event) => {
-                    self.handle_cpu_event(cpu_event).await?;
+
+                command = self.command_rx.recv(), if commands_open => {
+                    match command {
+                        Some(command) => self.handle_command(command).await,
+                        None => commands_open = false,
+                    }
+                }
+
+                Some(lifecycle) = self.lifecycle_rx.recv() => {
+                    self.history.record(&lifecycle.id, lifecycle.entry);
+                }
+
+                _ = async {
+                    match reconnect_deadline {
+                        Some(deadline) => tokio::time::sleep_until(deadline).await,
+                        None => std::future::pending().await,
+                    }
+                } => {
+                    self.expire_reconnects().await;
                 }
             }
         }
@@ -82,36 +503,418 @@ impl Backplane {
         Ok(())
     }
 
-    /// Shutdown all boards managed by this backplane.
-    pub async fn shutdown_all_boards(&mut self) {
-        let board_ids: Vec<String> = self.boards.keys().cloned().collect();
+    /// Handle a command from another component.
+    async fn handle_command(&mut self, command: BackplaneCommand) {
+        match command {
+            BackplaneCommand::PowerDownAll { reply_tx } => {
+                info!(boards = self.boards.len(), "Powering down all boards.");
+                let count = self.shutdown_all_boards().await;
+                let _ = reply_tx.send(count);
+            }
+            BackplaneCommand::Ping { reply_tx } => {
+                let _ = reply_tx.send(());
+            }
+            BackplaneCommand::SetOperatingPoint { point, reply_tx } => {
+                let _ = reply_tx.send(self.set_operating_point(point).await);
+            }
+            BackplaneCommand::SetBoardOperatingPoint {
+                id,
+                point,
+                reply_tx,
+            } => {
+                let result = match self.boards.get(&id) {
+                    Some(board) => Some(board.set_operating_point(point).await),
+                    None => None,
+                };
+                if let Some(Err(e)) = &result {
+                    warn!(serial = %id, %point, error = %e, "Failed to retune board");
+                }
+                let _ = reply_tx.send(result);
+            }
+            BackplaneCommand::ResetBoardChips { id, reply_tx } => {
+                let result = match self.boards.get(&id) {
+                    Some(board) => Some(board.reset_chips().await),
+                    None => None,
+                };
+                match &result {
+                    Some(Ok(chips)) => info!(serial = %id, chips, "Board chips reset."),
+                    Some(Err(e)) => warn!(serial = %id, error = %e, "Failed to reset board chips"),
+                    None => {}
+                }
+                let _ = reply_tx.send(result);
+            }
+            BackplaneCommand::IdentifyBoard { id, reply_tx } => {
+                let result = match self.boards.get(&id) {
+                    Some(board) => Some(board.identify().await),
+                    None => None,
+                };
+                let _ = reply_tx.send(result);
+            }
+            BackplaneCommand::StoreBoardRegulatorConfig { id, reply_tx } => {
+                let result = match self.boards.get(&id) {
+                    Some(board) => Some(board.store_regulator_config().await),
+                    None => None,
+                };
+                if let Some(Err(e)) = &result {
+                    warn!(serial = %id, error = %e, "Failed to store regulator settings");
+                }
+                let _ = reply_tx.send(result);
+            }
+            BackplaneCommand::ShutdownBoard { id, reply_tx } => {
+                let result = match self.boards.remove(&id) {
+                    Some(board) => {
+                        // Forgotten, so only a replug brings it back
+                        self.usb_boards.retain(|_, board_id| *board_id != id);
+                        info!(board = %board.name(), serial = %id, "Shutting down board on request.");
+                        let result = board.shutdown().await;
+                        self.record_detached(&id, "shut down on request");
+                        Some(result)
+                    }
+                    None => None,
+                };
+                if let Some(Err(e)) = &result {
+                    warn!(serial = %id, error = %e, "Board shutdown failed");
+                }
+                let _ = reply_tx.send(result);
+            }
+            BackplaneCommand::ReadBoardIdentity { id, reply_tx } => {
+                let result = match self.boards.get(&id) {
+                    Some(board) => Some(board.identity().await),
+                    None => None,
+                };
+                let _ = reply_tx.send(result);
+            }
+            BackplaneCommand::WriteBoardIdentity {
+                id,
+                identity,
+                reply_tx,
+            } => {
+                let result = match self.boards.get(&id) {
+                    Some(board) => Some(board.write_identity(identity).await),
+                    None => None,
+                };
+                let _ = reply_tx.send(result);
+            }
+            BackplaneCommand::ReadBoardHistory { id, reply_tx } => {
+                let _ = reply_tx.send(self.history.get(&id));
+            }
+            BackplaneCommand::ReadBoardSettings { id, reply_tx } => {
+                let settings = self.settings.get(&id);
+                let result = if self.boards.contains_key(&id) || !settings.is_empty() {
+                    Some(settings)
+                } else {
+                    None
+                };
+                let _ = reply_tx.send(result);
+            }
+            BackplaneCommand::WriteBoardSettings {
+                id,
+                settings,
+                reply_tx,
+            } => {
+                let result = if self.boards.contains_key(&id) {
+                    Some(self.write_settings(&id, settings).await)
+                } else {
+                    None
+                };
+                if let Some(Err(e)) = &result {
+                    warn!(serial = %id, error = %e, "Failed to update board settings");
+                }
+                let _ = reply_tx.send(result);
+            }
+            BackplaneCommand::ApplyPreset {
+                id,
+                preset,
+                reply_tx,
+            } => {
+                let result = self.apply_preset(id.as_deref(), preset).await;
+                if let Err(e) = &result {
+                    warn!(%preset, error = %e, "Failed to apply preset");
+                }
+                let _ = reply_tx.send(result);
+            }
+            BackplaneCommand::ReadNonceMap { id, reply_tx } => {
+                let result = match self.boards.get(&id) {
+                    Some(board) => Some(board.nonce_map().await),
+                    None => None,
+                };
+                let _ = reply_tx.send(result);
+            }
+            BackplaneCommand::ScanBoardI2c { id, reply_tx } => {
+                let result = match self.boards.get(&id) {
+                    Some(board) => Some(board.scan_i2c().await),
+                    None => None,
+                };
+                let _ = reply_tx.send(result);
+            }
+            BackplaneCommand::ReadPower { reply_tx } => {
+                let _ = reply_tx.send(self.read_power().await);
+            }
+            BackplaneCommand::ListBoards { reply_tx } => {
+                let _ = reply_tx.send(self.list_boards());
+            }
+            BackplaneCommand::UpdateFirmware {
+                id,
+                image,
+                reply_tx,
+            } => {
+                let result = if self.boards.contains_key(&id) {
+                    Some(self.update_firmware(&id, image).await)
+                } else {
+                    None
+                };
+                if let Some(Err(e)) = &result {
+                    warn!(serial = %id, error = %e, "Firmware update refused");
+                }
+                let _ = reply_tx.send(result);
+            }
+            BackplaneCommand::FirmwareProgress { id, reply_tx } => {
+                let _ = reply_tx.send(self.firmware_updates.get(&id).cloned());
+            }
+        }
+    }
 
-        for board_id in board_ids {
-            if let Some(mut board) = self.boards.remove(&board_id) {
-                let model = board.board_info().model;
-                debug!(board = %model, serial = %board_id, "Shutting down board");
+    /// Stop a board and start updating its controller's firmware.
+    async fn update_firmware(
+        &mut self,
+        id: &str,
+        image: FirmwareImage,
+    ) -> std::result::Result<watch::Receiver<FirmwareProgress>, BoardError> {
+        // Loaders can't be told apart on USB, so one update at a time
+        if self
+            .firmware_updates
+            .values()
+            .any(|progress| !progress.borrow().is_finished())
+        {
+            return Err(BoardError::HardwareControl(
+                "another firmware update is in progress".into(),
+            ));
+        }
+        let Some(board) = self.boards.get(id) else {
+            return Err(BoardError::HardwareControl("board is gone".into()));
+        };
+        let Some(port) = board.firmware_port().await? else {
+            return Err(BoardError::HardwareControl(
+                "firmware updates not supported by this board".into(),
+            ));
+        };
 
-                match board.shutdown().await {
-                    Ok(()) => {
-                        debug!(board = %model, serial = %board_id, "Board shutdown complete");
-                    }
-                    Err(e) => {
-                        error!(
-                            board = %model,
-                            serial = %board_id,
-                            error = %e,
-                            "Failed to shutdown board"
-                        );
+        info!(serial = %id, %port, bytes = image.data.len(), "Stopping board for firmware update.");
+        self.stop_board(id, "firmware update").await;
+        self.usb_boards.retain(|_, board_id| board_id != id);
+
+        let (loader_tx, loader_rx) = oneshot::channel();
+        let (progress_tx, progress_rx) = watch::channel(FirmwareProgress::Resetting);
+        self.loader_tx = Some(loader_tx);
+        self.firmware_updates
+            .insert(id.to_string(), progress_rx.clone());
+        tokio::spawn(firmware::update(port, image, loader_rx, progress_tx));
+        Ok(progress_rx)
+    }
+
+    /// Apply new settings to a board, if it's running, then store them.
+    ///
+    /// Settings the board refuses aren't stored. A frequency the board
+    /// can't set is skipped, with a warning, and the rest still applies. An
+    /// override that's been cleared stays in effect until the board is next
+    /// started.
+    async fn write_settings(
+        &mut self,
+        id: &str,
+        mut settings: BoardSettings,
+    ) -> std::result::Result<(), SettingsError> {
+        let Some(board) = self.boards.get(id) else {
+            return Err(BoardError::HardwareControl("board is gone".into()).into());
+        };
+        if board.health() == BoardHealth::Running {
+            let point = settings.operating_point();
+            if point != OperatingPoint::default() {
+                match board.set_operating_point(point).await {
+                    Err(BoardError::FrequencyUnsupported) => {
+                        warn!(serial = %id, %point, "Board can't change its chip frequency; frequency skipped");
+                        settings.frequency_mhz = None;
+                        let point = OperatingPoint {
+                            frequency_mhz: None,
+                            ..point
+                        };
+                        if point != OperatingPoint::default() {
+                            board.set_operating_point(point).await?;
+                        }
                     }
+                    result => result?,
                 }
             }
+            if let Some(mode) = settings.fan {
+                board.set_fan_mode(mode).await?;
+            }
         }
+        board.set_settings(settings.clone());
+        self.settings.set(id, settings)?;
+        info!(serial = %id, "Board settings stored.");
+        Ok(())
+    }
+
+    /// Switch the board `id`, or every board, to `preset`, stopping at the
+    /// first failure.
+    ///
+    /// The preset is resolved for every board before any is changed, so a
+    /// model it isn't defined for leaves them all as they were. Fields the
+    /// preset doesn't set keep their stored values.
+    async fn apply_preset(
+        &mut self,
+        id: Option<&str>,
+        preset: Preset,
+    ) -> std::result::Result<Vec<String>, PresetError> {
+        let mut ids: Vec<String> = match id {
+            Some(id) if self.boards.contains_key(id) => vec![id.to_string()],
+            Some(id) => return Err(PresetError::NoBoard(id.to_string())),
+            None => self.boards.keys().cloned().collect(),
+        };
+        ids.sort();
+
+        let mut updates = Vec::with_capacity(ids.len());
+        for id in &ids {
+            let model = self.boards[id].name();
+            let mut settings = self.settings.get(id);
+            let point =
+                preset::resolve(&self.presets, model, settings.presets.get(&preset), preset)
+                    .ok_or_else(|| PresetError::Undefined {
+                        preset,
+                        model: model.to_string(),
+                    })?;
+            settings.frequency_mhz = point.frequency_mhz.or(settings.frequency_mhz);
+            settings.voltage = point.voltage.or(settings.voltage);
+            settings.fan = point.fan.or(settings.fan);
+            settings.preset = Some(preset);
+            updates.push((id, settings));
+        }
+        for (id, settings) in updates {
+            self.write_settings(id, settings).await?;
+        }
+
+        info!(%preset, boards = ids.len(), "Preset applied.");
+        Ok(ids)
+    }
+
+    /// Retune every board, stopping at the first failure.
+    async fn set_operating_point(
+        &mut self,
+        point: OperatingPoint,
+    ) -> std::result::Result<usize, String> {
+        for (board_id, board) in self.boards.iter() {
+            let model = board.name();
+            board.set_operating_point(point).await.map_err(|e| {
+                warn!(board = %model, serial = %board_id, %point, error = %e, "Failed to retune board");
+                format!("{} ({}): {}", model, board_id, e)
+            })?;
+            debug!(board = %model, serial = %board_id, %point, "Board retuned");
+        }
+
+        Ok(self.boards.len())
+    }
+
+    /// Sum the power draw of the boards that report it.
+    ///
+    /// Boards are read concurrently, and one that doesn't answer within
+    /// [`BOARD_STATUS_TIMEOUT`] is left out rather than holding up the rest.
+    async fn read_power(&mut self) -> Option<f32> {
+        let readings = join_all(self.boards.iter().map(|(board_id, board)| async move {
+            match tokio::time::timeout(BOARD_STATUS_TIMEOUT, board.power_watts()).await {
+                Ok(watts) => watts,
+                Err(_) => {
+                    warn!(board = %board.name(), serial = %board_id, "Board power read timed out");
+                    None
+                }
+            }
+        }))
+        .await;
+
+        readings
+            .into_iter()
+            .flatten()
+            .reduce(|total, watts| total + watts)
+    }
+
+    /// Every board's status, sorted by ID.
+    fn list_boards(&self) -> Vec<BoardStatus> {
+        let mut boards: Vec<BoardStatus> = self
+            .boards
+            .iter()
+            .map(|(id, board)| BoardStatus {
+                id: id.clone(),
+                model: board.name().to_string(),
+                health: board.health(),
+                generation: board.generation(),
+                error: board.last_error(),
+                telemetry: board.telemetry(),
+                power_limited: board.power_limited(),
+            })
+            .collect();
+        boards.sort_by(|a, b| a.id.cmp(&b.id));
+        boards
+    }
+
+    /// Shutdown all boards managed by this backplane.
+    ///
+    /// Boards shut down concurrently, each running its stages (park the
+    /// chips, core voltage off, fans down) under per-stage timeouts, so one
+    /// slow board doesn't leave the others powered.
+    ///
+    /// Returns the number of boards that were removed.
+    pub async fn shutdown_all_boards(&mut self) -> usize {
+        let count = self.boards.len();
+        self.usb_boards.clear();
+        self.reconnecting.clear();
+
+        let stopped = join_all(self.boards.drain().map(|(board_id, board)| async move {
+            let model = board.name().to_string();
+            debug!(board = %model, serial = %board_id, "Shutting down board");
+
+            match board.shutdown().await {
+                Ok(()) => {
+                    debug!(board = %model, serial = %board_id, "Board shutdown complete");
+                }
+                Err(e) => {
+                    error!(
+                        board = %model,
+                        serial = %board_id,
+                        error = %e,
+                        "Failed to shutdown board"
+                    );
+                }
+            }
+            board_id
+        }))
+        .await;
+        for id in stopped {
+            self.record_detached(&id, "powered down");
+            self.emit(RuntimeEvent::BoardDisconnected { id });
+        }
+
+        count
     }
 
     /// Handle USB transport events.
     async fn handle_usb_event(&mut self, event: UsbTransportEvent) -> Result<()> {
         match event {
             UsbTransportEvent::UsbDeviceConnected(device_info) => {
+                // A controller reset for a firmware update, now in its loader
+                if device_info.vid == ESPRESSIF_VID {
+                    if let Some(loader_tx) = self.loader_tx.take() {
+                        match device_info
+                            .serial_ports()
+                            .map(|ports| ports.first().map(|port| port.path.clone()))
+                        {
+                            Ok(Some(port)) => {
+                                debug!(%port, "ROM loader connected");
+                                let _ = loader_tx.send(port);
+                                return Ok(());
+                            }
+                            _ => self.loader_tx = Some(loader_tx),
+                        }
+                    }
+                }
+
                 // Check if this device matches any registered board pattern
                 let Some(descriptor) = self.registry.find_descriptor(&device_info) else {
                     // No match - this is expected for most USB devices
@@ -129,84 +932,48 @@ impl Backplane {
                     "Hash board connected via USB."
                 );
 
-                // Create the board using the descriptor's factory function
-                let mut board = match (descriptor.create_fn)(device_info).await {
-                    Ok(board) => board,
-                    Err(e) => {
-                        error!(
-                            board = descriptor.name,
-                            error = %e,
-                            "Failed to create board"
-                        );
-                        return Ok(());
-                    }
-                };
-
-                let board_info = board.board_info();
-                let board_id = board_info
+                let board_id = device_info
                     .serial_number
                     .clone()
                     .unwrap_or_else(|| "unknown".to_string());
+                let device_path = device_info.device_path.clone();
 
-                // Create hash threads from the board
-                match board.create_hash_threads().await {
-                    Ok(threads) => {
-                        // Store board for lifecycle management
-                        self.boards.insert(board_id.clone(), board);
-
-                        // Send threads to scheduler individually
-                        for thread in threads {
-                            if let Err(e) = self.scheduler_tx.send(thread).await {
-                                tracing::error!(
-                                    board = %board_info.model,
-                                    error = %e,
-                                    "Failed to send thread to scheduler"
-                                );
-                                break;
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        tracing::error!(
-                            board = %board_info.model,
-                            serial = %board_id,
-                            error = %e,
-                            "Hash board failed to start."
-                        );
-                    }
+                // Back from a moment off the bus
+                if self.reconnect_board(&board_id, &device_info).await {
+                    self.usb_boards.retain(|_, id| *id != board_id);
+                    self.usb_boards.insert(device_path, board_id);
+                    return Ok(());
                 }
+
+                // The board is created on its own task, from the factory of
+                // the descriptor its stored identity picks, and created again
+                // if it crashes
+                let make_board: MakeBoardFn = Box::new(move || {
+                    let device_info = device_info.clone();
+                    Box::pin(async move {
+                        let descriptor = BoardRegistry.select(&device_info).await?;
+                        (descriptor.create_fn)(device_info).await
+                    })
+                });
+                self.start_board(descriptor.name, board_id.clone(), make_board)
+                    .await;
+                self.usb_boards.retain(|_, id| *id != board_id);
+                self.usb_boards.insert(device_path, board_id);
             }
-            UsbTransportEvent::UsbDeviceDisconnected { device_path: _ } => {
-                // Find and shutdown the board
-                // Note: Current design uses serial number as key, but we get device_path
-                // in disconnect event. For single-board setups this works fine.
-                // TODO: Maintain device_path -> board_id mapping for multi-board support
-                let board_ids: Vec<String> = self.boards.keys().cloned().collect();
-                for board_id in board_ids {
-                    if let Some(mut board) = self.boards.remove(&board_id) {
-                        let model = board.board_info().model;
-                        debug!(board = %model, serial = %board_id, "Shutting down board");
-
-                        match board.shutdown().await {
-                            Ok(()) => {
-                                info!(
-                                    board = %model,
-                                    serial = %board_id,
-                                    "Board disconnected"
-                                );
-                            }
-                            Err(e) => {
-                                tracing::error!(
-                                    board = %model,
-                                    serial = %board_id,
-                                    error = %e,
-                                    "Failed to shutdown board"
-                                );
-                            }
-                        }
-                        // Don't re-insert - board is removed
-                        break; // For now, assume one board per device
-                    }
+            UsbTransportEvent::UsbDeviceDisconnected { device_path } => {
+                let Some(board_id) = self.usb_boards.remove(&device_path) else {
+                    return Ok(());
+                };
+                let grace = self.restart_policy.reconnect_grace;
+                if grace.is_zero() {
+                    self.stop_board(&board_id, "unplugged").await;
+                } else {
+                    info!(
+                        serial = %board_id,
+                        grace_secs = grace.as_secs_f64(),
+                        "Board unplugged; waiting for it to come back."
+                    );
+                    self.reconnecting.insert(board_id, Instant::now() + grace);
                 }
             }
         }
@@ -214,96 +981,640 @@ impl Backplane {
         Ok(())
     }
 
+    /// Move an unplugged board still in its grace period over to `device`,
+    /// the USB device it came back as. Returns whether it was; a board that
+    /// can't reconnect is left to be replaced.
+    async fn reconnect_board(&mut self, board_id: &str, device: &UsbDeviceInfo) -> bool {
+        if self.reconnecting.remove(board_id).is_none() {
+            return false;
+        }
+        let Some(board) = self.boards.get(board_id) else {
+            return false;
+        };
+        match board.reconnect(device.clone()).await {
+            Ok(()) => {
+                info!(serial = %board_id, "Board reconnected in place.");
+                true
+            }
+            Err(e) => {
+                warn!(serial = %board_id, error = %e, "Board couldn't reconnect; restarting it.");
+                false
+            }
+        }
+    }
+
+    /// Shut down unplugged boards whose grace period is up.
+    async fn expire_reconnects(&mut self) {
+        let now = Instant::now();
+        let expired: Vec<String> = self
+            .reconnecting
+            .iter()
+            .filter(|(_, deadline)| **deadline <= now)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for board_id in expired {
+            info!(serial = %board_id, "Board didn't come back; shutting it down.");
+            self.stop_board(&board_id, "unplugged").await;
+        }
+    }
+
     /// Handle CPU miner transport events.
     async fn handle_cpu_event(&mut self, event: CpuTransportEvent) -> Result<()> {
         match event {
             CpuTransportEvent::CpuDeviceConnected(device_info) => {
-                // Find the virtual board descriptor for cpu_miner
-                let Some(descriptor) = self.virtual_registry.find("cpu_miner") else {
-                    error!("No virtual board descriptor found for cpu_miner");
-                    return Ok(());
-                };
-
                 info!(
-                    board = descriptor.name,
                     threads = device_info.thread_count,
                     duty = device_info.duty_percent,
                     "CPU miner board connected."
                 );
+                self.connect_virtual_board("cpu_miner", VirtualDeviceInfo::Cpu(device_info))
+                    .await;
+            }
+            CpuTransportEvent::CpuDeviceDisconnected { device_id } => {
+                self.disconnect_virtual_board(&device_id).await;
+            }
+        }
 
-                // Create the board using the descriptor's factory function
-                let mut board = match (descriptor.create_fn)().await {
-                    Ok(board) => board,
-                    Err(e) => {
-                        error!(
-                            board = descriptor.name,
-                            error = %e,
-                            "Failed to create CPU miner board"
-                        );
-                        return Ok(());
-                    }
-                };
+        Ok(())
+    }
 
-                let board_info = board.board_info();
-                let board_id = device_info.device_id.clone();
-
-                // Create hash threads from the board
-                match board.create_hash_threads().await {
-                    Ok(threads) => {
-                        let thread_count = threads.len();
-
-                        // Store board for lifecycle management
-                        self.boards.insert(board_id.clone(), board);
-
-                        // Send threads to scheduler individually
-                        for thread in threads {
-                            if let Err(e) = self.scheduler_tx.send(thread).await {
-                                tracing::error!(
-                                    board = %board_info.model,
-                                    error = %e,
-                                    "Failed to send thread to scheduler"
-                                );
-                                break;
-                            }
-                        }
+    /// Handle simulation board transport events.
+    async fn handle_sim_event(&mut self, event: SimTransportEvent) -> Result<()> {
+        match event {
+            SimTransportEvent::SimDeviceConnected(device_info) => {
+                info!(
+                    threads = device_info.thread_count,
+                    hashrate = %device_info.hashrate,
+                    scripted = device_info.script.is_some(),
+                    "Simulation board connected."
+                );
+                self.connect_virtual_board("sim", VirtualDeviceInfo::Sim(device_info))
+                    .await;
+            }
+            SimTransportEvent::SimDeviceDisconnected { device_id } => {
+                self.disconnect_virtual_board(&device_id).await;
+            }
+        }
 
-                        info!(
-                            board = %board_info.model,
-                            threads = thread_count,
-                            "CPU miner started."
-                        );
-                    }
-                    Err(e) => {
-                        tracing::error!(
-                            board = %board_info.model,
-                            error = %e,
-                            "CPU miner failed to start."
-                        );
-                    }
-                }
+        Ok(())
+    }
+
+    /// Handle events of boards wired directly to the host.
+    async fn handle_direct_event(&mut self, event: DirectTransportEvent) {
+        match event {
+            DirectTransportEvent::DirectDeviceConnected(device_info) => {
+                info!(
+                    id = %device_info.device_id,
+                    uart = %device_info.uart,
+                    i2c = %device_info.i2c.display(),
+                    "Directly attached board configured."
+                );
+                self.connect_virtual_board("direct_bitaxe", VirtualDeviceInfo::Direct(device_info))
+                    .await;
             }
-            CpuTransportEvent::CpuDeviceDisconnected { device_id } => {
-                if let Some(mut board) = self.boards.remove(&device_id) {
-                    let model = board.board_info().model;
-                    debug!(board = %model, id = %device_id, "Shutting down CPU miner");
+            DirectTransportEvent::DirectDeviceDisconnected { device_id } => {
+                self.disconnect_virtual_board(&device_id).await;
+            }
+        }
+    }
 
-                    match board.shutdown().await {
-                        Ok(()) => {
-                            info!(board = %model, id = %device_id, "CPU miner disconnected");
-                        }
-                        Err(e) => {
-                            tracing::error!(
-                                board = %model,
-                                id = %device_id,
-                                error = %e,
-                                "Failed to shutdown CPU miner"
-                            );
-                        }
-                    }
-                }
+    /// Create a virtual board and hand its threads to the scheduler.
+    async fn connect_virtual_board(&mut self, device_type: &str, device_info: VirtualDeviceInfo) {
+        let Some(descriptor) = self.virtual_registry.find(device_type) else {
+            error!(device_type, "No virtual board descriptor found");
+            return;
+        };
+
+        let board_id = device_info.device_id().to_string();
+        let create_fn = descriptor.create_fn;
+        let make_board: MakeBoardFn = Box::new(move || create_fn(&device_info));
+        self.start_board(descriptor.name, board_id, make_board)
+            .await;
+    }
+
+    /// Shut down and remove a virtual board.
+    async fn disconnect_virtual_board(&mut self, device_id: &str) {
+        self.stop_board(device_id, "removed").await;
+    }
+
+    /// Start a supervised task for a board, replacing any board already
+    /// registered under the same ID.
+    async fn start_board(&mut self, name: &str, board_id: String, make_board: MakeBoardFn) {
+        if let Some(filter) = &self.filter {
+            if !filter.allows(name, &board_id) {
+                info!(board = name, id = %board_id, "Board excluded by filter; not starting.");
+                return;
             }
         }
+        self.stop_board(&board_id, "replaced").await;
 
-        Ok(())
+        // Every board the task makes, restarts included, gets the panel
+        let make_board: MakeBoardFn = match self.panel.clone() {
+            Some(panel) => Box::new(move || {
+                let board = make_board();
+                let panel = panel.clone();
+                Box::pin(async move {
+                    let mut board = board.await?;
+                    board.attach_panel(panel);
+                    Ok(board)
+                })
+            }),
+            None => make_board,
+        };
+
+        self.history.record(
+            &board_id,
+            HistoryEntry::now(BoardEvent::Attached {
+                model: name.to_string(),
+            }),
+        );
+        let board = BoardHandle::spawn(
+            name,
+            board_id.clone(),
+            make_board,
+            self.scheduler_tx.clone(),
+            self.restart_policy,
+            self.settings.get(&board_id),
+            self.warmup,
+            self.lifecycle_tx.clone(),
+        );
+        self.emit(RuntimeEvent::BoardConnected {
+            id: board_id.clone(),
+            model: name.to_string(),
+        });
+        self.boards.insert(board_id, board);
+    }
+
+    /// Shut down and remove a board, if there is one with this ID, giving
+    /// `reason` in its history.
+    async fn stop_board(&mut self, board_id: &str, reason: &str) {
+        self.reconnecting.remove(board_id);
+        let Some(board) = self.boards.remove(board_id) else {
+            return;
+        };
+        let model = board.name().to_string();
+        debug!(board = %model, id = %board_id, "Shutting down board");
+
+        match board.shutdown().await {
+            Ok(()) => {
+                info!(board = %model, id = %board_id, "Board shut down.");
+            }
+            Err(e) => {
+                error!(
+                    board = %model,
+                    id = %board_id,
+                    error = %e,
+                    "Failed to shutdown board"
+                );
+            }
+        }
+        self.record_detached(board_id, reason);
+        self.emit(RuntimeEvent::BoardDisconnected {
+            id: board_id.to_string(),
+        });
+    }
+
+    /// Record a board's removal in its history, after whatever its task
+    /// reported on the way down.
+    fn record_detached(&mut self, board_id: &str, reason: &str) {
+        while let Ok(lifecycle) = self.lifecycle_rx.try_recv() {
+            self.history.record(&lifecycle.id, lifecycle.entry);
+        }
+        let event = BoardEvent::Detached {
+            reason: reason.to_string(),
+        };
+        self.history.record(board_id, HistoryEntry::now(event));
+    }
+
+    /// Announce an event to whoever is listening.
+    fn emit(&self, event: RuntimeEvent) {
+        if let Some(events) = &self.events {
+            // Nobody subscribed is fine
+            let _ = events.send(event);
+        }
+    }
+}
+
+/// The most specific of `candidates` whose identity criteria `identity`
+/// meets.
+fn best_descriptor<'a>(
+    candidates: impl Iterator<Item = &'a BoardDescriptor>,
+    identity: Option<&BoardIdentity>,
+) -> Option<&'a BoardDescriptor> {
+    candidates
+        .filter(|desc| desc.pattern.matches_identity(identity))
+        .max_by_key(|desc| desc.pattern.specificity())
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::board::{Board, BoardError, BoardInfo};
+
+    /// Board drawing a fixed power, or hanging on every sensor read.
+    struct TestBoard {
+        watts: Option<f32>,
+    }
+
+    #[async_trait]
+    impl Board for TestBoard {
+        fn board_info(&self) -> BoardInfo {
+            BoardInfo {
+                model: "Test".into(),
+                firmware_version: None,
+                serial_number: None,
+            }
+        }
+
+        async fn shutdown(&mut self) -> std::result::Result<(), BoardError> {
+            Ok(())
+        }
+
+        async fn create_hash_threads(
+            &mut self,
+        ) -> std::result::Result<Vec<Box<dyn HashThread>>, BoardError> {
+            Ok(Vec::new())
+        }
+
+        async fn power_watts(&mut self) -> Option<f32> {
+            match self.watts {
+                Some(watts) => Some(watts),
+                None => std::future::pending().await,
+            }
+        }
+
+        async fn set_operating_point(
+            &mut self,
+            _point: OperatingPoint,
+        ) -> std::result::Result<(), BoardError> {
+            Ok(())
+        }
+
+        async fn reconnect(
+            &mut self,
+            _device: &UsbDeviceInfo,
+        ) -> std::result::Result<(), BoardError> {
+            Ok(())
+        }
+    }
+
+    fn make_board(watts: Option<f32>) -> MakeBoardFn {
+        Box::new(move || Box::pin(async move { Ok(Box::new(TestBoard { watts }) as _) }))
+    }
+
+    /// Board that sets its core voltage but not its chips' clock, recording
+    /// the points it takes.
+    struct FixedClockBoard {
+        set: Arc<std::sync::Mutex<Vec<OperatingPoint>>>,
+    }
+
+    #[async_trait]
+    impl Board for FixedClockBoard {
+        fn board_info(&self) -> BoardInfo {
+            BoardInfo {
+                model: "Test".into(),
+                firmware_version: None,
+                serial_number: None,
+            }
+        }
+
+        async fn shutdown(&mut self) -> std::result::Result<(), BoardError> {
+            Ok(())
+        }
+
+        async fn create_hash_threads(
+            &mut self,
+        ) -> std::result::Result<Vec<Box<dyn HashThread>>, BoardError> {
+            Ok(Vec::new())
+        }
+
+        async fn set_operating_point(
+            &mut self,
+            point: OperatingPoint,
+        ) -> std::result::Result<(), BoardError> {
+            if point.frequency_mhz.is_some() {
+                return Err(BoardError::FrequencyUnsupported);
+            }
+            self.set.lock().unwrap().push(point);
+            Ok(())
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_hung_board_does_not_hold_up_the_rest() {
+        let (_event_tx, event_rx) = mpsc::channel(1);
+        let (scheduler_tx, _scheduler_rx) = mpsc::channel(1);
+        let (_command_tx, command_rx) = mpsc::channel(1);
+        let mut backplane = Backplane::new(event_rx, scheduler_tx, command_rx);
+
+        backplane
+            .start_board("Test", "hung".into(), make_board(None))
+            .await;
+        backplane
+            .start_board("Test", "ok".into(), make_board(Some(12.0)))
+            .await;
+        while backplane
+            .list_boards()
+            .iter()
+            .any(|b| b.health != BoardHealth::Running)
+        {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        // Power comes back from the working board once the hung one times out
+        let started = tokio::time::Instant::now();
+        assert_eq!(backplane.read_power().await, Some(12.0));
+        assert!(started.elapsed() <= BOARD_STATUS_TIMEOUT + Duration::from_millis(10));
+
+        // Only the working board has telemetry, and listing doesn't wait
+        let boards = backplane.list_boards();
+        assert_eq!(boards[0].id, "hung");
+        assert!(boards[0].telemetry.is_none());
+        assert_eq!(
+            boards[1].telemetry.as_ref().unwrap().power_watts,
+            Some(12.0)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_unplugged_board_reconnects_in_place() {
+        let (_event_tx, event_rx) = mpsc::channel(1);
+        let (scheduler_tx, _scheduler_rx) = mpsc::channel(1);
+        let (_command_tx, command_rx) = mpsc::channel(1);
+        let mut backplane = Backplane::new(event_rx, scheduler_tx, command_rx);
+        backplane
+            .start_board("Test", "abc".into(), make_board(Some(12.0)))
+            .await;
+        backplane
+            .usb_boards
+            .insert("/sys/usb/1-1".into(), "abc".into());
+        while backplane.boards["abc"].health() != BoardHealth::Running {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let unplug = |path: &str| UsbTransportEvent::UsbDeviceDisconnected {
+            device_path: path.into(),
+        };
+        let plug = |path: &str| {
+            UsbTransportEvent::UsbDeviceConnected(UsbDeviceInfo::new_for_test(
+                0x303a,
+                0x1001,
+                Some("abc".into()),
+                Some("OSMU".into()),
+                Some("Bitaxe".into()),
+                path.into(),
+            ))
+        };
+
+        // Off the bus for a moment, back on another port
+        backplane
+            .handle_usb_event(unplug("/sys/usb/1-1"))
+            .await
+            .unwrap();
+        assert_eq!(backplane.boards["abc"].health(), BoardHealth::Running);
+        backplane
+            .handle_usb_event(plug("/sys/usb/1-2"))
+            .await
+            .unwrap();
+        assert!(backplane.reconnecting.is_empty());
+        assert_eq!(backplane.usb_boards["/sys/usb/1-2"], "abc");
+        assert_eq!(backplane.boards["abc"].generation(), 1);
+        while let Ok(lifecycle) = backplane.lifecycle_rx.try_recv() {
+            backplane.history.record(&lifecycle.id, lifecycle.entry);
+        }
+        let history = backplane.history.get("abc").unwrap();
+        assert_eq!(history.last().unwrap().event, BoardEvent::Reconnected);
+
+        // Gone for longer than its grace
+        backplane
+            .handle_usb_event(unplug("/sys/usb/1-2"))
+            .await
+            .unwrap();
+        tokio::time::sleep(backplane.restart_policy.reconnect_grace).await;
+        backplane.expire_reconnects().await;
+        assert!(backplane.boards.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_filtered_board_not_started() {
+        let (_event_tx, event_rx) = mpsc::channel(1);
+        let (scheduler_tx, _scheduler_rx) = mpsc::channel(1);
+        let (_command_tx, command_rx) = mpsc::channel(1);
+        let (events_tx, mut events_rx) = broadcast::channel(8);
+        let mut backplane = Backplane::new(event_rx, scheduler_tx, command_rx)
+            .with_board_filter(BoardFilter::new(|_, id| id != "theirs"))
+            .with_events(events_tx);
+
+        backplane
+            .start_board("Test", "theirs".into(), make_board(Some(12.0)))
+            .await;
+        backplane
+            .start_board("Test", "ours".into(), make_board(Some(12.0)))
+            .await;
+
+        let ids: Vec<_> = backplane.list_boards().into_iter().map(|b| b.id).collect();
+        assert_eq!(ids, ["ours"]);
+        assert_eq!(
+            events_rx.try_recv().unwrap(),
+            RuntimeEvent::BoardConnected {
+                id: "ours".into(),
+                model: "Test".into()
+            }
+        );
+        assert!(events_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_firmware_update_refused_without_controller() {
+        let (_event_tx, event_rx) = mpsc::channel(1);
+        let (scheduler_tx, _scheduler_rx) = mpsc::channel(1);
+        let (_command_tx, command_rx) = mpsc::channel(1);
+        let mut backplane = Backplane::new(event_rx, scheduler_tx, command_rx);
+        backplane
+            .start_board("Test", "plain".into(), make_board(Some(12.0)))
+            .await;
+        while backplane.boards["plain"].health() != BoardHealth::Running {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let image = FirmwareImage {
+            offset: 0,
+            data: bytes::Bytes::from_static(&[0xe9; 16]),
+        };
+        let err = backplane.update_firmware("plain", image).await.unwrap_err();
+        assert!(err.to_string().contains("not supported"), "{err}");
+
+        // The board wasn't stopped for nothing
+        assert_eq!(backplane.boards["plain"].health(), BoardHealth::Running);
+        assert!(backplane.firmware_updates.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_refused_settings_not_stored() {
+        let (_event_tx, event_rx) = mpsc::channel(1);
+        let (scheduler_tx, _scheduler_rx) = mpsc::channel(1);
+        let (_command_tx, command_rx) = mpsc::channel(1);
+        let mut backplane = Backplane::new(event_rx, scheduler_tx, command_rx);
+        backplane
+            .start_board("Test", "fanless".into(), make_board(Some(12.0)))
+            .await;
+        while backplane.boards["fanless"].health() != BoardHealth::Running {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let settings = BoardSettings {
+            fan: Some(crate::board::FanMode::Fixed { percent: 50 }),
+            ..Default::default()
+        };
+        let err = backplane
+            .write_settings("fanless", settings)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("not supported"), "{err}");
+        assert!(backplane.settings.get("fanless").is_empty());
+        assert!(backplane.boards["fanless"].settings().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_apply_preset() {
+        use crate::board::preset::PresetPoint;
+
+        let (_event_tx, event_rx) = mpsc::channel(1);
+        let (scheduler_tx, _scheduler_rx) = mpsc::channel(1);
+        let (_command_tx, command_rx) = mpsc::channel(1);
+        let eco = PresetPoint {
+            frequency_mhz: Some(400.0),
+            voltage: Some(1.1),
+            ..Default::default()
+        };
+        let presets = BTreeMap::from([(
+            "Test".to_string(),
+            PresetSet {
+                eco: Some(eco),
+                ..Default::default()
+            },
+        )]);
+        let mut backplane =
+            Backplane::new(event_rx, scheduler_tx, command_rx).with_presets(presets);
+        for id in ["a", "b"] {
+            backplane
+                .start_board("Test", id.into(), make_board(Some(12.0)))
+                .await;
+            while backplane.boards[id].health() != BoardHealth::Running {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }
+
+        // Board b's chips run eco at a lower voltage
+        let mut refined = BoardSettings::default();
+        refined.presets.insert(
+            Preset::Eco,
+            PresetPoint {
+                voltage: Some(1.05),
+                ..Default::default()
+            },
+        );
+        backplane.settings.set("b", refined).unwrap();
+
+        let ids = backplane.apply_preset(None, Preset::Eco).await.unwrap();
+        assert_eq!(ids, ["a", "b"]);
+        let a = backplane.settings.get("a");
+        assert_eq!((a.frequency_mhz, a.voltage), (Some(400.0), Some(1.1)));
+        assert_eq!(a.preset, Some(Preset::Eco));
+        assert_eq!(backplane.boards["b"].settings().voltage, Some(1.05));
+
+        let err = backplane
+            .apply_preset(Some("a"), Preset::Turbo)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, PresetError::Undefined { .. }), "{err}");
+        assert_eq!(backplane.settings.get("a").preset, Some(Preset::Eco));
+        assert!(matches!(
+            backplane.apply_preset(Some("c"), Preset::Eco).await,
+            Err(PresetError::NoBoard(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_preset_skips_unsupported_frequency() {
+        use crate::board::preset::PresetPoint;
+
+        let (_event_tx, event_rx) = mpsc::channel(1);
+        let (scheduler_tx, _scheduler_rx) = mpsc::channel(1);
+        let (_command_tx, command_rx) = mpsc::channel(1);
+        let eco = PresetPoint {
+            frequency_mhz: Some(400.0),
+            voltage: Some(1.1),
+            ..Default::default()
+        };
+        let presets = BTreeMap::from([(
+            "Test".to_string(),
+            PresetSet {
+                eco: Some(eco),
+                ..Default::default()
+            },
+        )]);
+        let mut backplane =
+            Backplane::new(event_rx, scheduler_tx, command_rx).with_presets(presets);
+        let set = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let board_set = set.clone();
+        let make_board: MakeBoardFn = Box::new(move || {
+            let set = board_set.clone();
+            Box::pin(async move { Ok(Box::new(FixedClockBoard { set }) as _) })
+        });
+        backplane
+            .start_board("Test", "fixed".into(), make_board)
+            .await;
+        while backplane.boards["fixed"].health() != BoardHealth::Running {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        // The voltage is applied and stored; the frequency isn't
+        backplane.apply_preset(None, Preset::Eco).await.unwrap();
+        let applied = set.lock().unwrap().clone();
+        assert_eq!(
+            applied,
+            [OperatingPoint {
+                frequency_mhz: None,
+                voltage: Some(1.1),
+            }]
+        );
+        let stored = backplane.settings.get("fixed");
+        assert_eq!((stored.frequency_mhz, stored.voltage), (None, Some(1.1)));
+        assert_eq!(stored.preset, Some(Preset::Eco));
+    }
+
+    #[test]
+    fn test_stored_identity_picks_descriptor() {
+        use crate::board::pattern::{BoardPattern, Match, StringMatch};
+
+        let descriptor = |name, model| BoardDescriptor {
+            pattern: BoardPattern {
+                manufacturer: Match::Specific(StringMatch::Exact("OSMU")),
+                model,
+                ..BoardPattern::wildcard()
+            },
+            name,
+            create_fn: |_| {
+                Box::pin(async { Err(crate::error::Error::Hardware("test descriptor".into())) })
+            },
+            identify_fn: None,
+        };
+        let generic = descriptor("Generic", Match::Any);
+        let supra = descriptor("Supra", Match::Specific(StringMatch::Exact("Bitaxe Supra")));
+        let identity = |model: &str| BoardIdentity {
+            model: model.into(),
+            revision: None,
+            serial: None,
+            calibration: Default::default(),
+        };
+        let pick = |identity: Option<&BoardIdentity>| {
+            best_descriptor([&generic, &supra].into_iter(), identity).map(|desc| desc.name)
+        };
+
+        assert_eq!(pick(Some(&identity("Bitaxe Supra"))), Some("Supra"));
+        assert_eq!(pick(Some(&identity("Bitaxe Gamma"))), Some("Generic"));
+        assert_eq!(pick(None), Some("Generic"));
     }
 }
@@ -0,0 +1,751 @@
+//! Board benchmarking.
+//!
+//! A benchmark sweeps the connected hash threads through a matrix of
+//! operating points (chip frequency x core voltage), mining the dummy
+//! source's fallback job at each one, and records hashrate, power draw,
+//! efficiency, and error rate. The result is a [`BenchmarkReport`], written
+//! as JSON or CSV.
+//!
+//! Hashrate is measured from shares rather than taken from what the threads
+//! report: each share at the task's share target stands for
+//! `expected_hashes` of work, so their sum over the measurement window
+//! divided by its length is an unbiased estimate. A share whose hash doesn't
+//! actually meet its target counts as an error, as do hardware errors the
+//! threads report themselves.
+//!
+//! Boards that can't retune (see [`Board::set_operating_point`]) can still
+//! be measured at their current settings with a plan that changes nothing.
+//!
+//! Run with `mujina-minerd --benchmark [REPORT]`; see `--help` for the
+//! matrix options.
+
+use std::{
+    fmt::Write as _,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use serde::Serialize;
+use tokio::sync::{mpsc, oneshot};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    asic::hash_thread::{HashTask, HashThread, Share},
+    backplane::BackplaneCommand,
+    board::{Board, OperatingPoint},
+    job_source::{dummy, JobTemplate, MerkleRootKind},
+    tracing::prelude::*,
+    types::{target_for_share_rate, HashRate, ShareRate, Target},
+    u256::U256,
+};
+
+/// Shares per second each thread should find while being measured.
+///
+/// High enough for a tight hashrate estimate within a minute, low enough not
+/// to load slow share paths.
+const SHARES_PER_SECOND: f64 = 4.0;
+
+/// How long to wait for further boards after the first thread arrives.
+const SETTLE_TIME: Duration = Duration::from_secs(5);
+
+/// Interval between power readings.
+const POWER_SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// What to measure, and for how long.
+#[derive(Debug, Clone)]
+pub struct BenchmarkPlan {
+    /// Operating points, measured in order.
+    pub points: Vec<OperatingPoint>,
+
+    /// Time to let each point settle before measuring.
+    pub warmup: Duration,
+
+    /// Measurement window at each point.
+    pub duration: Duration,
+}
+
+impl BenchmarkPlan {
+    /// Every combination of the given frequencies and voltages.
+    ///
+    /// Frequencies vary fastest, so each voltage step is followed by a sweep
+    /// of frequencies. An empty list leaves that setting alone; with both
+    /// empty the plan measures the current settings once.
+    pub fn matrix(
+        frequencies_mhz: &[f32],
+        voltages: &[f32],
+        warmup: Duration,
+        duration: Duration,
+    ) -> Self {
+        let frequencies: Vec<Option<f32>> = if frequencies_mhz.is_empty() {
+            vec![None]
+        } else {
+            frequencies_mhz.iter().copied().map(Some).collect()
+        };
+        let voltages: Vec<Option<f32>> = if voltages.is_empty() {
+            vec![None]
+        } else {
+            voltages.iter().copied().map(Some).collect()
+        };
+
+        let points = voltages
+            .iter()
+            .flat_map(|&voltage| {
+                frequencies
+                    .iter()
+                    .map(move |&frequency_mhz| OperatingPoint {
+                        frequency_mhz,
+                        voltage,
+                    })
+            })
+            .collect();
+
+        Self {
+            points,
+            warmup,
+            duration,
+        }
+    }
+}
+
+impl Default for BenchmarkPlan {
+    fn default() -> Self {
+        Self::matrix(&[], &[], Duration::from_secs(10), Duration::from_secs(60))
+    }
+}
+
+/// Benchmark settings for the daemon.
+#[derive(Debug, Clone)]
+pub struct BenchmarkOptions {
+    /// What to measure.
+    pub plan: BenchmarkPlan,
+
+    /// Where to write the report; stdout if unset. A `.csv` extension
+    /// selects CSV, anything else JSON.
+    pub output: Option<PathBuf>,
+}
+
+impl BenchmarkOptions {
+    /// Write `report` to the configured destination.
+    pub fn write_report(&self, report: &BenchmarkReport) -> anyhow::Result<()> {
+        match &self.output {
+            Some(path) => {
+                let text = report.render(ReportFormat::from_path(path));
+                std::fs::write(path, text)
+                    .with_context(|| format!("writing benchmark report {}", path.display()))?;
+                info!(path = %path.display(), "Benchmark report written.");
+            }
+            None => println!("{}", report.to_json()),
+        }
+        Ok(())
+    }
+}
+
+/// Something that can retune boards and read their power draw.
+#[async_trait]
+pub trait OperatingPointControl: Send {
+    /// Move to `point`.
+    async fn set_operating_point(&mut self, point: OperatingPoint) -> Result<(), String>;
+
+    /// Present power draw in watts, if known.
+    async fn power_watts(&mut self) -> Option<f32>;
+}
+
+#[async_trait]
+impl OperatingPointControl for Box<dyn Board + Send> {
+    async fn set_operating_point(&mut self, point: OperatingPoint) -> Result<(), String> {
+        Board::set_operating_point(self.as_mut(), point)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    async fn power_watts(&mut self) -> Option<f32> {
+        Board::power_watts(self.as_mut()).await
+    }
+}
+
+/// Controls every board on the backplane.
+pub struct BackplaneControl {
+    command_tx: mpsc::Sender<BackplaneCommand>,
+}
+
+impl BackplaneControl {
+    /// Control boards through the backplane's command channel.
+    pub fn new(command_tx: mpsc::Sender<BackplaneCommand>) -> Self {
+        Self { command_tx }
+    }
+}
+
+#[async_trait]
+impl OperatingPointControl for BackplaneControl {
+    async fn set_operating_point(&mut self, point: OperatingPoint) -> Result<(), String> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.command_tx
+            .send(BackplaneCommand::SetOperatingPoint { point, reply_tx })
+            .await
+            .map_err(|_| "backplane is not running".to_string())?;
+        let count = reply_rx
+            .await
+            .map_err(|_| "backplane dropped the request".to_string())??;
+        if count == 0 {
+            return Err("no boards to retune".into());
+        }
+        Ok(())
+    }
+
+    async fn power_watts(&mut self) -> Option<f32> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.command_tx
+            .send(BackplaneCommand::ReadPower { reply_tx })
+            .await
+            .ok()?;
+        reply_rx.await.ok().flatten()
+    }
+}
+
+/// Results of a benchmark run.
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchmarkReport {
+    /// Names of the hash threads that were measured.
+    pub threads: Vec<String>,
+
+    /// Warm-up time at each point, in seconds.
+    pub warmup_secs: f64,
+
+    /// Measurement window at each point, in seconds.
+    pub duration_secs: f64,
+
+    /// One entry per operating point, in plan order.
+    pub results: Vec<PointResult>,
+}
+
+/// Measurements at one operating point.
+#[derive(Debug, Clone, Serialize)]
+pub struct PointResult {
+    /// Requested chip frequency; None if left unchanged
+    pub frequency_mhz: Option<f32>,
+
+    /// Requested core voltage; None if left unchanged
+    pub voltage: Option<f32>,
+
+    /// Measured hashrate across all threads
+    pub hashrate_ghs: f64,
+
+    /// Mean power draw, if the boards can measure it
+    pub power_w: Option<f32>,
+
+    /// Energy per unit of work, if power is known
+    pub joules_per_terahash: Option<f64>,
+
+    /// Valid shares found during the window
+    pub shares: u64,
+
+    /// Invalid shares plus thread-reported hardware errors
+    pub errors: u64,
+
+    /// Errors as a fraction of all results
+    pub error_rate: f64,
+
+    /// Why the point couldn't be measured, if it couldn't
+    pub failure: Option<String>,
+}
+
+impl PointResult {
+    fn failed(point: OperatingPoint, reason: String) -> Self {
+        Self {
+            frequency_mhz: point.frequency_mhz,
+            voltage: point.voltage,
+            hashrate_ghs: 0.0,
+            power_w: None,
+            joules_per_terahash: None,
+            shares: 0,
+            errors: 0,
+            error_rate: 0.0,
+            failure: Some(reason),
+        }
+    }
+}
+
+/// Output format for a report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Json,
+    Csv,
+}
+
+impl ReportFormat {
+    /// Pick the format from a file extension, defaulting to JSON.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => Self::Csv,
+            _ => Self::Json,
+        }
+    }
+}
+
+impl BenchmarkReport {
+    /// Render the report in `format`.
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Json => self.to_json(),
+            ReportFormat::Csv => self.to_csv(),
+        }
+    }
+
+    /// Pretty-printed JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("report serializes")
+    }
+
+    /// CSV with a header row and one row per operating point.
+    ///
+    /// Unknown values are left empty.
+    pub fn to_csv(&self) -> String {
+        fn opt<T: std::fmt::Display>(value: Option<T>) -> String {
+            value.map(|v| v.to_string()).unwrap_or_default()
+        }
+
+        let mut csv = String::from(
+            "frequency_mhz,voltage,hashrate_ghs,power_w,joules_per_terahash,\
+             shares,errors,error_rate,failure\n",
+        );
+        for r in &self.results {
+            let failure = r
+                .failure
+                .as_deref()
+                .map(|f| format!("\"{}\"", f.replace('"', "\"\"")))
+                .unwrap_or_default();
+            let _ = writeln!(
+                csv,
+                "{},{},{:.3},{},{},{},{},{:.6},{}",
+                opt(r.frequency_mhz),
+                opt(r.voltage),
+                r.hashrate_ghs,
+                opt(r.power_w.map(|w| format!("{:.2}", w))),
+                opt(r.joules_per_terahash.map(|j| format!("{:.2}", j))),
+                r.shares,
+                r.errors,
+                r.error_rate,
+                failure,
+            );
+        }
+        csv
+    }
+}
+
+/// Run a benchmark plan on `threads`.
+///
+/// Threads are left idle afterwards. If `shutdown` fires part way through,
+/// the points measured so far are returned.
+pub async fn run(
+    threads: &mut [Box<dyn HashThread>],
+    control: &mut dyn OperatingPointControl,
+    plan: &BenchmarkPlan,
+    shutdown: &CancellationToken,
+) -> anyhow::Result<BenchmarkReport> {
+    anyhow::ensure!(!threads.is_empty(), "no hash threads to benchmark");
+    let template = Arc::new(dummy::job_template()?);
+
+    let mut report = BenchmarkReport {
+        threads: threads.iter().map(|t| t.name().to_string()).collect(),
+        warmup_secs: plan.warmup.as_secs_f64(),
+        duration_secs: plan.duration.as_secs_f64(),
+        results: Vec::with_capacity(plan.points.len()),
+    };
+
+    for (i, &point) in plan.points.iter().enumerate() {
+        if shutdown.is_cancelled() {
+            break;
+        }
+        info!(
+            point = %point,
+            step = i + 1,
+            of = plan.points.len(),
+            "Benchmarking operating point."
+        );
+
+        let result = match measure_point(threads, control, &template, point, plan, shutdown).await {
+            Ok(Some(result)) => result,
+            Ok(None) => break,
+            Err(reason) => {
+                warn!(point = %point, reason = %reason, "Skipping operating point");
+                PointResult::failed(point, reason)
+            }
+        };
+        info!(
+            point = %point,
+            hashrate_ghs = format!("{:.2}", result.hashrate_ghs),
+            power_w = ?result.power_w,
+            error_rate = result.error_rate,
+            "Operating point measured."
+        );
+        report.results.push(result);
+    }
+
+    for thread in threads.iter_mut() {
+        if let Err(e) = thread.go_idle().await {
+            warn!(thread = %thread.name(), error = %e, "Failed to idle thread after benchmark");
+        }
+    }
+
+    Ok(report)
+}
+
+/// Measure one operating point.
+///
+/// Returns `Ok(None)` if shutdown interrupted the measurement, and `Err`
+/// with a reason if the point couldn't be measured at all.
+async fn measure_point(
+    threads: &mut [Box<dyn HashThread>],
+    control: &mut dyn OperatingPointControl,
+    template: &Arc<JobTemplate>,
+    point: OperatingPoint,
+    plan: &BenchmarkPlan,
+    shutdown: &CancellationToken,
+) -> Result<Option<PointResult>, String> {
+    control.set_operating_point(point).await?;
+
+    // Fresh tasks, and fresh share channels so stragglers from the previous
+    // point aren't counted
+    let en2_ranges = match &template.merkle_root {
+        MerkleRootKind::Computed(m) => m
+            .extranonce2_range()
+            .split(threads.len())
+            .unwrap_or_else(|| vec![m.extranonce2_range().clone(); threads.len()]),
+        MerkleRootKind::Fixed(_) => unreachable!("fallback job computes its merkle root"),
+    };
+    let mut receivers = Vec::with_capacity(threads.len());
+    for (thread, en2_range) in threads.iter_mut().zip(en2_ranges) {
+        let share_target = share_target_for(thread.capabilities().hashrate_estimate);
+        let (share_tx, share_rx) = mpsc::channel(1000);
+        let task = HashTask {
+            template: template.clone(),
+            en2: en2_range.iter().next(),
+            en2_range: Some(en2_range),
+            share_target,
+            ntime: template.time,
+            share_tx,
+        };
+        thread
+            .replace_task(task)
+            .await
+            .map_err(|e| format!("{}: {}", thread.name(), e))?;
+        receivers.push((share_rx, share_target));
+    }
+
+    // Warm up, discarding whatever is found meanwhile
+    tokio::select! {
+        _ = tokio::time::sleep(plan.warmup) => {}
+        _ = shutdown.cancelled() => return Ok(None),
+    }
+    for (rx, _) in receivers.iter_mut() {
+        while rx.try_recv().is_ok() {}
+    }
+    let errors_before: u64 = threads.iter().map(|t| t.status().hardware_errors).sum();
+
+    // Measure
+    let start = tokio::time::Instant::now();
+    let deadline = tokio::time::Instant::now() + plan.duration;
+    let collect = futures::future::join_all(
+        receivers
+            .into_iter()
+            .map(|(rx, target)| collect_shares(rx, target, deadline)),
+    );
+    let (tallies, power) = tokio::select! {
+        both = async { tokio::join!(collect, sample_power(control, deadline)) } => both,
+        _ = shutdown.cancelled() => return Ok(None),
+    };
+    let elapsed = start.elapsed().max(plan.duration).as_secs_f64();
+
+    let errors_after: u64 = threads.iter().map(|t| t.status().hardware_errors).sum();
+    let mut total = Tally::default();
+    for tally in tallies {
+        total.work += tally.work;
+        total.shares += tally.shares;
+        total.invalid += tally.invalid;
+    }
+    let errors = total.invalid + errors_after.saturating_sub(errors_before);
+
+    let hashrate = total.work / elapsed;
+    let hashrate_ghs = hashrate / 1e9;
+    let joules_per_terahash = power
+        .filter(|_| hashrate > 0.0)
+        .map(|watts| f64::from(watts) / (hashrate / 1e12));
+    let attempts = total.shares + errors;
+    let error_rate = if attempts == 0 {
+        0.0
+    } else {
+        errors as f64 / attempts as f64
+    };
+
+    Ok(Some(PointResult {
+        frequency_mhz: point.frequency_mhz,
+        voltage: point.voltage,
+        hashrate_ghs,
+        power_w: power,
+        joules_per_terahash,
+        shares: total.shares,
+        errors,
+        error_rate,
+        failure: None,
+    }))
+}
+
+/// Share target giving [`SHARES_PER_SECOND`] at the thread's estimated rate.
+fn share_target_for(hashrate: HashRate) -> Target {
+    if hashrate.is_zero() {
+        return Target::MAX_ATTAINABLE_MAINNET;
+    }
+    target_for_share_rate(ShareRate::per_second(SHARES_PER_SECOND), hashrate)
+}
+
+/// Shares seen on one thread during the measurement window.
+#[derive(Debug, Default)]
+struct Tally {
+    /// Expected hashes represented by the valid shares
+    work: f64,
+    shares: u64,
+    invalid: u64,
+}
+
+/// Count the shares arriving on `rx` until `deadline`.
+async fn collect_shares(
+    mut rx: mpsc::Receiver<Share>,
+    target: Target,
+    deadline: tokio::time::Instant,
+) -> Tally {
+    let mut tally = Tally::default();
+    loop {
+        let share = tokio::select! {
+            share = rx.recv() => share,
+            _ = tokio::time::sleep_until(deadline) => break,
+        };
+        let Some(share) = share else {
+            break;
+        };
+
+        if target.is_met_by(share.hash) {
+            tally.shares += 1;
+            tally.work += U256::from(target.to_work()).to_f64_approx();
+        } else {
+            tally.invalid += 1;
+        }
+    }
+    tally
+}
+
+/// Average power over the window, or None if no reading was available.
+async fn sample_power(
+    control: &mut dyn OperatingPointControl,
+    deadline: tokio::time::Instant,
+) -> Option<f32> {
+    let mut interval = tokio::time::interval(POWER_SAMPLE_INTERVAL);
+    let mut sum = 0.0;
+    let mut count = 0u32;
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = tokio::time::sleep_until(deadline) => break,
+        }
+        if let Some(watts) = control.power_watts().await {
+            sum += watts;
+            count += 1;
+        }
+    }
+    (count > 0).then(|| sum / count as f32)
+}
+
+/// Wait for hash threads to arrive from the backplane.
+///
+/// Returns once no new thread has shown up for a few seconds after the first,
+/// so that every board found at startup is included. Returns an empty list
+/// if shutdown fires first.
+pub async fn gather_threads(
+    thread_rx: &mut mpsc::Receiver<Box<dyn HashThread>>,
+    shutdown: &CancellationToken,
+) -> Vec<Box<dyn HashThread>> {
+    let mut threads = Vec::new();
+
+    tokio::select! {
+        thread = thread_rx.recv() => threads.extend(thread),
+        _ = shutdown.cancelled() => return threads,
+    }
+
+    loop {
+        tokio::select! {
+            thread = tokio::time::timeout(SETTLE_TIME, thread_rx.recv()) => match thread {
+                Ok(Some(thread)) => threads.push(thread),
+                Ok(None) | Err(_) => break,
+            },
+            _ = shutdown.cancelled() => break,
+        }
+    }
+
+    threads
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::sim::{SimBoard, SimConfig};
+
+    fn sim_board(threads: usize) -> Box<dyn Board + Send> {
+        Box::new(SimBoard::new(SimConfig {
+            thread_count: threads,
+            hashrate: HashRate::from_gigahashes(100.0),
+            seed: 11,
+            script: None,
+        }))
+    }
+
+    #[test]
+    fn test_matrix_order() {
+        let plan = BenchmarkPlan::matrix(
+            &[400.0, 500.0],
+            &[1.1, 1.2],
+            Duration::ZERO,
+            Duration::from_secs(1),
+        );
+        let points: Vec<_> = plan
+            .points
+            .iter()
+            .map(|p| (p.frequency_mhz.unwrap(), p.voltage.unwrap()))
+            .collect();
+        assert_eq!(
+            points,
+            [(400.0, 1.1), (500.0, 1.1), (400.0, 1.2), (500.0, 1.2)]
+        );
+
+        // Nothing to sweep: one point at current settings
+        let plan = BenchmarkPlan::default();
+        assert_eq!(plan.points, [OperatingPoint::default()]);
+
+        // Frequency-only sweep
+        let plan = BenchmarkPlan::matrix(&[400.0, 500.0], &[], Duration::ZERO, Duration::ZERO);
+        assert_eq!(plan.points.len(), 2);
+        assert!(plan.points.iter().all(|p| p.voltage.is_none()));
+    }
+
+    #[test]
+    fn test_csv_report() {
+        let report = BenchmarkReport {
+            threads: vec!["Sim 0".into()],
+            warmup_secs: 0.0,
+            duration_secs: 60.0,
+            results: vec![
+                PointResult {
+                    frequency_mhz: Some(500.0),
+                    voltage: Some(1.15),
+                    hashrate_ghs: 1000.0,
+                    power_w: Some(20.0),
+                    joules_per_terahash: Some(20.0),
+                    shares: 240,
+                    errors: 0,
+                    error_rate: 0.0,
+                    failure: None,
+                },
+                PointResult::failed(
+                    OperatingPoint {
+                        frequency_mhz: Some(600.0),
+                        voltage: None,
+                    },
+                    "said \"no\"".into(),
+                ),
+            ],
+        };
+
+        let csv = report.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("frequency_mhz,voltage,hashrate_ghs"));
+        assert_eq!(lines[1], "500,1.15,1000.000,20.00,20.00,240,0,0.000000,");
+        assert_eq!(lines[2], "600,,0.000,,,0,0,0.000000,\"said \"\"no\"\"\"");
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json["results"][0]["hashrate_ghs"], 1000.0);
+        assert!(json["results"][1]["power_w"].is_null());
+    }
+
+    #[test]
+    fn test_report_format_from_path() {
+        assert_eq!(
+            ReportFormat::from_path(Path::new("out.CSV")),
+            ReportFormat::Csv
+        );
+        assert_eq!(
+            ReportFormat::from_path(Path::new("out.json")),
+            ReportFormat::Json
+        );
+        assert_eq!(
+            ReportFormat::from_path(Path::new("out")),
+            ReportFormat::Json
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_benchmark_sim_board() {
+        let mut board = sim_board(2);
+        let mut threads = board.create_hash_threads().await.unwrap();
+        let plan = BenchmarkPlan::matrix(
+            &[250.0, 500.0],
+            &[1.15],
+            Duration::from_secs(2),
+            Duration::from_secs(120),
+        );
+
+        let report = run(&mut threads, &mut board, &plan, &CancellationToken::new())
+            .await
+            .unwrap();
+
+        assert_eq!(report.threads, ["Sim 0", "Sim 1"]);
+        assert_eq!(report.results.len(), 2);
+
+        // Two threads at 100 GH/s nominal; half that at half the clock
+        let slow = &report.results[0];
+        let fast = &report.results[1];
+        assert!((90.0..110.0).contains(&slow.hashrate_ghs), "{:?}", slow);
+        assert!((180.0..220.0).contains(&fast.hashrate_ghs), "{:?}", fast);
+        assert_eq!(fast.errors, 0);
+
+        // Power scales with frequency, so efficiency holds at fixed voltage
+        let (slow_j, fast_j) = (
+            slow.joules_per_terahash.unwrap(),
+            fast.joules_per_terahash.unwrap(),
+        );
+        assert!(
+            (slow_j / fast_j - 1.0).abs() < 0.15,
+            "{} vs {}",
+            slow_j,
+            fast_j
+        );
+
+        assert!(threads.iter().all(|t| !t.status().is_active));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_unsupported_point_is_recorded() {
+        let mut board = sim_board(1);
+        let mut threads = board.create_hash_threads().await.unwrap();
+        let plan = BenchmarkPlan {
+            points: vec![
+                OperatingPoint {
+                    frequency_mhz: Some(-1.0),
+                    voltage: None,
+                },
+                OperatingPoint::default(),
+            ],
+            warmup: Duration::ZERO,
+            duration: Duration::from_secs(10),
+        };
+
+        let report = run(&mut threads, &mut board, &plan, &CancellationToken::new())
+            .await
+            .unwrap();
+
+        assert!(report.results[0].failure.is_some());
+        assert!(report.results[1].failure.is_none());
+        assert!(report.results[1].shares > 0);
+    }
+}
@@ -21,6 +21,40 @@ struct EchoResponse {
     message: String,
 }
 
+/// I2C scan response payload.
+#[derive(Debug, Deserialize)]
+struct I2cScanResponse {
+    devices: Vec<ScannedDevice>,
+}
+
+/// A device found by an I2C scan.
+#[derive(Debug, Deserialize)]
+struct ScannedDevice {
+    address: u8,
+    part: Option<String>,
+}
+
+/// What's paused, as the pause and resume endpoints answer.
+#[derive(Debug, Deserialize)]
+struct PauseResponse {
+    paused: bool,
+    schedule: bool,
+    boards: Vec<String>,
+}
+
+/// Preset to switch boards to.
+#[derive(Debug, Serialize)]
+struct PresetRequest {
+    preset: String,
+}
+
+/// Boards switched to a preset.
+#[derive(Debug, Deserialize)]
+struct PresetResponse {
+    preset: String,
+    boards: Vec<String>,
+}
+
 /// Default API base URL.
 /// Port 7785 represents ASCII 'M' (77) and 'U' (85).
 const DEFAULT_API_URL: &str = "http://127.0.0.1:7785";
@@ -34,6 +68,10 @@ async fn main() -> Result<()> {
         eprintln!();
         eprintln!("Commands:");
         eprintln!("  echo [message]    Echo a message (reads from stdin if no args)");
+        eprintln!("  i2c-scan <board>  List and identify the devices on a board's I2C bus");
+        eprintln!("  pause [board]     Stop mining, or one board, keeping the boards up");
+        eprintln!("  resume [board]    Resume mining, or one board");
+        eprintln!("  preset <name> [board]  Switch boards, or one, to eco, balanced or turbo");
         std::process::exit(1);
     }
 
@@ -41,6 +79,10 @@ async fn main() -> Result<()> {
 
     match command.as_str() {
         "echo" => cmd_echo(&args[2..]).await?,
+        "i2c-scan" => cmd_i2c_scan(&args[2..]).await?,
+        "pause" => cmd_pause("pause", &args[2..]).await?,
+        "resume" => cmd_pause("resume", &args[2..]).await?,
+        "preset" => cmd_preset(&args[2..]).await?,
         _ => {
             eprintln!("Unknown command: {}", command);
             eprintln!("Run without arguments to see usage.");
@@ -88,3 +130,115 @@ async fn cmd_echo(args: &[String]) -> Result<()> {
 
     Ok(())
 }
+
+/// Execute the i2c-scan command.
+async fn cmd_i2c_scan(args: &[String]) -> Result<()> {
+    let [board] = args else {
+        anyhow::bail!("Usage: mujina-cli i2c-scan <board>");
+    };
+
+    let api_url = env::var("MUJINA_API_URL").unwrap_or_else(|_| DEFAULT_API_URL.to_string());
+    let url = format!("{}/api/v1/boards/{}/i2c/scan", api_url, board);
+
+    let response = Client::new()
+        .post(&url)
+        .send()
+        .await
+        .context("Failed to send request to API")?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        anyhow::bail!("API request failed: {} {}", status, body);
+    }
+
+    let scan: I2cScanResponse = response.json().await.context("Failed to parse response")?;
+
+    if scan.devices.is_empty() {
+        println!("No devices found");
+    }
+    for device in scan.devices {
+        let part = device.part.as_deref().unwrap_or("unknown");
+        println!("0x{:02x}  {}", device.address, part);
+    }
+
+    Ok(())
+}
+
+/// Execute the pause or resume command, for all mining or one board.
+async fn cmd_pause(action: &str, args: &[String]) -> Result<()> {
+    let path = match args {
+        [] => format!("mining/{}", action),
+        [board] => format!("boards/{}/{}", board, action),
+        _ => anyhow::bail!("Usage: mujina-cli {} [board]", action),
+    };
+
+    let api_url = env::var("MUJINA_API_URL").unwrap_or_else(|_| DEFAULT_API_URL.to_string());
+    let url = format!("{}/api/v1/{}", api_url, path);
+
+    let response = Client::new()
+        .post(&url)
+        .send()
+        .await
+        .context("Failed to send request to API")?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        anyhow::bail!("API request failed: {} {}", status, body);
+    }
+
+    let paused: PauseResponse = response.json().await.context("Failed to parse response")?;
+
+    match (paused.paused, paused.schedule) {
+        (true, true) => println!("Mining paused by the schedule"),
+        (true, false) => println!("Mining paused"),
+        (false, _) => println!("Mining"),
+    }
+    if !paused.boards.is_empty() {
+        println!("Paused boards: {}", paused.boards.join(", "));
+    }
+
+    Ok(())
+}
+
+/// Execute the preset command, for every board or one.
+async fn cmd_preset(args: &[String]) -> Result<()> {
+    let (preset, path) = match args {
+        [preset] => (preset, "preset".to_string()),
+        [preset, board] => (preset, format!("boards/{}/preset", board)),
+        _ => anyhow::bail!("Usage: mujina-cli preset <eco|balanced|turbo> [board]"),
+    };
+
+    let api_url = env::var("MUJINA_API_URL").unwrap_or_else(|_| DEFAULT_API_URL.to_string());
+    let url = format!("{}/api/v1/{}", api_url, path);
+
+    let response = Client::new()
+        .put(&url)
+        .json(&PresetRequest {
+            preset: preset.clone(),
+        })
+        .send()
+        .await
+        .context("Failed to send request to API")?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        anyhow::bail!("API request failed: {} {}", status, body);
+    }
+
+    let switched: PresetResponse = response.json().await.context("Failed to parse response")?;
+
+    if switched.boards.is_empty() {
+        println!("No boards to switch");
+    } else {
+        println!(
+            "Switched to {}: {}",
+            switched.preset,
+            switched.boards.join(", ")
+        );
+    }
+
+    Ok(())
+}
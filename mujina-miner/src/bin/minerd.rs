@@ -1,40 +1,352 @@
 //! Main entry point for the mujina-miner daemon.
 
-use mujina_miner::{daemon::Daemon, tracing};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 
-fn print_help() {
-    println!("mujina-minerd - Bitcoin mining daemon for Mujina Mining Firmware");
-    println!();
-    println!("USAGE:");
-    println!("    mujina-minerd [OPTIONS]");
-    println!();
-    println!("OPTIONS:");
-    println!("    --help     Print this help message");
-    println!();
-    println!("DESCRIPTION:");
-    println!("    A high-performance open-source Bitcoin mining daemon");
+use anyhow::Context;
+use clap::Parser;
+
+use mujina_miner::{
+    benchmark::{BenchmarkOptions, BenchmarkPlan},
+    board::{BoardDescriptor, VirtualBoardDescriptor},
+    config::{Config, DEFAULT_CONFIG},
+    cpu_miner::CpuMinerConfig,
+    daemon::{Daemon, DaemonOptions, ExitReason},
+    power::PowerBudget,
+    systemd, tracing,
+    types::Network,
+};
+
+/// Bitcoin mining daemon for Mujina Mining Firmware.
+///
+/// Options given here override the config file, which overrides the
+/// MUJINA_* environment variables.
+#[derive(Parser, Debug)]
+#[command(name = "mujina-minerd", version)]
+struct Args {
+    /// Load settings from a TOML config file
+    #[arg(short, long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Default log level (error, warn, info, debug, trace)
+    #[arg(short, long, value_name = "LEVEL")]
+    log_level: Option<String>,
+
+    /// Don't start the HTTP API server
+    #[arg(long)]
+    no_api: bool,
+
+    /// Pool URL, e.g. stratum+tcp://pool.example.com:3333
+    #[arg(long, value_name = "URL")]
+    pool: Option<String>,
+
+    /// Pool worker name
+    #[arg(long, value_name = "NAME", requires = "pool", alias = "pool-user")]
+    pool_worker: Option<String>,
+
+    /// Bitcoin network: mainnet, testnet4, or regtest
+    #[arg(long, value_name = "NETWORK")]
+    network: Option<Network>,
+
+    /// Mine on the CPU with this many threads
+    #[arg(long, value_name = "THREADS")]
+    cpu_miner: Option<usize>,
+
+    /// CPU miner duty cycle percentage
+    #[arg(
+        long,
+        value_name = "PERCENT",
+        default_value_t = 50,
+        value_parser = clap::value_parser!(u8).range(1..=100),
+        requires = "cpu_miner"
+    )]
+    cpu_duty: u8,
+
+    /// Cap the boards' combined power draw, in watts
+    #[arg(long, value_name = "WATTS")]
+    power_limit: Option<f32>,
+
+    /// Benchmark the boards instead of mining, write the report (JSON, or
+    /// CSV for a .csv path; stdout if omitted), and exit
+    #[arg(long, value_name = "REPORT", num_args = 0..=1)]
+    benchmark: Option<Option<PathBuf>>,
+
+    /// Chip frequencies to benchmark, in MHz
+    #[arg(
+        long,
+        value_name = "MHZ,...",
+        value_delimiter = ',',
+        requires = "benchmark"
+    )]
+    benchmark_frequencies: Vec<f32>,
+
+    /// Core voltages to benchmark, in volts
+    #[arg(
+        long,
+        value_name = "VOLTS,...",
+        value_delimiter = ',',
+        requires = "benchmark"
+    )]
+    benchmark_voltages: Vec<f32>,
+
+    /// Seconds to settle at each benchmark point before measuring
+    #[arg(
+        long,
+        value_name = "SECS",
+        default_value_t = 10,
+        requires = "benchmark"
+    )]
+    benchmark_warmup: u64,
+
+    /// Seconds to measure at each benchmark point
+    #[arg(
+        long,
+        value_name = "SECS",
+        default_value_t = 60,
+        requires = "benchmark"
+    )]
+    benchmark_duration: u64,
+
+    /// Print the supported board types and exit
+    #[arg(long)]
+    list_boards_and_exit: bool,
+
+    /// Check the config file, print any problems, and exit
+    #[arg(long, requires = "config")]
+    check_config: bool,
+
+    /// Print the default config file, every setting commented, and exit
+    #[arg(long)]
+    dump_default_config: bool,
 }
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    // Check for command-line arguments
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() > 1 {
-        match args[1].as_str() {
-            "--help" => {
-                print_help();
-                return Ok(());
-            }
-            _ => {
-                eprintln!("Unknown option: {}", args[1]);
-                eprintln!("Use --help for usage information");
-                std::process::exit(1);
+impl Args {
+    /// Build daemon options from the config file and command line.
+    fn daemon_options(&self, config: Option<&Config>) -> DaemonOptions {
+        let mut options = config.map(DaemonOptions::from).unwrap_or_default();
+        options.config_path = self.config.clone();
+
+        if self.no_api {
+            options.api_enabled = false;
+        }
+        if let Some(url) = &self.pool {
+            options.pool_url = Some(url.clone());
+        }
+        if let Some(worker) = &self.pool_worker {
+            options.pool_worker = Some(worker.clone());
+        }
+        if let Some(network) = self.network {
+            options.network = Some(network);
+        }
+        if let Some(thread_count) = self.cpu_miner {
+            options.cpu_miner = Some(CpuMinerConfig {
+                thread_count,
+                duty_percent: self.cpu_duty,
+            });
+        }
+        if let Some(limit_watts) = self.power_limit {
+            match &mut options.power_budget {
+                Some(budget) => budget.limit_watts = limit_watts,
+                None => {
+                    options.power_budget = Some(PowerBudget {
+                        limit_watts,
+                        weights: Default::default(),
+                    })
+                }
             }
         }
+        if let Some(output) = &self.benchmark {
+            options.benchmark = Some(BenchmarkOptions {
+                plan: BenchmarkPlan::matrix(
+                    &self.benchmark_frequencies,
+                    &self.benchmark_voltages,
+                    Duration::from_secs(self.benchmark_warmup),
+                    Duration::from_secs(self.benchmark_duration),
+                ),
+                output: output.clone(),
+            });
+        }
+
+        options
+    }
+}
+
+/// Print every problem with the config file at `path`; true if it has none.
+fn check_config(path: &Path) -> anyhow::Result<bool> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("reading config file {}", path.display()))?;
+    let problems = Config::check(&text);
+    for problem in &problems {
+        println!("{}: {}", path.display(), problem);
+    }
+    if problems.is_empty() {
+        println!("{}: OK", path.display());
+    }
+    Ok(problems.is_empty())
+}
+
+fn list_boards() {
+    let mut boards: Vec<&str> = inventory::iter::<BoardDescriptor>()
+        .map(|desc| desc.name)
+        .collect();
+    boards.sort_unstable();
+
+    let mut virtual_boards: Vec<(&str, &str)> = inventory::iter::<VirtualBoardDescriptor>()
+        .map(|desc| (desc.name, desc.device_type))
+        .collect();
+    virtual_boards.sort_unstable();
+
+    println!("USB boards:");
+    for name in boards {
+        println!("    {}", name);
+    }
+    println!();
+    println!("Virtual boards:");
+    for (name, device_type) in virtual_boards {
+        println!("    {} ({})", name, device_type);
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+
+    if args.list_boards_and_exit {
+        list_boards();
+        return Ok(());
+    }
+    if args.dump_default_config {
+        print!("{}", DEFAULT_CONFIG);
+        return Ok(());
+    }
+    if let (true, Some(path)) = (args.check_config, &args.config) {
+        if !check_config(path)? {
+            std::process::exit(1);
+        }
+        return Ok(());
     }
 
+    let mut config = args.config.as_deref().map(Config::load_from).transpose()?;
+
     tracing::init_journald_or_stdout();
 
-    let daemon = Daemon::new();
-    daemon.run().await
+    let log_level = args
+        .log_level
+        .as_deref()
+        .or(config.as_ref().map(|c| c.daemon.log_level.as_str()));
+    if let Some(level) = log_level {
+        tracing::set_default_level(tracing::parse_level(level)?)?;
+    }
+
+    // SAFETY: the runtime isn't built yet, so this is the only thread
+    let api_listener = unsafe { systemd::take_api_listener() }.map(Arc::new);
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?
+        .block_on(async {
+            // A restart requested through the API tears the daemon down and
+            // runs a fresh one in the same process.
+            loop {
+                let mut options = args.daemon_options(config.as_ref());
+                options.api_listener = api_listener.clone();
+                match Daemon::with_options(options).run().await? {
+                    ExitReason::Restart => {
+                        // Pick up pool changes the API saved to the config file
+                        if let Some(path) = &args.config {
+                            config = Some(Config::load_from(path)?);
+                        }
+                    }
+                    ExitReason::Shutdown => return Ok(()),
+                }
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_args_are_well_formed() {
+        use clap::CommandFactory;
+        Args::command().debug_assert();
+    }
+
+    #[test]
+    fn test_check_config_needs_a_file() {
+        assert!(Args::try_parse_from(["mujina-minerd", "--check-config"]).is_err());
+        let args =
+            Args::try_parse_from(["mujina-minerd", "--check-config", "-c", "mujina.toml"]).unwrap();
+        assert!(args.check_config);
+    }
+
+    #[test]
+    fn test_command_line_overrides() {
+        let args = Args::parse_from([
+            "mujina-minerd",
+            "--no-api",
+            "--pool",
+            "stratum+tcp://localhost:3333",
+            "--pool-worker",
+            "rig1",
+            "--cpu-miner",
+            "4",
+            "--network",
+            "regtest",
+        ]);
+        let options = args.daemon_options(None);
+
+        assert!(!options.api_enabled);
+        assert_eq!(
+            options.pool_url.as_deref(),
+            Some("stratum+tcp://localhost:3333")
+        );
+        assert_eq!(options.pool_worker.as_deref(), Some("rig1"));
+        let cpu = options.cpu_miner.unwrap();
+        assert_eq!(cpu.thread_count, 4);
+        assert_eq!(cpu.duty_percent, 50);
+        assert_eq!(options.network, Some(Network::Regtest));
+    }
+
+    #[test]
+    fn test_pool_user_still_accepted() {
+        let args = Args::parse_from(["mujina-minerd", "--pool", "x", "--pool-user", "rig1"]);
+        assert_eq!(args.pool_worker.as_deref(), Some("rig1"));
+    }
+
+    #[test]
+    fn test_benchmark_options() {
+        let args = Args::parse_from([
+            "mujina-minerd",
+            "--benchmark",
+            "--benchmark-frequencies",
+            "400,500",
+            "--benchmark-voltages",
+            "1.1,1.2",
+        ]);
+        let benchmark = args.daemon_options(None).benchmark.unwrap();
+        assert_eq!(benchmark.output, None);
+        assert_eq!(benchmark.plan.points.len(), 4);
+        assert_eq!(benchmark.plan.duration, Duration::from_secs(60));
+
+        let args = Args::parse_from(["mujina-minerd", "--benchmark", "report.csv"]);
+        let benchmark = args.daemon_options(None).benchmark.unwrap();
+        assert_eq!(benchmark.output, Some(PathBuf::from("report.csv")));
+        assert_eq!(benchmark.plan.points.len(), 1);
+
+        assert!(Args::try_parse_from(["mujina-minerd", "--benchmark-duration", "5"]).is_err());
+        assert!(Args::parse_from(["mujina-minerd"])
+            .daemon_options(None)
+            .benchmark
+            .is_none());
+    }
+
+    #[test]
+    fn test_cpu_duty_range() {
+        assert!(
+            Args::try_parse_from(["mujina-minerd", "--cpu-miner", "1", "--cpu-duty", "0"]).is_err()
+        );
+        assert!(Args::try_parse_from(["mujina-minerd", "--cpu-duty", "50"]).is_err());
+    }
 }
@@ -8,7 +8,7 @@ use std::{
 };
 use tokio::{
     io::{AsyncRead, ReadBuf},
-    sync::{watch, Mutex},
+    sync::{broadcast, mpsc, watch, Mutex},
     time,
 };
 use tokio_stream::StreamExt;
@@ -16,38 +16,91 @@ use tokio_util::codec::{FramedRead, FramedWrite};
 
 use crate::{
     asic::{
-        bm13xx::{self, protocol::Command, thread::BM13xxThread, BM13xxProtocol},
-        hash_thread::{BoardPeripherals, HashThread, ThreadRemovalSignal},
+        bm13xx::{
+            self,
+            protocol::Command,
+            rx::{BaudMonitor, RxStats},
+            thread::{BM13xxThread, ChipClockHandle, ChipResetHandle, ThreadStatusHandle},
+            BM13xxProtocol,
+        },
+        hash_thread::{BaudRateControl, BoardPeripherals, HashThread, ThreadRemovalSignal},
+        nonce_map::NonceMap,
         ChipInfo,
     },
     hw_trait::{
-        gpio::{Gpio, GpioPin, PinValue},
+        gpio::{Gpio, GpioPin, PinMode, PinValue},
         i2c::I2c,
+        HwError, I2cTrace, TimedI2c,
     },
     mgmt_protocol::{
+        bitaxe_raw::Notification,
         bitaxe_raw::{
             gpio::{BitaxeRawGpioController, BitaxeRawGpioPin},
             i2c::BitaxeRawI2c,
+            version::{self, Features, FirmwareInfo},
         },
         ControlChannel,
     },
     peripheral::{
+        eeprom::{Eeprom, EepromLayout},
         emc2101::{Emc2101, Percent},
+        scan::{self, ScannedDevice},
+        ssd1306::Ssd1306,
         tps546::{Tps546, Tps546Config},
     },
     tracing::prelude::*,
-    transport::serial::{SerialControl, SerialReader, SerialStream, SerialWriter},
+    transport::{
+        serial::{
+            FlowControl, PortBusy, SerialConfig, SerialControl, SerialError, SerialReader,
+            SerialStream, SerialWriter,
+        },
+        PortRole,
+    },
 };
 
 use super::{
+    identity::{self, BoardIdentity},
+    panel::{self, ButtonAction, Page, PanelLink, PanelStats},
     pattern::{Match, StringMatch},
-    Board, BoardError, BoardInfo,
+    supply::InputSupply,
+    Board, BoardError, BoardInfo, FanMode, IoLatency, OperatingPoint, ShutdownStage,
+    TelemetrySnapshot, VoltageRange,
+};
+#[cfg(all(target_os = "linux", feature = "direct-attach"))]
+use crate::{
+    board::{VirtualBoardDescriptor, VirtualDeviceInfo},
+    hw_trait::linux::{LinuxGpio, LinuxGpioPin, LinuxI2c},
 };
 
+/// Core voltages the TPS546 is configured to accept (its VOUT_MIN/VOUT_MAX).
+const CORE_VOLTAGE_RANGE: VoltageRange = VoltageRange { min: 1.0, max: 2.0 };
+
+/// bitaxe-raw's CDC-ACM interfaces, in interface order.
+const PORT_LAYOUT: [PortRole; 2] = [PortRole::Control, PortRole::Data];
+
+/// The board's 5 V barrel or USB-C input, which the TPS546 reads as VIN.
+const INPUT_SUPPLY: InputSupply = InputSupply { nominal_volts: 5.0 };
+
+/// EEPROM a bitaxe-raw board keeps its identity on, if it has one fitted: a
+/// 24C32 or larger at the default address, of which the first 4 KiB are
+/// used. The Gamma itself has none.
+const IDENTITY_EEPROM: EepromLayout = EepromLayout::AT24C32;
+
+/// Model reported by boards without a stored identity.
+const DEFAULT_MODEL: &str = "Bitaxe Gamma";
+
+/// Fan pulses that make a board easy to pick out, and how long each half
+/// of a pulse lasts.
+const IDENTIFY_PULSES: u32 = 5;
+const IDENTIFY_STEP: Duration = Duration::from_millis(700);
+
+/// Fan speed at the low end of an identify pulse.
+const IDENTIFY_LOW: Percent = Percent::new_clamped(20);
+
 /// Adapter implementing `AsicEnable` for Bitaxe's GPIO-based reset control.
 struct BitaxeAsicEnable {
     /// Reset pin (directly controls nRST on the BM1370)
-    nrst_pin: BitaxeRawGpioPin,
+    nrst_pin: ResetPin,
 }
 
 #[async_trait]
@@ -69,6 +122,111 @@ impl crate::asic::hash_thread::AsicEnable for BitaxeAsicEnable {
     }
 }
 
+/// Adapter implementing `BaudRateControl` for the Bitaxe's ASIC data port.
+///
+/// bitaxe-raw retunes its UART to the chips whenever the host changes the
+/// USB serial line coding, so setting the port's rate moves both.
+struct BitaxeBaudRate {
+    data_control: SerialControl,
+}
+
+#[async_trait]
+impl BaudRateControl for BitaxeBaudRate {
+    fn target_baud_rate(&self) -> u32 {
+        BitaxeBoard::TARGET_BAUD_RATE
+    }
+
+    async fn set_baud_rate(&mut self, baud: u32) -> anyhow::Result<()> {
+        self.data_control
+            .set_baud_rate(baud)
+            .map_err(|e| anyhow::anyhow!("Failed to set data port baud rate: {}", e))
+    }
+}
+
+/// The board's I2C bus: tunneled through bitaxe-raw, or the host's own
+/// adapter when the board is wired to it directly.
+#[derive(Clone)]
+enum BoardI2c {
+    Raw(BitaxeRawI2c),
+    #[cfg(all(target_os = "linux", feature = "direct-attach"))]
+    Host(LinuxI2c),
+}
+
+#[async_trait]
+impl I2c for BoardI2c {
+    async fn write(&mut self, addr: u8, data: &[u8]) -> crate::hw_trait::Result<()> {
+        match self {
+            Self::Raw(i2c) => i2c.write(addr, data).await,
+            #[cfg(all(target_os = "linux", feature = "direct-attach"))]
+            Self::Host(i2c) => i2c.write(addr, data).await,
+        }
+    }
+
+    async fn read(&mut self, addr: u8, buffer: &mut [u8]) -> crate::hw_trait::Result<()> {
+        match self {
+            Self::Raw(i2c) => i2c.read(addr, buffer).await,
+            #[cfg(all(target_os = "linux", feature = "direct-attach"))]
+            Self::Host(i2c) => i2c.read(addr, buffer).await,
+        }
+    }
+
+    async fn write_read(
+        &mut self,
+        addr: u8,
+        write: &[u8],
+        read: &mut [u8],
+    ) -> crate::hw_trait::Result<()> {
+        match self {
+            Self::Raw(i2c) => i2c.write_read(addr, write, read).await,
+            #[cfg(all(target_os = "linux", feature = "direct-attach"))]
+            Self::Host(i2c) => i2c.write_read(addr, write, read).await,
+        }
+    }
+
+    async fn set_frequency(&mut self, hz: u32) -> crate::hw_trait::Result<()> {
+        match self {
+            Self::Raw(i2c) => i2c.set_frequency(hz).await,
+            #[cfg(all(target_os = "linux", feature = "direct-attach"))]
+            Self::Host(i2c) => i2c.set_frequency(hz).await,
+        }
+    }
+}
+
+/// The ASICs' reset line, through bitaxe-raw or on the host's GPIO chip.
+#[derive(Clone)]
+enum ResetPin {
+    Raw(BitaxeRawGpioPin),
+    #[cfg(all(target_os = "linux", feature = "direct-attach"))]
+    Host(LinuxGpioPin),
+}
+
+#[async_trait]
+impl GpioPin for ResetPin {
+    async fn set_mode(&mut self, mode: PinMode) -> crate::hw_trait::Result<()> {
+        match self {
+            Self::Raw(pin) => pin.set_mode(mode).await,
+            #[cfg(all(target_os = "linux", feature = "direct-attach"))]
+            Self::Host(pin) => pin.set_mode(mode).await,
+        }
+    }
+
+    async fn write(&mut self, value: PinValue) -> crate::hw_trait::Result<()> {
+        match self {
+            Self::Raw(pin) => pin.write(value).await,
+            #[cfg(all(target_os = "linux", feature = "direct-attach"))]
+            Self::Host(pin) => pin.write(value).await,
+        }
+    }
+
+    async fn read(&mut self) -> crate::hw_trait::Result<PinValue> {
+        match self {
+            Self::Raw(pin) => pin.read().await,
+            #[cfg(all(target_os = "linux", feature = "direct-attach"))]
+            Self::Host(pin) => pin.read().await,
+        }
+    }
+}
+
 /// A wrapper around AsyncRead that traces raw bytes as they're read
 struct TracingReader<R> {
     inner: R,
@@ -114,32 +272,59 @@ impl<R: AsyncRead + Unpin> AsyncRead for TracingReader<R> {
 ///
 /// The Bitaxe Gamma running bitaxe-raw firmware provides a control interface for managing the
 /// hashboard, including GPIO reset control and board initialization sequences.
+///
+/// A Gamma can also be wired straight to a host such as a Raspberry Pi, its
+/// ASIC UART, I2C bus and reset line on the host's header, with no
+/// management controller at all; see [`BitaxeBoard::direct`].
 pub struct BitaxeBoard {
     /// Control channel for board management
-    control_channel: ControlChannel,
+    control_channel: Option<ControlChannel>,
     /// ASIC reset (active low)
-    asic_nrst: Option<BitaxeRawGpioPin>,
-    /// I2C bus controller
-    i2c: BitaxeRawI2c,
+    asic_nrst: Option<ResetPin>,
+    /// I2C bus controller, timing every transaction
+    i2c: TimedI2c<BoardI2c>,
     /// Fan controller (board-controlled only, not shared with thread)
-    fan_controller: Option<Emc2101<BitaxeRawI2c>>,
+    fan_controller: Option<Emc2101<TimedI2c<BoardI2c>>>,
     /// Voltage regulator (shared with thread, cached state)
-    regulator: Option<Arc<Mutex<Tps546<BitaxeRawI2c>>>>,
+    regulator: Option<Arc<Mutex<Tps546<TimedI2c<BoardI2c>>>>>,
     /// Writer for sending commands to chips (transferred to hash thread)
     data_writer: Option<FramedWrite<SerialWriter, bm13xx::FrameCodec>>,
     /// Reader for receiving responses from chips (transferred to hash thread)
     data_reader: Option<FramedRead<TracingReader<SerialReader>, bm13xx::FrameCodec>>,
     /// Control handle for data channel (for baud rate changes)
-    #[expect(dead_code, reason = "will be used when baud rate change is fixed")]
     data_control: SerialControl,
+    /// Receive counters from the data reader's decoder
+    rx_stats: Arc<RxStats>,
     /// Discovered chip information (passive record-keeping)
     chip_infos: Vec<ChipInfo>,
     /// Thread shutdown signal (board-to-thread implementation detail)
     thread_shutdown: Option<watch::Sender<ThreadRemovalSignal>>,
+    /// Faults the hash thread reports, until the board's task takes them
+    fault_rx: Option<mpsc::Receiver<String>>,
+    /// Resets the hash thread's chips once it exists
+    chip_reset: Option<ChipResetHandle>,
+    /// Retunes the hash thread's chips once it exists
+    chip_clock: Option<ChipClockHandle>,
+    /// Reads the hash thread's status once it exists
+    thread_status: Option<ThreadStatusHandle>,
     /// Handle for the statistics task
     stats_task_handle: Option<tokio::task::JoinHandle<()>>,
+    /// Fan pulsing started by `identify`, if it's still going
+    identify_task: Option<tokio::task::JoinHandle<()>>,
+    /// Link to the miner for the screen and button, if given one
+    panel: Option<PanelLink>,
+    /// Task driving the screen and button
+    panel_task: Option<tokio::task::JoinHandle<()>>,
     /// Serial number from USB device info
     serial_number: Option<String>,
+    /// Identity stored on the board, as read at initialization
+    identity: Option<BoardIdentity>,
+
+    /// Path of the control port, for firmware updates
+    control_path: Option<String>,
+    /// What the management firmware reported it can do; `None` when there
+    /// is no management firmware, the board being wired to the host
+    firmware: Option<FirmwareInfo>,
 }
 
 impl BitaxeBoard {
@@ -148,17 +333,28 @@ impl BitaxeBoard {
 
     /// Bitaxe Gamma board configuration
     /// The Gamma uses a BM1370 chip and runs at 1Mbps after initialization
-    #[expect(dead_code, reason = "will be used when baud rate change is fixed")]
     const TARGET_BAUD_RATE: u32 = 1_000_000;
-    #[expect(dead_code, reason = "will be used when baud rate change is fixed")]
-    const CHIP_BAUD_REGISTER: bm13xx::protocol::BaudRate = bm13xx::protocol::BaudRate::Baud1M;
-    const EXPECTED_CHIP_ID: [u8; 2] = [0x13, 0x70]; // BM1370
+
+    /// Both ports are the ESP32-S3's native USB CDC-ACM, which passes bytes
+    /// on as they arrive, so they need no latency tuning. The control port's
+    /// rate is nominal.
+    const CONTROL_PORT: SerialConfig = SerialConfig::new(115_200);
+
+    /// The data port's line coding sets the ASIC UART, which starts at the
+    /// chips' reset rate. It's held across a USB drop (see
+    /// [`Board::reconnect`]), so the hash thread's framing survives one.
+    const DATA_PORT: SerialConfig = SerialConfig::new(115_200).with_hold_on_disconnect();
+
+    /// A host UART wired to the ASICs, likewise at their reset rate.
+    #[cfg(all(target_os = "linux", feature = "direct-attach"))]
+    const HOST_UART: SerialConfig = SerialConfig::new(115_200);
+    const CHIP_TYPE: bm13xx::protocol::ChipType = bm13xx::protocol::ChipType::BM1370;
 
     /// Creates a new BitaxeBoard instance with the provided serial streams.
     ///
     /// # Arguments
     /// * `control` - Serial stream for sending board control commands
-    /// * `data_path` - Path to the data serial port (e.g., "/dev/ttyACM1")
+    /// * `data` - Data serial port (e.g., "/dev/ttyACM1") at 115200 baud
     ///
     /// # Returns
     /// A new BitaxeBoard instance ready for hardware operations
@@ -168,36 +364,98 @@ impl BitaxeBoard {
     /// are detected (by VID/PID) and pass already-opened serial streams.
     pub fn new(
         control: tokio_serial::SerialStream,
-        data_path: &str,
+        data: SerialStream,
         serial_number: Option<String>,
-    ) -> Result<Self, BoardError> {
+    ) -> Self {
         // Create control channel and I2C controller
         let control_channel = ControlChannel::new(control);
-        let i2c = BitaxeRawI2c::new(control_channel.clone());
+        let i2c = BitaxeRawI2c::new(control_channel.clone())
+            .with_trace(I2cTrace::from_env(serial_number.as_deref()));
 
-        // Create SerialStream for data channel at initial baud rate
-        let data_stream = SerialStream::new(data_path, 115200).map_err(|e| {
-            BoardError::InitializationFailed(format!("Failed to open data port: {}", e))
-        })?;
-        let (data_reader, data_writer, data_control) = data_stream.split();
+        let mut board = Self::from_parts(BoardI2c::Raw(i2c), data, serial_number);
+        board.control_channel = Some(control_channel);
+        board.firmware = Some(FirmwareInfo::LEGACY);
+        board
+    }
+
+    /// Creates a BitaxeBoard wired straight to the host, with no management
+    /// controller: the ASIC UART, I2C bus and reset line are the host's own.
+    #[cfg(all(target_os = "linux", feature = "direct-attach"))]
+    pub fn direct(i2c: LinuxI2c, reset: LinuxGpioPin, data: SerialStream, id: String) -> Self {
+        let mut board = Self::from_parts(BoardI2c::Host(i2c), data, Some(id));
+        board.asic_nrst = Some(ResetPin::Host(reset));
+        board
+    }
+
+    /// The board around its I2C bus and data port, before the way it's
+    /// managed is known.
+    fn from_parts(i2c: BoardI2c, data: SerialStream, serial_number: Option<String>) -> Self {
+        let (data_reader, data_writer, data_control) = data.split();
 
         // Wrap the data reader with tracing
         let tracing_reader = TracingReader::new(data_reader, "Data");
+        let rx_stats = Arc::new(RxStats::default());
 
-        Ok(BitaxeBoard {
-            control_channel,
+        BitaxeBoard {
+            control_channel: None,
             asic_nrst: None,
-            i2c,
+            i2c: TimedI2c::new(i2c, Arc::default()),
             fan_controller: None,
             regulator: None,
-            data_writer: Some(FramedWrite::new(data_writer, bm13xx::FrameCodec)),
-            data_reader: Some(FramedRead::new(tracing_reader, bm13xx::FrameCodec)),
+            data_writer: Some(FramedWrite::new(data_writer, bm13xx::FrameCodec::default())),
+            data_reader: Some(FramedRead::new(
+                tracing_reader,
+                bm13xx::FrameCodec::with_stats(rx_stats.clone())
+                    .with_variant(Self::CHIP_TYPE.variant()),
+            )),
             data_control,
+            rx_stats,
             chip_infos: Vec::new(),
             thread_shutdown: None,
+            fault_rx: None,
+            chip_reset: None,
+            chip_clock: None,
+            thread_status: None,
             stats_task_handle: None,
+            identify_task: None,
+            panel: None,
+            panel_task: None,
             serial_number,
-        })
+            identity: None,
+            control_path: None,
+            firmware: None,
+        }
+    }
+
+    /// Learn which firmware drives the board, refusing any too old to. A
+    /// board wired to the host has none to ask.
+    pub async fn negotiate_firmware(&mut self) -> crate::error::Result<()> {
+        let Some(ref control_channel) = self.control_channel else {
+            return Ok(());
+        };
+        let firmware = version::query(control_channel).await.map_err(|e| {
+            crate::error::Error::Hardware(format!("Failed to query firmware version: {}", e))
+        })?;
+        if firmware.version < version::MIN_VERSION {
+            return Err(crate::error::Error::FirmwareTooOld {
+                version: firmware.version,
+                minimum: version::MIN_VERSION,
+            });
+        }
+        info!(
+            version = %firmware.version,
+            features = ?firmware.features,
+            "Board firmware negotiated."
+        );
+        self.firmware = Some(firmware);
+        Ok(())
+    }
+
+    /// Record where the control port was opened, so the controller behind
+    /// it can be updated.
+    pub fn with_control_path(mut self, path: &str) -> Self {
+        self.control_path = Some(path.to_string());
+        self
     }
 
     /// Performs a momentary reset of the mining chips via GPIO control.
@@ -227,10 +485,7 @@ impl BitaxeBoard {
             .ok_or_else(|| BoardError::HardwareControl("Reset pin not initialized".to_string()))?;
 
         // Set reset high (inactive - active low signal)
-        debug!(
-            "De-asserting ASIC nRST (GPIO {} = high)",
-            Self::ASIC_RESET_PIN
-        );
+        debug!("De-asserting ASIC nRST (high)");
         reset_pin.write(PinValue::High).await.map_err(|e| {
             BoardError::HardwareControl(format!("Failed to de-assert reset: {}", e))
         })?;
@@ -314,8 +569,12 @@ impl BitaxeBoard {
                             register: bm13xx::Register::ChipId { chip_type, core_count, address }
                         })) => {
                             let chip_id = chip_type.id_bytes();
-                            debug!("Discovered chip {:?} ({:02x}{:02x}) at address {address}",
-                                         chip_type, chip_id[0], chip_id[1]);
+                            debug!(
+                                chip_type = ?chip_type,
+                                chip_id = %format!("{:02x}{:02x}", chip_id[0], chip_id[1]),
+                                address,
+                                "Discovered chip"
+                            );
 
                             let chip_info = ChipInfo {
                                 chip_id,
@@ -330,7 +589,7 @@ impl BitaxeBoard {
                             warn!("Unexpected response during chip discovery");
                         }
                         Some(Err(e)) => {
-                            error!("Error during chip discovery: {e}");
+                            error!(error = %e, "Error during chip discovery");
                         }
                         None => break,
                     }
@@ -370,8 +629,8 @@ impl BitaxeBoard {
 
             // Output voltage configuration
             vout_scale_loop: 0.25,
-            vout_min: 1.0,
-            vout_max: 2.0,
+            vout_min: CORE_VOLTAGE_RANGE.min,
+            vout_max: CORE_VOLTAGE_RANGE.max,
             vout_command: 1.15, // BM1370 default voltage
 
             // Output voltage protection (relative to vout_command)
@@ -402,6 +661,9 @@ impl BitaxeBoard {
 
             // Pin configuration
             pin_detect_override: 0xFFFF,
+
+            // One regulator drives the core rail
+            stack: Vec::new(),
         };
 
         let mut tps546 = Tps546::new(power_i2c, config);
@@ -416,24 +678,24 @@ impl BitaxeBoard {
                 const DEFAULT_VOUT: f32 = 1.15;
                 match tps546.set_vout(DEFAULT_VOUT).await {
                     Ok(()) => {
-                        debug!("Core voltage set to {DEFAULT_VOUT}V");
+                        debug!(vout = DEFAULT_VOUT, "Core voltage set");
 
                         // Wait for voltage to stabilize
                         tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
 
                         // Verify voltage
                         match tps546.get_vout().await {
-                            Ok(mv) => debug!("Core voltage readback: {:.3}V", mv as f32 / 1000.0),
-                            Err(e) => warn!("Failed to read core voltage: {}", e),
+                            Ok(mv) => debug!(vout_mv = mv, "Core voltage readback"),
+                            Err(e) => warn!(error = %e, "Failed to read core voltage"),
                         }
 
                         // Dump complete configuration for debugging
                         if let Err(e) = tps546.dump_configuration().await {
-                            warn!("Failed to dump TPS546 configuration: {}", e);
+                            warn!(error = %e, "Failed to dump TPS546 configuration");
                         }
                     }
                     Err(e) => {
-                        error!("Failed to set initial core voltage: {}", e);
+                        error!(error = %e, "Failed to set initial core voltage");
                         return Err(BoardError::InitializationFailed(format!(
                             "Failed to set core voltage: {}",
                             e
@@ -445,7 +707,7 @@ impl BitaxeBoard {
                 Ok(())
             }
             Err(e) => {
-                error!("Failed to initialize TPS546D24A power controller: {}", e);
+                error!(error = %e, "Failed to initialize TPS546D24A power controller");
                 Err(BoardError::InitializationFailed(format!(
                     "Power controller init failed: {}",
                     e
@@ -469,7 +731,7 @@ impl BitaxeBoard {
                         debug!("Fan speed set to 100%");
                     }
                     Err(e) => {
-                        warn!("Failed to set fan speed: {}", e);
+                        warn!(error = %e, "Failed to set fan speed");
                     }
                 }
 
@@ -477,7 +739,7 @@ impl BitaxeBoard {
                 Ok(())
             }
             Err(e) => {
-                warn!("Failed to initialize EMC2101 fan controller: {}", e);
+                warn!(error = %e, "Failed to initialize EMC2101 fan controller");
                 // Continue without fan control - not critical for operation
                 Ok(())
             }
@@ -502,7 +764,7 @@ impl BitaxeBoard {
             if let Some(config) = Self::calculate_pll_for_frequency(current) {
                 configs.push(config);
             } else {
-                warn!("Failed to calculate PLL for {:.2} MHz, skipping", current);
+                warn!(freq_mhz = current, "Failed to calculate PLL, skipping");
             }
 
             current += step_mhz;
@@ -605,24 +867,49 @@ impl BitaxeBoard {
     ///
     /// After initialization, the board is ready for `create_hash_threads()`.
     pub async fn initialize(&mut self) -> Result<(), BoardError> {
-        // Create GPIO controller and get reset pin handle
-        let mut gpio_controller = BitaxeRawGpioController::new(self.control_channel.clone());
-        let reset_pin = gpio_controller
-            .pin(Self::ASIC_RESET_PIN)
-            .await
-            .map_err(|e| {
-                BoardError::InitializationFailed(format!("Failed to get reset pin: {}", e))
-            })?;
-        self.asic_nrst = Some(reset_pin);
+        // Get the reset pin handle from bitaxe-raw, unless the host's own
+        // line was given
+        if let (None, Some(control_channel)) = (&self.asic_nrst, &self.control_channel) {
+            let mut gpio_controller = BitaxeRawGpioController::new(control_channel.clone());
+            let reset_pin = gpio_controller
+                .pin(Self::ASIC_RESET_PIN)
+                .await
+                .map_err(|e| {
+                    BoardError::InitializationFailed(format!("Failed to get reset pin: {}", e))
+                })?;
+            self.asic_nrst = Some(ResetPin::Raw(reset_pin));
+        }
 
         // Phase 1: Hold ASIC in reset during power configuration
         trace!("Holding ASIC in reset during power initialization");
         self.hold_in_reset().await?;
 
         // Phase 2: Initialize power controller while ASIC is in reset
-        self.i2c.set_frequency(100_000).await.map_err(|e| {
-            BoardError::InitializationFailed(format!("Failed to set I2C frequency: {}", e))
-        })?;
+        // A host adapter's speed is fixed by its device tree
+        match self.i2c.set_frequency(100_000).await {
+            Ok(()) | Err(HwError::NotSupported(_)) => {}
+            Err(e) => {
+                return Err(BoardError::InitializationFailed(format!(
+                    "Failed to set I2C frequency: {}",
+                    e
+                )))
+            }
+        }
+
+        // Most boards have no EEPROM to answer, so a failed read just means
+        // there's no identity
+        match self.identity().await {
+            Ok(identity) => self.identity = identity,
+            Err(e) => debug!(error = %e, "No stored identity"),
+        }
+        if let Some(ref identity) = self.identity {
+            info!(
+                model = %identity.model,
+                revision = ?identity.revision,
+                serial = ?identity.serial,
+                "Board identity read."
+            );
+        }
 
         self.init_fan_controller().await?;
         self.init_power_controller().await?;
@@ -638,7 +925,7 @@ impl BitaxeBoard {
         // Phase 4: Version mask and chip discovery
         debug!("Sending version mask configuration (3 times)");
         for i in 1..=3 {
-            trace!("Version mask send {}/3", i);
+            trace!(attempt = i, "Version mask send");
             let version_cmd = Command::WriteRegister {
                 broadcast: true,
                 chip_address: 0x00,
@@ -658,10 +945,11 @@ impl BitaxeBoard {
 
         // Verify expected BM1370 chip was found
         if let Some(first_chip) = self.chip_infos.first() {
-            if first_chip.chip_id != Self::EXPECTED_CHIP_ID {
+            let expected = Self::CHIP_TYPE.id_bytes();
+            if first_chip.chip_id != expected {
                 return Err(BoardError::InitializationFailed(format!(
-                    "Wrong chip type for Bitaxe Gamma: expected BM1370 ({:02x}{:02x}), found {:02x}{:02x}",
-                    Self::EXPECTED_CHIP_ID[0], Self::EXPECTED_CHIP_ID[1],
+                    "Wrong chip type for Bitaxe Gamma: expected {:?} ({:02x}{:02x}), found {:02x}{:02x}",
+                    Self::CHIP_TYPE, expected[0], expected[1],
                     first_chip.chip_id[0], first_chip.chip_id[1]
                 )));
             }
@@ -692,6 +980,16 @@ impl BitaxeBoard {
             .clone()
             .expect("Regulator must be initialized before spawning stats monitor");
 
+        // Receive-path health, and the means to retune the data port if the
+        // chips stop making sense at the current rate
+        let rx_stats = self.rx_stats.clone();
+        let data_control = self.data_control.clone();
+        let control_channel = self.control_channel.clone();
+        let mut baud_monitor = BaudMonitor::new(
+            &[Self::DATA_PORT.baud_rate, Self::TARGET_BAUD_RATE],
+            data_control.current_baud_rate(),
+        );
+
         // Capture board info for logging
         let board_info = self.board_info();
         let board_model = board_info.model.clone();
@@ -703,6 +1001,7 @@ impl BitaxeBoard {
             interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
 
             // Create fan controller for the stats task
+            let i2c_stats = i2c.stats().clone();
             let mut fan = Emc2101::new(i2c);
 
             // Discard first tick (fires immediately, ADC readings may not be settled)
@@ -711,6 +1010,21 @@ impl BitaxeBoard {
             loop {
                 interval.tick().await;
 
+                let rx = rx_stats.snapshot(data_control.line_errors().ok());
+                let i2c_latency = i2c_stats.snapshot();
+                let control_latency = control_channel.as_ref().map(ControlChannel::latency);
+                if let Some(baud) = baud_monitor.check(rx, data_control.current_baud_rate()) {
+                    warn!(
+                        board = %board_model,
+                        serial = ?board_serial,
+                        baud,
+                        "Persistent corruption on ASIC data line, retuning port."
+                    );
+                    if let Err(e) = data_control.set_baud_rate(baud) {
+                        error!(error = %e, "Failed to retune ASIC data port");
+                    }
+                }
+
                 // Read temperature
                 let temp = match fan.get_external_temperature().await {
                     Ok(t) => format!("{:.1} degC", t),
@@ -726,7 +1040,7 @@ impl BitaxeBoard {
                 // Read fan RPM (if TACH is connected)
                 let fan_rpm = match fan.get_tach_count().await {
                     Ok(count) => {
-                        trace!("TACH count: 0x{:04x}", count);
+                        trace!(tach = %format!("0x{:04x}", count), "TACH count");
                         match fan.get_rpm().await {
                             Ok(rpm) if rpm > 0 => format!("{} RPM", rpm),
                             Ok(_) => format!("0 RPM (TACH: 0x{:04x})", count),
@@ -734,7 +1048,7 @@ impl BitaxeBoard {
                         }
                     }
                     Err(e) => {
-                        trace!("Failed to read TACH: {}", e);
+                        trace!(error = %e, "Failed to read TACH");
                         "N/A".to_string()
                     }
                 };
@@ -749,7 +1063,7 @@ impl BitaxeBoard {
                     Ok(mv) => {
                         let volts = mv as f32 / 1000.0;
                         if volts < 1.0 {
-                            warn!("Core voltage low: {:.3}V", volts);
+                            warn!(board = %board_model, serial = ?board_serial, vout = volts, "Core voltage low");
                         }
                         format!("{:.3}V", volts)
                     }
@@ -773,12 +1087,12 @@ impl BitaxeBoard {
 
                 // Check power status - critical faults will return error
                 if let Err(e) = regulator.lock().await.check_status().await {
-                    error!("CRITICAL: Power controller fault detected: {}", e);
+                    error!(board = %board_model, serial = ?board_serial, error = %e, "CRITICAL: Power controller fault detected");
 
                     // Try to clear the fault once
                     warn!("Attempting to clear power controller faults...");
                     if let Err(clear_err) = regulator.lock().await.clear_faults().await {
-                        error!("Failed to clear faults: {}", clear_err);
+                        error!(error = %clear_err, "Failed to clear faults");
                     }
 
                     // Continue monitoring
@@ -796,6 +1110,17 @@ impl BitaxeBoard {
                     current = %iout,
                     vin = %vin,
                     vout = %vout,
+                    rx_frames = rx.frames,
+                    rx_crc_errors = rx.crc_errors,
+                    rx_skipped_bytes = rx.skipped_bytes,
+                    rx_framing_errors = rx.line.framing,
+                    rx_overruns = rx.line.overrun + rx.line.buffer_overrun,
+                    i2c_p50_ms = i2c_latency.quantile_ms(0.5),
+                    i2c_p99_ms = i2c_latency.quantile_ms(0.99),
+                    i2c_timeouts = i2c_latency.timeouts,
+                    i2c_errors = i2c_latency.errors,
+                    control_p99_ms = control_latency.as_ref().and_then(|l| l.quantile_ms(0.99)),
+                    control_timeouts = control_latency.as_ref().map(|l| l.timeouts),
                     "Board status."
                 );
             }
@@ -803,51 +1128,306 @@ impl BitaxeBoard {
 
         self.stats_task_handle = Some(handle);
     }
+
+    /// Spawn a task showing the miner's state on the board's screen, and
+    /// turning pages or restarting the miner on presses of its button.
+    fn spawn_panel(&mut self) {
+        let (Some(link), Some(thread_status)) = (self.panel.clone(), self.thread_status.clone())
+        else {
+            return;
+        };
+        let i2c = self.i2c.clone();
+        let regulator = self.regulator.clone();
+        // Only the management firmware reports button presses
+        let mut notifications = self
+            .control_channel
+            .as_ref()
+            .map(ControlChannel::notifications);
+
+        let handle = tokio::spawn(async move {
+            let mut display = Ssd1306::new(i2c.clone());
+            let mut display = match display.init().await {
+                Ok(()) => Some(display),
+                Err(e) => {
+                    debug!(error = %e, "No display answered, button only");
+                    None
+                }
+            };
+            let mut fan = Emc2101::new(i2c);
+            let mut page = Page::default();
+            let mut refresh = tokio::time::interval(panel::REFRESH_INTERVAL);
+
+            loop {
+                tokio::select! {
+                    _ = refresh.tick() => {}
+                    held = next_press(&mut notifications) => match ButtonAction::for_press(held) {
+                        ButtonAction::NextPage => {
+                            page = page.next();
+                            refresh.reset();
+                        }
+                        ButtonAction::Restart => {
+                            warn!("Restart requested from the board's button.");
+                            if let Some(display) = &mut display {
+                                let _ = display.show(&["Restarting".to_string()]).await;
+                            }
+                            link.restart();
+                            return;
+                        }
+                    },
+                }
+
+                let Some(display) = &mut display else {
+                    continue;
+                };
+                let status = thread_status.get();
+                let power_watts = match &regulator {
+                    Some(regulator) => regulator.lock().await.get_power().await.ok(),
+                    None => None,
+                };
+                let stats = PanelStats {
+                    hashrate: status.hashrate,
+                    temperature_c: fan.get_external_temperature().await.ok(),
+                    power_watts: power_watts.map(|mw| mw as f32 / 1000.0),
+                    best_difficulty: status.best_difficulty,
+                    pool: link.active_pool().await,
+                };
+                if let Err(e) = display.show(&panel::render(page, &stats)).await {
+                    debug!(error = %e, "Failed to update display");
+                }
+            }
+        });
+
+        self.panel_task = Some(handle);
+    }
 }
 
 #[async_trait]
 impl Board for BitaxeBoard {
     fn board_info(&self) -> BoardInfo {
         BoardInfo {
-            model: "Bitaxe Gamma".to_string(),
-            firmware_version: Some("bitaxe-raw".to_string()),
+            model: self
+                .identity
+                .as_ref()
+                .map_or(DEFAULT_MODEL, |identity| &identity.model)
+                .to_string(),
+            firmware_version: self
+                .firmware
+                .map(|firmware| format!("bitaxe-raw {}", firmware.version)),
             serial_number: self.serial_number.clone(),
         }
     }
 
-    async fn shutdown(&mut self) -> Result<(), BoardError> {
-        // Signal hash threads to shut down gracefully
-        if let Some(ref tx) = self.thread_shutdown {
-            if let Err(e) = tx.send(ThreadRemovalSignal::Shutdown) {
-                warn!("Failed to send shutdown signal to threads: {}", e);
-            } else {
-                debug!("Sent shutdown signal to hash threads");
-                tokio::time::sleep(Duration::from_millis(200)).await;
-            }
+    fn voltage_range(&self) -> Option<VoltageRange> {
+        Some(CORE_VOLTAGE_RANGE)
+    }
+
+    fn input_supply(&self) -> Option<InputSupply> {
+        Some(INPUT_SUPPLY)
+    }
+
+    fn take_fault_receiver(&mut self) -> Option<mpsc::Receiver<String>> {
+        self.fault_rx.take()
+    }
+
+    async fn reset_chips(&mut self) -> Result<usize, BoardError> {
+        let chip_reset = self
+            .chip_reset
+            .as_ref()
+            .ok_or_else(|| BoardError::HardwareControl("hash thread not created yet".into()))?;
+        chip_reset
+            .reset()
+            .await
+            .map_err(|e| BoardError::HardwareControl(format!("chip reset failed: {}", e)))
+    }
+
+    async fn identity(&mut self) -> Result<Option<BoardIdentity>, BoardError> {
+        let mut eeprom = Eeprom::new(self.i2c.clone(), IDENTITY_EEPROM);
+        identity::read(&mut eeprom)
+            .await
+            .map_err(|e| BoardError::HardwareControl(format!("identity read failed: {}", e)))
+    }
+
+    async fn write_identity(&mut self, identity: BoardIdentity) -> Result<(), BoardError> {
+        let mut eeprom = Eeprom::new(self.i2c.clone(), IDENTITY_EEPROM);
+        identity::write(&mut eeprom, &identity)
+            .await
+            .map_err(|e| BoardError::HardwareControl(format!("identity write failed: {}", e)))?;
+        info!(model = %identity.model, revision = ?identity.revision, "Board identity stored.");
+        self.identity = Some(identity);
+        Ok(())
+    }
+
+    async fn set_fan_mode(&mut self, mode: FanMode) -> Result<(), BoardError> {
+        let Some(ref mut fan) = self.fan_controller else {
+            return Err(BoardError::HardwareControl(
+                "fan controller not available".into(),
+            ));
+        };
+        // The new mode wins over an identify still pulsing the fan
+        if let Some(task) = self.identify_task.take() {
+            task.abort();
         }
+        // Auto is full speed until closed-loop control is implemented
+        let speed = match mode {
+            FanMode::Auto => Percent::FULL,
+            FanMode::Fixed { percent } => Percent::new_clamped(percent),
+        };
+        fan.set_fan_speed(speed)
+            .await
+            .map_err(|e| BoardError::HardwareControl(format!("failed to set fan speed: {}", e)))?;
+        debug!(%mode, "Fan mode set");
+        Ok(())
+    }
 
-        // Hold chips in reset
-        self.hold_in_reset().await?;
+    /// Pulse the fan between full and low speed, then put it back. Runs on
+    /// its own task, so the board keeps answering while it does.
+    async fn identify(&mut self) -> Result<(), BoardError> {
+        let Some(ref mut fan) = self.fan_controller else {
+            return Err(BoardError::HardwareControl(
+                "fan controller not available".into(),
+            ));
+        };
+        let previous = fan
+            .get_fan_speed()
+            .await
+            .map_err(|e| BoardError::HardwareControl(format!("failed to read fan speed: {}", e)))?;
+        if let Some(task) = self.identify_task.take() {
+            task.abort();
+        }
 
-        // Turn off core voltage
-        if let Some(ref regulator) = self.regulator {
-            match regulator.lock().await.set_vout(0.0).await {
-                Ok(()) => debug!("Core voltage turned off"),
-                Err(e) => warn!("Failed to turn off core voltage: {}", e),
+        let mut fan = Emc2101::new(self.i2c.clone());
+        self.identify_task = Some(tokio::spawn(async move {
+            for _ in 0..IDENTIFY_PULSES {
+                for speed in [Percent::FULL, IDENTIFY_LOW] {
+                    if let Err(e) = fan.set_fan_speed(speed).await {
+                        warn!(error = %e, "Failed to pulse fan");
+                        break;
+                    }
+                    tokio::time::sleep(IDENTIFY_STEP).await;
+                }
             }
+            if let Err(e) = fan.set_fan_speed(previous).await {
+                warn!(error = %e, "Failed to restore fan speed after identify");
+            }
+        }));
+        info!(serial = ?self.serial_number, "Identifying board by pulsing its fan.");
+        Ok(())
+    }
+
+    /// Scan the management controller's I2C bus. Transactions interleave
+    /// with the regulator's and fan controller's, which the controller
+    /// serializes.
+    async fn scan_i2c(&mut self) -> Result<Vec<ScannedDevice>, BoardError> {
+        let devices = scan::scan(&mut self.i2c.clone()).await;
+        info!(serial = ?self.serial_number, devices = devices.len(), "I2C bus scanned.");
+        Ok(devices)
+    }
+
+    async fn reconnect(
+        &mut self,
+        device: &crate::transport::UsbDeviceInfo,
+    ) -> Result<(), BoardError> {
+        let Some(control_channel) = self.control_channel.clone() else {
+            return Err(BoardError::HardwareControl(
+                "board has no USB controller".into(),
+            ));
+        };
+        let hardware = |e: crate::error::Error| BoardError::HardwareControl(e.to_string());
+        let control_path = device
+            .serial_port(PortRole::Control, &PORT_LAYOUT)
+            .map_err(hardware)?
+            .path
+            .clone();
+        let data_path = &device
+            .serial_port(PortRole::Data, &PORT_LAYOUT)
+            .map_err(hardware)?
+            .path;
+
+        control_channel
+            .reconnect(open_control_port(&control_path).map_err(hardware)?)
+            .await;
+        self.data_control.reopen(data_path).map_err(|e| {
+            BoardError::HardwareControl(format!("failed to reopen data port: {}", e))
+        })?;
+        self.control_path = Some(control_path);
+
+        // The controller may have reset, and the chips with it
+        self.negotiate_firmware().await.map_err(hardware)?;
+        let chips = match self.chip_reset {
+            Some(_) => Some(self.reset_chips().await?),
+            None => None,
+        };
+        info!(
+            serial = ?self.serial_number,
+            data = %data_path,
+            ?chips,
+            "Board reconnected."
+        );
+        Ok(())
+    }
+
+    async fn nonce_map(&mut self) -> Result<NonceMap, BoardError> {
+        self.thread_status
+            .as_ref()
+            .map(ThreadStatusHandle::nonce_map)
+            .ok_or_else(|| BoardError::HardwareControl("hash thread not created yet".into()))
+    }
+
+    async fn shutdown(&mut self) -> Result<(), BoardError> {
+        for stage in ShutdownStage::ALL {
+            self.shutdown_stage(stage).await?;
         }
+        Ok(())
+    }
 
-        // Reduce fan speed (no more heat generation)
-        if let Some(ref mut fan) = self.fan_controller {
-            let shutdown_speed = Percent::new_clamped(25);
-            if let Err(e) = fan.set_fan_speed(shutdown_speed).await {
-                warn!("Failed to set fan speed: {}", e);
+    async fn shutdown_stage(&mut self, stage: ShutdownStage) -> Result<(), BoardError> {
+        match stage {
+            ShutdownStage::Park => {
+                // A port lost with the board isn't coming back now
+                self.data_control.release();
+
+                // Signal hash threads to shut down gracefully
+                if let Some(ref tx) = self.thread_shutdown {
+                    if let Err(e) = tx.send(ThreadRemovalSignal::Shutdown) {
+                        warn!(error = %e, "Failed to send shutdown signal to threads");
+                    } else {
+                        debug!("Sent shutdown signal to hash threads");
+                        tokio::time::sleep(Duration::from_millis(200)).await;
+                    }
+                }
+
+                // Hold chips in reset
+                self.hold_in_reset().await?;
             }
-        }
+            ShutdownStage::PowerOff => {
+                if let Some(ref regulator) = self.regulator {
+                    match regulator.lock().await.set_vout(0.0).await {
+                        Ok(()) => debug!("Core voltage turned off"),
+                        Err(e) => warn!(error = %e, "Failed to turn off core voltage"),
+                    }
+                }
+            }
+            ShutdownStage::CoolDown => {
+                if let Some(task) = self.identify_task.take() {
+                    task.abort();
+                }
 
-        // Cancel the statistics monitoring task
-        if let Some(handle) = self.stats_task_handle.take() {
-            handle.abort();
+                // Reduce fan speed (no more heat generation)
+                if let Some(ref mut fan) = self.fan_controller {
+                    let shutdown_speed = Percent::new_clamped(25);
+                    if let Err(e) = fan.set_fan_speed(shutdown_speed).await {
+                        warn!(error = %e, "Failed to set fan speed");
+                    }
+                }
+
+                // Cancel the statistics monitoring and panel tasks
+                if let Some(handle) = self.stats_task_handle.take() {
+                    handle.abort();
+                }
+                if let Some(handle) = self.panel_task.take() {
+                    handle.abort();
+                }
+            }
         }
 
         Ok(())
@@ -860,6 +1440,10 @@ impl Board for BitaxeBoard {
         // Store removal signal sender for later shutdown
         self.thread_shutdown = Some(removal_tx);
 
+        // The thread asks for a board reinit through this
+        let (fault_tx, fault_rx) = mpsc::channel(1);
+        self.fault_rx = Some(fault_rx);
+
         // Take ownership of serial I/O streams
         let data_reader = self
             .data_reader
@@ -888,6 +1472,17 @@ impl Board for BitaxeBoard {
         let peripherals = BoardPeripherals {
             asic_enable: Some(Box::new(asic_enable)),
             voltage_regulator: None, // Not used by hash thread yet
+            // Without it, raising the rate would leave the chips behind. A
+            // host UART is retuned directly.
+            baud_rate: self
+                .firmware
+                .is_none_or(|firmware| firmware.supports(Features::BAUD_CHANGE))
+                .then(|| {
+                    Box::new(BitaxeBaudRate {
+                        data_control: self.data_control.clone(),
+                    }) as Box<dyn BaudRateControl>
+                }),
+            board_fault: Some(fault_tx),
         };
 
         // Build thread name from board model and serial
@@ -899,48 +1494,168 @@ impl Board for BitaxeBoard {
         // Create BM13xxThread with streams and peripherals
         let thread = BM13xxThread::new(
             thread_name,
+            Self::CHIP_TYPE,
             data_reader,
             data_writer,
             peripherals,
             removal_rx,
         );
 
+        self.chip_reset = Some(thread.chip_reset_handle());
+        self.chip_clock = Some(thread.chip_clock_handle());
+        self.thread_status = Some(thread.status_handle());
         debug!("Created BM13xx hash thread from BitaxeBoard");
+        self.spawn_panel();
 
         Ok(vec![Box::new(thread)])
     }
+
+    fn self_test(&self) -> bool {
+        true
+    }
+
+    fn firmware_port(&self) -> Option<String> {
+        self.control_path.clone()
+    }
+
+    fn attach_panel(&mut self, panel: PanelLink) {
+        self.panel = Some(panel);
+    }
+
+    async fn set_operating_point(&mut self, point: OperatingPoint) -> Result<(), BoardError> {
+        // PLL changes go over the data channel, which belongs to the hash
+        // thread; check it can take one before touching the voltage
+        let chip_clock = match point.frequency_mhz {
+            Some(mhz) if mhz.is_nan() || mhz <= 0.0 => {
+                return Err(BoardError::HardwareControl(format!(
+                    "invalid frequency {} MHz",
+                    mhz
+                )));
+            }
+            Some(_) => Some(self.chip_clock.clone().ok_or_else(|| {
+                BoardError::HardwareControl("hash thread not created yet".into())
+            })?),
+            None => None,
+        };
+
+        if let Some(volts) = point.voltage {
+            // Zero would switch the regulator off rather than retune it
+            if volts.is_nan() || volts <= 0.0 {
+                return Err(BoardError::HardwareControl(format!(
+                    "invalid core voltage {}",
+                    volts
+                )));
+            }
+            let regulator = self.regulator.as_ref().ok_or_else(|| {
+                BoardError::HardwareControl("Regulator not initialized".to_string())
+            })?;
+            regulator.lock().await.set_vout(volts).await.map_err(|e| {
+                BoardError::HardwareControl(format!("Failed to set core voltage: {}", e))
+            })?;
+            info!(vout = volts, "Core voltage changed.");
+        }
+
+        if let (Some(chip_clock), Some(mhz)) = (chip_clock, point.frequency_mhz) {
+            chip_clock.set_frequency(mhz).await.map_err(|e| {
+                BoardError::HardwareControl(format!("Failed to set frequency: {}", e))
+            })?;
+        }
+
+        Ok(())
+    }
+
+    async fn store_regulator_config(&mut self) -> Result<(), BoardError> {
+        let regulator = self
+            .regulator
+            .as_ref()
+            .ok_or_else(|| BoardError::HardwareControl("Regulator not initialized".to_string()))?;
+        regulator.lock().await.store_config().await.map_err(|e| {
+            BoardError::HardwareControl(format!("Failed to store regulator settings: {}", e))
+        })?;
+        info!(serial = ?self.serial_number, "Regulator settings stored to NVM.");
+        Ok(())
+    }
+
+    async fn power_watts(&mut self) -> Option<f32> {
+        let regulator = self.regulator.as_ref()?;
+        let mw = regulator.lock().await.get_power().await.ok()?;
+        Some(mw as f32 / 1000.0)
+    }
+
+    async fn telemetry(&mut self) -> TelemetrySnapshot {
+        let mut snapshot = TelemetrySnapshot::new();
+
+        if let Some(ref mut fan) = self.fan_controller {
+            snapshot.temperature_c = fan.get_external_temperature().await.ok();
+            snapshot.fan_percent = fan.get_fan_speed().await.ok().map(u8::from);
+            snapshot.fan_rpm = fan.get_rpm().await.ok();
+        }
+
+        if let Some(ref regulator) = self.regulator {
+            let mut regulator = regulator.lock().await;
+            snapshot.input_voltage = regulator.get_vin().await.ok().map(|mv| mv as f32 / 1000.0);
+            snapshot.core_voltage = regulator.get_vout().await.ok().map(|mv| mv as f32 / 1000.0);
+            snapshot.core_current = regulator.get_iout().await.ok().map(|ma| ma as f32 / 1000.0);
+            snapshot.power_watts = regulator
+                .get_power()
+                .await
+                .ok()
+                .map(|mw| mw as f32 / 1000.0);
+        }
+
+        if let Some(ref thread_status) = self.thread_status {
+            snapshot.chip_difficulty = thread_status.get().chip_difficulty;
+        }
+
+        snapshot.io_latency = Some(IoLatency {
+            i2c: Some(self.i2c.stats().snapshot()),
+            control: self.control_channel.as_ref().map(ControlChannel::latency),
+        });
+
+        snapshot
+    }
+}
+
+/// How long the next press of the board's button was held. Never returns
+/// for a board whose presses aren't reported.
+async fn next_press(notifications: &mut Option<broadcast::Receiver<Notification>>) -> Duration {
+    let Some(notifications) = notifications else {
+        return std::future::pending().await;
+    };
+    loop {
+        match notifications.recv().await {
+            Ok(Notification::ButtonPressed { held_ms, .. }) => {
+                return Duration::from_millis(held_ms.into());
+            }
+            Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+            Err(broadcast::error::RecvError::Closed) => return std::future::pending().await,
+        }
+    }
 }
 
 // Factory function to create a Bitaxe board from USB device info
 async fn create_from_usb(
     device: crate::transport::UsbDeviceInfo,
 ) -> crate::error::Result<Box<dyn Board + Send>> {
-    use tokio_serial::SerialPortBuilderExt;
-
-    // Get serial ports
-    let serial_ports = device.serial_ports()?;
-
-    // Bitaxe Gamma requires exactly 2 serial ports
-    if serial_ports.len() != 2 {
-        return Err(crate::error::Error::Hardware(format!(
-            "Bitaxe Gamma requires exactly 2 serial ports, found {}",
-            serial_ports.len()
-        )));
-    }
+    let control_path = &device.serial_port(PortRole::Control, &PORT_LAYOUT)?.path;
+    let data_path = &device.serial_port(PortRole::Data, &PORT_LAYOUT)?.path;
 
     debug!(
         serial = ?device.serial_number,
-        control = %serial_ports[0],
-        data = %serial_ports[1],
+        control = %control_path,
+        data = %data_path,
         "Opening Bitaxe Gamma serial ports"
     );
 
-    // Open control port at 115200 baud
-    let control_port = tokio_serial::new(&serial_ports[0], 115200).open_native_async()?;
+    // Open both ports as the board's profile says. Each is locked, so a
+    // firmware flasher or another instance can't drive the board alongside
+    // us.
+    let control_port = open_control_port(control_path)?;
+    let data_port = open_data_port(data_path, BitaxeBoard::DATA_PORT)?;
 
-    // Create the board with the control port and data port path
-    let mut board = BitaxeBoard::new(control_port, &serial_ports[1], device.serial_number.clone())
-        .map_err(|e| crate::error::Error::Hardware(format!("Failed to create board: {}", e)))?;
+    let mut board = BitaxeBoard::new(control_port, data_port, device.serial_number.clone())
+        .with_control_path(control_path);
+    board.negotiate_firmware().await?;
 
     // Initialize the board (reset, discover chips, start event monitoring)
     board
@@ -956,6 +1671,99 @@ async fn create_from_usb(
     Ok(Box::new(board))
 }
 
+// Factory function to create a Bitaxe board wired to the host's header
+#[cfg(all(target_os = "linux", feature = "direct-attach"))]
+async fn create_direct(device: VirtualDeviceInfo) -> crate::error::Result<Box<dyn Board + Send>> {
+    let VirtualDeviceInfo::Direct(device) = device else {
+        return Err(crate::error::Error::Config(
+            "Direct-attach board created from other device info".into(),
+        ));
+    };
+
+    debug!(
+        id = %device.device_id,
+        uart = %device.uart,
+        i2c = %device.i2c.display(),
+        gpio_chip = %device.gpio_chip.display(),
+        reset_line = device.reset_line,
+        "Opening directly attached Bitaxe Gamma"
+    );
+
+    let i2c = LinuxI2c::open(&device.i2c).map_err(|e| {
+        crate::error::Error::Hardware(format!(
+            "Failed to open I2C adapter {}: {}",
+            device.i2c.display(),
+            e
+        ))
+    })?;
+    let reset = LinuxGpio::new(&device.gpio_chip)
+        .pin(device.reset_line)
+        .await
+        .map_err(|e| {
+            crate::error::Error::Hardware(format!(
+                "Failed to request reset line {} of {}: {}",
+                device.reset_line,
+                device.gpio_chip.display(),
+                e
+            ))
+        })?;
+    let data_port = open_data_port(&device.uart, BitaxeBoard::HOST_UART)?;
+
+    let mut board = BitaxeBoard::direct(i2c, reset, data_port, device.device_id);
+    board
+        .initialize()
+        .await
+        .map_err(|e| crate::error::Error::Hardware(format!("Failed to initialize board: {}", e)))?;
+
+    debug!(
+        "Bitaxe board initialized successfully with {} chips",
+        board.chip_count()
+    );
+
+    Ok(Box::new(board))
+}
+
+/// Open the port wired to the ASICs, locked like the control port.
+fn open_data_port(path: &str, config: SerialConfig) -> crate::error::Result<SerialStream> {
+    SerialStream::with_config(path, config).map_err(|e| match e {
+        SerialError::Busy(busy) => busy.into(),
+        e => crate::error::Error::Hardware(format!("Failed to open data port: {}", e)),
+    })
+}
+
+/// Read the identity stored on a bitaxe-raw board, before it's created.
+async fn identify_from_usb(
+    device: crate::transport::UsbDeviceInfo,
+) -> crate::error::Result<Option<BoardIdentity>> {
+    let control_path = &device.serial_port(PortRole::Control, &PORT_LAYOUT)?.path;
+    let control_channel = ControlChannel::new(open_control_port(control_path)?);
+    let mut i2c = BitaxeRawI2c::new(control_channel);
+    i2c.set_frequency(100_000).await.map_err(|e| {
+        crate::error::Error::Hardware(format!("Failed to set I2C frequency: {}", e))
+    })?;
+    identity::read(&mut Eeprom::new(i2c, IDENTITY_EEPROM))
+        .await
+        .map_err(|e| crate::error::Error::Hardware(format!("Failed to read identity: {}", e)))
+}
+
+/// Open a bitaxe-raw control port. tokio-serial locks the port itself and
+/// reports a lock another process holds as NoDevice.
+fn open_control_port(path: &str) -> crate::error::Result<tokio_serial::SerialStream> {
+    use tokio_serial::SerialPortBuilderExt;
+
+    let config = BitaxeBoard::CONTROL_PORT;
+    tokio_serial::new(path, config.baud_rate)
+        .flow_control(match config.flow_control {
+            FlowControl::None => tokio_serial::FlowControl::None,
+            FlowControl::Hardware => tokio_serial::FlowControl::Hardware,
+        })
+        .open_native_async()
+        .map_err(|e| match e.kind {
+            tokio_serial::ErrorKind::NoDevice => PortBusy::new(path).into(),
+            _ => crate::error::Error::from(e),
+        })
+}
+
 // Register this board type with the inventory system
 inventory::submit! {
     crate::board::BoardDescriptor {
@@ -965,9 +1773,20 @@ inventory::submit! {
             manufacturer: Match::Specific(StringMatch::Exact("OSMU")),
             product: Match::Specific(StringMatch::Exact("Bitaxe")),
             serial_pattern: Match::Any,
+            model: Match::Any,
         },
         name: "Bitaxe Gamma",
         create_fn: |device| Box::pin(create_from_usb(device)),
+        identify_fn: Some(|device| Box::pin(identify_from_usb(device))),
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "direct-attach"))]
+inventory::submit! {
+    VirtualBoardDescriptor {
+        device_type: "direct_bitaxe",
+        name: "Bitaxe Gamma (direct)",
+        create_fn: |device| Box::pin(create_direct(device.clone())),
     }
 }
 
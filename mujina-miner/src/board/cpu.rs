@@ -1,23 +1,24 @@
 //! CPU mining board implementation.
 //!
 //! Provides a virtual board that uses CPU cores for SHA-256 hashing.
-//! Configured via environment variables, creates one HashThread per core.
+//! Configured from the CPU transport's device info, creates one HashThread
+//! per core, each searching its own slice of the nonce space.
 
 use async_trait::async_trait;
 
-use super::{Board, BoardError, BoardInfo, VirtualBoardDescriptor};
+use super::{Board, BoardError, BoardInfo, VirtualBoardDescriptor, VirtualDeviceInfo};
 use crate::{
     asic::hash_thread::HashThread,
-    cpu_miner::{CpuHashThread, CpuMinerConfig},
+    cpu_miner::{CpuHashThread, CpuMinerConfig, NonceRange},
 };
 
 /// CPU mining board.
 ///
 /// A virtual board that spawns CPU-based mining threads. Unlike hardware
 /// boards, this doesn't require any physical devices---it's configured
-/// entirely via environment variables.
+/// entirely by the daemon (command line or environment variables).
 pub struct CpuBoard {
-    /// Configuration parsed from environment.
+    /// Thread count and duty cycle.
     config: CpuMinerConfig,
 
     /// Threads created by this board (kept for shutdown).
@@ -25,7 +26,7 @@ pub struct CpuBoard {
 }
 
 impl CpuBoard {
-    /// Create a new CPU mining board from environment configuration.
+    /// Create a new CPU mining board.
     pub fn new(config: CpuMinerConfig) -> Self {
         Self {
             config,
@@ -59,8 +60,13 @@ impl Board for CpuBoard {
     async fn create_hash_threads(&mut self) -> Result<Vec<Box<dyn HashThread>>, BoardError> {
         let mut threads: Vec<Box<dyn HashThread>> = Vec::new();
 
-        for i in 0..self.config.thread_count {
-            let thread = CpuHashThread::new(format!("CPU Core {}", i), self.config.duty_percent);
+        let count = self.config.thread_count;
+        for i in 0..count {
+            let thread = CpuHashThread::new(
+                format!("CPU Core {}", i),
+                self.config.duty_percent,
+                NonceRange::partition(i, count),
+            );
             threads.push(Box::new(thread));
         }
 
@@ -73,18 +79,30 @@ impl Board for CpuBoard {
 // ---------------------------------------------------------------------------
 
 /// Factory function for creating CpuBoard instances.
-async fn create_cpu_board() -> crate::error::Result<Box<dyn Board + Send>> {
-    let config = CpuMinerConfig::from_env().ok_or_else(|| {
-        crate::error::Error::Config("CPU miner not configured (MUJINA_CPU_MINER not set)".into())
-    })?;
+async fn create_cpu_board(
+    device: VirtualDeviceInfo,
+) -> crate::error::Result<Box<dyn Board + Send>> {
+    let VirtualDeviceInfo::Cpu(device) = device else {
+        return Err(crate::error::Error::Config(
+            "CPU miner created from non-CPU device info".into(),
+        ));
+    };
+    if device.thread_count == 0 {
+        return Err(crate::error::Error::Config(
+            "CPU miner needs at least one thread".into(),
+        ));
+    }
 
-    Ok(Box::new(CpuBoard::new(config)))
+    Ok(Box::new(CpuBoard::new(CpuMinerConfig {
+        thread_count: device.thread_count,
+        duty_percent: device.duty_percent.clamp(1, 100),
+    })))
 }
 
 inventory::submit! {
     VirtualBoardDescriptor {
         device_type: "cpu_miner",
         name: "CPU Miner",
-        create_fn: || Box::pin(create_cpu_board()),
+        create_fn: |device| Box::pin(create_cpu_board(device.clone())),
     }
 }
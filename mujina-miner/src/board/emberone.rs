@@ -64,9 +64,11 @@ inventory::submit! {
             manufacturer: Match::Specific(StringMatch::Exact("256F")),
             product: Match::Specific(StringMatch::Exact("EmberOne00")),
             serial_pattern: Match::Any,
+            model: Match::Any,
         },
         name: "EmberOne",
         create_fn: |device| Box::pin(create_from_usb(device)),
+        identify_fn: None,
     }
 }
 
@@ -0,0 +1,216 @@
+//! History of each board's comings and goings.
+//!
+//! Intermittent USB and power problems show up as a board dropping off the
+//! bus, failing to initialize, or being recreated after a fault, often
+//! hours before anyone looks. Each such event is recorded here with its
+//! time and, for failures, the reason, keyed by board ID (the serial
+//! number for USB boards), so the pattern can be read back afterwards
+//! through `GET /api/v1/boards/{id}/history`.
+//!
+//! The history is one JSON file, `board_history.json`, in the daemon's
+//! state directory (see [`crate::settings`]), and keeps the latest
+//! [`MAX_EVENTS`] events of each board.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::tracing::prelude::*;
+
+/// Events kept per board; older ones are dropped.
+pub const MAX_EVENTS: usize = 200;
+
+/// Name of the history in the state directory.
+const FILE_NAME: &str = "board_history.json";
+
+/// Something that happened to a board.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum BoardEvent {
+    /// Found on its transport and handed to a board task
+    Attached { model: String },
+    /// First incarnation up and mining
+    Initialized,
+    /// Failed to come up, crashed, or reported a fault
+    Failed { reason: String },
+    /// Recreated after a failure and mining again
+    Reinitialized { generation: u64 },
+    /// Back on its transport after dropping off it, without being recreated
+    Reconnected,
+    /// Shut down and removed from the backplane
+    Detached { reason: String },
+}
+
+/// A board event and when it happened.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Unix seconds
+    pub at: u64,
+    #[serde(flatten)]
+    pub event: BoardEvent,
+}
+
+/// An event a board task reports to the backplane.
+#[derive(Debug)]
+pub struct BoardLifecycle {
+    /// Board ID
+    pub id: String,
+    pub entry: HistoryEntry,
+}
+
+/// Every board's history, optionally kept on disk.
+#[derive(Debug, Default)]
+pub struct BoardHistory {
+    /// File the history is kept in, if any
+    path: Option<PathBuf>,
+    boards: BTreeMap<String, VecDeque<HistoryEntry>>,
+}
+
+impl HistoryEntry {
+    /// `event`, happening now.
+    pub fn now(event: BoardEvent) -> Self {
+        let at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        Self { at, event }
+    }
+}
+
+impl BoardHistory {
+    /// History kept in memory only, lost on exit.
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    /// History kept in `dir`, loading what an earlier run saved there.
+    ///
+    /// A file that can't be parsed is discarded and the history starts
+    /// empty.
+    pub fn load(dir: &Path) -> Self {
+        let path = dir.join(FILE_NAME);
+        let boards = match std::fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+                warn!(path = %path.display(), error = %e, "Discarding unreadable board history");
+                BTreeMap::new()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => {
+                warn!(path = %path.display(), error = %e, "Failed to read board history");
+                BTreeMap::new()
+            }
+        };
+        Self {
+            path: Some(path),
+            boards,
+        }
+    }
+
+    /// Events of the board with ID `id`, oldest first; None if it has none.
+    pub fn get(&self, id: &str) -> Option<Vec<HistoryEntry>> {
+        self.boards
+            .get(id)
+            .map(|events| events.iter().cloned().collect())
+    }
+
+    /// Record an event of board `id`, saving the history.
+    ///
+    /// A history that can't be saved is only warned about; it's a
+    /// diagnostic aid, not worth disturbing the boards over.
+    pub fn record(&mut self, id: &str, entry: HistoryEntry) {
+        debug!(serial = %id, event = ?entry.event, "Board event");
+        let events = self.boards.entry(id.to_string()).or_default();
+        if events.len() == MAX_EVENTS {
+            events.pop_front();
+        }
+        events.push_back(entry);
+
+        if let Err(e) = self.save() {
+            warn!(error = %e, "Failed to save board history");
+        }
+    }
+
+    fn save(&self) -> std::io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        // Written aside and renamed into place, so a crash mid-write leaves
+        // the old file intact
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec(&self.boards)?)?;
+        std::fs::rename(&tmp, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_survives_reload() {
+        let dir = std::env::temp_dir().join(format!("mujina-history-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let events = [
+            BoardEvent::Attached {
+                model: "Bitaxe Gamma".into(),
+            },
+            BoardEvent::Initialized,
+            BoardEvent::Failed {
+                reason: "board fault: chips stopped answering".into(),
+            },
+            BoardEvent::Reinitialized { generation: 2 },
+            BoardEvent::Detached {
+                reason: "unplugged".into(),
+            },
+        ];
+        let mut history = BoardHistory::load(&dir);
+        for (at, event) in events.iter().enumerate() {
+            let entry = HistoryEntry {
+                at: at as u64,
+                event: event.clone(),
+            };
+            history.record("BX0001", entry);
+        }
+
+        let reloaded = BoardHistory::load(&dir);
+        let entries = reloaded.get("BX0001").unwrap();
+        assert_eq!(entries.len(), events.len());
+        assert_eq!(entries[2].event, events[2]);
+        assert_eq!(entries[4].at, 4);
+        assert_eq!(reloaded.get("BX0002"), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_history_bounded() {
+        let mut history = BoardHistory::in_memory();
+        for at in 0..MAX_EVENTS as u64 + 5 {
+            let event = BoardEvent::Initialized;
+            history.record("BX0001", HistoryEntry { at, event });
+        }
+        let entries = history.get("BX0001").unwrap();
+        assert_eq!(entries.len(), MAX_EVENTS);
+        assert_eq!(entries[0].at, 5);
+    }
+
+    #[test]
+    fn test_entry_format() {
+        let entry = HistoryEntry {
+            at: 1_760_486_400,
+            event: BoardEvent::Failed {
+                reason: "no chips found".into(),
+            },
+        };
+        assert_eq!(
+            serde_json::to_string(&entry).unwrap(),
+            r#"{"at":1760486400,"event":"failed","reason":"no chips found"}"#
+        );
+    }
+}
@@ -0,0 +1,267 @@
+//! Board identity stored on the board.
+//!
+//! USB descriptors name a board's controller, not the board: every board
+//! running bitaxe-raw enumerates as OSMU's "Bitaxe", whatever chips and
+//! regulator sit behind it. A board can carry its own identity (model,
+//! revision, serial number and calibration values) in an I2C EEPROM next to
+//! its controller, programmed at the factory or through the API. Board
+//! descriptors whose pattern names a model (see
+//! [`BoardPattern::model`](super::pattern::BoardPattern::model)) are picked
+//! by it, ahead of those matching on USB descriptors alone.
+//!
+//! ## Record layout
+//!
+//! | Offset  | Size | Contents                                     |
+//! |---------|------|----------------------------------------------|
+//! | 0       | 4    | Magic, `MJID`                                |
+//! | 4       | 1    | Format version, 1                            |
+//! | 5       | 2    | Payload length, little-endian                |
+//! | 7       | n    | Payload: the identity as JSON                |
+//! | 7 + n   | 2    | CRC-16/CCITT-FALSE of the payload, little-endian |
+//!
+//! JSON keeps the record readable with an EEPROM dump and lets fields be
+//! added without a format change. A part without the magic is blank, not
+//! corrupt.
+
+use std::collections::BTreeMap;
+
+use crc_all::CrcAlgo;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    hw_trait::{i2c::I2c, HwError},
+    peripheral::eeprom::Eeprom,
+};
+
+/// Marks the start of an identity record.
+const MAGIC: [u8; 4] = *b"MJID";
+
+/// Record format written by this build.
+const FORMAT_VERSION: u8 = 1;
+
+/// Magic, version and payload length.
+const HEADER_LEN: usize = 7;
+
+/// Payload CRC.
+const CRC_LEN: usize = 2;
+
+const CRC16_INIT: u16 = 0xFFFF;
+
+const CRC16: CrcAlgo<u16> = CrcAlgo::<u16>::new(
+    0x1021,     // polynomial (CRC-16-CCITT-FALSE)
+    16,         // width
+    CRC16_INIT, // init
+    0,          // xorout
+    false,      // reflect
+);
+
+/// What a board says it is.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BoardIdentity {
+    /// Board model, e.g. "Bitaxe Gamma"
+    pub model: String,
+
+    /// Hardware revision, e.g. "602"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<String>,
+
+    /// Serial number assigned when the board was made
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub serial: Option<String>,
+
+    /// Calibration values measured for this board, by name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub calibration: BTreeMap<String, f64>,
+}
+
+/// Why an identity couldn't be read or written.
+#[derive(Debug, thiserror::Error)]
+pub enum IdentityError {
+    #[error("identity record is corrupt: {0}")]
+    Corrupt(String),
+
+    #[error("identity record format {0} is newer than this build understands")]
+    UnknownFormat(u8),
+
+    #[error("identity record of {size} bytes doesn't fit in {capacity}")]
+    TooLarge { size: usize, capacity: usize },
+
+    #[error("invalid identity: {0}")]
+    Invalid(String),
+
+    #[error("identity record didn't read back as written")]
+    Verify,
+
+    #[error(transparent)]
+    Hardware(#[from] HwError),
+}
+
+impl BoardIdentity {
+    /// Check the identity is fit to store.
+    pub fn validate(&self) -> Result<(), IdentityError> {
+        if self.model.trim().is_empty() {
+            return Err(IdentityError::Invalid("model is empty".into()));
+        }
+        if let Some((name, _)) = self.calibration.iter().find(|(_, v)| !v.is_finite()) {
+            return Err(IdentityError::Invalid(format!(
+                "calibration value {name} is not a number"
+            )));
+        }
+        Ok(())
+    }
+
+    /// The identity as a record.
+    pub fn encode(&self) -> Result<Vec<u8>, IdentityError> {
+        self.validate()?;
+        let payload =
+            serde_json::to_vec(self).map_err(|e| IdentityError::Invalid(e.to_string()))?;
+        let len = u16::try_from(payload.len()).map_err(|_| IdentityError::TooLarge {
+            size: payload.len(),
+            capacity: u16::MAX as usize,
+        })?;
+
+        let mut record = Vec::with_capacity(HEADER_LEN + payload.len() + CRC_LEN);
+        record.extend_from_slice(&MAGIC);
+        record.push(FORMAT_VERSION);
+        record.extend_from_slice(&len.to_le_bytes());
+        record.extend_from_slice(&payload);
+        record.extend_from_slice(&crc16(&payload).to_le_bytes());
+        Ok(record)
+    }
+
+    /// The identity in a record, or None if the record is blank.
+    pub fn decode(record: &[u8]) -> Result<Option<Self>, IdentityError> {
+        let Some(payload_len) = payload_len(record)? else {
+            return Ok(None);
+        };
+        let end = HEADER_LEN + payload_len;
+        if record.len() < end + CRC_LEN {
+            return Err(IdentityError::Corrupt(format!(
+                "{} bytes, header says {}",
+                record.len(),
+                end + CRC_LEN
+            )));
+        }
+
+        let payload = &record[HEADER_LEN..end];
+        let crc = u16::from_le_bytes([record[end], record[end + 1]]);
+        if crc != crc16(payload) {
+            return Err(IdentityError::Corrupt("CRC mismatch".into()));
+        }
+        serde_json::from_slice(payload)
+            .map(Some)
+            .map_err(|e| IdentityError::Corrupt(e.to_string()))
+    }
+}
+
+/// Read the identity from the start of `eeprom`, if one was stored.
+pub async fn read<I: I2c>(eeprom: &mut Eeprom<I>) -> Result<Option<BoardIdentity>, IdentityError> {
+    let mut header = [0; HEADER_LEN];
+    eeprom.read(0, &mut header).await?;
+    let Some(payload_len) = payload_len(&header)? else {
+        return Ok(None);
+    };
+    let size = HEADER_LEN + payload_len + CRC_LEN;
+    if size > eeprom.capacity() {
+        return Err(IdentityError::Corrupt(format!(
+            "header says {} bytes, more than the EEPROM holds",
+            size
+        )));
+    }
+
+    let mut record = vec![0; size];
+    eeprom.read(0, &mut record).await?;
+    BoardIdentity::decode(&record)
+}
+
+/// Store `identity` at the start of `eeprom`, checking it reads back.
+pub async fn write<I: I2c>(
+    eeprom: &mut Eeprom<I>,
+    identity: &BoardIdentity,
+) -> Result<(), IdentityError> {
+    let record = identity.encode()?;
+    if record.len() > eeprom.capacity() {
+        return Err(IdentityError::TooLarge {
+            size: record.len(),
+            capacity: eeprom.capacity(),
+        });
+    }
+    eeprom.write(0, &record).await?;
+
+    let mut back = vec![0; record.len()];
+    eeprom.read(0, &mut back).await?;
+    if back != record {
+        return Err(IdentityError::Verify);
+    }
+    Ok(())
+}
+
+/// Payload length from a record's header, or None if there's no record.
+fn payload_len(record: &[u8]) -> Result<Option<usize>, IdentityError> {
+    if record.len() < HEADER_LEN || record[..4] != MAGIC {
+        return Ok(None);
+    }
+    if record[4] != FORMAT_VERSION {
+        return Err(IdentityError::UnknownFormat(record[4]));
+    }
+    Ok(Some(u16::from_le_bytes([record[5], record[6]]) as usize))
+}
+
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = CRC16_INIT;
+    CRC16.update_crc(&mut crc, data);
+    CRC16.finish_crc(&crc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gamma() -> BoardIdentity {
+        BoardIdentity {
+            model: "Bitaxe Gamma".into(),
+            revision: Some("602".into()),
+            serial: Some("G602-0042".into()),
+            calibration: [("vout_offset_mv".to_string(), -3.5)].into(),
+        }
+    }
+
+    #[test]
+    fn test_record_round_trip() {
+        let record = gamma().encode().unwrap();
+        assert_eq!(&record[..5], b"MJID\x01");
+        assert_eq!(BoardIdentity::decode(&record).unwrap(), Some(gamma()));
+
+        // Trailing bytes, as read from a larger part, are ignored
+        let mut padded = record.clone();
+        padded.resize(256, 0xff);
+        assert_eq!(BoardIdentity::decode(&padded).unwrap(), Some(gamma()));
+    }
+
+    #[test]
+    fn test_blank_part_has_no_identity() {
+        assert_eq!(BoardIdentity::decode(&[0xff; 64]).unwrap(), None);
+        assert_eq!(BoardIdentity::decode(&[0x00; 64]).unwrap(), None);
+    }
+
+    #[test]
+    fn test_damaged_record_is_corrupt() {
+        let mut record = gamma().encode().unwrap();
+        record[HEADER_LEN + 3] ^= 0x20;
+        assert!(matches!(
+            BoardIdentity::decode(&record),
+            Err(IdentityError::Corrupt(_))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_identity_not_encoded() {
+        let mut identity = gamma();
+        identity.calibration.insert("gain".into(), f64::NAN);
+        assert!(matches!(identity.encode(), Err(IdentityError::Invalid(_))));
+
+        identity.calibration.clear();
+        identity.model = " ".into();
+        assert!(matches!(identity.encode(), Err(IdentityError::Invalid(_))));
+    }
+}
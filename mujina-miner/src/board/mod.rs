@@ -1,12 +1,28 @@
 pub(crate) mod bitaxe;
+#[cfg(feature = "cpu-miner")]
 pub mod cpu;
 pub(crate) mod emberone;
+pub mod history;
+pub mod identity;
+pub mod panel;
 pub mod pattern;
+pub mod preset;
+pub mod sim;
+pub mod supply;
+pub mod task;
+pub mod warmup;
 
 use async_trait::async_trait;
-use std::{error::Error, fmt, future::Future, pin::Pin};
+use serde::{Deserialize, Serialize};
+use std::{error::Error, fmt, future::Future, pin::Pin, time::SystemTime};
 
-use crate::{asic::hash_thread::HashThread, transport::UsbDeviceInfo};
+use crate::{
+    asic::{hash_thread::HashThread, nonce_map::NonceMap},
+    board::{identity::BoardIdentity, supply::InputSupply},
+    hw_trait::LatencyHistogram,
+    peripheral::scan::ScannedDevice,
+    transport::{CpuDeviceInfo, DirectDeviceInfo, SimDeviceInfo, UsbDeviceInfo},
+};
 
 /// Represents a mining board containing one or more ASIC chips.
 ///
@@ -25,6 +41,19 @@ pub trait Board: Send {
     /// stopping hashing and ensuring chips are in a low-power or reset state.
     async fn shutdown(&mut self) -> Result<(), BoardError>;
 
+    /// Run one stage of a staged shutdown.
+    ///
+    /// The board's task runs every [`ShutdownStage`] in order, each under its
+    /// own timeout, so a stage that hangs (e.g., on a wedged bus) doesn't
+    /// keep the core voltage on. Boards that don't split their shutdown keep
+    /// the default, which does all of [`Board::shutdown`] when parking.
+    async fn shutdown_stage(&mut self, stage: ShutdownStage) -> Result<(), BoardError> {
+        match stage {
+            ShutdownStage::Park => self.shutdown().await,
+            ShutdownStage::PowerOff | ShutdownStage::CoolDown => Ok(()),
+        }
+    }
+
     /// Create hash threads for this board.
     ///
     /// Transfers serial channel ownership to threads. Board retains peripheral
@@ -33,6 +62,329 @@ pub trait Board: Send {
     /// Board-to-thread shutdown is implementation-specific (not exposed through
     /// HashThread trait). Call board.shutdown() to trigger thread shutdown.
     async fn create_hash_threads(&mut self) -> Result<Vec<Box<dyn HashThread>>, BoardError>;
+
+    /// Whether the board's threads must pass the known-answer self-test
+    /// (see [`crate::asic::self_test`]) before they're put to work.
+    ///
+    /// Only worthwhile for threads that sweep the nonce range in well under
+    /// [`crate::asic::self_test::VECTOR_TIMEOUT`]; boards with slower or
+    /// simulated threads keep the default.
+    fn self_test(&self) -> bool {
+        false
+    }
+
+    /// Control port of the board's ESP32 management controller, for
+    /// firmware updates (see [`crate::firmware`]), if it has one whose ROM
+    /// loader can be reached by resetting it over the port's DTR and RTS
+    /// lines.
+    fn firmware_port(&self) -> Option<String> {
+        None
+    }
+
+    /// Drive the board's screen and buttons, if it has any, through `panel`
+    /// (see [`panel`]). Called before the hash threads are created. Boards
+    /// without a front panel keep the default, which ignores it.
+    fn attach_panel(&mut self, _panel: panel::PanelLink) {}
+
+    /// Pick up where the board left off on `device`, the USB device it came
+    /// back as after dropping off the bus for a moment: reopen its ports and
+    /// bring its firmware and chips back in step, keeping the hash threads
+    /// the scheduler holds.
+    ///
+    /// Boards that can't keep the default, which fails, and are recreated
+    /// instead.
+    async fn reconnect(&mut self, _device: &UsbDeviceInfo) -> Result<(), BoardError> {
+        Err(BoardError::HardwareControl(
+            "reconnect not supported".into(),
+        ))
+    }
+
+    /// Retune the chips to a new clock frequency and/or core voltage.
+    ///
+    /// Fields left as `None` keep their current value. Boards that can't
+    /// retune while hashing keep the default, which rejects the request.
+    /// Boards that can set the voltage but not the frequency reject a point
+    /// with a frequency with [`BoardError::FrequencyUnsupported`], changing
+    /// nothing.
+    ///
+    /// The board's task checks the voltage against [`Board::voltage_range`]
+    /// before calling this, so implementations only see in-range requests.
+    async fn set_operating_point(&mut self, _point: OperatingPoint) -> Result<(), BoardError> {
+        Err(BoardError::HardwareControl(
+            "operating point control not supported".into(),
+        ))
+    }
+
+    /// Reset the chips in place: pulse their reset line, re-enumerate them,
+    /// and reprogram their registers, leaving power and the hash threads as
+    /// they are.
+    ///
+    /// Much lighter than recreating the board, for chips that have locked
+    /// up. Returns the number of chips that answered after the reset.
+    async fn reset_chips(&mut self) -> Result<usize, BoardError> {
+        Err(BoardError::HardwareControl(
+            "chip reset not supported".into(),
+        ))
+    }
+
+    /// Identity the board stores about itself (see
+    /// [`crate::board::identity`]), or None if none was stored.
+    ///
+    /// Boards with nowhere to store one keep the default, which fails.
+    async fn identity(&mut self) -> Result<Option<BoardIdentity>, BoardError> {
+        Err(BoardError::HardwareControl(
+            "identity storage not supported".into(),
+        ))
+    }
+
+    /// Store the board's identity, replacing any already stored. Takes
+    /// effect for descriptor matching the next time the board is created.
+    async fn write_identity(&mut self, _identity: BoardIdentity) -> Result<(), BoardError> {
+        Err(BoardError::HardwareControl(
+            "identity storage not supported".into(),
+        ))
+    }
+
+    /// Store the regulator's present settings, tuning included, in its
+    /// non-volatile memory, so it powers up with them.
+    ///
+    /// Boards whose regulator can't store its settings keep the default,
+    /// which fails.
+    async fn store_regulator_config(&mut self) -> Result<(), BoardError> {
+        Err(BoardError::HardwareControl(
+            "regulator storage not supported".into(),
+        ))
+    }
+
+    /// Scan the board's I2C bus and identify the parts on it (see
+    /// [`crate::peripheral::scan`]).
+    ///
+    /// Boards without an I2C bus keep the default, which fails.
+    async fn scan_i2c(&mut self) -> Result<Vec<ScannedDevice>, BoardError> {
+        Err(BoardError::HardwareControl("I2C scan not supported".into()))
+    }
+
+    /// Set how the board drives its fans.
+    ///
+    /// Boards without fan control keep the default, which fails.
+    async fn set_fan_mode(&mut self, _mode: FanMode) -> Result<(), BoardError> {
+        Err(BoardError::HardwareControl(
+            "fan control not supported".into(),
+        ))
+    }
+
+    /// Make the board easy to pick out among others for a few seconds, with
+    /// whatever it can signal with. Returns once the signal has started.
+    ///
+    /// Boards with nothing to signal with keep the default, which fails.
+    async fn identify(&mut self) -> Result<(), BoardError> {
+        Err(BoardError::HardwareControl("identify not supported".into()))
+    }
+
+    /// Nonces the chips have found per core, for spotting dead cores and
+    /// marginal chips.
+    async fn nonce_map(&mut self) -> Result<NonceMap, BoardError> {
+        Err(BoardError::HardwareControl(
+            "nonce statistics not supported".into(),
+        ))
+    }
+
+    /// Take the receiver for faults that need the board reinitialized.
+    ///
+    /// Called once by the board's task after the hash threads are created.
+    /// A message on it, giving the reason, makes the task shut the board
+    /// down and recreate it as it would after a crash. Boards whose threads
+    /// can't ask for that keep the default.
+    fn take_fault_receiver(&mut self) -> Option<tokio::sync::mpsc::Receiver<String>> {
+        None
+    }
+
+    /// Core voltages the board's regulator is configured to accept.
+    ///
+    /// `None` means the board doesn't set a range and checks requests itself.
+    fn voltage_range(&self) -> Option<VoltageRange> {
+        None
+    }
+
+    /// What the board's input should read, for noticing a supply that
+    /// can't keep up (see [`supply`]).
+    ///
+    /// `None` if the board can't read its input voltage.
+    fn input_supply(&self) -> Option<InputSupply> {
+        None
+    }
+
+    /// Present power draw in watts, if the board can measure it.
+    async fn power_watts(&mut self) -> Option<f32> {
+        None
+    }
+
+    /// Read the board's sensors.
+    ///
+    /// Called periodically by the board's task, which caches the result for
+    /// the API; readings a board can't take are left as `None`.
+    async fn telemetry(&mut self) -> TelemetrySnapshot {
+        TelemetrySnapshot {
+            power_watts: self.power_watts().await,
+            ..TelemetrySnapshot::new()
+        }
+    }
+}
+
+/// Sensor readings from one board, taken together.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TelemetrySnapshot {
+    /// When the readings were taken
+    #[serde(skip, default = "SystemTime::now")]
+    pub taken_at: SystemTime,
+    /// Supply voltage in volts
+    pub input_voltage: Option<f32>,
+    /// Core voltage in volts
+    pub core_voltage: Option<f32>,
+    /// Core current in amps
+    pub core_current: Option<f32>,
+    /// Power draw in watts
+    pub power_watts: Option<f32>,
+    /// ASIC temperature in degrees Celsius
+    pub temperature_c: Option<f32>,
+    /// Fan duty cycle in percent
+    pub fan_percent: Option<u8>,
+    /// Fan speed in RPM
+    pub fan_rpm: Option<u32>,
+    /// Difficulty below which the chips drop nonces (ticket mask)
+    pub chip_difficulty: Option<u64>,
+    /// How long the board's management I/O has taken since it was created
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub io_latency: Option<IoLatency>,
+}
+
+/// Latency of a board's management I/O.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct IoLatency {
+    /// I2C transactions, however the bus is reached
+    pub i2c: Option<LatencyHistogram>,
+    /// Exchanges with the management controller over USB, including those
+    /// carrying I2C transactions
+    pub control: Option<LatencyHistogram>,
+}
+
+impl TelemetrySnapshot {
+    /// Snapshot taken now, with no readings yet.
+    pub fn new() -> Self {
+        Self {
+            taken_at: SystemTime::now(),
+            input_voltage: None,
+            core_voltage: None,
+            core_current: None,
+            power_watts: None,
+            temperature_c: None,
+            fan_percent: None,
+            fan_rpm: None,
+            chip_difficulty: None,
+            io_latency: None,
+        }
+    }
+}
+
+impl Default for TelemetrySnapshot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Chip clock frequency and core voltage.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct OperatingPoint {
+    /// Chip clock frequency in MHz
+    pub frequency_mhz: Option<f32>,
+    /// Core voltage in volts
+    pub voltage: Option<f32>,
+}
+
+impl fmt::Display for OperatingPoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.frequency_mhz, self.voltage) {
+            (Some(mhz), Some(v)) => write!(f, "{} MHz @ {:.3} V", mhz, v),
+            (Some(mhz), None) => write!(f, "{} MHz", mhz),
+            (None, Some(v)) => write!(f, "{:.3} V", v),
+            (None, None) => write!(f, "current settings"),
+        }
+    }
+}
+
+impl OperatingPoint {
+    /// Check the requested voltage, if any, against a board's range.
+    pub fn check_voltage(&self, range: Option<VoltageRange>) -> Result<(), BoardError> {
+        match (self.voltage, range) {
+            (Some(volts), Some(range)) if !range.contains(volts) => {
+                Err(BoardError::VoltageOutOfRange {
+                    volts,
+                    min: range.min,
+                    max: range.max,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// How a board drives its fans.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum FanMode {
+    /// The board's own policy
+    Auto,
+    /// A fixed duty cycle
+    Fixed { percent: u8 },
+}
+
+impl fmt::Display for FanMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Auto => write!(f, "auto"),
+            Self::Fixed { percent } => write!(f, "{}%", percent),
+        }
+    }
+}
+
+/// Inclusive range of core voltages, in volts.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct VoltageRange {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl VoltageRange {
+    /// Whether `volts` lies within the range. NaN never does.
+    pub fn contains(&self, volts: f32) -> bool {
+        (self.min..=self.max).contains(&volts)
+    }
+}
+
+/// A step in powering a board down, in the order they're run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownStage {
+    /// Stop hashing and leave the chips idle or held in reset
+    Park,
+    /// Turn the core voltage off
+    PowerOff,
+    /// Bring the fans down now that no heat is being made
+    CoolDown,
+}
+
+impl ShutdownStage {
+    /// Every stage, in order.
+    pub const ALL: [ShutdownStage; 3] = [Self::Park, Self::PowerOff, Self::CoolDown];
+}
+
+impl fmt::Display for ShutdownStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Park => write!(f, "park"),
+            Self::PowerOff => write!(f, "power off"),
+            Self::CoolDown => write!(f, "cool down"),
+        }
+    }
 }
 
 /// Information about a board
@@ -55,6 +407,14 @@ pub enum BoardError {
     Communication(std::io::Error),
     /// GPIO or hardware control error
     HardwareControl(String),
+    /// Requested core voltage is outside the board's range
+    VoltageOutOfRange { volts: f32, min: f32, max: f32 },
+    /// Board can't change its chips' clock; returned before anything else
+    /// in the request was changed
+    FrequencyUnsupported,
+    /// Board was recreated after the request was made, so it was dropped;
+    /// worth retrying
+    Restarted,
 }
 
 impl fmt::Display for BoardError {
@@ -65,6 +425,15 @@ impl fmt::Display for BoardError {
             }
             BoardError::Communication(err) => write!(f, "Board communication error: {}", err),
             BoardError::HardwareControl(msg) => write!(f, "Hardware control error: {}", msg),
+            BoardError::VoltageOutOfRange { volts, min, max } => write!(
+                f,
+                "Core voltage {} V is outside the board's range of {}-{} V",
+                volts, min, max
+            ),
+            BoardError::FrequencyUnsupported => {
+                write!(f, "Board can't change its chip frequency")
+            }
+            BoardError::Restarted => write!(f, "Board restarted; retry the request"),
         }
     }
 }
@@ -84,6 +453,11 @@ type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
 pub type BoardFactoryFn =
     fn(UsbDeviceInfo) -> BoxFuture<'static, crate::error::Result<Box<dyn Board + Send>>>;
 
+/// Type alias for a function reading a board's stored identity before the
+/// board is created
+pub type IdentifyFn =
+    fn(UsbDeviceInfo) -> BoxFuture<'static, crate::error::Result<Option<BoardIdentity>>>;
+
 /// Board descriptor that gets collected by inventory.
 ///
 /// Board implementors use `inventory::submit!` to register their board type
@@ -95,7 +469,10 @@ pub type BoardFactoryFn =
 /// Each descriptor includes a pattern that specifies which devices it can handle.
 /// When multiple descriptors match a device, the one with the highest specificity
 /// score is selected. This allows generic fallback handlers while ensuring
-/// specific boards are matched correctly.
+/// specific boards are matched correctly. A pattern can also name the model
+/// a board stores in its identity; the identity is read, with the
+/// `identify_fn` of a descriptor for the same controller, only when such a
+/// pattern is among the candidates.
 pub struct BoardDescriptor {
     /// Pattern for matching USB devices
     pub pattern: pattern::BoardPattern,
@@ -103,6 +480,9 @@ pub struct BoardDescriptor {
     pub name: &'static str,
     /// Factory function to create the board from USB device info
     pub create_fn: BoardFactoryFn,
+    /// Reads the identity boards of this type store, so descriptors whose
+    /// pattern names a model can be matched
+    pub identify_fn: Option<IdentifyFn>,
 }
 
 // This creates the inventory collection for board descriptors
@@ -114,10 +494,32 @@ inventory::collect!(BoardDescriptor);
 
 /// Type alias for virtual board factory function.
 ///
-/// Unlike USB boards, virtual boards don't receive device info---they're
-/// configured via environment variables or other means.
+/// Unlike USB boards, virtual boards aren't discovered; the daemon
+/// synthesizes their device info from its configuration.
 pub type VirtualBoardFactoryFn =
-    fn() -> BoxFuture<'static, crate::error::Result<Box<dyn Board + Send>>>;
+    fn(&VirtualDeviceInfo) -> BoxFuture<'static, crate::error::Result<Box<dyn Board + Send>>>;
+
+/// Device info for a virtual board, one variant per virtual transport.
+#[derive(Debug, Clone)]
+pub enum VirtualDeviceInfo {
+    /// CPU miner
+    Cpu(CpuDeviceInfo),
+    /// Simulation board
+    Sim(SimDeviceInfo),
+    /// Board wired to the host's own UART, I2C and GPIO
+    Direct(DirectDeviceInfo),
+}
+
+impl VirtualDeviceInfo {
+    /// Unique identifier of the virtual device.
+    pub fn device_id(&self) -> &str {
+        match self {
+            Self::Cpu(info) => &info.device_id,
+            Self::Sim(info) => &info.device_id,
+            Self::Direct(info) => &info.device_id,
+        }
+    }
+}
 
 /// Descriptor for virtual boards (CPU miner, test boards, etc.).
 ///
@@ -0,0 +1,241 @@
+//! Front panel of boards with a screen and a button.
+//!
+//! A board running standalone shows the miner's state on its own screen, a
+//! page at a time as AxeOS does: hashrate, the pool being mined, and the best
+//! share. A short press of its button turns the page; a long press restarts
+//! the miner, with the same orderly shutdown as a restart through the API.
+//!
+//! What the pages say is the same for any board. The board driving the
+//! screen gathers [`PanelStats`] and draws the lines [`render`] gives; what
+//! it can't know itself, the pool and the way to restart, comes through the
+//! [`PanelLink`] the backplane hands it.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::{mpsc, oneshot};
+use tokio_util::sync::CancellationToken;
+
+use crate::pools::{PoolCommand, PoolInfo};
+use crate::types::{Difficulty, HashRate};
+
+/// Held at least this long, a press restarts the miner.
+pub const LONG_PRESS: Duration = Duration::from_secs(3);
+
+/// How often the page shown is redrawn with fresh numbers.
+pub const REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long to wait on the pool manager before drawing without the pool.
+const POOL_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// What a board's front panel needs from the rest of the miner.
+#[derive(Clone)]
+pub struct PanelLink {
+    pools_tx: mpsc::Sender<PoolCommand>,
+    shutdown: CancellationToken,
+    restart_requested: Arc<AtomicBool>,
+}
+
+/// A page of the front panel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Page {
+    #[default]
+    Hashrate,
+    Pool,
+    BestShare,
+}
+
+/// What a button press asks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ButtonAction {
+    /// Show the next page
+    NextPage,
+    /// Restart the miner
+    Restart,
+}
+
+/// The numbers the pages show.
+#[derive(Debug, Clone, Default)]
+pub struct PanelStats {
+    /// The board's hashrate
+    pub hashrate: HashRate,
+    /// ASIC temperature in degrees Celsius
+    pub temperature_c: Option<f32>,
+    /// Power draw in watts
+    pub power_watts: Option<f32>,
+    /// Highest difficulty of any nonce the board has found
+    pub best_difficulty: Option<Difficulty>,
+    /// The pool being mined
+    pub pool: Option<PoolInfo>,
+}
+
+impl PanelLink {
+    /// Ask `pools_tx` for the pool being mined, and restart by setting
+    /// `restart_requested` and cancelling `shutdown`, as the API does.
+    pub fn new(
+        pools_tx: mpsc::Sender<PoolCommand>,
+        shutdown: CancellationToken,
+        restart_requested: Arc<AtomicBool>,
+    ) -> Self {
+        Self {
+            pools_tx,
+            shutdown,
+            restart_requested,
+        }
+    }
+
+    /// The pool being mined, if there is one and the pool manager answers
+    /// in time.
+    pub async fn active_pool(&self) -> Option<PoolInfo> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.pools_tx
+            .send(PoolCommand::List { reply_tx })
+            .await
+            .ok()?;
+        let pools = tokio::time::timeout(POOL_TIMEOUT, reply_rx)
+            .await
+            .ok()?
+            .ok()?;
+        pools.into_iter().find(|pool| pool.active)
+    }
+
+    /// Stop the miner and start it again.
+    pub fn restart(&self) {
+        self.restart_requested.store(true, Ordering::SeqCst);
+        self.shutdown.cancel();
+    }
+}
+
+impl Page {
+    /// The page after this one, back to the first after the last.
+    pub fn next(self) -> Self {
+        match self {
+            Self::Hashrate => Self::Pool,
+            Self::Pool => Self::BestShare,
+            Self::BestShare => Self::Hashrate,
+        }
+    }
+}
+
+impl ButtonAction {
+    /// What a press held for `held` asks for.
+    pub fn for_press(held: Duration) -> Self {
+        if held >= LONG_PRESS {
+            Self::Restart
+        } else {
+            Self::NextPage
+        }
+    }
+}
+
+/// Lines of `page`, top to bottom.
+pub fn render(page: Page, stats: &PanelStats) -> Vec<String> {
+    match page {
+        Page::Hashrate => {
+            let temperature = stats
+                .temperature_c
+                .map_or_else(|| "-".to_string(), |t| format!("{t:.0}C"));
+            let power = stats
+                .power_watts
+                .map_or_else(|| "-".to_string(), |w| format!("{w:.1}W"));
+            vec![
+                "Hashrate".to_string(),
+                stats.hashrate.to_string(),
+                format!("{temperature} {power}"),
+            ]
+        }
+        Page::Pool => match &stats.pool {
+            Some(pool) => vec![
+                "Pool".to_string(),
+                host(&pool.url).to_string(),
+                if pool.status.connected {
+                    format!("A:{} R:{}", pool.status.accepted, pool.status.rejected)
+                } else {
+                    "Connecting".to_string()
+                },
+            ],
+            None => vec!["Pool".to_string(), "None".to_string()],
+        },
+        Page::BestShare => vec![
+            "Best share".to_string(),
+            stats
+                .best_difficulty
+                .map_or_else(|| "-".to_string(), |d| d.to_string()),
+        ],
+    }
+}
+
+/// `url` without its scheme, which takes room the screen doesn't have.
+fn host(url: &str) -> &str {
+    url.split_once("://").map_or(url, |(_, rest)| rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job_source::stratum_v1::PoolStatus;
+
+    #[test]
+    fn test_button_actions() {
+        assert_eq!(
+            ButtonAction::for_press(Duration::from_millis(150)),
+            ButtonAction::NextPage
+        );
+        assert_eq!(ButtonAction::for_press(LONG_PRESS), ButtonAction::Restart);
+
+        let mut page = Page::default();
+        for expected in [Page::Pool, Page::BestShare, Page::Hashrate] {
+            page = page.next();
+            assert_eq!(page, expected);
+        }
+    }
+
+    #[test]
+    fn test_pages() {
+        let mut stats = PanelStats {
+            hashrate: HashRate::from_terahashes(1.2),
+            temperature_c: Some(54.6),
+            power_watts: None,
+            best_difficulty: Some(Difficulty::from(1_250_000_u64)),
+            pool: None,
+        };
+        assert_eq!(render(Page::Hashrate, &stats)[1..], ["1.20 TH/s", "55C -"]);
+        assert_eq!(render(Page::Pool, &stats)[1], "None");
+        assert_eq!(render(Page::BestShare, &stats)[1], "1.25M");
+
+        stats.pool = Some(PoolInfo {
+            id: 1,
+            url: "stratum+tcp://pool.example.com:3333".into(),
+            worker: "bc1q.worker".into(),
+            priority: 0,
+            active: true,
+            forced: false,
+            status: PoolStatus {
+                connected: true,
+                accepted: 12,
+                rejected: 1,
+                ..Default::default()
+            },
+        });
+        assert_eq!(
+            render(Page::Pool, &stats)[1..],
+            ["pool.example.com:3333", "A:12 R:1"]
+        );
+    }
+
+    #[test]
+    fn test_restart() {
+        let shutdown = CancellationToken::new();
+        let restart_requested = Arc::new(AtomicBool::new(false));
+        let link = PanelLink::new(
+            mpsc::channel(1).0,
+            shutdown.clone(),
+            restart_requested.clone(),
+        );
+
+        link.restart();
+        assert!(shutdown.is_cancelled());
+        assert!(restart_requested.load(Ordering::SeqCst));
+    }
+}
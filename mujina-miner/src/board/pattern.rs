@@ -2,7 +2,9 @@
 //!
 //! This module provides a pattern-based system for matching USB devices to
 //! board implementations. Patterns can specify any combination of VID, PID,
-//! manufacturer string, product string, and serial number pattern.
+//! manufacturer string, product string, and serial number pattern, and the
+//! model in the identity a board stores about itself (see
+//! [`super::identity`]).
 //!
 //! ## Specificity Scoring
 //!
@@ -14,6 +16,10 @@
 //! - Manufacturer exact match: +20 points (regex: +15, contains: +10)
 //! - Product exact match: +20 points (regex: +15, contains: +10)
 //! - Serial pattern exact: +20 points (regex: +15, contains: +10)
+//! - Stored model exact: +40 points (regex: +35, contains: +30)
+//!
+//! A stored model counts double a USB string, so a descriptor for one model
+//! that repeats its controller's USB criteria wins over the generic one.
 //!
 //! ## Example
 //!
@@ -25,6 +31,7 @@
 //!     manufacturer: Match::Any,
 //!     product: Match::Specific(StringMatch::Exact("Bitaxe Gamma")),
 //!     serial_pattern: Match::Any,
+//!     model: Match::Any,
 //! }
 //!
 //! // Lower specificity: vid + contains manufacturer = 20 points
@@ -34,11 +41,13 @@
 //!     manufacturer: Match::Specific(StringMatch::Contains("FTDI")),
 //!     product: Match::Any,
 //!     serial_pattern: Match::Any,
+//!     model: Match::Any,
 //! }
 //! ```
 
 use regex::Regex;
 
+use super::identity::BoardIdentity;
 use crate::transport::UsbDeviceInfo;
 
 /// Matching criterion that can be either a wildcard or a specific value.
@@ -134,6 +143,10 @@ pub struct BoardPattern {
     pub product: Match<StringMatch>,
     /// Serial number pattern
     pub serial_pattern: Match<StringMatch>,
+    /// Model in the board's stored identity. Patterns that set it are only
+    /// matched once the identity has been read, and never by boards
+    /// without one.
+    pub model: Match<StringMatch>,
 }
 
 impl BoardPattern {
@@ -149,6 +162,7 @@ impl BoardPattern {
             manufacturer: Match::Any,
             product: Match::Any,
             serial_pattern: Match::Any,
+            model: Match::Any,
         }
     }
 
@@ -195,6 +209,22 @@ impl BoardPattern {
         true
     }
 
+    /// Whether matching this pattern needs the board's stored identity.
+    pub fn needs_identity(&self) -> bool {
+        self.model.is_specific()
+    }
+
+    /// Check the identity criteria against a board's stored identity, if
+    /// it has one. Patterns without identity criteria always match.
+    pub fn matches_identity(&self, identity: Option<&BoardIdentity>) -> bool {
+        match self.model {
+            Match::Any => true,
+            Match::Specific(ref matcher) => {
+                matcher.matches(&identity.map(|identity| identity.model.clone()))
+            }
+        }
+    }
+
     /// Calculate the specificity score for this pattern.
     ///
     /// Higher scores indicate more specific patterns. When multiple patterns
@@ -217,6 +247,9 @@ impl BoardPattern {
         if let Match::Specific(ref m) = self.serial_pattern {
             score += m.specificity();
         }
+        if let Match::Specific(ref m) = self.model {
+            score += 20 + m.specificity();
+        }
 
         score
     }
@@ -259,6 +292,7 @@ mod tests {
             manufacturer: Match::Specific(StringMatch::Exact("ACME Corp")),
             product: Match::Specific(StringMatch::Exact("Widget Pro")),
             serial_pattern: Match::Any,
+            model: Match::Any,
         };
 
         // Exact match
@@ -282,6 +316,7 @@ mod tests {
             manufacturer: Match::Any,
             product: Match::Specific(StringMatch::Regex(r"Bitaxe.*Gamma")),
             serial_pattern: Match::Any,
+            model: Match::Any,
         };
 
         let device = make_device(0x1234, 0x5678, None, Some("Bitaxe Ultra Gamma"), None);
@@ -302,6 +337,7 @@ mod tests {
             manufacturer: Match::Specific(StringMatch::Contains("FTDI")),
             product: Match::Any,
             serial_pattern: Match::Any,
+            model: Match::Any,
         };
 
         let device = make_device(0x0403, 0x6015, Some("FTDI"), None, None);
@@ -325,6 +361,7 @@ mod tests {
             manufacturer: Match::Any,
             product: Match::Any,
             serial_pattern: Match::Any,
+            model: Match::Any,
         };
 
         // Should match any device with VID 0x1234
@@ -354,6 +391,7 @@ mod tests {
             manufacturer: Match::Any,
             product: Match::Any,
             serial_pattern: Match::Any,
+            model: Match::Any,
         };
         let vid_and_pid = BoardPattern {
             vid: Match::Specific(0x1234),
@@ -361,6 +399,7 @@ mod tests {
             manufacturer: Match::Any,
             product: Match::Any,
             serial_pattern: Match::Any,
+            model: Match::Any,
         };
         assert!(vid_and_pid.specificity() > vid_only.specificity());
 
@@ -371,6 +410,7 @@ mod tests {
             manufacturer: Match::Specific(StringMatch::Exact("ACME")),
             product: Match::Any,
             serial_pattern: Match::Any,
+            model: Match::Any,
         };
         let regex = BoardPattern {
             vid: Match::Specific(0x1234),
@@ -378,6 +418,7 @@ mod tests {
             manufacturer: Match::Specific(StringMatch::Regex("ACME")),
             product: Match::Any,
             serial_pattern: Match::Any,
+            model: Match::Any,
         };
         let contains = BoardPattern {
             vid: Match::Specific(0x1234),
@@ -385,6 +426,7 @@ mod tests {
             manufacturer: Match::Specific(StringMatch::Contains("ACME")),
             product: Match::Any,
             serial_pattern: Match::Any,
+            model: Match::Any,
         };
         assert!(exact.specificity() > regex.specificity());
         assert!(regex.specificity() > contains.specificity());
@@ -396,6 +438,7 @@ mod tests {
             manufacturer: Match::Specific(StringMatch::Exact("ACME")),
             product: Match::Any,
             serial_pattern: Match::Any,
+            model: Match::Any,
         };
         assert!(with_manufacturer.specificity() > vid_and_pid.specificity());
 
@@ -406,6 +449,7 @@ mod tests {
             manufacturer: Match::Specific(StringMatch::Exact("ACME")),
             product: Match::Specific(StringMatch::Exact("Widget")),
             serial_pattern: Match::Specific(StringMatch::Exact("SN123")),
+            model: Match::Any,
         };
         assert!(full_spec.specificity() > with_manufacturer.specificity());
     }
@@ -428,6 +472,7 @@ mod tests {
             manufacturer: Match::Specific(StringMatch::Contains("FTDI")),
             product: Match::Any,
             serial_pattern: Match::Any,
+            model: Match::Any,
         };
 
         // Specific Bitaxe - matches VID+PID+product
@@ -437,6 +482,7 @@ mod tests {
             manufacturer: Match::Any,
             product: Match::Specific(StringMatch::Regex("Bitaxe")),
             serial_pattern: Match::Any,
+            model: Match::Any,
         };
 
         // Very specific Bitaxe Gamma - matches everything
@@ -446,6 +492,7 @@ mod tests {
             manufacturer: Match::Any,
             product: Match::Specific(StringMatch::Exact("Bitaxe Gamma")),
             serial_pattern: Match::Specific(StringMatch::Regex("^BX")),
+            model: Match::Any,
         };
 
         // All three patterns match the device
@@ -477,6 +524,7 @@ mod tests {
             manufacturer: Match::Any,
             product: Match::Any,
             serial_pattern: Match::Specific(StringMatch::Regex(r"^E1-\d+$")),
+            model: Match::Any,
         };
 
         let device = make_device(0x1234, 0x5678, None, None, Some("E1-12345"));
@@ -491,4 +539,34 @@ mod tests {
         let device = make_device(0x1234, 0x5678, None, None, None);
         assert!(!pattern.matches(&device)); // No serial number
     }
+
+    #[test]
+    fn test_stored_model_matching() {
+        let device = make_device(0x303a, 0x1001, Some("OSMU"), Some("Bitaxe"), None);
+        let controller = BoardPattern {
+            manufacturer: Match::Specific(StringMatch::Exact("OSMU")),
+            product: Match::Specific(StringMatch::Exact("Bitaxe")),
+            ..BoardPattern::wildcard()
+        };
+        let supra = BoardPattern {
+            model: Match::Specific(StringMatch::Exact("Bitaxe Supra")),
+            ..controller.clone()
+        };
+        assert!(supra.matches(&device), "USB criteria alone still match");
+        assert!(supra.needs_identity());
+        assert!(!controller.needs_identity());
+
+        let identity = |model: &str| BoardIdentity {
+            model: model.into(),
+            revision: None,
+            serial: None,
+            calibration: Default::default(),
+        };
+        assert!(supra.matches_identity(Some(&identity("Bitaxe Supra"))));
+        assert!(!supra.matches_identity(Some(&identity("Bitaxe Gamma"))));
+        assert!(!supra.matches_identity(None));
+        assert!(controller.matches_identity(None));
+
+        assert!(supra.specificity() > controller.specificity());
+    }
 }
@@ -0,0 +1,212 @@
+//! Named operating presets: eco, balanced and turbo.
+//!
+//! A preset pairs an operating point with a fan mode, so a board can be
+//! moved between quiet and fast with one request rather than three. Their
+//! values come from the `[presets]` config section, by board model, with a
+//! `default` entry for models not listed:
+//!
+//! ```toml
+//! [presets.default]
+//! eco = { frequency_mhz = 400.0, voltage = 1.1 }
+//! turbo = { frequency_mhz = 575.0, voltage = 1.25, fan = { mode = "fixed", percent = 100 } }
+//!
+//! [presets."Bitaxe Gamma"]
+//! turbo = { frequency_mhz = 600.0, voltage = 1.2 }
+//! ```
+//!
+//! Chips vary from unit to unit, so a board's stored settings (see
+//! [`crate::settings`]) can refine each preset for that board alone, as
+//! tuning finds what it's capable of. A preset is resolved field by field:
+//! the board's own value, then its model's, then the default's.
+//!
+//! Applying a preset stores the resolved values as the board's overrides,
+//! so it outlasts restarts like any other setting. Fields no level sets
+//! are left as they were.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::{FanMode, OperatingPoint};
+use crate::settings::SettingsError;
+
+/// Key of the presets for models not listed on their own.
+pub const DEFAULT_MODEL: &str = "default";
+
+/// A named preset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Preset {
+    Eco,
+    Balanced,
+    Turbo,
+}
+
+/// What a preset sets; fields left unset are left alone.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+pub struct PresetPoint {
+    /// Chip clock frequency in MHz
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frequency_mhz: Option<f32>,
+
+    /// Core voltage in volts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub voltage: Option<f32>,
+
+    /// How the board drives its fans
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fan: Option<FanMode>,
+}
+
+/// The presets of one board model.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct PresetSet {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eco: Option<PresetPoint>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub balanced: Option<PresetPoint>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub turbo: Option<PresetPoint>,
+}
+
+/// Why boards couldn't be switched to a preset.
+#[derive(Debug, thiserror::Error)]
+pub enum PresetError {
+    #[error("no board with ID {0}")]
+    NoBoard(String),
+
+    #[error("no {preset} preset for {model} boards")]
+    Undefined { preset: Preset, model: String },
+
+    #[error(transparent)]
+    Settings(#[from] SettingsError),
+}
+
+impl fmt::Display for Preset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Eco => "eco",
+            Self::Balanced => "balanced",
+            Self::Turbo => "turbo",
+        })
+    }
+}
+
+impl PresetPoint {
+    /// This point's fields, falling back to `other`'s where unset.
+    pub fn or(self, other: Self) -> Self {
+        Self {
+            frequency_mhz: self.frequency_mhz.or(other.frequency_mhz),
+            voltage: self.voltage.or(other.voltage),
+            fan: self.fan.or(other.fan),
+        }
+    }
+
+    /// The operating point part.
+    pub fn operating_point(&self) -> OperatingPoint {
+        OperatingPoint {
+            frequency_mhz: self.frequency_mhz,
+            voltage: self.voltage,
+        }
+    }
+
+    /// Whether nothing is set.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Check the values make sense for any board.
+    pub fn validate(&self) -> Result<(), String> {
+        if self
+            .frequency_mhz
+            .is_some_and(|f| !(f.is_finite() && f > 0.0))
+        {
+            return Err("frequency_mhz must be a positive number".into());
+        }
+        if self.voltage.is_some_and(|v| !(v.is_finite() && v >= 0.0)) {
+            return Err("voltage must be a non-negative number".into());
+        }
+        if let Some(FanMode::Fixed { percent }) = self.fan {
+            if percent > 100 {
+                return Err(format!("fan percent {} is over 100", percent));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl PresetSet {
+    /// The values of `preset`, if set.
+    pub fn get(&self, preset: Preset) -> Option<PresetPoint> {
+        match preset {
+            Preset::Eco => self.eco,
+            Preset::Balanced => self.balanced,
+            Preset::Turbo => self.turbo,
+        }
+    }
+
+    /// Each preset that's set, with its values.
+    pub fn iter(&self) -> impl Iterator<Item = (Preset, PresetPoint)> + '_ {
+        [Preset::Eco, Preset::Balanced, Preset::Turbo]
+            .into_iter()
+            .filter_map(|preset| Some((preset, self.get(preset)?)))
+    }
+}
+
+/// Resolve `preset` for a board of `model`, refined by the board's own
+/// values in `refined`. None if no level sets anything for it.
+pub fn resolve(
+    presets: &BTreeMap<String, PresetSet>,
+    model: &str,
+    refined: Option<&PresetPoint>,
+    preset: Preset,
+) -> Option<PresetPoint> {
+    let level = |key: &str| presets.get(key).and_then(|set| set.get(preset));
+    let point = refined
+        .copied()
+        .unwrap_or_default()
+        .or(level(model).unwrap_or_default())
+        .or(level(DEFAULT_MODEL).unwrap_or_default());
+    (!point.is_empty()).then_some(point)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_by_unit_model_then_default() {
+        let presets: BTreeMap<String, PresetSet> = toml::from_str(
+            r#"
+            [default]
+            eco = { frequency_mhz = 400.0, voltage = 1.1, fan = { mode = "auto" } }
+            turbo = { frequency_mhz = 575.0, voltage = 1.25 }
+
+            ["Bitaxe Gamma"]
+            turbo = { frequency_mhz = 600.0 }
+            "#,
+        )
+        .unwrap();
+
+        let turbo = resolve(&presets, "Bitaxe Gamma", None, Preset::Turbo).unwrap();
+        assert_eq!(turbo.frequency_mhz, Some(600.0));
+        assert_eq!(turbo.voltage, Some(1.25));
+        assert_eq!(turbo.fan, None);
+
+        // This unit's chips take less voltage
+        let refined = PresetPoint {
+            voltage: Some(1.18),
+            ..Default::default()
+        };
+        let turbo = resolve(&presets, "Bitaxe Gamma", Some(&refined), Preset::Turbo).unwrap();
+        assert_eq!(turbo.operating_point().voltage, Some(1.18));
+        assert_eq!(turbo.frequency_mhz, Some(600.0));
+
+        let eco = resolve(&presets, "EmberOne", None, Preset::Eco).unwrap();
+        assert_eq!(eco.fan, Some(FanMode::Auto));
+        assert_eq!(resolve(&presets, "EmberOne", None, Preset::Balanced), None);
+    }
+}
@@ -0,0 +1,821 @@
+//! Deterministic simulation board.
+//!
+//! A virtual board whose hash threads accept real work from the scheduler
+//! but don't hash. Instead they "find" shares, either:
+//!
+//! - **Randomly**, at the rate a real device of the configured hashrate
+//!   would find shares for the task's share target. Share arrival is a
+//!   Poisson process driven by a seeded PRNG, so a given seed and sequence
+//!   of work assignments always produces the same shares. The reported
+//!   hashes are synthetic: they meet the share target so they flow through
+//!   the scheduler, but a real pool will reject them.
+//!
+//! - **From a script**, a JSON fixture of nonces (and optionally ntime and
+//!   version) emitted at fixed delays. Hashes are computed from the real
+//!   header, so replaying a known solution produces a genuinely valid share.
+//!
+//! Useful for load-testing the scheduler and Stratum submit path at
+//! hashrates no test rig has, and for reproducible end-to-end tests.
+//!
+//! # Configuration
+//!
+//! - `MUJINA_SIM_THREADS=N` - Number of hash threads (presence enables)
+//! - `MUJINA_SIM_HASHRATE_GH=R` - Per-thread hashrate in GH/s (default: 1000)
+//! - `MUJINA_SIM_SEED=S` - PRNG seed (default: 0)
+//! - `MUJINA_SIM_SCRIPT=PATH` - Scripted share fixture
+//!
+//! # Script format
+//!
+//! ```json
+//! [
+//!   { "after_ms": 500, "nonce": 4278581250 },
+//!   { "after_ms": 1000, "nonce": 1234, "ntime": 1738195305, "version": 777609216 }
+//! ]
+//! ```
+//!
+//! Each entry is emitted `after_ms` after the previous one (or after work is
+//! assigned), against whatever task is current. Each thread plays the script
+//! once, then stays quiet.
+//!
+//! # Operating point
+//!
+//! The configured hashrate is taken to be the rate at 500 MHz and 1.15 V.
+//! Retuning scales hashrate with frequency and power with frequency times
+//! voltage squared, like CMOS dynamic power, so benchmarks have something
+//! plausible to sweep.
+
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bitcoin::{block::Header as BlockHeader, block::Version, hashes::Hash, BlockHash};
+use serde::Deserialize;
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio_util::sync::CancellationToken;
+
+use super::{
+    Board, BoardError, BoardInfo, OperatingPoint, TelemetrySnapshot, VirtualBoardDescriptor,
+    VirtualDeviceInfo,
+};
+use crate::{
+    asic::hash_thread::{
+        HashTask, HashThread, HashThreadCapabilities, HashThreadError, HashThreadEvent,
+        HashThreadStatus, Share,
+    },
+    tracing::prelude::*,
+    transport::SimDeviceInfo,
+    types::{expected_time_to_share_from_target, HashRate},
+    u256::U256,
+};
+
+/// Default per-thread hashrate: roughly one modern ASIC chip.
+const DEFAULT_HASHRATE_GH: f64 = 1000.0;
+
+/// Frequency at which a thread runs at its configured hashrate.
+const NOMINAL_FREQUENCY_MHZ: f32 = 500.0;
+
+/// Core voltage at the nominal operating point.
+const NOMINAL_VOLTAGE: f32 = 1.15;
+
+/// Power efficiency at the nominal operating point.
+const NOMINAL_JOULES_PER_TERAHASH: f64 = 20.0;
+
+/// One entry of a share script.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ScriptedShare {
+    /// Delay before emitting this share, from the previous one.
+    pub after_ms: u64,
+
+    /// Header nonce.
+    pub nonce: u32,
+
+    /// Header ntime; defaults to the task's current ntime.
+    #[serde(default)]
+    pub ntime: Option<u32>,
+
+    /// Full header version; defaults to the template's base version.
+    #[serde(default)]
+    pub version: Option<i32>,
+}
+
+/// Simulation board configuration.
+#[derive(Debug, Clone)]
+pub struct SimConfig {
+    /// Number of hash threads.
+    pub thread_count: usize,
+
+    /// Simulated hashrate of each thread.
+    pub hashrate: HashRate,
+
+    /// PRNG seed. Thread `i` uses `seed + i`.
+    pub seed: u64,
+
+    /// Scripted shares; overrides the random model when set.
+    pub script: Option<Vec<ScriptedShare>>,
+}
+
+impl SimConfig {
+    /// Parse configuration from environment variables.
+    ///
+    /// Returns `Some(device)` if `MUJINA_SIM_THREADS` is set, `None`
+    /// otherwise. The script path is only recorded here; it's loaded when
+    /// the board is created.
+    pub fn device_from_env() -> Option<SimDeviceInfo> {
+        let thread_count: usize = std::env::var("MUJINA_SIM_THREADS")
+            .ok()
+            .and_then(|s| s.parse().ok())?;
+
+        let hashrate_gh = std::env::var("MUJINA_SIM_HASHRATE_GH")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_HASHRATE_GH);
+
+        let seed = std::env::var("MUJINA_SIM_SEED")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(0);
+
+        let script = std::env::var_os("MUJINA_SIM_SCRIPT").map(Into::into);
+
+        Some(SimDeviceInfo {
+            device_id: format!("sim-{}x{}", thread_count, seed),
+            thread_count,
+            hashrate: HashRate::from_gigahashes(hashrate_gh),
+            seed,
+            script,
+        })
+    }
+
+    /// Build the board configuration from device info, loading the script.
+    pub fn from_device(device: &SimDeviceInfo) -> crate::error::Result<Self> {
+        let script = device.script.as_deref().map(load_script).transpose()?;
+
+        Ok(Self {
+            thread_count: device.thread_count,
+            hashrate: device.hashrate,
+            seed: device.seed,
+            script,
+        })
+    }
+}
+
+/// Load a share script from a JSON file.
+pub fn load_script(path: &Path) -> crate::error::Result<Vec<ScriptedShare>> {
+    let text = std::fs::read_to_string(path).map_err(|e| {
+        crate::error::Error::Config(format!("reading sim script {}: {}", path.display(), e))
+    })?;
+    serde_json::from_str(&text).map_err(|e| {
+        crate::error::Error::Config(format!("parsing sim script {}: {}", path.display(), e))
+    })
+}
+
+/// Simulation board.
+pub struct SimBoard {
+    config: SimConfig,
+
+    /// Cancels the threads' actor tasks on shutdown.
+    shutdown: CancellationToken,
+
+    /// Current operating point; both fields are always set.
+    point: OperatingPoint,
+
+    /// Per-thread hashrate at the current operating point.
+    hashrate_tx: watch::Sender<HashRate>,
+}
+
+impl SimBoard {
+    /// Create a new simulation board.
+    pub fn new(config: SimConfig) -> Self {
+        let (hashrate_tx, _) = watch::channel(config.hashrate);
+        Self {
+            config,
+            shutdown: CancellationToken::new(),
+            point: OperatingPoint {
+                frequency_mhz: Some(NOMINAL_FREQUENCY_MHZ),
+                voltage: Some(NOMINAL_VOLTAGE),
+            },
+            hashrate_tx,
+        }
+    }
+
+    fn frequency_scale(&self) -> f64 {
+        f64::from(self.point.frequency_mhz.unwrap_or(NOMINAL_FREQUENCY_MHZ) / NOMINAL_FREQUENCY_MHZ)
+    }
+
+    fn voltage_scale(&self) -> f64 {
+        f64::from(self.point.voltage.unwrap_or(NOMINAL_VOLTAGE) / NOMINAL_VOLTAGE)
+    }
+}
+
+#[async_trait]
+impl Board for SimBoard {
+    fn board_info(&self) -> BoardInfo {
+        BoardInfo {
+            model: "Simulation".into(),
+            firmware_version: None,
+            serial_number: Some(format!(
+                "sim-{}x{}",
+                self.config.thread_count, self.config.seed
+            )),
+        }
+    }
+
+    async fn shutdown(&mut self) -> Result<(), BoardError> {
+        self.shutdown.cancel();
+        Ok(())
+    }
+
+    async fn create_hash_threads(&mut self) -> Result<Vec<Box<dyn HashThread>>, BoardError> {
+        let threads = (0..self.config.thread_count)
+            .map(|i| {
+                let finder = match &self.config.script {
+                    Some(script) => ShareFinder::scripted(script.clone()),
+                    None => ShareFinder::random(self.config.seed.wrapping_add(i as u64)),
+                };
+                Box::new(SimHashThread::new(
+                    format!("Sim {}", i),
+                    self.hashrate_tx.subscribe(),
+                    finder,
+                    self.shutdown.child_token(),
+                )) as Box<dyn HashThread>
+            })
+            .collect();
+
+        Ok(threads)
+    }
+
+    async fn set_operating_point(&mut self, point: OperatingPoint) -> Result<(), BoardError> {
+        if let Some(mhz) = point.frequency_mhz {
+            if mhz.is_nan() || mhz <= 0.0 {
+                return Err(BoardError::HardwareControl(format!(
+                    "invalid frequency {} MHz",
+                    mhz
+                )));
+            }
+            self.point.frequency_mhz = Some(mhz);
+        }
+        if let Some(volts) = point.voltage {
+            if volts.is_nan() || volts <= 0.0 {
+                return Err(BoardError::HardwareControl(format!(
+                    "invalid voltage {} V",
+                    volts
+                )));
+            }
+            self.point.voltage = Some(volts);
+        }
+
+        let hashrate = HashRate((self.config.hashrate.0 as f64 * self.frequency_scale()) as u64);
+        self.hashrate_tx.send_replace(hashrate);
+        debug!(point = %self.point, hashrate = %hashrate, "Simulated operating point changed");
+        Ok(())
+    }
+
+    async fn telemetry(&mut self) -> TelemetrySnapshot {
+        TelemetrySnapshot {
+            core_voltage: self.point.voltage,
+            power_watts: self.power_watts().await,
+            ..TelemetrySnapshot::new()
+        }
+    }
+
+    async fn power_watts(&mut self) -> Option<f32> {
+        let nominal = self.config.hashrate.as_terahashes()
+            * NOMINAL_JOULES_PER_TERAHASH
+            * self.config.thread_count as f64;
+        Some((nominal * self.frequency_scale() * self.voltage_scale().powi(2)) as f32)
+    }
+}
+
+/// Commands from the HashThread handle to the actor task.
+#[derive(Debug)]
+struct SetTask {
+    task: Option<HashTask>,
+    response_tx: oneshot::Sender<Option<HashTask>>,
+}
+
+/// Simulated hash thread.
+///
+/// Runs a small actor task that holds the current work and emits shares on
+/// the task's share channel when the [`ShareFinder`] says one is due.
+pub struct SimHashThread {
+    name: String,
+    capabilities: HashThreadCapabilities,
+    command_tx: mpsc::Sender<SetTask>,
+    event_rx: Option<mpsc::Receiver<HashThreadEvent>>,
+    status: Arc<RwLock<HashThreadStatus>>,
+}
+
+impl SimHashThread {
+    /// Create a thread and spawn its actor task.
+    ///
+    /// The thread follows `hashrate` as the board retunes.
+    pub fn new(
+        name: String,
+        hashrate: watch::Receiver<HashRate>,
+        finder: ShareFinder,
+        shutdown: CancellationToken,
+    ) -> Self {
+        let (command_tx, command_rx) = mpsc::channel(10);
+        let (event_tx, event_rx) = mpsc::channel(100);
+        let status = Arc::new(RwLock::new(HashThreadStatus::default()));
+        let hashrate_estimate = *hashrate.borrow();
+
+        tokio::spawn(run_actor(
+            command_rx,
+            event_tx,
+            Arc::clone(&status),
+            hashrate,
+            finder,
+            shutdown,
+        ));
+
+        Self {
+            name,
+            capabilities: HashThreadCapabilities::new(hashrate_estimate),
+            command_tx,
+            event_rx: Some(event_rx),
+            status,
+        }
+    }
+
+    async fn set_task(&self, task: Option<HashTask>) -> Result<Option<HashTask>, HashThreadError> {
+        let (response_tx, response_rx) = oneshot::channel();
+        self.command_tx
+            .send(SetTask { task, response_tx })
+            .await
+            .map_err(|_| HashThreadError::ThreadOffline)?;
+        response_rx
+            .await
+            .map_err(|_| HashThreadError::WorkAssignmentFailed("no response from thread".into()))
+    }
+}
+
+#[async_trait]
+impl HashThread for SimHashThread {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn capabilities(&self) -> &HashThreadCapabilities {
+        &self.capabilities
+    }
+
+    async fn update_task(
+        &mut self,
+        new_task: HashTask,
+    ) -> Result<Option<HashTask>, HashThreadError> {
+        self.set_task(Some(new_task)).await
+    }
+
+    async fn replace_task(
+        &mut self,
+        new_task: HashTask,
+    ) -> Result<Option<HashTask>, HashThreadError> {
+        self.set_task(Some(new_task)).await
+    }
+
+    async fn go_idle(&mut self) -> Result<Option<HashTask>, HashThreadError> {
+        self.set_task(None).await
+    }
+
+    fn take_event_receiver(&mut self) -> Option<mpsc::Receiver<HashThreadEvent>> {
+        self.event_rx.take()
+    }
+
+    fn status(&self) -> HashThreadStatus {
+        self.status.read().unwrap().clone()
+    }
+}
+
+/// Actor loop for a simulated thread.
+///
+/// Exits on shutdown or when the handle is dropped; dropping `event_tx`
+/// then tells the scheduler the thread is gone.
+async fn run_actor(
+    mut command_rx: mpsc::Receiver<SetTask>,
+    _event_tx: mpsc::Sender<HashThreadEvent>,
+    status: Arc<RwLock<HashThreadStatus>>,
+    mut hashrate_rx: watch::Receiver<HashRate>,
+    mut finder: ShareFinder,
+    shutdown: CancellationToken,
+) {
+    let mut current: Option<HashTask> = None;
+    let mut deadline: Option<tokio::time::Instant> = None;
+    let mut hashrate = *hashrate_rx.borrow_and_update();
+    let mut retunable = true;
+
+    loop {
+        if deadline.is_none() {
+            deadline = current
+                .as_ref()
+                .and_then(|task| finder.next_delay(task, hashrate))
+                .map(|delay| tokio::time::Instant::now() + delay);
+        }
+
+        let due = async {
+            match deadline {
+                Some(at) => tokio::time::sleep_until(at).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+
+            command = command_rx.recv() => {
+                let Some(SetTask { task, response_tx }) = command else {
+                    break;
+                };
+                {
+                    let mut s = status.write().unwrap();
+                    s.is_active = task.is_some();
+                    s.hashrate = if task.is_some() { hashrate } else { HashRate::default() };
+                }
+                let old = std::mem::replace(&mut current, task);
+                deadline = None;
+                let _ = response_tx.send(old);
+            }
+
+            changed = hashrate_rx.changed(), if retunable => {
+                if changed.is_err() {
+                    // Board gone; keep the last rate
+                    retunable = false;
+                    continue;
+                }
+                hashrate = *hashrate_rx.borrow_and_update();
+                if current.is_some() {
+                    status.write().unwrap().hashrate = hashrate;
+                }
+                // Arrivals are memoryless, so resampling is exact
+                deadline = None;
+            }
+
+            _ = due => {
+                deadline = None;
+                let Some(task) = &current else { continue };
+                if let Some(share) = finder.share(task) {
+                    status.write().unwrap().chip_shares_found += 1;
+                    trace!(nonce = format!("{:#010x}", share.nonce), "Simulated share");
+                    let _ = task.share_tx.send(share).await;
+                }
+            }
+        }
+    }
+}
+
+/// Decides when a simulated thread finds a share, and what it looks like.
+#[derive(Debug)]
+pub enum ShareFinder {
+    /// Poisson arrivals at the rate implied by hashrate and share target.
+    Random { rng: SplitMix64 },
+
+    /// Fixed sequence of shares.
+    Scripted {
+        script: Vec<ScriptedShare>,
+        next: usize,
+    },
+}
+
+impl ShareFinder {
+    /// Random share finder with the given seed.
+    pub fn random(seed: u64) -> Self {
+        Self::Random {
+            rng: SplitMix64::new(seed),
+        }
+    }
+
+    /// Scripted share finder.
+    pub fn scripted(script: Vec<ScriptedShare>) -> Self {
+        Self::Scripted { script, next: 0 }
+    }
+
+    /// Time until the next share on `task`, or None if none will come.
+    fn next_delay(&mut self, task: &HashTask, hashrate: HashRate) -> Option<Duration> {
+        match self {
+            Self::Random { rng } => {
+                let mean = expected_time_to_share_from_target(task.share_target, hashrate);
+                if mean == Duration::MAX {
+                    return None;
+                }
+                // Inverse-CDF sample of the exponential distribution
+                let u = rng.next_f64();
+                Some(mean.mul_f64(-(1.0 - u).ln()))
+            }
+            Self::Scripted { script, next } => script
+                .get(*next)
+                .map(|entry| Duration::from_millis(entry.after_ms)),
+        }
+    }
+
+    /// Produce the share that is due on `task`.
+    fn share(&mut self, task: &HashTask) -> Option<Share> {
+        let expected_hashes = U256::from(task.share_target.to_work());
+
+        match self {
+            Self::Random { rng } => {
+                // Uniform below the target, like a real hash that met it
+                let target = U256::from(task.share_target);
+                let fraction = rng.next_u64() >> 32;
+                let hash = (target / (1u64 << 32)) * fraction;
+
+                Some(Share {
+                    nonce: rng.next_u64() as u32,
+                    hash: BlockHash::from_byte_array(hash.to_le_bytes()),
+                    version: task.template.version.base(),
+                    ntime: task.ntime,
+                    extranonce2: task.en2,
+                    expected_hashes,
+                })
+            }
+            Self::Scripted { script, next } => {
+                let entry = script.get(*next)?.clone();
+                *next += 1;
+
+                let Some(merkle_root) = task.merkle_root() else {
+                    warn!(job_id = %task.template.id, "Cannot compute merkle root for scripted share");
+                    return None;
+                };
+                let header = BlockHeader {
+                    version: entry
+                        .version
+                        .map(Version::from_consensus)
+                        .unwrap_or_else(|| task.template.version.base()),
+                    prev_blockhash: task.template.prev_blockhash,
+                    merkle_root,
+                    time: entry.ntime.unwrap_or(task.ntime),
+                    bits: task.template.bits,
+                    nonce: entry.nonce,
+                };
+
+                Some(Share {
+                    nonce: header.nonce,
+                    hash: header.block_hash(),
+                    version: header.version,
+                    ntime: header.time,
+                    extranonce2: task.en2,
+                    expected_hashes,
+                })
+            }
+        }
+    }
+}
+
+/// SplitMix64 pseudo-random generator.
+///
+/// Tiny, fast, and fully determined by its seed, which is all the simulation
+/// needs. Not suitable for anything security-related.
+#[derive(Debug, Clone)]
+pub struct SplitMix64(u64);
+
+impl SplitMix64 {
+    /// Create a generator from a seed.
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    /// Next 64 random bits.
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform sample in [0, 1).
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Virtual board registration
+// ---------------------------------------------------------------------------
+
+/// Factory function for creating SimBoard instances.
+async fn create_sim_board(
+    device: VirtualDeviceInfo,
+) -> crate::error::Result<Box<dyn Board + Send>> {
+    let VirtualDeviceInfo::Sim(device) = device else {
+        return Err(crate::error::Error::Config(
+            "simulation board created from non-sim device info".into(),
+        ));
+    };
+    if device.thread_count == 0 {
+        return Err(crate::error::Error::Config(
+            "simulation board needs at least one thread".into(),
+        ));
+    }
+
+    Ok(Box::new(SimBoard::new(SimConfig::from_device(&device)?)))
+}
+
+inventory::submit! {
+    VirtualBoardDescriptor {
+        device_type: "sim",
+        name: "Simulation",
+        create_fn: |device| Box::pin(create_sim_board(device.clone())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job_source::{
+        test_blocks::block_881423, GeneralPurposeBits, JobTemplate, MerkleRootKind, VersionTemplate,
+    };
+    use bitcoin::pow::Target;
+
+    fn block_task(share_target: Target) -> (HashTask, mpsc::Receiver<Share>) {
+        let template = Arc::new(JobTemplate {
+            id: "sim-test".into(),
+            prev_blockhash: *block_881423::PREV_BLOCKHASH,
+            version: VersionTemplate::new(
+                Version::from_consensus(0x2000_0000),
+                GeneralPurposeBits::none(),
+            )
+            .unwrap(),
+            bits: *block_881423::BITS,
+            share_target,
+            time: block_881423::TIME,
+            merkle_root: MerkleRootKind::Fixed(*block_881423::MERKLE_ROOT),
+        });
+        let (share_tx, share_rx) = mpsc::channel(100);
+
+        let task = HashTask {
+            template,
+            en2_range: None,
+            en2: None,
+            share_target,
+            ntime: block_881423::TIME,
+            share_tx,
+        };
+        (task, share_rx)
+    }
+
+    /// A hashrate the thread can never be retuned from.
+    fn fixed(hashrate: HashRate) -> watch::Receiver<HashRate> {
+        watch::channel(hashrate).1
+    }
+
+    /// Collect the nonces of the first `count` shares from a fresh thread.
+    async fn first_nonces(seed: u64, hashrate: HashRate, count: usize) -> Vec<u32> {
+        let mut thread = SimHashThread::new(
+            "sim".into(),
+            fixed(hashrate),
+            ShareFinder::random(seed),
+            CancellationToken::new(),
+        );
+        let (task, mut share_rx) = block_task(Target::MAX_ATTAINABLE_MAINNET);
+        thread.update_task(task.clone()).await.unwrap();
+
+        let mut nonces = Vec::new();
+        for _ in 0..count {
+            let share = share_rx.recv().await.unwrap();
+            assert!(task.share_target.is_met_by(share.hash));
+            nonces.push(share.nonce);
+        }
+        nonces
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_random_shares_are_reproducible() {
+        let hashrate = HashRate::from_terahashes(1.0);
+        let a = first_nonces(7, hashrate, 5).await;
+        let b = first_nonces(7, hashrate, 5).await;
+        let c = first_nonces(8, hashrate, 5).await;
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_random_share_rate_tracks_hashrate() {
+        // Difficulty 1 at 10 GH/s: one share per ~430 ms, long enough that
+        // the timer's millisecond granularity doesn't bias the mean
+        let hashrate = HashRate::from_gigahashes(10.0);
+        let start = tokio::time::Instant::now();
+        first_nonces(1, hashrate, 1000).await;
+        let mean = start.elapsed() / 1000;
+
+        let expected = expected_time_to_share_from_target(Target::MAX_ATTAINABLE_MAINNET, hashrate);
+        let ratio = mean.as_secs_f64() / expected.as_secs_f64();
+        assert!((0.9..1.1).contains(&ratio), "ratio {}", ratio);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_scripted_share_replays_real_block() {
+        let script = vec![ScriptedShare {
+            after_ms: 250,
+            nonce: block_881423::NONCE,
+            ntime: None,
+            version: Some(block_881423::VERSION.to_consensus()),
+        }];
+        let mut thread = SimHashThread::new(
+            "sim".into(),
+            fixed(HashRate::from_terahashes(1.0)),
+            ShareFinder::scripted(script),
+            CancellationToken::new(),
+        );
+        let (task, mut share_rx) = block_task(Target::MAX_ATTAINABLE_MAINNET);
+
+        let start = tokio::time::Instant::now();
+        thread.update_task(task).await.unwrap();
+        let share = share_rx.recv().await.unwrap();
+
+        assert_eq!(start.elapsed(), Duration::from_millis(250));
+        assert_eq!(share.hash, *block_881423::BLOCK_HASH);
+        assert_eq!(share.nonce, block_881423::NONCE);
+
+        // Script is exhausted; nothing more arrives
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        assert!(share_rx.try_recv().is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_idle_thread_finds_nothing() {
+        let mut thread = SimHashThread::new(
+            "sim".into(),
+            fixed(HashRate::from_terahashes(1.0)),
+            ShareFinder::random(0),
+            CancellationToken::new(),
+        );
+        let (task, mut share_rx) = block_task(Target::MAX_ATTAINABLE_MAINNET);
+
+        thread.update_task(task).await.unwrap();
+        let old = thread.go_idle().await.unwrap();
+        assert!(old.is_some());
+        while share_rx.try_recv().is_ok() {}
+
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        assert!(share_rx.try_recv().is_err());
+        assert!(!thread.status().is_active);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_operating_point_scales_hashrate_and_power() {
+        let mut board = SimBoard::new(SimConfig {
+            thread_count: 2,
+            hashrate: HashRate::from_gigahashes(10.0),
+            seed: 3,
+            script: None,
+        });
+        let mut threads = board.create_hash_threads().await.unwrap();
+        let (task, mut share_rx) = block_task(Target::MAX_ATTAINABLE_MAINNET);
+        threads[0].update_task(task).await.unwrap();
+
+        let nominal_power = board.power_watts().await.unwrap();
+        board
+            .set_operating_point(OperatingPoint {
+                frequency_mhz: Some(2.0 * NOMINAL_FREQUENCY_MHZ),
+                voltage: None,
+            })
+            .await
+            .unwrap();
+        tokio::task::yield_now().await;
+
+        assert_eq!(
+            threads[0].status().hashrate,
+            HashRate::from_gigahashes(20.0)
+        );
+        let power = board.power_watts().await.unwrap();
+        assert!(
+            (power / nominal_power - 2.0).abs() < 1e-3,
+            "power {}",
+            power
+        );
+
+        // Twice the hashrate, half the mean share interval
+        let start = tokio::time::Instant::now();
+        for _ in 0..1000 {
+            share_rx.recv().await.unwrap();
+        }
+        let mean = start.elapsed() / 1000;
+        let expected = expected_time_to_share_from_target(
+            Target::MAX_ATTAINABLE_MAINNET,
+            HashRate::from_gigahashes(20.0),
+        );
+        let ratio = mean.as_secs_f64() / expected.as_secs_f64();
+        assert!((0.9..1.1).contains(&ratio), "ratio {}", ratio);
+
+        assert!(board
+            .set_operating_point(OperatingPoint {
+                frequency_mhz: None,
+                voltage: Some(-1.0),
+            })
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_script() {
+        let script: Vec<ScriptedShare> = serde_json::from_str(
+            r#"[{"after_ms": 10, "nonce": 5}, {"after_ms": 0, "nonce": 6, "ntime": 7}]"#,
+        )
+        .unwrap();
+        assert_eq!(script.len(), 2);
+        assert_eq!(script[1].ntime, Some(7));
+        assert_eq!(script[0].version, None);
+    }
+}
@@ -0,0 +1,181 @@
+//! Noticing a supply that can't keep up with its board.
+//!
+//! A board on a weak PSU or a current-limited USB port doesn't fail
+//! cleanly: its input voltage sags under load until the core regulator
+//! drops out, and the board crashes or fails to come up for no reason the
+//! logs show. Boards that know their nominal input (see
+//! [`Board::input_supply`](super::Board::input_supply)) have the input
+//! voltage in each telemetry reading checked against it. Once it's read
+//! more than [`SAG_FRACTION`] low [`SAG_READINGS`] times running, the core
+//! voltage is lowered a [`CUT_STEP`], and again for each further run of
+//! low readings, down to the bottom of the board's range.
+//!
+//! The board is then marked [`PowerLimited`], which its status shows until
+//! the daemon restarts; the cut stays until the board is next started. A
+//! warm-up stopped by a sagging input (see [`super::warmup`]) marks it too.
+//!
+//! Only the input voltage is used. The bitaxe-raw management firmware
+//! reports nothing of USB power negotiation or current limits.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use super::TelemetrySnapshot;
+
+/// Fraction below its nominal voltage the input may read before it counts
+/// as sagging.
+pub const SAG_FRACTION: f32 = 0.05;
+
+/// Consecutive sagging readings before the core voltage is cut.
+pub const SAG_READINGS: u32 = 2;
+
+/// Core voltage taken off per cut, in volts.
+pub const CUT_STEP: f32 = 0.02;
+
+/// What a board's input supply should read.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InputSupply {
+    /// Nominal input voltage in volts, e.g. 5.0 for a Bitaxe Gamma
+    pub nominal_volts: f32,
+}
+
+/// A board found drawing more than its supply gives.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PowerLimited {
+    /// When the sag was first seen, Unix seconds
+    pub since: u64,
+    /// Lowest input voltage read, in volts
+    pub input_voltage: f32,
+    /// Power the board drew when the sag was first seen, in watts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sagged_at_watts: Option<f32>,
+    /// Core voltage the board was cut to, if it was
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub core_voltage: Option<f32>,
+}
+
+/// Watches one board's input voltage.
+#[derive(Debug)]
+pub struct SupplyMonitor {
+    supply: InputSupply,
+    low_readings: u32,
+    limited: Option<PowerLimited>,
+}
+
+impl InputSupply {
+    /// Whether `volts` at the input counts as sagging.
+    pub fn sagging(&self, volts: f32) -> bool {
+        volts < self.nominal_volts * (1.0 - SAG_FRACTION)
+    }
+}
+
+impl PowerLimited {
+    /// Marked now, from a reading of `input_voltage` while drawing
+    /// `watts`.
+    pub fn new(input_voltage: f32, watts: Option<f32>) -> Self {
+        Self {
+            since: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            input_voltage,
+            sagged_at_watts: watts,
+            core_voltage: None,
+        }
+    }
+
+    /// Note a reading of `input_voltage`, keeping the lowest.
+    fn saw(&mut self, input_voltage: f32) {
+        self.input_voltage = self.input_voltage.min(input_voltage);
+    }
+}
+
+impl SupplyMonitor {
+    /// Watch a board expected to see `supply`, already marked `limited`
+    /// by an earlier incarnation or its warm-up.
+    pub fn new(supply: InputSupply, limited: Option<PowerLimited>) -> Self {
+        Self {
+            supply,
+            low_readings: 0,
+            limited,
+        }
+    }
+
+    /// Take in a reading. Returns the core voltage to cut the board to, if
+    /// it's time for a cut; the board is never cut below `floor`.
+    pub fn observe(&mut self, reading: &TelemetrySnapshot, floor: Option<f32>) -> Option<f32> {
+        let volts = reading.input_voltage?;
+        if !self.supply.sagging(volts) {
+            self.low_readings = 0;
+            return None;
+        }
+        self.low_readings += 1;
+        if self.low_readings < SAG_READINGS {
+            return None;
+        }
+        self.low_readings = 0;
+        let limited = self
+            .limited
+            .get_or_insert_with(|| PowerLimited::new(volts, reading.power_watts));
+        limited.saw(volts);
+
+        let core = reading.core_voltage?;
+        let mut target = core - CUT_STEP;
+        if let Some(floor) = floor {
+            target = target.max(floor);
+        }
+        // At the floor already
+        if core - target < CUT_STEP / 2.0 {
+            return None;
+        }
+        limited.core_voltage = Some(target);
+        Some(target)
+    }
+
+    /// Whether, and how, the board has been found power limited.
+    pub fn limited(&self) -> Option<PowerLimited> {
+        self.limited
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(input: f32, core: f32) -> TelemetrySnapshot {
+        TelemetrySnapshot {
+            input_voltage: Some(input),
+            core_voltage: Some(core),
+            power_watts: Some(18.0),
+            ..TelemetrySnapshot::new()
+        }
+    }
+
+    #[test]
+    fn test_sustained_sag_cuts_core_voltage() {
+        let supply = InputSupply { nominal_volts: 5.0 };
+        let mut monitor = SupplyMonitor::new(supply, None);
+
+        assert_eq!(monitor.observe(&reading(4.9, 1.2), Some(1.0)), None);
+        assert_eq!(monitor.limited(), None);
+
+        // A single dip isn't acted on
+        assert_eq!(monitor.observe(&reading(4.6, 1.2), Some(1.0)), None);
+        assert_eq!(monitor.observe(&reading(4.9, 1.2), Some(1.0)), None);
+        assert_eq!(monitor.limited(), None);
+
+        assert_eq!(monitor.observe(&reading(4.7, 1.2), Some(1.0)), None);
+        let cut = monitor.observe(&reading(4.5, 1.2), Some(1.0)).unwrap();
+        assert!((cut - 1.18).abs() < 1e-6);
+        let limited = monitor.limited().unwrap();
+        assert_eq!(limited.input_voltage, 4.5);
+        assert_eq!(limited.sagged_at_watts, Some(18.0));
+        assert_eq!(limited.core_voltage, Some(cut));
+
+        // Never below the board's range
+        monitor.observe(&reading(4.5, 1.015), Some(1.0));
+        assert_eq!(monitor.observe(&reading(4.5, 1.015), Some(1.0)), Some(1.0));
+        monitor.observe(&reading(4.5, 1.0), Some(1.0));
+        assert_eq!(monitor.observe(&reading(4.5, 1.0), Some(1.0)), None);
+    }
+}
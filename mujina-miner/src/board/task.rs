@@ -0,0 +1,1733 @@
+//! Per-board task and its supervisor.
+//!
+//! Each board the backplane connects runs on its own Tokio task, which
+//! creates the board, hands its hash threads to the scheduler (tagged with
+//! the board's ID, by which the scheduler pauses boards), and then
+//! serves the backplane's requests for it. The board's own workers (serial
+//! reader, hash thread actor) hang off that task, so a slow initialization
+//! or a wedged I2C read on one board never holds up the backplane or the
+//! other boards.
+//!
+//! The task also polls the board's sensors every [`TELEMETRY_INTERVAL`] and
+//! caches the [`TelemetrySnapshot`]. Status requests are answered from the
+//! cache without touching the hardware, so sensor traffic stays the same
+//! however often the API is asked.
+//!
+//! Shutting a board down runs each [`ShutdownStage`] in turn under its own
+//! timeout: park the chips, turn off the core voltage, slow the fans. A stage
+//! that hangs is abandoned so the next still runs; above all, the core
+//! voltage is turned off even if the chips couldn't be parked.
+//!
+//! The task runs under a supervisor. If it panics, fails to bring the board
+//! up, or the board reports a fault (see [`Board::take_fault_receiver`]), the
+//! supervisor waits out a backoff and creates the board afresh from its
+//! factory. Restarts show in the board's [`BoardHealth`]; a board that fails
+//! [`RestartPolicy::max_restarts`] times in a row without ever running for
+//! [`Backoff::stable_after`] is given up on and marked failed. A board whose
+//! serial port another process holds (see [`PortBusy`]) isn't failing: it
+//! waits, retrying every [`RestartPolicy::port_busy_retry`] for as long as
+//! it takes, and the error naming the
+//! holder stays on the handle for the API to show. A board whose firmware
+//! is too old (see [`crate::error::Error::FirmwareTooOld`]) is marked failed
+//! at once, since no restart will change that.
+//!
+//! Every incarnation of the board gets a new generation number. Requests
+//! carry the generation the caller saw running, and an incarnation refuses
+//! those meant for an earlier one with [`BoardError::Restarted`], so a
+//! request queued across a restart fails fast rather than acting on a board
+//! the caller never saw.
+//!
+//! Each incarnation applies the board's stored settings (see
+//! [`crate::settings`]) once it's running, so overrides outlive restarts.
+//! A stored operating point is ramped up to (see [`super::warmup`]) rather
+//! than jumped to. Each telemetry poll's input voltage is checked for a
+//! supply that can't keep up (see [`super::supply`]), which cuts the core
+//! voltage and marks the board power limited.
+//!
+//! Incarnations coming up and failing are reported to the backplane as
+//! [`BoardLifecycle`] events for the board's history (see
+//! [`super::history`]).
+
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use tokio::{
+    sync::{mpsc, oneshot, watch, Mutex},
+    task::JoinHandle,
+    time::Instant,
+};
+
+use super::{
+    history::{BoardEvent, BoardLifecycle, HistoryEntry},
+    identity::BoardIdentity,
+    supply::{PowerLimited, SupplyMonitor},
+    warmup::{self, Anomaly, Warmup, WarmupError},
+    Board, BoardError, BoxFuture, FanMode, OperatingPoint, ShutdownStage, TelemetrySnapshot,
+};
+use crate::{
+    asic::{
+        hash_thread::{
+            HashTask, HashThread, HashThreadCapabilities, HashThreadError, HashThreadEvent,
+            HashThreadStatus,
+        },
+        nonce_map::NonceMap,
+        self_test,
+    },
+    config::RecoveryConfig,
+    peripheral::scan::ScannedDevice,
+    settings::BoardSettings,
+    supervisor::{self, Backoff, Exit},
+    tracing::prelude::*,
+    transport::{PortBusy, UsbDeviceInfo},
+};
+
+/// Consecutive failures before a board is given up on, by default.
+pub const MAX_RESTARTS: u32 = 5;
+
+/// How often a board waiting on a busy port tries it again, by default.
+pub const PORT_BUSY_RETRY: Duration = Duration::from_secs(5);
+
+/// How long an unplugged board is given to come back, by default.
+pub const RECONNECT_GRACE: Duration = Duration::from_secs(5);
+
+/// How often a running board's sensors are read.
+pub const TELEMETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long a sensor poll may take before it's abandoned, so a hung bus
+/// doesn't stop the board answering requests.
+const TELEMETRY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long each shutdown stage may take before it's abandoned.
+const PARK_TIMEOUT: Duration = Duration::from_secs(2);
+const POWER_OFF_TIMEOUT: Duration = Duration::from_secs(2);
+const COOL_DOWN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Creates a board; called again for every restart.
+pub type MakeBoardFn =
+    Box<dyn Fn() -> BoxFuture<'static, crate::error::Result<Box<dyn Board + Send>>> + Send + Sync>;
+
+/// When a failing board is restarted, and when it's given up on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RestartPolicy {
+    /// Delays between restarts.
+    pub backoff: Backoff,
+
+    /// Consecutive failures before the board is given up on.
+    pub max_restarts: u32,
+
+    /// How often a board waiting on a busy port tries it again.
+    pub port_busy_retry: Duration,
+
+    /// How long an unplugged board is given to come back before it's shut
+    /// down. Zero shuts it down at once.
+    pub reconnect_grace: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            backoff: Backoff::default(),
+            max_restarts: MAX_RESTARTS,
+            port_busy_retry: PORT_BUSY_RETRY,
+            reconnect_grace: RECONNECT_GRACE,
+        }
+    }
+}
+
+impl From<&RecoveryConfig> for RestartPolicy {
+    fn from(config: &RecoveryConfig) -> Self {
+        Self {
+            backoff: Backoff {
+                initial: Duration::from_secs(config.backoff_initial_secs),
+                max: Duration::from_secs(config.backoff_max_secs),
+                stable_after: Duration::from_secs(config.stable_after_secs),
+            },
+            max_restarts: config.max_restarts,
+            port_busy_retry: Duration::from_secs(config.port_busy_retry_secs),
+            reconnect_grace: Duration::from_secs(config.reconnect_grace_secs),
+        }
+    }
+}
+
+/// Supervision state of a board.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoardHealth {
+    /// Creating and initializing the board
+    Starting,
+    /// Board is up and its threads are with the scheduler
+    Running,
+    /// Board crashed or failed to start, and will be recreated
+    Restarting { restarts: u32 },
+    /// Board's port is held by another process; retried until it's free
+    Waiting,
+    /// Board failed too many times in a row to keep trying
+    Failed,
+    /// Board was shut down on request
+    Stopped,
+}
+
+/// Requests the backplane makes of a board.
+enum BoardCommand {
+    SetOperatingPoint {
+        point: OperatingPoint,
+        reply_tx: oneshot::Sender<Result<(), BoardError>>,
+    },
+    SetFanMode {
+        mode: FanMode,
+        reply_tx: oneshot::Sender<Result<(), BoardError>>,
+    },
+    ResetChips {
+        reply_tx: oneshot::Sender<Result<usize, BoardError>>,
+    },
+    Identify {
+        reply_tx: oneshot::Sender<Result<(), BoardError>>,
+    },
+    StoreRegulatorConfig {
+        reply_tx: oneshot::Sender<Result<(), BoardError>>,
+    },
+    ReadNonceMap {
+        reply_tx: oneshot::Sender<Result<NonceMap, BoardError>>,
+    },
+    ScanI2c {
+        reply_tx: oneshot::Sender<Result<Vec<ScannedDevice>, BoardError>>,
+    },
+    ReadIdentity {
+        reply_tx: oneshot::Sender<Result<Option<BoardIdentity>, BoardError>>,
+    },
+    WriteIdentity {
+        identity: BoardIdentity,
+        reply_tx: oneshot::Sender<Result<(), BoardError>>,
+    },
+    ReadPower {
+        reply_tx: oneshot::Sender<Option<f32>>,
+    },
+    FirmwarePort {
+        reply_tx: oneshot::Sender<Result<Option<String>, BoardError>>,
+    },
+    Reconnect {
+        device: UsbDeviceInfo,
+        reply_tx: oneshot::Sender<Result<(), BoardError>>,
+    },
+    Shutdown {
+        reply_tx: oneshot::Sender<Result<(), BoardError>>,
+    },
+}
+
+/// A command and the incarnation it's meant for.
+struct Request {
+    /// Generation the sender saw running, or None for whichever incarnation
+    /// gets it
+    generation: Option<u64>,
+    command: BoardCommand,
+}
+
+/// The backplane's handle on a supervised board.
+pub struct BoardHandle {
+    name: String,
+    command_tx: mpsc::Sender<Request>,
+    health_rx: watch::Receiver<BoardHealth>,
+    generation_rx: watch::Receiver<u64>,
+    error_rx: watch::Receiver<Option<String>>,
+    telemetry_rx: watch::Receiver<Option<TelemetrySnapshot>>,
+    power_limited_rx: watch::Receiver<Option<PowerLimited>>,
+    settings_tx: watch::Sender<BoardSettings>,
+    task: JoinHandle<()>,
+}
+
+/// Everything a board incarnation needs besides the board itself.
+#[derive(Clone)]
+struct BoardContext {
+    name: String,
+    id: String,
+    scheduler_tx: mpsc::Sender<Box<dyn HashThread>>,
+    commands: Arc<Mutex<mpsc::Receiver<Request>>>,
+    health_tx: Arc<watch::Sender<BoardHealth>>,
+    generation_tx: Arc<watch::Sender<u64>>,
+    error_tx: Arc<watch::Sender<Option<String>>>,
+    telemetry_tx: Arc<watch::Sender<Option<TelemetrySnapshot>>>,
+    power_limited_tx: Arc<watch::Sender<Option<PowerLimited>>>,
+    settings_rx: watch::Receiver<BoardSettings>,
+    warmup: Warmup,
+    lifecycle_tx: mpsc::UnboundedSender<BoardLifecycle>,
+}
+
+/// A board's hash thread, as handed to the scheduler: the thread, tagged
+/// with the board's ID.
+struct BoardThread {
+    board_id: String,
+    inner: Box<dyn HashThread>,
+}
+
+impl BoardCommand {
+    /// Answer a command the board won't carry out, with `error` where the
+    /// reply has room for one.
+    fn refuse(self, error: fn() -> BoardError) {
+        match self {
+            Self::SetOperatingPoint { reply_tx, .. } => {
+                let _ = reply_tx.send(Err(error()));
+            }
+            Self::SetFanMode { reply_tx, .. } => {
+                let _ = reply_tx.send(Err(error()));
+            }
+            Self::ResetChips { reply_tx } => {
+                let _ = reply_tx.send(Err(error()));
+            }
+            Self::Identify { reply_tx } => {
+                let _ = reply_tx.send(Err(error()));
+            }
+            Self::StoreRegulatorConfig { reply_tx } => {
+                let _ = reply_tx.send(Err(error()));
+            }
+            Self::ReadNonceMap { reply_tx } => {
+                let _ = reply_tx.send(Err(error()));
+            }
+            Self::ScanI2c { reply_tx } => {
+                let _ = reply_tx.send(Err(error()));
+            }
+            Self::ReadIdentity { reply_tx } => {
+                let _ = reply_tx.send(Err(error()));
+            }
+            Self::WriteIdentity { reply_tx, .. } => {
+                let _ = reply_tx.send(Err(error()));
+            }
+            Self::ReadPower { reply_tx } => {
+                let _ = reply_tx.send(None);
+            }
+            Self::FirmwarePort { reply_tx } => {
+                let _ = reply_tx.send(Err(error()));
+            }
+            Self::Reconnect { reply_tx, .. } => {
+                let _ = reply_tx.send(Err(error()));
+            }
+            Self::Shutdown { reply_tx } => {
+                let _ = reply_tx.send(Ok(()));
+            }
+        }
+    }
+}
+
+impl BoardContext {
+    /// Report an event for the board's history.
+    fn record(&self, event: BoardEvent) {
+        // The backplane gone means the daemon is stopping
+        let _ = self.lifecycle_tx.send(BoardLifecycle {
+            id: self.id.clone(),
+            entry: HistoryEntry::now(event),
+        });
+    }
+}
+
+impl BoardHandle {
+    /// Start a supervised task for the board `make_board` creates.
+    ///
+    /// `name` and `id` identify the board in logs until it exists to ask.
+    /// `settings` are applied each time the board comes up, its operating
+    /// point ramped up to as `warmup` says. Incarnations
+    /// starting and failing are reported on `lifecycle_tx`, which is
+    /// unbounded so a board never waits on the backplane to report one.
+    #[expect(
+        clippy::too_many_arguments,
+        reason = "each is the backplane's setting for this board"
+    )]
+    pub fn spawn(
+        name: impl Into<String>,
+        id: impl Into<String>,
+        make_board: MakeBoardFn,
+        scheduler_tx: mpsc::Sender<Box<dyn HashThread>>,
+        policy: RestartPolicy,
+        settings: BoardSettings,
+        warmup: Warmup,
+        lifecycle_tx: mpsc::UnboundedSender<BoardLifecycle>,
+    ) -> Self {
+        let name = name.into();
+        let (command_tx, command_rx) = mpsc::channel(8);
+        let (health_tx, health_rx) = watch::channel(BoardHealth::Starting);
+        let (generation_tx, generation_rx) = watch::channel(0);
+        let (error_tx, error_rx) = watch::channel(None);
+        let (telemetry_tx, telemetry_rx) = watch::channel(None);
+        let (power_limited_tx, power_limited_rx) = watch::channel(None);
+        let (settings_tx, settings_rx) = watch::channel(settings);
+
+        let context = BoardContext {
+            name: name.clone(),
+            id: id.into(),
+            scheduler_tx,
+            commands: Arc::new(Mutex::new(command_rx)),
+            health_tx: Arc::new(health_tx),
+            generation_tx: Arc::new(generation_tx),
+            error_tx: Arc::new(error_tx),
+            telemetry_tx: Arc::new(telemetry_tx),
+            power_limited_tx: Arc::new(power_limited_tx),
+            settings_rx,
+            warmup,
+            lifecycle_tx,
+        };
+        let task = tokio::spawn(supervise(context, make_board, policy));
+
+        Self {
+            name,
+            command_tx,
+            health_rx,
+            generation_rx,
+            error_rx,
+            telemetry_rx,
+            power_limited_rx,
+            settings_tx,
+            task,
+        }
+    }
+
+    /// Board model, as given at spawn.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Current supervision state.
+    pub fn health(&self) -> BoardHealth {
+        *self.health_rx.borrow()
+    }
+
+    /// Incarnation of the board: 1 for the first, and one more each time
+    /// it's recreated.
+    pub fn generation(&self) -> u64 {
+        *self.generation_rx.borrow()
+    }
+
+    /// Why the board last failed to start or stopped running, until it
+    /// next comes up.
+    pub fn last_error(&self) -> Option<String> {
+        self.error_rx.borrow().clone()
+    }
+
+    /// Latest sensor readings, if the board is running and has been polled.
+    pub fn telemetry(&self) -> Option<TelemetrySnapshot> {
+        self.telemetry_rx.borrow().clone()
+    }
+
+    /// How the board was found short of power, if it was. Kept across
+    /// restarts, since a brown-out is a likely cause of one.
+    pub fn power_limited(&self) -> Option<PowerLimited> {
+        *self.power_limited_rx.borrow()
+    }
+
+    /// Retune the board. Fails without waiting if the board isn't running.
+    pub async fn set_operating_point(&self, point: OperatingPoint) -> Result<(), BoardError> {
+        let generation = self.running_generation()?;
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.request(
+            Some(generation),
+            BoardCommand::SetOperatingPoint { point, reply_tx },
+        )
+        .await;
+        reply_rx.await.unwrap_or_else(|_| Err(not_running()))
+    }
+
+    /// Set how the board drives its fans. Fails without waiting if the
+    /// board isn't running.
+    pub async fn set_fan_mode(&self, mode: FanMode) -> Result<(), BoardError> {
+        let generation = self.running_generation()?;
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.request(
+            Some(generation),
+            BoardCommand::SetFanMode { mode, reply_tx },
+        )
+        .await;
+        reply_rx.await.unwrap_or_else(|_| Err(not_running()))
+    }
+
+    /// Settings applied each time the board comes up.
+    pub fn settings(&self) -> BoardSettings {
+        self.settings_tx.borrow().clone()
+    }
+
+    /// Replace the settings applied each time the board comes up. Doesn't
+    /// touch a board that's already running.
+    pub fn set_settings(&self, settings: BoardSettings) {
+        self.settings_tx.send_replace(settings);
+    }
+
+    /// Reset the board's chips in place (see [`Board::reset_chips`]). Fails
+    /// without waiting if the board isn't running.
+    pub async fn reset_chips(&self) -> Result<usize, BoardError> {
+        let generation = self.running_generation()?;
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.request(Some(generation), BoardCommand::ResetChips { reply_tx })
+            .await;
+        reply_rx.await.unwrap_or_else(|_| Err(not_running()))
+    }
+
+    /// Signal so the board can be picked out (see [`Board::identify`]).
+    /// Fails without waiting if the board isn't running.
+    pub async fn identify(&self) -> Result<(), BoardError> {
+        let generation = self.running_generation()?;
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.request(Some(generation), BoardCommand::Identify { reply_tx })
+            .await;
+        reply_rx.await.unwrap_or_else(|_| Err(not_running()))
+    }
+
+    /// Store the regulator's settings in its NVM (see
+    /// [`Board::store_regulator_config`]). Fails without waiting if the
+    /// board isn't running.
+    pub async fn store_regulator_config(&self) -> Result<(), BoardError> {
+        let generation = self.running_generation()?;
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.request(
+            Some(generation),
+            BoardCommand::StoreRegulatorConfig { reply_tx },
+        )
+        .await;
+        reply_rx.await.unwrap_or_else(|_| Err(not_running()))
+    }
+
+    /// Nonces found per core (see [`Board::nonce_map`]). Fails without
+    /// waiting if the board isn't running.
+    pub async fn nonce_map(&self) -> Result<NonceMap, BoardError> {
+        let generation = self.running_generation()?;
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.request(Some(generation), BoardCommand::ReadNonceMap { reply_tx })
+            .await;
+        reply_rx.await.unwrap_or_else(|_| Err(not_running()))
+    }
+
+    /// Devices on the board's I2C bus (see [`Board::scan_i2c`]). Fails
+    /// without waiting if the board isn't running.
+    pub async fn scan_i2c(&self) -> Result<Vec<ScannedDevice>, BoardError> {
+        let generation = self.running_generation()?;
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.request(Some(generation), BoardCommand::ScanI2c { reply_tx })
+            .await;
+        reply_rx.await.unwrap_or_else(|_| Err(not_running()))
+    }
+
+    /// Identity the board stores about itself (see [`Board::identity`]).
+    /// Fails without waiting if the board isn't running.
+    pub async fn identity(&self) -> Result<Option<BoardIdentity>, BoardError> {
+        let generation = self.running_generation()?;
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.request(Some(generation), BoardCommand::ReadIdentity { reply_tx })
+            .await;
+        reply_rx.await.unwrap_or_else(|_| Err(not_running()))
+    }
+
+    /// Store the board's identity (see [`Board::write_identity`]). Fails
+    /// without waiting if the board isn't running.
+    pub async fn write_identity(&self, identity: BoardIdentity) -> Result<(), BoardError> {
+        let generation = self.running_generation()?;
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.request(
+            Some(generation),
+            BoardCommand::WriteIdentity { identity, reply_tx },
+        )
+        .await;
+        reply_rx.await.unwrap_or_else(|_| Err(not_running()))
+    }
+
+    /// Power draw in watts, if the board is running and can measure it.
+    pub async fn power_watts(&self) -> Option<f32> {
+        let generation = self.running_generation().ok()?;
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.request(Some(generation), BoardCommand::ReadPower { reply_tx })
+            .await;
+        reply_rx.await.ok().flatten()
+    }
+
+    /// Control port of the board's management controller, for firmware
+    /// updates (see [`Board::firmware_port`]). Fails without waiting if the
+    /// board isn't running.
+    pub async fn firmware_port(&self) -> Result<Option<String>, BoardError> {
+        let generation = self.running_generation()?;
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.request(Some(generation), BoardCommand::FirmwarePort { reply_tx })
+            .await;
+        reply_rx.await.unwrap_or_else(|_| Err(not_running()))
+    }
+
+    /// Move the running board over to `device`, the USB device it came
+    /// back as after a disconnect (see [`Board::reconnect`]), keeping its
+    /// hash threads. Fails without waiting if the board isn't running.
+    pub async fn reconnect(&self, device: UsbDeviceInfo) -> Result<(), BoardError> {
+        let generation = self.running_generation()?;
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.request(
+            Some(generation),
+            BoardCommand::Reconnect { device, reply_tx },
+        )
+        .await;
+        reply_rx.await.unwrap_or_else(|_| Err(not_running()))
+    }
+
+    /// Shut the board down and wait for its task to finish.
+    ///
+    /// A board still starting is shut down once it's up; one between
+    /// restarts just isn't restarted.
+    pub async fn shutdown(self) -> Result<(), BoardError> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.request(None, BoardCommand::Shutdown { reply_tx })
+            .await;
+        let result = reply_rx.await.unwrap_or(Ok(()));
+        drop(self.command_tx);
+        let _ = self.task.await;
+        result
+    }
+
+    /// Generation of the running incarnation, or an error if none is
+    /// running.
+    fn running_generation(&self) -> Result<u64, BoardError> {
+        // Read first: an incarnation is running only after its generation
+        // is set, so a stale read is refused as restarted, never misapplied
+        let generation = self.generation();
+        if self.health() != BoardHealth::Running {
+            return Err(not_running());
+        }
+        Ok(generation)
+    }
+
+    async fn request(&self, generation: Option<u64>, command: BoardCommand) {
+        let request = Request {
+            generation,
+            command,
+        };
+        // A closed channel drops the reply sender, which the caller sees
+        if let Err(mpsc::error::SendError(request)) = self.command_tx.send(request).await {
+            request.command.refuse(not_running);
+        }
+    }
+}
+
+/// Run board incarnations until one stops on request or the board is given
+/// up on.
+#[async_trait]
+impl HashThread for BoardThread {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn board_id(&self) -> Option<&str> {
+        Some(&self.board_id)
+    }
+
+    fn capabilities(&self) -> &HashThreadCapabilities {
+        self.inner.capabilities()
+    }
+
+    async fn update_task(
+        &mut self,
+        new_task: HashTask,
+    ) -> Result<Option<HashTask>, HashThreadError> {
+        self.inner.update_task(new_task).await
+    }
+
+    async fn replace_task(
+        &mut self,
+        new_task: HashTask,
+    ) -> Result<Option<HashTask>, HashThreadError> {
+        self.inner.replace_task(new_task).await
+    }
+
+    async fn go_idle(&mut self) -> Result<Option<HashTask>, HashThreadError> {
+        self.inner.go_idle().await
+    }
+
+    fn take_event_receiver(&mut self) -> Option<mpsc::Receiver<HashThreadEvent>> {
+        self.inner.take_event_receiver()
+    }
+
+    fn status(&self) -> HashThreadStatus {
+        self.inner.status()
+    }
+}
+
+async fn supervise(context: BoardContext, make_board: MakeBoardFn, policy: RestartPolicy) {
+    let backoff = policy.backoff;
+    let mut delay = backoff.initial;
+    let mut restarts: u32 = 0;
+    let mut waiting = false;
+
+    loop {
+        context
+            .generation_tx
+            .send_modify(|generation| *generation += 1);
+        context.health_tx.send_replace(BoardHealth::Starting);
+        let started = Instant::now();
+        let exit = supervisor::run_to_exit(run_board(context.clone(), make_board())).await;
+        let uptime = started.elapsed();
+
+        // Readings from a board that's gone would only mislead
+        context.telemetry_tx.send_replace(None);
+
+        if let Exit::Returned = exit {
+            context.health_tx.send_replace(BoardHealth::Stopped);
+            return;
+        }
+        let error = exit_error(&exit);
+        context.error_tx.send_replace(error.clone());
+
+        // Someone else has the board; that doesn't count toward giving up
+        if let Some(busy) = port_busy(&exit) {
+            if !waiting {
+                context.record(BoardEvent::Failed {
+                    reason: busy.to_string(),
+                });
+                warn!(board = %context.name, id = %context.id, error = %busy, "Board's port is busy; waiting for it.");
+            }
+            waiting = true;
+            context.health_tx.send_replace(BoardHealth::Waiting);
+            if refuse_until_shutdown(&context, Some(policy.port_busy_retry)).await {
+                context.health_tx.send_replace(BoardHealth::Stopped);
+                return;
+            }
+            continue;
+        }
+        waiting = false;
+        context.record(BoardEvent::Failed {
+            reason: error.unwrap_or_default(),
+        });
+
+        // Restarting won't update the firmware
+        if let Some(too_old) = firmware_too_old(&exit) {
+            error!(
+                board = %context.name,
+                id = %context.id,
+                error = %too_old,
+                "Board firmware too old; giving up on it."
+            );
+            context.health_tx.send_replace(BoardHealth::Failed);
+            refuse_until_shutdown(&context, None).await;
+            return;
+        }
+        supervisor::log_unexpected_exit(&context.name, &exit, uptime);
+
+        if uptime >= backoff.stable_after {
+            delay = backoff.initial;
+            restarts = 0;
+        }
+
+        restarts += 1;
+        if restarts > policy.max_restarts {
+            error!(
+                board = %context.name,
+                id = %context.id,
+                restarts = restarts - 1,
+                "Board keeps failing; giving up on it."
+            );
+            context.health_tx.send_replace(BoardHealth::Failed);
+            refuse_until_shutdown(&context, None).await;
+            return;
+        }
+
+        context
+            .health_tx
+            .send_replace(BoardHealth::Restarting { restarts });
+        warn!(
+            board = %context.name,
+            id = %context.id,
+            restarts,
+            delay_secs = delay.as_secs_f64(),
+            "Restarting board after delay."
+        );
+
+        if refuse_until_shutdown(&context, Some(delay)).await {
+            context.health_tx.send_replace(BoardHealth::Stopped);
+            return;
+        }
+        delay = (delay * 2).min(backoff.max);
+    }
+}
+
+/// What went wrong, for a board that stopped other than on request.
+fn exit_error(exit: &Exit) -> Option<String> {
+    match exit {
+        Exit::Returned => None,
+        Exit::Failed(e) => Some(format!("{:#}", e)),
+        Exit::Panicked(msg) => Some(format!("panicked: {}", msg)),
+        Exit::Cancelled => Some("cancelled".to_string()),
+    }
+}
+
+/// The busy port, if that's why the board couldn't be created.
+fn port_busy(exit: &Exit) -> Option<&PortBusy> {
+    let Exit::Failed(e) = exit else {
+        return None;
+    };
+    match e.downcast_ref::<crate::error::Error>() {
+        Some(crate::error::Error::PortBusy(busy)) => Some(busy),
+        _ => None,
+    }
+}
+
+/// The error, if the board couldn't be created because its firmware is
+/// too old.
+fn firmware_too_old(exit: &Exit) -> Option<&crate::error::Error> {
+    let Exit::Failed(e) = exit else {
+        return None;
+    };
+    match e.downcast_ref::<crate::error::Error>() {
+        Some(too_old @ crate::error::Error::FirmwareTooOld { .. }) => Some(too_old),
+        _ => None,
+    }
+}
+
+/// Refuse commands while no board is running, for `delay` or indefinitely.
+/// Returns whether shutdown was requested.
+async fn refuse_until_shutdown(context: &BoardContext, delay: Option<std::time::Duration>) -> bool {
+    let mut commands = context.commands.lock().await;
+    let sleep = async {
+        match delay {
+            Some(delay) => tokio::time::sleep(delay).await,
+            None => std::future::pending().await,
+        }
+    };
+    tokio::pin!(sleep);
+
+    loop {
+        tokio::select! {
+            _ = &mut sleep => return false,
+            request = commands.recv() => match request.map(|request| request.command) {
+                Some(BoardCommand::Shutdown { reply_tx }) => {
+                    let _ = reply_tx.send(Ok(()));
+                    return true;
+                }
+                Some(command) => command.refuse(not_running),
+                None => return true,
+            }
+        }
+    }
+}
+
+/// One incarnation of a board: bring it up, then serve commands until asked
+/// to shut down. Returns an error if the board couldn't be brought up.
+async fn run_board(
+    context: BoardContext,
+    board: BoxFuture<'static, crate::error::Result<Box<dyn Board + Send>>>,
+) -> anyhow::Result<()> {
+    let generation = *context.generation_tx.borrow();
+    let mut board = board.await?;
+
+    let threads = match create_threads(board.as_mut()).await {
+        Ok(threads) => threads,
+        Err(e) => {
+            if let Err(e) = shut_down(&context, board.as_mut()).await {
+                warn!(board = %context.name, id = %context.id, error = %e, "Failed to shutdown board");
+            }
+            return Err(e.into());
+        }
+    };
+
+    let thread_count = threads.len();
+    for thread in threads {
+        let thread = Box::new(BoardThread {
+            board_id: context.id.clone(),
+            inner: thread,
+        });
+        if let Err(e) = context.scheduler_tx.send(thread).await {
+            error!(
+                board = %context.name,
+                error = %e,
+                "Failed to send thread to scheduler"
+            );
+            break;
+        }
+    }
+
+    context.health_tx.send_replace(BoardHealth::Running);
+    context.error_tx.send_replace(None);
+    info!(
+        board = %context.name,
+        id = %context.id,
+        threads = thread_count,
+        "Board started."
+    );
+    context.record(if generation == 1 {
+        BoardEvent::Initialized
+    } else {
+        BoardEvent::Reinitialized { generation }
+    });
+    apply_settings(&context, board.as_mut()).await;
+    let mut supply = board
+        .input_supply()
+        .map(|supply| SupplyMonitor::new(supply, *context.power_limited_tx.borrow()));
+
+    let mut faults = board.take_fault_receiver();
+    let mut commands = context.commands.lock().await;
+    let mut telemetry_interval = tokio::time::interval(TELEMETRY_INTERVAL);
+    telemetry_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+    loop {
+        let request = tokio::select! {
+            request = commands.recv() => request,
+            Some(reason) = async {
+                match &mut faults {
+                    Some(rx) => rx.recv().await,
+                    None => std::future::pending().await,
+                }
+            } => {
+                // Recreated by the supervisor, like a crash
+                warn!(board = %context.name, id = %context.id, %reason, "Board fault; reinitializing");
+                if let Err(e) = shut_down(&context, board.as_mut()).await {
+                    warn!(board = %context.name, id = %context.id, error = %e, "Failed to shutdown board");
+                }
+                anyhow::bail!("board fault: {}", reason);
+            }
+            _ = telemetry_interval.tick() => {
+                match tokio::time::timeout(TELEMETRY_TIMEOUT, board.telemetry()).await {
+                    Ok(snapshot) => {
+                        if let Some(monitor) = &mut supply {
+                            check_supply(&context, board.as_mut(), monitor, &snapshot).await;
+                        }
+                        context.telemetry_tx.send_replace(Some(snapshot));
+                    }
+                    Err(_) => {
+                        // The cached snapshot's age shows it's going stale
+                        warn!(board = %context.name, id = %context.id, "Telemetry poll timed out");
+                    }
+                }
+                continue;
+            }
+        };
+        let Some(Request {
+            generation: meant_for,
+            command,
+        }) = request
+        else {
+            break;
+        };
+        if meant_for.is_some_and(|meant_for| meant_for != generation) {
+            debug!(board = %context.name, id = %context.id, generation, ?meant_for, "Refusing request meant for an earlier incarnation");
+            command.refuse(restarted);
+            continue;
+        }
+
+        match command {
+            BoardCommand::SetOperatingPoint { point, reply_tx } => {
+                let result = match point.check_voltage(board.voltage_range()) {
+                    Ok(()) => board.set_operating_point(point).await,
+                    Err(e) => Err(e),
+                };
+                let _ = reply_tx.send(result);
+            }
+            BoardCommand::SetFanMode { mode, reply_tx } => {
+                let _ = reply_tx.send(board.set_fan_mode(mode).await);
+            }
+            BoardCommand::ResetChips { reply_tx } => {
+                let _ = reply_tx.send(board.reset_chips().await);
+            }
+            BoardCommand::Identify { reply_tx } => {
+                let _ = reply_tx.send(board.identify().await);
+            }
+            BoardCommand::StoreRegulatorConfig { reply_tx } => {
+                let _ = reply_tx.send(board.store_regulator_config().await);
+            }
+            BoardCommand::ReadNonceMap { reply_tx } => {
+                let _ = reply_tx.send(board.nonce_map().await);
+            }
+            BoardCommand::ScanI2c { reply_tx } => {
+                let _ = reply_tx.send(board.scan_i2c().await);
+            }
+            BoardCommand::ReadIdentity { reply_tx } => {
+                let _ = reply_tx.send(board.identity().await);
+            }
+            BoardCommand::WriteIdentity { identity, reply_tx } => {
+                let _ = reply_tx.send(board.write_identity(identity).await);
+            }
+            BoardCommand::ReadPower { reply_tx } => {
+                let _ = reply_tx.send(board.power_watts().await);
+            }
+            BoardCommand::FirmwarePort { reply_tx } => {
+                let _ = reply_tx.send(Ok(board.firmware_port()));
+            }
+            BoardCommand::Reconnect { device, reply_tx } => {
+                let result = board.reconnect(&device).await;
+                if result.is_ok() {
+                    context.record(BoardEvent::Reconnected);
+                }
+                let _ = reply_tx.send(result);
+            }
+            BoardCommand::Shutdown { reply_tx } => {
+                let _ = reply_tx.send(shut_down(&context, board.as_mut()).await);
+                return Ok(());
+            }
+        }
+    }
+
+    // The handle is gone; leave the hardware safe regardless
+    shut_down(&context, board.as_mut()).await?;
+    Ok(())
+}
+
+/// Create the board's hash threads, self-testing them if the board asks.
+/// A thread that fails fails the board's initialization.
+async fn create_threads(
+    board: &mut (dyn Board + Send),
+) -> Result<Vec<Box<dyn HashThread>>, BoardError> {
+    let mut threads = board.create_hash_threads().await?;
+    if board.self_test() {
+        for thread in &mut threads {
+            self_test::run(thread.as_mut()).await.map_err(|e| {
+                BoardError::InitializationFailed(format!(
+                    "{} failed self-test: {}",
+                    thread.name(),
+                    e
+                ))
+            })?;
+        }
+    }
+    Ok(threads)
+}
+
+/// Apply the board's stored settings. A setting the board refuses is
+/// logged and skipped; the board runs on at its defaults.
+async fn apply_settings(context: &BoardContext, board: &mut (dyn Board + Send)) {
+    let settings = context.settings_rx.borrow().clone();
+    let point = settings.operating_point();
+    if point != OperatingPoint::default() {
+        let result = match point.check_voltage(board.voltage_range()) {
+            Ok(()) => warmup::ramp(&context.warmup, board, point).await,
+            Err(e) => Err(e.into()),
+        };
+        match result {
+            Ok(reached) if reached.frequency_mhz != point.frequency_mhz => {
+                warn!(board = %context.name, id = %context.id, %point, %reached, "Board can't change its chip frequency; stored frequency skipped")
+            }
+            Ok(_) => {
+                info!(board = %context.name, id = %context.id, %point, "Stored operating point applied.")
+            }
+            Err(e @ WarmupError::Aborted { anomaly, reached }) => {
+                warn!(board = %context.name, id = %context.id, %point, reason = %e, "Warm-up aborted; running below stored operating point");
+                if let Anomaly::InputSag { volts, .. } = anomaly {
+                    context.power_limited_tx.send_modify(|limited| {
+                        let limited = limited.get_or_insert_with(|| PowerLimited::new(volts, None));
+                        limited.input_voltage = limited.input_voltage.min(volts);
+                        limited.core_voltage = reached;
+                    });
+                }
+            }
+            Err(e) => {
+                warn!(board = %context.name, id = %context.id, %point, error = %e, "Failed to apply stored operating point")
+            }
+        }
+    }
+    if let Some(mode) = settings.fan {
+        match board.set_fan_mode(mode).await {
+            Ok(()) => {
+                debug!(board = %context.name, id = %context.id, %mode, "Stored fan mode applied")
+            }
+            Err(e) => {
+                warn!(board = %context.name, id = %context.id, %mode, error = %e, "Failed to apply stored fan mode")
+            }
+        }
+    }
+}
+
+/// Check a telemetry reading for a sagging input, cutting the core voltage
+/// if the monitor says to.
+async fn check_supply(
+    context: &BoardContext,
+    board: &mut (dyn Board + Send),
+    monitor: &mut SupplyMonitor,
+    reading: &TelemetrySnapshot,
+) {
+    let floor = board.voltage_range().map(|range| range.min);
+    if let Some(volts) = monitor.observe(reading, floor) {
+        let point = OperatingPoint {
+            frequency_mhz: None,
+            voltage: Some(volts),
+        };
+        match board.set_operating_point(point).await {
+            Ok(()) => {
+                warn!(board = %context.name, id = %context.id, vin = ?reading.input_voltage, vout = volts, "Input supply sagging; core voltage cut.")
+            }
+            Err(e) => {
+                warn!(board = %context.name, id = %context.id, vin = ?reading.input_voltage, error = %e, "Failed to cut core voltage for sagging supply")
+            }
+        }
+    }
+    let limited = monitor.limited();
+    let was_limited = context.power_limited_tx.send_replace(limited).is_some();
+    if !was_limited {
+        if let Some(limited) = limited {
+            warn!(board = %context.name, id = %context.id, vin = limited.input_voltage, watts = ?limited.sagged_at_watts, "Board power limited by its supply.");
+        }
+    }
+}
+
+/// Run every shutdown stage, each under its timeout, even if an earlier one
+/// failed. Returns the first failure.
+async fn shut_down(
+    context: &BoardContext,
+    board: &mut (dyn Board + Send),
+) -> Result<(), BoardError> {
+    let mut result = Ok(());
+    for stage in ShutdownStage::ALL {
+        let timeout = match stage {
+            ShutdownStage::Park => PARK_TIMEOUT,
+            ShutdownStage::PowerOff => POWER_OFF_TIMEOUT,
+            ShutdownStage::CoolDown => COOL_DOWN_TIMEOUT,
+        };
+        let outcome = match tokio::time::timeout(timeout, board.shutdown_stage(stage)).await {
+            Ok(outcome) => outcome,
+            Err(_) => Err(BoardError::HardwareControl(format!(
+                "{} timed out after {:?}",
+                stage, timeout
+            ))),
+        };
+        if let Err(e) = outcome {
+            warn!(board = %context.name, id = %context.id, %stage, error = %e, "Shutdown stage failed");
+            if result.is_ok() {
+                result = Err(e);
+            }
+        }
+    }
+    result
+}
+
+fn not_running() -> BoardError {
+    BoardError::HardwareControl("board is not running".into())
+}
+
+fn restarted() -> BoardError {
+    BoardError::Restarted
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::asic::hash_thread::{
+        HashTask, HashThreadCapabilities, HashThreadError, HashThreadEvent, HashThreadStatus,
+    };
+    use crate::board::{BoardInfo, VoltageRange};
+    use crate::mgmt_protocol::bitaxe_raw::version::FirmwareVersion;
+    use crate::types::HashRate;
+
+    /// Board whose retune panics if it `crashes`.
+    struct FlakyBoard {
+        crashes: bool,
+        shutdowns: Arc<AtomicU32>,
+    }
+
+    #[async_trait]
+    impl Board for FlakyBoard {
+        fn board_info(&self) -> BoardInfo {
+            BoardInfo {
+                model: "Flaky".into(),
+                firmware_version: None,
+                serial_number: None,
+            }
+        }
+
+        async fn shutdown(&mut self) -> Result<(), BoardError> {
+            self.shutdowns.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        async fn create_hash_threads(&mut self) -> Result<Vec<Box<dyn HashThread>>, BoardError> {
+            Ok(Vec::new())
+        }
+
+        async fn set_operating_point(&mut self, _point: OperatingPoint) -> Result<(), BoardError> {
+            assert!(!self.crashes, "retune crashed");
+            Ok(())
+        }
+
+        fn voltage_range(&self) -> Option<VoltageRange> {
+            Some(VoltageRange { min: 1.0, max: 1.5 })
+        }
+
+        async fn power_watts(&mut self) -> Option<f32> {
+            Some(10.0)
+        }
+    }
+
+    struct Flaky {
+        starts: Arc<AtomicU32>,
+        shutdowns: Arc<AtomicU32>,
+        lifecycle_rx: mpsc::UnboundedReceiver<BoardLifecycle>,
+    }
+
+    /// Spawn a board whose first `crashes` incarnations crash on retune and
+    /// whose first `failed_starts` creations fail outright.
+    fn spawn_flaky(crashes: u32, failed_starts: u32) -> (BoardHandle, Flaky) {
+        let starts = Arc::new(AtomicU32::new(0));
+        let shutdowns = Arc::new(AtomicU32::new(0));
+        let make_board: MakeBoardFn = Box::new({
+            let starts = starts.clone();
+            let shutdowns = shutdowns.clone();
+            move || {
+                let start = starts.fetch_add(1, Ordering::SeqCst);
+                let shutdowns = shutdowns.clone();
+                Box::pin(async move {
+                    if start < failed_starts {
+                        return Err(crate::error::Error::Hardware("no chips".into()));
+                    }
+                    Ok(Box::new(FlakyBoard {
+                        crashes: start < failed_starts + crashes,
+                        shutdowns,
+                    }) as Box<dyn Board + Send>)
+                })
+            }
+        });
+
+        let (scheduler_tx, _) = mpsc::channel(1);
+        let policy = RestartPolicy {
+            backoff: Backoff {
+                initial: Duration::from_secs(1),
+                max: Duration::from_secs(4),
+                stable_after: Duration::from_secs(300),
+            },
+            ..RestartPolicy::default()
+        };
+        let (lifecycle_tx, lifecycle_rx) = mpsc::unbounded_channel();
+        let handle = BoardHandle::spawn(
+            "Flaky",
+            "test",
+            make_board,
+            scheduler_tx,
+            policy,
+            BoardSettings::default(),
+            Warmup::default(),
+            lifecycle_tx,
+        );
+        let flaky = Flaky {
+            starts,
+            shutdowns,
+            lifecycle_rx,
+        };
+        (handle, flaky)
+    }
+
+    async fn wait_for(handle: &BoardHandle, health: BoardHealth) {
+        let mut health_rx = handle.health_rx.clone();
+        health_rx
+            .wait_for(|h| *h == health)
+            .await
+            .expect("board task ended");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_board_restarts_after_panic() {
+        let (handle, mut flaky) = spawn_flaky(1, 0);
+        wait_for(&handle, BoardHealth::Running).await;
+        assert_eq!(handle.power_watts().await, Some(10.0));
+
+        // The retune panics, and the board is refused while restarting
+        assert!(handle
+            .set_operating_point(OperatingPoint::default())
+            .await
+            .is_err());
+        wait_for(&handle, BoardHealth::Restarting { restarts: 1 }).await;
+        assert_eq!(handle.power_watts().await, None);
+
+        // The next incarnation comes up after the backoff and works
+        wait_for(&handle, BoardHealth::Running).await;
+        assert_eq!(flaky.starts.load(Ordering::SeqCst), 2);
+        handle
+            .set_operating_point(OperatingPoint::default())
+            .await
+            .unwrap();
+
+        handle.shutdown().await.unwrap();
+        assert_eq!(flaky.shutdowns.load(Ordering::SeqCst), 1);
+
+        // Each incarnation's start and the crash between are in the history
+        let mut events = Vec::new();
+        while let Ok(lifecycle) = flaky.lifecycle_rx.try_recv() {
+            assert_eq!(lifecycle.id, "test");
+            events.push(lifecycle.entry.event);
+        }
+        assert_eq!(events.len(), 3, "{:?}", events);
+        assert_eq!(events[0], BoardEvent::Initialized);
+        assert!(matches!(&events[1], BoardEvent::Failed { reason } if reason.contains("panicked")));
+        assert_eq!(events[2], BoardEvent::Reinitialized { generation: 2 });
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_request_for_earlier_incarnation_refused() {
+        let (handle, _flaky) = spawn_flaky(1, 0);
+        wait_for(&handle, BoardHealth::Running).await;
+        assert_eq!(handle.generation(), 1);
+        let stale = handle.running_generation().unwrap();
+
+        // Crash the first incarnation and let the next come up
+        assert!(handle
+            .set_operating_point(OperatingPoint::default())
+            .await
+            .is_err());
+        wait_for(&handle, BoardHealth::Restarting { restarts: 1 }).await;
+        wait_for(&handle, BoardHealth::Running).await;
+        assert_eq!(handle.generation(), 2);
+
+        // A request made before the restart doesn't reach the new board
+        let (reply_tx, reply_rx) = oneshot::channel();
+        handle
+            .request(
+                Some(stale),
+                BoardCommand::SetOperatingPoint {
+                    point: OperatingPoint::default(),
+                    reply_tx,
+                },
+            )
+            .await;
+        assert!(matches!(
+            reply_rx.await.unwrap(),
+            Err(BoardError::Restarted)
+        ));
+
+        handle
+            .set_operating_point(OperatingPoint::default())
+            .await
+            .unwrap();
+        handle.shutdown().await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_board_given_up_after_repeated_failures() {
+        let (handle, flaky) = spawn_flaky(0, u32::MAX);
+
+        wait_for(&handle, BoardHealth::Failed).await;
+        assert_eq!(flaky.starts.load(Ordering::SeqCst), MAX_RESTARTS + 1);
+
+        // A failed board still shuts down cleanly
+        handle.shutdown().await.unwrap();
+        assert_eq!(flaky.shutdowns.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_board_waits_out_busy_port() {
+        // Busy for longer than a failing board would be given
+        let busy_starts = MAX_RESTARTS + 3;
+        let starts = Arc::new(AtomicU32::new(0));
+        let make_board: MakeBoardFn = Box::new({
+            let starts = starts.clone();
+            move || {
+                let start = starts.fetch_add(1, Ordering::SeqCst);
+                Box::pin(async move {
+                    if start < busy_starts {
+                        return Err(PortBusy {
+                            path: "/dev/ttyACM1".into(),
+                            holders: vec![812],
+                        }
+                        .into());
+                    }
+                    Ok(Box::new(FlakyBoard {
+                        crashes: false,
+                        shutdowns: Arc::default(),
+                    }) as Box<dyn Board + Send>)
+                })
+            }
+        });
+        let (scheduler_tx, _) = mpsc::channel(1);
+        let handle = BoardHandle::spawn(
+            "Busy",
+            "test",
+            make_board,
+            scheduler_tx,
+            RestartPolicy::default(),
+            BoardSettings::default(),
+            Warmup::default(),
+            mpsc::unbounded_channel().0,
+        );
+
+        wait_for(&handle, BoardHealth::Waiting).await;
+        assert_eq!(
+            handle.last_error().as_deref(),
+            Some("/dev/ttyACM1 is in use by pid 812")
+        );
+
+        // The holder lets go, and the next try brings the board up
+        wait_for(&handle, BoardHealth::Running).await;
+        assert_eq!(starts.load(Ordering::SeqCst), busy_starts + 1);
+        assert_eq!(handle.last_error(), None);
+
+        handle.shutdown().await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_old_firmware_fails_without_restarts() {
+        let starts = Arc::new(AtomicU32::new(0));
+        let make_board: MakeBoardFn = Box::new({
+            let starts = starts.clone();
+            move || {
+                starts.fetch_add(1, Ordering::SeqCst);
+                Box::pin(async {
+                    Err(crate::error::Error::FirmwareTooOld {
+                        version: FirmwareVersion::new(0, 9, 0),
+                        minimum: FirmwareVersion::new(1, 0, 0),
+                    })
+                })
+            }
+        });
+        let (scheduler_tx, _) = mpsc::channel(1);
+        let handle = BoardHandle::spawn(
+            "Old",
+            "test",
+            make_board,
+            scheduler_tx,
+            RestartPolicy::default(),
+            BoardSettings::default(),
+            Warmup::default(),
+            mpsc::unbounded_channel().0,
+        );
+
+        wait_for(&handle, BoardHealth::Failed).await;
+        assert_eq!(starts.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            handle.last_error().as_deref(),
+            Some("Board firmware 0.9.0 is too old; version 1.0.0 or newer is required")
+        );
+
+        handle.shutdown().await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_shutdown_while_restarting() {
+        let (handle, flaky) = spawn_flaky(0, 1);
+        wait_for(&handle, BoardHealth::Restarting { restarts: 1 }).await;
+
+        let health_rx = handle.health_rx.clone();
+        handle.shutdown().await.unwrap();
+
+        assert_eq!(*health_rx.borrow(), BoardHealth::Stopped);
+        assert_eq!(flaky.starts.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_telemetry_cached_while_running() {
+        let (handle, _flaky) = spawn_flaky(1, 0);
+        wait_for(&handle, BoardHealth::Running).await;
+
+        // Polled as soon as the board is up
+        let mut telemetry_rx = handle.telemetry_rx.clone();
+        telemetry_rx.wait_for(Option::is_some).await.unwrap();
+        assert_eq!(handle.telemetry().unwrap().power_watts, Some(10.0));
+
+        // Dropped when the board crashes
+        let _ = handle.set_operating_point(OperatingPoint::default()).await;
+        wait_for(&handle, BoardHealth::Restarting { restarts: 1 }).await;
+        assert!(handle.telemetry().is_none());
+
+        handle.shutdown().await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_voltage_outside_range_refused() {
+        // Any retune that reached this board would crash it
+        let (handle, _flaky) = spawn_flaky(1, 0);
+        wait_for(&handle, BoardHealth::Running).await;
+
+        let point = OperatingPoint {
+            frequency_mhz: None,
+            voltage: Some(2.0),
+        };
+        match handle.set_operating_point(point).await {
+            Err(BoardError::VoltageOutOfRange { volts, min, max }) => {
+                assert_eq!((volts, min, max), (2.0, 1.0, 1.5));
+            }
+            other => panic!("expected VoltageOutOfRange, got {:?}", other),
+        }
+        assert_eq!(handle.health(), BoardHealth::Running);
+
+        handle.shutdown().await.unwrap();
+    }
+
+    /// Board whose chips never finish parking.
+    struct WedgedBoard {
+        stages: Arc<std::sync::Mutex<Vec<ShutdownStage>>>,
+    }
+
+    #[async_trait]
+    impl Board for WedgedBoard {
+        fn board_info(&self) -> BoardInfo {
+            BoardInfo {
+                model: "Wedged".into(),
+                firmware_version: None,
+                serial_number: None,
+            }
+        }
+
+        async fn shutdown(&mut self) -> Result<(), BoardError> {
+            unreachable!("shutdown runs in stages")
+        }
+
+        async fn shutdown_stage(&mut self, stage: ShutdownStage) -> Result<(), BoardError> {
+            if stage == ShutdownStage::Park {
+                std::future::pending::<()>().await;
+            }
+            self.stages.lock().unwrap().push(stage);
+            Ok(())
+        }
+
+        async fn create_hash_threads(&mut self) -> Result<Vec<Box<dyn HashThread>>, BoardError> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_power_off_runs_after_park_times_out() {
+        let stages = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let make_board: MakeBoardFn = Box::new({
+            let stages = stages.clone();
+            move || {
+                let stages = stages.clone();
+                Box::pin(
+                    async move { Ok(Box::new(WedgedBoard { stages }) as Box<dyn Board + Send>) },
+                )
+            }
+        });
+        let (scheduler_tx, _) = mpsc::channel(1);
+        let handle = BoardHandle::spawn(
+            "Wedged",
+            "test",
+            make_board,
+            scheduler_tx,
+            RestartPolicy::default(),
+            BoardSettings::default(),
+            Warmup::default(),
+            mpsc::unbounded_channel().0,
+        );
+        wait_for(&handle, BoardHealth::Running).await;
+
+        let started = Instant::now();
+        assert!(handle.shutdown().await.is_err());
+        assert_eq!(started.elapsed(), PARK_TIMEOUT);
+        assert_eq!(
+            *stages.lock().unwrap(),
+            [ShutdownStage::PowerOff, ShutdownStage::CoolDown]
+        );
+    }
+
+    /// Board whose first incarnation reports a fault as soon as it's up.
+    struct FaultingBoard {
+        fault_rx: Option<mpsc::Receiver<String>>,
+    }
+
+    #[async_trait]
+    impl Board for FaultingBoard {
+        fn board_info(&self) -> BoardInfo {
+            BoardInfo {
+                model: "Faulting".into(),
+                firmware_version: None,
+                serial_number: None,
+            }
+        }
+
+        async fn shutdown(&mut self) -> Result<(), BoardError> {
+            Ok(())
+        }
+
+        async fn create_hash_threads(&mut self) -> Result<Vec<Box<dyn HashThread>>, BoardError> {
+            Ok(Vec::new())
+        }
+
+        fn take_fault_receiver(&mut self) -> Option<mpsc::Receiver<String>> {
+            self.fault_rx.take()
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_board_fault_restarts_board() {
+        let starts = Arc::new(AtomicU32::new(0));
+        let make_board: MakeBoardFn = Box::new({
+            let starts = starts.clone();
+            move || {
+                let first = starts.fetch_add(1, Ordering::SeqCst) == 0;
+                Box::pin(async move {
+                    let (fault_tx, fault_rx) = mpsc::channel(1);
+                    if first {
+                        fault_tx.try_send("chips stalled".into()).unwrap();
+                    }
+                    Ok(Box::new(FaultingBoard {
+                        fault_rx: Some(fault_rx),
+                    }) as Box<dyn Board + Send>)
+                })
+            }
+        });
+        let (scheduler_tx, _) = mpsc::channel(1);
+        let handle = BoardHandle::spawn(
+            "Faulting",
+            "test",
+            make_board,
+            scheduler_tx,
+            RestartPolicy::default(),
+            BoardSettings::default(),
+            Warmup::default(),
+            mpsc::unbounded_channel().0,
+        );
+
+        wait_for(&handle, BoardHealth::Restarting { restarts: 1 }).await;
+        wait_for(&handle, BoardHealth::Running).await;
+        assert_eq!(starts.load(Ordering::SeqCst), 2);
+
+        handle.shutdown().await.unwrap();
+    }
+
+    /// Thread that takes tasks but never finds anything.
+    struct MuteThread {
+        capabilities: HashThreadCapabilities,
+    }
+
+    #[async_trait]
+    impl HashThread for MuteThread {
+        fn name(&self) -> &str {
+            "mute"
+        }
+
+        fn capabilities(&self) -> &HashThreadCapabilities {
+            &self.capabilities
+        }
+
+        async fn update_task(
+            &mut self,
+            _task: HashTask,
+        ) -> Result<Option<HashTask>, HashThreadError> {
+            Ok(None)
+        }
+
+        async fn replace_task(
+            &mut self,
+            _task: HashTask,
+        ) -> Result<Option<HashTask>, HashThreadError> {
+            Ok(None)
+        }
+
+        async fn go_idle(&mut self) -> Result<Option<HashTask>, HashThreadError> {
+            Ok(None)
+        }
+
+        fn take_event_receiver(&mut self) -> Option<mpsc::Receiver<HashThreadEvent>> {
+            None
+        }
+
+        fn status(&self) -> HashThreadStatus {
+            Default::default()
+        }
+    }
+
+    /// Board whose one thread can't hash.
+    struct MuteBoard;
+
+    #[async_trait]
+    impl Board for MuteBoard {
+        fn board_info(&self) -> BoardInfo {
+            BoardInfo {
+                model: "Mute".into(),
+                firmware_version: None,
+                serial_number: None,
+            }
+        }
+
+        async fn shutdown(&mut self) -> Result<(), BoardError> {
+            Ok(())
+        }
+
+        async fn create_hash_threads(&mut self) -> Result<Vec<Box<dyn HashThread>>, BoardError> {
+            Ok(vec![Box::new(MuteThread {
+                capabilities: HashThreadCapabilities::new(HashRate::from_terahashes(1.0)),
+            })])
+        }
+
+        fn self_test(&self) -> bool {
+            true
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_self_test_failure_fails_board_start() {
+        let make_board: MakeBoardFn =
+            Box::new(|| Box::pin(async { Ok(Box::new(MuteBoard) as Box<dyn Board + Send>) }));
+        let (scheduler_tx, mut scheduler_rx) = mpsc::channel(1);
+        let handle = BoardHandle::spawn(
+            "Mute",
+            "test",
+            make_board,
+            scheduler_tx,
+            RestartPolicy::default(),
+            BoardSettings::default(),
+            Warmup::default(),
+            mpsc::unbounded_channel().0,
+        );
+
+        wait_for(&handle, BoardHealth::Restarting { restarts: 1 }).await;
+        assert!(scheduler_rx.try_recv().is_err());
+
+        handle.shutdown().await.unwrap();
+    }
+
+    /// Board recording the settings applied to it.
+    struct TunedBoard {
+        applied: Arc<parking_lot::Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl Board for TunedBoard {
+        fn board_info(&self) -> BoardInfo {
+            BoardInfo {
+                model: "Tuned".into(),
+                firmware_version: None,
+                serial_number: None,
+            }
+        }
+
+        async fn shutdown(&mut self) -> Result<(), BoardError> {
+            Ok(())
+        }
+
+        async fn create_hash_threads(&mut self) -> Result<Vec<Box<dyn HashThread>>, BoardError> {
+            Ok(Vec::new())
+        }
+
+        async fn set_operating_point(&mut self, point: OperatingPoint) -> Result<(), BoardError> {
+            self.applied.lock().push(point.to_string());
+            Ok(())
+        }
+
+        async fn set_fan_mode(&mut self, mode: FanMode) -> Result<(), BoardError> {
+            self.applied.lock().push(mode.to_string());
+            Ok(())
+        }
+
+        fn voltage_range(&self) -> Option<VoltageRange> {
+            Some(VoltageRange { min: 1.0, max: 1.5 })
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stored_settings_applied_at_start() {
+        let applied = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let make_board: MakeBoardFn = Box::new({
+            let applied = applied.clone();
+            move || {
+                let applied = applied.clone();
+                Box::pin(
+                    async move { Ok(Box::new(TunedBoard { applied }) as Box<dyn Board + Send>) },
+                )
+            }
+        });
+        let (scheduler_tx, _scheduler_rx) = mpsc::channel(1);
+        let settings = BoardSettings {
+            frequency_mhz: Some(500.0),
+            fan: Some(FanMode::Fixed { percent: 60 }),
+            ..Default::default()
+        };
+        let handle = BoardHandle::spawn(
+            "Tuned",
+            "test",
+            make_board,
+            scheduler_tx,
+            RestartPolicy::default(),
+            settings,
+            Warmup::default(),
+            mpsc::unbounded_channel().0,
+        );
+
+        wait_for(&handle, BoardHealth::Running).await;
+        // Settings are applied before the first command is served
+        handle.nonce_map().await.ok();
+        assert_eq!(*applied.lock(), vec!["500 MHz", "60%"]);
+        handle.shutdown().await.unwrap();
+    }
+}
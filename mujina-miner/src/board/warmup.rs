@@ -0,0 +1,387 @@
+//! Warm-up ramp to a board's stored operating point.
+//!
+//! A board comes up at its default core voltage, and its chips ramp their
+//! own PLLs from a low clock (see [`crate::asic::bm13xx`]). Jumping from
+//! there straight to a raised stored voltage draws a step of current that a
+//! marginal USB supply answers with a brown-out, and the board fails to
+//! come up. Instead the voltage is raised in steps of
+//! [`Warmup::voltage_step`], and the board's sensors are read after each:
+//!
+//! - the chips hotter than [`Warmup::max_temperature_c`],
+//! - the input voltage sagging more than [`Warmup::max_input_sag`] below
+//!   its reading before the ramp, or
+//! - the core current above [`Warmup::max_core_current`], if set,
+//!
+//! stop the ramp and put the board back at the last step that read clean.
+//! It runs on there, and the reason is logged. A frequency, where the board
+//! takes one, is set with the last step; a board that can't change its
+//! chips' clock takes the last step's voltage alone. Lowering the voltage
+//! takes one step, since it only eases the load. A step the board fails
+//! puts it back at the voltage it started at.
+
+use std::time::Duration;
+
+use super::{Board, BoardError, OperatingPoint, TelemetrySnapshot};
+use crate::{config::HardwareConfig, tracing::prelude::*};
+
+/// Core voltage raised per step, by default.
+pub const DEFAULT_VOLTAGE_STEP: f32 = 0.01;
+
+/// Time the board settles after each step before its sensors are read, by
+/// default.
+pub const DEFAULT_STEP_DELAY: Duration = Duration::from_millis(250);
+
+/// Fraction the input voltage may sag below its reading before the ramp,
+/// by default.
+pub const DEFAULT_MAX_INPUT_SAG: f32 = 0.05;
+
+/// How long a sensor read after a step may take; one that takes longer
+/// counts as reading nothing.
+const READING_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How a board is warmed up to its operating point.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Warmup {
+    /// Core voltage raised per step, in volts
+    pub voltage_step: f32,
+
+    /// Time the board settles after each step
+    pub step_delay: Duration,
+
+    /// Chip temperature that stops the ramp, in degrees Celsius
+    pub max_temperature_c: f32,
+
+    /// Fraction the input voltage may sag below its reading before the ramp
+    pub max_input_sag: f32,
+
+    /// Core current that stops the ramp, in amps
+    pub max_core_current: Option<f32>,
+}
+
+/// A reading that stopped a warm-up.
+#[derive(Debug, Clone, Copy, PartialEq, thiserror::Error)]
+pub enum Anomaly {
+    #[error("chips at {temperature_c:.1} °C, over {limit:.1} °C")]
+    Overheat { temperature_c: f32, limit: f32 },
+
+    #[error("input sagged to {volts:.2} V from {baseline:.2} V")]
+    InputSag { volts: f32, baseline: f32 },
+
+    #[error("core current {amps:.1} A, over {limit:.1} A")]
+    Overcurrent { amps: f32, limit: f32 },
+}
+
+/// Why a warm-up didn't reach its operating point.
+#[derive(Debug, thiserror::Error)]
+pub enum WarmupError {
+    #[error("stopped at {}: {anomaly}", volts(*.reached))]
+    Aborted {
+        anomaly: Anomaly,
+        /// Core voltage the board was put back at, if known
+        reached: Option<f32>,
+    },
+
+    #[error(transparent)]
+    Board(#[from] BoardError),
+}
+
+impl Default for Warmup {
+    fn default() -> Self {
+        Self {
+            voltage_step: DEFAULT_VOLTAGE_STEP,
+            step_delay: DEFAULT_STEP_DELAY,
+            max_temperature_c: 85.0,
+            max_input_sag: DEFAULT_MAX_INPUT_SAG,
+            max_core_current: None,
+        }
+    }
+}
+
+impl From<&HardwareConfig> for Warmup {
+    fn from(config: &HardwareConfig) -> Self {
+        let warmup = config.warmup.clone().unwrap_or_default();
+        Self {
+            voltage_step: warmup.step_volts,
+            step_delay: Duration::from_millis(warmup.step_ms),
+            max_temperature_c: config.temp_limit,
+            max_input_sag: warmup.max_input_sag_percent / 100.0,
+            max_core_current: warmup.max_core_current_a,
+        }
+    }
+}
+
+impl Warmup {
+    /// Points to step through from a core voltage of `from` to `to`. One
+    /// step if either voltage isn't known or the voltage isn't rising.
+    pub fn steps(&self, from: Option<f32>, to: OperatingPoint) -> Vec<OperatingPoint> {
+        let (Some(from), Some(target)) = (from, to.voltage) else {
+            return vec![to];
+        };
+        if target <= from || self.voltage_step <= 0.0 {
+            return vec![to];
+        }
+
+        let count = ((target - from) / self.voltage_step).ceil() as u32;
+        let mut points: Vec<_> = (1..count)
+            .map(|i| OperatingPoint {
+                frequency_mhz: None,
+                voltage: Some(from + self.voltage_step * i as f32),
+            })
+            .collect();
+        points.push(to);
+        points
+    }
+
+    /// What's wrong with `reading`, taken during a ramp that started at
+    /// `baseline`.
+    pub fn check(
+        &self,
+        baseline: &TelemetrySnapshot,
+        reading: &TelemetrySnapshot,
+    ) -> Option<Anomaly> {
+        if let Some(temperature_c) = reading.temperature_c {
+            if temperature_c > self.max_temperature_c {
+                return Some(Anomaly::Overheat {
+                    temperature_c,
+                    limit: self.max_temperature_c,
+                });
+            }
+        }
+        if let (Some(baseline), Some(volts)) = (baseline.input_voltage, reading.input_voltage) {
+            if volts < baseline * (1.0 - self.max_input_sag) {
+                return Some(Anomaly::InputSag { volts, baseline });
+            }
+        }
+        if let (Some(limit), Some(amps)) = (self.max_core_current, reading.core_current) {
+            if amps > limit {
+                return Some(Anomaly::Overcurrent { amps, limit });
+            }
+        }
+        None
+    }
+}
+
+/// Bring `board` to `target`, in steps while the voltage rises, returning
+/// the point it was brought to: `target`, without its frequency if the
+/// board can't change its chips' clock.
+///
+/// On an anomaly the board is put back at the last clean step and the
+/// ramp is abandoned. If the board fails a step, it's put back at the
+/// voltage it started at.
+pub async fn ramp(
+    warmup: &Warmup,
+    board: &mut (dyn Board + Send),
+    target: OperatingPoint,
+) -> Result<OperatingPoint, WarmupError> {
+    let baseline = read(board).await;
+    let mut reached = baseline.core_voltage;
+    let mut applied = OperatingPoint::default();
+    for point in warmup.steps(reached, target) {
+        let point = match set(board, point).await {
+            Ok(point) => point,
+            Err(e) => {
+                if let Some(volts) = baseline.core_voltage {
+                    let back = OperatingPoint {
+                        frequency_mhz: None,
+                        voltage: Some(volts),
+                    };
+                    if let Err(e) = board.set_operating_point(back).await {
+                        warn!(volts, error = %e, "Failed to put the core voltage back after a failed step");
+                    }
+                }
+                return Err(e.into());
+            }
+        };
+        tokio::time::sleep(warmup.step_delay).await;
+
+        let reading = read(board).await;
+        if let Some(anomaly) = warmup.check(&baseline, &reading) {
+            if let Some(volts) = reached {
+                let back = OperatingPoint {
+                    frequency_mhz: None,
+                    voltage: Some(volts),
+                };
+                board.set_operating_point(back).await?;
+            }
+            return Err(WarmupError::Aborted { anomaly, reached });
+        }
+        reached = point.voltage.or(reached);
+        applied = point;
+    }
+    Ok(applied)
+}
+
+/// Set `point` on `board`, or its voltage alone if the board can't change
+/// its chips' clock, returning what was set.
+async fn set(
+    board: &mut (dyn Board + Send),
+    point: OperatingPoint,
+) -> Result<OperatingPoint, BoardError> {
+    match board.set_operating_point(point).await {
+        Err(BoardError::FrequencyUnsupported) => {
+            let point = OperatingPoint {
+                frequency_mhz: None,
+                ..point
+            };
+            board.set_operating_point(point).await?;
+            Ok(point)
+        }
+        result => result.map(|()| point),
+    }
+}
+
+/// The board's sensors, or nothing if they don't answer in time.
+async fn read(board: &mut (dyn Board + Send)) -> TelemetrySnapshot {
+    tokio::time::timeout(READING_TIMEOUT, board.telemetry())
+        .await
+        .unwrap_or_default()
+}
+
+fn volts(reached: Option<f32>) -> String {
+    match reached {
+        Some(volts) => format!("{:.3} V", volts),
+        None => "the starting voltage".into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::{asic::hash_thread::HashThread, board::BoardInfo};
+
+    /// Board whose input sags 20 mV for every 10 mV of core voltage above
+    /// 1.15 V, recording what it's set to.
+    struct SaggingBoard {
+        core_voltage: f32,
+        set: Vec<OperatingPoint>,
+        /// How the board answers a frequency, if it refuses them
+        refuse_frequency: Option<fn() -> BoardError>,
+    }
+
+    #[async_trait]
+    impl Board for SaggingBoard {
+        fn board_info(&self) -> BoardInfo {
+            BoardInfo {
+                model: "Sagging".into(),
+                firmware_version: None,
+                serial_number: None,
+            }
+        }
+
+        async fn shutdown(&mut self) -> Result<(), BoardError> {
+            Ok(())
+        }
+
+        async fn create_hash_threads(&mut self) -> Result<Vec<Box<dyn HashThread>>, BoardError> {
+            Ok(Vec::new())
+        }
+
+        async fn set_operating_point(&mut self, point: OperatingPoint) -> Result<(), BoardError> {
+            if let (Some(refuse), Some(_)) = (self.refuse_frequency, point.frequency_mhz) {
+                return Err(refuse());
+            }
+            if let Some(volts) = point.voltage {
+                self.core_voltage = volts;
+            }
+            self.set.push(point);
+            Ok(())
+        }
+
+        async fn telemetry(&mut self) -> TelemetrySnapshot {
+            let above = (self.core_voltage - 1.15).max(0.0);
+            TelemetrySnapshot {
+                input_voltage: Some(5.0 - above * 2.0),
+                core_voltage: Some(self.core_voltage),
+                ..TelemetrySnapshot::new()
+            }
+        }
+    }
+
+    fn point(frequency_mhz: Option<f32>, voltage: f32) -> OperatingPoint {
+        OperatingPoint {
+            frequency_mhz,
+            voltage: Some(voltage),
+        }
+    }
+
+    #[test]
+    fn test_steps_only_while_rising() {
+        let warmup = Warmup {
+            voltage_step: 0.02,
+            ..Warmup::default()
+        };
+        let target = point(Some(550.0), 1.2);
+        let steps = warmup.steps(Some(1.15), target);
+        assert_eq!(steps.len(), 3);
+        assert_eq!(steps[0].frequency_mhz, None);
+        assert!((steps[1].voltage.unwrap() - 1.19).abs() < 1e-6);
+        assert_eq!(steps[2], target);
+
+        assert_eq!(warmup.steps(Some(1.25), target), [target]);
+        assert_eq!(warmup.steps(None, target), [target]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_sag_stops_ramp_at_last_clean_step() {
+        let warmup = Warmup::default();
+        let mut board = SaggingBoard {
+            core_voltage: 1.15,
+            set: Vec::new(),
+            refuse_frequency: None,
+        };
+
+        // 5% of 5 V sags by 1.275 V
+        let err = ramp(&warmup, &mut board, point(None, 1.3))
+            .await
+            .unwrap_err();
+        let WarmupError::Aborted { anomaly, reached } = err else {
+            panic!("unexpected error {err}");
+        };
+        assert!(matches!(anomaly, Anomaly::InputSag { .. }), "{anomaly}");
+        let reached = reached.unwrap();
+        assert!((reached - 1.27).abs() < 1e-3, "{reached}");
+        assert_eq!(board.set.last().unwrap().voltage, Some(reached));
+        assert_eq!(board.core_voltage, reached);
+
+        // Within the supply's reach the ramp completes
+        board.core_voltage = 1.15;
+        let target = point(None, 1.2);
+        assert_eq!(ramp(&warmup, &mut board, target).await.unwrap(), target);
+        assert_eq!(board.core_voltage, 1.2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_failed_last_step_restores_voltage() {
+        let warmup = Warmup::default();
+        let mut board = SaggingBoard {
+            core_voltage: 1.15,
+            set: Vec::new(),
+            refuse_frequency: Some(|| BoardError::HardwareControl("PLL write failed".into())),
+        };
+
+        let err = ramp(&warmup, &mut board, point(Some(550.0), 1.2))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, WarmupError::Board(_)), "{err}");
+        assert_eq!(board.set.last(), Some(&point(None, 1.15)));
+        assert_eq!(board.core_voltage, 1.15);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_fixed_clock_board_takes_voltage_alone() {
+        let warmup = Warmup::default();
+        let mut board = SaggingBoard {
+            core_voltage: 1.15,
+            set: Vec::new(),
+            refuse_frequency: Some(|| BoardError::FrequencyUnsupported),
+        };
+
+        let reached = ramp(&warmup, &mut board, point(Some(550.0), 1.2))
+            .await
+            .unwrap();
+        assert_eq!(reached, point(None, 1.2));
+        assert_eq!(board.core_voltage, 1.2);
+    }
+}
@@ -1,14 +1,54 @@
 //! Configuration management for mujina-miner.
 //!
 //! This module handles loading and validating configuration from TOML files,
-//! environment variables, and command-line arguments. It supports hot-reload
-//! via file watching.
+//! environment variables, and command-line arguments. Changes saved through
+//! the API are applied to the running daemon where they can be (log level,
+//! pools); the rest wait for a restart.
+//!
+//! The `MUJINA_*` environment variables are all read here, once, into an
+//! [`EnvConfig`]; the daemon fills whatever neither the command line nor the
+//! config file set from it. A variable that was renamed still works under
+//! its old name, with a warning.
 
+use anyhow::Context;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
+use thiserror::Error;
+use toml::de::{DeTable, DeValue};
+use toml_edit::{DocumentMut, Item, RawString, TableLike};
+
+use crate::{
+    alert::WebhookFormat,
+    board::{preset::PresetSet, task, warmup, OperatingPoint},
+    cpu_miner::CpuMinerConfig,
+    schedule::Profile,
+    secret::{self, Redacted},
+    supervisor::Backoff,
+    tracing::prelude::*,
+    types::Network,
+};
+
+/// Stands in for secrets in configuration shown to clients.
+pub const REDACTED: &str = "********";
+
+/// The default configuration, every setting commented, as
+/// `mujina-minerd --dump-default-config` prints it.
+pub const DEFAULT_CONFIG: &str = include_str!("../config/mujina.toml");
+
+/// Duty cycle of a CPU miner enabled without `MUJINA_CPUMINER_DUTY`.
+const DEFAULT_CPUMINER_DUTY: u8 = 50;
+
+/// Environment variables that were renamed, old name first.
+const RENAMED_VARS: &[(&str, &str)] = &[
+    ("MUJINA_POOL_USER", "MUJINA_POOL_WORKER"),
+    ("MUJINA_POOL_PASS", "MUJINA_POOL_PASSWORD"),
+];
 
 /// Main configuration structure for the miner.
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct Config {
     /// Daemon configuration
     pub daemon: DaemonConfig,
@@ -21,10 +61,61 @@ pub struct Config {
 
     /// API server configuration
     pub api: ApiConfig,
+
+    /// Mining profiles by time of day and electricity price
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<ScheduleConfig>,
+
+    /// Retrying shares that failed to reach the pool
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub share_queue: Option<ShareQueueConfig>,
+
+    /// Refreshing and failing over pools that reject too many shares
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reject_breaker: Option<RejectBreakerConfig>,
+
+    /// Serving work to other miners over Stratum
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<ProxyConfig>,
+
+    /// Restarting boards that fail
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recovery: Option<RecoveryConfig>,
+
+    /// Notifications when boards or pools need attention
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alerts: Option<AlertConfig>,
+
+    /// Eco, balanced and turbo presets by board model, or `default`; see
+    /// [`crate::board::preset`]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub presets: BTreeMap<String, PresetSet>,
+}
+
+/// Why a configuration was rejected, one entry per problem.
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("invalid configuration: {}", .0.join("; "))]
+pub struct InvalidConfig(pub Vec<String>);
+
+/// A key in a config file that no setting has, most likely misspelled.
+/// Such keys are otherwise ignored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownKey {
+    /// Where the key is, e.g. `hardware.direct[0].uart`
+    pub path: String,
+
+    /// Line of the file it's on, counting from 1
+    pub line: Option<usize>,
+}
+
+/// One step of the way to a key: a table key or an array index.
+enum KeySegment {
+    Key(String),
+    Index(usize),
 }
 
 /// Daemon process configuration.
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct DaemonConfig {
     /// PID file location
     pub pid_file: Option<PathBuf>,
@@ -35,10 +126,22 @@ pub struct DaemonConfig {
     /// Use systemd notification
     #[serde(default)]
     pub systemd: bool,
+
+    /// Bitcoin network to mine (mainnet, testnet4, or regtest)
+    #[serde(default)]
+    pub network: Network,
+
+    /// Directory for state kept between runs, such as boards' settings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state_dir: Option<PathBuf>,
+
+    /// Start with mining paused, until resumed through the API
+    #[serde(default)]
+    pub paused: bool,
 }
 
 /// Pool connection configuration.
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Clone, PartialEq, Deserialize, Serialize)]
 pub struct PoolConfig {
     /// Pool URL (stratum+tcp://...)
     pub url: String,
@@ -46,16 +149,27 @@ pub struct PoolConfig {
     /// Worker name
     pub worker: String,
 
-    /// Password (if required)
+    /// Password (if required), or where to find it: `env:NAME`,
+    /// `file:PATH` or `credential:NAME` (see [`crate::secret`])
     pub password: Option<String>,
 
     /// Priority (lower is higher priority)
     #[serde(default)]
     pub priority: u32,
+
+    /// Share rate to steer the pool's difficulty toward by suggesting
+    /// difficulties; unset leaves difficulty to the pool
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shares_per_minute: Option<f64>,
+
+    /// Keep a transcript of the messages exchanged with the pool, for
+    /// debugging; see [`crate::stratum_v1::trace`]
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub trace: bool,
 }
 
 /// Hardware configuration.
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct HardwareConfig {
     /// Temperature limits
     pub temp_limit: f32,
@@ -66,10 +180,55 @@ pub struct HardwareConfig {
 
     /// Power limits
     pub power_limit: Option<f32>,
+
+    /// Priority weights for sharing `power_limit`, by board ID. Boards not
+    /// listed weigh 1; a heavier board is throttled less.
+    #[serde(default)]
+    pub power_weights: BTreeMap<String, f32>,
+
+    /// Boards kept up but given no work, by ID, until resumed through the
+    /// API
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paused_boards: Vec<String>,
+
+    /// Raising boards' core voltage to their stored operating point in
+    /// steps; see [`crate::board::warmup`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warmup: Option<WarmupConfig>,
+
+    /// Boards wired to the host's own UART, I2C and GPIO rather than
+    /// attached over USB
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub direct: Vec<DirectBoardConfig>,
+}
+
+/// A Bitaxe wired straight to the host (e.g. a Raspberry Pi's header), with
+/// no management controller in between.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct DirectBoardConfig {
+    /// Board ID, in place of the serial number a USB board reports
+    pub id: String,
+
+    /// UART connected to the ASICs, e.g. "/dev/ttyAMA0"
+    pub uart: String,
+
+    /// I2C adapter of the fan controller and regulator, e.g. "/dev/i2c-1"
+    pub i2c: PathBuf,
+
+    /// GPIO chip of the reset line
+    #[serde(default = "default_gpio_chip")]
+    pub gpio_chip: PathBuf,
+
+    /// Line on `gpio_chip` driving the ASICs' nRST
+    pub reset_line: u8,
+}
+
+fn default_gpio_chip() -> PathBuf {
+    PathBuf::from("/dev/gpiochip0")
 }
 
 /// API server configuration.
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct ApiConfig {
     /// Listen address
     pub listen: String,
@@ -85,17 +244,1829 @@ pub struct ApiConfig {
     pub key_path: Option<PathBuf>,
 }
 
-impl Config {
-    /// Load configuration from the default location.
-    pub fn load() -> anyhow::Result<Self> {
-        // TODO: Implement config loading from /etc/mujina/mujina.toml
-        // and ~/.config/mujina/mujina.toml with proper merging
-        unimplemented!("Config loading not yet implemented")
+/// When to mine at full power, at reduced power, or not at all.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ScheduleConfig {
+    /// Profile outside every window
+    #[serde(default)]
+    pub default: Profile,
+
+    /// Operating point for the full profile; unset fields are left alone
+    #[serde(default)]
+    pub full: OperatingPoint,
+
+    /// Operating point for the eco profile
+    #[serde(default)]
+    pub eco: OperatingPoint,
+
+    /// Seconds to ramp between operating points
+    #[serde(default = "default_transition_secs")]
+    pub transition_secs: u64,
+
+    /// Time windows, in local time; the first that matches wins
+    #[serde(default)]
+    pub windows: Vec<ScheduleWindow>,
+
+    /// Electricity price feed
+    pub price: Option<PriceFeedConfig>,
+}
+
+/// A daily time window with its profile.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ScheduleWindow {
+    /// Start time, "HH:MM"
+    pub start: String,
+
+    /// End time, "HH:MM"; before `start` for windows that span midnight
+    pub end: String,
+
+    /// Profile while the window is open
+    pub profile: Profile,
+}
+
+/// Electricity price feed.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct PriceFeedConfig {
+    /// URL returning the current price as JSON: a bare number, or an object
+    /// with a numeric `price` field
+    pub url: String,
+
+    /// Seconds between fetches
+    #[serde(default = "default_price_poll_secs")]
+    pub poll_secs: u64,
+
+    /// Price above which to switch to eco
+    pub eco_above: Option<f64>,
+
+    /// Price above which to stop mining
+    pub off_above: Option<f64>,
+}
+
+/// Shares that failed to reach the pool, kept for resubmission.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ShareQueueConfig {
+    /// Directory to keep queued shares in across restarts, one file per
+    /// pool; without it they're kept in memory only
+    pub dir: Option<PathBuf>,
+
+    /// Most shares kept per pool; the oldest are dropped beyond this
+    #[serde(default = "default_queue_max_shares")]
+    pub max_shares: usize,
+
+    /// Seconds after which a queued share is too stale to resubmit
+    #[serde(default = "default_queue_max_age_secs")]
+    pub max_age_secs: u64,
+}
+
+/// When the pool being mined rejects so many shares that our work must be
+/// stale or misconfigured; see [`crate::pools`].
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct RejectBreakerConfig {
+    /// Percentage of shares rejected within the window that trips the
+    /// breaker; 100 turns it off
+    #[serde(default = "default_breaker_max_reject_percent")]
+    pub max_reject_percent: f64,
+
+    /// Seconds of shares the reject rate is judged on
+    #[serde(default = "default_breaker_window_secs")]
+    pub window_secs: u64,
+
+    /// Fewest shares within the window the reject rate is judged on
+    #[serde(default = "default_breaker_min_shares")]
+    pub min_shares: u64,
+
+    /// Seconds after a refresh within which tripping again fails the pool
+    /// over, and for which a failed-over pool is passed over
+    #[serde(default = "default_breaker_cooldown_secs")]
+    pub cooldown_secs: u64,
+}
+
+/// Stratum v1 server for downstream miners; see [`crate::proxy`].
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ProxyConfig {
+    /// Address and port to accept downstream miners on
+    pub listen: String,
+
+    /// Extranonce2 bytes each downstream miner rolls; must be smaller than
+    /// the pool's
+    #[serde(default = "default_proxy_extranonce2_size")]
+    pub extranonce2_size: u8,
+}
+
+/// How boards are warmed up to their stored operating point. The ramp also
+/// stops at `hardware.temp_limit`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct WarmupConfig {
+    /// Core voltage raised per step, in volts
+    #[serde(default = "default_warmup_step_volts")]
+    pub step_volts: f32,
+
+    /// Milliseconds a board settles after each step before its sensors are
+    /// read
+    #[serde(default = "default_warmup_step_ms")]
+    pub step_ms: u64,
+
+    /// Percentage the input voltage may sag below its reading before the
+    /// ramp
+    #[serde(default = "default_max_input_sag_percent")]
+    pub max_input_sag_percent: f32,
+
+    /// Core current, in amps, that stops the ramp
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_core_current_a: Option<f32>,
+}
+
+/// When a failing board is restarted, and when it's given up on.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct RecoveryConfig {
+    /// Consecutive failures before a board is given up on
+    #[serde(default = "default_max_restarts")]
+    pub max_restarts: u32,
+
+    /// Seconds before the first restart; the delay doubles after each
+    /// consecutive failure
+    #[serde(default = "default_backoff_initial_secs")]
+    pub backoff_initial_secs: u64,
+
+    /// Most seconds between restarts
+    #[serde(default = "default_backoff_max_secs")]
+    pub backoff_max_secs: u64,
+
+    /// Seconds a board must run for its earlier failures to be forgotten
+    #[serde(default = "default_stable_after_secs")]
+    pub stable_after_secs: u64,
+
+    /// Seconds between tries of a port another process holds
+    #[serde(default = "default_port_busy_retry_secs")]
+    pub port_busy_retry_secs: u64,
+
+    /// Seconds an unplugged USB board is kept waiting to come back before
+    /// it's shut down; 0 shuts it down at once
+    #[serde(default = "default_reconnect_grace_secs")]
+    pub reconnect_grace_secs: u64,
+}
+
+/// Alert rules and where to send alerts; see [`crate::alert`]. Rules left
+/// unset are off.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct AlertConfig {
+    /// Seconds a board may be down or missing before it's alerted on
+    pub board_offline_secs: Option<u64>,
+
+    /// Chip temperature, in degrees Celsius, above which to alert
+    pub max_temperature_c: Option<f32>,
+
+    /// Percentage of a pool's recent shares rejected above which to alert
+    pub max_reject_percent: Option<f64>,
+
+    /// Seconds the pool being mined may be disconnected before it's
+    /// alerted on
+    pub pool_down_secs: Option<u64>,
+
+    /// Webhooks to post alerts to
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+
+    /// Mailing alerts
+    pub email: Option<EmailConfig>,
+}
+
+/// A webhook alerts are posted to.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct WebhookConfig {
+    /// URL to post to, or a reference to a secret holding it; Discord and
+    /// Telegram URLs carry their token
+    pub url: String,
+
+    /// Body to post: json, discord or telegram
+    #[serde(default)]
+    pub format: WebhookFormat,
+
+    /// Chat to send to, for telegram
+    pub chat_id: Option<String>,
+}
+
+/// Mailing alerts through the local `sendmail`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct EmailConfig {
+    /// Addresses to mail
+    pub to: Vec<String>,
+
+    /// Sender address
+    pub from: String,
+
+    /// The `sendmail` program to run
+    #[serde(default = "default_sendmail")]
+    pub sendmail: PathBuf,
+}
+
+/// Settings taken from `MUJINA_*` environment variables.
+#[derive(Debug, Clone, Default)]
+pub struct EnvConfig {
+    /// `MUJINA_NETWORK`
+    pub network: Option<Network>,
+
+    /// `MUJINA_STATE_DIR`
+    pub state_dir: Option<PathBuf>,
+
+    /// `MUJINA_USB_DISABLE`, set to anything
+    pub usb_disable: bool,
+
+    /// `MUJINA_POOL_URL`
+    pub pool_url: Option<String>,
+
+    /// `MUJINA_POOL_WORKER`, formerly `MUJINA_POOL_USER`
+    pub pool_worker: Option<String>,
+
+    /// `MUJINA_POOL_PASSWORD`, formerly `MUJINA_POOL_PASS`
+    pub pool_password: Option<String>,
+
+    /// `MUJINA_CPUMINER_THREADS`, which enables the CPU miner, and
+    /// `MUJINA_CPUMINER_DUTY` (default 50, clamped to 1-100)
+    pub cpu_miner: Option<CpuMinerConfig>,
+
+    /// `MUJINA_RECOVERY_MAX_RESTARTS`, `MUJINA_RECOVERY_BACKOFF_INITIAL_SECS`,
+    /// `MUJINA_RECOVERY_BACKOFF_MAX_SECS`, `MUJINA_RECOVERY_STABLE_AFTER_SECS`,
+    /// `MUJINA_RECOVERY_PORT_BUSY_RETRY_SECS` and
+    /// `MUJINA_RECOVERY_RECONNECT_GRACE_SECS`; unset ones keep their
+    /// defaults
+    pub recovery: Option<RecoveryConfig>,
+}
+
+impl Default for WarmupConfig {
+    fn default() -> Self {
+        Self {
+            step_volts: default_warmup_step_volts(),
+            step_ms: default_warmup_step_ms(),
+            max_input_sag_percent: default_max_input_sag_percent(),
+            max_core_current_a: None,
+        }
+    }
+}
+
+impl Default for RecoveryConfig {
+    fn default() -> Self {
+        Self {
+            max_restarts: default_max_restarts(),
+            backoff_initial_secs: default_backoff_initial_secs(),
+            backoff_max_secs: default_backoff_max_secs(),
+            stable_after_secs: default_stable_after_secs(),
+            port_busy_retry_secs: default_port_busy_retry_secs(),
+            reconnect_grace_secs: default_reconnect_grace_secs(),
+        }
+    }
+}
+
+impl EnvConfig {
+    /// Read the process's environment.
+    pub fn from_env() -> Result<Self, InvalidConfig> {
+        Self::from_lookup(|name| std::env::var(name).ok())
+    }
+
+    /// Read variables through `lookup`, reporting every one that doesn't
+    /// parse.
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, InvalidConfig> {
+        let mut problems = Vec::new();
+        let var = |name: &str| {
+            lookup(name).or_else(|| {
+                let (old, _) = RENAMED_VARS.iter().find(|(_, new)| *new == name)?;
+                let value = lookup(old)?;
+                warn!(
+                    variable = old,
+                    replacement = name,
+                    "Environment variable is deprecated."
+                );
+                Some(value)
+            })
+        };
+        let mut parsed = |name: &str| -> Option<u64> {
+            let value = var(name)?;
+            value
+                .parse()
+                .map_err(|_| problems.push(format!("{}: '{}' isn't a whole number", name, value)))
+                .ok()
+        };
+
+        let cpu_threads = parsed("MUJINA_CPUMINER_THREADS");
+        let cpu_duty = parsed("MUJINA_CPUMINER_DUTY");
+        let recovery_vars = [
+            parsed("MUJINA_RECOVERY_MAX_RESTARTS"),
+            parsed("MUJINA_RECOVERY_BACKOFF_INITIAL_SECS"),
+            parsed("MUJINA_RECOVERY_BACKOFF_MAX_SECS"),
+            parsed("MUJINA_RECOVERY_STABLE_AFTER_SECS"),
+            parsed("MUJINA_RECOVERY_PORT_BUSY_RETRY_SECS"),
+            parsed("MUJINA_RECOVERY_RECONNECT_GRACE_SECS"),
+        ];
+
+        let network = var("MUJINA_NETWORK").and_then(|value| {
+            value
+                .parse()
+                .map_err(|e| problems.push(format!("MUJINA_NETWORK: {}", e)))
+                .ok()
+        });
+
+        let cpu_miner = cpu_threads.map(|threads| CpuMinerConfig {
+            thread_count: threads as usize,
+            duty_percent: cpu_duty
+                .unwrap_or(DEFAULT_CPUMINER_DUTY.into())
+                .clamp(1, 100) as u8,
+        });
+
+        let recovery = recovery_vars.iter().any(Option::is_some).then(|| {
+            let [max_restarts, initial, max, stable_after, port_busy_retry, reconnect_grace] =
+                recovery_vars;
+            let defaults = RecoveryConfig::default();
+            RecoveryConfig {
+                max_restarts: max_restarts.map_or(defaults.max_restarts, |n| {
+                    u32::try_from(n).unwrap_or(u32::MAX)
+                }),
+                backoff_initial_secs: initial.unwrap_or(defaults.backoff_initial_secs),
+                backoff_max_secs: max.unwrap_or(defaults.backoff_max_secs),
+                stable_after_secs: stable_after.unwrap_or(defaults.stable_after_secs),
+                port_busy_retry_secs: port_busy_retry.unwrap_or(defaults.port_busy_retry_secs),
+                reconnect_grace_secs: reconnect_grace.unwrap_or(defaults.reconnect_grace_secs),
+            }
+        });
+        if let Some(recovery) = &recovery {
+            problems.extend(recovery.problems().into_iter().map(|(field, problem)| {
+                format!("MUJINA_RECOVERY_{}: {}", field.to_uppercase(), problem)
+            }));
+        }
+
+        if !problems.is_empty() {
+            return Err(InvalidConfig(problems));
+        }
+        Ok(Self {
+            network,
+            state_dir: var("MUJINA_STATE_DIR").map(PathBuf::from),
+            usb_disable: var("MUJINA_USB_DISABLE").is_some(),
+            pool_url: var("MUJINA_POOL_URL"),
+            pool_worker: var("MUJINA_POOL_WORKER"),
+            pool_password: var("MUJINA_POOL_PASSWORD"),
+            cpu_miner,
+            recovery,
+        })
+    }
+}
+
+impl RecoveryConfig {
+    /// What's wrong with these settings, by field.
+    fn problems(&self) -> Vec<(&'static str, &'static str)> {
+        let mut problems = Vec::new();
+        if self.backoff_initial_secs == 0 {
+            problems.push(("backoff_initial_secs", "must be positive"));
+        }
+        if self.backoff_initial_secs > self.backoff_max_secs {
+            problems.push((
+                "backoff_max_secs",
+                "must not be less than backoff_initial_secs",
+            ));
+        }
+        if self.port_busy_retry_secs == 0 {
+            problems.push(("port_busy_retry_secs", "must be positive"));
+        }
+        problems
+    }
+}
+
+impl RejectBreakerConfig {
+    /// What's wrong with these settings, by field.
+    fn problems(&self) -> Vec<(&'static str, &'static str)> {
+        let mut problems = Vec::new();
+        if !(self.max_reject_percent > 0.0 && self.max_reject_percent <= 100.0) {
+            problems.push(("max_reject_percent", "must be above 0 and at most 100"));
+        }
+        if self.window_secs == 0 {
+            problems.push(("window_secs", "must be positive"));
+        }
+        if self.min_shares == 0 {
+            problems.push(("min_shares", "must be positive"));
+        }
+        problems
+    }
+}
+
+impl Default for RejectBreakerConfig {
+    fn default() -> Self {
+        Self {
+            max_reject_percent: default_breaker_max_reject_percent(),
+            window_secs: default_breaker_window_secs(),
+            min_shares: default_breaker_min_shares(),
+            cooldown_secs: default_breaker_cooldown_secs(),
+        }
+    }
+}
+
+impl fmt::Debug for PoolConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PoolConfig")
+            .field("url", &self.url)
+            .field("worker", &self.worker)
+            .field("password", &self.password.as_deref().map(Redacted))
+            .field("priority", &self.priority)
+            .field("shares_per_minute", &self.shares_per_minute)
+            .field("trace", &self.trace)
+            .finish()
+    }
+}
+
+impl Default for ShareQueueConfig {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            max_shares: default_queue_max_shares(),
+            max_age_secs: default_queue_max_age_secs(),
+        }
+    }
+}
+
+impl fmt::Display for UnknownKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}: unknown key", line, self.path),
+            None => write!(f, "{}: unknown key", self.path),
+        }
+    }
+}
+
+/// The steps to the key at `path`.
+fn key_segments(path: &serde_ignored::Path, segments: &mut Vec<KeySegment>) {
+    use serde_ignored::Path;
+
+    match path {
+        Path::Root => {}
+        Path::Seq { parent, index } => {
+            key_segments(parent, segments);
+            segments.push(KeySegment::Index(*index));
+        }
+        Path::Map { parent, key } => {
+            key_segments(parent, segments);
+            segments.push(KeySegment::Key(key.clone()));
+        }
+        Path::Some { parent }
+        | Path::NewtypeStruct { parent }
+        | Path::NewtypeVariant { parent } => key_segments(parent, segments),
+    }
+}
+
+/// A key's path as validation problems name it, e.g. `pools[0].url`.
+fn key_path(segments: &[KeySegment]) -> String {
+    let mut path = String::new();
+    for segment in segments {
+        match segment {
+            KeySegment::Key(key) if path.is_empty() => path.push_str(key),
+            KeySegment::Key(key) => {
+                path.push('.');
+                path.push_str(key);
+            }
+            KeySegment::Index(index) => path.push_str(&format!("[{}]", index)),
+        }
+    }
+    path
+}
+
+/// The steps to the key at `path`, as [`key_path`] writes it.
+fn path_segments(path: &str) -> Vec<KeySegment> {
+    let mut segments = Vec::new();
+    for part in path.split('.') {
+        let (key, indices) = part.split_once('[').unwrap_or((part, ""));
+        segments.push(KeySegment::Key(key.to_string()));
+        for index in indices.split('[') {
+            if let Ok(index) = index.trim_end_matches(']').parse() {
+                segments.push(KeySegment::Index(index));
+            }
+        }
+    }
+    segments
+}
+
+/// Line of `text` the key at `segments` is on.
+fn key_line(text: &str, segments: &[KeySegment]) -> Option<usize> {
+    let root = DeTable::parse(text).ok()?;
+    let mut table = root.get_ref();
+    let mut array: Option<&[toml::Spanned<DeValue>]> = None;
+    let mut span = None;
+    for segment in segments {
+        let value = match (segment, array.take()) {
+            (KeySegment::Key(name), None) => {
+                let (key, value) = table.iter().find(|(key, _)| key.get_ref() == name)?;
+                span = Some(key.span());
+                value
+            }
+            (KeySegment::Index(index), Some(values)) => {
+                let value = values.get(*index)?;
+                span = Some(value.span());
+                value
+            }
+            _ => return None,
+        };
+        match value.get_ref() {
+            DeValue::Table(inner) => table = inner,
+            DeValue::Array(values) => array = Some(values.as_ref()),
+            _ => {}
+        }
+    }
+    let start = span?.start;
+    Some(text[..start].matches('\n').count() + 1)
+}
+
+/// Bring `table`, a config file's table holding `old`, up to date with
+/// `new`, taking changed entries from `fresh`, the serialized `new`. Only
+/// the entries that changed are touched; `inline` says whether `table` is
+/// written inline.
+fn update_table(
+    table: &mut dyn TableLike,
+    old: &toml::Table,
+    new: &toml::Table,
+    fresh: &dyn TableLike,
+    inline: bool,
+) {
+    for (key, value) in new {
+        let Some(item) = fresh.get(key) else {
+            continue;
+        };
+        match (old.get(key), value) {
+            (Some(old_value), _) if old_value == value => {}
+            (Some(toml::Value::Table(old_inner)), toml::Value::Table(new_inner))
+                if table.get(key).is_some_and(Item::is_table_like) =>
+            {
+                let inline = inline || table.get(key).is_some_and(Item::is_value);
+                let inner = table
+                    .get_mut(key)
+                    .and_then(Item::as_table_like_mut)
+                    .expect("checked above");
+                let fresh_inner = item.as_table_like().expect("serialized from a table");
+                update_table(inner, old_inner, new_inner, fresh_inner, inline);
+            }
+            _ => replace_item(table, key, item.clone(), inline),
+        }
+    }
+    for key in old.keys().filter(|key| !new.contains_key(*key)) {
+        table.remove(key);
+    }
+}
+
+/// Put `item` at `key` in `table`, in the old entry's place and under its
+/// comments.
+fn replace_item(table: &mut dyn TableLike, key: &str, mut item: Item, inline: bool) {
+    if inline {
+        item = item.into_value().map_or_else(|item| item, Item::Value);
+    }
+    let Some(slot) = table.get_mut(key) else {
+        set_position(&mut item, None);
+        table.insert(key, item);
+        return;
+    };
+
+    // A value keeps the spacing and any comment beside it
+    if let (Item::Value(old), Item::Value(new)) = (&*slot, &mut item) {
+        *new.decor_mut() = old.decor().clone();
+        *slot = item;
+        return;
     }
 
+    set_position(&mut item, position(slot));
+    let leading = leading_decor(table, key);
+    *table.get_mut(key).expect("checked above") = item;
+    if let Some(mut key) = table.key_mut(key) {
+        key.leaf_decor_mut().clear();
+    }
+    if let Some(leading) = leading {
+        set_leading_decor(table, key, leading);
+    }
+}
+
+/// Where a table, or the first of an array of tables, sits in its file.
+fn position(item: &Item) -> Option<isize> {
+    match item {
+        Item::Table(table) => table.position(),
+        Item::ArrayOfTables(array) => array.get(0)?.position(),
+        _ => None,
+    }
+}
+
+/// Place `item`'s tables at `position`, or after whatever comes before
+/// them with None.
+fn set_position(item: &mut Item, position: Option<isize>) {
+    match item {
+        Item::Table(table) => {
+            table.set_position(position);
+            for (_, child) in table.iter_mut() {
+                set_position(child, position);
+            }
+        }
+        Item::ArrayOfTables(array) => {
+            for table in array.iter_mut() {
+                table.set_position(position);
+                for (_, child) in table.iter_mut() {
+                    set_position(child, position);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Comments and blank lines above the entry at `key`.
+fn leading_decor(table: &dyn TableLike, key: &str) -> Option<RawString> {
+    match table.get(key)? {
+        Item::Table(inner) => inner.decor().prefix().cloned(),
+        Item::ArrayOfTables(array) => array.get(0)?.decor().prefix().cloned(),
+        _ => table.key(key)?.leaf_decor().prefix().cloned(),
+    }
+}
+
+/// Put `leading` above the entry at `key`.
+fn set_leading_decor(table: &mut dyn TableLike, key: &str, leading: RawString) {
+    match table.get_mut(key) {
+        Some(Item::Table(inner)) => inner.decor_mut().set_prefix(leading),
+        Some(Item::ArrayOfTables(array)) => {
+            if let Some(first) = array.get_mut(0) {
+                first.decor_mut().set_prefix(leading);
+            }
+        }
+        Some(_) => {
+            if let Some(mut key) = table.key_mut(key) {
+                key.leaf_decor_mut().set_prefix(leading);
+            }
+        }
+        None => {}
+    }
+}
+
+fn default_transition_secs() -> u64 {
+    60
+}
+
+fn default_price_poll_secs() -> u64 {
+    300
+}
+
+fn default_queue_max_shares() -> usize {
+    100
+}
+
+fn default_queue_max_age_secs() -> u64 {
+    120
+}
+
+fn default_breaker_max_reject_percent() -> f64 {
+    50.0
+}
+
+fn default_breaker_window_secs() -> u64 {
+    300
+}
+
+fn default_breaker_min_shares() -> u64 {
+    20
+}
+
+fn default_breaker_cooldown_secs() -> u64 {
+    900
+}
+
+fn default_warmup_step_volts() -> f32 {
+    warmup::DEFAULT_VOLTAGE_STEP
+}
+
+fn default_warmup_step_ms() -> u64 {
+    warmup::DEFAULT_STEP_DELAY.as_millis() as u64
+}
+
+fn default_max_input_sag_percent() -> f32 {
+    warmup::DEFAULT_MAX_INPUT_SAG * 100.0
+}
+
+fn default_max_restarts() -> u32 {
+    task::MAX_RESTARTS
+}
+
+fn default_backoff_initial_secs() -> u64 {
+    Backoff::default().initial.as_secs()
+}
+
+fn default_backoff_max_secs() -> u64 {
+    Backoff::default().max.as_secs()
+}
+
+fn default_stable_after_secs() -> u64 {
+    Backoff::default().stable_after.as_secs()
+}
+
+fn default_port_busy_retry_secs() -> u64 {
+    task::PORT_BUSY_RETRY.as_secs()
+}
+
+fn default_reconnect_grace_secs() -> u64 {
+    task::RECONNECT_GRACE.as_secs()
+}
+
+fn default_sendmail() -> PathBuf {
+    PathBuf::from("/usr/sbin/sendmail")
+}
+
+fn default_proxy_extranonce2_size() -> u8 {
+    // Pools commonly give out four bytes; two leave the scheduler room to
+    // slice the rest between miners
+    2
+}
+
+impl Config {
     /// Load configuration from a specific file.
-    pub fn load_from(_path: &Path) -> anyhow::Result<Self> {
-        // TODO: Implement TOML parsing
-        unimplemented!("Config loading not yet implemented")
+    pub fn load_from(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("reading config file {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("parsing config file {}", path.display()))
+    }
+
+    /// Write the configuration to a file, replacing it atomically.
+    ///
+    /// An existing file is edited in place: only the settings that changed
+    /// are rewritten, and comments and formatting elsewhere are kept.
+    pub fn save_to(&self, path: &Path) -> anyhow::Result<()> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(existing) => self
+                .edit(&existing)
+                .with_context(|| format!("updating config file {}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                toml::to_string_pretty(self).context("serializing config")?
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("reading config file {}", path.display()))
+            }
+        };
+        let tmp = path.with_extension("toml.tmp");
+        std::fs::write(&tmp, text).with_context(|| format!("writing {}", tmp.display()))?;
+        std::fs::rename(&tmp, path)
+            .with_context(|| format!("replacing config file {}", path.display()))
+    }
+
+    /// The config file `text` changed to hold this configuration.
+    fn edit(&self, text: &str) -> anyhow::Result<String> {
+        let mut doc: DocumentMut = text.parse()?;
+        let old = toml::Table::try_from(Self::parse(text)?)?;
+        let new = toml::Table::try_from(self)?;
+        let fresh: DocumentMut = toml::to_string_pretty(self)?.parse()?;
+        update_table(doc.as_table_mut(), &old, &new, fresh.as_table(), false);
+        Ok(doc.to_string())
+    }
+
+    /// Parse configuration from TOML text.
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        Ok(toml::from_str(text)?)
+    }
+
+    /// Parse configuration from TOML text like [`Self::parse`], also
+    /// returning the keys no setting has.
+    pub fn parse_checked(text: &str) -> anyhow::Result<(Self, Vec<UnknownKey>)> {
+        let mut unknown = Vec::new();
+        let config = serde_ignored::deserialize(toml::de::Deserializer::parse(text)?, |path| {
+            let mut segments = Vec::new();
+            key_segments(&path, &mut segments);
+            unknown.push(UnknownKey {
+                path: key_path(&segments),
+                line: key_line(text, &segments),
+            });
+        })?;
+        Ok((config, unknown))
+    }
+
+    /// Everything wrong with the config file `text`: a TOML error, keys no
+    /// setting has, and settings that don't make sense. Problems are
+    /// prefixed with their line where it's known.
+    pub fn check(text: &str) -> Vec<String> {
+        let (config, mut unknown) = match Self::parse_checked(text) {
+            Ok(parsed) => parsed,
+            // TOML errors carry their own line and column
+            Err(e) => return vec![e.to_string().trim_end().to_string()],
+        };
+        unknown.sort_by_key(|key| key.line);
+        let mut problems: Vec<String> = unknown.iter().map(ToString::to_string).collect();
+        if let Err(InvalidConfig(invalid)) = config.validate() {
+            problems.extend(invalid.into_iter().map(|problem| {
+                let line = problem
+                    .split_once(": ")
+                    .and_then(|(path, _)| key_line(text, &path_segments(path)));
+                match line {
+                    Some(line) => format!("line {}: {}", line, problem),
+                    None => problem,
+                }
+            }));
+        }
+        problems
+    }
+
+    /// Check the settings make sense together, reporting every problem.
+    pub fn validate(&self) -> Result<(), InvalidConfig> {
+        let mut problems = Vec::new();
+
+        if let Err(e) = crate::tracing::parse_level(&self.daemon.log_level) {
+            problems.push(format!("daemon.log_level: {}", e));
+        }
+        for (i, pool) in self.pools.iter().enumerate() {
+            if let Err(e) = crate::pools::check_pool(pool, self.daemon.network) {
+                problems.push(format!("pools[{}]: {}", i, e));
+            }
+        }
+        if self.hardware.temp_limit.is_nan() || self.hardware.temp_limit <= 0.0 {
+            problems.push("hardware.temp_limit: must be positive".into());
+        }
+        if let Some(limit) = self.hardware.power_limit {
+            if limit.is_nan() || limit <= 0.0 {
+                problems.push("hardware.power_limit: must be positive".into());
+            }
+        }
+        for (id, weight) in &self.hardware.power_weights {
+            if weight.is_nan() || *weight <= 0.0 {
+                problems.push(format!("hardware.power_weights.{}: must be positive", id));
+            }
+        }
+        if let Some(warmup) = &self.hardware.warmup {
+            if !(warmup.step_volts.is_finite() && warmup.step_volts > 0.0) {
+                problems.push("hardware.warmup.step_volts: must be positive".into());
+            }
+            if !(0.0..100.0).contains(&warmup.max_input_sag_percent) {
+                problems.push("hardware.warmup.max_input_sag_percent: must be 0 to 100".into());
+            }
+            if warmup
+                .max_core_current_a
+                .is_some_and(|amps| !(amps.is_finite() && amps > 0.0))
+            {
+                problems.push("hardware.warmup.max_core_current_a: must be positive".into());
+            }
+        }
+        if self.hardware.fan_min_rpm > self.hardware.fan_max_rpm {
+            problems.push("hardware.fan_min_rpm: must not exceed fan_max_rpm".into());
+        }
+        for (i, board) in self.hardware.direct.iter().enumerate() {
+            if board.id.is_empty() {
+                problems.push(format!("hardware.direct[{}].id: must not be empty", i));
+            } else if self.hardware.direct[..i].iter().any(|b| b.id == board.id) {
+                problems.push(format!(
+                    "hardware.direct[{}].id: '{}' is used by another board",
+                    i, board.id
+                ));
+            }
+        }
+        if self.api.listen.parse::<SocketAddr>().is_err() {
+            problems.push(format!(
+                "api.listen: '{}' isn't an address and port",
+                self.api.listen
+            ));
+        }
+        if self.api.tls && (self.api.cert_path.is_none() || self.api.key_path.is_none()) {
+            problems.push("api.tls: needs cert_path and key_path".into());
+        }
+        if let Some(schedule) = &self.schedule {
+            // Otherwise leaving eco couldn't undo it
+            if (schedule.eco.voltage.is_some() && schedule.full.voltage.is_none())
+                || (schedule.eco.frequency_mhz.is_some() && schedule.full.frequency_mhz.is_none())
+            {
+                problems.push("schedule.full: must set everything schedule.eco sets".into());
+            }
+            for (i, window) in schedule.windows.iter().enumerate() {
+                for time in [&window.start, &window.end] {
+                    if let Err(e) = crate::schedule::parse_time_of_day(time) {
+                        problems.push(format!("schedule.windows[{}]: {}", i, e));
+                    }
+                }
+            }
+            if let Some(price) = &schedule.price {
+                if !price.url.starts_with("http://") && !price.url.starts_with("https://") {
+                    problems.push(format!(
+                        "schedule.price.url: '{}' isn't an HTTP URL",
+                        price.url
+                    ));
+                }
+                if price.poll_secs == 0 {
+                    problems.push("schedule.price.poll_secs: must be positive".into());
+                }
+            }
+        }
+
+        if let Some(proxy) = &self.proxy {
+            if proxy.listen.parse::<SocketAddr>().is_err() {
+                problems.push(format!(
+                    "proxy.listen: '{}' isn't an address and port",
+                    proxy.listen
+                ));
+            }
+            if !(1..=7).contains(&proxy.extranonce2_size) {
+                problems.push("proxy.extranonce2_size: must be 1 to 7".into());
+            }
+        }
+        if let Some(recovery) = &self.recovery {
+            for (field, problem) in recovery.problems() {
+                problems.push(format!("recovery.{}: {}", field, problem));
+            }
+        }
+        if let Some(breaker) = &self.reject_breaker {
+            for (field, problem) in breaker.problems() {
+                problems.push(format!("reject_breaker.{}: {}", field, problem));
+            }
+        }
+        if let Some(alerts) = &self.alerts {
+            if alerts
+                .max_temperature_c
+                .is_some_and(|limit| limit.is_nan() || limit <= 0.0)
+            {
+                problems.push("alerts.max_temperature_c: must be positive".into());
+            }
+            if alerts
+                .max_reject_percent
+                .is_some_and(|limit| !(0.0..100.0).contains(&limit))
+            {
+                problems.push("alerts.max_reject_percent: must be 0 to 100".into());
+            }
+            if alerts.webhooks.is_empty() && alerts.email.is_none() {
+                problems.push("alerts: needs webhooks or email to send alerts to".into());
+            }
+            for (i, webhook) in alerts.webhooks.iter().enumerate() {
+                if secret::is_reference(&webhook.url) {
+                    if let Err(e) = secret::check(&webhook.url) {
+                        problems.push(format!("alerts.webhooks[{}].url: {}", i, e));
+                    }
+                } else if !webhook.url.starts_with("http://")
+                    && !webhook.url.starts_with("https://")
+                {
+                    problems.push(format!("alerts.webhooks[{}].url: isn't an HTTP URL", i));
+                }
+                if webhook.format == WebhookFormat::Telegram && webhook.chat_id.is_none() {
+                    problems.push(format!(
+                        "alerts.webhooks[{}].chat_id: needed for telegram",
+                        i
+                    ));
+                }
+            }
+            if let Some(email) = &alerts.email {
+                if email.to.is_empty() {
+                    problems.push("alerts.email.to: must not be empty".into());
+                }
+            }
+        }
+
+        for (model, set) in &self.presets {
+            for (preset, point) in set.iter() {
+                if let Err(e) = point.validate() {
+                    problems.push(format!("presets.{:?}.{}: {}", model, preset, e));
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(InvalidConfig(problems))
+        }
+    }
+
+    /// This configuration with secrets replaced by [`REDACTED`]. References
+    /// to secrets are left as they are.
+    pub fn redacted(&self) -> Self {
+        let mut config = self.clone();
+        for pool in &mut config.pools {
+            if pool
+                .password
+                .as_deref()
+                .is_some_and(|password| !secret::is_reference(password))
+            {
+                pool.password = Some(REDACTED.into());
+            }
+        }
+        // Webhook URLs carry their token
+        for webhook in config.alerts.iter_mut().flat_map(|a| &mut a.webhooks) {
+            if !secret::is_reference(&webhook.url) {
+                webhook.url = REDACTED.into();
+            }
+        }
+        config
+    }
+
+    /// Fill secrets left as [`REDACTED`] from `current`, so a client can
+    /// send back what it was shown.
+    ///
+    /// Pools are matched by URL and worker.
+    pub fn restore_secrets(&mut self, current: &Config) {
+        for pool in &mut self.pools {
+            if pool.password.as_deref() == Some(REDACTED) {
+                pool.password = current
+                    .pools
+                    .iter()
+                    .find(|p| p.url == pool.url && p.worker == pool.worker)
+                    .and_then(|p| p.password.clone());
+            }
+        }
+        // Webhooks are matched by position
+        let current_webhooks = current.alerts.as_ref().map(|a| &a.webhooks[..]);
+        for (i, webhook) in self
+            .alerts
+            .iter_mut()
+            .flat_map(|a| &mut a.webhooks)
+            .enumerate()
+        {
+            if webhook.url == REDACTED {
+                if let Some(url) = current_webhooks.and_then(|w| w.get(i)).map(|w| &w.url) {
+                    webhook.url = url.clone();
+                }
+            }
+        }
+    }
+
+    /// Settings that differ in `new` and can be applied while running.
+    pub fn reloadable_changes(&self, new: &Config) -> Vec<&'static str> {
+        let mut changes = Vec::new();
+        if self.daemon.log_level != new.daemon.log_level {
+            changes.push("daemon.log_level");
+        }
+        if self.pools != new.pools {
+            changes.push("pools");
+        }
+        changes
+    }
+
+    /// Settings that differ in `new` and take effect only after a restart.
+    pub fn restart_required_changes(&self, new: &Config) -> Vec<&'static str> {
+        let mut changes = Vec::new();
+        if self.daemon.pid_file != new.daemon.pid_file {
+            changes.push("daemon.pid_file");
+        }
+        if self.daemon.systemd != new.daemon.systemd {
+            changes.push("daemon.systemd");
+        }
+        if self.daemon.network != new.daemon.network {
+            changes.push("daemon.network");
+        }
+        if self.daemon.state_dir != new.daemon.state_dir {
+            changes.push("daemon.state_dir");
+        }
+        if self.daemon.paused != new.daemon.paused {
+            changes.push("daemon.paused");
+        }
+        if self.hardware != new.hardware {
+            changes.push("hardware");
+        }
+        if self.api != new.api {
+            changes.push("api");
+        }
+        if self.schedule != new.schedule {
+            changes.push("schedule");
+        }
+        if self.share_queue != new.share_queue {
+            changes.push("share_queue");
+        }
+        if self.reject_breaker != new.reject_breaker {
+            changes.push("reject_breaker");
+        }
+        if self.proxy != new.proxy {
+            changes.push("proxy");
+        }
+        if self.recovery != new.recovery {
+            changes.push("recovery");
+        }
+        if self.alerts != new.alerts {
+            changes.push("alerts");
+        }
+        if self.presets != new.presets {
+            changes.push("presets");
+        }
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_example() {
+        let config = Config::parse(
+            r#"
+            [daemon]
+            log_level = "debug"
+            systemd = true
+
+            [[pools]]
+            url = "stratum+tcp://pool.example.com:3333"
+            worker = "bc1qexample.rig1"
+
+            [hardware]
+            temp_limit = 85.0
+            fan_min_rpm = 1000
+            fan_max_rpm = 6000
+
+            [api]
+            listen = "127.0.0.1:7785"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.daemon.log_level, "debug");
+        assert!(config.daemon.systemd);
+        assert_eq!(config.daemon.network, Network::Mainnet);
+        assert_eq!(config.pools.len(), 1);
+        assert_eq!(config.pools[0].worker, "bc1qexample.rig1");
+        assert_eq!(config.pools[0].password, None);
+        assert!(!config.pools[0].trace);
+        assert_eq!(config.api.listen, "127.0.0.1:7785");
+    }
+
+    #[test]
+    fn test_parse_network() {
+        let config = Config::parse(
+            r#"
+            pools = []
+
+            [daemon]
+            log_level = "info"
+            network = "regtest"
+
+            [hardware]
+            temp_limit = 85.0
+            fan_min_rpm = 1000
+            fan_max_rpm = 6000
+
+            [api]
+            listen = "127.0.0.1:7785"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.daemon.network, Network::Regtest);
+    }
+
+    #[test]
+    fn test_save_round_trip() {
+        let dir = std::env::temp_dir().join(format!("mujina-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("mujina.toml");
+        let mut config = Config::parse(
+            r#"
+            pools = []
+
+            [daemon]
+            log_level = "info"
+
+            [hardware]
+            temp_limit = 85.0
+            fan_min_rpm = 1000
+            fan_max_rpm = 6000
+
+            [api]
+            listen = "127.0.0.1:7785"
+            "#,
+        )
+        .unwrap();
+        config.pools.push(PoolConfig {
+            url: "stratum+tcp://pool.example.com:3333".into(),
+            worker: "rig1".into(),
+            password: None,
+            priority: 2,
+            shares_per_minute: None,
+            trace: true,
+        });
+
+        config.save_to(&path).unwrap();
+        let saved = Config::load_from(&path).unwrap();
+        assert_eq!(saved.pools.len(), 1);
+        assert_eq!(saved.pools[0].priority, 2);
+        assert!(saved.pools[0].trace);
+        assert_eq!(saved.api.listen, "127.0.0.1:7785");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_save_replaces_only_pools() {
+        let mut config = Config::parse(DEFAULT_CONFIG).unwrap();
+        config.pools.push(PoolConfig {
+            url: "stratum+tcp://pool.example.com:3333".into(),
+            worker: "rig1".into(),
+            password: None,
+            priority: 0,
+            shares_per_minute: None,
+            trace: false,
+        });
+
+        let added = config.edit(DEFAULT_CONFIG).unwrap();
+        assert_eq!(Config::parse(&added).unwrap(), config);
+        assert!(added.contains("# Pools, in priority order"));
+        let pools = added.find("[[pools]]").unwrap();
+        assert!(pools < added.find("[daemon]").unwrap());
+        // Everything from the first section on is as it was
+        let rest = DEFAULT_CONFIG.find("# [[pools]]").unwrap();
+        assert!(added.ends_with(&DEFAULT_CONFIG[rest..]));
+
+        // And back again
+        config.pools.clear();
+        let removed = config.edit(&added).unwrap();
+        assert_eq!(removed, DEFAULT_CONFIG);
+    }
+
+    #[test]
+    fn test_save_keeps_comments() {
+        const COMMENTED: &str = r#"# Rig in the garage
+pools = []
+
+[daemon]
+# Quiet unless something's wrong
+log_level = "warn"    # bumped while debugging
+network = "mainnet"
+
+[hardware]
+temp_limit = 80.0 # keeps the garage cool
+fan_min_rpm = 1000
+fan_max_rpm = 6000
+power_weights = { e2f56f9b = 2.0 } # the Gamma
+
+# Only on the LAN
+[api]
+listen = "192.168.1.10:7785"
+"#;
+        let dir = std::env::temp_dir().join(format!("mujina-comments-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("mujina.toml");
+        std::fs::write(&path, COMMENTED).unwrap();
+
+        // Saving what the file holds changes nothing
+        let mut config = Config::load_from(&path).unwrap();
+        config.save_to(&path).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), COMMENTED);
+
+        config.daemon.log_level = "debug".into();
+        config.hardware.temp_limit = 75.0;
+        config.hardware.power_weights.insert("e2f56f9b".into(), 3.0);
+        config.save_to(&path).unwrap();
+        let saved = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            saved,
+            COMMENTED
+                .replace(r#""warn""#, r#""debug""#)
+                .replace("80.0", "75.0")
+                .replace("2.0", "3.0")
+        );
+        assert_eq!(Config::parse(&saved).unwrap(), config);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_rejects_missing_section() {
+        assert!(Config::parse("[daemon]\nlog_level = \"info\"\n").is_err());
+    }
+
+    fn example() -> Config {
+        Config::parse(
+            r#"
+            [daemon]
+            log_level = "info"
+
+            [[pools]]
+            url = "stratum+tcp://pool.example.com:3333"
+            worker = "rig1"
+            password = "hunter2"
+
+            [hardware]
+            temp_limit = 85.0
+            fan_min_rpm = 1000
+            fan_max_rpm = 6000
+
+            [api]
+            listen = "127.0.0.1:7785"
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_default_config() {
+        let config = Config::parse(DEFAULT_CONFIG).unwrap();
+        assert_eq!(Config::check(DEFAULT_CONFIG), Vec::<String>::new());
+        assert_eq!(config.api.listen, "127.0.0.1:7785");
+
+        // Every commented-out setting is valid too, the example pool
+        // standing in for the empty list
+        let uncommented: String = DEFAULT_CONFIG
+            .lines()
+            .filter(|line| *line != "pools = []")
+            .map(|line| match line.strip_prefix("# ") {
+                Some(setting)
+                    if setting.contains(" = ")
+                        || setting.trim_start().starts_with(['[', ']', '{']) =>
+                {
+                    setting
+                }
+                _ => line,
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(Config::check(&uncommented), Vec::<String>::new());
+        let full = Config::parse(&uncommented).unwrap();
+        assert_eq!(full.pools.len(), 1);
+        assert_eq!(full.hardware.direct.len(), 1);
+        assert_eq!(full.recovery, Some(RecoveryConfig::default()));
+        assert_eq!(full.share_queue.unwrap().max_shares, 100);
+        assert_eq!(full.reject_breaker, Some(RejectBreakerConfig::default()));
+        assert_eq!(full.schedule.unwrap().windows.len(), 1);
+        assert!(full.proxy.is_some());
+        assert_eq!(full.alerts.unwrap().webhooks.len(), 2);
+    }
+
+    #[test]
+    fn test_check_locates_problems() {
+        let text = r#"
+            [daemon]
+            log_level = "info"
+
+            [[pools]]
+            url = "stratum+tcp://pool.example.com:3333"
+            worker = "rig1"
+
+            [[pools]]
+            url = "stratum+tcp://backup.example.com"
+            worker = "rig1"
+            pasword = "x"
+
+            [hardware]
+            temp_limit = 85.0
+            fan_min_rpm = 1000
+            fan_max_rpm = 6000
+            fan_mode = "auto"
+
+            [api]
+            listen = "localhost"
+            "#;
+        assert_eq!(
+            Config::check(text),
+            [
+                "line 12: pools[1].pasword: unknown key",
+                "line 18: hardware.fan_mode: unknown key",
+                "line 9: pools[1]: invalid pool: URL 'stratum+tcp://backup.example.com' isn't of the form stratum+tcp://host:port",
+                "line 21: api.listen: 'localhost' isn't an address and port",
+            ]
+        );
+
+        let problems = Config::check("[daemon]\nlog_level = 3\n");
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("line 2"), "{}", problems[0]);
+    }
+
+    #[test]
+    fn test_validate_reports_every_problem() {
+        assert_eq!(example().validate(), Ok(()));
+
+        let mut config = example();
+        config.daemon.log_level = "loud".into();
+        config.pools[0].url = "stratum+tcp://pool.example.com".into();
+        config.hardware.fan_min_rpm = 7000;
+        let problems = config.validate().unwrap_err().0;
+        assert_eq!(problems.len(), 3);
+        assert!(problems[0].starts_with("daemon.log_level"));
+        assert!(problems[1].starts_with("pools[0]"));
+        assert!(problems[2].starts_with("hardware.fan_min_rpm"));
+    }
+
+    #[test]
+    fn test_parse_direct_boards() {
+        let mut config = Config::parse(
+            r#"
+            pools = []
+
+            [daemon]
+            log_level = "info"
+
+            [hardware]
+            temp_limit = 85.0
+            fan_min_rpm = 1000
+            fan_max_rpm = 6000
+
+            [[hardware.direct]]
+            id = "pi-gamma"
+            uart = "/dev/ttyAMA0"
+            i2c = "/dev/i2c-1"
+            reset_line = 17
+
+            [api]
+            listen = "127.0.0.1:7785"
+            "#,
+        )
+        .unwrap();
+        let board = &config.hardware.direct[0];
+        assert_eq!(board.gpio_chip, PathBuf::from("/dev/gpiochip0"));
+        assert_eq!(board.reset_line, 17);
+        assert_eq!(config.validate(), Ok(()));
+
+        let mut twin = board.clone();
+        twin.uart = "/dev/ttyAMA1".into();
+        config.hardware.direct.push(twin);
+        let problems = config.validate().unwrap_err().0;
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("hardware.direct[1].id"));
+    }
+
+    /// Environment settings from `vars`.
+    fn env(vars: &[(&str, &str)]) -> Result<EnvConfig, InvalidConfig> {
+        let vars: BTreeMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        EnvConfig::from_lookup(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn test_env_cpu_miner() {
+        assert!(env(&[]).unwrap().cpu_miner.is_none());
+
+        let cpu = env(&[("MUJINA_CPUMINER_THREADS", "2")])
+            .unwrap()
+            .cpu_miner
+            .unwrap();
+        assert_eq!((cpu.thread_count, cpu.duty_percent), (2, 50));
+
+        // Duty is clamped to 1-100
+        for (duty, clamped) in [("150", 100), ("0", 1)] {
+            let cpu = env(&[
+                ("MUJINA_CPUMINER_THREADS", "99"),
+                ("MUJINA_CPUMINER_DUTY", duty),
+            ])
+            .unwrap()
+            .cpu_miner
+            .unwrap();
+            assert_eq!(cpu.duty_percent, clamped);
+        }
+    }
+
+    #[test]
+    fn test_env_renamed_variables() {
+        let old = env(&[("MUJINA_POOL_USER", "rig1"), ("MUJINA_POOL_PASS", "x")]).unwrap();
+        assert_eq!(old.pool_worker.as_deref(), Some("rig1"));
+        assert_eq!(old.pool_password.as_deref(), Some("x"));
+
+        // The new name wins when both are set
+        let both = env(&[("MUJINA_POOL_USER", "rig1"), ("MUJINA_POOL_WORKER", "rig2")]).unwrap();
+        assert_eq!(both.pool_worker.as_deref(), Some("rig2"));
+    }
+
+    #[test]
+    fn test_env_recovery() {
+        assert!(env(&[]).unwrap().recovery.is_none());
+
+        let recovery = env(&[("MUJINA_RECOVERY_MAX_RESTARTS", "10")])
+            .unwrap()
+            .recovery
+            .unwrap();
+        assert_eq!(
+            recovery,
+            RecoveryConfig {
+                max_restarts: 10,
+                ..RecoveryConfig::default()
+            }
+        );
+        let recovery = env(&[("MUJINA_RECOVERY_RECONNECT_GRACE_SECS", "0")])
+            .unwrap()
+            .recovery
+            .unwrap();
+        assert_eq!(recovery.reconnect_grace_secs, 0);
+
+        let problems = env(&[
+            ("MUJINA_RECOVERY_STABLE_AFTER_SECS", "soon"),
+            ("MUJINA_NETWORK", "moonnet"),
+        ])
+        .unwrap_err()
+        .0;
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].starts_with("MUJINA_RECOVERY_STABLE_AFTER_SECS"));
+        assert!(problems[1].starts_with("MUJINA_NETWORK"));
+
+        let problems = env(&[("MUJINA_RECOVERY_BACKOFF_INITIAL_SECS", "120")])
+            .unwrap_err()
+            .0;
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("MUJINA_RECOVERY_BACKOFF_MAX_SECS"));
+    }
+
+    #[test]
+    fn test_parse_recovery() {
+        let mut config = example();
+        assert!(config.recovery.is_none());
+        config.recovery = Some(RecoveryConfig {
+            backoff_initial_secs: 0,
+            ..RecoveryConfig::default()
+        });
+        let problems = config.validate().unwrap_err().0;
+        assert_eq!(
+            problems,
+            ["recovery.backoff_initial_secs: must be positive"]
+        );
+
+        let text = toml::to_string(&Config {
+            recovery: Some(RecoveryConfig {
+                max_restarts: 3,
+                ..RecoveryConfig::default()
+            }),
+            ..example()
+        })
+        .unwrap();
+        let parsed = Config::parse(&text).unwrap();
+        assert_eq!(parsed.recovery.unwrap().max_restarts, 3);
+        let partial = Config::parse(&text.replace("max_restarts = 3\n", "")).unwrap();
+        assert_eq!(partial.recovery, Some(RecoveryConfig::default()));
+    }
+
+    #[test]
+    fn test_parse_warmup() {
+        let mut config = example();
+        config.hardware.warmup = Some(WarmupConfig {
+            step_volts: 0.02,
+            max_core_current_a: Some(-1.0),
+            ..WarmupConfig::default()
+        });
+        assert_eq!(
+            config.validate().unwrap_err().0,
+            ["hardware.warmup.max_core_current_a: must be positive"]
+        );
+
+        let text = toml::to_string(&config).unwrap();
+        let parsed = Config::parse(&text.replace("max_core_current_a = -1.0\n", "")).unwrap();
+        let warmup = parsed.hardware.warmup.unwrap();
+        assert_eq!(warmup.step_volts, 0.02);
+        assert_eq!(warmup.step_ms, 250);
+        assert_eq!(warmup.max_core_current_a, None);
+    }
+
+    #[test]
+    fn test_parse_power_weights() {
+        let mut config = example();
+        assert!(config.hardware.power_weights.is_empty());
+
+        let config_text = r#"
+            pools = []
+
+            [daemon]
+            log_level = "info"
+
+            [hardware]
+            temp_limit = 85.0
+            fan_min_rpm = 1000
+            fan_max_rpm = 6000
+            power_limit = 100.0
+
+            [hardware.power_weights]
+            e2f56f9b = 2.0
+
+            [api]
+            listen = "127.0.0.1:7785"
+            "#;
+        let parsed = Config::parse(config_text).unwrap();
+        assert_eq!(parsed.hardware.power_limit, Some(100.0));
+        assert_eq!(parsed.hardware.power_weights.get("e2f56f9b"), Some(&2.0));
+
+        config.hardware.power_limit = Some(0.0);
+        config
+            .hardware
+            .power_weights
+            .insert("e2f56f9b".into(), -1.0);
+        assert_eq!(config.validate().unwrap_err().0.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_schedule() {
+        let mut config = Config::parse(
+            r#"
+            pools = []
+
+            [daemon]
+            log_level = "info"
+
+            [hardware]
+            temp_limit = 85.0
+            fan_min_rpm = 1000
+            fan_max_rpm = 6000
+
+            [api]
+            listen = "127.0.0.1:7785"
+
+            [schedule]
+            full = { voltage = 1.2 }
+            eco = { voltage = 1.1 }
+
+            [[schedule.windows]]
+            start = "17:00"
+            end = "21:00"
+            profile = "off"
+
+            [schedule.price]
+            url = "https://prices.example.com/now.json"
+            off_above = 0.3
+            "#,
+        )
+        .unwrap();
+        let schedule = config.schedule.clone().unwrap();
+        assert_eq!(schedule.default, Profile::Full);
+        assert_eq!(schedule.eco.voltage, Some(1.1));
+        assert_eq!(schedule.transition_secs, 60);
+        assert_eq!(schedule.windows[0].profile, Profile::Off);
+        assert_eq!(schedule.price.unwrap().poll_secs, 300);
+        assert_eq!(config.validate(), Ok(()));
+
+        config.schedule.as_mut().unwrap().windows[0].end = "25:00".into();
+        let problems = config.validate().unwrap_err().0;
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("schedule.windows[0]"));
+    }
+
+    #[test]
+    fn test_parse_reject_breaker() {
+        let mut config = example();
+        assert!(config.reject_breaker.is_none());
+        config.reject_breaker = Some(RejectBreakerConfig {
+            max_reject_percent: 0.0,
+            ..RejectBreakerConfig::default()
+        });
+        assert_eq!(
+            config.validate().unwrap_err().0,
+            ["reject_breaker.max_reject_percent: must be above 0 and at most 100"]
+        );
+
+        let text = toml::to_string(&Config {
+            reject_breaker: Some(RejectBreakerConfig {
+                window_secs: 60,
+                ..RejectBreakerConfig::default()
+            }),
+            ..example()
+        })
+        .unwrap();
+        let parsed = Config::parse(&text).unwrap();
+        assert_eq!(parsed.reject_breaker.unwrap().window_secs, 60);
+        let partial = Config::parse(&text.replace("window_secs = 60\n", "")).unwrap();
+        assert_eq!(partial.reject_breaker, Some(RejectBreakerConfig::default()));
+    }
+
+    #[test]
+    fn test_parse_share_queue() {
+        let config = Config::parse(
+            r#"
+            pools = []
+
+            [daemon]
+            log_level = "info"
+
+            [hardware]
+            temp_limit = 85.0
+            fan_min_rpm = 1000
+            fan_max_rpm = 6000
+
+            [api]
+            listen = "127.0.0.1:7785"
+
+            [share_queue]
+            dir = "/var/lib/mujina/shares"
+            "#,
+        )
+        .unwrap();
+        let queue = config.share_queue.unwrap();
+        assert_eq!(queue.dir, Some(PathBuf::from("/var/lib/mujina/shares")));
+        assert_eq!(queue.max_shares, 100);
+        assert_eq!(queue.max_age_secs, 120);
+    }
+
+    #[test]
+    fn test_parse_proxy() {
+        let mut config = Config::parse(
+            r#"
+            pools = []
+
+            [daemon]
+            log_level = "info"
+
+            [hardware]
+            temp_limit = 85.0
+            fan_min_rpm = 1000
+            fan_max_rpm = 6000
+
+            [api]
+            listen = "127.0.0.1:7785"
+
+            [proxy]
+            listen = "0.0.0.0:3333"
+            "#,
+        )
+        .unwrap();
+        let proxy = config.proxy.clone().unwrap();
+        assert_eq!(proxy.extranonce2_size, 2);
+        assert_eq!(config.validate(), Ok(()));
+
+        let proxy = config.proxy.as_mut().unwrap();
+        proxy.listen = "3333".into();
+        proxy.extranonce2_size = 8;
+        let problems = config.validate().unwrap_err().0;
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].starts_with("proxy.listen"));
+    }
+
+    #[test]
+    fn test_redacted_secrets_restored() {
+        let current = example();
+        let shown = current.redacted();
+        assert_eq!(shown.pools[0].password.as_deref(), Some(REDACTED));
+
+        let mut update = shown.clone();
+        update.daemon.log_level = "debug".into();
+        update.restore_secrets(&current);
+        assert_eq!(update.pools[0].password.as_deref(), Some("hunter2"));
+
+        assert_eq!(current.reloadable_changes(&update), ["daemon.log_level"]);
+        assert!(current.restart_required_changes(&update).is_empty());
+
+        // A reference isn't a secret, so it's shown, but kept from logs
+        let mut referring = example();
+        referring.pools[0].password = Some("credential:pool-password".into());
+        assert_eq!(
+            referring.redacted().pools[0].password.as_deref(),
+            Some("credential:pool-password")
+        );
+        assert!(!format!("{:?}", current).contains("hunter2"));
+    }
+
+    #[test]
+    fn test_parse_alerts() {
+        let mut config = Config::parse(
+            r#"
+            pools = []
+
+            [daemon]
+            log_level = "info"
+
+            [hardware]
+            temp_limit = 85.0
+            fan_min_rpm = 1000
+            fan_max_rpm = 6000
+
+            [api]
+            listen = "127.0.0.1:7785"
+
+            [alerts]
+            board_offline_secs = 300
+            max_reject_percent = 5.0
+            webhooks = [
+                { url = "https://discord.com/api/webhooks/1/token", format = "discord" },
+                { url = "https://api.telegram.org/botTOKEN/sendMessage", format = "telegram" },
+            ]
+            "#,
+        )
+        .unwrap();
+        let problems = config.validate().unwrap_err().0;
+        assert_eq!(
+            problems,
+            ["alerts.webhooks[1].chat_id: needed for telegram"]
+        );
+
+        let alerts = config.alerts.as_mut().unwrap();
+        alerts.webhooks[1].chat_id = Some("-100123".into());
+        assert_eq!(alerts.webhooks[0].format, WebhookFormat::Discord);
+        assert!(config.validate().is_ok());
+
+        // The token is in the URL
+        let shown = config.redacted();
+        assert_eq!(shown.alerts.as_ref().unwrap().webhooks[0].url, REDACTED);
+        let mut update = shown.clone();
+        update.restore_secrets(&config);
+        assert_eq!(update, config);
+    }
+
+    #[test]
+    fn test_parse_presets() {
+        let config = Config::parse(
+            r#"
+            pools = []
+
+            [daemon]
+            log_level = "info"
+
+            [hardware]
+            temp_limit = 85.0
+            fan_min_rpm = 1000
+            fan_max_rpm = 6000
+
+            [api]
+            listen = "127.0.0.1:7785"
+
+            [presets.default]
+            eco = { frequency_mhz = 400.0, voltage = 1.1 }
+
+            [presets."Bitaxe Gamma"]
+            turbo = { frequency_mhz = 600.0, fan = { mode = "fixed", percent = 120 } }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.validate().unwrap_err().0,
+            ["presets.\"Bitaxe Gamma\".turbo: fan percent 120 is over 100"]
+        );
+        assert_eq!(config.presets["default"].eco.unwrap().voltage, Some(1.1));
+        assert!(config.presets["default"].turbo.is_none());
+
+        let mut changed = config.clone();
+        changed.presets.remove("default");
+        assert_eq!(config.restart_required_changes(&changed), ["presets"]);
     }
 }
@@ -5,20 +5,31 @@
 //!
 //! # Performance
 //!
-//! This implementation prioritizes readability over performance. It achieves
-//! ~5 MH/s per thread, which is sufficient for testing and development but
-//! leaves significant performance on the table. Potential optimizations:
+//! Hashing goes through [`HeaderHasher`], which avoids redundant work per
+//! nonce:
 //!
-//! - **Midstate caching**: Precompute SHA-256 state after the first 64 bytes
-//!   of the header (which don't change between nonces), saving ~50% of hash
-//!   work. This alone can double throughput.
+//! - **Midstate caching**: The first 64 bytes of the header (version,
+//!   previous block hash, most of the merkle root) don't change between
+//!   nonces, so the SHA-256 state after that block is computed once per
+//!   task. Each nonce then costs two compression rounds instead of three.
 //!
-//! - **SIMD multi-buffer hashing**: Process 4-8 nonces in parallel using
-//!   AVX2/AVX-512 intrinsics. This abandons SHA-NI (which is single-stream)
-//!   in favor of wider SIMD registers, potentially achieving 2-4x throughput.
+//! - **Hardware SHA**: Compression uses `sha2::compress256`, which detects
+//!   the SHA extensions (x86 SHA-NI, ARMv8 SHA2) at runtime via `std::arch`
+//!   and falls back to portable code elsewhere.
 //!
-//! Production CPU miners like cpuminer-opt use these techniques to achieve
-//! 10-20+ MH/s per thread on modern hardware.
+//! Production CPU miners go further with multi-buffer hashing---4-8 nonces
+//! in parallel across AVX2/AVX-512 lanes---which is not worth the complexity
+//! for a development backend.
+//!
+//! # Nonce partitioning
+//!
+//! Each thread searches a disjoint slice of the 32-bit nonce space
+//! ([`NonceRange`]), so threads never repeat each other's work even when
+//! given the same task. When a thread exhausts its slice it moves to the
+//! next extranonce2 in the task's range, which changes the merkle root and
+//! so the whole header. Once the extranonce2 range is used up it reports
+//! `WorkExhausted` to the scheduler and rolls ntime forward until new work
+//! arrives, as far as the pool accepts (see [`crate::job_source::clock`]).
 
 use std::sync::{
     atomic::{AtomicBool, Ordering},
@@ -27,16 +38,28 @@ use std::sync::{
 };
 use std::time::{Duration, Instant};
 
-use bitcoin::block::Header as BlockHeader;
+use bitcoin::{block::Header as BlockHeader, hashes::Hash, BlockHash};
+use sha2::digest::generic_array::GenericArray;
+use tokio::sync::mpsc as tokio_mpsc;
 
 use crate::{
-    asic::hash_thread::{HashTask, HashThreadError, HashThreadStatus, Share},
-    job_source::MerkleRootKind,
+    asic::hash_thread::{HashTask, HashThreadError, HashThreadEvent, HashThreadStatus, Share},
     tracing::prelude::*,
     types::HashRate,
     u256::U256,
 };
 
+/// SHA-256 initial hash values (FIPS 180-4, section 5.3.3).
+const SHA256_IV: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// Byte offset of ntime within the header's second SHA-256 block.
+const TAIL_TIME_OFFSET: usize = 4;
+
+/// Byte offset of the nonce within the header's second SHA-256 block.
+const TAIL_NONCE_OFFSET: usize = 12;
+
 /// Commands sent to the mining thread.
 #[derive(Debug)]
 pub enum MinerCommand {
@@ -61,6 +84,111 @@ pub enum MinerCommand {
     Shutdown,
 }
 
+/// Inclusive slice of the nonce space searched by one thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NonceRange {
+    /// First nonce in the slice.
+    pub start: u32,
+    /// Last nonce in the slice (inclusive).
+    pub end: u32,
+}
+
+impl NonceRange {
+    /// The entire nonce space.
+    pub const FULL: Self = Self {
+        start: 0,
+        end: u32::MAX,
+    };
+
+    /// Slice `index` of the nonce space split `count` ways.
+    ///
+    /// Slices are contiguous, disjoint, and together cover every nonce; the
+    /// last slice absorbs any remainder.
+    pub fn partition(index: usize, count: usize) -> Self {
+        assert!(count > 0 && index < count, "invalid nonce partition");
+
+        let span = (1u64 << 32) / count as u64;
+        let start = span * index as u64;
+        let end = if index == count - 1 {
+            u32::MAX as u64
+        } else {
+            start + span - 1
+        };
+
+        Self {
+            start: start as u32,
+            end: end as u32,
+        }
+    }
+}
+
+/// Double-SHA256 of a block header with the nonce-independent work cached.
+///
+/// A header is 80 bytes, which SHA-256 processes as two 64-byte blocks. The
+/// first block contains no nonce or time, so its compressed state (the
+/// "midstate") is computed once. Per nonce, only the second block and the
+/// outer hash remain.
+#[derive(Debug, Clone)]
+pub struct HeaderHasher {
+    /// SHA-256 state after the first 64 header bytes.
+    midstate: [u32; 8],
+    /// Second message block: header bytes 64..80 plus padding.
+    tail: [u8; 64],
+}
+
+impl HeaderHasher {
+    /// Prepare to hash `header` with varying nonces.
+    pub fn new(header: &BlockHeader) -> Self {
+        let bytes = bitcoin::consensus::serialize(header);
+
+        let mut midstate = SHA256_IV;
+        compress(&mut midstate, &bytes[..64]);
+
+        // Padding for an 80-byte message: 0x80, zeros, bit length
+        let mut tail = [0u8; 64];
+        tail[..16].copy_from_slice(&bytes[64..80]);
+        tail[16] = 0x80;
+        tail[56..].copy_from_slice(&(80u64 * 8).to_be_bytes());
+
+        Self { midstate, tail }
+    }
+
+    /// Change the header's ntime. Doesn't affect the midstate.
+    pub fn set_time(&mut self, time: u32) {
+        self.tail[TAIL_TIME_OFFSET..TAIL_TIME_OFFSET + 4].copy_from_slice(&time.to_le_bytes());
+    }
+
+    /// Block hash of the header with `nonce`.
+    pub fn hash(&mut self, nonce: u32) -> BlockHash {
+        self.tail[TAIL_NONCE_OFFSET..TAIL_NONCE_OFFSET + 4].copy_from_slice(&nonce.to_le_bytes());
+
+        let mut inner = self.midstate;
+        compress(&mut inner, &self.tail);
+
+        // Padding for a 32-byte message
+        let mut block = [0u8; 64];
+        for (chunk, word) in block[..32].chunks_exact_mut(4).zip(inner) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        block[32] = 0x80;
+        block[56..].copy_from_slice(&(32u64 * 8).to_be_bytes());
+
+        let mut outer = SHA256_IV;
+        compress(&mut outer, &block);
+
+        let mut digest = [0u8; 32];
+        for (chunk, word) in digest.chunks_exact_mut(4).zip(outer) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        BlockHash::from_byte_array(digest)
+    }
+}
+
+/// Run one SHA-256 compression over a 64-byte block.
+fn compress(state: &mut [u32; 8], block: &[u8]) {
+    sha2::compress256(state, std::slice::from_ref(GenericArray::from_slice(block)));
+}
+
 /// Run the CPU mining loop.
 ///
 /// This function runs in a dedicated `std::thread`. It receives commands
@@ -71,13 +199,17 @@ pub enum MinerCommand {
 /// * `thread_name` - Name for logging
 /// * `cmd_rx` - Channel for receiving commands
 /// * `status` - Shared status for queries
+/// * `event_tx` - Events for the scheduler
 /// * `duty_percent` - Target CPU duty cycle (1-100)
+/// * `nonce_range` - Slice of the nonce space this thread searches
 /// * `shutdown` - Atomic flag for graceful shutdown
 pub fn run_mining_loop(
     thread_name: String,
     cmd_rx: mpsc::Receiver<MinerCommand>,
     status: Arc<RwLock<HashThreadStatus>>,
+    event_tx: tokio_mpsc::Sender<HashThreadEvent>,
     duty_percent: u8,
+    nonce_range: NonceRange,
     shutdown: Arc<AtomicBool>,
 ) {
     // Calculate duty cycle timing
@@ -85,12 +217,13 @@ pub fn run_mining_loop(
     let work_ms = (cycle_ms as f64 * duty_percent as f64 / 100.0) as u64;
 
     let mut current_task: Option<HashTask> = None;
-    let mut cached_merkle_root: Option<bitcoin::TxMerkleNode> = None;
-    let mut nonce: u32 = 0;
+    let mut hasher: Option<HeaderHasher> = None;
+    let mut nonce: u32 = nonce_range.start;
     let mut last_ntime_tick = Instant::now();
     let mut shares_found: u64 = 0;
     let mut hashes_computed: u64 = 0;
     let mut last_hashrate_update = Instant::now();
+    let mut en2_exhausted = false;
 
     loop {
         // Check shutdown flag
@@ -103,21 +236,23 @@ pub fn run_mining_loop(
             match cmd_rx.try_recv() {
                 Ok(cmd) => match cmd {
                     MinerCommand::UpdateTask { task, response_tx } => {
-                        cached_merkle_root = compute_merkle_root(&task);
+                        hasher = prepare_hasher(&task);
                         let old = current_task.replace(task);
-                        nonce = 0;
+                        nonce = nonce_range.start;
+                        en2_exhausted = false;
                         update_status(&status, true, shares_found);
                         let _ = response_tx.send(Ok(old));
                     }
                     MinerCommand::ReplaceTask { task, response_tx } => {
-                        cached_merkle_root = compute_merkle_root(&task);
+                        hasher = prepare_hasher(&task);
                         let old = current_task.replace(task);
-                        nonce = 0;
+                        nonce = nonce_range.start;
+                        en2_exhausted = false;
                         update_status(&status, true, shares_found);
                         let _ = response_tx.send(Ok(old));
                     }
                     MinerCommand::GoIdle { response_tx } => {
-                        cached_merkle_root = None;
+                        hasher = None;
                         let old = current_task.take();
                         update_status(&status, false, shares_found);
                         let _ = response_tx.send(Ok(old));
@@ -152,24 +287,26 @@ pub fn run_mining_loop(
                     if let Ok(cmd) = cmd_rx.try_recv() {
                         match cmd {
                             MinerCommand::ReplaceTask { task, response_tx } => {
-                                cached_merkle_root = compute_merkle_root(&task);
+                                hasher = prepare_hasher(&task);
                                 let old = current_task.replace(task);
-                                nonce = 0;
+                                nonce = nonce_range.start;
+                                en2_exhausted = false;
                                 update_status(&status, true, shares_found);
                                 let _ = response_tx.send(Ok(old));
                                 // Continue with new task in next iteration
                                 break;
                             }
                             MinerCommand::UpdateTask { task, response_tx } => {
-                                cached_merkle_root = compute_merkle_root(&task);
+                                hasher = prepare_hasher(&task);
                                 let old = current_task.replace(task);
-                                nonce = 0;
+                                nonce = nonce_range.start;
+                                en2_exhausted = false;
                                 update_status(&status, true, shares_found);
                                 let _ = response_tx.send(Ok(old));
                                 break;
                             }
                             MinerCommand::GoIdle { response_tx } => {
-                                cached_merkle_root = None;
+                                hasher = None;
                                 let old = current_task.take();
                                 update_status(&status, false, shares_found);
                                 let _ = response_tx.send(Ok(old));
@@ -181,8 +318,8 @@ pub fn run_mining_loop(
                 }
 
                 // Try this nonce
-                if let (Some(ref task), Some(merkle_root)) = (&current_task, cached_merkle_root) {
-                    if let Some(share) = try_nonce(task, merkle_root, nonce) {
+                if let (Some(task), Some(hasher)) = (&mut current_task, &mut hasher) {
+                    if let Some(share) = try_nonce(task, hasher, nonce) {
                         shares_found += 1;
                         debug!(
                             thread = %thread_name,
@@ -192,9 +329,33 @@ pub fn run_mining_loop(
                         // Send share via blocking send (we're in std::thread)
                         let _ = task.share_tx.blocking_send(share);
                     }
+
+                    // Slice exhausted: move on to a fresh header
+                    if nonce == nonce_range.end {
+                        if task.advance_en2() {
+                            if let Some(next) = prepare_hasher(task) {
+                                *hasher = next;
+                            }
+                        } else {
+                            if !en2_exhausted {
+                                en2_exhausted = true;
+                                let en2_searched =
+                                    task.en2_range.as_ref().map_or(0, |range| range.len());
+                                debug!(thread = %thread_name, en2_searched, "Extranonce2 range exhausted");
+                                let _ = event_tx
+                                    .try_send(HashThreadEvent::WorkExhausted { en2_searched });
+                            }
+                            if task.roll_ntime() {
+                                hasher.set_time(task.ntime);
+                            }
+                            last_ntime_tick = Instant::now();
+                        }
+                        nonce = nonce_range.start;
+                    } else {
+                        nonce += 1;
+                    }
                 }
 
-                nonce = nonce.wrapping_add(1);
                 hashes_computed += 1;
             }
 
@@ -207,8 +368,10 @@ pub fn run_mining_loop(
 
             // Roll ntime every second
             if last_ntime_tick.elapsed() >= Duration::from_secs(1) {
-                if let Some(ref mut task) = current_task {
-                    task.ntime += 1;
+                if let (Some(task), Some(hasher)) = (&mut current_task, &mut hasher) {
+                    if task.roll_ntime() {
+                        hasher.set_time(task.ntime);
+                    }
                 }
                 last_ntime_tick = Instant::now();
             }
@@ -242,32 +405,32 @@ pub fn run_mining_loop(
     }
 }
 
-/// Compute merkle root for a task (called once when task is assigned).
-fn compute_merkle_root(task: &HashTask) -> Option<bitcoin::TxMerkleNode> {
-    let template = task.template.as_ref();
-    match &template.merkle_root {
-        MerkleRootKind::Fixed(mr) => Some(*mr),
-        MerkleRootKind::Computed(_) => {
-            let en2 = task.en2.as_ref()?;
-            template.compute_merkle_root(en2).ok()
-        }
-    }
-}
+/// Build the header hasher for a newly assigned task.
+///
+/// Returns None if the merkle root can't be computed, in which case the
+/// thread holds the task without hashing it.
+fn prepare_hasher(task: &HashTask) -> Option<HeaderHasher> {
+    let Some(merkle_root) = task.merkle_root() else {
+        warn!(job_id = %task.template.id, "Cannot compute merkle root for task");
+        return None;
+    };
 
-/// Try a single nonce and return a share if it meets the task's share target.
-fn try_nonce(task: &HashTask, merkle_root: bitcoin::TxMerkleNode, nonce: u32) -> Option<Share> {
     let template = task.template.as_ref();
-
-    // Build block header and compute double-SHA256
     let header = BlockHeader {
         version: template.version.base(),
         prev_blockhash: template.prev_blockhash,
         merkle_root,
         time: task.ntime,
         bits: template.bits,
-        nonce,
+        nonce: 0,
     };
-    let hash = header.block_hash();
+    Some(HeaderHasher::new(&header))
+}
+
+/// Try a single nonce and return a share if it meets the task's share target.
+fn try_nonce(task: &HashTask, hasher: &mut HeaderHasher, nonce: u32) -> Option<Share> {
+    let template = task.template.as_ref();
+    let hash = hasher.hash(nonce);
 
     if task.share_target.is_met_by(hash) {
         Some(Share {
@@ -338,12 +501,12 @@ mod tests {
     #[test]
     fn test_try_nonce_finds_easy_shares() {
         let task = make_test_task();
-        let merkle_root = compute_merkle_root(&task).unwrap();
+        let mut hasher = prepare_hasher(&task).unwrap();
 
         // With such an easy target, we should find a share within a few attempts
         let mut found = false;
         for nonce in 0..1000 {
-            if try_nonce(&task, merkle_root, nonce).is_some() {
+            if try_nonce(&task, &mut hasher, nonce).is_some() {
                 found = true;
                 break;
             }
@@ -355,11 +518,11 @@ mod tests {
     #[test]
     fn test_try_nonce_returns_correct_share_fields() {
         let task = make_test_task();
-        let merkle_root = compute_merkle_root(&task).unwrap();
+        let mut hasher = prepare_hasher(&task).unwrap();
 
         // Find a valid share
         let share = (0..10000)
-            .find_map(|nonce| try_nonce(&task, merkle_root, nonce))
+            .find_map(|nonce| try_nonce(&task, &mut hasher, nonce))
             .expect("Should find a share");
 
         // Verify share fields match task
@@ -409,13 +572,13 @@ mod tests {
             bits: *block_881423::BITS,
             share_target: easy_target,
             time: block_881423::TIME,
-            merkle_root: MerkleRootKind::Computed(MerkleRootTemplate {
-                coinbase1: block_881423::coinbase1_bytes().to_vec(),
-                extranonce1: block_881423::extranonce1_bytes().to_vec(),
+            merkle_root: MerkleRootKind::Computed(MerkleRootTemplate::new(
+                block_881423::coinbase1_bytes().to_vec(),
+                block_881423::extranonce1_bytes().to_vec(),
                 extranonce2_range,
-                coinbase2: block_881423::coinbase2_bytes().to_vec(),
-                merkle_branches: block_881423::MERKLE_BRANCHES.clone(),
-            }),
+                block_881423::coinbase2_bytes().to_vec(),
+                block_881423::MERKLE_BRANCHES.clone(),
+            )),
         });
 
         let (share_tx, _share_rx) = tokio_mpsc::channel(100);
@@ -430,10 +593,10 @@ mod tests {
         };
 
         // With computed merkle root and easy target, we should find shares
-        let merkle_root = compute_merkle_root(&task).unwrap();
+        let mut hasher = prepare_hasher(&task).unwrap();
         let mut found = false;
         for nonce in 0..10000 {
-            if try_nonce(&task, merkle_root, nonce).is_some() {
+            if try_nonce(&task, &mut hasher, nonce).is_some() {
                 found = true;
                 break;
             }
@@ -441,4 +604,107 @@ mod tests {
 
         assert!(found, "Should find a share with computed merkle root");
     }
+
+    #[test]
+    fn test_mining_loop_rolls_extranonce2() {
+        use crate::job_source::{dummy, Extranonce2, Extranonce2Range};
+
+        let template = Arc::new(dummy::job_template().unwrap());
+        let en2_range = Extranonce2Range::new_range(10, 12, 4).unwrap();
+        let (share_tx, mut share_rx) = tokio_mpsc::channel(100);
+        let task = HashTask {
+            template: template.clone(),
+            en2: Some(Extranonce2::new(10, 4).unwrap()),
+            en2_range: Some(en2_range),
+            share_target: Target::from_be_bytes([0xff; 32]),
+            ntime: template.time,
+            share_tx,
+        };
+
+        let (cmd_tx, cmd_rx) = mpsc::channel();
+        let (event_tx, mut event_rx) = tokio_mpsc::channel(10);
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let handle = std::thread::spawn({
+            let shutdown = shutdown.clone();
+            move || {
+                run_mining_loop(
+                    "test".into(),
+                    cmd_rx,
+                    Arc::new(RwLock::new(HashThreadStatus::default())),
+                    event_tx,
+                    100,
+                    NonceRange { start: 0, end: 3 },
+                    shutdown,
+                )
+            }
+        });
+        let (response_tx, _response_rx) = tokio::sync::oneshot::channel();
+        cmd_tx
+            .send(MinerCommand::UpdateTask { task, response_tx })
+            .unwrap();
+
+        // Every hash meets the target: four nonces per extranonce2, in order
+        let shares: Vec<Share> = (0..16).map(|_| share_rx.blocking_recv().unwrap()).collect();
+        for (i, share) in shares[..12].iter().enumerate() {
+            assert_eq!(share.nonce, i as u32 % 4);
+            assert_eq!(share.extranonce2.unwrap().value(), 10 + i as u64 / 4);
+            assert_eq!(share.ntime, template.time);
+        }
+
+        // Range used up: reported once, then ntime rolls on the last value
+        assert!(matches!(
+            event_rx.blocking_recv(),
+            Some(HashThreadEvent::WorkExhausted { en2_searched: 3 })
+        ));
+        for share in &shares[12..] {
+            assert_eq!(share.extranonce2.unwrap().value(), 12);
+            assert_eq!(share.ntime, template.time + 1);
+        }
+
+        shutdown.store(true, Ordering::Relaxed);
+        drop(share_rx);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_header_hasher_matches_block_hash() {
+        use crate::job_source::test_blocks::block_881423;
+
+        let mut header = BlockHeader {
+            version: *block_881423::VERSION,
+            prev_blockhash: *block_881423::PREV_BLOCKHASH,
+            merkle_root: bitcoin::TxMerkleNode::all_zeros(),
+            time: block_881423::TIME,
+            bits: *block_881423::BITS,
+            nonce: 0,
+        };
+        let mut hasher = HeaderHasher::new(&header);
+
+        for nonce in [0, 1, 0x1234_5678, u32::MAX] {
+            header.nonce = nonce;
+            assert_eq!(hasher.hash(nonce), header.block_hash());
+        }
+
+        // Changing ntime reuses the midstate
+        header.time += 7;
+        hasher.set_time(header.time);
+        assert_eq!(hasher.hash(header.nonce), header.block_hash());
+    }
+
+    #[test]
+    fn test_nonce_partitions_cover_space_without_overlap() {
+        for count in [1, 2, 3, 7, 16] {
+            let ranges: Vec<_> = (0..count)
+                .map(|i| NonceRange::partition(i, count))
+                .collect();
+
+            assert_eq!(ranges[0].start, 0);
+            assert_eq!(ranges[count - 1].end, u32::MAX);
+            for pair in ranges.windows(2) {
+                assert_eq!(pair[0].end as u64 + 1, pair[1].start as u64);
+            }
+        }
+
+        assert_eq!(NonceRange::partition(0, 1), NonceRange::FULL);
+    }
 }
@@ -9,10 +9,21 @@
 //!
 //! - `MUJINA_CPUMINER_THREADS=N` - Number of mining threads (presence enables)
 //! - `MUJINA_CPUMINER_DUTY=P` - Duty cycle percentage (default: 50)
+//!
+//! or on the command line with `--cpu-miner N` and `--cpu-duty P`.
+//!
+//! The hashing itself is built only with the `cpu-miner` feature. Without
+//! it the configuration is still understood, so a request for the CPU
+//! miner can be refused with a warning rather than an unknown option.
 
 mod config;
+#[cfg(feature = "cpu-miner")]
 mod hasher;
+#[cfg(feature = "cpu-miner")]
 mod thread;
 
 pub use config::CpuMinerConfig;
+#[cfg(feature = "cpu-miner")]
+pub use hasher::{HeaderHasher, NonceRange};
+#[cfg(feature = "cpu-miner")]
 pub use thread::CpuHashThread;
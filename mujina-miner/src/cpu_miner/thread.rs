@@ -11,7 +11,7 @@ use std::sync::{
 use async_trait::async_trait;
 use tokio::sync::mpsc as tokio_mpsc;
 
-use super::hasher::{self, MinerCommand};
+use super::hasher::{self, MinerCommand, NonceRange};
 use crate::{
     asic::hash_thread::{
         HashTask, HashThread, HashThreadCapabilities, HashThreadError, HashThreadEvent,
@@ -61,7 +61,9 @@ impl CpuHashThread {
     ///
     /// * `name` - Human-readable name for logging
     /// * `duty_percent` - Target CPU duty cycle (1-100)
-    pub fn new(name: String, duty_percent: u8) -> Self {
+    /// * `nonce_range` - Slice of the nonce space to search, disjoint from
+    ///   the other threads on the same board
+    pub fn new(name: String, duty_percent: u8, nonce_range: NonceRange) -> Self {
         let (cmd_tx, cmd_rx) = mpsc::channel();
         let (evt_tx, evt_rx) = tokio_mpsc::channel(100);
 
@@ -72,6 +74,7 @@ impl CpuHashThread {
         let status_clone = Arc::clone(&status);
         let shutdown_clone = Arc::clone(&shutdown);
         let thread_name = name.clone();
+        let event_tx = evt_tx.clone();
 
         // Spawn the mining thread
         let handle = std::thread::Builder::new()
@@ -81,7 +84,9 @@ impl CpuHashThread {
                     thread_name,
                     cmd_rx,
                     status_clone,
+                    event_tx,
                     duty_percent,
+                    nonce_range,
                     shutdown_clone,
                 );
             })
@@ -93,10 +98,8 @@ impl CpuHashThread {
             event_tx: evt_tx,
             event_rx: Some(evt_rx),
             status,
-            capabilities: HashThreadCapabilities {
-                // Conservative estimate: ~5 MH/s per core on modern hardware
-                hashrate_estimate: HashRate::from_megahashes(5.0),
-            },
+            // Conservative estimate: ~5 MH/s per core on modern hardware
+            capabilities: HashThreadCapabilities::new(HashRate::from_megahashes(5.0)),
             shutdown,
             _thread_handle: Some(handle),
         }
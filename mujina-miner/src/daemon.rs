@@ -3,63 +3,317 @@
 //! This module handles the core daemon functionality including initialization,
 //! task management, signal handling, and graceful shutdown.
 
-use std::env;
+use std::collections::BTreeMap;
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::time::Duration;
 
+use anyhow::Context;
 use tokio::signal::unix::{self, SignalKind};
-use tokio::sync::mpsc;
+use tokio::sync::{broadcast, mpsc, oneshot, watch};
+use tokio::time::MissedTickBehavior;
 use tokio_util::{sync::CancellationToken, task::TaskTracker};
 
+#[cfg(feature = "api")]
+use crate::api::{self, ApiConfig, ApiState};
 use crate::tracing::prelude::*;
 use crate::{
-    api::{self, ApiConfig},
+    alert::AlertManager,
     asic::hash_thread::HashThread,
-    backplane::Backplane,
+    backplane::{Backplane, BackplaneCommand, BoardFilter},
+    benchmark::{self, BackplaneControl, BenchmarkOptions},
+    board::{
+        history::BoardHistory, panel::PanelLink, preset::PresetSet, sim::SimConfig,
+        task::RestartPolicy, warmup::Warmup,
+    },
+    config::{
+        AlertConfig, Config, DirectBoardConfig, EnvConfig, PoolConfig, ProxyConfig, RecoveryConfig,
+        RejectBreakerConfig, ScheduleConfig, ShareQueueConfig,
+    },
     cpu_miner::CpuMinerConfig,
-    job_source::{
-        dummy::DummySource,
-        forced_rate::{ForcedRateConfig, ForcedRateSource},
-        stratum_v1::StratumV1Source,
-        SourceCommand, SourceEvent,
+    found_block,
+    job_source::{clock, forced_rate::ForcedRateConfig},
+    pools::{self, PoolCommand, PoolManager},
+    power::{PowerBudget, PowerManager},
+    proxy::ProxyServer,
+    runtime::RuntimeEvent,
+    schedule::ScheduleManager,
+    scheduler::{self, Paused, SourceRegistration},
+    settings::{SettingsStore, DEFAULT_STATE_DIR},
+    stratum_v1,
+    supervisor::{Backoff, Supervisor},
+    systemd,
+    transport::{
+        cpu as cpu_transport, direct as direct_transport, sim as sim_transport, CpuDeviceInfo,
+        TransportEvent, UsbTransport,
     },
-    scheduler::{self, SourceRegistration},
-    stratum_v1::{PoolConfig as StratumPoolConfig, FLOOD_PREVENTION_CAP},
-    transport::{cpu as cpu_transport, CpuDeviceInfo, TransportEvent, UsbTransport},
+    types::Network,
 };
 
+/// Events buffered for each subscriber before the slowest starts missing
+/// them.
+pub(crate) const EVENT_CAPACITY: usize = 64;
+
+/// Why the daemon stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitReason {
+    /// Stopped by a signal or an API shutdown request.
+    Shutdown,
+
+    /// A restart was requested; the caller should run a fresh daemon.
+    Restart,
+}
+
+/// Settings supplied by the command line or a config file.
+///
+/// Anything left unset falls back to the corresponding `MUJINA_*`
+/// environment variable, then to the built-in default.
+#[derive(Debug, Clone)]
+pub struct DaemonOptions {
+    /// Serve the HTTP API.
+    pub api_enabled: bool,
+
+    /// Address for the API server to bind.
+    pub api_bind_addr: Option<String>,
+
+    /// Listener inherited through socket activation, served instead of
+    /// binding `api_bind_addr` (see [`systemd::take_api_listener`]).
+    pub api_listener: Option<Arc<TcpListener>>,
+
+    /// Pool URL (`MUJINA_POOL_URL`). Overrides `pools` with a single pool.
+    pub pool_url: Option<String>,
+
+    /// Pool worker name (`MUJINA_POOL_WORKER`).
+    pub pool_worker: Option<String>,
+
+    /// Pool password (`MUJINA_POOL_PASSWORD`).
+    pub pool_pass: Option<String>,
+
+    /// Pools from the config file. Without these or a pool URL, the dummy
+    /// source is used.
+    pub pools: Vec<PoolConfig>,
+
+    /// Config file `pools` came from; pool changes made through the API are
+    /// written back to it.
+    pub config_path: Option<PathBuf>,
+
+    /// Bitcoin network to mine (`MUJINA_NETWORK`, default mainnet).
+    pub network: Option<Network>,
+
+    /// CPU miner settings (`MUJINA_CPUMINER_THREADS`, `MUJINA_CPUMINER_DUTY`).
+    pub cpu_miner: Option<CpuMinerConfig>,
+
+    /// Boards wired to the host's own UART, I2C and GPIO.
+    pub direct_boards: Vec<DirectBoardConfig>,
+
+    /// Limit on the boards' combined power draw.
+    pub power_budget: Option<PowerBudget>,
+
+    /// Mining profiles by time of day and electricity price.
+    pub schedule: Option<ScheduleConfig>,
+
+    /// Retrying shares that failed to reach the pool.
+    pub share_queue: ShareQueueConfig,
+
+    /// Refreshing and failing over pools that reject too many shares.
+    pub reject_breaker: RejectBreakerConfig,
+
+    /// Benchmark the boards instead of mining, then exit.
+    pub benchmark: Option<BenchmarkOptions>,
+
+    /// Serve work to downstream miners while mining.
+    pub proxy: Option<ProxyConfig>,
+
+    /// Directory for state kept between runs (`MUJINA_STATE_DIR`, default
+    /// [`DEFAULT_STATE_DIR`]).
+    pub state_dir: Option<PathBuf>,
+
+    /// Discover boards on USB (unless `MUJINA_USB_DISABLE` is set).
+    pub usb_discovery: bool,
+
+    /// When failing boards are restarted (`MUJINA_RECOVERY_*`).
+    pub recovery: Option<RecoveryConfig>,
+
+    /// Alerts by webhook and email.
+    pub alerts: Option<AlertConfig>,
+
+    /// What's paused at start, wholly or by board.
+    pub paused: Paused,
+
+    /// Eco, balanced and turbo presets, by board model.
+    pub presets: BTreeMap<String, PresetSet>,
+
+    /// How boards are ramped up to their stored operating point.
+    pub warmup: Warmup,
+
+    /// Boards to start, if not all of them.
+    pub board_filter: Option<BoardFilter>,
+
+    /// Shut down on SIGINT and SIGTERM. Off when embedded in a process
+    /// that handles signals itself.
+    pub handle_signals: bool,
+}
+
+impl Default for DaemonOptions {
+    fn default() -> Self {
+        Self {
+            api_enabled: true,
+            api_bind_addr: None,
+            api_listener: None,
+            pool_url: None,
+            pool_worker: None,
+            pool_pass: None,
+            pools: Vec::new(),
+            config_path: None,
+            network: None,
+            cpu_miner: None,
+            direct_boards: Vec::new(),
+            power_budget: None,
+            schedule: None,
+            share_queue: ShareQueueConfig::default(),
+            reject_breaker: RejectBreakerConfig::default(),
+            benchmark: None,
+            proxy: None,
+            state_dir: None,
+            usb_discovery: true,
+            recovery: None,
+            alerts: None,
+            paused: Paused::default(),
+            presets: BTreeMap::new(),
+            warmup: Warmup::default(),
+            board_filter: None,
+            handle_signals: true,
+        }
+    }
+}
+
+impl From<&Config> for DaemonOptions {
+    fn from(config: &Config) -> Self {
+        Self {
+            api_bind_addr: Some(config.api.listen.clone()),
+            pools: config.pools.clone(),
+            network: Some(config.daemon.network),
+            direct_boards: config.hardware.direct.clone(),
+            power_budget: config.hardware.power_limit.map(|limit_watts| PowerBudget {
+                limit_watts,
+                weights: config.hardware.power_weights.clone(),
+            }),
+            schedule: config.schedule.clone(),
+            share_queue: config.share_queue.clone().unwrap_or_default(),
+            reject_breaker: config.reject_breaker.clone().unwrap_or_default(),
+            proxy: config.proxy.clone(),
+            state_dir: config.daemon.state_dir.clone(),
+            recovery: config.recovery.clone(),
+            alerts: config.alerts.clone(),
+            paused: Paused {
+                manual: config.daemon.paused,
+                boards: config.hardware.paused_boards.iter().cloned().collect(),
+                ..Paused::default()
+            },
+            presets: config.presets.clone(),
+            warmup: Warmup::from(&config.hardware),
+            ..Self::default()
+        }
+    }
+}
+
+impl DaemonOptions {
+    /// Fill what was left unset from the environment.
+    pub fn with_env(mut self, env: EnvConfig) -> Self {
+        self.network = self.network.or(env.network);
+        self.state_dir = self.state_dir.or(env.state_dir);
+        if env.usb_disable {
+            self.usb_discovery = false;
+        }
+        // The environment's pool stands in only when none was configured
+        if self.pool_url.is_none() && self.pools.is_empty() {
+            self.pool_url = env.pool_url;
+        }
+        self.pool_worker = self.pool_worker.or(env.pool_worker);
+        self.pool_pass = self.pool_pass.or(env.pool_password);
+        self.cpu_miner = self.cpu_miner.or(env.cpu_miner);
+        self.recovery = self.recovery.or(env.recovery);
+        self
+    }
+}
+
 /// The main daemon.
 pub struct Daemon {
     shutdown: CancellationToken,
     tracker: TaskTracker,
+    restart_requested: Arc<AtomicBool>,
+    options: DaemonOptions,
+    events: broadcast::Sender<RuntimeEvent>,
 }
 
 impl Daemon {
-    /// Create a new daemon instance.
+    /// Create a new daemon instance configured from the environment.
     pub fn new() -> Self {
+        Self::with_options(DaemonOptions::default())
+    }
+
+    /// Create a new daemon instance with explicit settings.
+    pub fn with_options(options: DaemonOptions) -> Self {
         Self {
             shutdown: CancellationToken::new(),
             tracker: TaskTracker::new(),
+            restart_requested: Arc::new(AtomicBool::new(false)),
+            options,
+            events: broadcast::channel(EVENT_CAPACITY).0,
         }
     }
 
+    /// Stop when `shutdown` is cancelled, as well as on a signal or an API
+    /// shutdown request.
+    pub fn with_shutdown(mut self, shutdown: CancellationToken) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// Announce what the daemon is doing on `events`.
+    pub fn with_events(mut self, events: broadcast::Sender<RuntimeEvent>) -> Self {
+        self.events = events;
+        self
+    }
+
     /// Run the daemon until shutdown is requested.
-    pub async fn run(self) -> anyhow::Result<()> {
+    pub async fn run(mut self) -> anyhow::Result<ExitReason> {
+        let env = EnvConfig::from_env().context("environment")?;
+        self.options = self.options.with_env(env);
+
+        // Jobs are checked against local time, so a bad clock loses work
+        clock::check_local_clock();
+
         // Create channels for component communication
         let (transport_tx, transport_rx) = mpsc::channel::<TransportEvent>(100);
         let (thread_tx, thread_rx) = mpsc::channel::<Box<dyn HashThread>>(10);
-        let (source_reg_tx, source_reg_rx) = mpsc::channel::<SourceRegistration>(10);
+        let (backplane_cmd_tx, backplane_cmd_rx) = mpsc::channel::<BackplaneCommand>(10);
+        let (pool_cmd_tx, pool_cmd_rx) = mpsc::channel::<PoolCommand>(10);
+        let (pause_tx, pause_rx) = watch::channel(self.options.paused.clone());
+
+        // Long-running tasks are spawned through the supervisor, which
+        // restarts or shuts down if one of them stops unexpectedly
+        let supervisor = Supervisor::new(self.tracker.clone(), self.shutdown.clone());
 
         // Create and start USB transport discovery
-        if std::env::var("MUJINA_USB_DISABLE").is_err() {
+        if self.options.usb_discovery {
             let usb_transport = UsbTransport::new(transport_tx.clone());
             if let Err(e) = usb_transport.start_discovery(self.shutdown.clone()).await {
-                error!("Failed to start USB discovery: {}", e);
+                error!(error = %e, "Failed to start USB discovery");
             }
         } else {
-            info!("USB discovery disabled (MUJINA_USB_DISABLE set)");
+            info!("USB discovery disabled");
         }
 
         // Inject CPU miner virtual device if configured
-        if let Some(config) = CpuMinerConfig::from_env() {
+        let cpu_miner = self.options.cpu_miner.clone();
+        if cpu_miner.is_some() && !cfg!(feature = "cpu-miner") {
+            warn!("CPU miner requested, but this build doesn't include it");
+        } else if let Some(config) = cpu_miner {
             info!(
                 threads = config.thread_count,
                 duty = config.duty_percent,
@@ -73,199 +327,515 @@ impl Daemon {
                 },
             ));
             if let Err(e) = transport_tx.send(event).await {
-                error!("Failed to send CPU miner event: {}", e);
+                error!(error = %e, "Failed to send CPU miner event");
             }
         }
 
+        // Inject boards wired directly to the host
+        if !self.options.direct_boards.is_empty()
+            && !cfg!(all(target_os = "linux", feature = "direct-attach"))
+        {
+            warn!("Directly attached boards configured, but this build doesn't support them");
+        } else {
+            for board in &self.options.direct_boards {
+                let event = TransportEvent::Direct(
+                    direct_transport::TransportEvent::DirectDeviceConnected(board.into()),
+                );
+                if let Err(e) = transport_tx.send(event).await {
+                    error!(error = %e, "Failed to send directly attached board event");
+                }
+            }
+        }
+
+        // Inject simulation board if configured
+        if let Some(device) = SimConfig::device_from_env() {
+            info!(
+                threads = device.thread_count,
+                hashrate = %device.hashrate,
+                seed = device.seed,
+                "Simulation board enabled"
+            );
+            let event =
+                TransportEvent::Sim(sim_transport::TransportEvent::SimDeviceConnected(device));
+            if let Err(e) = transport_tx.send(event).await {
+                error!(error = %e, "Failed to send simulation board event");
+            }
+        }
+
+        // Downstream miners are mined as hash threads alongside the boards,
+        // so only while mining
+        let proxy = match (&self.options.proxy, &self.options.benchmark) {
+            (Some(config), None) => Some(ProxyServer::new(
+                config.clone(),
+                thread_tx.clone(),
+                self.shutdown.clone(),
+            )),
+            _ => None,
+        };
+
         // Create and start backplane
-        let mut backplane = Backplane::new(transport_rx, thread_tx);
-        self.tracker.spawn({
+        let restart_policy = self
+            .options
+            .recovery
+            .as_ref()
+            .map(RestartPolicy::from)
+            .unwrap_or_default();
+        let mut backplane = Backplane::new(transport_rx, thread_tx, backplane_cmd_rx)
+            .with_settings(SettingsStore::load(&self.state_dir()))
+            .with_history(BoardHistory::load(&self.state_dir()))
+            .with_presets(self.options.presets.clone())
+            .with_warmup(self.options.warmup)
+            .with_restart_policy(restart_policy)
+            .with_events(self.events.clone())
+            .with_panel(PanelLink::new(
+                pool_cmd_tx.clone(),
+                self.shutdown.clone(),
+                self.restart_requested.clone(),
+            ));
+        if let Some(filter) = self.options.board_filter.clone() {
+            backplane = backplane.with_board_filter(filter);
+        }
+        supervisor.spawn_critical("backplane", {
             let shutdown = self.shutdown.clone();
             async move {
-                tokio::select! {
-                    result = backplane.run() => {
-                        if let Err(e) = result {
-                            error!("Backplane error: {}", e);
-                        }
-                    }
-                    _ = shutdown.cancelled() => {}
-                }
+                let result = tokio::select! {
+                    result = backplane.run() => result,
+                    _ = shutdown.cancelled() => Ok(()),
+                };
 
                 backplane.shutdown_all_boards().await;
+                Ok(result?)
             }
         });
 
-        // Create job source (Stratum v1 or Dummy)
-        // Controlled by environment variables:
-        // - MUJINA_POOL_URL: Pool address (e.g., stratum+tcp://localhost:3333)
-        // - MUJINA_POOL_USER: Worker username (optional, defaults to "mujina-testing")
-        // - MUJINA_POOL_PASS: Worker password (optional, defaults to "x")
-        let (source_event_tx, source_event_rx) = mpsc::channel::<SourceEvent>(100);
-        let (source_cmd_tx, source_cmd_rx) = mpsc::channel(10);
-
-        if let Ok(pool_url) = env::var("MUJINA_POOL_URL") {
-            // Use Stratum v1 source
-            let pool_user =
-                env::var("MUJINA_POOL_USER").unwrap_or_else(|_| "mujina-testing".to_string());
-            let pool_pass = env::var("MUJINA_POOL_PASS").unwrap_or_else(|_| "x".to_string());
-
-            let stratum_config = StratumPoolConfig {
-                url: pool_url,
-                username: pool_user,
-                password: pool_pass,
-                user_agent: "mujina-miner/0.1.0-alpha".to_string(),
-                suggested_difficulty: None,
-            };
-
-            // Optionally wrap with ForcedRateSource for testing
-            if let Some(forced_rate_config) = ForcedRateConfig::from_env() {
-                info!(
-                    rate = %forced_rate_config.target_rate,
-                    "Forced share rate wrapper enabled"
-                );
+        if let Some(benchmark) = self.options.benchmark.clone() {
+            // The benchmark drives the threads with its own job in place of
+            // the scheduler, and stops the daemon when it's done
+            info!(points = benchmark.plan.points.len(), "Benchmark mode");
+            supervisor.spawn_critical(
+                "benchmark",
+                run_benchmark(
+                    benchmark,
+                    thread_rx,
+                    backplane_cmd_tx.clone(),
+                    self.shutdown.clone(),
+                ),
+            );
+        } else {
+            self.start_mining(&supervisor, thread_rx, pool_cmd_rx, pause_rx)
+                .await?;
 
-                // Create inner channels (stratum <-> wrapper)
-                let (inner_event_tx, inner_event_rx) = mpsc::channel::<SourceEvent>(100);
-                let (inner_cmd_tx, inner_cmd_rx) = mpsc::channel::<SourceCommand>(10);
+            if let Some(server) = proxy.clone() {
+                supervisor
+                    .spawn_restartable("proxy", Backoff::default(), move || server.clone().run());
+            }
 
-                let stratum_source = StratumV1Source::new(
-                    stratum_config,
-                    inner_cmd_rx,
-                    inner_event_tx,
-                    self.shutdown.clone(),
-                );
-                let stratum_name = stratum_source.name();
+            if let Some(schedule) = self.options.schedule.clone() {
+                let manager =
+                    ScheduleManager::new(schedule, backplane_cmd_tx.clone(), pause_tx.clone());
+                supervisor.spawn_critical("schedule", manager.run(self.shutdown.clone()));
+            }
 
-                // Spawn stratum source
-                self.tracker.spawn(async move {
-                    if let Err(e) = stratum_source.run().await {
-                        error!("Stratum v1 source error: {}", e);
-                    }
-                });
-
-                // Create and spawn wrapper (uses outer channels from above)
-                let forced_rate = ForcedRateSource::new(
-                    forced_rate_config,
-                    inner_event_rx,
-                    source_event_tx,
-                    inner_cmd_tx,
-                    source_cmd_rx,
-                    self.shutdown.clone(),
-                );
+            // The benchmark sets its own operating points, so the budget
+            // applies only while mining
+            if let Some(budget) = self.options.power_budget.clone() {
+                let manager = PowerManager::new(budget, backplane_cmd_tx.clone());
+                supervisor.spawn_critical("power", manager.run(self.shutdown.clone()));
+            }
 
-                source_reg_tx
-                    .send(SourceRegistration {
-                        name: format!("{} (forced-rate)", stratum_name),
-                        event_rx: source_event_rx,
-                        command_tx: source_cmd_tx,
-                        max_share_rate: None, // Wrapper controls rate
-                    })
-                    .await?;
-
-                self.tracker.spawn(async move {
-                    if let Err(e) = forced_rate.run().await {
-                        error!("Forced rate wrapper error: {}", e);
-                    }
-                });
-            } else {
-                // Direct stratum source (no wrapper)
-                let stratum_source = StratumV1Source::new(
-                    stratum_config,
-                    source_cmd_rx,
-                    source_event_tx,
-                    self.shutdown.clone(),
-                );
+            if let Some(alerts) = self.options.alerts.clone() {
+                let manager =
+                    AlertManager::new(alerts, backplane_cmd_tx.clone(), pool_cmd_tx.clone())
+                        .context("alerts")?
+                        .with_events(self.events.subscribe());
+                supervisor.spawn_critical("alerts", manager.run(self.shutdown.clone()));
+            }
+        }
 
-                source_reg_tx
-                    .send(SourceRegistration {
-                        name: stratum_source.name(),
-                        event_rx: source_event_rx,
-                        command_tx: source_cmd_tx,
-                        max_share_rate: Some(FLOOD_PREVENTION_CAP),
-                    })
-                    .await?;
-
-                self.tracker.spawn(async move {
-                    if let Err(e) = stratum_source.run().await {
-                        error!("Stratum v1 source error: {}", e);
-                    }
-                });
+        // Start the API server
+        if self.options.api_enabled {
+            // Only the scheduler heeds pausing, so it's offered only while mining
+            let pause_tx = self.options.benchmark.is_none().then_some(pause_tx);
+            self.start_api(
+                &supervisor,
+                backplane_cmd_tx.clone(),
+                pool_cmd_tx,
+                pause_tx,
+                proxy.as_ref(),
+            )?;
+        } else {
+            info!("API server disabled");
+        }
+
+        // Keep the systemd watchdog fed while the backplane is responsive
+        if let Some(interval) = systemd::watchdog_interval() {
+            info!(
+                interval_secs = interval.as_secs_f64(),
+                "systemd watchdog enabled"
+            );
+            supervisor.spawn_critical(
+                "watchdog",
+                watchdog(backplane_cmd_tx, interval, self.shutdown.clone()),
+            );
+        }
+
+        self.tracker.close();
+
+        info!("Started.");
+        systemd::notify_ready();
+        let _ = self.events.send(RuntimeEvent::Started);
+        info!("For debugging, set RUST_LOG=mujina_miner=debug or trace.");
+
+        // Wait for shutdown signal or an API-initiated shutdown
+        if self.options.handle_signals {
+            let mut sigint = unix::signal(SignalKind::interrupt())?;
+            let mut sigterm = unix::signal(SignalKind::terminate())?;
+
+            tokio::select! {
+                _ = sigint.recv() => {
+                    info!("Received SIGINT.");
+                },
+                _ = sigterm.recv() => {
+                    info!("Received SIGTERM.");
+                },
+                _ = self.shutdown.cancelled() => {},
             }
         } else {
-            // Use DummySource
-            info!("Using dummy job source (set MUJINA_POOL_URL to use Stratum v1)");
+            self.shutdown.cancelled().await;
+        }
+        let _ = self.events.send(RuntimeEvent::Stopping);
 
-            let dummy_source = DummySource::new(
-                source_cmd_rx,
-                source_event_tx,
-                self.shutdown.clone(),
-                tokio::time::Duration::from_secs(30),
-            )?;
+        if self.restart_requested.load(Ordering::SeqCst) {
+            systemd::notify_reloading();
+        } else {
+            systemd::notify_stopping();
+        }
 
-            source_reg_tx
-                .send(SourceRegistration {
-                    name: "dummy".into(),
-                    event_rx: source_event_rx,
-                    command_tx: source_cmd_tx,
-                    max_share_rate: Some(FLOOD_PREVENTION_CAP),
-                })
-                .await?;
+        // Initiate shutdown. The scheduler stops handing out jobs and flushes
+        // the shares it holds to the pools, which get a deadline to submit
+        // them, while each board parks its chips, turns off the core voltage
+        // and slows its fans, stage by stage
+        self.shutdown.cancel();
 
-            self.tracker.spawn(async move {
-                if let Err(e) = dummy_source.run().await {
-                    error!("DummySource error: {}", e);
-                }
-            });
+        // Wait for all tasks to complete
+        self.tracker.wait().await;
+
+        if self.restart_requested.load(Ordering::SeqCst) {
+            info!("Restarting.");
+            Ok(ExitReason::Restart)
+        } else {
+            info!("Exiting.");
+            Ok(ExitReason::Shutdown)
         }
+    }
+
+    /// Start the pool manager and the scheduler that feeds it hash threads.
+    async fn start_mining(
+        &self,
+        supervisor: &Supervisor,
+        thread_rx: mpsc::Receiver<Box<dyn HashThread>>,
+        pool_cmd_rx: mpsc::Receiver<PoolCommand>,
+        pause_rx: watch::Receiver<Paused>,
+    ) -> anyhow::Result<()> {
+        let (source_reg_tx, source_reg_rx) = mpsc::channel::<SourceRegistration>(10);
 
-        // Start the scheduler
-        self.tracker.spawn(scheduler::task(
+        let network = self.options.network.unwrap_or_default();
+        if network != Network::Mainnet {
+            info!(%network, "Mining on a non-mainnet network");
+        }
+
+        let (pools, config_path) = self.initial_pools();
+        for pool in &pools {
+            pools::check_pool(pool, network).with_context(|| format!("pool {}", pool.url))?;
+        }
+
+        let mut manager = PoolManager::new(
+            pools,
+            source_reg_tx,
+            pool_cmd_rx,
+            supervisor.clone(),
             self.shutdown.clone(),
-            thread_rx,
-            source_reg_rx,
-        ));
+        )
+        .with_network(network)
+        .with_share_queue(self.options.share_queue.clone())
+        .with_trace_dir(self.state_dir().join(stratum_v1::trace::DIR_NAME))
+        .with_reject_breaker(self.options.reject_breaker.clone())
+        .with_events(self.events.clone());
+        if let Some(path) = config_path {
+            manager = manager.with_config_file(path);
+        }
+        if let Some(forced_rate_config) = ForcedRateConfig::from_env() {
+            info!(
+                rate = %forced_rate_config.target_rate,
+                "Forced share rate wrapper enabled"
+            );
+            manager = manager.with_forced_rate(forced_rate_config);
+        }
+        supervisor.spawn_critical("pools", manager.run());
+
+        // Start the scheduler, and what keeps the blocks it finds
+        let (found_tx, found_rx) = mpsc::unbounded_channel();
+        supervisor.spawn_critical("found-blocks", {
+            let task = found_block::task(
+                found_rx,
+                self.state_dir().join(found_block::DIR_NAME),
+                self.events.clone(),
+            );
+            async move {
+                task.await;
+                Ok(())
+            }
+        });
+        supervisor.spawn_critical("scheduler", {
+            let task = scheduler::task(
+                self.shutdown.clone(),
+                thread_rx,
+                source_reg_rx,
+                pause_rx,
+                found_tx,
+            );
+            async move {
+                task.await;
+                Ok(())
+            }
+        });
 
-        // Start the API server
-        self.tracker.spawn({
-            let shutdown = self.shutdown.clone();
+        Ok(())
+    }
+
+    /// Start the API server. It holds no state of its own, so it can simply
+    /// be rebuilt if it fails (e.g., the port was briefly taken).
+    #[cfg(feature = "api")]
+    fn start_api(
+        &self,
+        supervisor: &Supervisor,
+        backplane_cmd_tx: mpsc::Sender<BackplaneCommand>,
+        pool_cmd_tx: mpsc::Sender<PoolCommand>,
+        pause_tx: Option<watch::Sender<Paused>>,
+        proxy: Option<&ProxyServer>,
+    ) -> anyhow::Result<()> {
+        let shutdown = self.shutdown.clone();
+        let mut config = ApiConfig::default();
+        if let Some(addr) = &self.options.api_bind_addr {
+            config.bind_addr = addr.clone();
+        }
+        let state = ApiState::new(
+            backplane_cmd_tx,
+            self.shutdown.clone(),
+            self.restart_requested.clone(),
+        )
+        .with_pools(pool_cmd_tx.clone());
+        let state = match proxy {
+            Some(server) => state.with_proxy(server.stats()),
+            None => state,
+        };
+        let state = match pause_tx {
+            Some(pause_tx) => state.with_pause(pause_tx),
+            None => state,
+        };
+        let state = match &self.options.config_path {
+            Some(path) => {
+                let config = Config::load_from(path)?;
+                let (saved_tx, saved_rx) = watch::channel(config);
+                // Pools given on the command line stand in for the
+                // configured ones, so edits to the file don't replace them
+                let reload_pools =
+                    self.options.benchmark.is_none() && self.initial_pools().1.is_some();
+                supervisor.spawn_critical(
+                    "config",
+                    apply_config_changes(
+                        saved_rx,
+                        reload_pools.then_some(pool_cmd_tx),
+                        self.shutdown.clone(),
+                    ),
+                );
+                state.with_config_file(path.clone(), saved_tx)
+            }
+            None => state,
+        };
+        let api_listener = self.options.api_listener.clone();
+        supervisor.spawn_restartable("api", Backoff::default(), move || {
+            let config = config.clone();
+            let state = state.clone();
+            let shutdown = shutdown.clone();
+            // A fresh handle to the same socket each time, so a restarted
+            // server keeps it
+            let activated = api_listener.as_deref().map(TcpListener::try_clone);
             async move {
-                let config = ApiConfig::default();
-                if let Err(e) = api::serve(config, shutdown).await {
-                    error!("API server error: {}", e);
+                match activated {
+                    Some(listener) => api::serve_listener(listener?, state, shutdown).await,
+                    None => api::serve(config, state, shutdown).await,
                 }
             }
         });
 
-        self.tracker.close();
+        Ok(())
+    }
 
-        info!("Started.");
-        info!("For debugging, set RUST_LOG=mujina_miner=debug or trace.");
+    /// Stand-in for the API server in builds without it.
+    #[cfg(not(feature = "api"))]
+    fn start_api(
+        &self,
+        _supervisor: &Supervisor,
+        _backplane_cmd_tx: mpsc::Sender<BackplaneCommand>,
+        _pool_cmd_tx: mpsc::Sender<PoolCommand>,
+        _pause_tx: Option<watch::Sender<Paused>>,
+        _proxy: Option<&ProxyServer>,
+    ) -> anyhow::Result<()> {
+        warn!("API server requested, but this build doesn't include it");
+        Ok(())
+    }
+
+    /// Directory for state kept between runs.
+    fn state_dir(&self) -> PathBuf {
+        self.options
+            .state_dir
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_STATE_DIR))
+    }
+
+    /// Pools to start with, and the config file to save changes to.
+    ///
+    /// A pool URL from the command line replaces the configured pools, and
+    /// `MUJINA_POOL_URL` stands in when there are none. Either is a single
+    /// pool that exists only for this run, so changes to it aren't saved.
+    fn initial_pools(&self) -> (Vec<PoolConfig>, Option<PathBuf>) {
+        let Some(url) = self.options.pool_url.clone() else {
+            return (self.options.pools.clone(), self.options.config_path.clone());
+        };
+
+        // Worker defaults to "mujina-testing", password to "x"
+        let worker = self
+            .options
+            .pool_worker
+            .clone()
+            .unwrap_or_else(|| "mujina-testing".to_string());
+        let password = self.options.pool_pass.clone();
+
+        let pool = PoolConfig {
+            url,
+            worker,
+            password,
+            priority: 0,
+            shares_per_minute: None,
+            trace: false,
+        };
+        (vec![pool], None)
+    }
+}
 
-        // Install signal handlers
-        let mut sigint = unix::signal(SignalKind::interrupt())?;
-        let mut sigterm = unix::signal(SignalKind::terminate())?;
+/// Send systemd watchdog keep-alives while the backplane event loop responds.
+///
+/// Pings the backplane at half the watchdog interval, as systemd recommends,
+/// and withholds the keep-alive when the ping goes unanswered. If the
+/// backplane stays wedged, systemd kills and restarts the service.
+async fn watchdog(
+    backplane_tx: mpsc::Sender<BackplaneCommand>,
+    interval: Duration,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let period = interval / 2;
+    let mut ticker = tokio::time::interval(period);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
 
-        // Wait for shutdown signal
+    loop {
         tokio::select! {
-            _ = sigint.recv() => {
-                info!("Received SIGINT.");
-            },
-            _ = sigterm.recv() => {
-                info!("Received SIGTERM.");
-            },
+            _ = ticker.tick() => {}
+            _ = shutdown.cancelled() => return Ok(()),
         }
 
-        // Initiate shutdown
-        self.shutdown.cancel();
+        let ping = async {
+            let (reply_tx, reply_rx) = oneshot::channel();
+            backplane_tx
+                .send(BackplaneCommand::Ping { reply_tx })
+                .await
+                .is_ok()
+                && reply_rx.await.is_ok()
+        };
+
+        if tokio::time::timeout(period, ping).await == Ok(true) {
+            systemd::notify_watchdog();
+        } else {
+            warn!("Backplane unresponsive; withholding watchdog keep-alive");
+        }
+    }
+}
 
-        // Wait for all tasks to complete
-        self.tracker.wait().await;
-        info!("Exiting.");
+/// Apply configuration saved through the API to the running daemon.
+///
+/// Only the log level and pools can change while running; the API reports
+/// the other changes as needing a restart, and they're left alone here.
+#[cfg(feature = "api")]
+async fn apply_config_changes(
+    mut saved_rx: watch::Receiver<Config>,
+    pool_cmd_tx: Option<mpsc::Sender<PoolCommand>>,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let mut current = saved_rx.borrow_and_update().clone();
 
-        Ok(())
+    loop {
+        tokio::select! {
+            changed = saved_rx.changed() => {
+                if changed.is_err() {
+                    // The API is gone; nothing more will be saved
+                    shutdown.cancelled().await;
+                    return Ok(());
+                }
+            }
+            _ = shutdown.cancelled() => return Ok(()),
+        }
+
+        let new = saved_rx.borrow_and_update().clone();
+        for setting in current.reloadable_changes(&new) {
+            match setting {
+                "daemon.log_level" => {
+                    match crate::tracing::parse_level(&new.daemon.log_level)
+                        .and_then(crate::tracing::set_default_level)
+                    {
+                        Ok(_) => info!(level = %new.daemon.log_level, "Log level reloaded."),
+                        Err(e) => warn!(error = %e, "Failed to apply log level"),
+                    }
+                }
+                "pools" => {
+                    if let Some(tx) = &pool_cmd_tx {
+                        let command = PoolCommand::Replace {
+                            pools: new.pools.clone(),
+                        };
+                        if tx.send(command).await.is_err() {
+                            warn!("Pool manager stopped; pool changes not applied");
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        current = new;
     }
 }
 
+/// Benchmark the threads the backplane produces, write the report, and stop
+/// the daemon.
+async fn run_benchmark(
+    options: BenchmarkOptions,
+    mut thread_rx: mpsc::Receiver<Box<dyn HashThread>>,
+    backplane_tx: mpsc::Sender<BackplaneCommand>,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let mut threads = benchmark::gather_threads(&mut thread_rx, &shutdown).await;
+    if shutdown.is_cancelled() {
+        return Ok(());
+    }
+    info!(threads = threads.len(), "Starting benchmark.");
+
+    let mut control = BackplaneControl::new(backplane_tx);
+    let report = benchmark::run(&mut threads, &mut control, &options.plan, &shutdown).await?;
+    options.write_report(&report)?;
+
+    info!(points = report.results.len(), "Benchmark complete.");
+    shutdown.cancel();
+    Ok(())
+}
+
 impl Default for Daemon {
     fn default() -> Self {
         Self::new()
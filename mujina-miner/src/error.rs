@@ -24,6 +24,17 @@ pub enum Error {
     #[error("Protocol error: {0}")]
     Protocol(String),
 
+    /// A board's port is held by another process
+    #[error(transparent)]
+    PortBusy(#[from] crate::transport::PortBusy),
+
+    /// A board's management firmware is older than the driver supports
+    #[error("Board firmware {version} is too old; version {minimum} or newer is required")]
+    FirmwareTooOld {
+        version: crate::mgmt_protocol::bitaxe_raw::version::FirmwareVersion,
+        minimum: crate::mgmt_protocol::bitaxe_raw::version::FirmwareVersion,
+    },
+
     /// Hardware communication errors
     #[error("Hardware error: {0}")]
     Hardware(String),
@@ -0,0 +1,273 @@
+//! Firmware updates for boards' ESP32 management controllers.
+//!
+//! A Bitaxe's control port is served by bitaxe-raw on an ESP32-S3. To update
+//! it in the field, the backplane stops the board and hands its control port
+//! to [`update`], which:
+//!
+//! 1. Resets the ESP32 into its ROM loader by toggling DTR and RTS, the
+//!    sequence esptool uses for the chip's native USB port. The firmware
+//!    drops off USB and the loader enumerates in its place, under
+//!    Espressif's vendor ID.
+//! 2. Waits for the backplane to report the loader's serial port, which it
+//!    spots among USB hotplug events.
+//! 3. Writes the image with the ESP ROM loader protocol (see
+//!    [`crate::mgmt_protocol::esp_loader`]), then resets the chip out of the
+//!    loader. The board comes back through hotplug running the new firmware.
+//!
+//! Progress is published on a watch channel for the API to stream.
+
+use std::time::Duration;
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{oneshot, watch};
+use tokio_serial::{SerialPort, SerialPortBuilderExt, SerialStream};
+
+use crate::{
+    mgmt_protocol::esp_loader::{EspLoader, LoaderError},
+    tracing::prelude::*,
+};
+
+/// Baud rate of both ports. Native USB ignores it, but the loader syncs
+/// at it.
+const BAUD_RATE: u32 = 115_200;
+
+/// How long the control lines are held at each step of the reset.
+const RESET_STEP: Duration = Duration::from_millis(100);
+
+/// How long the board's port may stay open after the board is stopped, as
+/// its workers wind down.
+const PORT_RELEASE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long the loader has to show up on USB after the reset.
+pub const LOADER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Firmware to write and where.
+#[derive(Clone)]
+pub struct FirmwareImage {
+    /// Flash address to write at: 0 for a merged image with bootloader and
+    /// partition table, or an app partition's offset for an app image
+    pub offset: u32,
+
+    /// Image contents
+    pub data: Bytes,
+}
+
+/// How far an update has got.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum FirmwareProgress {
+    /// Resetting the controller into its ROM loader
+    Resetting,
+    /// Waiting for the loader to enumerate and answer
+    Connecting,
+    /// Writing the image
+    Writing { written: usize, total: usize },
+    /// Image written and the controller reset into it
+    Done,
+    /// The update failed; the controller may need the update run again
+    Failed { error: String },
+}
+
+/// Why an update failed.
+#[derive(Debug, thiserror::Error)]
+pub enum FirmwareError {
+    #[error("failed to open {path}: {source}")]
+    Open {
+        path: String,
+        source: tokio_serial::Error,
+    },
+
+    #[error("failed to reset {path} into its loader: {source}")]
+    Reset {
+        path: String,
+        source: tokio_serial::Error,
+    },
+
+    #[error("loader didn't appear within {0:?} of the reset")]
+    NoLoader(Duration),
+
+    #[error(transparent)]
+    Loader(#[from] LoaderError),
+}
+
+impl std::fmt::Debug for FirmwareImage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FirmwareImage")
+            .field("offset", &self.offset)
+            .field("len", &self.data.len())
+            .finish()
+    }
+}
+
+impl FirmwareProgress {
+    /// Whether the update has ended, one way or the other.
+    pub fn is_finished(&self) -> bool {
+        matches!(self, Self::Done | Self::Failed { .. })
+    }
+}
+
+/// Update the controller on `control_path`, reporting on `progress_tx`.
+///
+/// `loader_rx` delivers the serial port the ROM loader enumerates on; the
+/// backplane watches hotplug for it.
+pub async fn update(
+    control_path: String,
+    image: FirmwareImage,
+    loader_rx: oneshot::Receiver<String>,
+    progress_tx: watch::Sender<FirmwareProgress>,
+) {
+    info!(port = %control_path, bytes = image.data.len(), offset = image.offset, "Updating firmware.");
+    match run(&control_path, &image, loader_rx, &progress_tx).await {
+        Ok(()) => {
+            info!(port = %control_path, "Firmware updated.");
+            progress_tx.send_replace(FirmwareProgress::Done);
+        }
+        Err(e) => {
+            error!(port = %control_path, error = %e, "Firmware update failed");
+            progress_tx.send_replace(FirmwareProgress::Failed {
+                error: e.to_string(),
+            });
+        }
+    }
+}
+
+async fn run(
+    control_path: &str,
+    image: &FirmwareImage,
+    loader_rx: oneshot::Receiver<String>,
+    progress_tx: &watch::Sender<FirmwareProgress>,
+) -> Result<(), FirmwareError> {
+    progress_tx.send_replace(FirmwareProgress::Resetting);
+    let mut control = open_released(control_path).await?;
+    reset_to_loader(&mut control)
+        .await
+        .map_err(|source| FirmwareError::Reset {
+            path: control_path.to_string(),
+            source,
+        })?;
+    drop(control);
+
+    progress_tx.send_replace(FirmwareProgress::Connecting);
+    let loader_path = tokio::time::timeout(LOADER_TIMEOUT, loader_rx)
+        .await
+        .ok()
+        .and_then(Result::ok)
+        .ok_or(FirmwareError::NoLoader(LOADER_TIMEOUT))?;
+    debug!(port = %loader_path, "ROM loader enumerated");
+    let port = open(&loader_path)?;
+
+    let mut loader = EspLoader::new(port);
+    loader.sync().await?;
+    loader.spi_attach().await?;
+    loader
+        .flash(image.offset, &image.data, |written, total| {
+            progress_tx.send_replace(FirmwareProgress::Writing { written, total });
+        })
+        .await?;
+    loader.finish().await?;
+
+    // Out of the loader and into the new firmware
+    let mut port = loader.into_inner();
+    hard_reset(&mut port)
+        .await
+        .map_err(|source| FirmwareError::Reset {
+            path: loader_path,
+            source,
+        })
+}
+
+fn open(path: &str) -> Result<SerialStream, FirmwareError> {
+    tokio_serial::new(path, BAUD_RATE)
+        .open_native_async()
+        .map_err(|source| FirmwareError::Open {
+            path: path.to_string(),
+            source,
+        })
+}
+
+/// Open the port of a board just stopped, once the board lets go of it.
+async fn open_released(path: &str) -> Result<SerialStream, FirmwareError> {
+    let deadline = tokio::time::Instant::now() + PORT_RELEASE_TIMEOUT;
+    loop {
+        match open(path) {
+            Err(FirmwareError::Open { source, .. })
+                if source.kind == tokio_serial::ErrorKind::NoDevice
+                    && tokio::time::Instant::now() < deadline =>
+            {
+                tokio::time::sleep(RESET_STEP).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Reset into the ROM loader: hold the boot strap (DTR) through a reset
+/// pulse (RTS). This is esptool's sequence for the ESP32-S3's native USB,
+/// which goes through both lines high rather than both low on the way into
+/// reset.
+async fn reset_to_loader(port: &mut SerialStream) -> Result<(), tokio_serial::Error> {
+    port.write_request_to_send(false)?;
+    port.write_data_terminal_ready(false)?;
+    tokio::time::sleep(RESET_STEP).await;
+
+    port.write_data_terminal_ready(true)?;
+    port.write_request_to_send(false)?;
+    tokio::time::sleep(RESET_STEP).await;
+
+    port.write_request_to_send(true)?;
+    port.write_data_terminal_ready(false)?;
+    port.write_request_to_send(true)?;
+    tokio::time::sleep(RESET_STEP).await;
+
+    port.write_data_terminal_ready(false)?;
+    port.write_request_to_send(false)?;
+    Ok(())
+}
+
+/// Reset the chip with no boot strap held, so it starts its firmware.
+async fn hard_reset(port: &mut SerialStream) -> Result<(), tokio_serial::Error> {
+    port.write_request_to_send(true)?;
+    tokio::time::sleep(RESET_STEP).await;
+    port.write_request_to_send(false)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_missing_port_fails_update() {
+        let (_loader_tx, loader_rx) = oneshot::channel();
+        let (progress_tx, progress_rx) = watch::channel(FirmwareProgress::Resetting);
+        let image = FirmwareImage {
+            offset: 0,
+            data: Bytes::from_static(&[0xe9; 16]),
+        };
+
+        update("/dev/mujina-missing".into(), image, loader_rx, progress_tx).await;
+        let progress = progress_rx.borrow().clone();
+        assert!(progress.is_finished());
+        assert!(
+            matches!(&progress, FirmwareProgress::Failed { error } if error.contains("/dev/mujina-missing")),
+            "{progress:?}"
+        );
+    }
+
+    #[test]
+    fn test_progress_wire_format() {
+        let progress = FirmwareProgress::Writing {
+            written: 1024,
+            total: 4096,
+        };
+        assert_eq!(
+            serde_json::to_value(&progress).unwrap(),
+            serde_json::json!({ "state": "writing", "written": 1024, "total": 4096 })
+        );
+        assert_eq!(
+            serde_json::to_value(FirmwareProgress::Done).unwrap(),
+            serde_json::json!({ "state": "done" })
+        );
+    }
+}
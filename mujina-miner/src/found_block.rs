@@ -0,0 +1,226 @@
+//! Shares that solve a block.
+//!
+//! A share meeting the network target is worth more than every other share
+//! the miner will ever find, and is gone for good if it's lost to a pool
+//! disconnect, a node that doesn't answer, or a crash. So the scheduler
+//! takes one down a path of its own: besides going to its source as any
+//! share does, it's handed to the found-block [`task`], which writes the
+//! solved header and coinbase to `found-blocks/` in the state directory
+//! before anything else, then announces it on the event bus, where the
+//! alert manager picks it up.
+//!
+//! From the record kept on disk the block can be rebuilt and submitted by
+//! hand, if neither the pool nor the node got it: the header is complete,
+//! and with the coinbase and merkle branches the pool's template can be
+//! checked against it.
+
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bitcoin::consensus::serialize;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, mpsc};
+
+use crate::{
+    asic::hash_thread::Share,
+    job_source::{HeaderTemplate, JobTemplate, MerkleRootKind},
+    runtime::RuntimeEvent,
+    tracing::prelude::*,
+};
+
+/// Directory under the state directory found blocks are kept in.
+pub const DIR_NAME: &str = "found-blocks";
+
+/// A solved block, as kept on disk.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FoundBlock {
+    /// When the share reached the scheduler, Unix seconds
+    pub found_at: u64,
+    /// Name of the source the job came from
+    pub source: String,
+    /// Job ID the source assigned
+    pub job_id: String,
+    /// Block hash
+    pub hash: String,
+    /// The 80-byte header, hex
+    pub header: String,
+    /// The full coinbase transaction, hex; None for header-only jobs
+    pub coinbase: Option<String>,
+    /// Merkle branches from the coinbase to the root, hex
+    pub merkle_branches: Vec<String>,
+}
+
+impl FoundBlock {
+    /// The block `share` solves for `template`, if its hash meets the
+    /// network target.
+    pub fn from_share(source: &str, template: &JobTemplate, share: &Share) -> Option<Self> {
+        if !template.target().is_met_by(share.hash) {
+            return None;
+        }
+
+        let header = HeaderTemplate::new(template, share.extranonce2, share.ntime)?
+            .header(share.version, share.nonce);
+        let (coinbase, merkle_branches) = match &template.merkle_root {
+            MerkleRootKind::Computed(merkle) => {
+                let mut coinbase = merkle.coinbase1().to_vec();
+                coinbase.extend_from_slice(merkle.extranonce1());
+                share.extranonce2?.extend_vec(&mut coinbase);
+                coinbase.extend_from_slice(merkle.coinbase2());
+                let branches = merkle
+                    .merkle_branches()
+                    .iter()
+                    .map(|branch| hex::encode(serialize(branch)))
+                    .collect();
+                (Some(hex::encode(coinbase)), branches)
+            }
+            MerkleRootKind::Fixed(_) => (None, Vec::new()),
+        };
+
+        Some(Self {
+            found_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            source: source.to_string(),
+            job_id: template.id.clone(),
+            hash: header.block_hash().to_string(),
+            header: hex::encode(serialize(&header)),
+            coinbase,
+            merkle_branches,
+        })
+    }
+
+    /// Write the block to its own file under `dir`, synced to disk before
+    /// returning; returns the file's path.
+    pub fn persist(&self, dir: &Path) -> std::io::Result<PathBuf> {
+        use std::io::Write;
+
+        std::fs::create_dir_all(dir)?;
+        let path = dir.join(format!("{}-{}.json", self.found_at, self.hash));
+        let mut file = std::fs::File::create(&path)?;
+        file.write_all(&serde_json::to_vec_pretty(self)?)?;
+        file.sync_all()?;
+        Ok(path)
+    }
+}
+
+/// Keep each block `found_rx` yields in `dir` and announce it on `events`.
+///
+/// Runs until the scheduler drops its sender, so a block found while
+/// shutting down is still kept.
+pub async fn task(
+    mut found_rx: mpsc::UnboundedReceiver<FoundBlock>,
+    dir: PathBuf,
+    events: broadcast::Sender<RuntimeEvent>,
+) {
+    while let Some(block) = found_rx.recv().await {
+        info!(hash = %block.hash, source = %block.source, job_id = %block.job_id, "Block found!");
+
+        let persisted = tokio::task::spawn_blocking({
+            let block = block.clone();
+            let dir = dir.clone();
+            move || block.persist(&dir)
+        })
+        .await;
+        match persisted {
+            Ok(Ok(path)) => info!(hash = %block.hash, path = %path.display(), "Kept found block"),
+            Ok(Err(e)) => error!(
+                hash = %block.hash,
+                header = %block.header,
+                coinbase = ?block.coinbase,
+                error = %e,
+                "Failed to keep found block"
+            ),
+            Err(e) => error!(hash = %block.hash, error = %e, "Failed to keep found block"),
+        }
+
+        let _ = events.send(RuntimeEvent::BlockFound {
+            hash: block.hash,
+            source: block.source,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job_source::test_blocks::block_881423;
+    use crate::job_source::{
+        Extranonce2Range, GeneralPurposeBits, MerkleRootTemplate, VersionTemplate,
+    };
+    use crate::u256::U256;
+    use bitcoin::block::Version;
+    use bitcoin::hashes::Hash;
+    use bitcoin::pow::Target;
+
+    fn template() -> JobTemplate {
+        JobTemplate {
+            id: "block".into(),
+            prev_blockhash: *block_881423::PREV_BLOCKHASH,
+            // The share carries the rolled version; the template's base
+            // has the rolling bits masked away
+            version: VersionTemplate::new(
+                Version::from_consensus(block_881423::VERSION.to_consensus() & !0x1fff_e000),
+                GeneralPurposeBits::full(),
+            )
+            .unwrap(),
+            bits: *block_881423::BITS,
+            share_target: Target::MAX,
+            time: block_881423::TIME,
+            merkle_root: MerkleRootKind::Computed(MerkleRootTemplate::new(
+                block_881423::coinbase1_bytes().to_vec(),
+                block_881423::extranonce1_bytes().to_vec(),
+                Extranonce2Range::new(block_881423::EXTRANONCE2.size()).unwrap(),
+                block_881423::coinbase2_bytes().to_vec(),
+                block_881423::MERKLE_BRANCHES.clone(),
+            )),
+        }
+    }
+
+    fn share(nonce: u32, hash: bitcoin::BlockHash) -> Share {
+        Share {
+            nonce,
+            hash,
+            version: *block_881423::VERSION,
+            ntime: block_881423::TIME,
+            extranonce2: Some(*block_881423::EXTRANONCE2),
+            expected_hashes: U256::from(Target::MAX.to_work()),
+        }
+    }
+
+    #[test]
+    fn test_block_rebuilt_from_share() {
+        let winning = share(block_881423::NONCE, *block_881423::BLOCK_HASH);
+        let block = FoundBlock::from_share("pool", &template(), &winning).unwrap();
+
+        assert_eq!(block.hash, block_881423::BLOCK_HASH.to_string());
+        assert_eq!(block.header, hex::encode(block_881423::HEADER_BYTES));
+        assert_eq!(
+            block.coinbase.as_deref(),
+            Some(hex::encode(block_881423::COINBASE_TX).as_str())
+        );
+        assert_eq!(block.merkle_branches.len(), 11);
+
+        // An ordinary share, however good, isn't a block
+        let mut hash = block_881423::BLOCK_HASH.to_byte_array();
+        hash[31] = 0x01;
+        let losing = share(
+            block_881423::NONCE,
+            bitcoin::BlockHash::from_byte_array(hash),
+        );
+        assert!(FoundBlock::from_share("pool", &template(), &losing).is_none());
+    }
+
+    #[test]
+    fn test_persisted_block_reads_back() {
+        let dir = std::env::temp_dir().join(format!("mujina-found-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let winning = share(block_881423::NONCE, *block_881423::BLOCK_HASH);
+        let block = FoundBlock::from_share("pool", &template(), &winning).unwrap();
+        let path = block.persist(&dir).unwrap();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(serde_json::from_str::<FoundBlock>(&text).unwrap(), block);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,258 @@
+//! Transaction-level I2C tracing.
+//!
+//! Records every transaction an `I2c` implementation carries out as the
+//! rows a Saleae Logic 2 I2C analyzer exports (start, address, data, stop),
+//! so a trace taken on a miner in the field feeds straight into
+//! mujina-dissect like a logic analyzer capture would. Timestamps are
+//! seconds since the Unix epoch, taken when the transaction finishes. A
+//! failed transaction is recorded with its address unacknowledged and the
+//! error in the `error` column.
+//!
+//! Boards enable tracing when `MUJINA_I2C_TRACE` names a directory, writing
+//! one `i2c-<serial>.csv` per board there.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use parking_lot::Mutex;
+use tracing::warn;
+
+use super::{HwError, Result};
+
+/// Environment variable naming the directory traces are written to.
+pub const TRACE_DIR_ENV: &str = "MUJINA_I2C_TRACE";
+
+/// Column header of a Saleae Logic 2 I2C export.
+const HEADER: &str = "name,type,start_time,duration,ack,address,read,data,error";
+
+/// A transaction as the bus saw it.
+#[derive(Debug, Clone, Copy)]
+pub enum Transaction<'a> {
+    Write {
+        addr: u8,
+        data: &'a [u8],
+    },
+    Read {
+        addr: u8,
+        data: &'a [u8],
+    },
+    WriteRead {
+        addr: u8,
+        write: &'a [u8],
+        read: &'a [u8],
+    },
+}
+
+/// Shared handle on a trace file. Clones write to the same file.
+#[derive(Clone)]
+pub struct I2cTrace {
+    out: Arc<Mutex<Box<dyn Write + Send>>>,
+}
+
+impl I2cTrace {
+    /// Start a trace written to `out`.
+    pub fn new(out: impl Write + Send + 'static) -> io::Result<Self> {
+        let mut out: Box<dyn Write + Send> = Box::new(out);
+        writeln!(out, "{}", HEADER)?;
+        out.flush()?;
+        Ok(Self {
+            out: Arc::new(Mutex::new(out)),
+        })
+    }
+
+    /// Start a trace in a new file at `path`, replacing any already there.
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+
+    /// Start the trace of the board `serial` if `MUJINA_I2C_TRACE` asks for
+    /// one. A trace that can't be created is logged and skipped; tracing
+    /// shouldn't keep a board from mining.
+    pub fn from_env(serial: Option<&str>) -> Option<Self> {
+        let dir = PathBuf::from(std::env::var_os(TRACE_DIR_ENV)?);
+        let path = dir.join(format!("i2c-{}.csv", serial.unwrap_or("unknown")));
+        match Self::create(&path) {
+            Ok(trace) => Some(trace),
+            Err(e) => {
+                warn!(path = %path.display(), error = %e, "Failed to create I2C trace");
+                None
+            }
+        }
+    }
+
+    /// Record a transaction and how it ended.
+    pub fn record(&self, transaction: Transaction<'_>, result: &Result<()>) {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        let error = match result {
+            Ok(()) => None,
+            Err(HwError::I2c(super::I2cError::NoAck(_))) => Some("NAK".to_string()),
+            Err(e) => Some(format!("\"{}\"", e.to_string().replace('"', "\"\""))),
+        };
+
+        let mut rows = Vec::new();
+        match transaction {
+            Transaction::Write { addr, data } => {
+                rows.push(Row::Start);
+                rows.extend(segment(addr, false, data, error.is_none()));
+            }
+            Transaction::Read { addr, data } => {
+                rows.push(Row::Start);
+                rows.extend(segment(addr, true, data, error.is_none()));
+            }
+            Transaction::WriteRead { addr, write, read } => {
+                rows.push(Row::Start);
+                rows.extend(segment(addr, false, write, error.is_none()));
+                if error.is_none() {
+                    rows.push(Row::Start);
+                    rows.extend(segment(addr, true, read, true));
+                }
+            }
+        }
+        rows.push(Row::Stop);
+
+        let mut out = self.out.lock();
+        let written = rows
+            .iter()
+            .try_for_each(|row| row.write(&mut *out, time, error.as_deref()))
+            .and_then(|()| out.flush());
+        if let Err(e) = written {
+            warn!(error = %e, "Failed to write I2C trace");
+        }
+    }
+}
+
+/// A row of the export.
+enum Row {
+    Start,
+    Address { addr: u8, read: bool, ack: bool },
+    Data { byte: u8, ack: bool },
+    Stop,
+}
+
+impl Row {
+    fn write(&self, out: &mut dyn Write, time: f64, error: Option<&str>) -> io::Result<()> {
+        let error = error.unwrap_or("");
+        match self {
+            Row::Start => writeln!(out, "I2C,start,{:.9},0,,,,,", time),
+            Row::Address { addr, read, ack } => writeln!(
+                out,
+                "I2C,address,{:.9},0,{},0x{:02X},{},,{}",
+                time,
+                ack,
+                addr,
+                read,
+                if *ack { "" } else { error }
+            ),
+            Row::Data { byte, ack } => {
+                writeln!(out, "I2C,data,{:.9},0,{},,,0x{:02X},", time, ack, byte)
+            }
+            Row::Stop => writeln!(out, "I2C,stop,{:.9},0,,,,,", time),
+        }
+    }
+}
+
+/// The rows of one addressed segment. An unacknowledged address ends the
+/// segment; the controller acknowledges every byte it reads but the last.
+fn segment(addr: u8, read: bool, data: &[u8], ack: bool) -> Vec<Row> {
+    let mut rows = vec![Row::Address { addr, read, ack }];
+    if ack {
+        rows.extend(data.iter().enumerate().map(|(i, &byte)| Row::Data {
+            byte,
+            ack: !read || i + 1 < data.len(),
+        }));
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hw_trait::I2cError;
+
+    /// Writer whose contents stay readable after the trace takes it.
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// The trace's rows with the timestamps cut out.
+    fn rows(out: &Shared) -> Vec<String> {
+        String::from_utf8(out.0.lock().clone())
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let mut fields: Vec<_> = line.split(',').collect();
+                fields.remove(2);
+                fields.join(",")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_write_read_traced_as_repeated_start() {
+        let out = Shared::default();
+        let trace = I2cTrace::new(out.clone()).unwrap();
+
+        trace.record(
+            Transaction::WriteRead {
+                addr: 0x4C,
+                write: &[0xFE],
+                read: &[0x5D, 0x28],
+            },
+            &Ok(()),
+        );
+
+        assert_eq!(
+            rows(&out),
+            vec![
+                "name,type,duration,ack,address,read,data,error",
+                "I2C,start,0,,,,,",
+                "I2C,address,0,true,0x4C,false,,",
+                "I2C,data,0,true,,,0xFE,",
+                "I2C,start,0,,,,,",
+                "I2C,address,0,true,0x4C,true,,",
+                "I2C,data,0,true,,,0x5D,",
+                "I2C,data,0,false,,,0x28,",
+                "I2C,stop,0,,,,,",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nak_traced_on_address() {
+        let out = Shared::default();
+        let trace = I2cTrace::new(out.clone()).unwrap();
+
+        trace.record(
+            Transaction::Read {
+                addr: 0x51,
+                data: &[0],
+            },
+            &Err(I2cError::NoAck(0x51).into()),
+        );
+
+        assert_eq!(
+            rows(&out)[1..],
+            [
+                "I2C,start,0,,,,,",
+                "I2C,address,0,false,0x51,true,,NAK",
+                "I2C,stop,0,,,,,",
+            ]
+        );
+    }
+}
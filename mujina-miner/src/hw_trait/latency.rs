@@ -0,0 +1,255 @@
+//! Latency histograms for hardware operations.
+//!
+//! Reading a board's sensors takes a few dozen I2C transactions, each of
+//! which may cross USB to a management controller and back. [`LatencyStats`]
+//! counts how long such operations take, and how many time out or fail, so
+//! slow status reads can be traced to the layer responsible. It's shared by
+//! whoever times the operations and whoever reports on them, like the
+//! receive counters of the ASIC data port.
+//!
+//! [`TimedI2c`] times every transaction of any [`I2c`] bus.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use super::{HwError, I2c, Result};
+
+/// Upper bounds of the histogram buckets, in microseconds. Operations
+/// slower than the last land in an overflow bucket.
+const BUCKET_BOUNDS_US: [u64; 11] = [
+    500, 1_000, 2_000, 5_000, 10_000, 20_000, 50_000, 100_000, 200_000, 500_000, 1_000_000,
+];
+
+/// How a timed operation ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Ok,
+    Timeout,
+    Error,
+}
+
+impl Outcome {
+    /// How an operation returning `result` ended.
+    pub fn of<T>(result: &Result<T>) -> Self {
+        match result {
+            Ok(_) => Self::Ok,
+            Err(HwError::Timeout) => Self::Timeout,
+            Err(HwError::Io(e)) if e.kind() == std::io::ErrorKind::TimedOut => Self::Timeout,
+            Err(_) => Self::Error,
+        }
+    }
+}
+
+/// Latency counters shared between the code timing operations and whoever
+/// reports on them.
+#[derive(Debug, Default)]
+pub struct LatencyStats {
+    buckets: [AtomicU64; BUCKET_BOUNDS_US.len() + 1],
+    total_us: AtomicU64,
+    max_us: AtomicU64,
+    timeouts: AtomicU64,
+    errors: AtomicU64,
+}
+
+/// Point-in-time latency counts for one kind of operation.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LatencyHistogram {
+    /// Operations finished, whatever their outcome
+    pub count: u64,
+    /// Operations that timed out
+    pub timeouts: u64,
+    /// Operations that failed other than by timing out
+    pub errors: u64,
+    /// Mean latency in milliseconds
+    pub mean_ms: Option<f64>,
+    /// Slowest operation in milliseconds
+    pub max_ms: Option<f64>,
+    /// Operations by latency, fastest first; each bucket counts those
+    /// slower than the one before
+    pub buckets: Vec<LatencyBucket>,
+}
+
+/// One bucket of a [`LatencyHistogram`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LatencyBucket {
+    /// Upper bound in milliseconds, `None` for the overflow bucket
+    pub le_ms: Option<f64>,
+    /// Operations in the bucket
+    pub count: u64,
+}
+
+/// An I2C bus whose every transaction is timed.
+#[derive(Clone)]
+pub struct TimedI2c<I> {
+    inner: I,
+    stats: Arc<LatencyStats>,
+}
+
+impl LatencyStats {
+    /// Count an operation that took `elapsed` and ended as `outcome`.
+    pub fn record(&self, elapsed: Duration, outcome: Outcome) {
+        let us = u64::try_from(elapsed.as_micros()).unwrap_or(u64::MAX);
+        let bucket = BUCKET_BOUNDS_US
+            .iter()
+            .position(|&bound| us <= bound)
+            .unwrap_or(BUCKET_BOUNDS_US.len());
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.total_us.fetch_add(us, Ordering::Relaxed);
+        self.max_us.fetch_max(us, Ordering::Relaxed);
+        match outcome {
+            Outcome::Ok => {}
+            Outcome::Timeout => {
+                self.timeouts.fetch_add(1, Ordering::Relaxed);
+            }
+            Outcome::Error => {
+                self.errors.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Time `op`, counting it as its result says it ended.
+    pub async fn time<T>(&self, op: impl std::future::Future<Output = Result<T>>) -> Result<T> {
+        let started = Instant::now();
+        let result = op.await;
+        self.record(started.elapsed(), Outcome::of(&result));
+        result
+    }
+
+    /// Current counts.
+    pub fn snapshot(&self) -> LatencyHistogram {
+        let counts: Vec<u64> = self
+            .buckets
+            .iter()
+            .map(|bucket| bucket.load(Ordering::Relaxed))
+            .collect();
+        let count = counts.iter().sum();
+        let ms = |us: u64| us as f64 / 1000.0;
+
+        LatencyHistogram {
+            count,
+            timeouts: self.timeouts.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            mean_ms: (count > 0).then(|| ms(self.total_us.load(Ordering::Relaxed)) / count as f64),
+            max_ms: (count > 0).then(|| ms(self.max_us.load(Ordering::Relaxed))),
+            buckets: counts
+                .into_iter()
+                .enumerate()
+                .map(|(i, count)| LatencyBucket {
+                    le_ms: BUCKET_BOUNDS_US.get(i).map(|&bound| ms(bound)),
+                    count,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl LatencyHistogram {
+    /// Latency in milliseconds under which a fraction `q` of operations
+    /// finished, to the resolution of the buckets. Operations in the
+    /// overflow bucket are taken to be as slow as the slowest.
+    pub fn quantile_ms(&self, q: f64) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = (q.clamp(0.0, 1.0) * self.count as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for bucket in &self.buckets {
+            seen += bucket.count;
+            if seen >= rank {
+                return bucket.le_ms.or(self.max_ms);
+            }
+        }
+        self.max_ms
+    }
+}
+
+impl<I> TimedI2c<I> {
+    /// Time the transactions of `inner`, counting them in `stats`.
+    pub fn new(inner: I, stats: Arc<LatencyStats>) -> Self {
+        Self { inner, stats }
+    }
+
+    /// The counters this bus reports to.
+    pub fn stats(&self) -> &Arc<LatencyStats> {
+        &self.stats
+    }
+}
+
+#[async_trait]
+impl<I: I2c> I2c for TimedI2c<I> {
+    async fn write(&mut self, addr: u8, data: &[u8]) -> Result<()> {
+        self.stats.time(self.inner.write(addr, data)).await
+    }
+
+    async fn read(&mut self, addr: u8, buffer: &mut [u8]) -> Result<()> {
+        self.stats.time(self.inner.read(addr, buffer)).await
+    }
+
+    async fn write_read(&mut self, addr: u8, write: &[u8], read: &mut [u8]) -> Result<()> {
+        self.stats
+            .time(self.inner.write_read(addr, write, read))
+            .await
+    }
+
+    async fn set_frequency(&mut self, hz: u32) -> Result<()> {
+        self.inner.set_frequency(hz).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hw_trait::mock::MockI2c;
+    use crate::hw_trait::I2cError;
+
+    #[test]
+    fn test_histogram_buckets_and_quantiles() {
+        let stats = LatencyStats::default();
+        for _ in 0..98 {
+            stats.record(Duration::from_micros(800), Outcome::Ok);
+        }
+        stats.record(Duration::from_millis(30), Outcome::Error);
+        stats.record(Duration::from_millis(1500), Outcome::Timeout);
+
+        let histogram = stats.snapshot();
+        assert_eq!(histogram.count, 100);
+        assert_eq!(histogram.timeouts, 1);
+        assert_eq!(histogram.errors, 1);
+        assert_eq!(histogram.max_ms, Some(1500.0));
+        assert_eq!(histogram.buckets.len(), BUCKET_BOUNDS_US.len() + 1);
+        assert_eq!(histogram.buckets[1].le_ms, Some(1.0));
+        assert_eq!(histogram.buckets[1].count, 98);
+        assert_eq!(histogram.buckets[6].count, 1);
+        assert_eq!(histogram.buckets.last().unwrap().le_ms, None);
+        assert_eq!(histogram.buckets.last().unwrap().count, 1);
+
+        assert_eq!(histogram.quantile_ms(0.5), Some(1.0));
+        assert_eq!(histogram.quantile_ms(0.99), Some(50.0));
+        assert_eq!(histogram.quantile_ms(1.0), Some(1500.0));
+        assert_eq!(LatencyStats::default().snapshot().quantile_ms(0.5), None);
+    }
+
+    #[tokio::test]
+    async fn test_timed_i2c_counts_failures() {
+        let mock = MockI2c::new();
+        mock.expect_write(0x4C, &[0x00]);
+        mock.expect_write(0x4C, &[0x00])
+            .inject_error(I2cError::NoAck(0x4C));
+        mock.expect_read(0x4C, 1).inject_error(HwError::Timeout);
+
+        let mut i2c = TimedI2c::new(mock.clone(), Arc::default());
+        i2c.write(0x4C, &[0x00]).await.unwrap();
+        i2c.write(0x4C, &[0x00]).await.unwrap_err();
+        i2c.read(0x4C, &mut [0u8; 1]).await.unwrap_err();
+        mock.done();
+
+        let histogram = i2c.stats().snapshot();
+        assert_eq!(histogram.count, 3);
+        assert_eq!(histogram.errors, 1);
+        assert_eq!(histogram.timeouts, 1);
+    }
+}
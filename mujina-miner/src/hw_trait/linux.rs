@@ -0,0 +1,314 @@
+//! Hardware interfaces of the Linux host itself.
+//!
+//! A hash board can be wired straight to the host's header, e.g. a
+//! Raspberry Pi driving a Bitaxe's ASIC UART, I2C bus and reset line with no
+//! ESP32 management controller in between. Its peripherals are then reached
+//! through the kernel's own interfaces: I2C adapters through i2c-dev
+//! (`/dev/i2c-N`) and GPIO lines through the GPIO character device
+//! (`/dev/gpiochipN`). Both are stable kernel interfaces, so nothing here is
+//! specific to one SoC and it cross-compiles like the rest of the crate.
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use gpiocdev::line::Value;
+use gpiocdev::request::{Config, Request};
+use nix::libc;
+use parking_lot::Mutex;
+use tracing::debug;
+
+use super::gpio::{Gpio, GpioPin, PinMode, PinValue};
+use super::i2c::{I2c, I2cError};
+use super::{HwError, Result};
+
+/// `I2C_RDWR` from `linux/i2c-dev.h`: carry out several messages as one
+/// transaction, with repeated starts between them.
+const I2C_RDWR: libc::c_ulong = 0x0707;
+
+/// `I2C_M_RD` from `linux/i2c.h`: the message reads from the device.
+const I2C_M_RD: u16 = 0x0001;
+
+/// Name GPIO lines are requested under, as `gpioinfo` shows it.
+const GPIO_CONSUMER: &str = "mujina";
+
+/// Kernel `struct i2c_msg`.
+#[repr(C)]
+struct I2cMsg {
+    addr: u16,
+    flags: u16,
+    len: u16,
+    buf: *mut u8,
+}
+
+/// Kernel `struct i2c_rdwr_ioctl_data`.
+#[repr(C)]
+struct I2cRdwrData {
+    msgs: *mut I2cMsg,
+    nmsgs: u32,
+}
+
+/// One message of a transaction: bytes to write, or a buffer to read into.
+enum Message {
+    Write(Vec<u8>),
+    Read(Vec<u8>),
+}
+
+/// An I2C adapter of the host, through i2c-dev.
+///
+/// Clones share the adapter; the kernel serializes their transactions.
+#[derive(Clone)]
+pub struct LinuxI2c {
+    bus: Arc<File>,
+    path: PathBuf,
+}
+
+impl LinuxI2c {
+    /// Open the adapter at `path`, e.g. `/dev/i2c-1`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let bus = OpenOptions::new().read(true).write(true).open(&path)?;
+        Ok(Self {
+            bus: Arc::new(bus),
+            path,
+        })
+    }
+
+    /// Carry out `messages` as one transaction with the device at `addr`,
+    /// returning them with the read buffers filled.
+    async fn transfer(&self, addr: u8, messages: Vec<Message>) -> Result<Vec<Message>> {
+        let bus = self.bus.clone();
+        tokio::task::spawn_blocking(move || rdwr(&bus, addr, messages))
+            .await
+            .map_err(|e| HwError::Other(format!("I2C transfer task failed: {}", e)))?
+    }
+}
+
+#[async_trait]
+impl I2c for LinuxI2c {
+    async fn write(&mut self, addr: u8, data: &[u8]) -> Result<()> {
+        self.transfer(addr, vec![Message::Write(data.to_vec())])
+            .await?;
+        Ok(())
+    }
+
+    async fn read(&mut self, addr: u8, buffer: &mut [u8]) -> Result<()> {
+        let messages = self
+            .transfer(addr, vec![Message::Read(vec![0; buffer.len()])])
+            .await?;
+        if let [Message::Read(data)] = messages.as_slice() {
+            buffer.copy_from_slice(data);
+        }
+        Ok(())
+    }
+
+    async fn write_read(&mut self, addr: u8, write: &[u8], read: &mut [u8]) -> Result<()> {
+        let messages = self
+            .transfer(
+                addr,
+                vec![
+                    Message::Write(write.to_vec()),
+                    Message::Read(vec![0; read.len()]),
+                ],
+            )
+            .await?;
+        if let [_, Message::Read(data)] = messages.as_slice() {
+            read.copy_from_slice(data);
+        }
+        Ok(())
+    }
+
+    async fn set_frequency(&mut self, hz: u32) -> Result<()> {
+        debug!(bus = %self.path.display(), hz, "I2C bus speed left to the device tree");
+        Err(HwError::NotSupported(
+            "the bus speed of a host I2C adapter is set by the device tree".into(),
+        ))
+    }
+}
+
+/// Issue `I2C_RDWR` for `messages` on `bus`.
+fn rdwr(bus: &File, addr: u8, mut messages: Vec<Message>) -> Result<Vec<Message>> {
+    let mut msgs = messages
+        .iter_mut()
+        .map(|message| {
+            let (flags, buf) = match message {
+                Message::Write(buf) => (0, buf),
+                Message::Read(buf) => (I2C_M_RD, buf),
+            };
+            let len = u16::try_from(buf.len()).map_err(|_| {
+                HwError::InvalidParameter(format!("I2C message of {} bytes", buf.len()))
+            })?;
+            Ok(I2cMsg {
+                addr: addr.into(),
+                flags,
+                len,
+                buf: buf.as_mut_ptr(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let mut data = I2cRdwrData {
+        msgs: msgs.as_mut_ptr(),
+        nmsgs: msgs.len() as u32,
+    };
+
+    // SAFETY: I2C_RDWR takes one i2c_rdwr_ioctl_data, which I2cRdwrData
+    // mirrors. Its messages point into buffers of `messages` that outlive
+    // the call, each at least as long as the message says.
+    let ret = unsafe {
+        libc::ioctl(
+            bus.as_raw_fd(),
+            I2C_RDWR as _,
+            &mut data as *mut I2cRdwrData,
+        )
+    };
+    if ret < 0 {
+        return Err(transfer_error(addr, io::Error::last_os_error()));
+    }
+    Ok(messages)
+}
+
+/// What a failed `I2C_RDWR` means. Adapter drivers report an address
+/// nobody acknowledged as ENXIO or EREMOTEIO.
+fn transfer_error(addr: u8, e: io::Error) -> HwError {
+    match e.raw_os_error() {
+        Some(libc::ENXIO | libc::EREMOTEIO) => I2cError::NoAck(addr).into(),
+        Some(libc::EAGAIN) => I2cError::ArbitrationLost.into(),
+        Some(libc::ETIMEDOUT) => HwError::Timeout,
+        _ => e.into(),
+    }
+}
+
+/// A GPIO chip of the host, through the GPIO character device.
+#[derive(Debug, Clone)]
+pub struct LinuxGpio {
+    chip: PathBuf,
+}
+
+impl LinuxGpio {
+    /// Use the chip at `chip`, e.g. `/dev/gpiochip0`.
+    pub fn new(chip: impl Into<PathBuf>) -> Self {
+        Self { chip: chip.into() }
+    }
+}
+
+#[async_trait]
+impl Gpio for LinuxGpio {
+    type Pin = LinuxGpioPin;
+
+    /// Request line `number` of the chip, as an input so nothing is driven
+    /// until the pin is written.
+    async fn pin(&mut self, number: u8) -> Result<Self::Pin> {
+        let offset = u32::from(number);
+        let request = Request::builder()
+            .on_chip(&self.chip)
+            .with_consumer(GPIO_CONSUMER)
+            .with_line(offset)
+            .as_input()
+            .request()
+            .map_err(gpio_error)?;
+        Ok(LinuxGpioPin {
+            request: Arc::new(request),
+            offset,
+            mode: Arc::new(Mutex::new(PinMode::Input)),
+        })
+    }
+}
+
+/// A requested line of a host GPIO chip. The line is held until the last
+/// clone is dropped.
+///
+/// Like a bitaxe-raw pin, writing an input pin makes it an output, so a
+/// reset line can be driven without first setting its mode.
+#[derive(Clone)]
+pub struct LinuxGpioPin {
+    request: Arc<Request>,
+    offset: u32,
+    mode: Arc<Mutex<PinMode>>,
+}
+
+impl LinuxGpioPin {
+    /// Reconfigure the line as an output driving `value`.
+    fn make_output(&self, value: Value) -> Result<()> {
+        let mut config = Config::default();
+        config.with_line(self.offset).as_output(value);
+        self.request.reconfigure(&config).map_err(gpio_error)?;
+        *self.mode.lock() = PinMode::Output;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl GpioPin for LinuxGpioPin {
+    async fn set_mode(&mut self, mode: PinMode) -> Result<()> {
+        if *self.mode.lock() == mode {
+            return Ok(());
+        }
+        match mode {
+            PinMode::Output => self.make_output(Value::Inactive),
+            PinMode::Input => {
+                let mut config = Config::default();
+                config.with_line(self.offset).as_input();
+                self.request.reconfigure(&config).map_err(gpio_error)?;
+                *self.mode.lock() = PinMode::Input;
+                Ok(())
+            }
+        }
+    }
+
+    async fn write(&mut self, value: PinValue) -> Result<()> {
+        debug!(line = self.offset, value = ?value, "GPIO write");
+        let value = to_value(value);
+        if *self.mode.lock() == PinMode::Input {
+            return self.make_output(value);
+        }
+        self.request
+            .set_value(self.offset, value)
+            .map_err(gpio_error)
+    }
+
+    async fn read(&mut self) -> Result<PinValue> {
+        let value = self.request.value(self.offset).map_err(gpio_error)?;
+        Ok(PinValue::from(value == Value::Active))
+    }
+}
+
+fn to_value(value: PinValue) -> Value {
+    match value {
+        PinValue::High => Value::Active,
+        PinValue::Low => Value::Inactive,
+    }
+}
+
+fn gpio_error(e: gpiocdev::Error) -> HwError {
+    HwError::Other(format!("GPIO: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unacknowledged_address_is_nak() {
+        let nak = transfer_error(0x4C, io::Error::from_raw_os_error(libc::EREMOTEIO));
+        assert!(matches!(nak, HwError::I2c(I2cError::NoAck(0x4C))));
+        let nak = transfer_error(0x4C, io::Error::from_raw_os_error(libc::ENXIO));
+        assert!(matches!(nak, HwError::I2c(I2cError::NoAck(0x4C))));
+        let other = transfer_error(0x4C, io::Error::from_raw_os_error(libc::EIO));
+        assert!(matches!(other, HwError::Io(_)));
+    }
+
+    #[tokio::test]
+    async fn test_missing_adapter_refused() {
+        assert!(matches!(
+            LinuxI2c::open("/nonexistent/i2c-1"),
+            Err(HwError::Io(_))
+        ));
+        assert!(LinuxGpio::new("/nonexistent/gpiochip0")
+            .pin(17)
+            .await
+            .is_err());
+    }
+}
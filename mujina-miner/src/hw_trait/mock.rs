@@ -0,0 +1,486 @@
+//! Scripted mocks of the hardware traits, for driver tests.
+//!
+//! Each mock is given the operations a driver is expected to carry out, in
+//! order, and what each should answer. An operation that isn't the next one
+//! expected panics naming both, so a test fails at the first byte a driver
+//! gets wrong rather than at some later consequence of it.
+//!
+//! ```ignore
+//! let i2c = MockI2c::new();
+//! i2c.expect_write_read(0x4C, &[0xFE], 1).respond_with([0x5D]);
+//! i2c.expect_write(0x4C, &[0x4C, 0x3F]).inject_error(I2cError::NoAck(0x4C));
+//! // ... drive a clone of `i2c` ...
+//! i2c.done();
+//! ```
+//!
+//! Mocks are cheap handles on a shared script, so a test keeps one while the
+//! driver under test owns another.
+
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use super::gpio::{Gpio, GpioPin, PinMode, PinValue};
+use super::i2c::I2c;
+use super::{HwError, Result};
+
+/// An expected operation, with what it answers.
+struct Expectation<Op, R, E> {
+    op: Op,
+    response: Option<R>,
+    error: Option<E>,
+}
+
+/// Operations still expected, in order.
+struct Script<Op, R, E> {
+    name: &'static str,
+    expected: VecDeque<Expectation<Op, R, E>>,
+}
+
+type SharedScript<Op, R, E> = Arc<Mutex<Script<Op, R, E>>>;
+
+impl<Op: Debug + PartialEq, R, E> Script<Op, R, E> {
+    fn shared(name: &'static str) -> SharedScript<Op, R, E> {
+        Arc::new(Mutex::new(Self {
+            name,
+            expected: VecDeque::new(),
+        }))
+    }
+
+    /// Take the next expectation, which must be for `actual`.
+    fn next(&mut self, actual: &Op) -> Expectation<Op, R, E> {
+        let Some(expectation) = self.expected.pop_front() else {
+            panic!(
+                "{}: unexpected {:?}, nothing more expected",
+                self.name, actual
+            );
+        };
+        assert_eq!(
+            &expectation.op, actual,
+            "{}: expected the left operation, got the right",
+            self.name
+        );
+        expectation
+    }
+
+    fn done(&self) {
+        let remaining: Vec<_> = self.expected.iter().map(|e| &e.op).collect();
+        assert!(
+            remaining.is_empty(),
+            "{}: expected operations never happened: {:?}",
+            self.name,
+            remaining
+        );
+    }
+}
+
+/// Handle on an expectation just added, to say what it answers.
+pub struct Expect<Op, R, E> {
+    script: SharedScript<Op, R, E>,
+    index: usize,
+}
+
+impl<Op, R, E> Expect<Op, R, E> {
+    /// Answer the operation with `response`.
+    pub fn respond_with(self, response: impl Into<R>) {
+        self.script.lock().expected[self.index].response = Some(response.into());
+    }
+
+    /// Fail the operation with `error`.
+    pub fn inject_error(self, error: impl Into<E>) {
+        self.script.lock().expected[self.index].error = Some(error.into());
+    }
+}
+
+fn expect<Op, R, E>(script: &SharedScript<Op, R, E>, op: Op) -> Expect<Op, R, E> {
+    let mut locked = script.lock();
+    locked.expected.push_back(Expectation {
+        op,
+        response: None,
+        error: None,
+    });
+    Expect {
+        script: script.clone(),
+        index: locked.expected.len() - 1,
+    }
+}
+
+/// An I2C transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum I2cOp {
+    Write {
+        addr: u8,
+        data: Vec<u8>,
+    },
+    Read {
+        addr: u8,
+        len: usize,
+    },
+    WriteRead {
+        addr: u8,
+        write: Vec<u8>,
+        len: usize,
+    },
+    SetFrequency(u32),
+}
+
+/// Scripted I2C bus. Reads without a response read zeros.
+#[derive(Clone)]
+pub struct MockI2c {
+    script: SharedScript<I2cOp, Vec<u8>, HwError>,
+}
+
+impl MockI2c {
+    pub fn new() -> Self {
+        Self {
+            script: Script::shared("MockI2c"),
+        }
+    }
+
+    pub fn expect_write(&self, addr: u8, data: &[u8]) -> Expect<I2cOp, Vec<u8>, HwError> {
+        let data = data.to_vec();
+        expect(&self.script, I2cOp::Write { addr, data })
+    }
+
+    pub fn expect_read(&self, addr: u8, len: usize) -> Expect<I2cOp, Vec<u8>, HwError> {
+        expect(&self.script, I2cOp::Read { addr, len })
+    }
+
+    pub fn expect_write_read(
+        &self,
+        addr: u8,
+        write: &[u8],
+        len: usize,
+    ) -> Expect<I2cOp, Vec<u8>, HwError> {
+        let write = write.to_vec();
+        expect(&self.script, I2cOp::WriteRead { addr, write, len })
+    }
+
+    pub fn expect_set_frequency(&self, hz: u32) -> Expect<I2cOp, Vec<u8>, HwError> {
+        expect(&self.script, I2cOp::SetFrequency(hz))
+    }
+
+    /// Assert that every expected transaction happened.
+    pub fn done(&self) {
+        self.script.lock().done();
+    }
+
+    fn transact(&self, op: I2cOp, read: &mut [u8]) -> Result<()> {
+        let expectation = self.script.lock().next(&op);
+        if let Some(error) = expectation.error {
+            return Err(error);
+        }
+        if let Some(response) = expectation.response {
+            assert_eq!(
+                response.len(),
+                read.len(),
+                "MockI2c: response to {:?} is the wrong length",
+                op
+            );
+            read.copy_from_slice(&response);
+        } else {
+            read.fill(0);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl I2c for MockI2c {
+    async fn write(&mut self, addr: u8, data: &[u8]) -> Result<()> {
+        let data = data.to_vec();
+        self.transact(I2cOp::Write { addr, data }, &mut [])
+    }
+
+    async fn read(&mut self, addr: u8, buffer: &mut [u8]) -> Result<()> {
+        let len = buffer.len();
+        self.transact(I2cOp::Read { addr, len }, buffer)
+    }
+
+    async fn write_read(&mut self, addr: u8, write: &[u8], read: &mut [u8]) -> Result<()> {
+        let write = write.to_vec();
+        let len = read.len();
+        self.transact(I2cOp::WriteRead { addr, write, len }, read)
+    }
+
+    async fn set_frequency(&mut self, hz: u32) -> Result<()> {
+        self.transact(I2cOp::SetFrequency(hz), &mut [])
+    }
+}
+
+/// A GPIO pin operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GpioOp {
+    SetMode { pin: u8, mode: PinMode },
+    Write { pin: u8, value: PinValue },
+    Read { pin: u8 },
+}
+
+/// Scripted GPIO controller. One script covers all its pins, so the order
+/// of operations across pins is checked too. Reads without a response read
+/// low.
+#[derive(Clone)]
+pub struct MockGpio {
+    script: SharedScript<GpioOp, PinValue, HwError>,
+}
+
+impl MockGpio {
+    pub fn new() -> Self {
+        Self {
+            script: Script::shared("MockGpio"),
+        }
+    }
+
+    pub fn expect_set_mode(&self, pin: u8, mode: PinMode) -> Expect<GpioOp, PinValue, HwError> {
+        expect(&self.script, GpioOp::SetMode { pin, mode })
+    }
+
+    pub fn expect_write(&self, pin: u8, value: PinValue) -> Expect<GpioOp, PinValue, HwError> {
+        expect(&self.script, GpioOp::Write { pin, value })
+    }
+
+    pub fn expect_read(&self, pin: u8) -> Expect<GpioOp, PinValue, HwError> {
+        expect(&self.script, GpioOp::Read { pin })
+    }
+
+    /// Assert that every expected operation happened.
+    pub fn done(&self) {
+        self.script.lock().done();
+    }
+}
+
+#[async_trait]
+impl Gpio for MockGpio {
+    type Pin = MockGpioPin;
+
+    async fn pin(&mut self, number: u8) -> Result<Self::Pin> {
+        Ok(MockGpioPin {
+            script: self.script.clone(),
+            number,
+        })
+    }
+}
+
+/// A pin of a [`MockGpio`].
+#[derive(Clone)]
+pub struct MockGpioPin {
+    script: SharedScript<GpioOp, PinValue, HwError>,
+    number: u8,
+}
+
+impl MockGpioPin {
+    fn operate(&self, op: GpioOp) -> Result<PinValue> {
+        let expectation = self.script.lock().next(&op);
+        match expectation.error {
+            Some(error) => Err(error),
+            None => Ok(expectation.response.unwrap_or(PinValue::Low)),
+        }
+    }
+}
+
+#[async_trait]
+impl GpioPin for MockGpioPin {
+    async fn set_mode(&mut self, mode: PinMode) -> Result<()> {
+        let pin = self.number;
+        self.operate(GpioOp::SetMode { pin, mode }).map(drop)
+    }
+
+    async fn write(&mut self, value: PinValue) -> Result<()> {
+        let pin = self.number;
+        self.operate(GpioOp::Write { pin, value }).map(drop)
+    }
+
+    async fn read(&mut self) -> Result<PinValue> {
+        let pin = self.number;
+        self.operate(GpioOp::Read { pin })
+    }
+}
+
+/// Bytes a serial stream wrote, as one expected write.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SerialWrite(pub Vec<u8>);
+
+/// Bytes in flight through a [`MockSerial`].
+#[derive(Default)]
+struct SerialBuffers {
+    /// Written bytes not yet matched to an expected write
+    written: Vec<u8>,
+    /// Responses waiting to be read
+    readable: VecDeque<u8>,
+    reader: Option<Waker>,
+}
+
+/// Scripted serial stream. Each expected write is matched once as many
+/// bytes as it holds have been written, however they were split; its
+/// response then becomes readable. Reads with nothing to read wait.
+#[derive(Clone)]
+pub struct MockSerial {
+    script: SharedScript<SerialWrite, Vec<u8>, io::Error>,
+    buffers: Arc<Mutex<SerialBuffers>>,
+}
+
+impl MockSerial {
+    pub fn new() -> Self {
+        Self {
+            script: Script::shared("MockSerial"),
+            buffers: Arc::default(),
+        }
+    }
+
+    pub fn expect_write(&self, data: &[u8]) -> Expect<SerialWrite, Vec<u8>, io::Error> {
+        expect(&self.script, SerialWrite(data.to_vec()))
+    }
+
+    /// Assert that every expected write happened.
+    pub fn done(&self) {
+        self.script.lock().done();
+    }
+}
+
+impl AsyncWrite for MockSerial {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut script = self.script.lock();
+        let mut buffers = self.buffers.lock();
+        buffers.written.extend_from_slice(buf);
+
+        let wanted = match script.expected.front() {
+            Some(expectation) => expectation.op.0.len(),
+            None => buffers.written.len(),
+        };
+        if buffers.written.len() < wanted {
+            return Poll::Ready(Ok(buf.len()));
+        }
+
+        let rest = buffers.written.split_off(wanted);
+        let written = SerialWrite(std::mem::replace(&mut buffers.written, rest));
+        let expectation = script.next(&written);
+        if let Some(error) = expectation.error {
+            return Poll::Ready(Err(error));
+        }
+        if let Some(response) = expectation.response {
+            buffers.readable.extend(response);
+            if let Some(reader) = buffers.reader.take() {
+                reader.wake();
+            }
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncRead for MockSerial {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let mut buffers = self.buffers.lock();
+        if buffers.readable.is_empty() {
+            buffers.reader = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        let count = buf.remaining().min(buffers.readable.len());
+        let bytes: Vec<u8> = buffers.readable.drain(..count).collect();
+        buf.put_slice(&bytes);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+    use crate::hw_trait::I2cError;
+
+    #[tokio::test]
+    async fn test_i2c_answers_in_order() {
+        let i2c = MockI2c::new();
+        i2c.expect_set_frequency(100_000);
+        i2c.expect_read(0x50, 2).respond_with([0x12, 0x34]);
+        i2c.expect_write_read(0x4C, &[0xFE], 1).respond_with([0x5D]);
+        i2c.expect_write(0x4C, &[0x4C, 0x3F])
+            .inject_error(I2cError::NoAck(0x4C));
+
+        let mut bus = i2c.clone();
+        bus.set_frequency(100_000).await.unwrap();
+        let mut data = [0u8; 2];
+        bus.read(0x50, &mut data).await.unwrap();
+        assert_eq!(data, [0x12, 0x34]);
+        let mut id = [0u8; 1];
+        bus.write_read(0x4C, &[0xFE], &mut id).await.unwrap();
+        assert_eq!(id, [0x5D]);
+        assert!(matches!(
+            bus.write(0x4C, &[0x4C, 0x3F]).await,
+            Err(HwError::I2c(I2cError::NoAck(0x4C)))
+        ));
+        i2c.done();
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "expected the left operation")]
+    async fn test_i2c_refuses_unexpected_transaction() {
+        let i2c = MockI2c::new();
+        i2c.expect_write(0x4C, &[0x4C, 0x3F]);
+
+        let _ = i2c.clone().write(0x4C, &[0x4C, 0x20]).await;
+    }
+
+    #[tokio::test]
+    async fn test_gpio_script_spans_pins() {
+        let gpio = MockGpio::new();
+        gpio.expect_set_mode(1, PinMode::Input);
+        gpio.expect_write(0, PinValue::High);
+        gpio.expect_read(1).respond_with(PinValue::High);
+
+        let mut controller = gpio.clone();
+        let mut reset = controller.pin(0).await.unwrap();
+        let mut status = controller.pin(1).await.unwrap();
+        status.set_mode(PinMode::Input).await.unwrap();
+        reset.write(PinValue::High).await.unwrap();
+        assert_eq!(status.read().await.unwrap(), PinValue::High);
+        gpio.done();
+    }
+
+    #[test]
+    #[should_panic(expected = "never happened")]
+    fn test_done_reports_what_was_left() {
+        let gpio = MockGpio::new();
+        gpio.expect_write(0, PinValue::Low);
+        gpio.done();
+    }
+
+    #[tokio::test]
+    async fn test_serial_matches_writes_however_split() {
+        let serial = MockSerial::new();
+        serial
+            .expect_write(&[0x01, 0x02, 0x03])
+            .respond_with([0xAA, 0xBB]);
+
+        let mut stream = serial.clone();
+        stream.write_all(&[0x01]).await.unwrap();
+        stream.write_all(&[0x02, 0x03]).await.unwrap();
+        let mut response = [0u8; 2];
+        stream.read_exact(&mut response).await.unwrap();
+        assert_eq!(response, [0xAA, 0xBB]);
+        serial.done();
+    }
+}
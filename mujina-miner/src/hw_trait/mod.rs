@@ -8,11 +8,19 @@
 pub mod adc;
 pub mod gpio;
 pub mod i2c;
+pub mod i2c_trace;
+pub mod latency;
+#[cfg(all(target_os = "linux", feature = "direct-attach"))]
+pub mod linux;
+#[cfg(test)]
+pub(crate) mod mock;
 
 // Re-export traits
 pub use adc::{Adc, AdcChannel};
 pub use gpio::{Gpio, GpioPin, PinMode, PinValue};
 pub use i2c::{I2c, I2cError};
+pub use i2c_trace::I2cTrace;
+pub use latency::{LatencyHistogram, LatencyStats, TimedI2c};
 
 /// Common error type for hardware operations
 #[derive(Debug, thiserror::Error)]
@@ -0,0 +1,158 @@
+//! Keeping ntime in step with the pool's clock.
+//!
+//! Hardware rolls a job's ntime forward once a second while it works the
+//! job, starting from the job's own ntime, so headers follow the pool's
+//! clock rather than ours. Pools accept ntime only within a window of the
+//! job's (ckpool and most others refuse shares about ten minutes past it),
+//! so rolling stops [`MAX_NTIME_ROLL`] past the job's ntime; a job held
+//! that long is hashed at the last ntime until new work arrives.
+//!
+//! Our clock still matters where it's compared with the pool's: jobs far
+//! from local time are rejected as invalid, and a miner whose clock runs
+//! off will eventually refuse good work. [`ClockDrift`] tracks the offset
+//! between each job's ntime and local time so the skew can be reported and
+//! warned about before it gets that far, and [`check_local_clock`] looks
+//! for a clock that was never set at startup.
+
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tracing::{info, warn};
+
+/// How far past a job's ntime hardware may roll, in seconds.
+pub const MAX_NTIME_ROLL: u32 = 600;
+
+/// Offset from the pool's clock, in seconds, that's warned about.
+const SKEW_WARNING: i64 = 120;
+
+/// How many recent jobs the offset is estimated from.
+const OFFSET_WINDOW: usize = 10;
+
+/// A clock reading earlier than this (2025-01-01) was never set.
+const EARLIEST_PLAUSIBLE_TIME: u64 = 1_735_689_600;
+
+/// The offset between a pool's clock and ours, from its jobs' ntimes.
+///
+/// A job's ntime is when the pool built it, which may be some seconds
+/// before it's sent, so the estimate is the largest offset among recent
+/// jobs: the freshest job's.
+#[derive(Debug, Default)]
+pub struct ClockDrift {
+    offsets: VecDeque<i64>,
+    warned: bool,
+}
+
+impl ClockDrift {
+    /// Note a job with `ntime` arriving at Unix time `now`, warning if our
+    /// clock has drifted far from the pool's.
+    pub fn observe(&mut self, pool: &str, ntime: u32, now: u64) {
+        if self.offsets.len() == OFFSET_WINDOW {
+            self.offsets.pop_front();
+        }
+        self.offsets.push_back(i64::from(ntime) - now as i64);
+
+        let Some(offset) = self.offset() else {
+            return;
+        };
+        if offset.abs() > SKEW_WARNING && !self.warned {
+            self.warned = true;
+            warn!(
+                pool,
+                offset_secs = offset,
+                "Local clock is far from the pool's; check NTP"
+            );
+        } else if offset.abs() <= SKEW_WARNING / 2 && self.warned {
+            self.warned = false;
+            info!(
+                pool,
+                offset_secs = offset,
+                "Local clock back in step with the pool's"
+            );
+        }
+    }
+
+    /// Pool time minus local time, in seconds, once a job has been seen.
+    pub fn offset(&self) -> Option<i64> {
+        self.offsets.iter().copied().max()
+    }
+}
+
+/// Warn if the local clock looks wrong: earlier than it can be, or not
+/// synchronized by NTP as far as the kernel knows.
+pub fn check_local_clock() {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    for problem in clock_problems(now, kernel_synchronized()) {
+        warn!(unix_time = now, "{}", problem);
+    }
+}
+
+fn clock_problems(now: u64, synchronized: Option<bool>) -> Vec<&'static str> {
+    let mut problems = Vec::new();
+    if now < EARLIEST_PLAUSIBLE_TIME {
+        problems.push("Local clock was never set; pools' jobs will be rejected until it is");
+    }
+    if synchronized == Some(false) {
+        problems.push("Local clock isn't synchronized by NTP");
+    }
+    problems
+}
+
+/// Whether the kernel's clock is NTP-synchronized, if it can be asked.
+#[cfg(target_os = "linux")]
+fn kernel_synchronized() -> Option<bool> {
+    use nix::libc;
+
+    // SAFETY: with modes zero, adjtimex only reads the clock state into
+    // the timex it's given; all-zero bytes are a valid one
+    let mut timex: libc::timex = unsafe { std::mem::zeroed() };
+    let state = unsafe { libc::adjtimex(&mut timex) };
+    if state < 0 {
+        return None;
+    }
+    Some(state != libc::TIME_ERROR && timex.status & libc::STA_UNSYNC == 0)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn kernel_synchronized() -> Option<bool> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offset_from_freshest_job() {
+        let mut drift = ClockDrift::default();
+        assert_eq!(drift.offset(), None);
+
+        // Jobs built 20s and 5s before they arrived, by a clock 30s ahead
+        drift.observe("pool", 1_000_010, 1_000_000);
+        drift.observe("pool", 1_000_025, 1_000_000);
+        assert_eq!(drift.offset(), Some(25));
+
+        // The estimate follows the clock once old jobs leave the window
+        for _ in 0..OFFSET_WINDOW {
+            drift.observe("pool", 1_000_000, 1_000_300);
+        }
+        assert_eq!(drift.offset(), Some(-300));
+        assert!(drift.warned);
+        for _ in 0..OFFSET_WINDOW {
+            drift.observe("pool", 1_000_000, 1_000_000);
+        }
+        assert!(!drift.warned);
+    }
+
+    #[test]
+    fn test_clock_problems() {
+        assert!(clock_problems(EARLIEST_PLAUSIBLE_TIME, Some(true)).is_empty());
+        assert!(clock_problems(EARLIEST_PLAUSIBLE_TIME, None).is_empty());
+        assert_eq!(clock_problems(0, Some(true)).len(), 1);
+        assert_eq!(
+            clock_problems(EARLIEST_PLAUSIBLE_TIME, Some(false)).len(),
+            1
+        );
+    }
+}
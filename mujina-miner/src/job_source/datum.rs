@@ -0,0 +1,757 @@
+//! DATUM-style job source: block templates from our own node.
+//!
+//! Ocean's DATUM gateway lets a miner build its own blocks: templates come
+//! from a local Bitcoin node via `getblocktemplate`, and the miner writes
+//! the coinbase rather than taking one from the pool. This source does the
+//! same. It polls the node for templates, builds a coinbase paying the
+//! worker's address, and turns each template into a [`JobTemplate`] whose
+//! extranonce2 rolls inside that coinbase. A share that meets the network
+//! target is assembled into a full block and handed back to the node with
+//! `submitblock`, again and again if the node doesn't answer.
+//!
+//! A pool is configured with a `datum://host:port` URL naming the node's
+//! RPC port; the worker name is the payout address (with an optional
+//! `.rig` suffix) and the password, if any, is the RPC `user:password`.
+//!
+//! The gateway's encrypted link to Ocean itself, over which pool-target
+//! shares are reported and the pool's coinbase outputs are received, isn't
+//! implemented. Until it is, this source mines on its own template the way
+//! the gateway does when the pool can't be reached: shares short of the
+//! network target are counted and dropped.
+
+use std::collections::VecDeque;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use bitcoin::address::NetworkUnchecked;
+use bitcoin::block::{Header, Version};
+use bitcoin::consensus::{deserialize, serialize};
+use bitcoin::hash_types::{BlockHash, TxMerkleNode, Txid};
+use bitcoin::hashes::{sha256d, Hash};
+use bitcoin::pow::CompactTarget;
+use bitcoin::script::{Builder, PushBytesBuf};
+use bitcoin::{Address, Amount, Block, ScriptBuf, Transaction, TxOut, Witness};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::sync::{mpsc, watch, Mutex};
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, info_span, warn, Instrument};
+
+use crate::config::PoolConfig;
+use crate::stratum_v1::RejectReason;
+use crate::supervisor::{Backoff, Supervisor};
+use crate::types::{target_for_share_rate, HashRate, Network, ShareRate};
+
+use super::registry::{self, JobSource, SourceDescriptor, SourceParams};
+use super::stratum_v1::PoolStatus;
+use super::{
+    Extranonce2Range, GeneralPurposeBits, JobTemplate, MerkleRootKind, MerkleRootTemplate, Share,
+    SourceCommand, SourceEvent, VersionTemplate,
+};
+
+/// URL scheme naming a node to take templates from.
+pub const URL_SCHEME: &str = "datum://";
+
+/// How often the node is asked for a template.
+const TEMPLATE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long the same block's job is mined before one with the node's
+/// latest transactions replaces it.
+const JOB_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long an RPC call may take.
+const RPC_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How many times a block is offered to a node that doesn't answer.
+const SUBMIT_BLOCK_ATTEMPTS: u32 = 10;
+
+/// Pause between attempts at submitting a block.
+const SUBMIT_BLOCK_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Extranonce2 bytes in the coinbase; there's no extranonce1 to share.
+const EXTRANONCE2_SIZE: usize = 8;
+
+/// Tag written into the coinbase.
+const COINBASE_TAG: &[u8] = b"/mujina/";
+
+/// Jobs kept for the shares still to come from them.
+const MAX_JOBS: usize = 16;
+
+/// Share rate aimed for when the pool config doesn't set one.
+const DEFAULT_SHARES_PER_MINUTE: f64 = 6.0;
+
+/// Where to reach the node and who to pay.
+#[derive(Clone)]
+pub struct DatumConfig {
+    /// Pool URL, `datum://host:port`
+    pub url: String,
+    /// RPC user and password, if the node wants them
+    pub credentials: Option<(String, String)>,
+    /// Worker name: the payout address, optionally followed by `.rig`
+    pub worker: String,
+}
+
+/// The parts of a `getblocktemplate` result a job is built from.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BlockTemplate {
+    pub version: i32,
+    #[serde(rename = "previousblockhash", deserialize_with = "from_hex")]
+    pub prev_blockhash: BlockHash,
+    pub transactions: Vec<TemplateTransaction>,
+    /// Subsidy plus fees, in satoshis
+    #[serde(rename = "coinbasevalue")]
+    pub coinbase_value: u64,
+    /// Compact network target, hex
+    pub bits: String,
+    #[serde(rename = "curtime")]
+    pub time: u32,
+    pub height: u32,
+    /// Witness commitment output script, hex, if the block has witnesses
+    #[serde(default)]
+    pub default_witness_commitment: Option<String>,
+}
+
+/// A transaction in a block template.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TemplateTransaction {
+    /// Serialized transaction, hex
+    pub data: String,
+    #[serde(deserialize_with = "from_hex")]
+    pub txid: Txid,
+}
+
+/// Coinbase for a template, split around extranonce2.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoinbaseParts {
+    pub coinbase1: Vec<u8>,
+    pub coinbase2: Vec<u8>,
+}
+
+/// Job source mining on templates from our own node.
+pub struct DatumSource {
+    config: DatumConfig,
+    network: Network,
+    payout: ScriptBuf,
+
+    /// Where to send events to scheduler
+    event_tx: mpsc::Sender<SourceEvent>,
+
+    /// Where to receive commands from scheduler
+    command_rx: mpsc::Receiver<SourceCommand>,
+
+    /// Shutdown signal
+    shutdown: CancellationToken,
+
+    client: reqwest::Client,
+
+    /// Jobs issued, newest last
+    jobs: VecDeque<IssuedJob>,
+    next_job_id: u64,
+    /// When the newest job was issued
+    issued_at: Option<Instant>,
+
+    /// Expected hashrate (an estimate, not a measurement)
+    expected_hashrate: HashRate,
+    share_rate: ShareRate,
+
+    /// Node reachability and blocks found, for status reporting
+    status_tx: watch::Sender<PoolStatus>,
+}
+
+/// A job and the template it was built from, for assembling its block.
+#[derive(Debug, Clone)]
+struct IssuedJob {
+    job: JobTemplate,
+    template: BlockTemplate,
+}
+
+impl DatumConfig {
+    /// RPC endpoint of the node.
+    fn rpc_url(&self) -> String {
+        format!("http://{}", pool_name(&self.url))
+    }
+}
+
+impl std::fmt::Debug for DatumConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DatumConfig")
+            .field("url", &self.url)
+            .field(
+                "credentials",
+                &self
+                    .credentials
+                    .as_ref()
+                    .map(|(user, password)| (user, crate::secret::Redacted(password))),
+            )
+            .field("worker", &self.worker)
+            .finish()
+    }
+}
+
+impl BlockTemplate {
+    /// Compact network target.
+    pub fn compact_target(&self) -> Result<CompactTarget> {
+        let bits = u32::from_str_radix(&self.bits, 16)
+            .with_context(|| format!("bad bits '{}' in template", self.bits))?;
+        Ok(CompactTarget::from_consensus(bits))
+    }
+
+    /// Witness commitment output script, if the block has witnesses.
+    fn witness_commitment(&self) -> Result<Option<ScriptBuf>> {
+        self.default_witness_commitment
+            .as_deref()
+            .map(|hex| Ok(ScriptBuf::from_bytes(hex::decode(hex)?)))
+            .transpose()
+    }
+
+    /// Merkle branches from the coinbase up to the root.
+    pub fn merkle_branches(&self) -> Vec<TxMerkleNode> {
+        let mut level: Vec<[u8; 32]> = self
+            .transactions
+            .iter()
+            .map(|tx| tx.txid.to_byte_array())
+            .collect();
+        let mut branches = Vec::new();
+
+        // The coinbase's path is left out of each level: its sibling is the
+        // branch, and the rest pair up among themselves
+        while let Some(&sibling) = level.first() {
+            branches.push(TxMerkleNode::from_byte_array(sibling));
+            level = level[1..]
+                .chunks(2)
+                .map(|pair| {
+                    let mut combined = [0u8; 64];
+                    combined[..32].copy_from_slice(&pair[0]);
+                    combined[32..].copy_from_slice(pair.get(1).unwrap_or(&pair[0]));
+                    sha256d::Hash::hash(&combined).to_byte_array()
+                })
+                .collect();
+        }
+        branches
+    }
+
+    /// Coinbase paying the whole block reward to `payout`.
+    ///
+    /// The input script holds the BIP34 height, [`COINBASE_TAG`], then
+    /// extranonce2. The coinbase is in legacy serialization; the witness
+    /// reserved value is added when a block is assembled.
+    pub fn coinbase(&self, payout: &ScriptBuf) -> Result<CoinbaseParts> {
+        let height = Builder::new().push_int(self.height as i64).into_script();
+        let tag = Builder::new()
+            .push_slice(PushBytesBuf::try_from(COINBASE_TAG.to_vec())?)
+            .into_script();
+        let script_len = height.len() + tag.len() + EXTRANONCE2_SIZE;
+        if script_len > 100 {
+            bail!("coinbase script of {} bytes is too long", script_len);
+        }
+
+        let mut coinbase1 = Vec::new();
+        coinbase1.extend_from_slice(&2i32.to_le_bytes());
+        coinbase1.push(1); // One input
+        coinbase1.extend_from_slice(&[0; 32]);
+        coinbase1.extend_from_slice(&u32::MAX.to_le_bytes());
+        coinbase1.push(script_len as u8);
+        coinbase1.extend_from_slice(height.as_bytes());
+        coinbase1.extend_from_slice(tag.as_bytes());
+
+        let mut outputs = vec![TxOut {
+            value: Amount::from_sat(self.coinbase_value),
+            script_pubkey: payout.clone(),
+        }];
+        if let Some(commitment) = self.witness_commitment()? {
+            outputs.push(TxOut {
+                value: Amount::ZERO,
+                script_pubkey: commitment,
+            });
+        }
+
+        let mut coinbase2 = Vec::new();
+        coinbase2.extend_from_slice(&u32::MAX.to_le_bytes()); // Sequence
+        coinbase2.extend_from_slice(&serialize(&outputs));
+        coinbase2.extend_from_slice(&0u32.to_le_bytes()); // Lock time
+        Ok(CoinbaseParts {
+            coinbase1,
+            coinbase2,
+        })
+    }
+}
+
+impl DatumSource {
+    /// Create a source for `config`, checking its payout address is for
+    /// `network`.
+    pub fn new(
+        config: DatumConfig,
+        network: Network,
+        command_rx: mpsc::Receiver<SourceCommand>,
+        event_tx: mpsc::Sender<SourceEvent>,
+        shutdown: CancellationToken,
+    ) -> Result<Self> {
+        let payout = payout_script(&config.worker, network)?;
+        Ok(Self {
+            config,
+            network,
+            payout,
+            event_tx,
+            command_rx,
+            shutdown,
+            client: reqwest::Client::new(),
+            jobs: VecDeque::new(),
+            next_job_id: 0,
+            issued_at: None,
+            expected_hashrate: HashRate::default(),
+            share_rate: ShareRate::per_minute(DEFAULT_SHARES_PER_MINUTE),
+            status_tx: watch::Sender::new(PoolStatus::default()),
+        })
+    }
+
+    /// Aim for `rate` shares from the scheduler (6 a minute unless set).
+    pub fn with_target_share_rate(mut self, rate: ShareRate) -> Self {
+        self.share_rate = rate;
+        self
+    }
+
+    /// Watch the node's reachability and the blocks found.
+    pub fn status(&self) -> watch::Receiver<PoolStatus> {
+        self.status_tx.subscribe()
+    }
+
+    /// Human-readable name derived from the URL (e.g., "127.0.0.1:8332").
+    pub fn name(&self) -> String {
+        pool_name(&self.config.url)
+    }
+
+    /// Run until shutdown, or until the node can't be reached.
+    pub async fn run(&mut self) -> Result<()> {
+        debug!(node = %self.config.url, "Fetching templates from node");
+        let mut poll = tokio::time::interval(TEMPLATE_POLL_INTERVAL);
+
+        let result = loop {
+            tokio::select! {
+                _ = poll.tick() => {
+                    if let Err(e) = self.poll_template().await {
+                        break Err(e);
+                    }
+                }
+
+                Some(cmd) = self.command_rx.recv() => {
+                    match cmd {
+                        SourceCommand::SubmitShare(share) => self.handle_share(share).await,
+                        SourceCommand::UpdateHashRate(hashrate) => {
+                            self.expected_hashrate = hashrate;
+                        }
+                    }
+                }
+
+                _ = self.shutdown.cancelled() => {
+                    info!(node = %self.name(), "Datum source shutting down");
+                    break Ok(());
+                }
+            }
+        };
+
+        let connected = self.status_tx.borrow().connected;
+        self.status_tx
+            .send_modify(|status| status.connected = false);
+        if result.is_err() && connected {
+            self.jobs.clear();
+            self.issued_at = None;
+            let _ = self.event_tx.send(SourceEvent::ClearJobs).await;
+        }
+        result
+    }
+
+    /// Fetch a template, issuing a job if the tip moved or the current job
+    /// is due for refresh.
+    async fn poll_template(&mut self) -> Result<()> {
+        let template: BlockTemplate = serde_json::from_value(
+            self.rpc("getblocktemplate", json!([{ "rules": ["segwit"] }]))
+                .await?,
+        )
+        .context("unexpected getblocktemplate result")?;
+
+        if !self.status_tx.borrow().connected {
+            info!(node = %self.name(), height = template.height, "Receiving templates from node");
+            self.status_tx.send_modify(|status| status.connected = true);
+        }
+
+        let new_block = self
+            .jobs
+            .back()
+            .is_none_or(|last| last.template.prev_blockhash != template.prev_blockhash);
+        let due = self
+            .issued_at
+            .is_none_or(|at| at.elapsed() >= JOB_REFRESH_INTERVAL);
+        if !(new_block || due) {
+            return Ok(());
+        }
+
+        let job = self.job_from_template(&template)?;
+        debug!(job_id = %job.id, height = template.height, txs = template.transactions.len(), "Issuing job");
+        if new_block {
+            self.jobs.clear();
+        }
+        if self.jobs.len() == MAX_JOBS {
+            self.jobs.pop_front();
+        }
+        self.jobs.push_back(IssuedJob {
+            job: job.clone(),
+            template,
+        });
+        self.issued_at = Some(Instant::now());
+
+        let event = if new_block {
+            SourceEvent::ReplaceJob(job)
+        } else {
+            SourceEvent::UpdateJob(job)
+        };
+        self.event_tx.send(event).await?;
+        Ok(())
+    }
+
+    fn job_from_template(&mut self, template: &BlockTemplate) -> Result<JobTemplate> {
+        let bits = template.compact_target()?;
+        if bitcoin::pow::Target::from(bits) > self.network.max_target() {
+            bail!("template target is easier than {} allows", self.network);
+        }
+        let coinbase = template.coinbase(&self.payout)?;
+
+        // With no pool to authorize a mask, all general purpose bits roll
+        let base = template.version & !0x1fff_e000;
+        let version =
+            VersionTemplate::new(Version::from_consensus(base), GeneralPurposeBits::full())?;
+
+        let id = format!("{:x}", self.next_job_id);
+        self.next_job_id += 1;
+        Ok(JobTemplate {
+            id,
+            prev_blockhash: template.prev_blockhash,
+            version,
+            bits,
+            share_target: target_for_share_rate(self.share_rate, self.expected_hashrate),
+            time: template.time,
+            merkle_root: MerkleRootKind::Computed(MerkleRootTemplate::new(
+                coinbase.coinbase1,
+                Vec::new(),
+                Extranonce2Range::for_pool_size(EXTRANONCE2_SIZE)?,
+                coinbase.coinbase2,
+                template.merkle_branches(),
+            )),
+        })
+    }
+
+    /// Submit `share` as a block if it meets the network target.
+    async fn handle_share(&mut self, share: Share) {
+        let Some(issued) = self.jobs.iter().find(|j| j.job.id == share.job_id) else {
+            debug!(job_id = %share.job_id, "Share for a job no longer held");
+            return;
+        };
+        let block = match assemble_block(issued, &share) {
+            Ok(block) => block,
+            Err(e) => {
+                warn!(job_id = %share.job_id, error = %e, "Failed to assemble block from share");
+                return;
+            }
+        };
+
+        let hash = block.block_hash();
+        if !block.header.target().is_met_by(hash) {
+            debug!(job_id = %share.job_id, nonce = format!("{:#x}", share.nonce), "Share below network target");
+            return;
+        }
+
+        info!(%hash, height = issued.template.height, "Found block, submitting to node");
+        let hex = hex::encode(serialize(&block));
+
+        // Only a node that didn't answer is asked again: one that rejected
+        // the block won't change its mind
+        let mut attempt = 1;
+        let result = loop {
+            match self.rpc("submitblock", json!([hex])).await {
+                Err(e) if attempt < SUBMIT_BLOCK_ATTEMPTS => {
+                    warn!(%hash, attempt, error = %e, "Failed to submit block, retrying");
+                    attempt += 1;
+                    tokio::time::sleep(SUBMIT_BLOCK_RETRY_DELAY).await;
+                }
+                result => break result,
+            }
+        };
+        match result {
+            Ok(Value::Null) => {
+                info!(%hash, "Node accepted block");
+                self.status_tx.send_modify(|status| status.accepted += 1);
+            }
+            Ok(answer) => {
+                // BIP 22 reasons, e.g. "duplicate" or "high-hash"
+                let reason = RejectReason::parse(None, answer.as_str().unwrap_or_default());
+                warn!(%hash, %reason, %answer, "Node rejected block");
+                self.status_tx.send_modify(|status| {
+                    status.rejected += 1;
+                    status.rejections.record(reason);
+                });
+            }
+            Err(e) => {
+                warn!(%hash, error = %e, "Failed to submit block");
+                self.status_tx.send_modify(|status| {
+                    status.rejected += 1;
+                    status.rejections.record(RejectReason::Other);
+                });
+            }
+        }
+    }
+
+    /// Call `method` on the node, returning its result.
+    async fn rpc(&self, method: &str, params: Value) -> Result<Value> {
+        let mut request = self
+            .client
+            .post(self.config.rpc_url())
+            .timeout(RPC_TIMEOUT)
+            .json(&json!({
+                "jsonrpc": "1.0",
+                "id": "mujina",
+                "method": method,
+                "params": params,
+            }));
+        if let Some((user, password)) = &self.config.credentials {
+            request = request.basic_auth(user, Some(password));
+        }
+
+        // The node answers errors with a JSON body and a non-200 status
+        let mut response: Value = request
+            .send()
+            .await
+            .with_context(|| format!("{} failed", method))?
+            .json()
+            .await
+            .with_context(|| format!("{} answered with something other than JSON", method))?;
+        match response.get("error") {
+            Some(Value::Null) | None => Ok(response["result"].take()),
+            Some(error) => Err(anyhow!("{} failed: {}", method, error)),
+        }
+    }
+}
+
+impl JobSource for DatumSource {
+    fn name(&self) -> String {
+        DatumSource::name(self)
+    }
+
+    fn status(&self) -> watch::Receiver<PoolStatus> {
+        DatumSource::status(self)
+    }
+
+    /// Run the source, restarting it with backoff if the node can't be
+    /// reached.
+    fn spawn(self: Box<Self>, group: &Supervisor) {
+        let name = self.name();
+        let source = Arc::new(Mutex::new(*self));
+        group.spawn_restartable("pool", Backoff::default(), move || {
+            let source = source.clone();
+            async move { source.lock().await.run().await }
+                .instrument(info_span!("pool", pool = %name))
+        });
+    }
+}
+
+/// Build the block `share` solves.
+fn assemble_block(issued: &IssuedJob, share: &Share) -> Result<Block> {
+    let MerkleRootKind::Computed(merkle) = &issued.job.merkle_root else {
+        bail!("job has a fixed merkle root");
+    };
+    let en2 = share
+        .extranonce2
+        .ok_or_else(|| anyhow!("share has no extranonce2"))?;
+
+    let mut coinbase_bytes = merkle.coinbase1().to_vec();
+    en2.extend_vec(&mut coinbase_bytes);
+    coinbase_bytes.extend_from_slice(merkle.coinbase2());
+    let mut coinbase: Transaction = deserialize(&coinbase_bytes)?;
+    if issued.template.default_witness_commitment.is_some() {
+        coinbase.input[0].witness = Witness::from_slice(&[[0u8; 32]]);
+    }
+
+    let mut txdata = vec![coinbase];
+    for tx in &issued.template.transactions {
+        txdata.push(deserialize(&hex::decode(&tx.data)?)?);
+    }
+
+    Ok(Block {
+        header: Header {
+            version: share.version,
+            prev_blockhash: issued.job.prev_blockhash,
+            merkle_root: merkle.compute_merkle_root(&en2)?,
+            time: share.time,
+            bits: issued.job.bits,
+            nonce: share.nonce,
+        },
+        txdata,
+    })
+}
+
+/// Output script paying the address in `worker`.
+pub fn payout_script(worker: &str, network: Network) -> Result<ScriptBuf> {
+    let address = worker.split('.').next().unwrap_or_default();
+    let address = Address::<NetworkUnchecked>::from_str(address)
+        .with_context(|| format!("worker '{}' doesn't start with a payout address", worker))?
+        .require_network(network.into())
+        .with_context(|| format!("payout address '{}' isn't for {}", address, network))?;
+    Ok(address.script_pubkey())
+}
+
+/// Deserialize a hash from the hex the node displays it in.
+fn from_hex<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    let hex = String::deserialize(deserializer)?;
+    hex.parse().map_err(serde::de::Error::custom)
+}
+
+/// Address part of a `datum://` URL.
+fn pool_name(url: &str) -> String {
+    url.strip_prefix(URL_SCHEME).unwrap_or(url).to_string()
+}
+
+/// A datum pool pays the worker, so it must be an address.
+fn check(pool: &PoolConfig, network: Network) -> Result<(), String> {
+    registry::check_host_port(&pool.url, &[URL_SCHEME])?;
+    payout_script(&pool.worker, network).map_err(|e| format!("{:#}", e))?;
+    network
+        .check_worker_name(&pool.worker)
+        .map_err(|e| format!("worker '{}' on {}: {}", pool.worker, network, e))
+}
+
+/// The password, if any, is the node's RPC `user:password`.
+fn create(params: SourceParams<'_>) -> Result<Box<dyn JobSource>> {
+    let config = DatumConfig {
+        url: params.pool.url.clone(),
+        credentials: params.password.map(|p| match p.split_once(':') {
+            Some((user, password)) => (user.to_string(), password.to_string()),
+            None => (String::new(), p),
+        }),
+        worker: params.pool.worker.clone(),
+    };
+    let mut source = DatumSource::new(
+        config,
+        params.network,
+        params.command_rx,
+        params.event_tx,
+        params.shutdown,
+    )?;
+    if let Some(rate) = params.pool.shares_per_minute {
+        source = source.with_target_share_rate(ShareRate::per_minute(rate));
+    }
+    Ok(Box::new(source))
+}
+
+// Register this source type with the inventory system
+inventory::submit! {
+    SourceDescriptor {
+        schemes: &[URL_SCHEME],
+        name: "DATUM",
+        check_fn: check,
+        create_fn: create,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::merkle_tree;
+
+    use super::*;
+    use crate::job_source::Extranonce2;
+
+    const PAYOUT: &str = "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080";
+
+    fn template() -> BlockTemplate {
+        let txids = [
+            "9b0fc92260312ce44e74ef369f5c66bbb85848f2eddd5a7a1cde251e54ccfdd5",
+            "e7a74f2c8d7c6d0ac8b1a5b9d0c4e1c2b7a6f5e4d3c2b1a09f8e7d6c5b4a3921",
+            "0f1e2d3c4b5a69788796a5b4c3d2e1f00f1e2d3c4b5a69788796a5b4c3d2e1f0",
+        ];
+        serde_json::from_value(json!({
+            "version": 0x2000_0000,
+            "previousblockhash": "000000000000000000022d4b6f0c3f3dd6e2e0c6e4f1e6b0c5a4e3d2c1b0a998",
+            "transactions": txids.iter().map(|txid| json!({ "data": "", "txid": txid })).collect::<Vec<_>>(),
+            "coinbasevalue": 312_500_000u64,
+            "bits": "17028c61",
+            "curtime": 1_760_486_400u32,
+            "height": 918_000,
+            "default_witness_commitment": "6a24aa21a9ede2f61c3f71d1defd3fa999dfa36953755c690689799962b48bebd836974e8cf9",
+        }))
+        .unwrap()
+    }
+
+    fn payout() -> ScriptBuf {
+        payout_script(PAYOUT, Network::Regtest).unwrap()
+    }
+
+    #[test]
+    fn test_coinbase_pays_payout_and_commits_witnesses() {
+        let template = template();
+        let parts = template.coinbase(&payout()).unwrap();
+
+        let mut bytes = parts.coinbase1.clone();
+        Extranonce2::new(7, EXTRANONCE2_SIZE as u8)
+            .unwrap()
+            .extend_vec(&mut bytes);
+        bytes.extend_from_slice(&parts.coinbase2);
+        let coinbase: Transaction = deserialize(&bytes).unwrap();
+
+        assert!(coinbase.is_coinbase());
+        let script = coinbase.input[0].script_sig.as_bytes();
+        let height = Builder::new().push_int(918_000).into_script();
+        assert!(script.starts_with(height.as_bytes()));
+        assert!(script.ends_with(&7u64.to_le_bytes()));
+
+        assert_eq!(coinbase.output.len(), 2);
+        assert_eq!(coinbase.output[0].value, Amount::from_sat(312_500_000));
+        assert_eq!(coinbase.output[0].script_pubkey, payout());
+        assert_eq!(coinbase.output[1].value, Amount::ZERO);
+        assert_eq!(
+            Some(hex::encode(coinbase.output[1].script_pubkey.as_bytes())),
+            template.default_witness_commitment
+        );
+    }
+
+    #[test]
+    fn test_merkle_root_matches_full_tree() {
+        let template = template();
+        let parts = template.coinbase(&payout()).unwrap();
+        let merkle = MerkleRootTemplate::new(
+            parts.coinbase1.clone(),
+            Vec::new(),
+            Extranonce2Range::for_pool_size(EXTRANONCE2_SIZE).unwrap(),
+            parts.coinbase2.clone(),
+            template.merkle_branches(),
+        );
+
+        for value in [0, 1, u64::MAX] {
+            let en2 = Extranonce2::new(value, EXTRANONCE2_SIZE as u8).unwrap();
+            let mut bytes = parts.coinbase1.clone();
+            en2.extend_vec(&mut bytes);
+            bytes.extend_from_slice(&parts.coinbase2);
+            let coinbase: Transaction = deserialize(&bytes).unwrap();
+
+            let txids = std::iter::once(coinbase.compute_txid())
+                .chain(template.transactions.iter().map(|tx| tx.txid))
+                .map(|txid| txid.to_raw_hash());
+            let expected = merkle_tree::calculate_root(txids).unwrap();
+            assert_eq!(
+                merkle.compute_merkle_root(&en2).unwrap().to_raw_hash(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_payout_must_be_for_network() {
+        assert!(payout_script(&format!("{}.rig1", PAYOUT), Network::Regtest).is_ok());
+        assert!(payout_script(PAYOUT, Network::Mainnet).is_err());
+        assert!(payout_script("not-an-address", Network::Regtest).is_err());
+    }
+}
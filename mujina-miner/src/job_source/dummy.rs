@@ -5,22 +5,36 @@
 //! initialized to the actual winning value. This gives mining hardware a high
 //! probability of finding the real block hash quickly, making it an excellent test
 //! of the complete mining stack.
+//!
+//! The pool manager runs it when no pools are configured; a pool with the
+//! URL `dummy://` runs it too, so it can be picked through the API like any
+//! other source.
 
 use anyhow::Result;
 use bitcoin::block::Version;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch};
 use tokio::time::Duration;
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, info};
 
-use crate::types::{target_for_share_rate, HashRate, ShareRate};
+use crate::config::PoolConfig;
+use crate::supervisor::Supervisor;
+use crate::types::{target_for_share_rate, HashRate, Network, ShareRate};
 
+use super::registry::{JobSource, SourceDescriptor, SourceParams};
+use super::stratum_v1::PoolStatus;
 use super::test_blocks::block_881423;
 use super::{
     Extranonce2Range, GeneralPurposeBits, JobTemplate, MerkleRootKind, MerkleRootTemplate,
     SourceCommand, SourceEvent, VersionTemplate,
 };
 
+/// URL of the dummy source.
+pub const URL: &str = "dummy://";
+
+/// How often a new job is issued.
+const JOB_INTERVAL: Duration = Duration::from_secs(30);
+
 /// Dummy job source that generates work from test block data.
 ///
 /// Emits JobTemplates on a fixed interval using authentic data from block 881,423.
@@ -56,59 +70,11 @@ impl DummySource {
         shutdown: CancellationToken,
         interval: Duration,
     ) -> Result<Self> {
-        // Extract the actual extranonce2 from the winning block
-        let extranonce2_bytes = block_881423::extranonce2_bytes();
-        let extranonce2_actual = u32::from_le_bytes(extranonce2_bytes.try_into().expect("4 bytes"));
-
-        // Create extranonce2 range around the winning value
-        // This gives hardware high probability of hitting the real block hash
-        let extranonce2_range = Extranonce2Range::new_range(
-            extranonce2_actual as u64,
-            extranonce2_actual as u64 + 100, // Small range for quick testing
-            4,                               // 4 bytes
-        )?;
-
-        // Get merkle branches as typed TxMerkleNode
-        let merkle_branches = block_881423::MERKLE_BRANCHES.clone();
-
-        // Use block_881423 version with GP bits (13-28) masked away
-        let v = block_881423::VERSION.to_consensus() as u32;
-        let base_cleaned = (v & !0x1fff_e000) as i32;
-        let version = VersionTemplate::new(
-            Version::from_consensus(base_cleaned),
-            GeneralPurposeBits::full(),
-        )
-        .expect("Masked version has no GP bits set");
-
-        let job_template = JobTemplate {
-            id: "dummy-0".into(),
-            prev_blockhash: *block_881423::PREV_BLOCKHASH,
-            version,
-            bits: *block_881423::BITS,
-
-            // Share difficulty: ~1 share per 10 seconds at 1 TH/s
-            share_target: target_for_share_rate(
-                ShareRate::per_minute(6.0),
-                HashRate::from_terahashes(1.0),
-            ),
-
-            time: block_881423::TIME,
-
-            // Use computed merkle root with authentic coinbase parts
-            merkle_root: MerkleRootKind::Computed(MerkleRootTemplate {
-                coinbase1: block_881423::coinbase1_bytes().to_vec(),
-                extranonce1: block_881423::extranonce1_bytes().to_vec(),
-                extranonce2_range,
-                coinbase2: block_881423::coinbase2_bytes().to_vec(),
-                merkle_branches,
-            }),
-        };
-
         Ok(Self {
             event_tx,
             command_rx,
             shutdown,
-            job_template,
+            job_template: job_template()?,
             interval,
         })
     }
@@ -157,6 +123,108 @@ impl DummySource {
     }
 }
 
+impl JobSource for DummySource {
+    fn name(&self) -> String {
+        "dummy".into()
+    }
+
+    /// Always connected, with nothing accepted or rejected.
+    fn status(&self) -> watch::Receiver<PoolStatus> {
+        let status = PoolStatus {
+            connected: true,
+            ..Default::default()
+        };
+        watch::channel(status).1
+    }
+
+    fn spawn(self: Box<Self>, group: &Supervisor) {
+        group.spawn_critical("dummy-source", self.run());
+    }
+}
+
+/// Build the job template for block 881,423.
+///
+/// Also serves as the fallback job wherever work is needed without a pool,
+/// e.g. for benchmarking.
+pub fn job_template() -> Result<JobTemplate> {
+    // Extract the actual extranonce2 from the winning block
+    let extranonce2_bytes = block_881423::extranonce2_bytes();
+    let extranonce2_actual = u32::from_le_bytes(extranonce2_bytes.try_into().expect("4 bytes"));
+
+    // Create extranonce2 range around the winning value
+    // This gives hardware high probability of hitting the real block hash
+    let extranonce2_range = Extranonce2Range::new_range(
+        extranonce2_actual as u64,
+        extranonce2_actual as u64 + 100, // Small range for quick testing
+        4,                               // 4 bytes
+    )?;
+
+    // Get merkle branches as typed TxMerkleNode
+    let merkle_branches = block_881423::MERKLE_BRANCHES.clone();
+
+    // Use block_881423 version with GP bits (13-28) masked away
+    let v = block_881423::VERSION.to_consensus() as u32;
+    let base_cleaned = (v & !0x1fff_e000) as i32;
+    let version = VersionTemplate::new(
+        Version::from_consensus(base_cleaned),
+        GeneralPurposeBits::full(),
+    )
+    .expect("Masked version has no GP bits set");
+
+    Ok(JobTemplate {
+        id: "dummy-0".into(),
+        prev_blockhash: *block_881423::PREV_BLOCKHASH,
+        version,
+        bits: *block_881423::BITS,
+
+        // Share difficulty: ~1 share per 10 seconds at 1 TH/s
+        share_target: target_for_share_rate(
+            ShareRate::per_minute(6.0),
+            HashRate::from_terahashes(1.0),
+        ),
+
+        time: block_881423::TIME,
+
+        // Use computed merkle root with authentic coinbase parts
+        merkle_root: MerkleRootKind::Computed(MerkleRootTemplate::new(
+            block_881423::coinbase1_bytes().to_vec(),
+            block_881423::extranonce1_bytes().to_vec(),
+            extranonce2_range,
+            block_881423::coinbase2_bytes().to_vec(),
+            merkle_branches,
+        )),
+    })
+}
+
+/// Any worker will do, and the URL names nothing.
+fn check(pool: &PoolConfig, _network: Network) -> Result<(), String> {
+    if pool.url == URL {
+        Ok(())
+    } else {
+        Err(format!("URL '{}' isn't {}", pool.url, URL))
+    }
+}
+
+fn create(params: SourceParams<'_>) -> Result<Box<dyn JobSource>> {
+    let source = DummySource::new(
+        params.command_rx,
+        params.event_tx,
+        params.shutdown,
+        JOB_INTERVAL,
+    )?;
+    Ok(Box::new(source))
+}
+
+// Register this source type with the inventory system
+inventory::submit! {
+    SourceDescriptor {
+        schemes: &[URL],
+        name: "Dummy",
+        check_fn: check,
+        create_fn: create,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
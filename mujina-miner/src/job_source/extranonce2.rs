@@ -7,16 +7,19 @@
 //! - **Extranonce2** (variable size) - Typically rolled by software
 //! - **nTime** (32 bits) - Typically rolled by software
 //!
-//! This module provides three types for managing the extranonce2 dimension:
+//! This module provides four types for managing the extranonce2 dimension:
 //!
 //! - `Extranonce2`: An immutable value with a specific size (1-8 bytes)
 //! - `Extranonce2Range`: A range specification [min, max] with no position state
 //! - `Extranonce2Iter`: An iterator that generates `Extranonce2` values from a range
+//! - `Extranonce2Allocator`: Hands out disjoint slices of a range on demand
 //!
 //! Mining pools allocate a specific byte size for extranonce2 (typically 4-8 bytes),
 //! which determines how many unique coinbase transactions a miner can generate before
 //! needing new work. The range type provides splitting for dividing work between
-//! domains, while the iterator type handles sequential value generation.
+//! domains, while the iterator type handles sequential value generation. The
+//! allocator lets the scheduler give each hash thread its own slice of one
+//! pool connection's space, including threads that arrive after the job.
 
 use std::fmt;
 
@@ -76,6 +79,12 @@ impl Extranonce2 {
         self.size
     }
 
+    /// The next value of the same size, or None if this is the largest.
+    pub fn checked_increment(&self) -> Option<Self> {
+        let value = self.value.checked_add(1)?;
+        Self::new(value, self.size).ok()
+    }
+
     /// Get the maximum value for a given size.
     fn max_for_size(size: u8) -> u64 {
         if size >= 8 {
@@ -150,6 +159,25 @@ impl Extranonce2Range {
         Ok(Self { min, max, size })
     }
 
+    /// Create a range covering the full space for a size given by a pool.
+    ///
+    /// Pools send the size as an arbitrary integer in `mining.subscribe`;
+    /// anything outside 1-8 bytes is rejected rather than truncated.
+    pub fn for_pool_size(size: usize) -> Result<Self, Extranonce2Error> {
+        let size = u8::try_from(size).unwrap_or(u8::MAX);
+        Self::new(size)
+    }
+
+    /// Check whether `en2` lies in this range and has the range's size.
+    pub fn contains(&self, en2: &Extranonce2) -> bool {
+        en2.size == self.size && (self.min..=self.max).contains(&en2.value)
+    }
+
+    /// Check whether this range shares any value with `other`.
+    pub fn overlaps(&self, other: &Extranonce2Range) -> bool {
+        self.size == other.size && self.min <= other.max && other.min <= self.max
+    }
+
     /// Get the total number of values in the range.
     ///
     /// Returns `u64::MAX` if the range spans the entire u64 space (the true
@@ -174,42 +202,122 @@ impl Extranonce2Range {
         if n == 0 {
             return None;
         }
+        (0..n).map(|i| self.partition(i, n)).collect()
+    }
 
-        if n == 1 {
-            return Some(vec![self.clone()]);
+    /// Sub-range `index` of this range divided into `count` parts.
+    ///
+    /// Matches the corresponding element of `split(count)` without building
+    /// the others. Returns `None` if `index` is out of bounds or the range
+    /// has fewer than `count` values.
+    pub fn partition(&self, index: usize, count: usize) -> Option<Extranonce2Range> {
+        let (index, count) = (index as u64, count as u64);
+        if index >= count || self.len() < count {
+            return None;
         }
 
-        let total = self.len();
-        if (total as usize) < n {
-            return None;
+        // Distribute the remainder among the first few parts. len() saturates
+        // for the full u64 range, which leaves its very last value unused.
+        let chunk_size = self.len() / count;
+        let remainder = self.len() % count;
+        let start = self.min + index * chunk_size + index.min(remainder);
+        let size = chunk_size + u64::from(index < remainder);
+
+        Some(
+            Self::new_range(start, start + (size - 1), self.size)
+                .expect("sub-range should be valid"),
+        )
+    }
+
+    /// Create an iterator over this range.
+    pub fn iter(&self) -> Extranonce2Iter {
+        Extranonce2Iter {
+            range: self.clone(),
+            current: self.min,
         }
+    }
+}
 
-        let chunk_size = total / (n as u64);
-        let remainder = total % (n as u64);
+/// Allocator that hands out disjoint slices of an extranonce2 range.
+///
+/// Slices are taken from the front of the range in order, so no two
+/// allocations ever overlap. Unsearched parts of slices can be handed back
+/// with [`release`](Self::release) and are allocated again once the front
+/// is used up. Once everything is used up, further allocations fail until
+/// the job (and with it the range) is replaced.
+#[derive(Debug, Clone)]
+pub struct Extranonce2Allocator {
+    range: Extranonce2Range,
+    /// Start of the unallocated remainder; None when exhausted
+    next: Option<u64>,
+    /// Slices handed back, allocated after the remainder
+    released: Vec<Extranonce2Range>,
+}
 
-        let mut ranges = Vec::with_capacity(n);
-        let mut start = self.min;
+impl Extranonce2Allocator {
+    /// Create an allocator over `range`.
+    pub fn new(range: Extranonce2Range) -> Self {
+        let next = (!range.is_empty()).then_some(range.min);
+        Self {
+            range,
+            next,
+            released: Vec::new(),
+        }
+    }
 
-        for i in 0..n {
-            // Distribute remainder among first few chunks
-            let size = chunk_size + if (i as u64) < remainder { 1 } else { 0 };
-            let end = start + size - 1;
+    /// Allocate up to `len` values. The last slice may be shorter.
+    ///
+    /// Returns `None` once the range is exhausted or if `len` is zero.
+    pub fn allocate(&mut self, len: u64) -> Option<Extranonce2Range> {
+        if len == 0 {
+            return None;
+        }
+        let Some(start) = self.next else {
+            return self.allocate_released(len);
+        };
+
+        let end = start.saturating_add(len - 1).min(self.range.max);
+        self.next = end.checked_add(1).filter(|&n| n <= self.range.max);
 
-            ranges.push(Self::new_range(start, end, self.size).expect("sub-range should be valid"));
+        Some(
+            Extranonce2Range::new_range(start, end, self.range.size)
+                .expect("slice of a valid range"),
+        )
+    }
 
-            start = end + 1;
+    /// Allocate up to `len` values from the front of a released slice.
+    fn allocate_released(&mut self, len: u64) -> Option<Extranonce2Range> {
+        let slice = self.released.last_mut()?;
+        let end = slice.min.saturating_add(len - 1);
+        if end >= slice.max {
+            return self.released.pop();
         }
 
-        Some(ranges)
+        let allocated = Extranonce2Range::new_range(slice.min, end, slice.size)
+            .expect("slice of a valid range");
+        slice.min = end + 1;
+        Some(allocated)
     }
 
-    /// Create an iterator over this range.
-    pub fn iter(&self) -> Extranonce2Iter {
-        Extranonce2Iter {
-            range: self.clone(),
-            current: self.min,
+    /// Hand back an allocated slice that won't be searched.
+    ///
+    /// The caller must make sure nothing searches it any more.
+    pub fn release(&mut self, slice: Extranonce2Range) {
+        if !slice.is_empty() && slice.size == self.range.size {
+            self.released.push(slice);
         }
     }
+
+    /// Number of values not yet allocated.
+    pub fn remaining(&self) -> u64 {
+        let front = match self.next {
+            Some(next) => (self.range.max - next).saturating_add(1),
+            None => 0,
+        };
+        self.released
+            .iter()
+            .fold(front, |total, slice| total.saturating_add(slice.len()))
+    }
 }
 
 /// Iterator that generates `Extranonce2` values from a range.
@@ -366,6 +474,123 @@ mod tests {
         assert_eq!(splits[2].len(), 3);
     }
 
+    #[test]
+    fn test_range_partition_matches_split() {
+        let range = Extranonce2Range::new_range(1000, 1010, 2).unwrap();
+        let splits = range.split(4).unwrap();
+        for (i, split) in splits.iter().enumerate() {
+            assert_eq!(range.partition(i, 4).as_ref(), Some(split));
+        }
+        assert_eq!(range.partition(4, 4), None);
+        assert_eq!(range.split(0), None);
+
+        // Too small to give every part a value
+        assert_eq!(range.split(12), None);
+
+        // Parts of the full 8-byte space don't overlap
+        let full = Extranonce2Range::new(8).unwrap();
+        let a = full.partition(0, 3).unwrap();
+        let b = full.partition(1, 3).unwrap();
+        let c = full.partition(2, 3).unwrap();
+        assert_eq!(a.min, 0);
+        assert_eq!(b.min, a.max + 1);
+        assert_eq!(c.min, b.max + 1);
+    }
+
+    #[test]
+    fn test_checked_increment() {
+        let en2 = Extranonce2::new(0xfe, 1).unwrap();
+        let next = en2.checked_increment().unwrap();
+        assert_eq!(next.value(), 0xff);
+        assert_eq!(next.size(), 1);
+        assert_eq!(next.checked_increment(), None);
+
+        let max = Extranonce2::new(u64::MAX, 8).unwrap();
+        assert_eq!(max.checked_increment(), None);
+    }
+
+    #[test]
+    fn test_range_contains() {
+        let range = Extranonce2Range::new_range(10, 20, 4).unwrap();
+        assert!(range.contains(&Extranonce2::new(10, 4).unwrap()));
+        assert!(range.contains(&Extranonce2::new(20, 4).unwrap()));
+        assert!(!range.contains(&Extranonce2::new(21, 4).unwrap()));
+        assert!(!range.contains(&Extranonce2::new(15, 8).unwrap()));
+    }
+
+    #[test]
+    fn test_range_overlaps() {
+        let a = Extranonce2Range::new_range(0, 9, 1).unwrap();
+        let b = Extranonce2Range::new_range(9, 20, 1).unwrap();
+        let c = Extranonce2Range::new_range(10, 20, 1).unwrap();
+        assert!(a.overlaps(&b) && b.overlaps(&a));
+        assert!(!a.overlaps(&c) && !c.overlaps(&a));
+        assert!(a.overlaps(&a));
+
+        // Values of different sizes are different extranonce2s
+        let wide = Extranonce2Range::new_range(0, 9, 2).unwrap();
+        assert!(!a.overlaps(&wide));
+    }
+
+    #[test]
+    fn test_range_for_pool_size() {
+        assert_eq!(Extranonce2Range::for_pool_size(4).unwrap().max, 0xffff_ffff);
+        assert_eq!(
+            Extranonce2Range::for_pool_size(0),
+            Err(Extranonce2Error::InvalidSize(0))
+        );
+        // Not truncated to 4 bytes
+        assert!(Extranonce2Range::for_pool_size(260).is_err());
+    }
+
+    #[test]
+    fn test_allocator_hands_out_disjoint_slices() {
+        let range = Extranonce2Range::new_range(0, 9, 1).unwrap();
+        let mut allocator = Extranonce2Allocator::new(range);
+
+        let a = allocator.allocate(4).unwrap();
+        let b = allocator.allocate(4).unwrap();
+        assert_eq!((a.min, a.max), (0, 3));
+        assert_eq!((b.min, b.max), (4, 7));
+        assert_eq!(allocator.remaining(), 2);
+
+        // Last slice is short
+        let c = allocator.allocate(4).unwrap();
+        assert_eq!((c.min, c.max), (8, 9));
+        assert_eq!(allocator.remaining(), 0);
+        assert_eq!(allocator.allocate(1), None);
+    }
+
+    #[test]
+    fn test_allocator_full_space() {
+        let mut allocator = Extranonce2Allocator::new(Extranonce2Range::new(8).unwrap());
+        let slice = allocator.allocate(u64::MAX).unwrap();
+        assert_eq!((slice.min, slice.max), (0, u64::MAX - 1));
+        let last = allocator.allocate(u64::MAX).unwrap();
+        assert_eq!((last.min, last.max), (u64::MAX, u64::MAX));
+        assert_eq!(allocator.allocate(1), None);
+    }
+
+    #[test]
+    fn test_allocator_reuses_released_slices() {
+        let range = Extranonce2Range::new_range(0, 9, 1).unwrap();
+        let mut allocator = Extranonce2Allocator::new(range);
+
+        let a = allocator.allocate(8).unwrap();
+        assert_eq!((a.min, a.max), (0, 7));
+        allocator.release(Extranonce2Range::new_range(4, 7, 1).unwrap());
+        assert_eq!(allocator.remaining(), 6);
+
+        // The untouched front goes first, then the released slice
+        let b = allocator.allocate(3).unwrap();
+        assert_eq!((b.min, b.max), (8, 9));
+        let c = allocator.allocate(3).unwrap();
+        assert_eq!((c.min, c.max), (4, 6));
+        let d = allocator.allocate(3).unwrap();
+        assert_eq!((d.min, d.max), (7, 7));
+        assert_eq!(allocator.allocate(1), None);
+    }
+
     // Extranonce2Iter tests
     #[test]
     fn test_iter_basic() {
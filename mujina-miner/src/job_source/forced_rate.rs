@@ -23,6 +23,7 @@ use super::{JobTemplate, SourceCommand, SourceEvent};
 use crate::types::{target_for_share_rate, Difficulty, HashRate, ShareRate};
 
 /// Configuration for forced share rate wrapper.
+#[derive(Debug, Clone)]
 pub struct ForcedRateConfig {
     /// Target share rate (shares per minute)
     pub target_rate: ShareRate,
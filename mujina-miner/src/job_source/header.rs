@@ -0,0 +1,101 @@
+//! Headers with everything but the hardware's rolling filled in.
+
+use bitcoin::block::{Header as BlockHeader, Version};
+use bitcoin::hash_types::{BlockHash, TxMerkleNode};
+use bitcoin::pow::CompactTarget;
+
+use super::{Extranonce2, JobTemplate, MerkleRootKind, VersionTemplate};
+
+/// A block header with its extranonce2 chosen and merkle root computed.
+///
+/// The second level of the template hierarchy (see the [module
+/// docs](super)): hardware rolls the nonce, and the version bits the
+/// template allows, under a header template. Computing the merkle root is
+/// the expensive part of making one, so a template is made once per
+/// extranonce2 and rolled to later times with [`HeaderTemplate::with_ntime`].
+#[derive(Debug, Clone)]
+pub struct HeaderTemplate {
+    /// Block version, with the bits the hardware may roll
+    pub version: VersionTemplate,
+
+    /// Previous block hash
+    pub prev_blockhash: BlockHash,
+
+    /// Merkle root for `extranonce2`
+    pub merkle_root: TxMerkleNode,
+
+    /// Encoded network target
+    pub bits: CompactTarget,
+
+    /// Block timestamp
+    pub ntime: u32,
+
+    /// Extranonce2 the merkle root was computed for; None if the template's
+    /// root is fixed
+    pub extranonce2: Option<Extranonce2>,
+}
+
+impl HeaderTemplate {
+    /// Instantiate `template` with `extranonce2` at `ntime`.
+    ///
+    /// Returns None if the template's merkle root depends on extranonce2
+    /// and none is given, or the root can't be computed.
+    pub fn new(
+        template: &JobTemplate,
+        extranonce2: Option<Extranonce2>,
+        ntime: u32,
+    ) -> Option<Self> {
+        let merkle_root = match &template.merkle_root {
+            MerkleRootKind::Fixed(root) => *root,
+            MerkleRootKind::Computed(merkle) => {
+                merkle.compute_merkle_root(extranonce2.as_ref()?).ok()?
+            }
+        };
+        Some(Self {
+            version: template.version.clone(),
+            prev_blockhash: template.prev_blockhash,
+            merkle_root,
+            bits: template.bits,
+            ntime,
+            extranonce2,
+        })
+    }
+
+    /// The same header at another time, without recomputing anything.
+    pub fn with_ntime(&self, ntime: u32) -> Self {
+        Self {
+            ntime,
+            ..self.clone()
+        }
+    }
+
+    /// The full header for a `version` and `nonce` the hardware reports.
+    pub fn header(&self, version: Version, nonce: u32) -> BlockHeader {
+        BlockHeader {
+            version,
+            prev_blockhash: self.prev_blockhash,
+            merkle_root: self.merkle_root,
+            time: self.ntime,
+            bits: self.bits,
+            nonce,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job_source::{dummy, test_blocks::block_881423};
+
+    #[test]
+    fn test_instantiates_winning_header() {
+        let template = dummy::job_template().unwrap();
+        let header = HeaderTemplate::new(&template, Some(*block_881423::EXTRANONCE2), 0).unwrap();
+        assert_eq!(header.merkle_root, *block_881423::MERKLE_ROOT);
+        assert!(HeaderTemplate::new(&template, None, 0).is_none());
+
+        let header = header.with_ntime(block_881423::TIME);
+        let block = header.header(*block_881423::VERSION, block_881423::NONCE);
+        assert_eq!(block.block_hash(), *block_881423::BLOCK_HASH);
+    }
+}
@@ -4,6 +4,7 @@ use bitcoin::block::Version;
 use bitcoin::hash_types::BlockHash;
 use bitcoin::pow::{CompactTarget, Target};
 
+use super::clock::MAX_NTIME_ROLL;
 use super::{Extranonce2, MerkleRootKind, VersionTemplate};
 
 /// Template for mining jobs from any source.
@@ -50,6 +51,11 @@ impl JobTemplate {
         Target::from(self.bits)
     }
 
+    /// The latest ntime hardware may roll this job to.
+    pub fn max_ntime(&self) -> u32 {
+        self.time.saturating_add(MAX_NTIME_ROLL)
+    }
+
     /// Compute merkle root for the given extranonce2.
     ///
     /// Returns an error if this is a fixed merkle root (header-only job)
@@ -1,9 +1,11 @@
 //! Merkle root specification for mining jobs.
 
-use anyhow::Result;
-use bitcoin::consensus::deserialize;
+use std::fmt;
+
+use anyhow::{bail, Result};
+use bitcoin::consensus::{deserialize, serialize};
 use bitcoin::hash_types::TxMerkleNode;
-use bitcoin::hashes::{sha256d, Hash};
+use bitcoin::hashes::{sha256, sha256d, Hash, HashEngine};
 use bitcoin::Transaction;
 
 use super::{Extranonce2, Extranonce2Range};
@@ -38,65 +40,205 @@ pub enum MerkleRootKind {
 /// its merkle root. As extranonce2 is rolled, each unique value produces a different
 /// coinbase transaction hash, which propagates up the merkle tree to produce a
 /// different merkle root.
-#[derive(Debug, Clone)]
+///
+/// Everything before extranonce2 in the coinbase is the same for every root, so
+/// the SHA-256 state after hashing it (the "midstate") is computed once at
+/// construction. Each root then hashes only extranonce2, the rest of the
+/// coinbase, and one block per merkle branch. The parts are read-only so the
+/// cached state can't go stale.
+#[derive(Clone)]
 pub struct MerkleRootTemplate {
     /// First part of coinbase transaction (before extranonces).
-    pub coinbase1: Vec<u8>,
+    coinbase1: Vec<u8>,
 
     /// Extranonce1 value assigned by the source.
     ///
     /// This is set once per connection and tends to remain constant for all jobs from
     /// this source.
-    pub extranonce1: Vec<u8>,
+    extranonce1: Vec<u8>,
+
+    /// Extranonce2 range defining the available rolling space.
+    extranonce2_range: Extranonce2Range,
+
+    /// Second part of coinbase transaction (after extranonces).
+    coinbase2: Vec<u8>,
+
+    /// Merkle branches for building the merkle root.
+    merkle_branches: Vec<TxMerkleNode>,
+
+    /// Cached txid hashing state; None if the coinbase doesn't parse.
+    midstate: Option<Box<CoinbaseMidstate>>,
+}
+
+/// Txid hashing state of a coinbase, split at extranonce2.
+///
+/// The txid covers the legacy serialization, so for a coinbase in segwit
+/// serialization the marker, flag, and witness are left out.
+#[derive(Clone)]
+struct CoinbaseMidstate {
+    /// SHA-256 state after everything before extranonce2
+    engine: sha256::HashEngine,
+
+    /// Everything after extranonce2
+    suffix: Vec<u8>,
+}
+
+impl MerkleRootTemplate {
+    /// Create a template, hashing the coinbase up to extranonce2.
+    pub fn new(
+        coinbase1: Vec<u8>,
+        extranonce1: Vec<u8>,
+        extranonce2_range: Extranonce2Range,
+        coinbase2: Vec<u8>,
+        merkle_branches: Vec<TxMerkleNode>,
+    ) -> Self {
+        let mut template = Self {
+            coinbase1,
+            extranonce1,
+            extranonce2_range,
+            coinbase2,
+            merkle_branches,
+            midstate: None,
+        };
+        template.midstate = template.coinbase_midstate();
+        template
+    }
+
+    /// First part of coinbase transaction (before extranonces).
+    pub fn coinbase1(&self) -> &[u8] {
+        &self.coinbase1
+    }
+
+    /// Extranonce1 value assigned by the source.
+    pub fn extranonce1(&self) -> &[u8] {
+        &self.extranonce1
+    }
 
     /// Extranonce2 range defining the available rolling space.
     ///
     /// The caller will create an iterator from this range to generate different
     /// extranonce2 values for unique block headers.
-    pub extranonce2_range: Extranonce2Range,
+    pub fn extranonce2_range(&self) -> &Extranonce2Range {
+        &self.extranonce2_range
+    }
 
     /// Second part of coinbase transaction (after extranonces).
-    pub coinbase2: Vec<u8>,
+    pub fn coinbase2(&self) -> &[u8] {
+        &self.coinbase2
+    }
 
     /// Merkle branches for building the merkle root.
     ///
     /// After hashing the coinbase transaction, these branches are used to climb
     /// the merkle tree to compute the final merkle root for the block header.
-    pub merkle_branches: Vec<TxMerkleNode>,
-}
+    pub fn merkle_branches(&self) -> &[TxMerkleNode] {
+        &self.merkle_branches
+    }
 
-impl MerkleRootTemplate {
     /// Compute merkle root for a specific extranonce2 value.
     ///
-    /// Builds the complete coinbase transaction by concatenating parts with the
-    /// given extranonce2, computes its txid, then climbs the merkle tree using
-    /// the branches to produce the final merkle root.
+    /// Finishes the coinbase txid from the cached midstate, then climbs the
+    /// merkle tree using the branches to produce the final merkle root.
     ///
     /// This is a pure function - it doesn't modify the template. Callers manage
     /// extranonce2 iteration externally via `Extranonce2Iter`.
     pub fn compute_merkle_root(&self, extranonce2: &Extranonce2) -> Result<TxMerkleNode> {
-        // Build complete coinbase transaction
+        if extranonce2.size() != self.extranonce2_range.size {
+            bail!(
+                "extranonce2 is {} bytes, coinbase expects {}",
+                extranonce2.size(),
+                self.extranonce2_range.size
+            );
+        }
+
+        let mut current_hash = match &self.midstate {
+            Some(midstate) => {
+                let mut en2_bytes = Vec::with_capacity(extranonce2.size() as usize);
+                extranonce2.extend_vec(&mut en2_bytes);
+
+                let mut engine = midstate.engine.clone();
+                engine.input(&en2_bytes);
+                engine.input(&midstate.suffix);
+                sha256d::Hash::from_engine(engine).to_byte_array()
+            }
+            // Doesn't parse; let the full parse report why
+            None => self
+                .coinbase_tx(extranonce2)?
+                .compute_txid()
+                .to_byte_array(),
+        };
+
+        // Climb the merkle tree
+        let mut combined = [0u8; 64];
+        for branch in &self.merkle_branches {
+            combined[..32].copy_from_slice(&current_hash);
+            combined[32..].copy_from_slice(branch.as_byte_array());
+            current_hash = sha256d::Hash::hash(&combined).to_byte_array();
+        }
+
+        Ok(TxMerkleNode::from_byte_array(current_hash))
+    }
+
+    /// Build and parse the complete coinbase transaction.
+    fn coinbase_tx(&self, extranonce2: &Extranonce2) -> Result<Transaction> {
         let mut coinbase_bytes = Vec::new();
         coinbase_bytes.extend_from_slice(&self.coinbase1);
         coinbase_bytes.extend_from_slice(&self.extranonce1);
         extranonce2.extend_vec(&mut coinbase_bytes);
         coinbase_bytes.extend_from_slice(&self.coinbase2);
 
-        // Parse and compute coinbase txid
-        let coinbase_tx: Transaction = deserialize(&coinbase_bytes)?;
-        let mut current_hash = coinbase_tx.compute_txid().to_byte_array();
+        Ok(deserialize(&coinbase_bytes)?)
+    }
 
-        // Climb the merkle tree
-        for branch in &self.merkle_branches {
-            let mut combined = Vec::new();
-            combined.extend_from_slice(&current_hash);
-            combined.extend_from_slice(branch.as_byte_array());
+    /// Split the coinbase's legacy serialization at extranonce2 and hash the
+    /// part before it.
+    fn coinbase_midstate(&self) -> Option<Box<CoinbaseMidstate>> {
+        let sample =
+            Extranonce2::new(self.extranonce2_range.min, self.extranonce2_range.size).ok()?;
+        let mut tx = self.coinbase_tx(&sample).ok()?;
 
-            let parent_hash = sha256d::Hash::hash(&combined);
-            current_hash = parent_hash.to_byte_array();
+        // Segwit serialization puts a marker and flag after the 4-byte version
+        let mut prefix = if tx.input.iter().any(|input| !input.witness.is_empty()) {
+            let (version, rest) = self.coinbase1.split_at_checked(4)?;
+            [version, rest.get(2..)?].concat()
+        } else {
+            self.coinbase1.clone()
+        };
+        prefix.extend_from_slice(&self.extranonce1);
+
+        for input in &mut tx.input {
+            input.witness.clear();
         }
+        let legacy = serialize(&tx);
 
-        Ok(TxMerkleNode::from_byte_array(current_hash))
+        // Extranonce2 must sit at the same place in the legacy serialization
+        let mut sample_bytes = Vec::new();
+        sample.extend_vec(&mut sample_bytes);
+        let suffix_start = prefix.len() + sample_bytes.len();
+        if legacy.get(..prefix.len())? != prefix.as_slice()
+            || legacy.get(prefix.len()..suffix_start)? != sample_bytes.as_slice()
+        {
+            return None;
+        }
+
+        let mut engine = sha256d::Hash::engine();
+        engine.input(&prefix);
+        Some(Box::new(CoinbaseMidstate {
+            engine,
+            suffix: legacy[suffix_start..].to_vec(),
+        }))
+    }
+}
+
+impl fmt::Debug for MerkleRootTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MerkleRootTemplate")
+            .field("coinbase1", &hex::encode(&self.coinbase1))
+            .field("extranonce1", &hex::encode(&self.extranonce1))
+            .field("extranonce2_range", &self.extranonce2_range)
+            .field("coinbase2", &hex::encode(&self.coinbase2))
+            .field("merkle_branches", &self.merkle_branches)
+            .finish()
     }
 }
 
@@ -105,18 +247,37 @@ mod tests {
     use super::*;
     use crate::job_source::test_blocks::block_881423;
 
+    fn template_881423(extranonce2_range: Extranonce2Range) -> MerkleRootTemplate {
+        MerkleRootTemplate::new(
+            block_881423::coinbase1_bytes().to_vec(),
+            block_881423::extranonce1_bytes().to_vec(),
+            extranonce2_range,
+            block_881423::coinbase2_bytes().to_vec(),
+            block_881423::MERKLE_BRANCHES.clone(),
+        )
+    }
+
+    /// Merkle root by parsing the whole coinbase, as a reference.
+    fn reference_root(template: &MerkleRootTemplate, extranonce2: &Extranonce2) -> TxMerkleNode {
+        let mut hash = template
+            .coinbase_tx(extranonce2)
+            .unwrap()
+            .compute_txid()
+            .to_byte_array();
+        for branch in template.merkle_branches() {
+            let mut combined = hash.to_vec();
+            combined.extend_from_slice(branch.as_byte_array());
+            hash = sha256d::Hash::hash(&combined).to_byte_array();
+        }
+        TxMerkleNode::from_byte_array(hash)
+    }
+
     #[test]
     fn test_compute_merkle_root_with_block_881423() {
         let extranonce2 = *block_881423::EXTRANONCE2;
 
         // Construct a template from golden values
-        let template = MerkleRootTemplate {
-            coinbase1: block_881423::coinbase1_bytes().to_vec(),
-            extranonce1: block_881423::extranonce1_bytes().to_vec(),
-            extranonce2_range: Extranonce2Range::new(extranonce2.size()).unwrap(),
-            coinbase2: block_881423::coinbase2_bytes().to_vec(),
-            merkle_branches: block_881423::MERKLE_BRANCHES.clone(),
-        };
+        let template = template_881423(Extranonce2Range::new(extranonce2.size()).unwrap());
 
         // Compute merkle root
         let computed_merkle_root = template
@@ -130,4 +291,64 @@ mod tests {
             "Computed merkle root doesn't match block 881,423"
         );
     }
+
+    #[test]
+    fn test_midstate_matches_full_coinbase_parse() {
+        let template = template_881423(Extranonce2Range::new_range(0, 100, 4).unwrap());
+        assert!(template.midstate.is_some());
+
+        for en2 in template.extranonce2_range().iter().step_by(7) {
+            assert_eq!(
+                template.compute_merkle_root(&en2).unwrap(),
+                reference_root(&template, &en2)
+            );
+        }
+    }
+
+    #[test]
+    fn test_legacy_serialized_coinbase() {
+        let extranonce2 = *block_881423::EXTRANONCE2;
+
+        // Block 881,423's coinbase without segwit marker, flag, and witness:
+        // same txid, so same merkle root
+        let coinbase1 = block_881423::coinbase1_bytes();
+        let legacy_coinbase1 = [&coinbase1[..4], &coinbase1[6..]].concat();
+        let coinbase2 = block_881423::coinbase2_bytes();
+        let (outputs, witness_and_locktime) = coinbase2.split_at(coinbase2.len() - 38);
+        let legacy_coinbase2 = [outputs, &witness_and_locktime[34..]].concat();
+
+        let template = MerkleRootTemplate::new(
+            legacy_coinbase1,
+            block_881423::extranonce1_bytes().to_vec(),
+            Extranonce2Range::new(extranonce2.size()).unwrap(),
+            legacy_coinbase2,
+            block_881423::MERKLE_BRANCHES.clone(),
+        );
+
+        assert!(template.midstate.is_some());
+        assert_eq!(
+            template.compute_merkle_root(&extranonce2).unwrap(),
+            *block_881423::MERKLE_ROOT
+        );
+    }
+
+    #[test]
+    fn test_malformed_coinbase_and_wrong_extranonce2_size() {
+        let template = MerkleRootTemplate::new(
+            vec![0xaa],
+            vec![],
+            Extranonce2Range::new(4).unwrap(),
+            vec![0xbb],
+            vec![],
+        );
+        assert!(template.midstate.is_none());
+        assert!(template
+            .compute_merkle_root(&Extranonce2::new(0, 4).unwrap())
+            .is_err());
+
+        let template = template_881423(Extranonce2Range::new(4).unwrap());
+        assert!(template
+            .compute_merkle_root(&Extranonce2::new(0, 8).unwrap())
+            .is_err());
+    }
 }
@@ -17,6 +17,11 @@
 //! pattern: messages include a `SourceHandle` that the scheduler can use to
 //! route shares back to the correct source.
 //!
+//! Each kind of source implements [`JobSource`] and registers a
+//! [`SourceDescriptor`] for the URL schemes it serves (see [`registry`]), so
+//! the pool manager starts whichever source a pool's URL names without
+//! knowing the kinds there are.
+//!
 //! ## Work Generation Hierarchy
 //!
 //! The mining workflow follows a three-level template hierarchy:
@@ -26,7 +31,7 @@
 //!    - Extranonce2 space ([`Extranonce2Range`])
 //!    - Merkle root specification ([`MerkleRootKind`])
 //!
-//! 2. **`HeaderTemplate`** (to hardware) - Partially instantiated:
+//! 2. **[`HeaderTemplate`]** (to hardware) - Partially instantiated:
 //!    - Specific extranonce2 value selected
 //!    - Nonce = 0 (hardware will roll)
 //!    - Version bits may be rolled by hardware
@@ -51,23 +56,31 @@
 //! scheduler enforces it.
 
 // Submodules
+pub mod clock;
+pub mod datum;
 pub mod dummy;
 mod extranonce2;
 pub mod forced_rate;
+mod header;
 pub(crate) mod job;
 mod merkle;
 mod messages;
+pub mod registry;
+pub mod share_queue;
 pub mod stratum_v1;
 pub mod test_blocks;
 mod version;
 
 // Re-export types from submodules
-pub use extranonce2::{Extranonce2, Extranonce2Error, Extranonce2Iter, Extranonce2Range};
+pub use extranonce2::{
+    Extranonce2, Extranonce2Allocator, Extranonce2Error, Extranonce2Iter, Extranonce2Range,
+};
+pub use header::HeaderTemplate;
 pub use job::{JobTemplate, Share};
 pub use merkle::{MerkleRootKind, MerkleRootTemplate};
 pub use messages::{SourceCommand, SourceEvent, SourceHandle};
+pub use registry::{JobSource, SourceDescriptor, SourceParams, SourceRegistry};
 pub use version::{GeneralPurposeBits, VersionTemplate, VersionTemplateError};
 
-// TODO: Add HeaderTemplate type (Level 2 in the hierarchy)
 // TODO: Implement dummy source
 // TODO: Implement scheduler
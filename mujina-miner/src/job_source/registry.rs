@@ -0,0 +1,144 @@
+//! Job source registry.
+//!
+//! Each kind of job source registers a [`SourceDescriptor`] naming the URL
+//! schemes it serves, how to check a pool configured with one, and how to
+//! create the source. The pool manager looks sources up here by the pool's
+//! URL, so pools from the config file and pools added through the API are
+//! started the same way, and a new kind of source needs no changes outside
+//! its own module.
+//!
+//! A URL without a scheme is taken to be Stratum v1, as it always has been.
+
+use std::path::PathBuf;
+
+use anyhow::Result;
+use tokio::sync::{mpsc, watch};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    config::{PoolConfig, ShareQueueConfig},
+    supervisor::Supervisor,
+    types::Network,
+};
+
+use super::{stratum_v1::PoolStatus, SourceCommand, SourceEvent};
+
+/// Scheme of URLs that don't name one.
+pub const DEFAULT_SCHEME: &str = "stratum+tcp://";
+
+/// What a source is created from.
+pub struct SourceParams<'a> {
+    /// The pool the source mines
+    pub pool: &'a PoolConfig,
+    /// The pool's password, with any secret reference resolved
+    pub password: Option<String>,
+    /// Network the source's work must be for
+    pub network: Network,
+    /// How shares that fail to reach the pool are queued
+    pub share_queue: &'a ShareQueueConfig,
+    /// Where to keep transcripts of the pool's messages, if the pool is
+    /// traced and the source can
+    pub trace_dir: Option<PathBuf>,
+    /// Commands from the scheduler
+    pub command_rx: mpsc::Receiver<SourceCommand>,
+    /// Events to the scheduler
+    pub event_tx: mpsc::Sender<SourceEvent>,
+    /// Cancelled when the source is switched away from or the daemon stops
+    pub shutdown: CancellationToken,
+}
+
+/// A created job source, not yet running.
+pub trait JobSource: Send {
+    /// Human-readable name, used to register with the scheduler and in logs.
+    fn name(&self) -> String;
+
+    /// Watch the connection state and share counts.
+    fn status(&self) -> watch::Receiver<PoolStatus>;
+
+    /// Run the source in `group` until the group is cancelled.
+    fn spawn(self: Box<Self>, group: &Supervisor);
+}
+
+/// Checks a pool's URL and worker for a kind of source, returning why
+/// they won't do.
+pub type SourceCheckFn = fn(&PoolConfig, Network) -> Result<(), String>;
+
+/// Creates a source from its pool and channels.
+pub type SourceFactoryFn = fn(SourceParams<'_>) -> Result<Box<dyn JobSource>>;
+
+/// A kind of job source, registered with `inventory::submit!`.
+pub struct SourceDescriptor {
+    /// URL schemes served, including the `://`
+    pub schemes: &'static [&'static str],
+    /// Human-readable kind of source (e.g., "Stratum v1")
+    pub name: &'static str,
+    /// Checks beyond those every pool gets
+    pub check_fn: SourceCheckFn,
+    /// Factory function to create the source
+    pub create_fn: SourceFactoryFn,
+}
+
+inventory::collect!(SourceDescriptor);
+
+/// Registry for job source descriptors.
+pub struct SourceRegistry;
+
+impl SourceRegistry {
+    /// Find the descriptor serving `url`'s scheme.
+    pub fn find(&self, url: &str) -> Option<&'static SourceDescriptor> {
+        let scheme = match url.find("://") {
+            Some(end) => &url[..end + 3],
+            None => DEFAULT_SCHEME,
+        };
+        inventory::iter::<SourceDescriptor>().find(|desc| desc.schemes.contains(&scheme))
+    }
+
+    /// Every registered scheme, sorted.
+    pub fn schemes(&self) -> Vec<&'static str> {
+        let mut schemes: Vec<_> = inventory::iter::<SourceDescriptor>()
+            .flat_map(|desc| desc.schemes.iter().copied())
+            .collect();
+        schemes.sort_unstable();
+        schemes
+    }
+}
+
+/// Check that `url` is `host:port` after one of `schemes`, if it has one.
+pub fn check_host_port(url: &str, schemes: &[&str]) -> Result<(), String> {
+    let address = schemes
+        .iter()
+        .find_map(|scheme| url.strip_prefix(scheme))
+        .unwrap_or(url);
+    match address.rsplit_once(':') {
+        Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => Ok(()),
+        _ => Err(format!(
+            "URL '{}' isn't of the form {}host:port",
+            url,
+            schemes.first().copied().unwrap_or_default()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_by_scheme() {
+        let kind = |url: &str| SourceRegistry.find(url).map(|desc| desc.name);
+
+        assert_eq!(
+            kind("stratum+tcp://pool.example.com:3333"),
+            Some("Stratum v1")
+        );
+        assert_eq!(kind("tcp://pool.example.com:3333"), Some("Stratum v1"));
+        assert_eq!(kind("pool.example.com:3333"), Some("Stratum v1"));
+        assert_eq!(kind("datum://127.0.0.1:8332"), Some("DATUM"));
+        assert_eq!(kind("dummy://"), Some("Dummy"));
+        assert_eq!(kind("http://pool.example.com"), None);
+
+        let schemes = SourceRegistry.schemes();
+        assert!(schemes.contains(&DEFAULT_SCHEME));
+        assert!(schemes.windows(2).all(|w| w[0] < w[1]), "{:?}", schemes);
+    }
+}
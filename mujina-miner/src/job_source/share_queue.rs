@@ -0,0 +1,300 @@
+//! Shares that failed to reach the pool, kept for resubmission.
+//!
+//! A share is only worth anything to the pool that issued the work, and
+//! only in the session it was found in: the coinbase embeds the session's
+//! extranonce1. When a connection drops, shares in flight are queued here
+//! with the extranonce1 they were found under. On reconnect the source asks
+//! the pool to resume the old session, and if the pool hands back the same
+//! extranonce1, the queued shares that aren't too stale are resubmitted.
+//! The rest are discarded, since the pool would only reject them.
+//!
+//! The queue can be kept on disk, so shares found just before a restart
+//! survive it, and the subscription ID to resume is remembered with them.
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::config::ShareQueueConfig;
+use crate::stratum_v1::SubmitParams;
+
+/// A share waiting for a resumed session.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct QueuedShare {
+    /// Extranonce1 of the session the share was found in, hex
+    extranonce1: String,
+    /// When the share was queued, Unix seconds
+    queued_at: u64,
+    params: SubmitParams,
+}
+
+/// What's kept on disk.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct QueueFile {
+    session_id: Option<String>,
+    shares: VecDeque<QueuedShare>,
+}
+
+/// Bounded queue of shares to resubmit when a session resumes.
+#[derive(Debug)]
+pub struct ShareQueue {
+    shares: VecDeque<QueuedShare>,
+    /// Subscription ID of the latest session, to ask the pool to resume
+    session_id: Option<String>,
+    max_shares: usize,
+    max_age_secs: u64,
+    /// File the queue is kept in, if any
+    path: Option<PathBuf>,
+}
+
+/// Shares taken from the queue for a new session.
+#[derive(Debug, Default, PartialEq)]
+pub struct Resumed {
+    /// Shares the session can accept, oldest first
+    pub shares: Vec<SubmitParams>,
+    /// Shares found in another session
+    pub other_session: usize,
+    /// Shares older than the staleness window
+    pub stale: usize,
+}
+
+impl ShareQueue {
+    /// Queue for the pool called `pool_name`, loading any shares kept on
+    /// disk from an earlier run.
+    pub fn new(config: &ShareQueueConfig, pool_name: &str) -> Self {
+        let path = config
+            .dir
+            .as_ref()
+            .map(|dir| dir.join(file_name(pool_name)));
+        let file = path.as_deref().map(load).unwrap_or_default();
+        if !file.shares.is_empty() {
+            debug!(pool = %pool_name, shares = file.shares.len(), "Loaded queued shares");
+        }
+
+        let mut queue = Self {
+            shares: file.shares,
+            session_id: file.session_id,
+            max_shares: config.max_shares,
+            max_age_secs: config.max_age_secs,
+            path,
+        };
+        queue.truncate();
+        queue
+    }
+
+    /// Number of shares queued.
+    pub fn len(&self) -> usize {
+        self.shares.len()
+    }
+
+    /// Whether no shares are queued.
+    pub fn is_empty(&self) -> bool {
+        self.shares.is_empty()
+    }
+
+    /// Subscription ID of the latest session.
+    pub fn session_id(&self) -> Option<&str> {
+        self.session_id.as_deref()
+    }
+
+    /// Remember the subscription ID of a new session.
+    pub fn set_session(&mut self, session_id: Option<String>) {
+        if self.session_id != session_id {
+            self.session_id = session_id;
+            self.save();
+        }
+    }
+
+    /// Queue a share found in the session with `extranonce1`, dropping the
+    /// oldest if the queue is full.
+    pub fn push(&mut self, extranonce1: &[u8], params: SubmitParams) {
+        self.push_at(extranonce1, params, unix_now());
+    }
+
+    fn push_at(&mut self, extranonce1: &[u8], params: SubmitParams, now: u64) {
+        if self.max_shares == 0 {
+            return;
+        }
+        self.shares.push_back(QueuedShare {
+            extranonce1: hex::encode(extranonce1),
+            queued_at: now,
+            params,
+        });
+        self.truncate();
+        self.save();
+    }
+
+    /// Take every queued share, returning those a session with
+    /// `extranonce1` can still accept.
+    pub fn resume(&mut self, extranonce1: &[u8]) -> Resumed {
+        self.resume_at(extranonce1, unix_now())
+    }
+
+    fn resume_at(&mut self, extranonce1: &[u8], now: u64) -> Resumed {
+        if self.shares.is_empty() {
+            return Resumed::default();
+        }
+
+        let extranonce1 = hex::encode(extranonce1);
+        let mut resumed = Resumed::default();
+        for share in self.shares.drain(..) {
+            if share.extranonce1 != extranonce1 {
+                resumed.other_session += 1;
+            } else if now.saturating_sub(share.queued_at) > self.max_age_secs {
+                resumed.stale += 1;
+            } else {
+                resumed.shares.push(share.params);
+            }
+        }
+        self.save();
+        resumed
+    }
+
+    /// Drop the oldest shares beyond the limit.
+    fn truncate(&mut self) {
+        let excess = self.shares.len().saturating_sub(self.max_shares);
+        if excess > 0 {
+            self.shares.drain(..excess);
+            warn!(dropped = excess, "Share queue full, dropped oldest shares");
+        }
+    }
+
+    /// Write the queue to its file, if it has one.
+    fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let file = QueueFile {
+            session_id: self.session_id.clone(),
+            shares: self.shares.clone(),
+        };
+        if let Err(e) = store(path, &file) {
+            warn!(path = %path.display(), error = %e, "Failed to save share queue");
+        }
+    }
+}
+
+/// File name for a pool's queue, e.g. "pool.example.com_3333.json".
+fn file_name(pool_name: &str) -> String {
+    let stem: String = pool_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}.json", stem)
+}
+
+/// Read a queue file, treating a missing or unreadable one as empty.
+fn load(path: &Path) -> QueueFile {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return QueueFile::default(),
+        Err(e) => {
+            warn!(path = %path.display(), error = %e, "Failed to read share queue");
+            return QueueFile::default();
+        }
+    };
+    serde_json::from_str(&text).unwrap_or_else(|e| {
+        warn!(path = %path.display(), error = %e, "Discarding corrupt share queue");
+        QueueFile::default()
+    })
+}
+
+/// Write a queue file atomically.
+fn store(path: &Path, file: &QueueFile) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_vec(file)?)?;
+    std::fs::rename(&tmp, path)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(nonce: u32) -> SubmitParams {
+        SubmitParams {
+            username: "worker".into(),
+            job_id: "1".into(),
+            extranonce2: vec![0, 0, 0, 1],
+            ntime: 0x6650_0000,
+            nonce,
+            version_bits: None,
+        }
+    }
+
+    fn config(dir: Option<PathBuf>, max_shares: usize) -> ShareQueueConfig {
+        ShareQueueConfig {
+            dir,
+            max_shares,
+            max_age_secs: 60,
+        }
+    }
+
+    #[test]
+    fn test_resume_filters_by_session_and_age() {
+        let mut queue = ShareQueue::new(&config(None, 10), "pool:3333");
+        queue.push_at(&[1, 2], params(1), 1000);
+        queue.push_at(&[1, 2], params(2), 1050);
+        queue.push_at(&[3, 4], params(3), 1050);
+
+        let resumed = queue.resume_at(&[1, 2], 1100);
+        assert_eq!(resumed.shares, vec![params(2)]);
+        assert_eq!(resumed.stale, 1);
+        assert_eq!(resumed.other_session, 1);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_bounded() {
+        let mut queue = ShareQueue::new(&config(None, 2), "pool:3333");
+        for nonce in 1..=3 {
+            queue.push_at(&[1], params(nonce), 1000);
+        }
+        assert_eq!(queue.len(), 2);
+        assert_eq!(
+            queue.resume_at(&[1], 1000).shares,
+            vec![params(2), params(3)]
+        );
+
+        let mut disabled = ShareQueue::new(&config(None, 0), "pool:3333");
+        disabled.push_at(&[1], params(1), 1000);
+        assert!(disabled.is_empty());
+    }
+
+    #[test]
+    fn test_persists_across_restarts() {
+        let dir = std::env::temp_dir().join(format!("mujina-shares-{}", std::process::id()));
+        let config = config(Some(dir.clone()), 10);
+
+        let mut queue = ShareQueue::new(&config, "pool.example.com:3333");
+        queue.set_session(Some("ae6812eb".into()));
+        queue.push_at(&[1, 2], params(1), 1000);
+        assert!(dir.join("pool.example.com_3333.json").exists());
+
+        let mut reloaded = ShareQueue::new(&config, "pool.example.com:3333");
+        assert_eq!(reloaded.session_id(), Some("ae6812eb"));
+        assert_eq!(reloaded.resume_at(&[1, 2], 1010).shares, vec![params(1)]);
+
+        // Taking the shares empties the file too
+        assert!(ShareQueue::new(&config, "pool.example.com:3333").is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -4,19 +4,51 @@
 //! abstraction. It handles the conversion between Stratum protocol messages and
 //! the internal JobTemplate/Share types used by the scheduler.
 
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
 use anyhow::Result;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch, Mutex};
+use tokio::time::Instant;
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, info, warn};
+use tracing::{debug, info, info_span, warn, Instrument};
 
-use crate::stratum_v1::{ClientEvent, JobNotification, PoolConfig};
-use crate::types::{Difficulty, HashRate};
+use crate::config::{self, ShareQueueConfig};
+use crate::stratum_v1::{
+    validate_job, ClientCommand, ClientEvent, JobNotification, JobRejectionCounts, PoolConfig,
+    ShareRejectionCounts, SubmitParams, Vardiff, SHARE_FLUSH_TIMEOUT,
+};
+use crate::supervisor::{Backoff, Supervisor};
+use crate::types::{Difficulty, HashRate, Network, ShareRate};
 
+use super::clock::ClockDrift;
+use super::registry::{self, JobSource, SourceDescriptor, SourceParams};
+use super::share_queue::ShareQueue;
 use super::{
     Extranonce2Range, GeneralPurposeBits, JobTemplate, MerkleRootKind, MerkleRootTemplate, Share,
     SourceCommand, SourceEvent, VersionTemplate,
 };
 
+/// How long the scheduler's command channel may stay quiet at shutdown
+/// before the flush is taken to be complete.
+const SHARE_FLUSH_QUIET: Duration = Duration::from_millis(250);
+
+/// How often the share rate is checked for a difficulty suggestion when no
+/// shares are coming in to trigger the check.
+const VARDIFF_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// URL schemes naming a Stratum v1 pool.
+const URL_SCHEMES: &[&str] = &["stratum+tcp://", "stratum://", "tcp://"];
+
+/// How many of a session's jobs since the last clean one are kept, to
+/// re-derive work and check shares against when the version mask changes.
+const MAX_LIVE_JOBS: usize = 32;
+
+/// Sent to pools in `mining.subscribe`.
+const USER_AGENT: &str = "mujina-miner/0.1.0-alpha";
+
 /// Stratum v1 job source.
 ///
 /// Wraps a StratumV1Client and bridges between the Stratum protocol and
@@ -38,11 +70,55 @@ pub struct StratumV1Source {
     /// Protocol state from subscription
     state: Option<ProtocolState>,
 
+    /// Protocol state of the last session that got as far as subscribing,
+    /// once it has ended. Shares found on its work are queued under it.
+    previous: Option<ProtocolState>,
+
+    /// Shares that failed to reach the pool, for a resumed session
+    queue: ShareQueue,
+
     /// Track if first accepted share has been logged
     first_share_logged: bool,
 
     /// Expected hashrate (an estimate, not a measurement)
     expected_hashrate: HashRate,
+
+    /// Jobs from this pool that failed validation
+    rejected_jobs: JobRejectionCounts,
+
+    /// Offset of our clock from the pool's
+    clock_drift: ClockDrift,
+
+    /// Network the pool is expected to serve
+    network: Network,
+
+    /// Connection state and share counts, for status reporting
+    status_tx: watch::Sender<PoolStatus>,
+
+    /// Share rate tracking for difficulty suggestions, if enabled
+    vardiff: Option<Vardiff>,
+
+    /// Where to keep transcripts of the pool's messages, if anywhere
+    trace_dir: Option<PathBuf>,
+}
+
+/// Live state of a pool connection.
+///
+/// Share counts accumulate across reconnects for the life of the source.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PoolStatus {
+    /// Subscribed and receiving work
+    pub connected: bool,
+    /// Share difficulty last set by the pool
+    pub difficulty: Option<f64>,
+    /// Shares the pool accepted
+    pub accepted: u64,
+    /// Shares the pool rejected
+    pub rejected: u64,
+    /// The rejected shares, by reason
+    pub rejections: ShareRejectionCounts,
+    /// Pool time minus local time, in seconds, from the pool's jobs
+    pub clock_offset: Option<i64>,
 }
 
 /// Protocol state after successful subscription.
@@ -59,6 +135,9 @@ struct ProtocolState {
 
     /// Authorized version mask (from mining.configure or mining.set_version_mask)
     version_mask: Option<u32>,
+
+    /// Jobs since the last clean one, oldest first
+    jobs: VecDeque<JobNotification>,
 }
 
 impl StratumV1Source {
@@ -69,26 +148,152 @@ impl StratumV1Source {
         event_tx: mpsc::Sender<SourceEvent>,
         shutdown: CancellationToken,
     ) -> Self {
+        let name = pool_name(&config.url);
         Self {
+            queue: ShareQueue::new(&ShareQueueConfig::default(), &name),
             config,
             event_tx,
             command_rx,
             shutdown,
             state: None,
+            previous: None,
             first_share_logged: false,
             expected_hashrate: HashRate::default(),
+            rejected_jobs: JobRejectionCounts::default(),
+            clock_drift: ClockDrift::default(),
+            network: Network::default(),
+            status_tx: watch::Sender::new(PoolStatus::default()),
+            vardiff: None,
+            trace_dir: None,
         }
     }
 
+    /// Set the network the pool is expected to serve (mainnet by default).
+    ///
+    /// Jobs for a different network are rejected by validation.
+    pub fn with_network(mut self, network: Network) -> Self {
+        self.network = network;
+        self
+    }
+
+    /// Set how shares that fail to reach the pool are queued for retry
+    /// (in memory, with default limits, unless set).
+    pub fn with_share_queue(mut self, config: &ShareQueueConfig) -> Self {
+        self.queue = ShareQueue::new(config, &self.name());
+        self
+    }
+
+    /// Suggest difficulties to the pool that bring our share rate near
+    /// `rate` when it strays far from it (off unless set).
+    pub fn with_target_share_rate(mut self, rate: ShareRate) -> Self {
+        self.vardiff = Some(Vardiff::new(rate, Instant::now()));
+        self
+    }
+
+    /// Keep a transcript of the messages exchanged with the pool in `dir`
+    /// (off unless set).
+    pub fn with_trace_dir(mut self, dir: PathBuf) -> Self {
+        self.trace_dir = Some(dir);
+        self
+    }
+
+    /// Counts of jobs from this pool rejected by validation, by reason.
+    pub fn rejected_jobs(&self) -> JobRejectionCounts {
+        self.rejected_jobs
+    }
+
+    /// Watch the connection state and share counts.
+    pub fn status(&self) -> watch::Receiver<PoolStatus> {
+        self.status_tx.subscribe()
+    }
+
     /// Human-readable name derived from pool URL (e.g., "solo.ckpool.org:3333").
     pub fn name(&self) -> String {
-        self.config
-            .url
-            .strip_prefix("stratum+tcp://")
-            .or_else(|| self.config.url.strip_prefix("stratum://"))
-            .or_else(|| self.config.url.strip_prefix("tcp://"))
-            .unwrap_or(&self.config.url)
-            .to_string()
+        pool_name(&self.config.url)
+    }
+
+    /// Protocol state of the session shares found now belong to: the
+    /// current one once subscribed, otherwise the last one.
+    fn share_session(&self) -> Option<&ProtocolState> {
+        if self.status_tx.borrow().connected {
+            self.state.as_ref()
+        } else {
+            self.previous.as_ref()
+        }
+    }
+
+    /// End the current session, keeping its state for shares still to
+    /// come from its work if it got as far as subscribing.
+    fn end_session(&mut self) {
+        let subscribed = self.status_tx.borrow().connected;
+        self.status_tx
+            .send_modify(|status| status.connected = false);
+        let state = self.state.take();
+        if subscribed {
+            self.previous = state;
+        }
+        if let Some(vardiff) = &mut self.vardiff {
+            vardiff.reset(Instant::now());
+        }
+    }
+
+    /// Suggest a difficulty if our share rate has strayed from the target.
+    async fn suggest_difficulty(&mut self, client_command_tx: &mpsc::Sender<ClientCommand>) {
+        let Some(difficulty) = self
+            .vardiff
+            .as_mut()
+            .and_then(|vardiff| vardiff.poll(Instant::now()))
+        else {
+            return;
+        };
+        if let Err(e) = client_command_tx
+            .send(ClientCommand::SuggestDifficulty(difficulty))
+            .await
+        {
+            debug!(error = %e, "Failed to send difficulty suggestion to client");
+        }
+    }
+
+    /// Queue a share that couldn't be submitted, for a resumed session.
+    fn queue_share(&mut self, params: SubmitParams) {
+        let Some(session) = self.share_session() else {
+            warn!(pool = %self.name(), job_id = %params.job_id, "Dropped share from unknown session");
+            return;
+        };
+        let extranonce1 = session.extranonce1.clone();
+        self.queue.push(&extranonce1, params);
+        debug!(pool = %self.name(), queued = self.queue.len(), "Queued share for retry");
+    }
+
+    /// Resubmit the queued shares the new session can accept.
+    async fn resubmit_queued(&mut self, client_command_tx: &mpsc::Sender<ClientCommand>) {
+        let Some(state) = &self.state else {
+            return;
+        };
+        let resumed = self.queue.resume(&state.extranonce1.clone());
+        if resumed.other_session + resumed.stale > 0 {
+            info!(
+                pool = %self.name(),
+                other_session = resumed.other_session,
+                stale = resumed.stale,
+                "Discarded queued shares the pool can't accept"
+            );
+        }
+        if resumed.shares.is_empty() {
+            return;
+        }
+
+        info!(pool = %self.name(), shares = resumed.shares.len(), "Session resumed, resubmitting queued shares");
+        for params in resumed.shares {
+            if let Err(e) = client_command_tx
+                .send(ClientCommand::SubmitShare(params))
+                .await
+            {
+                if let ClientCommand::SubmitShare(params) = e.0 {
+                    self.queue_share(params);
+                }
+            }
+        }
     }
 
     /// Convert Stratum JobNotification to JobTemplate.
@@ -99,7 +304,7 @@ impl StratumV1Source {
             .ok_or_else(|| anyhow::anyhow!("No protocol state (not subscribed)"))?;
 
         // Create extranonce2 range (full range for the given size)
-        let extranonce2_range = Extranonce2Range::new(state.extranonce2_size as u8)?;
+        let extranonce2_range = Extranonce2Range::for_pool_size(state.extranonce2_size)?;
 
         // Convert version to VersionTemplate
         // Use authorized mask from pool (or none if pool didn't authorize version rolling)
@@ -121,13 +326,13 @@ impl StratumV1Source {
             bits: job.nbits,
             share_target,
             time: job.ntime,
-            merkle_root: MerkleRootKind::Computed(MerkleRootTemplate {
-                coinbase1: job.coinbase1,
-                extranonce1: state.extranonce1.clone(),
+            merkle_root: MerkleRootKind::Computed(MerkleRootTemplate::new(
+                job.coinbase1,
+                state.extranonce1.clone(),
                 extranonce2_range,
-                coinbase2: job.coinbase2,
-                merkle_branches: job.merkle_branches,
-            }),
+                job.coinbase2,
+                job.merkle_branches,
+            )),
         })
     }
 
@@ -155,6 +360,7 @@ impl StratumV1Source {
                         extranonce2_size: 0,
                         share_difficulty: None,
                         version_mask: authorized_mask,
+                        jobs: VecDeque::new(),
                     });
                 }
             }
@@ -162,12 +368,15 @@ impl StratumV1Source {
             ClientEvent::Subscribed {
                 extranonce1,
                 extranonce2_size,
+                session_id,
             } => {
                 info!(
                     pool = %self.config.url,
                     user = %self.config.username,
                     "Subscribed."
                 );
+                self.status_tx.send_modify(|status| status.connected = true);
+                self.queue.set_session(session_id);
 
                 // Update or create protocol state
                 // Preserve version_mask if already set by VersionRollingConfigured
@@ -180,6 +389,7 @@ impl StratumV1Source {
                         extranonce2_size,
                         share_difficulty: None,
                         version_mask: None,
+                        jobs: VecDeque::new(),
                     });
                 }
             }
@@ -187,7 +397,49 @@ impl StratumV1Source {
             ClientEvent::NewJob(job) => {
                 debug!(job_id = %job.job_id, clean_jobs = job.clean_jobs, "Received job from pool");
 
+                // Drop garbage before it reaches the chips. A rejected clean
+                // job leaves the previous work running, which is no worse
+                // than hashing a job the pool can't accept shares for.
+                let extranonce_len = self
+                    .state
+                    .as_ref()
+                    .map(|s| s.extranonce1.len() + s.extranonce2_size)
+                    .unwrap_or(0);
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0);
+                self.clock_drift.observe(&self.name(), job.ntime, now);
+                let clock_offset = self.clock_drift.offset();
+                self.status_tx.send_if_modified(|status| {
+                    let changed = status.clock_offset != clock_offset;
+                    status.clock_offset = clock_offset;
+                    changed
+                });
+                if let Err(rejection) =
+                    validate_job(&job, extranonce_len, self.network.max_target(), now as u32)
+                {
+                    self.rejected_jobs.record(&rejection);
+                    warn!(
+                        pool = %self.name(),
+                        job_id = %job.job_id,
+                        reason = %rejection,
+                        rejected_total = self.rejected_jobs.total(),
+                        "Rejected invalid job from pool"
+                    );
+                    return Ok(());
+                }
+
                 let template = self.job_to_template(job.clone())?;
+                if let Some(state) = &mut self.state {
+                    if job.clean_jobs {
+                        state.jobs.clear();
+                    }
+                    if state.jobs.len() == MAX_LIVE_JOBS {
+                        state.jobs.pop_front();
+                    }
+                    state.jobs.push_back(job.clone());
+                }
 
                 // Clean jobs means previous work is invalid
                 let event = if job.clean_jobs {
@@ -200,21 +452,41 @@ impl StratumV1Source {
             }
 
             ClientEvent::DifficultyChanged(diff) => {
-                let difficulty = Difficulty::from(diff);
+                let difficulty = Difficulty::from_f64(diff);
                 debug!(difficulty = %difficulty, "Pool difficulty changed");
+                self.status_tx
+                    .send_modify(|status| status.difficulty = Some(diff));
                 if let Some(state) = &mut self.state {
                     state.share_difficulty = Some(difficulty);
                 }
+                if let Some(vardiff) = &mut self.vardiff {
+                    vardiff.set_difficulty(diff, Instant::now());
+                }
             }
 
             ClientEvent::VersionMaskSet(mask) => {
                 info!(mask = format!("{:#010x}", mask), "Version mask set");
-                if let Some(state) = &mut self.state {
-                    state.version_mask = Some(mask);
+                let Some(state) = &mut self.state else {
+                    return Ok(());
+                };
+                let previous = state.version_mask.replace(mask);
+
+                // Work rolling only bits the new mask still allows stays
+                // valid. Otherwise the current job is reissued under the new
+                // mask alongside the old work, rather than replacing it;
+                // shares the old work finds on bits no longer allowed are
+                // dropped when submitted.
+                let narrowed = previous.is_some_and(|previous| previous & !mask != 0);
+                let current = state.jobs.back().cloned();
+                if let (true, Some(job)) = (narrowed, current) {
+                    debug!(job_id = %job.job_id, "Version mask narrowed, reissuing current job");
+                    let template = self.job_to_template(job)?;
+                    self.event_tx.send(SourceEvent::UpdateJob(template)).await?;
                 }
             }
 
             ClientEvent::ShareAccepted { job_id, nonce } => {
+                self.status_tx.send_modify(|status| status.accepted += 1);
                 if !self.first_share_logged {
                     self.first_share_logged = true;
                     info!(
@@ -235,15 +507,28 @@ impl StratumV1Source {
                 }
             }
 
-            ClientEvent::ShareRejected { job_id, reason } => {
-                warn!(job_id = %job_id, reason = %reason, "Share rejected by pool");
+            ClientEvent::ShareRejected {
+                job_id,
+                reason,
+                message,
+            } => {
+                warn!(job_id = %job_id, %reason, %message, "Share rejected by pool");
+                self.status_tx.send_modify(|status| {
+                    status.rejected += 1;
+                    status.rejections.record(reason);
+                });
             }
 
             ClientEvent::Disconnected => {
                 warn!("Disconnected from pool");
+                self.end_session();
                 self.event_tx.send(SourceEvent::ClearJobs).await?;
             }
 
+            ClientEvent::SubmitFailed(params) => {
+                self.queue_share(params);
+            }
+
             ClientEvent::Error(err) => {
                 warn!(error = %err, "Pool error");
             }
@@ -252,11 +537,29 @@ impl StratumV1Source {
         Ok(())
     }
 
-    /// Convert Share to SubmitParams.
-    fn share_to_submit_params(&self, share: Share) -> Result<crate::stratum_v1::SubmitParams> {
+    /// Whether `share` rolled only version bits its session's mask allows.
+    ///
+    /// A share for a job no longer kept is given the benefit of the doubt.
+    fn version_allowed(&self, share: &Share) -> bool {
+        let Some(state) = self.share_session() else {
+            return true;
+        };
+        let Some(job) = state
+            .jobs
+            .iter()
+            .rev()
+            .find(|job| job.job_id == share.job_id)
+        else {
+            return true;
+        };
+        let rolled = share.version.to_consensus() as u32 ^ job.version.to_consensus() as u32;
+        rolled & !state.version_mask.unwrap_or(0) == 0
+    }
+
+    /// Convert Share to SubmitParams, for the session it was found in.
+    fn share_to_submit_params(&self, share: Share) -> Result<SubmitParams> {
         let state = self
-            .state
-            .as_ref()
+            .share_session()
             .ok_or_else(|| anyhow::anyhow!("No protocol state (not subscribed)"))?;
 
         // Extract extranonce2 from share (if present)
@@ -273,7 +576,7 @@ impl StratumV1Source {
             rolled & mask
         });
 
-        Ok(crate::stratum_v1::SubmitParams {
+        Ok(SubmitParams {
             username: self.config.username.clone(),
             job_id: share.job_id,
             extranonce2,
@@ -283,28 +586,115 @@ impl StratumV1Source {
         })
     }
 
+    /// Handle a command from the scheduler.
+    async fn handle_command(
+        &mut self,
+        cmd: SourceCommand,
+        client_command_tx: &mpsc::Sender<ClientCommand>,
+    ) {
+        match cmd {
+            SourceCommand::SubmitShare(share) => {
+                debug!(
+                    pool = %self.name(),
+                    job_id = %share.job_id,
+                    nonce = format!("{:#x}", share.nonce),
+                    "Submitting share"
+                );
+
+                if !self.version_allowed(&share) {
+                    debug!(
+                        job_id = %share.job_id,
+                        version = format!("{:#010x}", share.version.to_consensus()),
+                        "Dropping share rolled outside the version mask"
+                    );
+                    return;
+                }
+
+                // Convert share to Stratum format and send to client, or
+                // queue it if there's no session to send it in
+                match self.share_to_submit_params(share) {
+                    Ok(submit_params) if !self.status_tx.borrow().connected => {
+                        self.queue_share(submit_params);
+                    }
+                    Ok(submit_params) => {
+                        if let Err(e) = client_command_tx
+                            .send(ClientCommand::SubmitShare(submit_params))
+                            .await
+                        {
+                            warn!(error = %e, "Failed to send share to client");
+                            if let ClientCommand::SubmitShare(submit_params) = e.0 {
+                                self.queue_share(submit_params);
+                            }
+                            return;
+                        }
+                        if let Some(vardiff) = &mut self.vardiff {
+                            vardiff.share();
+                        }
+                        self.suggest_difficulty(client_command_tx).await;
+                    }
+                    Err(e) => {
+                        warn!(error = %e, "Failed to convert share");
+                    }
+                }
+            }
+
+            SourceCommand::UpdateHashRate(rate) => {
+                self.expected_hashrate = rate;
+            }
+        }
+    }
+
+    /// Pass on the shares the scheduler flushes at shutdown.
+    ///
+    /// The scheduler flushes as soon as it stops, so this waits only until
+    /// the command channel closes or goes quiet for [`SHARE_FLUSH_QUIET`],
+    /// and never longer than [`SHARE_FLUSH_TIMEOUT`].
+    async fn flush_shares(&mut self, client_command_tx: &mpsc::Sender<ClientCommand>) {
+        let deadline = Instant::now() + SHARE_FLUSH_TIMEOUT;
+
+        loop {
+            let quiet = (Instant::now() + SHARE_FLUSH_QUIET).min(deadline);
+            match tokio::time::timeout_at(quiet, self.command_rx.recv()).await {
+                Ok(Some(cmd)) => self.handle_command(cmd, client_command_tx).await,
+                Ok(None) | Err(_) => return,
+            }
+        }
+    }
+
     /// Run the source (main event loop).
     ///
     /// Spawns the Stratum client and bridges events between the client and
-    /// the job source interface.
-    pub async fn run(mut self) -> Result<()> {
+    /// the job source interface. Returns when the client exits or shutdown is
+    /// requested. The source may be run again afterwards to reconnect; each
+    /// run starts a fresh session, asking the pool to resume the last one so
+    /// queued shares can be resubmitted.
+    pub async fn run(&mut self) -> Result<()> {
         debug!(pool = %self.config.url, "Connecting to pool");
 
+        // Protocol state belongs to a single session
+        self.end_session();
+
         // Create channels for client communication
         let (client_event_tx, mut client_event_rx) = mpsc::channel(100);
         let (client_command_tx, client_command_rx) = mpsc::channel(100);
 
         // Create the Stratum client with command channel
-        let client = crate::stratum_v1::StratumV1Client::with_commands(
+        let mut client = crate::stratum_v1::StratumV1Client::with_commands(
             self.config.clone(),
             client_event_tx,
             client_command_rx,
             self.shutdown.clone(),
-        );
+        )
+        .with_session(self.queue.session_id().map(str::to_string));
+        if let Some(dir) = &self.trace_dir {
+            client = client.with_trace_dir(dir.clone());
+        }
 
         // Spawn client task
         let client_handle = tokio::spawn(async move { client.run().await });
 
+        let mut vardiff_poll = tokio::time::interval(VARDIFF_POLL_INTERVAL);
+
         // Main event loop
         loop {
             tokio::select! {
@@ -312,9 +702,13 @@ impl StratumV1Source {
                 event_opt = client_event_rx.recv() => {
                     match event_opt {
                         Some(event) => {
+                            let subscribed = matches!(event, ClientEvent::Subscribed { .. });
                             if let Err(e) = self.handle_client_event(event).await {
                                 warn!(error = %e, "Error handling client event");
                             }
+                            if subscribed {
+                                self.resubmit_queued(&client_command_tx).await;
+                            }
                         }
                         None => {
                             warn!("Client event channel closed (client task exited)");
@@ -325,43 +719,32 @@ impl StratumV1Source {
 
                 // Commands from scheduler
                 Some(cmd) = self.command_rx.recv() => {
-                    match cmd {
-                        SourceCommand::SubmitShare(share) => {
-                            debug!(
-                                pool = %self.name(),
-                                job_id = %share.job_id,
-                                nonce = format!("{:#x}", share.nonce),
-                                "Submitting share"
-                            );
-
-                            // Convert share to Stratum format and send to client
-                            match self.share_to_submit_params(share) {
-                                Ok(submit_params) => {
-                                    if let Err(e) = client_command_tx.send(
-                                        crate::stratum_v1::ClientCommand::SubmitShare(submit_params)
-                                    ).await {
-                                        warn!(error = %e, "Failed to send share to client");
-                                    }
-                                }
-                                Err(e) => {
-                                    warn!(error = %e, "Failed to convert share");
-                                }
-                            }
-                        }
+                    self.handle_command(cmd, &client_command_tx).await;
+                }
 
-                        SourceCommand::UpdateHashRate(rate) => {
-                            self.expected_hashrate = rate;
-                        }
-                    }
+                // Starved of shares, the rate is checked on a timer
+                _ = vardiff_poll.tick(), if self.vardiff.is_some() => {
+                    self.suggest_difficulty(&client_command_tx).await;
                 }
 
                 // Shutdown
                 _ = self.shutdown.cancelled() => {
+                    self.flush_shares(&client_command_tx).await;
                     break;
                 }
             }
         }
 
+        // Let the client finish, and count the results of flushed shares
+        drop(client_command_tx);
+        while let Some(event) = client_event_rx.recv().await {
+            if let Err(e) = self.handle_client_event(event).await {
+                debug!(error = %e, "Error handling client event after shutdown");
+            }
+        }
+
+        self.end_session();
+
         // Wait for client to finish and propagate any errors
         match client_handle.await? {
             Ok(()) => Ok(()),
@@ -373,6 +756,74 @@ impl StratumV1Source {
     }
 }
 
+impl JobSource for StratumV1Source {
+    fn name(&self) -> String {
+        StratumV1Source::name(self)
+    }
+
+    fn status(&self) -> watch::Receiver<PoolStatus> {
+        StratumV1Source::status(self)
+    }
+
+    /// Run the source, reconnecting with backoff if its client fails.
+    fn spawn(self: Box<Self>, group: &Supervisor) {
+        let name = self.name();
+        let source = Arc::new(Mutex::new(*self));
+        group.spawn_restartable("pool", Backoff::default(), move || {
+            let source = source.clone();
+            async move { source.lock().await.run().await }
+                .instrument(info_span!("pool", pool = %name))
+        });
+    }
+}
+
+/// Human-readable name for a pool URL, without the scheme.
+fn pool_name(url: &str) -> String {
+    URL_SCHEMES
+        .iter()
+        .find_map(|scheme| url.strip_prefix(scheme))
+        .unwrap_or(url)
+        .to_string()
+}
+
+fn check(pool: &config::PoolConfig, network: Network) -> Result<(), String> {
+    registry::check_host_port(&pool.url, URL_SCHEMES)?;
+    network
+        .check_worker_name(&pool.worker)
+        .map_err(|e| format!("worker '{}' on {}: {}", pool.worker, network, e))
+}
+
+fn create(params: SourceParams<'_>) -> Result<Box<dyn JobSource>> {
+    let config = PoolConfig {
+        url: params.pool.url.clone(),
+        username: params.pool.worker.clone(),
+        password: params.password.unwrap_or_else(|| "x".to_string()),
+        user_agent: USER_AGENT.to_string(),
+        suggested_difficulty: None,
+    };
+    let mut source =
+        StratumV1Source::new(config, params.command_rx, params.event_tx, params.shutdown)
+            .with_network(params.network)
+            .with_share_queue(params.share_queue);
+    if let Some(rate) = params.pool.shares_per_minute {
+        source = source.with_target_share_rate(ShareRate::per_minute(rate));
+    }
+    if let Some(dir) = params.trace_dir {
+        source = source.with_trace_dir(dir);
+    }
+    Ok(Box::new(source))
+}
+
+// Register this source type with the inventory system
+inventory::submit! {
+    SourceDescriptor {
+        schemes: URL_SCHEMES,
+        name: "Stratum v1",
+        check_fn: check,
+        create_fn: create,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -382,7 +833,7 @@ mod tests {
     };
     use crate::asic::bm13xx::test_data::stratum_json;
     use crate::job_source::Extranonce2;
-    use crate::stratum_v1::JobNotification;
+    use crate::stratum_v1::{JobNotification, RejectReason};
     use bitcoin::block::Version;
     use serde_json::json;
 
@@ -422,7 +873,11 @@ mod tests {
             extranonce2_size,
             share_difficulty: share_difficulty.map(Difficulty::from),
             version_mask,
+            jobs: VecDeque::new(),
         });
+        source
+            .status_tx
+            .send_modify(|status| status.connected = true);
 
         source
     }
@@ -489,18 +944,18 @@ mod tests {
         match &template.merkle_root {
             MerkleRootKind::Computed(mrt) => {
                 assert_eq!(
-                    mrt.coinbase1,
+                    mrt.coinbase1(),
                     hex::decode(notify::COINBASE1).unwrap(),
                     "coinbase1 mismatch"
                 );
-                assert_eq!(mrt.extranonce1, extranonce1, "extranonce1 mismatch");
+                assert_eq!(mrt.extranonce1(), extranonce1, "extranonce1 mismatch");
                 assert_eq!(
-                    mrt.coinbase2,
+                    mrt.coinbase2(),
                     hex::decode(notify::COINBASE2).unwrap(),
                     "coinbase2 mismatch"
                 );
                 assert_eq!(
-                    mrt.merkle_branches.len(),
+                    mrt.merkle_branches().len(),
                     12,
                     "Wrong number of merkle branches"
                 );
@@ -792,4 +1247,410 @@ mod tests {
             "Computed merkle root doesn't match capture"
         );
     }
+
+    /// A job that fails validation is counted and never reaches the scheduler.
+    ///
+    /// The capture's ntime is years old, so against the real clock it's
+    /// rejected for drift.
+    #[tokio::test]
+    async fn test_invalid_job_counted_and_dropped() {
+        let (event_tx, mut event_rx) = mpsc::channel(10);
+        let mut source = source_with_state(
+            hex::decode(STRATUM_EXTRANONCE1).unwrap(),
+            STRATUM_EXTRANONCE2_SIZE,
+            Some(POOL_SHARE_DIFFICULTY_INT),
+            Some(VERSION_MASK),
+        );
+        source.event_tx = event_tx;
+
+        let json: serde_json::Value = serde_json::from_str(stratum_json::MINING_NOTIFY).unwrap();
+        let job = JobNotification::from_stratum_params(json["params"].as_array().unwrap()).unwrap();
+
+        source
+            .handle_client_event(ClientEvent::NewJob(job))
+            .await
+            .unwrap();
+
+        assert_eq!(source.rejected_jobs().ntime, 1);
+        assert_eq!(source.rejected_jobs().total(), 1);
+        assert!(event_rx.try_recv().is_err(), "rejected job was forwarded");
+    }
+
+    /// Narrowing the version mask reissues the current job under the new
+    /// mask as an update, and drops shares rolled on bits it no longer
+    /// allows. Widening it leaves the work alone.
+    #[tokio::test]
+    async fn test_narrowed_version_mask_reissues_job() {
+        let (event_tx, mut event_rx) = mpsc::channel(10);
+        let mut source = source_with_state(
+            hex::decode(STRATUM_EXTRANONCE1).unwrap(),
+            STRATUM_EXTRANONCE2_SIZE,
+            Some(POOL_SHARE_DIFFICULTY_INT),
+            Some(VERSION_MASK),
+        );
+        source.event_tx = event_tx;
+
+        let json: serde_json::Value = serde_json::from_str(stratum_json::MINING_NOTIFY).unwrap();
+        let job = JobNotification::from_stratum_params(json["params"].as_array().unwrap()).unwrap();
+        let base = job.version.to_consensus() as u32;
+        source.state.as_mut().unwrap().jobs.push_back(job.clone());
+
+        let narrow = 0x00ff_e000;
+        for mask in [narrow, VERSION_MASK, narrow] {
+            source
+                .handle_client_event(ClientEvent::VersionMaskSet(mask))
+                .await
+                .unwrap();
+        }
+        for _ in 0..2 {
+            let Ok(SourceEvent::UpdateJob(template)) = event_rx.try_recv() else {
+                panic!("expected the job reissued as an update");
+            };
+            assert_eq!(template.id, job.job_id);
+            assert_eq!(
+                template.version.gp_bits_mask(),
+                GeneralPurposeBits::from(&narrow.to_be_bytes())
+            );
+        }
+        assert!(event_rx.try_recv().is_err(), "widening reissued the job");
+
+        let (client_command_tx, mut client_command_rx) = mpsc::channel(10);
+        for (nonce, bits) in [(1, 0x0000_2000), (2, 0x1000_0000)] {
+            let share = Share {
+                job_id: job.job_id.clone(),
+                nonce,
+                time: job.ntime,
+                version: Version::from_consensus((base ^ bits) as i32),
+                extranonce2: None,
+            };
+            source
+                .handle_command(SourceCommand::SubmitShare(share), &client_command_tx)
+                .await;
+        }
+        let Ok(ClientCommand::SubmitShare(params)) = client_command_rx.try_recv() else {
+            panic!("expected a share submission");
+        };
+        assert_eq!(params.nonce, 1);
+        assert!(
+            client_command_rx.try_recv().is_err(),
+            "share outside mask sent"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_status_tracks_session() {
+        let (event_tx, _event_rx) = mpsc::channel(10);
+        let mut source = source_with_state(Vec::new(), 4, None, None);
+        source.event_tx = event_tx;
+        let status = source.status();
+
+        let events = [
+            ClientEvent::Subscribed {
+                extranonce1: vec![0; 4],
+                extranonce2_size: 4,
+                session_id: None,
+            },
+            ClientEvent::DifficultyChanged(512.0),
+            ClientEvent::ShareAccepted {
+                job_id: "1".into(),
+                nonce: 1,
+            },
+            ClientEvent::ShareAccepted {
+                job_id: "1".into(),
+                nonce: 2,
+            },
+            ClientEvent::ShareRejected {
+                job_id: "1".into(),
+                reason: RejectReason::Stale,
+                message: "Stale".into(),
+            },
+        ];
+        for event in events {
+            source.handle_client_event(event).await.unwrap();
+        }
+        assert_eq!(
+            *status.borrow(),
+            PoolStatus {
+                connected: true,
+                difficulty: Some(512.0),
+                accepted: 2,
+                rejected: 1,
+                rejections: ShareRejectionCounts {
+                    stale: 1,
+                    ..Default::default()
+                },
+                clock_offset: None,
+            }
+        );
+
+        source
+            .handle_client_event(ClientEvent::Disconnected)
+            .await
+            .unwrap();
+        assert!(!status.borrow().connected);
+        assert_eq!(status.borrow().accepted, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_queued_shares_flushed_at_shutdown() {
+        let mut source = source_with_state(
+            hex::decode(STRATUM_EXTRANONCE1).unwrap(),
+            STRATUM_EXTRANONCE2_SIZE,
+            Some(POOL_SHARE_DIFFICULTY_INT),
+            None,
+        );
+        let (command_tx, command_rx) = mpsc::channel(10);
+        source.command_rx = command_rx;
+        let (client_command_tx, mut client_command_rx) = mpsc::channel(10);
+
+        for nonce in [1, 2] {
+            let share = Share {
+                job_id: "testjob".to_string(),
+                nonce,
+                time: 0x65432100,
+                version: Version::from_consensus(0x20000000),
+                extranonce2: None,
+            };
+            command_tx
+                .send(SourceCommand::SubmitShare(share))
+                .await
+                .unwrap();
+        }
+
+        // The scheduler is still up, so the flush ends once it goes quiet
+        let started = Instant::now();
+        source.flush_shares(&client_command_tx).await;
+        assert_eq!(started.elapsed(), SHARE_FLUSH_QUIET);
+
+        for nonce in [1, 2] {
+            let Ok(ClientCommand::SubmitShare(params)) = client_command_rx.try_recv() else {
+                panic!("expected a share submission");
+            };
+            assert_eq!(params.nonce, nonce);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shares_queued_across_reconnect() {
+        let extranonce1 = vec![0x08, 0x00, 0x00, 0x02];
+        let mut source = source_with_state(extranonce1.clone(), 4, None, None);
+        let (event_tx, _event_rx) = mpsc::channel(10);
+        source.event_tx = event_tx;
+        let (client_command_tx, mut client_command_rx) = mpsc::channel(10);
+
+        // The connection fails while the board is still finding shares
+        source
+            .handle_client_event(ClientEvent::Disconnected)
+            .await
+            .unwrap();
+        for nonce in [1, 2] {
+            let share = Share {
+                job_id: "testjob".to_string(),
+                nonce,
+                time: 0x65432100,
+                version: Version::from_consensus(0x20000000),
+                extranonce2: None,
+            };
+            source
+                .handle_command(SourceCommand::SubmitShare(share), &client_command_tx)
+                .await;
+        }
+        assert!(client_command_rx.try_recv().is_err());
+        assert_eq!(source.queue.len(), 2);
+
+        // The pool resumes the session, so the shares are still good
+        source
+            .handle_client_event(ClientEvent::Subscribed {
+                extranonce1,
+                extranonce2_size: 4,
+                session_id: Some("ae6812eb".into()),
+            })
+            .await
+            .unwrap();
+        source.resubmit_queued(&client_command_tx).await;
+        for nonce in [1, 2] {
+            let Ok(ClientCommand::SubmitShare(params)) = client_command_rx.try_recv() else {
+                panic!("expected a share submission");
+            };
+            assert_eq!(params.nonce, nonce);
+        }
+        assert!(source.queue.is_empty());
+        assert_eq!(source.queue.session_id(), Some("ae6812eb"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_flooding_pool_suggests_higher_difficulty() {
+        let mut source = source_with_state(vec![0x08, 0x00, 0x00, 0x02], 4, None, None)
+            .with_target_share_rate(ShareRate::per_minute(6.0));
+        let (client_command_tx, mut client_command_rx) = mpsc::channel(100);
+        source
+            .handle_client_event(ClientEvent::DifficultyChanged(1000.0))
+            .await
+            .unwrap();
+
+        // A share a second is ten times the target rate
+        for nonce in 0..30 {
+            tokio::time::advance(Duration::from_secs(1)).await;
+            let share = Share {
+                job_id: "testjob".to_string(),
+                nonce,
+                time: 0x65432100,
+                version: Version::from_consensus(0x20000000),
+                extranonce2: None,
+            };
+            source
+                .handle_command(SourceCommand::SubmitShare(share), &client_command_tx)
+                .await;
+        }
+
+        let mut suggestions = Vec::new();
+        while let Ok(cmd) = client_command_rx.try_recv() {
+            if let ClientCommand::SuggestDifficulty(difficulty) = cmd {
+                suggestions.push(difficulty);
+            }
+        }
+        assert_eq!(suggestions, [10_000]);
+    }
+
+    #[tokio::test]
+    async fn test_queued_shares_dropped_for_new_session() {
+        let mut source = source_with_state(vec![0x08, 0x00, 0x00, 0x02], 4, None, None);
+        let (event_tx, _event_rx) = mpsc::channel(10);
+        source.event_tx = event_tx;
+        let (client_command_tx, mut client_command_rx) = mpsc::channel(10);
+
+        let params = source
+            .share_to_submit_params(Share {
+                job_id: "testjob".to_string(),
+                nonce: 1,
+                time: 0x65432100,
+                version: Version::from_consensus(0x20000000),
+                extranonce2: None,
+            })
+            .unwrap();
+        source
+            .handle_client_event(ClientEvent::SubmitFailed(params))
+            .await
+            .unwrap();
+        source
+            .handle_client_event(ClientEvent::Disconnected)
+            .await
+            .unwrap();
+        assert_eq!(source.queue.len(), 1);
+
+        // A new extranonce1 means the pool started a new session
+        source
+            .handle_client_event(ClientEvent::Subscribed {
+                extranonce1: vec![0x08, 0x00, 0x00, 0x03],
+                extranonce2_size: 4,
+                session_id: None,
+            })
+            .await
+            .unwrap();
+        source.resubmit_queued(&client_command_tx).await;
+        assert!(client_command_rx.try_recv().is_err());
+        assert!(source.queue.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_round_trip_through_mock_pool() {
+        use crate::stratum_v1::mock_pool::{MockPool, Verdict};
+
+        // Small enough a difficulty that any header meets it
+        let mut pool = MockPool::new()
+            .with_difficulty(1e-12)
+            .with_vardiff(2, 4.0)
+            .with_rejects(3, 21, "Stale job")
+            .start()
+            .await;
+        let config = PoolConfig {
+            url: pool.url().to_string(),
+            username: "mock.worker".to_string(),
+            password: "x".to_string(),
+            user_agent: "test".to_string(),
+            suggested_difficulty: None,
+        };
+        let (event_tx, mut event_rx) = mpsc::channel(10);
+        let (command_tx, command_rx) = mpsc::channel(10);
+        let shutdown = CancellationToken::new();
+        let mut source = StratumV1Source::new(config, command_rx, event_tx, shutdown.clone());
+        let status = source.status();
+        let running = tokio::spawn(async move { source.run().await });
+
+        let next_event = async |event_rx: &mut mpsc::Receiver<SourceEvent>| {
+            tokio::time::timeout(Duration::from_secs(10), event_rx.recv())
+                .await
+                .expect("no event from source")
+                .unwrap()
+        };
+        let SourceEvent::ReplaceJob(job) = next_event(&mut event_rx).await else {
+            panic!("expected the pool's first job to replace all work");
+        };
+        let MerkleRootKind::Computed(merkle) = &job.merkle_root else {
+            panic!("expected a coinbase to roll extranonce2 in");
+        };
+        let extranonce2 = merkle.extranonce2_range().iter().nth(7).unwrap();
+
+        for nonce in 1..=3 {
+            let share = Share {
+                job_id: job.id.clone(),
+                nonce,
+                time: job.time + 1,
+                version: job.version.base(),
+                extranonce2: Some(extranonce2),
+            };
+            command_tx
+                .send(SourceCommand::SubmitShare(share))
+                .await
+                .unwrap();
+        }
+
+        for nonce in 1..=3 {
+            let submission = pool.next_submission().await;
+            let expected = if nonce == 3 {
+                Verdict::Refused {
+                    code: 21,
+                    message: "Stale job",
+                }
+            } else {
+                Verdict::Accepted
+            };
+            assert_eq!(submission.verdict, expected);
+            assert_eq!(submission.difficulty, 1e-12);
+            assert_eq!(submission.params.unwrap().nonce, nonce);
+            assert_eq!(
+                submission.raw,
+                [
+                    json!("mock.worker"),
+                    json!(job.id),
+                    json!("07000000"),
+                    json!(format!("{:08x}", job.time + 1)),
+                    json!(format!("{nonce:08x}")),
+                    json!("00000000"),
+                ]
+            );
+        }
+
+        // Two accepted shares raise the difficulty for the next job
+        let SourceEvent::UpdateJob(next) = next_event(&mut event_rx).await else {
+            panic!("expected a job at the new difficulty");
+        };
+        assert_eq!(next.share_target, Difficulty::from_f64(4e-12).to_target());
+
+        let mut status = status;
+        tokio::time::timeout(
+            Duration::from_secs(10),
+            status.wait_for(|status| status.accepted + status.rejected == 3),
+        )
+        .await
+        .expect("pool's answers never counted")
+        .unwrap();
+        let counted = *status.borrow();
+        assert_eq!((counted.accepted, counted.rejected), (2, 1));
+        assert_eq!(counted.rejections.stale, 1);
+        assert_eq!(counted.difficulty, Some(4e-12));
+
+        shutdown.cancel();
+        running.await.unwrap().unwrap();
+    }
 }
@@ -1,18 +1,32 @@
+pub mod alert;
+#[cfg(feature = "api")]
 pub mod api;
 pub mod api_client;
 pub mod asic;
 pub mod backplane;
+pub mod benchmark;
 pub mod board;
 pub mod config;
 pub mod cpu_miner;
 pub mod daemon;
 pub mod error;
+pub mod firmware;
+pub mod found_block;
 pub mod hw_trait;
 pub mod job_source;
 pub mod mgmt_protocol;
 pub mod peripheral;
+pub mod pools;
+pub mod power;
+pub mod proxy;
+pub mod runtime;
+pub mod schedule;
 pub mod scheduler;
+pub mod secret;
+pub mod settings;
 pub mod stratum_v1;
+pub mod supervisor;
+pub mod systemd;
 pub mod tracing;
 pub mod transport;
 pub mod types;
@@ -1,19 +1,46 @@
 //! Control channel for bitaxe-raw protocol.
 //!
 //! This module provides a control channel abstraction that handles
-//! packet ID management and request/response correlation.
+//! packet ID management and request/response correlation, and times every
+//! exchange.
+//!
+//! Requests don't wait for each other. Each is given a packet ID not already
+//! in flight and written as soon as the port is free; a reader task hands
+//! each response to the request with its ID. Components sharing a board's
+//! controller, like the fan loop and the regulator, can therefore have
+//! requests outstanding at once instead of queueing behind each other's
+//! round trips.
+//!
+//! Packets the firmware sends unasked, under the reserved notification ID,
+//! go to whoever subscribed to [`ControlChannel::notifications`] rather than
+//! to a request.
 
 use futures::SinkExt;
+use parking_lot::Mutex as SyncMutex;
+use std::collections::HashMap;
 use std::io;
+use std::pin::Pin;
 use std::sync::Arc;
-use std::time::Duration;
-use tokio::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::{broadcast, oneshot, Mutex};
+use tokio::task::JoinHandle;
 use tokio::time;
-use tokio_serial::SerialStream;
 use tokio_stream::StreamExt;
 use tokio_util::codec::{FramedRead, FramedWrite};
+use tracing::{debug, info, warn};
+
+use super::{ControlCodec, ErrorCode, Notification, Packet, Response, NOTIFICATION_ID};
+use crate::hw_trait::latency::{LatencyHistogram, LatencyStats, Outcome};
 
-use super::{ControlCodec, Packet, Response};
+/// How long a request waits for its response.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Notifications kept for a subscriber that has fallen behind.
+const NOTIFICATION_CAPACITY: usize = 16;
+
+type Reader = FramedRead<Pin<Box<dyn AsyncRead + Send>>, ControlCodec>;
+type Writer = FramedWrite<Pin<Box<dyn AsyncWrite + Send>>, ControlCodec>;
 
 /// Control channel for bitaxe-raw protocol communication.
 ///
@@ -21,74 +48,432 @@ use super::{ControlCodec, Packet, Response};
 /// It can be cloned to allow multiple components to share the same channel.
 #[derive(Clone)]
 pub struct ControlChannel {
-    inner: Arc<Mutex<ControlChannelInner>>,
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    writer: Mutex<Writer>,
+    requests: Arc<SyncMutex<Requests>>,
+    /// Task reading responses from the current stream
+    reader: SyncMutex<JoinHandle<()>>,
+    notifications: broadcast::Sender<Notification>,
+    stats: LatencyStats,
 }
 
-struct ControlChannelInner {
-    writer: FramedWrite<tokio::io::WriteHalf<SerialStream>, ControlCodec>,
-    reader: FramedRead<tokio::io::ReadHalf<SerialStream>, ControlCodec>,
+/// Requests awaiting their responses, by packet ID.
+#[derive(Default)]
+struct Requests {
     next_id: u8,
+    waiting: HashMap<u8, oneshot::Sender<io::Result<Response>>>,
+    /// Counts streams, so a reader replaced by a reconnect can't answer
+    /// requests made on the new one
+    generation: u64,
+    /// Whether the stream has ended
+    closed: bool,
+}
+
+/// Forgets a request when its caller stops waiting, so its ID can be
+/// reused.
+struct Pending<'a> {
+    requests: &'a SyncMutex<Requests>,
+    id: u8,
+    generation: u64,
 }
 
 impl ControlChannel {
     /// Create a new control channel from a serial stream.
-    pub fn new(stream: SerialStream) -> Self {
-        let (reader, writer) = tokio::io::split(stream);
+    ///
+    /// Must be called within a Tokio runtime, which runs the reader task.
+    pub fn new(stream: impl AsyncRead + AsyncWrite + Send + 'static) -> Self {
+        let (reader, writer) = split(stream);
+        let requests = Arc::new(SyncMutex::new(Requests::default()));
+        let notifications = broadcast::channel(NOTIFICATION_CAPACITY).0;
+        let reader = tokio::spawn(read_responses(
+            reader,
+            requests.clone(),
+            notifications.clone(),
+            0,
+        ));
         Self {
-            inner: Arc::new(Mutex::new(ControlChannelInner {
-                writer: FramedWrite::new(writer, ControlCodec::default()),
-                reader: FramedRead::new(reader, ControlCodec::default()),
-                next_id: 0,
-            })),
+            shared: Arc::new(Shared {
+                writer: Mutex::new(writer),
+                requests,
+                reader: SyncMutex::new(reader),
+                notifications,
+                stats: LatencyStats::default(),
+            }),
         }
     }
 
-    /// Send a raw packet and wait for response.
-    pub async fn send_packet(&self, mut packet: Packet) -> io::Result<Response> {
-        let mut inner = self.inner.lock().await;
+    /// Carry on over `stream`, the port the controller came back on after a
+    /// disconnect. Every clone of the channel moves with it; requests still
+    /// waiting on the old port fail.
+    pub async fn reconnect(&self, stream: impl AsyncRead + AsyncWrite + Send + 'static) {
+        let (reader, writer) = split(stream);
+        let mut current = self.shared.writer.lock().await;
+        *current = writer;
 
-        // Assign packet ID
-        packet.id = inner.next_id;
-        inner.next_id = inner.next_id.wrapping_add(1);
-        let expected_id = packet.id;
+        let generation = {
+            let mut requests = self.shared.requests.lock();
+            requests.fail_all(io::ErrorKind::ConnectionReset, "Control port reconnected");
+            requests.generation += 1;
+            requests.next_id = 0;
+            requests.closed = false;
+            requests.generation
+        };
+        let task = tokio::spawn(read_responses(
+            reader,
+            self.shared.requests.clone(),
+            self.shared.notifications.clone(),
+            generation,
+        ));
+        std::mem::replace(&mut *self.shared.reader.lock(), task).abort();
+    }
 
-        // Send the packet (logging happens in encoder)
-        inner.writer.send(packet).await?;
-
-        // Wait for response with matching ID
-        let timeout = Duration::from_secs(1);
-        let response = time::timeout(timeout, async {
-            match inner.reader.next().await {
-                Some(Ok(resp)) => {
-                    if resp.id != expected_id {
-                        return Err(io::Error::new(
-                            io::ErrorKind::InvalidData,
-                            format!(
-                                "Response ID mismatch: expected {}, got {}",
-                                expected_id, resp.id
-                            ),
-                        ));
-                    }
-                    Ok(resp)
-                }
-                Some(Err(e)) => Err(e),
-                None => Err(io::Error::new(
-                    io::ErrorKind::UnexpectedEof,
-                    "Control stream closed",
-                )),
-            }
-        })
-        .await
-        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "Control command timeout"))??;
+    /// Notifications the firmware sends from now on, across reconnects.
+    pub fn notifications(&self) -> broadcast::Receiver<Notification> {
+        self.shared.notifications.subscribe()
+    }
 
-        // Check for protocol errors
+    /// How long exchanges have taken, waiting for the channel included, and
+    /// how many timed out or failed.
+    pub fn latency(&self) -> LatencyHistogram {
+        self.shared.stats.snapshot()
+    }
+
+    /// Send a raw packet and wait for response, failing on an error
+    /// response.
+    pub async fn send_packet(&self, packet: Packet) -> io::Result<Response> {
+        let response = self.exchange(packet).await?;
         if let Some(error) = response.error() {
             return Err(io::Error::other(format!(
                 "Control protocol error: {:?}",
                 error
             )));
         }
-
         Ok(response)
     }
+
+    /// Send a raw packet and wait for response, error responses included.
+    pub async fn exchange(&self, packet: Packet) -> io::Result<Response> {
+        let started = Instant::now();
+        let result = self.exchange_untimed(packet).await;
+        let outcome = match &result {
+            Ok(response) => match response.error() {
+                None => Outcome::Ok,
+                Some(error) if error.code == ErrorCode::Timeout => Outcome::Timeout,
+                Some(_) => Outcome::Error,
+            },
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => Outcome::Timeout,
+            Err(_) => Outcome::Error,
+        };
+        self.shared.stats.record(started.elapsed(), outcome);
+        result
+    }
+
+    async fn exchange_untimed(&self, mut packet: Packet) -> io::Result<Response> {
+        let (tx, rx) = oneshot::channel();
+        let (id, generation) = self.shared.requests.lock().register(tx)?;
+        let pending = Pending {
+            requests: &self.shared.requests,
+            id,
+            generation,
+        };
+        packet.id = pending.id;
+
+        // Send the packet (logging happens in encoder)
+        self.shared.writer.lock().await.send(packet).await?;
+
+        match time::timeout(RESPONSE_TIMEOUT, rx).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Control stream closed",
+            )),
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "Control command timeout",
+            )),
+        }
+    }
+}
+
+impl Drop for Shared {
+    fn drop(&mut self) {
+        self.reader.get_mut().abort();
+    }
+}
+
+impl Requests {
+    /// Wait for a response on the next free packet ID, never the one
+    /// notifications come under.
+    fn register(&mut self, tx: oneshot::Sender<io::Result<Response>>) -> io::Result<(u8, u64)> {
+        if self.closed {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Control stream closed",
+            ));
+        }
+        let id = (0..=u8::MAX)
+            .map(|offset| self.next_id.wrapping_add(offset))
+            .find(|id| *id != NOTIFICATION_ID && !self.waiting.contains_key(id))
+            .ok_or_else(|| io::Error::other("Every control packet ID is in flight"))?;
+        self.next_id = id.wrapping_add(1);
+        self.waiting.insert(id, tx);
+        Ok((id, self.generation))
+    }
+
+    /// The request waiting on `id`, if the response came from the current
+    /// stream.
+    fn take(&mut self, generation: u64, id: u8) -> Option<oneshot::Sender<io::Result<Response>>> {
+        if generation != self.generation {
+            return None;
+        }
+        self.waiting.remove(&id)
+    }
+
+    /// Fail every waiting request.
+    fn fail_all(&mut self, kind: io::ErrorKind, message: &str) {
+        for (_, tx) in self.waiting.drain() {
+            let _ = tx.send(Err(io::Error::new(kind, message.to_string())));
+        }
+    }
+}
+
+impl Drop for Pending<'_> {
+    fn drop(&mut self) {
+        let mut requests = self.requests.lock();
+        if requests.generation == self.generation {
+            requests.waiting.remove(&self.id);
+        }
+    }
+}
+
+/// Split a stream into the channel's framed halves.
+fn split(stream: impl AsyncRead + AsyncWrite + Send + 'static) -> (Reader, Writer) {
+    let (reader, writer) = tokio::io::split(stream);
+    let reader: Pin<Box<dyn AsyncRead + Send>> = Box::pin(reader);
+    let writer: Pin<Box<dyn AsyncWrite + Send>> = Box::pin(writer);
+    (
+        FramedRead::new(reader, ControlCodec::default()),
+        FramedWrite::new(writer, ControlCodec::default()),
+    )
+}
+
+/// Hand each response read from the `generation`th stream to the request
+/// waiting on its ID, and each notification to the subscribers, until the
+/// stream ends.
+async fn read_responses(
+    mut reader: Reader,
+    requests: Arc<SyncMutex<Requests>>,
+    notifications: broadcast::Sender<Notification>,
+    generation: u64,
+) {
+    // After a decoding error the codec ends the stream once, then reads on
+    let mut errored = false;
+    loop {
+        match reader.next().await {
+            Some(Ok(response)) if response.id == NOTIFICATION_ID => {
+                errored = false;
+                let notification = Notification::parse(&response.data);
+                match notification {
+                    Notification::Reset { reason } => {
+                        // Whatever was in flight died with the firmware
+                        warn!(reason, "Management controller reset");
+                        let mut requests = requests.lock();
+                        if requests.generation == generation {
+                            requests.fail_all(
+                                io::ErrorKind::ConnectionReset,
+                                "Management controller reset",
+                            );
+                        }
+                    }
+                    _ => info!(?notification, "Control notification"),
+                }
+                let _ = notifications.send(notification);
+            }
+            Some(Ok(response)) => {
+                errored = false;
+                let id = response.id;
+                match requests.lock().take(generation, id) {
+                    Some(tx) => {
+                        let _ = tx.send(Ok(response));
+                    }
+                    None => debug!(id, "Control response with no request waiting, dropped"),
+                }
+            }
+            Some(Err(e)) => {
+                // Whichever response this was is lost; fail everything in
+                // flight rather than leave it to time out
+                errored = true;
+                warn!(error = %e, "Undecodable control response");
+                let mut requests = requests.lock();
+                if requests.generation == generation {
+                    requests.fail_all(e.kind(), &e.to_string());
+                }
+            }
+            None if errored => errored = false,
+            None => {
+                let mut requests = requests.lock();
+                if requests.generation == generation {
+                    requests.fail_all(io::ErrorKind::UnexpectedEof, "Control stream closed");
+                    requests.closed = true;
+                }
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+
+    use super::*;
+    use crate::mgmt_protocol::bitaxe_raw::Page;
+
+    /// The ID of the next packet the host wrote.
+    async fn read_request(controller: &mut DuplexStream) -> u8 {
+        let length = controller.read_u16_le().await.unwrap();
+        let mut rest = vec![0u8; usize::from(length) - 2];
+        controller.read_exact(&mut rest).await.unwrap();
+        rest[0]
+    }
+
+    async fn respond(controller: &mut DuplexStream, id: u8, data: &[u8]) {
+        let mut frame = (data.len() as u16).to_le_bytes().to_vec();
+        frame.push(id);
+        frame.extend_from_slice(data);
+        controller.write_all(&frame).await.unwrap();
+    }
+
+    fn read_vdd() -> Packet {
+        Packet::new(0, Page::ADC, 0x50, vec![])
+    }
+
+    #[tokio::test]
+    async fn test_responses_reach_their_requests_out_of_order() {
+        let (host, mut controller) = tokio::io::duplex(256);
+        let channel = ControlChannel::new(host);
+
+        let first = tokio::spawn({
+            let channel = channel.clone();
+            async move { channel.send_packet(read_vdd()).await }
+        });
+        let first_id = read_request(&mut controller).await;
+        let second = tokio::spawn({
+            let channel = channel.clone();
+            async move { channel.send_packet(read_vdd()).await }
+        });
+        let second_id = read_request(&mut controller).await;
+        assert_ne!(first_id, second_id);
+
+        // Answer the second request first, after a stray response nobody
+        // is waiting on
+        respond(&mut controller, 0x7F, &[0xEE]).await;
+        respond(&mut controller, second_id, &[0x02]).await;
+        respond(&mut controller, first_id, &[0x01]).await;
+
+        assert_eq!(second.await.unwrap().unwrap().data, [0x02]);
+        assert_eq!(first.await.unwrap().unwrap().data, [0x01]);
+        assert_eq!(channel.latency().count, 2);
+    }
+
+    #[tokio::test]
+    async fn test_notifications_between_responses() {
+        let (host, mut controller) = tokio::io::duplex(256);
+        let channel = ControlChannel::new(host);
+        let mut notifications = channel.notifications();
+
+        let request = tokio::spawn({
+            let channel = channel.clone();
+            async move { channel.send_packet(read_vdd()).await }
+        });
+        let id = read_request(&mut controller).await;
+
+        // A button press lands before the answer, which still gets through
+        respond(&mut controller, NOTIFICATION_ID, &[0x01, 0x00, 0x64, 0x00]).await;
+        respond(&mut controller, id, &[0x01]).await;
+        assert_eq!(request.await.unwrap().unwrap().data, [0x01]);
+        assert_eq!(
+            notifications.recv().await.unwrap(),
+            Notification::ButtonPressed {
+                button: 0,
+                held_ms: 100
+            }
+        );
+
+        // A reset means no answer is coming for what's in flight
+        let stranded = tokio::spawn({
+            let channel = channel.clone();
+            async move { channel.send_packet(read_vdd()).await }
+        });
+        read_request(&mut controller).await;
+        respond(&mut controller, NOTIFICATION_ID, &[0x03, 0x01]).await;
+        let error = stranded.await.unwrap().unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::ConnectionReset);
+        assert_eq!(
+            notifications.recv().await.unwrap(),
+            Notification::Reset { reason: 1 }
+        );
+    }
+
+    #[test]
+    fn test_requests_never_get_the_notification_id() {
+        let mut requests = Requests {
+            next_id: NOTIFICATION_ID,
+            ..Default::default()
+        };
+        let (id, _) = requests.register(oneshot::channel().0).unwrap();
+        assert_eq!(id, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_timed_out_request_frees_its_id() {
+        let (host, mut controller) = tokio::io::duplex(256);
+        let channel = ControlChannel::new(host);
+
+        let error = channel.send_packet(read_vdd()).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        let late_id = read_request(&mut controller).await;
+
+        // The late answer is dropped, and the channel carries on
+        respond(&mut controller, late_id, &[0x01]).await;
+        let next = tokio::spawn({
+            let channel = channel.clone();
+            async move { channel.send_packet(read_vdd()).await }
+        });
+        let next_id = read_request(&mut controller).await;
+        respond(&mut controller, next_id, &[0x02]).await;
+        assert_eq!(next.await.unwrap().unwrap().data, [0x02]);
+
+        let latency = channel.latency();
+        assert_eq!((latency.count, latency.timeouts), (2, 1));
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_fails_requests_in_flight() {
+        let (host, mut controller) = tokio::io::duplex(256);
+        let channel = ControlChannel::new(host);
+
+        let stranded = tokio::spawn({
+            let channel = channel.clone();
+            async move { channel.send_packet(read_vdd()).await }
+        });
+        read_request(&mut controller).await;
+
+        let (host, mut controller) = tokio::io::duplex(256);
+        channel.reconnect(host).await;
+        let error = stranded.await.unwrap().unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::ConnectionReset);
+
+        // IDs start over on the new port
+        let next = tokio::spawn({
+            let channel = channel.clone();
+            async move { channel.send_packet(read_vdd()).await }
+        });
+        assert_eq!(read_request(&mut controller).await, 0);
+        respond(&mut controller, 0, &[0x03]).await;
+        assert_eq!(next.await.unwrap().unwrap().data, [0x03]);
+    }
 }
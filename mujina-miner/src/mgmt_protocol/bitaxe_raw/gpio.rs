@@ -87,3 +87,51 @@ impl GpioPin for BitaxeRawGpioPin {
         Ok(value)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+    use crate::hw_trait::mock::MockSerial;
+
+    #[tokio::test]
+    async fn test_pin_packets() {
+        let serial = MockSerial::new();
+        // Length 7, ID 0, bus 0, GPIO page, pin 0 as the command, high
+        serial
+            .expect_write(&[0x07, 0x00, 0x00, 0x00, 0x06, 0x00, 0x01])
+            .respond_with([0x00, 0x00, 0x00]);
+        // Read of pin 3, which is low
+        serial
+            .expect_write(&[0x06, 0x00, 0x01, 0x00, 0x06, 0x03])
+            .respond_with([0x01, 0x00, 0x01, 0x00]);
+
+        let mut gpio = BitaxeRawGpioController::new(ControlChannel::new(serial.clone()));
+        gpio.pin(0)
+            .await
+            .unwrap()
+            .write(PinValue::High)
+            .await
+            .unwrap();
+        let value = gpio.pin(3).await.unwrap().read().await.unwrap();
+        assert_eq!(value, PinValue::Low);
+        serial.done();
+    }
+
+    #[tokio::test]
+    async fn test_write_failure_reported() {
+        let serial = MockSerial::new();
+        serial
+            .expect_write(&[0x07, 0x00, 0x00, 0x00, 0x06, 0x00, 0x00])
+            .inject_error(io::Error::from(io::ErrorKind::BrokenPipe));
+
+        let mut gpio = BitaxeRawGpioController::new(ControlChannel::new(serial.clone()));
+        let mut pin = gpio.pin(0).await.unwrap();
+        assert!(matches!(
+            pin.write(PinValue::Low).await,
+            Err(HwError::Io(_))
+        ));
+        serial.done();
+    }
+}
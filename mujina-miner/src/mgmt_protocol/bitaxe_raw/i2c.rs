@@ -1,28 +1,37 @@
 //! I2C implementation using bitaxe-raw control protocol.
 
 use async_trait::async_trait;
+use std::io;
 
 use super::channel::ControlChannel;
 use super::{I2CCommand, Packet, Page};
 use crate::hw_trait::i2c::{I2c, I2cError};
+use crate::hw_trait::i2c_trace::{I2cTrace, Transaction};
 use crate::hw_trait::{HwError, Result};
 
 /// I2C bus implementation using bitaxe-raw control protocol.
 #[derive(Clone)]
 pub struct BitaxeRawI2c {
     channel: ControlChannel,
+    trace: Option<I2cTrace>,
 }
 
 impl BitaxeRawI2c {
     /// Create a new I2C bus using the given control channel.
     pub fn new(channel: ControlChannel) -> Self {
-        Self { channel }
+        Self {
+            channel,
+            trace: None,
+        }
     }
-}
 
-#[async_trait]
-impl I2c for BitaxeRawI2c {
-    async fn write(&mut self, addr: u8, data: &[u8]) -> Result<()> {
+    /// Record every transaction on this bus, and its clones, to `trace`.
+    pub fn with_trace(mut self, trace: Option<I2cTrace>) -> Self {
+        self.trace = trace;
+        self
+    }
+
+    async fn write_untraced(&mut self, addr: u8, data: &[u8]) -> Result<()> {
         let packet = Packet::new(
             0, // ID will be assigned by channel
             Page::I2C,
@@ -33,12 +42,12 @@ impl I2c for BitaxeRawI2c {
         self.channel
             .send_packet(packet)
             .await
-            .map_err(|e| HwError::I2c(I2cError::Other(format!("Write failed: {}", e))))?;
+            .map_err(|e| failed("Write", e))?;
 
         Ok(())
     }
 
-    async fn read(&mut self, addr: u8, buffer: &mut [u8]) -> Result<()> {
+    async fn read_untraced(&mut self, addr: u8, buffer: &mut [u8]) -> Result<()> {
         let packet = Packet::new(
             0, // ID will be assigned by channel
             Page::I2C,
@@ -50,7 +59,7 @@ impl I2c for BitaxeRawI2c {
             .channel
             .send_packet(packet)
             .await
-            .map_err(|e| HwError::I2c(I2cError::Other(format!("Read failed: {}", e))))?;
+            .map_err(|e| failed("Read", e))?;
 
         if response.data.len() != buffer.len() {
             return Err(HwError::I2c(I2cError::Other(format!(
@@ -64,7 +73,7 @@ impl I2c for BitaxeRawI2c {
         Ok(())
     }
 
-    async fn write_read(&mut self, addr: u8, write: &[u8], read: &mut [u8]) -> Result<()> {
+    async fn write_read_untraced(&mut self, addr: u8, write: &[u8], read: &mut [u8]) -> Result<()> {
         let mut data = vec![addr];
         data.extend_from_slice(write);
         data.push(read.len() as u8);
@@ -80,7 +89,7 @@ impl I2c for BitaxeRawI2c {
             .channel
             .send_packet(packet)
             .await
-            .map_err(|e| HwError::I2c(I2cError::Other(format!("WriteRead failed: {}", e))))?;
+            .map_err(|e| failed("WriteRead", e))?;
 
         if response.data.len() != read.len() {
             return Err(HwError::I2c(I2cError::Other(format!(
@@ -93,6 +102,33 @@ impl I2c for BitaxeRawI2c {
         read.copy_from_slice(&response.data);
         Ok(())
     }
+}
+
+#[async_trait]
+impl I2c for BitaxeRawI2c {
+    async fn write(&mut self, addr: u8, data: &[u8]) -> Result<()> {
+        let result = self.write_untraced(addr, data).await;
+        if let Some(trace) = &self.trace {
+            trace.record(Transaction::Write { addr, data }, &result);
+        }
+        result
+    }
+
+    async fn read(&mut self, addr: u8, buffer: &mut [u8]) -> Result<()> {
+        let result = self.read_untraced(addr, buffer).await;
+        if let Some(trace) = &self.trace {
+            trace.record(Transaction::Read { addr, data: buffer }, &result);
+        }
+        result
+    }
+
+    async fn write_read(&mut self, addr: u8, write: &[u8], read: &mut [u8]) -> Result<()> {
+        let result = self.write_read_untraced(addr, write, read).await;
+        if let Some(trace) = &self.trace {
+            trace.record(Transaction::WriteRead { addr, write, read }, &result);
+        }
+        result
+    }
 
     async fn set_frequency(&mut self, hz: u32) -> Result<()> {
         let packet = Packet::new(
@@ -105,8 +141,76 @@ impl I2c for BitaxeRawI2c {
         self.channel
             .send_packet(packet)
             .await
-            .map_err(|e| HwError::I2c(I2cError::Other(format!("SetFrequency failed: {}", e))))?;
+            .map_err(|e| failed("SetFrequency", e))?;
 
         Ok(())
     }
 }
+
+/// The error for an I2C command the control channel couldn't carry out.
+/// A controller that never answered is a timeout, so it's counted as one.
+fn failed(command: &str, e: io::Error) -> HwError {
+    if e.kind() == io::ErrorKind::TimedOut {
+        HwError::Timeout
+    } else {
+        HwError::I2c(I2cError::Other(format!("{} failed: {}", command, e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hw_trait::mock::MockSerial;
+
+    #[tokio::test]
+    async fn test_write_read_packet() {
+        let serial = MockSerial::new();
+        // Length 9, ID 0, bus 0, I2C page, WriteRead: address, register,
+        // read length
+        serial
+            .expect_write(&[0x09, 0x00, 0x00, 0x00, 0x05, 0x40, 0x4C, 0xFE, 0x01])
+            .respond_with([0x01, 0x00, 0x00, 0x5D]);
+        // IDs count up; Write: address, data
+        serial
+            .expect_write(&[0x09, 0x00, 0x01, 0x00, 0x05, 0x20, 0x4C, 0x4C, 0x3F])
+            .respond_with([0x00, 0x00, 0x01]);
+
+        let mut i2c = BitaxeRawI2c::new(ControlChannel::new(serial.clone()));
+        let mut id = [0u8; 1];
+        i2c.write_read(0x4C, &[0xFE], &mut id).await.unwrap();
+        assert_eq!(id, [0x5D]);
+        i2c.write(0x4C, &[0x4C, 0x3F]).await.unwrap();
+        serial.done();
+    }
+
+    #[tokio::test]
+    async fn test_error_response_fails_transaction() {
+        let serial = MockSerial::new();
+        // Read of two bytes, answered with a timeout error
+        serial
+            .expect_write(&[0x08, 0x00, 0x00, 0x00, 0x05, 0x30, 0x50, 0x02])
+            .respond_with([0x02, 0x00, 0x00, 0xFF, 0x10]);
+
+        let channel = ControlChannel::new(serial.clone());
+        let mut i2c = BitaxeRawI2c::new(channel.clone());
+        let error = i2c.read(0x50, &mut [0u8; 2]).await.unwrap_err();
+        assert!(matches!(error, HwError::I2c(I2cError::Other(_))), "{error}");
+        serial.done();
+
+        // The controller's bus timed out, which the channel counts
+        let latency = channel.latency();
+        assert_eq!((latency.count, latency.timeouts, latency.errors), (1, 1, 0));
+    }
+
+    #[tokio::test]
+    async fn test_short_response_refused() {
+        let serial = MockSerial::new();
+        serial
+            .expect_write(&[0x08, 0x00, 0x00, 0x00, 0x05, 0x30, 0x50, 0x02])
+            .respond_with([0x01, 0x00, 0x00, 0xAA]);
+
+        let mut i2c = BitaxeRawI2c::new(ControlChannel::new(serial.clone()));
+        assert!(i2c.read(0x50, &mut [0u8; 2]).await.is_err());
+        serial.done();
+    }
+}
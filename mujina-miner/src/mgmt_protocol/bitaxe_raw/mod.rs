@@ -24,6 +24,7 @@
 //!
 //! ## Pages
 //!
+//! - `0x00` - System operations (firmware version and features)
 //! - `0x05` - I2C operations (peripheral communication)
 //! - `0x06` - GPIO operations (ASIC reset, status pins)
 //! - `0x07` - ADC operations (voltage monitoring)
@@ -47,15 +48,23 @@
 //!
 //! Errors are indicated by a response data field starting with `0xFF` followed
 //! by an error code. See [`ErrorCode`] for defined error types.
+//!
+//! ## Notifications
+//!
+//! The firmware may also send packets nobody asked for, such as a button
+//! press or its own restart, between responses. They're framed like
+//! responses under the reserved ID [`NOTIFICATION_ID`], which no request is
+//! given, with a kind byte leading the data. See [`Notification`].
 
 pub mod channel;
 pub mod gpio;
 pub mod i2c;
+pub mod version;
 
 use bytes::{BufMut, BytesMut};
 use std::{fmt, io};
 use tokio_util::codec::{Decoder, Encoder};
-use tracing::trace;
+use tracing::{trace, warn};
 
 /// Wrapper for formatting byte slices as space-separated hex.
 struct HexBytes<'a>(&'a [u8]);
@@ -75,10 +84,16 @@ impl fmt::Display for HexBytes<'_> {
 /// Error response marker
 const ERROR_MARKER: u8 = 0xff;
 
+/// Packet ID the firmware sends notifications under. Never given to a
+/// request, so a notification can't be taken for a response.
+pub const NOTIFICATION_ID: u8 = 0xff;
+
 /// Control protocol pages
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum Page {
+    /// System operations (firmware version)
+    System = 0x00,
     /// I2C operations (EMC2101, TMP75, INA260)
     I2C = 0x05,
     /// GPIO operations (ASIC reset control)
@@ -97,6 +112,14 @@ pub enum I2CCommand {
     WriteRead = 0x40,
 }
 
+/// System commands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum SystemCommand {
+    /// Firmware version and feature bits
+    Version = 0x00,
+}
+
 // Note: For GPIO page, the command byte is the pin number itself
 
 /// ADC commands
@@ -108,28 +131,43 @@ pub enum ADCCommand {
 
 /// Control protocol error codes
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[repr(u8)]
 pub enum ErrorCode {
-    Timeout = 0x10,
-    InvalidCommand = 0x11,
-    BufferOverflow = 0x12,
-    Custom = 0xff,
+    Timeout,
+    InvalidCommand,
+    BufferOverflow,
+    Custom,
+    /// A code this implementation doesn't know, from newer firmware or a
+    /// corrupted packet
+    Unknown(u8),
 }
 
-impl TryFrom<u8> for ErrorCode {
-    type Error = u8;
-
-    fn try_from(value: u8) -> Result<Self, Self::Error> {
+impl From<u8> for ErrorCode {
+    fn from(value: u8) -> Self {
         match value {
-            x if x == Self::Timeout as u8 => Ok(Self::Timeout),
-            x if x == Self::InvalidCommand as u8 => Ok(Self::InvalidCommand),
-            x if x == Self::BufferOverflow as u8 => Ok(Self::BufferOverflow),
-            x if x == Self::Custom as u8 => Ok(Self::Custom),
-            _ => Err(value),
+            0x10 => Self::Timeout,
+            0x11 => Self::InvalidCommand,
+            0x12 => Self::BufferOverflow,
+            0xff => Self::Custom,
+            _ => Self::Unknown(value),
         }
     }
 }
 
+/// Unsolicited packet from the firmware.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Notification {
+    /// A button on the board was pressed and released after `held_ms`
+    ButtonPressed { button: u8, held_ms: u16 },
+    /// An ADC channel crossed its alert threshold
+    AdcAlert { channel: u8, millivolts: u16 },
+    /// The controller restarted; requests in flight won't be answered and
+    /// peripheral state the host set up is gone
+    Reset { reason: u8 },
+    /// A kind this implementation doesn't know, from newer firmware, or one
+    /// too short to read; the data as received
+    Unknown(Vec<u8>),
+}
+
 /// Control protocol packet
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Packet {
@@ -192,6 +230,25 @@ impl Packet {
     }
 }
 
+impl Notification {
+    /// Parse the data of a packet sent under [`NOTIFICATION_ID`]. Never
+    /// fails: anything unreadable is kept as [`Notification::Unknown`].
+    pub fn parse(data: &[u8]) -> Self {
+        match *data {
+            [0x01, button, lo, hi, ..] => Self::ButtonPressed {
+                button,
+                held_ms: u16::from_le_bytes([lo, hi]),
+            },
+            [0x02, channel, lo, hi, ..] => Self::AdcAlert {
+                channel,
+                millivolts: u16::from_le_bytes([lo, hi]),
+            },
+            [0x03, reason, ..] => Self::Reset { reason },
+            _ => Self::Unknown(data.to_vec()),
+        }
+    }
+}
+
 /// Control protocol response
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Response {
@@ -224,14 +281,10 @@ impl Response {
         let id = bytes[0];
         let data = &bytes[1..];
 
-        // Check for error response
+        // Check for error response. An unknown code is still an error in
+        // reply to this ID, so it's kept rather than failing the parse.
         if data.len() >= 2 && data[0] == ERROR_MARKER {
-            let code = ErrorCode::try_from(data[1]).map_err(|unknown| {
-                io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    format!("Unknown error code: 0x{:02x}", unknown),
-                )
-            })?;
+            let code = ErrorCode::from(data[1]);
 
             let message = if code == ErrorCode::Custom && data.len() > 2 {
                 Some(String::from_utf8_lossy(&data[2..]).to_string())
@@ -296,11 +349,18 @@ impl Decoder for ControlCodec {
         // Total packet size = 2 (length) + 1 (ID) + length_field
         let total_packet_size = 2 + 1 + length_field;
 
+        // No packet is this long, so the length is line noise and there's
+        // no telling where the next packet starts. Returning an error would
+        // end the stream for good; instead drop what's buffered, so the
+        // request in flight times out and the next one starts clean.
         if total_packet_size > self.max_length {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!("Packet too large: {} bytes", total_packet_size),
-            ));
+            warn!(
+                length = total_packet_size,
+                dropped = %HexBytes(src),
+                "Control packet length out of range, resynchronizing"
+            );
+            src.clear();
+            return Ok(None);
         }
 
         if src.len() < total_packet_size {
@@ -355,6 +415,7 @@ impl Encoder<Packet> for ControlCodec {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
 
     #[test]
     fn test_gpio_packet_encoding() {
@@ -396,5 +457,71 @@ mod tests {
         assert_eq!(response.id, 0x42);
         assert!(response.is_error());
         assert_eq!(response.error().unwrap().code, ErrorCode::InvalidCommand);
+
+        // Unknown codes still answer their request
+        let response = Response::parse(&[0x42, 0xff, 0x7e]).unwrap();
+        assert_eq!(response.error().unwrap().code, ErrorCode::Unknown(0x7e));
+    }
+
+    #[test]
+    fn test_notification_parsing() {
+        assert_eq!(
+            Notification::parse(&[0x01, 0x00, 0xd0, 0x07]),
+            Notification::ButtonPressed {
+                button: 0,
+                held_ms: 2000
+            }
+        );
+        assert_eq!(
+            Notification::parse(&[0x02, 0x01, 0xb0, 0x04]),
+            Notification::AdcAlert {
+                channel: 1,
+                millivolts: 1200
+            }
+        );
+        assert_eq!(
+            Notification::parse(&[0x03, 0x02]),
+            Notification::Reset { reason: 2 }
+        );
+
+        // Unknown kinds and truncated packets are kept, not errors
+        assert_eq!(
+            Notification::parse(&[0x42, 0x01]),
+            Notification::Unknown(vec![0x42, 0x01])
+        );
+        assert_eq!(
+            Notification::parse(&[0x02, 0x01]),
+            Notification::Unknown(vec![0x02, 0x01])
+        );
+    }
+
+    #[test]
+    fn test_decoder_resynchronizes_after_bad_length() {
+        let mut codec = ControlCodec::default();
+        let mut buf = BytesMut::from(&[0xff, 0xff, 0x01, 0x02][..]);
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+        assert!(buf.is_empty());
+
+        // The next packet decodes normally
+        buf.extend_from_slice(&[0x01, 0x00, 0x07, 0x01]);
+        let response = codec.decode(&mut buf).unwrap().unwrap();
+        assert_eq!((response.id, response.data), (0x07, vec![0x01]));
+    }
+
+    proptest! {
+        #[test]
+        fn prop_decoder_survives_arbitrary_bytes(
+            stream in proptest::collection::vec(any::<u8>(), 0..512),
+            chunk_len in 1usize..64,
+        ) {
+            let mut codec = ControlCodec::default();
+            let mut buf = BytesMut::new();
+
+            for chunk in stream.chunks(chunk_len) {
+                buf.extend_from_slice(chunk);
+                // Never an error, which would end the stream
+                while codec.decode(&mut buf).unwrap().is_some() {}
+            }
+        }
     }
 }
@@ -0,0 +1,154 @@
+//! Firmware version and capability negotiation.
+//!
+//! Firmware that knows the system page answers VERSION with what it is and
+//! which optional features it has. Firmware from before the system page
+//! refuses the command as invalid; it speaks the original protocol, which
+//! [`FirmwareInfo::LEGACY`] describes. Features a board can do without are
+//! used only when the firmware reports them, and firmware older than
+//! [`MIN_VERSION`] isn't driven at all.
+
+use std::fmt;
+use std::io;
+
+use bitflags::bitflags;
+
+use super::channel::ControlChannel;
+use super::{ErrorCode, Packet, Page, SystemCommand};
+
+/// Oldest firmware this driver works with.
+pub const MIN_VERSION: FirmwareVersion = FirmwareVersion::new(1, 0, 0);
+
+/// A firmware release.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FirmwareVersion {
+    pub major: u8,
+    pub minor: u8,
+    pub patch: u8,
+}
+
+impl FirmwareVersion {
+    pub const fn new(major: u8, minor: u8, patch: u8) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+}
+
+impl fmt::Display for FirmwareVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+bitflags! {
+    /// What the firmware can do, beyond what every version can.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Features: u16 {
+        /// I2C page
+        const I2C = 1 << 0;
+        /// GPIO page
+        const GPIO = 1 << 1;
+        /// ADC page
+        const ADC = 1 << 2;
+        /// The ASIC UART follows the data port's line coding
+        const BAUD_CHANGE = 1 << 3;
+        /// WS2812 status LED control
+        const WS2812 = 1 << 4;
+    }
+}
+
+/// What a board's firmware reported about itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FirmwareInfo {
+    pub version: FirmwareVersion,
+    pub features: Features,
+}
+
+impl FirmwareInfo {
+    /// Firmware from before VERSION: the pages it has, and the UART
+    /// retuning boards have always relied on.
+    pub const LEGACY: Self = Self {
+        version: FirmwareVersion::new(1, 0, 0),
+        features: Features::I2C
+            .union(Features::GPIO)
+            .union(Features::ADC)
+            .union(Features::BAUD_CHANGE),
+    };
+
+    /// Whether the firmware has every feature in `features`.
+    pub fn supports(&self, features: Features) -> bool {
+        self.features.contains(features)
+    }
+
+    /// Parse a VERSION response: major, minor, patch, then the feature
+    /// bits, little-endian. Later firmware may append more.
+    fn parse(data: &[u8]) -> io::Result<Self> {
+        let [major, minor, patch, lo, hi, ..] = *data else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("VERSION response too short: {} bytes", data.len()),
+            ));
+        };
+        Ok(Self {
+            version: FirmwareVersion::new(major, minor, patch),
+            features: Features::from_bits_truncate(u16::from_le_bytes([lo, hi])),
+        })
+    }
+}
+
+/// Ask the firmware what it is.
+pub async fn query(channel: &ControlChannel) -> io::Result<FirmwareInfo> {
+    let packet = Packet::new(0, Page::System, SystemCommand::Version as u8, Vec::new());
+    let response = channel.exchange(packet).await?;
+    match response.error() {
+        None => FirmwareInfo::parse(&response.data),
+        Some(error) if error.code == ErrorCode::InvalidCommand => Ok(FirmwareInfo::LEGACY),
+        Some(error) => Err(io::Error::other(format!(
+            "Control protocol error: {:?}",
+            error
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hw_trait::mock::MockSerial;
+
+    /// VERSION request with ID 0
+    const VERSION_REQUEST: [u8; 6] = [0x06, 0x00, 0x00, 0x00, 0x00, 0x00];
+
+    #[tokio::test]
+    async fn test_reported_version_and_features() {
+        let serial = MockSerial::new();
+        serial
+            .expect_write(&VERSION_REQUEST)
+            .respond_with([0x05, 0x00, 0x00, 0x01, 0x02, 0x03, 0x13, 0x00]);
+
+        let info = query(&ControlChannel::new(serial.clone())).await.unwrap();
+        assert_eq!(info.version, FirmwareVersion::new(1, 2, 3));
+        assert!(info.supports(Features::I2C | Features::GPIO | Features::WS2812));
+        assert!(!info.supports(Features::BAUD_CHANGE));
+        serial.done();
+    }
+
+    #[tokio::test]
+    async fn test_firmware_without_version_is_legacy() {
+        let serial = MockSerial::new();
+        serial
+            .expect_write(&VERSION_REQUEST)
+            .respond_with([0x02, 0x00, 0x00, 0xFF, 0x11]);
+
+        let info = query(&ControlChannel::new(serial.clone())).await.unwrap();
+        assert_eq!(info, FirmwareInfo::LEGACY);
+        assert!(info.version >= MIN_VERSION);
+        serial.done();
+    }
+
+    #[test]
+    fn test_short_response_refused() {
+        assert!(FirmwareInfo::parse(&[1, 2, 3]).is_err());
+    }
+}
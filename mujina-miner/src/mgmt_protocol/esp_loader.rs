@@ -0,0 +1,482 @@
+//! ESP32 ROM serial loader protocol, the one esptool speaks.
+//!
+//! Bitaxe-raw runs on an ESP32-S3. Reset with its boot strap held, the chip
+//! starts the loader in its mask ROM instead of the firmware, and the loader
+//! takes commands over USB serial to write flash. This module is the host
+//! side of that: enough of the protocol to sync, attach the SPI flash, write
+//! an image and leave the loader.
+//!
+//! # Framing
+//!
+//! Packets are SLIP-framed: each starts and ends with `0xC0`, and inside a
+//! packet `0xC0` is sent as `0xDB 0xDC` and `0xDB` as `0xDB 0xDD`.
+//!
+//! ```text
+//! Request:  [0x00] [Op:1] [Size:2 LE] [Checksum:4 LE] [Data:Size]
+//! Response: [0x01] [Op:1] [Size:2 LE] [Value:4 LE] [Data:Size]
+//! ```
+//!
+//! The checksum only matters for `FLASH_DATA`, where it's the XOR of the
+//! written bytes seeded with `0xEF`. A response's data ends in status bytes:
+//! a status (0 for success) and an error code. The ROM sends four, of which
+//! the last two are unused; the commands used here return no other data, so
+//! the status is always the first byte.
+//!
+//! # Flashing
+//!
+//! `FLASH_BEGIN` erases the region and says how many blocks follow, each
+//! `FLASH_DATA` writes one [`FLASH_BLOCK_SIZE`] block (the last padded with
+//! `0xFF`), and `FLASH_END` finishes. The ESP32-S3 loader takes a fifth
+//! `FLASH_BEGIN` word, for flash encryption, that the original ESP32's
+//! doesn't.
+
+use bytes::{Buf, BufMut, BytesMut};
+use futures::{SinkExt, StreamExt};
+use std::io;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::time;
+use tokio_util::codec::{Decoder, Encoder, Framed};
+use tracing::{debug, trace};
+
+/// Espressif's USB vendor ID, used by the ROM loader's USB serial port.
+pub const ESPRESSIF_VID: u16 = 0x303a;
+
+/// Bytes written per `FLASH_DATA` command; the ROM loader's block size.
+pub const FLASH_BLOCK_SIZE: usize = 0x400;
+
+/// SLIP frame delimiter and escapes.
+const SLIP_END: u8 = 0xc0;
+const SLIP_ESC: u8 = 0xdb;
+const SLIP_ESC_END: u8 = 0xdc;
+const SLIP_ESC_ESC: u8 = 0xdd;
+
+/// Seed of the `FLASH_DATA` checksum.
+const CHECKSUM_SEED: u8 = 0xef;
+
+/// Direction bytes that start requests and responses.
+const REQUEST: u8 = 0x00;
+const RESPONSE: u8 = 0x01;
+
+/// Sync attempts before giving up on the loader.
+const SYNC_ATTEMPTS: u32 = 10;
+
+/// How long the loader gets to answer a sync.
+const SYNC_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// How long the loader gets to answer most commands.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Erase time allowed per megabyte at `FLASH_BEGIN`, as esptool allows.
+const ERASE_TIMEOUT_PER_MB: Duration = Duration::from_secs(30);
+
+/// Loader commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Op {
+    FlashBegin = 0x02,
+    FlashData = 0x03,
+    FlashEnd = 0x04,
+    Sync = 0x08,
+    SpiAttach = 0x0d,
+}
+
+/// Errors talking to the ROM loader.
+#[derive(Debug, thiserror::Error)]
+pub enum LoaderError {
+    #[error("loader I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("no answer from the loader after {attempts} sync attempts")]
+    NoSync { attempts: u32 },
+
+    #[error("loader didn't answer {op:?} within {timeout:?}")]
+    Timeout { op: Op, timeout: Duration },
+
+    #[error("loader connection closed")]
+    Closed,
+
+    #[error("loader failed {op:?} with error {error:#04x}")]
+    Failed { op: Op, error: u8 },
+
+    #[error("image of {size} bytes doesn't fit the loader's 32-bit flash addresses")]
+    TooLarge { size: usize },
+}
+
+/// Codec for SLIP-framed packets.
+///
+/// Bytes outside frames, such as the boot messages the chip prints before
+/// the loader takes over, are dropped.
+#[derive(Debug, Default)]
+pub struct SlipCodec {
+    /// Inside a frame, having seen its opening delimiter
+    in_frame: bool,
+}
+
+impl Decoder for SlipCodec {
+    type Item = Vec<u8>;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        loop {
+            if !self.in_frame {
+                let Some(start) = src.iter().position(|&b| b == SLIP_END) else {
+                    src.clear();
+                    return Ok(None);
+                };
+                src.advance(start + 1);
+                self.in_frame = true;
+            }
+
+            let Some(end) = src.iter().position(|&b| b == SLIP_END) else {
+                return Ok(None);
+            };
+            let raw = src.split_to(end + 1);
+            let raw = &raw[..end];
+            if raw.is_empty() {
+                // Back-to-back delimiters: this one opens the next frame
+                continue;
+            }
+            self.in_frame = false;
+
+            let mut frame = Vec::with_capacity(raw.len());
+            let mut bytes = raw.iter();
+            while let Some(&b) = bytes.next() {
+                frame.push(match (b, bytes.clone().next()) {
+                    (SLIP_ESC, Some(&SLIP_ESC_END)) => {
+                        bytes.next();
+                        SLIP_END
+                    }
+                    (SLIP_ESC, Some(&SLIP_ESC_ESC)) => {
+                        bytes.next();
+                        SLIP_ESC
+                    }
+                    (SLIP_ESC, _) => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "invalid SLIP escape",
+                        ))
+                    }
+                    (b, _) => b,
+                });
+            }
+            return Ok(Some(frame));
+        }
+    }
+}
+
+impl Encoder<Vec<u8>> for SlipCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, frame: Vec<u8>, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        dst.reserve(frame.len() + 2);
+        dst.put_u8(SLIP_END);
+        for b in frame {
+            match b {
+                SLIP_END => dst.put_slice(&[SLIP_ESC, SLIP_ESC_END]),
+                SLIP_ESC => dst.put_slice(&[SLIP_ESC, SLIP_ESC_ESC]),
+                b => dst.put_u8(b),
+            }
+        }
+        dst.put_u8(SLIP_END);
+        Ok(())
+    }
+}
+
+/// Host side of a connection to the ROM loader.
+pub struct EspLoader<S> {
+    framed: Framed<S, SlipCodec>,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> EspLoader<S> {
+    /// Talk to the loader over `port`, already at the loader's baud rate.
+    pub fn new(port: S) -> Self {
+        Self {
+            framed: Framed::new(port, SlipCodec::default()),
+        }
+    }
+
+    /// Give back the port, for resetting the chip out of the loader.
+    pub fn into_inner(self) -> S {
+        self.framed.into_inner()
+    }
+
+    /// Sync with the loader, which also lets it settle on our baud rate.
+    pub async fn sync(&mut self) -> Result<(), LoaderError> {
+        let mut data = vec![0x07, 0x07, 0x12, 0x20];
+        data.extend([0x55; 32]);
+
+        for attempt in 1..=SYNC_ATTEMPTS {
+            match self.command(Op::Sync, &data, 0, SYNC_TIMEOUT).await {
+                Ok(_) => {
+                    debug!(attempt, "ROM loader synced");
+                    self.drain().await?;
+                    return Ok(());
+                }
+                Err(LoaderError::Timeout { .. }) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Err(LoaderError::NoSync {
+            attempts: SYNC_ATTEMPTS,
+        })
+    }
+
+    /// Attach the SPI flash the chip boots from.
+    pub async fn spi_attach(&mut self) -> Result<(), LoaderError> {
+        self.command(Op::SpiAttach, &[0; 8], 0, COMMAND_TIMEOUT)
+            .await
+            .map(drop)
+    }
+
+    /// Write `image` to flash at `offset`, calling `progress` with the
+    /// bytes written so far and the total after each block.
+    pub async fn flash(
+        &mut self,
+        offset: u32,
+        image: &[u8],
+        mut progress: impl FnMut(usize, usize),
+    ) -> Result<(), LoaderError> {
+        let size = u32::try_from(image.len())
+            .ok()
+            .filter(|size| size.checked_add(offset).is_some())
+            .ok_or(LoaderError::TooLarge { size: image.len() })?;
+        let blocks = image.len().div_ceil(FLASH_BLOCK_SIZE) as u32;
+
+        let mut begin = BytesMut::with_capacity(20);
+        begin.put_u32_le(size);
+        begin.put_u32_le(blocks);
+        begin.put_u32_le(FLASH_BLOCK_SIZE as u32);
+        begin.put_u32_le(offset);
+        begin.put_u32_le(0); // Not encrypted
+        let erase_timeout = ERASE_TIMEOUT_PER_MB.mul_f64(image.len() as f64 / 1_048_576.0);
+        self.command(
+            Op::FlashBegin,
+            &begin,
+            0,
+            erase_timeout.max(COMMAND_TIMEOUT),
+        )
+        .await?;
+
+        let mut written = 0;
+        for (seq, chunk) in image.chunks(FLASH_BLOCK_SIZE).enumerate() {
+            let mut block = chunk.to_vec();
+            block.resize(FLASH_BLOCK_SIZE, 0xff);
+
+            let mut data = BytesMut::with_capacity(16 + FLASH_BLOCK_SIZE);
+            data.put_u32_le(FLASH_BLOCK_SIZE as u32);
+            data.put_u32_le(seq as u32);
+            data.put_u32_le(0);
+            data.put_u32_le(0);
+            data.put_slice(&block);
+            self.command(Op::FlashData, &data, checksum(&block), COMMAND_TIMEOUT)
+                .await?;
+
+            written += chunk.len();
+            progress(written, image.len());
+        }
+        Ok(())
+    }
+
+    /// End flashing, staying in the loader until the chip is reset.
+    pub async fn finish(&mut self) -> Result<(), LoaderError> {
+        self.command(Op::FlashEnd, &1u32.to_le_bytes(), 0, COMMAND_TIMEOUT)
+            .await
+            .map(drop)
+    }
+
+    /// Send a command and wait for its response, returning the response's
+    /// data. Responses to other commands, such as the extra replies to a
+    /// sync, are skipped.
+    async fn command(
+        &mut self,
+        op: Op,
+        data: &[u8],
+        checksum: u8,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, LoaderError> {
+        let mut packet = Vec::with_capacity(8 + data.len());
+        packet.push(REQUEST);
+        packet.push(op as u8);
+        packet.extend((data.len() as u16).to_le_bytes());
+        packet.extend(u32::from(checksum).to_le_bytes());
+        packet.extend(data);
+        trace!(?op, len = data.len(), "Loader command");
+        self.framed.send(packet).await?;
+
+        let response = time::timeout(timeout, async {
+            loop {
+                let frame = self.framed.next().await.ok_or(LoaderError::Closed)??;
+                if frame.len() >= 8 && frame[0] == RESPONSE && frame[1] == op as u8 {
+                    return Ok::<_, LoaderError>(frame);
+                }
+                trace!(len = frame.len(), "Skipping unrelated loader packet");
+            }
+        })
+        .await
+        .map_err(|_| LoaderError::Timeout { op, timeout })??;
+
+        let data = response[8..].to_vec();
+        match data.as_slice() {
+            [0, ..] => Ok(data),
+            [_, error, ..] => Err(LoaderError::Failed { op, error: *error }),
+            _ => Err(LoaderError::Failed { op, error: 0 }),
+        }
+    }
+
+    /// Discard whatever the loader still has to say.
+    async fn drain(&mut self) -> Result<(), LoaderError> {
+        while let Ok(frame) = time::timeout(SYNC_TIMEOUT, self.framed.next()).await {
+            if frame.transpose()?.is_none() {
+                return Err(LoaderError::Closed);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `FLASH_DATA` checksum of a block.
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(CHECKSUM_SEED, |sum, b| sum ^ b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// ROM loader stand-in, writing flash to memory. Answers every sync
+    /// several times, as the real one does.
+    async fn mock_loader(port: tokio::io::DuplexStream, corrupt_checksums: bool) -> Vec<u8> {
+        let mut framed = Framed::new(port, SlipCodec::default());
+        let mut flash = Vec::new();
+        let mut offset = 0;
+
+        while let Some(Ok(packet)) = framed.next().await {
+            assert_eq!(packet[0], REQUEST);
+            let op = packet[1];
+            let data = &packet[8..];
+            let mut status = [0u8, 0, 0, 0];
+            let mut replies = 1;
+
+            match op {
+                0x08 => replies = 8,
+                0x02 => {
+                    offset = u32::from_le_bytes(data[12..16].try_into().unwrap()) as usize;
+                    assert_eq!(data.len(), 20);
+                }
+                0x03 => {
+                    let block = &data[16..];
+                    let sum = u32::from_le_bytes(packet[4..8].try_into().unwrap()) as u8;
+                    if corrupt_checksums || sum != checksum(block) {
+                        status = [1, 0x07, 0, 0];
+                    } else {
+                        flash.extend_from_slice(block);
+                    }
+                }
+                0x04 => {
+                    let mut response = vec![RESPONSE, op, 4, 0, 0, 0, 0, 0];
+                    response.extend(status);
+                    framed.send(response).await.unwrap();
+                    break;
+                }
+                _ => {}
+            }
+
+            for _ in 0..replies {
+                let mut response = vec![RESPONSE, op, 4, 0, 0, 0, 0, 0];
+                response.extend(status);
+                framed.send(response).await.unwrap();
+            }
+        }
+
+        let mut memory = vec![0xff; offset];
+        memory.extend(flash);
+        memory
+    }
+
+    #[test]
+    fn test_slip_round_trip_with_escapes() {
+        let mut codec = SlipCodec::default();
+        let frame = vec![0x01, SLIP_END, 0x02, SLIP_ESC, 0x03];
+
+        let mut wire = BytesMut::new();
+        codec.encode(frame.clone(), &mut wire).unwrap();
+        assert_eq!(
+            &wire[..],
+            &[
+                SLIP_END,
+                0x01,
+                SLIP_ESC,
+                SLIP_ESC_END,
+                0x02,
+                SLIP_ESC,
+                SLIP_ESC_ESC,
+                0x03,
+                SLIP_END
+            ]
+        );
+
+        // Boot chatter before the frame is dropped
+        let mut src = BytesMut::from(&b"ESP-ROM:esp32s3\r\n"[..]);
+        src.extend_from_slice(&wire);
+        assert_eq!(codec.decode(&mut src).unwrap(), Some(frame));
+        assert_eq!(codec.decode(&mut src).unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_flash_writes_image_in_padded_blocks() {
+        let (host, device) = tokio::io::duplex(64 * 1024);
+        let loader_task = tokio::spawn(mock_loader(device, false));
+
+        // Two and a half blocks, with bytes that need escaping
+        let image: Vec<u8> = (0..FLASH_BLOCK_SIZE * 5 / 2)
+            .map(|i| [SLIP_END, SLIP_ESC, i as u8][i % 3])
+            .collect();
+        let mut reported = Vec::new();
+
+        let mut loader = EspLoader::new(host);
+        loader.sync().await.unwrap();
+        loader.spi_attach().await.unwrap();
+        loader
+            .flash(0x10, &image, |written, total| {
+                reported.push((written, total))
+            })
+            .await
+            .unwrap();
+        loader.finish().await.unwrap();
+        drop(loader);
+
+        let memory = loader_task.await.unwrap();
+        assert_eq!(&memory[0x10..0x10 + image.len()], &image[..]);
+        assert!(memory[0x10 + image.len()..].iter().all(|&b| b == 0xff));
+        assert_eq!(memory.len(), 0x10 + 3 * FLASH_BLOCK_SIZE);
+        assert_eq!(
+            reported,
+            [
+                (FLASH_BLOCK_SIZE, image.len()),
+                (2 * FLASH_BLOCK_SIZE, image.len()),
+                (image.len(), image.len())
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_loader_error_fails_flash() {
+        let (host, device) = tokio::io::duplex(64 * 1024);
+        tokio::spawn(mock_loader(device, true));
+
+        let mut loader = EspLoader::new(host);
+        loader.sync().await.unwrap();
+        let err = loader.flash(0, &[0xaa; 16], |_, _| {}).await.unwrap_err();
+        assert!(
+            matches!(
+                err,
+                LoaderError::Failed {
+                    op: Op::FlashData,
+                    error: 0x07
+                }
+            ),
+            "{err}"
+        );
+    }
+}
@@ -5,6 +5,7 @@
 //! passthrough, ADC readings, and other board management functions.
 
 pub mod bitaxe_raw;
+pub mod esp_loader;
 
 // Re-export commonly used types
 pub use bitaxe_raw::channel::ControlChannel;
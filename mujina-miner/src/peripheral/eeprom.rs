@@ -0,0 +1,208 @@
+//! 24-series I2C EEPROM driver.
+//!
+//! Covers the AT24C32 and larger parts and their clones (24LC32, M24C64,
+//! ...): two address bytes, big-endian, ahead of the data. Writes are split
+//! at page boundaries, since a write that runs off the end of a page wraps
+//! to its start, and each page is given the part's write cycle time before
+//! the next access.
+//!
+//! Datasheet: <https://www.microchip.com/en-us/product/at24c32d>
+
+use std::time::Duration;
+
+use crate::{
+    hw_trait::{i2c::I2c, HwError, Result},
+    tracing::prelude::*,
+};
+
+/// Default I2C address, with the A0-A2 straps all low.
+pub const DEFAULT_ADDRESS: u8 = 0x50;
+
+/// Longest the part takes to commit a page write.
+const WRITE_CYCLE: Duration = Duration::from_millis(5);
+
+/// Most bytes read in one transfer; bridges like bitaxe-raw cap transfers
+/// well below the size of the part.
+const READ_CHUNK: usize = 32;
+
+/// Size and page layout of an EEPROM part.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EepromLayout {
+    /// Capacity in bytes
+    pub capacity: usize,
+    /// Page write size in bytes
+    pub page_size: usize,
+}
+
+impl EepromLayout {
+    /// AT24C32: 4 KiB in 32-byte pages.
+    pub const AT24C32: Self = Self {
+        capacity: 4096,
+        page_size: 32,
+    };
+
+    /// AT24C64: 8 KiB in 32-byte pages.
+    pub const AT24C64: Self = Self {
+        capacity: 8192,
+        page_size: 32,
+    };
+}
+
+/// An EEPROM on an I2C bus.
+pub struct Eeprom<I2C> {
+    i2c: I2C,
+    address: u8,
+    layout: EepromLayout,
+}
+
+impl<I2C: I2c> Eeprom<I2C> {
+    /// EEPROM with the given layout at [`DEFAULT_ADDRESS`].
+    pub fn new(i2c: I2C, layout: EepromLayout) -> Self {
+        Self {
+            i2c,
+            address: DEFAULT_ADDRESS,
+            layout,
+        }
+    }
+
+    /// Use a strapped address other than the default.
+    pub fn with_address(mut self, address: u8) -> Self {
+        self.address = address;
+        self
+    }
+
+    /// Capacity in bytes.
+    pub fn capacity(&self) -> usize {
+        self.layout.capacity
+    }
+
+    /// Fill `buffer` from `offset` on.
+    pub async fn read(&mut self, offset: usize, buffer: &mut [u8]) -> Result<()> {
+        self.check_range(offset, buffer.len())?;
+        for (i, chunk) in buffer.chunks_mut(READ_CHUNK).enumerate() {
+            let at = offset + i * READ_CHUNK;
+            self.i2c
+                .write_read(self.address, &(at as u16).to_be_bytes(), chunk)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Write `data` from `offset` on, a page at a time.
+    pub async fn write(&mut self, offset: usize, data: &[u8]) -> Result<()> {
+        self.check_range(offset, data.len())?;
+        let mut at = offset;
+        let mut rest = data;
+        while !rest.is_empty() {
+            let room = self.layout.page_size - at % self.layout.page_size;
+            let (page, next) = rest.split_at(room.min(rest.len()));
+            let mut transfer = (at as u16).to_be_bytes().to_vec();
+            transfer.extend_from_slice(page);
+            self.i2c.write(self.address, &transfer).await?;
+            trace!(
+                address = self.address,
+                offset = at,
+                len = page.len(),
+                "EEPROM page written"
+            );
+            tokio::time::sleep(WRITE_CYCLE).await;
+            at += page.len();
+            rest = next;
+        }
+        Ok(())
+    }
+
+    fn check_range(&self, offset: usize, len: usize) -> Result<()> {
+        if offset.saturating_add(len) > self.layout.capacity {
+            return Err(HwError::InvalidParameter(format!(
+                "{} bytes at offset {} run past the end of a {}-byte EEPROM",
+                len, offset, self.layout.capacity
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use async_trait::async_trait;
+    use parking_lot::Mutex;
+
+    use super::*;
+
+    /// A 24-series part in memory, recording the size of each write.
+    #[derive(Clone)]
+    struct MemEeprom {
+        memory: Arc<Mutex<Vec<u8>>>,
+        writes: Arc<Mutex<Vec<usize>>>,
+        page_size: usize,
+    }
+
+    impl MemEeprom {
+        fn new(layout: EepromLayout) -> Self {
+            Self {
+                memory: Arc::new(Mutex::new(vec![0xff; layout.capacity])),
+                writes: Default::default(),
+                page_size: layout.page_size,
+            }
+        }
+    }
+
+    #[async_trait]
+    impl I2c for MemEeprom {
+        async fn write(&mut self, addr: u8, data: &[u8]) -> Result<()> {
+            assert_eq!(addr, DEFAULT_ADDRESS);
+            let at = u16::from_be_bytes([data[0], data[1]]) as usize;
+            let payload = &data[2..];
+            assert!(
+                at % self.page_size + payload.len() <= self.page_size,
+                "write crosses a page boundary"
+            );
+            self.memory.lock()[at..at + payload.len()].copy_from_slice(payload);
+            self.writes.lock().push(payload.len());
+            Ok(())
+        }
+
+        async fn read(&mut self, _addr: u8, _buffer: &mut [u8]) -> Result<()> {
+            unimplemented!("reads go through write_read")
+        }
+
+        async fn write_read(&mut self, addr: u8, write: &[u8], read: &mut [u8]) -> Result<()> {
+            assert_eq!(addr, DEFAULT_ADDRESS);
+            let at = u16::from_be_bytes([write[0], write[1]]) as usize;
+            read.copy_from_slice(&self.memory.lock()[at..at + read.len()]);
+            Ok(())
+        }
+
+        async fn set_frequency(&mut self, _hz: u32) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_write_split_at_page_boundaries() {
+        let part = MemEeprom::new(EepromLayout::AT24C32);
+        let mut eeprom = Eeprom::new(part.clone(), EepromLayout::AT24C32);
+
+        let data: Vec<u8> = (0..70).collect();
+        eeprom.write(20, &data).await.unwrap();
+        assert_eq!(*part.writes.lock(), vec![12, 32, 26]);
+
+        let mut back = vec![0; 70];
+        eeprom.read(20, &mut back).await.unwrap();
+        assert_eq!(back, data);
+    }
+
+    #[tokio::test]
+    async fn test_access_past_end_refused() {
+        let part = MemEeprom::new(EepromLayout::AT24C32);
+        let mut eeprom = Eeprom::new(part.clone(), EepromLayout::AT24C32);
+
+        assert!(eeprom.write(4090, &[0; 8]).await.is_err());
+        assert!(part.writes.lock().is_empty());
+        let mut buffer = [0; 8];
+        assert!(eeprom.read(4090, &mut buffer).await.is_err());
+    }
+}
@@ -67,12 +67,18 @@ impl TryFrom<u8> for Percent {
 
 /// Protocol dissection utilities for EMC2101
 pub mod protocol {
+    use std::fmt;
+
     /// Default I2C address for EMC2101
     pub const DEFAULT_ADDRESS: u8 = 0x4C;
 
     /// Expected manufacturer ID
     pub const EXPECTED_MFG_ID: u8 = 0x5D;
 
+    /// Product IDs of the two variants
+    pub const PRODUCT_ID_EMC2101: u8 = 0x16;
+    pub const PRODUCT_ID_EMC2101_R: u8 = 0x28;
+
     /// EMC2101 register addresses
     pub mod regs {
         /// Internal temperature reading
@@ -125,110 +131,288 @@ pub mod protocol {
         pub const REVISION: u8 = 0xFF;
     }
 
+    /// How a register's contents are interpreted.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum RegisterKind {
+        /// Signed whole degrees Celsius
+        Temperature,
+        /// Integer part of the external temperature; pairs with the low byte
+        ExternalTempHigh,
+        /// Fractional bits 7:5 of the external temperature
+        ExternalTempLow,
+        /// 6-bit fan drive setting
+        FanDrive,
+        /// High byte of the TACH count; pairs with the low byte
+        TachHigh,
+        /// Low byte of the TACH count
+        TachLow,
+        /// Conversion rate code
+        ConversionRate,
+        /// Manufacturer ID
+        ManufacturerId,
+        /// Anything without a more specific meaning
+        Byte,
+    }
+
+    /// Register name and interpretation.
+    #[derive(Debug, Clone, Copy)]
+    pub struct RegisterInfo {
+        pub address: u8,
+        pub name: &'static str,
+        pub kind: RegisterKind,
+    }
+
+    const fn reg(address: u8, name: &'static str, kind: RegisterKind) -> RegisterInfo {
+        RegisterInfo {
+            address,
+            name,
+            kind,
+        }
+    }
+
+    /// Every register the dissector knows about.
+    pub const REGISTERS: &[RegisterInfo] = &[
+        reg(
+            regs::INTERNAL_TEMP,
+            "INTERNAL_TEMP",
+            RegisterKind::Temperature,
+        ),
+        reg(
+            regs::EXTERNAL_TEMP_HIGH,
+            "EXTERNAL_TEMP_HIGH",
+            RegisterKind::ExternalTempHigh,
+        ),
+        reg(
+            regs::EXTERNAL_TEMP_LOW,
+            "EXTERNAL_TEMP_LOW",
+            RegisterKind::ExternalTempLow,
+        ),
+        reg(regs::CONFIG, "CONFIG", RegisterKind::Byte),
+        reg(
+            regs::CONVERSION_RATE,
+            "CONVERSION_RATE",
+            RegisterKind::ConversionRate,
+        ),
+        reg(
+            regs::INTERNAL_TEMP_LIMIT,
+            "INTERNAL_TEMP_LIMIT",
+            RegisterKind::Temperature,
+        ),
+        reg(
+            regs::EXTERNAL_TEMP_LIMIT_HIGH,
+            "EXTERNAL_TEMP_LIMIT_HIGH",
+            RegisterKind::Temperature,
+        ),
+        reg(
+            regs::EXTERNAL_TEMP_LIMIT_LOW,
+            "EXTERNAL_TEMP_LIMIT_LOW",
+            RegisterKind::Byte,
+        ),
+        reg(regs::FAN_CONFIG, "FAN_CONFIG", RegisterKind::Byte),
+        reg(regs::FAN_SPINUP, "FAN_SPINUP", RegisterKind::Byte),
+        reg(regs::FAN_SETTING, "FAN_SETTING", RegisterKind::FanDrive),
+        reg(regs::PWM_FREQ, "PWM_FREQ", RegisterKind::Byte),
+        reg(regs::PWM_DIV, "PWM_DIV", RegisterKind::Byte),
+        reg(regs::FAN_MIN_DRIVE, "FAN_MIN_DRIVE", RegisterKind::FanDrive),
+        reg(regs::FAN_VALID_TACH, "FAN_VALID_TACH", RegisterKind::Byte),
+        reg(
+            regs::FAN_FAIL_BAND_LOW,
+            "FAN_FAIL_BAND_LOW",
+            RegisterKind::Byte,
+        ),
+        reg(
+            regs::FAN_FAIL_BAND_HIGH,
+            "FAN_FAIL_BAND_HIGH",
+            RegisterKind::Byte,
+        ),
+        reg(regs::TACH_LOW, "TACH_LOW", RegisterKind::TachLow),
+        reg(regs::TACH_HIGH, "TACH_HIGH", RegisterKind::TachHigh),
+        reg(regs::TACH_LIMIT_HIGH, "TACH_LIMIT_HIGH", RegisterKind::Byte),
+        reg(regs::TACH_LIMIT_LOW, "TACH_LIMIT_LOW", RegisterKind::Byte),
+        reg(regs::PRODUCT_ID, "PRODUCT_ID", RegisterKind::Byte),
+        reg(regs::MFG_ID, "MFG_ID", RegisterKind::ManufacturerId),
+        reg(regs::REVISION, "REVISION", RegisterKind::Byte),
+    ];
+
+    /// Largest FAN_SETTING value; the drive setting is 6 bits.
+    pub const FAN_DRIVE_MAX: u8 = 63;
+
+    /// Look up a register by address.
+    pub fn register_info(addr: u8) -> Option<&'static RegisterInfo> {
+        REGISTERS.iter().find(|info| info.address == addr)
+    }
+
     /// Get register name from address
     pub fn register_name(addr: u8) -> String {
-        match addr {
-            regs::INTERNAL_TEMP => "INTERNAL_TEMP".to_string(),
-            regs::EXTERNAL_TEMP_HIGH => "EXTERNAL_TEMP_HIGH".to_string(),
-            regs::EXTERNAL_TEMP_LOW => "EXTERNAL_TEMP_LOW".to_string(),
-            regs::CONFIG => "CONFIG".to_string(),
-            regs::CONVERSION_RATE => "CONVERSION_RATE".to_string(),
-            regs::INTERNAL_TEMP_LIMIT => "INTERNAL_TEMP_LIMIT".to_string(),
-            regs::EXTERNAL_TEMP_LIMIT_HIGH => "EXTERNAL_TEMP_LIMIT_HIGH".to_string(),
-            regs::EXTERNAL_TEMP_LIMIT_LOW => "EXTERNAL_TEMP_LIMIT_LOW".to_string(),
-            regs::FAN_CONFIG => "FAN_CONFIG".to_string(),
-            regs::FAN_SPINUP => "FAN_SPINUP".to_string(),
-            regs::FAN_SETTING => "FAN_SETTING".to_string(),
-            regs::PWM_FREQ => "PWM_FREQ".to_string(),
-            regs::PWM_DIV => "PWM_DIV".to_string(),
-            regs::FAN_MIN_DRIVE => "FAN_MIN_DRIVE".to_string(),
-            regs::FAN_VALID_TACH => "FAN_VALID_TACH".to_string(),
-            regs::FAN_FAIL_BAND_LOW => "FAN_FAIL_BAND_LOW".to_string(),
-            regs::FAN_FAIL_BAND_HIGH => "FAN_FAIL_BAND_HIGH".to_string(),
-            regs::TACH_LOW => "TACH_LOW".to_string(),
-            regs::TACH_HIGH => "TACH_HIGH".to_string(),
-            regs::TACH_LIMIT_HIGH => "TACH_LIMIT_HIGH".to_string(),
-            regs::TACH_LIMIT_LOW => "TACH_LIMIT_LOW".to_string(),
-            regs::PRODUCT_ID => "PRODUCT_ID".to_string(),
-            regs::MFG_ID => "MFG_ID".to_string(),
-            regs::REVISION => "REVISION".to_string(),
-            _ => format!("UNKNOWN[0x{:02x}]", addr),
+        match register_info(addr) {
+            Some(info) => info.name.to_string(),
+            None => format!("UNKNOWN[0x{:02x}]", addr),
+        }
+    }
+
+    /// External temperature from its high and low registers.
+    ///
+    /// The reading is 11-bit two's complement with 0.125 degC resolution:
+    /// the high byte is the integer part, bits 7:5 of the low byte the
+    /// fraction.
+    pub fn external_temperature(high: u8, low: u8) -> f32 {
+        const FRACTION_BITS: u8 = 3;
+        const FRACTION_SHIFT: u8 = 5;
+        const RESOLUTION: f32 = 0.125; // degC per LSB
+        const SIGN_BIT: u16 = 0x400; // 11-bit sign bit
+        const VALUE_MASK: u16 = 0x7FF; // 11-bit mask
+
+        let raw = ((high as u16) << FRACTION_BITS) | ((low as u16) >> FRACTION_SHIFT);
+        if raw & SIGN_BIT != 0 {
+            -(((!raw & VALUE_MASK) + 1) as f32) * RESOLUTION
+        } else {
+            (raw as f32) * RESOLUTION
         }
     }
 
-    /// Decode temperature value (signed 8-bit)
-    pub fn decode_temperature(value: u8) -> String {
-        let temp = value as i8;
-        format!("{} degC", temp)
+    /// Fan speed from a TACH count, using esp-miner's simplified formula
+    /// (RPM = 5400000 / count). Returns 0 for a stopped fan.
+    pub fn rpm_from_tach(count: u16) -> u32 {
+        const TACH_ERROR_VALUE: u16 = 0xFFFF; // Indicates fan stopped/error
+        if count == 0 || count == TACH_ERROR_VALUE {
+            return 0;
+        }
+
+        // EMC2101 constant for RPM calculation (from esp-miner)
+        const EMC2101_FAN_RPM_NUMERATOR: u32 = 5_400_000;
+        let rpm = EMC2101_FAN_RPM_NUMERATOR / (count as u32);
+
+        // esp-miner returns 0 if RPM is exactly 82 (not sure why)
+        const INVALID_RPM: u32 = 82;
+        if rpm == INVALID_RPM {
+            return 0;
+        }
+
+        rpm
     }
 
-    /// Decode PWM duty cycle to percentage
-    pub fn decode_pwm_percent(value: u8) -> String {
-        let percent = (value as f32 / 255.0 * 100.0) as u8;
-        format!("{}% (0x{:02x})", percent, value)
+    /// Decoded value of an EMC2101 register access.
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Emc2101Value {
+        Temperature(f32),
+        FanDrive(u8),
+        TachCount(u16),
+        ConversionRate(u8),
+        ManufacturerId(u8),
+        Byte(u8),
+        Raw(Vec<u8>),
     }
 
-    /// Format an EMC2101 I2C transaction
-    pub fn format_transaction(reg: u8, data: Option<&[u8]>, is_read: bool) -> String {
-        let reg_name = register_name(reg);
+    impl Emc2101Value {
+        /// Fan drive as a percentage of full scale.
+        pub fn fan_percent(raw: u8) -> f32 {
+            raw.min(FAN_DRIVE_MAX) as f32 / FAN_DRIVE_MAX as f32 * 100.0
+        }
+    }
 
-        if is_read {
-            if let Some(data) = data {
-                if data.len() == 1 {
-                    let decoded = match reg {
-                        regs::INTERNAL_TEMP | regs::EXTERNAL_TEMP_HIGH => {
-                            decode_temperature(data[0])
-                        }
-                        regs::FAN_SETTING | regs::FAN_MIN_DRIVE => decode_pwm_percent(data[0]),
-                        regs::MFG_ID => format!(
-                            "0x{:02x} ({})",
-                            data[0],
-                            if data[0] == EXPECTED_MFG_ID {
-                                "SMSC/Microchip"
-                            } else {
-                                "Unknown"
-                            }
-                        ),
-                        regs::CONVERSION_RATE => {
-                            let rate_desc = match data[0] {
-                                0x00 => "1/16 Hz",
-                                0x01 => "1/8 Hz",
-                                0x02 => "1/4 Hz",
-                                0x03 => "1/2 Hz",
-                                0x04 => "1 Hz",
-                                0x05 => "2 Hz",
-                                0x06 => "4 Hz",
-                                0x07 => "8 Hz",
-                                0x08 => "16 Hz",
-                                0x09 => "32 Hz",
-                                _ => "unknown",
-                            };
-                            format!("0x{:02x} ({})", data[0], rate_desc)
-                        }
-                        regs::INTERNAL_TEMP_LIMIT => {
-                            format!("0x{:02x} ({} degC)", data[0], data[0] as i8)
-                        }
-                        _ => format!("0x{:02x}", data[0]),
+    impl fmt::Display for Emc2101Value {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Self::Temperature(t) => write!(f, "{} degC", t),
+                Self::FanDrive(raw) => {
+                    write!(f, "{:.0}% (0x{:02x})", Self::fan_percent(*raw), raw)
+                }
+                Self::TachCount(count) => {
+                    write!(f, "{} RPM (tach 0x{:04x})", rpm_from_tach(*count), count)
+                }
+                Self::ConversionRate(code) => {
+                    let rate = match code {
+                        0x00 => "1/16 Hz",
+                        0x01 => "1/8 Hz",
+                        0x02 => "1/4 Hz",
+                        0x03 => "1/2 Hz",
+                        0x04 => "1 Hz",
+                        0x05 => "2 Hz",
+                        0x06 => "4 Hz",
+                        0x07 => "8 Hz",
+                        0x08 => "16 Hz",
+                        0x09 => "32 Hz",
+                        _ => "unknown",
+                    };
+                    write!(f, "0x{:02x} ({})", code, rate)
+                }
+                Self::ManufacturerId(id) => {
+                    let vendor = if *id == EXPECTED_MFG_ID {
+                        "SMSC/Microchip"
+                    } else {
+                        "Unknown"
                     };
-                    format!("-> READ {}={}", reg_name, decoded)
-                } else {
-                    format!("-> READ {}={:02x?}", reg_name, data)
+                    write!(f, "0x{:02x} ({})", id, vendor)
                 }
-            } else {
-                format!("-> READ {}", reg_name)
+                Self::Byte(value) => write!(f, "0x{:02x}", value),
+                Self::Raw(data) => write!(f, "{:02x?}", data),
+            }
+        }
+    }
+
+    /// State carried between accesses to one device.
+    ///
+    /// The external temperature and TACH count span two registers, read high
+    /// byte first. The high byte is remembered so the following low-byte
+    /// read can be shown as the combined value.
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct DecodeContext {
+        external_temp_high: Option<u8>,
+        tach_high: Option<u8>,
+    }
+
+    /// Decode a register access, updating `context` for paired registers.
+    pub fn parse_value(reg: u8, data: &[u8], context: &mut DecodeContext) -> Emc2101Value {
+        let &[value] = data else {
+            return Emc2101Value::Raw(data.to_vec());
+        };
+        let kind = register_info(reg).map_or(RegisterKind::Byte, |info| info.kind);
+
+        match kind {
+            RegisterKind::Temperature => Emc2101Value::Temperature(value as i8 as f32),
+            RegisterKind::ExternalTempHigh => {
+                context.external_temp_high = Some(value);
+                Emc2101Value::Temperature(value as i8 as f32)
             }
-        } else if let Some(data) = data {
-            if data.len() == 1 {
-                let decoded = match reg {
-                    regs::FAN_SETTING | regs::FAN_MIN_DRIVE => decode_pwm_percent(data[0]),
-                    _ => format!("0x{:02x}", data[0]),
-                };
-                format!("<- WRITE {}={}", reg_name, decoded)
-            } else {
-                format!("<- WRITE {}={:02x?}", reg_name, data)
+            RegisterKind::ExternalTempLow => match context.external_temp_high.take() {
+                Some(high) => Emc2101Value::Temperature(external_temperature(high, value)),
+                None => Emc2101Value::Byte(value),
+            },
+            RegisterKind::FanDrive => Emc2101Value::FanDrive(value),
+            RegisterKind::TachHigh => {
+                context.tach_high = Some(value);
+                Emc2101Value::Byte(value)
             }
+            RegisterKind::TachLow => match context.tach_high.take() {
+                Some(high) => Emc2101Value::TachCount(u16::from_be_bytes([high, value])),
+                None => Emc2101Value::Byte(value),
+            },
+            RegisterKind::ConversionRate => Emc2101Value::ConversionRate(value),
+            RegisterKind::ManufacturerId => Emc2101Value::ManufacturerId(value),
+            RegisterKind::Byte => Emc2101Value::Byte(value),
+        }
+    }
+
+    /// Format an EMC2101 I2C transaction
+    pub fn format_transaction(
+        reg: u8,
+        data: Option<&[u8]>,
+        is_read: bool,
+        context: &mut DecodeContext,
+    ) -> String {
+        let reg_name = register_name(reg);
+        let (direction, op_type) = if is_read {
+            ("->", "READ")
         } else {
-            format!("<- WRITE REG[0x{:02x}]", reg)
+            ("<-", "WRITE")
+        };
+
+        match data {
+            Some(data) => {
+                let value = parse_value(reg, data, context);
+                format!("{} {} {}={}", direction, op_type, reg_name, value)
+            }
+            None => format!("{} {} {}", direction, op_type, reg_name),
         }
     }
 }
@@ -244,7 +428,7 @@ pub struct Emc2101<I: I2c> {
 
 impl<I: I2c> Emc2101<I> {
     /// EMC2101 uses 6-bit PWM duty cycle (0-63 = 0-100%)
-    const PWM_MAX: u8 = 63;
+    const PWM_MAX: u8 = protocol::FAN_DRIVE_MAX;
 
     /// Create a new EMC2101 driver with default address
     pub fn new(i2c: I) -> Self {
@@ -276,12 +460,14 @@ impl<I: I2c> Emc2101<I> {
         }
 
         // Valid product IDs for EMC2101
-        const PRODUCT_ID_EMC2101_1: u8 = 0x16;
-        const PRODUCT_ID_EMC2101_2: u8 = 0x28;
-        if product_id != PRODUCT_ID_EMC2101_1 && product_id != PRODUCT_ID_EMC2101_2 {
+        if product_id != protocol::PRODUCT_ID_EMC2101
+            && product_id != protocol::PRODUCT_ID_EMC2101_R
+        {
             return Err(HwError::InvalidParameter(format!(
                 "Wrong product ID: 0x{:02X}, expected 0x{:02X} or 0x{:02X}",
-                product_id, PRODUCT_ID_EMC2101_1, PRODUCT_ID_EMC2101_2
+                product_id,
+                protocol::PRODUCT_ID_EMC2101,
+                protocol::PRODUCT_ID_EMC2101_R
             )));
         }
 
@@ -335,25 +521,7 @@ impl<I: I2c> Emc2101<I> {
         let high = self.read_register(regs::EXTERNAL_TEMP_HIGH).await?;
         let low = self.read_register(regs::EXTERNAL_TEMP_LOW).await?;
 
-        // Temperature is in 11-bit format with 0.125 degC resolution
-        // High byte is integer part, low byte bits 7-5 are fractional
-        const FRACTION_BITS: u8 = 3;
-        const FRACTION_SHIFT: u8 = 5;
-        let raw = ((high as u16) << FRACTION_BITS) | ((low as u16) >> FRACTION_SHIFT);
-
-        // Convert to Celsius
-        const RESOLUTION: f32 = 0.125; // degC per LSB
-        const SIGN_BIT: u16 = 0x400; // 11-bit sign bit
-        const VALUE_MASK: u16 = 0x7FF; // 11-bit mask
-
-        let temp = if raw & SIGN_BIT != 0 {
-            // Negative temperature (11-bit two's complement)
-            -(((!raw & VALUE_MASK) + 1) as f32) * RESOLUTION
-        } else {
-            (raw as f32) * RESOLUTION
-        };
-
-        Ok(temp)
+        Ok(protocol::external_temperature(high, low))
     }
 
     /// Read internal temperature in Celsius
@@ -385,23 +553,7 @@ impl<I: I2c> Emc2101<I> {
     /// Uses the simplified formula from esp-miner: RPM = 5400000 / TACH_count
     pub async fn get_rpm(&mut self) -> Result<u32> {
         let tach = self.get_tach_count().await?;
-
-        const TACH_ERROR_VALUE: u16 = 0xFFFF; // Indicates fan stopped/error
-        if tach == 0 || tach == TACH_ERROR_VALUE {
-            return Ok(0); // Fan stopped or error
-        }
-
-        // EMC2101 constant for RPM calculation (from esp-miner)
-        const EMC2101_FAN_RPM_NUMERATOR: u32 = 5_400_000;
-        let rpm = EMC2101_FAN_RPM_NUMERATOR / (tach as u32);
-
-        // esp-miner returns 0 if RPM is exactly 82 (not sure why)
-        const INVALID_RPM: u32 = 82;
-        if rpm == INVALID_RPM {
-            return Ok(0);
-        }
-
-        Ok(rpm)
+        Ok(protocol::rpm_from_tach(tach))
     }
 
     // Helper methods for register access
@@ -416,3 +568,126 @@ impl<I: I2c> Emc2101<I> {
         self.i2c.write(self.address, &[reg, value]).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::protocol::*;
+    use super::{Emc2101, Percent};
+    use crate::hw_trait::{mock::MockI2c, I2cError};
+
+    #[test]
+    fn test_register_table() {
+        assert_eq!(register_name(regs::TACH_LOW), "TACH_LOW");
+        assert_eq!(register_name(0x99), "UNKNOWN[0x99]");
+        for info in REGISTERS {
+            assert_eq!(
+                REGISTERS
+                    .iter()
+                    .filter(|other| other.address == info.address)
+                    .count(),
+                1,
+                "duplicate register 0x{:02x}",
+                info.address
+            );
+        }
+    }
+
+    #[test]
+    fn test_external_temperature() {
+        assert_eq!(external_temperature(0x2d, 0x20), 45.125);
+        assert_eq!(external_temperature(0x00, 0x00), 0.0);
+        assert_eq!(external_temperature(0xff, 0xe0), -0.125);
+    }
+
+    #[test]
+    fn test_paired_registers() {
+        let mut context = DecodeContext::default();
+
+        // High byte alone shows whole degrees; the low byte completes it
+        assert_eq!(
+            parse_value(regs::EXTERNAL_TEMP_HIGH, &[0x2d], &mut context),
+            Emc2101Value::Temperature(45.0)
+        );
+        assert_eq!(
+            parse_value(regs::EXTERNAL_TEMP_LOW, &[0x60], &mut context),
+            Emc2101Value::Temperature(45.375)
+        );
+
+        parse_value(regs::TACH_HIGH, &[0x07], &mut context);
+        let value = parse_value(regs::TACH_LOW, &[0xd0], &mut context);
+        assert_eq!(value, Emc2101Value::TachCount(0x07d0));
+        assert_eq!(value.to_string(), "2700 RPM (tach 0x07d0)");
+
+        // A low byte without its high byte can't be combined
+        assert_eq!(
+            parse_value(regs::TACH_LOW, &[0xd0], &mut context),
+            Emc2101Value::Byte(0xd0)
+        );
+    }
+
+    #[test]
+    fn test_format_transaction() {
+        let mut context = DecodeContext::default();
+        assert_eq!(
+            format_transaction(regs::FAN_SETTING, Some(&[63]), false, &mut context),
+            "<- WRITE FAN_SETTING=100% (0x3f)"
+        );
+        assert_eq!(
+            format_transaction(regs::INTERNAL_TEMP, Some(&[0xf6]), true, &mut context),
+            "-> READ INTERNAL_TEMP=-10 degC"
+        );
+        assert_eq!(
+            format_transaction(regs::MFG_ID, Some(&[EXPECTED_MFG_ID]), true, &mut context),
+            "-> READ MFG_ID=0x5d (SMSC/Microchip)"
+        );
+        assert_eq!(
+            format_transaction(regs::CONFIG, None, false, &mut context),
+            "<- WRITE CONFIG"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_init_enables_tach_and_pwm() {
+        let i2c = MockI2c::new();
+        i2c.expect_write_read(DEFAULT_ADDRESS, &[regs::MFG_ID], 1)
+            .respond_with([EXPECTED_MFG_ID]);
+        i2c.expect_write_read(DEFAULT_ADDRESS, &[regs::PRODUCT_ID], 1)
+            .respond_with([PRODUCT_ID_EMC2101_R]);
+        i2c.expect_write_read(DEFAULT_ADDRESS, &[regs::REVISION], 1)
+            .respond_with([0x01]);
+        i2c.expect_write_read(DEFAULT_ADDRESS, &[regs::CONFIG], 1)
+            .respond_with([0x80]);
+        i2c.expect_write(DEFAULT_ADDRESS, &[regs::CONFIG, 0x84]);
+        i2c.expect_write(DEFAULT_ADDRESS, &[regs::FAN_CONFIG, 0x23]);
+
+        Emc2101::new(i2c.clone()).init().await.unwrap();
+        i2c.done();
+    }
+
+    #[tokio::test]
+    async fn test_init_refuses_other_parts() {
+        let i2c = MockI2c::new();
+        i2c.expect_write_read(DEFAULT_ADDRESS, &[regs::MFG_ID], 1)
+            .respond_with([0x54]);
+        i2c.expect_write_read(DEFAULT_ADDRESS, &[regs::PRODUCT_ID], 1);
+        i2c.expect_write_read(DEFAULT_ADDRESS, &[regs::REVISION], 1);
+
+        assert!(Emc2101::new(i2c.clone()).init().await.is_err());
+        i2c.done();
+    }
+
+    #[tokio::test]
+    async fn test_fan_speed_scaled_to_pwm_range() {
+        let i2c = MockI2c::new();
+        i2c.expect_write(DEFAULT_ADDRESS, &[regs::FAN_SETTING, FAN_DRIVE_MAX]);
+        i2c.expect_write(DEFAULT_ADDRESS, &[regs::FAN_SETTING, 0]);
+        i2c.expect_write(DEFAULT_ADDRESS, &[regs::FAN_SETTING, FAN_DRIVE_MAX])
+            .inject_error(I2cError::NoAck(DEFAULT_ADDRESS));
+
+        let mut fan = Emc2101::new(i2c.clone());
+        fan.set_fan_speed(Percent::FULL).await.unwrap();
+        fan.set_fan_speed(Percent::new_clamped(0)).await.unwrap();
+        assert!(fan.set_fan_speed(Percent::FULL).await.is_err());
+        i2c.done();
+    }
+}
@@ -2,9 +2,13 @@
 //!
 //! This module contains drivers for non-mining peripheral chips such as
 //! temperature sensors (TMP75), power monitors (INA260), fan controllers
-//! (EMC2101), and other board management ICs. All drivers are generic
-//! over the hw_trait interfaces.
+//! (EMC2101), EEPROMs, status displays (SSD1306), and other board management
+//! ICs. All drivers are generic over the hw_trait interfaces.
 
+pub mod eeprom;
 pub mod emc2101;
 pub mod pmbus;
+pub mod scan;
+pub mod smbus;
+pub mod ssd1306;
 pub mod tps546;
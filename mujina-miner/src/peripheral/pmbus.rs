@@ -109,7 +109,10 @@ define_pmbus_commands! {
     OnOffConfig = 0x02, "ON_OFF_CONFIG", "on/off configuration",
     ClearFaults = 0x03, "CLEAR_FAULTS", "clears all fault status bits",
     Phase = 0x04, "PHASE", "phase selection",
+    StoreUserAll = 0x15, "STORE_USER_ALL", "store operating memory to user NVM",
+    RestoreUserAll = 0x16, "RESTORE_USER_ALL", "restore operating memory from user NVM",
     Capability = 0x19, "CAPABILITY", "device capability",
+    Query = 0x1A, "QUERY", "command support and data format",
     VoutMode = 0x20, "VOUT_MODE", "output voltage data format",
     VoutCommand = 0x21, "VOUT_COMMAND", "commanded output voltage",
     VoutMax = 0x24, "VOUT_MAX", "maximum output voltage",
@@ -152,6 +155,7 @@ define_pmbus_commands! {
     ReadVout = 0x8B, "READ_VOUT", "output voltage",
     ReadIout = 0x8C, "READ_IOUT", "output current",
     ReadTemperature1 = 0x8D, "READ_TEMPERATURE_1", "temperature 1",
+    ReadPout = 0x96, "READ_POUT", "output power",
     MfrId = 0x99, "MFR_ID", "manufacturer ID",
     MfrModel = 0x9A, "MFR_MODEL", "manufacturer model",
     MfrRevision = 0x9B, "MFR_REVISION", "manufacturer revision",
@@ -1337,6 +1341,9 @@ pub mod linear11 {
     const MANTISSA_MASK: u16 = 0x07FF;
     const MANTISSA_SIGN_BIT: u16 = 0x0400;
 
+    /// Largest value representable: mantissa 1023 at exponent 15
+    pub const MAX: f32 = 1023.0 * 32768.0;
+
     /// Convert SLINEAR11 format to floating point
     pub fn to_float(value: u16) -> f32 {
         let exp_raw = ((value >> EXPONENT_SHIFT) & 0x1F) as u8;
@@ -1362,31 +1369,34 @@ pub mod linear11 {
     }
 
     /// Convert floating point to SLINEAR11 format
+    ///
+    /// Values beyond the format's range saturate; NaN encodes as zero.
     pub fn from_float(value: f32) -> u16 {
-        if value == 0.0 {
+        if value == 0.0 || value.is_nan() {
             return 0;
         }
 
-        let mut best_exp = 0i8;
+        // Range-check the mantissa after rounding: one that rounds up to
+        // 1024 would wrap to -1024
+        let mut best = None;
         let mut best_error = f32::MAX;
 
         for exp in -16i8..=15 {
-            let mantissa_f = value / 2.0_f32.powi(exp as i32);
+            let mantissa = (value / 2.0_f32.powi(exp as i32)).round();
 
-            if (-1024.0..1024.0).contains(&mantissa_f) {
-                let mantissa = mantissa_f.round() as i32;
-                let reconstructed = mantissa as f32 * 2.0_f32.powi(exp as i32);
+            if (-1024.0..1024.0).contains(&mantissa) {
+                let reconstructed = mantissa * 2.0_f32.powi(exp as i32);
                 let error = (reconstructed - value).abs();
 
                 if error < best_error {
                     best_error = error;
-                    best_exp = exp;
+                    best = Some((exp, mantissa as i32));
                 }
             }
         }
 
-        let mantissa = (value / 2.0_f32.powi(best_exp as i32)).round() as i32;
-        let exp_bits = (best_exp as u16) & 0x1F;
+        let (exp, mantissa) = best.unwrap_or(if value > 0.0 { (15, 1023) } else { (15, -1024) });
+        let exp_bits = (exp as u16) & 0x1F;
         let mant_bits = (mantissa as u16) & MANTISSA_MASK;
 
         (exp_bits << EXPONENT_SHIFT) | mant_bits
@@ -1438,10 +1448,49 @@ pub enum PMBusError {
 mod pmbus_types;
 pub use pmbus_types::*;
 
+mod device;
+pub use device::{CommandSupport, Mismatch, PmbusDevice, QueryFormat, Setting};
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_linear11_reencodes_every_value() {
+        for raw in 0..=u16::MAX {
+            let value = linear11::to_float(raw);
+            assert_eq!(
+                linear11::to_float(linear11::from_float(value)),
+                value,
+                "{raw:#06x}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_linear11_rounding_stays_in_range() {
+        // 63.97 rounds to mantissa 1024 at exponent -4; it must not wrap
+        // to -1024
+        let value = linear11::to_float(linear11::from_float(63.97));
+        assert!((value - 63.97).abs() < 0.05, "{value}");
+
+        assert_eq!(
+            linear11::to_float(linear11::from_float(f32::INFINITY)),
+            linear11::MAX
+        );
+        assert_eq!(linear11::from_float(f32::NAN), 0);
+    }
+
+    #[test]
+    fn test_parse_negative_linear11() {
+        // -5 degC: mantissa -5 at exponent 0
+        let value = parse_pmbus_value(PmbusCommand::ReadTemperature1, &[0xfb, 0x07], None);
+        let PmbusValue::Temperature(temp) = value else {
+            panic!("expected a temperature, got {value:?}");
+        };
+        assert_eq!(temp.value(), -5.0);
+    }
+
     #[test]
     fn test_pmbus_voltage_direct() {
         let voltage = PmbusVoltage::new(3.3);
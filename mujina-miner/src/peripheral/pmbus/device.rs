@@ -0,0 +1,832 @@
+//! A PMBus device on an I2C bus.
+//!
+//! [`PmbusDevice`] holds what every PMBus regulator driver needs: command
+//! reads and writes at the device's address, page selection for multi-rail
+//! parts, QUERY-based discovery of the commands a part implements, VOUT_MODE
+//! handling, telemetry in SI units, and decoding of the status registers.
+//! Drivers for particular parts, such as the TPS546, wrap one and add only
+//! their configuration sequence and manufacturer-specific commands.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use tracing::{debug, error, trace, warn};
+
+use super::{
+    linear11, PmbusCommand, StatusDecoder, StatusInput, StatusIout, StatusTemperature, StatusVout,
+    StatusWord, VoutMode,
+};
+use crate::hw_trait::I2c;
+
+/// What QUERY reports about a command.
+///
+/// Bit 7 says the command is supported, bits 6 and 5 whether it may be
+/// written and read, and bits 4:2 the format of its data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandSupport(pub u8);
+
+/// The data format a QUERY response gives for a command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryFormat {
+    /// SLINEAR11, or ULINEAR16 for output voltage commands
+    Linear,
+    /// 16-bit signed integer
+    Signed,
+    /// DIRECT, scaled by the coefficients
+    Direct,
+    /// 8-bit unsigned integer
+    Unsigned,
+    /// VID code
+    Vid,
+    /// Manufacturer-defined
+    Manufacturer,
+    /// Not a number: bit fields, strings, or no data
+    NonNumeric,
+    Reserved(u8),
+}
+
+/// A value a driver wants a command to hold
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Setting {
+    Byte(PmbusCommand, u8),
+    Word(PmbusCommand, u16),
+    /// A SLINEAR11 word, which matches any encoding of the same value:
+    /// parts may store it with another exponent than it was written with
+    Linear11(PmbusCommand, u16),
+}
+
+/// A setting the device doesn't hold, and what it holds instead
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mismatch {
+    pub wanted: Setting,
+    pub actual: u16,
+}
+
+/// A PMBus device at one address on an I2C bus
+pub struct PmbusDevice<I2C> {
+    i2c: I2C,
+    address: u8,
+    /// Page last selected; None until one is
+    page: Option<u8>,
+    /// VOUT_MODE of each page, read once
+    vout_modes: HashMap<u8, u8>,
+    /// QUERY results for discovered commands
+    support: HashMap<u8, CommandSupport>,
+}
+
+impl CommandSupport {
+    pub fn is_supported(&self) -> bool {
+        self.0 & 0x80 != 0
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.0 & 0x40 != 0
+    }
+
+    pub fn is_readable(&self) -> bool {
+        self.0 & 0x20 != 0
+    }
+
+    pub fn format(&self) -> QueryFormat {
+        match (self.0 >> 2) & 0x07 {
+            0b000 => QueryFormat::Linear,
+            0b001 => QueryFormat::Signed,
+            0b011 => QueryFormat::Direct,
+            0b100 => QueryFormat::Unsigned,
+            0b101 => QueryFormat::Vid,
+            0b110 => QueryFormat::Manufacturer,
+            0b111 => QueryFormat::NonNumeric,
+            format => QueryFormat::Reserved(format),
+        }
+    }
+}
+
+impl Setting {
+    pub fn command(&self) -> PmbusCommand {
+        match *self {
+            Self::Byte(command, _) | Self::Word(command, _) | Self::Linear11(command, _) => command,
+        }
+    }
+
+    /// Whether `actual`, as read back, is this setting's value
+    pub fn is_met_by(&self, actual: u16) -> bool {
+        match *self {
+            Self::Byte(_, value) => actual == value as u16,
+            Self::Word(_, value) => actual == value,
+            Self::Linear11(_, value) => linear11::to_float(actual) == linear11::to_float(value),
+        }
+    }
+}
+
+impl<I2C: I2c> PmbusDevice<I2C> {
+    pub fn new(i2c: I2C, address: u8) -> Self {
+        Self {
+            i2c,
+            address,
+            page: None,
+            vout_modes: HashMap::new(),
+            support: HashMap::new(),
+        }
+    }
+
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+
+    // Command I/O
+
+    /// Send a command that carries no data, like CLEAR_FAULTS
+    pub async fn send_byte(&mut self, command: PmbusCommand) -> Result<()> {
+        self.i2c.write(self.address, &[command.as_u8()]).await?;
+        Ok(())
+    }
+
+    pub async fn read_byte(&mut self, command: PmbusCommand) -> Result<u8> {
+        let mut data = [0u8; 1];
+        self.i2c
+            .write_read(self.address, &[command.as_u8()], &mut data)
+            .await?;
+        Ok(data[0])
+    }
+
+    pub async fn write_byte(&mut self, command: PmbusCommand, data: u8) -> Result<()> {
+        self.i2c
+            .write(self.address, &[command.as_u8(), data])
+            .await?;
+        Ok(())
+    }
+
+    pub async fn read_word(&mut self, command: PmbusCommand) -> Result<u16> {
+        let mut data = [0u8; 2];
+        self.i2c
+            .write_read(self.address, &[command.as_u8()], &mut data)
+            .await?;
+        Ok(u16::from_le_bytes(data))
+    }
+
+    pub async fn write_word(&mut self, command: PmbusCommand, data: u16) -> Result<()> {
+        let bytes = data.to_le_bytes();
+        self.i2c
+            .write(self.address, &[command.as_u8(), bytes[0], bytes[1]])
+            .await?;
+        Ok(())
+    }
+
+    /// Block read of `length` data bytes, without the leading count
+    pub async fn read_block(&mut self, command: PmbusCommand, length: usize) -> Result<Vec<u8>> {
+        let mut buffer = vec![0u8; length + 1];
+        self.i2c
+            .write_read(self.address, &[command.as_u8()], &mut buffer)
+            .await?;
+
+        let reported_length = buffer[0] as usize;
+        if reported_length != length {
+            warn!(
+                command = %command,
+                expected = length,
+                reported = reported_length,
+                "Block read length mismatch"
+            );
+        }
+
+        Ok(buffer[1..=length].to_vec())
+    }
+
+    // Paging
+
+    /// Select the page later commands apply to.
+    ///
+    /// The selection is remembered, so selecting the current page again
+    /// costs no bus traffic.
+    pub async fn select_page(&mut self, page: u8) -> Result<()> {
+        if self.page != Some(page) {
+            self.write_byte(PmbusCommand::Page, page).await?;
+            self.page = Some(page);
+        }
+        Ok(())
+    }
+
+    /// The page last selected, if any
+    pub fn page(&self) -> Option<u8> {
+        self.page
+    }
+
+    /// Select the phase later commands apply to; 0xFF selects all of them
+    pub async fn select_phase(&mut self, phase: u8) -> Result<()> {
+        self.write_byte(PmbusCommand::Phase, phase).await
+    }
+
+    // Capability discovery
+
+    /// Ask the device whether it implements `command`, and how.
+    pub async fn query(&mut self, command: PmbusCommand) -> Result<CommandSupport> {
+        // Block write-block read process call: one byte each way
+        let mut response = [0u8; 2];
+        self.i2c
+            .write_read(
+                self.address,
+                &[PmbusCommand::Query.as_u8(), 1, command.as_u8()],
+                &mut response,
+            )
+            .await?;
+        let support = CommandSupport(response[1]);
+        self.support.insert(command.as_u8(), support);
+        Ok(support)
+    }
+
+    /// Query each of `commands`, remembering the answers for
+    /// [`supports`](Self::supports).
+    ///
+    /// Parts without QUERY refuse the first one; they're left undiscovered
+    /// and every command is assumed supported.
+    pub async fn discover(&mut self, commands: &[PmbusCommand]) -> Result<()> {
+        for &command in commands {
+            if let Err(e) = self.query(command).await {
+                debug!(address = self.address, error = %e, "QUERY unavailable");
+                self.support.clear();
+                return Ok(());
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether `command` is implemented, as far as discovery found.
+    ///
+    /// Commands that weren't discovered are assumed to be.
+    pub fn supports(&self, command: PmbusCommand) -> bool {
+        self.support
+            .get(&command.as_u8())
+            .is_none_or(CommandSupport::is_supported)
+    }
+
+    // Configuration
+
+    /// Read back each of `settings` and return those the device doesn't
+    /// hold.
+    pub async fn diff(&mut self, settings: &[Setting]) -> Result<Vec<Mismatch>> {
+        let mut mismatches = Vec::new();
+        for &wanted in settings {
+            let actual = match wanted {
+                Setting::Byte(command, _) => self.read_byte(command).await? as u16,
+                Setting::Word(command, _) | Setting::Linear11(command, _) => {
+                    self.read_word(command).await?
+                }
+            };
+            if !wanted.is_met_by(actual) {
+                mismatches.push(Mismatch { wanted, actual });
+            }
+        }
+        Ok(mismatches)
+    }
+
+    /// Write those of `settings` the device doesn't already hold, in order.
+    ///
+    /// Returns how many were written. Registers that match are left alone,
+    /// which spares bus traffic and, for settings restored from NVM, keeps
+    /// the device from seeing any change.
+    pub async fn apply(&mut self, settings: &[Setting]) -> Result<usize> {
+        let mismatches = self.diff(settings).await?;
+        for mismatch in &mismatches {
+            let command = mismatch.wanted.command();
+            trace!(%command, from = mismatch.actual, wanted = ?mismatch.wanted, "Writing setting");
+            match mismatch.wanted {
+                Setting::Byte(_, value) => self.write_byte(command, value).await?,
+                Setting::Word(_, value) | Setting::Linear11(_, value) => {
+                    self.write_word(command, value).await?
+                }
+            }
+            if command == PmbusCommand::VoutMode {
+                self.invalidate_vout_mode();
+            }
+        }
+        Ok(mismatches.len())
+    }
+
+    /// Copy the operating settings to the device's user NVM, so it powers
+    /// up with them.
+    ///
+    /// Parts ignore the bus while they program their NVM; callers wait out
+    /// the time their datasheet gives before the next command.
+    pub async fn store_user_all(&mut self) -> Result<()> {
+        self.send_byte(PmbusCommand::StoreUserAll).await
+    }
+
+    /// Replace the operating settings with those stored in user NVM
+    pub async fn restore_user_all(&mut self) -> Result<()> {
+        self.send_byte(PmbusCommand::RestoreUserAll).await?;
+        self.invalidate_vout_mode();
+        Ok(())
+    }
+
+    // Output voltage format
+
+    /// VOUT_MODE of the current page, read on first use
+    pub async fn vout_mode(&mut self) -> Result<u8> {
+        let page = self.page.unwrap_or(0);
+        if let Some(&mode) = self.vout_modes.get(&page) {
+            return Ok(mode);
+        }
+        let mode = self.read_byte(PmbusCommand::VoutMode).await?;
+        debug!(
+            page,
+            vout_mode = format_args!("0x{:02x}", mode),
+            "Read VOUT_MODE"
+        );
+        self.vout_modes.insert(page, mode);
+        Ok(mode)
+    }
+
+    /// Forget VOUT_MODE, after writing it or restoring settings
+    pub fn invalidate_vout_mode(&mut self) {
+        self.vout_modes.clear();
+    }
+
+    /// Encode an output voltage command in the device's VOUT_MODE
+    pub async fn encode_voltage(&mut self, volts: f32) -> Result<u16> {
+        let vout_mode = VoutMode::new(self.vout_mode().await?);
+        vout_mode
+            .encode_linear16(volts)
+            .map_err(|e| anyhow::anyhow!("Voltage encoding error: {}", e))
+    }
+
+    /// Decode an output voltage reading or limit
+    pub async fn decode_voltage(&mut self, value: u16) -> Result<f32> {
+        let vout_mode = VoutMode::new(self.vout_mode().await?);
+        Ok(vout_mode.decode_linear16(value))
+    }
+
+    // Telemetry
+
+    /// Input voltage in volts
+    pub async fn read_vin(&mut self) -> Result<f32> {
+        self.read_linear11(PmbusCommand::ReadVin).await
+    }
+
+    /// Output voltage in volts
+    pub async fn read_vout(&mut self) -> Result<f32> {
+        let value = self.read_word(PmbusCommand::ReadVout).await?;
+        self.decode_voltage(value).await
+    }
+
+    /// Output current in amps
+    pub async fn read_iout(&mut self) -> Result<f32> {
+        self.read_linear11(PmbusCommand::ReadIout).await
+    }
+
+    /// Output power in watts
+    pub async fn read_pout(&mut self) -> Result<f32> {
+        self.read_linear11(PmbusCommand::ReadPout).await
+    }
+
+    /// Temperature in degrees Celsius
+    pub async fn read_temperature(&mut self) -> Result<f32> {
+        self.read_linear11(PmbusCommand::ReadTemperature1).await
+    }
+
+    /// Read a SLINEAR11 command as a float
+    pub async fn read_linear11(&mut self, command: PmbusCommand) -> Result<f32> {
+        Ok(linear11::to_float(self.read_word(command).await?))
+    }
+
+    // Status
+
+    pub async fn clear_faults(&mut self) -> Result<()> {
+        self.send_byte(PmbusCommand::ClearFaults).await
+    }
+
+    pub async fn status_word(&mut self) -> Result<u16> {
+        self.read_word(PmbusCommand::StatusWord).await
+    }
+
+    /// Read the status registers STATUS_WORD points at and describe the
+    /// faults among them.
+    ///
+    /// Warnings are logged; faults that leave the output unsafe or off are
+    /// logged and returned, so the caller decides what they mean for it. An
+    /// empty list means the output is usable.
+    pub async fn check_faults(&mut self) -> Result<Vec<String>> {
+        let status = self.status_word().await?;
+
+        let mut critical_faults = Vec::new();
+        if status == 0 {
+            return Ok(critical_faults);
+        }
+
+        let status_flags = StatusWord::from_bits_truncate(status);
+        if status_flags.contains(StatusWord::VOUT) {
+            let vout_status = self.read_byte(PmbusCommand::StatusVout).await?;
+            let desc = StatusDecoder::decode_status_vout(vout_status).join(", ");
+
+            // The output is outside its safe operating range
+            let vout_flags = StatusVout::from_bits_truncate(vout_status);
+            if vout_flags.intersects(StatusVout::VOUT_OV_FAULT | StatusVout::VOUT_UV_FAULT) {
+                error!(
+                    "CRITICAL: VOUT fault detected: 0x{:02X} ({})",
+                    vout_status, desc
+                );
+                critical_faults.push(format!("VOUT fault: {}", desc));
+            } else {
+                warn!("VOUT warning: 0x{:02X} ({})", vout_status, desc);
+            }
+        }
+
+        if status_flags.contains(StatusWord::IOUT) {
+            let iout_status = self.read_byte(PmbusCommand::StatusIout).await?;
+            let desc = StatusDecoder::decode_status_iout(iout_status).join(", ");
+
+            // Overcurrent can damage hardware
+            let iout_flags = StatusIout::from_bits_truncate(iout_status);
+            if iout_flags.contains(StatusIout::IOUT_OC_FAULT) {
+                error!(
+                    "CRITICAL: IOUT overcurrent fault detected: 0x{:02X} ({})",
+                    iout_status, desc
+                );
+                critical_faults.push(format!("IOUT overcurrent: {}", desc));
+            } else {
+                warn!("IOUT warning: 0x{:02X} ({})", iout_status, desc);
+            }
+        }
+
+        if status_flags.contains(StatusWord::INPUT) {
+            let input_status = self.read_byte(PmbusCommand::StatusInput).await?;
+            let desc = StatusDecoder::decode_status_input(input_status).join(", ");
+
+            // The unit is off for want of a usable input
+            let input_flags = StatusInput::from_bits_truncate(input_status);
+            if input_flags.intersects(
+                StatusInput::UNIT_OFF_VIN_LOW
+                    | StatusInput::VIN_UV_FAULT
+                    | StatusInput::VIN_OV_FAULT,
+            ) {
+                error!(
+                    "CRITICAL: INPUT fault detected: 0x{:02X} ({})",
+                    input_status, desc
+                );
+                critical_faults.push(format!("INPUT fault: {}", desc));
+            } else {
+                warn!("INPUT warning: 0x{:02X} ({})", input_status, desc);
+            }
+        }
+
+        if status_flags.contains(StatusWord::TEMP) {
+            let temp_status = self.read_byte(PmbusCommand::StatusTemperature).await?;
+            let desc = StatusDecoder::decode_status_temp(temp_status).join(", ");
+
+            let temp_flags = StatusTemperature::from_bits_truncate(temp_status);
+            if temp_flags.contains(StatusTemperature::OT_FAULT) {
+                error!(
+                    "CRITICAL: Overtemperature fault detected: 0x{:02X} ({})",
+                    temp_status, desc
+                );
+                critical_faults.push(format!("Overtemperature: {}", desc));
+            } else {
+                warn!("TEMPERATURE warning: 0x{:02X} ({})", temp_status, desc);
+            }
+        }
+
+        // Communication, memory, and logic faults leave the device's state
+        // in doubt
+        if status_flags.contains(StatusWord::CML) {
+            let cml_status = self.read_byte(PmbusCommand::StatusCml).await?;
+            let desc = StatusDecoder::decode_status_cml(cml_status).join(", ");
+            error!(
+                "CRITICAL: CML fault detected: 0x{:02X} ({})",
+                cml_status, desc
+            );
+            critical_faults.push(format!("CML fault: {}", desc));
+        }
+
+        if status_flags.contains(StatusWord::OFF) {
+            error!(
+                "CRITICAL: Power controller is OFF - Reading all status registers for diagnostics"
+            );
+            error!("STATUS_WORD: 0x{:04X}", status);
+            self.log_off_diagnostics().await;
+            critical_faults.push("Power controller is OFF".to_string());
+        }
+
+        Ok(critical_faults)
+    }
+
+    /// Log every status register and the telemetry, to explain why the
+    /// output shut off
+    async fn log_off_diagnostics(&mut self) {
+        for command in [
+            PmbusCommand::StatusVout,
+            PmbusCommand::StatusIout,
+            PmbusCommand::StatusInput,
+            PmbusCommand::StatusTemperature,
+            PmbusCommand::StatusCml,
+        ] {
+            if let Ok(status) = self.read_byte(command).await {
+                let desc = describe_status_byte(command, status);
+                error!("  {}: 0x{:02X} ({})", command, status, desc.join(", "));
+            }
+        }
+
+        if let Ok(vout) = self.read_vout().await {
+            error!("  Current VOUT: {:.3}V", vout);
+        }
+        if let Ok(iout) = self.read_iout().await {
+            error!("  Current IOUT: {:.2}A", iout);
+        }
+        if let Ok(vin) = self.read_vin().await {
+            error!("  Current VIN: {:.2}V", vin);
+        }
+        if let Ok(temp) = self.read_temperature().await {
+            error!("  Current Temperature: {} degC", temp as i32);
+        }
+    }
+
+    /// Log STATUS_WORD and the status registers it points at
+    pub async fn dump_status(&mut self) -> Result<()> {
+        let status_word = self.status_word().await?;
+        let status_desc = StatusDecoder::decode_status_word(status_word);
+        if status_desc.is_empty() {
+            debug!("STATUS_WORD: 0x{:04X} (no flags set)", status_word);
+        } else {
+            debug!(
+                "STATUS_WORD: 0x{:04X} ({})",
+                status_word,
+                status_desc.join(", ")
+            );
+        }
+
+        let status_flags = StatusWord::from_bits_truncate(status_word);
+        for (flag, command) in [
+            (StatusWord::VOUT, PmbusCommand::StatusVout),
+            (StatusWord::IOUT, PmbusCommand::StatusIout),
+            (StatusWord::INPUT, PmbusCommand::StatusInput),
+            (StatusWord::TEMP, PmbusCommand::StatusTemperature),
+            (StatusWord::CML, PmbusCommand::StatusCml),
+        ] {
+            if status_flags.contains(flag) {
+                let status = self.read_byte(command).await?;
+                let desc = describe_status_byte(command, status);
+                debug!("{}: 0x{:02X} ({})", command, status, desc.join(", "));
+            }
+        }
+        Ok(())
+    }
+
+    /// Log the telemetry the device supports
+    pub async fn dump_telemetry(&mut self) -> Result<()> {
+        if self.supports(PmbusCommand::ReadVin) {
+            debug!("READ_VIN: {:.2}V", self.read_vin().await?);
+        }
+        if self.supports(PmbusCommand::ReadVout) {
+            debug!("READ_VOUT: {:.2}V", self.read_vout().await?);
+        }
+        if self.supports(PmbusCommand::ReadIout) {
+            debug!("READ_IOUT: {:.2}A", self.read_iout().await?);
+        }
+        if self.supports(PmbusCommand::ReadPout) {
+            debug!("READ_POUT: {:.2}W", self.read_pout().await?);
+        }
+        if self.supports(PmbusCommand::ReadTemperature1) {
+            debug!(
+                "READ_TEMPERATURE_1: {} degC",
+                self.read_temperature().await? as i32
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Describe the bits set in one of the status byte registers
+fn describe_status_byte(command: PmbusCommand, status: u8) -> Vec<&'static str> {
+    match command {
+        PmbusCommand::StatusVout => StatusDecoder::decode_status_vout(status),
+        PmbusCommand::StatusIout => StatusDecoder::decode_status_iout(status),
+        PmbusCommand::StatusInput => StatusDecoder::decode_status_input(status),
+        PmbusCommand::StatusTemperature => StatusDecoder::decode_status_temp(status),
+        PmbusCommand::StatusCml => StatusDecoder::decode_status_cml(status),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use async_trait::async_trait;
+    use parking_lot::Mutex;
+
+    use super::*;
+    use crate::hw_trait::{i2c::I2cError, Result as HwResult};
+
+    const ADDRESS: u8 = 0x24;
+
+    /// Register contents by page and command
+    type Registers = HashMap<(u8, u8), Vec<u8>>;
+
+    /// A PMBus part answering from a register map, recording each write.
+    ///
+    /// Registers are kept per page; QUERY answers from `support`, or is
+    /// refused if that's empty.
+    #[derive(Clone, Default)]
+    struct MemPmbus {
+        registers: Arc<Mutex<Registers>>,
+        support: Arc<Mutex<HashMap<u8, u8>>>,
+        writes: Arc<Mutex<Vec<Vec<u8>>>>,
+        page: Arc<Mutex<u8>>,
+    }
+
+    impl MemPmbus {
+        fn set(&self, page: u8, command: PmbusCommand, data: &[u8]) {
+            self.registers
+                .lock()
+                .insert((page, command.as_u8()), data.to_vec());
+        }
+    }
+
+    #[async_trait]
+    impl I2c for MemPmbus {
+        async fn write(&mut self, addr: u8, data: &[u8]) -> HwResult<()> {
+            assert_eq!(addr, ADDRESS);
+            if data[0] == PmbusCommand::Page.as_u8() {
+                *self.page.lock() = data[1];
+            }
+            self.writes.lock().push(data.to_vec());
+            Ok(())
+        }
+
+        async fn read(&mut self, _addr: u8, _buffer: &mut [u8]) -> HwResult<()> {
+            unimplemented!("reads go through write_read")
+        }
+
+        async fn write_read(&mut self, addr: u8, write: &[u8], read: &mut [u8]) -> HwResult<()> {
+            assert_eq!(addr, ADDRESS);
+            if write[0] == PmbusCommand::Query.as_u8() {
+                let support = self.support.lock();
+                if support.is_empty() {
+                    return Err(I2cError::NoAck(addr).into());
+                }
+                read.copy_from_slice(&[1, support.get(&write[2]).copied().unwrap_or(0)]);
+                return Ok(());
+            }
+            let page = *self.page.lock();
+            let registers = self.registers.lock();
+            let data = registers
+                .get(&(page, write[0]))
+                .ok_or(I2cError::NoAck(addr))?;
+            read.copy_from_slice(&data[..read.len()]);
+            Ok(())
+        }
+
+        async fn set_frequency(&mut self, _hz: u32) -> HwResult<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_command_support_decoding() {
+        // Supported, read-write, linear
+        let support = CommandSupport(0b1110_0000);
+        assert!(support.is_supported() && support.is_writable() && support.is_readable());
+        assert_eq!(support.format(), QueryFormat::Linear);
+
+        // Supported, read-only, not numeric
+        let support = CommandSupport(0b1011_1100);
+        assert!(!support.is_writable());
+        assert_eq!(support.format(), QueryFormat::NonNumeric);
+
+        assert!(!CommandSupport(0).is_supported());
+    }
+
+    #[tokio::test]
+    async fn test_discovery() {
+        let part = MemPmbus::default();
+        part.support
+            .lock()
+            .insert(PmbusCommand::ReadVin.as_u8(), 0b1010_0000);
+        part.set(
+            0,
+            PmbusCommand::ReadVin,
+            &linear11::from_float(12.0).to_le_bytes(),
+        );
+        let mut device = PmbusDevice::new(part.clone(), ADDRESS);
+
+        device
+            .discover(&[PmbusCommand::ReadVin, PmbusCommand::ReadPout])
+            .await
+            .unwrap();
+        assert!(device.supports(PmbusCommand::ReadVin));
+        assert!(!device.supports(PmbusCommand::ReadPout));
+        assert!(device.supports(PmbusCommand::ReadIout), "undiscovered");
+
+        part.support.lock().clear();
+        let mut device = PmbusDevice::new(part, ADDRESS);
+        device.discover(&[PmbusCommand::ReadVin]).await.unwrap();
+        assert!(device.supports(PmbusCommand::ReadPout), "no QUERY");
+    }
+
+    #[tokio::test]
+    async fn test_pages_keep_their_own_vout_mode() {
+        let part = MemPmbus::default();
+        part.set(0, PmbusCommand::VoutMode, &[0x17]); // 2^-9
+        part.set(1, PmbusCommand::VoutMode, &[0x18]); // 2^-8
+        part.set(0, PmbusCommand::ReadVout, &512u16.to_le_bytes());
+        part.set(1, PmbusCommand::ReadVout, &512u16.to_le_bytes());
+        let mut device = PmbusDevice::new(part.clone(), ADDRESS);
+
+        device.select_page(0).await.unwrap();
+        assert_eq!(device.read_vout().await.unwrap(), 1.0);
+        device.select_page(1).await.unwrap();
+        assert_eq!(device.read_vout().await.unwrap(), 2.0);
+        device.select_page(1).await.unwrap();
+
+        let page_writes = part
+            .writes
+            .lock()
+            .iter()
+            .filter(|w| w[0] == PmbusCommand::Page.as_u8())
+            .count();
+        assert_eq!(page_writes, 2, "reselecting the current page is free");
+    }
+
+    #[tokio::test]
+    async fn test_telemetry_in_si_units() {
+        let part = MemPmbus::default();
+        part.set(
+            0,
+            PmbusCommand::ReadVin,
+            &linear11::from_float(12.0).to_le_bytes(),
+        );
+        part.set(
+            0,
+            PmbusCommand::ReadIout,
+            &linear11::from_float(10.5).to_le_bytes(),
+        );
+        part.set(
+            0,
+            PmbusCommand::ReadTemperature1,
+            &linear11::from_float(-5.0).to_le_bytes(),
+        );
+        let mut device = PmbusDevice::new(part, ADDRESS);
+
+        assert_eq!(device.read_vin().await.unwrap(), 12.0);
+        assert_eq!(device.read_iout().await.unwrap(), 10.5);
+        assert_eq!(device.read_temperature().await.unwrap(), -5.0);
+    }
+
+    #[tokio::test]
+    async fn test_apply_writes_only_what_differs() {
+        let part = MemPmbus::default();
+        // 650 stored as 325 * 2^1, written as 650 * 2^0
+        part.set(0, PmbusCommand::FrequencySwitch, &0x0945u16.to_le_bytes());
+        part.set(0, PmbusCommand::OtFaultResponse, &[0xC0]);
+        part.set(0, PmbusCommand::PinDetectOverride, &0x0000u16.to_le_bytes());
+        let mut device = PmbusDevice::new(part.clone(), ADDRESS);
+
+        let settings = [
+            Setting::Linear11(PmbusCommand::FrequencySwitch, 0x028A),
+            Setting::Byte(PmbusCommand::OtFaultResponse, 0xC0),
+            Setting::Word(PmbusCommand::PinDetectOverride, 0xFFFF),
+        ];
+        let mismatches = device.diff(&settings).await.unwrap();
+        assert_eq!(
+            mismatches,
+            vec![Mismatch {
+                wanted: settings[2],
+                actual: 0,
+            }]
+        );
+
+        assert_eq!(device.apply(&settings).await.unwrap(), 1);
+        assert_eq!(
+            *part.writes.lock(),
+            vec![vec![PmbusCommand::PinDetectOverride.as_u8(), 0xFF, 0xFF]]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_faults_reported_warnings_not() {
+        let part = MemPmbus::default();
+        let mut device = PmbusDevice::new(part.clone(), ADDRESS);
+
+        part.set(0, PmbusCommand::StatusWord, &0u16.to_le_bytes());
+        assert!(device.check_faults().await.unwrap().is_empty());
+
+        part.set(
+            0,
+            PmbusCommand::StatusWord,
+            &StatusWord::TEMP.bits().to_le_bytes(),
+        );
+        part.set(
+            0,
+            PmbusCommand::StatusTemperature,
+            &[StatusTemperature::OT_WARN.bits()],
+        );
+        assert!(device.check_faults().await.unwrap().is_empty());
+
+        part.set(
+            0,
+            PmbusCommand::StatusTemperature,
+            &[StatusTemperature::OT_FAULT.bits()],
+        );
+        let faults = device.check_faults().await.unwrap();
+        assert_eq!(faults.len(), 1);
+        assert!(faults[0].starts_with("Overtemperature"));
+    }
+}
@@ -3,7 +3,7 @@
 //! This module provides type-safe wrappers for PMBus Linear11 and Linear16 formats,
 //! along with VOUT_MODE handling for proper voltage encoding/decoding.
 
-use super::{extract_5bit_exponent, PMBusError};
+use super::{extract_5bit_exponent, linear11, PMBusError};
 
 /// PMBus VOUT_MODE format enumeration
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -78,7 +78,7 @@ impl VoutMode {
     }
 }
 
-/// Linear11 format (11-bit mantissa, 5-bit exponent)
+/// Linear11 format (11-bit two's complement mantissa, 5-bit exponent)
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Linear11(pub u16);
 
@@ -88,45 +88,14 @@ impl Linear11 {
     }
 
     pub fn to_f32(self) -> f32 {
-        let exp_raw = ((self.0 >> 11) & 0x1F) as u8;
-        let exponent = extract_5bit_exponent(exp_raw) as i32;
-        let mantissa = (self.0 & 0x07FF) as u32;
-        mantissa as f32 * 2.0_f32.powi(exponent)
+        linear11::to_float(self.0)
     }
 
     pub fn from_f32(value: f32) -> Result<Self, PMBusError> {
-        if value == 0.0 {
-            return Ok(Self(0));
-        }
-
-        // Find best exponent for unsigned mantissa
-        let mut best_exp = 0i8;
-        let mut best_error = f32::MAX;
-
-        for exp in -16i8..=15 {
-            let mantissa_f = value / 2.0_f32.powi(exp as i32);
-
-            if (0.0..2048.0).contains(&mantissa_f) {
-                let mantissa = mantissa_f.round() as u32;
-                let reconstructed = mantissa as f32 * 2.0_f32.powi(exp as i32);
-                let error = (reconstructed - value).abs();
-
-                if error < best_error {
-                    best_error = error;
-                    best_exp = exp;
-                }
-            }
-        }
-
-        let mantissa = (value / 2.0_f32.powi(best_exp as i32)).round() as u32;
-        if mantissa > 0x07FF {
+        if !value.is_finite() || value.abs() > linear11::MAX {
             return Err(PMBusError::ValueOutOfRange);
         }
-
-        let exp_bits = (best_exp as u16) & 0x1F;
-        let mant_bits = (mantissa & 0x07FF) as u16;
-
-        Ok(Self((exp_bits << 11) | mant_bits))
+        Ok(Self(linear11::from_float(value)))
     }
 }
 
@@ -252,9 +221,12 @@ mod tests {
 
     #[test]
     fn test_linear11_max_mantissa() {
-        // Max unsigned mantissa is 0x7FF (2047) with exp=0
-        let l = Linear11::new(0x07FF); // exp=0, mant=2047
-        assert_eq!(l.to_f32(), 2047.0);
+        // The mantissa is two's complement: 0x3FF (1023) is the largest,
+        // and 0x7FF is -1
+        let l = Linear11::new(0x03FF); // exp=0, mant=1023
+        assert_eq!(l.to_f32(), 1023.0);
+        let l = Linear11::new(0x07FF); // exp=0, mant=-1
+        assert_eq!(l.to_f32(), -1.0);
 
         // Test with positive exponent
         let l = Linear11::new(0x7801); // exp=15, mant=1
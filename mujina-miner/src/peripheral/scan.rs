@@ -0,0 +1,173 @@
+//! I2C bus scanning.
+//!
+//! Finds the devices on a bus and names those it recognizes, for bringing
+//! up board revisions whose parts aren't known yet. Every address outside
+//! the ranges the I2C spec reserves gets a one-byte read; a device that
+//! acknowledges it is then checked against the signatures of the parts the
+//! drivers here know, each only at the addresses that part can take, so
+//! unknown devices see nothing but reads.
+
+use std::ops::RangeInclusive;
+
+use serde::{Deserialize, Serialize};
+
+use super::pmbus::PmbusCommand;
+use super::{eeprom, emc2101, tps546};
+use crate::hw_trait::I2c;
+
+/// Addresses scanned: all 7-bit addresses but the reserved ones.
+pub const SCAN_RANGE: RangeInclusive<u8> = 0x08..=0x77;
+
+/// Addresses 24-series EEPROMs can be strapped to. They have no ID
+/// register, so they're named by address alone.
+const EEPROM_ADDRESSES: RangeInclusive<u8> = eeprom::DEFAULT_ADDRESS..=0x57;
+
+/// A device that answered a scan.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScannedDevice {
+    /// 7-bit address
+    pub address: u8,
+    /// Part the device was recognized as, if any
+    pub part: Option<String>,
+}
+
+/// Scan `i2c` for devices and identify the ones it can.
+pub async fn scan<I2C: I2c>(i2c: &mut I2C) -> Vec<ScannedDevice> {
+    let mut devices = Vec::new();
+    for address in SCAN_RANGE {
+        if i2c.read(address, &mut [0u8; 1]).await.is_ok() {
+            let part = identify(i2c, address).await.map(str::to_string);
+            devices.push(ScannedDevice { address, part });
+        }
+    }
+    devices
+}
+
+/// Name the part at `address`, if it's one the drivers know.
+async fn identify<I2C: I2c>(i2c: &mut I2C, address: u8) -> Option<&'static str> {
+    if EEPROM_ADDRESSES.contains(&address) {
+        return Some("24-series EEPROM");
+    }
+
+    if address == emc2101::protocol::DEFAULT_ADDRESS {
+        let mut mfg_id = [0u8; 1];
+        let mut product_id = [0u8; 1];
+        i2c.write_read(address, &[emc2101::protocol::regs::MFG_ID], &mut mfg_id)
+            .await
+            .ok()?;
+        i2c.write_read(
+            address,
+            &[emc2101::protocol::regs::PRODUCT_ID],
+            &mut product_id,
+        )
+        .await
+        .ok()?;
+        if mfg_id[0] == emc2101::protocol::EXPECTED_MFG_ID {
+            match product_id[0] {
+                emc2101::protocol::PRODUCT_ID_EMC2101 => return Some("EMC2101"),
+                emc2101::protocol::PRODUCT_ID_EMC2101_R => return Some("EMC2101-R"),
+                _ => {}
+            }
+        }
+    }
+
+    // PMBus block read: length byte, then the ID
+    let mut id = [0u8; 7];
+    i2c.write_read(address, &[PmbusCommand::IcDeviceId.as_u8()], &mut id)
+        .await
+        .ok()?;
+    match id[1..] {
+        ref id if id == tps546::constants::DEVICE_ID1 || id == tps546::constants::DEVICE_ID2 => {
+            Some("TPS546D24A")
+        }
+        ref id if id == tps546::constants::DEVICE_ID3 => Some("TPS546D24S"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::hw_trait::{i2c::I2cError, Result as HwResult};
+
+    /// A bus of devices answering from register maps, by address.
+    #[derive(Default)]
+    struct Bus {
+        devices: HashMap<u8, HashMap<u8, Vec<u8>>>,
+    }
+
+    impl Bus {
+        fn with(mut self, address: u8, registers: &[(u8, &[u8])]) -> Self {
+            let registers = registers
+                .iter()
+                .map(|(register, data)| (*register, data.to_vec()))
+                .collect();
+            self.devices.insert(address, registers);
+            self
+        }
+    }
+
+    #[async_trait]
+    impl I2c for Bus {
+        async fn write(&mut self, _addr: u8, _data: &[u8]) -> HwResult<()> {
+            unimplemented!("scans only read")
+        }
+
+        async fn read(&mut self, addr: u8, _buffer: &mut [u8]) -> HwResult<()> {
+            match self.devices.contains_key(&addr) {
+                true => Ok(()),
+                false => Err(I2cError::NoAck(addr).into()),
+            }
+        }
+
+        async fn write_read(&mut self, addr: u8, write: &[u8], read: &mut [u8]) -> HwResult<()> {
+            let data = self
+                .devices
+                .get(&addr)
+                .and_then(|registers| registers.get(&write[0]))
+                .ok_or(I2cError::NoAck(addr))?;
+            read.copy_from_slice(&data[..read.len()]);
+            Ok(())
+        }
+
+        async fn set_frequency(&mut self, _hz: u32) -> HwResult<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scan_names_known_parts() {
+        let mut tps546_id = vec![6];
+        tps546_id.extend(tps546::constants::DEVICE_ID3);
+        let mut bus = Bus::default()
+            .with(0x24, &[(PmbusCommand::IcDeviceId.as_u8(), &tps546_id)])
+            .with(
+                0x4C,
+                &[
+                    (emc2101::protocol::regs::MFG_ID, &[0x5D]),
+                    (emc2101::protocol::regs::PRODUCT_ID, &[0x28]),
+                ],
+            )
+            .with(0x50, &[])
+            .with(0x68, &[]);
+
+        let found = scan(&mut bus).await;
+        let parts: Vec<_> = found
+            .iter()
+            .map(|device| (device.address, device.part.as_deref()))
+            .collect();
+        assert_eq!(
+            parts,
+            vec![
+                (0x24, Some("TPS546D24S")),
+                (0x4C, Some("EMC2101-R")),
+                (0x50, Some("24-series EEPROM")),
+                (0x68, None),
+            ]
+        );
+    }
+}
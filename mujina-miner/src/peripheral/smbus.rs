@@ -0,0 +1,80 @@
+//! Heuristic decoding of SMBus transfers from unknown devices.
+//!
+//! Without a register map, the length and shape of the data are the only
+//! clues. SMBus defines byte, word (two bytes, little-endian), and block
+//! (count byte followed by that many bytes) transfers, and block reads from
+//! management devices are often ASCII identification strings. These
+//! heuristics pick the most plausible reading; they can be wrong, so the
+//! raw bytes are always shown alongside.
+
+use std::fmt;
+
+/// Most plausible interpretation of an SMBus transfer's data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SmbusValue {
+    Byte(u8),
+    Word(u16),
+    Block(Vec<u8>),
+    Text(String),
+    Raw(Vec<u8>),
+}
+
+impl fmt::Display for SmbusValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Byte(value) => write!(f, "0x{:02x} ({})", value, value),
+            Self::Word(value) => write!(f, "0x{:04x} ({})", value, value),
+            Self::Block(data) => write!(f, "block[{}] {:02x?}", data.len(), data),
+            Self::Text(text) => write!(f, "{:?}", text),
+            Self::Raw(data) => write!(f, "{:02x?}", data),
+        }
+    }
+}
+
+/// Guess how to interpret the data of an SMBus transfer.
+pub fn parse_smbus_value(data: &[u8]) -> SmbusValue {
+    match data {
+        [value] => SmbusValue::Byte(*value),
+        [low, high] => SmbusValue::Word(u16::from_le_bytes([*low, *high])),
+        [count, payload @ ..] if *count as usize == payload.len() && !payload.is_empty() => {
+            let printable = payload.iter().all(|b| b.is_ascii_graphic() || *b == b' ');
+            if printable {
+                SmbusValue::Text(String::from_utf8_lossy(payload).into_owned())
+            } else {
+                SmbusValue::Block(payload.to_vec())
+            }
+        }
+        _ => SmbusValue::Raw(data.to_vec()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_smbus_value() {
+        assert_eq!(parse_smbus_value(&[0x2a]), SmbusValue::Byte(0x2a));
+        assert_eq!(parse_smbus_value(&[0x34, 0x12]), SmbusValue::Word(0x1234));
+        assert_eq!(
+            parse_smbus_value(&[3, b'T', b'I', b'!']),
+            SmbusValue::Text("TI!".to_string())
+        );
+        assert_eq!(
+            parse_smbus_value(&[3, 0x00, 0x01, 0x02]),
+            SmbusValue::Block(vec![0x00, 0x01, 0x02])
+        );
+        // Count byte doesn't match the length: not a block
+        assert_eq!(
+            parse_smbus_value(&[9, 0x00, 0x01]),
+            SmbusValue::Raw(vec![9, 0x00, 0x01])
+        );
+        assert_eq!(parse_smbus_value(&[]), SmbusValue::Raw(vec![]));
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(SmbusValue::Word(0x1234).to_string(), "0x1234 (4660)");
+        assert_eq!(SmbusValue::Text("TPS546".into()).to_string(), "\"TPS546\"");
+    }
+}
@@ -0,0 +1,340 @@
+//! SSD1306 OLED display driver.
+//!
+//! The Bitaxe's 128x32 status screen. The driver keeps a frame buffer the
+//! size of the screen, draws text into it with a fixed 5x7 font, and sends
+//! the whole frame on [`Ssd1306::flush`], so nothing on the bus depends on
+//! what was drawn before.
+//!
+//! Datasheet: <https://cdn-shop.adafruit.com/datasheets/SSD1306.pdf>
+
+use crate::hw_trait::{i2c::I2c, Result};
+
+/// Default I2C address for SSD1306 modules (SA0 low)
+pub const DEFAULT_ADDRESS: u8 = 0x3C;
+
+/// Screen width in pixels
+pub const WIDTH: usize = 128;
+
+/// Screen height in pixels
+pub const HEIGHT: usize = 32;
+
+/// Lines of text that fit on the screen, one per 8-pixel page
+pub const LINES: usize = HEIGHT / 8;
+
+/// Characters that fit on a line
+pub const COLUMNS: usize = WIDTH / GLYPH_WIDTH;
+
+/// Pixels a character takes across, its spacing included
+const GLYPH_WIDTH: usize = 6;
+
+/// Control byte ahead of a command stream
+const CONTROL_COMMAND: u8 = 0x00;
+
+/// Control byte ahead of display data
+const CONTROL_DATA: u8 = 0x40;
+
+/// Display data sent per write. The management protocol tunnels each write
+/// in one packet, so frames go in pieces.
+const DATA_CHUNK: usize = 32;
+
+/// Command bytes
+mod cmd {
+    pub const DISPLAY_OFF: u8 = 0xAE;
+    pub const DISPLAY_ON: u8 = 0xAF;
+    pub const CLOCK_DIV: u8 = 0xD5;
+    pub const MULTIPLEX: u8 = 0xA8;
+    pub const DISPLAY_OFFSET: u8 = 0xD3;
+    pub const START_LINE: u8 = 0x40;
+    pub const CHARGE_PUMP: u8 = 0x8D;
+    pub const ADDRESSING_MODE: u8 = 0x20;
+    pub const SEGMENT_REMAP: u8 = 0xA1;
+    pub const COM_SCAN_DEC: u8 = 0xC8;
+    pub const COM_PINS: u8 = 0xDA;
+    pub const CONTRAST: u8 = 0x81;
+    pub const PRECHARGE: u8 = 0xD9;
+    pub const VCOMH_DESELECT: u8 = 0xDB;
+    pub const RESUME_FROM_RAM: u8 = 0xA4;
+    pub const NORMAL_DISPLAY: u8 = 0xA6;
+    pub const COLUMN_ADDRESS: u8 = 0x21;
+    pub const PAGE_ADDRESS: u8 = 0x22;
+}
+
+/// Printable ASCII from space to tilde, five columns a character, least
+/// significant bit at the top.
+const FONT: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5F, 0x00, 0x00], // !
+    [0x00, 0x07, 0x00, 0x07, 0x00], // "
+    [0x14, 0x7F, 0x14, 0x7F, 0x14], // #
+    [0x24, 0x2A, 0x7F, 0x2A, 0x12], // $
+    [0x23, 0x13, 0x08, 0x64, 0x62], // %
+    [0x36, 0x49, 0x55, 0x22, 0x50], // &
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '
+    [0x00, 0x1C, 0x22, 0x41, 0x00], // (
+    [0x00, 0x41, 0x22, 0x1C, 0x00], // )
+    [0x08, 0x2A, 0x1C, 0x2A, 0x08], // *
+    [0x08, 0x08, 0x3E, 0x08, 0x08], // +
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ,
+    [0x08, 0x08, 0x08, 0x08, 0x08], // -
+    [0x00, 0x60, 0x60, 0x00, 0x00], // .
+    [0x20, 0x10, 0x08, 0x04, 0x02], // /
+    [0x3E, 0x51, 0x49, 0x45, 0x3E], // 0
+    [0x00, 0x42, 0x7F, 0x40, 0x00], // 1
+    [0x42, 0x61, 0x51, 0x49, 0x46], // 2
+    [0x21, 0x41, 0x45, 0x4B, 0x31], // 3
+    [0x18, 0x14, 0x12, 0x7F, 0x10], // 4
+    [0x27, 0x45, 0x45, 0x45, 0x39], // 5
+    [0x3C, 0x4A, 0x49, 0x49, 0x30], // 6
+    [0x01, 0x71, 0x09, 0x05, 0x03], // 7
+    [0x36, 0x49, 0x49, 0x49, 0x36], // 8
+    [0x06, 0x49, 0x49, 0x29, 0x1E], // 9
+    [0x00, 0x36, 0x36, 0x00, 0x00], // :
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ;
+    [0x08, 0x14, 0x22, 0x41, 0x00], // <
+    [0x14, 0x14, 0x14, 0x14, 0x14], // =
+    [0x00, 0x41, 0x22, 0x14, 0x08], // >
+    [0x02, 0x01, 0x51, 0x09, 0x06], // ?
+    [0x32, 0x49, 0x79, 0x41, 0x3E], // @
+    [0x7E, 0x11, 0x11, 0x11, 0x7E], // A
+    [0x7F, 0x49, 0x49, 0x49, 0x36], // B
+    [0x3E, 0x41, 0x41, 0x41, 0x22], // C
+    [0x7F, 0x41, 0x41, 0x22, 0x1C], // D
+    [0x7F, 0x49, 0x49, 0x49, 0x41], // E
+    [0x7F, 0x09, 0x09, 0x09, 0x01], // F
+    [0x3E, 0x41, 0x49, 0x49, 0x7A], // G
+    [0x7F, 0x08, 0x08, 0x08, 0x7F], // H
+    [0x00, 0x41, 0x7F, 0x41, 0x00], // I
+    [0x20, 0x40, 0x41, 0x3F, 0x01], // J
+    [0x7F, 0x08, 0x14, 0x22, 0x41], // K
+    [0x7F, 0x40, 0x40, 0x40, 0x40], // L
+    [0x7F, 0x02, 0x0C, 0x02, 0x7F], // M
+    [0x7F, 0x04, 0x08, 0x10, 0x7F], // N
+    [0x3E, 0x41, 0x41, 0x41, 0x3E], // O
+    [0x7F, 0x09, 0x09, 0x09, 0x06], // P
+    [0x3E, 0x41, 0x51, 0x21, 0x5E], // Q
+    [0x7F, 0x09, 0x19, 0x29, 0x46], // R
+    [0x46, 0x49, 0x49, 0x49, 0x31], // S
+    [0x01, 0x01, 0x7F, 0x01, 0x01], // T
+    [0x3F, 0x40, 0x40, 0x40, 0x3F], // U
+    [0x1F, 0x20, 0x40, 0x20, 0x1F], // V
+    [0x3F, 0x40, 0x38, 0x40, 0x3F], // W
+    [0x63, 0x14, 0x08, 0x14, 0x63], // X
+    [0x07, 0x08, 0x70, 0x08, 0x07], // Y
+    [0x61, 0x51, 0x49, 0x45, 0x43], // Z
+    [0x00, 0x7F, 0x41, 0x41, 0x00], // [
+    [0x02, 0x04, 0x08, 0x10, 0x20], // \
+    [0x00, 0x41, 0x41, 0x7F, 0x00], // ]
+    [0x04, 0x02, 0x01, 0x02, 0x04], // ^
+    [0x40, 0x40, 0x40, 0x40, 0x40], // _
+    [0x00, 0x01, 0x02, 0x04, 0x00], // `
+    [0x20, 0x54, 0x54, 0x54, 0x78], // a
+    [0x7F, 0x48, 0x44, 0x44, 0x38], // b
+    [0x38, 0x44, 0x44, 0x44, 0x20], // c
+    [0x38, 0x44, 0x44, 0x48, 0x7F], // d
+    [0x38, 0x54, 0x54, 0x54, 0x18], // e
+    [0x08, 0x7E, 0x09, 0x01, 0x02], // f
+    [0x0C, 0x52, 0x52, 0x52, 0x3E], // g
+    [0x7F, 0x08, 0x04, 0x04, 0x78], // h
+    [0x00, 0x44, 0x7D, 0x40, 0x00], // i
+    [0x20, 0x40, 0x44, 0x3D, 0x00], // j
+    [0x7F, 0x10, 0x28, 0x44, 0x00], // k
+    [0x00, 0x41, 0x7F, 0x40, 0x00], // l
+    [0x7C, 0x04, 0x18, 0x04, 0x78], // m
+    [0x7C, 0x08, 0x04, 0x04, 0x78], // n
+    [0x38, 0x44, 0x44, 0x44, 0x38], // o
+    [0x7C, 0x14, 0x14, 0x14, 0x08], // p
+    [0x08, 0x14, 0x14, 0x18, 0x7C], // q
+    [0x7C, 0x08, 0x04, 0x04, 0x08], // r
+    [0x48, 0x54, 0x54, 0x54, 0x20], // s
+    [0x04, 0x3F, 0x44, 0x40, 0x20], // t
+    [0x3C, 0x40, 0x40, 0x20, 0x7C], // u
+    [0x1C, 0x20, 0x40, 0x20, 0x1C], // v
+    [0x3C, 0x40, 0x30, 0x40, 0x3C], // w
+    [0x44, 0x28, 0x10, 0x28, 0x44], // x
+    [0x0C, 0x50, 0x50, 0x50, 0x3C], // y
+    [0x44, 0x64, 0x54, 0x4C, 0x44], // z
+    [0x00, 0x08, 0x36, 0x41, 0x00], // {
+    [0x00, 0x00, 0x7F, 0x00, 0x00], // |
+    [0x00, 0x41, 0x36, 0x08, 0x00], // }
+    [0x08, 0x04, 0x08, 0x10, 0x08], // ~
+];
+
+/// SSD1306 display on an I2C bus.
+pub struct Ssd1306<I: I2c> {
+    i2c: I,
+    address: u8,
+    /// One byte per column of each page, pages top to bottom
+    frame: [u8; WIDTH * LINES],
+}
+
+impl<I: I2c> Ssd1306<I> {
+    /// Create a new SSD1306 driver with default address
+    pub fn new(i2c: I) -> Self {
+        Self::new_with_address(i2c, DEFAULT_ADDRESS)
+    }
+
+    /// Create a new SSD1306 driver with custom address
+    pub fn new_with_address(i2c: I, address: u8) -> Self {
+        Self {
+            i2c,
+            address,
+            frame: [0; WIDTH * LINES],
+        }
+    }
+
+    /// Configure the panel and switch it on, blank.
+    ///
+    /// The controller has no ID register, so a failed write is the only sign
+    /// that no display is fitted.
+    pub async fn init(&mut self) -> Result<()> {
+        self.command(&[
+            cmd::DISPLAY_OFF,
+            cmd::CLOCK_DIV,
+            0x80,
+            cmd::MULTIPLEX,
+            (HEIGHT - 1) as u8,
+            cmd::DISPLAY_OFFSET,
+            0x00,
+            cmd::START_LINE,
+            cmd::CHARGE_PUMP,
+            0x14,
+            cmd::ADDRESSING_MODE,
+            0x00, // horizontal
+            cmd::SEGMENT_REMAP,
+            cmd::COM_SCAN_DEC,
+            cmd::COM_PINS,
+            0x02, // sequential, as 32-row panels are wired
+            cmd::CONTRAST,
+            0x8F,
+            cmd::PRECHARGE,
+            0xF1,
+            cmd::VCOMH_DESELECT,
+            0x40,
+            cmd::RESUME_FROM_RAM,
+            cmd::NORMAL_DISPLAY,
+        ])
+        .await?;
+        self.clear();
+        self.flush().await?;
+        self.command(&[cmd::DISPLAY_ON]).await
+    }
+
+    /// Switch the panel on or off, keeping what it shows.
+    pub async fn set_on(&mut self, on: bool) -> Result<()> {
+        let command = if on {
+            cmd::DISPLAY_ON
+        } else {
+            cmd::DISPLAY_OFF
+        };
+        self.command(&[command]).await
+    }
+
+    /// Blank the frame buffer.
+    pub fn clear(&mut self) {
+        self.frame.fill(0);
+    }
+
+    /// Draw `text` on line `line` of the frame buffer, replacing what was
+    /// there. Text past [`COLUMNS`] is cut off, and characters the font
+    /// lacks are shown as `?`.
+    pub fn draw_line(&mut self, line: usize, text: &str) {
+        let Some(row) = self.frame.chunks_mut(WIDTH).nth(line) else {
+            return;
+        };
+        row.fill(0);
+        for (cell, c) in row.chunks_exact_mut(GLYPH_WIDTH).zip(text.chars()) {
+            cell[..GLYPH_WIDTH - 1].copy_from_slice(glyph(c));
+        }
+    }
+
+    /// Draw `lines`, one per line of the screen, and send the frame.
+    pub async fn show(&mut self, lines: &[String]) -> Result<()> {
+        self.clear();
+        for (line, text) in lines.iter().enumerate().take(LINES) {
+            self.draw_line(line, text);
+        }
+        self.flush().await
+    }
+
+    /// Send the frame buffer to the display.
+    pub async fn flush(&mut self) -> Result<()> {
+        self.command(&[
+            cmd::COLUMN_ADDRESS,
+            0,
+            (WIDTH - 1) as u8,
+            cmd::PAGE_ADDRESS,
+            0,
+            (LINES - 1) as u8,
+        ])
+        .await?;
+
+        let mut packet = [0u8; DATA_CHUNK + 1];
+        packet[0] = CONTROL_DATA;
+        for chunk in self.frame.chunks(DATA_CHUNK) {
+            packet[1..=chunk.len()].copy_from_slice(chunk);
+            self.i2c
+                .write(self.address, &packet[..=chunk.len()])
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn command(&mut self, commands: &[u8]) -> Result<()> {
+        let mut data = Vec::with_capacity(commands.len() + 1);
+        data.push(CONTROL_COMMAND);
+        data.extend_from_slice(commands);
+        self.i2c.write(self.address, &data).await
+    }
+}
+
+/// Columns of the font's glyph for `c`.
+fn glyph(c: char) -> &'static [u8; 5] {
+    let index = (c as usize)
+        .checked_sub(' ' as usize)
+        .filter(|&i| i < FONT.len())
+        .unwrap_or('?' as usize - ' ' as usize);
+    &FONT[index]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hw_trait::mock::MockI2c;
+
+    #[test]
+    fn test_draw_line() {
+        let mut display = Ssd1306::new(MockI2c::new());
+        display.draw_line(1, "A1");
+
+        let row = &display.frame[WIDTH..2 * WIDTH];
+        assert_eq!(row[..5], FONT['A' as usize - 0x20]);
+        assert_eq!(row[5], 0, "spacing column");
+        assert_eq!(row[6..11], FONT['1' as usize - 0x20]);
+        assert!(display.frame[..WIDTH].iter().all(|&b| b == 0));
+
+        // Long lines are cut off, unknown characters shown as '?'
+        display.draw_line(0, &"é".repeat(COLUMNS + 5));
+        assert_eq!(display.frame[..5], FONT['?' as usize - 0x20]);
+        assert_eq!(display.frame[WIDTH - 2..WIDTH], [0, 0]);
+
+        // Lines off the screen are ignored
+        display.draw_line(LINES, "X");
+    }
+
+    #[tokio::test]
+    async fn test_flush_sends_whole_frame() {
+        let mock = MockI2c::new();
+        mock.expect_write(DEFAULT_ADDRESS, &[0x00, 0x21, 0, 127, 0x22, 0, 3]);
+        let mut display = Ssd1306::new(mock.clone());
+        display.draw_line(0, "!");
+        for chunk in 0..WIDTH * LINES / DATA_CHUNK {
+            let mut packet = vec![CONTROL_DATA];
+            packet.extend_from_slice(&display.frame[chunk * DATA_CHUNK..][..DATA_CHUNK]);
+            mock.expect_write(DEFAULT_ADDRESS, &packet);
+        }
+
+        display.flush().await.unwrap();
+        mock.done();
+        assert_eq!(display.frame[2], 0x5F);
+    }
+}
@@ -1,15 +1,19 @@
 //! TPS546D24A Power Management Controller Driver
 //!
 //! This module provides a driver for the Texas Instruments TPS546D24A
-//! synchronous buck converter with PMBus interface.
+//! synchronous buck converter with PMBus interface. Bus access, telemetry,
+//! and fault decoding come from [`PmbusDevice`]; this adds the part's
+//! configuration sequence and TI-specific registers.
 //!
 //! Datasheet: <https://www.ti.com/lit/ds/symlink/tps546d24a.pdf>
 
+use std::time::Duration;
+
 use anyhow::{bail, Result};
 use thiserror::Error;
-use tracing::{debug, error, trace, warn};
+use tracing::{debug, error};
 
-use super::pmbus::{self, linear11, PmbusCommand, StatusDecoder, VoutMode};
+use super::pmbus::{self, linear11, Mismatch, PmbusCommand, PmbusDevice, Setting, StatusDecoder};
 use crate::hw_trait::I2c;
 
 /// Constants for TPS546 device identification
@@ -26,6 +30,9 @@ pub mod constants {
 // Use constants for driver
 use constants::{DEFAULT_ADDRESS as TPS546_I2C_ADDR, DEVICE_ID1, DEVICE_ID2, DEVICE_ID3};
 
+/// How long STORE_USER_ALL keeps the part busy programming its NVM
+const NVM_STORE_TIME: Duration = Duration::from_millis(100);
+
 /// TPS546 configuration parameters
 #[derive(Debug, Clone)]
 pub struct Tps546Config {
@@ -104,6 +111,11 @@ pub struct Tps546Config {
     // Pin configuration
     /// Pin detect override value
     pub pin_detect_override: u16,
+
+    // Stacking
+    /// Addresses of the devices stacked with this one on its rail, which
+    /// share its load; empty when it drives the rail alone
+    pub stack: Vec<u8>,
 }
 
 /// TPS546 error types
@@ -115,23 +127,37 @@ pub enum Tps546Error {
     VoltageOutOfRange(f32, f32, f32),
     #[error("PMBus fault detected: {0}")]
     FaultDetected(String),
+    #[error("Configured with {0} stacked devices, but the loop master isn't stacked")]
+    NotStacked(usize),
 }
 
 /// TPS546D24A driver
+///
+/// Drives one device, or a stack of them sharing a rail. In a stack, the
+/// device at the default address is the loop master: it regulates the
+/// output and sequences the others, so output control goes to it alone.
+/// The others carry a share of the current and have their own protection
+/// limits and faults, so they get the per-phase settings, and their
+/// telemetry and status are read alongside the master's.
 pub struct Tps546<I2C> {
-    i2c: I2C,
+    device: PmbusDevice<I2C>,
+    /// Devices stacked with the loop master
+    secondaries: Vec<PmbusDevice<I2C>>,
     config: Tps546Config,
-    /// Cached VOUT_MODE value to avoid redundant reads
-    cached_vout_mode: Option<u8>,
 }
 
-impl<I2C: I2c> Tps546<I2C> {
+impl<I2C: I2c + Clone> Tps546<I2C> {
     /// Create a new TPS546 instance
     pub fn new(i2c: I2C, config: Tps546Config) -> Self {
+        let secondaries = config
+            .stack
+            .iter()
+            .map(|&address| PmbusDevice::new(i2c.clone(), address))
+            .collect();
         Self {
-            i2c,
+            device: PmbusDevice::new(i2c, TPS546_I2C_ADDR),
+            secondaries,
             config,
-            cached_vout_mode: None,
         }
     }
 
@@ -140,14 +166,15 @@ impl<I2C: I2c> Tps546<I2C> {
         debug!("Initializing TPS546D24A power regulator");
 
         // First verify device ID to ensure I2C communication is working
-        self.verify_device_id().await?;
+        verify_device_id(&mut self.device).await?;
 
         // Turn off output during configuration
-        self.write_byte(
-            PmbusCommand::Operation,
-            pmbus::Operation::OffImmediate.as_u8(),
-        )
-        .await?;
+        self.device
+            .write_byte(
+                PmbusCommand::Operation,
+                pmbus::Operation::OffImmediate.as_u8(),
+            )
+            .await?;
         debug!("Power output turned off");
 
         // Configure ON_OFF_CONFIG immediately after turning off (esp-miner sequence)
@@ -157,7 +184,8 @@ impl<I2C: I2c> Tps546<I2C> {
             | pmbus::OnOffConfig::CP
             | pmbus::OnOffConfig::CMD
             | pmbus::OnOffConfig::PU;
-        self.write_byte(PmbusCommand::OnOffConfig, on_off_config.bits())
+        self.device
+            .write_byte(PmbusCommand::OnOffConfig, on_off_config.bits())
             .await?;
         let mut config_desc = Vec::new();
         if on_off_config.contains(pmbus::OnOffConfig::PU) {
@@ -182,15 +210,18 @@ impl<I2C: I2c> Tps546<I2C> {
         );
 
         // Read VOUT_MODE to verify data format (esp-miner does this)
-        let vout_mode = self.read_byte(PmbusCommand::VoutMode).await?;
+        let vout_mode = self.device.read_byte(PmbusCommand::VoutMode).await?;
         debug!("VOUT_MODE: 0x{:02X}", vout_mode);
 
         // Write entire configuration like esp-miner does
         self.write_config().await?;
+        if !self.secondaries.is_empty() {
+            self.init_stack().await?;
+        }
 
         // Read back STATUS_WORD for verification
-        let status = self.read_word(PmbusCommand::StatusWord).await?;
-        let status_desc = self.decode_status_word(status);
+        let status = self.device.read_word(PmbusCommand::StatusWord).await?;
+        let status_desc = StatusDecoder::decode_status_word(status);
         if status_desc.is_empty() {
             debug!("STATUS_WORD after config: 0x{:04X}", status);
         } else {
@@ -204,294 +235,212 @@ impl<I2C: I2c> Tps546<I2C> {
         Ok(())
     }
 
-    /// Write all configuration parameters from the config struct
-    async fn write_config(&mut self) -> Result<()> {
-        trace!("---Writing new config values to TPS546---");
-
-        // Phase configuration
-        trace!("Setting PHASE: {:02X}", self.config.phase);
-        self.write_byte(PmbusCommand::Phase, self.config.phase)
-            .await?;
-
-        // Switching frequency
-        trace!("Setting FREQUENCY: {}kHz", self.config.frequency_switch_khz);
-        self.write_word(
-            PmbusCommand::FrequencySwitch,
-            self.int_to_slinear11(self.config.frequency_switch_khz),
-        )
-        .await?;
+    /// Check the stack matches the configuration, and give each secondary
+    /// the per-phase settings
+    async fn init_stack(&mut self) -> Result<()> {
+        // Strapping sets the stack up; a loop master that reports none is
+        // wired or strapped differently from what the board expects
+        let stack_config = self.device.read_word(PmbusCommand::StackConfig).await?;
+        debug!(
+            stack_config = format_args!("0x{:04X}", stack_config),
+            desc = %StatusDecoder::decode_stack_config(stack_config),
+            secondaries = self.secondaries.len(),
+            "TPS546 stack"
+        );
+        if stack_config == 0 {
+            bail!(Tps546Error::NotStacked(self.secondaries.len()));
+        }
 
-        // Input voltage thresholds (handle UV_WARN_LIMIT bug like esp-miner)
-        if self.config.vin_uv_warn_limit > 0.0 {
-            trace!(
-                "Setting VIN_UV_WARN_LIMIT: {:.2}V",
-                self.config.vin_uv_warn_limit
+        let settings: Vec<Setting> = self
+            .settings()
+            .await?
+            .into_iter()
+            .filter(|setting| !is_rail_setting(setting.command()))
+            .collect();
+        for secondary in &mut self.secondaries {
+            verify_device_id(secondary).await?;
+            let written = secondary.apply(&settings).await?;
+            debug!(
+                address = format_args!("0x{:02X}", secondary.address()),
+                written, "TPS546 stack member configured"
             );
-            self.write_word(
-                PmbusCommand::VinUvWarnLimit,
-                self.float_to_slinear11(self.config.vin_uv_warn_limit),
-            )
-            .await?;
         }
+        Ok(())
+    }
 
-        trace!("Setting VIN_ON: {:.2}V", self.config.vin_on);
-        self.write_word(
-            PmbusCommand::VinOn,
-            self.float_to_slinear11(self.config.vin_on),
-        )
-        .await?;
-
-        trace!("Setting VIN_OFF: {:.2}V", self.config.vin_off);
-        self.write_word(
-            PmbusCommand::VinOff,
-            self.float_to_slinear11(self.config.vin_off),
-        )
-        .await?;
-
-        trace!(
-            "Setting VIN_OV_FAULT_LIMIT: {:.2}V",
-            self.config.vin_ov_fault_limit
-        );
-        self.write_word(
-            PmbusCommand::VinOvFaultLimit,
-            self.float_to_slinear11(self.config.vin_ov_fault_limit),
-        )
-        .await?;
-
-        trace!(
-            "Setting VIN_OV_FAULT_RESPONSE: 0x{:02X}",
-            self.config.vin_ov_fault_response
-        );
-        self.write_byte(
-            PmbusCommand::VinOvFaultResponse,
-            self.config.vin_ov_fault_response,
-        )
-        .await?;
-
-        // Output voltage configuration
-        trace!("Setting VOUT SCALE: {:.2}", self.config.vout_scale_loop);
-        self.write_word(
-            PmbusCommand::VoutScaleLoop,
-            self.float_to_slinear11(self.config.vout_scale_loop),
-        )
-        .await?;
-
-        trace!("Setting VOUT_COMMAND: {:.2}V", self.config.vout_command);
-        let vout_command = self.encode_voltage(self.config.vout_command).await?;
-        self.write_word(PmbusCommand::VoutCommand, vout_command)
-            .await?;
-
-        trace!("Setting VOUT_MAX: {:.2}V", self.config.vout_max);
-        let vout_max = self.encode_voltage(self.config.vout_max).await?;
-        self.write_word(PmbusCommand::VoutMax, vout_max).await?;
-
-        trace!("Setting VOUT_MIN: {:.2}V", self.config.vout_min);
-        let vout_min = self.encode_voltage(self.config.vout_min).await?;
-        self.write_word(PmbusCommand::VoutMin, vout_min).await?;
-
-        // Output voltage protection (relative to vout_command)
-        trace!(
-            "Setting VOUT_OV_FAULT_LIMIT: {:.2}",
-            self.config.vout_ov_fault_limit
-        );
-        let vout_ov_fault = self.encode_voltage(self.config.vout_ov_fault_limit).await?;
-        self.write_word(PmbusCommand::VoutOvFaultLimit, vout_ov_fault)
-            .await?;
-
-        trace!(
-            "Setting VOUT_OV_WARN_LIMIT: {:.2}",
-            self.config.vout_ov_warn_limit
-        );
-        let vout_ov_warn = self.encode_voltage(self.config.vout_ov_warn_limit).await?;
-        self.write_word(PmbusCommand::VoutOvWarnLimit, vout_ov_warn)
-            .await?;
-
-        trace!(
-            "Setting VOUT_MARGIN_HIGH: {:.2}",
-            self.config.vout_margin_high
-        );
-        let vout_margin_high = self.encode_voltage(self.config.vout_margin_high).await?;
-        self.write_word(PmbusCommand::VoutMarginHigh, vout_margin_high)
-            .await?;
-
-        trace!(
-            "Setting VOUT_MARGIN_LOW: {:.2}",
-            self.config.vout_margin_low
+    /// Write the configuration parameters the device doesn't already hold
+    async fn write_config(&mut self) -> Result<()> {
+        let settings = self.settings().await?;
+        let written = self.device.apply(&settings).await?;
+        debug!(
+            written,
+            unchanged = settings.len() - written,
+            "TPS546 configuration written"
         );
-        let vout_margin_low = self.encode_voltage(self.config.vout_margin_low).await?;
-        self.write_word(PmbusCommand::VoutMarginLow, vout_margin_low)
-            .await?;
+        Ok(())
+    }
 
-        trace!(
-            "Setting VOUT_UV_WARN_LIMIT: {:.2}",
-            self.config.vout_uv_warn_limit
-        );
-        let vout_uv_warn = self.encode_voltage(self.config.vout_uv_warn_limit).await?;
-        self.write_word(PmbusCommand::VoutUvWarnLimit, vout_uv_warn)
-            .await?;
+    /// The register values the config struct asks for, in the order they're
+    /// written
+    async fn settings(&mut self) -> Result<Vec<Setting>> {
+        let config = self.config.clone();
+        let mut settings = vec![
+            // Phase and frequency
+            Setting::Byte(PmbusCommand::Phase, config.phase),
+            Setting::Linear11(
+                PmbusCommand::FrequencySwitch,
+                linear11::from_float(config.frequency_switch_khz as f32),
+            ),
+        ];
 
-        trace!(
-            "Setting VOUT_UV_FAULT_LIMIT: {:.2}",
-            self.config.vout_uv_fault_limit
-        );
-        let vout_uv_fault = self.encode_voltage(self.config.vout_uv_fault_limit).await?;
-        self.write_word(PmbusCommand::VoutUvFaultLimit, vout_uv_fault)
-            .await?;
+        // Input voltage thresholds (handle UV_WARN_LIMIT bug like esp-miner)
+        if config.vin_uv_warn_limit > 0.0 {
+            settings.push(Setting::Linear11(
+                PmbusCommand::VinUvWarnLimit,
+                linear11::from_float(config.vin_uv_warn_limit),
+            ));
+        }
+        settings.extend([
+            Setting::Linear11(PmbusCommand::VinOn, linear11::from_float(config.vin_on)),
+            Setting::Linear11(PmbusCommand::VinOff, linear11::from_float(config.vin_off)),
+            Setting::Linear11(
+                PmbusCommand::VinOvFaultLimit,
+                linear11::from_float(config.vin_ov_fault_limit),
+            ),
+            Setting::Byte(
+                PmbusCommand::VinOvFaultResponse,
+                config.vin_ov_fault_response,
+            ),
+            // Output voltage configuration
+            Setting::Linear11(
+                PmbusCommand::VoutScaleLoop,
+                linear11::from_float(config.vout_scale_loop),
+            ),
+        ]);
+
+        // Output voltages, then their protection (relative to vout_command)
+        for (command, volts) in [
+            (PmbusCommand::VoutCommand, config.vout_command),
+            (PmbusCommand::VoutMax, config.vout_max),
+            (PmbusCommand::VoutMin, config.vout_min),
+            (PmbusCommand::VoutOvFaultLimit, config.vout_ov_fault_limit),
+            (PmbusCommand::VoutOvWarnLimit, config.vout_ov_warn_limit),
+            (PmbusCommand::VoutMarginHigh, config.vout_margin_high),
+            (PmbusCommand::VoutMarginLow, config.vout_margin_low),
+            (PmbusCommand::VoutUvWarnLimit, config.vout_uv_warn_limit),
+            (PmbusCommand::VoutUvFaultLimit, config.vout_uv_fault_limit),
+        ] {
+            let value = self.device.encode_voltage(volts).await?;
+            settings.push(Setting::Word(command, value));
+        }
 
-        // Output current protection
-        trace!("----- IOUT");
-        trace!(
-            "Setting IOUT_OC_WARN_LIMIT: {:.2}A",
-            self.config.iout_oc_warn_limit
-        );
-        self.write_word(
-            PmbusCommand::IoutOcWarnLimit,
-            self.float_to_slinear11(self.config.iout_oc_warn_limit),
-        )
-        .await?;
-
-        trace!(
-            "Setting IOUT_OC_FAULT_LIMIT: {:.2}A",
-            self.config.iout_oc_fault_limit
-        );
-        self.write_word(
-            PmbusCommand::IoutOcFaultLimit,
-            self.float_to_slinear11(self.config.iout_oc_fault_limit),
-        )
-        .await?;
-
-        trace!(
-            "Setting IOUT_OC_FAULT_RESPONSE: 0x{:02X}",
-            self.config.iout_oc_fault_response
-        );
-        self.write_byte(
-            PmbusCommand::IoutOcFaultResponse,
-            self.config.iout_oc_fault_response,
-        )
-        .await?;
-
-        // Temperature protection
-        trace!("----- TEMPERATURE");
-        trace!("Setting OT_WARN_LIMIT: {} degC", self.config.ot_warn_limit);
-        self.write_word(
-            PmbusCommand::OtWarnLimit,
-            self.int_to_slinear11(self.config.ot_warn_limit),
-        )
-        .await?;
-
-        trace!(
-            "Setting OT_FAULT_LIMIT: {} degC",
-            self.config.ot_fault_limit
-        );
-        self.write_word(
-            PmbusCommand::OtFaultLimit,
-            self.int_to_slinear11(self.config.ot_fault_limit),
-        )
-        .await?;
-
-        trace!(
-            "Setting OT_FAULT_RESPONSE: 0x{:02X}",
-            self.config.ot_fault_response
-        );
-        self.write_byte(PmbusCommand::OtFaultResponse, self.config.ot_fault_response)
-            .await?;
+        settings.extend([
+            // Output current protection
+            Setting::Linear11(
+                PmbusCommand::IoutOcWarnLimit,
+                linear11::from_float(config.iout_oc_warn_limit),
+            ),
+            Setting::Linear11(
+                PmbusCommand::IoutOcFaultLimit,
+                linear11::from_float(config.iout_oc_fault_limit),
+            ),
+            Setting::Byte(
+                PmbusCommand::IoutOcFaultResponse,
+                config.iout_oc_fault_response,
+            ),
+            // Temperature protection
+            Setting::Linear11(
+                PmbusCommand::OtWarnLimit,
+                linear11::from_float(config.ot_warn_limit as f32),
+            ),
+            Setting::Linear11(
+                PmbusCommand::OtFaultLimit,
+                linear11::from_float(config.ot_fault_limit as f32),
+            ),
+            Setting::Byte(PmbusCommand::OtFaultResponse, config.ot_fault_response),
+            // Timing configuration
+            Setting::Linear11(
+                PmbusCommand::TonDelay,
+                linear11::from_float(config.ton_delay as f32),
+            ),
+            Setting::Linear11(
+                PmbusCommand::TonRise,
+                linear11::from_float(config.ton_rise as f32),
+            ),
+            Setting::Linear11(
+                PmbusCommand::TonMaxFaultLimit,
+                linear11::from_float(config.ton_max_fault_limit as f32),
+            ),
+            Setting::Byte(
+                PmbusCommand::TonMaxFaultResponse,
+                config.ton_max_fault_response,
+            ),
+            Setting::Linear11(
+                PmbusCommand::ToffDelay,
+                linear11::from_float(config.toff_delay as f32),
+            ),
+            Setting::Linear11(
+                PmbusCommand::ToffFall,
+                linear11::from_float(config.toff_fall as f32),
+            ),
+            // Pin detect override
+            Setting::Word(PmbusCommand::PinDetectOverride, config.pin_detect_override),
+        ]);
+
+        Ok(settings)
+    }
 
-        // Timing configuration
-        trace!("----- TIMING");
-        trace!("Setting TON_DELAY: {}ms", self.config.ton_delay);
-        self.write_word(
-            PmbusCommand::TonDelay,
-            self.int_to_slinear11(self.config.ton_delay),
-        )
-        .await?;
-
-        trace!("Setting TON_RISE: {}ms", self.config.ton_rise);
-        self.write_word(
-            PmbusCommand::TonRise,
-            self.int_to_slinear11(self.config.ton_rise),
-        )
-        .await?;
-
-        trace!(
-            "Setting TON_MAX_FAULT_LIMIT: {}ms",
-            self.config.ton_max_fault_limit
-        );
-        self.write_word(
-            PmbusCommand::TonMaxFaultLimit,
-            self.int_to_slinear11(self.config.ton_max_fault_limit),
-        )
-        .await?;
-
-        trace!(
-            "Setting TON_MAX_FAULT_RESPONSE: 0x{:02X}",
-            self.config.ton_max_fault_response
-        );
-        self.write_byte(
-            PmbusCommand::TonMaxFaultResponse,
-            self.config.ton_max_fault_response,
-        )
-        .await?;
-
-        trace!("Setting TOFF_DELAY: {}ms", self.config.toff_delay);
-        self.write_word(
-            PmbusCommand::ToffDelay,
-            self.int_to_slinear11(self.config.toff_delay),
-        )
-        .await?;
-
-        trace!("Setting TOFF_FALL: {}ms", self.config.toff_fall);
-        self.write_word(
-            PmbusCommand::ToffFall,
-            self.int_to_slinear11(self.config.toff_fall),
-        )
-        .await?;
-
-        // Pin detect override
-        trace!(
-            "Setting PIN_DETECT_OVERRIDE: 0x{:04X}",
-            self.config.pin_detect_override
-        );
-        self.write_word(
-            PmbusCommand::PinDetectOverride,
-            self.config.pin_detect_override,
-        )
-        .await?;
+    /// Registers that differ from the configuration, as each device in
+    /// the stack holds them now, by device address
+    pub async fn diff_config(&mut self) -> Result<Vec<(u8, Mismatch)>> {
+        let settings = self.settings().await?;
+        let mut mismatches: Vec<(u8, Mismatch)> = self
+            .device
+            .diff(&settings)
+            .await?
+            .into_iter()
+            .map(|mismatch| (self.device.address(), mismatch))
+            .collect();
+
+        let phase_settings: Vec<Setting> = settings
+            .into_iter()
+            .filter(|setting| !is_rail_setting(setting.command()))
+            .collect();
+        for secondary in &mut self.secondaries {
+            let address = secondary.address();
+            for mismatch in secondary.diff(&phase_settings).await? {
+                mismatches.push((address, mismatch));
+            }
+        }
+        Ok(mismatches)
+    }
 
-        debug!("TPS546 configuration written successfully");
+    /// Store each device's present settings, tuning included, in its NVM,
+    /// so it powers up with them and init finds nothing to rewrite.
+    pub async fn store_config(&mut self) -> Result<()> {
+        for device in std::iter::once(&mut self.device).chain(&mut self.secondaries) {
+            device.store_user_all().await?;
+        }
+        // The parts NACK the bus while they program their NVM
+        tokio::time::sleep(NVM_STORE_TIME).await;
+        debug!("TPS546 settings stored to NVM");
         Ok(())
     }
 
-    /// Verify the device ID
-    async fn verify_device_id(&mut self) -> Result<()> {
-        let mut id_data = vec![0u8; 7]; // Length byte + 6 ID bytes
-        self.i2c
-            .write_read(
-                TPS546_I2C_ADDR,
-                &[PmbusCommand::IcDeviceId.as_u8()],
-                &mut id_data,
-            )
-            .await?;
-
-        // First byte is length, actual ID starts at byte 1
-        let device_id = &id_data[1..7];
-        debug!(
-            "Device ID: {:02X} {:02X} {:02X} {:02X} {:02X} {:02X}",
-            device_id[0], device_id[1], device_id[2], device_id[3], device_id[4], device_id[5]
-        );
-
-        if device_id != DEVICE_ID1 && device_id != DEVICE_ID2 && device_id != DEVICE_ID3 {
-            error!("Device ID mismatch");
-            bail!(Tps546Error::DeviceIdMismatch);
+    /// Discard unstored changes, going back to the settings in NVM
+    pub async fn restore_config(&mut self) -> Result<()> {
+        for device in std::iter::once(&mut self.device).chain(&mut self.secondaries) {
+            device.restore_user_all().await?;
         }
-
+        debug!("TPS546 settings restored from NVM");
         Ok(())
     }
 
     /// Clear all faults
     pub async fn clear_faults(&mut self) -> Result<()> {
-        self.i2c
-            .write(TPS546_I2C_ADDR, &[PmbusCommand::ClearFaults.as_u8()])
-            .await?;
+        for device in std::iter::once(&mut self.device).chain(&mut self.secondaries) {
+            device.clear_faults().await?;
+        }
         Ok(())
     }
 
@@ -499,11 +448,12 @@ impl<I2C: I2c> Tps546<I2C> {
     pub async fn set_vout(&mut self, volts: f32) -> Result<()> {
         if volts == 0.0 {
             // Turn off output
-            self.write_byte(
-                PmbusCommand::Operation,
-                pmbus::Operation::OffImmediate.as_u8(),
-            )
-            .await?;
+            self.device
+                .write_byte(
+                    PmbusCommand::Operation,
+                    pmbus::Operation::OffImmediate.as_u8(),
+                )
+                .await?;
             debug!("Output voltage turned off");
         } else {
             // Check voltage range
@@ -516,8 +466,10 @@ impl<I2C: I2c> Tps546<I2C> {
             }
 
             // Set voltage
-            let value = self.encode_voltage(volts).await?;
-            self.write_word(PmbusCommand::VoutCommand, value).await?;
+            let value = self.device.encode_voltage(volts).await?;
+            self.device
+                .write_word(PmbusCommand::VoutCommand, value)
+                .await?;
             debug!("Output voltage set to {:.2}V", volts);
 
             // Clear any faults before turning on
@@ -525,11 +477,12 @@ impl<I2C: I2c> Tps546<I2C> {
             debug!("Cleared faults before turn-on");
 
             // Turn on output
-            self.write_byte(PmbusCommand::Operation, pmbus::Operation::On.as_u8())
+            self.device
+                .write_byte(PmbusCommand::Operation, pmbus::Operation::On.as_u8())
                 .await?;
 
             // Verify operation
-            let op_val = self.read_byte(PmbusCommand::Operation).await?;
+            let op_val = self.device.read_byte(PmbusCommand::Operation).await?;
             if op_val != pmbus::Operation::On.as_u8() {
                 error!("Failed to turn on output, OPERATION = 0x{:02X}", op_val);
             } else {
@@ -537,17 +490,17 @@ impl<I2C: I2c> Tps546<I2C> {
             }
 
             // Check immediate status after turn-on
-            let status = self.read_word(PmbusCommand::StatusWord).await?;
+            let status = self.device.read_word(PmbusCommand::StatusWord).await?;
             if pmbus::StatusWord::from_bits_truncate(status).contains(pmbus::StatusWord::OFF) {
                 error!(
                     "WARNING: Power is still OFF after turn-on command! STATUS_WORD = 0x{:04X}",
                     status
                 );
                 // Read all status registers to understand why
-                if let Ok(vout_status) = self.read_byte(PmbusCommand::StatusVout).await {
+                if let Ok(vout_status) = self.device.read_byte(PmbusCommand::StatusVout).await {
                     error!("  STATUS_VOUT: 0x{:02X}", vout_status);
                 }
-                if let Ok(iout_status) = self.read_byte(PmbusCommand::StatusIout).await {
+                if let Ok(iout_status) = self.device.read_byte(PmbusCommand::StatusIout).await {
                     error!("  STATUS_IOUT: 0x{:02X}", iout_status);
                 }
             } else {
@@ -559,32 +512,35 @@ impl<I2C: I2c> Tps546<I2C> {
 
     /// Read input voltage in millivolts
     pub async fn get_vin(&mut self) -> Result<u32> {
-        let value = self.read_word(PmbusCommand::ReadVin).await?;
-        let volts = self.slinear11_to_float(value);
+        let volts = self.device.read_vin().await?;
         Ok((volts * 1000.0) as u32)
     }
 
     /// Read output voltage in millivolts
     pub async fn get_vout(&mut self) -> Result<u32> {
-        let value = self.read_word(PmbusCommand::ReadVout).await?;
-        let volts = self.decode_voltage(value).await?;
+        let volts = self.device.read_vout().await?;
         Ok((volts * 1000.0) as u32)
     }
 
-    /// Read output current in milliamps
+    /// Read output current in milliamps, summed over the stack
     pub async fn get_iout(&mut self) -> Result<u32> {
-        // Set phase to 0xFF to read all phases
-        self.write_byte(PmbusCommand::Phase, 0xFF).await?;
-
-        let value = self.read_word(PmbusCommand::ReadIout).await?;
-        let amps = self.slinear11_to_float(value);
+        let mut amps = 0.0;
+        for device in std::iter::once(&mut self.device).chain(&mut self.secondaries) {
+            // Set phase to 0xFF to read all phases
+            device.select_phase(0xFF).await?;
+            amps += device.read_iout().await?;
+        }
         Ok((amps * 1000.0) as u32)
     }
 
-    /// Read temperature in degrees Celsius
+    /// Read temperature in degrees Celsius, of the hottest device in the
+    /// stack
     pub async fn get_temperature(&mut self) -> Result<i32> {
-        let value = self.read_word(PmbusCommand::ReadTemperature1).await?;
-        Ok(self.slinear11_to_int(value))
+        let mut hottest = self.device.read_temperature().await?;
+        for secondary in &mut self.secondaries {
+            hottest = hottest.max(secondary.read_temperature().await?);
+        }
+        Ok(hottest as i32)
     }
 
     /// Calculate power in milliwatts
@@ -595,190 +551,18 @@ impl<I2C: I2c> Tps546<I2C> {
         Ok(power_mw as u32)
     }
 
-    /// Check and report status
+    /// Check and report status of every device in the stack
     pub async fn check_status(&mut self) -> Result<()> {
-        let status = self.read_word(PmbusCommand::StatusWord).await?;
-
-        if status == 0 {
-            return Ok(());
-        }
-
-        // Track critical faults that should fail the check
-        let mut critical_faults = Vec::new();
-        let mut warnings = Vec::new();
-
-        // Check for output voltage faults (critical)
-        let status_flags = pmbus::StatusWord::from_bits_truncate(status);
-        if status_flags.contains(pmbus::StatusWord::VOUT) {
-            let vout_status = self.read_byte(PmbusCommand::StatusVout).await?;
-            let desc = self.decode_status_vout(vout_status);
-
-            // OV and UV faults are critical - the output is not within safe operating range
-            let vout_flags = pmbus::StatusVout::from_bits_truncate(vout_status);
-            if vout_flags
-                .intersects(pmbus::StatusVout::VOUT_OV_FAULT | pmbus::StatusVout::VOUT_UV_FAULT)
-            {
-                error!(
-                    "CRITICAL: VOUT fault detected: 0x{:02X} ({})",
-                    vout_status,
-                    desc.join(", ")
-                );
-                critical_faults.push(format!("VOUT fault: {}", desc.join(", ")));
-            } else {
-                warn!("VOUT warning: 0x{:02X} ({})", vout_status, desc.join(", "));
-                warnings.push(format!("VOUT warning: {}", desc.join(", ")));
-            }
-        }
-
-        // Check for output current faults (critical)
-        if status_flags.contains(pmbus::StatusWord::IOUT) {
-            let iout_status = self.read_byte(PmbusCommand::StatusIout).await?;
-            let desc = self.decode_status_iout(iout_status);
-
-            // Overcurrent fault is critical - can damage hardware
-            let iout_flags = pmbus::StatusIout::from_bits_truncate(iout_status);
-            if iout_flags.contains(pmbus::StatusIout::IOUT_OC_FAULT) {
-                error!(
-                    "CRITICAL: IOUT overcurrent fault detected: 0x{:02X} ({})",
-                    iout_status,
-                    desc.join(", ")
-                );
-                critical_faults.push(format!("IOUT overcurrent: {}", desc.join(", ")));
-            } else {
-                warn!("IOUT warning: 0x{:02X} ({})", iout_status, desc.join(", "));
-                warnings.push(format!("IOUT warning: {}", desc.join(", ")));
+        let mut critical_faults = self.device.check_faults().await?;
+        for secondary in &mut self.secondaries {
+            let address = secondary.address();
+            for fault in secondary.check_faults().await? {
+                critical_faults.push(format!("0x{:02X}: {}", address, fault));
             }
         }
-
-        // Check for input voltage faults (critical if unit is off)
-        if status_flags.contains(pmbus::StatusWord::INPUT) {
-            let input_status = self.read_byte(PmbusCommand::StatusInput).await?;
-            let desc = self.decode_status_input(input_status);
-
-            // Unit off due to low input or UV/OV faults are critical
-            let input_flags = pmbus::StatusInput::from_bits_truncate(input_status);
-            if input_flags.intersects(
-                pmbus::StatusInput::UNIT_OFF_VIN_LOW
-                    | pmbus::StatusInput::VIN_UV_FAULT
-                    | pmbus::StatusInput::VIN_OV_FAULT,
-            ) {
-                error!(
-                    "CRITICAL: INPUT fault detected: 0x{:02X} ({})",
-                    input_status,
-                    desc.join(", ")
-                );
-                critical_faults.push(format!("INPUT fault: {}", desc.join(", ")));
-            } else {
-                warn!(
-                    "INPUT warning: 0x{:02X} ({})",
-                    input_status,
-                    desc.join(", ")
-                );
-                warnings.push(format!("INPUT warning: {}", desc.join(", ")));
-            }
-        }
-
-        // Check for temperature faults (critical)
-        if status_flags.contains(pmbus::StatusWord::TEMP) {
-            let temp_status = self.read_byte(PmbusCommand::StatusTemperature).await?;
-            let desc = self.decode_status_temp(temp_status);
-
-            // Overtemperature fault is critical
-            let temp_flags = pmbus::StatusTemperature::from_bits_truncate(temp_status);
-            if temp_flags.contains(pmbus::StatusTemperature::OT_FAULT) {
-                error!(
-                    "CRITICAL: Overtemperature fault detected: 0x{:02X} ({})",
-                    temp_status,
-                    desc.join(", ")
-                );
-                critical_faults.push(format!("Overtemperature: {}", desc.join(", ")));
-            } else {
-                warn!(
-                    "TEMPERATURE warning: 0x{:02X} ({})",
-                    temp_status,
-                    desc.join(", ")
-                );
-                warnings.push(format!("TEMP warning: {}", desc.join(", ")));
-            }
-        }
-
-        // Check for communication/memory/logic faults (treat as critical)
-        if status_flags.contains(pmbus::StatusWord::CML) {
-            let cml_status = self.read_byte(PmbusCommand::StatusCml).await?;
-            let desc = self.decode_status_cml(cml_status);
-            error!(
-                "CRITICAL: CML fault detected: 0x{:02X} ({})",
-                cml_status,
-                desc.join(", ")
-            );
-            critical_faults.push(format!("CML fault: {}", desc.join(", ")));
-        }
-
-        // Check if unit is OFF (critical - means power has shut down)
-        if status_flags.contains(pmbus::StatusWord::OFF) {
-            error!(
-                "CRITICAL: Power controller is OFF - Reading all status registers for diagnostics"
-            );
-
-            // Read ALL status registers to understand why it shut off
-            error!("STATUS_WORD: 0x{:04X}", status);
-
-            // Always read these status bytes when OFF to understand the fault
-            if let Ok(vout_status) = self.read_byte(PmbusCommand::StatusVout).await {
-                let desc = self.decode_status_vout(vout_status);
-                error!("  STATUS_VOUT: 0x{:02X} ({})", vout_status, desc.join(", "));
-            }
-
-            if let Ok(iout_status) = self.read_byte(PmbusCommand::StatusIout).await {
-                let desc = self.decode_status_iout(iout_status);
-                error!("  STATUS_IOUT: 0x{:02X} ({})", iout_status, desc.join(", "));
-            }
-
-            if let Ok(input_status) = self.read_byte(PmbusCommand::StatusInput).await {
-                let desc = self.decode_status_input(input_status);
-                error!(
-                    "  STATUS_INPUT: 0x{:02X} ({})",
-                    input_status,
-                    desc.join(", ")
-                );
-            }
-
-            if let Ok(temp_status) = self.read_byte(PmbusCommand::StatusTemperature).await {
-                let desc = self.decode_status_temp(temp_status);
-                error!(
-                    "  STATUS_TEMPERATURE: 0x{:02X} ({})",
-                    temp_status,
-                    desc.join(", ")
-                );
-            }
-
-            if let Ok(cml_status) = self.read_byte(PmbusCommand::StatusCml).await {
-                let desc = self.decode_status_cml(cml_status);
-                error!("  STATUS_CML: 0x{:02X} ({})", cml_status, desc.join(", "));
-            }
-
-            // Also read current telemetry
-            if let Ok(vout_mv) = self.get_vout().await {
-                error!("  Current VOUT: {:.3}V", vout_mv as f32 / 1000.0);
-            }
-            if let Ok(iout_ma) = self.get_iout().await {
-                error!("  Current IOUT: {:.2}A", iout_ma as f32 / 1000.0);
-            }
-            if let Ok(vin_mv) = self.get_vin().await {
-                error!("  Current VIN: {:.2}V", vin_mv as f32 / 1000.0);
-            }
-            if let Ok(temp) = self.get_temperature().await {
-                error!("  Current Temperature: {} degC", temp);
-            }
-
-            critical_faults.push("Power controller is OFF".to_string());
-        }
-
-        // Return error if any critical faults detected
         if !critical_faults.is_empty() {
             bail!(Tps546Error::FaultDetected(critical_faults.join("; ")));
         }
-
         Ok(())
     }
 
@@ -790,130 +574,145 @@ impl<I2C: I2c> Tps546<I2C> {
         debug!("--- Voltage Configuration ---");
 
         // VIN settings
-        let vin_on = self.read_word(PmbusCommand::VinOn).await?;
+        let vin_on = self.device.read_word(PmbusCommand::VinOn).await?;
         debug!(
             "VIN_ON: {:.2}V (raw: 0x{:04X})",
-            self.slinear11_to_float(vin_on),
+            linear11::to_float(vin_on),
             vin_on
         );
 
-        let vin_off = self.read_word(PmbusCommand::VinOff).await?;
+        let vin_off = self.device.read_word(PmbusCommand::VinOff).await?;
         debug!(
             "VIN_OFF: {:.2}V (raw: 0x{:04X})",
-            self.slinear11_to_float(vin_off),
+            linear11::to_float(vin_off),
             vin_off
         );
 
-        let vin_ov_fault = self.read_word(PmbusCommand::VinOvFaultLimit).await?;
+        let vin_ov_fault = self.device.read_word(PmbusCommand::VinOvFaultLimit).await?;
         debug!(
             "VIN_OV_FAULT_LIMIT: {:.2}V (raw: 0x{:04X})",
-            self.slinear11_to_float(vin_ov_fault),
+            linear11::to_float(vin_ov_fault),
             vin_ov_fault
         );
 
-        let vin_uv_warn = self.read_word(PmbusCommand::VinUvWarnLimit).await?;
+        let vin_uv_warn = self.device.read_word(PmbusCommand::VinUvWarnLimit).await?;
         debug!(
             "VIN_UV_WARN_LIMIT: {:.2}V (raw: 0x{:04X})",
-            self.slinear11_to_float(vin_uv_warn),
+            linear11::to_float(vin_uv_warn),
             vin_uv_warn
         );
 
-        let vin_ov_response = self.read_byte(PmbusCommand::VinOvFaultResponse).await?;
-        let vin_ov_desc = self.decode_fault_response(vin_ov_response);
+        let vin_ov_response = self
+            .device
+            .read_byte(PmbusCommand::VinOvFaultResponse)
+            .await?;
+        let vin_ov_desc = StatusDecoder::decode_fault_response(vin_ov_response);
         debug!(
             "VIN_OV_FAULT_RESPONSE: 0x{:02X} ({})",
             vin_ov_response, vin_ov_desc
         );
 
         // VOUT settings
-        let vout_max = self.read_word(PmbusCommand::VoutMax).await?;
+        let vout_max = self.device.read_word(PmbusCommand::VoutMax).await?;
         debug!(
             "VOUT_MAX: {:.2}V (raw: 0x{:04X})",
-            self.decode_voltage(vout_max).await?,
+            self.device.decode_voltage(vout_max).await?,
             vout_max
         );
 
-        let vout_ov_fault = self.read_word(PmbusCommand::VoutOvFaultLimit).await?;
-        let vout_ov_fault_v = self.decode_voltage(vout_ov_fault).await?;
+        let vout_ov_fault = self
+            .device
+            .read_word(PmbusCommand::VoutOvFaultLimit)
+            .await?;
+        let vout_ov_fault_v = self.device.decode_voltage(vout_ov_fault).await?;
         debug!(
             "VOUT_OV_FAULT_LIMIT: {:.2}V (raw: 0x{:04X})",
             vout_ov_fault_v * self.config.vout_command,
             vout_ov_fault
         );
 
-        let vout_ov_warn = self.read_word(PmbusCommand::VoutOvWarnLimit).await?;
-        let vout_ov_warn_v = self.decode_voltage(vout_ov_warn).await?;
+        let vout_ov_warn = self.device.read_word(PmbusCommand::VoutOvWarnLimit).await?;
+        let vout_ov_warn_v = self.device.decode_voltage(vout_ov_warn).await?;
         debug!(
             "VOUT_OV_WARN_LIMIT: {:.2}V (raw: 0x{:04X})",
             vout_ov_warn_v * self.config.vout_command,
             vout_ov_warn
         );
 
-        let vout_margin_high = self.read_word(PmbusCommand::VoutMarginHigh).await?;
-        let vout_margin_high_v = self.decode_voltage(vout_margin_high).await?;
+        let vout_margin_high = self.device.read_word(PmbusCommand::VoutMarginHigh).await?;
+        let vout_margin_high_v = self.device.decode_voltage(vout_margin_high).await?;
         debug!(
             "VOUT_MARGIN_HIGH: {:.2}V (raw: 0x{:04X})",
             vout_margin_high_v * self.config.vout_command,
             vout_margin_high
         );
 
-        let vout_command = self.read_word(PmbusCommand::VoutCommand).await?;
+        let vout_command = self.device.read_word(PmbusCommand::VoutCommand).await?;
         debug!(
             "VOUT_COMMAND: {:.2}V (raw: 0x{:04X})",
-            self.decode_voltage(vout_command).await?,
+            self.device.decode_voltage(vout_command).await?,
             vout_command
         );
 
-        let vout_margin_low = self.read_word(PmbusCommand::VoutMarginLow).await?;
-        let vout_margin_low_v = self.decode_voltage(vout_margin_low).await?;
+        let vout_margin_low = self.device.read_word(PmbusCommand::VoutMarginLow).await?;
+        let vout_margin_low_v = self.device.decode_voltage(vout_margin_low).await?;
         debug!(
             "VOUT_MARGIN_LOW: {:.2}V (raw: 0x{:04X})",
             vout_margin_low_v * self.config.vout_command,
             vout_margin_low
         );
 
-        let vout_uv_warn = self.read_word(PmbusCommand::VoutUvWarnLimit).await?;
-        let vout_uv_warn_v = self.decode_voltage(vout_uv_warn).await?;
+        let vout_uv_warn = self.device.read_word(PmbusCommand::VoutUvWarnLimit).await?;
+        let vout_uv_warn_v = self.device.decode_voltage(vout_uv_warn).await?;
         debug!(
             "VOUT_UV_WARN_LIMIT: {:.2}V (raw: 0x{:04X})",
             vout_uv_warn_v * self.config.vout_command,
             vout_uv_warn
         );
 
-        let vout_uv_fault = self.read_word(PmbusCommand::VoutUvFaultLimit).await?;
-        let vout_uv_fault_v = self.decode_voltage(vout_uv_fault).await?;
+        let vout_uv_fault = self
+            .device
+            .read_word(PmbusCommand::VoutUvFaultLimit)
+            .await?;
+        let vout_uv_fault_v = self.device.decode_voltage(vout_uv_fault).await?;
         debug!(
             "VOUT_UV_FAULT_LIMIT: {:.2}V (raw: 0x{:04X})",
             vout_uv_fault_v * self.config.vout_command,
             vout_uv_fault
         );
 
-        let vout_min = self.read_word(PmbusCommand::VoutMin).await?;
+        let vout_min = self.device.read_word(PmbusCommand::VoutMin).await?;
         debug!(
             "VOUT_MIN: {:.2}V (raw: 0x{:04X})",
-            self.decode_voltage(vout_min).await?,
+            self.device.decode_voltage(vout_min).await?,
             vout_min
         );
 
         // Current Configuration and Limits
         debug!("--- Current Configuration ---");
 
-        let iout_oc_warn = self.read_word(PmbusCommand::IoutOcWarnLimit).await?;
+        let iout_oc_warn = self.device.read_word(PmbusCommand::IoutOcWarnLimit).await?;
         debug!(
             "IOUT_OC_WARN_LIMIT: {:.2}A (raw: 0x{:04X})",
-            self.slinear11_to_float(iout_oc_warn),
+            linear11::to_float(iout_oc_warn),
             iout_oc_warn
         );
 
-        let iout_oc_fault = self.read_word(PmbusCommand::IoutOcFaultLimit).await?;
+        let iout_oc_fault = self
+            .device
+            .read_word(PmbusCommand::IoutOcFaultLimit)
+            .await?;
         debug!(
             "IOUT_OC_FAULT_LIMIT: {:.2}A (raw: 0x{:04X})",
-            self.slinear11_to_float(iout_oc_fault),
+            linear11::to_float(iout_oc_fault),
             iout_oc_fault
         );
 
-        let iout_oc_response = self.read_byte(PmbusCommand::IoutOcFaultResponse).await?;
-        let iout_oc_desc = self.decode_fault_response(iout_oc_response);
+        let iout_oc_response = self
+            .device
+            .read_byte(PmbusCommand::IoutOcFaultResponse)
+            .await?;
+        let iout_oc_desc = StatusDecoder::decode_fault_response(iout_oc_response);
         debug!(
             "IOUT_OC_FAULT_RESPONSE: 0x{:02X} ({})",
             iout_oc_response, iout_oc_desc
@@ -922,74 +721,66 @@ impl<I2C: I2c> Tps546<I2C> {
         // Temperature Configuration
         debug!("--- Temperature Configuration ---");
 
-        let ot_warn = self.read_word(PmbusCommand::OtWarnLimit).await?;
+        let ot_warn = self.device.read_word(PmbusCommand::OtWarnLimit).await?;
         debug!(
             "OT_WARN_LIMIT: {} degC (raw: 0x{:04X})",
-            self.slinear11_to_int(ot_warn),
+            linear11::to_float(ot_warn) as i32,
             ot_warn
         );
 
-        let ot_fault = self.read_word(PmbusCommand::OtFaultLimit).await?;
+        let ot_fault = self.device.read_word(PmbusCommand::OtFaultLimit).await?;
         debug!(
             "OT_FAULT_LIMIT: {} degC (raw: 0x{:04X})",
-            self.slinear11_to_int(ot_fault),
+            linear11::to_float(ot_fault) as i32,
             ot_fault
         );
 
-        let ot_response = self.read_byte(PmbusCommand::OtFaultResponse).await?;
-        let ot_desc = self.decode_fault_response(ot_response);
+        let ot_response = self.device.read_byte(PmbusCommand::OtFaultResponse).await?;
+        let ot_desc = StatusDecoder::decode_fault_response(ot_response);
         debug!("OT_FAULT_RESPONSE: 0x{:02X} ({})", ot_response, ot_desc);
 
         // Current Readings
         debug!("--- Current Readings ---");
-
-        let read_vin = self.read_word(PmbusCommand::ReadVin).await?;
-        debug!("READ_VIN: {:.2}V", self.slinear11_to_float(read_vin));
-
-        let read_vout = self.read_word(PmbusCommand::ReadVout).await?;
-        debug!("READ_VOUT: {:.2}V", self.decode_voltage(read_vout).await?);
-
-        let read_iout = self.read_word(PmbusCommand::ReadIout).await?;
-        debug!("READ_IOUT: {:.2}A", self.slinear11_to_float(read_iout));
-
-        let read_temp = self.read_word(PmbusCommand::ReadTemperature1).await?;
-        debug!(
-            "READ_TEMPERATURE_1: {} degC",
-            self.slinear11_to_int(read_temp)
-        );
+        self.device.dump_telemetry().await?;
 
         // Timing Configuration
         debug!("--- Timing Configuration ---");
 
-        let ton_delay = self.read_word(PmbusCommand::TonDelay).await?;
-        debug!("TON_DELAY: {}ms", self.slinear11_to_int(ton_delay));
+        let ton_delay = self.device.read_word(PmbusCommand::TonDelay).await?;
+        debug!("TON_DELAY: {}ms", linear11::to_float(ton_delay) as i32);
 
-        let ton_rise = self.read_word(PmbusCommand::TonRise).await?;
-        debug!("TON_RISE: {}ms", self.slinear11_to_int(ton_rise));
+        let ton_rise = self.device.read_word(PmbusCommand::TonRise).await?;
+        debug!("TON_RISE: {}ms", linear11::to_float(ton_rise) as i32);
 
-        let ton_max_fault = self.read_word(PmbusCommand::TonMaxFaultLimit).await?;
+        let ton_max_fault = self
+            .device
+            .read_word(PmbusCommand::TonMaxFaultLimit)
+            .await?;
         debug!(
             "TON_MAX_FAULT_LIMIT: {}ms",
-            self.slinear11_to_int(ton_max_fault)
+            linear11::to_float(ton_max_fault) as i32
         );
 
-        let ton_max_response = self.read_byte(PmbusCommand::TonMaxFaultResponse).await?;
-        let ton_max_desc = self.decode_fault_response(ton_max_response);
+        let ton_max_response = self
+            .device
+            .read_byte(PmbusCommand::TonMaxFaultResponse)
+            .await?;
+        let ton_max_desc = StatusDecoder::decode_fault_response(ton_max_response);
         debug!(
             "TON_MAX_FAULT_RESPONSE: 0x{:02X} ({})",
             ton_max_response, ton_max_desc
         );
 
-        let toff_delay = self.read_word(PmbusCommand::ToffDelay).await?;
-        debug!("TOFF_DELAY: {}ms", self.slinear11_to_int(toff_delay));
+        let toff_delay = self.device.read_word(PmbusCommand::ToffDelay).await?;
+        debug!("TOFF_DELAY: {}ms", linear11::to_float(toff_delay) as i32);
 
-        let toff_fall = self.read_word(PmbusCommand::ToffFall).await?;
-        debug!("TOFF_FALL: {}ms", self.slinear11_to_int(toff_fall));
+        let toff_fall = self.device.read_word(PmbusCommand::ToffFall).await?;
+        debug!("TOFF_FALL: {}ms", linear11::to_float(toff_fall) as i32);
 
         // Operational Configuration
         debug!("--- Operational Configuration ---");
 
-        let phase = self.read_byte(PmbusCommand::Phase).await?;
+        let phase = self.device.read_byte(PmbusCommand::Phase).await?;
         let phase_desc = if phase == 0xFF {
             "all phases".to_string()
         } else {
@@ -997,16 +788,16 @@ impl<I2C: I2c> Tps546<I2C> {
         };
         debug!("PHASE: 0x{:02X} ({})", phase, phase_desc);
 
-        let stack_config = self.read_word(PmbusCommand::StackConfig).await?;
+        let stack_config = self.device.read_word(PmbusCommand::StackConfig).await?;
         debug!("STACK_CONFIG: 0x{:04X}", stack_config);
 
-        let sync_config = self.read_byte(PmbusCommand::SyncConfig).await?;
+        let sync_config = self.device.read_byte(PmbusCommand::SyncConfig).await?;
         debug!("SYNC_CONFIG: 0x{:02X}", sync_config);
 
-        let interleave = self.read_word(PmbusCommand::Interleave).await?;
+        let interleave = self.device.read_word(PmbusCommand::Interleave).await?;
         debug!("INTERLEAVE: 0x{:04X}", interleave);
 
-        let capability = self.read_byte(PmbusCommand::Capability).await?;
+        let capability = self.device.read_byte(PmbusCommand::Capability).await?;
         let mut cap_desc = Vec::new();
         if capability & 0x80 != 0 {
             cap_desc.push("PEC supported");
@@ -1027,7 +818,7 @@ impl<I2C: I2c> Tps546<I2C> {
             }
         );
 
-        let op_val = self.read_byte(PmbusCommand::Operation).await?;
+        let op_val = self.device.read_byte(PmbusCommand::Operation).await?;
         let op_desc = match pmbus::Operation::try_from(op_val) {
             Ok(pmbus::Operation::OffImmediate) => "OFF (immediate)",
             Ok(pmbus::Operation::SoftOff) => "SOFT OFF",
@@ -1040,7 +831,7 @@ impl<I2C: I2c> Tps546<I2C> {
         };
         debug!("OPERATION: 0x{:02X} ({})", op_val, op_desc);
 
-        let on_off_val = self.read_byte(PmbusCommand::OnOffConfig).await?;
+        let on_off_val = self.device.read_byte(PmbusCommand::OnOffConfig).await?;
         let on_off_flags = pmbus::OnOffConfig::from_bits_truncate(on_off_val);
         let mut on_off_desc = Vec::new();
         if on_off_flags.contains(pmbus::OnOffConfig::PU) {
@@ -1065,7 +856,11 @@ impl<I2C: I2c> Tps546<I2C> {
         );
 
         // Compensation Configuration
-        match self.read_block(PmbusCommand::CompensationConfig, 5).await {
+        match self
+            .device
+            .read_block(PmbusCommand::CompensationConfig, 5)
+            .await
+        {
             Ok(comp_config) => {
                 debug!("COMPENSATION_CONFIG: {:02X?}", comp_config);
             }
@@ -1076,190 +871,250 @@ impl<I2C: I2c> Tps546<I2C> {
 
         // Status Information
         debug!("--- Status Information ---");
-
-        let status_word = self.read_word(PmbusCommand::StatusWord).await?;
-        let status_desc = self.decode_status_word(status_word);
-
-        if status_desc.is_empty() {
-            debug!("STATUS_WORD: 0x{:04X} (no flags set)", status_word);
-        } else {
-            debug!(
-                "STATUS_WORD: 0x{:04X} ({})",
-                status_word,
-                status_desc.join(", ")
-            );
-        }
-
-        // Read detailed status registers if main status indicates issues
-        let status_flags = pmbus::StatusWord::from_bits_truncate(status_word);
-        if status_flags.contains(pmbus::StatusWord::VOUT) {
-            let vout_status = self.read_byte(PmbusCommand::StatusVout).await?;
-            let desc = self.decode_status_vout(vout_status);
-            debug!("STATUS_VOUT: 0x{:02X} ({})", vout_status, desc.join(", "));
-        }
-
-        if status_flags.contains(pmbus::StatusWord::IOUT) {
-            let iout_status = self.read_byte(PmbusCommand::StatusIout).await?;
-            let desc = self.decode_status_iout(iout_status);
-            debug!("STATUS_IOUT: 0x{:02X} ({})", iout_status, desc.join(", "));
-        }
-
-        if status_flags.contains(pmbus::StatusWord::INPUT) {
-            let input_status = self.read_byte(PmbusCommand::StatusInput).await?;
-            let desc = self.decode_status_input(input_status);
-            debug!("STATUS_INPUT: 0x{:02X} ({})", input_status, desc.join(", "));
-        }
-
-        if status_flags.contains(pmbus::StatusWord::TEMP) {
-            let temp_status = self.read_byte(PmbusCommand::StatusTemperature).await?;
-            let desc = self.decode_status_temp(temp_status);
-            debug!(
-                "STATUS_TEMPERATURE: 0x{:02X} ({})",
-                temp_status,
-                desc.join(", ")
-            );
-        }
-
-        if status_flags.contains(pmbus::StatusWord::CML) {
-            let cml_status = self.read_byte(PmbusCommand::StatusCml).await?;
-            let desc = self.decode_status_cml(cml_status);
-            debug!("STATUS_CML: 0x{:02X} ({})", cml_status, desc.join(", "));
-        }
+        self.device.dump_status().await?;
 
         debug!("=== End Configuration Dump ===");
         Ok(())
     }
+}
 
-    // Helper methods for decoding status registers
-
-    fn decode_status_word(&self, status: u16) -> Vec<&'static str> {
-        StatusDecoder::decode_status_word(status)
+/// Verify a device is a TPS546
+async fn verify_device_id<I2C: I2c>(device: &mut PmbusDevice<I2C>) -> Result<()> {
+    let device_id = device.read_block(PmbusCommand::IcDeviceId, 6).await?;
+    debug!(
+        "Device ID: {:02X} {:02X} {:02X} {:02X} {:02X} {:02X}",
+        device_id[0], device_id[1], device_id[2], device_id[3], device_id[4], device_id[5]
+    );
+
+    if device_id != DEVICE_ID1 && device_id != DEVICE_ID2 && device_id != DEVICE_ID3 {
+        error!(address = device.address(), "Device ID mismatch");
+        bail!(Tps546Error::DeviceIdMismatch);
     }
 
-    fn decode_status_vout(&self, status: u8) -> Vec<&'static str> {
-        StatusDecoder::decode_status_vout(status)
-    }
+    Ok(())
+}
 
-    fn decode_status_iout(&self, status: u8) -> Vec<&'static str> {
-        StatusDecoder::decode_status_iout(status)
-    }
+/// Whether a command sets up the rail as a whole, which in a stack is the
+/// loop master's job
+fn is_rail_setting(command: PmbusCommand) -> bool {
+    use PmbusCommand::*;
+
+    matches!(
+        command,
+        VoutScaleLoop
+            | VoutCommand
+            | VoutMax
+            | VoutMin
+            | VoutOvFaultLimit
+            | VoutOvWarnLimit
+            | VoutMarginHigh
+            | VoutMarginLow
+            | VoutUvWarnLimit
+            | VoutUvFaultLimit
+            | TonDelay
+            | TonRise
+            | TonMaxFaultLimit
+            | TonMaxFaultResponse
+            | ToffDelay
+            | ToffFall
+    )
+}
 
-    fn decode_status_input(&self, status: u8) -> Vec<&'static str> {
-        StatusDecoder::decode_status_input(status)
-    }
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
 
-    fn decode_status_temp(&self, status: u8) -> Vec<&'static str> {
-        StatusDecoder::decode_status_temp(status)
-    }
+    use async_trait::async_trait;
+    use parking_lot::Mutex;
 
-    fn decode_status_cml(&self, status: u8) -> Vec<&'static str> {
-        StatusDecoder::decode_status_cml(status)
-    }
+    use super::*;
+    use crate::hw_trait::{i2c::I2cError, mock::MockI2c, Result as HwResult};
 
-    fn decode_fault_response(&self, response: u8) -> String {
-        StatusDecoder::decode_fault_response(response)
-    }
+    const SECONDARY: u8 = 0x25;
 
-    // Helper methods for I2C operations
+    /// Register contents by device address and command
+    type Registers = HashMap<(u8, u8), Vec<u8>>;
 
-    async fn read_byte(&mut self, command: PmbusCommand) -> Result<u8> {
-        let mut data = [0u8; 1];
-        self.i2c
-            .write_read(TPS546_I2C_ADDR, &[command.as_u8()], &mut data)
-            .await?;
-        Ok(data[0])
+    /// TPS546s answering from register maps, by address and command.
+    #[derive(Clone, Default)]
+    struct Stack {
+        registers: Arc<Mutex<Registers>>,
     }
 
-    async fn write_byte(&mut self, command: PmbusCommand, data: u8) -> Result<()> {
-        self.i2c
-            .write(TPS546_I2C_ADDR, &[command.as_u8(), data])
-            .await?;
-        Ok(())
-    }
+    impl Stack {
+        fn set(&self, address: u8, command: PmbusCommand, data: &[u8]) {
+            self.registers
+                .lock()
+                .insert((address, command.as_u8()), data.to_vec());
+        }
 
-    async fn read_word(&mut self, command: PmbusCommand) -> Result<u16> {
-        let mut data = [0u8; 2];
-        self.i2c
-            .write_read(TPS546_I2C_ADDR, &[command.as_u8()], &mut data)
-            .await?;
-        Ok(u16::from_le_bytes(data))
+        fn set_linear11(&self, address: u8, command: PmbusCommand, value: f32) {
+            self.set(address, command, &linear11::from_float(value).to_le_bytes());
+        }
     }
 
-    async fn write_word(&mut self, command: PmbusCommand, data: u16) -> Result<()> {
-        let bytes = data.to_le_bytes();
-        self.i2c
-            .write(TPS546_I2C_ADDR, &[command.as_u8(), bytes[0], bytes[1]])
-            .await?;
-        Ok(())
-    }
+    #[async_trait]
+    impl I2c for Stack {
+        async fn write(&mut self, _addr: u8, _data: &[u8]) -> HwResult<()> {
+            Ok(())
+        }
 
-    async fn read_block(&mut self, command: PmbusCommand, length: usize) -> Result<Vec<u8>> {
-        // PMBus block read: first byte is length, then data
-        let mut buffer = vec![0u8; length + 1];
-        self.i2c
-            .write_read(TPS546_I2C_ADDR, &[command.as_u8()], &mut buffer)
-            .await?;
+        async fn read(&mut self, _addr: u8, _buffer: &mut [u8]) -> HwResult<()> {
+            unimplemented!("reads go through write_read")
+        }
 
-        // First byte is the length; verify it matches expected value
-        let reported_length = buffer[0] as usize;
-        if reported_length != length {
-            warn!(
-                "Block read length mismatch: expected {}, got {}",
-                length, reported_length
-            );
+        async fn write_read(&mut self, addr: u8, write: &[u8], read: &mut [u8]) -> HwResult<()> {
+            let registers = self.registers.lock();
+            let data = registers
+                .get(&(addr, write[0]))
+                .ok_or(I2cError::NoAck(addr))?;
+            read.copy_from_slice(&data[..read.len()]);
+            Ok(())
         }
 
-        // Return just the data portion (skip length byte)
-        Ok(buffer[1..=length].to_vec())
+        async fn set_frequency(&mut self, _hz: u32) -> HwResult<()> {
+            Ok(())
+        }
     }
 
-    // Helper functions to use PMBus format converters
-
-    fn slinear11_to_float(&self, value: u16) -> f32 {
-        linear11::to_float(value)
+    fn config(stack: Vec<u8>) -> Tps546Config {
+        Tps546Config {
+            phase: 0x00,
+            frequency_switch_khz: 650,
+            vin_on: 4.8,
+            vin_off: 4.5,
+            vin_uv_warn_limit: 0.0,
+            vin_ov_fault_limit: 6.5,
+            vin_ov_fault_response: 0xB7,
+            vout_scale_loop: 0.25,
+            vout_min: 1.0,
+            vout_max: 1.4,
+            vout_command: 1.15,
+            vout_ov_fault_limit: 1.25,
+            vout_ov_warn_limit: 1.16,
+            vout_margin_high: 1.10,
+            vout_margin_low: 0.90,
+            vout_uv_warn_limit: 0.90,
+            vout_uv_fault_limit: 0.75,
+            iout_oc_warn_limit: 25.0,
+            iout_oc_fault_limit: 30.0,
+            iout_oc_fault_response: 0xC0,
+            ot_warn_limit: 105,
+            ot_fault_limit: 145,
+            ot_fault_response: 0xFF,
+            ton_delay: 0,
+            ton_rise: 3,
+            ton_max_fault_limit: 0,
+            ton_max_fault_response: 0x3B,
+            toff_delay: 0,
+            toff_fall: 0,
+            pin_detect_override: 0xFFFF,
+            stack,
+        }
     }
 
-    fn slinear11_to_int(&self, value: u16) -> i32 {
-        linear11::to_float(value) as i32
+    #[tokio::test]
+    async fn test_stack_telemetry_covers_every_device() {
+        let stack = Stack::default();
+        stack.set_linear11(TPS546_I2C_ADDR, PmbusCommand::ReadIout, 20.0);
+        stack.set_linear11(SECONDARY, PmbusCommand::ReadIout, 18.5);
+        stack.set_linear11(TPS546_I2C_ADDR, PmbusCommand::ReadTemperature1, 60.0);
+        stack.set_linear11(SECONDARY, PmbusCommand::ReadTemperature1, 72.0);
+        let mut tps546 = Tps546::new(stack, config(vec![SECONDARY]));
+
+        assert_eq!(tps546.get_iout().await.unwrap(), 38_500);
+        assert_eq!(tps546.get_temperature().await.unwrap(), 72);
     }
 
-    fn float_to_slinear11(&self, value: f32) -> u16 {
-        linear11::from_float(value)
-    }
+    #[tokio::test]
+    async fn test_secondary_faults_name_their_device() {
+        let stack = Stack::default();
+        stack.set(TPS546_I2C_ADDR, PmbusCommand::StatusWord, &[0, 0]);
+        stack.set(
+            SECONDARY,
+            PmbusCommand::StatusWord,
+            &pmbus::StatusWord::IOUT.bits().to_le_bytes(),
+        );
+        stack.set(
+            SECONDARY,
+            PmbusCommand::StatusIout,
+            &[pmbus::StatusIout::IOUT_OC_FAULT.bits()],
+        );
+        let mut tps546 = Tps546::new(stack, config(vec![SECONDARY]));
 
-    fn int_to_slinear11(&self, value: i32) -> u16 {
-        linear11::from_float(value as f32)
+        let error = tps546.check_status().await.unwrap_err().to_string();
+        assert!(error.contains("0x25: IOUT overcurrent"), "{error}");
     }
 
-    /// Get VOUT_MODE with caching to avoid redundant I2C reads
-    async fn get_vout_mode(&mut self) -> Result<u8> {
-        if let Some(cached) = self.cached_vout_mode {
-            Ok(cached)
-        } else {
-            let vout_mode = self.read_byte(PmbusCommand::VoutMode).await?;
-            self.cached_vout_mode = Some(vout_mode);
-            debug!("Cached VOUT_MODE: 0x{:02x}", vout_mode);
-            Ok(vout_mode)
-        }
+    #[tokio::test]
+    async fn test_unstacked_master_refused() {
+        let stack = Stack::default();
+        stack.set(TPS546_I2C_ADDR, PmbusCommand::StackConfig, &[0, 0]);
+        let mut tps546 = Tps546::new(stack, config(vec![SECONDARY]));
+
+        let error = tps546.init_stack().await.unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<Tps546Error>(),
+            Some(Tps546Error::NotStacked(1))
+        ));
     }
 
-    /// Invalidate VOUT_MODE cache (call after writing to VOUT_MODE register)
-    #[expect(dead_code)]
-    fn invalidate_vout_mode_cache(&mut self) {
-        self.cached_vout_mode = None;
+    #[tokio::test]
+    async fn test_set_vout_sequence() {
+        let i2c = MockI2c::new();
+        let command = |command: PmbusCommand| command.as_u8();
+        // VOUT_MODE: ULINEAR16 with exponent -9
+        i2c.expect_write_read(TPS546_I2C_ADDR, &[command(PmbusCommand::VoutMode)], 1)
+            .respond_with([0x17]);
+        // 1.2 V is 614.4 in 2^-9 V steps
+        i2c.expect_write(
+            TPS546_I2C_ADDR,
+            &[command(PmbusCommand::VoutCommand), 0x66, 0x02],
+        );
+        i2c.expect_write(TPS546_I2C_ADDR, &[command(PmbusCommand::ClearFaults)]);
+        i2c.expect_write(
+            TPS546_I2C_ADDR,
+            &[
+                command(PmbusCommand::Operation),
+                pmbus::Operation::On.as_u8(),
+            ],
+        );
+        i2c.expect_write_read(TPS546_I2C_ADDR, &[command(PmbusCommand::Operation)], 1)
+            .respond_with([pmbus::Operation::On.as_u8()]);
+        i2c.expect_write_read(TPS546_I2C_ADDR, &[command(PmbusCommand::StatusWord)], 2)
+            .respond_with([0x00, 0x00]);
+        i2c.expect_write(
+            TPS546_I2C_ADDR,
+            &[
+                command(PmbusCommand::Operation),
+                pmbus::Operation::OffImmediate.as_u8(),
+            ],
+        );
+
+        let mut tps546 = Tps546::new(i2c.clone(), config(Vec::new()));
+        tps546.set_vout(1.2).await.unwrap();
+        tps546.set_vout(0.0).await.unwrap();
+        i2c.done();
     }
 
-    /// Convert f32 to Linear16 format using cached VOUT_MODE
-    async fn encode_voltage(&mut self, volts: f32) -> Result<u16> {
-        let vout_mode = VoutMode::new(self.get_vout_mode().await?);
-        vout_mode
-            .encode_linear16(volts)
-            .map_err(|e| anyhow::anyhow!("Voltage encoding error: {}", e))
+    #[tokio::test]
+    async fn test_set_vout_refuses_out_of_range_without_writing() {
+        let i2c = MockI2c::new();
+        let mut tps546 = Tps546::new(i2c.clone(), config(Vec::new()));
+
+        let error = tps546.set_vout(1.5).await.unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<Tps546Error>(),
+            Some(Tps546Error::VoltageOutOfRange(..))
+        ));
+        i2c.done();
     }
 
-    /// Convert Linear16 format to f32 using cached VOUT_MODE
-    async fn decode_voltage(&mut self, value: u16) -> Result<f32> {
-        let vout_mode = VoutMode::new(self.get_vout_mode().await?);
-        Ok(vout_mode.decode_linear16(value))
+    #[test]
+    fn test_secondaries_leave_the_rail_to_the_master() {
+        assert!(is_rail_setting(PmbusCommand::VoutCommand));
+        assert!(!is_rail_setting(PmbusCommand::IoutOcFaultLimit));
+        assert!(!is_rail_setting(PmbusCommand::OtFaultLimit));
     }
 }
@@ -0,0 +1,1130 @@
+//! Pool manager.
+//!
+//! The manager owns the list of configured pools and runs a job source for
+//! the one being mined: the pool picked through the API, if any, otherwise
+//! the pool with the lowest priority value. With no pools at all, the dummy
+//! source runs instead, as it always has. The kind of source is whichever
+//! is registered for the pool's URL scheme (see
+//! [`crate::job_source::registry`]): a pool with a `datum://` URL, for
+//! instance, is mined on templates from the named node rather than over
+//! Stratum, and one with the URL `dummy://` runs the dummy source.
+//!
+//! Switching pools stops the old source before registering the new one. The
+//! old source's event channel closes, and the scheduler drops its work.
+//!
+//! Adding, removing, and reprioritizing pools are written back to the config
+//! file the pools came from, if any, before they take effect; a change that
+//! can't be saved isn't made. Picking a pool is a runtime choice and isn't
+//! saved.
+//!
+//! # Reject Breaker
+//!
+//! A pool rejecting most of our shares is usually one our work has fallen
+//! out of step with: jobs gone stale behind a half-dead connection, or a
+//! session the pool has forgotten. Every [`BREAKER_CHECK_INTERVAL`] the
+//! share counts of the pool being mined are checked, and if more than
+//! `max_reject_percent` of those within the window were rejected, the
+//! breaker trips. The first time, the pool's source is restarted. Its work
+//! is dropped with it, so nothing more is submitted until the fresh
+//! session's first job. If the breaker trips again within `cooldown_secs`,
+//! the pool is passed over for that long and the next pool by priority is
+//! mined. Each step is announced on the event bus.
+
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::Context;
+use thiserror::Error;
+use tokio::sync::{broadcast, mpsc, oneshot, watch};
+use tokio::time::{Instant, MissedTickBehavior};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    config::{Config, PoolConfig, RejectBreakerConfig, ShareQueueConfig},
+    job_source::{
+        dummy,
+        forced_rate::{ForcedRateConfig, ForcedRateSource},
+        stratum_v1::PoolStatus,
+        SourceCommand, SourceEvent, SourceParams, SourceRegistry,
+    },
+    runtime::RuntimeEvent,
+    scheduler::SourceRegistration,
+    secret,
+    stratum_v1::FLOOD_PREVENTION_CAP,
+    supervisor::Supervisor,
+    tracing::prelude::*,
+    types::Network,
+};
+
+/// Identifies a pool for the life of the daemon.
+pub type PoolId = u32;
+
+/// How often the pool being mined is checked against the reject breaker.
+pub const BREAKER_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Requests the API makes of the pool manager.
+#[derive(Debug)]
+pub enum PoolCommand {
+    /// List the pools in priority order.
+    List {
+        reply_tx: oneshot::Sender<Vec<PoolInfo>>,
+    },
+
+    /// Add a pool. Replies with the pool as added.
+    Add {
+        pool: PoolConfig,
+        reply_tx: oneshot::Sender<Result<PoolInfo, PoolError>>,
+    },
+
+    /// Remove a pool, switching away from it if it's being mined.
+    Remove {
+        id: PoolId,
+        reply_tx: oneshot::Sender<Result<(), PoolError>>,
+    },
+
+    /// Change a pool's priority. Hands the choice of pool back to priority
+    /// order if one was picked.
+    SetPriority {
+        id: PoolId,
+        priority: u32,
+        reply_tx: oneshot::Sender<Result<PoolInfo, PoolError>>,
+    },
+
+    /// Mine a pool regardless of priority, until it's removed or a priority
+    /// changes.
+    Activate {
+        id: PoolId,
+        reply_tx: oneshot::Sender<Result<PoolInfo, PoolError>>,
+    },
+
+    /// Replace the whole list, without saving it. Sent when the config file
+    /// was replaced through the API, which saves it itself.
+    Replace { pools: Vec<PoolConfig> },
+}
+
+/// A pool as the manager sees it.
+#[derive(Debug, Clone)]
+pub struct PoolInfo {
+    pub id: PoolId,
+    pub url: String,
+    pub worker: String,
+    /// Lower is preferred
+    pub priority: u32,
+    /// Being mined now
+    pub active: bool,
+    /// Picked through the API rather than by priority
+    pub forced: bool,
+    /// Connection state and share counts of its latest session
+    pub status: PoolStatus,
+}
+
+/// Why a pool request failed.
+#[derive(Error, Debug)]
+pub enum PoolError {
+    #[error("no pool with ID {0}")]
+    NotFound(PoolId),
+
+    #[error("invalid pool: {0}")]
+    Invalid(String),
+
+    #[error("failed to save pools to the config file: {0:#}")]
+    Save(anyhow::Error),
+}
+
+/// Runs the source for the selected pool and serves pool requests.
+pub struct PoolManager {
+    pools: Vec<PoolEntry>,
+    next_id: PoolId,
+    /// Pool picked through the API, overriding priority
+    forced: Option<PoolId>,
+    /// Source being run, if any
+    active: Option<ActiveSource>,
+    /// Config file to write pool changes back to
+    config_path: Option<PathBuf>,
+    network: Network,
+    forced_rate: Option<ForcedRateConfig>,
+    share_queue: ShareQueueConfig,
+    /// Where transcripts of traced pools are kept
+    trace_dir: Option<PathBuf>,
+    /// Reject breaker of the pool being mined
+    breaker: RejectBreaker,
+    /// Pools failed over from, and until when they're passed over
+    benched: HashMap<PoolId, Instant>,
+    events: Option<broadcast::Sender<RuntimeEvent>>,
+    supervisor: Supervisor,
+    source_reg_tx: mpsc::Sender<SourceRegistration>,
+    command_rx: mpsc::Receiver<PoolCommand>,
+    shutdown: CancellationToken,
+}
+
+struct PoolEntry {
+    id: PoolId,
+    config: PoolConfig,
+    /// Status of the pool's latest source, once it has run
+    status_rx: Option<watch::Receiver<PoolStatus>>,
+}
+
+/// The source being run, and the supervisor group that stops it.
+struct ActiveSource {
+    /// Pool mined, or None for the dummy source
+    pool: Option<PoolId>,
+    group: Supervisor,
+}
+
+/// What the reject breaker calls for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Trip {
+    /// Restart the pool's source for fresh work
+    Refresh { reject_percent: f64 },
+    /// Mine another pool for a while
+    FailOver { reject_percent: f64 },
+}
+
+/// Judges the reject rate of the pool being mined.
+#[derive(Debug)]
+struct RejectBreaker {
+    config: RejectBreakerConfig,
+    /// Accepted and rejected counts sampled within the window, oldest first
+    samples: VecDeque<(Instant, u64, u64)>,
+    /// When the pool was last refreshed for its rejects
+    refreshed_at: Option<Instant>,
+}
+
+impl PoolManager {
+    /// Create a manager for `pools`, registering sources with the scheduler
+    /// through `source_reg_tx` and spawning them through `supervisor`.
+    pub fn new(
+        pools: Vec<PoolConfig>,
+        source_reg_tx: mpsc::Sender<SourceRegistration>,
+        command_rx: mpsc::Receiver<PoolCommand>,
+        supervisor: Supervisor,
+        shutdown: CancellationToken,
+    ) -> Self {
+        let pools: Vec<PoolEntry> = pools
+            .into_iter()
+            .zip(1..)
+            .map(|(config, id)| PoolEntry {
+                id,
+                config,
+                status_rx: None,
+            })
+            .collect();
+
+        Self {
+            next_id: pools.len() as PoolId + 1,
+            pools,
+            forced: None,
+            active: None,
+            config_path: None,
+            network: Network::default(),
+            forced_rate: None,
+            share_queue: ShareQueueConfig::default(),
+            trace_dir: None,
+            breaker: RejectBreaker::new(RejectBreakerConfig::default()),
+            benched: HashMap::new(),
+            events: None,
+            supervisor,
+            source_reg_tx,
+            command_rx,
+            shutdown,
+        }
+    }
+
+    /// Set the network the pools are expected to serve (mainnet by default).
+    pub fn with_network(mut self, network: Network) -> Self {
+        self.network = network;
+        self
+    }
+
+    /// Write pool changes back to this config file.
+    pub fn with_config_file(mut self, path: PathBuf) -> Self {
+        self.config_path = Some(path);
+        self
+    }
+
+    /// Queue shares that fail to reach a pool as `config` says (in memory,
+    /// with default limits, unless set).
+    pub fn with_share_queue(mut self, config: ShareQueueConfig) -> Self {
+        self.share_queue = config;
+        self
+    }
+
+    /// Keep transcripts of traced pools' messages in `dir` (pools' `trace`
+    /// does nothing unless set).
+    pub fn with_trace_dir(mut self, dir: PathBuf) -> Self {
+        self.trace_dir = Some(dir);
+        self
+    }
+
+    /// Trip the reject breaker as `config` says (with default limits
+    /// unless set).
+    pub fn with_reject_breaker(mut self, config: RejectBreakerConfig) -> Self {
+        self.breaker = RejectBreaker::new(config);
+        self
+    }
+
+    /// Announce refreshes and failovers on `events`.
+    pub fn with_events(mut self, events: broadcast::Sender<RuntimeEvent>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Wrap each pool's source to force its share rate (for testing).
+    pub fn with_forced_rate(mut self, config: ForcedRateConfig) -> Self {
+        self.forced_rate = Some(config);
+        self
+    }
+
+    /// Start the selected source and serve requests until shutdown.
+    pub async fn run(mut self) -> anyhow::Result<()> {
+        self.switch_to_selected().await?;
+        let mut breaker_ticker = tokio::time::interval(BREAKER_CHECK_INTERVAL);
+        breaker_ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                Some(command) = self.command_rx.recv() => {
+                    self.handle_command(command).await?;
+                }
+                _ = breaker_ticker.tick() => {
+                    self.check_rejects(Instant::now()).await?;
+                }
+                _ = self.shutdown.cancelled() => break,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Apply a request and switch pools if it changed the selection.
+    ///
+    /// Replies once the switch is made, so the reply shows the pool being
+    /// mined. Errors only if a source can't be started, which means the
+    /// scheduler is gone.
+    async fn handle_command(&mut self, command: PoolCommand) -> anyhow::Result<()> {
+        match command {
+            PoolCommand::List { reply_tx } => {
+                let _ = reply_tx.send(self.list());
+            }
+            PoolCommand::Add { pool, reply_tx } => {
+                let result = self.add(pool);
+                self.switch_to_selected().await?;
+                let _ = reply_tx.send(result.and_then(|id| self.info_by_id(id)));
+            }
+            PoolCommand::Remove { id, reply_tx } => {
+                let result = self.remove(id);
+                self.switch_to_selected().await?;
+                let _ = reply_tx.send(result);
+            }
+            PoolCommand::SetPriority {
+                id,
+                priority,
+                reply_tx,
+            } => {
+                let result = self.set_priority(id, priority);
+                self.switch_to_selected().await?;
+                let _ = reply_tx.send(result.and_then(|id| self.info_by_id(id)));
+            }
+            PoolCommand::Activate { id, reply_tx } => {
+                let result = self.activate(id);
+                self.switch_to_selected().await?;
+                let _ = reply_tx.send(result.and_then(|id| self.info_by_id(id)));
+            }
+            PoolCommand::Replace { pools } => {
+                self.replace(pools);
+                self.switch_to_selected().await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Every pool, in priority order.
+    fn list(&self) -> Vec<PoolInfo> {
+        let mut pools: Vec<PoolInfo> = self.pools.iter().map(|p| self.info(p)).collect();
+        pools.sort_by_key(|p| p.priority);
+        pools
+    }
+
+    fn add(&mut self, pool: PoolConfig) -> Result<PoolId, PoolError> {
+        check_pool(&pool, self.network)?;
+
+        let mut configs = self.configs();
+        configs.push(pool.clone());
+        self.save(configs)?;
+        let url = pool.url.clone();
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.pools.push(PoolEntry {
+            id,
+            config: pool,
+            status_rx: None,
+        });
+        info!(pool = id, url = %url, "Pool added.");
+        Ok(id)
+    }
+
+    fn remove(&mut self, id: PoolId) -> Result<(), PoolError> {
+        let index = self.index(id)?;
+
+        let mut configs = self.configs();
+        configs.remove(index);
+        self.save(configs)?;
+
+        let removed = self.pools.remove(index);
+        if self.forced == Some(id) {
+            self.forced = None;
+        }
+        info!(pool = id, url = %removed.config.url, "Pool removed.");
+        Ok(())
+    }
+
+    fn set_priority(&mut self, id: PoolId, priority: u32) -> Result<PoolId, PoolError> {
+        let index = self.index(id)?;
+
+        let mut configs = self.configs();
+        configs[index].priority = priority;
+        self.save(configs)?;
+
+        self.pools[index].config.priority = priority;
+        self.forced = None;
+        info!(pool = id, priority, "Pool priority changed.");
+        Ok(id)
+    }
+
+    fn activate(&mut self, id: PoolId) -> Result<PoolId, PoolError> {
+        self.index(id)?;
+        self.forced = Some(id);
+        info!(pool = id, "Pool selected via API.");
+        Ok(id)
+    }
+
+    /// Pools with the same URL, worker, and password as before keep their ID
+    /// and status, so the pool being mined isn't reconnected needlessly.
+    fn replace(&mut self, pools: Vec<PoolConfig>) {
+        let mut previous = std::mem::take(&mut self.pools);
+        for config in pools {
+            let kept = previous.iter().position(|p| {
+                p.config.url == config.url
+                    && p.config.worker == config.worker
+                    && p.config.password == config.password
+            });
+            let entry = match kept {
+                Some(index) => PoolEntry {
+                    config,
+                    ..previous.swap_remove(index)
+                },
+                None => {
+                    let id = self.next_id;
+                    self.next_id += 1;
+                    PoolEntry {
+                        id,
+                        config,
+                        status_rx: None,
+                    }
+                }
+            };
+            self.pools.push(entry);
+        }
+
+        if self.forced.is_some_and(|id| self.index(id).is_err()) {
+            self.forced = None;
+        }
+        info!(pools = self.pools.len(), "Pools reloaded.");
+    }
+
+    /// The pool that should be mined: the forced one, else the preferred,
+    /// passing over pools failed over from unless there's no other.
+    fn selected(&self) -> Option<PoolId> {
+        let available = |id: &PoolId| !self.benched.contains_key(id);
+        let preferred = self
+            .pools
+            .iter()
+            .filter(|p| available(&p.id))
+            .min_by_key(|p| p.config.priority)
+            .map(|p| p.id);
+        self.forced
+            .filter(available)
+            .or(preferred)
+            .or(self.forced)
+            .or_else(|| {
+                self.pools
+                    .iter()
+                    .min_by_key(|p| p.config.priority)
+                    .map(|p| p.id)
+            })
+    }
+
+    /// Stop the running source and start the selected one, if they differ.
+    async fn switch_to_selected(&mut self) -> anyhow::Result<()> {
+        let selected = self.selected();
+        if let Some(active) = &self.active {
+            if active.pool == selected {
+                return Ok(());
+            }
+        }
+        self.breaker.reset();
+        self.restart(selected).await
+    }
+
+    /// Check the pool being mined against the reject breaker, refreshing or
+    /// failing it over if it trips, and return to pools whose time out is
+    /// over.
+    async fn check_rejects(&mut self, now: Instant) -> anyhow::Result<()> {
+        let benched = self.benched.len();
+        self.benched.retain(|_, until| *until > now);
+        if self.benched.len() < benched {
+            self.switch_to_selected().await?;
+        }
+
+        let Some(id) = self.active.as_ref().and_then(|a| a.pool) else {
+            return Ok(());
+        };
+        let Ok(index) = self.index(id) else {
+            return Ok(());
+        };
+        let Some(status) = self.pools[index].status_rx.as_ref().map(|rx| *rx.borrow()) else {
+            return Ok(());
+        };
+        let Some(trip) = self.breaker.sample(status.accepted, status.rejected, now) else {
+            return Ok(());
+        };
+
+        let url = self.pools[index].config.url.clone();
+        let reject_percent = match trip {
+            Trip::Refresh { reject_percent } | Trip::FailOver { reject_percent } => reject_percent,
+        };
+        if let Trip::FailOver { .. } = trip {
+            let cooldown = Duration::from_secs(self.breaker.config.cooldown_secs);
+            self.benched.insert(id, now + cooldown);
+            if let Some(to) = self.selected().filter(|&to| to != id) {
+                let to_url = self.pools[self.index(to)?].config.url.clone();
+                warn!(
+                    pool = id,
+                    url = %url,
+                    to = %to_url,
+                    reject_percent = format!("{:.0}", reject_percent),
+                    cooldown_secs = cooldown.as_secs(),
+                    "Pool still rejecting too many shares after a refresh; failing over"
+                );
+                self.switch_to_selected().await?;
+                self.emit(RuntimeEvent::PoolFailedOver {
+                    from: url,
+                    to: to_url,
+                    reject_percent: reject_percent.round() as u32,
+                });
+                return Ok(());
+            }
+        }
+
+        warn!(
+            pool = id,
+            url = %url,
+            reject_percent = format!("{:.0}", reject_percent),
+            "Pool rejecting too many shares; reconnecting for fresh work"
+        );
+        self.restart(Some(id)).await?;
+        self.emit(RuntimeEvent::PoolRefreshed {
+            url,
+            reject_percent: reject_percent.round() as u32,
+        });
+        Ok(())
+    }
+
+    /// Stop the running source, if any, and start the one for `selected`.
+    async fn restart(&mut self, selected: Option<PoolId>) -> anyhow::Result<()> {
+        if let Some(active) = self.active.take() {
+            active.group.cancellation_token().cancel();
+        }
+
+        let group = self.supervisor.child();
+        let registration = match selected {
+            Some(id) => {
+                let index = self.index(id)?;
+                let (registration, status_rx) =
+                    self.start_pool(&group, &self.pools[index].config)?;
+                self.pools[index].status_rx = Some(status_rx);
+                registration
+            }
+            None => self.start_dummy(&group)?,
+        };
+        self.source_reg_tx.send(registration).await?;
+
+        self.active = Some(ActiveSource {
+            pool: selected,
+            group,
+        });
+        Ok(())
+    }
+
+    /// Start a source for `pool` in `group`.
+    fn start_pool(
+        &self,
+        group: &Supervisor,
+        pool: &PoolConfig,
+    ) -> anyhow::Result<(SourceRegistration, watch::Receiver<PoolStatus>)> {
+        let shutdown = group.cancellation_token();
+        let (event_tx, event_rx) = mpsc::channel::<SourceEvent>(100);
+        let (command_tx, command_rx) = mpsc::channel::<SourceCommand>(10);
+
+        let Some(forced_rate_config) = &self.forced_rate else {
+            let (name, status_rx) = self.spawn_source(group, pool, command_rx, event_tx)?;
+            let registration = SourceRegistration {
+                name,
+                event_rx,
+                command_tx,
+                max_share_rate: Some(FLOOD_PREVENTION_CAP),
+            };
+            return Ok((registration, status_rx));
+        };
+
+        // The wrapper sits between the scheduler and the source
+        let (inner_event_tx, inner_event_rx) = mpsc::channel::<SourceEvent>(100);
+        let (inner_cmd_tx, inner_cmd_rx) = mpsc::channel::<SourceCommand>(10);
+        let (name, status_rx) = self.spawn_source(group, pool, inner_cmd_rx, inner_event_tx)?;
+
+        let forced_rate = ForcedRateSource::new(
+            forced_rate_config.clone(),
+            inner_event_rx,
+            event_tx,
+            inner_cmd_tx,
+            command_rx,
+            shutdown,
+        );
+        group.spawn_critical("forced-rate", forced_rate.run());
+
+        let registration = SourceRegistration {
+            name: format!("{} (forced-rate)", name),
+            event_rx,
+            command_tx,
+            max_share_rate: None, // Wrapper controls rate
+        };
+        Ok((registration, status_rx))
+    }
+
+    /// Spawn the source `pool`'s URL calls for in `group`, returning its
+    /// name and status.
+    fn spawn_source(
+        &self,
+        group: &Supervisor,
+        pool: &PoolConfig,
+        command_rx: mpsc::Receiver<SourceCommand>,
+        event_tx: mpsc::Sender<SourceEvent>,
+    ) -> anyhow::Result<(String, watch::Receiver<PoolStatus>)> {
+        let descriptor = SourceRegistry
+            .find(&pool.url)
+            .with_context(|| format!("no job source serves {}", pool.url))?;
+        let password = pool
+            .password
+            .as_deref()
+            .map(secret::resolve)
+            .transpose()
+            .with_context(|| format!("password of pool {}", pool.url))?;
+
+        let source = (descriptor.create_fn)(SourceParams {
+            pool,
+            password,
+            network: self.network,
+            share_queue: &self.share_queue,
+            trace_dir: self.trace_dir.clone().filter(|_| pool.trace),
+            command_rx,
+            event_tx,
+            shutdown: group.cancellation_token(),
+        })?;
+        let name = source.name();
+        let status_rx = source.status();
+        source.spawn(group);
+        Ok((name, status_rx))
+    }
+
+    /// Start the dummy source in `group`.
+    fn start_dummy(&self, group: &Supervisor) -> anyhow::Result<SourceRegistration> {
+        info!("Using dummy job source (no pools configured)");
+
+        let (event_tx, event_rx) = mpsc::channel::<SourceEvent>(100);
+        let (command_tx, command_rx) = mpsc::channel::<SourceCommand>(10);
+        let pool = PoolConfig {
+            url: dummy::URL.into(),
+            worker: String::new(),
+            password: None,
+            priority: 0,
+            shares_per_minute: None,
+            trace: false,
+        };
+        let (name, _) = self.spawn_source(group, &pool, command_rx, event_tx)?;
+
+        Ok(SourceRegistration {
+            name,
+            event_rx,
+            command_tx,
+            max_share_rate: Some(FLOOD_PREVENTION_CAP),
+        })
+    }
+
+    fn emit(&self, event: RuntimeEvent) {
+        if let Some(events) = &self.events {
+            let _ = events.send(event);
+        }
+    }
+
+    /// Write `pools` to the config file, if there is one.
+    fn save(&self, pools: Vec<PoolConfig>) -> Result<(), PoolError> {
+        let Some(path) = &self.config_path else {
+            return Ok(());
+        };
+
+        let mut config = Config::load_from(path).map_err(PoolError::Save)?;
+        config.pools = pools;
+        config.save_to(path).map_err(PoolError::Save)?;
+        debug!(path = %path.display(), "Saved pools to config file");
+        Ok(())
+    }
+
+    fn configs(&self) -> Vec<PoolConfig> {
+        self.pools.iter().map(|p| p.config.clone()).collect()
+    }
+
+    fn index(&self, id: PoolId) -> Result<usize, PoolError> {
+        self.pools
+            .iter()
+            .position(|p| p.id == id)
+            .ok_or(PoolError::NotFound(id))
+    }
+
+    fn info_by_id(&self, id: PoolId) -> Result<PoolInfo, PoolError> {
+        Ok(self.info(&self.pools[self.index(id)?]))
+    }
+
+    fn info(&self, pool: &PoolEntry) -> PoolInfo {
+        let active = self
+            .active
+            .as_ref()
+            .is_some_and(|a| a.pool == Some(pool.id));
+        let mut status = pool
+            .status_rx
+            .as_ref()
+            .map(|rx| *rx.borrow())
+            .unwrap_or_default();
+        // A stopped source's last status may still say connected
+        status.connected &= active;
+
+        PoolInfo {
+            id: pool.id,
+            url: pool.config.url.clone(),
+            worker: pool.config.worker.clone(),
+            priority: pool.config.priority,
+            active,
+            forced: self.forced == Some(pool.id),
+            status,
+        }
+    }
+}
+
+impl RejectBreaker {
+    fn new(config: RejectBreakerConfig) -> Self {
+        Self {
+            config,
+            samples: VecDeque::new(),
+            refreshed_at: None,
+        }
+    }
+
+    /// Start over, for another pool.
+    fn reset(&mut self) {
+        self.samples.clear();
+        self.refreshed_at = None;
+    }
+
+    /// Take the pool's share counts at `now`, returning what they call for.
+    fn sample(&mut self, accepted: u64, rejected: u64, now: Instant) -> Option<Trip> {
+        // Counts start over with each session
+        if self
+            .samples
+            .back()
+            .is_some_and(|&(_, a, r)| accepted < a || rejected < r)
+        {
+            self.samples.clear();
+        }
+        self.samples.push_back((now, accepted, rejected));
+        let window = Duration::from_secs(self.config.window_secs);
+        while self
+            .samples
+            .front()
+            .is_some_and(|&(at, ..)| now.saturating_duration_since(at) > window)
+        {
+            self.samples.pop_front();
+        }
+
+        let &(_, first_accepted, first_rejected) = self.samples.front()?;
+        let rejected = rejected - first_rejected;
+        let total = accepted - first_accepted + rejected;
+        if total < self.config.min_shares {
+            return None;
+        }
+        let reject_percent = rejected as f64 * 100.0 / total as f64;
+        if reject_percent <= self.config.max_reject_percent {
+            return None;
+        }
+
+        self.samples.clear();
+        let cooldown = Duration::from_secs(self.config.cooldown_secs);
+        if self
+            .refreshed_at
+            .is_some_and(|at| now.saturating_duration_since(at) < cooldown)
+        {
+            self.refreshed_at = None;
+            Some(Trip::FailOver { reject_percent })
+        } else {
+            self.refreshed_at = Some(now);
+            Some(Trip::Refresh { reject_percent })
+        }
+    }
+}
+
+/// Check that a pool can be connected to and mined on `network`.
+pub fn check_pool(pool: &PoolConfig, network: Network) -> Result<(), PoolError> {
+    let Some(descriptor) = SourceRegistry.find(&pool.url) else {
+        return Err(PoolError::Invalid(format!(
+            "URL '{}' names no known job source (schemes: {})",
+            pool.url,
+            SourceRegistry.schemes().join(", ")
+        )));
+    };
+
+    if pool.worker.is_empty() {
+        return Err(PoolError::Invalid("worker name is empty".into()));
+    }
+    if let Some(password) = &pool.password {
+        secret::check(password).map_err(|e| PoolError::Invalid(format!("password: {}", e)))?;
+    }
+    if let Some(rate) = pool.shares_per_minute {
+        if !(rate.is_finite() && rate > 0.0) {
+            return Err(PoolError::Invalid(
+                "shares_per_minute must be positive".into(),
+            ));
+        }
+    }
+    (descriptor.check_fn)(pool, network).map_err(PoolError::Invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_util::task::TaskTracker;
+
+    use super::*;
+
+    fn pool(port: u16, priority: u32) -> PoolConfig {
+        PoolConfig {
+            url: format!("stratum+tcp://127.0.0.1:{}", port),
+            worker: "rig1".into(),
+            password: None,
+            priority,
+            shares_per_minute: None,
+            trace: false,
+        }
+    }
+
+    struct Harness {
+        pools_tx: mpsc::Sender<PoolCommand>,
+        source_reg_rx: mpsc::Receiver<SourceRegistration>,
+        shutdown: CancellationToken,
+    }
+
+    fn spawn_manager(pools: Vec<PoolConfig>, config_path: Option<PathBuf>) -> Harness {
+        let (pools_tx, pools_rx) = mpsc::channel(1);
+        let (source_reg_tx, source_reg_rx) = mpsc::channel(4);
+        let shutdown = CancellationToken::new();
+        let supervisor = Supervisor::new(TaskTracker::new(), shutdown.clone());
+
+        let mut manager =
+            PoolManager::new(pools, source_reg_tx, pools_rx, supervisor, shutdown.clone());
+        if let Some(path) = config_path {
+            manager = manager.with_config_file(path);
+        }
+        tokio::spawn(manager.run());
+
+        Harness {
+            pools_tx,
+            source_reg_rx,
+            shutdown,
+        }
+    }
+
+    impl Harness {
+        async fn request<T>(&self, command: impl FnOnce(oneshot::Sender<T>) -> PoolCommand) -> T {
+            let (reply_tx, reply_rx) = oneshot::channel();
+            self.pools_tx.send(command(reply_tx)).await.unwrap();
+            reply_rx.await.unwrap()
+        }
+
+        async fn next_source(&mut self) -> SourceRegistration {
+            self.source_reg_rx.recv().await.unwrap()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_selects_by_priority_until_activated() {
+        let mut h = spawn_manager(vec![pool(1, 1), pool(2, 0)], None);
+
+        let mut first = h.next_source().await;
+        assert_eq!(first.name, "127.0.0.1:2");
+
+        // Activating the other pool stops the first source
+        let info = h
+            .request(|reply_tx| PoolCommand::Activate { id: 1, reply_tx })
+            .await
+            .unwrap();
+        assert!(info.active && info.forced);
+        assert_eq!(h.next_source().await.name, "127.0.0.1:1");
+        assert!(first.event_rx.recv().await.is_none());
+
+        // A priority change hands the choice back to priority order
+        let info = h
+            .request(|reply_tx| PoolCommand::SetPriority {
+                id: 1,
+                priority: 5,
+                reply_tx,
+            })
+            .await
+            .unwrap();
+        assert!(!info.active && !info.forced);
+        assert_eq!(h.next_source().await.name, "127.0.0.1:2");
+
+        let pools = h.request(|reply_tx| PoolCommand::List { reply_tx }).await;
+        let ids: Vec<PoolId> = pools.iter().map(|p| p.id).collect();
+        assert_eq!(ids, [2, 1]);
+
+        h.shutdown.cancel();
+    }
+
+    #[test]
+    fn test_reject_breaker_refreshes_then_fails_over() {
+        let mut breaker = RejectBreaker::new(RejectBreakerConfig::default());
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        assert_eq!(breaker.sample(100, 10, at(0)), None);
+        assert_eq!(breaker.sample(130, 30, at(60)), None);
+        assert_eq!(
+            breaker.sample(130, 80, at(120)),
+            Some(Trip::Refresh {
+                reject_percent: 70.0
+            })
+        );
+
+        // The refreshed session counts from zero
+        assert_eq!(breaker.sample(0, 0, at(130)), None);
+        assert!(matches!(
+            breaker.sample(5, 20, at(200)),
+            Some(Trip::FailOver { .. })
+        ));
+
+        // Long after a refresh, tripping again is a fresh start
+        assert_eq!(breaker.sample(5, 20, at(1000)), None);
+        assert!(matches!(
+            breaker.sample(5, 45, at(1100)),
+            Some(Trip::Refresh { .. })
+        ));
+    }
+
+    #[test]
+    fn test_reject_breaker_judges_recent_shares() {
+        let mut breaker = RejectBreaker::new(RejectBreakerConfig::default());
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        // Too few shares to judge
+        assert_eq!(breaker.sample(0, 0, at(0)), None);
+        assert_eq!(breaker.sample(5, 10, at(10)), None);
+
+        // Rejects from before the window don't count
+        assert_eq!(breaker.sample(200, 10, at(400)), None);
+        assert_eq!(breaker.sample(400, 20, at(500)), None);
+    }
+
+    #[tokio::test]
+    async fn test_failed_over_pool_passed_over() {
+        let shutdown = CancellationToken::new();
+        let mut manager = PoolManager::new(
+            vec![pool(1, 0), pool(2, 1)],
+            mpsc::channel(1).0,
+            mpsc::channel(1).1,
+            Supervisor::new(TaskTracker::new(), shutdown.clone()),
+            shutdown,
+        );
+        assert_eq!(manager.selected(), Some(1));
+
+        let until = Instant::now() + Duration::from_secs(900);
+        manager.benched.insert(1, until);
+        assert_eq!(manager.selected(), Some(2));
+        manager.forced = Some(1);
+        assert_eq!(manager.selected(), Some(2));
+
+        // With nowhere else to go, a pool sitting out is mined anyway
+        manager.benched.insert(2, until);
+        assert_eq!(manager.selected(), Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_removing_last_pool_falls_back_to_dummy() {
+        let mut h = spawn_manager(vec![pool(1, 0)], None);
+        assert_eq!(h.next_source().await.name, "127.0.0.1:1");
+
+        h.request(|reply_tx| PoolCommand::Remove { id: 1, reply_tx })
+            .await
+            .unwrap();
+        assert_eq!(h.next_source().await.name, "dummy");
+
+        let result = h
+            .request(|reply_tx| PoolCommand::Remove { id: 1, reply_tx })
+            .await;
+        assert!(matches!(result, Err(PoolError::NotFound(1))));
+
+        h.shutdown.cancel();
+    }
+
+    #[tokio::test]
+    async fn test_added_pool_started_by_its_scheme() {
+        let mut h = spawn_manager(vec![pool(1, 1)], None);
+        assert_eq!(h.next_source().await.name, "127.0.0.1:1");
+
+        let mut dummy = pool(0, 0);
+        dummy.url = dummy::URL.into();
+        let info = h
+            .request(|reply_tx| PoolCommand::Add {
+                pool: dummy,
+                reply_tx,
+            })
+            .await
+            .unwrap();
+        assert!(info.active && info.status.connected);
+        assert_eq!(h.next_source().await.name, "dummy");
+
+        h.shutdown.cancel();
+    }
+
+    #[tokio::test]
+    async fn test_changes_saved_to_config_file() {
+        let dir = std::env::temp_dir().join(format!("mujina-pools-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("mujina.toml");
+        std::fs::write(
+            &path,
+            r#"
+            [daemon]
+            log_level = "info"
+
+            [[pools]]
+            url = "stratum+tcp://127.0.0.1:1"
+            worker = "rig1"
+
+            [hardware]
+            temp_limit = 85.0
+            fan_min_rpm = 1000
+            fan_max_rpm = 6000
+
+            [api]
+            listen = "127.0.0.1:7785"
+            "#,
+        )
+        .unwrap();
+        let config = Config::load_from(&path).unwrap();
+
+        let mut h = spawn_manager(config.pools, Some(path.clone()));
+        h.next_source().await;
+
+        let added = h
+            .request(|reply_tx| PoolCommand::Add {
+                pool: pool(2, 3),
+                reply_tx,
+            })
+            .await
+            .unwrap();
+        assert_eq!(added.id, 2);
+        assert!(!added.active);
+
+        // A bad pool is refused and not saved
+        let mut bad = pool(3, 0);
+        bad.url = "stratum+tcp://no-port".into();
+        let result = h
+            .request(|reply_tx| PoolCommand::Add {
+                pool: bad,
+                reply_tx,
+            })
+            .await;
+        assert!(matches!(result, Err(PoolError::Invalid(_))));
+
+        let saved = Config::load_from(&path).unwrap();
+        assert_eq!(saved.pools.len(), 2);
+        assert_eq!(saved.pools[1].url, "stratum+tcp://127.0.0.1:2");
+        assert_eq!(saved.pools[1].priority, 3);
+        assert_eq!(saved.api.listen, "127.0.0.1:7785");
+
+        h.shutdown.cancel();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_replace_keeps_unchanged_pools() {
+        let mut h = spawn_manager(vec![pool(1, 0), pool(2, 1)], None);
+        h.next_source().await;
+
+        // Pool 1 is unchanged apart from priority, so it keeps mining
+        h.pools_tx
+            .send(PoolCommand::Replace {
+                pools: vec![pool(3, 5), pool(1, 2)],
+            })
+            .await
+            .unwrap();
+        let pools = h.request(|reply_tx| PoolCommand::List { reply_tx }).await;
+        let ids: Vec<(PoolId, u32, bool)> =
+            pools.iter().map(|p| (p.id, p.priority, p.active)).collect();
+        assert_eq!(ids, [(1, 2, true), (3, 5, false)]);
+        assert!(h.source_reg_rx.try_recv().is_err(), "pool was reconnected");
+
+        h.shutdown.cancel();
+    }
+
+    #[test]
+    fn test_check_pool() {
+        assert!(check_pool(&pool(3333, 0), Network::Mainnet).is_ok());
+
+        let mut no_scheme = pool(3333, 0);
+        no_scheme.url = "pool.example.com:3333".into();
+        assert!(check_pool(&no_scheme, Network::Mainnet).is_ok());
+
+        let mut bad_port = pool(0, 0);
+        bad_port.url = "stratum+tcp://pool.example.com:http".into();
+        assert!(check_pool(&bad_port, Network::Mainnet).is_err());
+
+        let mut no_worker = pool(3333, 0);
+        no_worker.worker.clear();
+        assert!(check_pool(&no_worker, Network::Mainnet).is_err());
+
+        // A password reference is checked but not read
+        let mut referring = pool(3333, 0);
+        referring.password = Some("env:MUJINA_TEST_UNSET_PASSWORD".into());
+        assert!(check_pool(&referring, Network::Mainnet).is_ok());
+        referring.password = Some("env:".into());
+        assert!(check_pool(&referring, Network::Mainnet).is_err());
+
+        // A datum pool pays the worker, so it must be an address
+        let mut datum = pool(0, 0);
+        datum.url = "datum://127.0.0.1:8332".into();
+        assert!(check_pool(&datum, Network::Mainnet).is_err());
+        datum.worker = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4.rig1".into();
+        assert!(check_pool(&datum, Network::Mainnet).is_ok());
+
+        let mut dummy = pool(0, 0);
+        dummy.url = "dummy://".into();
+        assert!(check_pool(&dummy, Network::Mainnet).is_ok());
+        dummy.url = "dummy://127.0.0.1:3333".into();
+        assert!(check_pool(&dummy, Network::Mainnet).is_err());
+
+        let mut unknown = pool(0, 0);
+        unknown.url = "http://pool.example.com:3333".into();
+        assert!(check_pool(&unknown, Network::Mainnet).is_err());
+    }
+}
@@ -0,0 +1,413 @@
+//! Total power budget across boards.
+//!
+//! A PSU or a solar array can supply only so much, and several boards
+//! together easily draw more. The power manager reads each board's draw from
+//! the telemetry its task caches (on most boards, the core regulator's
+//! readings) and, when the total goes over [`PowerBudget::limit_watts`],
+//! lowers core voltages until it fits. Once there's room again, throttled
+//! boards are brought back up toward the voltage they ran at before.
+//!
+//! Boards don't give up power equally: a cut falls on each in proportion to
+//! its draw divided by its weight, so a board weighted 2 loses half as much
+//! as one weighted 1 drawing the same. Room to raise is shared out by weight.
+//!
+//! Power is taken to go with the square of the core voltage, which holds
+//! well enough over the range a regulator allows; each check corrects what
+//! the last got wrong. Only voltage is adjusted, as it's the one setting
+//! every board can change while hashing.
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use tokio::sync::{mpsc, oneshot};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    backplane::{BackplaneCommand, BoardStatus},
+    board::{task::BoardHealth, BoardError, OperatingPoint},
+    tracing::prelude::*,
+};
+
+/// How often board power is checked against the budget. Longer than the
+/// telemetry interval, so each check sees the effect of the last.
+pub const POWER_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Fraction of the limit kept free before throttled boards are raised, so
+/// they don't bounce between cut and raise.
+const HEADROOM: f32 = 0.05;
+
+/// Smallest voltage change worth making.
+const MIN_VOLTAGE_STEP: f32 = 0.005;
+
+/// Excess too small to spread any further.
+const MIN_CUT_WATTS: f32 = 0.01;
+
+/// How long to wait for the backplane to answer.
+const BACKPLANE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A limit on the combined power draw of all boards.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PowerBudget {
+    /// Most the boards may draw together, in watts
+    pub limit_watts: f32,
+    /// Priority weights by board ID; boards not listed weigh 1
+    pub weights: BTreeMap<String, f32>,
+}
+
+/// A running board's draw, as the manager sees it.
+#[derive(Debug, Clone, PartialEq)]
+struct BoardPower {
+    id: String,
+    watts: f32,
+    volts: f32,
+    weight: f32,
+    /// Running below its own voltage because of the budget
+    throttled: bool,
+}
+
+/// Keeps the boards within a power budget.
+pub struct PowerManager {
+    budget: PowerBudget,
+    backplane_tx: mpsc::Sender<BackplaneCommand>,
+    /// Voltage each throttled board ran at before it was first cut
+    nominal_volts: HashMap<String, f32>,
+}
+
+impl PowerBudget {
+    /// Weight of the board with `id`.
+    pub fn weight(&self, id: &str) -> f32 {
+        self.weights.get(id).copied().unwrap_or(1.0)
+    }
+}
+
+impl PowerManager {
+    /// Create a manager that controls boards through the backplane.
+    pub fn new(budget: PowerBudget, backplane_tx: mpsc::Sender<BackplaneCommand>) -> Self {
+        Self {
+            budget,
+            backplane_tx,
+            nominal_volts: HashMap::new(),
+        }
+    }
+
+    /// Check the budget every [`POWER_CHECK_INTERVAL`] until shutdown.
+    pub async fn run(mut self, shutdown: CancellationToken) -> anyhow::Result<()> {
+        info!(
+            limit_watts = self.budget.limit_watts,
+            "Power budget enabled"
+        );
+        let mut ticker = tokio::time::interval(POWER_CHECK_INTERVAL);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = shutdown.cancelled() => return Ok(()),
+            }
+
+            match self.list_boards().await {
+                Some(boards) => self.check(boards).await,
+                None => warn!("Backplane didn't list boards; power budget not checked"),
+            }
+        }
+    }
+
+    /// Bring the boards' draw back within the budget, or let throttled
+    /// boards up if there's room.
+    async fn check(&mut self, statuses: Vec<BoardStatus>) {
+        let present: Vec<&str> = statuses.iter().map(|s| s.id.as_str()).collect();
+        self.nominal_volts
+            .retain(|id, _| present.contains(&id.as_str()));
+
+        let boards: Vec<BoardPower> = statuses
+            .iter()
+            .filter(|s| s.health == BoardHealth::Running)
+            .filter_map(|s| {
+                let telemetry = s.telemetry.as_ref()?;
+                Some(BoardPower {
+                    id: s.id.clone(),
+                    watts: telemetry.power_watts?,
+                    volts: telemetry.core_voltage?,
+                    weight: self.budget.weight(&s.id),
+                    throttled: self.nominal_volts.contains_key(&s.id),
+                })
+            })
+            .collect();
+
+        let total: f32 = boards.iter().map(|b| b.watts).sum();
+        let Some(targets) = allocate(self.budget.limit_watts, &boards) else {
+            trace!(total_watts = total, "Within power budget");
+            return;
+        };
+        if total > self.budget.limit_watts {
+            info!(
+                total_watts = total,
+                limit_watts = self.budget.limit_watts,
+                "Over power budget; throttling boards"
+            );
+        } else {
+            debug!(total_watts = total, "Room in power budget; raising boards");
+        }
+
+        for (board, target) in boards.iter().zip(targets) {
+            let nominal = self.nominal_volts.get(&board.id).copied();
+            let Some(volts) = voltage_for(board, target, nominal) else {
+                continue;
+            };
+            if volts < board.volts {
+                self.nominal_volts
+                    .entry(board.id.clone())
+                    .or_insert(board.volts);
+            }
+            match self.set_voltage(&board.id, volts).await {
+                Ok(volts) => {
+                    debug!(
+                        serial = %board.id,
+                        watts = board.watts,
+                        target_watts = target,
+                        volts,
+                        "Board voltage set for power budget"
+                    );
+                    if nominal.is_some_and(|nominal| volts >= nominal) {
+                        self.nominal_volts.remove(&board.id);
+                        info!(serial = %board.id, "Board no longer throttled for power.");
+                    }
+                }
+                Err(e) => warn!(serial = %board.id, error = %e, "Failed to set board voltage"),
+            }
+        }
+    }
+
+    async fn list_boards(&self) -> Option<Vec<BoardStatus>> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.backplane_tx
+            .send(BackplaneCommand::ListBoards { reply_tx })
+            .await
+            .ok()?;
+        tokio::time::timeout(BACKPLANE_TIMEOUT, reply_rx)
+            .await
+            .ok()?
+            .ok()
+    }
+
+    /// Set a board's core voltage, clamped to its range if it refuses the
+    /// request. Returns the voltage set.
+    async fn set_voltage(&self, id: &str, volts: f32) -> Result<f32, String> {
+        match self.set_operating_point(id, volts).await? {
+            Err(BoardError::VoltageOutOfRange { min, max, .. }) => {
+                let clamped = volts.clamp(min, max);
+                self.set_operating_point(id, clamped)
+                    .await?
+                    .map_err(|e| e.to_string())?;
+                Ok(clamped)
+            }
+            Err(e) => Err(e.to_string()),
+            Ok(()) => Ok(volts),
+        }
+    }
+
+    async fn set_operating_point(
+        &self,
+        id: &str,
+        volts: f32,
+    ) -> Result<Result<(), BoardError>, String> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.backplane_tx
+            .send(BackplaneCommand::SetBoardOperatingPoint {
+                id: id.to_string(),
+                point: OperatingPoint {
+                    frequency_mhz: None,
+                    voltage: Some(volts),
+                },
+                reply_tx,
+            })
+            .await
+            .map_err(|_| "backplane is not running".to_string())?;
+
+        match tokio::time::timeout(BACKPLANE_TIMEOUT, reply_rx).await {
+            Ok(Ok(Some(result))) => Ok(result),
+            Ok(Ok(None)) => Err("board is gone".into()),
+            Ok(Err(_)) => Err("backplane dropped the request".into()),
+            Err(_) => Err("timed out".into()),
+        }
+    }
+}
+
+/// Watts each board should draw, or None if no change is needed.
+///
+/// Over the limit, the excess is cut from each board in proportion to its
+/// draw over its weight. Below the limit less [`HEADROOM`], throttled boards
+/// share the spare in proportion to their weight.
+fn allocate(limit: f32, boards: &[BoardPower]) -> Option<Vec<f32>> {
+    let total: f32 = boards.iter().map(|b| b.watts).sum();
+    let mut targets: Vec<f32> = boards.iter().map(|b| b.watts).collect();
+
+    if total > limit {
+        // Boards cut to nothing can't give more, so the rest of the excess
+        // is spread over the others
+        let mut excess = total - limit;
+        let mut active: Vec<usize> = (0..boards.len()).collect();
+        while excess > MIN_CUT_WATTS && !active.is_empty() {
+            let shares: f32 = active.iter().map(|&i| targets[i] / boards[i].weight).sum();
+            if shares <= 0.0 {
+                break;
+            }
+            let mut taken = 0.0;
+            active.retain(|&i| {
+                let cut = excess * (targets[i] / boards[i].weight) / shares;
+                let cut = cut.min(targets[i]);
+                targets[i] -= cut;
+                taken += cut;
+                targets[i] > 0.0
+            });
+            excess -= taken;
+        }
+        return Some(targets);
+    }
+
+    let ceiling = limit * (1.0 - HEADROOM);
+    let throttled_weight: f32 = boards
+        .iter()
+        .filter(|b| b.throttled)
+        .map(|b| b.weight)
+        .sum();
+    if total >= ceiling || throttled_weight <= 0.0 {
+        return None;
+    }
+    let spare = ceiling - total;
+    for (board, target) in boards.iter().zip(&mut targets) {
+        if board.throttled {
+            *target += spare * board.weight / throttled_weight;
+        }
+    }
+    Some(targets)
+}
+
+/// Core voltage for `board` to draw `target` watts, capped at `nominal`
+/// when raising. None if the change is too small to bother with.
+fn voltage_for(board: &BoardPower, target: f32, nominal: Option<f32>) -> Option<f32> {
+    if board.watts <= 0.0 {
+        return None;
+    }
+    let mut volts = board.volts * (target / board.watts).sqrt();
+    if volts > board.volts {
+        volts = volts.min(nominal.unwrap_or(board.volts));
+    }
+    ((volts - board.volts).abs() >= MIN_VOLTAGE_STEP).then_some(volts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::TelemetrySnapshot;
+
+    fn board(id: &str, watts: f32, weight: f32, throttled: bool) -> BoardPower {
+        BoardPower {
+            id: id.into(),
+            watts,
+            volts: 1.2,
+            weight,
+            throttled,
+        }
+    }
+
+    #[test]
+    fn test_cut_weighted_by_priority() {
+        let boards = [board("a", 60.0, 2.0, false), board("b", 60.0, 1.0, false)];
+        let targets = allocate(90.0, &boards).unwrap();
+
+        // b gives twice what a does
+        assert!((targets[0] - 50.0).abs() < 0.01);
+        assert!((targets[1] - 40.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_cut_spills_over_when_board_exhausted() {
+        let boards = [
+            board("a", 10.0, 1.0, false),
+            board("b", 100.0, 100.0, false),
+        ];
+        let targets = allocate(60.0, &boards).unwrap();
+
+        assert_eq!(targets[0], 0.0);
+        assert!((targets.iter().sum::<f32>() - 60.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_raise_only_throttled_boards() {
+        let boards = [board("a", 30.0, 1.0, true), board("b", 30.0, 1.0, false)];
+        let targets = allocate(100.0, &boards).unwrap();
+        assert!((targets[0] - 65.0).abs() < 0.01);
+        assert_eq!(targets[1], 30.0);
+
+        // Nothing to do within the headroom, or with nobody throttled
+        assert_eq!(allocate(62.0, &boards), None);
+        let unthrottled = [board("a", 30.0, 1.0, false)];
+        assert_eq!(allocate(100.0, &unthrottled), None);
+    }
+
+    #[test]
+    fn test_voltage_for_target() {
+        let b = board("a", 40.0, 1.0, true);
+        let volts = voltage_for(&b, 10.0, Some(1.3)).unwrap();
+        assert!((volts - 0.6).abs() < 0.001);
+
+        // Raising stops at the voltage the board had before throttling
+        assert_eq!(voltage_for(&b, 160.0, Some(1.3)), Some(1.3));
+        assert_eq!(voltage_for(&b, 40.1, Some(1.3)), None);
+    }
+
+    #[tokio::test]
+    async fn test_over_budget_lowers_voltage() {
+        let (backplane_tx, mut backplane_rx) = mpsc::channel(10);
+        let budget = PowerBudget {
+            limit_watts: 20.0,
+            weights: BTreeMap::new(),
+        };
+        let mut manager = PowerManager::new(budget, backplane_tx);
+
+        let backplane = tokio::spawn(async move {
+            let Some(BackplaneCommand::SetBoardOperatingPoint {
+                id,
+                point,
+                reply_tx,
+            }) = backplane_rx.recv().await
+            else {
+                panic!("expected a retune");
+            };
+            assert_eq!(id, "1a2b3c");
+            // Clamp to the board's range
+            reply_tx
+                .send(Some(point.check_voltage(Some(
+                    crate::board::VoltageRange { min: 1.0, max: 1.3 },
+                ))))
+                .unwrap();
+            let Some(BackplaneCommand::SetBoardOperatingPoint {
+                point, reply_tx, ..
+            }) = backplane_rx.recv().await
+            else {
+                panic!("expected a clamped retune");
+            };
+            reply_tx.send(Some(Ok(()))).unwrap();
+            point.voltage.unwrap()
+        });
+
+        let status = BoardStatus {
+            id: "1a2b3c".into(),
+            model: "Test".into(),
+            health: BoardHealth::Running,
+            generation: 1,
+            error: None,
+            power_limited: None,
+            telemetry: Some(TelemetrySnapshot {
+                power_watts: Some(40.0),
+                core_voltage: Some(1.2),
+                ..TelemetrySnapshot::new()
+            }),
+        };
+        manager.check(vec![status]).await;
+
+        assert_eq!(backplane.await.unwrap(), 1.0);
+        assert_eq!(manager.nominal_volts.get("1a2b3c"), Some(&1.2));
+    }
+}
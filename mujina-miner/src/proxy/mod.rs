@@ -0,0 +1,172 @@
+//! Stratum v1 proxy for downstream miners.
+//!
+//! With a `[proxy]` section in the config, the daemon accepts Stratum v1
+//! connections from other miners on the local network and mines their
+//! hashrate under its own pool connection, so a farm of small miners shows
+//! up at the pool as one worker.
+//!
+//! Each downstream miner that subscribes and authorizes becomes a hash
+//! thread like any board's, registered with the scheduler on the same
+//! channel the backplane uses. The scheduler hands it tasks with a slice of
+//! the pool's extranonce2 space, as it does for chips, so downstream miners
+//! never duplicate each other's or the boards' work; and the shares they
+//! find come back through the scheduler, which checks and submits them as
+//! it does any other.
+//!
+//! A downstream miner is told it has no extranonce1 and
+//! [`ProxyConfig::extranonce2_size`] bytes of extranonce2. For each task,
+//! the proxy picks a block of the task's slice that size's values cover
+//! exactly, and moves the pool's extranonce1 and the fixed upper bytes of
+//! extranonce2 into the coinbase parts it sends. The task's share target
+//! becomes the downstream difficulty.
+//!
+//! Per-downstream statistics (worker, shares, hashrate estimated from
+//! them) are kept in [`ProxyStats`] for the API.
+
+mod session;
+mod thread;
+
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use ::tracing::{info_span, Instrument};
+use anyhow::Context;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    asic::hash_thread::HashThread, config::ProxyConfig, tracing::prelude::*, types::HashRate,
+};
+use session::Session;
+
+/// What the proxy knows about one downstream miner.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DownstreamStats {
+    /// Connection ID, unique for the life of the daemon
+    pub id: u64,
+    /// Where the miner connected from
+    pub address: SocketAddr,
+    /// Worker name it authorized as
+    pub worker: Option<String>,
+    /// User agent it subscribed with
+    pub user_agent: Option<String>,
+    /// When it connected
+    pub connected_at: SystemTime,
+    /// Share difficulty last sent to it
+    pub difficulty: Option<f64>,
+    /// Hashrate estimated from its shares
+    pub hashrate: HashRate,
+    /// Shares accepted and passed on to the scheduler
+    pub accepted: u64,
+    /// Shares refused (stale, duplicate, or short of the difficulty)
+    pub rejected: u64,
+}
+
+/// Statistics of the connected downstream miners.
+///
+/// Cheap to clone; clones share the same state.
+#[derive(Debug, Clone, Default)]
+pub struct ProxyStats {
+    downstreams: Arc<Mutex<BTreeMap<u64, DownstreamStats>>>,
+}
+
+/// Accepts downstream miners and runs a session for each.
+#[derive(Clone)]
+pub struct ProxyServer {
+    config: ProxyConfig,
+    thread_tx: mpsc::Sender<Box<dyn HashThread>>,
+    stats: ProxyStats,
+    shutdown: CancellationToken,
+}
+
+impl ProxyStats {
+    /// The connected downstream miners, in order of connection.
+    pub fn snapshot(&self) -> Vec<DownstreamStats> {
+        self.downstreams.lock().unwrap().values().cloned().collect()
+    }
+
+    fn insert(&self, stats: DownstreamStats) {
+        self.downstreams.lock().unwrap().insert(stats.id, stats);
+    }
+
+    fn update(&self, id: u64, f: impl FnOnce(&mut DownstreamStats)) {
+        if let Some(stats) = self.downstreams.lock().unwrap().get_mut(&id) {
+            f(stats);
+        }
+    }
+
+    fn remove(&self, id: u64) {
+        self.downstreams.lock().unwrap().remove(&id);
+    }
+}
+
+impl ProxyServer {
+    /// Create a server registering downstream miners as hash threads on
+    /// `thread_tx`.
+    pub fn new(
+        config: ProxyConfig,
+        thread_tx: mpsc::Sender<Box<dyn HashThread>>,
+        shutdown: CancellationToken,
+    ) -> Self {
+        Self {
+            config,
+            thread_tx,
+            stats: ProxyStats::default(),
+            shutdown,
+        }
+    }
+
+    /// Statistics of the connected downstream miners.
+    pub fn stats(&self) -> ProxyStats {
+        self.stats.clone()
+    }
+
+    /// Accept downstream miners until shutdown, then wait for their
+    /// sessions to close.
+    pub async fn run(self) -> anyhow::Result<()> {
+        let listener = TcpListener::bind(&self.config.listen)
+            .await
+            .with_context(|| format!("binding proxy to {}", self.config.listen))?;
+        info!(listen = %self.config.listen, "Proxy accepting downstream miners");
+
+        let mut sessions = JoinSet::new();
+        let mut next_id = 0;
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => {
+                    let (stream, address) = match accepted {
+                        Ok(accepted) => accepted,
+                        Err(e) => {
+                            warn!(error = %e, "Failed to accept downstream miner");
+                            continue;
+                        }
+                    };
+                    next_id += 1;
+                    debug!(%address, id = next_id, "Downstream miner connected");
+                    let session = Session::new(
+                        next_id,
+                        stream,
+                        address,
+                        self.config.extranonce2_size,
+                        self.thread_tx.clone(),
+                        self.stats.clone(),
+                        self.shutdown.clone(),
+                    );
+                    sessions.spawn(session.run().instrument(info_span!("downstream", %address)));
+                }
+
+                // Reap finished sessions as they go
+                Some(_) = sessions.join_next() => {}
+
+                _ = self.shutdown.cancelled() => break,
+            }
+        }
+
+        while sessions.join_next().await.is_some() {}
+        Ok(())
+    }
+}
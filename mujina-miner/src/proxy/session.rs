@@ -0,0 +1,710 @@
+//! One downstream miner's Stratum session.
+//!
+//! The session speaks the server side of Stratum v1: it answers the
+//! miner's configure, subscribe, authorize and submit requests, and sends it
+//! a job for each task the scheduler gives the miner's hash thread. Shares
+//! the miner submits are mapped back onto the task they were mined from,
+//! checked, and passed to the scheduler as a chip's would be.
+
+use std::collections::{HashSet, VecDeque};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use bitcoin::block::Version;
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+use super::thread::{DownstreamThread, Work};
+use super::{DownstreamStats, ProxyStats};
+use crate::{
+    asic::hash_thread::{
+        HashTask, HashThread, HashThreadCapabilities, HashThreadEvent, HashThreadStatus,
+    },
+    job_source::{Extranonce2, MerkleRootKind},
+    stratum_v1::{Connection, JobNotification, JsonRpcMessage, StratumResult, SubmitParams},
+    tracing::prelude::*,
+    types::{Difficulty, HashRate},
+};
+
+/// Version bits a miner may roll (BIP320).
+const VERSION_ROLLING_MASK: u32 = 0x1fff_e000;
+
+/// Hashrate assumed for a miner until its shares say otherwise.
+const INITIAL_HASHRATE: HashRate = HashRate(1_000_000_000_000);
+
+/// Shares must span this long before they replace the assumed hashrate.
+const HASHRATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Jobs kept for shares still to come in against them.
+const MAX_JOBS: usize = 8;
+
+// Error codes as pools commonly send them
+const ERR_OTHER: i64 = 20;
+const ERR_JOB_NOT_FOUND: i64 = 21;
+const ERR_DUPLICATE: i64 = 22;
+const ERR_LOW_DIFFICULTY: i64 = 23;
+const ERR_UNAUTHORIZED: i64 = 24;
+const ERR_NOT_SUBSCRIBED: i64 = 25;
+
+/// Result of a request, or its Stratum error code and message.
+type Reply = Result<Value, (i64, &'static str)>;
+
+/// A job sent to the miner and the task it came from.
+struct DownstreamJob {
+    id: String,
+    task: HashTask,
+    /// Extranonce2 of the task that the miner's extranonce2 of zero stands
+    /// for
+    base: u64,
+    /// Shares already accepted, to refuse repeats
+    submitted: HashSet<(Vec<u8>, u32, u32, Option<u32>)>,
+}
+
+/// The miner's hash thread, as seen from the session.
+struct Registration {
+    work_rx: mpsc::Receiver<Work>,
+    /// Held so the scheduler keeps the thread until the session ends
+    _event_tx: mpsc::Sender<HashThreadEvent>,
+    status: Arc<Mutex<HashThreadStatus>>,
+}
+
+pub(super) struct Session {
+    id: u64,
+    conn: Connection,
+    address: SocketAddr,
+    extranonce2_size: u8,
+    thread_tx: mpsc::Sender<Box<dyn HashThread>>,
+    stats: ProxyStats,
+    shutdown: CancellationToken,
+
+    subscribed: bool,
+    worker: Option<String>,
+    /// Version bits granted through mining.configure
+    version_mask: Option<u32>,
+    /// Version mask the miner was last told of
+    sent_mask: Option<u32>,
+    difficulty: Option<f64>,
+    registration: Option<Registration>,
+    jobs: VecDeque<DownstreamJob>,
+    next_job_id: u64,
+    /// Work shown by accepted shares since registering, in hashes
+    hashes: f64,
+    registered_at: Instant,
+}
+
+impl Session {
+    pub(super) fn new(
+        id: u64,
+        stream: TcpStream,
+        address: SocketAddr,
+        extranonce2_size: u8,
+        thread_tx: mpsc::Sender<Box<dyn HashThread>>,
+        stats: ProxyStats,
+        shutdown: CancellationToken,
+    ) -> Self {
+        stats.insert(DownstreamStats {
+            id,
+            address,
+            worker: None,
+            user_agent: None,
+            connected_at: SystemTime::now(),
+            difficulty: None,
+            hashrate: HashRate(0),
+            accepted: 0,
+            rejected: 0,
+        });
+
+        Self {
+            id,
+            conn: Connection::new(stream),
+            address,
+            extranonce2_size,
+            thread_tx,
+            stats,
+            shutdown,
+            subscribed: false,
+            worker: None,
+            version_mask: None,
+            sent_mask: None,
+            difficulty: None,
+            registration: None,
+            jobs: VecDeque::new(),
+            next_job_id: 0,
+            hashes: 0.0,
+            registered_at: Instant::now(),
+        }
+    }
+
+    /// Serve the miner until it disconnects or the daemon shuts down.
+    pub(super) async fn run(mut self) {
+        loop {
+            tokio::select! {
+                message = self.conn.read_message() => match message {
+                    Ok(Some(message)) => {
+                        if let Err(e) = self.handle_message(message).await {
+                            debug!(error = %e, "Failed to answer downstream miner");
+                            break;
+                        }
+                    }
+                    Ok(None) => {
+                        debug!("Downstream miner disconnected");
+                        break;
+                    }
+                    Err(e) => {
+                        debug!(error = %e, "Downstream connection failed");
+                        break;
+                    }
+                },
+
+                Some(work) = next_work(&mut self.registration) => {
+                    if let Err(e) = self.handle_work(work).await {
+                        debug!(error = %e, "Failed to send work to downstream miner");
+                        break;
+                    }
+                }
+
+                _ = self.shutdown.cancelled() => break,
+            }
+        }
+
+        self.stats.remove(self.id);
+        if let Some(worker) = &self.worker {
+            info!(%worker, "Downstream miner left");
+        }
+    }
+
+    async fn handle_message(&mut self, message: JsonRpcMessage) -> StratumResult<()> {
+        // The proxy asks the miner nothing, so there are no responses to
+        // expect
+        let JsonRpcMessage::Request { id, method, params } = message else {
+            return Ok(());
+        };
+
+        let reply = match method.as_str() {
+            "mining.configure" => Ok(self.configure(&params)),
+            "mining.subscribe" => Ok(self.subscribe(&params)),
+            "mining.authorize" => self.authorize(&params).await,
+            "mining.submit" => self.submit(&params).await,
+            // Difficulty follows the scheduler's share target, and the
+            // extranonce never changes, so both are acknowledged and ignored
+            "mining.suggest_difficulty" | "mining.extranonce.subscribe" => Ok(Value::Bool(true)),
+            _ => {
+                debug!(%method, "Unsupported method from downstream miner");
+                Err((ERR_OTHER, "Unsupported method"))
+            }
+        };
+
+        match id {
+            Some(id) => self.reply(id, reply).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn reply(&mut self, id: u64, reply: Reply) -> StratumResult<()> {
+        let message = match reply {
+            Ok(result) => JsonRpcMessage::Response {
+                id,
+                result: Some(result),
+                error: Some(Value::Null),
+            },
+            Err((code, message)) => JsonRpcMessage::Response {
+                id,
+                result: Some(Value::Null),
+                error: Some(json!([code, message, null])),
+            },
+        };
+        self.conn.write_message(&message).await
+    }
+
+    async fn notify(&mut self, method: &str, params: Value) -> StratumResult<()> {
+        self.conn
+            .write_message(&JsonRpcMessage::notification(method, params))
+            .await
+    }
+
+    fn configure(&mut self, params: &Value) -> Value {
+        let extensions = params.get(0).and_then(Value::as_array);
+        let mut result = serde_json::Map::new();
+        for extension in extensions.into_iter().flatten().filter_map(Value::as_str) {
+            if extension == "version-rolling" {
+                let requested = params
+                    .get(1)
+                    .and_then(|options| options.get("version-rolling.mask"))
+                    .and_then(Value::as_str)
+                    .and_then(|mask| u32::from_str_radix(mask, 16).ok())
+                    .unwrap_or(VERSION_ROLLING_MASK);
+                let mask = requested & VERSION_ROLLING_MASK;
+                self.version_mask = Some(mask);
+                self.sent_mask = Some(mask);
+                result.insert(extension.into(), Value::Bool(true));
+                result.insert(
+                    "version-rolling.mask".into(),
+                    Value::String(format!("{:08x}", mask)),
+                );
+            } else {
+                result.insert(extension.into(), Value::Bool(false));
+            }
+        }
+        Value::Object(result)
+    }
+
+    fn subscribe(&mut self, params: &Value) -> Value {
+        let user_agent = params.get(0).and_then(Value::as_str).map(String::from);
+        self.subscribed = true;
+        self.stats
+            .update(self.id, |stats| stats.user_agent = user_agent);
+
+        // No extranonce1: the pool's goes into the coinbase1 of every job
+        let subscription = format!("{:x}", self.id);
+        json!([
+            [
+                ["mining.set_difficulty", subscription],
+                ["mining.notify", subscription]
+            ],
+            "",
+            self.extranonce2_size
+        ])
+    }
+
+    async fn authorize(&mut self, params: &Value) -> Reply {
+        if !self.subscribed {
+            return Err((ERR_NOT_SUBSCRIBED, "Not subscribed"));
+        }
+        let worker = params
+            .get(0)
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+
+        // Later workers on the same connection share its one thread
+        if self.registration.is_none() {
+            self.register(&worker).await?;
+            self.stats
+                .update(self.id, |stats| stats.worker = Some(worker.clone()));
+            info!(%worker, "Downstream miner joined");
+            self.worker = Some(worker);
+        }
+        Ok(Value::Bool(true))
+    }
+
+    /// Register the miner with the scheduler as a hash thread.
+    async fn register(&mut self, worker: &str) -> Result<(), (i64, &'static str)> {
+        let (work_tx, work_rx) = mpsc::channel(MAX_JOBS);
+        let (event_tx, event_rx) = mpsc::channel(1);
+        let status = Arc::new(Mutex::new(HashThreadStatus {
+            hashrate: INITIAL_HASHRATE,
+            ..Default::default()
+        }));
+
+        let mut capabilities =
+            HashThreadCapabilities::new(INITIAL_HASHRATE).with_max_pending_jobs(MAX_JOBS);
+        if self.version_mask.is_some_and(|mask| mask != 0) {
+            capabilities = capabilities.with_version_rolling();
+        }
+        let thread = DownstreamThread::new(
+            format!("{} ({})", worker, self.address),
+            capabilities,
+            work_tx,
+            event_rx,
+            status.clone(),
+        );
+        if self.thread_tx.send(Box::new(thread)).await.is_err() {
+            return Err((ERR_OTHER, "Not mining"));
+        }
+
+        self.registration = Some(Registration {
+            work_rx,
+            _event_tx: event_tx,
+            status,
+        });
+        self.registered_at = Instant::now();
+        Ok(())
+    }
+
+    async fn handle_work(&mut self, work: Work) -> StratumResult<()> {
+        let (task, clean) = match work {
+            Work::Task { task, clean } => (task, clean),
+            Work::Idle => {
+                self.jobs.clear();
+                self.set_active(false);
+                return Ok(());
+            }
+        };
+        let Some((notification, base)) = self.job_for(&task, clean) else {
+            return Ok(());
+        };
+
+        let difficulty = Difficulty::from_target(task.share_target).as_f64();
+        if self.difficulty != Some(difficulty) {
+            self.notify("mining.set_difficulty", json!([difficulty]))
+                .await?;
+            self.difficulty = Some(difficulty);
+            self.stats
+                .update(self.id, |stats| stats.difficulty = Some(difficulty));
+        }
+
+        if let Some(granted) = self.version_mask {
+            let mask = granted & template_mask(&task);
+            if self.sent_mask != Some(mask) {
+                self.notify("mining.set_version_mask", json!([format!("{:08x}", mask)]))
+                    .await?;
+                self.sent_mask = Some(mask);
+            }
+        }
+
+        if clean {
+            self.jobs.clear();
+        }
+        if self.jobs.len() == MAX_JOBS {
+            self.jobs.pop_front();
+        }
+        let id = notification.job_id.clone();
+        self.notify(
+            "mining.notify",
+            Value::Array(notification.to_stratum_params()),
+        )
+        .await?;
+        self.jobs.push_back(DownstreamJob {
+            id,
+            task,
+            base,
+            submitted: HashSet::new(),
+        });
+        self.set_active(true);
+        Ok(())
+    }
+
+    /// The job the miner should mine for `task`, and the task extranonce2
+    /// its extranonce2 values start from.
+    ///
+    /// The miner's values cover a whole block of the task's slice, aligned
+    /// so the bytes above the miner's are the same throughout and can move
+    /// into coinbase2. Returns None, with a warning, if the task can't be
+    /// served that way.
+    fn job_for(&mut self, task: &HashTask, clean: bool) -> Option<(JobNotification, u64)> {
+        let MerkleRootKind::Computed(merkle) = &task.template.merkle_root else {
+            warn!("Task has no coinbase to share with downstream miner");
+            return None;
+        };
+        let range = task.en2_range.as_ref()?;
+        let size = self.extranonce2_size;
+        if size >= range.size {
+            warn!(
+                pool = range.size,
+                downstream = size,
+                "Pool's extranonce2 too small to share with downstream miner"
+            );
+            return None;
+        }
+
+        let block = 1u64 << (8 * u32::from(size));
+        let base = range.min.div_ceil(block).checked_mul(block)?;
+        if base.checked_add(block - 1)? > range.max {
+            warn!(
+                min = range.min,
+                max = range.max,
+                "Extranonce2 slice too small for downstream miner"
+            );
+            return None;
+        }
+
+        let mut coinbase1 = merkle.coinbase1().to_vec();
+        coinbase1.extend_from_slice(merkle.extranonce1());
+        let mut coinbase2 = base.to_le_bytes()[size as usize..range.size as usize].to_vec();
+        coinbase2.extend_from_slice(merkle.coinbase2());
+
+        let job_id = format!("{:x}", self.next_job_id);
+        self.next_job_id += 1;
+        let notification = JobNotification {
+            job_id,
+            prev_hash: task.template.prev_blockhash,
+            coinbase1,
+            coinbase2,
+            merkle_branches: merkle.merkle_branches().to_vec(),
+            version: task.template.version.base(),
+            nbits: task.template.bits,
+            ntime: task.ntime,
+            clean_jobs: clean,
+        };
+        Some((notification, base))
+    }
+
+    async fn submit(&mut self, params: &Value) -> Reply {
+        if self.registration.is_none() {
+            return Err((ERR_UNAUTHORIZED, "Unauthorized worker"));
+        }
+        let submit = params
+            .as_array()
+            .and_then(|params| SubmitParams::from_stratum_params(params).ok())
+            .ok_or((ERR_OTHER, "Malformed submit"))?;
+
+        let result = self.check_share(&submit).await;
+        match &result {
+            Ok(difficulty) => {
+                self.hashes += difficulty * 2f64.powi(32);
+                let hashrate = self.estimate_hashrate();
+                self.stats.update(self.id, |stats| {
+                    stats.accepted += 1;
+                    stats.hashrate = hashrate;
+                });
+                if let Some(registration) = &self.registration {
+                    let mut status = registration.status.lock().unwrap();
+                    status.chip_shares_found += 1;
+                    status.hashrate = hashrate;
+                }
+            }
+            Err((_, reason)) => {
+                debug!(job = %submit.job_id, reason, "Refused share from downstream miner");
+                self.stats.update(self.id, |stats| stats.rejected += 1);
+            }
+        }
+        result.map(|_| Value::Bool(true))
+    }
+
+    /// Check a submitted share and pass it to the scheduler, returning the
+    /// difficulty it was mined at.
+    async fn check_share(&mut self, submit: &SubmitParams) -> Result<f64, (i64, &'static str)> {
+        let size = usize::from(self.extranonce2_size);
+        let granted = self.version_mask.unwrap_or(0);
+        let job = self
+            .jobs
+            .iter_mut()
+            .find(|job| job.id == submit.job_id)
+            .ok_or((ERR_JOB_NOT_FOUND, "Job not found"))?;
+        if submit.extranonce2.len() != size {
+            return Err((ERR_OTHER, "Wrong extranonce2 size"));
+        }
+        let key = (
+            submit.extranonce2.clone(),
+            submit.ntime,
+            submit.nonce,
+            submit.version_bits,
+        );
+        if job.submitted.contains(&key) {
+            return Err((ERR_DUPLICATE, "Duplicate share"));
+        }
+
+        let mut low = [0u8; 8];
+        low[..size].copy_from_slice(&submit.extranonce2);
+        let mut task = job.task.clone();
+        let en2_size = task.en2_range.as_ref().map_or(0, |range| range.size);
+        task.en2 = Some(
+            Extranonce2::new(job.base + u64::from_le_bytes(low), en2_size)
+                .map_err(|_| (ERR_OTHER, "Bad extranonce2"))?,
+        );
+
+        let base = task.template.version.base().to_consensus() as u32;
+        let mask = granted & template_mask(&task);
+        let version = match submit.version_bits {
+            Some(bits) => (base & !mask) | (bits & mask),
+            None => base,
+        };
+        let header = task
+            .header(
+                Version::from_consensus(version as i32),
+                submit.ntime,
+                submit.nonce,
+            )
+            .ok_or((ERR_OTHER, "Bad job"))?;
+        let share = task
+            .verify_nonce(&header)
+            .map_err(|_| (ERR_LOW_DIFFICULTY, "Low difficulty share"))?;
+
+        job.submitted.insert(key);
+        // The scheduler closes a task's channel once it has moved on
+        task.share_tx
+            .send(share)
+            .await
+            .map_err(|_| (ERR_JOB_NOT_FOUND, "Stale job"))?;
+        Ok(Difficulty::from_target(task.share_target).as_f64())
+    }
+
+    /// Hashrate shown by the shares accepted so far, or the initial
+    /// assumption until they span long enough to say.
+    fn estimate_hashrate(&self) -> HashRate {
+        let elapsed = self.registered_at.elapsed();
+        if elapsed < HASHRATE_WINDOW {
+            return INITIAL_HASHRATE;
+        }
+        HashRate((self.hashes / elapsed.as_secs_f64()) as u64)
+    }
+
+    fn set_active(&self, active: bool) {
+        if let Some(registration) = &self.registration {
+            registration.status.lock().unwrap().is_active = active;
+        }
+    }
+}
+
+/// The next work for a registered miner; never ready before registration.
+async fn next_work(registration: &mut Option<Registration>) -> Option<Work> {
+    match registration {
+        Some(registration) => registration.work_rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Version bits the task's template lets miners roll.
+fn template_mask(task: &HashTask) -> u32 {
+    let mask = task.template.version.gp_bits_mask();
+    u32::from(u16::from_be_bytes(*mask.as_bytes())) << 13
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job_source::{dummy, test_blocks::block_881423, Extranonce2Range};
+    use tokio::net::TcpListener;
+
+    /// Send a request and return the result or error of its response.
+    async fn request(conn: &mut Connection, id: u64, method: &str, params: Value) -> Reply {
+        conn.write_message(&JsonRpcMessage::request(id, method, params))
+            .await
+            .unwrap();
+        match conn.read_message().await.unwrap().unwrap() {
+            JsonRpcMessage::Response {
+                id: reply_id,
+                result,
+                error,
+            } => {
+                assert_eq!(reply_id, id);
+                match error {
+                    Some(error) => Err((error[0].as_i64().unwrap(), "")),
+                    None => Ok(result.unwrap()),
+                }
+            }
+            other => panic!("expected response, got {:?}", other),
+        }
+    }
+
+    /// Read the next notification, returning its method and params.
+    async fn notification(conn: &mut Connection) -> (String, Value) {
+        match conn.read_message().await.unwrap().unwrap() {
+            JsonRpcMessage::Request {
+                id: None,
+                method,
+                params,
+            } => (method, params),
+            other => panic!("expected notification, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_serves_slice_and_passes_shares_on() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut miner = Connection::new(
+            TcpStream::connect(listener.local_addr().unwrap())
+                .await
+                .unwrap(),
+        );
+        let (stream, address) = listener.accept().await.unwrap();
+        let (thread_tx, mut thread_rx) = mpsc::channel(1);
+        let stats = ProxyStats::default();
+        let shutdown = CancellationToken::new();
+        let session = tokio::spawn(
+            Session::new(
+                1,
+                stream,
+                address,
+                1,
+                thread_tx,
+                stats.clone(),
+                shutdown.clone(),
+            )
+            .run(),
+        );
+
+        let configured = request(
+            &mut miner,
+            1,
+            "mining.configure",
+            json!([["version-rolling"], {"version-rolling.mask": "ffffffff"}]),
+        )
+        .await
+        .unwrap();
+        assert_eq!(configured["version-rolling.mask"], "1fffe000");
+        let subscribed = request(&mut miner, 2, "mining.subscribe", json!(["test/1.0"]))
+            .await
+            .unwrap();
+        assert_eq!(subscribed[1], "");
+        assert_eq!(subscribed[2], 1);
+        let authorized = request(&mut miner, 3, "mining.authorize", json!(["rig", "x"])).await;
+        assert_eq!(authorized, Ok(Value::Bool(true)));
+
+        let mut thread = thread_rx.recv().await.unwrap();
+        assert!(thread.capabilities().rolls_version);
+
+        // A one-byte slice around the winning extranonce2 of block 881,423
+        let template = Arc::new(dummy::job_template().unwrap());
+        let winning = u64::from(u32::from_le_bytes(
+            block_881423::extranonce2_bytes().try_into().unwrap(),
+        ));
+        let (share_tx, mut share_rx) = mpsc::channel(4);
+        let task = HashTask {
+            template: template.clone(),
+            en2_range: Some(
+                Extranonce2Range::new_range(winning & !0xff, winning | 0xff, 4).unwrap(),
+            ),
+            en2: None,
+            share_target: template.share_target,
+            ntime: block_881423::TIME,
+            share_tx,
+        };
+        thread.replace_task(task).await.unwrap();
+
+        let (method, _) = notification(&mut miner).await;
+        assert_eq!(method, "mining.set_difficulty");
+        let (method, params) = notification(&mut miner).await;
+        assert_eq!(method, "mining.notify");
+        let job = JobNotification::from_stratum_params(params.as_array().unwrap()).unwrap();
+        let mut coinbase1 = block_881423::coinbase1_bytes().to_vec();
+        coinbase1.extend_from_slice(block_881423::extranonce1_bytes());
+        assert_eq!(job.coinbase1, coinbase1);
+        let mut coinbase2 = block_881423::extranonce2_bytes()[1..].to_vec();
+        coinbase2.extend_from_slice(block_881423::coinbase2_bytes());
+        assert_eq!(job.coinbase2, coinbase2);
+        assert!(job.clean_jobs);
+
+        let version_bits = block_881423::VERSION.to_consensus() as u32 & VERSION_ROLLING_MASK;
+        let submit = |nonce: u32| {
+            json!([
+                "rig",
+                job.job_id,
+                hex::encode(&block_881423::extranonce2_bytes()[..1]),
+                format!("{:08x}", block_881423::TIME),
+                format!("{:08x}", nonce),
+                format!("{:08x}", version_bits),
+            ])
+        };
+
+        let accepted = request(&mut miner, 4, "mining.submit", submit(block_881423::NONCE)).await;
+        assert_eq!(accepted, Ok(Value::Bool(true)));
+        let share = share_rx.recv().await.unwrap();
+        assert_eq!(share.hash, *block_881423::BLOCK_HASH);
+        assert_eq!(share.extranonce2.unwrap().value(), winning);
+
+        let repeated = request(&mut miner, 5, "mining.submit", submit(block_881423::NONCE)).await;
+        assert_eq!(repeated, Err((ERR_DUPLICATE, "")));
+        let short = request(
+            &mut miner,
+            6,
+            "mining.submit",
+            submit(block_881423::NONCE ^ 1),
+        )
+        .await;
+        assert_eq!(short, Err((ERR_LOW_DIFFICULTY, "")));
+
+        let downstream = &stats.snapshot()[0];
+        assert_eq!(downstream.worker.as_deref(), Some("rig"));
+        assert_eq!(downstream.user_agent.as_deref(), Some("test/1.0"));
+        assert_eq!((downstream.accepted, downstream.rejected), (1, 2));
+
+        shutdown.cancel();
+        session.await.unwrap();
+        assert!(stats.snapshot().is_empty());
+    }
+}
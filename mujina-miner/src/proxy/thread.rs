@@ -0,0 +1,110 @@
+//! The scheduler's view of a downstream miner.
+//!
+//! A [`DownstreamThread`] passes the tasks the scheduler assigns to the
+//! miner's session, which turns them into Stratum jobs. The session holds
+//! the thread's event sender; when the miner disconnects, the session ends
+//! and the closed channel tells the scheduler the thread is gone.
+
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use tokio::sync::mpsc;
+
+use crate::asic::hash_thread::{
+    HashTask, HashThread, HashThreadCapabilities, HashThreadError, HashThreadEvent,
+    HashThreadStatus,
+};
+
+/// Work for a session to pass on to its miner.
+#[derive(Debug)]
+pub(super) enum Work {
+    /// Mine this task; `clean` if earlier tasks are no longer valid
+    Task { task: HashTask, clean: bool },
+    /// Stop; shares for earlier tasks are refused
+    Idle,
+}
+
+/// A downstream miner as a schedulable hash thread.
+pub(super) struct DownstreamThread {
+    name: String,
+    capabilities: HashThreadCapabilities,
+    work_tx: mpsc::Sender<Work>,
+    event_rx: Option<mpsc::Receiver<HashThreadEvent>>,
+    /// Kept up to date by the session
+    status: Arc<Mutex<HashThreadStatus>>,
+    current: Option<HashTask>,
+}
+
+impl DownstreamThread {
+    pub(super) fn new(
+        name: String,
+        capabilities: HashThreadCapabilities,
+        work_tx: mpsc::Sender<Work>,
+        event_rx: mpsc::Receiver<HashThreadEvent>,
+        status: Arc<Mutex<HashThreadStatus>>,
+    ) -> Self {
+        Self {
+            name,
+            capabilities,
+            work_tx,
+            event_rx: Some(event_rx),
+            status,
+            current: None,
+        }
+    }
+
+    async fn send(&self, work: Work) -> Result<(), HashThreadError> {
+        self.work_tx
+            .send(work)
+            .await
+            .map_err(|_| HashThreadError::ThreadOffline)
+    }
+}
+
+#[async_trait]
+impl HashThread for DownstreamThread {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn capabilities(&self) -> &HashThreadCapabilities {
+        &self.capabilities
+    }
+
+    async fn update_task(
+        &mut self,
+        new_task: HashTask,
+    ) -> Result<Option<HashTask>, HashThreadError> {
+        self.send(Work::Task {
+            task: new_task.clone(),
+            clean: false,
+        })
+        .await?;
+        Ok(self.current.replace(new_task))
+    }
+
+    async fn replace_task(
+        &mut self,
+        new_task: HashTask,
+    ) -> Result<Option<HashTask>, HashThreadError> {
+        self.send(Work::Task {
+            task: new_task.clone(),
+            clean: true,
+        })
+        .await?;
+        Ok(self.current.replace(new_task))
+    }
+
+    async fn go_idle(&mut self) -> Result<Option<HashTask>, HashThreadError> {
+        self.send(Work::Idle).await?;
+        Ok(self.current.take())
+    }
+
+    fn take_event_receiver(&mut self) -> Option<mpsc::Receiver<HashThreadEvent>> {
+        self.event_rx.take()
+    }
+
+    fn status(&self) -> HashThreadStatus {
+        self.status.lock().unwrap().clone()
+    }
+}
@@ -0,0 +1,451 @@
+//! Embedding the miner in another program.
+//!
+//! [`MujinaRuntime`] assembles the same daemon mujina-minerd runs, from
+//! settings given in code rather than on a command line, and starts it on
+//! the caller's Tokio runtime. The [`RuntimeHandle`] it returns stops the
+//! miner and carries the event bus, on which the miner announces boards
+//! coming and going and its own starting and stopping.
+//!
+//! ```no_run
+//! # async fn example() -> anyhow::Result<()> {
+//! use mujina_miner::runtime::{MujinaRuntime, RuntimeEvent};
+//!
+//! let handle = MujinaRuntime::new()
+//!     .with_api(false)
+//!     .with_board_filter(|name, _id| name.starts_with("Bitaxe"))
+//!     .start();
+//! let mut events = handle.subscribe();
+//! while let Ok(event) = events.recv().await {
+//!     if let RuntimeEvent::BoardConnected { id, .. } = event {
+//!         println!("mining on {id}");
+//!     }
+//! }
+//! handle.stop().await
+//! # }
+//! ```
+//!
+//! Unlike mujina-minerd, an embedded miner leaves signal handling to its
+//! host. A restart requested through the API runs a fresh daemon with the
+//! same settings; the handle stays valid across it.
+
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    backplane::BoardFilter,
+    config::{Config, PoolConfig},
+    cpu_miner::CpuMinerConfig,
+    daemon::{Daemon, DaemonOptions, ExitReason, EVENT_CAPACITY},
+};
+
+/// Something the running miner announces on the event bus.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuntimeEvent {
+    /// Every component has been started.
+    Started,
+
+    /// A board was plugged into the backplane and is being brought up.
+    BoardConnected { id: String, model: String },
+
+    /// A board was shut down and removed from the backplane.
+    BoardDisconnected { id: String },
+
+    /// A share solved a block; the header and coinbase have been written
+    /// to the state directory.
+    BlockFound { hash: String, source: String },
+
+    /// The pool being mined rejected too many shares, and was reconnected
+    /// for fresh work.
+    PoolRefreshed { url: String, reject_percent: u32 },
+
+    /// The pool being mined kept rejecting too many shares after being
+    /// refreshed; mining moved to the pool at `to` while it sits out.
+    PoolFailedOver {
+        from: String,
+        to: String,
+        reject_percent: u32,
+    },
+
+    /// Shutdown has begun.
+    Stopping,
+
+    /// A restart was requested through the API; the miner stops and starts
+    /// again with the same settings.
+    Restarting,
+}
+
+/// Builder for a miner embedded in another program.
+pub struct MujinaRuntime {
+    config: Option<Config>,
+    pools: Vec<PoolConfig>,
+    api_enabled: bool,
+    usb_discovery: bool,
+    cpu_miner: Option<CpuMinerConfig>,
+    board_filter: Option<BoardFilter>,
+    events: broadcast::Sender<RuntimeEvent>,
+}
+
+impl MujinaRuntime {
+    /// A miner with the built-in defaults: every board found on USB, the
+    /// API served, and settings the config file would give read from the
+    /// `MUJINA_*` environment variables.
+    pub fn new() -> Self {
+        Self {
+            config: None,
+            pools: Vec::new(),
+            api_enabled: true,
+            usb_discovery: true,
+            cpu_miner: None,
+            board_filter: None,
+            events: broadcast::channel(EVENT_CAPACITY).0,
+        }
+    }
+
+    /// Take settings from a config file's contents.
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Mine at `pool`. Pools given here, in priority order, replace those
+    /// of the config.
+    pub fn with_pool(mut self, pool: PoolConfig) -> Self {
+        self.pools.push(pool);
+        self
+    }
+
+    /// Serve the HTTP API, or not.
+    pub fn with_api(mut self, enabled: bool) -> Self {
+        self.api_enabled = enabled;
+        self
+    }
+
+    /// Look for boards on USB, or not.
+    pub fn with_usb_discovery(mut self, enabled: bool) -> Self {
+        self.usb_discovery = enabled;
+        self
+    }
+
+    /// Mine on the CPU as well.
+    pub fn with_cpu_miner(mut self, config: CpuMinerConfig) -> Self {
+        self.cpu_miner = Some(config);
+        self
+    }
+
+    /// Start only the boards for which `allows(name, id)` is true, given
+    /// the board type name (as `mujina-minerd --list-boards-and-exit`
+    /// prints it) and the board ID.
+    pub fn with_board_filter(
+        mut self,
+        allows: impl Fn(&str, &str) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.board_filter = Some(BoardFilter::new(allows));
+        self
+    }
+
+    /// Follow the event bus from before the miner starts, so no event is
+    /// missed.
+    pub fn subscribe(&self) -> broadcast::Receiver<RuntimeEvent> {
+        self.events.subscribe()
+    }
+
+    /// Start the miner on the current Tokio runtime.
+    pub fn start(self) -> RuntimeHandle {
+        let shutdown = CancellationToken::new();
+        let events = self.events.clone();
+        let options = self.daemon_options();
+
+        let task = tokio::spawn({
+            let shutdown = shutdown.clone();
+            let events = events.clone();
+            async move {
+                loop {
+                    let daemon = Daemon::with_options(options.clone())
+                        .with_shutdown(shutdown.child_token())
+                        .with_events(events.clone());
+                    match daemon.run().await? {
+                        ExitReason::Restart => {
+                            let _ = events.send(RuntimeEvent::Restarting);
+                        }
+                        ExitReason::Shutdown => {
+                            // An API shutdown request stops the runtime too
+                            shutdown.cancel();
+                            return Ok(());
+                        }
+                    }
+                }
+            }
+        });
+
+        RuntimeHandle {
+            shutdown,
+            events,
+            task,
+        }
+    }
+
+    /// The daemon's settings, with what was given here taking precedence
+    /// over the config.
+    fn daemon_options(&self) -> DaemonOptions {
+        let mut options = self
+            .config
+            .as_ref()
+            .map(DaemonOptions::from)
+            .unwrap_or_default();
+        if !self.pools.is_empty() {
+            options.pools = self.pools.clone();
+        }
+        options.api_enabled = self.api_enabled;
+        options.usb_discovery = self.usb_discovery;
+        options.cpu_miner = self.cpu_miner.clone();
+        options.board_filter = self.board_filter.clone();
+        options.handle_signals = false;
+        options
+    }
+}
+
+impl Default for MujinaRuntime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A running miner.
+pub struct RuntimeHandle {
+    shutdown: CancellationToken,
+    events: broadcast::Sender<RuntimeEvent>,
+    task: JoinHandle<anyhow::Result<()>>,
+}
+
+impl RuntimeHandle {
+    /// Follow the event bus from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<RuntimeEvent> {
+        self.events.subscribe()
+    }
+
+    /// Whether the miner has stopped, on its own or by [`Self::stop`].
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    /// Shut the miner down, waiting for boards to be powered down and
+    /// shares to be flushed to the pools.
+    pub async fn stop(self) -> anyhow::Result<()> {
+        self.shutdown.cancel();
+        self.wait().await
+    }
+
+    /// Wait for the miner to stop on its own, e.g. by an API shutdown
+    /// request. Fails if it couldn't start.
+    pub async fn wait(self) -> anyhow::Result<()> {
+        self.task.await?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Receive events until `wanted` comes.
+    #[cfg(feature = "cpu-miner")]
+    async fn wait_for(
+        events: &mut broadcast::Receiver<RuntimeEvent>,
+        wanted: RuntimeEvent,
+    ) -> Vec<RuntimeEvent> {
+        let mut seen = Vec::new();
+        tokio::time::timeout(std::time::Duration::from_secs(10), async {
+            loop {
+                let event = events.recv().await.unwrap();
+                seen.push(event.clone());
+                if event == wanted {
+                    return;
+                }
+            }
+        })
+        .await
+        .unwrap_or_else(|_| panic!("no {wanted:?} among {seen:?}"));
+        seen
+    }
+
+    /// Settings for a miner with no pools of its own, keeping its state
+    /// in `dir`.
+    #[cfg(feature = "cpu-miner")]
+    fn embedded_config(dir: &std::path::Path) -> Config {
+        Config::parse(&format!(
+            r#"
+            pools = []
+
+            [daemon]
+            log_level = "info"
+            state_dir = "{}"
+
+            [hardware]
+            temp_limit = 85.0
+            fan_min_rpm = 1000
+            fan_max_rpm = 6000
+
+            [api]
+            listen = "127.0.0.1:0"
+            "#,
+            dir.display()
+        ))
+        .unwrap()
+    }
+
+    #[cfg(feature = "cpu-miner")]
+    #[tokio::test]
+    async fn test_start_and_stop_embedded() {
+        let dir = std::env::temp_dir().join(format!("mujina-runtime-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let runtime = MujinaRuntime::new()
+            .with_config(embedded_config(&dir))
+            .with_api(false)
+            .with_usb_discovery(false)
+            .with_cpu_miner(CpuMinerConfig {
+                thread_count: 1,
+                duty_percent: 1,
+            });
+        let mut events = runtime.subscribe();
+        let handle = runtime.start();
+
+        let connected = RuntimeEvent::BoardConnected {
+            id: "cpu-1x1%".into(),
+            model: "CPU Miner".into(),
+        };
+        let mut seen = wait_for(&mut events, RuntimeEvent::Started).await;
+        if !seen.contains(&connected) {
+            seen.extend(wait_for(&mut events, connected).await);
+        }
+        assert!(!handle.is_finished());
+
+        handle.stop().await.unwrap();
+        let rest: Vec<_> = std::iter::from_fn(|| events.try_recv().ok()).collect();
+        assert_eq!(
+            rest,
+            [
+                RuntimeEvent::Stopping,
+                RuntimeEvent::BoardDisconnected {
+                    id: "cpu-1x1%".into()
+                },
+            ]
+        );
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[cfg(feature = "cpu-miner")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_cpu_shares_reach_mock_pool() {
+        use crate::stratum_v1::mock_pool::{MockPool, Verdict};
+
+        const SHARES: usize = 6;
+
+        // Easy enough that shares come as fast as the scheduler's flood
+        // cap lets them, about one a second from an unoptimized build
+        let difficulty = 1.0 / 1_048_576.0;
+        let mut pool = MockPool::new()
+            .with_difficulty(difficulty)
+            .with_vardiff(2, 2.0)
+            .with_rejects(3, 21, "Stale job")
+            .start()
+            .await;
+
+        let dir = std::env::temp_dir().join(format!("mujina-e2e-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let handle = MujinaRuntime::new()
+            .with_config(embedded_config(&dir))
+            .with_pool(PoolConfig {
+                url: pool.url().to_string(),
+                worker: "e2e.cpu".into(),
+                password: None,
+                priority: 0,
+                shares_per_minute: None,
+                trace: false,
+            })
+            .with_api(false)
+            .with_usb_discovery(false)
+            .with_cpu_miner(CpuMinerConfig {
+                thread_count: 1,
+                duty_percent: 100,
+            })
+            .start();
+
+        let mut submissions = Vec::new();
+        while submissions.len() < SHARES {
+            submissions.push(pool.next_submission().await);
+        }
+        handle.stop().await.unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+
+        // Every share is well-formed and meets its job's difficulty
+        for submission in &submissions {
+            assert!(
+                !matches!(submission.verdict, Verdict::Invalid { .. }),
+                "pool refused {:?}: {:?}",
+                submission.raw,
+                submission.verdict
+            );
+            let params = submission.params.as_ref().unwrap();
+            assert_eq!(params.username, "e2e.cpu");
+            assert_eq!(params.extranonce2.len(), 4);
+        }
+
+        // Mining carries on past refusals, and follows the pool's vardiff
+        let refused = submissions
+            .iter()
+            .filter(|s| matches!(s.verdict, Verdict::Refused { .. }))
+            .count();
+        assert_eq!(refused, SHARES / 3);
+        assert!(submissions
+            .iter()
+            .any(|submission| submission.difficulty > difficulty));
+    }
+
+    #[test]
+    fn test_builder_settings_override_config() {
+        let config = Config::parse(
+            r#"
+            [daemon]
+            log_level = "info"
+
+            [[pools]]
+            url = "stratum+tcp://configured.example.com:3333"
+            worker = "rig1"
+
+            [hardware]
+            temp_limit = 85.0
+            fan_min_rpm = 1000
+            fan_max_rpm = 6000
+
+            [api]
+            listen = "127.0.0.1:7785"
+            "#,
+        )
+        .unwrap();
+        let pool = PoolConfig {
+            url: "stratum+tcp://embedded.example.com:3333".into(),
+            worker: "rig2".into(),
+            password: None,
+            priority: 0,
+            shares_per_minute: None,
+            trace: false,
+        };
+
+        let options = MujinaRuntime::new()
+            .with_config(config)
+            .with_pool(pool.clone())
+            .with_api(false)
+            .with_board_filter(|_, id| id == "keep")
+            .daemon_options();
+
+        assert_eq!(options.pools, [pool]);
+        assert_eq!(options.api_bind_addr.as_deref(), Some("127.0.0.1:7785"));
+        assert!(!options.api_enabled);
+        assert!(!options.handle_signals);
+        let filter = options.board_filter.unwrap();
+        assert!(filter.allows("Bitaxe Gamma", "keep"));
+        assert!(!filter.allows("Bitaxe Gamma", "other"));
+    }
+}
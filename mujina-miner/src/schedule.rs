@@ -0,0 +1,522 @@
+//! Mining profiles by time of day and electricity price.
+//!
+//! Operators on time-of-use tariffs, or mining off surplus solar, want to
+//! mine hard when power is cheap and back off or stop when it isn't. The
+//! schedule picks one of three [`Profile`]s:
+//!
+//! - **full** and **eco** are operating points (`schedule.full` and
+//!   `schedule.eco` in the config). The boards are ramped from one to the
+//!   other in steps over `transition_secs` rather than jumped, so the supply
+//!   and the chips see a gradual change.
+//! - **off** pauses the scheduler: the threads go idle but the boards stay
+//!   up, ready to resume.
+//!
+//! The profile comes from the first configured time window that's open
+//! (local time), or the default outside them. A [`PriceFeed`], if
+//! configured, can push that further toward off when the price passes its
+//! thresholds; a feed that fails is ignored until it answers again.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    backplane::BackplaneCommand,
+    board::OperatingPoint,
+    config::{PriceFeedConfig, ScheduleConfig, ScheduleWindow},
+    scheduler::Paused,
+    tracing::prelude::*,
+};
+
+/// How often the schedule is checked.
+pub const SCHEDULE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Time between steps of a ramp between operating points.
+const RAMP_STEP_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long a price fetch may take.
+const PRICE_FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long to wait for the backplane to retune the boards.
+const BACKPLANE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How hard to mine.
+///
+/// Ordered from most to least power, so the stricter of two profiles is
+/// the greater.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Profile {
+    /// Mine at the full operating point
+    #[default]
+    Full,
+    /// Mine at the reduced eco operating point
+    Eco,
+    /// Don't mine
+    Off,
+}
+
+/// Source of the current electricity price.
+#[async_trait]
+pub trait PriceFeed: Send + Sync {
+    /// Fetch the current price.
+    async fn price(&mut self) -> anyhow::Result<f64>;
+}
+
+/// Price feed that fetches JSON from a URL; see [`parse_price`].
+pub struct JsonUrlFeed {
+    client: reqwest::Client,
+    url: String,
+}
+
+/// Switches mining profile as the schedule and prices dictate.
+pub struct ScheduleManager {
+    config: ScheduleConfig,
+    backplane_tx: mpsc::Sender<BackplaneCommand>,
+    pause_tx: watch::Sender<Paused>,
+    feed: Option<Box<dyn PriceFeed>>,
+    /// Profile in effect, once one has been entered
+    profile: Option<Profile>,
+    /// Operating point last applied
+    point: OperatingPoint,
+    /// Latest price, if the feed answered last time
+    price: Option<f64>,
+    next_fetch: Instant,
+}
+
+impl std::fmt::Display for Profile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Full => write!(f, "full"),
+            Self::Eco => write!(f, "eco"),
+            Self::Off => write!(f, "off"),
+        }
+    }
+}
+
+impl JsonUrlFeed {
+    /// Feed that fetches `url`.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl PriceFeed for JsonUrlFeed {
+    async fn price(&mut self) -> anyhow::Result<f64> {
+        let text = self
+            .client
+            .get(&self.url)
+            .timeout(PRICE_FETCH_TIMEOUT)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        parse_price(&text)
+    }
+}
+
+impl ScheduleManager {
+    /// Create a manager that retunes boards through the backplane and
+    /// pauses the scheduler through `pause_tx`.
+    ///
+    /// A price URL in the config gets a [`JsonUrlFeed`]; use
+    /// [`ScheduleManager::with_price_feed`] for another source.
+    pub fn new(
+        config: ScheduleConfig,
+        backplane_tx: mpsc::Sender<BackplaneCommand>,
+        pause_tx: watch::Sender<Paused>,
+    ) -> Self {
+        let feed = config
+            .price
+            .as_ref()
+            .map(|price| Box::new(JsonUrlFeed::new(&price.url)) as Box<dyn PriceFeed>);
+        Self {
+            config,
+            backplane_tx,
+            pause_tx,
+            feed,
+            profile: None,
+            point: OperatingPoint::default(),
+            price: None,
+            next_fetch: Instant::now(),
+        }
+    }
+
+    /// Take prices from `feed` instead of the configured URL.
+    pub fn with_price_feed(mut self, feed: Box<dyn PriceFeed>) -> Self {
+        self.feed = Some(feed);
+        self
+    }
+
+    /// Follow the schedule until shutdown.
+    pub async fn run(mut self, shutdown: CancellationToken) -> anyhow::Result<()> {
+        let mut ticker = tokio::time::interval(SCHEDULE_CHECK_INTERVAL);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = shutdown.cancelled() => return Ok(()),
+            }
+
+            self.refresh_price().await;
+            let profile = self.due_profile(local_minute());
+            if self.profile != Some(profile) {
+                self.enter(profile, &shutdown).await;
+            }
+        }
+    }
+
+    /// Fetch the price if it's time to.
+    async fn refresh_price(&mut self) {
+        let (Some(feed), Some(config)) = (&mut self.feed, &self.config.price) else {
+            return;
+        };
+        let now = Instant::now();
+        if now < self.next_fetch {
+            return;
+        }
+        self.next_fetch = now + Duration::from_secs(config.poll_secs);
+
+        match feed.price().await {
+            Ok(price) => {
+                debug!(price, "Electricity price fetched");
+                self.price = Some(price);
+            }
+            Err(e) => {
+                warn!(error = %e, "Failed to fetch electricity price; following the time windows");
+                self.price = None;
+            }
+        }
+    }
+
+    /// The profile due at `minute` past local midnight.
+    fn due_profile(&self, minute: u16) -> Profile {
+        let by_time = window_profile(&self.config.windows, self.config.default, minute);
+        let by_price = match (self.price, &self.config.price) {
+            (Some(price), Some(config)) => price_profile(config, price),
+            _ => Profile::Full,
+        };
+        by_time.max(by_price)
+    }
+
+    /// Switch to `profile`, ramping the operating point if mining continues.
+    async fn enter(&mut self, profile: Profile, shutdown: &CancellationToken) {
+        info!(
+            from = %self.profile.map_or("none".to_string(), |p| p.to_string()),
+            to = %profile,
+            price = ?self.price,
+            "Switching mining profile."
+        );
+        let previous = self.profile.replace(profile);
+
+        let target = match profile {
+            Profile::Off => {
+                self.pause_tx.send_modify(|paused| paused.schedule = true);
+                return;
+            }
+            Profile::Full => self.config.full,
+            Profile::Eco => self.config.eco,
+        };
+
+        // Idle chips can take the new point at once
+        let steps = match previous {
+            Some(Profile::Full | Profile::Eco) => {
+                (self.config.transition_secs / RAMP_STEP_INTERVAL.as_secs()).max(1) as u32
+            }
+            _ => 1,
+        };
+        let points = ramp(self.point, target, steps);
+        let last = points.len() - 1;
+        for (i, point) in points.into_iter().enumerate() {
+            if point != OperatingPoint::default() {
+                self.apply(point).await;
+                self.point = merge(self.point, point);
+            }
+            if i < last {
+                tokio::select! {
+                    _ = tokio::time::sleep(RAMP_STEP_INTERVAL) => {}
+                    _ = shutdown.cancelled() => return,
+                }
+            }
+        }
+
+        self.pause_tx.send_modify(|paused| paused.schedule = false);
+    }
+
+    /// Retune every board to `point`.
+    async fn apply(&self, point: OperatingPoint) {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        if self
+            .backplane_tx
+            .send(BackplaneCommand::SetOperatingPoint { point, reply_tx })
+            .await
+            .is_err()
+        {
+            warn!("Backplane stopped; operating point not applied");
+            return;
+        }
+        match tokio::time::timeout(BACKPLANE_TIMEOUT, reply_rx).await {
+            Ok(Ok(Ok(boards))) => debug!(%point, boards, "Boards retuned for schedule"),
+            Ok(Ok(Err(e))) => warn!(%point, error = %e, "Failed to retune boards for schedule"),
+            Ok(Err(_)) | Err(_) => warn!(%point, "Backplane didn't answer retune request"),
+        }
+    }
+}
+
+/// Parse a price: a bare JSON number, or an object with a numeric `price`.
+pub fn parse_price(text: &str) -> anyhow::Result<f64> {
+    let value: serde_json::Value = serde_json::from_str(text)?;
+    value
+        .as_f64()
+        .or_else(|| value.get("price")?.as_f64())
+        .ok_or_else(|| anyhow::anyhow!("no numeric price in feed response"))
+}
+
+/// Parse "HH:MM" into minutes past midnight.
+pub fn parse_time_of_day(text: &str) -> Result<u16, String> {
+    let invalid = || format!("'{}' isn't a time of day (HH:MM)", text);
+    let (hour, minute) = text.split_once(':').ok_or_else(invalid)?;
+    let hour: u16 = hour.parse().map_err(|_| invalid())?;
+    let minute: u16 = minute.parse().map_err(|_| invalid())?;
+    if hour >= 24 || minute >= 60 {
+        return Err(invalid());
+    }
+    Ok(hour * 60 + minute)
+}
+
+/// Minutes past midnight, local time (UTC if the offset is unknown).
+fn local_minute() -> u16 {
+    let now = OffsetDateTime::now_local().unwrap_or(OffsetDateTime::now_utc());
+    u16::from(now.hour()) * 60 + u16::from(now.minute())
+}
+
+/// Profile of the first window open at `minute`, or `default`.
+fn window_profile(windows: &[ScheduleWindow], default: Profile, minute: u16) -> Profile {
+    for window in windows {
+        let (Ok(start), Ok(end)) = (
+            parse_time_of_day(&window.start),
+            parse_time_of_day(&window.end),
+        ) else {
+            continue;
+        };
+        let open = if start <= end {
+            (start..end).contains(&minute)
+        } else {
+            minute >= start || minute < end
+        };
+        if open {
+            return window.profile;
+        }
+    }
+    default
+}
+
+/// Profile the price calls for.
+fn price_profile(config: &PriceFeedConfig, price: f64) -> Profile {
+    if config.off_above.is_some_and(|limit| price > limit) {
+        Profile::Off
+    } else if config.eco_above.is_some_and(|limit| price > limit) {
+        Profile::Eco
+    } else {
+        Profile::Full
+    }
+}
+
+/// Operating points stepping evenly from `from` to `to` in `steps`, ending
+/// at `to`. Fields set only in `to` are set at the first step; a single
+/// step suffices when nothing can be interpolated.
+fn ramp(from: OperatingPoint, to: OperatingPoint, steps: u32) -> Vec<OperatingPoint> {
+    let interpolable = matches!((from.frequency_mhz, to.frequency_mhz), (Some(_), Some(_)))
+        || matches!((from.voltage, to.voltage), (Some(_), Some(_)));
+    let steps = if interpolable { steps.max(1) } else { 1 };
+
+    let step = |a: Option<f32>, b: Option<f32>, frac: f32| match (a, b) {
+        (Some(a), Some(b)) => Some(a + (b - a) * frac),
+        (_, b) => b,
+    };
+    (1..=steps)
+        .map(|i| {
+            let frac = i as f32 / steps as f32;
+            OperatingPoint {
+                frequency_mhz: step(from.frequency_mhz, to.frequency_mhz, frac),
+                voltage: step(from.voltage, to.voltage, frac),
+            }
+        })
+        .collect()
+}
+
+/// `base` with the fields `update` sets replaced.
+fn merge(base: OperatingPoint, update: OperatingPoint) -> OperatingPoint {
+    OperatingPoint {
+        frequency_mhz: update.frequency_mhz.or(base.frequency_mhz),
+        voltage: update.voltage.or(base.voltage),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(start: &str, end: &str, profile: Profile) -> ScheduleWindow {
+        ScheduleWindow {
+            start: start.into(),
+            end: end.into(),
+            profile,
+        }
+    }
+
+    fn schedule() -> ScheduleConfig {
+        ScheduleConfig {
+            default: Profile::Full,
+            full: OperatingPoint {
+                frequency_mhz: None,
+                voltage: Some(1.2),
+            },
+            eco: OperatingPoint {
+                frequency_mhz: None,
+                voltage: Some(1.1),
+            },
+            transition_secs: 20,
+            windows: vec![window("17:00", "21:00", Profile::Eco)],
+            price: Some(PriceFeedConfig {
+                url: "http://prices.example.com".into(),
+                poll_secs: 300,
+                eco_above: Some(0.2),
+                off_above: Some(0.4),
+            }),
+        }
+    }
+
+    struct FixedPrice(f64);
+
+    #[async_trait]
+    impl PriceFeed for FixedPrice {
+        async fn price(&mut self) -> anyhow::Result<f64> {
+            Ok(self.0)
+        }
+    }
+
+    #[test]
+    fn test_parse_time_of_day() {
+        assert_eq!(parse_time_of_day("00:00"), Ok(0));
+        assert_eq!(parse_time_of_day("17:30"), Ok(17 * 60 + 30));
+        assert!(parse_time_of_day("24:00").is_err());
+        assert!(parse_time_of_day("12:60").is_err());
+        assert!(parse_time_of_day("noon").is_err());
+    }
+
+    #[test]
+    fn test_window_spanning_midnight() {
+        let windows = [
+            window("22:00", "06:00", Profile::Eco),
+            window("12:00", "13:00", Profile::Off),
+        ];
+        assert_eq!(
+            window_profile(&windows, Profile::Full, 23 * 60),
+            Profile::Eco
+        );
+        assert_eq!(
+            window_profile(&windows, Profile::Full, 5 * 60),
+            Profile::Eco
+        );
+        assert_eq!(
+            window_profile(&windows, Profile::Full, 6 * 60),
+            Profile::Full
+        );
+        assert_eq!(
+            window_profile(&windows, Profile::Full, 12 * 60),
+            Profile::Off
+        );
+        assert_eq!(
+            window_profile(&windows, Profile::Full, 13 * 60),
+            Profile::Full
+        );
+    }
+
+    #[test]
+    fn test_price_tightens_window_profile() {
+        let mut manager = ScheduleManager::new(
+            schedule(),
+            mpsc::channel(1).0,
+            watch::channel(Paused::default()).0,
+        );
+        assert_eq!(manager.due_profile(18 * 60), Profile::Eco);
+
+        manager.price = Some(0.3);
+        assert_eq!(manager.due_profile(9 * 60), Profile::Eco);
+        manager.price = Some(0.5);
+        assert_eq!(manager.due_profile(18 * 60), Profile::Off);
+        // Cheap power doesn't override the window
+        manager.price = Some(0.01);
+        assert_eq!(manager.due_profile(18 * 60), Profile::Eco);
+    }
+
+    #[test]
+    fn test_parse_price() {
+        assert_eq!(parse_price("0.12").unwrap(), 0.12);
+        assert_eq!(
+            parse_price(r#"{"price": 0.3, "unit": "kWh"}"#).unwrap(),
+            0.3
+        );
+        assert!(parse_price(r#"{"cost": 0.3}"#).is_err());
+    }
+
+    #[test]
+    fn test_ramp_steps_evenly() {
+        let from = OperatingPoint {
+            frequency_mhz: None,
+            voltage: Some(1.2),
+        };
+        let to = OperatingPoint {
+            frequency_mhz: Some(400.0),
+            voltage: Some(1.0),
+        };
+        let points = ramp(from, to, 4);
+        assert_eq!(points.len(), 4);
+        assert!((points[0].voltage.unwrap() - 1.15).abs() < 1e-6);
+        assert_eq!(points[0].frequency_mhz, Some(400.0));
+        assert_eq!(points[3], to);
+
+        // Nothing to interpolate from
+        assert_eq!(ramp(OperatingPoint::default(), to, 4), vec![to]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_price_spike_pauses_then_resumes() {
+        let (backplane_tx, mut backplane_rx) = mpsc::channel(10);
+        let (pause_tx, pause_rx) = watch::channel(Paused::default());
+        let mut manager = ScheduleManager::new(schedule(), backplane_tx, pause_tx)
+            .with_price_feed(Box::new(FixedPrice(0.5)));
+        let shutdown = CancellationToken::new();
+
+        manager.refresh_price().await;
+        let profile = manager.due_profile(9 * 60);
+        manager.enter(profile, &shutdown).await;
+        assert!(pause_rx.borrow().schedule);
+
+        // Back to full: the boards are idle, so no ramp
+        let backplane = tokio::spawn(async move {
+            let Some(BackplaneCommand::SetOperatingPoint { point, reply_tx }) =
+                backplane_rx.recv().await
+            else {
+                panic!("expected a retune");
+            };
+            reply_tx.send(Ok(1)).unwrap();
+            point
+        });
+        manager.enter(Profile::Full, &shutdown).await;
+        assert!(!pause_rx.borrow().schedule);
+        assert_eq!(backplane.await.unwrap().voltage, Some(1.2));
+    }
+}
@@ -6,9 +6,10 @@
 //! Share filtering happens at three independent levels:
 //!
 //! **Layer 1 - Chip TicketMask (hardware pre-filter):**
-//! - Configured by thread during initialization
+//! - Configured by thread from each task's share target
 //! - Chip only reports nonces meeting this threshold
-//! - Set for frequent health signals (~1/sec at current hashrate)
+//! - Kept easy enough for health signals (at least ~1/sec at current
+//!   hashrate) and hard enough not to flood the chip link
 //!
 //! **Layer 2 - HashTask.share_target (thread-to-scheduler filter):**
 //! - Configured by scheduler when assigning work
@@ -25,22 +26,86 @@
 //! statistics and monitoring, then filters again before pool submission. This
 //! provides accurate per-thread metrics while controlling network traffic.
 //!
+//! # Extranonce2 Allocation
+//!
+//! Each job's extranonce2 space is handed out in disjoint slices so no two
+//! threads ever search the same header. When a job arrives, the threads
+//! claim half of it between them, each in proportion to its share of the
+//! total hashrate; the remainder is held back for threads that arrive later
+//! and for threads that report `WorkExhausted`, which receive a fresh slice
+//! of the same job. Once the space runs out, threads keep rolling ntime
+//! until the next job arrives.
+//!
+//! On a rig with slow and fast boards, a slow thread may sit on a slice it
+//! will never get through while a fast one runs out. Every
+//! [`REBALANCE_INTERVAL`], if a thread is waiting for space, the scheduler
+//! estimates how far each other thread has got through its slice from its
+//! hashrate and capabilities (a thread rolling version bits spends far
+//! longer on each extranonce2), narrows the slice to what the thread could
+//! plausibly reach before the next check (with a generous margin), and
+//! hands the untouched tail to the waiting threads.
+//!
+//! Every thread behind a source shares its pool connection, so the slices
+//! are all that keeps two boards from submitting the same share. Within a
+//! slice each thread rolls nonces and version bits freely: a distinct
+//! extranonce2 already makes every header distinct. The allocation is
+//! checked as it's handed out, and again for every share: a share whose
+//! extranonce2 lies outside its task's slice, or that repeats one already
+//! submitted for the job, is counted as an overlap and not submitted.
+//!
+//! # Share Yield
+//!
+//! Shares meeting a task's share target arrive as a Poisson process whose
+//! rate is set by the thread's hashrate, so each task's share count can be
+//! held against the count its hashrate and duration predict. A board that
+//! loses part of its chips or cores without erroring still reports its
+//! nominal hashrate, and this shortfall is often the first sign of it. The
+//! counts are pooled per thread into windows of about
+//! [`YIELD_WINDOW_SHARES`] expected shares; a window short by more than
+//! [`YIELD_SIGMAS`] standard deviations is low, and a thread whose last
+//! [`LOW_YIELD_WINDOWS`] windows were all low is flagged as underperforming.
+//!
+//! # Pausing
+//!
+//! Mining can be paused as a whole, by the time-of-day schedule or through
+//! the API, or on single boards (see [`Paused`]). A paused scheduler idles
+//! every thread and hands out no work, but keeps following its sources, so
+//! resuming puts every thread straight onto the latest job. A paused
+//! board's threads are idled the same way while the others carry on; the
+//! board itself stays up, its fans running.
+//!
+//! Pausing is a gate on the scheduler's work, not on the boards: their
+//! tasks, pool connections and telemetry carry on regardless.
+//!
+//! # Found Blocks
+//!
+//! Every share is checked against the network target as well as its
+//! source's. One that meets it goes to its source like any other, and is
+//! also handed to the [`found_block`](crate::found_block) task, which keeps
+//! it on disk and raises the alarm, whatever becomes of the submission.
+//!
 //! This is a work-in-progress. It's currently the main and initial place where
 //! functionality is added, after which the functionality is refactored out to
 //! where it belongs.
 
+use futures::FutureExt;
 use slotmap::SlotMap;
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use bitcoin::block::Version;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch};
+use tokio::time::Instant;
 use tokio_stream::wrappers::ReceiverStream;
 use tokio_stream::{StreamExt, StreamMap};
 use tokio_util::sync::CancellationToken;
 
 use crate::asic::hash_thread::{HashTask, HashThread, HashThreadEvent, Share};
+use crate::found_block::FoundBlock;
 use crate::job_source::{
-    JobTemplate, MerkleRootKind, Share as SourceShare, SourceCommand, SourceEvent,
+    Extranonce2, Extranonce2Allocator, Extranonce2Range, JobTemplate, MerkleRootKind,
+    Share as SourceShare, SourceCommand, SourceEvent,
 };
 use crate::tracing::prelude::*;
 use crate::types::{
@@ -58,6 +123,27 @@ type ThreadId = slotmap::DefaultKey;
 /// Unique identifier for a task, assigned by the scheduler.
 type TaskId = slotmap::DefaultKey;
 
+/// How often to check for threads starved of extranonce2 space.
+const REBALANCE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How many times its estimated progress a thread keeps of its slice when
+/// the rest is reclaimed.
+const REBALANCE_MARGIN: f64 = 4.0;
+
+/// Most submitted shares remembered per source for duplicate detection.
+/// Jobs are replaced far sooner than this fills at any sane share rate.
+const MAX_SUBMITTED_SHARES: usize = 4096;
+
+/// Shares a thread is expected to find in each yield window.
+const YIELD_WINDOW_SHARES: f64 = 100.0;
+
+/// Standard deviations short of expectation that make a yield window low.
+/// With [`YIELD_WINDOW_SHARES`] of 100, that's under 70 shares.
+const YIELD_SIGMAS: f64 = 3.0;
+
+/// Low yield windows in a row before a thread is flagged.
+const LOW_YIELD_WINDOWS: u32 = 3;
+
 // StreamMap type aliases for cleaner function signatures.
 // These are kept as locals in run() rather than struct fields to avoid
 // borrow conflicts with tokio::select!.
@@ -80,6 +166,53 @@ struct TaskEntry {
 
     /// Thread this task was assigned to
     thread_id: ThreadId,
+
+    /// Extranonce2 slice the thread may search
+    en2_range: Extranonce2Range,
+
+    /// When the task was sent, for estimating progress through the slice
+    assigned_at: Instant,
+
+    /// Share target the thread reports at
+    share_target: Target,
+
+    /// Shares received on the task's channel
+    shares: u64,
+
+    /// When a newer task was sent to the thread, ending this one's
+    /// contribution to the thread's yield
+    superseded_at: Option<Instant>,
+}
+
+/// What makes a share distinct: the header fields the thread rolls.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ShareKey {
+    job_id: String,
+    extranonce2: Option<Extranonce2>,
+    ntime: u32,
+    nonce: u32,
+    version: Version,
+}
+
+/// Shares submitted to a source since its last clean job.
+#[derive(Debug, Default)]
+struct SubmittedShares {
+    keys: HashSet<ShareKey>,
+}
+
+impl SubmittedShares {
+    /// Record a share. Returns false if it was already submitted.
+    fn insert(&mut self, key: ShareKey) -> bool {
+        if self.keys.len() >= MAX_SUBMITTED_SHARES {
+            self.keys.clear();
+        }
+        self.keys.insert(key)
+    }
+
+    /// Forget everything, e.g., when the source's work is replaced.
+    fn clear(&mut self) {
+        self.keys.clear();
+    }
 }
 
 /// Registration message for adding a job source to the scheduler.
@@ -101,6 +234,36 @@ pub struct SourceRegistration {
     pub max_share_rate: Option<ShareRate>,
 }
 
+/// What's paused, wholly or by board.
+///
+/// Each part has its own writer, so one doesn't undo another's pause: the
+/// time-of-day schedule sets `schedule`, and the operator, through the API
+/// or the config file, sets `manual` and `boards`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Paused {
+    /// Paused by the time-of-day schedule
+    pub schedule: bool,
+
+    /// Paused by the operator
+    pub manual: bool,
+
+    /// Boards paused by the operator, by ID
+    pub boards: BTreeSet<String>,
+}
+
+impl Paused {
+    /// Whether all mining is paused.
+    pub fn all(&self) -> bool {
+        self.schedule || self.manual
+    }
+
+    /// Whether threads on the board with ID `board`, or on no board, get
+    /// no work.
+    pub fn board(&self, board: Option<&str>) -> bool {
+        self.all() || board.is_some_and(|id| self.boards.contains(id))
+    }
+}
+
 /// Internal scheduler tracking for a registered source.
 #[derive(Debug)]
 struct SourceEntry {
@@ -113,12 +276,22 @@ struct SourceEntry {
     /// Last job received from this source (for assigning to newly-arriving threads)
     last_job: Option<Arc<JobTemplate>>,
 
+    /// Unallocated extranonce2 space of `last_job`
+    en2_allocator: Option<Extranonce2Allocator>,
+
+    /// Shares submitted since the last clean job, to catch duplicates
+    submitted: SubmittedShares,
+
+    /// Threads that ran out of `last_job`'s extranonce2 space and are
+    /// waiting for a rebalance
+    starved: HashSet<ThreadId>,
+
     /// Maximum average share submission rate for this source.
     max_share_rate: Option<ShareRate>,
 }
 
 /// Whether to update alongside existing work or replace it.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 enum AssignMode {
     /// Add new task alongside existing (UpdateJob behavior)
     Update,
@@ -149,6 +322,15 @@ struct Scheduler {
 
     /// Track thread count for disconnect detection
     last_thread_count: usize,
+
+    /// Share yield of each thread
+    yields: HashMap<ThreadId, ShareYield>,
+
+    /// What's idled and gets no work
+    paused: Paused,
+
+    /// Where shares solving a block are handed over
+    found_tx: Option<mpsc::UnboundedSender<FoundBlock>>,
 }
 
 impl Scheduler {
@@ -160,6 +342,9 @@ impl Scheduler {
             stats: MiningStats::default(),
             difficulty_warned_sources: HashSet::new(),
             last_thread_count: 0,
+            yields: HashMap::new(),
+            paused: Paused::default(),
+            found_tx: None,
         }
     }
 
@@ -191,6 +376,32 @@ impl Scheduler {
         }
     }
 
+    /// A thread's measured hashrate, or its estimate before measurements.
+    fn thread_hashrate(&self, thread_id: ThreadId) -> HashRate {
+        let Some(thread) = self.threads.get(thread_id) else {
+            return HashRate::default();
+        };
+        let measured = thread.status().hashrate;
+        if measured.is_zero() {
+            thread.capabilities().hashrate_estimate
+        } else {
+            measured
+        }
+    }
+
+    /// Fraction of the total hashrate a thread contributes.
+    fn hashrate_share(&self, thread_id: ThreadId) -> f64 {
+        let total: u64 = self
+            .threads
+            .keys()
+            .map(|id| self.thread_hashrate(id).0)
+            .sum();
+        if total == 0 {
+            return 1.0 / self.threads.len().max(1) as f64;
+        }
+        self.thread_hashrate(thread_id).0 as f64 / total as f64
+    }
+
     /// Compute the share_target for a HashTask.
     ///
     /// Applies the source's rate limit (if any) to avoid flooding. Returns
@@ -239,11 +450,67 @@ impl Scheduler {
             .collect();
 
         for task_id in task_ids {
-            self.tasks.remove(task_id);
+            if let Some(entry) = self.tasks.remove(task_id) {
+                self.record_yield(&entry);
+            }
             share_channels.remove(&task_id);
         }
     }
 
+    /// Fold a finished task's shares into its thread's yield.
+    fn record_yield(&mut self, entry: &TaskEntry) {
+        let Some(thread) = self.threads.get(entry.thread_id) else {
+            return;
+        };
+        let end = entry.superseded_at.unwrap_or_else(Instant::now);
+        let active = end.saturating_duration_since(entry.assigned_at);
+        let interval = expected_time_to_share_from_target(
+            entry.share_target,
+            self.thread_hashrate(entry.thread_id),
+        );
+        let expected = active.as_secs_f64() / interval.as_secs_f64();
+
+        let Some(window) = self
+            .yields
+            .entry(entry.thread_id)
+            .or_default()
+            .record(expected, entry.shares)
+        else {
+            return;
+        };
+        let yield_percent = (100.0 * window.actual as f64 / window.expected).round();
+        match window.check {
+            YieldCheck::Normal => trace!(
+                thread = %thread.name(),
+                expected = window.expected.round(),
+                actual = window.actual,
+                "Share yield as expected"
+            ),
+            YieldCheck::Low => debug!(
+                thread = %thread.name(),
+                expected = window.expected.round(),
+                actual = window.actual,
+                yield_percent,
+                "Share yield low"
+            ),
+            YieldCheck::Underperforming => warn!(
+                thread = %thread.name(),
+                expected = window.expected.round(),
+                actual = window.actual,
+                yield_percent,
+                windows = LOW_YIELD_WINDOWS,
+                "Thread consistently finds fewer shares than its hashrate predicts; \
+                 check for failed chips or cores"
+            ),
+            YieldCheck::Recovered => info!(
+                thread = %thread.name(),
+                expected = window.expected.round(),
+                actual = window.actual,
+                "Thread share yield back to normal"
+            ),
+        }
+    }
+
     /// Handle registration of a new job source.
     async fn handle_source_registration(
         &mut self,
@@ -254,6 +521,9 @@ impl Scheduler {
             name: registration.name.clone(),
             command_tx: registration.command_tx,
             last_job: None,
+            en2_allocator: None,
+            submitted: SubmittedShares::default(),
+            starved: HashSet::new(),
             max_share_rate: registration.max_share_rate,
         });
         source_events.insert(source_id, ReceiverStream::new(registration.event_rx));
@@ -283,7 +553,7 @@ impl Scheduler {
 
         // Extract EN2 range (only supported for computed merkle roots)
         let full_en2_range = match &job_template.merkle_root {
-            MerkleRootKind::Computed(template) => template.extranonce2_range.clone(),
+            MerkleRootKind::Computed(template) => template.extranonce2_range().clone(),
             MerkleRootKind::Fixed(_) => {
                 error!(job_id = %job_template.id, "Header-only jobs not supported");
                 return;
@@ -291,10 +561,13 @@ impl Scheduler {
         };
 
         let template = Arc::new(job_template);
+        let full_len = full_en2_range.len();
 
-        // Cache job for newly-arriving threads
+        // Cache job and its EN2 space for newly-arriving threads
         if let Some(source) = self.sources.get_mut(source_id) {
             source.last_job = Some(template.clone());
+            source.en2_allocator = Some(Extranonce2Allocator::new(full_en2_range));
+            source.starved.clear();
         }
 
         // Skip assignment if no threads registered yet
@@ -302,6 +575,10 @@ impl Scheduler {
             debug!(source = %source_name, "No threads yet, job cached for later");
             return;
         }
+        if self.paused.all() {
+            trace!(source = %source_name, "Paused, job cached for later");
+            return;
+        }
 
         // Check if difficulty is reasonable for our hashrate (once per source)
         if !self.difficulty_warned_sources.contains(&source_id) {
@@ -314,51 +591,152 @@ impl Scheduler {
         // If replacing, invalidate old tasks for this source first
         if matches!(mode, AssignMode::Replace) {
             self.remove_tasks_where(share_channels, |e| e.source_id == source_id);
+            if let Some(source) = self.sources.get_mut(source_id) {
+                source.submitted.clear();
+            }
         }
 
-        // Split EN2 range among all threads
-        let en2_slices = full_en2_range
-            .split(self.threads.len())
-            .expect("Failed to split EN2 range among threads");
-
         // Compute share_target with rate limiting applied
         let max_share_rate = self.sources.get(source_id).and_then(|s| s.max_share_rate);
         let hashrate = self.measured_hashrate();
         let share_target =
             Self::compute_share_target(max_share_rate, hashrate, template.share_target);
 
-        // Assign work to all threads
-        for ((thread_id, thread), en2_range) in self.threads.iter_mut().zip(en2_slices) {
-            let starting_en2 = en2_range.iter().next();
-
-            // Create share channel for this task
-            let (share_tx, share_rx) = mpsc::channel(32);
+        // Give each thread its own slice of the EN2 space
+        let thread_ids: Vec<ThreadId> = self
+            .threads
+            .keys()
+            .filter(|&id| !self.thread_paused(id))
+            .collect();
+        for thread_id in thread_ids {
+            let slice_len = en2_slice_len(full_len, self.hashrate_share(thread_id));
+            let Some(en2_range) = self.allocate_en2(source_id, slice_len) else {
+                warn!(
+                    source = %source_name,
+                    job_id = %template.id,
+                    "Extranonce2 space exhausted, thread left without this job"
+                );
+                break;
+            };
 
-            let hash_task = HashTask {
-                template: template.clone(),
-                en2_range: Some(en2_range),
-                en2: starting_en2,
+            self.send_task(
+                mode,
+                thread_id,
+                source_id,
+                &template,
+                en2_range,
                 share_target,
-                ntime: template.time,
-                share_tx,
-            };
+                share_channels,
+            )
+            .await;
+        }
+    }
 
-            let result = match mode {
-                AssignMode::Update => thread.update_task(hash_task).await,
-                AssignMode::Replace => thread.replace_task(hash_task).await,
-            };
+    /// Take the next slice of a source's extranonce2 space.
+    fn allocate_en2(&mut self, source_id: SourceId, len: u64) -> Option<Extranonce2Range> {
+        self.sources
+            .get_mut(source_id)?
+            .en2_allocator
+            .as_mut()?
+            .allocate(len)
+    }
 
-            if let Err(e) = result {
-                error!(thread = %thread.name(), error = %e, "Failed to assign task");
-            } else {
-                let task_id = self.tasks.insert(TaskEntry {
-                    source_id,
-                    template: template.clone(),
-                    thread_id,
-                });
-                share_channels.insert(task_id, ReceiverStream::new(share_rx));
+    /// Send a task covering `en2_range` of `template` to a thread.
+    ///
+    /// Returns whether the thread accepted it.
+    #[expect(
+        clippy::too_many_arguments,
+        reason = "task fields come from several sources"
+    )]
+    async fn send_task(
+        &mut self,
+        mode: AssignMode,
+        thread_id: ThreadId,
+        source_id: SourceId,
+        template: &Arc<JobTemplate>,
+        en2_range: Extranonce2Range,
+        share_target: Target,
+        share_channels: &mut ShareStream,
+    ) -> bool {
+        if !self.check_disjoint(thread_id, template, &en2_range) {
+            return false;
+        }
+
+        let Some(thread) = self.threads.get_mut(thread_id) else {
+            return false;
+        };
+
+        // Create share channel for this task
+        let (share_tx, share_rx) = mpsc::channel(32);
+
+        let hash_task = HashTask {
+            template: template.clone(),
+            en2: en2_range.iter().next(),
+            en2_range: Some(en2_range.clone()),
+            share_target,
+            ntime: template.time,
+            share_tx,
+        };
+
+        let result = match mode {
+            AssignMode::Update => thread.update_task(hash_task).await,
+            AssignMode::Replace => thread.replace_task(hash_task).await,
+        };
+
+        if let Err(e) = result {
+            error!(thread = %thread.name(), error = %e, "Failed to assign task");
+            return false;
+        }
+
+        let now = Instant::now();
+        for task in self.tasks.values_mut() {
+            if task.thread_id == thread_id && task.superseded_at.is_none() {
+                task.superseded_at = Some(now);
             }
         }
+        let task_id = self.tasks.insert(TaskEntry {
+            source_id,
+            template: template.clone(),
+            thread_id,
+            en2_range,
+            assigned_at: now,
+            share_target,
+            shares: 0,
+            superseded_at: None,
+        });
+        share_channels.insert(task_id, ReceiverStream::new(share_rx));
+        true
+    }
+
+    /// Check that `en2_range` overlaps no other thread's live slice of
+    /// `template`.
+    ///
+    /// The allocator makes this so by construction; a failure means a bug
+    /// that would have two threads submitting the same shares.
+    fn check_disjoint(
+        &mut self,
+        thread_id: ThreadId,
+        template: &Arc<JobTemplate>,
+        en2_range: &Extranonce2Range,
+    ) -> bool {
+        let clash = self.tasks.values().find(|task| {
+            task.thread_id != thread_id
+                && Arc::ptr_eq(&task.template, template)
+                && task.en2_range.overlaps(en2_range)
+        });
+        let Some(clash) = clash else {
+            return true;
+        };
+
+        self.stats.overlaps += 1;
+        error!(
+            job_id = %template.id,
+            slice = ?en2_range,
+            existing = ?clash.en2_range,
+            "Extranonce2 slice overlaps another thread's, not assigned"
+        );
+        debug_assert!(false, "overlapping extranonce2 slices");
+        false
     }
 
     /// Handle ClearJobs event from a source.
@@ -373,6 +751,8 @@ impl Scheduler {
         // Clear cached job so newly-arriving threads don't get stale work
         if let Some(source) = self.sources.get_mut(source_id) {
             source.last_job = None;
+            source.en2_allocator = None;
+            source.submitted.clear();
         }
 
         // Remove tasks for this source (channels close, stale shares fail)
@@ -382,7 +762,7 @@ impl Scheduler {
     /// Handle a share arriving from a task's channel.
     async fn handle_share(&mut self, task_id: TaskId, share: Share) {
         // Look up task context for routing
-        let Some(task_entry) = self.tasks.get(task_id) else {
+        let Some(task_entry) = self.tasks.get_mut(task_id) else {
             // Task was removed (ReplaceJob/ClearJobs) but share arrived
             // before channel closed. This is normal; just drop the share.
             trace!(task_id = ?task_id, "Share for removed task (dropped)");
@@ -407,9 +787,64 @@ impl Scheduler {
 
         // Track hashes for hashrate measurement (see MiningStats doc)
         self.stats.total_hashes += share.expected_hashes;
+        task_entry.shares += 1;
+
+        // A block goes to the source whatever its share target says
+        let source_name = self
+            .sources
+            .get(task_entry.source_id)
+            .map_or("unknown", |s| s.name.as_str());
+        let found = FoundBlock::from_share(source_name, &task_entry.template, &share);
 
         // Check if share meets source threshold
-        if task_entry.template.share_target.is_met_by(hash) {
+        if task_entry.template.share_target.is_met_by(hash) || found.is_some() {
+            // Overlapping search space shows up as shares outside the
+            // task's slice or repeats of shares already submitted
+            let in_slice = share
+                .extranonce2
+                .is_none_or(|en2| task_entry.en2_range.contains(&en2));
+            let key = ShareKey {
+                job_id: task_entry.template.id.clone(),
+                extranonce2: share.extranonce2,
+                ntime: share.ntime,
+                nonce,
+                version: share.version,
+            };
+            // A block is valid wherever in the template it was found, so
+            // only a repeat of one already submitted is held back
+            let source_id = task_entry.source_id;
+            let fresh = (in_slice || found.is_some())
+                && self
+                    .sources
+                    .get_mut(source_id)
+                    .is_none_or(|source| source.submitted.insert(key));
+
+            if let Some(block) = found.filter(|_| fresh) {
+                warn!(hash = %block.hash, job_id = %block.job_id, "Share solves a block");
+                if let Some(found_tx) = &self.found_tx {
+                    let _ = found_tx.send(block);
+                }
+            }
+
+            if !fresh || !in_slice {
+                self.stats.overlaps += 1;
+                let task_entry = &self.tasks[task_id];
+                warn!(
+                    thread = %self.threads.get(task_entry.thread_id).map(|t| t.name()).unwrap_or("unknown"),
+                    job_id = %task_entry.template.id,
+                    extranonce2 = ?share.extranonce2,
+                    nonce = format!("{:#x}", nonce),
+                    reason = if in_slice { "duplicate" } else { "outside slice" },
+                    overlaps = self.stats.overlaps,
+                    submitted = fresh,
+                    "Overlapping share"
+                );
+                if !fresh {
+                    return;
+                }
+            }
+
+            let task_entry = &self.tasks[task_id];
             self.stats.shares_submitted += 1;
 
             // Submit share to originating source
@@ -445,7 +880,12 @@ impl Scheduler {
     }
 
     /// Handle an event from a hash thread.
-    fn handle_thread_event(&mut self, thread_id: ThreadId, event: HashThreadEvent) {
+    async fn handle_thread_event(
+        &mut self,
+        thread_id: ThreadId,
+        event: HashThreadEvent,
+        share_channels: &mut ShareStream,
+    ) {
         let thread_name = self
             .threads
             .get(thread_id)
@@ -455,7 +895,7 @@ impl Scheduler {
         match event {
             HashThreadEvent::WorkExhausted { en2_searched } => {
                 info!(thread = %thread_name, en2_searched, "Work exhausted");
-                // TODO: Assign new work to this thread
+                self.refill_thread(thread_id, share_channels).await;
             }
 
             HashThreadEvent::WorkDepletionWarning {
@@ -473,9 +913,70 @@ impl Scheduler {
                     "Thread status"
                 );
             }
+
+            HashThreadEvent::Stalled { silent_for, action } => {
+                self.stats.stalls += 1;
+                warn!(
+                    thread = %thread_name,
+                    silent_secs = silent_for.as_secs(),
+                    %action,
+                    "Thread stalled"
+                );
+            }
+
+            HashThreadEvent::Recovered => {
+                info!(thread = %thread_name, "Thread recovered from stall");
+            }
         }
     }
 
+    /// Give a thread that exhausted its EN2 slice a fresh slice of the same job.
+    ///
+    /// Only the source's current job is refilled; a thread still working on
+    /// an older job keeps rolling ntime until new work arrives.
+    async fn refill_thread(&mut self, thread_id: ThreadId, share_channels: &mut ShareStream) {
+        let current = self.tasks.values().find_map(|task| {
+            let source = self.sources.get(task.source_id)?;
+            let last_job = source.last_job.as_ref()?;
+            (task.thread_id == thread_id && Arc::ptr_eq(&task.template, last_job))
+                .then(|| (task.source_id, last_job.clone(), source.max_share_rate))
+        });
+        let Some((source_id, template, max_share_rate)) = current else {
+            return;
+        };
+
+        let MerkleRootKind::Computed(merkle) = &template.merkle_root else {
+            return;
+        };
+        let slice_len = en2_slice_len(
+            merkle.extranonce2_range().len(),
+            self.hashrate_share(thread_id),
+        );
+        let Some(en2_range) = self.allocate_en2(source_id, slice_len) else {
+            debug!(job_id = %template.id, "Extranonce2 space exhausted, waiting for rebalance");
+            if let Some(source) = self.sources.get_mut(source_id) {
+                source.starved.insert(thread_id);
+            }
+            return;
+        };
+
+        let share_target = Self::compute_share_target(
+            max_share_rate,
+            self.measured_hashrate(),
+            template.share_target,
+        );
+        self.send_task(
+            AssignMode::Update,
+            thread_id,
+            source_id,
+            &template,
+            en2_range,
+            share_target,
+            share_channels,
+        )
+        .await;
+    }
+
     /// Handle a new thread arriving from the backplane.
     async fn handle_new_thread(
         &mut self,
@@ -488,9 +989,16 @@ impl Scheduler {
             .expect("Thread missing event receiver");
 
         let thread_name = thread.name().to_string();
+        let capabilities = thread.capabilities();
+        debug!(
+            thread = %thread_name,
+            rolls_version = capabilities.rolls_version,
+            midstates = capabilities.midstates,
+            max_pending_jobs = capabilities.max_pending_jobs,
+            "Thread registered"
+        );
         let thread_id = self.threads.insert(thread);
         thread_events.insert(thread_id, ReceiverStream::new(event_rx));
-        debug!(thread = %thread_name, "Thread registered");
 
         // Broadcast updated hashrate to all sources
         let hashrate = self.measured_hashrate();
@@ -502,58 +1010,237 @@ impl Scheduler {
 
         self.last_thread_count = thread_events.len();
 
+        if self.thread_paused(thread_id) {
+            return;
+        }
+        self.assign_cached_jobs(thread_id, share_channels).await;
+    }
+
+    /// Give a thread each source's latest job, from unused extranonce2
+    /// space.
+    async fn assign_cached_jobs(&mut self, thread_id: ThreadId, share_channels: &mut ShareStream) {
+        let Some(thread_name) = self.threads.get(thread_id).map(|t| t.name().to_string()) else {
+            return;
+        };
+
         // Compute hashrate once for all sources
         let hashrate = self.measured_hashrate();
 
-        // Assign cached jobs from all sources to the new thread
-        for (source_id, source) in self.sources.iter() {
-            let Some(template) = &source.last_job else {
+        let source_ids: Vec<SourceId> = self.sources.keys().collect();
+        for source_id in source_ids {
+            let source = &self.sources[source_id];
+            let Some(template) = source.last_job.clone() else {
                 continue;
             };
-
-            // Extract full EN2 range (new thread overlaps with others)
-            let full_en2_range = match &template.merkle_root {
-                MerkleRootKind::Computed(t) => t.extranonce2_range.clone(),
-                MerkleRootKind::Fixed(_) => continue,
+            let MerkleRootKind::Computed(merkle) = &template.merkle_root else {
+                continue;
             };
+            let source_name = source.name.clone();
 
             // Compute share_target with rate limiting applied
             let share_target =
                 Self::compute_share_target(source.max_share_rate, hashrate, template.share_target);
 
-            let (share_tx, share_rx) = mpsc::channel(32);
-            let hash_task = HashTask {
-                template: template.clone(),
-                en2_range: Some(full_en2_range.clone()),
-                en2: full_en2_range.iter().next(),
-                share_target,
-                ntime: template.time,
-                share_tx,
+            // Take an unused slice so the thread doesn't overlap others
+            let slice_len = en2_slice_len(
+                merkle.extranonce2_range().len(),
+                self.hashrate_share(thread_id),
+            );
+            let Some(en2_range) = self.allocate_en2(source_id, slice_len) else {
+                warn!(
+                    thread = %thread_name,
+                    source = %source_name,
+                    job_id = %template.id,
+                    "Extranonce2 space exhausted, thread waits for next job"
+                );
+                continue;
             };
 
-            let thread = self
-                .threads
-                .get_mut(thread_id)
-                .expect("Just inserted thread");
-            if let Err(e) = thread.update_task(hash_task).await {
-                error!(thread = %thread.name(), error = %e, "Failed to assign cached job");
-            } else {
-                let task_id = self.tasks.insert(TaskEntry {
-                    source_id,
-                    template: template.clone(),
+            if self
+                .send_task(
+                    AssignMode::Update,
                     thread_id,
-                });
-                share_channels.insert(task_id, ReceiverStream::new(share_rx));
+                    source_id,
+                    &template,
+                    en2_range,
+                    share_target,
+                    share_channels,
+                )
+                .await
+            {
                 debug!(
-                    thread = %thread.name(),
-                    source = %source.name,
+                    thread = %thread_name,
+                    source = %source_name,
                     job_id = %template.id,
-                    "Assigned cached job to new thread"
+                    "Assigned cached job to thread"
                 );
             }
         }
     }
 
+    /// Reclaim untouched extranonce2 space for threads that ran out.
+    ///
+    /// For each source with starved threads, every other thread on its
+    /// current job has its slice narrowed to what it could reach before the
+    /// next rebalance, and the tails go to the starved threads.
+    async fn rebalance(&mut self, share_channels: &mut ShareStream) {
+        let source_ids: Vec<SourceId> = self
+            .sources
+            .iter()
+            .filter(|(_, source)| !source.starved.is_empty() && source.last_job.is_some())
+            .map(|(id, _)| id)
+            .collect();
+
+        for source_id in source_ids {
+            let hashrate = self.measured_hashrate();
+            let source = &mut self.sources[source_id];
+            let starved = std::mem::take(&mut source.starved);
+            let Some(template) = source.last_job.clone() else {
+                continue;
+            };
+            let share_target =
+                Self::compute_share_target(source.max_share_rate, hashrate, template.share_target);
+
+            let holders: Vec<(TaskId, ThreadId)> = self
+                .tasks
+                .iter()
+                .filter(|(_, task)| {
+                    Arc::ptr_eq(&task.template, &template) && !starved.contains(&task.thread_id)
+                })
+                .map(|(id, task)| (id, task.thread_id))
+                .collect();
+
+            let mut reclaimed = 0u64;
+            for (task_id, thread_id) in holders {
+                let task = &self.tasks[task_id];
+                let hashes_per_en2 = self.threads[thread_id]
+                    .capabilities()
+                    .hashes_per_en2(&template.version);
+                let Some((resume, tail)) = split_untouched(
+                    &task.en2_range,
+                    task.assigned_at.elapsed(),
+                    self.thread_hashrate(thread_id),
+                    hashes_per_en2,
+                ) else {
+                    continue;
+                };
+
+                let end = resume.max;
+                if !self
+                    .send_task(
+                        AssignMode::Update,
+                        thread_id,
+                        source_id,
+                        &template,
+                        resume,
+                        share_target,
+                        share_channels,
+                    )
+                    .await
+                {
+                    continue;
+                }
+
+                // Shares still to come from the old task must lie before
+                // the tail, which is about to go to another thread
+                if let Some(task) = self.tasks.get_mut(task_id) {
+                    task.en2_range.max = end;
+                }
+                reclaimed = reclaimed.saturating_add(tail.len());
+                if let Some(allocator) = self.sources[source_id].en2_allocator.as_mut() {
+                    allocator.release(tail);
+                }
+            }
+
+            if reclaimed == 0 {
+                self.sources[source_id].starved = starved;
+                continue;
+            }
+            info!(
+                source = %self.sources[source_id].name,
+                job_id = %template.id,
+                reclaimed,
+                starved = starved.len(),
+                "Rebalanced extranonce2 space"
+            );
+            for thread_id in starved {
+                self.refill_thread(thread_id, share_channels).await;
+            }
+        }
+    }
+
+    /// Whether a thread gets no work, because mining or its board is paused.
+    fn thread_paused(&self, thread_id: ThreadId) -> bool {
+        let board = self.threads.get(thread_id).and_then(|t| t.board_id());
+        self.paused.board(board)
+    }
+
+    /// Pause or resume mining, wholly or on some boards.
+    ///
+    /// Threads newly paused are idled and their tasks dropped; threads newly
+    /// resumed get each source's latest job.
+    async fn set_paused(&mut self, paused: Paused, share_channels: &mut ShareStream) {
+        if paused == self.paused {
+            return;
+        }
+        let was_paused: HashSet<ThreadId> = self
+            .threads
+            .keys()
+            .filter(|&id| self.thread_paused(id))
+            .collect();
+        let was_all = self.paused.all();
+        let previous = std::mem::replace(&mut self.paused, paused);
+
+        match (was_all, self.paused.all()) {
+            (false, true) => info!(threads = self.threads.len(), "Mining paused."),
+            (true, false) => info!(threads = self.threads.len(), "Mining resumed."),
+            _ => {}
+        }
+        for board in self.paused.boards.difference(&previous.boards) {
+            info!(board = %board, "Board paused.");
+        }
+        for board in previous.boards.difference(&self.paused.boards) {
+            info!(board = %board, "Board resumed.");
+        }
+
+        let now_paused: HashSet<ThreadId> = self
+            .threads
+            .keys()
+            .filter(|&id| self.thread_paused(id))
+            .collect();
+        let pausing: HashSet<ThreadId> = now_paused.difference(&was_paused).copied().collect();
+        for &thread_id in &pausing {
+            let thread = &mut self.threads[thread_id];
+            if let Err(e) = thread.go_idle().await {
+                warn!(thread = %thread.name(), error = %e, "Failed to idle thread");
+            }
+        }
+        self.remove_tasks_where(share_channels, |e| pausing.contains(&e.thread_id));
+
+        if !was_all || self.paused.all() {
+            // The rest are mining, so resumed threads join them like new ones
+            for &thread_id in was_paused.difference(&now_paused) {
+                self.assign_cached_jobs(thread_id, share_channels).await;
+            }
+            return;
+        }
+
+        let jobs: Vec<(SourceId, Arc<JobTemplate>)> = self
+            .sources
+            .iter()
+            .filter_map(|(id, source)| Some((id, source.last_job.clone()?)))
+            .collect();
+        for (source_id, template) in jobs {
+            self.assign_job_to_threads(
+                AssignMode::Replace,
+                source_id,
+                (*template).clone(),
+                share_channels,
+            )
+            .await;
+        }
+    }
+
     /// Detect and handle thread disconnections.
     async fn handle_thread_disconnections(
         &mut self,
@@ -574,6 +1261,7 @@ impl Scheduler {
         // Remove threads that no longer have active event streams
         let active_thread_ids: HashSet<_> = thread_events.keys().collect();
         self.threads.retain(|id, _| active_thread_ids.contains(&id));
+        self.yields.retain(|id, _| active_thread_ids.contains(id));
 
         // Remove tasks for disconnected threads
         self.remove_tasks_where(share_channels, |e| {
@@ -591,23 +1279,57 @@ impl Scheduler {
         self.difficulty_warned_sources.clear();
     }
 
+    /// Drop sources whose event channel has closed, along with their tasks.
+    ///
+    /// A source is stopped for good when the daemon switches pools; its
+    /// cached job mustn't be handed to threads that arrive afterwards.
+    fn handle_source_disconnections(
+        &mut self,
+        source_events: &SourceEventStream,
+        share_channels: &mut ShareStream,
+    ) {
+        if source_events.len() == self.sources.len() {
+            return;
+        }
+
+        let active_source_ids: HashSet<_> = source_events.keys().collect();
+        let ended: Vec<SourceId> = self
+            .sources
+            .keys()
+            .filter(|id| !active_source_ids.contains(id))
+            .collect();
+        for source_id in ended {
+            if let Some(source) = self.sources.remove(source_id) {
+                debug!(source = %source.name, "Source removed");
+            }
+            self.difficulty_warned_sources.remove(&source_id);
+            self.remove_tasks_where(share_channels, |e| e.source_id == source_id);
+        }
+    }
+
     /// Main scheduler loop.
     async fn run(
         &mut self,
         running: CancellationToken,
         mut thread_rx: mpsc::Receiver<Box<dyn HashThread>>,
         mut source_reg_rx: mpsc::Receiver<SourceRegistration>,
+        mut paused_rx: watch::Receiver<Paused>,
     ) {
         // StreamMaps as locals (not in self) to avoid borrow conflicts in select!
         let mut source_events: SourceEventStream = StreamMap::new();
         let mut thread_events: ThreadEventStream = StreamMap::new();
         let mut share_channels: ShareStream = StreamMap::new();
+        self.paused = paused_rx.borrow_and_update().clone();
 
         // Create interval for periodic status logging
         let mut status_interval = tokio::time::interval(Duration::from_secs(30));
         status_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
         let mut first_status_tick = true;
 
+        // Create interval for extranonce2 rebalancing
+        let mut rebalance_interval = tokio::time::interval(REBALANCE_INTERVAL);
+        rebalance_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
         // Create interval for periodic hashrate broadcasts to sources
         let mut hashrate_interval = tokio::time::interval(Duration::from_secs(10));
         hashrate_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
@@ -668,7 +1390,7 @@ impl Scheduler {
 
                 // Thread events
                 Some((thread_id, event)) = thread_events.next() => {
-                    self.handle_thread_event(thread_id, event);
+                    self.handle_thread_event(thread_id, event, &mut share_channels).await;
                 }
 
                 // New thread from backplane
@@ -676,6 +1398,12 @@ impl Scheduler {
                     self.handle_new_thread(thread, &mut thread_events, &mut share_channels).await;
                 }
 
+                // Pause or resume
+                Ok(()) = paused_rx.changed() => {
+                    let paused = paused_rx.borrow_and_update().clone();
+                    self.set_paused(paused, &mut share_channels).await;
+                }
+
                 // Periodic status logging
                 _ = status_interval.tick() => {
                     if first_status_tick {
@@ -685,6 +1413,13 @@ impl Scheduler {
                     }
                 }
 
+                // Periodic extranonce2 rebalancing
+                _ = rebalance_interval.tick() => {
+                    if !self.paused.all() {
+                        self.rebalance(&mut share_channels).await;
+                    }
+                }
+
                 // Periodic hashrate broadcast to sources
                 _ = hashrate_interval.tick() => {
                     if first_hashrate_tick {
@@ -706,6 +1441,20 @@ impl Scheduler {
             // Detect thread disconnections (StreamMap silently removes ended streams)
             self.handle_thread_disconnections(&thread_events, &mut share_channels)
                 .await;
+
+            // Likewise for sources that have stopped
+            self.handle_source_disconnections(&source_events, &mut share_channels);
+        }
+
+        // No more jobs go out, but shares the chips have already found are
+        // still worth submitting; the sources flush them to their pools
+        let mut flushed = 0;
+        while let Some(Some((task_id, share))) = share_channels.next().now_or_never() {
+            self.handle_share(task_id, share).await;
+            flushed += 1;
+        }
+        if flushed > 0 {
+            debug!(shares = flushed, "Flushed queued shares");
         }
 
         // Log final statistics
@@ -756,14 +1505,63 @@ fn warn_if_difficulty_too_high(job: &JobTemplate, hashrate: HashRate, source_nam
     }
 }
 
+/// Number of EN2 values to hand a thread contributing `hashrate_share` of
+/// the total hashrate.
+///
+/// Threads initially claim half the space between them, leaving the rest for
+/// late arrivals and refills.
+fn en2_slice_len(range_len: u64, hashrate_share: f64) -> u64 {
+    ((range_len as f64 * hashrate_share.clamp(0.0, 1.0) / 2.0) as u64).max(1)
+}
+
+/// Split a slice a thread has been searching for `elapsed` at `hashrate`
+/// into the part it keeps, starting at its estimated position, and an
+/// untouched tail to reclaim.
+///
+/// `hashes_per_en2` is how much the thread searches under each extranonce2,
+/// as its capabilities report.
+///
+/// Returns None if the thread could plausibly reach the end of the slice
+/// before the next rebalance.
+fn split_untouched(
+    slice: &Extranonce2Range,
+    elapsed: Duration,
+    hashrate: HashRate,
+    hashes_per_en2: f64,
+) -> Option<(Extranonce2Range, Extranonce2Range)> {
+    let per_second = hashrate.0 as f64 / hashes_per_en2;
+    let searched = (elapsed.as_secs_f64() * per_second) as u64;
+    let keep = (REBALANCE_MARGIN * (elapsed + REBALANCE_INTERVAL).as_secs_f64() * per_second)
+        .ceil()
+        .max(1.0) as u64;
+
+    let end = slice
+        .min
+        .checked_add(keep - 1)
+        .filter(|&end| end < slice.max)?;
+    let resume =
+        Extranonce2Range::new_range(slice.min.saturating_add(searched).min(end), end, slice.size)
+            .ok()?;
+    let tail = Extranonce2Range::new_range(end + 1, slice.max, slice.size).ok()?;
+    Some((resume, tail))
+}
+
 /// Run the scheduler task, receiving hash threads and job sources.
+///
+/// Threads get no work while `paused_rx` says they're paused. Shares
+/// solving a block are also sent to `found_tx`.
 pub async fn task(
     running: CancellationToken,
     thread_rx: mpsc::Receiver<Box<dyn HashThread>>,
     source_reg_rx: mpsc::Receiver<SourceRegistration>,
+    paused_rx: watch::Receiver<Paused>,
+    found_tx: mpsc::UnboundedSender<FoundBlock>,
 ) {
     let mut scheduler = Scheduler::new();
-    scheduler.run(running, thread_rx, source_reg_rx).await;
+    scheduler.found_tx = Some(found_tx);
+    scheduler
+        .run(running, thread_rx, source_reg_rx, paused_rx)
+        .await;
 }
 
 /// Format seconds as human-readable duration.
@@ -825,6 +1623,10 @@ struct MiningStats {
     /// Uses U256 for overflow safety and to match Share::expected_hashes.
     total_hashes: U256,
     shares_submitted: u64,
+    /// Times a thread stopped producing nonces.
+    stalls: u64,
+    /// Shares or slices caught overlapping another thread's search space.
+    overlaps: u64,
 }
 
 impl Default for MiningStats {
@@ -833,6 +1635,8 @@ impl Default for MiningStats {
             start_time: std::time::Instant::now(),
             total_hashes: U256::ZERO,
             shares_submitted: 0,
+            stalls: 0,
+            overlaps: 0,
         }
     }
 }
@@ -852,7 +1656,778 @@ impl MiningStats {
             uptime = %format_duration(elapsed.as_secs()),
             hashrate = %hashrate_str,
             shares = self.shares_submitted,
+            stalls = self.stalls,
+            overlaps = self.overlaps,
             "Mining status."
         );
     }
 }
+
+/// What a completed yield window says about a thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum YieldCheck {
+    /// Within what chance explains
+    Normal,
+    /// Short, but not for long enough to flag
+    Low,
+    /// Short for [`LOW_YIELD_WINDOWS`] windows running: newly flagged
+    Underperforming,
+    /// Back to normal after being flagged
+    Recovered,
+}
+
+/// A completed yield window.
+#[derive(Debug, Clone, Copy)]
+struct YieldWindow {
+    expected: f64,
+    actual: u64,
+    check: YieldCheck,
+}
+
+/// A thread's shares against what its hashrate predicts, in windows.
+#[derive(Debug, Default)]
+struct ShareYield {
+    /// Shares expected so far in the current window
+    expected: f64,
+    /// Shares found so far in the current window
+    actual: u64,
+    /// Low windows in a row, up to the latest complete one
+    low_windows: u32,
+}
+
+impl ShareYield {
+    /// Add a task's expected and actual shares, returning the window they
+    /// complete, if any.
+    fn record(&mut self, expected: f64, actual: u64) -> Option<YieldWindow> {
+        if !expected.is_finite() {
+            return None;
+        }
+        self.expected += expected;
+        self.actual += actual;
+        if self.expected < YIELD_WINDOW_SHARES {
+            return None;
+        }
+
+        let expected = std::mem::take(&mut self.expected);
+        let actual = std::mem::take(&mut self.actual);
+        let low = (actual as f64) < expected - YIELD_SIGMAS * expected.sqrt();
+        let was_flagged = self.low_windows >= LOW_YIELD_WINDOWS;
+        self.low_windows = if low { self.low_windows + 1 } else { 0 };
+
+        let check = match (low, was_flagged) {
+            (false, true) => YieldCheck::Recovered,
+            (false, false) => YieldCheck::Normal,
+            (true, _) if self.low_windows == LOW_YIELD_WINDOWS => YieldCheck::Underperforming,
+            (true, _) => YieldCheck::Low,
+        };
+        Some(YieldWindow {
+            expected,
+            actual,
+            check,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(nonce: u32) -> ShareKey {
+        ShareKey {
+            job_id: "1".into(),
+            extranonce2: Some(Extranonce2::new(7, 4).unwrap()),
+            ntime: 0x6650_0000,
+            nonce,
+            version: Version::from_consensus(0x2000_0000),
+        }
+    }
+
+    #[test]
+    fn test_submitted_shares_catch_duplicates() {
+        let mut submitted = SubmittedShares::default();
+        assert!(submitted.insert(key(1)));
+        assert!(submitted.insert(key(2)));
+        assert!(!submitted.insert(key(1)));
+
+        // The same nonce under another extranonce2 is a different header
+        let mut other = key(1);
+        other.extranonce2 = Some(Extranonce2::new(8, 4).unwrap());
+        assert!(submitted.insert(other));
+
+        submitted.clear();
+        assert!(submitted.insert(key(1)));
+    }
+
+    #[test]
+    fn test_paused_by_board() {
+        let mut paused = Paused::default();
+        paused.boards.insert("1a2b3c".into());
+        assert!(!paused.all());
+        assert!(paused.board(Some("1a2b3c")));
+        assert!(!paused.board(Some("4d5e6f")));
+        assert!(!paused.board(None));
+
+        // Pausing everything covers threads on no board, like the proxy's
+        paused.schedule = true;
+        assert!(paused.board(Some("4d5e6f")));
+        assert!(paused.board(None));
+    }
+
+    #[test]
+    fn test_slice_len_proportional_to_hashrate() {
+        assert_eq!(en2_slice_len(1000, 0.5), 250);
+        assert_eq!(en2_slice_len(1000, 0.1), 50);
+        assert_eq!(en2_slice_len(1000, 0.0), 1);
+        assert_eq!(en2_slice_len(u64::MAX, 1.0), 1 << 63);
+    }
+
+    #[test]
+    fn test_split_untouched() {
+        let slice = Extranonce2Range::new_range(100, 1_000_000, 4).unwrap();
+        // 2^32 H/s searches one extranonce2 a second
+        let hashrate = HashRate(1 << 32);
+
+        let nonces = 2f64.powi(32);
+
+        let (resume, tail) =
+            split_untouched(&slice, Duration::from_secs(10), hashrate, nonces).unwrap();
+        assert_eq!(resume.min, 110);
+        assert_eq!(resume.max, 100 + 4 * 40 - 1);
+        assert_eq!(tail.min, resume.max + 1);
+        assert_eq!(tail.max, 1_000_000);
+
+        // Nothing to reclaim from a slice the thread could finish
+        let small = Extranonce2Range::new_range(0, 99, 4).unwrap();
+        assert_eq!(
+            split_untouched(&small, Duration::from_secs(10), hashrate, nonces),
+            None
+        );
+
+        // A thread rolling 16 version bits barely leaves its first extranonce2
+        let (resume, _) =
+            split_untouched(&slice, Duration::from_secs(10), hashrate, nonces * 65536.0).unwrap();
+        assert_eq!((resume.min, resume.max), (100, 100));
+    }
+
+    #[test]
+    fn test_share_yield_flags_consistent_shortfall() {
+        let mut share_yield = ShareYield::default();
+
+        // Windows fill over several tasks
+        assert!(share_yield.record(60.0, 55).is_none());
+        let window = share_yield.record(60.0, 60).unwrap();
+        assert_eq!(window.actual, 115);
+        assert_eq!(window.check, YieldCheck::Normal);
+
+        // Chance explains one short window; three running flag the thread
+        assert_eq!(
+            share_yield.record(100.0, 60).unwrap().check,
+            YieldCheck::Low
+        );
+        assert_eq!(
+            share_yield.record(100.0, 60).unwrap().check,
+            YieldCheck::Low
+        );
+        assert_eq!(
+            share_yield.record(100.0, 60).unwrap().check,
+            YieldCheck::Underperforming
+        );
+        assert_eq!(
+            share_yield.record(100.0, 60).unwrap().check,
+            YieldCheck::Low
+        );
+        assert_eq!(
+            share_yield.record(100.0, 95).unwrap().check,
+            YieldCheck::Recovered
+        );
+
+        // A short window between good ones resets the count
+        share_yield.record(100.0, 60);
+        share_yield.record(100.0, 60);
+        share_yield.record(100.0, 100);
+        assert_eq!(
+            share_yield.record(100.0, 60).unwrap().check,
+            YieldCheck::Low
+        );
+
+        // An expectation that couldn't be computed adds nothing
+        assert!(share_yield.record(f64::INFINITY, 0).is_none());
+    }
+
+    #[test]
+    fn test_submitted_shares_bounded() {
+        let mut submitted = SubmittedShares::default();
+        for nonce in 0..MAX_SUBMITTED_SHARES as u32 {
+            submitted.insert(key(nonce));
+        }
+        assert!(submitted.insert(key(u32::MAX)));
+        assert_eq!(submitted.keys.len(), 1);
+    }
+
+    /// Dozens of simulated threads under one scheduler, driven end to end
+    /// through its channels the way the daemon drives it.
+    mod harness {
+        use super::*;
+        use async_trait::async_trait;
+        use bitcoin::hashes::Hash;
+        use bitcoin::{BlockHash, CompactTarget};
+        use std::sync::Mutex;
+        use tokio::task::JoinHandle;
+
+        use crate::asic::hash_thread::{HashThreadCapabilities, HashThreadError, HashThreadStatus};
+        use crate::job_source::test_blocks::block_881423;
+        use crate::job_source::{GeneralPurposeBits, MerkleRootTemplate, VersionTemplate};
+
+        /// Long enough that only a stuck scheduler runs out of it.
+        const DEADLINE: Duration = Duration::from_secs(3600);
+
+        /// A task as a simulated thread received it.
+        #[derive(Debug)]
+        struct Assigned {
+            replace: bool,
+            job_id: String,
+            en2_range: Extranonce2Range,
+            share_tx: mpsc::Sender<Share>,
+        }
+
+        /// Tasks each simulated thread received, oldest first, by thread
+        /// index.
+        type Ledger = Arc<Mutex<Vec<Vec<Assigned>>>>;
+
+        /// Thread that takes its time over every command and records the
+        /// tasks it gets.
+        struct LaggyThread {
+            index: usize,
+            name: String,
+            latency: Duration,
+            capabilities: HashThreadCapabilities,
+            event_rx: Option<mpsc::Receiver<HashThreadEvent>>,
+            ledger: Ledger,
+        }
+
+        impl LaggyThread {
+            async fn take(&mut self, replace: bool, task: HashTask) {
+                tokio::time::sleep(self.latency).await;
+                self.ledger.lock().unwrap()[self.index].push(Assigned {
+                    replace,
+                    job_id: task.template.id.clone(),
+                    en2_range: task.en2_range.expect("scheduler always slices"),
+                    share_tx: task.share_tx,
+                });
+            }
+        }
+
+        #[async_trait]
+        impl HashThread for LaggyThread {
+            fn name(&self) -> &str {
+                &self.name
+            }
+
+            fn capabilities(&self) -> &HashThreadCapabilities {
+                &self.capabilities
+            }
+
+            async fn update_task(
+                &mut self,
+                task: HashTask,
+            ) -> Result<Option<HashTask>, HashThreadError> {
+                self.take(false, task).await;
+                Ok(None)
+            }
+
+            async fn replace_task(
+                &mut self,
+                task: HashTask,
+            ) -> Result<Option<HashTask>, HashThreadError> {
+                self.take(true, task).await;
+                Ok(None)
+            }
+
+            async fn go_idle(&mut self) -> Result<Option<HashTask>, HashThreadError> {
+                tokio::time::sleep(self.latency).await;
+                Ok(None)
+            }
+
+            fn take_event_receiver(&mut self) -> Option<mpsc::Receiver<HashThreadEvent>> {
+                self.event_rx.take()
+            }
+
+            fn status(&self) -> HashThreadStatus {
+                Default::default()
+            }
+        }
+
+        /// A job source as the scheduler sees it.
+        struct Source {
+            event_tx: mpsc::Sender<SourceEvent>,
+            /// Latest hashrate the scheduler reported
+            hashrate_rx: watch::Receiver<HashRate>,
+            /// Shares the scheduler submitted
+            share_rx: mpsc::UnboundedReceiver<SourceShare>,
+        }
+
+        /// A running scheduler and the threads and sources fed to it.
+        struct Harness {
+            running: CancellationToken,
+            thread_tx: mpsc::Sender<Box<dyn HashThread>>,
+            source_reg_tx: mpsc::Sender<SourceRegistration>,
+            _paused_tx: watch::Sender<Paused>,
+            scheduler: JoinHandle<()>,
+            /// Blocks the scheduler found
+            found_rx: mpsc::UnboundedReceiver<FoundBlock>,
+            ledger: Ledger,
+            /// Event senders of the threads still attached, by index;
+            /// dropping one detaches its thread
+            threads: Vec<Option<mpsc::Sender<HashThreadEvent>>>,
+        }
+
+        impl Harness {
+            fn start() -> Self {
+                let running = CancellationToken::new();
+                let (thread_tx, thread_rx) = mpsc::channel(8);
+                let (source_reg_tx, source_reg_rx) = mpsc::channel(8);
+                let (paused_tx, paused_rx) = watch::channel(Paused::default());
+                let (found_tx, found_rx) = mpsc::unbounded_channel();
+                let scheduler = tokio::spawn(task(
+                    running.clone(),
+                    thread_rx,
+                    source_reg_rx,
+                    paused_rx,
+                    found_tx,
+                ));
+                Self {
+                    running,
+                    thread_tx,
+                    source_reg_tx,
+                    _paused_tx: paused_tx,
+                    scheduler,
+                    found_rx,
+                    ledger: Ledger::default(),
+                    threads: Vec::new(),
+                }
+            }
+
+            /// Attach a thread hashing at `hashrate` that takes `latency`
+            /// over each command; returns its index.
+            async fn add_thread(&mut self, hashrate: HashRate, latency: Duration) -> usize {
+                let index = self.threads.len();
+                let (event_tx, event_rx) = mpsc::channel(8);
+                self.ledger.lock().unwrap().push(Vec::new());
+                self.threads.push(Some(event_tx));
+
+                let thread = LaggyThread {
+                    index,
+                    name: format!("laggy-{index}"),
+                    latency,
+                    capabilities: HashThreadCapabilities::new(hashrate),
+                    event_rx: Some(event_rx),
+                    ledger: self.ledger.clone(),
+                };
+                self.thread_tx.send(Box::new(thread)).await.unwrap();
+                index
+            }
+
+            /// Detach thread `index`, as an unplugged board would.
+            fn remove_thread(&mut self, index: usize) {
+                self.threads[index] = None;
+            }
+
+            /// Indices of the threads still attached.
+            fn attached(&self) -> Vec<usize> {
+                (0..self.threads.len())
+                    .filter(|&i| self.threads[i].is_some())
+                    .collect()
+            }
+
+            /// Register a source whose commands are answered as a pool
+            /// connection would, never holding the scheduler up.
+            async fn add_source(&self, name: &str) -> Source {
+                let (event_tx, event_rx) = mpsc::channel(16);
+                let (command_tx, mut command_rx) = mpsc::channel(16);
+                let (hashrate_tx, hashrate_rx) = watch::channel(HashRate::default());
+                let (share_tx, share_rx) = mpsc::unbounded_channel();
+                tokio::spawn(async move {
+                    while let Some(command) = command_rx.recv().await {
+                        match command {
+                            SourceCommand::UpdateHashRate(hashrate) => {
+                                hashrate_tx.send_replace(hashrate);
+                            }
+                            SourceCommand::SubmitShare(share) => {
+                                let _ = share_tx.send(share);
+                            }
+                        }
+                    }
+                });
+
+                self.source_reg_tx
+                    .send(SourceRegistration {
+                        name: name.into(),
+                        event_rx,
+                        command_tx,
+                        max_share_rate: None,
+                    })
+                    .await
+                    .unwrap();
+                Source {
+                    event_tx,
+                    hashrate_rx,
+                    share_rx,
+                }
+            }
+
+            /// Wait until the ledger satisfies `done`.
+            async fn settle(&self, done: impl Fn(&[Vec<Assigned>]) -> bool) {
+                tokio::time::timeout(DEADLINE, async {
+                    while !done(&self.ledger.lock().unwrap()) {
+                        tokio::time::sleep(Duration::from_millis(10)).await;
+                    }
+                })
+                .await
+                .expect("scheduler stopped handing out work");
+            }
+
+            /// Whether the latest task of each attached thread has been
+            /// dropped by the scheduler.
+            fn latest_closed(&self) -> Vec<(usize, bool)> {
+                let ledger = self.ledger.lock().unwrap();
+                self.attached()
+                    .into_iter()
+                    .filter_map(|i| {
+                        let last = ledger[i].last()?;
+                        Some((i, last.share_tx.is_closed()))
+                    })
+                    .collect()
+            }
+
+            async fn shutdown(self) {
+                self.running.cancel();
+                tokio::time::timeout(DEADLINE, self.scheduler)
+                    .await
+                    .expect("scheduler didn't shut down")
+                    .unwrap();
+            }
+        }
+
+        impl Source {
+            /// Wait until the scheduler reports `hashrate`, meaning every
+            /// thread adding up to it has registered.
+            async fn await_hashrate(&mut self, hashrate: HashRate) {
+                tokio::time::timeout(DEADLINE, self.hashrate_rx.wait_for(|&h| h == hashrate))
+                    .await
+                    .expect("threads never registered")
+                    .unwrap();
+            }
+        }
+
+        /// Job `id` with a 4-byte extranonce2 space.
+        fn job(id: &str) -> JobTemplate {
+            JobTemplate {
+                id: id.into(),
+                prev_blockhash: BlockHash::all_zeros(),
+                version: VersionTemplate::new(
+                    Version::from_consensus(0x2000_0000),
+                    GeneralPurposeBits::none(),
+                )
+                .unwrap(),
+                bits: CompactTarget::from_consensus(0x1d00_ffff),
+                share_target: Target::MAX,
+                time: 0x6650_0000,
+                merkle_root: MerkleRootKind::Computed(MerkleRootTemplate::new(
+                    vec![0; 42],
+                    vec![0; 4],
+                    Extranonce2Range::new(4).unwrap(),
+                    vec![0; 60],
+                    Vec::new(),
+                )),
+            }
+        }
+
+        /// Spread of latencies, from instant to slower than a USB round
+        /// trip, varying from thread to thread.
+        fn latency(index: usize) -> Duration {
+            Duration::from_millis((index as u64 * 7) % 50)
+        }
+
+        /// Check no two threads were given overlapping slices of any job.
+        fn assert_disjoint(ledger: &[Vec<Assigned>]) {
+            let mut by_job: HashMap<&str, Vec<(usize, &Extranonce2Range)>> = HashMap::new();
+            for (index, tasks) in ledger.iter().enumerate() {
+                for task in tasks {
+                    by_job
+                        .entry(&task.job_id)
+                        .or_default()
+                        .push((index, &task.en2_range));
+                }
+            }
+            for (job_id, mut slices) in by_job {
+                slices.sort_by_key(|(_, range)| range.min);
+                for pair in slices.windows(2) {
+                    let ((a, first), (b, second)) = (pair[0], pair[1]);
+                    assert!(
+                        a == b || first.max < second.min,
+                        "job {job_id}: thread {a} has {first:?}, thread {b} {second:?}"
+                    );
+                }
+            }
+        }
+
+        #[tokio::test(start_paused = true)]
+        async fn test_work_shared_in_proportion_to_hashrate() {
+            let mut harness = Harness::start();
+            let mut source = harness.add_source("pool").await;
+
+            let hashrates: Vec<HashRate> = (1..=48)
+                .map(|i| HashRate::from_gigahashes(100.0 * i as f64))
+                .collect();
+            for (i, &hashrate) in hashrates.iter().enumerate() {
+                harness.add_thread(hashrate, latency(i)).await;
+            }
+            let total = HashRate(hashrates.iter().map(|h| h.0).sum());
+            source.await_hashrate(total).await;
+
+            source
+                .event_tx
+                .send(SourceEvent::UpdateJob(job("1")))
+                .await
+                .unwrap();
+            harness
+                .settle(|ledger| ledger.iter().all(|tasks| tasks.len() == 1))
+                .await;
+
+            let full = Extranonce2Range::new(4).unwrap().len();
+            {
+                let ledger = harness.ledger.lock().unwrap();
+                for (tasks, hashrate) in ledger.iter().zip(&hashrates) {
+                    let share = hashrate.0 as f64 / total.0 as f64;
+                    assert_eq!(tasks[0].en2_range.len(), en2_slice_len(full, share));
+                }
+                let claimed: u64 = ledger.iter().map(|t| t[0].en2_range.len()).sum();
+                assert!(claimed <= full / 2);
+                assert_disjoint(&ledger);
+            }
+
+            harness.shutdown().await;
+        }
+
+        #[tokio::test(start_paused = true)]
+        async fn test_replace_preempts_update_stacks() {
+            let mut harness = Harness::start();
+            let mut source = harness.add_source("pool").await;
+            for i in 0..16 {
+                harness
+                    .add_thread(HashRate::from_terahashes(1.0), latency(i))
+                    .await;
+            }
+            source.await_hashrate(HashRate::from_terahashes(16.0)).await;
+
+            // An update stacks on the tasks before it: shares for an older
+            // job are still good until the pool says otherwise
+            for id in ["1", "2"] {
+                source
+                    .event_tx
+                    .send(SourceEvent::UpdateJob(job(id)))
+                    .await
+                    .unwrap();
+            }
+            harness
+                .settle(|ledger| ledger.iter().all(|tasks| tasks.len() == 2))
+                .await;
+            for tasks in harness.ledger.lock().unwrap().iter() {
+                assert!(tasks.iter().all(|t| !t.replace && !t.share_tx.is_closed()));
+            }
+
+            // A replacement invalidates every one of them
+            source
+                .event_tx
+                .send(SourceEvent::ReplaceJob(job("3")))
+                .await
+                .unwrap();
+            harness
+                .settle(|ledger| ledger.iter().all(|tasks| tasks.len() == 3))
+                .await;
+            for tasks in harness.ledger.lock().unwrap().iter() {
+                let (new, old) = tasks.split_last().unwrap();
+                assert!(new.replace && !new.share_tx.is_closed());
+                assert!(old.iter().all(|t| t.share_tx.is_closed()));
+            }
+
+            // Clearing leaves the threads nothing to submit against
+            source.event_tx.send(SourceEvent::ClearJobs).await.unwrap();
+            harness
+                .settle(|ledger| {
+                    ledger
+                        .iter()
+                        .flatten()
+                        .all(|task| task.share_tx.is_closed())
+                })
+                .await;
+
+            harness.shutdown().await;
+        }
+
+        #[tokio::test(start_paused = true)]
+        async fn test_block_outside_slice_still_submitted() {
+            let mut harness = Harness::start();
+            let mut source = harness.add_source("pool").await;
+            harness
+                .add_thread(HashRate::from_terahashes(1.0), Duration::ZERO)
+                .await;
+            source.await_hashrate(HashRate::from_terahashes(1.0)).await;
+
+            // A coinbase that parses, so the block's header can be built
+            let size = block_881423::EXTRANONCE2.size();
+            let template = JobTemplate {
+                merkle_root: MerkleRootKind::Computed(MerkleRootTemplate::new(
+                    block_881423::coinbase1_bytes().to_vec(),
+                    block_881423::extranonce1_bytes().to_vec(),
+                    Extranonce2Range::new(size).unwrap(),
+                    block_881423::coinbase2_bytes().to_vec(),
+                    block_881423::MERKLE_BRANCHES.clone(),
+                )),
+                ..job("1")
+            };
+            source
+                .event_tx
+                .send(SourceEvent::UpdateJob(template))
+                .await
+                .unwrap();
+            harness.settle(|ledger| ledger[0].len() == 1).await;
+            let (share_tx, en2_range) = {
+                let ledger = harness.ledger.lock().unwrap();
+                (
+                    ledger[0][0].share_tx.clone(),
+                    ledger[0][0].en2_range.clone(),
+                )
+            };
+
+            // Meets the network target, from past the end of the slice
+            let outside = Extranonce2::new(en2_range.max + 1, size).unwrap();
+            let share = |nonce, extranonce2| Share {
+                nonce,
+                hash: BlockHash::all_zeros(),
+                version: Version::from_consensus(0x2000_0000),
+                ntime: 0x6650_0000,
+                extranonce2: Some(extranonce2),
+                expected_hashes: U256::ZERO,
+            };
+            share_tx.send(share(1, outside)).await.unwrap();
+
+            let block = tokio::time::timeout(DEADLINE, harness.found_rx.recv())
+                .await
+                .expect("block never handed off")
+                .unwrap();
+            assert_eq!(block.job_id, "1");
+            let submitted = tokio::time::timeout(DEADLINE, source.share_rx.recv())
+                .await
+                .expect("block never submitted")
+                .unwrap();
+            assert_eq!((submitted.nonce, submitted.extranonce2), (1, Some(outside)));
+
+            // A repeat is held back; the next block through is a new one
+            share_tx.send(share(1, outside)).await.unwrap();
+            let inside = Extranonce2::new(en2_range.min, size).unwrap();
+            share_tx.send(share(2, inside)).await.unwrap();
+            let submitted = tokio::time::timeout(DEADLINE, source.share_rx.recv())
+                .await
+                .expect("block never submitted")
+                .unwrap();
+            assert_eq!(submitted.nonce, 2);
+            let block = tokio::time::timeout(DEADLINE, harness.found_rx.recv())
+                .await
+                .expect("block never handed off")
+                .unwrap();
+            assert_eq!(block.job_id, "1");
+            assert!(harness.found_rx.try_recv().is_err());
+
+            harness.shutdown().await;
+        }
+
+        #[tokio::test(start_paused = true)]
+        async fn test_survives_job_and_thread_churn() {
+            const JOBS: usize = 150;
+
+            let mut harness = Harness::start();
+            let mut sources = Vec::new();
+            for name in ["pool-a", "pool-b", "pool-c"] {
+                sources.push(harness.add_source(name).await);
+            }
+            for i in 0..32 {
+                harness
+                    .add_thread(HashRate::from_terahashes(1.0), latency(i))
+                    .await;
+            }
+
+            // Every source churns jobs as fast as the scheduler takes them
+            let churners: Vec<_> = sources
+                .iter()
+                .enumerate()
+                .map(|(s, source)| {
+                    let event_tx = source.event_tx.clone();
+                    tokio::spawn(async move {
+                        for n in 0..JOBS {
+                            let template = job(&format!("{s}-{n}"));
+                            let event = match n % 10 {
+                                9 => SourceEvent::ClearJobs,
+                                n if n % 3 == 0 => SourceEvent::ReplaceJob(template),
+                                _ => SourceEvent::UpdateJob(template),
+                            };
+                            event_tx.send(event).await.unwrap();
+                        }
+                        // Marks the end: every thread holding it has had
+                        // all of this source's jobs before it
+                        let end = SourceEvent::ReplaceJob(job(&format!("{s}-end")));
+                        event_tx.send(end).await.unwrap();
+                    })
+                })
+                .collect();
+
+            // While boards come and go, and others run through their slices
+            for round in 0..8 {
+                harness.remove_thread(round * 4);
+                let index = harness
+                    .add_thread(HashRate::from_terahashes(2.0), latency(round))
+                    .await;
+                let exhausted = HashThreadEvent::WorkExhausted { en2_searched: 1 };
+                let event_tx = harness.threads[index - 1].as_ref().unwrap();
+                event_tx.send(exhausted).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(200)).await;
+            }
+
+            tokio::time::timeout(DEADLINE, futures::future::join_all(churners))
+                .await
+                .expect("scheduler stopped taking jobs");
+            // Wait for every end marker, or a job still queued could land
+            // after the final one
+            let attached = harness.attached();
+            let ends: Vec<String> = (0..sources.len()).map(|s| format!("{s}-end")).collect();
+            harness
+                .settle(|ledger| {
+                    attached.iter().all(|&i| {
+                        ends.iter()
+                            .all(|end| ledger[i].iter().any(|task| &task.job_id == end))
+                    })
+                })
+                .await;
+
+            // Still answering: one more job reaches every attached thread
+            sources[0]
+                .event_tx
+                .send(SourceEvent::ReplaceJob(job("final")))
+                .await
+                .unwrap();
+            harness
+                .settle(|ledger| {
+                    attached
+                        .iter()
+                        .all(|&i| ledger[i].last().is_some_and(|task| task.job_id == "final"))
+                })
+                .await;
+            for (index, closed) in harness.latest_closed() {
+                assert!(!closed, "thread {index} holds a dead task");
+            }
+            assert_disjoint(&harness.ledger.lock().unwrap());
+
+            harness.shutdown().await;
+        }
+    }
+}
@@ -0,0 +1,207 @@
+//! Secrets referred to from configuration.
+//!
+//! A pool password in the config file may say where the secret is rather
+//! than be it, so the file can live in dotfiles or version control:
+//!
+//! ```toml
+//! [[pools]]
+//! url = "stratum+tcp://pool.example.com:3333"
+//! worker = "rig1"
+//! password = "credential:pool-password"
+//! ```
+//!
+//! - `env:NAME` is the environment variable `NAME`
+//! - `file:PATH` is the contents of the file at `PATH`, less a trailing
+//!   newline
+//! - `credential:NAME` is the systemd credential `NAME`, as the unit's
+//!   `LoadCredential=` or `SetCredentialEncrypted=` provides it
+//!
+//! Anything else is the secret itself. A reference is resolved only when a
+//! pool is connected to, so it's the reference, not the secret, that the
+//! config API shows and that's written back to the file, and a rotated
+//! secret is picked up when the pool is next started.
+
+use std::env;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+use crate::config::REDACTED;
+
+/// Environment variable systemd passes the credentials directory in.
+const CREDENTIALS_DIRECTORY: &str = "CREDENTIALS_DIRECTORY";
+
+/// Why a secret couldn't be had.
+#[derive(Error, Debug)]
+pub enum SecretError {
+    #[error("'{0}' names no variable, file or credential")]
+    Empty(String),
+
+    #[error("environment variable {0} isn't set")]
+    MissingEnv(String),
+
+    #[error("reading {path}: {source}")]
+    File {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("credential '{0}' isn't a file name")]
+    InvalidCredential(String),
+
+    #[error("no systemd credentials; is the service run with LoadCredential={0}?")]
+    NoCredentials(String),
+}
+
+/// Where a secret is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Source<'a> {
+    Literal(&'a str),
+    Env(&'a str),
+    File(&'a Path),
+    Credential(&'a str),
+}
+
+/// Shows a secret in logs as [`REDACTED`], and a reference as itself.
+pub struct Redacted<'a>(pub &'a str);
+
+impl<'a> Source<'a> {
+    fn parse(value: &'a str) -> Self {
+        if let Some(name) = value.strip_prefix("env:") {
+            Self::Env(name)
+        } else if let Some(path) = value.strip_prefix("file:") {
+            Self::File(Path::new(path))
+        } else if let Some(name) = value.strip_prefix("credential:") {
+            Self::Credential(name)
+        } else {
+            Self::Literal(value)
+        }
+    }
+}
+
+impl fmt::Debug for Redacted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if is_reference(self.0) {
+            fmt::Debug::fmt(self.0, f)
+        } else {
+            fmt::Debug::fmt(REDACTED, f)
+        }
+    }
+}
+
+/// Whether `value` says where a secret is rather than being it.
+pub fn is_reference(value: &str) -> bool {
+    !matches!(Source::parse(value), Source::Literal(_))
+}
+
+/// Check that a reference names something, without reading it; the
+/// secret may only be there where the daemon runs.
+pub fn check(value: &str) -> Result<(), SecretError> {
+    let empty = match Source::parse(value) {
+        Source::Literal(_) => false,
+        Source::Env(name) | Source::Credential(name) => name.is_empty(),
+        Source::File(path) => path.as_os_str().is_empty(),
+    };
+    if empty {
+        return Err(SecretError::Empty(value.to_string()));
+    }
+    if let Source::Credential(name) = Source::parse(value) {
+        credential_path(Path::new(""), name)?;
+    }
+    Ok(())
+}
+
+/// The secret `value` is or refers to.
+pub fn resolve(value: &str) -> Result<String, SecretError> {
+    resolve_with(
+        value,
+        |name| env::var(name).ok(),
+        env::var_os(CREDENTIALS_DIRECTORY).map(PathBuf::from),
+    )
+}
+
+fn resolve_with(
+    value: &str,
+    env_var: impl Fn(&str) -> Option<String>,
+    credentials: Option<PathBuf>,
+) -> Result<String, SecretError> {
+    check(value)?;
+    match Source::parse(value) {
+        Source::Literal(secret) => Ok(secret.to_string()),
+        Source::Env(name) => env_var(name).ok_or_else(|| SecretError::MissingEnv(name.into())),
+        Source::File(path) => read_secret(path),
+        Source::Credential(name) => {
+            let dir = credentials.ok_or_else(|| SecretError::NoCredentials(name.into()))?;
+            read_secret(&credential_path(&dir, name)?)
+        }
+    }
+}
+
+/// Path of the credential `name` in `dir`. Credential names are plain file
+/// names, so one can't reach outside the directory.
+fn credential_path(dir: &Path, name: &str) -> Result<PathBuf, SecretError> {
+    if name.contains('/') || name == "." || name == ".." {
+        return Err(SecretError::InvalidCredential(name.into()));
+    }
+    Ok(dir.join(name))
+}
+
+fn read_secret(path: &Path) -> Result<String, SecretError> {
+    let text = std::fs::read_to_string(path).map_err(|source| SecretError::File {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let secret = text.strip_suffix('\n').unwrap_or(&text);
+    Ok(secret.strip_suffix('\r').unwrap_or(secret).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_sources() {
+        let dir = std::env::temp_dir().join(format!("mujina-secret-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("pool-password"), "s3cret\n").unwrap();
+        let env_var = |name: &str| (name == "POOL_PASS").then(|| "from-env".to_string());
+        let resolve = |value: &str| resolve_with(value, env_var, Some(dir.clone()));
+
+        assert_eq!(resolve("hunter2").unwrap(), "hunter2");
+        assert_eq!(resolve("env:POOL_PASS").unwrap(), "from-env");
+        let file = format!("file:{}", dir.join("pool-password").display());
+        assert_eq!(resolve(&file).unwrap(), "s3cret");
+        assert_eq!(resolve("credential:pool-password").unwrap(), "s3cret");
+
+        assert!(matches!(
+            resolve("env:OTHER"),
+            Err(SecretError::MissingEnv(_))
+        ));
+        assert!(matches!(
+            resolve("credential:../pool-password"),
+            Err(SecretError::InvalidCredential(_))
+        ));
+        assert!(matches!(resolve("file:"), Err(SecretError::Empty(_))));
+        assert!(matches!(
+            resolve_with("credential:pool-password", env_var, None),
+            Err(SecretError::NoCredentials(_))
+        ));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_redacted_shows_only_references() {
+        assert_eq!(
+            format!("{:?}", Redacted("hunter2")),
+            format!("{:?}", REDACTED)
+        );
+        assert_eq!(
+            format!("{:?}", Redacted("env:POOL_PASS")),
+            "\"env:POOL_PASS\""
+        );
+    }
+}
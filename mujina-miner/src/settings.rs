@@ -0,0 +1,343 @@
+//! Per-board settings that outlive the board.
+//!
+//! Overrides set through the API for one board (operating point, fan mode)
+//! and the results of tuning it are kept here, keyed by the board's serial
+//! number. The board's task applies them each time a board with that
+//! serial comes up, so they survive hotplug, crashes and daemon restarts.
+//!
+//! The store is one TOML file, `boards.toml`, in the daemon's state
+//! directory: [`DEFAULT_STATE_DIR`] unless `daemon.state_dir` or
+//! `MUJINA_STATE_DIR` names another.
+//!
+//! ```toml
+//! [boards.BX0001]
+//! frequency_mhz = 525.0
+//! voltage = 1.15
+//! fan = { mode = "fixed", percent = 70 }
+//!
+//! [boards.BX0001.tuning]
+//! frequency_mhz = 550.0
+//! voltage = 1.2
+//! hashrate_ghs = 1210.0
+//! joules_per_terahash = 17.2
+//! tuned_at = 1760486400
+//!
+//! [boards.BX0001.presets.turbo]
+//! voltage = 1.18
+//! ```
+//!
+//! A tuned operating point applies where no override is set. Per-board
+//! `presets` refine the configured presets for this unit alone (see
+//! [`crate::board::preset`]).
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    board::{
+        preset::{Preset, PresetPoint},
+        BoardError, FanMode, OperatingPoint,
+    },
+    tracing::prelude::*,
+};
+
+/// Where the daemon keeps state between runs.
+pub const DEFAULT_STATE_DIR: &str = "/var/lib/mujina";
+
+/// Name of the store in the state directory.
+const FILE_NAME: &str = "boards.toml";
+
+/// Settings kept for one board.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BoardSettings {
+    /// Chip clock frequency in MHz
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frequency_mhz: Option<f32>,
+
+    /// Core voltage in volts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub voltage: Option<f32>,
+
+    /// How the board drives its fans
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fan: Option<FanMode>,
+
+    /// Best operating point found by tuning the board
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tuning: Option<TuningResult>,
+
+    /// Preset the overrides were last set from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preset: Option<Preset>,
+
+    /// This board's refinements of the configured presets
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub presets: BTreeMap<Preset, PresetPoint>,
+}
+
+/// Outcome of tuning one board.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TuningResult {
+    /// Chip clock frequency in MHz
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frequency_mhz: Option<f32>,
+
+    /// Core voltage in volts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub voltage: Option<f32>,
+
+    /// Hashrate measured at this point
+    pub hashrate_ghs: f64,
+
+    /// Energy per unit of work measured at this point, if power was known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub joules_per_terahash: Option<f64>,
+
+    /// When the board was tuned, Unix seconds
+    pub tuned_at: u64,
+}
+
+/// Why settings couldn't be stored or applied.
+#[derive(Debug, thiserror::Error)]
+pub enum SettingsError {
+    #[error("failed to save {path}: {source}")]
+    Save {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("failed to encode settings: {0}")]
+    Encode(#[from] toml::ser::Error),
+
+    #[error(transparent)]
+    Apply(#[from] BoardError),
+}
+
+/// The settings of every board, optionally kept on disk.
+#[derive(Debug, Default)]
+pub struct SettingsStore {
+    /// File the store is kept in, if any
+    path: Option<PathBuf>,
+    boards: BTreeMap<String, BoardSettings>,
+}
+
+/// What's kept on disk.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SettingsFile {
+    #[serde(default)]
+    boards: BTreeMap<String, BoardSettings>,
+}
+
+impl BoardSettings {
+    /// Operating point to apply: each override, or the tuned value where
+    /// there's none.
+    pub fn operating_point(&self) -> OperatingPoint {
+        let tuned = self.tuning.as_ref();
+        OperatingPoint {
+            frequency_mhz: self
+                .frequency_mhz
+                .or_else(|| tuned.and_then(|t| t.frequency_mhz)),
+            voltage: self.voltage.or_else(|| tuned.and_then(|t| t.voltage)),
+        }
+    }
+
+    /// Whether nothing is set.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Check the settings make sense for any board. Whether a voltage is in
+    /// a particular board's range is checked when it's applied.
+    pub fn validate(&self) -> Result<(), String> {
+        let point = self.operating_point();
+        if point
+            .frequency_mhz
+            .is_some_and(|f| !(f.is_finite() && f > 0.0))
+        {
+            return Err("frequency_mhz must be a positive number".into());
+        }
+        if point.voltage.is_some_and(|v| !(v.is_finite() && v >= 0.0)) {
+            return Err("voltage must be a non-negative number".into());
+        }
+        if let Some(FanMode::Fixed { percent }) = self.fan {
+            if percent > 100 {
+                return Err(format!("fan percent {} is over 100", percent));
+            }
+        }
+        for (preset, point) in &self.presets {
+            point
+                .validate()
+                .map_err(|e| format!("{} preset: {}", preset, e))?;
+        }
+        Ok(())
+    }
+}
+
+impl SettingsStore {
+    /// Store kept in memory only, lost on exit.
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    /// Store kept in `dir`, loading what an earlier run saved there.
+    ///
+    /// A file that can't be parsed is moved aside rather than overwritten,
+    /// and the store starts empty.
+    pub fn load(dir: &Path) -> Self {
+        let path = dir.join(FILE_NAME);
+        let boards = match std::fs::read_to_string(&path) {
+            Ok(text) => match toml::from_str::<SettingsFile>(&text) {
+                Ok(file) => file.boards,
+                Err(e) => {
+                    let aside = path.with_extension("toml.invalid");
+                    warn!(path = %path.display(), error = %e, aside = %aside.display(), "Board settings unreadable; starting afresh");
+                    if let Err(e) = std::fs::rename(&path, &aside) {
+                        warn!(path = %path.display(), error = %e, "Failed to move board settings aside");
+                    }
+                    BTreeMap::new()
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => {
+                warn!(path = %path.display(), error = %e, "Failed to read board settings");
+                BTreeMap::new()
+            }
+        };
+        if !boards.is_empty() {
+            debug!(path = %path.display(), boards = boards.len(), "Loaded board settings");
+        }
+        Self {
+            path: Some(path),
+            boards,
+        }
+    }
+
+    /// Settings for the board with serial `id`; empty if none were stored.
+    pub fn get(&self, id: &str) -> BoardSettings {
+        self.boards.get(id).cloned().unwrap_or_default()
+    }
+
+    /// Replace the settings of board `id`, saving the store. Empty settings
+    /// remove the board's entry.
+    pub fn set(&mut self, id: &str, settings: BoardSettings) -> Result<(), SettingsError> {
+        let previous = if settings.is_empty() {
+            self.boards.remove(id)
+        } else {
+            self.boards.insert(id.to_string(), settings)
+        };
+        if let Err(e) = self.save() {
+            // Keep memory matching what's on disk
+            match previous {
+                Some(previous) => self.boards.insert(id.to_string(), previous),
+                None => self.boards.remove(id),
+            };
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn save(&self) -> Result<(), SettingsError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let text = toml::to_string(&SettingsFile {
+            boards: self.boards.clone(),
+        })?;
+
+        // Written aside and renamed into place, so a crash mid-write leaves
+        // the old file intact
+        let save_err = |source| SettingsError::Save {
+            path: path.clone(),
+            source,
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(save_err)?;
+        }
+        let tmp = path.with_extension("toml.tmp");
+        std::fs::write(&tmp, text).map_err(save_err)?;
+        std::fs::rename(&tmp, path).map_err(save_err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tuned() -> TuningResult {
+        TuningResult {
+            frequency_mhz: Some(550.0),
+            voltage: Some(1.2),
+            hashrate_ghs: 1210.0,
+            joules_per_terahash: Some(17.2),
+            tuned_at: 1_760_486_400,
+        }
+    }
+
+    #[test]
+    fn test_override_wins_over_tuning() {
+        let settings = BoardSettings {
+            voltage: Some(1.15),
+            tuning: Some(tuned()),
+            ..Default::default()
+        };
+        assert_eq!(
+            settings.operating_point(),
+            OperatingPoint {
+                frequency_mhz: Some(550.0),
+                voltage: Some(1.15),
+            }
+        );
+    }
+
+    #[test]
+    fn test_settings_survive_reload() {
+        let dir = std::env::temp_dir().join(format!("mujina-settings-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let settings = BoardSettings {
+            frequency_mhz: Some(525.0),
+            fan: Some(FanMode::Fixed { percent: 70 }),
+            tuning: Some(tuned()),
+            preset: Some(Preset::Turbo),
+            presets: BTreeMap::from([(
+                Preset::Turbo,
+                PresetPoint {
+                    voltage: Some(1.18),
+                    ..Default::default()
+                },
+            )]),
+            ..Default::default()
+        };
+        let mut store = SettingsStore::load(&dir);
+        store.set("BX0001", settings.clone()).unwrap();
+        store.set("BX0002", BoardSettings::default()).unwrap();
+
+        let reloaded = SettingsStore::load(&dir);
+        assert_eq!(reloaded.get("BX0001"), settings);
+        assert!(reloaded.get("BX0002").is_empty());
+        assert!(reloaded.get("BX0003").is_empty());
+
+        // Clearing removes the entry from disk
+        let mut store = reloaded;
+        store.set("BX0001", BoardSettings::default()).unwrap();
+        assert!(SettingsStore::load(&dir).boards.is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_unreadable_file_moved_aside() {
+        let dir = std::env::temp_dir().join(format!("mujina-settings-bad-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(FILE_NAME), "boards = 3").unwrap();
+
+        let store = SettingsStore::load(&dir);
+        assert!(store.boards.is_empty());
+        assert!(dir.join("boards.toml.invalid").exists());
+        assert!(!dir.join(FILE_NAME).exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -2,16 +2,45 @@
 //!
 //! This module contains the main client that manages the connection lifecycle,
 //! protocol state, and event emission.
+//!
+//! Shares are submitted without waiting for the pool to answer the one
+//! before: a rig finding many shares on a pool a few hundred milliseconds
+//! away would otherwise be held to one share per round trip. Up to
+//! [`MAX_PENDING_SUBMITS`] `mining.submit` requests are outstanding at
+//! once, each answer matched to its share by request ID. A pool that
+//! leaves one unanswered for [`SUBMIT_TIMEOUT`] is taken to have stalled,
+//! and the session ends; shares still awaiting an answer when a session
+//! ends are handed back as [`ClientEvent::SubmitFailed`], for the source to
+//! resubmit on the resumed session.
 
 use super::connection::Connection;
 use super::error::{StratumError, StratumResult};
 use super::messages::{ClientCommand, ClientEvent, JsonRpcMessage, SubmitParams};
+use super::reject::RejectReason;
+use super::trace::Transcript;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
 use tokio::sync::mpsc;
+use tokio::time::Instant;
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, trace, warn};
 
+/// Version bits we ask to roll: all of the BIP320 general purpose bits (13-28).
+const VERSION_ROLLING_MASK: u32 = 0x1fffe000;
+
+/// How long shares still queued at shutdown may take to submit.
+pub const SHARE_FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long the pool may take to answer a `mining.submit`.
+pub const SUBMIT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Most `mining.submit` requests awaiting an answer at once; further shares
+/// wait in the command channel.
+pub const MAX_PENDING_SUBMITS: usize = 64;
+
 /// Pool connection configuration.
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct PoolConfig {
     /// Pool URL (stratum+tcp://host:port or host:port)
     pub url: String,
@@ -33,6 +62,18 @@ pub struct PoolConfig {
     pub suggested_difficulty: Option<u64>,
 }
 
+impl std::fmt::Debug for PoolConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PoolConfig")
+            .field("url", &self.url)
+            .field("username", &self.username)
+            .field("password", &crate::secret::Redacted(&self.password))
+            .field("user_agent", &self.user_agent)
+            .field("suggested_difficulty", &self.suggested_difficulty)
+            .finish()
+    }
+}
+
 impl Default for PoolConfig {
     fn default() -> Self {
         Self {
@@ -69,8 +110,25 @@ pub struct StratumV1Client {
     /// Auto-incrementing message ID
     next_id: u64,
 
+    /// Subscription ID of an earlier session to ask the pool to resume
+    resume_session: Option<String>,
+
     /// Protocol state (filled after subscription)
     state: Option<ProtocolState>,
+
+    /// Shares submitted and not yet answered, by request ID
+    pending: BTreeMap<u64, PendingSubmit>,
+
+    /// Where to keep a transcript of each session, if anywhere
+    trace_dir: Option<PathBuf>,
+}
+
+/// A `mining.submit` awaiting the pool's answer.
+#[derive(Debug)]
+struct PendingSubmit {
+    params: SubmitParams,
+    /// When the pool must have answered by
+    deadline: Instant,
 }
 
 /// Protocol state after successful subscription.
@@ -82,8 +140,11 @@ struct ProtocolState {
     /// Extranonce2 size in bytes
     extranonce2_size: usize,
 
+    /// Subscription ID, if the pool gave one
+    session_id: Option<String>,
+
     /// Current difficulty (if set)
-    difficulty: Option<u64>,
+    difficulty: Option<f64>,
 
     /// Current version mask (if set)
     version_mask: Option<u32>,
@@ -102,7 +163,10 @@ impl StratumV1Client {
             command_rx: None,
             shutdown,
             next_id: 1,
+            resume_session: None,
             state: None,
+            pending: BTreeMap::new(),
+            trace_dir: None,
         }
     }
 
@@ -119,10 +183,30 @@ impl StratumV1Client {
             command_rx: Some(command_rx),
             shutdown,
             next_id: 1,
+            resume_session: None,
             state: None,
+            pending: BTreeMap::new(),
+            trace_dir: None,
         }
     }
 
+    /// Ask the pool to resume the session with this subscription ID.
+    ///
+    /// A pool that honors the request hands back the same extranonce1, so
+    /// shares found on the earlier session's work are still valid. Pools
+    /// are free to ignore it and start a new session.
+    pub fn with_session(mut self, session_id: Option<String>) -> Self {
+        self.resume_session = session_id;
+        self
+    }
+
+    /// Keep a transcript of the messages exchanged in `dir`; see
+    /// [`super::trace`].
+    pub fn with_trace_dir(mut self, dir: PathBuf) -> Self {
+        self.trace_dir = Some(dir);
+        self
+    }
+
     /// Get next message ID and increment counter.
     fn next_id(&mut self) -> u64 {
         let id = self.next_id;
@@ -216,14 +300,13 @@ impl StratumV1Client {
     ) -> StratumResult<Option<u32>> {
         use serde_json::json;
 
-        // Request GP bits mask (0x1fffe000 = bits 13-28)
         let result = self
             .send_request(
                 conn,
                 "mining.configure",
                 json!([
                     ["version-rolling"],
-                    {"version-rolling.mask": "1fffe000"}
+                    {"version-rolling.mask": format!("{:08x}", VERSION_ROLLING_MASK)}
                 ]),
             )
             .await;
@@ -258,11 +341,21 @@ impl StratumV1Client {
                         StratumError::InvalidMessage("Missing version-rolling.mask".to_string())
                     })?;
 
-                let mask =
-                    u32::from_str_radix(mask_str.trim_start_matches("0x"), 16).map_err(|_| {
+                let pool_mask = u32::from_str_radix(mask_str.trim_start_matches("0x"), 16)
+                    .map_err(|_| {
                         StratumError::InvalidMessage("Invalid version mask hex".to_string())
                     })?;
 
+                // Never roll bits we didn't ask for, whatever the pool says
+                let mask = pool_mask & VERSION_ROLLING_MASK;
+                if mask == 0 {
+                    debug!(
+                        mask = format!("{:#x}", pool_mask),
+                        "Pool authorized no usable version bits"
+                    );
+                    return Ok(None);
+                }
+
                 debug!(
                     mask = format!("{:#x}", mask),
                     "Pool authorized version rolling"
@@ -296,9 +389,11 @@ impl StratumV1Client {
     ) -> StratumResult<()> {
         use serde_json::json;
 
-        let response = self
-            .send_request(conn, "mining.subscribe", json!([&self.config.user_agent]))
-            .await?;
+        let params = match &self.resume_session {
+            Some(session_id) => json!([&self.config.user_agent, session_id]),
+            None => json!([&self.config.user_agent]),
+        };
+        let response = self.send_request(conn, "mining.subscribe", params).await?;
 
         // Parse response
         // Manual parsing for better error context than serde tuple structs
@@ -330,6 +425,7 @@ impl StratumV1Client {
                 self.state = Some(ProtocolState {
                     extranonce1: extranonce1.to_string(),
                     extranonce2_size,
+                    session_id: subscription_id(&arr[0]),
                     difficulty: None,
                     version_mask: authorized_mask,
                 });
@@ -406,21 +502,58 @@ impl StratumV1Client {
 
     /// Submit a share to the pool.
     ///
-    /// Sends `mining.submit` and waits for acceptance/rejection. Emits
-    /// ShareAccepted or ShareRejected events based on pool response.
-    async fn submit(&mut self, conn: &mut Connection, params: SubmitParams) -> StratumResult<bool> {
-        use serde_json::Value;
+    /// Sends `mining.submit` without waiting for the answer, which
+    /// [`Self::answer_submit`] takes when it arrives.
+    async fn submit(&mut self, conn: &mut Connection, params: SubmitParams) -> StratumResult<()> {
+        let id = self.next_id();
+        let msg = JsonRpcMessage::request(
+            id,
+            "mining.submit",
+            serde_json::Value::Array(params.to_stratum_json()),
+        );
+        conn.write_message(&msg).await?;
+        self.pending.insert(
+            id,
+            PendingSubmit {
+                params,
+                deadline: Instant::now() + SUBMIT_TIMEOUT,
+            },
+        );
+        Ok(())
+    }
 
+    /// Take the pool's answer to a submitted share, if `response` is one.
+    ///
+    /// Emits ShareAccepted or ShareRejected, or SubmitFailed if the answer
+    /// makes no sense. Returns false if `response` answers no share.
+    async fn answer_submit(&mut self, response: JsonRpcMessage) -> StratumResult<bool> {
+        let Some(pending) = response.id().and_then(|id| self.pending.remove(&id)) else {
+            return Ok(false);
+        };
+        match self.share_result(&pending.params, response).await {
+            Ok(_) => {}
+            Err(StratumError::UnexpectedResponse(e)) => {
+                warn!(pool = %self.config.url, error = %e, "Failed to submit share");
+                self.event_tx
+                    .send(ClientEvent::SubmitFailed(pending.params))
+                    .await
+                    .map_err(|_| StratumError::Disconnected)?;
+            }
+            Err(e) => return Err(e),
+        }
+        Ok(true)
+    }
+
+    /// Emit ShareAccepted or ShareRejected as the pool's `response` to
+    /// the share `params` says, returning whether it was accepted.
+    async fn share_result(
+        &mut self,
+        params: &SubmitParams,
+        response: JsonRpcMessage,
+    ) -> StratumResult<bool> {
         let job_id = params.job_id.clone();
         let nonce = params.nonce;
 
-        // Convert to Stratum JSON format
-        let submit_json = params.to_stratum_json();
-        let response = self
-            .send_request(conn, "mining.submit", Value::Array(submit_json))
-            .await?;
-
-        // Parse response and emit appropriate event
         match response {
             JsonRpcMessage::Response {
                 result: Some(result),
@@ -438,7 +571,8 @@ impl StratumV1Client {
                     self.event_tx
                         .send(ClientEvent::ShareRejected {
                             job_id,
-                            reason: "Pool returned false".to_string(),
+                            reason: RejectReason::Other,
+                            message: "Pool returned false".to_string(),
                         })
                         .await
                         .map_err(|_| StratumError::Disconnected)?;
@@ -450,19 +584,22 @@ impl StratumV1Client {
             } => {
                 // Pool rejected with error message
                 // Error format: [error_code, "error message", null]
-                let reason = if let Some(arr) = error.as_array() {
-                    arr.get(1)
+                let (code, message) = if let Some(arr) = error.as_array() {
+                    let message = arr
+                        .get(1)
                         .and_then(|v| v.as_str())
                         .unwrap_or("Unknown error")
-                        .to_string()
+                        .to_string();
+                    (arr.first().and_then(|v| v.as_i64()), message)
                 } else {
-                    format!("{:?}", error)
+                    (None, format!("{:?}", error))
                 };
 
                 self.event_tx
                     .send(ClientEvent::ShareRejected {
                         job_id,
-                        reason: reason.clone(),
+                        reason: RejectReason::parse(code, &message),
+                        message,
                     })
                     .await
                     .map_err(|_| StratumError::Disconnected)?;
@@ -535,10 +672,19 @@ impl StratumV1Client {
             ));
         }
 
+        // Pools may send fractional difficulties (e.g., 0.5 or 0.001 on
+        // testnets), so accept any positive, finite number
         let difficulty = arr[0]
-            .as_u64()
+            .as_f64()
             .ok_or_else(|| StratumError::InvalidMessage("difficulty not a number".to_string()))?;
 
+        if difficulty <= 0.0 || !difficulty.is_finite() {
+            return Err(StratumError::InvalidMessage(format!(
+                "difficulty not positive: {}",
+                difficulty
+            )));
+        }
+
         if let Some(state) = &mut self.state {
             state.difficulty = Some(difficulty);
         }
@@ -584,19 +730,128 @@ impl StratumV1Client {
         Ok(())
     }
 
+    /// Submit the shares still queued at shutdown.
+    ///
+    /// New jobs are no longer read. Submits until the command channel
+    /// closes, which the source does once it has passed on what it had,
+    /// then waits for the pool's answers, until [`SHARE_FLUSH_TIMEOUT`]
+    /// runs out. Shares left unanswered are handed back.
+    async fn flush_shares(&mut self, conn: &mut Connection) {
+        let Some(mut command_rx) = self.command_rx.take() else {
+            return;
+        };
+        let deadline = Instant::now() + SHARE_FLUSH_TIMEOUT;
+        let mut submitted = 0;
+
+        let flushed = tokio::time::timeout_at(deadline, async {
+            while let Some(cmd) = command_rx.recv().await {
+                // Difficulty no longer matters once we're leaving
+                if let ClientCommand::SubmitShare(params) = cmd {
+                    self.submit_or_report(conn, params).await;
+                    submitted += 1;
+                }
+            }
+            self.await_answers(conn).await
+        })
+        .await;
+
+        match flushed {
+            Err(_) => warn!(pool = %self.config.url, submitted, "Share flush timed out"),
+            Ok(Err(e)) => warn!(pool = %self.config.url, error = %e, "Share flush failed"),
+            Ok(Ok(())) if submitted > 0 => {
+                debug!(pool = %self.config.url, submitted, "Flushed queued shares");
+            }
+            Ok(Ok(())) => {}
+        }
+        self.return_pending().await;
+    }
+
+    /// Read from the pool until every submitted share is answered,
+    /// ignoring everything else.
+    async fn await_answers(&mut self, conn: &mut Connection) -> StratumResult<()> {
+        while !self.pending.is_empty() {
+            let msg = conn
+                .read_message()
+                .await?
+                .ok_or(StratumError::Disconnected)?;
+            self.answer_submit(msg).await?;
+        }
+        Ok(())
+    }
+
+    /// Submit a share, emitting [`ClientEvent::SubmitFailed`] if the pool
+    /// couldn't be reached.
+    async fn submit_or_report(&mut self, conn: &mut Connection, params: SubmitParams) {
+        if let Err(e) = self.submit(conn, params.clone()).await {
+            warn!(pool = %self.config.url, error = %e, "Failed to submit share");
+            self.event_tx
+                .send(ClientEvent::SubmitFailed(params))
+                .await
+                .ok();
+        }
+    }
+
+    /// Hand back the shares awaiting an answer, oldest first, as
+    /// [`ClientEvent::SubmitFailed`]: whether the pool got them is unknown.
+    async fn return_pending(&mut self) {
+        for (_, pending) in std::mem::take(&mut self.pending) {
+            self.event_tx
+                .send(ClientEvent::SubmitFailed(pending.params))
+                .await
+                .ok();
+        }
+    }
+
+    /// Hand back the shares awaiting an answer or left in the command
+    /// channel when the connection fails, as [`ClientEvent::SubmitFailed`].
+    async fn return_unsent_shares(&mut self) {
+        self.return_pending().await;
+        let Some(mut command_rx) = self.command_rx.take() else {
+            return;
+        };
+        command_rx.close();
+        while let Ok(cmd) = command_rx.try_recv() {
+            if let ClientCommand::SubmitShare(params) = cmd {
+                self.event_tx
+                    .send(ClientEvent::SubmitFailed(params))
+                    .await
+                    .ok();
+            }
+        }
+    }
+
     /// Run the client (main event loop).
     ///
     /// Connects to the pool, subscribes, authorizes, and then enters the main
-    /// event loop to handle notifications and submit shares.
+    /// event loop to handle notifications and submit shares. Shares that
+    /// can't be submitted because the connection failed are handed back as
+    /// [`ClientEvent::SubmitFailed`].
+    pub async fn run(mut self) -> StratumResult<()> {
+        let result = self.run_session().await;
+        if result.is_err() {
+            self.return_unsent_shares().await;
+        }
+        result
+    }
+
+    /// Connect and serve one session until it ends.
     ///
     /// Uses a separate reader task to handle the message router pattern,
     /// allowing requests to wait for responses while processing interleaved
     /// notifications.
-    pub async fn run(mut self) -> StratumResult<()> {
+    async fn run_session(&mut self) -> StratumResult<()> {
         use tracing::{debug, info, warn};
 
         // Connect
         let mut conn = Connection::connect(&self.config.url).await?;
+        if let Some(dir) = &self.trace_dir {
+            match Transcript::open(dir, &self.config.url) {
+                Ok(transcript) => conn = conn.with_transcript(transcript),
+                Err(e) => {
+                    warn!(pool = %self.config.url, error = %e, "Failed to open Stratum transcript");
+                }
+            }
+        }
 
         // Configure version rolling (before subscribe)
         let authorized_mask = self.configure_version_rolling(&mut conn).await?;
@@ -626,6 +881,7 @@ impl StratumV1Client {
             .send(ClientEvent::Subscribed {
                 extranonce1: extranonce1_bytes,
                 extranonce2_size: state.extranonce2_size,
+                session_id: state.session_id.clone(),
             })
             .await
             .map_err(|_| StratumError::Disconnected)?;
@@ -644,6 +900,7 @@ impl StratumV1Client {
 
         // Main event loop
         loop {
+            let submit_deadline = self.pending.values().map(|p| p.deadline).min();
             tokio::select! {
                 // Read messages from pool
                 msg = conn.read_message() => {
@@ -661,11 +918,12 @@ impl StratumV1Client {
                                         }
                                     }
                                 }
-                                JsonRpcMessage::Response { id, .. } => {
-                                    // Response to a request we sent
-                                    // During main loop, we handle responses inline in submit()
-                                    // This would be a stray response - log and ignore
-                                    debug!(msg_id = %id, "Received unexpected response in main loop");
+                                msg @ JsonRpcMessage::Response { id, .. } => {
+                                    // Only shares are submitted in the main loop;
+                                    // anything else is a stray response
+                                    if !self.answer_submit(msg).await? {
+                                        debug!(msg_id = %id, "Received unexpected response in main loop");
+                                    }
                                 }
                                 JsonRpcMessage::Request { id: Some(_), method, .. } => {
                                     // Request with ID from server (unusual, but handle it)
@@ -691,26 +949,44 @@ impl StratumV1Client {
                     }
                 }
 
-                // Commands from external code (if command channel exists)
+                // Commands from external code (if command channel exists),
+                // while there's room for more shares in flight
                 Some(cmd) = async {
                     match &mut self.command_rx {
                         Some(rx) => rx.recv().await,
                         None => std::future::pending().await,
                     }
-                } => {
+                }, if self.pending.len() < MAX_PENDING_SUBMITS => {
                     match cmd {
                         ClientCommand::SubmitShare(params) => {
                             debug!(pool = %self.config.url, job_id = %params.job_id, "Submitting share");
-                            if let Err(e) = self.submit(&mut conn, params).await {
-                                warn!(pool = %self.config.url, error = %e, "Failed to submit share");
+                            // Acceptance/rejection emitted via ShareAccepted/ShareRejected
+                            // events as the pool answers
+                            self.submit_or_report(&mut conn, params).await;
+                        }
+                        ClientCommand::SuggestDifficulty(difficulty) => {
+                            info!(pool = %self.config.url, difficulty, "Suggesting difficulty to pool");
+                            if let Err(e) = self.suggest_difficulty(&mut conn, difficulty).await {
+                                warn!(error = %e, "Failed to suggest difficulty");
                             }
-                            // Acceptance/rejection emitted via ShareAccepted/ShareRejected events
                         }
                     }
                 }
 
+                // A share the pool never answered
+                _ = tokio::time::sleep_until(submit_deadline.unwrap_or_else(Instant::now)),
+                    if submit_deadline.is_some() => {
+                    warn!(
+                        pool = %self.config.url,
+                        pending = self.pending.len(),
+                        "Pool stopped answering shares"
+                    );
+                    return Err(StratumError::Timeout);
+                }
+
                 // Shutdown signal
                 _ = self.shutdown.cancelled() => {
+                    self.flush_shares(&mut conn).await;
                     self.event_tx.send(ClientEvent::Disconnected).await.ok();
                     return Ok(());
                 }
@@ -719,6 +995,19 @@ impl StratumV1Client {
     }
 }
 
+/// The subscription ID from the first element of a `mining.subscribe`
+/// result, a list of `[method, id]` pairs. The `mining.notify` entry's ID
+/// is the one pools resume sessions by.
+fn subscription_id(subscriptions: &serde_json::Value) -> Option<String> {
+    let pairs = subscriptions.as_array()?;
+    let id = |pair: &serde_json::Value| pair.get(1)?.as_str().map(str::to_string);
+    pairs
+        .iter()
+        .find(|pair| pair.get(0).and_then(|m| m.as_str()) == Some("mining.notify"))
+        .or_else(|| pairs.first())
+        .and_then(id)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -792,7 +1081,7 @@ mod tests {
     /// # Environment Variables
     ///
     /// - `MUJINA_POOL_URL` - Pool URL (required, e.g., "stratum+tcp://localhost:3333")
-    /// - `MUJINA_POOL_USER` - Username/wallet address (optional, defaults to test address)
+    /// - `MUJINA_POOL_WORKER` - Username/wallet address (optional, defaults to test address)
     ///
     /// # Running
     ///
@@ -803,7 +1092,7 @@ mod tests {
     ///
     /// # With custom username
     /// MUJINA_POOL_URL="stratum+tcp://localhost:3333" \
-    /// MUJINA_POOL_USER="bc1qce93hy5rhg02s6aeu7mfdvxg76x66pqqtrvzs3.my-worker" \
+    /// MUJINA_POOL_WORKER="bc1qce93hy5rhg02s6aeu7mfdvxg76x66pqqtrvzs3.my-worker" \
     /// cargo test --lib test_pool_from_env -- --ignored --nocapture
     /// ```
     ///
@@ -812,9 +1101,11 @@ mod tests {
     #[tokio::test]
     #[ignore]
     async fn test_pool_from_env() {
-        let pool_url =
-            std::env::var("MUJINA_POOL_URL").expect("MUJINA_POOL_URL environment variable not set");
-        let username = std::env::var("MUJINA_POOL_USER").unwrap_or_else(|_| {
+        let env = crate::config::EnvConfig::from_env().unwrap();
+        let pool_url = env
+            .pool_url
+            .expect("MUJINA_POOL_URL environment variable not set");
+        let username = env.pool_worker.unwrap_or_else(|| {
             "bc1qce93hy5rhg02s6aeu7mfdvxg76x66pqqtrvzs3.mujina-integration-test".to_string()
         });
 
@@ -889,6 +1180,7 @@ mod tests {
                 ClientEvent::Subscribed {
                     extranonce1,
                     extranonce2_size,
+                    ..
                 } => {
                     println!("\n[Subscribed]");
                     println!("  Extranonce1: {}", hex::encode(extranonce1));
@@ -925,7 +1217,7 @@ mod tests {
                 ClientEvent::DifficultyChanged(diff) => {
                     println!("\n[Difficulty Changed]");
                     println!("  Difficulty: {}", diff);
-                    assert!(*diff > 0, "difficulty should be positive");
+                    assert!(*diff > 0.0, "difficulty should be positive");
                     *received_difficulty = true;
                 }
                 ClientEvent::VersionMaskSet(mask) => {
@@ -1049,12 +1341,31 @@ mod tests {
             .expect("Expected DifficultyChanged event");
         match event {
             ClientEvent::DifficultyChanged(diff) => {
-                assert_eq!(diff, 2048);
+                assert_eq!(diff, 2048.0);
             }
             _ => panic!("Expected DifficultyChanged event, got {:?}", event),
         }
     }
 
+    #[tokio::test]
+    async fn test_handle_set_difficulty_fractional() {
+        use serde_json::json;
+
+        let (mut client, mut event_rx) = test_client();
+
+        for expected in [0.5, 0.001] {
+            client
+                .handle_set_difficulty(&json!([expected]))
+                .await
+                .expect("fractional difficulty should be accepted");
+
+            match event_rx.try_recv() {
+                Ok(ClientEvent::DifficultyChanged(diff)) => assert_eq!(diff, expected),
+                other => panic!("Expected DifficultyChanged event, got {:?}", other),
+            }
+        }
+    }
+
     #[tokio::test]
     async fn test_handle_set_difficulty_invalid_params() {
         use serde_json::json;
@@ -1075,6 +1386,14 @@ mod tests {
         let params = json!(["not a number"]);
         let result = client.handle_set_difficulty(&params).await;
         assert!(result.is_err());
+
+        // Zero or negative
+        let params = json!([0]);
+        let result = client.handle_set_difficulty(&params).await;
+        assert!(result.is_err());
+        let params = json!([-1.5]);
+        let result = client.handle_set_difficulty(&params).await;
+        assert!(result.is_err());
     }
 
     #[tokio::test]
@@ -1180,8 +1499,8 @@ mod tests {
             version_bits: Some(0x20000000),
         };
 
-        let accepted = client.submit(&mut conn, params).await.unwrap();
-        assert!(accepted);
+        client.submit(&mut conn, params).await.unwrap();
+        client.await_answers(&mut conn).await.unwrap();
 
         // Verify ShareAccepted event was emitted
         let event = event_rx.try_recv().expect("Expected ShareAccepted event");
@@ -1234,15 +1553,20 @@ mod tests {
             version_bits: None,
         };
 
-        let accepted = client.submit(&mut conn, params).await.unwrap();
-        assert!(!accepted);
+        client.submit(&mut conn, params).await.unwrap();
+        client.await_answers(&mut conn).await.unwrap();
 
         // Verify ShareRejected event was emitted with reason
         let event = event_rx.try_recv().expect("Expected ShareRejected event");
         match event {
-            ClientEvent::ShareRejected { job_id, reason } => {
+            ClientEvent::ShareRejected {
+                job_id,
+                reason,
+                message,
+            } => {
                 assert_eq!(job_id, "job456");
-                assert_eq!(reason, "Low difficulty share");
+                assert_eq!(reason, RejectReason::LowDifficulty);
+                assert_eq!(message, "Low difficulty share");
             }
             _ => panic!("Expected ShareRejected, got {:?}", event),
         }
@@ -1287,17 +1611,185 @@ mod tests {
             version_bits: None,
         };
 
-        let accepted = client.submit(&mut conn, params).await.unwrap();
-        assert!(!accepted);
+        client.submit(&mut conn, params).await.unwrap();
+        client.await_answers(&mut conn).await.unwrap();
 
         // Verify ShareRejected event was emitted
         let event = event_rx.try_recv().expect("Expected ShareRejected event");
         match event {
-            ClientEvent::ShareRejected { job_id, reason } => {
+            ClientEvent::ShareRejected {
+                job_id,
+                reason,
+                message,
+            } => {
                 assert_eq!(job_id, "job789");
-                assert_eq!(reason, "Pool returned false");
+                assert_eq!(reason, RejectReason::Other);
+                assert_eq!(message, "Pool returned false");
             }
             _ => panic!("Expected ShareRejected, got {:?}", event),
         }
     }
+
+    #[test]
+    fn test_subscription_id() {
+        use serde_json::json;
+
+        let subscriptions = json!([
+            ["mining.set_difficulty", "b4b6693b72a50c7116db18d6497cac52"],
+            ["mining.notify", "ae6812eb4cd7735a302a8a9dd95cf71f"]
+        ]);
+        assert_eq!(
+            subscription_id(&subscriptions).as_deref(),
+            Some("ae6812eb4cd7735a302a8a9dd95cf71f")
+        );
+        assert_eq!(
+            subscription_id(&json!([["mining.set_difficulty", "1"]])).as_deref(),
+            Some("1")
+        );
+        assert_eq!(subscription_id(&json!([])), None);
+        assert_eq!(subscription_id(&json!(null)), None);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_resumes_session() {
+        use super::super::connection::Connection;
+        use serde_json::json;
+        use tokio::net::TcpListener;
+
+        let (client, _event_rx) = test_client();
+        let mut client = client.with_session(Some("ae6812eb".into()));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut conn = Connection::new(socket);
+            let msg = conn.read_message().await.unwrap().unwrap();
+            let JsonRpcMessage::Request { params, .. } = &msg else {
+                panic!("Expected request, got {:?}", msg);
+            };
+            let params = params.clone();
+
+            let response = JsonRpcMessage::Response {
+                id: msg.id().unwrap(),
+                result: Some(json!([[["mining.notify", "ae6812eb"]], "08000002", 4])),
+                error: None,
+            };
+            conn.write_message(&response).await.unwrap();
+            params
+        });
+
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let mut conn = Connection::new(stream);
+        client.subscribe(&mut conn, None).await.unwrap();
+
+        assert_eq!(server.await.unwrap(), json!(["test", "ae6812eb"]));
+        let state = client.state.as_ref().unwrap();
+        assert_eq!(state.extranonce1, "08000002");
+        assert_eq!(state.session_id.as_deref(), Some("ae6812eb"));
+    }
+
+    #[tokio::test]
+    async fn test_submit_failure_reported() {
+        use super::super::connection::Connection;
+        use tokio::net::TcpListener;
+
+        let (mut client, mut event_rx) = test_client();
+
+        // The pool reads the share and hangs up without answering
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut conn = Connection::new(socket);
+            conn.read_message().await.unwrap();
+        });
+
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let mut conn = Connection::new(stream);
+
+        let params = SubmitParams {
+            username: "worker".to_string(),
+            job_id: "job123".to_string(),
+            extranonce2: vec![0x01, 0x02, 0x03, 0x04],
+            ntime: 0x12345678,
+            nonce: 0xdeadbeef,
+            version_bits: None,
+        };
+        client.submit_or_report(&mut conn, params.clone()).await;
+        assert!(event_rx.try_recv().is_err());
+
+        // Unanswered when the connection drops, the share is handed back
+        assert!(client.await_answers(&mut conn).await.is_err());
+        client.return_unsent_shares().await;
+        match event_rx.try_recv() {
+            Ok(ClientEvent::SubmitFailed(failed)) => assert_eq!(failed, params),
+            other => panic!("Expected SubmitFailed, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_submits_pipelined() {
+        use super::super::connection::Connection;
+        use serde_json::json;
+        use tokio::net::TcpListener;
+
+        let (mut client, mut event_rx) = test_client();
+
+        // The pool reads all three shares before answering any, then
+        // answers them last first, rejecting the middle one
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut conn = Connection::new(socket);
+            let mut ids = Vec::new();
+            for _ in 0..3 {
+                ids.push(conn.read_message().await.unwrap().unwrap().id().unwrap());
+            }
+            for (i, id) in ids.into_iter().enumerate().rev() {
+                let response = JsonRpcMessage::Response {
+                    id,
+                    result: Some(json!(i != 1)),
+                    error: None,
+                };
+                conn.write_message(&response).await.unwrap();
+            }
+        });
+
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let mut conn = Connection::new(stream);
+
+        for job in ["job0", "job1", "job2"] {
+            let params = SubmitParams {
+                username: "worker".to_string(),
+                job_id: job.to_string(),
+                extranonce2: vec![0x01, 0x02, 0x03, 0x04],
+                ntime: 0x12345678,
+                nonce: 0xdeadbeef,
+                version_bits: None,
+            };
+            client.submit(&mut conn, params).await.unwrap();
+        }
+        assert_eq!(client.pending.len(), 3);
+        client.await_answers(&mut conn).await.unwrap();
+
+        let mut results = Vec::new();
+        while let Ok(event) = event_rx.try_recv() {
+            match event {
+                ClientEvent::ShareAccepted { job_id, .. } => results.push((job_id, true)),
+                ClientEvent::ShareRejected { job_id, .. } => results.push((job_id, false)),
+                other => panic!("Expected share result, got {:?}", other),
+            }
+        }
+        assert_eq!(
+            results,
+            [
+                ("job2".to_string(), true),
+                ("job1".to_string(), false),
+                ("job0".to_string(), true),
+            ]
+        );
+    }
 }
@@ -3,14 +3,33 @@
 //! Stratum v1 uses newline-delimited JSON over TCP. This module provides a
 //! wrapper around tokio's TCP stream that handles buffered reading and writing
 //! of complete JSON-RPC messages.
+//!
+//! Pools differ in how they frame what they send. Several messages often
+//! arrive in one TCP segment, and some pools send a JSON-RPC batch (an
+//! array of messages) on one line. The reader hands either out one message
+//! at a time, in the order sent; responses in a batch needn't be in request
+//! order, as callers match them by ID. Lines are bounded so a misbehaving
+//! pool can't make us buffer without limit.
+//!
+//! A connection given a [`Transcript`] records every line it reads and
+//! writes there.
+
+use std::collections::VecDeque;
 
 use super::error::{StratumError, StratumResult};
 use super::messages::JsonRpcMessage;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
+use super::trace::{Direction, Transcript};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, BufWriter};
 use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
 use tokio::net::TcpStream;
 use tracing::{debug, trace};
 
+/// Longest line accepted from a pool, in bytes.
+///
+/// A `mining.notify` is a few kilobytes even with a large coinbase and a
+/// full merkle branch; this leaves ample room while bounding the buffer.
+pub const MAX_LINE_BYTES: usize = 64 * 1024;
+
 /// Buffered connection for Stratum protocol.
 ///
 /// Wraps a TCP stream with buffered readers/writers optimized for
@@ -25,6 +44,12 @@ pub struct Connection {
 
     /// Line buffer for reading messages
     line_buf: String,
+
+    /// Messages from a batch not yet handed out
+    batched: VecDeque<JsonRpcMessage>,
+
+    /// Where lines exchanged are recorded, if anywhere
+    transcript: Option<Transcript>,
 }
 
 impl Connection {
@@ -37,9 +62,17 @@ impl Connection {
             reader: BufReader::new(read_half),
             writer: BufWriter::new(write_half),
             line_buf: String::with_capacity(4096),
+            batched: VecDeque::new(),
+            transcript: None,
         }
     }
 
+    /// Record the lines read and written in `transcript`.
+    pub fn with_transcript(mut self, transcript: Transcript) -> Self {
+        self.transcript = Some(transcript);
+        self
+    }
+
     /// Connect to a Stratum pool.
     ///
     /// Parses the URL, establishes TCP connection, and wraps it in a buffered
@@ -66,14 +99,23 @@ impl Connection {
     /// Read one complete JSON-RPC message.
     ///
     /// Reads a newline-delimited line from the stream and deserializes it as
-    /// JSON-RPC. Returns `None` if the connection is closed cleanly. Skips
-    /// empty lines automatically.
+    /// JSON-RPC, or as a batch whose messages are returned one per call.
+    /// Returns `None` if the connection is closed cleanly. Skips empty lines
+    /// and batches automatically.
+    ///
+    /// A line longer than [`MAX_LINE_BYTES`] fails with
+    /// [`StratumError::LineTooLong`]; the stream is then mid-line, so the
+    /// connection should be dropped.
     pub async fn read_message(&mut self) -> StratumResult<Option<JsonRpcMessage>> {
         loop {
+            if let Some(msg) = self.batched.pop_front() {
+                return Ok(Some(msg));
+            }
+
             self.line_buf.clear();
 
-            let n = self
-                .reader
+            let n = (&mut self.reader)
+                .take(MAX_LINE_BYTES as u64 + 1)
                 .read_line(&mut self.line_buf)
                 .await
                 .map_err(StratumError::Io)?;
@@ -82,6 +124,11 @@ impl Connection {
                 // EOF - connection closed
                 return Ok(None);
             }
+            if n > MAX_LINE_BYTES && !self.line_buf.ends_with('\n') {
+                return Err(StratumError::LineTooLong {
+                    limit: MAX_LINE_BYTES,
+                });
+            }
 
             let line = self.line_buf.trim();
             if line.is_empty() {
@@ -90,10 +137,19 @@ impl Connection {
             }
 
             trace!(rx = %line, "Received message");
+            if let Some(transcript) = &mut self.transcript {
+                transcript.record(Direction::Rx, line);
+            }
 
-            let msg = serde_json::from_str(line).map_err(|e| {
+            let parse_error = |e: serde_json::Error| {
                 StratumError::InvalidMessage(format!("Failed to parse JSON: {}, line: {}", e, line))
-            })?;
+            };
+            if line.starts_with('[') {
+                let batch: Vec<JsonRpcMessage> = serde_json::from_str(line).map_err(parse_error)?;
+                self.batched.extend(batch);
+                continue;
+            }
+            let msg = serde_json::from_str(line).map_err(parse_error)?;
 
             return Ok(Some(msg));
         }
@@ -106,6 +162,9 @@ impl Connection {
     pub async fn write_message(&mut self, msg: &JsonRpcMessage) -> StratumResult<()> {
         let json = serde_json::to_string(msg)?;
         trace!(tx = %json, "Sending message");
+        if let Some(transcript) = &mut self.transcript {
+            transcript.record(Direction::Tx, &json);
+        }
 
         self.writer.write_all(json.as_bytes()).await?;
         self.writer.write_all(b"\n").await?;
@@ -151,4 +210,98 @@ mod tests {
         assert_eq!(response.id(), Some(1));
         assert_eq!(response.method(), Some("test.method"));
     }
+
+    #[tokio::test]
+    async fn test_transcript_records_both_ways() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut conn = Connection::new(socket);
+            while let Ok(Some(msg)) = conn.read_message().await {
+                conn.write_message(&msg).await.unwrap();
+            }
+        });
+
+        let dir = std::env::temp_dir().join(format!("mujina-conn-trace-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let url = format!("tcp://{}", addr);
+        let transcript = Transcript::open(&dir, &url).unwrap();
+        let mut conn = Connection::connect(&url)
+            .await
+            .unwrap()
+            .with_transcript(transcript);
+
+        let request = JsonRpcMessage::request(1, "test.method", json!([]));
+        conn.write_message(&request).await.unwrap();
+        conn.read_message().await.unwrap().unwrap();
+
+        let path = std::fs::read_dir(&dir)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+            .path();
+        let dirs: Vec<String> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["dir"].to_string())
+            .collect();
+        assert_eq!(dirs, [r#""connect""#, r#""tx""#, r#""rx""#]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Connection to a server that sends `bytes` in one write, then waits.
+    async fn connection_receiving(bytes: Vec<u8>) -> Connection {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            socket.write_all(&bytes).await.unwrap();
+            std::future::pending::<()>().await;
+        });
+        Connection::new(TcpStream::connect(addr).await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_reads_coalesced_and_batched_messages() {
+        let bytes = concat!(
+            r#"{"id":null,"method":"mining.set_difficulty","params":[512]}"#,
+            "\n",
+            r#"[{"id":3,"result":true,"error":null},{"id":2,"result":false,"error":null}]"#,
+            "\n\n",
+            r#"{"id":4,"result":true,"error":null}"#,
+            "\n",
+        );
+        let mut conn = connection_receiving(bytes.into()).await;
+
+        let first = conn.read_message().await.unwrap().unwrap();
+        assert_eq!(first.method(), Some("mining.set_difficulty"));
+        let mut ids = Vec::new();
+        for _ in 0..3 {
+            ids.push(conn.read_message().await.unwrap().unwrap().id().unwrap());
+        }
+        // Batch order kept; callers match responses by ID
+        assert_eq!(ids, [3, 2, 4]);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_line_rejected() {
+        // Padded to exactly the limit: still fine
+        let msg = r#"{"id":1,"result":true,"error":null}"#;
+        let mut bytes = msg.as_bytes().to_vec();
+        bytes.resize(MAX_LINE_BYTES, b' ');
+        bytes.push(b'\n');
+        // One byte over, with no end in sight
+        bytes.extend(std::iter::repeat_n(b' ', MAX_LINE_BYTES + 1));
+
+        let mut conn = connection_receiving(bytes).await;
+        assert_eq!(conn.read_message().await.unwrap().unwrap().id(), Some(1));
+        assert!(matches!(
+            conn.read_message().await,
+            Err(StratumError::LineTooLong {
+                limit: MAX_LINE_BYTES
+            })
+        ));
+    }
 }
@@ -17,6 +17,10 @@ pub enum StratumError {
     #[error("Invalid message format: {0}")]
     InvalidMessage(String),
 
+    /// Pool sent a line longer than we're willing to buffer
+    #[error("Line exceeds {limit} bytes")]
+    LineTooLong { limit: usize },
+
     /// Pool returned an error response
     #[error("Pool error: {0}")]
     PoolError(String),
@@ -10,6 +10,8 @@ use bitcoin::{BlockHash, CompactTarget, TxMerkleNode};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use super::reject::RejectReason;
+
 /// Events emitted by the Stratum client.
 ///
 /// These events are sent via channel to the client consumer
@@ -32,13 +34,16 @@ pub enum ClientEvent {
         extranonce1: Vec<u8>,
         /// Extranonce2 size in bytes
         extranonce2_size: usize,
+        /// Subscription ID, which may be passed back on reconnect to ask
+        /// the pool to resume the session with the same extranonce1
+        session_id: Option<String>,
     },
 
     /// New mining job received from pool
     NewJob(JobNotification),
 
-    /// Difficulty changed
-    DifficultyChanged(u64),
+    /// Difficulty changed (may be fractional, always positive and finite)
+    DifficultyChanged(f64),
 
     /// Version mask set (for version rolling)
     VersionMaskSet(u32),
@@ -55,13 +60,21 @@ pub enum ClientEvent {
     ShareRejected {
         /// Job ID that was rejected
         job_id: String,
-        /// Rejection reason from pool
-        reason: String,
+        /// Why, as far as the pool's answer says
+        reason: RejectReason,
+        /// Rejection message from pool
+        message: String,
     },
 
     /// Disconnected from pool
     Disconnected,
 
+    /// A share couldn't be submitted because the connection failed.
+    ///
+    /// The pool never answered, so the share may be worth retrying in a
+    /// resumed session.
+    SubmitFailed(SubmitParams),
+
     /// Error occurred (non-fatal, client may continue)
     Error(String),
 }
@@ -73,6 +86,9 @@ pub enum ClientEvent {
 pub enum ClientCommand {
     /// Submit a share to the pool
     SubmitShare(SubmitParams),
+
+    /// Ask the pool for a new share difficulty (`mining.suggest_difficulty`)
+    SuggestDifficulty(u64),
 }
 
 /// Mining job notification from pool (mining.notify).
@@ -174,6 +190,28 @@ impl JobNotification {
             clean_jobs,
         })
     }
+
+    /// Convert to Stratum JSON array parameters (the inverse of
+    /// `from_stratum_params`), for serving work to downstream miners.
+    pub fn to_stratum_params(&self) -> Vec<Value> {
+        let branches = self
+            .merkle_branches
+            .iter()
+            .map(|branch| Value::String(hex::encode(branch.as_byte_array())))
+            .collect();
+
+        vec![
+            Value::String(self.job_id.clone()),
+            Value::String(encode_block_hash(&self.prev_hash)),
+            Value::String(hex::encode(&self.coinbase1)),
+            Value::String(hex::encode(&self.coinbase2)),
+            Value::Array(branches),
+            Value::String(format!("{:08x}", self.version.to_consensus() as u32)),
+            Value::String(format!("{:08x}", self.nbits.to_consensus())),
+            Value::String(format!("{:08x}", self.ntime)),
+            Value::Bool(self.clean_jobs),
+        ]
+    }
 }
 
 /// Parse a block hash from Stratum hex string.
@@ -244,6 +282,16 @@ fn parse_block_hash(hex: &str) -> Result<BlockHash, String> {
     BlockHash::from_slice(&bytes).map_err(|e| format!("block hash parse: {}", e))
 }
 
+/// Encode a block hash in Stratum's word-swapped hex (the inverse of
+/// [`parse_block_hash`]).
+fn encode_block_hash(hash: &BlockHash) -> String {
+    let mut bytes = hash.to_byte_array();
+    for chunk in bytes.chunks_mut(4) {
+        chunk.reverse();
+    }
+    hex::encode(bytes)
+}
+
 /// Parse a merkle node from Stratum hex string.
 fn parse_merkle_node(hex: &str) -> Result<TxMerkleNode, String> {
     let bytes = hex::decode(hex).map_err(|e| format!("merkle node hex: {}", e))?;
@@ -254,7 +302,7 @@ fn parse_merkle_node(hex: &str) -> Result<TxMerkleNode, String> {
 }
 
 /// Parameters for submitting a share to the pool.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SubmitParams {
     /// Worker username
     pub username: String,
@@ -298,6 +346,53 @@ impl SubmitParams {
 
         params
     }
+
+    /// Parse from Stratum JSON array parameters (the inverse of
+    /// `to_stratum_json`).
+    ///
+    /// The miner never receives mining.submit, but tools that decode captured
+    /// sessions do.
+    pub fn from_stratum_params(params: &[Value]) -> Result<Self, String> {
+        if params.len() < 5 {
+            return Err("mining.submit params too short".to_string());
+        }
+
+        let username = params[0]
+            .as_str()
+            .ok_or("username not a string")?
+            .to_string();
+        let job_id = params[1].as_str().ok_or("job_id not a string")?.to_string();
+
+        let extranonce2_str = params[2].as_str().ok_or("extranonce2 not a string")?;
+        let extranonce2 =
+            hex::decode(extranonce2_str).map_err(|e| format!("extranonce2 hex: {}", e))?;
+
+        let ntime_str = params[3].as_str().ok_or("ntime not a string")?;
+        let ntime = u32::from_str_radix(ntime_str, 16).map_err(|e| format!("ntime hex: {}", e))?;
+
+        let nonce_str = params[4].as_str().ok_or("nonce not a string")?;
+        let nonce = u32::from_str_radix(nonce_str, 16).map_err(|e| format!("nonce hex: {}", e))?;
+
+        let version_bits = match params.get(5) {
+            Some(value) => {
+                let bits_str = value.as_str().ok_or("version_bits not a string")?;
+                Some(
+                    u32::from_str_radix(bits_str, 16)
+                        .map_err(|e| format!("version_bits hex: {}", e))?,
+                )
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            username,
+            job_id,
+            extranonce2,
+            ntime,
+            nonce,
+            version_bits,
+        })
+    }
 }
 
 /// JSON-RPC message envelope.
@@ -363,10 +458,6 @@ impl JsonRpcMessage {
     }
 
     /// Get the message ID if present.
-    #[cfg_attr(
-        not(test),
-        expect(dead_code, reason = "will be used as client matures")
-    )]
     pub fn id(&self) -> Option<u64> {
         match self {
             JsonRpcMessage::Request { id, .. } => *id,
@@ -375,19 +466,11 @@ impl JsonRpcMessage {
     }
 
     /// Check if this is a notification (request without ID).
-    #[cfg_attr(
-        not(test),
-        expect(dead_code, reason = "will be used as client matures")
-    )]
     pub fn is_notification(&self) -> bool {
         matches!(self, JsonRpcMessage::Request { id: None, .. })
     }
 
     /// Get the method name for requests.
-    #[cfg_attr(
-        not(test),
-        expect(dead_code, reason = "will be used as client matures")
-    )]
     pub fn method(&self) -> Option<&str> {
         match self {
             JsonRpcMessage::Request { method, .. } => Some(method),
@@ -558,6 +641,33 @@ mod tests {
         assert_eq!(json[4], Value::String("55667788".to_string()));
     }
 
+    #[test]
+    fn test_submit_params_round_trip() {
+        for version_bits in [Some(0x1fffe000), None] {
+            let params = SubmitParams {
+                username: "worker1".to_string(),
+                job_id: "job123".to_string(),
+                extranonce2: vec![0x00, 0x00, 0x00, 0x2a],
+                ntime: 0x65432100,
+                nonce: 0x12345678,
+                version_bits,
+            };
+
+            let json = params.to_stratum_json();
+            assert_eq!(SubmitParams::from_stratum_params(&json), Ok(params));
+        }
+
+        assert!(SubmitParams::from_stratum_params(&[json!("worker1")]).is_err());
+        assert!(SubmitParams::from_stratum_params(&[
+            json!("worker1"),
+            json!("job123"),
+            json!("zz"),
+            json!("65432100"),
+            json!("12345678"),
+        ])
+        .is_err());
+    }
+
     #[test]
     fn test_job_notification_minimal_params() {
         // Minimum valid params array
@@ -642,6 +752,18 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_job_notification_round_trips_capture() {
+        use crate::asic::bm13xx::test_data::stratum_json;
+
+        let json: serde_json::Value = serde_json::from_str(stratum_json::MINING_NOTIFY)
+            .expect("Failed to parse MINING_NOTIFY JSON");
+        let params = json["params"].as_array().expect("params not an array");
+
+        let job = JobNotification::from_stratum_params(params).unwrap();
+        assert_eq!(&job.to_stratum_params(), params);
+    }
+
     /// Rosetta stone test: SubmitParams serialization matches wire format.
     ///
     /// Validates that SubmitParams::to_stratum_json() produces the correct
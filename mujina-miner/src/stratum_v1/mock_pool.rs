@@ -0,0 +1,494 @@
+//! An in-process Stratum v1 pool, for end-to-end tests.
+//!
+//! [`MockPool`] listens on a local port and serves the pool side of a
+//! session: it answers mining.configure, subscribe and authorize, sends a
+//! difficulty and a job, and checks every mining.submit the way a pool
+//! would. Each submission is handed to the test as a [`Submission`] with
+//! the pool's verdict, so a test can drive real hashing through the whole
+//! miner and look at exactly what reached the pool.
+//!
+//! ```ignore
+//! let mut pool = MockPool::new()
+//!     .with_difficulty(1.0 / 65536.0)
+//!     .with_vardiff(4, 2.0)
+//!     .start()
+//!     .await;
+//! // ... point a source at pool.url() ...
+//! let submission = pool.next_submission().await;
+//! assert_eq!(submission.verdict, Verdict::Accepted);
+//! ```
+//!
+//! Jobs are built from block 881,423 with the current time, so they pass
+//! the source's checks for mainnet. A share is checked against the
+//! difficulty in force when its job was sent: difficulty changes apply to
+//! the next job, as clients expect.
+
+use std::collections::HashSet;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bitcoin::block::{Header as BlockHeader, Version};
+use serde_json::{json, Value};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use super::validation::MAX_NTIME_DRIFT;
+use super::{Connection, JobNotification, JsonRpcMessage, StratumResult, SubmitParams};
+use crate::job_source::test_blocks::block_881423;
+use crate::job_source::{Extranonce2, Extranonce2Range, MerkleRootTemplate};
+use crate::types::Difficulty;
+
+/// Version bits the pool lets miners roll (BIP320).
+const VERSION_ROLLING_MASK: u32 = 0x1fff_e000;
+
+/// Extranonce2 size handed out at subscribe.
+const EXTRANONCE2_SIZE: usize = 4;
+
+/// How long a test waits for a submission before giving up.
+const SUBMISSION_TIMEOUT: Duration = Duration::from_secs(60);
+
+// Error codes as pools commonly send them
+const ERR_OTHER: i64 = 20;
+const ERR_JOB_NOT_FOUND: i64 = 21;
+const ERR_DUPLICATE: i64 = 22;
+const ERR_LOW_DIFFICULTY: i64 = 23;
+const ERR_UNAUTHORIZED: i64 = 24;
+const ERR_NOT_SUBSCRIBED: i64 = 25;
+
+/// Settings of a pool not yet started.
+#[derive(Debug, Clone)]
+pub(crate) struct MockPool {
+    difficulty: f64,
+    vardiff: Option<(u32, f64)>,
+    rejects: Option<(u32, i64, &'static str)>,
+}
+
+/// What the pool made of a submitted share.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Verdict {
+    /// Valid, and accepted
+    Accepted,
+    /// Valid, but refused as the pool was told to refuse it
+    Refused { code: i64, message: &'static str },
+    /// Malformed, or not meeting its job's difficulty
+    Invalid { code: i64, message: &'static str },
+}
+
+/// A mining.submit as it reached the pool.
+#[derive(Debug, Clone)]
+pub(crate) struct Submission {
+    /// The params array, untouched
+    pub raw: Vec<Value>,
+    /// The params, if they parsed
+    pub params: Option<SubmitParams>,
+    /// Difficulty of the share's job
+    pub difficulty: f64,
+    pub verdict: Verdict,
+}
+
+/// A running pool; stops when dropped.
+pub(crate) struct MockPoolHandle {
+    url: String,
+    submissions: mpsc::UnboundedReceiver<Submission>,
+    shutdown: CancellationToken,
+}
+
+/// A job sent on a session.
+struct SentJob {
+    notification: JobNotification,
+    difficulty: f64,
+    submitted: HashSet<(Vec<u8>, u32, u32, Option<u32>)>,
+}
+
+/// The pool side of one miner's connection.
+struct Session {
+    conn: Connection,
+    settings: MockPool,
+    submissions: mpsc::UnboundedSender<Submission>,
+    subscribed: bool,
+    worker: Option<String>,
+    version_mask: u32,
+    difficulty: f64,
+    jobs: Vec<SentJob>,
+    /// Valid shares since the difficulty last changed
+    accepted: u32,
+    /// Valid shares of the session, for the reject rule
+    valid: u32,
+}
+
+impl MockPool {
+    /// A pool at difficulty 1 that accepts every valid share.
+    pub(crate) fn new() -> Self {
+        Self {
+            difficulty: 1.0,
+            vardiff: None,
+            rejects: None,
+        }
+    }
+
+    /// Start sessions at `difficulty`; fractions make CPU hashing quick.
+    pub(crate) fn with_difficulty(mut self, difficulty: f64) -> Self {
+        self.difficulty = difficulty;
+        self
+    }
+
+    /// Multiply the difficulty by `factor` after every `shares` valid
+    /// shares of a session, sending a new job at the new difficulty.
+    pub(crate) fn with_vardiff(mut self, shares: u32, factor: f64) -> Self {
+        self.vardiff = Some((shares, factor));
+        self
+    }
+
+    /// Refuse every `every`th valid share of a session with error `code`
+    /// and `message`, as a pool calling it stale or duplicate would.
+    pub(crate) fn with_rejects(mut self, every: u32, code: i64, message: &'static str) -> Self {
+        self.rejects = Some((every, code, message));
+        self
+    }
+
+    /// Listen on a free local port and serve connections until the handle
+    /// is dropped.
+    pub(crate) async fn start(self) -> MockPoolHandle {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("stratum+tcp://{}", listener.local_addr().unwrap());
+        let (submission_tx, submissions) = mpsc::unbounded_channel();
+        let shutdown = CancellationToken::new();
+
+        let stop = shutdown.clone();
+        tokio::spawn(async move {
+            loop {
+                let stream = tokio::select! {
+                    accepted = listener.accept() => match accepted {
+                        Ok((stream, _)) => stream,
+                        Err(_) => break,
+                    },
+                    _ = stop.cancelled() => break,
+                };
+                let session = Session::new(stream, self.clone(), submission_tx.clone());
+                let stop = stop.clone();
+                tokio::spawn(async move {
+                    tokio::select! {
+                        _ = session.run() => {}
+                        _ = stop.cancelled() => {}
+                    }
+                });
+            }
+        });
+
+        MockPoolHandle {
+            url,
+            submissions,
+            shutdown,
+        }
+    }
+}
+
+impl MockPoolHandle {
+    /// URL to point a Stratum source at.
+    pub(crate) fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// The next share submitted on any session.
+    ///
+    /// Panics if none arrives within a minute.
+    pub(crate) async fn next_submission(&mut self) -> Submission {
+        tokio::time::timeout(SUBMISSION_TIMEOUT, self.submissions.recv())
+            .await
+            .expect("no share reached the pool")
+            .expect("pool stopped")
+    }
+}
+
+impl Drop for MockPoolHandle {
+    fn drop(&mut self) {
+        self.shutdown.cancel();
+    }
+}
+
+impl Session {
+    fn new(
+        stream: TcpStream,
+        settings: MockPool,
+        submissions: mpsc::UnboundedSender<Submission>,
+    ) -> Self {
+        Self {
+            conn: Connection::new(stream),
+            difficulty: settings.difficulty,
+            settings,
+            submissions,
+            subscribed: false,
+            worker: None,
+            version_mask: 0,
+            jobs: Vec::new(),
+            accepted: 0,
+            valid: 0,
+        }
+    }
+
+    async fn run(mut self) -> StratumResult<()> {
+        while let Some(message) = self.conn.read_message().await? {
+            let JsonRpcMessage::Request { id, method, params } = message else {
+                continue;
+            };
+            let reply = match method.as_str() {
+                "mining.configure" => Ok(self.configure(&params)),
+                "mining.subscribe" => self.subscribe(),
+                "mining.authorize" => self.authorize(&params),
+                "mining.submit" => self.submit(&params),
+                "mining.suggest_difficulty" => Ok(Value::Bool(true)),
+                _ => Err((ERR_OTHER, "Unsupported method")),
+            };
+            let authorized = method == "mining.authorize" && reply.is_ok();
+
+            if let Some(id) = id {
+                let response = match reply {
+                    Ok(result) => JsonRpcMessage::Response {
+                        id,
+                        result: Some(result),
+                        error: None,
+                    },
+                    Err((code, message)) => JsonRpcMessage::Response {
+                        id,
+                        result: None,
+                        error: Some(json!([code, message, null])),
+                    },
+                };
+                self.conn.write_message(&response).await?;
+            }
+
+            if authorized {
+                self.send_job(true).await?;
+            } else if self.retarget_due() {
+                self.accepted = 0;
+                self.difficulty *= self.settings.vardiff.map_or(1.0, |(_, factor)| factor);
+                self.send_job(false).await?;
+            }
+        }
+        Ok(())
+    }
+
+    fn configure(&mut self, params: &Value) -> Value {
+        let rolling = params
+            .get(0)
+            .and_then(Value::as_array)
+            .is_some_and(|extensions| extensions.iter().any(|e| e == "version-rolling"));
+        if !rolling {
+            return json!({});
+        }
+        let requested = params
+            .get(1)
+            .and_then(|options| options.get("version-rolling.mask"))
+            .and_then(Value::as_str)
+            .and_then(|mask| u32::from_str_radix(mask, 16).ok())
+            .unwrap_or(VERSION_ROLLING_MASK);
+        self.version_mask = requested & VERSION_ROLLING_MASK;
+        json!({
+            "version-rolling": true,
+            "version-rolling.mask": format!("{:08x}", self.version_mask),
+        })
+    }
+
+    fn subscribe(&mut self) -> Result<Value, (i64, &'static str)> {
+        self.subscribed = true;
+        Ok(json!([
+            [["mining.set_difficulty", "1"], ["mining.notify", "1"]],
+            hex::encode(block_881423::extranonce1_bytes()),
+            EXTRANONCE2_SIZE
+        ]))
+    }
+
+    fn authorize(&mut self, params: &Value) -> Result<Value, (i64, &'static str)> {
+        if !self.subscribed {
+            return Err((ERR_NOT_SUBSCRIBED, "Not subscribed"));
+        }
+        let worker = params.get(0).and_then(Value::as_str).unwrap_or_default();
+        self.worker = Some(worker.to_string());
+        Ok(Value::Bool(true))
+    }
+
+    /// Whether enough valid shares came in to raise the difficulty.
+    fn retarget_due(&self) -> bool {
+        self.settings
+            .vardiff
+            .is_some_and(|(shares, _)| self.accepted >= shares)
+    }
+
+    /// Send the current difficulty and a fresh job.
+    async fn send_job(&mut self, clean: bool) -> StratumResult<()> {
+        self.conn
+            .write_message(&JsonRpcMessage::notification(
+                "mining.set_difficulty",
+                json!([self.difficulty]),
+            ))
+            .await?;
+
+        let ntime = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as u32);
+        let notification = JobNotification {
+            job_id: format!("{:x}", self.jobs.len() + 1),
+            prev_hash: *block_881423::PREV_BLOCKHASH,
+            coinbase1: block_881423::coinbase1_bytes().to_vec(),
+            coinbase2: block_881423::coinbase2_bytes().to_vec(),
+            merkle_branches: block_881423::MERKLE_BRANCHES.clone(),
+            version: Version::from_consensus(
+                block_881423::VERSION.to_consensus() & !(VERSION_ROLLING_MASK as i32),
+            ),
+            nbits: *block_881423::BITS,
+            ntime,
+            clean_jobs: clean,
+        };
+        self.conn
+            .write_message(&JsonRpcMessage::notification(
+                "mining.notify",
+                Value::Array(notification.to_stratum_params()),
+            ))
+            .await?;
+
+        if clean {
+            self.jobs.clear();
+        }
+        self.jobs.push(SentJob {
+            notification,
+            difficulty: self.difficulty,
+            submitted: HashSet::new(),
+        });
+        Ok(())
+    }
+
+    fn submit(&mut self, params: &Value) -> Result<Value, (i64, &'static str)> {
+        let raw = params.as_array().cloned().unwrap_or_default();
+        let parsed = SubmitParams::from_stratum_params(&raw).ok();
+        let difficulty = parsed
+            .as_ref()
+            .and_then(|submit| self.job(&submit.job_id))
+            .map_or(self.difficulty, |job| job.difficulty);
+
+        let verdict = match self.check(&raw, parsed.as_ref()) {
+            Err((code, message)) => Verdict::Invalid { code, message },
+            Ok(()) => {
+                self.valid += 1;
+                match self.settings.rejects {
+                    Some((every, code, message)) if self.valid.is_multiple_of(every) => {
+                        Verdict::Refused { code, message }
+                    }
+                    _ => {
+                        self.accepted += 1;
+                        Verdict::Accepted
+                    }
+                }
+            }
+        };
+        let reply = match verdict {
+            Verdict::Accepted => Ok(Value::Bool(true)),
+            Verdict::Refused { code, message } | Verdict::Invalid { code, message } => {
+                Err((code, message))
+            }
+        };
+
+        let _ = self.submissions.send(Submission {
+            raw,
+            params: parsed,
+            difficulty,
+            verdict,
+        });
+        reply
+    }
+
+    fn job(&self, id: &str) -> Option<&SentJob> {
+        self.jobs.iter().find(|job| job.notification.job_id == id)
+    }
+
+    /// Check a share's format, its job and its proof of work.
+    fn check(
+        &mut self,
+        raw: &[Value],
+        submit: Option<&SubmitParams>,
+    ) -> Result<(), (i64, &'static str)> {
+        let Some(worker) = &self.worker else {
+            return Err((ERR_UNAUTHORIZED, "Unauthorized worker"));
+        };
+        let Some(submit) = submit else {
+            return Err((ERR_OTHER, "Malformed submit"));
+        };
+        if raw.len() > 6 {
+            return Err((ERR_OTHER, "Malformed submit"));
+        }
+
+        // Fixed-width lowercase hex, as pools parse it
+        let hex_field = |index: usize, len: usize| {
+            raw[index].as_str().is_some_and(|field| {
+                field.len() == len
+                    && field
+                        .bytes()
+                        .all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+            })
+        };
+        if !hex_field(2, 2 * EXTRANONCE2_SIZE) {
+            return Err((ERR_OTHER, "Wrong extranonce2 size"));
+        }
+        if !hex_field(3, 8) || !hex_field(4, 8) || (raw.len() == 6 && !hex_field(5, 8)) {
+            return Err((ERR_OTHER, "Malformed submit"));
+        }
+        if &submit.username != worker {
+            return Err((ERR_UNAUTHORIZED, "Unauthorized worker"));
+        }
+        let version_bits = submit.version_bits.unwrap_or(0);
+        if version_bits & !self.version_mask != 0 {
+            return Err((ERR_OTHER, "Version bits outside mask"));
+        }
+
+        let mask = self.version_mask;
+        let job = self
+            .jobs
+            .iter_mut()
+            .find(|job| job.notification.job_id == submit.job_id)
+            .ok_or((ERR_JOB_NOT_FOUND, "Job not found"))?;
+        let notification = &job.notification;
+        if submit.ntime < notification.ntime || submit.ntime - notification.ntime > MAX_NTIME_DRIFT
+        {
+            return Err((ERR_OTHER, "Time out of range"));
+        }
+        let key = (
+            submit.extranonce2.clone(),
+            submit.ntime,
+            submit.nonce,
+            submit.version_bits,
+        );
+        if job.submitted.contains(&key) {
+            return Err((ERR_DUPLICATE, "Duplicate share"));
+        }
+
+        let mut en2 = [0u8; 8];
+        en2[..EXTRANONCE2_SIZE].copy_from_slice(&submit.extranonce2);
+        let merkle_root = MerkleRootTemplate::new(
+            notification.coinbase1.clone(),
+            block_881423::extranonce1_bytes().to_vec(),
+            Extranonce2Range::new(EXTRANONCE2_SIZE as u8).unwrap(),
+            notification.coinbase2.clone(),
+            notification.merkle_branches.clone(),
+        )
+        .compute_merkle_root(
+            &Extranonce2::new(u64::from_le_bytes(en2), EXTRANONCE2_SIZE as u8).unwrap(),
+        )
+        .map_err(|_| (ERR_OTHER, "Bad extranonce2"))?;
+
+        let base = notification.version.to_consensus() as u32;
+        let header = BlockHeader {
+            version: Version::from_consensus(((base & !mask) | (version_bits & mask)) as i32),
+            prev_blockhash: notification.prev_hash,
+            merkle_root,
+            time: submit.ntime,
+            bits: notification.nbits,
+            nonce: submit.nonce,
+        };
+        let target = Difficulty::from_f64(job.difficulty).to_target();
+        if !target.is_met_by(header.block_hash()) {
+            return Err((ERR_LOW_DIFFICULTY, "Low difficulty share"));
+        }
+
+        job.submitted.insert(key);
+        Ok(())
+    }
+}
@@ -46,13 +46,23 @@ mod client;
 mod connection;
 mod error;
 mod messages;
+#[cfg(test)]
+pub(crate) mod mock_pool;
+mod reject;
+pub mod trace;
+mod validation;
+mod vardiff;
 
 use crate::types::ShareRate;
 use std::time::Duration;
 
-pub use client::{PoolConfig, StratumV1Client};
+pub use client::{PoolConfig, StratumV1Client, SHARE_FLUSH_TIMEOUT};
+pub use connection::Connection;
 pub use error::{StratumError, StratumResult};
-pub use messages::{ClientCommand, ClientEvent, JobNotification, SubmitParams};
+pub use messages::{ClientCommand, ClientEvent, JobNotification, JsonRpcMessage, SubmitParams};
+pub use reject::{RejectReason, ShareRejectionCounts};
+pub use validation::{validate_job, JobRejection, JobRejectionCounts};
+pub use vardiff::Vardiff;
 
 /// Safety cap to prevent flooding pools during startup or misconfiguration.
 ///
@@ -0,0 +1,133 @@
+//! Why pools reject shares.
+//!
+//! Pools answer a rejected `mining.submit` with an error array,
+//! `[code, "message", traceback]`. The codes most pools use come from the
+//! original Stratum proposal (21 job not found, 22 duplicate, 23 low
+//! difficulty), but pools differ in which they send, and some put a code of
+//! 20 ("other") on everything and say what went wrong only in the message.
+//! So the message is read first and the code is the fallback.
+
+use std::fmt;
+
+/// Why a share was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RejectReason {
+    /// Found on a job the pool had already moved past
+    Stale,
+    /// Already submitted
+    Duplicate,
+    /// Short of the share difficulty
+    LowDifficulty,
+    /// For a job the pool doesn't know
+    JobNotFound,
+    /// Anything else, including a bare `false` result
+    Other,
+}
+
+/// Per-pool counts of rejected shares, by reason.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShareRejectionCounts {
+    pub stale: u64,
+    pub duplicate: u64,
+    pub low_difficulty: u64,
+    pub job_not_found: u64,
+    pub other: u64,
+}
+
+impl RejectReason {
+    /// Classify a rejection from its error `code`, if any, and `message`.
+    pub fn parse(code: Option<i64>, message: &str) -> Self {
+        let message = message.to_ascii_lowercase();
+        let says = |words: &[&str]| words.iter().any(|word| message.contains(word));
+
+        if says(&["stale"]) {
+            Self::Stale
+        } else if says(&["duplicate"]) {
+            Self::Duplicate
+        } else if says(&["low difficulty", "low diff", "above target", "high-hash"]) {
+            Self::LowDifficulty
+        } else if says(&["job not found", "unknown job", "invalid job"]) {
+            Self::JobNotFound
+        } else {
+            match code {
+                Some(21) => Self::JobNotFound,
+                Some(22) => Self::Duplicate,
+                Some(23) => Self::LowDifficulty,
+                _ => Self::Other,
+            }
+        }
+    }
+}
+
+impl fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Stale => "stale",
+            Self::Duplicate => "duplicate",
+            Self::LowDifficulty => "low difficulty",
+            Self::JobNotFound => "job not found",
+            Self::Other => "other",
+        })
+    }
+}
+
+impl ShareRejectionCounts {
+    /// Count a rejection.
+    pub fn record(&mut self, reason: RejectReason) {
+        let counter = match reason {
+            RejectReason::Stale => &mut self.stale,
+            RejectReason::Duplicate => &mut self.duplicate,
+            RejectReason::LowDifficulty => &mut self.low_difficulty,
+            RejectReason::JobNotFound => &mut self.job_not_found,
+            RejectReason::Other => &mut self.other,
+        };
+        *counter += 1;
+    }
+
+    /// Total rejected shares across all reasons.
+    pub fn total(&self) -> u64 {
+        self.stale + self.duplicate + self.low_difficulty + self.job_not_found + self.other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_reasons() {
+        // Messages from ckpool, public-pool, and Bitcoin Core's submitblock
+        let cases = [
+            (Some(21), "Stale", RejectReason::Stale),
+            (Some(21), "Job not found (=stale)", RejectReason::Stale),
+            (Some(22), "Duplicate share", RejectReason::Duplicate),
+            (Some(23), "Above target", RejectReason::LowDifficulty),
+            (
+                Some(20),
+                "Low difficulty share",
+                RejectReason::LowDifficulty,
+            ),
+            (None, "high-hash", RejectReason::LowDifficulty),
+            (Some(20), "Invalid job id", RejectReason::JobNotFound),
+            // Code alone, when the message says nothing useful
+            (Some(21), "Rejected", RejectReason::JobNotFound),
+            (Some(23), "", RejectReason::LowDifficulty),
+            (Some(24), "Unauthorized worker", RejectReason::Other),
+            (None, "Pool returned false", RejectReason::Other),
+        ];
+        for (code, message, reason) in cases {
+            assert_eq!(RejectReason::parse(code, message), reason, "{}", message);
+        }
+    }
+
+    #[test]
+    fn test_counts() {
+        let mut counts = ShareRejectionCounts::default();
+        counts.record(RejectReason::Stale);
+        counts.record(RejectReason::Stale);
+        counts.record(RejectReason::Other);
+        assert_eq!(counts.stale, 2);
+        assert_eq!(counts.other, 1);
+        assert_eq!(counts.total(), 3);
+    }
+}
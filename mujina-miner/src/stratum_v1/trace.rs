@@ -0,0 +1,289 @@
+//! Transcripts of the messages exchanged with a pool.
+//!
+//! When a pool rejects every share at 3am, the log rarely says why, and
+//! tracing the whole daemon at TRACE level to catch it drowns the pool's
+//! messages in everything else. A pool configured with `trace = true`
+//! instead has every line sent and received written to a transcript of its
+//! own, one JSON object per line (NDJSON):
+//!
+//! ```json
+//! {"ts":"2026-10-15T03:00:00.123Z","dir":"rx","raw":"{...}","msg":{...}}
+//! ```
+//!
+//! `dir` is `rx` or `tx`, `raw` the line as it crossed the wire and `msg`
+//! the line parsed as JSON, or null if it isn't. Each connection opens with
+//! a `connect` entry naming the pool. The worker password sent with
+//! `mining.authorize` is replaced by `<redacted>`.
+//!
+//! Transcripts are kept in `stratum-traces/` in the state directory, named
+//! after the pool. One that reaches [`MAX_FILE_BYTES`] is rotated to
+//! `<name>.ndjson.1`, and so on, keeping [`KEEP_FILES`] old ones.
+//!
+//! Lines are written as they're exchanged, synchronously: this is a
+//! debugging aid, and a few hundred bytes a message is no burden.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use time::OffsetDateTime;
+use tracing::warn;
+
+/// Directory under the state directory transcripts are kept in.
+pub const DIR_NAME: &str = "stratum-traces";
+
+/// Size past which a transcript is rotated, in bytes.
+pub const MAX_FILE_BYTES: u64 = 16 * 1024 * 1024;
+
+/// Rotated transcripts kept per pool, besides the current one.
+pub const KEEP_FILES: usize = 3;
+
+/// Which way a line went.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Received from the pool
+    Rx,
+    /// Sent to the pool
+    Tx,
+}
+
+impl Direction {
+    fn as_str(self) -> &'static str {
+        match self {
+            Direction::Rx => "rx",
+            Direction::Tx => "tx",
+        }
+    }
+}
+
+/// A transcript line.
+#[derive(Debug, Serialize)]
+struct Entry<'a> {
+    ts: String,
+    dir: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    raw: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    msg: Option<Option<Value>>,
+}
+
+/// A pool's transcript, open for appending.
+#[derive(Debug)]
+pub struct Transcript {
+    path: PathBuf,
+    file: File,
+    /// Bytes in the current file
+    len: u64,
+    max_bytes: u64,
+}
+
+impl Transcript {
+    /// Open the transcript of the pool at `url` under `dir`, appending to
+    /// it, and mark the start of a connection.
+    pub fn open(dir: &Path, url: &str) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let path = dir.join(file_name(url));
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let len = file.metadata()?.len();
+
+        let mut transcript = Self {
+            path,
+            file,
+            len,
+            max_bytes: MAX_FILE_BYTES,
+        };
+        transcript.write_entry(&Entry {
+            ts: timestamp(),
+            dir: "connect",
+            url: Some(url),
+            raw: None,
+            msg: None,
+        });
+        Ok(transcript)
+    }
+
+    /// Rotate at `max_bytes` instead of [`MAX_FILE_BYTES`].
+    #[cfg(test)]
+    fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Record `line`, which went `dir`.
+    pub fn record(&mut self, dir: Direction, line: &str) {
+        let mut msg = serde_json::from_str::<Value>(line).ok();
+        let redacted = msg.as_mut().is_some_and(redact);
+        let raw = match &msg {
+            Some(msg) if redacted => msg.to_string(),
+            _ => line.to_string(),
+        };
+
+        self.write_entry(&Entry {
+            ts: timestamp(),
+            dir: dir.as_str(),
+            url: None,
+            raw: Some(&raw),
+            msg: Some(msg),
+        });
+    }
+
+    /// Append `entry` as a line, rotating first if the file is full.
+    ///
+    /// A transcript that can't be written is given up on quietly after a
+    /// warning: mining matters more than the record of it.
+    fn write_entry(&mut self, entry: &Entry<'_>) {
+        let Ok(mut line) = serde_json::to_string(entry) else {
+            return;
+        };
+        line.push('\n');
+
+        if self.len > 0 && self.len + line.len() as u64 > self.max_bytes {
+            if let Err(e) = self.rotate() {
+                warn!(path = %self.path.display(), error = %e, "Failed to rotate Stratum transcript");
+            }
+        }
+        match self.file.write_all(line.as_bytes()) {
+            Ok(()) => self.len += line.len() as u64,
+            Err(e) => {
+                warn!(path = %self.path.display(), error = %e, "Failed to write Stratum transcript");
+            }
+        }
+    }
+
+    /// Shift the rotated files up one, dropping the oldest, and start a
+    /// fresh current file.
+    fn rotate(&mut self) -> io::Result<()> {
+        let rotated = |n: usize| PathBuf::from(format!("{}.{}", self.path.display(), n));
+        for n in (1..KEEP_FILES).rev() {
+            if rotated(n).exists() {
+                fs::rename(rotated(n), rotated(n + 1))?;
+            }
+        }
+        fs::rename(&self.path, rotated(1))?;
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.len = 0;
+        Ok(())
+    }
+}
+
+/// Hide the password in a `mining.authorize` request, returning whether
+/// there was one.
+fn redact(msg: &mut Value) -> bool {
+    if msg.get("method").and_then(Value::as_str) != Some("mining.authorize") {
+        return false;
+    }
+    match msg.pointer_mut("/params/1") {
+        Some(password) => {
+            *password = json!("<redacted>");
+            true
+        }
+        None => false,
+    }
+}
+
+/// The current time, UTC, to the millisecond.
+fn timestamp() -> String {
+    OffsetDateTime::now_utc()
+        .format(time::macros::format_description!(
+            "[year]-[month]-[day]T[hour]:[minute]:[second].[subsecond digits:3]Z"
+        ))
+        .unwrap_or_default()
+}
+
+/// Transcript file name for the pool at `url`.
+fn file_name(url: &str) -> String {
+    let name = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let stem: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}.ndjson", stem)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("mujina-trace-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn entries(path: &Path) -> Vec<Value> {
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_records_lines_and_redacts_password() {
+        let dir = temp_dir("record");
+        let mut transcript = Transcript::open(&dir, "stratum+tcp://pool.example.com:3333").unwrap();
+        transcript.record(
+            Direction::Tx,
+            r#"{"id":2,"method":"mining.authorize","params":["worker","hunter2"]}"#,
+        );
+        transcript.record(Direction::Rx, r#"{"id":2,"result":true,"error":null}"#);
+        transcript.record(Direction::Rx, "not json");
+
+        let path = dir.join("pool.example.com_3333.ndjson");
+        let entries = entries(&path);
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[0]["dir"], "connect");
+        assert_eq!(entries[1]["dir"], "tx");
+        assert_eq!(entries[1]["msg"]["params"], json!(["worker", "<redacted>"]));
+        assert!(!entries[1]["raw"].as_str().unwrap().contains("hunter2"));
+        assert_eq!(entries[2]["dir"], "rx");
+        assert_eq!(entries[2]["msg"]["result"], true);
+        assert_eq!(entries[3]["raw"], "not json");
+        assert_eq!(entries[3]["msg"], Value::Null);
+        assert!(!fs::read_to_string(&path).unwrap().contains("hunter2"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_rotates_when_full() {
+        let dir = temp_dir("rotate");
+        let mut transcript = Transcript::open(&dir, "pool:3333")
+            .unwrap()
+            .with_max_bytes(200);
+        for id in 0..20 {
+            transcript.record(Direction::Rx, &format!(r#"{{"id":{},"result":true}}"#, id));
+        }
+
+        let path = dir.join("pool_3333.ndjson");
+        let newest = entries(&path);
+        assert!(fs::metadata(&path).unwrap().len() <= 200);
+        assert_eq!(newest.last().unwrap()["msg"]["id"], 19);
+
+        // Only the newest rotated files are kept, each older than the last
+        let rotated: Vec<_> = (1..=KEEP_FILES + 1)
+            .map(|n| dir.join(format!("pool_3333.ndjson.{}", n)))
+            .collect();
+        assert!(rotated[..KEEP_FILES].iter().all(|path| path.exists()));
+        assert!(!rotated[KEEP_FILES].exists());
+        let first = |path: &Path| entries(path)[0]["msg"]["id"].as_u64().unwrap();
+        assert!(first(&rotated[0]) < newest[0]["msg"]["id"].as_u64().unwrap());
+        assert!(first(&rotated[1]) < first(&rotated[0]));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,312 @@
+//! Sanity checks for pool-supplied jobs.
+//!
+//! `JobNotification::from_stratum_params` only checks that mining.notify is
+//! well-formed JSON with decodable hex. A job can parse cleanly and still be
+//! garbage: a truncated coinbase, a prevhash in the wrong byte order, or a
+//! timestamp from a pool with a broken clock. Hashing such a job wastes the
+//! chips' time at best and at worst produces shares the pool rejects until the
+//! next clean job. These checks run before a job reaches the scheduler so a
+//! misbehaving pool is caught, counted, and logged instead.
+
+use bitcoin::pow::Target;
+use thiserror::Error;
+
+use super::JobNotification;
+use crate::u256::U256;
+
+/// Smallest plausible coinbase1.
+///
+/// The extranonces sit inside the coinbase input's scriptSig, so coinbase1
+/// must contain at least the tx version (4), input count (1), null prevout
+/// (36), and scriptSig length (1).
+pub const MIN_COINBASE1_LEN: usize = 42;
+
+/// Largest coinbase we accept, in bytes (the standard transaction size limit).
+pub const MAX_COINBASE_LEN: usize = 100_000;
+
+/// Most merkle branches a job can have.
+///
+/// Branch count is log2 of the transaction count, and a 4 MWU block can't
+/// hold more than ~2^14 transactions. Anything near 32 is nonsense.
+pub const MAX_MERKLE_BRANCHES: usize = 32;
+
+/// How far ntime may be from our clock, in seconds.
+///
+/// Consensus allows block timestamps up to two hours in the future; a job
+/// further out than that on either side means the pool's clock (or ours) is
+/// badly wrong.
+pub const MAX_NTIME_DRIFT: u32 = 2 * 60 * 60;
+
+/// Why a job was rejected.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum JobRejection {
+    /// Job ID is empty, so shares couldn't be attributed to it
+    #[error("empty job ID")]
+    EmptyJobId,
+
+    /// Coinbase parts are too short or too long to be a real coinbase
+    #[error("coinbase length out of range (coinbase1 {coinbase1} bytes, total {total} bytes)")]
+    CoinbaseLength { coinbase1: usize, total: usize },
+
+    /// More merkle branches than any block could need
+    #[error("{0} merkle branches (max {MAX_MERKLE_BRANCHES})")]
+    TooManyMerkleBranches(usize),
+
+    /// Network target is easier than the configured network allows, so the
+    /// pool is serving a different chain
+    #[error("nbits target above network limit (pool on a different network?)")]
+    BitsAboveLimit,
+
+    /// Previous block hash doesn't meet the network's proof-of-work limit,
+    /// which almost always means the pool sent it in the wrong byte order
+    #[error("prevhash doesn't meet proof-of-work limit (wrong byte order?)")]
+    PrevHashByteOrder,
+
+    /// Block timestamp too far from the local clock
+    #[error("ntime {ntime} is {drift}s from local time")]
+    NtimeOutOfRange { ntime: u32, drift: u32 },
+}
+
+/// Per-pool counts of rejected jobs, by reason.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct JobRejectionCounts {
+    pub empty_job_id: u64,
+    pub coinbase_length: u64,
+    pub merkle_branches: u64,
+    pub nbits: u64,
+    pub prev_hash: u64,
+    pub ntime: u64,
+}
+
+impl JobRejectionCounts {
+    /// Count a rejection.
+    pub fn record(&mut self, rejection: &JobRejection) {
+        let counter = match rejection {
+            JobRejection::EmptyJobId => &mut self.empty_job_id,
+            JobRejection::CoinbaseLength { .. } => &mut self.coinbase_length,
+            JobRejection::TooManyMerkleBranches(_) => &mut self.merkle_branches,
+            JobRejection::BitsAboveLimit => &mut self.nbits,
+            JobRejection::PrevHashByteOrder => &mut self.prev_hash,
+            JobRejection::NtimeOutOfRange { .. } => &mut self.ntime,
+        };
+        *counter += 1;
+    }
+
+    /// Total rejected jobs across all reasons.
+    pub fn total(&self) -> u64 {
+        self.empty_job_id
+            + self.coinbase_length
+            + self.merkle_branches
+            + self.nbits
+            + self.prev_hash
+            + self.ntime
+    }
+}
+
+/// Check a job from mining.notify before it's turned into work.
+///
+/// `extranonce_len` is the combined extranonce1 and extranonce2 size for the
+/// session, `pow_limit` is the configured network's easiest block target, and
+/// `now` is the local Unix time in seconds.
+///
+/// # Prevhash byte order
+///
+/// Every block meets its own target, and a block's target is at most 4x the
+/// next block's (the retarget clamp), so a correctly decoded prevhash is no
+/// larger than four times the target in the job's nbits. Testnets let a block
+/// fall back to the proof-of-work limit, so the bound never goes below that.
+/// Decoding with the wrong byte order moves the hash's leading zeros to the
+/// other end, producing an enormous value that fails this check on any
+/// network with real proof-of-work. Regtest's limit is so easy that there the
+/// check accepts everything.
+pub fn validate_job(
+    job: &JobNotification,
+    extranonce_len: usize,
+    pow_limit: Target,
+    now: u32,
+) -> Result<(), JobRejection> {
+    if job.job_id.is_empty() {
+        return Err(JobRejection::EmptyJobId);
+    }
+
+    let coinbase1 = job.coinbase1.len();
+    // The extranonce2 size is the pool's word, so don't trust it not to
+    // overflow
+    let total = coinbase1
+        .saturating_add(extranonce_len)
+        .saturating_add(job.coinbase2.len());
+    if coinbase1 < MIN_COINBASE1_LEN || job.coinbase2.is_empty() || total > MAX_COINBASE_LEN {
+        return Err(JobRejection::CoinbaseLength { coinbase1, total });
+    }
+
+    if job.merkle_branches.len() > MAX_MERKLE_BRANCHES {
+        return Err(JobRejection::TooManyMerkleBranches(
+            job.merkle_branches.len(),
+        ));
+    }
+
+    let network_target = U256::from(Target::from_compact(job.nbits));
+    let pow_limit = U256::from(pow_limit);
+    if network_target > pow_limit {
+        return Err(JobRejection::BitsAboveLimit);
+    }
+
+    let bound = network_target
+        .saturating_mul(U256::from(4u64))
+        .max(pow_limit);
+    let prev_hash = U256::from(&job.prev_hash);
+    if prev_hash > bound {
+        return Err(JobRejection::PrevHashByteOrder);
+    }
+
+    let drift = job.ntime.abs_diff(now);
+    if drift > MAX_NTIME_DRIFT {
+        return Err(JobRejection::NtimeOutOfRange {
+            ntime: job.ntime,
+            drift,
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asic::bm13xx::test_data::esp_miner_job::notify;
+    use crate::asic::bm13xx::test_data::stratum_json;
+    use bitcoin::hashes::Hash;
+    use bitcoin::{BlockHash, CompactTarget, TxMerkleNode};
+
+    const EXTRANONCE_LEN: usize = 8;
+
+    fn capture_job() -> JobNotification {
+        let json: serde_json::Value = serde_json::from_str(stratum_json::MINING_NOTIFY).unwrap();
+        JobNotification::from_stratum_params(json["params"].as_array().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_capture_job_is_valid() {
+        let job = capture_job();
+        assert_eq!(
+            validate_job(&job, EXTRANONCE_LEN, Target::MAX, *notify::NTIME),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_rejects_malformed_fields() {
+        let now = *notify::NTIME;
+
+        let mut job = capture_job();
+        job.job_id.clear();
+        assert_eq!(
+            validate_job(&job, EXTRANONCE_LEN, Target::MAX, now),
+            Err(JobRejection::EmptyJobId)
+        );
+
+        let mut job = capture_job();
+        job.coinbase1.truncate(MIN_COINBASE1_LEN - 1);
+        assert!(matches!(
+            validate_job(&job, EXTRANONCE_LEN, Target::MAX, now),
+            Err(JobRejection::CoinbaseLength { .. })
+        ));
+
+        let mut job = capture_job();
+        job.coinbase2.clear();
+        assert!(matches!(
+            validate_job(&job, EXTRANONCE_LEN, Target::MAX, now),
+            Err(JobRejection::CoinbaseLength { .. })
+        ));
+
+        let mut job = capture_job();
+        job.coinbase2 = vec![0; MAX_COINBASE_LEN];
+        assert!(matches!(
+            validate_job(&job, EXTRANONCE_LEN, Target::MAX, now),
+            Err(JobRejection::CoinbaseLength { .. })
+        ));
+
+        assert!(matches!(
+            validate_job(&capture_job(), usize::MAX, Target::MAX, now),
+            Err(JobRejection::CoinbaseLength { .. })
+        ));
+
+        let mut job = capture_job();
+        job.merkle_branches = vec![TxMerkleNode::all_zeros(); MAX_MERKLE_BRANCHES + 1];
+        assert_eq!(
+            validate_job(&job, EXTRANONCE_LEN, Target::MAX, now),
+            Err(JobRejection::TooManyMerkleBranches(MAX_MERKLE_BRANCHES + 1))
+        );
+    }
+
+    #[test]
+    fn test_rejects_reversed_prevhash() {
+        let now = *notify::NTIME;
+        let mut job = capture_job();
+
+        let mut bytes = job.prev_hash.to_byte_array();
+        bytes.reverse();
+        job.prev_hash = BlockHash::from_byte_array(bytes);
+        assert_eq!(
+            validate_job(&job, EXTRANONCE_LEN, Target::MAX, now),
+            Err(JobRejection::PrevHashByteOrder)
+        );
+
+        // Regtest's limit is so easy that any hash passes
+        job.nbits = CompactTarget::from_consensus(0x207fffff);
+        assert_eq!(
+            validate_job(&job, EXTRANONCE_LEN, Target::MAX_ATTAINABLE_REGTEST, now),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_rejects_other_network() {
+        // A regtest job while configured for mainnet
+        let mut job = capture_job();
+        job.nbits = CompactTarget::from_consensus(0x207fffff);
+        assert_eq!(
+            validate_job(&job, EXTRANONCE_LEN, Target::MAX, *notify::NTIME),
+            Err(JobRejection::BitsAboveLimit)
+        );
+    }
+
+    #[test]
+    fn test_ntime_drift() {
+        let job = capture_job();
+        let ntime = *notify::NTIME;
+
+        assert_eq!(
+            validate_job(&job, EXTRANONCE_LEN, Target::MAX, ntime + MAX_NTIME_DRIFT),
+            Ok(())
+        );
+        assert_eq!(
+            validate_job(&job, EXTRANONCE_LEN, Target::MAX, ntime - MAX_NTIME_DRIFT),
+            Ok(())
+        );
+        assert_eq!(
+            validate_job(
+                &job,
+                EXTRANONCE_LEN,
+                Target::MAX,
+                ntime + MAX_NTIME_DRIFT + 1
+            ),
+            Err(JobRejection::NtimeOutOfRange {
+                ntime,
+                drift: MAX_NTIME_DRIFT + 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_rejection_counts() {
+        let mut counts = JobRejectionCounts::default();
+        counts.record(&JobRejection::PrevHashByteOrder);
+        counts.record(&JobRejection::PrevHashByteOrder);
+        counts.record(&JobRejection::EmptyJobId);
+
+        assert_eq!(counts.prev_hash, 2);
+        assert_eq!(counts.empty_job_id, 1);
+        assert_eq!(counts.total(), 3);
+    }
+}
@@ -0,0 +1,191 @@
+//! Client-side difficulty suggestions.
+//!
+//! Pools retarget share difficulty from the rate shares arrive, but only
+//! every few minutes and a bounded step at a time. When our hashrate jumps,
+//! say a board is added or removed, the pool is left flooded or starved for
+//! several of its retarget periods. [`Vardiff`] counts the shares we submit
+//! at the pool's current difficulty and, once the rate is far from the rate
+//! we want, works out the difficulty that would give it, for the client to
+//! send as `mining.suggest_difficulty`.
+//!
+//! Suggestions never go below the first difficulty the pool set in the
+//! session. Some pools drop clients that suggest less than their minimum,
+//! which they don't advertise; the starting difficulty is the lowest one we
+//! know the pool accepts.
+
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::types::ShareRate;
+
+/// Shares counted before the rate is judged.
+pub const WINDOW_SHARES: u32 = 30;
+
+/// Longest the rate is measured before it's judged, however few shares
+/// came in; bounds how long a starved pool goes unnoticed.
+pub const WINDOW: Duration = Duration::from_secs(300);
+
+/// Factor the measured rate must be off by, either way, to suggest a new
+/// difficulty. Wide enough that Poisson noise over a window doesn't trip it.
+pub const TOLERANCE: f64 = 3.0;
+
+/// Least time between suggestions while the pool ignores them.
+pub const HOLDOFF: Duration = Duration::from_secs(600);
+
+/// Share rate tracking for one pool session.
+#[derive(Debug)]
+pub struct Vardiff {
+    /// Rate we want shares at
+    target: ShareRate,
+
+    /// Difficulty the pool last set
+    difficulty: Option<f64>,
+
+    /// First difficulty the pool set this session
+    floor: Option<f64>,
+
+    /// Start of the current measurement window
+    window_start: Instant,
+
+    /// Shares submitted in the current window
+    shares: u32,
+
+    /// When we last suggested a difficulty
+    last_suggestion: Option<Instant>,
+}
+
+impl Vardiff {
+    /// Track shares against the `target` rate.
+    pub fn new(target: ShareRate, now: Instant) -> Self {
+        Self {
+            target,
+            difficulty: None,
+            floor: None,
+            window_start: now,
+            shares: 0,
+            last_suggestion: None,
+        }
+    }
+
+    /// Start over for a new session.
+    pub fn reset(&mut self, now: Instant) {
+        *self = Self::new(self.target, now);
+    }
+
+    /// The pool set a new share difficulty; shares found before it were at
+    /// the old one, so measuring starts over.
+    pub fn set_difficulty(&mut self, difficulty: f64, now: Instant) {
+        self.difficulty = Some(difficulty);
+        self.floor.get_or_insert(difficulty);
+        self.last_suggestion = None;
+        self.restart_window(now);
+    }
+
+    /// A share was submitted.
+    pub fn share(&mut self) {
+        self.shares = self.shares.saturating_add(1);
+    }
+
+    /// Difficulty to suggest, if the rate has strayed far enough from the
+    /// target and the last suggestion is old enough.
+    pub fn poll(&mut self, now: Instant) -> Option<u64> {
+        let difficulty = self.difficulty?;
+        let floor = self.floor?;
+        let elapsed = now.saturating_duration_since(self.window_start);
+        if self.shares < WINDOW_SHARES && elapsed < WINDOW {
+            return None;
+        }
+        if self
+            .last_suggestion
+            .is_some_and(|last| now.saturating_duration_since(last) < HOLDOFF)
+        {
+            return None;
+        }
+
+        // With no shares at all the rate is at most one per window
+        let rate = f64::from(self.shares.max(1)) / elapsed.as_secs_f64();
+        let ratio = rate / self.target.as_per_second();
+        self.restart_window(now);
+        if (1.0 / TOLERANCE..=TOLERANCE).contains(&ratio) {
+            return None;
+        }
+
+        let suggestion = (difficulty * ratio).max(floor).round().max(1.0) as u64;
+        if suggestion as f64 == difficulty.round() {
+            return None;
+        }
+        self.last_suggestion = Some(now);
+        Some(suggestion)
+    }
+
+    fn restart_window(&mut self, now: Instant) {
+        self.window_start = now;
+        self.shares = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One share every 10 seconds.
+    fn vardiff(now: Instant) -> Vardiff {
+        Vardiff::new(ShareRate::per_minute(6.0), now)
+    }
+
+    #[test]
+    fn test_suggests_higher_difficulty_when_flooding() {
+        let start = Instant::now();
+        let mut vardiff = vardiff(start);
+        vardiff.set_difficulty(1000.0, start);
+
+        // 30 shares in 30 seconds: ten times the target rate
+        for _ in 0..WINDOW_SHARES {
+            vardiff.share();
+        }
+        let now = start + Duration::from_secs(30);
+        assert_eq!(vardiff.poll(now), Some(10_000));
+
+        // Pool ignores us: no repeat until the holdoff passes
+        for _ in 0..WINDOW_SHARES {
+            vardiff.share();
+        }
+        assert_eq!(vardiff.poll(now + Duration::from_secs(30)), None);
+    }
+
+    #[test]
+    fn test_suggests_lower_difficulty_when_starved_but_not_below_floor() {
+        let start = Instant::now();
+        let mut vardiff = vardiff(start);
+        vardiff.set_difficulty(1000.0, start);
+        vardiff.set_difficulty(64_000.0, start);
+
+        // 3 shares in 300 seconds: a tenth of the target rate
+        for _ in 0..3 {
+            vardiff.share();
+        }
+        assert_eq!(vardiff.poll(start + Duration::from_secs(150)), None);
+        assert_eq!(vardiff.poll(start + WINDOW), Some(6400));
+
+        // Nothing for a while at the pool's new difficulty: the rate is
+        // bounded by one share per window, and the floor holds
+        let later = start + WINDOW + HOLDOFF;
+        vardiff.set_difficulty(6400.0, later);
+        assert_eq!(vardiff.poll(later + WINDOW * 10), Some(1000));
+    }
+
+    #[test]
+    fn test_quiet_near_target() {
+        let start = Instant::now();
+        let mut vardiff = vardiff(start);
+        assert_eq!(vardiff.poll(start + WINDOW), None, "no difficulty yet");
+
+        vardiff.set_difficulty(1000.0, start);
+        for _ in 0..WINDOW_SHARES {
+            vardiff.share();
+        }
+        // 30 shares in 200 seconds is 1.5x the target
+        assert_eq!(vardiff.poll(start + Duration::from_secs(200)), None);
+    }
+}
@@ -0,0 +1,354 @@
+//! Supervision of the daemon's long-running tasks.
+//!
+//! Each top-level task (backplane, scheduler, job sources, API server) is
+//! expected to run until shutdown is requested. A task that returns early or
+//! panics leaves the miner silently degraded---boards keep hashing with no
+//! one to hand out work, or shares pile up with no pool to take them. The
+//! [`Supervisor`] watches every task it spawns and, when one stops while the
+//! daemon is still running, logs what happened and then either restarts the
+//! task with exponential backoff or initiates an orderly shutdown, depending
+//! on how the task was spawned.
+//!
+//! Tasks are spawned onto a fresh Tokio task so panics are caught by the
+//! supervisor rather than unwinding through it; the supervisor itself runs on
+//! the daemon's `TaskTracker`, so `TaskTracker::wait()` still covers
+//! everything.
+
+use std::any::Any;
+use std::future::Future;
+use std::time::Duration;
+
+use tokio::time::Instant;
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+
+use crate::tracing::prelude::*;
+
+/// Restart delays for a restartable task.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Backoff {
+    /// Delay before the first restart.
+    pub initial: Duration,
+
+    /// Upper bound on the delay; it doubles after each consecutive failure.
+    pub max: Duration,
+
+    /// A task that ran at least this long before stopping is considered to
+    /// have been healthy, and the delay resets to `initial`.
+    pub stable_after: Duration,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(60),
+            stable_after: Duration::from_secs(300),
+        }
+    }
+}
+
+/// How a supervised task stopped.
+#[derive(Debug)]
+pub(crate) enum Exit {
+    /// Returned `Ok(())`.
+    Returned,
+    /// Returned an error.
+    Failed(anyhow::Error),
+    /// Panicked.
+    Panicked(String),
+    /// Aborted or cancelled by the runtime.
+    Cancelled,
+}
+
+/// Spawns and watches the daemon's top-level tasks.
+#[derive(Clone)]
+pub struct Supervisor {
+    tracker: TaskTracker,
+    shutdown: CancellationToken,
+}
+
+impl Supervisor {
+    /// Create a supervisor that spawns onto `tracker` and cancels `shutdown`
+    /// when a critical task stops unexpectedly.
+    pub fn new(tracker: TaskTracker, shutdown: CancellationToken) -> Self {
+        Self { tracker, shutdown }
+    }
+
+    /// A supervisor for a group of tasks that can be stopped on their own.
+    ///
+    /// The group stops when [`Supervisor::cancellation_token`] of the child
+    /// is cancelled, or with its parent. A critical task in the group that
+    /// stops unexpectedly stops only the group, not the daemon.
+    pub fn child(&self) -> Self {
+        Self {
+            tracker: self.tracker.clone(),
+            shutdown: self.shutdown.child_token(),
+        }
+    }
+
+    /// Token that stops this supervisor's tasks when cancelled.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.shutdown.clone()
+    }
+
+    /// Spawn a task the daemon cannot run without.
+    ///
+    /// If the task stops before shutdown is requested---by returning or by
+    /// panicking---the supervisor logs the cause and initiates an orderly
+    /// shutdown of the whole daemon.
+    pub fn spawn_critical<F>(&self, name: &'static str, task: F)
+    where
+        F: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        let shutdown = self.shutdown.clone();
+        self.tracker.spawn(async move {
+            let started = Instant::now();
+            let exit = run_to_exit(task).await;
+
+            if shutdown.is_cancelled() {
+                log_exit_during_shutdown(name, &exit);
+                return;
+            }
+
+            log_unexpected_exit(name, &exit, started.elapsed());
+            error!(task = name, "Critical task stopped; shutting down.");
+            shutdown.cancel();
+        });
+    }
+
+    /// Spawn a task that can be recreated after it stops.
+    ///
+    /// `make_task` is called to create the task initially and again for each
+    /// restart. If the task stops before shutdown is requested, the
+    /// supervisor logs the cause, waits according to `backoff`, and starts a
+    /// new instance. Restarting stops once shutdown is requested.
+    pub fn spawn_restartable<M, F>(&self, name: &'static str, backoff: Backoff, mut make_task: M)
+    where
+        M: FnMut() -> F + Send + 'static,
+        F: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        let shutdown = self.shutdown.clone();
+        self.tracker.spawn(async move {
+            let mut delay = backoff.initial;
+            let mut restarts: u32 = 0;
+
+            loop {
+                let started = Instant::now();
+                let exit = run_to_exit(make_task()).await;
+
+                if shutdown.is_cancelled() {
+                    log_exit_during_shutdown(name, &exit);
+                    return;
+                }
+
+                let uptime = started.elapsed();
+                log_unexpected_exit(name, &exit, uptime);
+
+                if uptime >= backoff.stable_after {
+                    delay = backoff.initial;
+                }
+
+                restarts += 1;
+                warn!(
+                    task = name,
+                    restarts,
+                    delay_secs = delay.as_secs_f64(),
+                    "Restarting task after delay."
+                );
+
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {}
+                    _ = shutdown.cancelled() => return,
+                }
+
+                delay = (delay * 2).min(backoff.max);
+            }
+        });
+    }
+}
+
+/// Run `task` on its own Tokio task and report how it stopped.
+pub(crate) async fn run_to_exit<F>(task: F) -> Exit
+where
+    F: Future<Output = anyhow::Result<()>> + Send + 'static,
+{
+    match tokio::spawn(task).await {
+        Ok(Ok(())) => Exit::Returned,
+        Ok(Err(e)) => Exit::Failed(e),
+        Err(e) if e.is_panic() => Exit::Panicked(panic_message(e.into_panic())),
+        Err(_) => Exit::Cancelled,
+    }
+}
+
+/// Extract a readable message from a panic payload.
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "non-string panic payload".to_string()
+    }
+}
+
+pub(crate) fn log_unexpected_exit(name: &str, exit: &Exit, uptime: Duration) {
+    let uptime_secs = uptime.as_secs();
+    match exit {
+        Exit::Returned => {
+            error!(task = name, uptime_secs, "Task exited unexpectedly.");
+        }
+        Exit::Failed(e) => {
+            error!(task = name, uptime_secs, error = %format!("{:#}", e), "Task failed.");
+        }
+        Exit::Panicked(msg) => {
+            error!(task = name, uptime_secs, panic = %msg, "Task panicked.");
+        }
+        Exit::Cancelled => {
+            error!(task = name, uptime_secs, "Task was cancelled.");
+        }
+    }
+}
+
+fn log_exit_during_shutdown(name: &'static str, exit: &Exit) {
+    match exit {
+        Exit::Returned | Exit::Cancelled => {
+            trace!(task = name, "Task stopped.");
+        }
+        Exit::Failed(e) => {
+            warn!(task = name, error = %format!("{:#}", e), "Task failed during shutdown.");
+        }
+        Exit::Panicked(msg) => {
+            error!(task = name, panic = %msg, "Task panicked during shutdown.");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    };
+
+    use super::*;
+
+    fn supervisor() -> (Supervisor, TaskTracker, CancellationToken) {
+        let tracker = TaskTracker::new();
+        let shutdown = CancellationToken::new();
+        let supervisor = Supervisor::new(tracker.clone(), shutdown.clone());
+        (supervisor, tracker, shutdown)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_critical_task_exit_initiates_shutdown() {
+        let (supervisor, tracker, shutdown) = supervisor();
+
+        supervisor.spawn_critical("test", async { Ok(()) });
+        tracker.close();
+        tracker.wait().await;
+
+        assert!(shutdown.is_cancelled());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_critical_task_panic_initiates_shutdown() {
+        let (supervisor, tracker, shutdown) = supervisor();
+
+        supervisor.spawn_critical("test", async { panic!("boom") });
+        tracker.close();
+        tracker.wait().await;
+
+        assert!(shutdown.is_cancelled());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_critical_task_stopping_for_shutdown_is_quiet() {
+        let (supervisor, tracker, shutdown) = supervisor();
+
+        let token = shutdown.clone();
+        supervisor.spawn_critical("test", async move {
+            token.cancelled().await;
+            Ok(())
+        });
+        tracker.close();
+
+        shutdown.cancel();
+        tracker.wait().await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_child_critical_task_stops_only_its_group() {
+        let (supervisor, tracker, shutdown) = supervisor();
+        let group = supervisor.child();
+
+        group.spawn_critical("test", async { Ok(()) });
+        tracker.close();
+        group.cancellation_token().cancelled().await;
+
+        assert!(!shutdown.is_cancelled());
+        tracker.wait().await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_restartable_task_restarts_with_backoff() {
+        let (supervisor, tracker, shutdown) = supervisor();
+        let starts = Arc::new(AtomicU32::new(0));
+
+        let backoff = Backoff {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(4),
+            stable_after: Duration::from_secs(300),
+        };
+        supervisor.spawn_restartable("test", backoff, {
+            let starts = starts.clone();
+            move || {
+                starts.fetch_add(1, Ordering::SeqCst);
+                async { anyhow::bail!("connection refused") }
+            }
+        });
+        tracker.close();
+
+        // Starts at t=0, then restarts after 1s, 2s, 4s, 4s delays
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(starts.load(Ordering::SeqCst), 1);
+        tokio::time::sleep(Duration::from_secs(1)).await; // t=1.5
+        assert_eq!(starts.load(Ordering::SeqCst), 2);
+        tokio::time::sleep(Duration::from_secs(2)).await; // t=3.5
+        assert_eq!(starts.load(Ordering::SeqCst), 3);
+        tokio::time::sleep(Duration::from_secs(4)).await; // t=7.5
+        assert_eq!(starts.load(Ordering::SeqCst), 4);
+        tokio::time::sleep(Duration::from_secs(4)).await; // t=11.5 (capped)
+        assert_eq!(starts.load(Ordering::SeqCst), 5);
+
+        // A restartable task never takes the daemon down
+        assert!(!shutdown.is_cancelled());
+
+        shutdown.cancel();
+        tracker.wait().await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_restartable_task_not_restarted_after_shutdown() {
+        let (supervisor, tracker, shutdown) = supervisor();
+        let starts = Arc::new(AtomicU32::new(0));
+
+        supervisor.spawn_restartable("test", Backoff::default(), {
+            let starts = starts.clone();
+            let shutdown = shutdown.clone();
+            move || {
+                starts.fetch_add(1, Ordering::SeqCst);
+                let shutdown = shutdown.clone();
+                async move {
+                    shutdown.cancelled().await;
+                    Ok(())
+                }
+            }
+        });
+        tracker.close();
+
+        shutdown.cancel();
+        tracker.wait().await;
+
+        assert_eq!(starts.load(Ordering::SeqCst), 1);
+    }
+}
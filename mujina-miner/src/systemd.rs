@@ -0,0 +1,260 @@
+//! systemd service integration.
+//!
+//! Implements the small parts of the systemd service protocol the daemon
+//! needs, without linking libsystemd:
+//!
+//! - Readiness and state notifications (`sd_notify`) over the datagram socket
+//!   named by `NOTIFY_SOCKET`, so a `Type=notify` unit knows when the miner
+//!   is up, reloading (API restart), or stopping.
+//! - Watchdog keep-alives. When the unit sets `WatchdogSec=`, systemd passes
+//!   the interval in `WATCHDOG_USEC` and expects `WATCHDOG=1` more often than
+//!   that. The daemon only sends a keep-alive after the backplane event loop
+//!   answers a ping, so a wedged backplane gets the process restarted.
+//! - Socket activation. When started from a `.socket` unit, the API listener
+//!   is inherited from systemd (`LISTEN_FDS`) instead of bound by the daemon.
+//!
+//! Everything here is a no-op when the environment variables are absent, so
+//! the daemon behaves the same when run by hand.
+//!
+//! See: https://www.freedesktop.org/software/systemd/man/latest/sd_notify.html
+//! and https://www.freedesktop.org/software/systemd/man/latest/sd_listen_fds.html
+
+use std::env;
+use std::io;
+use std::net::TcpListener;
+use std::os::fd::{FromRawFd, RawFd};
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::time::Duration;
+
+use crate::tracing::prelude::*;
+
+/// First file descriptor passed by socket activation (`SD_LISTEN_FDS_START`).
+const LISTEN_FDS_START: RawFd = 3;
+
+/// Name the socket unit may give the API listener with `FileDescriptorName=`.
+pub const API_SOCKET_NAME: &str = "api";
+
+/// Send a raw notification, e.g. `"READY=1"`.
+///
+/// Returns `Ok(false)` if the process is not running under a notify-aware
+/// service manager.
+pub fn notify(state: &str) -> io::Result<bool> {
+    let Some(path) = env::var_os("NOTIFY_SOCKET") else {
+        return Ok(false);
+    };
+    let path = path.to_string_lossy();
+    notify_to(&path, state)?;
+    Ok(true)
+}
+
+/// Tell systemd start-up is complete.
+pub fn notify_ready() {
+    send_logged("READY=1\nSTATUS=Mining");
+}
+
+/// Tell systemd the daemon is restarting in place (API-initiated restart).
+pub fn notify_reloading() {
+    send_logged("RELOADING=1\nSTATUS=Restarting");
+}
+
+/// Tell systemd the daemon is shutting down.
+pub fn notify_stopping() {
+    send_logged("STOPPING=1\nSTATUS=Shutting down");
+}
+
+/// Send a watchdog keep-alive.
+pub fn notify_watchdog() {
+    send_logged("WATCHDOG=1");
+}
+
+/// Interval at which systemd expects watchdog keep-alives, if enabled.
+pub fn watchdog_interval() -> Option<Duration> {
+    parse_watchdog(
+        env::var("WATCHDOG_USEC").ok().as_deref(),
+        env::var("WATCHDOG_PID").ok().as_deref(),
+        std::process::id(),
+    )
+}
+
+/// Send `state` to the notification socket at `path`.
+///
+/// A leading `@` denotes a Linux abstract-namespace socket.
+fn notify_to(path: &str, state: &str) -> io::Result<()> {
+    let addr = if let Some(name) = path.strip_prefix('@') {
+        abstract_addr(name)?
+    } else {
+        SocketAddr::from_pathname(path)?
+    };
+
+    let socket = UnixDatagram::unbound()?;
+    socket.send_to_addr(state.as_bytes(), &addr)?;
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn abstract_addr(name: &str) -> io::Result<SocketAddr> {
+    use std::os::linux::net::SocketAddrExt;
+    SocketAddr::from_abstract_name(name)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn abstract_addr(_name: &str) -> io::Result<SocketAddr> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "abstract sockets are only supported on Linux",
+    ))
+}
+
+/// Send a notification, logging rather than failing.
+///
+/// Notifications are advisory; failing to deliver one must not take the
+/// miner down.
+fn send_logged(state: &str) {
+    match notify(state) {
+        Ok(true) => trace!(state, "Sent systemd notification"),
+        Ok(false) => {}
+        Err(e) => warn!(error = %e, state, "Failed to send systemd notification"),
+    }
+}
+
+/// Parse the watchdog environment.
+///
+/// systemd recommends pinging at half the configured interval; this returns
+/// the full interval and leaves the ping rate to the caller.
+fn parse_watchdog(usec: Option<&str>, pid: Option<&str>, my_pid: u32) -> Option<Duration> {
+    if let Some(pid) = pid {
+        if pid.parse::<u32>().ok()? != my_pid {
+            return None;
+        }
+    }
+
+    let usec: u64 = usec?.parse().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec))
+}
+
+/// Parse the socket activation environment, returning how many descriptors
+/// were passed to this process.
+fn parse_listen_fds(pid: Option<&str>, fds: Option<&str>, my_pid: u32) -> usize {
+    let Some(pid) = pid.and_then(|p| p.parse::<u32>().ok()) else {
+        return 0;
+    };
+    if pid != my_pid {
+        return 0;
+    }
+    fds.and_then(|n| n.parse().ok()).unwrap_or(0)
+}
+
+/// Pick the API socket from the descriptor names.
+///
+/// Prefers a descriptor named [`API_SOCKET_NAME`]; otherwise uses the first.
+fn select_api_fd(count: usize, names: Option<&str>) -> Option<usize> {
+    if count == 0 {
+        return None;
+    }
+
+    let named = names.and_then(|names| {
+        names
+            .split(':')
+            .take(count)
+            .position(|n| n == API_SOCKET_NAME)
+    });
+    Some(named.unwrap_or(0))
+}
+
+/// Take ownership of the socket-activated API listener, if one was passed.
+///
+/// Clears the activation variables so child processes don't try to claim the
+/// same descriptors. Call it once, from `main`, and hand the listener to the
+/// daemon (see [`crate::daemon::DaemonOptions::api_listener`]).
+///
+/// # Safety
+///
+/// Changing the environment races with any other thread reading it, so no
+/// other thread may be running: call this before the async runtime starts.
+pub unsafe fn take_api_listener() -> Option<TcpListener> {
+    let count = parse_listen_fds(
+        env::var("LISTEN_PID").ok().as_deref(),
+        env::var("LISTEN_FDS").ok().as_deref(),
+        std::process::id(),
+    );
+    let names = env::var("LISTEN_FDNAMES").ok();
+
+    env::remove_var("LISTEN_PID");
+    env::remove_var("LISTEN_FDS");
+    env::remove_var("LISTEN_FDNAMES");
+
+    let index = select_api_fd(count, names.as_deref())?;
+    let fd = LISTEN_FDS_START + index as RawFd;
+
+    // SAFETY: systemd passes ownership of LISTEN_FDS descriptors starting at
+    // SD_LISTEN_FDS_START, and LISTEN_PID confirmed they are meant for us.
+    // The environment is cleared above, so nothing else will claim this fd.
+    let listener = unsafe { TcpListener::from_raw_fd(fd) };
+    if let Err(e) = listener.set_nonblocking(true) {
+        warn!(fd, error = %e, "Ignoring unusable socket-activated listener");
+        return None;
+    }
+
+    info!(fd, "Using socket-activated API listener.");
+    Some(listener)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notify_to_path_socket() {
+        let dir = std::env::temp_dir().join(format!("mujina-notify-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notify.sock");
+        let _ = std::fs::remove_file(&path);
+
+        let receiver = UnixDatagram::bind(&path).unwrap();
+        notify_to(path.to_str().unwrap(), "READY=1").unwrap();
+
+        let mut buf = [0u8; 64];
+        let n = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"READY=1");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_watchdog_interval() {
+        assert_eq!(
+            parse_watchdog(Some("30000000"), None, 42),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_watchdog(Some("30000000"), Some("42"), 42),
+            Some(Duration::from_secs(30))
+        );
+
+        // Meant for another process
+        assert_eq!(parse_watchdog(Some("30000000"), Some("7"), 42), None);
+
+        // Disabled or malformed
+        assert_eq!(parse_watchdog(None, None, 42), None);
+        assert_eq!(parse_watchdog(Some("0"), None, 42), None);
+        assert_eq!(parse_watchdog(Some("soon"), None, 42), None);
+    }
+
+    #[test]
+    fn test_listen_fds_requires_matching_pid() {
+        assert_eq!(parse_listen_fds(Some("42"), Some("2"), 42), 2);
+        assert_eq!(parse_listen_fds(Some("7"), Some("2"), 42), 0);
+        assert_eq!(parse_listen_fds(None, Some("2"), 42), 0);
+        assert_eq!(parse_listen_fds(Some("42"), None, 42), 0);
+    }
+
+    #[test]
+    fn test_select_api_fd() {
+        assert_eq!(select_api_fd(0, None), None);
+        assert_eq!(select_api_fd(1, None), Some(0));
+        assert_eq!(select_api_fd(2, Some("metrics:api")), Some(1));
+        assert_eq!(select_api_fd(2, Some("metrics:other")), Some(0));
+        // Names beyond the passed count are ignored
+        assert_eq!(select_api_fd(1, Some("metrics:api")), Some(0));
+    }
+}
@@ -7,7 +7,18 @@
 //! The rest of program the can include `use tracing::prelude::*` for convenient
 //! access to the `trace!()`, `debug!()`, `info!()`, `warn!()`, and `error!()`
 //! macros.
+//!
+//! Both outputs filter events through a reloadable [`EnvFilter`], seeded from
+//! `RUST_LOG`. The filter can be changed while running with
+//! [`set_default_level`] and [`set_module_level`], e.g., to bump
+//! `stratum_v1` to TRACE while debugging a pool without restarting.
+//!
+//! Under journald, event and span fields are sent as separate journal fields
+//! (prefixed with `F_`, e.g., `F_SERIAL`, `F_POOL`, `F_JOB_ID`), so prefer
+//! structured fields over values formatted into the message.
 
+use std::collections::BTreeMap;
+use std::sync::{Mutex, OnceLock};
 use std::{env, fmt};
 use time::OffsetDateTime;
 use tracing::field::{Field, Visit};
@@ -22,6 +33,7 @@ use tracing_subscriber::{
     },
     prelude::*,
     registry::LookupSpan,
+    reload, Registry,
 };
 
 #[cfg(target_os = "linux")]
@@ -37,6 +49,210 @@ pub mod prelude {
 
 use prelude::*;
 
+/// Name of this crate as it appears in event targets.
+const CRATE_TARGET: &str = "mujina_miner";
+
+/// Runtime-adjustable log filter state.
+struct LogFilter {
+    handle: reload::Handle<EnvFilter, Registry>,
+    /// Directives from `RUST_LOG` at startup, kept as the base of every reload
+    base: String,
+    /// Default level set at runtime, overriding the startup default
+    default_level: Option<LevelFilter>,
+    /// Per-target levels set at runtime
+    modules: BTreeMap<String, LevelFilter>,
+}
+
+static LOG_FILTER: OnceLock<Mutex<LogFilter>> = OnceLock::new();
+
+/// Errors from runtime log level changes.
+#[derive(Debug, thiserror::Error)]
+pub enum LogLevelError {
+    #[error("logging is not initialized")]
+    NotInitialized,
+
+    #[error("invalid log level: {0}")]
+    InvalidLevel(String),
+
+    #[error("invalid module path: {0}")]
+    InvalidModule(String),
+
+    #[error("failed to apply log filter: {0}")]
+    Reload(String),
+}
+
+/// Snapshot of the runtime log filter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogLevels {
+    /// Level applied to targets without a more specific directive
+    pub default_level: String,
+    /// Per-target levels set at runtime, keyed by full target path
+    pub modules: BTreeMap<String, String>,
+    /// The complete filter directive string currently in effect
+    pub directives: String,
+}
+
+/// Parse a level name (case-insensitive), including "off".
+pub fn parse_level(level: &str) -> Result<LevelFilter, LogLevelError> {
+    level
+        .parse::<LevelFilter>()
+        .map_err(|_| LogLevelError::InvalidLevel(level.to_string()))
+}
+
+/// Expand a module path to a full event target.
+///
+/// Paths are relative to this crate, matching how targets are displayed
+/// (`stratum_v1::client` means `mujina_miner::stratum_v1::client`). Paths
+/// already starting with the crate name are used as-is.
+pub fn qualify_module(module: &str) -> Result<String, LogLevelError> {
+    let valid = !module.is_empty()
+        && module.split("::").all(|segment| {
+            !segment.is_empty()
+                && segment
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        });
+    if !valid {
+        return Err(LogLevelError::InvalidModule(module.to_string()));
+    }
+
+    if module == CRATE_TARGET || module.starts_with(&format!("{}::", CRATE_TARGET)) {
+        Ok(module.to_string())
+    } else {
+        Ok(format!("{}::{}", CRATE_TARGET, module))
+    }
+}
+
+/// Build the directive string for the given filter state.
+///
+/// Runtime settings come last so they take precedence over `RUST_LOG`.
+fn build_directives(
+    base: &str,
+    default_level: Option<LevelFilter>,
+    modules: &BTreeMap<String, LevelFilter>,
+) -> String {
+    let mut directives: Vec<String> = Vec::new();
+    if let Some(level) = default_level {
+        directives.push(level.to_string().to_lowercase());
+    }
+    // A bare level in RUST_LOG would override the runtime default, so drop
+    // it when one is set.
+    directives.extend(
+        base.split(',')
+            .map(str::trim)
+            .filter(|d| !d.is_empty())
+            .filter(|d| default_level.is_none() || d.contains('='))
+            .map(str::to_string),
+    );
+    directives.extend(
+        modules
+            .iter()
+            .map(|(target, level)| format!("{}={}", target, level.to_string().to_lowercase())),
+    );
+    directives.join(",")
+}
+
+/// Build an `EnvFilter` from directives, defaulting to INFO.
+fn make_filter(directives: &str) -> EnvFilter {
+    EnvFilter::builder()
+        .with_default_directive(LevelFilter::INFO.into())
+        .parse_lossy(directives)
+}
+
+/// Create the reloadable filter layer and remember its handle.
+fn reloadable_filter() -> reload::Layer<EnvFilter, Registry> {
+    let base = env::var("RUST_LOG").unwrap_or_default();
+    let (layer, handle) = reload::Layer::new(make_filter(&base));
+
+    let _ = LOG_FILTER.set(Mutex::new(LogFilter {
+        handle,
+        base,
+        default_level: None,
+        modules: BTreeMap::new(),
+    }));
+
+    layer
+}
+
+/// Apply a change to the runtime filter and reload it.
+fn update_filter(change: impl FnOnce(&mut LogFilter)) -> Result<LogLevels, LogLevelError> {
+    let mut filter = LOG_FILTER
+        .get()
+        .ok_or(LogLevelError::NotInitialized)?
+        .lock()
+        .unwrap();
+
+    change(&mut filter);
+
+    let directives = build_directives(&filter.base, filter.default_level, &filter.modules);
+    filter
+        .handle
+        .reload(make_filter(&directives))
+        .map_err(|e| LogLevelError::Reload(e.to_string()))?;
+
+    Ok(snapshot(&filter))
+}
+
+fn snapshot(filter: &LogFilter) -> LogLevels {
+    let directives = build_directives(&filter.base, filter.default_level, &filter.modules);
+
+    // Runtime default, else the last bare level in RUST_LOG, else INFO
+    let default_level = filter
+        .default_level
+        .or_else(|| {
+            filter
+                .base
+                .split(',')
+                .rev()
+                .find_map(|d| d.trim().parse::<LevelFilter>().ok())
+        })
+        .unwrap_or(LevelFilter::INFO);
+
+    LogLevels {
+        default_level: default_level.to_string().to_lowercase(),
+        modules: filter
+            .modules
+            .iter()
+            .map(|(k, v)| (k.clone(), v.to_string().to_lowercase()))
+            .collect(),
+        directives,
+    }
+}
+
+/// Current runtime log levels.
+pub fn log_levels() -> Result<LogLevels, LogLevelError> {
+    let filter = LOG_FILTER
+        .get()
+        .ok_or(LogLevelError::NotInitialized)?
+        .lock()
+        .unwrap();
+    Ok(snapshot(&filter))
+}
+
+/// Set the level for targets without a more specific directive.
+pub fn set_default_level(level: LevelFilter) -> Result<LogLevels, LogLevelError> {
+    info!(level = %level, "Setting default log level");
+    update_filter(|f| f.default_level = Some(level))
+}
+
+/// Set the level for one module (see [`qualify_module`] for path rules).
+pub fn set_module_level(module: &str, level: LevelFilter) -> Result<LogLevels, LogLevelError> {
+    let target = qualify_module(module)?;
+    info!(module = %target, level = %level, "Setting module log level");
+    update_filter(|f| {
+        f.modules.insert(target, level);
+    })
+}
+
+/// Remove a runtime level for one module, reverting to `RUST_LOG`.
+pub fn clear_module_level(module: &str) -> Result<LogLevels, LogLevelError> {
+    let target = qualify_module(module)?;
+    info!(module = %target, "Clearing module log level");
+    update_filter(|f| {
+        f.modules.remove(&target);
+    })
+}
+
 /// Check if stderr is connected to systemd journal by validating JOURNAL_STREAM.
 ///
 /// Per systemd documentation, programs should parse the device and inode numbers
@@ -88,7 +304,10 @@ pub fn init_journald_or_stdout() {
     {
         if stderr_is_journal_stream() {
             if let Ok(layer) = tracing_journald::layer() {
-                tracing_subscriber::registry().with(layer).init();
+                tracing_subscriber::registry()
+                    .with(reloadable_filter())
+                    .with(layer)
+                    .init();
                 return;
             } else {
                 error!("Failed to initialize journald logging, using stdout.");
@@ -102,13 +321,8 @@ pub fn init_journald_or_stdout() {
 // Log to stdout, filtering according to environment variable RUST_LOG,
 // overriding the default level (ERROR) to INFO.
 fn use_stdout() {
-    let env_filter = EnvFilter::builder()
-        .with_default_directive(LevelFilter::INFO.into())
-        .with_env_var("RUST_LOG")
-        .from_env_lossy();
-
     tracing_subscriber::registry()
-        .with(env_filter)
+        .with(reloadable_filter())
         .with(
             tracing_subscriber::fmt::layer()
                 .with_timer(LocalTimer)
@@ -261,3 +475,55 @@ impl FormatTime for LocalTimer {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_qualify_module() {
+        assert_eq!(
+            qualify_module("stratum_v1").unwrap(),
+            "mujina_miner::stratum_v1"
+        );
+        assert_eq!(
+            qualify_module("stratum_v1::client").unwrap(),
+            "mujina_miner::stratum_v1::client"
+        );
+        assert_eq!(
+            qualify_module("mujina_miner::scheduler").unwrap(),
+            "mujina_miner::scheduler"
+        );
+        assert!(qualify_module("").is_err());
+        assert!(qualify_module("stratum_v1::").is_err());
+        assert!(qualify_module("a=trace").is_err());
+    }
+
+    #[test]
+    fn test_parse_level() {
+        assert_eq!(parse_level("TRACE").unwrap(), LevelFilter::TRACE);
+        assert_eq!(parse_level("off").unwrap(), LevelFilter::OFF);
+        assert!(parse_level("loud").is_err());
+    }
+
+    #[test]
+    fn test_build_directives_appends_runtime_modules() {
+        let mut modules = BTreeMap::new();
+        modules.insert("mujina_miner::stratum_v1".to_string(), LevelFilter::TRACE);
+
+        let directives = build_directives("info,hyper=warn", None, &modules);
+
+        assert_eq!(directives, "info,hyper=warn,mujina_miner::stratum_v1=trace");
+    }
+
+    #[test]
+    fn test_build_directives_runtime_default_replaces_bare_level() {
+        let directives = build_directives(
+            "warn,hyper=warn",
+            Some(LevelFilter::DEBUG),
+            &BTreeMap::new(),
+        );
+
+        assert_eq!(directives, "debug,hyper=warn");
+    }
+}
@@ -0,0 +1,52 @@
+//! Direct-attach virtual transport.
+//!
+//! A board wired straight to the host's UART, I2C bus and GPIO lines (e.g. a
+//! Bitaxe on a Raspberry Pi's header) has nothing to discover: no USB
+//! device appears for it. Like the CPU and simulation transports, its
+//! events are synthesized at startup from configuration, here the
+//! `[[hardware.direct]]` entries of the config file.
+
+use std::path::PathBuf;
+
+use crate::config::DirectBoardConfig;
+
+/// Transport events for directly attached boards.
+#[derive(Debug)]
+pub enum TransportEvent {
+    /// A directly attached board was configured.
+    DirectDeviceConnected(DirectDeviceInfo),
+
+    /// A directly attached board was removed.
+    DirectDeviceDisconnected { device_id: String },
+}
+
+/// Where a directly attached board is wired to the host.
+#[derive(Debug, Clone)]
+pub struct DirectDeviceInfo {
+    /// Unique identifier, standing in for a USB serial number.
+    pub device_id: String,
+
+    /// UART connected to the ASICs.
+    pub uart: String,
+
+    /// I2C adapter of the board's fan controller and regulator.
+    pub i2c: PathBuf,
+
+    /// GPIO chip of the reset line.
+    pub gpio_chip: PathBuf,
+
+    /// Line on `gpio_chip` driving the ASICs' reset.
+    pub reset_line: u8,
+}
+
+impl From<&DirectBoardConfig> for DirectDeviceInfo {
+    fn from(config: &DirectBoardConfig) -> Self {
+        Self {
+            device_id: config.id.clone(),
+            uart: config.uart.clone(),
+            i2c: config.i2c.clone(),
+            gpio_chip: config.gpio_chip.clone(),
+            reset_line: config.reset_line,
+        }
+    }
+}
@@ -6,16 +6,20 @@
 //! events when devices are connected or disconnected.
 
 pub mod cpu;
+pub mod direct;
 pub mod serial;
+pub mod sim;
 pub mod usb;
 
 // Re-export transport implementations
 pub use cpu::CpuDeviceInfo;
+pub use direct::DirectDeviceInfo;
 pub use serial::{
-    Parity, SerialConfig, SerialControl, SerialError, SerialReader, SerialStats, SerialStream,
-    SerialWriter,
+    FlowControl, LineErrors, Parity, PortBusy, SerialConfig, SerialControl, SerialError,
+    SerialReader, SerialStats, SerialStream, SerialWriter,
 };
-pub use usb::{UsbDeviceInfo, UsbTransport};
+pub use sim::SimDeviceInfo;
+pub use usb::{PortRole, SerialInterface, UsbDeviceInfo, UsbTransport};
 
 /// Generic transport event that can represent different transport types.
 #[derive(Debug)]
@@ -25,6 +29,12 @@ pub enum TransportEvent {
 
     /// CPU miner virtual device event
     Cpu(cpu::TransportEvent),
+
+    /// Simulated device event
+    Sim(sim::TransportEvent),
+
+    /// Directly attached board event
+    Direct(direct::TransportEvent),
 }
 
 /// Common trait for transport discovery (future enhancement).
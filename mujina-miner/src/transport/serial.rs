@@ -14,23 +14,56 @@
 //! Our implementation maintains shared ownership of the underlying file
 //! descriptor, allowing the Control handle to reconfigure the port even
 //! after it has been split for concurrent I/O operations.
+//!
+//! ## Port locking
+//!
+//! Ports are opened with an exclusive advisory lock, the same `flock`
+//! pyserial takes for `exclusive=True`, so a second mujina instance or a
+//! firmware flasher and the miner can't both drive a board. Whoever comes
+//! second gets [`PortBusy`], naming the processes that have the port open.
+//! The lock goes with the file descriptor when the stream is dropped.
+//!
+//! ## Latency
+//!
+//! USB serial adapters batch received bytes before handing them to the
+//! host; FTDI's wait up to 16 ms by default, which is long next to a chip's
+//! response. A [`SerialConfig`] can ask the driver for low latency (the
+//! `ASYNC_LOW_LATENCY` flag, which FTDI's Linux driver honors by dropping
+//! its latency timer to 1 ms) or set the FTDI latency timer outright. Ports
+//! whose driver has neither, such as CDC-ACM, are left as they are.
+//!
+//! ## Riding out a disconnect
+//!
+//! A USB serial device that drops off the bus for a moment, from a glitch
+//! or its controller resetting, comes back as a new device node. A port
+//! configured to [hold on disconnect](SerialConfig::with_hold_on_disconnect)
+//! doesn't fail its reader and writer when that happens: they wait until
+//! [`SerialControl::reopen`] points the port at the new node, then carry
+//! on there, so whatever framing sits on top never sees the gap.
+//! [`SerialControl::release`] stops the waiting, for a device that isn't
+//! coming back. `EAGAIN` is never an error; it only means no data yet.
 
 use std::io;
 #[cfg(test)]
 use std::os::unix::io::FromRawFd;
-use std::os::unix::io::{AsRawFd, BorrowedFd, IntoRawFd, RawFd};
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, OwnedFd};
+use std::path::Path;
 use std::pin::Pin;
-use std::sync::atomic::{AtomicU32, AtomicU64, AtomicU8, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicU8, Ordering};
 use std::sync::Arc;
 use std::task::{Context, Poll};
 use std::time::Duration;
 
 use futures::ready;
+use futures::task::AtomicWaker;
+use nix::libc;
 use parking_lot::RwLock;
-use rustix::fs::{open, Mode, OFlags};
+use rustix::fs::{flock, open, FlockOperation, Mode, OFlags};
+use rustix::io::Errno;
 use rustix::termios::{tcdrain, tcgetattr, tcsetattr, ControlModes};
 use tokio::io::unix::AsyncFd;
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tracing::{debug, warn};
 
 /// Parity configuration.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -40,6 +73,14 @@ pub enum Parity {
     Even = 2,
 }
 
+/// Flow control configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlowControl {
+    None,
+    /// RTS/CTS
+    Hardware,
+}
+
 /// Serial port configuration.
 #[derive(Debug, Clone, Copy)]
 pub struct SerialConfig {
@@ -47,17 +88,66 @@ pub struct SerialConfig {
     pub data_bits: u8,
     pub stop_bits: u8,
     pub parity: Parity,
+    pub flow_control: FlowControl,
+    /// Ask the driver to pass received bytes on at once (Linux only)
+    pub low_latency: bool,
+    /// FTDI latency timer in milliseconds, if it should be set
+    pub latency_timer_ms: Option<u8>,
+    /// Wait for the port to be reopened rather than fail when it's lost
+    pub hold_on_disconnect: bool,
 }
 
-impl Default for SerialConfig {
-    fn default() -> Self {
+impl SerialConfig {
+    /// 8N1 at `baud_rate`, without flow control.
+    pub const fn new(baud_rate: u32) -> Self {
         Self {
-            baud_rate: 115200,
+            baud_rate,
             data_bits: 8,
             stop_bits: 1,
             parity: Parity::None,
+            flow_control: FlowControl::None,
+            low_latency: false,
+            latency_timer_ms: None,
+            hold_on_disconnect: false,
         }
     }
+
+    pub const fn with_flow_control(mut self, flow_control: FlowControl) -> Self {
+        self.flow_control = flow_control;
+        self
+    }
+
+    pub const fn with_low_latency(mut self) -> Self {
+        self.low_latency = true;
+        self
+    }
+
+    pub const fn with_latency_timer(mut self, ms: u8) -> Self {
+        self.latency_timer_ms = Some(ms);
+        self
+    }
+
+    pub const fn with_hold_on_disconnect(mut self) -> Self {
+        self.hold_on_disconnect = true;
+        self
+    }
+}
+
+impl Default for SerialConfig {
+    fn default() -> Self {
+        Self::new(115200)
+    }
+}
+
+/// A serial port held by another process.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{path} is in use by {}", describe_holders(.holders))]
+pub struct PortBusy {
+    /// Port path
+    pub path: String,
+
+    /// Processes with the port open, where we're allowed to see them
+    pub holders: Vec<u32>,
 }
 
 /// Serial port error types.
@@ -66,6 +156,9 @@ pub enum SerialError {
     #[error("Failed to open serial port: {0}")]
     OpenError(#[source] io::Error),
 
+    #[error(transparent)]
+    Busy(#[from] PortBusy),
+
     #[error("Unsupported baud rate: {0}")]
     UnsupportedBaudRate(u32),
 
@@ -96,14 +189,45 @@ pub struct SerialStats {
     pub baud_rate: u32,
 }
 
+/// Receive errors counted by the UART driver since the port was opened.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LineErrors {
+    /// Characters received with a bad stop bit, usually a baud mismatch
+    pub framing: u64,
+    /// Characters lost because the UART's receive FIFO was full
+    pub overrun: u64,
+    /// Characters with a parity error
+    pub parity: u64,
+    /// Characters lost because the driver's buffer was full
+    pub buffer_overrun: u64,
+}
+
+/// Kernel `struct serial_icounter_struct`, filled by `TIOCGICOUNT`.
+#[repr(C)]
+#[derive(Default)]
+struct SerialIcounter {
+    cts: libc::c_int,
+    dsr: libc::c_int,
+    rng: libc::c_int,
+    dcd: libc::c_int,
+    rx: libc::c_int,
+    tx: libc::c_int,
+    frame: libc::c_int,
+    overrun: libc::c_int,
+    parity: libc::c_int,
+    brk: libc::c_int,
+    buf_overrun: libc::c_int,
+    reserved: [libc::c_int; 9],
+}
+
 /// A serial stream implementation that supports runtime reconfiguration.
 pub struct SerialStream {
     inner: Arc<SerialInner>,
 }
 
 struct SerialInner {
-    /// File descriptor - immutable after creation
-    fd: AsyncFd<RawFd>,
+    /// File descriptor - replaced only when the port is reopened
+    fd: RwLock<Arc<AsyncFd<OwnedFd>>>,
 
     /// Current configuration - atomic for lock-free reads
     baud_rate: AtomicU32,
@@ -111,9 +235,22 @@ struct SerialInner {
     stop_bits: AtomicU8,
     parity: AtomicU8, // 0 = None, 1 = Odd, 2 = Even
 
+    /// Settings fixed when the port was opened
+    flow_control: FlowControl,
+    low_latency: bool,
+    latency_timer_ms: Option<u8>,
+
     /// Lock only for actual reconfiguration
     reconfig_lock: RwLock<()>,
 
+    /// Whether I/O waits for a reopen when the port is lost
+    hold: AtomicBool,
+    /// Whether the port has been lost and not yet reopened
+    lost: AtomicBool,
+    /// Reader and writer waiting for a reopen
+    read_waker: AtomicWaker,
+    write_waker: AtomicWaker,
+
     /// Statistics (lock-free)
     bytes_read: AtomicU64,
     bytes_written: AtomicU64,
@@ -130,6 +267,10 @@ pub struct SerialWriter {
 }
 
 /// Control handle for a split serial stream.
+///
+/// Clones share the port, so a monitor can watch the line while another
+/// owner retunes it.
+#[derive(Clone)]
 pub struct SerialControl {
     inner: Arc<SerialInner>,
 }
@@ -196,6 +337,12 @@ fn apply_serial_config<Fd: rustix::fd::AsFd>(
         }
     }
 
+    // Configure flow control
+    match config.flow_control {
+        FlowControl::None => termios.control_modes &= !ControlModes::CRTSCTS,
+        FlowControl::Hardware => termios.control_modes |= ControlModes::CRTSCTS,
+    }
+
     // Apply configuration
     tcsetattr(fd, rustix::termios::OptionalActions::Now, &termios)
         .map_err(|e| SerialError::ConfigError(format!("Failed to apply termios: {}", e)))?;
@@ -231,28 +378,28 @@ impl SerialStream {
     /// Open a new serial port with the specified configuration.
     pub fn with_config(path: &str, config: SerialConfig) -> Result<Self, SerialError> {
         // Open serial port
-        let fd = open(
-            path,
-            OFlags::RDWR | OFlags::NOCTTY | OFlags::NONBLOCK,
-            Mode::empty(),
-        )
-        .map_err(|e| SerialError::OpenError(e.into()))?;
-
-        // Apply serial configuration
-        apply_serial_config(&fd, &config)?;
+        let fd = open_port(path, &config)?;
+        Self::from_configured_fd(fd, config)
+    }
 
-        // Convert OwnedFd to RawFd for AsyncFd
-        let raw_fd = fd.into_raw_fd();
-        let async_fd = AsyncFd::new(raw_fd).map_err(SerialError::IoError)?;
+    fn from_configured_fd(fd: OwnedFd, config: SerialConfig) -> Result<Self, SerialError> {
+        let async_fd = AsyncFd::new(fd).map_err(SerialError::IoError)?;
 
         Ok(Self {
             inner: Arc::new(SerialInner {
-                fd: async_fd,
+                fd: RwLock::new(Arc::new(async_fd)),
                 baud_rate: AtomicU32::new(config.baud_rate),
                 data_bits: AtomicU8::new(config.data_bits),
                 stop_bits: AtomicU8::new(config.stop_bits),
                 parity: AtomicU8::new(config.parity as u8),
+                flow_control: config.flow_control,
+                low_latency: config.low_latency,
+                latency_timer_ms: config.latency_timer_ms,
                 reconfig_lock: RwLock::new(()),
+                hold: AtomicBool::new(config.hold_on_disconnect),
+                lost: AtomicBool::new(false),
+                read_waker: AtomicWaker::new(),
+                write_waker: AtomicWaker::new(),
                 bytes_read: AtomicU64::new(0),
                 bytes_written: AtomicU64::new(0),
             }),
@@ -281,9 +428,12 @@ impl SerialStream {
     ///
     /// This is primarily used for testing with virtual serial ports.
     #[cfg(test)]
-    pub(crate) fn from_fd(fd: RawFd, config: SerialConfig) -> Result<Self, SerialError> {
+    pub(crate) fn from_fd(
+        fd: std::os::unix::io::RawFd,
+        config: SerialConfig,
+    ) -> Result<Self, SerialError> {
         // Convert raw fd to OwnedFd
-        let fd = unsafe { rustix::fd::OwnedFd::from_raw_fd(fd) };
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
 
         // Apply serial configuration
         apply_serial_config(&fd, &config)?;
@@ -295,20 +445,7 @@ impl SerialStream {
         fcntl_setfl(&fd, flags | OFlags::NONBLOCK)
             .map_err(|e| SerialError::ConfigError(format!("Failed to set fd flags: {}", e)))?;
 
-        let async_fd = AsyncFd::new(fd.into_raw_fd()).map_err(SerialError::IoError)?;
-
-        Ok(Self {
-            inner: Arc::new(SerialInner {
-                fd: async_fd,
-                baud_rate: AtomicU32::new(config.baud_rate),
-                data_bits: AtomicU8::new(config.data_bits),
-                stop_bits: AtomicU8::new(config.stop_bits),
-                parity: AtomicU8::new(config.parity as u8),
-                reconfig_lock: RwLock::new(()),
-                bytes_read: AtomicU64::new(0),
-                bytes_written: AtomicU64::new(0),
-            }),
-        })
+        Self::from_configured_fd(fd, config)
     }
 }
 
@@ -319,22 +456,17 @@ impl AsyncRead for SerialReader {
         buf: &mut ReadBuf<'_>,
     ) -> Poll<io::Result<()>> {
         loop {
-            let mut guard = ready!(self.inner.fd.poll_read_ready(cx))?;
+            let fd = self.inner.fd();
+            let mut guard = ready!(fd.poll_read_ready(cx))?;
 
-            match guard.try_io(|inner| {
+            let result = guard.try_io(|inner| {
                 let fd = inner.as_raw_fd();
                 let slice = buf.initialize_unfilled();
 
                 // Direct read syscall
                 let fd_ref = unsafe { BorrowedFd::borrow_raw(fd) };
                 match rustix::io::read(fd_ref, slice) {
-                    Ok(n) => {
-                        buf.advance(n);
-                        if n > 0 {
-                            self.inner.bytes_read.fetch_add(n as u64, Ordering::Relaxed);
-                        }
-                        Ok(())
-                    }
+                    Ok(n) => Ok(n),
                     Err(rustix::io::Errno::AGAIN) => {
                         Err(io::Error::from(io::ErrorKind::WouldBlock))
                     }
@@ -351,8 +483,20 @@ impl AsyncRead for SerialReader {
                     }
                     Err(e) => Err(e.into()),
                 }
-            }) {
-                Ok(result) => return Poll::Ready(result),
+            });
+            match result {
+                // A hung-up tty reads as end of file
+                Ok(Ok(0)) | Ok(Err(_)) if buf.remaining() > 0 && self.inner.holds() => {
+                    ready!(self.inner.wait_reopen(&fd, &self.inner.read_waker, cx));
+                }
+                Ok(result) => {
+                    let n = result?;
+                    buf.advance(n);
+                    if n > 0 {
+                        self.inner.bytes_read.fetch_add(n as u64, Ordering::Relaxed);
+                    }
+                    return Poll::Ready(Ok(()));
+                }
                 Err(_would_block) => continue,
             }
         }
@@ -366,7 +510,8 @@ impl AsyncWrite for SerialWriter {
         buf: &[u8],
     ) -> Poll<io::Result<usize>> {
         loop {
-            let mut guard = ready!(self.inner.fd.poll_write_ready(cx))?;
+            let fd = self.inner.fd();
+            let mut guard = ready!(fd.poll_write_ready(cx))?;
 
             match guard.try_io(|inner| {
                 let fd = inner.as_raw_fd();
@@ -399,6 +544,9 @@ impl AsyncWrite for SerialWriter {
                     Err(e) => Err(e.into()),
                 }
             }) {
+                Ok(Err(_)) if self.inner.holds() => {
+                    ready!(self.inner.wait_reopen(&fd, &self.inner.write_waker, cx));
+                }
                 Ok(result) => return Poll::Ready(result),
                 Err(_would_block) => continue,
             }
@@ -416,6 +564,41 @@ impl AsyncWrite for SerialWriter {
     }
 }
 
+impl SerialInner {
+    /// The port's descriptor as it stands.
+    fn fd(&self) -> Arc<AsyncFd<OwnedFd>> {
+        self.fd.read().clone()
+    }
+
+    fn holds(&self) -> bool {
+        self.hold.load(Ordering::Acquire)
+    }
+
+    /// Wait, on `waker`, for a port lost on `lost_fd` to be reopened.
+    /// Ready at once if it already has been.
+    fn wait_reopen(
+        &self,
+        lost_fd: &Arc<AsyncFd<OwnedFd>>,
+        waker: &AtomicWaker,
+        cx: &mut Context<'_>,
+    ) -> Poll<()> {
+        waker.register(cx.waker());
+        // Checked after registering, so a reopen in between isn't missed
+        if !Arc::ptr_eq(&self.fd.read(), lost_fd) || !self.holds() {
+            return Poll::Ready(());
+        }
+        if !self.lost.swap(true, Ordering::AcqRel) {
+            warn!("Serial port lost; waiting for it to be reopened");
+        }
+        Poll::Pending
+    }
+
+    fn wake(&self) {
+        self.read_waker.wake();
+        self.write_waker.wake();
+    }
+}
+
 impl SerialControl {
     /// Change the baud rate of the serial port.
     ///
@@ -447,7 +630,8 @@ impl SerialControl {
         };
 
         // Get current termios
-        let fd = self.inner.fd.as_raw_fd();
+        let fd = self.inner.fd();
+        let fd = fd.as_raw_fd();
         // Temporarily create a borrowed fd for the termios calls
         let fd_ref = unsafe { BorrowedFd::borrow_raw(fd) };
         let mut termios = tcgetattr(fd_ref)
@@ -500,7 +684,44 @@ impl SerialControl {
             data_bits: self.current_data_bits(),
             stop_bits: self.current_stop_bits(),
             parity: self.current_parity(),
+            flow_control: self.inner.flow_control,
+            low_latency: self.inner.low_latency,
+            latency_timer_ms: self.inner.latency_timer_ms,
+            hold_on_disconnect: self.inner.holds(),
+        }
+    }
+
+    /// Point the port at `path`, the node its device came back as after a
+    /// disconnect, opened and configured as the port is now. Reads and
+    /// writes waiting on the lost port carry on there.
+    pub fn reopen(&self, path: &str) -> Result<(), SerialError> {
+        const TIMEOUT: Duration = Duration::from_secs(5);
+        let Some(_lock) = self.inner.reconfig_lock.try_write_for(TIMEOUT) else {
+            return Err(SerialError::ConfigError(
+                "Failed to acquire configuration lock - possible deadlock".to_string(),
+            ));
+        };
+
+        let fd = open_port(path, &self.current_config())?;
+        let fd = AsyncFd::new(fd).map_err(SerialError::IoError)?;
+        *self.inner.fd.write() = Arc::new(fd);
+        if self.inner.lost.swap(false, Ordering::AcqRel) {
+            debug!(port = path, "Serial port reopened");
         }
+        self.inner.wake();
+        Ok(())
+    }
+
+    /// Whether the port has been lost and is waiting to be reopened.
+    pub fn is_lost(&self) -> bool {
+        self.inner.lost.load(Ordering::Acquire)
+    }
+
+    /// Stop holding on disconnect: reads and writes waiting for a reopen,
+    /// and any after, fail as the port does.
+    pub fn release(&self) {
+        self.inner.hold.store(false, Ordering::Release);
+        self.inner.wake();
     }
 
     /// Get statistics about the serial port.
@@ -512,6 +733,31 @@ impl SerialControl {
         }
     }
 
+    /// Read the driver's receive error counters.
+    ///
+    /// Most UART drivers, including USB CDC-ACM, keep these; ptys don't, and
+    /// return an error.
+    pub fn line_errors(&self) -> Result<LineErrors, SerialError> {
+        let mut counts = SerialIcounter::default();
+        let fd = self.inner.fd();
+        let fd = fd.as_raw_fd();
+
+        // SAFETY: TIOCGICOUNT writes one serial_icounter_struct, which
+        // SerialIcounter mirrors, to the pointer it's given
+        let ret = unsafe { libc::ioctl(fd, libc::TIOCGICOUNT, &mut counts as *mut SerialIcounter) };
+        if ret != 0 {
+            return Err(SerialError::IoError(io::Error::last_os_error()));
+        }
+
+        // The kernel counters are ints that wrap; read them as unsigned
+        Ok(LineErrors {
+            framing: counts.frame as u32 as u64,
+            overrun: counts.overrun as u32 as u64,
+            parity: counts.parity as u32 as u64,
+            buffer_overrun: counts.buf_overrun as u32 as u64,
+        })
+    }
+
     /// Reset the statistics counters to zero.
     pub fn reset_stats(&self) {
         self.inner.bytes_read.store(0, Ordering::Relaxed);
@@ -519,20 +765,181 @@ impl SerialControl {
     }
 }
 
+impl PortBusy {
+    /// Port at `path` is busy; find out who has it.
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            holders: port_holders(Path::new(path)),
+        }
+    }
+}
+
 impl Drop for SerialInner {
     fn drop(&mut self) {
-        // Note: AsyncFd automatically closes the file descriptor
+        // Note: AsyncFd drops the OwnedFd, closing it and releasing the lock
         // But we should drain pending output data first
-        let fd = self.fd.as_raw_fd();
+        let fd = self.fd.get_mut().as_raw_fd();
 
         // Best effort drain - ignore errors on drop
         let _ = tcdrain(unsafe { BorrowedFd::borrow_raw(fd) });
     }
 }
 
+/// Apply `config`'s latency settings to the port at `path`. They only
+/// speed the port up, so where they can't be applied the port is used as
+/// it is.
+fn apply_latency(fd: BorrowedFd<'_>, path: &str, config: &SerialConfig) {
+    if config.low_latency {
+        if let Err(e) = set_low_latency(fd) {
+            warn!(port = path, error = %e, "Failed to set low latency");
+        }
+    }
+    if let Some(ms) = config.latency_timer_ms {
+        let timer = latency_timer_path(path);
+        match std::fs::write(&timer, ms.to_string()) {
+            Ok(()) => debug!(port = path, ms, "Set latency timer"),
+            Err(e) => {
+                warn!(port = path, path = %timer.display(), error = %e, "Failed to set latency timer")
+            }
+        }
+    }
+}
+
+/// Kernel `struct serial_struct`, read and written by `TIOCGSERIAL` and
+/// `TIOCSSERIAL`.
+#[cfg(target_os = "linux")]
+#[repr(C)]
+struct SerialStruct {
+    type_: libc::c_int,
+    line: libc::c_int,
+    port: libc::c_uint,
+    irq: libc::c_int,
+    flags: libc::c_int,
+    xmit_fifo_size: libc::c_int,
+    custom_divisor: libc::c_int,
+    baud_base: libc::c_int,
+    close_delay: libc::c_ushort,
+    io_type: libc::c_char,
+    reserved_char: [libc::c_char; 1],
+    hub6: libc::c_int,
+    closing_wait: libc::c_ushort,
+    closing_wait2: libc::c_ushort,
+    iomem_base: *mut libc::c_uchar,
+    iomem_reg_shift: libc::c_ushort,
+    port_high: libc::c_uint,
+    iomap_base: libc::c_ulong,
+}
+
+/// Set `ASYNC_LOW_LATENCY` on the port.
+#[cfg(target_os = "linux")]
+fn set_low_latency(fd: BorrowedFd<'_>) -> io::Result<()> {
+    const ASYNC_LOW_LATENCY: libc::c_int = 1 << 13;
+
+    let fd = fd.as_raw_fd();
+    // SAFETY: both ioctls take a pointer to one serial_struct, which
+    // SerialStruct mirrors; all-zero bytes are a valid one to read into
+    let mut serial: SerialStruct = unsafe { std::mem::zeroed() };
+    if unsafe { libc::ioctl(fd, libc::TIOCGSERIAL, &mut serial as *mut SerialStruct) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    serial.flags |= ASYNC_LOW_LATENCY;
+    if unsafe { libc::ioctl(fd, libc::TIOCSSERIAL, &serial as *const SerialStruct) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_low_latency(_fd: BorrowedFd<'_>) -> io::Result<()> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+/// Sysfs attribute holding the FTDI latency timer of the port at `path`,
+/// which may be a symlink such as one under `/dev/serial/by-id`.
+fn latency_timer_path(path: &str) -> std::path::PathBuf {
+    let device = std::fs::canonicalize(path).unwrap_or_else(|_| path.into());
+    let name = device.file_name().unwrap_or(device.as_os_str());
+    Path::new("/sys/class/tty")
+        .join(name)
+        .join("device/latency_timer")
+}
+
+/// Open and lock the port at `path`, and configure it as `config` says.
+fn open_port(path: &str, config: &SerialConfig) -> Result<OwnedFd, SerialError> {
+    let fd = open(
+        path,
+        OFlags::RDWR | OFlags::NOCTTY | OFlags::NONBLOCK,
+        Mode::empty(),
+    )
+    .map_err(|e| match e {
+        // Opened by another process with TIOCEXCL
+        Errno::BUSY => SerialError::Busy(PortBusy::new(path)),
+        e => SerialError::OpenError(e.into()),
+    })?;
+    lock_port(fd.as_fd(), path)?;
+
+    // Apply serial configuration
+    apply_serial_config(&fd, config)?;
+    apply_latency(fd.as_fd(), path, config);
+    Ok(fd)
+}
+
+/// Take an exclusive advisory lock on the port open as `fd`, without
+/// waiting.
+///
+/// The lock belongs to the open file, so it's released when the last
+/// descriptor for it closes.
+fn lock_port(fd: BorrowedFd<'_>, path: &str) -> Result<(), SerialError> {
+    match flock(fd, FlockOperation::NonBlockingLockExclusive) {
+        Ok(()) => Ok(()),
+        Err(Errno::WOULDBLOCK) => Err(PortBusy::new(path).into()),
+        Err(e) => Err(SerialError::OpenError(e.into())),
+    }
+}
+
+/// Processes other than this one with `path` open, found the way `lsof`
+/// does, by reading the `/proc/<pid>/fd` links. Processes we may not look
+/// into are skipped.
+fn port_holders(path: &Path) -> Vec<u32> {
+    let Ok(path) = path.canonicalize() else {
+        return Vec::new();
+    };
+    let Ok(procs) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+
+    let own = std::process::id();
+    let mut holders: Vec<u32> = procs
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<u32>().ok())
+        .filter(|&pid| pid != own)
+        .filter(|pid| {
+            std::fs::read_dir(format!("/proc/{pid}/fd")).is_ok_and(|fds| {
+                fds.flatten()
+                    .any(|fd| std::fs::read_link(fd.path()).is_ok_and(|target| target == path))
+            })
+        })
+        .collect();
+    holders.sort_unstable();
+    holders
+}
+
+fn describe_holders(holders: &[u32]) -> String {
+    match holders {
+        [] => "another process".to_string(),
+        [pid] => format!("pid {pid}"),
+        pids => {
+            let pids: Vec<String> = pids.iter().map(u32::to_string).collect();
+            format!("pids {}", pids.join(", "))
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::os::unix::io::IntoRawFd;
     use std::time::Duration;
     use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
@@ -557,6 +964,7 @@ mod tests {
                 data_bits: 7,
                 stop_bits: 2,
                 parity: Parity::Even,
+                ..Default::default()
             };
             let result = apply_serial_config(&pty.master, &config);
             assert!(result.is_ok(), "Custom valid config should work");
@@ -753,6 +1161,52 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    #[cfg_attr(
+        feature = "skip-pty-tests",
+        ignore = "PTY tests skipped via feature flag"
+    )]
+    async fn test_second_open_finds_port_busy() {
+        let pty = nix::pty::openpty(None, None).unwrap();
+        let path = std::fs::read_link(format!("/proc/self/fd/{}", pty.slave.as_raw_fd())).unwrap();
+        let path = path.to_str().unwrap();
+
+        let first = SerialStream::new(path, 115200).unwrap();
+        match SerialStream::new(path, 115200) {
+            Err(SerialError::Busy(busy)) => {
+                assert_eq!(busy.path, path);
+                // Only we have it open, and we don't count
+                assert!(busy.holders.is_empty());
+            }
+            Err(e) => panic!("expected busy, got {e}"),
+            Ok(_) => panic!("expected busy, opened twice"),
+        }
+
+        // Closing the port releases the lock
+        drop(first);
+        SerialStream::new(path, 115200).unwrap();
+    }
+
+    #[test]
+    fn test_port_busy_names_holders() {
+        let busy = |holders: Vec<u32>| PortBusy {
+            path: "/dev/ttyACM1".into(),
+            holders,
+        };
+        assert_eq!(
+            busy(vec![]).to_string(),
+            "/dev/ttyACM1 is in use by another process"
+        );
+        assert_eq!(
+            busy(vec![812]).to_string(),
+            "/dev/ttyACM1 is in use by pid 812"
+        );
+        assert_eq!(
+            busy(vec![812, 9001]).to_string(),
+            "/dev/ttyACM1 is in use by pids 812, 9001"
+        );
+    }
+
     #[tokio::test]
     async fn test_baud_rate_change() {
         use test_support::create_virtual_pair;
@@ -884,6 +1338,89 @@ mod tests {
         assert_eq!(stats.bytes_written, 0);
     }
 
+    #[test]
+    fn test_latency_timer_found_in_sysfs() {
+        assert_eq!(
+            latency_timer_path("/dev/ttyUSB7"),
+            Path::new("/sys/class/tty/ttyUSB7/device/latency_timer")
+        );
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        feature = "skip-pty-tests",
+        ignore = "PTY tests skipped via feature flag"
+    )]
+    async fn test_flow_control_survives_baud_change() {
+        let pty = nix::pty::openpty(None, None).unwrap();
+        let config = SerialConfig::new(115200).with_flow_control(FlowControl::Hardware);
+        let stream = SerialStream::from_fd(pty.master.into_raw_fd(), config).unwrap();
+        let (_reader, _writer, control) = stream.split();
+
+        control.set_baud_rate(1_000_000).unwrap();
+
+        let fd = unsafe { BorrowedFd::borrow_raw(control.inner.fd().as_raw_fd()) };
+        let termios = tcgetattr(fd).unwrap();
+        assert!(termios.control_modes.contains(ControlModes::CRTSCTS));
+        assert_eq!(control.current_config().flow_control, FlowControl::Hardware);
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        feature = "skip-pty-tests",
+        ignore = "PTY tests skipped via feature flag"
+    )]
+    async fn test_latency_settings_skipped_where_unsupported() {
+        let pty = nix::pty::openpty(None, None).unwrap();
+        let path = nix::unistd::ttyname(&pty.slave).unwrap();
+        let config = SerialConfig::new(115200)
+            .with_low_latency()
+            .with_latency_timer(1);
+
+        // A pty has neither, and opens regardless
+        let stream = SerialStream::with_config(path.to_str().unwrap(), config).unwrap();
+        let (_reader, _writer, control) = stream.split();
+        assert_eq!(control.current_config().latency_timer_ms, Some(1));
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        feature = "skip-pty-tests",
+        ignore = "PTY tests skipped via feature flag"
+    )]
+    async fn test_held_port_carries_on_after_reopen() {
+        let first = nix::pty::openpty(None, None).unwrap();
+        let path = nix::unistd::ttyname(&first.slave).unwrap();
+        let config = SerialConfig::new(115200).with_hold_on_disconnect();
+        let stream = SerialStream::with_config(path.to_str().unwrap(), config).unwrap();
+        let (mut reader, _writer, control) = stream.split();
+
+        // The device drops off the bus
+        drop(first);
+        let read = tokio::spawn(async move {
+            let mut buf = [0u8; 5];
+            reader.read_exact(&mut buf).await.map(|_| buf)
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!read.is_finished());
+        assert!(control.is_lost());
+
+        // And comes back as another node
+        let second = nix::pty::openpty(None, None).unwrap();
+        let path = nix::unistd::ttyname(&second.slave).unwrap();
+        control.reopen(path.to_str().unwrap()).unwrap();
+        assert!(!control.is_lost());
+        let mut device = std::fs::File::from(second.master);
+        std::io::Write::write_all(&mut device, b"hello").unwrap();
+
+        let buf = tokio::time::timeout(Duration::from_secs(1), read)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(&buf, b"hello");
+    }
+
     #[test]
     fn test_invalid_configurations() {
         // Since we can't easily test with real device paths,
@@ -902,6 +1439,7 @@ mod tests {
             data_bits: 7,
             stop_bits: 2,
             parity: Parity::Even,
+            ..Default::default()
         };
         assert_eq!(config.data_bits, 7);
         assert_eq!(config.stop_bits, 2);
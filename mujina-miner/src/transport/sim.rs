@@ -0,0 +1,40 @@
+//! Simulation board virtual transport.
+//!
+//! Like the CPU transport, these events are synthesized at startup from
+//! configuration rather than discovered from hardware. See
+//! [`crate::board::sim`] for the board they create.
+
+use std::path::PathBuf;
+
+use crate::types::HashRate;
+
+/// Transport events for simulated devices.
+#[derive(Debug)]
+pub enum TransportEvent {
+    /// A simulated device was connected (enabled via configuration).
+    SimDeviceConnected(SimDeviceInfo),
+
+    /// A simulated device was disconnected.
+    SimDeviceDisconnected { device_id: String },
+}
+
+/// Information about a simulated device.
+#[derive(Debug, Clone)]
+pub struct SimDeviceInfo {
+    /// Unique identifier for this virtual device.
+    pub device_id: String,
+
+    /// Number of hash threads to create.
+    pub thread_count: usize,
+
+    /// Simulated hashrate of each thread.
+    pub hashrate: HashRate,
+
+    /// Seed for the share-timing random number generator. The same seed and
+    /// the same sequence of work assignments produce the same shares.
+    pub seed: u64,
+
+    /// Scripted share fixture (JSON). When set, shares come from the script
+    /// instead of the random model.
+    pub script: Option<PathBuf>,
+}
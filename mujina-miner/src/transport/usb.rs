@@ -4,12 +4,18 @@
 //! It provides raw device information without any knowledge of
 //! what the devices are or how they should be configured.
 //!
+//! A composite device, such as a bitaxe-raw controller with its control
+//! and data interfaces, is one [`UsbDeviceInfo`], its serial ports listed
+//! in interface order. The board implementation says which is which with a
+//! [`PortRole`] layout (see [`UsbDeviceInfo::serial_port`]).
+//!
 //! ## Platform Support
 //!
 //! - **Linux**: Uses udev for device enumeration and hotplug monitoring
 //! - **macOS**: Stub implementation (IOKit support planned for future)
 
 use crate::{error::Result, tracing::prelude::*};
+use std::fmt;
 use std::sync::OnceLock;
 use tokio::sync::mpsc;
 use tokio_util::sync::CancellationToken;
@@ -29,15 +35,36 @@ pub struct UsbDeviceInfo {
     pub product: Option<String>,
     /// USB device path (e.g., "/sys/bus/usb/devices/1-1.2")
     pub device_path: String,
-    /// Serial port device nodes associated with this USB device.
+    /// Serial interfaces of this USB device, in interface order.
     /// Lazily populated on first access via serial_ports() method.
     /// Stores a Result so we can cache both success and failure.
-    serial_ports: OnceLock<Result<Vec<String>>>,
+    serial_ports: OnceLock<Result<Vec<SerialInterface>>>,
     // Future: other interfaces like HID, mass storage, etc.
 }
 
+/// One serial interface of a USB device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SerialInterface {
+    /// Device node, e.g. "/dev/ttyACM0"
+    pub path: String,
+    /// USB interface number (bInterfaceNumber), if known
+    pub interface_number: Option<u8>,
+    /// Interface string descriptor (iInterface), if the device gives one
+    pub interface_name: Option<String>,
+}
+
+/// What a serial interface of a composite device is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortRole {
+    /// Management commands: GPIO, I2C, firmware version
+    Control,
+    /// The ASICs' own UART
+    Data,
+}
+
 impl UsbDeviceInfo {
-    /// Get serial ports associated with this USB device.
+    /// Get serial ports associated with this USB device, in interface
+    /// order.
     ///
     /// Scans for serial port device nodes (e.g., /dev/ttyACM0, /dev/ttyUSB0)
     /// on first call and caches the result. Returns cached value on subsequent calls.
@@ -45,7 +72,7 @@ impl UsbDeviceInfo {
     /// This lazy approach avoids expensive serial port enumeration for devices
     /// that won't be used (USB hubs, keyboards, etc.), only scanning when a board
     /// implementation actually needs the serial ports.
-    pub fn serial_ports(&self) -> Result<&[String]> {
+    pub fn serial_ports(&self) -> Result<&[SerialInterface]> {
         self.serial_ports
             .get_or_init(|| {
                 #[cfg(target_os = "linux")]
@@ -62,6 +89,30 @@ impl UsbDeviceInfo {
             .map_err(|e| crate::error::Error::Other(e.to_string()))
     }
 
+    /// The serial port serving `role` on a device whose interfaces are
+    /// laid out as `layout`, in interface order.
+    ///
+    /// A port whose interface name gives its role is taken first, so
+    /// firmware that names its interfaces is found whatever their order.
+    pub fn serial_port(&self, role: PortRole, layout: &[PortRole]) -> Result<&SerialInterface> {
+        let ports = self.serial_ports()?;
+        if ports.len() != layout.len() {
+            return Err(crate::error::Error::Hardware(format!(
+                "expected {} serial ports, found {}",
+                layout.len(),
+                ports.len()
+            )));
+        }
+        ports
+            .iter()
+            .find(|port| port.role() == Some(role))
+            .or_else(|| {
+                let index = layout.iter().position(|r| *r == role)?;
+                ports.get(index)
+            })
+            .ok_or_else(|| crate::error::Error::Hardware(format!("no {} serial port", role)))
+    }
+
     /// Create a UsbDeviceInfo for testing purposes.
     ///
     /// Serial ports are not scanned and will be empty when accessed.
@@ -84,6 +135,55 @@ impl UsbDeviceInfo {
             serial_ports: OnceLock::new(),
         }
     }
+
+    /// This device, with `ports` as its serial ports rather than scanning
+    /// for them.
+    #[cfg(test)]
+    pub fn with_serial_ports(self, ports: Vec<SerialInterface>) -> Self {
+        let serial_ports = OnceLock::new();
+        let _ = serial_ports.set(Ok(ports));
+        Self {
+            serial_ports,
+            ..self
+        }
+    }
+}
+
+impl SerialInterface {
+    /// The role the interface's name gives, if it names one.
+    pub fn role(&self) -> Option<PortRole> {
+        let name = self.interface_name.as_deref()?.to_ascii_lowercase();
+        match (name.contains("control"), name.contains("data")) {
+            (true, false) => Some(PortRole::Control),
+            (false, true) => Some(PortRole::Data),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for PortRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Control => "control",
+            Self::Data => "data",
+        })
+    }
+}
+
+impl Clone for UsbDeviceInfo {
+    /// Clones start with an empty serial port cache, so a board recreated
+    /// from the clone finds the device's current nodes.
+    fn clone(&self) -> Self {
+        Self {
+            vid: self.vid,
+            pid: self.pid,
+            serial_number: self.serial_number.clone(),
+            manufacturer: self.manufacturer.clone(),
+            product: self.product.clone(),
+            device_path: self.device_path.clone(),
+            serial_ports: OnceLock::new(),
+        }
+    }
 }
 
 /// Transport event emitted when devices are discovered or disconnected.
@@ -146,7 +246,7 @@ impl UsbTransport {
             .name("usb-monitor".to_string())
             .spawn(move || {
                 if let Err(e) = discovery.monitor_blocking(event_tx, shutdown) {
-                    error!("USB monitoring failed: {}", e);
+                    error!(error = %e, "USB monitoring failed");
                 }
             })
             .map_err(|e| {
@@ -217,3 +317,47 @@ fn create_discovery() -> Result<Box<dyn UsbDiscoveryImpl>> {
         compile_error!("USB discovery is not implemented for this platform");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LAYOUT: [PortRole; 2] = [PortRole::Control, PortRole::Data];
+
+    fn port(path: &str, interface_number: u8, interface_name: Option<&str>) -> SerialInterface {
+        SerialInterface {
+            path: path.into(),
+            interface_number: Some(interface_number),
+            interface_name: interface_name.map(Into::into),
+        }
+    }
+
+    fn composite(ports: Vec<SerialInterface>) -> UsbDeviceInfo {
+        UsbDeviceInfo::new_for_test(0xc0de, 0xcafe, None, None, None, "/sys/test".into())
+            .with_serial_ports(ports)
+    }
+
+    #[test]
+    fn test_serial_port_by_layout_or_name() {
+        // Nodes numbered past 9 still go by interface order
+        let device = composite(vec![
+            port("/dev/ttyACM10", 0, None),
+            port("/dev/ttyACM9", 2, None),
+        ]);
+        let control = device.serial_port(PortRole::Control, &LAYOUT).unwrap();
+        assert_eq!(control.path, "/dev/ttyACM10");
+        let data = device.serial_port(PortRole::Data, &LAYOUT).unwrap();
+        assert_eq!(data.path, "/dev/ttyACM9");
+
+        // Names, where given, win over position
+        let device = composite(vec![
+            port("/dev/ttyACM0", 0, Some("Bitaxe Data")),
+            port("/dev/ttyACM1", 2, Some("Bitaxe Control")),
+        ]);
+        let control = device.serial_port(PortRole::Control, &LAYOUT).unwrap();
+        assert_eq!(control.path, "/dev/ttyACM1");
+
+        let device = composite(vec![port("/dev/ttyACM0", 0, None)]);
+        assert!(device.serial_port(PortRole::Control, &LAYOUT).is_err());
+    }
+}
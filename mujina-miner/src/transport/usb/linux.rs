@@ -30,7 +30,7 @@
 //! reconnections. This is critical for boards that expect a specific port for
 //! control vs data communication.
 
-use super::{TransportEvent as UsbEvent, UsbDeviceInfo};
+use super::{SerialInterface, TransportEvent as UsbEvent, UsbDeviceInfo};
 use crate::{error::Result, tracing::prelude::*, transport::TransportEvent};
 use futures::stream::StreamExt;
 use tokio::sync::mpsc;
@@ -48,12 +48,13 @@ struct DeviceProperties {
 /// Find serial port devices (tty) associated with a USB device.
 ///
 /// Takes a USB device sysfs path (e.g., "/sys/devices/pci0000:00/...") and
-/// returns serial port device nodes (e.g., ["/dev/ttyACM0", "/dev/ttyACM1"]).
-/// Ports are sorted by device node name for consistent ordering.
+/// returns its serial interfaces (e.g., /dev/ttyACM0 on interface 0). Ports
+/// are sorted by interface number, which the device fixes, rather than by
+/// node name, which depends on what else was plugged in first.
 ///
 /// This is a public function so UsbDeviceInfo can lazily scan for serial ports
 /// without needing access to the original udev::Device reference.
-pub(super) fn find_serial_ports_for_device(device_path: &str) -> Result<Vec<String>> {
+pub(super) fn find_serial_ports_for_device(device_path: &str) -> Result<Vec<SerialInterface>> {
     let mut ports = Vec::new();
 
     // Create an enumerator to find tty devices
@@ -71,31 +72,52 @@ pub(super) fn find_serial_ports_for_device(device_path: &str) -> Result<Vec<Stri
         .map_err(|e| crate::error::Error::Other(format!("Failed to scan devices: {}", e)))?
     {
         // Check if this tty device is a descendant of our USB device
-        // by walking up the parent chain
+        // by walking up the parent chain, noting the interface it hangs off
         let mut current = Some(tty_device.clone());
         let mut is_child = false;
+        let mut interface = None;
 
         while let Some(dev) = current {
             if dev.syspath().to_str() == Some(device_path) {
                 is_child = true;
                 break;
             }
+            if interface.is_none()
+                && dev.devtype().and_then(|t| t.to_str()) == Some("usb_interface")
+            {
+                interface = Some(dev.clone());
+            }
             current = dev.parent();
         }
 
         if is_child {
             // Get the device node (e.g., /dev/ttyACM0)
-            if let Some(devnode) = tty_device.devnode() {
-                if let Some(path_str) = devnode.to_str() {
-                    ports.push(path_str.to_string());
-                }
+            if let Some(path_str) = tty_device.devnode().and_then(|n| n.to_str()) {
+                let attribute = |name: &str| {
+                    interface
+                        .as_ref()?
+                        .attribute_value(name)?
+                        .to_str()
+                        .map(|s| s.trim().to_string())
+                };
+                ports.push(SerialInterface {
+                    path: path_str.to_string(),
+                    interface_number: attribute("bInterfaceNumber")
+                        .and_then(|n| u8::from_str_radix(&n, 16).ok()),
+                    interface_name: attribute("interface").filter(|name| !name.is_empty()),
+                });
             }
         }
     }
 
-    // Sort ports by name for consistent ordering
-    // This ensures /dev/ttyACM0 comes before /dev/ttyACM1
-    ports.sort();
+    // Interface order; node name where the interface isn't known
+    ports.sort_by(|a, b| {
+        (a.interface_number.is_none(), a.interface_number, &a.path).cmp(&(
+            b.interface_number.is_none(),
+            b.interface_number,
+            &b.path,
+        ))
+    });
 
     Ok(ports)
 }
@@ -289,7 +311,7 @@ impl super::UsbDiscoveryImpl for LinuxUdevDiscovery {
                         let event = match event_result {
                             Some(Ok(e)) => e,
                             Some(Err(e)) => {
-                                error!("Error from USB monitor: {}", e);
+                                error!(error = %e, "Error from USB monitor");
                                 continue;
                             }
                             None => {
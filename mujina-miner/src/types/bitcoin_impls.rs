@@ -3,6 +3,8 @@
 //! These live here rather than in u256.rs to avoid coupling the generic
 //! integer type to bitcoin.
 
+use std::cmp::Ordering;
+
 use bitcoin::hashes::Hash;
 use bitcoin::pow::{Target, Work};
 
@@ -33,6 +35,30 @@ impl From<Work> for U256 {
     }
 }
 
+impl PartialEq<Target> for U256 {
+    fn eq(&self, target: &Target) -> bool {
+        *self == U256::from(*target)
+    }
+}
+
+impl PartialOrd<Target> for U256 {
+    fn partial_cmp(&self, target: &Target) -> Option<Ordering> {
+        Some(self.cmp(&U256::from(*target)))
+    }
+}
+
+impl PartialEq<U256> for Target {
+    fn eq(&self, u: &U256) -> bool {
+        U256::from(*self) == *u
+    }
+}
+
+impl PartialOrd<U256> for Target {
+    fn partial_cmp(&self, u: &U256) -> Option<Ordering> {
+        Some(U256::from(*self).cmp(u))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -44,4 +70,14 @@ mod tests {
         let back = Target::from(u);
         assert_eq!(target, back);
     }
+
+    #[test]
+    fn test_compare_with_target() {
+        let max = U256::from(Target::MAX);
+        assert_eq!(max, Target::MAX);
+        assert!(max / 2u64 < Target::MAX);
+        assert!(max * 2u64 > Target::MAX);
+        assert!(Target::MAX > max / 2u64);
+        assert!(Target::ZERO <= U256::ZERO);
+    }
 }
@@ -17,7 +17,7 @@ use std::fmt;
 /// ```
 ///
 /// Used for:
-/// - Stratum protocol (pools communicate difficulty as integers)
+/// - Stratum protocol (pools send integer or fractional difficulties)
 /// - Logging and display (human-readable values)
 /// - Share validation (via `to_target()`)
 /// - Forced low-difficulty testing (sub-1.0 values)
@@ -37,30 +37,53 @@ impl Difficulty {
     /// Maximum difficulty (target of zero---no hash can satisfy it).
     pub const MAX: Self = Self(Target::ZERO);
 
-    /// Create from f64 for sub-1.0 difficulties (testing only).
+    /// Create from a fractional difficulty.
     ///
-    /// Most code should use `Difficulty::from(u64)` instead. This exists
-    /// for forced-rate testing where fractional difficulties are needed.
-    /// The conversion is necessarily lossy.
+    /// Pools may send any positive number in `mining.set_difficulty`,
+    /// including fractions such as 0.5 or 0.001 on testnets. Every finite f64
+    /// is exactly `m * 2^e`, so the target is computed without rounding the
+    /// difficulty first: `MAX_TARGET * 2^-e / m` in 512-bit arithmetic,
+    /// rounded down. Rounding down makes the target at most one unit harder
+    /// than the exact value, never easier, and gives the same target as
+    /// `Difficulty::from(u64)` for whole numbers.
+    ///
+    /// Difficulties so small their target exceeds 256 bits (below ~2^-32)
+    /// saturate to the largest target. Zero, negative, NaN, and infinite
+    /// values are treated as difficulty 1.
     pub fn from_f64(value: f64) -> Self {
         if value <= 0.0 || !value.is_finite() {
             return Self(Target::MAX);
         }
 
-        let max_target = U256::from(Target::MAX);
+        // Decompose into integer mantissa and binary exponent
+        let bits = value.to_bits();
+        let biased_exponent = ((bits >> 52) & 0x7ff) as i32;
+        let fraction = bits & ((1 << 52) - 1);
+        let (mantissa, exponent) = if biased_exponent == 0 {
+            (fraction, -1074) // subnormal
+        } else {
+            (fraction | (1 << 52), biased_exponent - 1075)
+        };
 
-        if value >= 1.0 {
-            // target = MAX_TARGET / difficulty
-            // Use integer division (lossy for non-integer difficulties)
-            let target = max_target / (value as u64).max(1);
-            Self(Target::from(target))
+        let max_target = U256::from(Target::MAX);
+        let mantissa = U256::from(mantissa);
+        let shift = exponent.unsigned_abs() as usize;
+
+        let target = if exponent >= 0 {
+            // target = MAX_TARGET / (m * 2^e); too large a divisor means zero
+            match mantissa.checked_shl(shift) {
+                Some(divisor) => max_target / divisor,
+                None => U256::ZERO,
+            }
         } else {
-            // Sub-1.0 difficulty: target > MAX_TARGET
-            // target = MAX_TARGET / difficulty = MAX_TARGET * (1/difficulty)
-            let multiplier = (1.0 / value) as u64;
-            let target = max_target * multiplier;
-            Self(Target::from(target))
-        }
+            // target = MAX_TARGET * 2^-e / m
+            U256::from(1u64)
+                .checked_shl(shift)
+                .and_then(|scale| max_target.checked_mul_div(scale, mantissa))
+                .unwrap_or(U256::MAX)
+        };
+
+        Self(Target::from(target))
     }
 
     /// Get difficulty as f64 (lossy for very large values).
@@ -231,6 +254,10 @@ mod tests {
     }
 
     #[test]
+    #[expect(
+        clippy::nonminimal_bool,
+        reason = "equal values must compare neither greater nor less"
+    )]
     fn test_difficulty_ordering() {
         let diff_low = Difficulty::from(100_u64);
         let diff_high = Difficulty::from(1000_u64);
@@ -333,6 +360,55 @@ mod tests {
         assert_eq!(U256::from(target), expected_target);
     }
 
+    #[test]
+    fn test_fractional_difficulty_vectors() {
+        let max = U256::from(Target::MAX);
+        let target_of = |d: f64| U256::from(Difficulty::from_f64(d).to_target());
+
+        // Powers of two are exact
+        assert_eq!(target_of(0.5), max * 2u64);
+        assert_eq!(target_of(0.25), max * 4u64);
+        assert_eq!(target_of(0.125), max * 8u64);
+
+        // 1.5 = 3 * 2^-1, so target = MAX * 2 / 3 rounded down
+        assert_eq!(target_of(1.5), (max * 2u64) / 3u64);
+
+        // Non-dyadic fractions common on testnets (0.001, 0.01) match the
+        // exact ratio to f64 precision
+        for d in [0.001, 0.01, 0.1, 0.3, 2.5, 1024.75] {
+            let target = target_of(d);
+            let achieved = max.ratio(target);
+            assert!(((achieved - d) / d).abs() < 1e-15, "{d}: got {achieved}");
+        }
+    }
+
+    #[test]
+    fn test_from_f64_matches_integer_conversion() {
+        for d in [1u64, 2, 3, 7, 1000, 2048, 65536, 1 << 40, u64::MAX >> 11] {
+            assert_eq!(Difficulty::from_f64(d as f64), Difficulty::from(d));
+        }
+    }
+
+    #[test]
+    fn test_from_f64_edge_cases() {
+        let max = Difficulty::from_f64(1.0);
+        assert_eq!(Difficulty::from_f64(0.0), max);
+        assert_eq!(Difficulty::from_f64(-5.0), max);
+        assert_eq!(Difficulty::from_f64(f64::NAN), max);
+        assert_eq!(Difficulty::from_f64(f64::INFINITY), max);
+
+        // Tiny difficulties saturate to the largest target
+        assert_eq!(
+            U256::from(Difficulty::from_f64(1e-20).to_target()),
+            U256::MAX
+        );
+        // Astronomically large ones bottom out at zero
+        assert_eq!(
+            U256::from(Difficulty::from_f64(1e300).to_target()),
+            U256::ZERO
+        );
+    }
+
     #[test]
     fn test_lossless_roundtrip() {
         // Any u64 difficulty should round-trip exactly
@@ -7,6 +7,7 @@
 mod bitcoin_impls;
 mod difficulty;
 mod hash_rate;
+mod network;
 mod share_rate;
 
 use std::time::Duration;
@@ -15,9 +16,10 @@ use crate::u256::U256;
 
 // Re-export frequently used bitcoin types for convenience
 pub use bitcoin::block::Header as BlockHeader;
-pub use bitcoin::{Amount, BlockHash, Network, Target, Transaction, TxOut, Work};
+pub use bitcoin::{Amount, BlockHash, Target, Transaction, TxOut, Work};
 pub use difficulty::Difficulty;
 pub use hash_rate::HashRate;
+pub use network::Network;
 pub use share_rate::ShareRate;
 
 /// Calculate expected shares per second at given difficulty and hashrate.
@@ -60,6 +62,15 @@ pub fn expected_time_to_share_from_target(target: Target, hashrate: HashRate) ->
     Duration::from_secs_f64(1.0 / shares_per_sec)
 }
 
+/// Difficulty a hash achieves, to full f64 precision.
+///
+/// Difficulty is `MAX_TARGET / hash`. `Target::difficulty_float()` rounds
+/// both operands to f64 before dividing; this divides in 256 bits first. A
+/// zero hash gives infinity.
+pub fn difficulty_from_hash(hash: &BlockHash) -> f64 {
+    U256::from(Target::MAX).ratio(U256::from(hash))
+}
+
 /// Calculate target to achieve the given share rate at the given hashrate.
 ///
 /// A hash is valid if hash < target. With hashes uniform over [0, 2^256),
@@ -76,6 +87,8 @@ pub fn target_for_share_rate(rate: ShareRate, hashrate: HashRate) -> Target {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use bitcoin::hashes::Hash;
+    use proptest::prelude::*;
 
     #[test]
     fn test_expected_shares_per_second() {
@@ -159,4 +172,110 @@ mod tests {
             Duration::MAX
         );
     }
+
+    #[test]
+    fn test_difficulty_from_hash() {
+        let max = BlockHash::from_byte_array(Target::MAX.to_le_bytes());
+        assert_eq!(difficulty_from_hash(&max), 1.0);
+
+        let max_over_3 = BlockHash::from_byte_array((U256::from(Target::MAX) / 3u64).to_le_bytes());
+        assert!((difficulty_from_hash(&max_over_3) - 3.0).abs() < 1e-12);
+
+        let zero = BlockHash::from_byte_array([0; 32]);
+        assert_eq!(difficulty_from_hash(&zero), f64::INFINITY);
+    }
+
+    /// Mainnet blocks: hash as displayed, nBits, and the difficulty the hash
+    /// achieved.
+    const MAINNET_BLOCKS: [(&str, u32, f64); 4] = [
+        // Genesis
+        (
+            "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f",
+            0x1d00ffff,
+            2536.4262984453103,
+        ),
+        // Block 1
+        (
+            "00000000839a8e6886ab5951d76f411475428afc90947ee320161bbf18eb6048",
+            0x1d00ffff,
+            1.9452038562555845,
+        ),
+        // Block 100,000
+        (
+            "000000000003ba27aa200b1cecaad478d2b00432346c3f1f3986da1afd33e506",
+            0x1b04864c,
+            17583.056276040857,
+        ),
+        // Block 881,423
+        (
+            "0000000000000000000269d52c24ea451225613aab095d90d771d4e29aa96cdd",
+            0x17029a8a,
+            116627841100297.31,
+        ),
+    ];
+
+    #[test]
+    fn test_mainnet_hashes_against_targets() {
+        use std::str::FromStr;
+
+        for (hex, bits, achieved) in MAINNET_BLOCKS {
+            let hash = BlockHash::from_str(hex).unwrap();
+            let network = Target::from(bitcoin::CompactTarget::from_consensus(bits));
+            assert!(network.is_met_by(hash), "{hex}");
+
+            let exact = difficulty_from_hash(&hash);
+            assert!(
+                (exact - achieved).abs() <= achieved * 1e-12,
+                "{hex}: {exact}"
+            );
+
+            // A share at just under the achieved difficulty is met, just over
+            // isn't
+            let met = Difficulty::from_f64(achieved * 0.999).to_target();
+            let missed = Difficulty::from_f64(achieved * 1.001).to_target();
+            assert!(met.is_met_by(hash), "{hex}");
+            assert!(!missed.is_met_by(hash), "{hex}");
+
+            // Read in the wrong byte order, the hash meets nothing useful
+            let mut reversed = hash.to_byte_array();
+            reversed.reverse();
+            assert!(
+                !Target::MAX.is_met_by(BlockHash::from_byte_array(reversed)),
+                "{hex}"
+            );
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn prop_difficulty_from_hash_matches_bitcoin(bytes in any::<[u8; 32]>()) {
+            prop_assume!(bytes != [0; 32]);
+            let hash = BlockHash::from_byte_array(bytes);
+
+            let exact = difficulty_from_hash(&hash);
+            let float = Target::from_le_bytes(bytes).difficulty_float();
+            prop_assert!((exact - float).abs() <= float * 1e-12, "{exact} vs {float}");
+        }
+
+        #[test]
+        fn prop_difficulty_from_hash_is_monotonic(
+            a in any::<[u8; 32]>(),
+            b in any::<[u8; 32]>(),
+        ) {
+            let (a, b) = (U256::from_le_bytes(a), U256::from_le_bytes(b));
+            let (low, high) = (a.min(b), a.max(b));
+            let difficulty = |u: U256| difficulty_from_hash(&BlockHash::from_byte_array(u.to_le_bytes()));
+            prop_assert!(difficulty(low) >= difficulty(high));
+        }
+
+        #[test]
+        fn prop_hash_meets_target_iff_not_above_it(
+            hash in any::<[u8; 32]>(),
+            target in any::<[u8; 32]>(),
+        ) {
+            let target = Target::from_le_bytes(target);
+            let meets = target.is_met_by(BlockHash::from_byte_array(hash));
+            prop_assert_eq!(meets, U256::from_le_bytes(hash) <= target);
+        }
+    }
 }
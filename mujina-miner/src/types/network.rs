@@ -0,0 +1,132 @@
+//! Bitcoin network selection.
+
+use std::fmt;
+use std::str::FromStr;
+
+use bitcoin::address::{Address, NetworkUnchecked};
+use bitcoin::pow::Target;
+use serde::{Deserialize, Serialize};
+
+/// The Bitcoin network being mined.
+///
+/// Mining itself is network-agnostic---a header hashes the same everywhere---
+/// but a few checks depend on the chain: the proof-of-work limit used to
+/// sanity-check pool jobs, and which payout addresses are valid. Regtest is
+/// mainly useful against a local bitcoind and pool for end-to-end testing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Network {
+    #[default]
+    Mainnet,
+    Testnet4,
+    Regtest,
+}
+
+impl Network {
+    /// Easiest target a block on this network may have (the powLimit).
+    pub fn max_target(self) -> Target {
+        match self {
+            Network::Mainnet => Target::MAX_ATTAINABLE_MAINNET,
+            Network::Testnet4 => Target::MAX_ATTAINABLE_TESTNET,
+            Network::Regtest => Target::MAX_ATTAINABLE_REGTEST,
+        }
+    }
+
+    /// Check that a worker name doesn't pay to another network's address.
+    ///
+    /// Pools, and solo pools in particular, take a payout address as the part
+    /// of the username before the first '.'. A name that isn't an address at
+    /// all (a pool account) is fine; an address for the wrong network is an
+    /// error, since every block found would pay to an unspendable output.
+    pub fn check_worker_name(self, worker: &str) -> Result<(), bitcoin::address::ParseError> {
+        let candidate = worker.split('.').next().unwrap_or_default();
+        match candidate.parse::<Address<NetworkUnchecked>>() {
+            Ok(address) => address.require_network(self.into()).map(|_| ()),
+            Err(_) => Ok(()),
+        }
+    }
+}
+
+impl From<Network> for bitcoin::Network {
+    fn from(network: Network) -> Self {
+        match network {
+            Network::Mainnet => bitcoin::Network::Bitcoin,
+            Network::Testnet4 => bitcoin::Network::Testnet4,
+            Network::Regtest => bitcoin::Network::Regtest,
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "mainnet" | "bitcoin" => Ok(Network::Mainnet),
+            "testnet4" => Ok(Network::Testnet4),
+            "regtest" => Ok(Network::Regtest),
+            other => Err(format!(
+                "unknown network '{}' (expected mainnet, testnet4, or regtest)",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Network::Mainnet => "mainnet",
+            Network::Testnet4 => "testnet4",
+            Network::Regtest => "regtest",
+        };
+        f.write_str(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAINNET_ADDRESS: &str = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq";
+    const TESTNET_ADDRESS: &str = "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx";
+    const REGTEST_ADDRESS: &str = "bcrt1q6rhpng9evdsfnn833a4f4vej0asu6dk5srld6x";
+
+    #[test]
+    fn test_parse_and_display() {
+        for network in [Network::Mainnet, Network::Testnet4, Network::Regtest] {
+            assert_eq!(network.to_string().parse::<Network>(), Ok(network));
+        }
+        assert_eq!("bitcoin".parse::<Network>(), Ok(Network::Mainnet));
+        assert_eq!("RegTest".parse::<Network>(), Ok(Network::Regtest));
+        assert!("signet".parse::<Network>().is_err());
+    }
+
+    #[test]
+    fn test_max_target() {
+        assert_eq!(Network::Mainnet.max_target(), Target::MAX);
+        assert_eq!(Network::Testnet4.max_target(), Target::MAX);
+        assert!(Network::Regtest.max_target() > Target::MAX);
+    }
+
+    #[test]
+    fn test_check_worker_name() {
+        // Plain account names aren't addresses and are always accepted
+        assert!(Network::Mainnet.check_worker_name("alice.rig1").is_ok());
+        assert!(Network::Regtest.check_worker_name("mujina-testing").is_ok());
+
+        assert!(Network::Mainnet
+            .check_worker_name(&format!("{}.rig1", MAINNET_ADDRESS))
+            .is_ok());
+        assert!(Network::Testnet4.check_worker_name(TESTNET_ADDRESS).is_ok());
+        assert!(Network::Regtest.check_worker_name(REGTEST_ADDRESS).is_ok());
+
+        assert!(Network::Mainnet.check_worker_name(REGTEST_ADDRESS).is_err());
+        assert!(Network::Regtest
+            .check_worker_name(&format!("{}.worker", MAINNET_ADDRESS))
+            .is_err());
+        assert!(Network::Testnet4
+            .check_worker_name(MAINNET_ADDRESS)
+            .is_err());
+    }
+}
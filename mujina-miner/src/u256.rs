@@ -4,8 +4,9 @@
 //! module exists so we can swap the underlying library or implement our own
 //! arithmetic without changing callers.
 
-use ruint::aliases::U256 as Ruint256;
-use std::ops::{AddAssign, Div, Mul};
+use ruint::aliases::{U256 as Ruint256, U512};
+use ruint::UintTryFrom;
+use std::ops::{Add, AddAssign, Div, Mul, Rem};
 
 /// A 256-bit unsigned integer.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -33,6 +34,62 @@ impl U256 {
         self.0.saturating_to()
     }
 
+    /// Whether the value is zero.
+    pub fn is_zero(self) -> bool {
+        self.0.is_zero()
+    }
+
+    /// Multiply, returning None on overflow.
+    pub fn checked_mul(self, rhs: Self) -> Option<Self> {
+        self.0.checked_mul(rhs.0).map(Self)
+    }
+
+    /// Multiply, saturating at `U256::MAX`.
+    pub fn saturating_mul(self, rhs: Self) -> Self {
+        Self(self.0.saturating_mul(rhs.0))
+    }
+
+    /// Compute `self * mul / div` with a 512-bit intermediate, rounding down.
+    ///
+    /// Returns None if `div` is zero or the result doesn't fit in 256 bits.
+    pub fn checked_mul_div(self, mul: Self, div: Self) -> Option<Self> {
+        if div.is_zero() {
+            return None;
+        }
+        let product: U512 = self.0.widening_mul(mul.0);
+        Ruint256::uint_try_from(product / U512::from(div.0))
+            .ok()
+            .map(Self)
+    }
+
+    /// Shift left, returning None if any set bit would be shifted out.
+    pub fn checked_shl(self, bits: usize) -> Option<Self> {
+        self.0.checked_shl(bits).map(Self)
+    }
+
+    /// Quotient and remainder of `self / rhs`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rhs` is zero.
+    pub fn div_rem(self, rhs: Self) -> (Self, Self) {
+        let (quotient, remainder) = self.0.div_rem(rhs.0);
+        (Self(quotient), Self(remainder))
+    }
+
+    /// `self / rhs` as f64, correct to f64 precision.
+    ///
+    /// Converting both operands to f64 first would round each of them; here
+    /// only the integer quotient and the remainder's fraction are rounded.
+    /// Returns infinity if `rhs` is zero.
+    pub fn ratio(self, rhs: Self) -> f64 {
+        if rhs.is_zero() {
+            return f64::INFINITY;
+        }
+        let (quotient, remainder) = self.div_rem(rhs);
+        quotient.to_f64_approx() + remainder.to_f64_approx() / rhs.to_f64_approx()
+    }
+
     /// Convert to f64, losing precision for large values.
     ///
     /// For values larger than f64 can precisely represent (~2^53), this
@@ -89,6 +146,23 @@ impl Div<u128> for U256 {
     }
 }
 
+impl Rem for U256 {
+    type Output = Self;
+
+    fn rem(self, rhs: Self) -> Self::Output {
+        Self(self.0 % rhs.0)
+    }
+}
+
+/// Wrapping multiplication; use `checked_mul` where overflow matters.
+impl Mul for U256 {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self(self.0.wrapping_mul(rhs.0))
+    }
+}
+
 impl Mul<u64> for U256 {
     type Output = Self;
 
@@ -97,6 +171,26 @@ impl Mul<u64> for U256 {
     }
 }
 
+impl From<u64> for U256 {
+    fn from(value: u64) -> Self {
+        Self(Ruint256::from(value))
+    }
+}
+
+impl From<u128> for U256 {
+    fn from(value: u128) -> Self {
+        Self(Ruint256::from(value))
+    }
+}
+
+impl Add for U256 {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self(self.0 + rhs.0)
+    }
+}
+
 impl AddAssign for U256 {
     fn add_assign(&mut self, rhs: Self) {
         self.0 += rhs.0;
@@ -142,6 +236,61 @@ mod tests {
         assert_eq!(a / 10u64, expected);
     }
 
+    #[test]
+    fn test_multiplication() {
+        let a = U256::from(u128::MAX);
+        let b = U256::from(3u64);
+        assert_eq!(a * b / b, a);
+        assert_eq!(a.checked_mul(b), Some(a * b));
+
+        // (2^128 - 1)^2 fits; one more factor of 2^128 doesn't
+        let square = a.checked_mul(a).unwrap();
+        assert_eq!(square / a, a);
+        assert_eq!(square.checked_mul(a), None);
+        assert_eq!(square.saturating_mul(a), U256::MAX);
+    }
+
+    #[test]
+    fn test_checked_mul_div() {
+        // Intermediate product overflows 256 bits, result doesn't
+        let big = U256::MAX / 2u64;
+        assert_eq!(
+            big.checked_mul_div(U256::from(6u64), U256::from(3u64)),
+            Some(big * 2u64)
+        );
+        assert_eq!(
+            U256::from(10u64).checked_mul_div(U256::from(1u64), U256::from(3u64)),
+            Some(U256::from(3u64))
+        );
+
+        assert_eq!(
+            U256::MAX.checked_mul_div(U256::from(2u64), U256::from(1u64)),
+            None
+        );
+        assert_eq!(
+            U256::MAX.checked_mul_div(U256::from(1u64), U256::ZERO),
+            None
+        );
+
+        assert_eq!(U256::from(3u64).checked_shl(2), Some(U256::from(12u64)));
+        assert_eq!(U256::MAX.checked_shl(1), None);
+    }
+
+    #[test]
+    fn test_div_rem_and_ratio() {
+        let (q, r) = U256::from(100u64).div_rem(U256::from(7u64));
+        assert_eq!((q, r), (U256::from(14u64), U256::from(2u64)));
+        assert_eq!(U256::from(100u64) % U256::from(7u64), U256::from(2u64));
+
+        assert_eq!(U256::from(7u64).ratio(U256::from(2u64)), 3.5);
+        assert_eq!(U256::from(1u64).ratio(U256::ZERO), f64::INFINITY);
+
+        // Operands beyond f64 precision still give an exact small ratio
+        let big = U256::MAX / 3u64;
+        assert_eq!((big + U256::from(1u64)).ratio(big), 1.0);
+        assert_eq!((big * 2u64).ratio(big), 2.0);
+    }
+
     #[test]
     fn test_large_division() {
         // Large value / 1 = same value
@@ -5,22 +5,13 @@
 //! codec used during runtime to ensure consistency between dissection and
 //! live operation.
 //!
-//! Note: Any codec implementation specific to the dissector is a candidate for
-//! replacing the mujina-miner codec. The dissector currently experiments with
-//! different parsing strategies, but the goal is to converge on a single codec
-//! implementation shared between the dissector and the main miner.
+//! Command frames are decoded by `Command::decode`, the inverse of the
+//! driver's encoder. The streaming parsers here only find frame boundaries
+//! and keep per-byte timestamps, which the runtime codec has no use for.
 
 use crate::capture::{BaudRate, Channel, SerialEvent};
-use bitcoin::hashes::Hash;
 use bytes::{Buf, BytesMut};
-use mujina_miner::asic::bm13xx::{
-    crc::{crc16, crc5},
-    error::ProtocolError,
-    protocol::{
-        hash_from_wire_bytes, Command, FrameCodec, JobFullFormat, Register, RegisterAddress,
-        Response,
-    },
-};
+use mujina_miner::asic::bm13xx::protocol::{Command, FrameCodec, Response};
 use std::collections::VecDeque;
 use tokio_util::codec::Decoder;
 
@@ -158,7 +149,7 @@ impl CommandStreamingParser {
         self.buffer.advance(total_length);
 
         // Parse the command frame
-        match parse_bm13xx_command_frame(&frame_bytes) {
+        match Command::decode(&frame_bytes) {
             Ok(command) => Some(ParsedItem::ValidFrame {
                 command,
                 raw_bytes: frame_bytes,
@@ -212,7 +203,7 @@ impl ResponseStreamingParser {
             buffer: BytesMut::new(),
             byte_queue: VecDeque::new(),
             invalid_accumulator: Vec::new(),
-            frame_codec: FrameCodec,
+            frame_codec: FrameCodec::default(),
         }
     }
 
@@ -312,147 +303,6 @@ impl ResponseStreamingParser {
     }
 }
 
-/// Standalone BM13xx command frame parser
-fn parse_bm13xx_command_frame(data: &[u8]) -> Result<Command, ProtocolError> {
-    if data.len() < 5 {
-        return Err(ProtocolError::InvalidFrame);
-    }
-
-    // data[0..2] is preamble (already validated)
-    let type_flags = data[2];
-    let _length = data[3] as usize;
-
-    // Parse type flags according to protocol documentation
-    let is_work = (type_flags & 0x40) == 0;
-    let is_broadcast = (type_flags & 0x10) != 0;
-    let cmd = type_flags & 0x0f;
-
-    // Validate CRC
-    let crc_valid = if is_work {
-        // Work frames use CRC16 (big-endian on wire)
-        if data.len() >= 4 {
-            let payload_end = data.len() - 2;
-            let crc_bytes = &data[payload_end..];
-            let payload = &data[2..payload_end];
-            // Extract CRC from wire (big-endian: high byte first)
-            let expected_crc = u16::from_be_bytes([crc_bytes[0], crc_bytes[1]]);
-            let calculated_crc = crc16(payload);
-            calculated_crc == expected_crc
-        } else {
-            false
-        }
-    } else {
-        // Register frames use CRC5
-        if data.len() >= 3 {
-            let payload = &data[2..data.len() - 1];
-            let expected_crc = data[data.len() - 1];
-            let calculated_crc = crc5(payload);
-            calculated_crc == expected_crc
-        } else {
-            false
-        }
-    };
-
-    if !crc_valid {
-        return Err(ProtocolError::InvalidFrame);
-    }
-
-    if is_work {
-        // Parse work frame (JobFull)
-        let job_data_len = _length - 4;
-        if job_data_len == 82 && data.len() >= 2 + _length {
-            let job_data_bytes = &data[4..(4 + 82)];
-
-            // Extract job_id from job_header (shift right 3 to get 4-bit value)
-            let job_id = job_data_bytes[0] >> 3;
-
-            // Parse wire bytes to Bitcoin types
-            let starting_nonce = u32::from_le_bytes(job_data_bytes[2..6].try_into().unwrap());
-            let nbits = bitcoin::CompactTarget::from_consensus(u32::from_le_bytes(
-                job_data_bytes[6..10].try_into().unwrap(),
-            ));
-            let ntime = u32::from_le_bytes(job_data_bytes[10..14].try_into().unwrap());
-
-            // Convert hashes from wire format (word-swapped) to Bitcoin internal format
-            let merkle_wire: [u8; 32] = job_data_bytes[14..46].try_into().unwrap();
-            let merkle_internal = hash_from_wire_bytes(&merkle_wire);
-            let merkle_root = bitcoin::hash_types::TxMerkleNode::from_byte_array(merkle_internal);
-
-            let prev_hash_wire: [u8; 32] = job_data_bytes[46..78].try_into().unwrap();
-            let prev_hash_internal = hash_from_wire_bytes(&prev_hash_wire);
-            let prev_block_hash = bitcoin::BlockHash::from_byte_array(prev_hash_internal);
-
-            let version = bitcoin::block::Version::from_consensus(u32::from_le_bytes(
-                job_data_bytes[78..82].try_into().unwrap(),
-            ) as i32);
-
-            let job_data = JobFullFormat {
-                job_id,
-                num_midstates: job_data_bytes[1],
-                starting_nonce,
-                nbits,
-                ntime,
-                merkle_root,
-                prev_block_hash,
-                version,
-            };
-            return Ok(Command::JobFull { job_data });
-        } else {
-            return Err(ProtocolError::InvalidFrame);
-        }
-    }
-
-    // Parse register commands
-    let command = match (cmd, is_broadcast) {
-        (0, false) => Command::SetChipAddress {
-            chip_address: data[4],
-        },
-        (1, true) => {
-            let register_address =
-                RegisterAddress::from_repr(data[5]).ok_or(ProtocolError::InvalidFrame)?;
-            let register_value = [data[6], data[7], data[8], data[9]];
-            let register = Register::decode(register_address, &register_value);
-            Command::WriteRegister {
-                broadcast: true,
-                chip_address: 0,
-                register,
-            }
-        }
-        (1, false) => {
-            let register_address =
-                RegisterAddress::from_repr(data[5]).ok_or(ProtocolError::InvalidFrame)?;
-            let register_value = [data[6], data[7], data[8], data[9]];
-            let register = Register::decode(register_address, &register_value);
-            Command::WriteRegister {
-                broadcast: false,
-                chip_address: data[4],
-                register,
-            }
-        }
-        (2, true) => {
-            let register_address =
-                RegisterAddress::from_repr(data[5]).ok_or(ProtocolError::InvalidFrame)?;
-            Command::ReadRegister {
-                broadcast: true,
-                chip_address: 0,
-                register_address,
-            }
-        }
-        (2, false) => {
-            let register_address =
-                RegisterAddress::from_repr(data[5]).ok_or(ProtocolError::InvalidFrame)?;
-            Command::ReadRegister {
-                broadcast: false,
-                chip_address: data[4],
-                register_address,
-            }
-        }
-        _ => return Err(ProtocolError::InvalidFrame),
-    };
-
-    Ok(command)
-}
-
 impl DecodedFrame {
     pub fn timestamp(&self) -> f64 {
         match self {
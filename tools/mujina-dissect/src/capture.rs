@@ -72,6 +72,16 @@ pub enum BaudRate {
     Baud1M,
 }
 
+impl BaudRate {
+    /// Line rate in bits per second.
+    pub fn bps(self) -> u32 {
+        match self {
+            BaudRate::Baud115200 => 115_200,
+            BaudRate::Baud1M => 1_000_000,
+        }
+    }
+}
+
 impl RawEvent {
     /// Parse raw event into typed capture event
     pub fn parse(&self) -> Result<Option<CaptureEvent>> {
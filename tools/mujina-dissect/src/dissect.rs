@@ -1,16 +1,13 @@
 //! Protocol dissection engine.
 //!
-//! TODO: Build comprehensive unit tests based on known serial captures
-//! - Use captured frames from ~/mujina/captures/bitaxe-gamma-logic/esp-miner-boot.csv
-//! - Test CRC validation for both work frames (CRC16) and response frames (CRC5)
-//! - Test frame parsing for JobFull work frames and register responses
-//! - Add regression tests to prevent future parsing failures
+//! The golden-output tests in `main.rs` cover this module end to end
+//! against the captures in `testdata/`.
 
 use crate::bm13xx::{DecodedFrame, Direction};
 use crate::capture::BaudRate;
 use crate::i2c::I2cOperation;
 use colored::Colorize;
-use mujina_miner::peripheral::{emc2101, pmbus};
+use mujina_miner::peripheral::{emc2101, pmbus, smbus};
 use std::collections::HashMap;
 use std::fmt;
 
@@ -91,6 +88,8 @@ pub struct DissectedI2c {
     pub operation: String,
     pub raw_data: Vec<u8>,
     pub was_naked: bool,
+    /// The operation this was dissected from
+    pub source: I2cOperation,
 }
 
 /// I2C device contexts for state tracking
@@ -98,6 +97,8 @@ pub struct DissectedI2c {
 pub struct I2cContexts {
     /// VOUT_MODE cache for each TPS546 device address
     pub tps546_vout_modes: HashMap<u8, u8>,
+    /// Paired-register state for each EMC2101 device address
+    pub emc2101: HashMap<u8, emc2101::protocol::DecodeContext>,
 }
 
 /// Known I2C devices
@@ -131,8 +132,8 @@ pub fn dissect_i2c_operation_with_context(
 
         match device {
             I2cDevice::Emc2101 => {
-                // For now, keep using EMC2101 formatting until we refactor it too
-                emc2101::protocol::format_transaction(reg, data, is_read)
+                let context = contexts.emc2101.entry(op.address).or_default();
+                emc2101::protocol::format_transaction(reg, data, is_read, context)
             }
             I2cDevice::Tps546 => {
                 // Update VOUT_MODE cache if this is a VOUT_MODE operation
@@ -180,9 +181,13 @@ pub fn dissect_i2c_operation_with_context(
             }
             I2cDevice::Unknown => {
                 if let Some(data) = &op.read_data {
-                    format!("-> READ [0x{:02x}]={:02x?}", reg, data)
+                    format!("-> READ [0x{:02x}]={}", reg, smbus::parse_smbus_value(data))
                 } else if let Some(data) = &op.write_data {
-                    format!("<- WRITE [0x{:02x}]={:02x?}", reg, data)
+                    format!(
+                        "<- WRITE [0x{:02x}]={}",
+                        reg,
+                        smbus::parse_smbus_value(data)
+                    )
                 } else {
                     // Command-only write (no data after register/command byte)
                     format!("<- WRITE [0x{:02x}]", reg)
@@ -233,5 +238,6 @@ pub fn dissect_i2c_operation_with_context(
         operation,
         raw_data,
         was_naked: op.was_naked,
+        source: op.clone(),
     }
 }
@@ -0,0 +1,271 @@
+//! Live dissection of a tapped serial line.
+//!
+//! A logic analyzer sees both directions of the ASIC link at once, but a
+//! serial tap (a USB-UART adapter's RX pin clipped onto CI or RO, or one end
+//! of a pty pair sitting between the miner and a board) sees only one. The
+//! caller therefore says which line is tapped and at what baud rate, and every
+//! byte read is fed to the matching streaming parser. Frames are printed as
+//! soon as they complete.
+
+use crate::bm13xx::{CommandStreamingParser, DecodedFrame, ParsedItem, ResponseStreamingParser};
+use crate::capture::{BaudRate, Channel, SerialEvent};
+use crate::dissect::{dissect_decoded_frame, DissectedFrame};
+use anyhow::{bail, Context, Result};
+use std::io::{self, Read};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long a read may block before we check again.
+///
+/// Short enough that a partial frame sitting in the OS buffer is delivered
+/// promptly; the parsers don't care how bytes are chunked.
+const READ_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// Streaming dissector for one direction of a BM13xx serial link.
+pub struct LiveDissector {
+    channel: Channel,
+    baud_rate: BaudRate,
+    commands: CommandStreamingParser,
+    responses: ResponseStreamingParser,
+}
+
+impl LiveDissector {
+    pub fn new(channel: Channel, baud_rate: BaudRate) -> Self {
+        Self {
+            channel,
+            baud_rate,
+            commands: CommandStreamingParser::new(),
+            responses: ResponseStreamingParser::new(),
+        }
+    }
+
+    /// Feed bytes received at `timestamp` and return any frames they complete.
+    pub fn push(&mut self, bytes: &[u8], timestamp: f64) -> Vec<DissectedFrame> {
+        let mut frames = Vec::new();
+
+        for &data in bytes {
+            let event = SerialEvent {
+                channel: self.channel,
+                baud_rate: self.baud_rate,
+                timestamp,
+                data,
+                error: None,
+            };
+
+            let decoded = match self.channel {
+                Channel::CI => self
+                    .commands
+                    .process_event(&event)
+                    .filter_map(|item| match item {
+                        ParsedItem::ValidFrame {
+                            command,
+                            raw_bytes,
+                            timestamps,
+                        } => Some(DecodedFrame::Command {
+                            timestamp: timestamps.last().copied().unwrap_or(timestamp),
+                            command,
+                            raw_bytes,
+                            _has_errors: false,
+                            baud_rate: self.baud_rate,
+                        }),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>(),
+                Channel::RO => self
+                    .responses
+                    .process_event(&event)
+                    .filter_map(|item| match item {
+                        ParsedItem::ValidResponse {
+                            response,
+                            raw_bytes,
+                            timestamps,
+                        } => Some(DecodedFrame::Response {
+                            timestamp: timestamps.last().copied().unwrap_or(timestamp),
+                            response,
+                            raw_bytes,
+                            _has_errors: false,
+                            baud_rate: self.baud_rate,
+                        }),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>(),
+            };
+
+            frames.extend(decoded.iter().map(dissect_decoded_frame));
+        }
+
+        frames
+    }
+}
+
+/// Map a numeric baud rate onto the rates the BM13xx link uses.
+pub fn baud_rate_from_bps(bps: u32) -> Result<BaudRate> {
+    match bps {
+        115_200 => Ok(BaudRate::Baud115200),
+        1_000_000 => Ok(BaudRate::Baud1M),
+        other => bail!(
+            "unsupported baud rate {} (expected 115200 or 1000000)",
+            other
+        ),
+    }
+}
+
+/// Open `port` and dissect frames until the port closes or `on_frame` fails.
+pub fn capture_port(
+    port: &str,
+    bps: u32,
+    channel: Channel,
+    on_frame: impl FnMut(DissectedFrame) -> Result<()>,
+) -> Result<()> {
+    let baud_rate = baud_rate_from_bps(bps)?;
+    let serial = serialport::new(port, bps)
+        .timeout(READ_TIMEOUT)
+        .open()
+        .with_context(|| format!("Failed to open serial port {}", port))?;
+
+    dissect_reader(serial, LiveDissector::new(channel, baud_rate), on_frame)
+}
+
+/// Dissect everything read from `reader`, calling `on_frame` as frames
+/// complete.
+///
+/// Read timeouts are treated as idle time rather than errors. End of file,
+/// which a pty reports when its other end closes, ends the capture.
+pub fn dissect_reader(
+    mut reader: impl Read,
+    mut dissector: LiveDissector,
+    mut on_frame: impl FnMut(DissectedFrame) -> Result<()>,
+) -> Result<()> {
+    let mut buf = [0u8; 256];
+
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(n) => n,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::TimedOut | io::ErrorKind::Interrupted
+                ) =>
+            {
+                continue
+            }
+            Err(e) => return Err(e).context("Serial read failed"),
+        };
+
+        for frame in dissector.push(&buf[..n], now()) {
+            on_frame(frame)?;
+        }
+    }
+}
+
+/// Wall-clock time in seconds, matching the absolute timestamps of captures.
+pub fn now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bm13xx::Direction;
+    use bytes::BytesMut;
+    use mujina_miner::asic::bm13xx::protocol::{Command, FrameCodec, RegisterAddress};
+    use tokio_util::codec::Encoder;
+
+    /// Reader that hands out one chunk per call, with a timeout between each.
+    struct ChunkedReader {
+        chunks: Vec<Vec<u8>>,
+        timed_out: bool,
+    }
+
+    impl Read for ChunkedReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if !self.timed_out {
+                self.timed_out = true;
+                return Err(io::ErrorKind::TimedOut.into());
+            }
+            self.timed_out = false;
+
+            if self.chunks.is_empty() {
+                return Ok(0);
+            }
+            let chunk = self.chunks.remove(0);
+            buf[..chunk.len()].copy_from_slice(&chunk);
+            Ok(chunk.len())
+        }
+    }
+
+    fn read_chip_id() -> Vec<u8> {
+        let mut frame = BytesMut::new();
+        FrameCodec::default()
+            .encode(
+                Command::ReadRegister {
+                    broadcast: true,
+                    chip_address: 0,
+                    register_address: RegisterAddress::ChipId,
+                },
+                &mut frame,
+            )
+            .unwrap();
+        frame.to_vec()
+    }
+
+    #[test]
+    fn test_frame_split_across_reads() {
+        let frame = read_chip_id();
+
+        // Line noise, then the frame broken up mid-preamble and mid-body
+        let mut stream = vec![0x00, 0xff];
+        stream.extend(&frame);
+        stream.extend(&frame);
+        let chunks = stream.chunks(3).map(<[u8]>::to_vec).collect();
+        let reader = ChunkedReader {
+            chunks,
+            timed_out: false,
+        };
+
+        let mut frames = Vec::new();
+        dissect_reader(
+            reader,
+            LiveDissector::new(Channel::CI, BaudRate::Baud115200),
+            |frame| {
+                frames.push(frame);
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        assert_eq!(frames.len(), 2);
+        for dissected in frames {
+            assert_eq!(dissected.direction, Direction::HostToChip);
+            assert_eq!(dissected.raw_data, frame);
+        }
+    }
+
+    #[test]
+    fn test_response_channel() {
+        // Chip ID response from a BM1370
+        let response = [
+            0xaa, 0x55, 0x13, 0x70, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10,
+        ];
+        let mut dissector = LiveDissector::new(Channel::RO, BaudRate::Baud1M);
+
+        let frames = dissector.push(&response, 1.5);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].direction, Direction::ChipToHost);
+        assert_eq!(frames[0].baud_rate, BaudRate::Baud1M);
+        assert_eq!(frames[0].timestamp, 1.5);
+
+        // A command frame on the response line isn't a response
+        assert!(dissector.push(&read_chip_id(), 2.0).is_empty());
+    }
+
+    #[test]
+    fn test_baud_rate_from_bps() {
+        assert_eq!(baud_rate_from_bps(115_200).unwrap(), BaudRate::Baud115200);
+        assert_eq!(baud_rate_from_bps(1_000_000).unwrap(), BaudRate::Baud1M);
+        assert!(baud_rate_from_bps(9600).is_err());
+    }
+}
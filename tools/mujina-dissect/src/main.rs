@@ -1,10 +1,17 @@
-//! Mujina protocol dissector for Saleae Logic 2 captures.
+//! Mujina protocol dissector for Saleae Logic 2 and Stratum captures, and
+//! for serial lines tapped live.
 
 mod bm13xx;
 mod capture;
 mod dissect;
 mod i2c;
+mod live;
 mod output;
+mod pcap;
+mod pcapng;
+mod replay;
+mod saleae;
+mod stratum;
 
 use anyhow::{Context, Result};
 use bm13xx::{CommandStreamingParser, DecodedFrame, ParsedItem, ResponseStreamingParser};
@@ -13,14 +20,50 @@ use clap::Parser;
 use dissect::{dissect_decoded_frame, dissect_i2c_operation_with_context, I2cContexts};
 use i2c::{group_pmbus_transactions, group_transactions, I2cAssembler};
 use output::{OutputConfig, OutputEvent};
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
 /// Protocol dissector for Bitcoin mining hardware captures
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Path to Saleae Logic 2 CSV export file
-    input: PathBuf,
+    /// Path to a Saleae Logic 2 CSV or binary export, a pcap or pcapng file,
+    /// or (with `-p stratum`) a newline-delimited Stratum JSON transcript
+    #[arg(required_unless_present = "live", conflicts_with = "live")]
+    input: Option<PathBuf>,
+
+    /// Dissect BM13xx frames live from a serial port (or pty) tapping one
+    /// line of the ASIC link
+    #[arg(long, value_name = "PORT")]
+    live: Option<String>,
+
+    /// Baud rate of the tapped line (115200 or 1000000)
+    #[arg(long, default_value_t = 115_200, requires = "live")]
+    baud: u32,
+
+    /// Which line a live port or Saleae binary export carries: CI (host to
+    /// ASIC) or RO (ASIC to host)
+    #[arg(long, value_name = "CI|RO", default_value = "CI")]
+    tap: String,
+
+    /// Also write the dissected session to a pcapng file
+    #[arg(short = 'w', long, value_name = "PATH")]
+    write_pcapng: Option<PathBuf>,
+
+    /// Replay the capture's I2C operations through this bitaxe-raw control
+    /// port, comparing what the board answers with the capture
+    #[arg(long, value_name = "PORT", requires = "input")]
+    replay_control: Option<String>,
+
+    /// Replay the capture's BM13xx command frames out this ASIC data port,
+    /// comparing the chips' responses with the capture
+    #[arg(long, value_name = "PORT", requires = "input")]
+    replay_data: Option<String>,
+
+    /// Before replaying, pulse the active-low ASIC reset on this bitaxe-raw
+    /// GPIO pin (0 on Bitaxe boards)
+    #[arg(long, value_name = "PIN", requires = "replay_control")]
+    reset_pin: Option<u8>,
 
     /// Show raw hex data for each frame
     #[arg(short = 'x', long)]
@@ -34,7 +77,7 @@ struct Args {
     #[arg(short = 'f', long)]
     filter_channel: Option<String>,
 
-    /// Filter by protocol (bm13xx, i2c, all)
+    /// Filter by protocol (bm13xx, i2c, stratum, all)
     #[arg(short = 'p', long, default_value = "all")]
     protocol: String,
 
@@ -68,10 +111,6 @@ fn main() -> Result<()> {
             .init();
     }
 
-    // Open capture file
-    let mut reader = CaptureReader::open(&args.input)
-        .with_context(|| format!("Failed to open capture file: {:?}", args.input))?;
-
     // Setup output configuration
     let mut output_config = OutputConfig {
         show_raw_hex: args.hex,
@@ -87,6 +126,161 @@ fn main() -> Result<()> {
         colored::control::set_override(false);
     }
 
+    let input = match (&args.input, &args.live) {
+        (_, Some(port)) => return dissect_live(&args, port, output_config),
+        (Some(input), None) => input,
+        (None, None) => unreachable!("clap requires an input or --live"),
+    };
+
+    let mut all_events = if pcapng::is_pcapng(input) {
+        pcapng::read_events(input)?
+    } else if pcap::is_pcap(input) {
+        dissect_stratum_events(stratum::dissect_pcap(input)?)
+    } else if saleae::is_session(input) {
+        anyhow::bail!(
+            "Saleae .sal sessions can't be read directly; in Logic 2 use \
+             File > Export Raw Data > Binary and pass a digital_N.bin file"
+        );
+    } else if saleae::is_binary_export(input) {
+        let events = saleae::read_serial_events(input, parse_tap(&args.tap)?)?;
+        dissect_capture(&args, events.into_iter().map(Ok))?
+    } else if args.protocol == "stratum" {
+        dissect_stratum_events(stratum::dissect_transcript(input)?)
+    } else {
+        dissect_saleae(&args, input)?
+    };
+
+    // Sort events by timestamp
+    all_events.sort_by(|a, b| a.timestamp().partial_cmp(&b.timestamp()).unwrap());
+
+    // Set start time for relative timestamps (default behavior)
+    if !args.absolute_time && !all_events.is_empty() {
+        output_config.start_time = Some(all_events[0].timestamp());
+    }
+
+    if let Some(path) = &args.write_pcapng {
+        let file = std::fs::File::create(path)
+            .with_context(|| format!("Failed to create pcapng file: {:?}", path))?;
+        let mut writer = pcapng::PcapngWriter::new(std::io::BufWriter::new(file))?;
+        for event in &all_events {
+            writer.write_event(event)?;
+        }
+    }
+
+    if args.replay_control.is_some() || args.replay_data.is_some() {
+        return replay_capture(&args, &all_events, &output_config);
+    }
+
+    // Output results
+    if let Some(output_path) = args.output {
+        let mut file = std::fs::File::create(&output_path)
+            .with_context(|| format!("Failed to create output file: {:?}", output_path))?;
+
+        for event in all_events {
+            writeln!(file, "{}", event.format(&output_config))?;
+        }
+    } else {
+        for event in all_events {
+            println!("{}", event.format(&output_config));
+        }
+    }
+
+    Ok(())
+}
+
+/// Dissect frames from a tapped serial line, printing each as it arrives.
+fn dissect_live(args: &Args, port: &str, mut output_config: OutputConfig) -> Result<()> {
+    let channel = parse_tap(&args.tap)?;
+
+    if !args.absolute_time {
+        output_config.start_time = Some(live::now());
+    }
+
+    let mut out: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(
+            std::fs::File::create(path)
+                .with_context(|| format!("Failed to create output file: {:?}", path))?,
+        ),
+        None => Box::new(std::io::stdout()),
+    };
+
+    let mut pcapng = match &args.write_pcapng {
+        Some(path) => Some(pcapng::PcapngWriter::new(
+            std::fs::File::create(path)
+                .with_context(|| format!("Failed to create pcapng file: {:?}", path))?,
+        )?),
+        None => None,
+    };
+
+    live::capture_port(port, args.baud, channel, |frame| {
+        let event = OutputEvent::Serial(frame);
+        writeln!(out, "{}", event.format(&output_config))?;
+        out.flush()?;
+        if let Some(writer) = &mut pcapng {
+            writer.write_event(&event)?;
+        }
+        Ok(())
+    })
+}
+
+/// Replay a dissected capture against attached hardware, reporting how each
+/// step compares.
+fn replay_capture(args: &Args, events: &[OutputEvent], output_config: &OutputConfig) -> Result<()> {
+    let steps = replay::plan(events);
+    let mut hardware =
+        replay::Hardware::open(args.replay_data.as_deref(), args.replay_control.as_deref())?;
+    if let Some(pin) = args.reset_pin {
+        hardware.pulse_reset(pin)?;
+    }
+
+    let mut out: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(
+            std::fs::File::create(path)
+                .with_context(|| format!("Failed to create output file: {:?}", path))?,
+        ),
+        None => Box::new(std::io::stdout()),
+    };
+
+    let start = output_config.start_time.unwrap_or(0.0);
+    let summary = replay::replay(&steps, &mut hardware, |step, outcome| {
+        writeln!(
+            out,
+            "[{:>12.6}] {}  {}",
+            step.timestamp() - start,
+            step,
+            outcome
+        )?;
+        out.flush()?;
+        Ok(())
+    })?;
+
+    writeln!(out, "{} steps: {}", steps.len(), summary)?;
+    Ok(())
+}
+
+/// Parse the `--tap` argument.
+fn parse_tap(tap: &str) -> Result<Channel> {
+    match tap.to_ascii_uppercase().as_str() {
+        "CI" => Ok(Channel::CI),
+        "RO" => Ok(Channel::RO),
+        other => anyhow::bail!("unknown tap '{}' (expected CI or RO)", other),
+    }
+}
+
+/// Dissect a Saleae Logic 2 CSV export of serial and I2C channels.
+fn dissect_saleae(args: &Args, input: &Path) -> Result<Vec<OutputEvent>> {
+    // Open capture file
+    let mut reader = CaptureReader::open(input)
+        .with_context(|| format!("Failed to open capture file: {:?}", input))?;
+
+    dissect_capture(args, reader.events())
+}
+
+/// Dissect serial and I2C capture events, in time order.
+fn dissect_capture(
+    args: &Args,
+    events: impl Iterator<Item = Result<CaptureEvent>>,
+) -> Result<Vec<OutputEvent>> {
     // Setup streaming parsers - one for each baud rate per channel
     let mut ci_115k_parser = CommandStreamingParser::new();
     let mut ci_1m_parser = CommandStreamingParser::new();
@@ -100,7 +294,7 @@ fn main() -> Result<()> {
     let mut decoded_frames = Vec::new();
 
     // Process capture events
-    for event_result in reader.events() {
+    for event_result in events {
         let event = event_result?;
 
         match event {
@@ -263,30 +457,12 @@ fn main() -> Result<()> {
         }
     }
 
-    // Sort events by timestamp
-    all_events.sort_by(|a, b| a.timestamp().partial_cmp(&b.timestamp()).unwrap());
-
-    // Set start time for relative timestamps (default behavior)
-    if !args.absolute_time && !all_events.is_empty() {
-        output_config.start_time = Some(all_events[0].timestamp());
-    }
-
-    // Output results
-    if let Some(output_path) = args.output {
-        use std::io::Write;
-        let mut file = std::fs::File::create(&output_path)
-            .with_context(|| format!("Failed to create output file: {:?}", output_path))?;
-
-        for event in all_events {
-            writeln!(file, "{}", event.format(&output_config))?;
-        }
-    } else {
-        for event in all_events {
-            println!("{}", event.format(&output_config));
-        }
-    }
+    Ok(all_events)
+}
 
-    Ok(())
+/// Wrap dissected Stratum messages as output events.
+fn dissect_stratum_events(messages: Vec<stratum::DissectedStratum>) -> Vec<OutputEvent> {
+    messages.into_iter().map(OutputEvent::Stratum).collect()
 }
 
 // Check if output is a terminal (for color support)
@@ -300,3 +476,64 @@ mod atty {
         std::env::var("TERM").is_ok()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Dissect `testdata/<capture>.csv` as `mujina-dissect` would and
+    /// compare the output with `testdata/<capture>.txt`.
+    ///
+    /// After a deliberate change to a decoder, rerun with
+    /// `MUJINA_UPDATE_GOLDEN=1` to rewrite the golden files, and review
+    /// their diff like any other change.
+    fn check_golden(capture: &str) {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata");
+        let input = dir.join(format!("{}.csv", capture));
+        let golden = dir.join(format!("{}.txt", capture));
+
+        let args = Args::parse_from([
+            "mujina-dissect".as_ref(),
+            "--no-color".as_ref(),
+            input.as_os_str(),
+        ]);
+        let mut events = dissect_saleae(&args, &input).unwrap();
+        events.sort_by(|a, b| a.timestamp().partial_cmp(&b.timestamp()).unwrap());
+
+        let config = OutputConfig {
+            show_raw_hex: false,
+            use_relative_time: true,
+            start_time: events.first().map(|event| event.timestamp()),
+            use_color: false,
+        };
+        colored::control::set_override(false);
+        let actual: String = events
+            .iter()
+            .map(|event| format!("{}\n", event.format(&config)))
+            .collect();
+
+        if std::env::var_os("MUJINA_UPDATE_GOLDEN").is_some() {
+            std::fs::write(&golden, &actual).unwrap();
+            return;
+        }
+        let expected = std::fs::read_to_string(&golden)
+            .unwrap_or_else(|e| panic!("{}: {} (set MUJINA_UPDATE_GOLDEN=1)", golden.display(), e));
+        assert!(
+            actual == expected,
+            "{} no longer dissects as {}; got:\n{}",
+            input.display(),
+            golden.display(),
+            actual
+        );
+    }
+
+    #[test]
+    fn test_golden_esp_miner_boot() {
+        check_golden("esp-miner-boot");
+    }
+
+    #[test]
+    fn test_golden_tps546_init() {
+        check_golden("tps546-init");
+    }
+}
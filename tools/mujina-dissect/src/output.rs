@@ -3,6 +3,7 @@
 use crate::bm13xx::Direction;
 use crate::capture::BaudRate;
 use crate::dissect::{CrcStatus, DissectedFrame, DissectedI2c, FrameContent, I2cDevice};
+use crate::stratum::{DissectedStratum, StratumDirection};
 use colored::Colorize;
 
 /// Gray color for hex data output
@@ -92,12 +93,53 @@ pub fn format_serial_frame(frame: &DissectedFrame, config: &OutputConfig) -> Str
     result
 }
 
+/// Format a dissected Stratum message
+pub fn format_stratum_message(msg: &DissectedStratum, config: &OutputConfig) -> String {
+    let timestamp = format_timestamp(msg.timestamp, config);
+
+    let pool = msg.pool.as_deref().unwrap_or("POOL");
+    let (direction_str, device) = match msg.direction {
+        StratumDirection::MinerToPool => (
+            format!("MINER -> {}", pool),
+            Some(DeviceId::StratumMinerToPool),
+        ),
+        StratumDirection::PoolToMiner => (
+            format!("MINER <- {}", pool),
+            Some(DeviceId::StratumPoolToMiner),
+        ),
+        StratumDirection::Unknown => (format!("MINER ?? {}", pool), None),
+    };
+
+    let direction_str = match device {
+        Some(device) if config.use_color => {
+            format!("{}", direction_str.color(get_device_color(&device)))
+        }
+        _ => direction_str,
+    };
+
+    let mut result = format!("{} {}: {}", timestamp, direction_str, msg.summary);
+
+    // JSON is more readable as text than as hex
+    if config.show_raw_hex && !msg.raw_data.is_empty() {
+        let raw = String::from_utf8_lossy(&msg.raw_data);
+        if config.use_color {
+            result.push_str(&format!("\n        {}", gray_hex(&raw)));
+        } else {
+            result.push_str(&format!("\n        {}", raw));
+        }
+    }
+
+    result
+}
+
 /// Device/direction identifier for consistent coloring
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 enum DeviceId {
     I2cAddress(u8),
     AsicHostToChip,
     AsicChipToHost,
+    StratumMinerToPool,
+    StratumPoolToMiner,
 }
 
 /// Get a consistent color for any device or ASIC direction
@@ -107,6 +149,11 @@ fn get_device_color(device_id: &DeviceId) -> colored::Color {
         DeviceId::AsicHostToChip => colored::Color::BrightCyan, // CI -> ASIC
         DeviceId::AsicChipToHost => colored::Color::BrightYellow, // RO <- ASIC
 
+        // Stratum shares the ASIC palette: work flows toward the chips in
+        // cyan, results flow back in yellow
+        DeviceId::StratumPoolToMiner => colored::Color::Cyan,
+        DeviceId::StratumMinerToPool => colored::Color::Yellow,
+
         // I2C addresses get colors from remaining palette (reserve red for errors, avoid bright/regular pairs)
         DeviceId::I2cAddress(addr) => {
             // Distinct colors for I2C devices (avoiding ASIC colors: BrightCyan, BrightYellow)
@@ -276,6 +323,7 @@ fn format_i2c_transaction(data: &[u8], expected_address: u8) -> String {
 pub enum OutputEvent {
     Serial(DissectedFrame),
     I2c(DissectedI2c),
+    Stratum(DissectedStratum),
 }
 
 impl OutputEvent {
@@ -283,6 +331,7 @@ impl OutputEvent {
         match self {
             OutputEvent::Serial(frame) => frame.timestamp,
             OutputEvent::I2c(op) => op.timestamp,
+            OutputEvent::Stratum(msg) => msg.timestamp,
         }
     }
 
@@ -290,6 +339,7 @@ impl OutputEvent {
         match self {
             OutputEvent::Serial(frame) => format_serial_frame(frame, config),
             OutputEvent::I2c(op) => format_i2c_operation(op, config),
+            OutputEvent::Stratum(msg) => format_stratum_message(msg, config),
         }
     }
 }
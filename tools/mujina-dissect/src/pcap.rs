@@ -0,0 +1,423 @@
+//! Minimal pcap reader and TCP stream reassembly.
+//!
+//! Reads classic libpcap files (pcapng is handled by `pcapng.rs`) with
+//! Ethernet, Linux cooked, BSD loopback, or raw IP link types, and reassembles each TCP direction into a
+//! byte stream split on newlines---enough to recover line-delimited protocols
+//! like Stratum v1 from a tcpdump capture. Out-of-order segments aren't
+//! buffered; captures taken on the miner or pool host rarely need it.
+
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::path::Path;
+
+/// Microsecond-resolution pcap magic, in file byte order.
+const MAGIC_MICROS: u32 = 0xa1b2_c3d4;
+
+/// Nanosecond-resolution pcap magic, in file byte order.
+const MAGIC_NANOS: u32 = 0xa1b2_3c4d;
+
+/// Section header block type, the first four bytes of a pcapng file.
+const PCAPNG_MAGIC: u32 = 0x0a0d_0d0a;
+
+const LINKTYPE_NULL: u32 = 0;
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+const LINKTYPE_IPV4: u32 = 228;
+const LINKTYPE_IPV6: u32 = 229;
+const LINKTYPE_LINUX_SLL2: u32 = 276;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+
+const IP_PROTO_TCP: u8 = 6;
+
+const TCP_FLAG_SYN: u8 = 0x02;
+
+/// One end of a TCP connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Endpoint {
+    pub addr: IpAddr,
+    pub port: u16,
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.addr {
+            IpAddr::V4(addr) => write!(f, "{}:{}", addr, self.port),
+            IpAddr::V6(addr) => write!(f, "[{}]:{}", addr, self.port),
+        }
+    }
+}
+
+/// A TCP segment carrying payload (or a SYN).
+#[derive(Debug, Clone)]
+pub struct TcpSegment {
+    pub timestamp: f64,
+    pub src: Endpoint,
+    pub dst: Endpoint,
+    pub seq: u32,
+    pub syn: bool,
+    pub payload: Vec<u8>,
+}
+
+/// A complete line from one direction of a TCP stream.
+#[derive(Debug, Clone)]
+pub struct StreamLine {
+    /// Time of the segment that completed the line
+    pub timestamp: f64,
+    pub src: Endpoint,
+    pub dst: Endpoint,
+    pub line: Vec<u8>,
+}
+
+/// Check whether a file starts with a pcap magic number.
+pub fn is_pcap(path: &Path) -> bool {
+    let Ok(data) = std::fs::read(path) else {
+        return false;
+    };
+    data.len() >= 4 && read_magic(&data[..4]).is_some()
+}
+
+/// Read every TCP segment from a pcap file.
+pub fn read_tcp_segments(path: &Path) -> Result<Vec<TcpSegment>> {
+    let data =
+        std::fs::read(path).with_context(|| format!("reading pcap file {}", path.display()))?;
+    parse_tcp_segments(&data)
+}
+
+/// Parse TCP segments from an in-memory pcap file.
+pub fn parse_tcp_segments(data: &[u8]) -> Result<Vec<TcpSegment>> {
+    if data.len() < 24 {
+        bail!("file too short for a pcap header");
+    }
+    if u32::from_le_bytes(data[..4].try_into().unwrap()) == PCAPNG_MAGIC {
+        bail!("file is pcapng, not pcap");
+    }
+    let (big_endian, nanos) =
+        read_magic(&data[..4]).context("not a pcap file (bad magic number)")?;
+
+    let read_u32 = |bytes: &[u8]| {
+        let bytes: [u8; 4] = bytes.try_into().unwrap();
+        if big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        }
+    };
+
+    let link_type = read_u32(&data[20..24]) & 0x0fff_ffff;
+    let frac_scale = if nanos { 1e-9 } else { 1e-6 };
+
+    let mut segments = Vec::new();
+    let mut offset = 24;
+    while offset + 16 <= data.len() {
+        let ts_sec = read_u32(&data[offset..offset + 4]);
+        let ts_frac = read_u32(&data[offset + 4..offset + 8]);
+        let incl_len = read_u32(&data[offset + 8..offset + 12]) as usize;
+        offset += 16;
+
+        let Some(packet) = data.get(offset..offset + incl_len) else {
+            bail!("truncated packet record at offset {}", offset - 16);
+        };
+        offset += incl_len;
+
+        let timestamp = ts_sec as f64 + ts_frac as f64 * frac_scale;
+        if let Some(segment) = parse_packet(link_type, packet, timestamp) {
+            segments.push(segment);
+        }
+    }
+
+    Ok(segments)
+}
+
+/// Returns (big_endian, nanosecond_resolution) for a pcap magic number.
+fn read_magic(bytes: &[u8]) -> Option<(bool, bool)> {
+    let bytes: [u8; 4] = bytes.try_into().ok()?;
+    match (u32::from_be_bytes(bytes), u32::from_le_bytes(bytes)) {
+        (MAGIC_MICROS, _) => Some((true, false)),
+        (MAGIC_NANOS, _) => Some((true, true)),
+        (_, MAGIC_MICROS) => Some((false, false)),
+        (_, MAGIC_NANOS) => Some((false, true)),
+        _ => None,
+    }
+}
+
+/// Strip the link layer and parse the IP packet inside, if it's TCP.
+pub fn parse_packet(link_type: u32, packet: &[u8], timestamp: f64) -> Option<TcpSegment> {
+    let ip = match link_type {
+        LINKTYPE_ETHERNET => {
+            let mut ethertype = u16::from_be_bytes(packet.get(12..14)?.try_into().ok()?);
+            let mut header_len = 14;
+            if ethertype == ETHERTYPE_VLAN {
+                ethertype = u16::from_be_bytes(packet.get(16..18)?.try_into().ok()?);
+                header_len = 18;
+            }
+            match ethertype {
+                ETHERTYPE_IPV4 | ETHERTYPE_IPV6 => packet.get(header_len..)?,
+                _ => return None,
+            }
+        }
+        LINKTYPE_LINUX_SLL => packet.get(16..)?,
+        LINKTYPE_LINUX_SLL2 => packet.get(20..)?,
+        // Address family in host byte order; the IP version nibble is enough
+        LINKTYPE_NULL => packet.get(4..)?,
+        LINKTYPE_RAW | LINKTYPE_IPV4 | LINKTYPE_IPV6 => packet,
+        _ => return None,
+    };
+
+    match ip.first()? >> 4 {
+        4 => parse_ipv4(ip, timestamp),
+        6 => parse_ipv6(ip, timestamp),
+        _ => None,
+    }
+}
+
+fn parse_ipv4(ip: &[u8], timestamp: f64) -> Option<TcpSegment> {
+    let header_len = ((ip[0] & 0x0f) as usize) * 4;
+    let total_len = u16::from_be_bytes(ip.get(2..4)?.try_into().ok()?) as usize;
+    let fragment = u16::from_be_bytes(ip.get(6..8)?.try_into().ok()?);
+    let more_fragments = fragment & 0x2000 != 0;
+    let fragment_offset = fragment & 0x1fff;
+    if *ip.get(9)? != IP_PROTO_TCP || more_fragments || fragment_offset != 0 {
+        return None;
+    }
+
+    let src: [u8; 4] = ip.get(12..16)?.try_into().ok()?;
+    let dst: [u8; 4] = ip.get(16..20)?.try_into().ok()?;
+
+    // Total length excludes any Ethernet padding
+    let tcp = ip.get(header_len..total_len.min(ip.len()))?;
+    parse_tcp(tcp, IpAddr::from(src), IpAddr::from(dst), timestamp)
+}
+
+fn parse_ipv6(ip: &[u8], timestamp: f64) -> Option<TcpSegment> {
+    let payload_len = u16::from_be_bytes(ip.get(4..6)?.try_into().ok()?) as usize;
+    // Extension headers aren't followed; Stratum traffic doesn't use them
+    if *ip.get(6)? != IP_PROTO_TCP {
+        return None;
+    }
+
+    let src: [u8; 16] = ip.get(8..24)?.try_into().ok()?;
+    let dst: [u8; 16] = ip.get(24..40)?.try_into().ok()?;
+
+    let tcp = ip.get(40..(40 + payload_len).min(ip.len()))?;
+    parse_tcp(tcp, IpAddr::from(src), IpAddr::from(dst), timestamp)
+}
+
+fn parse_tcp(tcp: &[u8], src: IpAddr, dst: IpAddr, timestamp: f64) -> Option<TcpSegment> {
+    let src_port = u16::from_be_bytes(tcp.get(0..2)?.try_into().ok()?);
+    let dst_port = u16::from_be_bytes(tcp.get(2..4)?.try_into().ok()?);
+    let seq = u32::from_be_bytes(tcp.get(4..8)?.try_into().ok()?);
+    let data_offset = ((tcp.get(12)? >> 4) as usize) * 4;
+    let flags = *tcp.get(13)?;
+    let payload = tcp.get(data_offset..)?.to_vec();
+
+    let syn = flags & TCP_FLAG_SYN != 0;
+    if payload.is_empty() && !syn {
+        return None;
+    }
+
+    Some(TcpSegment {
+        timestamp,
+        src: Endpoint {
+            addr: src,
+            port: src_port,
+        },
+        dst: Endpoint {
+            addr: dst,
+            port: dst_port,
+        },
+        seq,
+        syn,
+        payload,
+    })
+}
+
+/// One direction of a TCP connection being reassembled.
+#[derive(Default)]
+struct Flow {
+    next_seq: Option<u32>,
+    buffer: Vec<u8>,
+}
+
+/// Reassemble segments into newline-terminated lines per direction.
+///
+/// Retransmitted bytes are dropped by sequence number. A gap (lost or
+/// out-of-order segment) is bridged by appending what arrived, which at worst
+/// garbles the line spanning it. A trailing partial line is discarded.
+pub fn reassemble_lines(segments: &[TcpSegment]) -> Vec<StreamLine> {
+    let mut flows: HashMap<(Endpoint, Endpoint), Flow> = HashMap::new();
+    let mut lines = Vec::new();
+
+    for segment in segments {
+        let flow = flows.entry((segment.src, segment.dst)).or_default();
+
+        if segment.syn {
+            flow.next_seq = Some(segment.seq.wrapping_add(1));
+            flow.buffer.clear();
+            continue;
+        }
+
+        let mut payload = segment.payload.as_slice();
+        if let Some(next_seq) = flow.next_seq {
+            let offset = segment.seq.wrapping_sub(next_seq) as i32;
+            if offset < 0 {
+                // Overlaps data already seen
+                let overlap = offset.unsigned_abs() as usize;
+                if overlap >= payload.len() {
+                    continue;
+                }
+                payload = &payload[overlap..];
+            } else if offset > 0 {
+                tracing::debug!(
+                    src = %segment.src,
+                    dst = %segment.dst,
+                    missing = offset,
+                    "Gap in TCP stream"
+                );
+            }
+        }
+        let start_seq = segment
+            .seq
+            .wrapping_add((segment.payload.len() - payload.len()) as u32);
+        flow.next_seq = Some(start_seq.wrapping_add(payload.len() as u32));
+
+        flow.buffer.extend_from_slice(payload);
+        while let Some(end) = flow.buffer.iter().position(|&b| b == b'\n') {
+            let mut line: Vec<u8> = flow.buffer.drain(..=end).collect();
+            line.pop();
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+            if !line.is_empty() {
+                lines.push(StreamLine {
+                    timestamp: segment.timestamp,
+                    src: segment.src,
+                    dst: segment.dst,
+                    line,
+                });
+            }
+        }
+    }
+
+    lines
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    /// Builds little-endian, microsecond pcap files of Ethernet/IPv4/TCP
+    /// packets for tests.
+    pub(crate) struct PcapBuilder {
+        data: Vec<u8>,
+    }
+
+    impl PcapBuilder {
+        pub(crate) fn new() -> Self {
+            let mut data = Vec::new();
+            data.extend_from_slice(&MAGIC_MICROS.to_le_bytes());
+            data.extend_from_slice(&2u16.to_le_bytes());
+            data.extend_from_slice(&4u16.to_le_bytes());
+            data.extend_from_slice(&[0; 8]);
+            data.extend_from_slice(&65535u32.to_le_bytes());
+            data.extend_from_slice(&LINKTYPE_ETHERNET.to_le_bytes());
+            Self { data }
+        }
+
+        pub(crate) fn packet(
+            &mut self,
+            timestamp: f64,
+            src: (Ipv4Addr, u16),
+            dst: (Ipv4Addr, u16),
+            seq: u32,
+            syn: bool,
+            payload: &[u8],
+        ) -> &mut Self {
+            let mut tcp = Vec::new();
+            tcp.extend_from_slice(&src.1.to_be_bytes());
+            tcp.extend_from_slice(&dst.1.to_be_bytes());
+            tcp.extend_from_slice(&seq.to_be_bytes());
+            tcp.extend_from_slice(&[0; 4]); // ack
+            tcp.push(5 << 4); // data offset
+            tcp.push(if syn { TCP_FLAG_SYN } else { 0x18 });
+            tcp.extend_from_slice(&[0; 6]); // window, checksum, urgent
+            tcp.extend_from_slice(payload);
+
+            let mut ip = vec![0x45, 0];
+            ip.extend_from_slice(&((20 + tcp.len()) as u16).to_be_bytes());
+            ip.extend_from_slice(&[0, 0, 0x40, 0, 64, IP_PROTO_TCP, 0, 0]);
+            ip.extend_from_slice(&src.0.octets());
+            ip.extend_from_slice(&dst.0.octets());
+            ip.extend_from_slice(&tcp);
+
+            let mut frame = vec![0; 12];
+            frame.extend_from_slice(&ETHERTYPE_IPV4.to_be_bytes());
+            frame.extend_from_slice(&ip);
+
+            let secs = timestamp.trunc() as u32;
+            let micros = (timestamp.fract() * 1e6).round() as u32;
+            self.data.extend_from_slice(&secs.to_le_bytes());
+            self.data.extend_from_slice(&micros.to_le_bytes());
+            self.data
+                .extend_from_slice(&(frame.len() as u32).to_le_bytes());
+            self.data
+                .extend_from_slice(&(frame.len() as u32).to_le_bytes());
+            self.data.extend_from_slice(&frame);
+            self
+        }
+
+        pub(crate) fn build(&self) -> Vec<u8> {
+            self.data.clone()
+        }
+    }
+
+    const MINER: (Ipv4Addr, u16) = (Ipv4Addr::new(192, 168, 1, 10), 50000);
+    const POOL: (Ipv4Addr, u16) = (Ipv4Addr::new(10, 0, 0, 1), 3333);
+
+    #[test]
+    fn test_parse_and_reassemble() {
+        let pcap = PcapBuilder::new()
+            .packet(1.0, MINER, POOL, 1000, true, &[])
+            .packet(1.5, MINER, POOL, 1001, false, b"{\"id\":1}\n{\"id\"")
+            .packet(2.0, POOL, MINER, 7, false, b"{\"id\":1,\"result\":true}\n")
+            // Retransmission of the first segment is dropped
+            .packet(2.2, MINER, POOL, 1001, false, b"{\"id\":1}\n{\"id\"")
+            .packet(2.5, MINER, POOL, 1015, false, b":2}\r\n")
+            .build();
+
+        let segments = parse_tcp_segments(&pcap).unwrap();
+        assert_eq!(segments.len(), 5);
+        assert!(segments[0].syn);
+        assert_eq!(segments[1].src.to_string(), "192.168.1.10:50000");
+
+        let lines = reassemble_lines(&segments);
+        let text: Vec<_> = lines
+            .iter()
+            .map(|l| (l.timestamp, String::from_utf8_lossy(&l.line).into_owned()))
+            .collect();
+        assert_eq!(
+            text,
+            vec![
+                (1.5, "{\"id\":1}".to_string()),
+                (2.0, "{\"id\":1,\"result\":true}".to_string()),
+                (2.5, "{\"id\":2}".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rejects_non_pcap() {
+        assert!(parse_tcp_segments(b"not a capture file at all").is_err());
+
+        let mut pcapng = PCAPNG_MAGIC.to_le_bytes().to_vec();
+        pcapng.resize(32, 0);
+        let err = parse_tcp_segments(&pcapng).unwrap_err();
+        assert!(err.to_string().contains("pcapng"));
+    }
+}
@@ -0,0 +1,624 @@
+//! pcapng export and import of dissected sessions.
+//!
+//! Dissected traffic is written as one section with four interfaces, using
+//! the link types pcapng reserves for private use:
+//!
+//! | Interface | Link type | Packet contents                             |
+//! |-----------|-----------|---------------------------------------------|
+//! | 0         | USER0     | BM13xx frame at 115200 baud                 |
+//! | 1         | USER0     | BM13xx frame at 1000000 baud                |
+//! | 2         | USER1     | I2C operation (see [`encode_i2c`])          |
+//! | 3         | USER2     | Stratum JSON line, pool endpoint as comment |
+//!
+//! Each BM13xx interface carries its rate in `if_speed`. Direction is the
+//! `epb_flags` inbound/outbound bits, from the host's point of view: commands
+//! and miner-to-pool messages are outbound. Wireshark shows the raw bytes
+//! (map the USER link types to a dissector under DLT_USER to decode them),
+//! and reading the file back into mujina-dissect re-dissects every packet.
+//!
+//! Any other link type in a pcapng file is treated as network traffic and
+//! searched for Stratum sessions, as with classic pcap.
+
+use crate::bm13xx::Direction;
+use crate::capture::{BaudRate, Channel};
+use crate::dissect::{dissect_i2c_operation_with_context, I2cContexts};
+use crate::i2c::I2cOperation;
+use crate::live::LiveDissector;
+use crate::output::OutputEvent;
+use crate::pcap;
+use crate::stratum::{self, StratumDirection, StratumDissector};
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Section header block type, which is also the file's magic number.
+const BLOCK_SECTION_HEADER: u32 = 0x0a0d_0d0a;
+const BLOCK_INTERFACE_DESCRIPTION: u32 = 1;
+const BLOCK_ENHANCED_PACKET: u32 = 6;
+
+const BYTE_ORDER_MAGIC: u32 = 0x1a2b_3c4d;
+
+const LINKTYPE_USER0: u16 = 147;
+const LINKTYPE_USER1: u16 = 148;
+const LINKTYPE_USER2: u16 = 149;
+
+const OPT_END: u16 = 0;
+const OPT_COMMENT: u16 = 1;
+const OPT_SHB_USERAPPL: u16 = 4;
+const OPT_IF_NAME: u16 = 2;
+const OPT_IF_SPEED: u16 = 8;
+const OPT_IF_TSRESOL: u16 = 9;
+const OPT_EPB_FLAGS: u16 = 2;
+
+const EPB_FLAG_INBOUND: u32 = 1;
+const EPB_FLAG_OUTBOUND: u32 = 2;
+const EPB_DIRECTION_MASK: u32 = 3;
+
+const IF_BM13XX_115K: u32 = 0;
+const IF_BM13XX_1M: u32 = 1;
+const IF_I2C: u32 = 2;
+const IF_STRATUM: u32 = 3;
+
+/// Timestamps are written in nanoseconds (`if_tsresol` = 10^-9).
+const TSRESOL_NANOS: u8 = 9;
+
+const I2C_HAS_REGISTER: u8 = 0x01;
+const I2C_HAS_WRITE: u8 = 0x02;
+const I2C_HAS_READ: u8 = 0x04;
+const I2C_NAKED: u8 = 0x08;
+
+/// Length of the I2C packet header before the data bytes.
+const I2C_HEADER_LEN: usize = 5;
+
+/// Writes dissected events to a pcapng stream.
+pub struct PcapngWriter<W: Write> {
+    out: W,
+}
+
+impl<W: Write> PcapngWriter<W> {
+    /// Write the section header and interface descriptions.
+    pub fn new(mut out: W) -> io::Result<Self> {
+        let mut body = Vec::new();
+        body.extend(BYTE_ORDER_MAGIC.to_le_bytes());
+        body.extend(1u16.to_le_bytes());
+        body.extend(0u16.to_le_bytes());
+        body.extend((-1i64).to_le_bytes());
+        push_option(&mut body, OPT_SHB_USERAPPL, b"mujina-dissect");
+        push_option(&mut body, OPT_END, &[]);
+        out.write_all(&block(BLOCK_SECTION_HEADER, &body))?;
+
+        let interfaces: [(u16, &str, Option<u64>); 4] = [
+            (LINKTYPE_USER0, "bm13xx", Some(115_200)),
+            (LINKTYPE_USER0, "bm13xx", Some(1_000_000)),
+            (LINKTYPE_USER1, "i2c", None),
+            (LINKTYPE_USER2, "stratum", None),
+        ];
+        for (link_type, name, speed) in interfaces {
+            let mut body = Vec::new();
+            body.extend(link_type.to_le_bytes());
+            body.extend(0u16.to_le_bytes());
+            body.extend(0u32.to_le_bytes()); // no snap length
+            push_option(&mut body, OPT_IF_NAME, name.as_bytes());
+            if let Some(speed) = speed {
+                push_option(&mut body, OPT_IF_SPEED, &speed.to_le_bytes());
+            }
+            push_option(&mut body, OPT_IF_TSRESOL, &[TSRESOL_NANOS]);
+            push_option(&mut body, OPT_END, &[]);
+            out.write_all(&block(BLOCK_INTERFACE_DESCRIPTION, &body))?;
+        }
+
+        Ok(Self { out })
+    }
+
+    /// Write one event as a packet.
+    pub fn write_event(&mut self, event: &OutputEvent) -> io::Result<()> {
+        match event {
+            OutputEvent::Serial(frame) => {
+                let interface = match frame.baud_rate {
+                    BaudRate::Baud115200 => IF_BM13XX_115K,
+                    BaudRate::Baud1M => IF_BM13XX_1M,
+                };
+                let flags = match frame.direction {
+                    Direction::HostToChip => EPB_FLAG_OUTBOUND,
+                    Direction::ChipToHost => EPB_FLAG_INBOUND,
+                };
+                self.write_packet(interface, frame.timestamp, &frame.raw_data, flags, None)
+            }
+            OutputEvent::I2c(op) => {
+                self.write_packet(IF_I2C, op.timestamp, &encode_i2c(&op.source), 0, None)
+            }
+            OutputEvent::Stratum(msg) => {
+                let flags = match msg.direction {
+                    StratumDirection::MinerToPool => EPB_FLAG_OUTBOUND,
+                    StratumDirection::PoolToMiner => EPB_FLAG_INBOUND,
+                    StratumDirection::Unknown => 0,
+                };
+                self.write_packet(
+                    IF_STRATUM,
+                    msg.timestamp,
+                    &msg.raw_data,
+                    flags,
+                    msg.pool.as_deref(),
+                )
+            }
+        }
+    }
+
+    fn write_packet(
+        &mut self,
+        interface: u32,
+        timestamp: f64,
+        data: &[u8],
+        flags: u32,
+        comment: Option<&str>,
+    ) -> io::Result<()> {
+        let nanos = (timestamp.max(0.0) * 1e9).round() as u64;
+
+        let mut body = Vec::new();
+        body.extend(interface.to_le_bytes());
+        body.extend(((nanos >> 32) as u32).to_le_bytes());
+        body.extend((nanos as u32).to_le_bytes());
+        body.extend((data.len() as u32).to_le_bytes());
+        body.extend((data.len() as u32).to_le_bytes());
+        body.extend(data);
+        pad(&mut body);
+        if let Some(comment) = comment {
+            push_option(&mut body, OPT_COMMENT, comment.as_bytes());
+        }
+        if flags != 0 {
+            push_option(&mut body, OPT_EPB_FLAGS, &flags.to_le_bytes());
+        }
+        push_option(&mut body, OPT_END, &[]);
+
+        self.out.write_all(&block(BLOCK_ENHANCED_PACKET, &body))?;
+        self.out.flush()
+    }
+
+    #[cfg(test)]
+    fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+/// Frame a block body with its type and length fields.
+fn block(block_type: u32, body: &[u8]) -> Vec<u8> {
+    let total = (body.len() + 12) as u32;
+    let mut block = Vec::with_capacity(total as usize);
+    block.extend(block_type.to_le_bytes());
+    block.extend(total.to_le_bytes());
+    block.extend(body);
+    block.extend(total.to_le_bytes());
+    block
+}
+
+fn push_option(body: &mut Vec<u8>, code: u16, value: &[u8]) {
+    body.extend(code.to_le_bytes());
+    body.extend((value.len() as u16).to_le_bytes());
+    body.extend(value);
+    pad(body);
+}
+
+fn pad(data: &mut Vec<u8>) {
+    data.resize(data.len().next_multiple_of(4), 0);
+}
+
+/// Encode an I2C operation as a USER1 packet.
+///
+/// Layout: target address, flags (0x01 register present, 0x02 write phase,
+/// 0x04 read phase, 0x08 NAKed), register (0 if absent), write length
+/// (u16 little-endian), write data, then read data to the end of the packet.
+pub fn encode_i2c(op: &I2cOperation) -> Vec<u8> {
+    let mut flags = 0;
+    if op.register.is_some() {
+        flags |= I2C_HAS_REGISTER;
+    }
+    if op.write_data.is_some() {
+        flags |= I2C_HAS_WRITE;
+    }
+    if op.read_data.is_some() {
+        flags |= I2C_HAS_READ;
+    }
+    if op.was_naked {
+        flags |= I2C_NAKED;
+    }
+
+    let write_data = op.write_data.as_deref().unwrap_or_default();
+    let mut packet = vec![op.address, flags, op.register.unwrap_or_default()];
+    packet.extend((write_data.len() as u16).to_le_bytes());
+    packet.extend(write_data);
+    packet.extend(op.read_data.as_deref().unwrap_or_default());
+    packet
+}
+
+/// Decode a USER1 packet written by [`encode_i2c`].
+pub fn decode_i2c(packet: &[u8], timestamp: f64) -> Option<I2cOperation> {
+    let header = packet.get(..I2C_HEADER_LEN)?;
+    let flags = header[1];
+    let write_len = u16::from_le_bytes([header[3], header[4]]) as usize;
+    let write_data = packet.get(I2C_HEADER_LEN..I2C_HEADER_LEN + write_len)?;
+    let read_data = &packet[I2C_HEADER_LEN + write_len..];
+
+    Some(I2cOperation {
+        start_time: timestamp,
+        address: header[0],
+        register: (flags & I2C_HAS_REGISTER != 0).then_some(header[2]),
+        write_data: (flags & I2C_HAS_WRITE != 0).then(|| write_data.to_vec()),
+        read_data: (flags & I2C_HAS_READ != 0).then(|| read_data.to_vec()),
+        was_naked: flags & I2C_NAKED != 0,
+    })
+}
+
+/// Check whether a file starts with a pcapng section header.
+pub fn is_pcapng(path: &Path) -> bool {
+    use std::io::Read;
+    let mut magic = [0u8; 4];
+    std::fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok_and(|()| u32::from_le_bytes(magic) == BLOCK_SECTION_HEADER)
+}
+
+/// Read and re-dissect a pcapng file.
+pub fn read_events(path: &Path) -> Result<Vec<OutputEvent>> {
+    let data =
+        std::fs::read(path).with_context(|| format!("reading pcapng file {}", path.display()))?;
+    parse_events(&data)
+}
+
+/// Interface fields needed to interpret its packets.
+#[derive(Debug, Clone, Copy)]
+struct Interface {
+    link_type: u16,
+    /// Seconds per timestamp unit
+    resolution: f64,
+    speed: Option<u64>,
+}
+
+/// Re-dissect the packets of an in-memory pcapng file.
+pub fn parse_events(data: &[u8]) -> Result<Vec<OutputEvent>> {
+    let mut events = Vec::new();
+    let mut serial: HashMap<(Channel, BaudRate), LiveDissector> = HashMap::new();
+    let mut i2c_contexts = I2cContexts::default();
+    let mut stratum = StratumDissector::new();
+    let mut stratum_sessions: HashMap<Option<String>, usize> = HashMap::new();
+    let mut tcp_segments = Vec::new();
+
+    let mut big_endian = false;
+    let mut interfaces: Vec<Interface> = Vec::new();
+    let mut offset = 0;
+
+    while offset + 12 <= data.len() {
+        let header = &data[offset..offset + 8];
+        let block_type = u32::from_le_bytes(header[..4].try_into().unwrap());
+
+        if block_type == BLOCK_SECTION_HEADER {
+            let magic: [u8; 4] = data
+                .get(offset + 8..offset + 12)
+                .context("truncated section header")?
+                .try_into()
+                .unwrap();
+            big_endian = match (u32::from_le_bytes(magic), u32::from_be_bytes(magic)) {
+                (BYTE_ORDER_MAGIC, _) => false,
+                (_, BYTE_ORDER_MAGIC) => true,
+                _ => bail!("bad pcapng byte-order magic at offset {}", offset),
+            };
+            interfaces.clear();
+        } else if offset == 0 {
+            bail!("not a pcapng file");
+        }
+
+        let read_u16 = |bytes: &[u8]| {
+            let bytes: [u8; 2] = bytes[..2].try_into().unwrap();
+            if big_endian {
+                u16::from_be_bytes(bytes)
+            } else {
+                u16::from_le_bytes(bytes)
+            }
+        };
+        let read_u32 = |bytes: &[u8]| {
+            let bytes: [u8; 4] = bytes[..4].try_into().unwrap();
+            if big_endian {
+                u32::from_be_bytes(bytes)
+            } else {
+                u32::from_le_bytes(bytes)
+            }
+        };
+
+        let block_type = read_u32(&header[..4]);
+        let length = read_u32(&header[4..8]) as usize;
+        if length < 12 || !length.is_multiple_of(4) {
+            bail!("bad pcapng block length {} at offset {}", length, offset);
+        }
+        let body = data
+            .get(offset + 8..offset + length - 4)
+            .with_context(|| format!("truncated pcapng block at offset {}", offset))?;
+        offset += length;
+
+        match block_type {
+            BLOCK_INTERFACE_DESCRIPTION => {
+                let body_start = body.get(8..).context("truncated interface block")?;
+                let mut interface = Interface {
+                    link_type: read_u16(body),
+                    resolution: 1e-6,
+                    speed: None,
+                };
+                for (code, value) in options(body_start, &read_u16) {
+                    match (code, value.len()) {
+                        (OPT_IF_TSRESOL, 1) => {
+                            let exponent = (value[0] & 0x7f) as i32;
+                            interface.resolution = if value[0] & 0x80 == 0 {
+                                10f64.powi(-exponent)
+                            } else {
+                                2f64.powi(-exponent)
+                            };
+                        }
+                        (OPT_IF_SPEED, 8) => {
+                            let bytes: [u8; 8] = value.try_into().unwrap();
+                            interface.speed = Some(if big_endian {
+                                u64::from_be_bytes(bytes)
+                            } else {
+                                u64::from_le_bytes(bytes)
+                            });
+                        }
+                        _ => {}
+                    }
+                }
+                interfaces.push(interface);
+            }
+            BLOCK_ENHANCED_PACKET => {
+                if body.len() < 20 {
+                    bail!("truncated enhanced packet block");
+                }
+                let interface = *interfaces
+                    .get(read_u32(body) as usize)
+                    .context("packet on undeclared interface")?;
+                let ticks = ((read_u32(&body[4..]) as u64) << 32) | read_u32(&body[8..]) as u64;
+                let timestamp = ticks as f64 * interface.resolution;
+                let captured = read_u32(&body[12..]) as usize;
+                let packet = body
+                    .get(20..20 + captured)
+                    .context("truncated packet data")?;
+
+                let mut flags = 0;
+                let mut comment = None;
+                let options_start = (20 + captured).next_multiple_of(4);
+                for (code, value) in
+                    options(body.get(options_start..).unwrap_or_default(), &read_u16)
+                {
+                    match (code, value.len()) {
+                        (OPT_EPB_FLAGS, 4) => flags = read_u32(value),
+                        (OPT_COMMENT, _) => {
+                            comment = Some(String::from_utf8_lossy(value).into_owned())
+                        }
+                        _ => {}
+                    }
+                }
+
+                match interface.link_type {
+                    LINKTYPE_USER0 => {
+                        let channel = if flags & EPB_DIRECTION_MASK == EPB_FLAG_INBOUND {
+                            Channel::RO
+                        } else {
+                            Channel::CI
+                        };
+                        let baud_rate = match interface.speed {
+                            Some(1_000_000) => BaudRate::Baud1M,
+                            _ => BaudRate::Baud115200,
+                        };
+                        let dissector = serial
+                            .entry((channel, baud_rate))
+                            .or_insert_with(|| LiveDissector::new(channel, baud_rate));
+                        events.extend(
+                            dissector
+                                .push(packet, timestamp)
+                                .into_iter()
+                                .map(OutputEvent::Serial),
+                        );
+                    }
+                    LINKTYPE_USER1 => {
+                        if let Some(op) = decode_i2c(packet, timestamp) {
+                            events.push(OutputEvent::I2c(dissect_i2c_operation_with_context(
+                                &op,
+                                &mut i2c_contexts,
+                            )));
+                        }
+                    }
+                    LINKTYPE_USER2 => {
+                        let next_id = stratum_sessions.len();
+                        let session_id =
+                            *stratum_sessions.entry(comment.clone()).or_insert(next_id);
+                        let mut dissected = stratum.process(session_id, timestamp, packet);
+                        if dissected.pool.is_none() {
+                            dissected.pool = comment;
+                        }
+                        events.push(OutputEvent::Stratum(dissected));
+                    }
+                    link_type => {
+                        if let Some(segment) =
+                            pcap::parse_packet(link_type as u32, packet, timestamp)
+                        {
+                            tcp_segments.push(segment);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    events.extend(
+        stratum::dissect_lines(&pcap::reassemble_lines(&tcp_segments))
+            .into_iter()
+            .map(OutputEvent::Stratum),
+    );
+    Ok(events)
+}
+
+/// Iterate over the (code, value) options in a block's option area.
+fn options<'a>(
+    mut area: &'a [u8],
+    read_u16: &'a impl Fn(&[u8]) -> u16,
+) -> impl Iterator<Item = (u16, &'a [u8])> + 'a {
+    std::iter::from_fn(move || {
+        if area.len() < 4 {
+            return None;
+        }
+        let code = read_u16(&area[..2]);
+        let len = read_u16(&area[2..4]) as usize;
+        if code == OPT_END {
+            return None;
+        }
+        let value = area.get(4..4 + len)?;
+        area = area
+            .get((4 + len).next_multiple_of(4)..)
+            .unwrap_or_default();
+        Some((code, value))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::OutputConfig;
+    use crate::pcap::tests::PcapBuilder;
+    use bytes::BytesMut;
+    use mujina_miner::asic::bm13xx::protocol::{Command, FrameCodec, RegisterAddress};
+    use std::net::Ipv4Addr;
+    use tokio_util::codec::Encoder;
+
+    fn format_all(events: &[OutputEvent]) -> Vec<String> {
+        let config = OutputConfig {
+            use_color: false,
+            ..OutputConfig::default()
+        };
+        events.iter().map(|e| e.format(&config)).collect()
+    }
+
+    fn sample_events() -> Vec<OutputEvent> {
+        let mut events = Vec::new();
+
+        let mut command = BytesMut::new();
+        FrameCodec::default()
+            .encode(
+                Command::ReadRegister {
+                    broadcast: true,
+                    chip_address: 0,
+                    register_address: RegisterAddress::ChipId,
+                },
+                &mut command,
+            )
+            .unwrap();
+        let mut ci = LiveDissector::new(Channel::CI, BaudRate::Baud115200);
+        events.extend(ci.push(&command, 1.25).into_iter().map(OutputEvent::Serial));
+
+        let response = [
+            0xaa, 0x55, 0x13, 0x70, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10,
+        ];
+        let mut ro = LiveDissector::new(Channel::RO, BaudRate::Baud1M);
+        events.extend(ro.push(&response, 1.5).into_iter().map(OutputEvent::Serial));
+
+        // TPS546 VOUT_MODE read, then READ_VOUT, which needs it to decode
+        let mut contexts = I2cContexts::default();
+        for (time, register, read) in [(2.0, 0x20, vec![0x17]), (2.5, 0x8b, vec![0x00, 0x02])] {
+            let op = I2cOperation {
+                start_time: time,
+                address: 0x24,
+                register: Some(register),
+                write_data: None,
+                read_data: Some(read),
+                was_naked: false,
+            };
+            events.push(OutputEvent::I2c(dissect_i2c_operation_with_context(
+                &op,
+                &mut contexts,
+            )));
+        }
+
+        let mut stratum = StratumDissector::new();
+        for (time, line) in [
+            (
+                3.0,
+                r#"{"id":1,"method":"mining.subscribe","params":["mujina"]}"#,
+            ),
+            (3.25, r#"{"id":1,"result":[[],"deadbeef",4],"error":null}"#),
+        ] {
+            let mut msg = stratum.process(0, time, line.as_bytes());
+            msg.pool = Some("10.0.0.1:3333".to_string());
+            events.push(OutputEvent::Stratum(msg));
+        }
+
+        events
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let events = sample_events();
+        assert_eq!(events.len(), 6);
+
+        let mut writer = PcapngWriter::new(Vec::new()).unwrap();
+        for event in &events {
+            writer.write_event(event).unwrap();
+        }
+        let file = writer.into_inner();
+
+        let read_back = parse_events(&file).unwrap();
+        assert_eq!(format_all(&read_back), format_all(&events));
+    }
+
+    #[test]
+    fn test_network_packets_in_pcapng() {
+        const MINER: (Ipv4Addr, u16) = (Ipv4Addr::new(192, 168, 1, 10), 50000);
+        const POOL: (Ipv4Addr, u16) = (Ipv4Addr::new(10, 0, 0, 1), 3333);
+
+        let request = b"{\"id\":1,\"method\":\"mining.subscribe\",\"params\":[]}\n";
+        let pcap = PcapBuilder::new()
+            .packet(1.0, MINER, POOL, 100, false, request)
+            .build();
+
+        // Re-wrap the pcap's single Ethernet frame as a pcapng packet
+        let frame = &pcap[24 + 16..];
+        let mut file = PcapngWriter::new(Vec::new()).unwrap().into_inner();
+        let mut idb = Vec::new();
+        idb.extend(1u16.to_le_bytes());
+        idb.extend([0; 6]);
+        file.extend(block(BLOCK_INTERFACE_DESCRIPTION, &idb));
+        let mut epb = Vec::new();
+        epb.extend(4u32.to_le_bytes());
+        epb.extend(0u32.to_le_bytes());
+        epb.extend(1_000_000u32.to_le_bytes());
+        epb.extend((frame.len() as u32).to_le_bytes());
+        epb.extend((frame.len() as u32).to_le_bytes());
+        epb.extend(frame);
+        pad(&mut epb);
+        file.extend(block(BLOCK_ENHANCED_PACKET, &epb));
+
+        let events = parse_events(&file).unwrap();
+        assert_eq!(events.len(), 1);
+        let OutputEvent::Stratum(msg) = &events[0] else {
+            panic!("expected a Stratum message");
+        };
+        assert_eq!(msg.direction, StratumDirection::MinerToPool);
+        assert_eq!(msg.pool.as_deref(), Some("10.0.0.1:3333"));
+        assert_eq!(msg.timestamp, 1.0);
+    }
+
+    #[test]
+    fn test_i2c_encoding() {
+        let op = I2cOperation {
+            start_time: 0.5,
+            address: 0x4c,
+            register: Some(0x00),
+            write_data: Some(vec![0xaa, 0x55]),
+            read_data: Some(vec![]),
+            was_naked: true,
+        };
+        let decoded = decode_i2c(&encode_i2c(&op), 0.5).unwrap();
+        assert_eq!(decoded.address, op.address);
+        assert_eq!(decoded.register, op.register);
+        assert_eq!(decoded.write_data, op.write_data);
+        assert_eq!(decoded.read_data, op.read_data);
+        assert!(decoded.was_naked);
+
+        assert!(decode_i2c(&[0x4c, I2C_HAS_WRITE, 0, 9, 0], 0.0).is_none());
+    }
+}
@@ -0,0 +1,625 @@
+//! Replay of a dissected capture against attached hardware.
+//!
+//! Bringing up a new board revision usually starts from a capture of working
+//! firmware initializing it. Replaying that capture's I2C operations and
+//! BM13xx command frames in order, with the captured spacing, and comparing
+//! what the hardware answers with what was captured shows step by step where
+//! the board in front of us differs from the one that was recorded.
+//!
+//! I2C operations go through a bitaxe-raw control port and command frames out
+//! the ASIC data port. Anything that happened off those lines, such as the
+//! ASIC reset pulse, has to be recreated separately; see
+//! [`Hardware::pulse_reset`].
+
+use crate::bm13xx::Direction;
+use crate::capture::{BaudRate, Channel};
+use crate::i2c::I2cOperation;
+use crate::live::LiveDissector;
+use crate::output::OutputEvent;
+use anyhow::{bail, Context, Result};
+use mujina_miner::hw_trait::gpio::{Gpio, GpioPin, PinValue};
+use mujina_miner::hw_trait::i2c::I2c;
+use mujina_miner::mgmt_protocol::bitaxe_raw::i2c::BitaxeRawI2c;
+use mujina_miner::mgmt_protocol::{BitaxeRawGpioController, ControlChannel};
+use serialport::SerialPort;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
+use tokio_serial::SerialPortBuilderExt;
+
+/// Longest captured pause reproduced between steps.
+///
+/// Captures often include long idle stretches (a human starting the miner, a
+/// regulator settling) that add nothing when replayed.
+const MAX_GAP: Duration = Duration::from_secs(1);
+
+/// Shortest time spent collecting responses to a command frame.
+const MIN_RESPONSE_WINDOW: Duration = Duration::from_millis(20);
+
+/// Data port read timeout while collecting responses.
+const READ_TIMEOUT: Duration = Duration::from_millis(5);
+
+/// How long the ASIC reset line is held low, matching the board driver.
+const RESET_PULSE: Duration = Duration::from_millis(100);
+
+/// One action from a capture to reproduce on hardware.
+#[derive(Debug, Clone)]
+pub enum Step {
+    /// A command frame sent to the ASICs, with the response frames captured
+    /// after it
+    Frame {
+        timestamp: f64,
+        baud_rate: BaudRate,
+        frame: Vec<u8>,
+        expected: Vec<Vec<u8>>,
+    },
+    /// An operation on the board's I2C bus
+    I2c(I2cOperation),
+}
+
+/// How a replayed step compared with the capture.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    /// Hardware answered as captured
+    Match,
+    /// Hardware answered differently
+    Mismatch { expected: String, actual: String },
+    /// The step couldn't be carried out
+    Failed(String),
+    /// No port was given for this kind of step
+    Skipped,
+}
+
+/// Tally of step outcomes over a replay.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Summary {
+    pub matched: usize,
+    pub mismatched: usize,
+    pub failed: usize,
+    pub skipped: usize,
+}
+
+/// Hardware a capture can be replayed against.
+pub trait Target {
+    /// Whether this target has the port `step` needs.
+    fn supports(&self, step: &Step) -> bool;
+
+    /// Send a command frame at `baud_rate` and return the response frames
+    /// received within `window`.
+    fn send_frame(
+        &mut self,
+        baud_rate: BaudRate,
+        frame: &[u8],
+        window: Duration,
+    ) -> Result<Vec<Vec<u8>>>;
+
+    /// Perform an I2C operation, returning the data read, if it reads.
+    fn i2c(&mut self, op: &I2cOperation) -> Result<Option<Vec<u8>>>;
+}
+
+/// Attached board reached through a bitaxe-raw control port and an ASIC data
+/// port, either of which may be absent.
+pub struct Hardware {
+    runtime: Runtime,
+    data: Option<(Box<dyn SerialPort>, BaudRate)>,
+    control: Option<ControlChannel>,
+}
+
+impl Step {
+    pub fn timestamp(&self) -> f64 {
+        match self {
+            Step::Frame { timestamp, .. } => *timestamp,
+            Step::I2c(op) => op.start_time,
+        }
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Step::Frame {
+                baud_rate, frame, ..
+            } => write!(f, "CI  {:>7} {}", baud_rate.bps(), hex::encode(frame)),
+            Step::I2c(op) => {
+                write!(f, "I2C 0x{:02x}", op.address)?;
+                if let Some(reg) = op.register {
+                    write!(f, " reg 0x{:02x}", reg)?;
+                }
+                if let Some(data) = &op.write_data {
+                    write!(f, " write {}", hex::encode(data))?;
+                }
+                if let Some(data) = &op.read_data {
+                    write!(f, " read {} bytes", data.len())?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Match => write!(f, "ok"),
+            Outcome::Mismatch { expected, actual } => {
+                write!(
+                    f,
+                    "MISMATCH\n    captured: {}\n    replayed: {}",
+                    expected, actual
+                )
+            }
+            Outcome::Failed(reason) => write!(f, "FAILED: {}", reason),
+            Outcome::Skipped => write!(f, "skipped"),
+        }
+    }
+}
+
+impl Summary {
+    fn record(&mut self, outcome: &Outcome) {
+        match outcome {
+            Outcome::Match => self.matched += 1,
+            Outcome::Mismatch { .. } => self.mismatched += 1,
+            Outcome::Failed(_) => self.failed += 1,
+            Outcome::Skipped => self.skipped += 1,
+        }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} matched, {} mismatched, {} failed, {} skipped",
+            self.matched, self.mismatched, self.failed, self.skipped
+        )
+    }
+}
+
+impl Hardware {
+    /// Open the given ports. The data port starts at 115200 baud, the rate
+    /// BM13xx chips come out of reset at.
+    pub fn open(data_port: Option<&str>, control_port: Option<&str>) -> Result<Self> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .context("Failed to start async runtime")?;
+
+        let data = match data_port {
+            Some(path) => {
+                let port = serialport::new(path, BaudRate::Baud115200.bps())
+                    .timeout(READ_TIMEOUT)
+                    .open()
+                    .with_context(|| format!("Failed to open data port {}", path))?;
+                Some((port, BaudRate::Baud115200))
+            }
+            None => None,
+        };
+
+        let control = match control_port {
+            Some(path) => {
+                // The async serial stream registers with the reactor on open
+                let _guard = runtime.enter();
+                let stream = tokio_serial::new(path, 115_200)
+                    .open_native_async()
+                    .with_context(|| format!("Failed to open control port {}", path))?;
+                Some(ControlChannel::new(stream))
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            runtime,
+            data,
+            control,
+        })
+    }
+
+    /// Pulse the active-low ASIC reset line on bitaxe-raw GPIO `pin`, leaving
+    /// the chips running.
+    pub fn pulse_reset(&mut self, pin: u8) -> Result<()> {
+        let Some(channel) = &self.control else {
+            bail!("resetting the ASICs needs a control port");
+        };
+
+        let mut gpio = BitaxeRawGpioController::new(channel.clone());
+        self.runtime.block_on(async {
+            let mut nrst = gpio.pin(pin).await?;
+            nrst.write(PinValue::Low).await?;
+            tokio::time::sleep(RESET_PULSE).await;
+            nrst.write(PinValue::High).await?;
+            tokio::time::sleep(RESET_PULSE).await;
+            Ok::<_, mujina_miner::hw_trait::HwError>(())
+        })?;
+        Ok(())
+    }
+}
+
+impl Target for Hardware {
+    fn supports(&self, step: &Step) -> bool {
+        match step {
+            Step::Frame { .. } => self.data.is_some(),
+            Step::I2c(_) => self.control.is_some(),
+        }
+    }
+
+    fn send_frame(
+        &mut self,
+        baud_rate: BaudRate,
+        frame: &[u8],
+        window: Duration,
+    ) -> Result<Vec<Vec<u8>>> {
+        let Some((port, current)) = &mut self.data else {
+            bail!("no data port");
+        };
+
+        // The capture switches rate when the host did, right after the
+        // command telling the chips to
+        if *current != baud_rate {
+            port.set_baud_rate(baud_rate.bps())
+                .context("Failed to change data port baud rate")?;
+            *current = baud_rate;
+        }
+
+        port.write_all(frame).context("Data port write failed")?;
+        port.flush().context("Data port write failed")?;
+
+        let mut dissector = LiveDissector::new(Channel::RO, baud_rate);
+        let mut responses = Vec::new();
+        let mut buf = [0u8; 256];
+        let deadline = Instant::now() + window;
+
+        while Instant::now() < deadline {
+            match port.read(&mut buf) {
+                Ok(n) => responses.extend(
+                    dissector
+                        .push(&buf[..n], 0.0)
+                        .into_iter()
+                        .map(|frame| frame.raw_data),
+                ),
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::TimedOut | io::ErrorKind::Interrupted
+                    ) => {}
+                Err(e) => return Err(e).context("Data port read failed"),
+            }
+        }
+
+        Ok(responses)
+    }
+
+    fn i2c(&mut self, op: &I2cOperation) -> Result<Option<Vec<u8>>> {
+        let Some(channel) = &self.control else {
+            bail!("no control port");
+        };
+
+        // The register byte was split off the front of the write when the
+        // capture was grouped; put it back
+        let write: Vec<u8> = op
+            .register
+            .into_iter()
+            .chain(op.write_data.iter().flatten().copied())
+            .collect();
+
+        let mut i2c = BitaxeRawI2c::new(channel.clone());
+        let result = self.runtime.block_on(async {
+            match &op.read_data {
+                Some(captured) => {
+                    let mut read = vec![0u8; captured.len()];
+                    if write.is_empty() {
+                        i2c.read(op.address, &mut read).await?;
+                    } else {
+                        i2c.write_read(op.address, &write, &mut read).await?;
+                    }
+                    Ok(Some(read))
+                }
+                None => i2c.write(op.address, &write).await.map(|()| None),
+            }
+        });
+
+        Ok(result?)
+    }
+}
+
+/// Turn dissected capture events into replay steps, in time order.
+///
+/// Each response frame is expected after the command frame most recently
+/// sent before it; responses captured before any command are dropped.
+/// Stratum traffic isn't replayed.
+pub fn plan(events: &[OutputEvent]) -> Vec<Step> {
+    let mut events: Vec<&OutputEvent> = events.iter().collect();
+    events.sort_by(|a, b| a.timestamp().total_cmp(&b.timestamp()));
+
+    let mut steps = Vec::new();
+    for event in events {
+        match event {
+            OutputEvent::Serial(frame) => match frame.direction {
+                Direction::HostToChip => steps.push(Step::Frame {
+                    timestamp: frame.timestamp,
+                    baud_rate: frame.baud_rate,
+                    frame: frame.raw_data.clone(),
+                    expected: Vec::new(),
+                }),
+                Direction::ChipToHost => {
+                    let last_command = steps
+                        .iter_mut()
+                        .rev()
+                        .find(|step| matches!(step, Step::Frame { .. }));
+                    if let Some(Step::Frame { expected, .. }) = last_command {
+                        expected.push(frame.raw_data.clone());
+                    }
+                }
+            },
+            OutputEvent::I2c(op) => steps.push(Step::I2c(op.source.clone())),
+            OutputEvent::Stratum(_) => {}
+        }
+    }
+
+    steps
+}
+
+/// Replay `steps` against `target`, calling `on_result` after each.
+///
+/// Steps are spaced as they were in the capture, up to [`MAX_GAP`]. The time
+/// before the next step is also how long responses to a command frame are
+/// collected, so chips that answered slowly in the capture get as long now.
+pub fn replay(
+    steps: &[Step],
+    target: &mut impl Target,
+    mut on_result: impl FnMut(&Step, &Outcome) -> Result<()>,
+) -> Result<Summary> {
+    let mut summary = Summary::default();
+
+    for (i, step) in steps.iter().enumerate() {
+        let gap = steps
+            .get(i + 1)
+            .map(|next| Duration::from_secs_f64((next.timestamp() - step.timestamp()).max(0.0)))
+            .unwrap_or(MIN_RESPONSE_WINDOW)
+            .min(MAX_GAP);
+
+        let outcome = if !target.supports(step) {
+            Outcome::Skipped
+        } else {
+            match step {
+                Step::Frame {
+                    baud_rate,
+                    frame,
+                    expected,
+                    ..
+                } => match target.send_frame(*baud_rate, frame, gap.max(MIN_RESPONSE_WINDOW)) {
+                    Ok(actual) => compare_frames(expected, &actual),
+                    Err(e) => Outcome::Failed(format!("{:#}", e)),
+                },
+                Step::I2c(op) => {
+                    let outcome = compare_i2c(op, target.i2c(op));
+                    std::thread::sleep(gap);
+                    outcome
+                }
+            }
+        };
+
+        summary.record(&outcome);
+        on_result(step, &outcome)?;
+    }
+
+    Ok(summary)
+}
+
+fn compare_frames(expected: &[Vec<u8>], actual: &[Vec<u8>]) -> Outcome {
+    if expected == actual {
+        Outcome::Match
+    } else {
+        Outcome::Mismatch {
+            expected: format_frames(expected),
+            actual: format_frames(actual),
+        }
+    }
+}
+
+fn format_frames(frames: &[Vec<u8>]) -> String {
+    if frames.is_empty() {
+        "(no response)".to_string()
+    } else {
+        frames.iter().map(hex::encode).collect::<Vec<_>>().join(" ")
+    }
+}
+
+/// Compare an I2C result with the capture. A NAK in the capture is
+/// reproduced if the replayed operation fails too.
+fn compare_i2c(op: &I2cOperation, result: Result<Option<Vec<u8>>>) -> Outcome {
+    let expected = || match &op.read_data {
+        Some(data) => hex::encode(data),
+        None => "ACK".to_string(),
+    };
+
+    match (op.was_naked, result) {
+        (true, Err(_)) => Outcome::Match,
+        (true, Ok(_)) => Outcome::Mismatch {
+            expected: "NAK".to_string(),
+            actual: "ACK".to_string(),
+        },
+        (false, Err(e)) => Outcome::Mismatch {
+            expected: expected(),
+            actual: format!("error: {:#}", e),
+        },
+        (false, Ok(read)) if read == op.read_data => Outcome::Match,
+        (false, Ok(read)) => Outcome::Mismatch {
+            expected: expected(),
+            actual: read.map(hex::encode).unwrap_or_else(|| "ACK".to_string()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dissect::{CrcStatus, DissectedFrame, FrameContent};
+
+    const READ_CHIP_ID: [u8; 7] = [0x55, 0xaa, 0x52, 0x05, 0x00, 0x00, 0x0a];
+    const CHIP_ID: [u8; 11] = [
+        0xaa, 0x55, 0x13, 0x70, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10,
+    ];
+
+    /// Target that answers from canned data and records what it was sent.
+    #[derive(Default)]
+    struct MockTarget {
+        responses: Vec<Vec<u8>>,
+        i2c_reads: Vec<u8>,
+        sent: Vec<(BaudRate, Vec<u8>)>,
+        no_i2c: bool,
+    }
+
+    impl Target for MockTarget {
+        fn supports(&self, step: &Step) -> bool {
+            !(self.no_i2c && matches!(step, Step::I2c(_)))
+        }
+
+        fn send_frame(
+            &mut self,
+            baud_rate: BaudRate,
+            frame: &[u8],
+            _window: Duration,
+        ) -> Result<Vec<Vec<u8>>> {
+            self.sent.push((baud_rate, frame.to_vec()));
+            Ok(self.responses.clone())
+        }
+
+        fn i2c(&mut self, op: &I2cOperation) -> Result<Option<Vec<u8>>> {
+            Ok(op.read_data.as_ref().map(|_| self.i2c_reads.clone()))
+        }
+    }
+
+    fn frame(timestamp: f64, direction: Direction, raw: &[u8]) -> OutputEvent {
+        OutputEvent::Serial(DissectedFrame {
+            timestamp,
+            direction,
+            baud_rate: BaudRate::Baud115200,
+            raw_data: raw.to_vec(),
+            content: FrameContent::Command(String::new()),
+            crc_status: CrcStatus::Valid,
+        })
+    }
+
+    fn i2c_read(timestamp: f64, data: &[u8]) -> I2cOperation {
+        I2cOperation {
+            start_time: timestamp,
+            address: 0x24,
+            register: Some(0x8b),
+            write_data: None,
+            read_data: Some(data.to_vec()),
+            was_naked: false,
+        }
+    }
+
+    fn i2c_event(op: I2cOperation) -> OutputEvent {
+        let mut contexts = crate::dissect::I2cContexts::default();
+        OutputEvent::I2c(crate::dissect::dissect_i2c_operation_with_context(
+            &op,
+            &mut contexts,
+        ))
+    }
+
+    #[test]
+    fn test_plan_pairs_responses_with_commands() {
+        // Out of order on purpose: plan sorts by time
+        let events = vec![
+            frame(0.002, Direction::ChipToHost, &CHIP_ID),
+            i2c_event(i2c_read(0.003, &[0x00, 0x03])),
+            frame(0.001, Direction::HostToChip, &READ_CHIP_ID),
+            frame(0.004, Direction::ChipToHost, &CHIP_ID),
+            frame(0.0, Direction::ChipToHost, &CHIP_ID),
+        ];
+
+        let steps = plan(&events);
+        assert_eq!(steps.len(), 2);
+        match &steps[0] {
+            Step::Frame {
+                frame, expected, ..
+            } => {
+                assert_eq!(frame, &READ_CHIP_ID);
+                // The response before any command is dropped; the one after
+                // the I2C read still belongs to the command
+                assert_eq!(expected, &vec![CHIP_ID.to_vec(), CHIP_ID.to_vec()]);
+            }
+            other => panic!("expected a frame step, got {:?}", other),
+        }
+        assert!(matches!(&steps[1], Step::I2c(op) if op.address == 0x24));
+    }
+
+    #[test]
+    fn test_replay_diffs_against_capture() {
+        let steps = vec![
+            Step::Frame {
+                timestamp: 0.0,
+                baud_rate: BaudRate::Baud115200,
+                frame: READ_CHIP_ID.to_vec(),
+                expected: vec![CHIP_ID.to_vec()],
+            },
+            Step::I2c(i2c_read(0.001, &[0x00, 0x03])),
+            Step::I2c(I2cOperation {
+                was_naked: true,
+                ..i2c_read(0.002, &[])
+            }),
+        ];
+        let mut target = MockTarget {
+            responses: vec![CHIP_ID.to_vec()],
+            i2c_reads: vec![0x00, 0x04],
+            ..Default::default()
+        };
+
+        let mut outcomes = Vec::new();
+        let summary = replay(&steps, &mut target, |_, outcome| {
+            outcomes.push(outcome.clone());
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(outcomes[0], Outcome::Match);
+        assert_eq!(
+            outcomes[1],
+            Outcome::Mismatch {
+                expected: "0003".to_string(),
+                actual: "0004".to_string(),
+            }
+        );
+        // The captured NAK wasn't reproduced
+        assert!(matches!(outcomes[2], Outcome::Mismatch { .. }));
+        assert_eq!(
+            summary,
+            Summary {
+                matched: 1,
+                mismatched: 2,
+                failed: 0,
+                skipped: 0,
+            }
+        );
+        assert_eq!(
+            target.sent,
+            vec![(BaudRate::Baud115200, READ_CHIP_ID.to_vec())]
+        );
+    }
+
+    #[test]
+    fn test_replay_skips_steps_without_a_port() {
+        let steps = vec![
+            Step::I2c(i2c_read(0.0, &[0x00])),
+            Step::Frame {
+                timestamp: 0.001,
+                baud_rate: BaudRate::Baud1M,
+                frame: READ_CHIP_ID.to_vec(),
+                expected: vec![CHIP_ID.to_vec()],
+            },
+        ];
+        let mut target = MockTarget {
+            no_i2c: true,
+            ..Default::default()
+        };
+
+        let summary = replay(&steps, &mut target, |_, _| Ok(())).unwrap();
+        assert_eq!(summary.skipped, 1);
+        // A chip that stays silent is a mismatch, not a failure
+        assert_eq!(summary.mismatched, 1);
+        assert_eq!(target.sent[0].0, BaudRate::Baud1M);
+    }
+}
@@ -0,0 +1,296 @@
+//! Saleae Logic 2 binary export parsing and UART decoding.
+//!
+//! Logic 2's "Export Raw Data > Binary" writes one file per digital channel
+//! (`digital_0.bin`, ...) listing the line's transition times. Unlike the CSV
+//! export this needs no async serial analyzer set up in Logic first: the
+//! dissector decodes UART itself, at both BM13xx link rates, and feeds the
+//! bytes to the same parsers the CSV path uses.
+//!
+//! Session files (`.sal`) are compressed archives in an undocumented format
+//! and aren't read; they're recognized so the user can be told how to export.
+
+use crate::capture::{BaudRate, CaptureEvent, Channel, SerialEvent};
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+
+/// File identifier at the start of every binary export.
+const SALEAE_MAGIC: &[u8; 8] = b"<SALEAE>";
+
+/// Zip local file header signature, the first bytes of a `.sal` session.
+const ZIP_MAGIC: &[u8; 4] = b"PK\x03\x04";
+
+/// Export type field for digital channels (analog is 1).
+const TYPE_DIGITAL: i32 = 0;
+
+/// Size of the digital export header, up to the transition count.
+const DIGITAL_HEADER_LEN: usize = 44;
+
+/// Transitions recorded on one digital channel.
+#[derive(Debug, Clone)]
+pub struct DigitalChannel {
+    pub initial_high: bool,
+    pub begin_time: f64,
+    pub end_time: f64,
+    pub transitions: Vec<f64>,
+}
+
+/// A byte decoded from a UART line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UartByte {
+    /// Time of the start bit's falling edge
+    pub timestamp: f64,
+    pub data: u8,
+    /// Stop bit was low
+    pub framing_error: bool,
+}
+
+/// Walks a channel's transitions forward in time, tracking the line level.
+struct LevelCursor<'a> {
+    transitions: &'a [f64],
+    next: usize,
+    high: bool,
+}
+
+impl<'a> LevelCursor<'a> {
+    fn new(channel: &'a DigitalChannel) -> Self {
+        Self {
+            transitions: &channel.transitions,
+            next: 0,
+            high: channel.initial_high,
+        }
+    }
+
+    /// Line level at `time`, which must not precede earlier queries.
+    fn level_at(&mut self, time: f64) -> bool {
+        while self.transitions.get(self.next).is_some_and(|&t| t <= time) {
+            self.high = !self.high;
+            self.next += 1;
+        }
+        self.high
+    }
+
+    /// Time of the next high-to-low transition.
+    fn next_falling_edge(&mut self) -> Option<f64> {
+        while let Some(&time) = self.transitions.get(self.next) {
+            self.high = !self.high;
+            self.next += 1;
+            if !self.high {
+                return Some(time);
+            }
+        }
+        None
+    }
+}
+
+impl DigitalChannel {
+    /// Parse a Logic 2 binary digital export.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < DIGITAL_HEADER_LEN || &data[..8] != SALEAE_MAGIC {
+            bail!("not a Saleae binary export");
+        }
+
+        let read_i32 = |at: usize| i32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+        let read_f64 = |at: usize| f64::from_le_bytes(data[at..at + 8].try_into().unwrap());
+
+        let version = read_i32(8);
+        if !(0..=1).contains(&version) {
+            bail!("unsupported Saleae binary export version {}", version);
+        }
+        if read_i32(12) != TYPE_DIGITAL {
+            bail!("Saleae export is not a digital channel");
+        }
+
+        let initial_high = read_i32(16) != 0;
+        let begin_time = read_f64(20);
+        let end_time = read_f64(28);
+        let count = u64::from_le_bytes(data[36..44].try_into().unwrap()) as usize;
+
+        let body = &data[DIGITAL_HEADER_LEN..];
+        if body.len() / 8 < count {
+            bail!(
+                "truncated Saleae export: {} transitions declared, {} present",
+                count,
+                body.len() / 8
+            );
+        }
+        let transitions = body
+            .chunks_exact(8)
+            .take(count)
+            .map(|chunk| f64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+
+        Ok(Self {
+            initial_high,
+            begin_time,
+            end_time,
+            transitions,
+        })
+    }
+
+    /// Decode the line as 8N1 UART at `bps`, idle high.
+    ///
+    /// Each bit is sampled at its midpoint, timed from the start bit's
+    /// falling edge. A byte whose stop bit falls past the end of the capture
+    /// is dropped.
+    pub fn decode_uart(&self, bps: u32) -> Vec<UartByte> {
+        let bit = 1.0 / bps as f64;
+        let mut cursor = LevelCursor::new(self);
+        let mut bytes = Vec::new();
+
+        // The capture may start mid-byte; only trust edges from an idle line
+        if !cursor.level_at(self.begin_time) {
+            cursor.next_falling_edge();
+        }
+
+        while let Some(start) = cursor.next_falling_edge() {
+            let stop_sample = start + 9.5 * bit;
+            if stop_sample > self.end_time {
+                break;
+            }
+
+            let mut data = 0u8;
+            for i in 0..8 {
+                if cursor.level_at(start + (1.5 + i as f64) * bit) {
+                    data |= 1 << i;
+                }
+            }
+            let framing_error = !cursor.level_at(stop_sample);
+
+            bytes.push(UartByte {
+                timestamp: start,
+                data,
+                framing_error,
+            });
+        }
+
+        bytes
+    }
+}
+
+/// Check whether a file is a Logic 2 binary export.
+pub fn is_binary_export(path: &Path) -> bool {
+    starts_with(path, SALEAE_MAGIC)
+}
+
+/// Check whether a file is a Logic 2 session archive.
+pub fn is_session(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "sal") && starts_with(path, ZIP_MAGIC)
+}
+
+fn starts_with(path: &Path, magic: &[u8]) -> bool {
+    use std::io::Read;
+    let mut head = vec![0u8; magic.len()];
+    std::fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut head))
+        .is_ok_and(|()| head == magic)
+}
+
+/// Read a binary export of one serial line as capture events.
+///
+/// The line is decoded at both BM13xx baud rates, like the pair of async
+/// serial analyzers the CSV workflow sets up per channel, so a capture that
+/// spans the switch to the fast rate decodes on both sides of it. Events are
+/// returned in time order.
+pub fn read_serial_events(path: &Path, channel: Channel) -> Result<Vec<CaptureEvent>> {
+    let data =
+        std::fs::read(path).with_context(|| format!("reading Saleae export {}", path.display()))?;
+    let line = DigitalChannel::parse(&data)
+        .with_context(|| format!("parsing Saleae export {}", path.display()))?;
+
+    let mut events: Vec<CaptureEvent> = [
+        (BaudRate::Baud115200, 115_200),
+        (BaudRate::Baud1M, 1_000_000),
+    ]
+    .into_iter()
+    .flat_map(|(baud_rate, bps)| {
+        line.decode_uart(bps).into_iter().map(move |byte| {
+            CaptureEvent::Serial(SerialEvent {
+                channel,
+                baud_rate,
+                timestamp: byte.timestamp,
+                data: byte.data,
+                error: byte.framing_error.then(|| "framing".to_string()),
+            })
+        })
+    })
+    .collect();
+
+    events.sort_by(|a, b| event_time(a).total_cmp(&event_time(b)));
+    Ok(events)
+}
+
+fn event_time(event: &CaptureEvent) -> f64 {
+    match event {
+        CaptureEvent::Serial(serial) => serial.timestamp,
+        CaptureEvent::I2c(i2c) => i2c.timestamp,
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Build a binary export of a UART line sending `bytes` back to back.
+    pub(crate) fn uart_export(bytes: &[u8], bps: u32, start: f64) -> Vec<u8> {
+        let bit = 1.0 / bps as f64;
+
+        // Levels for each bit period, then collapse runs into transitions
+        let mut levels = Vec::new();
+        for &byte in bytes {
+            levels.push(false);
+            levels.extend((0..8).map(|i| byte & (1 << i) != 0));
+            levels.push(true);
+        }
+        let mut transitions = Vec::new();
+        let mut high = true;
+        for (i, &level) in levels.iter().enumerate() {
+            if level != high {
+                transitions.push(start + i as f64 * bit);
+                high = level;
+            }
+        }
+        let end_time = start + (levels.len() + 2) as f64 * bit;
+
+        let mut data = SALEAE_MAGIC.to_vec();
+        data.extend(1i32.to_le_bytes());
+        data.extend(TYPE_DIGITAL.to_le_bytes());
+        data.extend(1u32.to_le_bytes());
+        data.extend(0f64.to_le_bytes());
+        data.extend(end_time.to_le_bytes());
+        data.extend((transitions.len() as u64).to_le_bytes());
+        for t in transitions {
+            data.extend(t.to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn test_decode_uart() {
+        let sent = [0x55, 0xaa, 0x00, 0xff, 0x13, 0x70];
+        let line = DigitalChannel::parse(&uart_export(&sent, 115_200, 0.001)).unwrap();
+        assert!(line.initial_high);
+
+        let decoded = line.decode_uart(115_200);
+        let data: Vec<u8> = decoded.iter().map(|b| b.data).collect();
+        assert_eq!(data, sent);
+        assert!(decoded.iter().all(|b| !b.framing_error));
+        assert!((decoded[0].timestamp - 0.001).abs() < 1e-9);
+
+        // Sampled at the wrong rate, the same line is garbage
+        let wrong: Vec<u8> = line.decode_uart(1_000_000).iter().map(|b| b.data).collect();
+        assert_ne!(wrong, sent);
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_exports() {
+        assert!(DigitalChannel::parse(b"PK\x03\x04 not an export").is_err());
+
+        let mut export = uart_export(&[0x55], 115_200, 0.0);
+        export[12] = 1; // analog
+        assert!(DigitalChannel::parse(&export).is_err());
+
+        let mut export = uart_export(&[0x55], 115_200, 0.0);
+        export.truncate(export.len() - 4);
+        assert!(DigitalChannel::parse(&export).is_err());
+    }
+}
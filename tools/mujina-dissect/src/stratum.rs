@@ -0,0 +1,665 @@
+//! Stratum v1 session dissector.
+//!
+//! Decodes JSON-RPC lines captured between a miner and a pool, either from a
+//! pcap file (via [`crate::pcap`]) or from a plain transcript of
+//! newline-delimited JSON. Requests are paired with their responses by ID,
+//! mining.notify and mining.submit are parsed with the miner's own
+//! `JobNotification` and `SubmitParams`, and each submitted nonce is
+//! correlated with the job it claims to solve: the job's age at submission
+//! and, when the session's extranonce1 is known, the difficulty the share
+//! actually achieves.
+
+use crate::pcap::{self, Endpoint, StreamLine};
+use anyhow::{Context, Result};
+use bitcoin::block::{Header, Version};
+use mujina_miner::job_source::{Extranonce2, Extranonce2Range, MerkleRootTemplate};
+use mujina_miner::stratum_v1::{JobNotification, SubmitParams};
+use mujina_miner::types::difficulty_from_hash;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Methods the pool sends; every other method comes from the miner.
+const POOL_METHODS: &[&str] = &[
+    "mining.notify",
+    "mining.set_difficulty",
+    "mining.set_version_mask",
+    "mining.set_extranonce",
+    "client.reconnect",
+    "client.show_message",
+    "client.get_version",
+];
+
+/// Version rolling mask assumed when a submit has version bits but the
+/// capture missed the mining.configure exchange (BIP 310's default).
+const DEFAULT_VERSION_MASK: u32 = 0x1fff_e000;
+
+/// Which way a message travelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StratumDirection {
+    MinerToPool,
+    PoolToMiner,
+    /// A response whose request wasn't captured
+    Unknown,
+}
+
+impl StratumDirection {
+    fn reverse(self) -> Self {
+        match self {
+            StratumDirection::MinerToPool => StratumDirection::PoolToMiner,
+            StratumDirection::PoolToMiner => StratumDirection::MinerToPool,
+            StratumDirection::Unknown => StratumDirection::Unknown,
+        }
+    }
+}
+
+/// Dissected Stratum message
+#[derive(Debug)]
+pub struct DissectedStratum {
+    pub timestamp: f64,
+    pub direction: StratumDirection,
+    /// Pool endpoint, when known from the capture
+    pub pool: Option<String>,
+    pub summary: String,
+    pub raw_data: Vec<u8>,
+}
+
+/// A request awaiting its response.
+#[derive(Debug)]
+struct PendingRequest {
+    method: String,
+    timestamp: f64,
+    direction: StratumDirection,
+    submit: Option<SubmitParams>,
+}
+
+/// Per-connection protocol state.
+#[derive(Debug, Default)]
+struct Session {
+    /// Whether timestamps are real capture times
+    timed: bool,
+    pool: Option<String>,
+    extranonce1: Option<Vec<u8>>,
+    version_mask: Option<u32>,
+    difficulty: Option<f64>,
+    jobs: HashMap<String, (JobNotification, f64)>,
+    pending: HashMap<String, PendingRequest>,
+}
+
+/// Stateful dissector for one or more Stratum sessions.
+#[derive(Debug)]
+pub struct StratumDissector {
+    sessions: HashMap<usize, Session>,
+    timed: bool,
+}
+
+impl StratumDissector {
+    pub fn new() -> Self {
+        Self {
+            sessions: HashMap::new(),
+            timed: true,
+        }
+    }
+
+    /// Dissector for input without capture times, such as a transcript.
+    /// Response latencies and job ages are omitted.
+    pub fn untimed() -> Self {
+        Self {
+            sessions: HashMap::new(),
+            timed: false,
+        }
+    }
+
+    /// Dissect one line from a session.
+    pub fn process(&mut self, session_id: usize, timestamp: f64, line: &[u8]) -> DissectedStratum {
+        let timed = self.timed;
+        let session = self.sessions.entry(session_id).or_insert_with(|| Session {
+            timed,
+            ..Default::default()
+        });
+
+        let (direction, summary) = match serde_json::from_slice::<Value>(line) {
+            Ok(message) => session.dissect(timestamp, &message),
+            Err(e) => (
+                StratumDirection::Unknown,
+                format!("unparsed line ({}): {}", e, String::from_utf8_lossy(line)),
+            ),
+        };
+
+        DissectedStratum {
+            timestamp,
+            direction,
+            pool: session.pool.clone(),
+            summary,
+            raw_data: line.to_vec(),
+        }
+    }
+
+    /// Record which endpoint of a session is the pool.
+    fn set_pool(&mut self, session_id: usize, pool: Endpoint) {
+        if let Some(session) = self.sessions.get_mut(&session_id) {
+            session.pool = Some(pool.to_string());
+        }
+    }
+}
+
+impl Default for StratumDissector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Session {
+    fn dissect(&mut self, timestamp: f64, message: &Value) -> (StratumDirection, String) {
+        let id = message.get("id").filter(|id| !id.is_null());
+        let params = message.get("params").and_then(Value::as_array);
+
+        if let Some(method) = message.get("method").and_then(Value::as_str) {
+            let direction = if POOL_METHODS.contains(&method) {
+                StratumDirection::PoolToMiner
+            } else {
+                StratumDirection::MinerToPool
+            };
+            let params = params.map(Vec::as_slice).unwrap_or_default();
+            let (summary, submit) = self.dissect_request(timestamp, method, params);
+
+            if let Some(id) = id {
+                self.pending.insert(
+                    id.to_string(),
+                    PendingRequest {
+                        method: method.to_string(),
+                        timestamp,
+                        direction,
+                        submit,
+                    },
+                );
+            }
+            return (direction, summary);
+        }
+
+        let Some(id) = id else {
+            return (
+                StratumDirection::Unknown,
+                format!("unknown message: {}", message),
+            );
+        };
+        let Some(request) = self.pending.remove(&id.to_string()) else {
+            return (
+                StratumDirection::Unknown,
+                format!("response to unknown request {}: {}", id, compact(message)),
+            );
+        };
+
+        let summary = self.dissect_response(timestamp, &request, message);
+        (request.direction.reverse(), summary)
+    }
+
+    fn dissect_request(
+        &mut self,
+        timestamp: f64,
+        method: &str,
+        params: &[Value],
+    ) -> (String, Option<SubmitParams>) {
+        match method {
+            "mining.notify" => match JobNotification::from_stratum_params(params) {
+                Ok(job) => {
+                    let summary = format!(
+                        "mining.notify job={} prevhash={} ntime={:#010x} nbits={:#010x} \
+                         version={:#010x} branches={}{}",
+                        job.job_id,
+                        job.prev_hash,
+                        job.ntime,
+                        job.nbits.to_consensus(),
+                        job.version.to_consensus(),
+                        job.merkle_branches.len(),
+                        if job.clean_jobs { " clean" } else { "" }
+                    );
+                    if job.clean_jobs {
+                        self.jobs.clear();
+                    }
+                    self.jobs.insert(job.job_id.clone(), (job, timestamp));
+                    (summary, None)
+                }
+                Err(e) => (format!("mining.notify (malformed: {})", e), None),
+            },
+
+            "mining.submit" => match SubmitParams::from_stratum_params(params) {
+                Ok(submit) => (self.describe_submit(timestamp, &submit), Some(submit)),
+                Err(e) => (format!("mining.submit (malformed: {})", e), None),
+            },
+
+            "mining.set_difficulty" => match params.first().and_then(Value::as_f64) {
+                Some(difficulty) => {
+                    self.difficulty = Some(difficulty);
+                    (format!("mining.set_difficulty {}", difficulty), None)
+                }
+                None => (
+                    format!("mining.set_difficulty (malformed: {:?})", params),
+                    None,
+                ),
+            },
+
+            "mining.set_version_mask" => match params.first().and_then(parse_hex_u32) {
+                Some(mask) => {
+                    self.version_mask = Some(mask);
+                    (format!("mining.set_version_mask {:#010x}", mask), None)
+                }
+                None => (
+                    format!("mining.set_version_mask (malformed: {:?})", params),
+                    None,
+                ),
+            },
+
+            _ => (
+                format!("{} {}", method, compact(&Value::from(params))),
+                None,
+            ),
+        }
+    }
+
+    fn dissect_response(
+        &mut self,
+        timestamp: f64,
+        request: &PendingRequest,
+        message: &Value,
+    ) -> String {
+        let latency_ms = (timestamp - request.timestamp) * 1000.0;
+        let result = message.get("result").unwrap_or(&Value::Null);
+        let error = message.get("error").filter(|e| !e.is_null());
+
+        let outcome = if let Some(error) = error {
+            format!("error {}", compact(error))
+        } else {
+            match request.method.as_str() {
+                "mining.subscribe" => self.record_subscribe(result),
+                "mining.configure" => self.record_configure(result),
+                "mining.submit" | "mining.authorize" => match result.as_bool() {
+                    Some(true) if request.method == "mining.submit" => "accepted".to_string(),
+                    Some(true) => "authorized".to_string(),
+                    Some(false) if request.method == "mining.submit" => "rejected".to_string(),
+                    Some(false) => "not authorized".to_string(),
+                    None => compact(result),
+                },
+                _ => compact(result),
+            }
+        };
+
+        let subject = match &request.submit {
+            Some(submit) => format!(
+                "mining.submit job={} nonce={:#010x}",
+                submit.job_id, submit.nonce
+            ),
+            None => request.method.clone(),
+        };
+        if self.timed {
+            format!("{} -> {} ({:.1} ms)", subject, outcome, latency_ms)
+        } else {
+            format!("{} -> {}", subject, outcome)
+        }
+    }
+
+    fn record_subscribe(&mut self, result: &Value) -> String {
+        let extranonce1 = result
+            .get(1)
+            .and_then(Value::as_str)
+            .and_then(|s| hex::decode(s).ok());
+        let extranonce2_size = result.get(2).and_then(Value::as_u64);
+
+        match (extranonce1, extranonce2_size) {
+            (Some(extranonce1), Some(size)) => {
+                let summary = format!(
+                    "subscribed extranonce1={} extranonce2_size={}",
+                    hex::encode(&extranonce1),
+                    size
+                );
+                self.extranonce1 = Some(extranonce1);
+                summary
+            }
+            _ => compact(result),
+        }
+    }
+
+    fn record_configure(&mut self, result: &Value) -> String {
+        if result.get("version-rolling").and_then(Value::as_bool) == Some(true) {
+            let mask = result.get("version-rolling.mask").and_then(parse_hex_u32);
+            self.version_mask = mask;
+            match mask {
+                Some(mask) => format!("version rolling mask={:#010x}", mask),
+                None => "version rolling (no mask)".to_string(),
+            }
+        } else {
+            compact(result)
+        }
+    }
+
+    /// Describe a submit and correlate it with the job it refers to.
+    fn describe_submit(&self, timestamp: f64, submit: &SubmitParams) -> String {
+        let mut summary = format!(
+            "mining.submit job={} nonce={:#010x} ntime={:#010x} extranonce2={}",
+            submit.job_id,
+            submit.nonce,
+            submit.ntime,
+            hex::encode(&submit.extranonce2)
+        );
+        if let Some(bits) = submit.version_bits {
+            summary.push_str(&format!(" version_bits={:#010x}", bits));
+        }
+
+        let Some((job, job_time)) = self.jobs.get(&submit.job_id) else {
+            summary.push_str(" [unknown job]");
+            return summary;
+        };
+
+        if self.timed {
+            summary.push_str(&format!(" job_age={:.3}s", timestamp - job_time));
+        }
+        if let Some(extranonce1) = &self.extranonce1 {
+            match share_difficulty(job, extranonce1, submit, self.version_mask) {
+                Some(difficulty) => {
+                    summary.push_str(&format!(" share_diff={:.1}", difficulty));
+                    if let Some(pool_difficulty) = self.difficulty {
+                        if difficulty < pool_difficulty {
+                            summary.push_str(&format!(" [below pool diff {}]", pool_difficulty));
+                        }
+                    }
+                }
+                None => summary.push_str(" [can't rebuild header]"),
+            }
+        }
+
+        summary
+    }
+}
+
+/// Rebuild the header a submit describes and return the difficulty it meets.
+fn share_difficulty(
+    job: &JobNotification,
+    extranonce1: &[u8],
+    submit: &SubmitParams,
+    version_mask: Option<u32>,
+) -> Option<f64> {
+    let size = submit.extranonce2.len();
+    let mut value = [0u8; 8];
+    value.get_mut(..size)?.copy_from_slice(&submit.extranonce2);
+    let extranonce2 = Extranonce2::new(u64::from_le_bytes(value), size as u8).ok()?;
+
+    let template = MerkleRootTemplate::new(
+        job.coinbase1.clone(),
+        extranonce1.to_vec(),
+        Extranonce2Range::new(size as u8).ok()?,
+        job.coinbase2.clone(),
+        job.merkle_branches.clone(),
+    );
+    let merkle_root = template.compute_merkle_root(&extranonce2).ok()?;
+
+    let base = job.version.to_consensus() as u32;
+    let version = match submit.version_bits {
+        Some(bits) => {
+            let mask = version_mask.unwrap_or(DEFAULT_VERSION_MASK);
+            (base & !mask) | (bits & mask)
+        }
+        None => base,
+    };
+
+    let header = Header {
+        version: Version::from_consensus(version as i32),
+        prev_blockhash: job.prev_hash,
+        merkle_root,
+        time: submit.ntime,
+        bits: job.nbits,
+        nonce: submit.nonce,
+    };
+    Some(difficulty_from_hash(&header.block_hash()))
+}
+
+fn parse_hex_u32(value: &Value) -> Option<u32> {
+    u32::from_str_radix(value.as_str()?, 16).ok()
+}
+
+/// Single-line JSON, truncated so huge results don't swamp the output.
+fn compact(value: &Value) -> String {
+    const MAX_LEN: usize = 200;
+    let text = value.to_string();
+    if text.len() > MAX_LEN {
+        let cut = (0..=MAX_LEN)
+            .rev()
+            .find(|&i| text.is_char_boundary(i))
+            .unwrap_or(0);
+        format!("{}...", &text[..cut])
+    } else {
+        text
+    }
+}
+
+/// Dissect the Stratum sessions in a pcap file.
+///
+/// Each TCP connection is its own session. Once a message's direction is
+/// known, the connection's pool endpoint is labelled on every message.
+pub fn dissect_pcap(path: &Path) -> Result<Vec<DissectedStratum>> {
+    let segments = pcap::read_tcp_segments(path)?;
+    Ok(dissect_lines(&pcap::reassemble_lines(&segments)))
+}
+
+/// Dissect reassembled stream lines, one session per TCP connection.
+pub fn dissect_lines(lines: &[StreamLine]) -> Vec<DissectedStratum> {
+    let mut dissector = StratumDissector::new();
+    let mut sessions: HashMap<(Endpoint, Endpoint), usize> = HashMap::new();
+    let mut messages = Vec::new();
+
+    for line in lines {
+        // Both directions of a connection share a session
+        let key = if (line.src.addr, line.src.port) <= (line.dst.addr, line.dst.port) {
+            (line.src, line.dst)
+        } else {
+            (line.dst, line.src)
+        };
+        let next_id = sessions.len();
+        let session_id = *sessions.entry(key).or_insert(next_id);
+
+        let mut dissected = dissector.process(session_id, line.timestamp, &line.line);
+        let pool = match dissected.direction {
+            StratumDirection::MinerToPool => Some(line.dst),
+            StratumDirection::PoolToMiner => Some(line.src),
+            StratumDirection::Unknown => None,
+        };
+        if let (Some(pool), None) = (pool, &dissected.pool) {
+            dissector.set_pool(session_id, pool);
+            dissected.pool = Some(pool.to_string());
+        }
+        messages.push(dissected);
+    }
+
+    messages
+}
+
+/// Dissect a transcript of newline-delimited Stratum JSON as one session.
+///
+/// Transcripts carry no timing, so each message's timestamp is its line
+/// number; blank lines are skipped.
+pub fn dissect_transcript(path: &Path) -> Result<Vec<DissectedStratum>> {
+    let text = std::fs::read(path)
+        .with_context(|| format!("reading Stratum transcript {}", path.display()))?;
+
+    let mut dissector = StratumDissector::untimed();
+    Ok(text
+        .split(|&b| b == b'\n')
+        .enumerate()
+        .map(|(n, line)| (n + 1, line.strip_suffix(b"\r").unwrap_or(line)))
+        .filter(|(_, line)| !line.iter().all(u8::is_ascii_whitespace))
+        .map(|(n, line)| dissector.process(0, n as f64, line))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pcap::tests::PcapBuilder;
+    use bitcoin::hashes::Hash;
+    use mujina_miner::job_source::test_blocks::block_881423;
+    use serde_json::json;
+    use std::net::Ipv4Addr;
+
+    /// Stratum's word-swapped prevhash encoding.
+    fn stratum_prevhash() -> String {
+        let mut bytes = block_881423::PREV_BLOCKHASH.to_byte_array();
+        for word in bytes.chunks_mut(4) {
+            word.reverse();
+        }
+        hex::encode(bytes)
+    }
+
+    /// The session that mined block 881,423, as the pool would have sent it.
+    fn session_lines() -> Vec<String> {
+        let extranonce2 = block_881423::extranonce2_bytes();
+        let branches: Vec<String> = block_881423::MERKLE_BRANCHES_BYTES
+            .iter()
+            .map(hex::encode)
+            .collect();
+
+        vec![
+            json!({"id": 1, "method": "mining.subscribe", "params": ["mujina/test"]}),
+            json!({"id": 1, "result": [[], hex::encode(block_881423::extranonce1_bytes()), extranonce2.len()], "error": null}),
+            json!({"id": null, "method": "mining.set_difficulty", "params": [1024]}),
+            json!({"id": null, "method": "mining.notify", "params": [
+                "b1",
+                stratum_prevhash(),
+                hex::encode(block_881423::coinbase1_bytes()),
+                hex::encode(block_881423::coinbase2_bytes()),
+                branches,
+                format!("{:08x}", block_881423::VERSION.to_consensus()),
+                format!("{:08x}", block_881423::BITS.to_consensus()),
+                format!("{:08x}", block_881423::TIME),
+                true
+            ]}),
+            json!({"id": 4, "method": "mining.submit", "params": [
+                "worker", "b1", hex::encode(extranonce2),
+                format!("{:08x}", block_881423::TIME),
+                format!("{:08x}", block_881423::NONCE)
+            ]}),
+            json!({"id": 5, "method": "mining.submit", "params": [
+                "worker", "stale", "00000000", "00000000", "00000000"
+            ]}),
+            json!({"id": 4, "result": true, "error": null}),
+            json!({"id": 5, "result": null, "error": [21, "Job not found", null]}),
+        ]
+        .into_iter()
+        .map(|v| v.to_string())
+        .collect()
+    }
+
+    #[test]
+    fn test_dissect_session() {
+        let mut dissector = StratumDissector::new();
+        let out: Vec<_> = session_lines()
+            .iter()
+            .enumerate()
+            .map(|(n, line)| dissector.process(0, n as f64, line.as_bytes()))
+            .collect();
+
+        assert_eq!(out[0].direction, StratumDirection::MinerToPool);
+        assert_eq!(out[1].direction, StratumDirection::PoolToMiner);
+        assert!(
+            out[1].summary.contains("extranonce2_size=4"),
+            "{}",
+            out[1].summary
+        );
+        assert_eq!(out[3].direction, StratumDirection::PoolToMiner);
+        assert!(out[3].summary.starts_with("mining.notify job=b1"));
+        assert!(
+            out[3].summary.ends_with("branches=11 clean"),
+            "{}",
+            out[3].summary
+        );
+
+        // The winning submit is correlated with its job and meets block
+        // difficulty, far above the pool's
+        let submit = &out[4].summary;
+        assert!(submit.contains("job_age=1.000s"), "{}", submit);
+        let share_diff: f64 = submit
+            .split("share_diff=")
+            .nth(1)
+            .and_then(|s| s.split_whitespace().next())
+            .and_then(|s| s.parse().ok())
+            .expect("share difficulty missing");
+        let network_diff = bitcoin::Target::from(*block_881423::BITS).difficulty_float();
+        assert!(
+            share_diff >= network_diff,
+            "{} < {}",
+            share_diff,
+            network_diff
+        );
+
+        assert!(out[5].summary.ends_with("[unknown job]"));
+
+        assert_eq!(out[6].direction, StratumDirection::PoolToMiner);
+        assert!(
+            out[6].summary.contains("-> accepted (2000.0 ms)"),
+            "{}",
+            out[6].summary
+        );
+        assert!(out[7].summary.contains("job=stale"));
+        assert!(out[7].summary.contains("Job not found"));
+    }
+
+    #[test]
+    fn test_unpaired_and_garbage() {
+        let mut dissector = StratumDissector::new();
+
+        let out = dissector.process(0, 0.0, br#"{"id":9,"result":true,"error":null}"#);
+        assert_eq!(out.direction, StratumDirection::Unknown);
+        assert!(out.summary.starts_with("response to unknown request 9"));
+
+        let out = dissector.process(0, 0.0, b"GET / HTTP/1.1");
+        assert!(out.summary.starts_with("unparsed line"));
+    }
+
+    #[test]
+    fn test_untimed_omits_latency() {
+        let mut dissector = StratumDissector::untimed();
+        let out: Vec<_> = session_lines()
+            .iter()
+            .enumerate()
+            .map(|(n, line)| dissector.process(0, n as f64, line.as_bytes()))
+            .collect();
+
+        assert!(!out[4].summary.contains("job_age"));
+        assert!(out[4].summary.contains("share_diff="));
+        assert!(
+            out[6].summary.ends_with("-> accepted"),
+            "{}",
+            out[6].summary
+        );
+    }
+
+    #[test]
+    fn test_dissect_pcap_lines() {
+        let miner = (Ipv4Addr::new(192, 168, 1, 10), 50000);
+        let pool = (Ipv4Addr::new(10, 0, 0, 1), 3333);
+
+        let mut builder = PcapBuilder::new();
+        let mut miner_seq = 100u32;
+        let mut pool_seq = 900u32;
+        for (n, line) in session_lines().iter().enumerate() {
+            let payload = format!("{}\n", line);
+            let from_pool = line.contains("\"result\"")
+                || line.contains("mining.notify")
+                || line.contains("set_difficulty");
+            let (src, dst, seq) = if from_pool {
+                (pool, miner, &mut pool_seq)
+            } else {
+                (miner, pool, &mut miner_seq)
+            };
+            builder.packet(10.0 + n as f64, src, dst, *seq, false, payload.as_bytes());
+            *seq += payload.len() as u32;
+        }
+
+        let segments = pcap::parse_tcp_segments(&builder.build()).unwrap();
+        let out = dissect_lines(&pcap::reassemble_lines(&segments));
+
+        assert_eq!(out.len(), 8);
+        assert!(out
+            .iter()
+            .all(|m| m.pool.as_deref() == Some("10.0.0.1:3333")));
+        assert!(out[4].summary.contains("share_diff="));
+    }
+}